@@ -9,7 +9,24 @@ file_names = {
     'terrain.vert': 'terrain_vert.spv',
     'instance.vert': 'instance_vert.spv',
     'ui.vert': 'ui_vert.spv',
-    'ui.frag': 'ui_frag.spv'
+    'ui.frag': 'ui_frag.spv',
+    'fullscreen.vert': 'fullscreen_vert.spv',
+    'bright_pass.frag': 'bright_pass_frag.spv',
+    'blur.frag': 'blur_frag.spv',
+    'composite.frag': 'composite_frag.spv',
+    'fxaa.frag': 'fxaa_frag.spv',
+    'particle.comp': 'particle_comp.spv',
+    'particle.vert': 'particle_vert.spv',
+    'particle.frag': 'particle_frag.spv',
+    'debug_line.vert': 'debug_line_vert.spv',
+    'debug_line.frag': 'debug_line_frag.spv',
+    'occlusion_box.vert': 'occlusion_box_vert.spv',
+    'occlusion_box.frag': 'occlusion_box_frag.spv',
+    'sprite.vert': 'sprite_vert.spv',
+    'sprite.frag': 'sprite_frag.spv',
+    'indirect.vert': 'indirect_vert.spv',
+    'instance_indirect.vert': 'instance_indirect_vert.spv',
+    'indirect_cull.comp': 'indirect_cull_comp.spv'
 }
 
 plt = platform.system()
@@ -18,11 +35,13 @@ if plt == 'Windows':
     file_names['water.frag'] = 'water_frag.spv'
     file_names['terrain.frag'] = 'terrain_frag.spv'
     file_names['basicShader.frag'] = 'frag.spv'
+    file_names['indirect.frag'] = 'indirect_frag.spv'
 elif plt == 'Darwin':
     file_names['macos/instance.frag'] = 'instance_frag.spv'
     file_names['macos/water.frag'] = 'water_frag.spv'
     file_names['macos/terrain.frag'] = 'terrain_frag.spv'
     file_names['macos/basicShader.frag'] = 'frag.spv'
+    file_names['macos/indirect.frag'] = 'indirect_frag.spv'
 
 # Compile shaders
 os.chdir('./shaders')
@@ -21,8 +21,14 @@ layout (push_constant) uniform PushConstant
     vec4 sky_color;
 } pco;
 
-layout (std430, set = 1, binding = 0) readonly buffer JointMatrices {
-    mat4 jointMatrices[];
+#define MAX_MORPH_TARGETS 8
+
+// The instance index points to the header of the morph targets of the primitive.
+// Each target holds a position delta and a normal delta per vertex.
+layout (std430, set = 1, binding = 0) readonly buffer SkinningData {
+    mat4 jointMatrices[500];
+    float morphWeights[MAX_MORPH_TARGETS];
+    vec4 morphDeltas[];
 };
 
 layout (location = 0) in vec3 inPosition;
@@ -34,30 +40,36 @@ layout (location = 4) in vec4 inWeight;
 layout (location = 1) out vec3 outNormal;
 layout (location = 2) out vec2 outTexCoord;
 layout (location = 3) out vec3 fragPos;
-layout (location = 4) out float visibility;
 layout (location = 5) out vec3 toCameraDirection;
 
-const float density = 0.0035;
-const float gradient = 5.0;
-
 void main()
 {
-    mat4 skinMatrix = inWeight.x * jointMatrices[int(inJoint.x)] +
-        inWeight.y * jointMatrices[int(inJoint.y)] +
-        inWeight.z * jointMatrices[int(inJoint.z)] +
-        inWeight.w * jointMatrices[int(inJoint.w)];
+    vec3 position = inPosition;
+    vec3 normal = inNormal;
+    vec4 morphHeader = morphDeltas[gl_InstanceIndex];
+    int targetCount = min(int(morphHeader.x), MAX_MORPH_TARGETS);
+    int vertexCount = int(morphHeader.y);
+    for (int i = 0; i < targetCount; ++i) {
+        int index = gl_InstanceIndex + 1 + (i * vertexCount + gl_VertexIndex) * 2;
+        position += morphWeights[i] * morphDeltas[index].xyz;
+        normal += morphWeights[i] * morphDeltas[index + 1].xyz;
+    }
 
-    vec4 worldPosition = world_matrices[pco.model_index] * skinMatrix * vec4(inPosition, 1.0);
+    mat4 skinMatrix = mat4(1.0);
+    if (inWeight != vec4(0.0)) {
+        skinMatrix = inWeight.x * jointMatrices[int(inJoint.x)] +
+            inWeight.y * jointMatrices[int(inJoint.y)] +
+            inWeight.z * jointMatrices[int(inJoint.z)] +
+            inWeight.w * jointMatrices[int(inJoint.w)];
+    }
+
+    vec4 worldPosition = world_matrices[pco.model_index] * skinMatrix * vec4(position, 1.0);
     vec4 positionRelativeToCamera = mvp.view * worldPosition;
     gl_Position = mvp.projection * positionRelativeToCamera;
 
-    outNormal = inNormal;
+    outNormal = normal;
     outNormal = mat3(transpose(inverse(world_matrices[pco.model_index]))) * outNormal;
     outTexCoord = inTexCoord;
     fragPos = vec3(worldPosition);
     toCameraDirection = (inverse(mvp.view) * vec4(0.0, 0.0, 0.0, 1.0)).xyz - worldPosition.xyz;
-
-    float distance = length(positionRelativeToCamera.xyz);
-    visibility = exp(-pow((distance * density), gradient));
-    visibility = clamp(visibility, 0.0, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,20 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "post_process.glsl"
+
+layout (location = 0) in vec2 inUV;
+
+layout (location = 0) out vec4 fragColor;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec2 offset = pc.direction * pc.texel_size;
+    vec3 color = texture(source_sampler, inUV).rgb * weights[0];
+    for (int i = 1; i < 5; ++i) {
+        color += texture(source_sampler, inUV + offset * i).rgb * weights[i];
+        color += texture(source_sampler, inUV - offset * i).rgb * weights[i];
+    }
+    fragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,16 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "post_process.glsl"
+
+layout (location = 0) in vec2 inUV;
+
+layout (location = 0) out vec4 fragColor;
+
+void main() {
+    vec3 color = texture(source_sampler, inUV).rgb;
+    // Keep only the part of the color brighter than the threshold.
+    float brightness = luminance(color);
+    float contribution = max(brightness - pc.bloom_threshold, 0.0) / max(brightness, 0.0001);
+    fragColor = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,37 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "post_process.glsl"
+
+layout (location = 0) in vec2 inUV;
+
+layout (location = 0) out vec4 fragColor;
+
+// ACES filmic curve fitted by Krzysztof Narkowicz.
+vec3 aces(vec3 color) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+void main() {
+    vec3 color = texture(source_sampler, inUV).rgb;
+    if ((pc.flags & BLOOM) != 0) {
+        color += texture(bloom_sampler, inUV).rgb * pc.bloom_intensity;
+    }
+    if ((pc.flags & TONEMAP) != 0) {
+        color = aces(color * pc.exposure);
+    } else {
+        color = clamp(color, 0.0, 1.0);
+    }
+    // Redden the edges of the screen while the player is close to the world bounds.
+    if (pc.boundary_warning > 0.0) {
+        float vignette = smoothstep(0.4, 1.0, length(inUV - 0.5) * 1.41421356);
+        color = mix(color, vec3(0.8, 0.05, 0.05), vignette * pc.boundary_warning * 0.6);
+    }
+    // FXAA estimates edges from luma, so it's stored in alpha.
+    fragColor = vec4(color, luminance(color));
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout (binding = 0) uniform ViewProjection
+{
+    mat4 view;
+    mat4 projection;
+} vp;
+
+layout (location = 0) in vec3 inPosition;
+layout (location = 1) in vec4 inColor;
+
+layout (location = 0) out vec4 outColor;
+
+void main() {
+    gl_Position = vp.projection * vp.view * vec4(inPosition, 1.0);
+    outColor = inColor;
+}
@@ -0,0 +1,47 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "post_process.glsl"
+
+layout (location = 0) in vec2 inUV;
+
+layout (location = 0) out vec4 fragColor;
+
+const float EDGE_THRESHOLD_MIN = 0.0312;
+const float EDGE_THRESHOLD_MAX = 0.125;
+const float SPAN_MAX = 8.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+
+void main() {
+    vec4 center = texture(source_sampler, inUV);
+    float luma_center = center.a;
+    float luma_nw = texture(source_sampler, inUV + vec2(-1.0, -1.0) * pc.texel_size).a;
+    float luma_ne = texture(source_sampler, inUV + vec2(1.0, -1.0) * pc.texel_size).a;
+    float luma_sw = texture(source_sampler, inUV + vec2(-1.0, 1.0) * pc.texel_size).a;
+    float luma_se = texture(source_sampler, inUV + vec2(1.0, 1.0) * pc.texel_size).a;
+
+    float luma_min = min(luma_center, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_center, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    float luma_range = luma_max - luma_min;
+    if (luma_range < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD_MAX)) {
+        fragColor = vec4(center.rgb, 1.0);
+        return;
+    }
+
+    vec2 direction = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se));
+    float direction_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_direction_min, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * pc.texel_size;
+
+    vec3 color_a = 0.5 * (
+        texture(source_sampler, inUV + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(source_sampler, inUV + direction * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 color_b = color_a * 0.5 + 0.25 * (
+        texture(source_sampler, inUV + direction * -0.5).rgb +
+        texture(source_sampler, inUV + direction * 0.5).rgb);
+    float luma_b = luminance(color_b);
+    fragColor = vec4((luma_b < luma_min || luma_b > luma_max) ? color_a : color_b, 1.0);
+}
@@ -0,0 +1,64 @@
+#version 450
+#extension GL_ARB_shader_draw_parameters : require
+
+layout (binding = 0) uniform ModelViewProjection
+{
+    mat4 view;
+    mat4 projection;
+} mvp;
+
+layout (std430, binding = 2) readonly buffer ModelMatrices {
+    mat4 world_matrices[50];
+    vec4 object_colors[50];
+    float reflectivities[50];
+    float shine_dampers[];
+};
+
+struct DrawInfo {
+    uint model_index;
+    uint texture_index;
+    uint instance_count;
+    uint padding;
+    vec4 bounding_sphere;
+};
+
+layout (std430, set = 1, binding = 0) readonly buffer DrawInfos {
+    DrawInfo draws[];
+};
+
+layout (push_constant) uniform PushConstant
+{
+    uint texture_index;
+    uint padding0;
+    // Index of the first draw of this indirect draw
+    uint first_draw;
+    vec4 sky_color;
+} pco;
+
+layout (location = 0) in vec3 inPosition;
+layout (location = 1) in vec3 inNormal;
+layout (location = 2) in vec2 inTexCoord;
+
+layout (location = 1) out vec3 outNormal;
+layout (location = 2) out vec2 outTexCoord;
+layout (location = 3) out vec3 fragPos;
+layout (location = 5) out vec3 toCameraDirection;
+layout (location = 6) flat out uint outModelIndex;
+layout (location = 7) flat out uint outTextureIndex;
+
+void main()
+{
+    DrawInfo draw = draws[pco.first_draw + gl_DrawIDARB];
+    mat4 world_matrix = world_matrices[draw.model_index];
+    vec4 worldPosition = world_matrix * vec4(inPosition, 1.0);
+    vec4 positionRelativeToCamera = mvp.view * worldPosition;
+    gl_Position = mvp.projection * positionRelativeToCamera;
+
+    outNormal = inNormal;
+    outNormal = mat3(transpose(inverse(world_matrix))) * outNormal;
+    outTexCoord = inTexCoord;
+    fragPos = vec3(worldPosition);
+    toCameraDirection = (inverse(mvp.view) * vec4(0.0, 0.0, 0.0, 1.0)).xyz - worldPosition.xyz;
+    outModelIndex = draw.model_index;
+    outTextureIndex = draw.texture_index;
+}
\ No newline at end of file
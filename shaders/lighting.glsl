@@ -0,0 +1,26 @@
+layout (binding = 1) uniform DirectionalLight
+{
+    vec4 diffuse;
+    vec3 light_position;
+    float padding0;
+    float ambient_intensity;
+    float specular_intensity;
+    float fog_density;
+    float fog_height_falloff;
+    float fog_base_height;
+    float fog_end;
+} directional_light;
+
+// Fraction of fog_end where fragments start blending into the sky color. Kept in sync with FOG_EDGE_START.
+const float FOG_EDGE_START = 0.8;
+
+// Exponential height and distance fog. The fog thins out above the base height,
+// and everything reaches the sky color at fog_end so distant terrain doesn't pop out at the far plane.
+float get_fog_visibility(vec3 world_position, vec3 to_camera) {
+    float distance = length(to_camera);
+    float height = max(world_position.y - directional_light.fog_base_height, 0.0);
+    float density = directional_light.fog_density * exp(-directional_light.fog_height_falloff * height);
+    float visibility = exp(-distance * density);
+    float edge = 1.0 - smoothstep(directional_light.fog_end * FOG_EDGE_START, directional_light.fog_end, distance);
+    return clamp(min(visibility, edge), 0.0, 1.0);
+}
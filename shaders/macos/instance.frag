@@ -1,11 +1,7 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
 
-layout (binding = 1) uniform DirectionalLight
-{
-    vec4 diffuse;
-    vec3 light_position;
-    float padding0;
-} directional_light;
+#include "../lighting.glsl"
 
 layout (std430, binding = 2) readonly buffer ModelMatrices {
     mat4 world_matrices[50];
@@ -27,13 +23,10 @@ layout (push_constant) uniform PushConstant
 layout (location = 1) in vec3 inNormal;
 layout (location = 2) in vec2 inTexCoord;
 layout (location = 3) in vec3 fragPos;
-layout (location = 4) in float visibility;
 layout (location = 5) in vec3 toCameraDirection;
 
 layout (location = 0) out vec4 fragColor;
 
-const float ambientIntensity = 0.5;
-
 void main()
 {
     // Texture
@@ -43,7 +36,7 @@ void main()
     }
 
     // Ambient
-    vec4 ambient = ambientIntensity * tex_color;
+    vec4 ambient = directional_light.ambient_intensity * tex_color;
 
     // Diffuse Light
     // Pointing from the pixel to the light
@@ -65,5 +58,5 @@ void main()
 
     vec4 result = ambient + diffuse + specular;
     fragColor = object_colors[pco.model_index] * result;
-    fragColor = mix(pco.sky_color, fragColor, visibility);
+    fragColor = mix(pco.sky_color, fragColor, get_fog_visibility(fragPos, toCameraDirection));
 }
\ No newline at end of file
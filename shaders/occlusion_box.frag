@@ -0,0 +1,5 @@
+#version 450
+
+// Only the number of samples passing the depth test matters, so nothing is written.
+void main() {
+}
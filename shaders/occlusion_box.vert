@@ -0,0 +1,33 @@
+#version 450
+
+layout (binding = 0) uniform ViewProjection
+{
+    mat4 view;
+    mat4 projection;
+} vp;
+
+layout (push_constant) uniform PushConstant
+{
+    vec4 bounds_min;
+    vec4 bounds_max;
+} pc;
+
+// Corners of a unit cube, indexed by the 36 vertices of its 12 triangles.
+const vec3 CORNERS[8] = vec3[](
+    vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(1.0, 1.0, 0.0), vec3(0.0, 1.0, 0.0),
+    vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 1.0), vec3(1.0, 1.0, 1.0), vec3(0.0, 1.0, 1.0)
+);
+const int INDICES[36] = int[](
+    0, 1, 2, 2, 3, 0,
+    4, 6, 5, 6, 4, 7,
+    0, 3, 7, 7, 4, 0,
+    1, 5, 6, 6, 2, 1,
+    3, 2, 6, 6, 7, 3,
+    0, 4, 5, 5, 1, 0
+);
+
+void main() {
+    vec3 corner = CORNERS[INDICES[gl_VertexIndex]];
+    vec3 position = mix(pc.bounds_min.xyz, pc.bounds_max.xyz, corner);
+    gl_Position = vp.projection * vp.view * vec4(position, 1.0);
+}
@@ -0,0 +1,26 @@
+#version 450
+
+layout (push_constant) uniform PushConstant
+{
+    vec4 colors[4];
+    vec4 color_times;
+    uint base;
+    uint capacity;
+    float size;
+    float additive;
+} pc;
+
+layout (location = 0) in vec2 inUV;
+layout (location = 1) in vec4 inColor;
+
+layout (location = 0) out vec4 fragColor;
+
+void main() {
+    float falloff = 1.0 - smoothstep(0.5, 1.0, length(inUV));
+    float alpha = inColor.a * falloff;
+    if (alpha <= 0.0) {
+        discard;
+    }
+    // Premultiplied output. Additive particles write no alpha, so the blend keeps what's behind them.
+    fragColor = vec4(inColor.rgb * alpha, alpha * (1.0 - pc.additive));
+}
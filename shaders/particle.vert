@@ -0,0 +1,64 @@
+#version 450
+
+struct Particle {
+    vec4 position;
+    vec4 velocity;
+};
+
+layout (std430, binding = 0) readonly buffer Particles {
+    Particle particles[];
+};
+
+layout (binding = 1) uniform ViewProjection
+{
+    mat4 view;
+    mat4 projection;
+} vp;
+
+layout (push_constant) uniform PushConstant
+{
+    vec4 colors[4];
+    vec4 color_times;
+    uint base;
+    uint capacity;
+    float size;
+    float additive;
+} pc;
+
+layout (location = 0) out vec2 outUV;
+layout (location = 1) out vec4 outColor;
+
+const vec2 CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+// Same calculation as ColorOverLife::evaluate.
+vec4 evaluate_color(float t) {
+    vec4 color = pc.colors[0];
+    for (int i = 1; i < 4; ++i) {
+        float start = pc.color_times[i - 1];
+        float end = pc.color_times[i];
+        if (t > start) {
+            float fraction = end > start ? clamp((t - start) / (end - start), 0.0, 1.0) : 1.0;
+            color = mix(pc.colors[i - 1], pc.colors[i], fraction);
+        }
+    }
+    return color;
+}
+
+void main() {
+    Particle particle = particles[pc.base + gl_InstanceIndex];
+    vec2 corner = CORNERS[gl_VertexIndex];
+    outUV = corner;
+    if (particle.position.w >= particle.velocity.w) {
+        // Dead particles are pushed outside the clip volume.
+        outColor = vec4(0.0);
+        gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+        return;
+    }
+    outColor = evaluate_color(particle.position.w / particle.velocity.w);
+    vec4 view_position = vp.view * vec4(particle.position.xyz, 1.0);
+    view_position.xy += corner * pc.size;
+    gl_Position = vp.projection * view_position;
+}
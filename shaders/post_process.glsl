@@ -0,0 +1,20 @@
+layout (binding = 0) uniform sampler2D source_sampler;
+layout (binding = 1) uniform sampler2D bloom_sampler;
+
+layout (push_constant) uniform PushConstant
+{
+    vec2 texel_size;
+    vec2 direction;
+    float exposure;
+    float bloom_threshold;
+    float bloom_intensity;
+    uint flags;
+    float boundary_warning;
+} pc;
+
+const uint TONEMAP = 1;
+const uint BLOOM = 2;
+
+float luminance(vec3 color) {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout (binding = 0) uniform sampler2D atlas;
+
+layout (location = 0) in vec2 inTexCoord;
+layout (location = 1) in vec4 inColor;
+
+layout (location = 0) out vec4 fragColor;
+
+void main() {
+    fragColor = texture(atlas, inTexCoord) * inColor;
+}
@@ -28,12 +28,8 @@ layout (location = 2) in vec2 inTexCoord;
 layout (location = 1) out vec3 outNormal;
 layout (location = 2) out vec2 outTexCoord;
 layout (location = 3) out vec3 fragPos;
-layout (location = 4) out float visibility;
 layout (location = 5) out vec3 toCameraDirection;
 
-const float density = 0.0035;
-const float gradient = 5.0;
-
 void main()
 {
     vec4 worldPosition = world_matrices[pco.model_index] * vec4(inPosition, 1.0);
@@ -45,8 +41,4 @@ void main()
     outTexCoord = inTexCoord * 40.0;
     fragPos = vec3(worldPosition);
     toCameraDirection = (inverse(mvp.view) * vec4(0.0, 0.0, 0.0, 1.0)).xyz - worldPosition.xyz;
-
-    float distance = length(positionRelativeToCamera.xyz);
-    visibility = exp(-pow((distance * density), gradient));
-    visibility = clamp(visibility, 0.0, 1.0);
 }
\ No newline at end of file
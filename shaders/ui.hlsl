@@ -0,0 +1,30 @@
+cbuffer Locals : register(b0) {
+    float4x4 projection_matrix;
+};
+
+Texture2D tex : register(t0);
+SamplerState tex_sampler : register(s0);
+
+struct VSInput {
+    float2 position : POSITION;
+    float2 uv : TEXCOORD;
+    float4 color : COLOR;
+};
+
+struct PSInput {
+    float4 position : SV_POSITION;
+    float2 uv : TEXCOORD;
+    float4 color : COLOR;
+};
+
+PSInput vs_main(VSInput input) {
+    PSInput output;
+    output.position = mul(projection_matrix, float4(input.position, 0.0, 1.0));
+    output.uv = input.uv;
+    output.color = input.color;
+    return output;
+}
+
+float4 ps_main(PSInput input) : SV_TARGET {
+    return input.color * tex.Sample(tex_sampler, input.uv);
+}
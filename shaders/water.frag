@@ -1,12 +1,8 @@
 #version 450
 #extension GL_EXT_nonuniform_qualifier : require
+#extension GL_GOOGLE_include_directive : require
 
-layout (binding = 1) uniform DirectionalLight
-{
-    vec4 diffuse;
-    vec3 light_position;
-    float padding0;
-} directional_light;
+#include "lighting.glsl"
 
 layout (std430, binding = 2) readonly buffer ModelMatrices {
     mat4 world_matrices[50];
@@ -28,31 +24,34 @@ layout (push_constant) uniform PushConstant
 layout (location = 1) in vec3 inNormal;
 layout (location = 2) in vec2 inTexCoord;
 layout (location = 3) in vec3 fragPos;
-layout (location = 4) in float visibility;
 layout (location = 5) in vec3 toCameraDirection;
 
 layout (location = 0) out vec4 fragColor;
 
-const float ambientIntensity = 0.5;
+// How far the ripple slopes tilt the normal.
+const float RIPPLE_NORMAL_STRENGTH = 4.0;
+// How much brighter ripple crests are than flat water.
+const float RIPPLE_CREST_BRIGHTNESS = 0.25;
 
 void main()
 {
-    // Texture
-    vec4 tex_color = texture(tex_sampler[pco.texture_index], inTexCoord);
-    if (tex_color.a < 0.1) {
-        discard;
-    }
+    // Ripples
+    // R is the height and G and B the slopes along X and Z, with 0.5 as flat water.
+    // Only the top mip is uploaded every frame, so the other mips are never read.
+    vec3 ripple = textureLod(tex_sampler[pco.texture_index], inTexCoord, 0.0).rgb * 2.0 - 1.0;
+    vec3 normal = normalize(inNormal - vec3(ripple.g, 0.0, ripple.b) * RIPPLE_NORMAL_STRENGTH);
+    vec4 water_color = object_colors[pco.model_index];
+    water_color.rgb *= 1.0 + ripple.r * RIPPLE_CREST_BRIGHTNESS;
 
     // Ambient
-    vec4 ambient = ambientIntensity * tex_color;
+    vec4 ambient = directional_light.ambient_intensity * water_color;
 
     // Diffuse Light
     // Pointing from the pixel to the light
     vec3 lightDirection = directional_light.light_position - fragPos;
     lightDirection = normalize(lightDirection);
-    vec3 normal = normalize(inNormal);
     float diffuseIntensity = max(dot(normal, lightDirection), 0.0);
-    vec4 diffuse = directional_light.diffuse * diffuseIntensity * tex_color;
+    vec4 diffuse = directional_light.diffuse * diffuseIntensity * water_color;
 
     // Specular Lighting
     vec3 normalizedToCameraDirection = normalize(toCameraDirection);
@@ -65,6 +64,6 @@ void main()
     vec4 specular = directional_light.diffuse * reflectivities[pco.model_index] * dampedSpecular;
 
     vec4 result = ambient + diffuse + specular;
-    fragColor = object_colors[pco.model_index];
-    fragColor = mix(pco.sky_color, fragColor, visibility);
+    fragColor = vec4(result.rgb, water_color.a);
+    fragColor = mix(pco.sky_color, fragColor, get_fog_visibility(fragPos, toCameraDirection));
 }
\ No newline at end of file
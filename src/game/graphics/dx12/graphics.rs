@@ -1,14 +1,18 @@
-use crate::game::graphics::dx12::{CommandQueue, DescriptorHeap, Pipeline, Resource, SwapChain};
+use crate::game::graphics::dx12::{
+    CommandQueue, DescriptorHeap, Pipeline, RenderContext, Resource, SwapChain,
+};
 use crate::game::shared::traits::GraphicsBase;
+use crate::game::ui::dx12::{DrawTarget, Drawer};
 use crate::game::util::{get_nullptr, log_error};
-use crate::game::{Camera, ResourceManager};
+use crate::game::{Camera, ResourceManager, UISystem};
 use parking_lot::RwLock;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::mem::ManuallyDrop;
 use std::rc::Rc;
 use std::sync::{Arc, Weak};
 use winapi::ctypes::c_void;
-use winapi::shared::basetsd::SIZE_T;
+use winapi::shared::basetsd::{SIZE_T, UINT64};
+use winapi::shared::dxgi::DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING;
 use winapi::shared::dxgi1_2::IDXGIFactory2;
 use winapi::shared::dxgi1_3::{CreateDXGIFactory2, DXGI_CREATE_FACTORY_DEBUG};
 use winapi::shared::dxgi1_5::DXGI_FEATURE_PRESENT_ALLOW_TEARING;
@@ -16,17 +20,19 @@ use winapi::shared::dxgi1_6::{
     IDXGIAdapter4, IDXGIFactory6, DXGI_ADAPTER_DESC3, DXGI_ADAPTER_FLAG3_SOFTWARE,
     DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE,
 };
-use winapi::shared::guiddef::REFIID;
+use winapi::shared::dxgiformat::DXGI_FORMAT_UNKNOWN;
+use winapi::shared::guiddef::{REFGUID, REFIID};
 use winapi::shared::minwindef::{BOOL, FALSE, TRUE, UINT};
 use winapi::shared::windef::HWND;
 use winapi::shared::winerror::{FAILED, SUCCEEDED};
-use winapi::um::d3d12::{
-    D3D12CreateDevice, D3D12GetDebugInterface, ID3D12Device2, ID3D12GraphicsCommandList,
-};
+use winapi::um::d3d12::*;
 use winapi::um::d3d12sdklayers::*;
 use winapi::um::d3dcommon::D3D_FEATURE_LEVEL_12_1;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::synchapi::{CreateEventW, WaitForSingleObject};
 use winapi::um::unknwnbase::IUnknown;
-use winapi::um::winnt::{RtlZeroMemory, LUID};
+use winapi::um::winbase::INFINITE;
+use winapi::um::winnt::{RtlZeroMemory, HANDLE, LUID};
 use winapi::Interface;
 use winit::platform::windows::WindowExtWindows;
 use wio::com::ComPtr;
@@ -39,6 +45,16 @@ type ResourceManagerHandle = Weak<
     >,
 >;
 
+type UIManagerHandle = std::rc::Weak<
+    RefCell<
+        ManuallyDrop<UISystem<Graphics, Resource, ComPtr<ID3D12GraphicsCommandList>, Resource>>,
+    >,
+>;
+
+/// 毎フレーム画面を消す色。<br />
+/// Color the screen is cleared to every frame.
+const CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
 #[allow(dead_code)]
 pub struct Graphics {
     pub is_initialized: bool,
@@ -47,12 +63,22 @@ pub struct Graphics {
     debug: ComPtr<ID3D12Debug2>,
     dxgi_factory: ComPtr<IDXGIFactory2>,
     dxgi_adapter: ComPtr<IDXGIAdapter4>,
-    device: Arc<ComPtr<ID3D12Device2>>,
+    pub device: Arc<ComPtr<ID3D12Device2>>,
     info_queue: ComPtr<ID3D12InfoQueue>,
-    command_queue: ManuallyDrop<CommandQueue>,
+    pub command_queue: ManuallyDrop<CommandQueue>,
     swap_chain: ManuallyDrop<SwapChain>,
     descriptor_heap: ManuallyDrop<DescriptorHeap>,
     pipeline: ManuallyDrop<Pipeline>,
+    pub ui_manager: Option<UIManagerHandle>,
+    tearing_support: BOOL,
+    fence: ComPtr<ID3D12Fence>,
+    fence_event: HANDLE,
+    /// 最後に送ったフェンスの値。`wait_idle`は`&self`なので`Cell`にする。<br />
+    /// The last fence value signalled, kept in a `Cell` because `wait_idle` takes `&self`.
+    fence_value: Cell<UINT64>,
+    /// バックバッファーごとに、最後にそれを描画したフレームのフェンスの値。<br />
+    /// Fence value of the frame that last rendered to each back buffer.
+    frame_fence_values: Vec<UINT64>,
 }
 
 impl Graphics {
@@ -85,6 +111,12 @@ impl Graphics {
         );
         let descriptor_heap = DescriptorHeap::new(&device, &swap_chain);
         let pipeline = Pipeline::new(&device);
+        let fence = Self::create_fence(&device);
+        let fence_event = CreateEventW(std::ptr::null_mut(), FALSE, FALSE, std::ptr::null());
+        if fence_event.is_null() {
+            panic!("Failed to create fence event.");
+        }
+        let frame_fence_values = vec![0; swap_chain.buffer_count as usize];
         Graphics {
             debug,
             camera,
@@ -97,8 +129,232 @@ impl Graphics {
             swap_chain: ManuallyDrop::new(swap_chain),
             descriptor_heap: ManuallyDrop::new(descriptor_heap),
             pipeline: ManuallyDrop::new(pipeline),
-            is_initialized: false,
+            ui_manager: None,
+            tearing_support,
+            fence,
+            fence_event,
+            fence_value: Cell::new(0),
+            frame_fence_values,
+            is_initialized: true,
+        }
+    }
+
+    /// 画面を消してUIを重ね、スワップチェーンに表示する。<br />
+    /// DirectX 12で描くのはログインとロビーのUIだけで、シーンのモデルはVulkanでしか描画しない。<br />
+    /// Clear the screen, draw the UI over it and present it to the swapchain.<br />
+    /// DirectX 12 only draws the login and lobby UI. Models in scenes are only rendered with Vulkan.
+    pub unsafe fn render(&mut self) -> anyhow::Result<()> {
+        let frame_index = self.swap_chain.swap_chain.GetCurrentBackBufferIndex() as usize;
+        // このバックバッファーを前に使ったフレームが終わるまで、アロケーターは使い回せない。
+        // The allocator can't be reused until the frame that last used this back buffer has finished.
+        self.wait_for_fence_value(self.frame_fence_values[frame_index]);
+        let render_target = self.descriptor_heap.rtvs[frame_index].as_raw();
+        let render_target_view = self.get_render_target_view(frame_index);
+        let depth_stencil_view = self
+            .descriptor_heap
+            .dsv_heap
+            .GetCPUDescriptorHandleForHeapStart();
+
+        let command_allocator = &self.command_queue.command_allocators[frame_index];
+        let res = command_allocator.Reset();
+        log_error(res, "Failed to reset command allocator.");
+        self.begin_commands(frame_index);
+        self.transition(
+            render_target,
+            D3D12_RESOURCE_STATE_PRESENT,
+            D3D12_RESOURCE_STATE_RENDER_TARGET,
+        );
+        let command_list = &self.command_queue.command_list;
+        command_list.ClearRenderTargetView(
+            render_target_view,
+            CLEAR_COLOR.as_ptr(),
+            0,
+            std::ptr::null(),
+        );
+        command_list.ClearDepthStencilView(
+            depth_stencil_view,
+            D3D12_CLEAR_FLAG_DEPTH,
+            1.0,
+            0,
+            0,
+            std::ptr::null(),
+        );
+        self.execute_commands();
+
+        // UIは同じキューに送るので、消した後、表示に戻す前に実行される。
+        // The UI is submitted to the same queue, so it runs after the clear and before the transition back to present.
+        if let Some(ui) = self.ui_manager.as_ref() {
+            let ui_manager = ui
+                .upgrade()
+                .ok_or_else(|| anyhow::anyhow!("Failed to upgrade UI handle."))?;
+            ui_manager.borrow_mut().render(
+                DrawTarget {
+                    render_target_view,
+                    viewport: self.get_viewport(),
+                },
+                nuklear::Vec2 { x: 1.0, y: 1.0 },
+            );
+        }
+
+        self.begin_commands(frame_index);
+        self.transition(
+            render_target,
+            D3D12_RESOURCE_STATE_RENDER_TARGET,
+            D3D12_RESOURCE_STATE_PRESENT,
+        );
+        self.execute_commands();
+
+        // フレームレートの上限の設定はまだないので、垂直同期で待つ。
+        // There's no frame rate cap setting yet, so wait for vertical sync.
+        let res = self.swap_chain.swap_chain.Present(1, 0);
+        if FAILED(res) {
+            return Err(anyhow::anyhow!(
+                "Failed to present the swap chain. Error: {}.",
+                res
+            ));
         }
+        self.frame_fence_values[frame_index] = self.signal();
+        Ok(())
+    }
+
+    /// ウィンドウの大きさに合わせてバックバッファーを作り直す。<br />
+    /// Recreate the back buffers to fit the size of the window.
+    pub unsafe fn recreate_swapchain(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        self.wait_idle();
+        // バックバッファーへの参照が残っているとサイズを変えられないので、先にビューを解放する。
+        // The buffers can't be resized while references to them remain, so release the views first.
+        ManuallyDrop::drop(&mut self.descriptor_heap);
+        let flags = if self.tearing_support != FALSE {
+            DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING
+        } else {
+            0
+        };
+        let res = self.swap_chain.swap_chain.ResizeBuffers(
+            self.swap_chain.buffer_count,
+            width,
+            height,
+            DXGI_FORMAT_UNKNOWN,
+            flags,
+        );
+        if SUCCEEDED(res) {
+            self.swap_chain.width = width;
+            self.swap_chain.height = height;
+        }
+        // 失敗しても、今のバックバッファーのビューを作り直してから返す。解放したままだと二重に解放してしまう。
+        // Recreate the views of the current back buffers even on failure. Leaving them released would free them twice.
+        self.descriptor_heap =
+            ManuallyDrop::new(DescriptorHeap::new(&self.device, &self.swap_chain));
+        if FAILED(res) {
+            return Err(anyhow::anyhow!(
+                "Failed to resize the swap chain. Error: {}.",
+                res
+            ));
+        }
+        self.camera
+            .borrow_mut()
+            .update_window(width as f64, height as f64);
+        Ok(())
+    }
+
+    unsafe fn begin_commands(&self, frame_index: usize) {
+        let res = self.command_queue.command_list.Reset(
+            self.command_queue.command_allocators[frame_index].as_raw(),
+            std::ptr::null_mut(),
+        );
+        log_error(res, "Failed to reset command list.");
+    }
+
+    unsafe fn execute_commands(&self) {
+        let command_list = &self.command_queue.command_list;
+        let res = command_list.Close();
+        log_error(res, "Failed to close command list.");
+        let command_lists = [command_list.as_raw() as *mut ID3D12CommandList];
+        self.command_queue
+            .command_queue
+            .ExecuteCommandLists(command_lists.len() as UINT, command_lists.as_ptr());
+    }
+
+    unsafe fn transition(
+        &self,
+        resource: *mut ID3D12Resource,
+        state_before: D3D12_RESOURCE_STATES,
+        state_after: D3D12_RESOURCE_STATES,
+    ) {
+        let mut barrier = D3D12_RESOURCE_BARRIER {
+            Type: D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
+            Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
+            u: Default::default(),
+        };
+        *barrier.u.Transition_mut() = D3D12_RESOURCE_TRANSITION_BARRIER {
+            pResource: resource,
+            Subresource: D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
+            StateBefore: state_before,
+            StateAfter: state_after,
+        };
+        self.command_queue
+            .command_list
+            .ResourceBarrier(1, &barrier as *const _);
+    }
+
+    unsafe fn get_render_target_view(&self, frame_index: usize) -> D3D12_CPU_DESCRIPTOR_HANDLE {
+        let mut handle = self
+            .descriptor_heap
+            .rtv_heap
+            .GetCPUDescriptorHandleForHeapStart();
+        let increment_size = self
+            .device
+            .GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_RTV);
+        handle.ptr += (increment_size as usize * frame_index) as SIZE_T;
+        handle
+    }
+
+    fn get_viewport(&self) -> D3D12_VIEWPORT {
+        D3D12_VIEWPORT {
+            TopLeftX: 0.0,
+            TopLeftY: 0.0,
+            Width: self.swap_chain.width as f32,
+            Height: self.swap_chain.height as f32,
+            MinDepth: 0.0,
+            MaxDepth: 1.0,
+        }
+    }
+
+    /// キューにフェンスの次の値を送り、その値を返す。<br />
+    /// Signal the next fence value on the queue and return it.
+    unsafe fn signal(&self) -> UINT64 {
+        let fence_value = self.fence_value.get() + 1;
+        let res = self
+            .command_queue
+            .command_queue
+            .Signal(self.fence.as_raw(), fence_value);
+        log_error(res, "Failed to signal fence.");
+        self.fence_value.set(fence_value);
+        fence_value
+    }
+
+    unsafe fn wait_for_fence_value(&self, fence_value: UINT64) {
+        if self.fence.GetCompletedValue() < fence_value {
+            let res = self
+                .fence
+                .SetEventOnCompletion(fence_value, self.fence_event);
+            log_error(res, "Failed to set fence event.");
+            WaitForSingleObject(self.fence_event, INFINITE);
+        }
+    }
+
+    unsafe fn create_fence(device: &ComPtr<ID3D12Device2>) -> ComPtr<ID3D12Fence> {
+        let mut ptr = get_nullptr();
+        let res = device.CreateFence(
+            0,
+            D3D12_FENCE_FLAG_NONE,
+            &ID3D12Fence::uuidof() as REFGUID,
+            &mut ptr as *mut _,
+        );
+        log_error(res, "Failed to create fence.");
+        ComPtr::from_raw(ptr as *mut ID3D12Fence)
     }
 
     unsafe fn get_adapter() -> (ComPtr<IDXGIFactory2>, ComPtr<IDXGIAdapter4>) {
@@ -241,6 +497,10 @@ impl Graphics {
 }
 
 impl GraphicsBase<Resource, ComPtr<ID3D12GraphicsCommandList>, Resource> for Graphics {
+    type UiDrawer = Drawer;
+    type RenderContext = RenderContext;
+    const CAN_RENDER_SCENES: bool = false;
+
     fn is_initialized(&self) -> bool {
         self.is_initialized
     }
@@ -249,12 +509,17 @@ impl GraphicsBase<Resource, ComPtr<ID3D12GraphicsCommandList>, Resource> for Gra
         self.is_initialized = false;
     }
 
-    unsafe fn wait_idle(&self) {}
+    unsafe fn wait_idle(&self) {
+        let fence_value = self.signal();
+        self.wait_for_fence_value(fence_value);
+    }
 }
 
 impl Drop for Graphics {
     fn drop(&mut self) {
         unsafe {
+            self.wait_idle();
+            CloseHandle(self.fence_event);
             ManuallyDrop::drop(&mut self.pipeline);
             ManuallyDrop::drop(&mut self.descriptor_heap);
             ManuallyDrop::drop(&mut self.swap_chain);
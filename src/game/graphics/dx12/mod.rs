@@ -7,6 +7,8 @@ pub mod graphics;
 //#[cfg(target_os = "windows")]
 pub mod pipeline;
 //#[cfg(target_os = "windows")]
+pub mod render_context;
+//#[cfg(target_os = "windows")]
 pub mod resource;
 //#[cfg(target_os = "windows")]
 pub mod swapchain;
@@ -19,6 +21,8 @@ pub use graphics::Graphics;
 //#[cfg(target_os = "windows")]
 pub use pipeline::Pipeline;
 //#[cfg(target_os = "windows")]
+pub use render_context::RenderContext;
+//#[cfg(target_os = "windows")]
 pub use resource::*;
 //#[cfg(target_os = "windows")]
 pub use swapchain::SwapChain;
@@ -0,0 +1,11 @@
+use winapi::um::d3d12::{ID3D12GraphicsCommandList, D3D12_RECT, D3D12_VIEWPORT};
+use wio::com::ComPtr;
+
+/// DirectX 12でモデルの描画コマンドを記録する先。<br />
+/// Where models record their draw commands with DirectX 12.
+#[derive(Clone)]
+pub struct RenderContext {
+    pub command_list: ComPtr<ID3D12GraphicsCommandList>,
+    pub viewport: D3D12_VIEWPORT,
+    pub scissor: D3D12_RECT,
+}
@@ -11,6 +11,7 @@ use std::ffi::c_void;
 use std::sync::Weak;
 use vk_mem::*;
 
+use crate::game::graphics::vk::GraphicsError;
 use crate::game::shared::traits::disposable::Disposable;
 use crate::game::shared::traits::mappable::Mappable;
 use crate::game::util::{end_one_time_command_buffer, get_single_time_command_buffer};
@@ -70,12 +71,44 @@ impl Buffer {
         usage_flag: BufferUsageFlags,
         memory_properties: MemoryPropertyFlags,
         allocator: Weak<ShardedLock<Allocator>>,
-    ) -> Self {
-        let create_info = BufferCreateInfo::builder()
-            .sharing_mode(SharingMode::EXCLUSIVE)
-            .size(buffer_size)
-            .usage(usage_flag)
-            .build();
+    ) -> Result<Self, GraphicsError> {
+        Self::with_queue_families(
+            device,
+            buffer_size,
+            usage_flag,
+            memory_properties,
+            allocator,
+            &[],
+        )
+    }
+
+    /// 複数のキューファミリーで共有するバッファを作る。ファミリーが一つだけなら共有しない。<br />
+    /// Create a buffer shared by several queue families. It isn't shared if there's only one family.
+    pub fn with_queue_families(
+        device: Weak<Device>,
+        buffer_size: DeviceSize,
+        usage_flag: BufferUsageFlags,
+        memory_properties: MemoryPropertyFlags,
+        allocator: Weak<ShardedLock<Allocator>>,
+        queue_families: &[u32],
+    ) -> Result<Self, GraphicsError> {
+        let mut families = queue_families.to_vec();
+        families.sort_unstable();
+        families.dedup();
+        let create_info = if families.len() > 1 {
+            BufferCreateInfo::builder()
+                .sharing_mode(SharingMode::CONCURRENT)
+                .queue_family_indices(families.as_slice())
+                .size(buffer_size)
+                .usage(usage_flag)
+                .build()
+        } else {
+            BufferCreateInfo::builder()
+                .sharing_mode(SharingMode::EXCLUSIVE)
+                .size(buffer_size)
+                .usage(usage_flag)
+                .build()
+        };
         let allocation_info = AllocationCreateInfo {
             usage: match usage_flag {
                 BufferUsageFlags::TRANSFER_SRC => MemoryUsage::CpuOnly,
@@ -101,15 +134,17 @@ impl Buffer {
             pool: None,
             user_data: None,
         };
-        let arc = allocator.upgrade().unwrap();
+        let arc = allocator
+            .upgrade()
+            .ok_or(GraphicsError::Unavailable("memory allocator"))?;
         let lock = arc.read().unwrap();
         let (buffer, allocation, allocation_info) = lock
             .create_buffer(&create_info, &allocation_info)
-            .expect("Failed to create buffer from VMA allocator.");
+            .map_err(|e| GraphicsError::allocation("create buffer from VMA allocator", e))?;
         drop(lock);
         let device_memory = allocation_info.get_device_memory();
         let mapped = allocation_info.get_mapped_data();
-        Buffer {
+        Ok(Buffer {
             logical_device: device,
             buffer,
             device_memory,
@@ -119,7 +154,7 @@ impl Buffer {
             allocation,
             allocation_info: Some(allocation_info),
             allocator,
-        }
+        })
     }
 
     /// バッファ元からこのバッファにコピーする。<br />
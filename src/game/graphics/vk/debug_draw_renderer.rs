@@ -0,0 +1,184 @@
+use ash::{version::DeviceV1_0, vk::*, Device};
+use crossbeam::sync::ShardedLock;
+use std::ffi::c_void;
+use std::sync::{Arc, Weak};
+use vk_mem::Allocator;
+
+use super::VkResultExt;
+use crate::game::shared::structs::DebugVertex;
+
+/// 最初に確保する頂点の数。足りなくなれば倍にする。<br />
+/// Number of vertices allocated at first, doubled whenever it runs out.
+const INITIAL_VERTEX_COUNT: usize = 4096;
+
+/// シーンのパスでデバッグ用の線を描く。<br />
+/// 頂点はフレームごとのホストから見えるバッファに毎フレーム書き込む。<br />
+/// Draws debug lines in the scene pass.<br />
+/// Vertices are written every frame into a host-visible buffer for each frame.
+pub struct DebugDrawRenderer {
+    pub descriptor_set: DescriptorSet,
+    logical_device: Arc<Device>,
+    allocator: Weak<ShardedLock<Allocator>>,
+    vertex_buffers: Vec<Option<super::Buffer>>,
+    command_pools: Vec<CommandPool>,
+    command_buffers: Vec<CommandBuffer>,
+}
+
+unsafe impl Send for DebugDrawRenderer {}
+unsafe impl Sync for DebugDrawRenderer {}
+
+impl DebugDrawRenderer {
+    pub fn new(
+        logical_device: Arc<Device>,
+        physical_device: &super::PhysicalDevice,
+        allocator: Weak<ShardedLock<Allocator>>,
+        frame_count: usize,
+    ) -> anyhow::Result<Self> {
+        let graphics_family = physical_device
+            .queue_indices
+            .graphics_family
+            .ok_or_else(|| anyhow::anyhow!("The device doesn't have a graphics queue."))?;
+        let pool_info = CommandPoolCreateInfo::builder().queue_family_index(graphics_family);
+        let mut command_pools = vec![];
+        let mut command_buffers = vec![];
+        unsafe {
+            for _ in 0..frame_count {
+                let pool = logical_device
+                    .create_command_pool(&pool_info, None)
+                    .or_graphics_error("create command pool for debug lines")?;
+                let allocate_info = CommandBufferAllocateInfo::builder()
+                    .command_pool(pool)
+                    .command_buffer_count(1)
+                    .level(CommandBufferLevel::SECONDARY);
+                command_buffers.push(
+                    logical_device
+                        .allocate_command_buffers(&allocate_info)
+                        .or_graphics_error("allocate command buffers for debug lines")?[0],
+                );
+                command_pools.push(pool);
+            }
+        }
+        log::info!("Debug draw renderer successfully created.");
+        Ok(DebugDrawRenderer {
+            descriptor_set: DescriptorSet::null(),
+            logical_device,
+            allocator,
+            vertex_buffers: (0..frame_count).map(|_| None).collect(),
+            command_pools,
+            command_buffers,
+        })
+    }
+
+    /// 頂点をこのフレームのバッファに書き込み、線を描く二次コマンドバッファを記録する。<br />
+    /// フレームのフェンスを待った後に呼ぶので、小さすぎるバッファはその場で作り直せる。<br />
+    /// Write the vertices into this frame's buffer and record the secondary command buffer drawing the lines.<br />
+    /// It's called after waiting for the frame's fence, so a buffer that's too small can be replaced right away.
+    pub fn record_draw(
+        &mut self,
+        frame_index: usize,
+        inheritance_info: &CommandBufferInheritanceInfo,
+        viewport: Viewport,
+        scissor: Rect2D,
+        vertices: &[DebugVertex],
+        pipeline_layout: PipelineLayout,
+        pipeline: ash::vk::Pipeline,
+    ) -> anyhow::Result<Option<CommandBuffer>> {
+        if vertices.is_empty()
+            || self.descriptor_set == DescriptorSet::null()
+            || pipeline == ash::vk::Pipeline::null()
+        {
+            return Ok(None);
+        }
+        let data_size = (std::mem::size_of::<DebugVertex>() * vertices.len()) as DeviceSize;
+        let is_too_small = self.vertex_buffers[frame_index]
+            .as_ref()
+            .map(|buffer| buffer.buffer_size < data_size)
+            .unwrap_or(true);
+        if is_too_small {
+            let mut vertex_count = INITIAL_VERTEX_COUNT;
+            while vertex_count < vertices.len() {
+                vertex_count *= 2;
+            }
+            self.vertex_buffers[frame_index] = Some(super::Buffer::new(
+                Arc::downgrade(&self.logical_device),
+                (std::mem::size_of::<DebugVertex>() * vertex_count) as DeviceSize,
+                BufferUsageFlags::VERTEX_BUFFER,
+                MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+                self.allocator.clone(),
+            )?);
+        }
+        let vertex_buffer = self.vertex_buffers[frame_index]
+            .as_ref()
+            .expect("Failed to get the debug line vertex buffer.");
+        let command_buffer = self.command_buffers[frame_index];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                vertices.as_ptr() as *const c_void,
+                vertex_buffer.mapped_memory,
+                data_size as usize,
+            );
+            self.logical_device
+                .reset_command_pool(
+                    self.command_pools[frame_index],
+                    CommandPoolResetFlags::empty(),
+                )
+                .or_graphics_error("reset the debug line command pool")?;
+            let begin_info = CommandBufferBeginInfo::builder()
+                .flags(
+                    CommandBufferUsageFlags::ONE_TIME_SUBMIT
+                        | CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+                )
+                .inheritance_info(inheritance_info);
+            self.logical_device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .or_graphics_error("begin the debug line command buffer")?;
+            self.logical_device
+                .cmd_set_viewport(command_buffer, 0, &[viewport]);
+            self.logical_device
+                .cmd_set_scissor(command_buffer, 0, &[scissor]);
+            self.logical_device.cmd_bind_pipeline(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline,
+            );
+            self.logical_device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            self.logical_device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[vertex_buffer.buffer],
+                &[0],
+            );
+            self.logical_device
+                .cmd_draw(command_buffer, vertices.len() as u32, 1, 0, 0);
+            self.logical_device
+                .end_command_buffer(command_buffer)
+                .or_graphics_error("end the debug line command buffer")?;
+        }
+        Ok(Some(command_buffer))
+    }
+}
+
+impl Drop for DebugDrawRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.logical_device.device_wait_idle() {
+                log::warn!("Failed to wait for device to idle: {}", e);
+            }
+            for (pool, command_buffer) in self.command_pools.iter().zip(self.command_buffers.iter())
+            {
+                self.logical_device
+                    .free_command_buffers(*pool, &[*command_buffer]);
+                self.logical_device.destroy_command_pool(*pool, None);
+            }
+        }
+        self.vertex_buffers.clear();
+        log::info!("Debug draw renderer successfully dropped.");
+    }
+}
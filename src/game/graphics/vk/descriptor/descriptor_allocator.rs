@@ -3,10 +3,14 @@ use ash::version::DeviceV1_0;
 use ash::vk::{
     DescriptorPool, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolResetFlags,
     DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout,
-    DescriptorType,
+    DescriptorSetVariableDescriptorCountAllocateInfo, DescriptorType,
 };
 use std::sync::Weak;
 
+/// 更新後バインドのプールのサイズに掛ける数。可変長の描述子セットは一つずつ配置するので小さくする。<br />
+/// Count multiplied to the sizes of update-after-bind pools, kept small since variable-sized descriptor sets are allocated one at a time.
+const UPDATE_AFTER_BIND_POOL_COUNT: u32 = 4;
+
 /// 各種類のプールとそのプールのサイズ。<br />
 /// Pool for each category and their respective sizes.
 struct PoolSizes {
@@ -35,6 +39,14 @@ pub struct DescriptorAllocator {
     /// 現在のプール。<br />
     /// The current pool.
     current_pool: DescriptorPool,
+
+    /// 可変長の描述子セットのために使われている更新後バインドのプールと、その可変長の描述子の数。<br />
+    /// Update-after-bind pools in use by variable-sized descriptor sets, with their number of variable descriptors.
+    used_update_after_bind_pools: Vec<(DescriptorPool, u32)>,
+
+    /// 使用可能の更新後バインドのプール。<br />
+    /// Update-after-bind pools that are available.
+    free_update_after_bind_pools: Vec<(DescriptorPool, u32)>,
 }
 
 impl DescriptorAllocator {
@@ -60,6 +72,8 @@ impl DescriptorAllocator {
             used_pools: vec![],
             free_pools: vec![],
             current_pool: DescriptorPool::null(),
+            used_update_after_bind_pools: vec![],
+            free_update_after_bind_pools: vec![],
             logical_device: device,
         }
     }
@@ -112,6 +126,82 @@ impl DescriptorAllocator {
         None
     }
 
+    /// 最後のバインディングが可変長のレイアウトに従って描述子セットを配置する。<br />
+    /// 更新後バインドのプールから配置するので、使用中でもまだ使われていない描述子を更新できる。<br />
+    /// Allocate a descriptor set based on a layout whose last binding is variable-sized.<br />
+    /// It's allocated from an update-after-bind pool, so descriptors not used yet can be updated while the set is in use.
+    pub fn allocate_variable(
+        &mut self,
+        layout: DescriptorSetLayout,
+        descriptor_type: DescriptorType,
+        descriptor_count: u32,
+    ) -> Option<DescriptorSet> {
+        let device = self
+            .logical_device
+            .upgrade()
+            .expect("Failed to upgrade device handle.");
+        let free_index = self
+            .free_update_after_bind_pools
+            .iter()
+            .position(|(_, count)| *count >= descriptor_count);
+        let pool = match free_index {
+            Some(index) => self.free_update_after_bind_pools.remove(index),
+            None => {
+                // 可変長の描述子の数だけ加え、他のバインディングには通常のプールと同じ割合を使う。
+                // Add as many as the variable descriptors, using the same ratios as normal pools for the other bindings.
+                let mut pool_sizes = PoolSizes {
+                    sizes: self.descriptor_sizes.sizes.clone(),
+                };
+                for (ty, size) in pool_sizes.sizes.iter_mut() {
+                    if *ty == descriptor_type {
+                        *size += descriptor_count as f32 / UPDATE_AFTER_BIND_POOL_COUNT as f32;
+                    }
+                }
+                let pool = Self::create_pool(
+                    &device,
+                    &pool_sizes,
+                    UPDATE_AFTER_BIND_POOL_COUNT,
+                    DescriptorPoolCreateFlags::UPDATE_AFTER_BIND,
+                );
+                (pool, descriptor_count)
+            }
+        };
+        self.used_update_after_bind_pools.push(pool);
+
+        let layouts = [layout];
+        let counts = [descriptor_count];
+        let mut variable_info =
+            DescriptorSetVariableDescriptorCountAllocateInfo::builder().descriptor_counts(&counts);
+        let allocate_info = DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool.0)
+            .set_layouts(&layouts[..])
+            .push_next(&mut variable_info);
+        unsafe {
+            match device.allocate_descriptor_sets(&allocate_info) {
+                Ok(set) => Some(set[0]),
+                Err(_) => None,
+            }
+        }
+    }
+
+    /// 更新後バインドのプールを全部リセットする。そのプールの描述子セットがGPUで使われていない時だけ呼び出す。<br />
+    /// Reset all update-after-bind pools. Only called when the descriptor sets of those pools aren't in use by the GPU.
+    pub fn reset_update_after_bind_pools(&mut self) {
+        let device = self
+            .logical_device
+            .upgrade()
+            .expect("Failed to upgrade device handle.");
+        unsafe {
+            for (pool, _) in self.used_update_after_bind_pools.iter() {
+                device
+                    .reset_descriptor_pool(*pool, DescriptorPoolResetFlags::empty())
+                    .expect("Failed to reset descriptor pool.");
+            }
+        }
+        self.free_update_after_bind_pools
+            .append(&mut self.used_update_after_bind_pools);
+    }
+
     /// 使用されたプールを全部リセットする。<br />
     /// Reset all used descriptor pools.
     pub fn reset_pool(&mut self) {
@@ -132,6 +222,15 @@ impl DescriptorAllocator {
         self.current_pool = DescriptorPool::null();
     }
 
+    /// 作ったプールの数。使用中と使用可能の両方を数える。<br />
+    /// Number of pools created, counting both those in use and those available.
+    pub fn get_pool_count(&self) -> usize {
+        self.used_pools.len()
+            + self.free_pools.len()
+            + self.used_update_after_bind_pools.len()
+            + self.free_update_after_bind_pools.len()
+    }
+
     /// 使用可能のプールからプールを取得する。<br />
     /// 使用可能のプールがなければ新しいプールを配置する。<br />
     /// Grab a pool from available pools.<br />
@@ -168,7 +267,7 @@ impl DescriptorAllocator {
         for (descriptor_type, size) in pool_sizes.sizes.iter() {
             sizes.push(
                 DescriptorPoolSize::builder()
-                    .descriptor_count((*size * count as f32) as u32)
+                    .descriptor_count((*size * count as f32).ceil() as u32)
                     .ty(*descriptor_type)
                     .build(),
             )
@@ -199,6 +298,13 @@ impl Drop for DescriptorAllocator {
             for pool in self.used_pools.iter() {
                 device.destroy_descriptor_pool(*pool, None);
             }
+            for (pool, _) in self
+                .free_update_after_bind_pools
+                .iter()
+                .chain(self.used_update_after_bind_pools.iter())
+            {
+                device.destroy_descriptor_pool(*pool, None);
+            }
         }
     }
 }
@@ -1,9 +1,10 @@
 use crate::game::graphics::vk::{DescriptorAllocator, DescriptorLayoutCache};
 use ash::version::DeviceV1_0;
 use ash::vk::{
-    DescriptorBufferInfo, DescriptorImageInfo, DescriptorSet, DescriptorSetLayout,
-    DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, ShaderStageFlags,
-    WriteDescriptorSet,
+    DescriptorBindingFlags, DescriptorBufferInfo, DescriptorImageInfo, DescriptorSet,
+    DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutBindingFlagsCreateInfo,
+    DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType,
+    ShaderStageFlags, WriteDescriptorSet,
 };
 
 /// 簡単に描述子セットを設定できるための描述子ビルダー。<br />
@@ -13,6 +14,10 @@ pub struct DescriptorBuilder<'a> {
     allocator: &'a mut DescriptorAllocator,
     writes: Vec<WriteDescriptorSet>,
     bindings: Vec<DescriptorSetLayoutBinding>,
+    binding_flags: Vec<DescriptorBindingFlags>,
+    /// 可変長のバインディングの種類と最大数。<br />
+    /// Type and maximum count of the variable-sized binding.
+    variable_binding: Option<(DescriptorType, u32)>,
 }
 
 impl<'a> DescriptorBuilder<'a> {
@@ -29,6 +34,8 @@ impl<'a> DescriptorBuilder<'a> {
             allocator,
             writes: vec![],
             bindings: vec![],
+            binding_flags: vec![],
+            variable_binding: None,
         }
     }
 
@@ -51,6 +58,7 @@ impl<'a> DescriptorBuilder<'a> {
             .binding(binding)
             .build();
         self.bindings.push(new_binding);
+        self.binding_flags.push(DescriptorBindingFlags::empty());
 
         let new_write = WriteDescriptorSet::builder()
             .descriptor_type(descriptor_type)
@@ -81,6 +89,7 @@ impl<'a> DescriptorBuilder<'a> {
             .binding(binding)
             .build();
         self.bindings.push(new_binding);
+        self.binding_flags.push(DescriptorBindingFlags::empty());
 
         let new_write = WriteDescriptorSet::builder()
             .descriptor_type(descriptor_type)
@@ -92,16 +101,77 @@ impl<'a> DescriptorBuilder<'a> {
         self
     }
 
+    /// 実行中に追加できるイメージの配列をバインドする。<br />
+    /// 最大`capacity`個まで、まだ書き込んでいない要素は後から使用中でも書き込める。最後のバインディングにする。<br />
+    /// Bind to an array of images that can be appended to at runtime.<br />
+    /// Up to `capacity` elements, the ones not written yet can be written later even while in use. It has to be the last binding.
+    pub fn bind_image_array(
+        mut self,
+        binding: u32,
+        capacity: u32,
+        image_info: &'a [DescriptorImageInfo],
+        descriptor_type: DescriptorType,
+        stage_flags: ShaderStageFlags,
+    ) -> Self {
+        let new_binding = DescriptorSetLayoutBinding::builder()
+            .descriptor_count(capacity)
+            .descriptor_type(descriptor_type)
+            .stage_flags(stage_flags)
+            .binding(binding)
+            .build();
+        self.bindings.push(new_binding);
+        self.binding_flags.push(
+            DescriptorBindingFlags::UPDATE_AFTER_BIND
+                | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING
+                | DescriptorBindingFlags::PARTIALLY_BOUND
+                | DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT,
+        );
+        self.variable_binding = Some((descriptor_type, capacity));
+
+        // Writing zero descriptors isn't allowed.
+        if !image_info.is_empty() {
+            let new_write = WriteDescriptorSet::builder()
+                .descriptor_type(descriptor_type)
+                .image_info(image_info)
+                .dst_array_element(0)
+                .dst_binding(binding)
+                .build();
+            self.writes.push(new_write);
+        }
+        self
+    }
+
     /// ビルダーを終えて配置された描述子セットとそのセットのレイアウトを返す。<br />
     /// Ends the builder and returns allocated descriptor set and its descriptor set layout.
     pub fn build(mut self) -> Option<(DescriptorSet, DescriptorSetLayout)> {
         // Build layout first.
-        let layout_info =
-            DescriptorSetLayoutCreateInfo::builder().bindings(self.bindings.as_slice());
-        let layout = self.layout_cache.create_descriptor_layout(&layout_info);
+        let mut flags_info = DescriptorSetLayoutBindingFlagsCreateInfo::builder()
+            .binding_flags(self.binding_flags.as_slice());
+        let layout = match self.variable_binding {
+            Some(_) => {
+                let layout_info = DescriptorSetLayoutCreateInfo::builder()
+                    .bindings(self.bindings.as_slice())
+                    .flags(DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+                    .push_next(&mut flags_info);
+                self.layout_cache
+                    .create_descriptor_layout(&layout_info, self.binding_flags.as_slice())
+            }
+            None => {
+                let layout_info =
+                    DescriptorSetLayoutCreateInfo::builder().bindings(self.bindings.as_slice());
+                self.layout_cache
+                    .create_descriptor_layout(&layout_info, &[])
+            }
+        };
 
         // Allocate the descriptor set.
-        let descriptor_set = self.allocator.allocate(layout);
+        let descriptor_set = match self.variable_binding {
+            Some((descriptor_type, capacity)) => {
+                self.allocator
+                    .allocate_variable(layout, descriptor_type, capacity)
+            }
+            None => self.allocator.allocate(layout),
+        };
         if let Some(set) = descriptor_set {
             // Write descriptor sets
             for write in self.writes.iter_mut() {
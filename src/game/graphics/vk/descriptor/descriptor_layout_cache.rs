@@ -1,5 +1,8 @@
 use ash::version::DeviceV1_0;
-use ash::vk::{DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo};
+use ash::vk::{
+    DescriptorBindingFlags, DescriptorSetLayout, DescriptorSetLayoutBinding,
+    DescriptorSetLayoutCreateInfo,
+};
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::sync::Weak;
@@ -10,6 +13,9 @@ use std::sync::Weak;
 /// This struct is used as keys in a HashMap, so it implements `PartialEq, Eq` and `Hash`.
 struct DescriptorLayoutInfo {
     pub bindings: Vec<DescriptorSetLayoutBinding>,
+    /// 各バインディングのフラグ。`bindings`と同じ順番。<br />
+    /// Flags of each binding, in the same order as `bindings`.
+    pub binding_flags: Vec<DescriptorBindingFlags>,
 }
 
 impl PartialEq for DescriptorLayoutInfo {
@@ -18,6 +24,9 @@ impl PartialEq for DescriptorLayoutInfo {
             false
         } else {
             // Compare each of the bindings is the same. Bindings are sorted so they will match.
+            if self.binding_flags != other.binding_flags {
+                return false;
+            }
             let iterator = self.bindings.iter().zip(other.bindings.iter());
             for (binding_self, binding_other) in iterator {
                 if binding_self.binding != binding_other.binding
@@ -44,6 +53,7 @@ impl Hash for DescriptorLayoutInfo {
             binding.descriptor_type.hash(state);
             binding.p_immutable_samplers.hash(state);
         }
+        self.binding_flags.hash(state);
     }
 }
 
@@ -69,13 +79,17 @@ impl DescriptorLayoutCache {
     }
 
     /// 描述子レイアウトを作成する。<br />
-    /// Create a descriptor layout.
+    /// `binding_flags`は`info`に繋いだフラグと同じもので、フラグのないバインディングだけなら空にする。<br />
+    /// Create a descriptor layout.<br />
+    /// `binding_flags` are the same flags chained to `info`, left empty if none of the bindings have flags.
     pub fn create_descriptor_layout(
         &mut self,
         info: &DescriptorSetLayoutCreateInfo,
+        binding_flags: &[DescriptorBindingFlags],
     ) -> DescriptorSetLayout {
         let mut layout_info = DescriptorLayoutInfo {
             bindings: Vec::with_capacity(info.binding_count as usize),
+            binding_flags: vec![],
         };
         let mut sorted = true;
         let mut last_binding = -1_i32;
@@ -84,6 +98,9 @@ impl DescriptorLayoutCache {
         // Copy from the direct info struct into our own one.
         for i in 0..info.binding_count {
             layout_info.bindings.push(bindings[i as usize]);
+            if !binding_flags.is_empty() {
+                layout_info.binding_flags.push(binding_flags[i as usize]);
+            }
 
             // Check that the bindings are in strict increasing order.
             if bindings[i as usize].binding > last_binding as u32 {
@@ -95,9 +112,22 @@ impl DescriptorLayoutCache {
 
         // Sort the bindings if they are not in order.
         if !sorted {
-            layout_info
-                .bindings
-                .sort_unstable_by(|a, b| a.binding.cmp(&b.binding));
+            if layout_info.binding_flags.is_empty() {
+                layout_info
+                    .bindings
+                    .sort_unstable_by(|a, b| a.binding.cmp(&b.binding));
+            } else {
+                // Keep the flags next to their bindings.
+                let mut pairs = layout_info
+                    .bindings
+                    .drain(..)
+                    .zip(layout_info.binding_flags.drain(..))
+                    .collect::<Vec<_>>();
+                pairs.sort_unstable_by(|a, b| a.0.binding.cmp(&b.0.binding));
+                let (sorted_bindings, sorted_flags) = pairs.into_iter().unzip();
+                layout_info.bindings = sorted_bindings;
+                layout_info.binding_flags = sorted_flags;
+            }
         }
 
         // Try to grab from cache.
@@ -1,6 +1,8 @@
 pub mod descriptor_allocator;
 pub mod descriptor_builder;
 pub mod descriptor_layout_cache;
+pub mod texture_sets;
 pub use descriptor_allocator::DescriptorAllocator;
 pub use descriptor_builder::DescriptorBuilder;
 pub use descriptor_layout_cache::DescriptorLayoutCache;
+pub use texture_sets::{TextureDescriptorSets, TextureLimits, TextureSetLayout};
@@ -0,0 +1,138 @@
+use ash::vk::DescriptorSet;
+use std::ops::Range;
+
+/// テクスチャ配列に関わる、デバイスとシェーダーの上限。<br />
+/// Device and shader limits on the texture array.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TextureLimits {
+    /// フラグメントシェーダーで使えるテクスチャの最大数。<br />
+    /// Maximum number of textures usable in the fragment stage.
+    pub max_per_stage: u32,
+    /// シェーダーが宣言するテクスチャ配列の長さ。長さのない配列なら`None`。<br />
+    /// Length of the texture array declared by the shaders, or `None` for unsized arrays.
+    pub shader_array_length: Option<u32>,
+    /// バインドレスの配列の長さ。バインドレスが使えなければ0。<br />
+    /// Length of the bindless array, or 0 without bindless support.
+    pub bindless_capacity: u32,
+}
+
+impl TextureLimits {
+    /// テクスチャの数に対して、描述子セットをいくつに分けるかを決める。<br />
+    /// 上限を超えるテクスチャは複数のセットに分ける。シェーダーがデバイスの上限を超える配列を宣言していれば失敗する。<br />
+    /// Decide how many descriptor sets the textures are split into.<br />
+    /// Textures over the limit are split across several sets. It fails if the shaders declare an array over the device limit.
+    pub fn plan(&self, texture_count: usize) -> anyhow::Result<TextureSetLayout> {
+        let textures_per_set = if self.bindless_capacity > 0 {
+            self.bindless_capacity
+        } else {
+            match self.shader_array_length {
+                Some(length) if length > self.max_per_stage => {
+                    return Err(anyhow::anyhow!(
+                        "The shaders declare {} textures, but the device allows only {} per stage. Lower the length of tex_sampler in the shaders to {} or less.",
+                        length,
+                        self.max_per_stage,
+                        self.max_per_stage
+                    ));
+                }
+                Some(length) => length,
+                None => self.max_per_stage,
+            }
+        } as usize;
+        if textures_per_set == 0 {
+            return Err(anyhow::anyhow!(
+                "The texture array can't hold any texture. Check the sampler limits of the device and the length of tex_sampler in the shaders."
+            ));
+        }
+        if texture_count == 0 && self.bindless_capacity == 0 && self.shader_array_length.is_some() {
+            return Err(anyhow::anyhow!(
+                "The shaders declare a fixed texture array, but no textures are loaded to fill it. Load at least one texture before creating the descriptor sets."
+            ));
+        }
+        let set_count = ((texture_count + textures_per_set - 1) / textures_per_set).max(1);
+        // 長さのない配列で分けなくてよければ、テクスチャの数だけの配列にする。
+        // Without splitting, an unsized array is only as long as the number of textures.
+        let array_length = match (self.bindless_capacity, self.shader_array_length) {
+            (0, None) if set_count == 1 => texture_count,
+            _ => textures_per_set,
+        };
+        Ok(TextureSetLayout {
+            textures_per_set,
+            array_length,
+            set_count,
+        })
+    }
+}
+
+/// テクスチャを描述子セットに分けた配置。<br />
+/// How the textures are laid out across descriptor sets.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct TextureSetLayout {
+    pub textures_per_set: usize,
+    /// 各セットの配列の長さ。足りないテクスチャは埋める。<br />
+    /// Length of the array in each set. Missing textures are padded.
+    pub array_length: usize,
+    pub set_count: usize,
+}
+
+impl TextureSetLayout {
+    /// テクスチャの番号から、セットの番号とセットの中での番号を得る。<br />
+    /// どのセットにも入らない番号は、最初のテクスチャを指す。<br />
+    /// Get the set index and the index within the set from a texture index.<br />
+    /// Indices outside every set point to the first texture.
+    pub fn locate(&self, texture_index: usize) -> (usize, usize) {
+        if self.textures_per_set == 0 {
+            return (0, 0);
+        }
+        let set_index = texture_index / self.textures_per_set;
+        if set_index >= self.set_count {
+            return (0, 0);
+        }
+        (set_index, texture_index % self.textures_per_set)
+    }
+
+    /// セットに入るテクスチャの番号の範囲。<br />
+    /// Range of the texture indices that go into a set.
+    pub fn get_texture_range(&self, set_index: usize, texture_count: usize) -> Range<usize> {
+        let start = (set_index * self.textures_per_set).min(texture_count);
+        let end = ((set_index + 1) * self.textures_per_set).min(texture_count);
+        start..end
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.textures_per_set * self.set_count
+    }
+}
+
+/// テクスチャ配列を持つ描述子セット。モデルは描く部分のテクスチャによってセットを選ぶ。<br />
+/// Descriptor sets holding the texture array. Models pick the set by the texture of the part being drawn.
+#[derive(Clone, Debug, Default)]
+pub struct TextureDescriptorSets {
+    pub layout: TextureSetLayout,
+    sets: Vec<DescriptorSet>,
+}
+
+impl TextureDescriptorSets {
+    pub fn new(layout: TextureSetLayout, sets: Vec<DescriptorSet>) -> Self {
+        TextureDescriptorSets { layout, sets }
+    }
+
+    pub fn get_sets(&self) -> &[DescriptorSet] {
+        &self.sets
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// テクスチャの番号から、バインドする描述子セットとシェーダーに渡す番号を得る。<br />
+    /// Get the descriptor set to bind and the index passed to the shader from a texture index.
+    pub fn locate(&self, texture_index: usize) -> (DescriptorSet, usize) {
+        let (set_index, index) = self.layout.locate(texture_index);
+        let set = self
+            .sets
+            .get(set_index)
+            .copied()
+            .unwrap_or_else(DescriptorSet::null);
+        (set, index)
+    }
+}
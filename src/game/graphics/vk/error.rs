@@ -0,0 +1,97 @@
+use ash::vk;
+use thiserror::Error;
+
+/// 描画で起きるエラー。<br />
+/// デバイスの喪失とスワップチェーンの期限切れは、作り直せば描画を続けられる。<br />
+/// Errors raised by the graphics module.<br />
+/// A lost device and an out-of-date swapchain can be recovered from by recreating them.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum GraphicsError {
+    #[error("The graphics device was lost while trying to {0}.")]
+    DeviceLost(&'static str),
+    #[error("The swapchain is out of date or suboptimal.")]
+    SwapchainOutOfDate,
+    #[error("Ran out of {memory} memory while trying to {operation}.")]
+    OutOfMemory {
+        operation: &'static str,
+        memory: &'static str,
+    },
+    #[error("Failed to {operation}: {result}")]
+    Vulkan {
+        operation: &'static str,
+        result: vk::Result,
+    },
+    /// VMAのエラーは種類を取り出せないので、メッセージだけを残す。<br />
+    /// The kind of VMA errors can't be taken out, so only the message is kept.
+    #[error("Failed to {operation}: {message}")]
+    Allocation {
+        operation: &'static str,
+        message: String,
+    },
+    /// 弱い参照の先がもう解放されている。<br />
+    /// The target of a weak reference has already been released.
+    #[error("The {0} is no longer available.")]
+    Unavailable(&'static str),
+}
+
+impl GraphicsError {
+    /// Vulkanの結果を、回復できるかどうかで分けたエラーにする。<br />
+    /// Turn a Vulkan result into an error classified by whether it can be recovered from.
+    pub fn from_vk(operation: &'static str, result: vk::Result) -> Self {
+        match result {
+            vk::Result::ERROR_DEVICE_LOST => GraphicsError::DeviceLost(operation),
+            vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::SUBOPTIMAL_KHR => {
+                GraphicsError::SwapchainOutOfDate
+            }
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY => GraphicsError::OutOfMemory {
+                operation,
+                memory: "host",
+            },
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => GraphicsError::OutOfMemory {
+                operation,
+                memory: "device",
+            },
+            result => GraphicsError::Vulkan { operation, result },
+        }
+    }
+
+    pub fn allocation<E: std::fmt::Display>(operation: &'static str, error: E) -> Self {
+        GraphicsError::Allocation {
+            operation,
+            message: error.to_string(),
+        }
+    }
+
+    pub fn is_device_lost(&self) -> bool {
+        matches!(self, GraphicsError::DeviceLost(_))
+    }
+
+    /// デバイスかスワップチェーンを作り直せば続けられるかどうか。<br />
+    /// Whether rendering can continue after recreating the device or the swapchain.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            GraphicsError::DeviceLost(_) | GraphicsError::SwapchainOutOfDate
+        )
+    }
+
+    /// `anyhow`のエラーの原因を辿り、描画のエラーを探す。<br />
+    /// Walk the causes of an `anyhow` error and look for a graphics error.
+    pub fn find(error: &anyhow::Error) -> Option<&GraphicsError> {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<GraphicsError>())
+    }
+}
+
+/// Vulkanの結果に、何をしようとしていたかを付けて`GraphicsError`にする。<br />
+/// Turn a Vulkan result into a `GraphicsError` noting what was being attempted.
+pub trait VkResultExt<T> {
+    fn or_graphics_error(self, operation: &'static str) -> Result<T, GraphicsError>;
+}
+
+impl<T> VkResultExt<T> for Result<T, vk::Result> {
+    fn or_graphics_error(self, operation: &'static str) -> Result<T, GraphicsError> {
+        self.map_err(|result| GraphicsError::from_vk(operation, result))
+    }
+}
@@ -0,0 +1,179 @@
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::{vk::*, Device, Instance};
+use std::sync::Weak;
+
+/// 一フレームで計測できるレンダーパスの最大数。<br />
+/// Maximum number of render passes measured in a frame.
+const MAX_TIMED_PASSES: u32 = 16;
+
+/// タイムスタンプクエリでレンダーパスごとのGPUの時間を測る。<br />
+/// フレームごとにクエリプールを持ち、同じフレームのフェンスを待った後に前回の結果を読む。<br />
+/// Measures the GPU time of each render pass with timestamp queries.<br />
+/// Each frame in flight has its own query pool, and the previous results are read after waiting for that frame's fence.
+pub struct GpuTimer {
+    query_pools: Vec<QueryPool>,
+    /// フレームごとに、書き込んだパスの名前。<br />
+    /// Names of the passes written in each frame.
+    pass_names: Vec<Vec<String>>,
+    /// 1ティックのナノ秒。<br />
+    /// Nanoseconds per tick.
+    timestamp_period: f64,
+    timestamp_mask: u64,
+    logical_device: Weak<Device>,
+}
+
+impl GpuTimer {
+    pub fn new(
+        device: Weak<Device>,
+        instance: &Instance,
+        physical_device: &super::PhysicalDevice,
+        frame_count: usize,
+    ) -> Self {
+        let valid_bits = physical_device
+            .queue_indices
+            .graphics_family
+            .and_then(|family| unsafe {
+                instance
+                    .get_physical_device_queue_family_properties(physical_device.physical_device)
+                    .get(family as usize)
+                    .map(|properties| properties.timestamp_valid_bits)
+            })
+            .unwrap_or(0);
+        let mut timer = GpuTimer {
+            query_pools: vec![],
+            pass_names: vec![vec![]; frame_count],
+            timestamp_period: physical_device.device_properties.limits.timestamp_period as f64,
+            timestamp_mask: if valid_bits >= 64 {
+                u64::MAX
+            } else {
+                (1_u64 << valid_bits) - 1
+            },
+            logical_device: device,
+        };
+        if valid_bits == 0 {
+            log::warn!("The graphics queue doesn't support timestamps. GPU timing is disabled.");
+            return timer;
+        }
+        let device = timer
+            .logical_device
+            .upgrade()
+            .expect("Failed to upgrade logical device to create query pools.");
+        let query_pool_info = QueryPoolCreateInfo::builder()
+            .query_type(QueryType::TIMESTAMP)
+            .query_count(MAX_TIMED_PASSES * 2);
+        for _ in 0..frame_count {
+            unsafe {
+                match device.create_query_pool(&query_pool_info, None) {
+                    Ok(query_pool) => timer.query_pools.push(query_pool),
+                    Err(e) => {
+                        log::warn!("Failed to create query pool. GPU timing is disabled: {}", e);
+                        for query_pool in timer.query_pools.drain(..) {
+                            device.destroy_query_pool(query_pool, None);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        timer
+    }
+
+    pub fn is_supported(&self) -> bool {
+        !self.query_pools.is_empty()
+    }
+
+    /// 前回このフレームで書き込んだ結果をミリ秒で読む。フレームのフェンスを待ってから呼び出す。<br />
+    /// Read the results last written in this frame in milliseconds. Called after waiting for the frame's fence.
+    pub unsafe fn read_results(&mut self, frame_index: usize) -> Vec<(String, f64)> {
+        let pass_names = std::mem::take(&mut self.pass_names[frame_index]);
+        if pass_names.is_empty() || !self.is_supported() {
+            return vec![];
+        }
+        let device = match self.logical_device.upgrade() {
+            Some(device) => device,
+            None => return vec![],
+        };
+        let mut timestamps = vec![0_u64; pass_names.len() * 2];
+        let result = device.get_query_pool_results(
+            self.query_pools[frame_index],
+            0,
+            timestamps.len() as u32,
+            timestamps.as_mut_slice(),
+            QueryResultFlags::TYPE_64,
+        );
+        if result.is_err() {
+            return vec![];
+        }
+        pass_names
+            .into_iter()
+            .zip(timestamps.chunks(2))
+            .map(|(name, timestamps)| {
+                let ticks = timestamps[1].wrapping_sub(timestamps[0]) & self.timestamp_mask;
+                (name, ticks as f64 * self.timestamp_period / 1_000_000.0)
+            })
+            .collect()
+    }
+
+    /// フレームのクエリをリセットする。コマンドバッファの最初に記録する。<br />
+    /// Reset the queries of the frame, recorded at the beginning of the command buffer.
+    pub unsafe fn begin_frame(&mut self, command_buffer: CommandBuffer, frame_index: usize) {
+        self.pass_names[frame_index].clear();
+        if let (Some(device), Some(query_pool)) = (
+            self.logical_device.upgrade(),
+            self.query_pools.get(frame_index),
+        ) {
+            device.cmd_reset_query_pool(command_buffer, *query_pool, 0, MAX_TIMED_PASSES * 2);
+        }
+    }
+
+    /// パスの前後にタイムスタンプを書く。最大数を超えたパスは測らない。<br />
+    /// Write timestamps around a pass. Passes beyond the maximum aren't measured.
+    pub unsafe fn time_pass<F, R>(
+        &mut self,
+        command_buffer: CommandBuffer,
+        frame_index: usize,
+        name: &str,
+        record: F,
+    ) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let query = self.pass_names[frame_index].len() as u32 * 2;
+        let (device, query_pool) = match (
+            self.logical_device.upgrade(),
+            self.query_pools.get(frame_index),
+        ) {
+            (Some(device), Some(query_pool)) if query < MAX_TIMED_PASSES * 2 => {
+                (device, *query_pool)
+            }
+            _ => return record(),
+        };
+        device.cmd_write_timestamp(
+            command_buffer,
+            PipelineStageFlags::TOP_OF_PIPE,
+            query_pool,
+            query,
+        );
+        let result = record();
+        device.cmd_write_timestamp(
+            command_buffer,
+            PipelineStageFlags::BOTTOM_OF_PIPE,
+            query_pool,
+            query + 1,
+        );
+        self.pass_names[frame_index].push(name.to_string());
+        result
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        if let Some(device) = self.logical_device.upgrade() {
+            unsafe {
+                for query_pool in self.query_pools.iter() {
+                    device.destroy_query_pool(*query_pool, None);
+                }
+            }
+        }
+    }
+}
@@ -1,39 +1,71 @@
+use anyhow::Context;
 use ash::{
     extensions::{ext::DebugUtils, khr::Surface},
-    version::{DeviceV1_0, InstanceV1_0},
+    version::{DeviceV1_0, InstanceV1_0, InstanceV1_1},
     vk::*,
     Device, Entry, Instance,
 };
 use crossbeam::sync::ShardedLock;
 use glam::{Mat4, Vec3A, Vec4};
 use parking_lot::{Mutex, RwLock};
+use slotmap::DefaultKey;
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::ffi::{c_void, CString};
 use std::mem::ManuallyDrop;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
 use std::sync::{Arc, Weak};
+use std::time::Instant;
 use vk_mem::*;
 
 use crate::game::enums::ShaderType;
 use crate::game::graphics::vk::{
-    DescriptorAllocator, DescriptorBuilder, DescriptorLayoutCache, Initializer, RenderPassType,
-    ThreadPool, UniformBuffers,
+    get_post_process_target, BufferUpload, DebugDrawRenderer, DescriptorAllocator,
+    DescriptorBuilder, DescriptorLayoutCache, FramebufferSource, GpuTimer, GraphicsError,
+    IndirectBatchKey, IndirectRenderer, Initializer, MeshBufferPool, MeshBuffers, MeshPool,
+    OcclusionRenderer, ParticleRenderer, PassTarget, PostProcessTargets, RenderContext,
+    RenderPassType, RippleTexture, SecondaryWindow, SpriteRenderer, StagingRing,
+    StreamedTextureSource, TextureDescriptorSets, TextureLimits, TextureSource, TextureStreamer,
+    TextureSwap, ThreadPool, TransferQueue, UniformBuffers, VkResultExt, HDR_FORMAT,
+    STAGING_RING_SIZE,
+};
+use crate::game::shared::camera::FAR_PLANE;
+use crate::game::shared::components::Bounds;
+use crate::game::shared::enums::{ImageFormat, PresentMode, SceneType};
+use crate::game::shared::structs::{
+    build_sprite_vertices, get_validation_messages, AssetKind, CrashReport, Daylight, DebugVertex,
+    DeletionQueue, Directional, FogSettings, FrameInfo, FrameSubmission, FrameTimings, Frustum,
+    GraphicsSettings, HeapUsage, IndirectDrawList, MemoryStats, OcclusionCuller, ParticleDispatch,
+    PassOutput, PostProcessPushConstant, PostProcessSettings, PostProcessStep, PushConstant,
+    RenderFeatures, RenderGraph, RenderPassNode, Settings, Sprite, SpriteAtlas, TimeOfDay,
+    ViewProjection, CRASH_REPORT_DIRECTORY, DEBUG_LINE_FRAGMENT_SHADER, DEBUG_LINE_VERTEX_SHADER,
+    HDR_SCENE_ATTACHMENT, INDIRECT_CULL_COMPUTE_SHADER, OCCLUSION_FRAGMENT_SHADER,
+    OCCLUSION_VERTEX_SHADER, PARTICLE_COMPUTE_SHADER, PARTICLE_FRAGMENT_SHADER,
+    PARTICLE_VERTEX_SHADER, POST_PROCESS_VERTEX_SHADER, RIPPLE_RESOLUTION, SPRITE_FRAGMENT_SHADER,
+    SPRITE_VERTEX_SHADER,
 };
-use crate::game::shared::enums::{ImageFormat, SceneType};
-use crate::game::shared::structs::{Directional, PushConstant, ViewProjection};
 use crate::game::shared::traits::{GraphicsBase, Renderable};
 use crate::game::shared::util::interpolate_alpha;
-use crate::game::traits::Mappable;
-use crate::game::util::{end_one_time_command_buffer, get_single_time_command_buffer};
-use crate::game::{Camera, ResourceManager, UISystem};
+use crate::game::shared::util::spirv_reflection::{
+    get_descriptor_array_length, DescriptorArrayLength,
+};
+use crate::game::traits::{Disposable, Mappable};
+use crate::game::ui::vk::{DrawTarget, Drawer};
+use crate::game::ui::TweakRegistry;
+use crate::game::{Camera, ReleasedCommandBuffers, ResourceManager, UISystem};
 use ash::prelude::VkResult;
 
 /// 既定のSSBO配列の長さ。<br />
 /// The default length of SSBO array.
 const SSBO_DATA_COUNT: usize = 50;
 
+/// CPUのコア何個ごとに読み込みのスレッドを一つ作るか。<br />
+/// How many CPU cores there are for each streaming thread.
+const STREAMING_THREAD_RATIO: usize = 4;
+
 /// 水面反射のレンダーターゲットの幅。<br />
 /// The width of the render target of water surface's reflection.
 const REFLECTION_WIDTH: u32 = 320;
@@ -50,6 +82,19 @@ const REFRACTION_WIDTH: u32 = 1280;
 /// The height of the render target of water surface's refraction.
 const REFRACTION_HEIGHT: u32 = 720;
 
+/// バインドレスのテクスチャ配列の最大の長さ。デバイスの上限の方が小さければそれを使う。<br />
+/// The maximum length of the bindless texture array. The device limit is used if it's smaller.
+const MAX_BINDLESS_TEXTURES: u32 = 4096;
+
+/// フラグメントシェーダーのテクスチャ配列のバインディング。<br />
+/// Binding of the texture array in the fragment shaders.
+const TEXTURE_ARRAY_BINDING: u32 = 3;
+
+/// 読み込めなかったシェーダーの代わりに使うシェーダー。テクスチャを使わずに物体の色だけで描く。<br />
+/// Shaders used in place of shaders that failed to load. They draw with the object color only, without textures.
+const FALLBACK_VERTEX_SHADER: &str = "./shaders/vert.spv";
+const FALLBACK_FRAGMENT_SHADER: &str = "./shaders/basicShader_noTexture.spv";
+
 /// リソースマネジャーのハンドルタイプ定義。<br />
 /// Type definition of resource manager handle.
 type ResourceManagerHandle = Weak<
@@ -67,6 +112,16 @@ type UIManagerHandle = std::rc::Weak<
 type LockableRenderable =
     Arc<Mutex<Box<dyn Renderable<Graphics, super::Buffer, CommandBuffer, super::Image> + Send>>>;
 
+/// フレームのフェンスが通知するまで壊すのを待つGPUのリソース。<br />
+/// GPU resources whose destruction waits until the fence of their frame signals.
+pub enum DeferredDeletion {
+    Resource(Box<dyn Disposable + Send>),
+    /// モデルはコマンドバッファも一緒に解放する。<br />
+    /// Models free their command buffers as well.
+    Renderable(LockableRenderable),
+    CommandBuffers(ReleasedCommandBuffers<CommandBuffer>),
+}
+
 /// 主なSSBOデータコンテンツ。<br />
 /// Primary SSBO contents.
 #[derive(Clone)]
@@ -137,9 +192,9 @@ pub struct Graphics {
     /// Rendering pipelines.
     pub pipeline: Arc<ShardedLock<ManuallyDrop<super::Pipeline>>>,
 
-    /// 描述子セット。<br />
-    /// Descriptor set.
-    pub descriptor_set: DescriptorSet,
+    /// テクスチャ配列を持つ描述子セット。テクスチャが上限を超えれば複数に分ける。<br />
+    /// Descriptor sets holding the texture array, split into several when the textures exceed the limit.
+    pub texture_descriptor_sets: Arc<TextureDescriptorSets>,
 
     /// プッシュコンスタント。<br />
     /// Push constant.
@@ -161,6 +216,10 @@ pub struct Graphics {
     /// Thread pool for multithreaded rendering.
     pub thread_pool: Arc<ThreadPool>,
 
+    /// テクスチャの高いミップのストリーミング。<br />
+    /// Streaming of the high mips of textures.
+    pub texture_streamer: Arc<TextureStreamer>,
+
     /// VMAメモリー配置器。<br />
     /// VMA memory allocator.
     pub allocator: Arc<ShardedLock<Allocator>>,
@@ -177,6 +236,18 @@ pub struct Graphics {
     /// Main compute queue.
     pub compute_queue: Arc<Mutex<Queue>>,
 
+    /// テクスチャとバッファのアップロードに使うキュー。<br />
+    /// Queue used for texture and buffer uploads.
+    pub transfer_queue: ManuallyDrop<Arc<TransferQueue>>,
+
+    /// アップロードのステージング領域を切り出すリングバッファ。<br />
+    /// Ring buffer that staging regions for uploads are cut out of.
+    pub staging_ring: ManuallyDrop<Arc<StagingRing>>,
+
+    /// 静的なメッシュと動的なメッシュの頂点とインデックスを切り出すプール。<br />
+    /// Pools that the vertices and indices of static and dynamic meshes are cut out of.
+    mesh_buffer_pools: HashMap<MeshPool, Arc<Mutex<MeshBufferPool>>>,
+
     pub swapchain: ManuallyDrop<super::Swapchain>,
     pub frame_buffers: Vec<Framebuffer>,
     pub resource_manager: ResourceManagerHandle,
@@ -226,8 +297,141 @@ pub struct Graphics {
     /// オフスクリーンのレンダパース。まだ実装していません。<br />
     /// Offscreen renderpass. Not yet implemented.
     offscreen_pass: ManuallyDrop<OffscreenPass>,
+
+    /// 毎フレーム実行するパスのグラフ。<br />
+    /// Graph of the passes executed every frame.
+    render_graph: RenderGraph,
+
+    /// パスの名前と、それを実行するためのリソース。<br />
+    /// Names of passes and the resources for executing them.
+    pass_targets: HashMap<String, PassTarget>,
+
+    /// ポストプロセスの設定。<br />
+    /// Post-processing settings.
+    post_process_settings: PostProcessSettings,
+
+    /// 今のシーンが使う描画の機能。<br />
+    /// Rendering features used by the current scene.
+    render_features: RenderFeatures,
+
+    /// 毎パス集める二次コマンドバッファ。フレームごとに確保しないように使い回す。<br />
+    /// Secondary command buffers gathered for each pass, reused so that they aren't allocated every frame.
+    secondary_command_buffers: Mutex<Vec<CommandBuffer>>,
+
+    /// メインのウィンドウの他に開いているウィンドウ。毎フレームメインの画像を写す。<br />
+    /// Windows open besides the main one, which the main image is copied to every frame.
+    secondary_windows: Mutex<Vec<SecondaryWindow>>,
+
+    /// フレームの番号ごとに壊すのを待っているリソース。<br />
+    /// Resources waiting to be destroyed, keyed by frame number.
+    deletion_queue: Mutex<DeletionQueue<DeferredDeletion>>,
+
+    /// 境界の警告の強さ。シーンが毎フレーム設定する。<br />
+    /// Strength of the boundary warning, set by the scene every frame.
+    boundary_warning: f32,
+
+    /// 粒子を動かして描くリソース。パイプラインを作る時に作られ、作れなければ粒子は出ない。<br />
+    /// Resources simulating and drawing particles, created along with the pipelines. Particles are disabled if they can't be created.
+    particle_renderer: Mutex<Option<ParticleRenderer>>,
+
+    /// このフレームに動かして描く発生源。シーンが毎フレーム設定する。<br />
+    /// Emitters simulated and drawn this frame, set by the scene every frame.
+    particle_dispatches: Mutex<Vec<ParticleDispatch>>,
+
+    /// デバッグ用の線を描くリソース。作れなければ線は描かれない。<br />
+    /// Resources drawing debug lines. No lines are drawn if they can't be created.
+    debug_draw_renderer: Mutex<Option<DebugDrawRenderer>>,
+
+    /// このフレームに描くデバッグ用の線の頂点。提出した後に空にする。<br />
+    /// Vertices of the debug lines drawn this frame, emptied after submitting.
+    debug_lines: Mutex<Vec<DebugVertex>>,
+
+    /// オクルージョンクエリで境界ボックスを描くリソース。作れなければ隠れたモデルも描く。<br />
+    /// Resources drawing bounding boxes with occlusion queries. Hidden models are drawn too if they can't be created.
+    occlusion_renderer: Mutex<Option<OcclusionRenderer>>,
+
+    /// オクルージョンクエリの結果から、隠れたエンティティを覚えておく。<br />
+    /// Remembers the hidden entities from the results of occlusion queries.
+    occlusion_culler: Mutex<OcclusionCuller>,
+
+    /// 静的なモデルとインスタンス描画を間接描画で描くリソース。対応しないか作れなければ、モデルごとに描く。<br />
+    /// Resources drawing static and instanced models with indirect draws. Models are drawn one by one if unsupported or if they can't be created.
+    indirect_renderer: Mutex<Option<IndirectRenderer>>,
+
+    /// シーンのパスでモデルが加えた間接描画。パスごとに空にする。<br />
+    /// Indirect draws added by models in the scene pass, emptied for each pass.
+    indirect_draws: Arc<Mutex<IndirectDrawList<IndirectBatchKey>>>,
+
+    /// モデルの二次コマンドバッファを記録し直させる世代。パイプラインや描述子セットを作り直すと上がる。<br />
+    /// Generation forcing models to record their secondary command buffers again, raised when pipelines or descriptor sets are rebuilt.
+    record_generation: u64,
+
+    /// 画面に重ねるスプライトを描くリソース。シーンがアトラスを設定するまでは作らない。<br />
+    /// Resources drawing sprites overlaid on the screen, not created until a scene sets an atlas.
+    sprite_renderer: Mutex<Option<SpriteRenderer>>,
+
+    /// 水面の波紋のテクスチャ。水面のシェーダーを使うプリミティブが最初に要求するまでは作らない。<br />
+    /// The texture of the ripples on the water surface, not created until a primitive using the water shader requests it.
+    ripple_texture: Mutex<Option<RippleTexture>>,
+
+    /// このフレームに描くスプライト。提出した後に空にする。<br />
+    /// Sprites drawn this frame, emptied after submitting.
+    sprites: Mutex<Vec<Sprite>>,
+
+    /// プレイヤーが選んだグラフィックスの設定。<br />
+    /// Graphics settings chosen by the player.
+    graphics_settings: GraphicsSettings,
+
+    /// 今のスワップチェーンを作る時に希望したプレゼントモード。<br />
+    /// Present mode requested when the current swapchain was created.
+    present_mode: PresentMode,
+
+    /// 今のポストプロセスのレンダーターゲットを作る時の描画解像度の倍率。<br />
+    /// Render scale used when the current post-processing targets were created.
+    render_scale: u32,
+
+    /// デバイスが対応しているMSAAのサンプル数。<br />
+    /// MSAA sample counts supported by the device.
+    supported_msaa_samples: Vec<u32>,
+
+    /// ポストプロセスのレンダーターゲット。パイプラインと一緒に作られる。<br />
+    /// Render targets of post-processing, created along with the pipelines.
+    post_process_targets: Option<PostProcessTargets>,
+
+    /// レンダーパスごとのGPUの時間を測るタイマー。<br />
+    /// Timer measuring the GPU time of each render pass.
+    gpu_timer: Mutex<ManuallyDrop<GpuTimer>>,
+
+    /// 前回の描画で測った時間。<br />
+    /// Times measured by the last render.
+    frame_timings: Mutex<FrameTimings>,
+
+    /// 描述子セットのテクスチャ配列の長さ。バインドレスが使えなければ0で、テクスチャが増えると描述子セットを作り直す。<br />
+    /// Length of the texture array of the descriptor set. It's 0 without bindless support, in which case the descriptor set is rebuilt when textures are added.
+    bindless_capacity: u32,
+
+    /// 描述子セットに書き込んだテクスチャの数。<br />
+    /// Number of textures written to the descriptor set.
+    bound_texture_count: AtomicUsize,
+
+    /// テクスチャ配列の長さを決める、デバイスとシェーダーの上限。<br />
+    /// Device and shader limits deciding the length of the texture array.
+    texture_limits: TextureLimits,
+
+    /// 平行光源の水平な位置。作り直した時にもこの位置を使う。<br />
+    /// Horizontal position of the directional light, used again on recreation.
+    light_position: (f32, f32),
+    /// 今の時間の光と空。作り直した時にもこの光を使う。<br />
+    /// Light and sky at the current time of day, used again on recreation.
+    daylight: Daylight,
+    fog_settings: FogSettings,
     is_initialized: bool,
-    //checkpoint_fn: NvDeviceDiagnosticCheckpointsFn,
+    /// 診断のチェックポイントの関数。検証レイヤーを使い、拡張機能がある時だけ読み込む。<br />
+    /// Functions of the diagnostic checkpoints, only loaded with the validation layers and the extension available.
+    checkpoints: Option<NvDeviceDiagnosticCheckpointsFn>,
+    /// 最後に記録したフレームのパスとコマンドバッファ。クラッシュレポートに書く。<br />
+    /// Passes and command buffers of the frame recorded last, written into crash reports.
+    frame_submission: Mutex<FrameSubmission>,
     /// 主なSSBOデータ。全部のモデルのデータはこの大きなSSBOに保存されます。<br />
     /// Primary SSBO data. Alll models' data are stored inside this large SSBO.
     primary_ssbo_data: PrimarySSBOData,
@@ -238,10 +442,11 @@ impl Graphics {
         window: std::rc::Weak<RefCell<winit::window::Window>>,
         camera: Rc<RefCell<Camera>>,
         resource_manager: ResourceManagerHandle,
+        settings: &Settings,
     ) -> anyhow::Result<Self> {
         let window_ptr = window.upgrade().expect("Failed to upgrade window handle.");
         let window_handle = window_ptr.borrow();
-        let debug = dotenv::var("DEBUG")?.parse::<bool>()?;
+        let debug = settings.debug;
         let entry = Entry::new()?;
         let enabled_layers = if debug {
             vec![CString::new("VK_LAYER_KHRONOS_validation")?]
@@ -252,14 +457,19 @@ impl Graphics {
             Initializer::create_instance(debug, &enabled_layers, &entry, &*window_handle)?;
         let surface_loader = Surface::new(&entry, &instance);
         let debug_messenger = if debug {
-            Initializer::create_debug_messenger(&instance, &entry)
+            Initializer::create_debug_messenger(&instance, &entry)?
         } else {
             DebugUtilsMessengerEXT::null()
         };
         let surface = Initializer::create_surface(&*window_handle, &entry, &instance)?;
         let physical_device = super::PhysicalDevice::new(&instance, &surface_loader, surface);
-        let (logical_device, graphics_queue, present_queue, compute_queue) =
-            Initializer::create_logical_device(&instance, &physical_device, &enabled_layers, debug);
+        let (logical_device, graphics_queue, present_queue, compute_queue, transfer_queue) =
+            Initializer::create_logical_device(
+                &instance,
+                &physical_device,
+                &enabled_layers,
+                debug,
+            )?;
         let allocator_info = vk_mem::AllocatorCreateInfo {
             physical_device: physical_device.physical_device,
             device: logical_device.clone(),
@@ -270,9 +480,10 @@ impl Graphics {
             heap_size_limits: None,
         };
         let allocator = vk_mem::Allocator::new(&allocator_info)
-            .expect("Failed to create VMA memory allocator.");
+            .map_err(|e| GraphicsError::allocation("create VMA memory allocator", e))?;
         let device = Arc::new(logical_device);
         let allocator = Arc::new(ShardedLock::new(allocator));
+        let graphics_settings = GraphicsSettings::from_settings(settings);
         let swapchain = Initializer::create_swapchain(
             &surface_loader,
             surface,
@@ -281,12 +492,10 @@ impl Graphics {
             &instance,
             Arc::downgrade(&device),
             Arc::downgrade(&allocator),
-        );
+            graphics_settings.present_mode,
+        )?;
 
-        let inflight_buffer_count = std::env::var("INFLIGHT_BUFFER_COUNT")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let inflight_buffer_count = settings.inflight_buffer_count;
         let mut frame_data = vec![];
         for _ in 0..inflight_buffer_count {
             let command_pool_create_info = CommandPoolCreateInfo::builder().queue_family_index(
@@ -298,11 +507,11 @@ impl Graphics {
             unsafe {
                 let command_pool = device
                     .create_command_pool(&command_pool_create_info, None)
-                    .expect("Failed to create command pool.");
+                    .or_graphics_error("create command pool")?;
                 let (fence, acquired_semaphore, completed_semaphore) =
-                    Initializer::create_sync_object(device.as_ref());
+                    Initializer::create_sync_object(device.as_ref())?;
                 let command_buffers =
-                    Initializer::allocate_command_buffers(device.as_ref(), command_pool, 1);
+                    Initializer::allocate_command_buffers(device.as_ref(), command_pool, 1)?;
                 frame_data.push(FrameData {
                     acquired_semaphore,
                     completed_semaphore,
@@ -313,9 +522,17 @@ impl Graphics {
             }
         }
 
+        let gpu_timer = GpuTimer::new(
+            Arc::downgrade(&device),
+            &instance,
+            &physical_device,
+            inflight_buffer_count,
+        );
+
         let cpu_count = num_cpus::get();
         let thread_pool = Arc::new(ThreadPool::new(
             cpu_count,
+            (cpu_count / STREAMING_THREAD_RATIO).max(1),
             inflight_buffer_count,
             device.as_ref(),
             physical_device
@@ -323,7 +540,11 @@ impl Graphics {
                 .graphics_family
                 .unwrap_or_default(),
         ));
-        let sample_count = Initializer::get_sample_count(&instance, &physical_device);
+        let supported_msaa_samples =
+            Initializer::get_supported_msaa_samples(&instance, &physical_device);
+        let sample_count = Initializer::get_sample_count(
+            graphics_settings.get_msaa_samples(&supported_msaa_samples),
+        );
         let depth_format = Initializer::get_depth_format(&instance, &physical_device);
         let depth_image = Initializer::create_depth_image(
             Arc::downgrade(&device),
@@ -333,7 +554,7 @@ impl Graphics {
             graphics_queue,
             sample_count,
             Arc::downgrade(&allocator),
-        );
+        )?;
 
         let msaa_image = Initializer::create_msaa_image(
             Arc::downgrade(&device),
@@ -343,34 +564,30 @@ impl Graphics {
             graphics_queue,
             sample_count,
             Arc::downgrade(&allocator),
-        );
+        )?;
 
         let view_projection = Initializer::create_view_projection(
             &*camera.borrow(),
             Arc::downgrade(&device),
             Arc::downgrade(&allocator),
         )?;
-        let light_x = std::env::var("LIGHT_X").unwrap().parse::<f32>().unwrap();
-        let light_z = std::env::var("LIGHT_Z").unwrap().parse::<f32>().unwrap();
-        let directional_light = Directional::new(
-            Vec4::new(1.0, 1.0, 1.0, 1.0),
-            Vec3A::new(light_x, 20000.0, light_z),
-            0.1,
-            0.5,
-        );
+        let light_position = (settings.light_x, settings.light_z);
+        let daylight =
+            TimeOfDay::new(settings.day_length).get_daylight(settings.light_x, settings.light_z);
         let directional = Initializer::create_directional_light(
-            &directional_light,
+            &Self::get_directional_light(&daylight, &FogSettings::default()),
             Arc::downgrade(&device),
             Arc::downgrade(&allocator),
         )?;
 
         let ssbo_descriptor_set_layout =
-            Initializer::create_ssbo_descriptor_set_layout(device.as_ref());
+            Initializer::create_ssbo_descriptor_set_layout(device.as_ref())?;
         let uniform_buffers = UniformBuffers::new(view_projection, directional);
         let mut pipeline = super::Pipeline::new(device.clone());
         let color_format = swapchain.format.format;
         pipeline.create_normal_renderpass(color_format, depth_format, sample_count);
-        pipeline.create_offscreen_renderpass(color_format, depth_format, sample_count)?;
+        pipeline.create_offscreen_renderpass(HDR_FORMAT, depth_format, sample_count)?;
+        pipeline.create_post_process_renderpass(HDR_FORMAT)?;
         let offscreen_renderpass = pipeline
             .render_pass
             .get(&RenderPassType::Offscreen)
@@ -378,7 +595,7 @@ impl Graphics {
             .expect("Failed to get offscreen renderpass.");
         let offscreen_pass = Self::create_offscreen_pass(
             Arc::downgrade(&device),
-            color_format,
+            HDR_FORMAT,
             depth_format,
             sample_count,
             swapchain.swapchain_images.len(),
@@ -391,16 +608,59 @@ impl Graphics {
         let descriptor_layout_cache = DescriptorLayoutCache::new(Arc::downgrade(&device));
         let descriptor_allocator = DescriptorAllocator::new(Arc::downgrade(&device));
 
-        let sky_color: Vec4 = Vec4::new(0.5, 0.5, 0.5, 1.0);
-        /*let checkpoint_fn = NvDeviceDiagnosticCheckpointsFn::load(|name| unsafe {
-            std::mem::transmute(instance.get_device_proc_addr(device.handle(), name.as_ptr()))
-        });*/
+        let post_process_settings = PostProcessSettings::default();
+        let (render_graph, pass_targets) = Self::create_render_graph(&post_process_settings)?;
+
+        let sky_color = daylight.sky_color;
+        let checkpoints = if debug && physical_device.supports_diagnostic_checkpoints {
+            log::info!("Diagnostic checkpoints are enabled.");
+            Some(NvDeviceDiagnosticCheckpointsFn::load(|name| unsafe {
+                std::mem::transmute(instance.get_device_proc_addr(device.handle(), name.as_ptr()))
+            }))
+        } else {
+            None
+        };
         let winit::dpi::PhysicalSize {
             width: window_width,
             height: window_height,
         } = window_handle.inner_size();
         drop(window_handle);
         drop(window_ptr);
+        let bindless_capacity = if physical_device.feature_support.supports_bindless() {
+            MAX_BINDLESS_TEXTURES.min(physical_device.max_update_after_bind_textures)
+        } else {
+            0
+        };
+        let texture_limits = TextureLimits {
+            max_per_stage: physical_device.max_textures_per_stage,
+            shader_array_length: Self::get_shader_texture_array_length()?,
+            bindless_capacity,
+        };
+        let graphics_queue = Arc::new(Mutex::new(graphics_queue));
+        let transfer_queue = TransferQueue::new(
+            Arc::downgrade(&device),
+            &physical_device,
+            graphics_queue.clone(),
+            transfer_queue,
+        )?;
+        let staging_ring = StagingRing::new(
+            Arc::downgrade(&device),
+            Arc::downgrade(&allocator),
+            STAGING_RING_SIZE,
+        )?;
+        let mesh_buffer_pools = [MeshPool::Static, MeshPool::Dynamic]
+            .iter()
+            .map(|pool| {
+                (
+                    *pool,
+                    Arc::new(Mutex::new(MeshBufferPool::new(
+                        *pool,
+                        Arc::downgrade(&device),
+                        Arc::downgrade(&allocator),
+                    ))),
+                )
+            })
+            .collect::<HashMap<_, _>>();
         Ok(Graphics {
             entry,
             instance: Arc::new(instance),
@@ -410,9 +670,12 @@ impl Graphics {
             physical_device,
             ui_manager: None,
             logical_device: device,
-            graphics_queue: Arc::new(Mutex::new(graphics_queue)),
+            graphics_queue,
             present_queue: Arc::new(Mutex::new(present_queue)),
             compute_queue: Arc::new(Mutex::new(compute_queue)),
+            transfer_queue: ManuallyDrop::new(Arc::new(transfer_queue)),
+            staging_ring: ManuallyDrop::new(Arc::new(staging_ring)),
+            mesh_buffer_pools,
             swapchain: ManuallyDrop::new(swapchain),
             depth_image: ManuallyDrop::new(depth_image),
             msaa_image: ManuallyDrop::new(msaa_image),
@@ -422,13 +685,14 @@ impl Graphics {
             camera,
             resource_manager,
             descriptor_pool: Arc::new(Mutex::new(DescriptorPool::null())),
-            descriptor_set: DescriptorSet::null(),
+            texture_descriptor_sets: Arc::new(TextureDescriptorSets::default()),
             pipeline: Arc::new(ShardedLock::new(ManuallyDrop::new(pipeline))),
             frame_buffers: vec![],
             sample_count,
             depth_format,
             allocator,
             thread_pool,
+            texture_streamer: Arc::new(TextureStreamer::new(bindless_capacity > 0)),
             ssbo_descriptor_set_layout,
             sky_color,
             is_initialized: false,
@@ -436,10 +700,44 @@ impl Graphics {
             current_frame: AtomicUsize::new(0),
             inflight_buffer_count,
             offscreen_pass: ManuallyDrop::new(offscreen_pass),
+            render_graph,
+            pass_targets,
+            post_process_settings,
+            render_features: RenderFeatures::ALL,
+            secondary_command_buffers: Mutex::new(vec![]),
+            secondary_windows: Mutex::new(vec![]),
+            deletion_queue: Mutex::new(DeletionQueue::new()),
+            boundary_warning: 0.0,
+            particle_renderer: Mutex::new(None),
+            particle_dispatches: Mutex::new(vec![]),
+            debug_draw_renderer: Mutex::new(None),
+            debug_lines: Mutex::new(vec![]),
+            occlusion_renderer: Mutex::new(None),
+            occlusion_culler: Mutex::new(OcclusionCuller::new(inflight_buffer_count)),
+            indirect_renderer: Mutex::new(None),
+            indirect_draws: Arc::new(Mutex::new(IndirectDrawList::new())),
+            record_generation: 0,
+            sprite_renderer: Mutex::new(None),
+            ripple_texture: Mutex::new(None),
+            sprites: Mutex::new(vec![]),
+            present_mode: graphics_settings.present_mode,
+            render_scale: graphics_settings.render_scale,
+            graphics_settings,
+            supported_msaa_samples,
+            post_process_targets: None,
+            gpu_timer: Mutex::new(ManuallyDrop::new(gpu_timer)),
+            frame_timings: Mutex::new(FrameTimings::default()),
+            bindless_capacity,
+            bound_texture_count: AtomicUsize::new(0),
+            texture_limits,
+            light_position,
+            daylight,
+            fog_settings: FogSettings::default(),
             window,
             window_width,
             window_height,
-            //checkpoint_fn,
+            checkpoints,
+            frame_submission: Mutex::new(FrameSubmission::default()),
             descriptor_allocator: Arc::new(Mutex::new(ManuallyDrop::new(descriptor_allocator))),
             descriptor_layout_cache: Arc::new(Mutex::new(ManuallyDrop::new(
                 descriptor_layout_cache,
@@ -463,122 +761,157 @@ impl Graphics {
         indices: Vec<u32>,
         command_pool: Arc<Mutex<ash::vk::CommandPool>>,
     ) -> anyhow::Result<(super::Buffer, super::Buffer)> {
-        use crossbeam::channel::*;
-
-        let device: Arc<ash::Device>;
-        let allocator: Arc<ShardedLock<vk_mem::Allocator>>;
-        {
-            let lock = graphics.read();
-            device = lock.logical_device.clone();
-            allocator = lock.allocator.clone();
-            drop(lock);
-        }
+        let lock = graphics.read();
+        let device = Arc::downgrade(&lock.logical_device);
+        let allocator = Arc::downgrade(&lock.allocator);
+        let transfer_queue = lock.get_transfer_queue();
+        let staging_ring = lock.get_staging_ring();
+        drop(lock);
         let vertex_buffer_size =
             DeviceSize::try_from(std::mem::size_of::<VertexType>() * vertices.len())?;
         let index_buffer_size = DeviceSize::try_from(std::mem::size_of::<u32>() * indices.len())?;
-        let cmd_buffer = get_single_time_command_buffer(device.as_ref(), *command_pool.lock());
-
-        let device_handle1 = device.clone();
-        let allocator_handle1 = allocator.clone();
-        let (vertices_send, vertices_recv) = bounded(5);
-        rayon::spawn(move || {
-            let device_handle = device_handle1;
-            let allocator_handle = allocator_handle1;
-            let mut vertex_staging = super::Buffer::new(
-                Arc::downgrade(&device_handle),
+
+        let vertex_staging = staging_ring.write(&vertices)?;
+        let index_staging = staging_ring.write(&indices)?;
+        let vertex_buffer = super::Buffer::new(
+            device.clone(),
+            vertex_buffer_size,
+            BufferUsageFlags::VERTEX_BUFFER | BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            allocator.clone(),
+        )?;
+        let index_buffer = super::Buffer::new(
+            device,
+            index_buffer_size,
+            BufferUsageFlags::INDEX_BUFFER | BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            allocator,
+        )?;
+        transfer_queue.upload_buffers(
+            &command_pool,
+            &[
+                BufferUpload {
+                    source: &vertex_staging,
+                    destination: &vertex_buffer,
+                    dst_offset: 0,
+                    size: vertex_buffer_size,
+                    dst_access: AccessFlags::VERTEX_ATTRIBUTE_READ,
+                    dst_stage: PipelineStageFlags::VERTEX_INPUT,
+                },
+                BufferUpload {
+                    source: &index_staging,
+                    destination: &index_buffer,
+                    dst_offset: 0,
+                    size: index_buffer_size,
+                    dst_access: AccessFlags::INDEX_READ,
+                    dst_stage: PipelineStageFlags::VERTEX_INPUT,
+                },
+            ],
+        )?;
+        Ok((vertex_buffer, index_buffer))
+    }
+
+    /// メッシュの頂点とインデックスを共有のプールの範囲に書き込む。自由関数。<br />
+    /// 静的なプールには転送キューでアップロードし、動的なプールにはマップしたメモリーに直接コピーする。<br />
+    /// プールのブロックに収まらない大きなメッシュには、専用のバッファを作る。<br />
+    /// Write a mesh's vertices and indices into ranges of a shared pool. Free function.<br />
+    /// The static pool is uploaded to on the transfer queue, and the dynamic pool is copied into through mapped memory.<br />
+    /// Meshes too large for a block of the pool get dedicated buffers.
+    pub fn create_mesh_buffers<VertexType: 'static + Send + Sync>(
+        graphics: Arc<RwLock<ManuallyDrop<Self>>>,
+        vertices: Vec<VertexType>,
+        indices: Vec<u32>,
+        command_pool: Arc<Mutex<ash::vk::CommandPool>>,
+        pool: MeshPool,
+    ) -> anyhow::Result<MeshBuffers> {
+        let lock = graphics.read();
+        let buffer_pool = lock.mesh_buffer_pools.get(&pool).cloned();
+        let transfer_queue = lock.get_transfer_queue();
+        let staging_ring = lock.get_staging_ring();
+        drop(lock);
+        let vertex_stride = DeviceSize::try_from(std::mem::size_of::<VertexType>())?;
+        let vertex_buffer_size = vertex_stride * DeviceSize::try_from(vertices.len())?;
+        let index_buffer_size = DeviceSize::try_from(std::mem::size_of::<u32>() * indices.len())?;
+        let allocation = match buffer_pool {
+            Some(buffer_pool) => MeshBufferPool::allocate(
+                &buffer_pool,
                 vertex_buffer_size,
-                BufferUsageFlags::TRANSFER_SRC,
-                MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE,
-                Arc::downgrade(&allocator_handle),
-            );
-            let vertex_mapped = vertex_staging.map_memory(vertex_buffer_size, 0);
-            unsafe {
+                vertex_stride,
+                index_buffer_size,
+            )?,
+            None => None,
+        };
+        let allocation = match allocation {
+            Some(allocation) => allocation,
+            None => {
+                let (vertex_buffer, index_buffer) = Self::create_vertex_and_index_buffer(
+                    graphics,
+                    vertices,
+                    indices,
+                    command_pool,
+                )?;
+                return Ok(MeshBuffers::Dedicated(vertex_buffer, index_buffer));
+            }
+        };
+        let block = allocation.get_block();
+        let range = *allocation.get_range();
+        match pool {
+            MeshPool::Static => {
+                let vertex_staging = staging_ring.write(&vertices)?;
+                let index_staging = staging_ring.write(&indices)?;
+                transfer_queue.upload_buffers(
+                    &command_pool,
+                    &[
+                        BufferUpload {
+                            source: &vertex_staging,
+                            destination: &block.vertex_buffer,
+                            dst_offset: range.vertices.offset,
+                            size: vertex_buffer_size,
+                            dst_access: AccessFlags::VERTEX_ATTRIBUTE_READ,
+                            dst_stage: PipelineStageFlags::VERTEX_INPUT,
+                        },
+                        BufferUpload {
+                            source: &index_staging,
+                            destination: &block.index_buffer,
+                            dst_offset: range.indices.offset,
+                            size: index_buffer_size,
+                            dst_access: AccessFlags::INDEX_READ,
+                            dst_stage: PipelineStageFlags::VERTEX_INPUT,
+                        },
+                    ],
+                )?;
+            }
+            MeshPool::Dynamic => unsafe {
                 std::ptr::copy_nonoverlapping(
-                    vertices.as_ptr() as *const c_void,
-                    vertex_mapped,
+                    vertices.as_ptr() as *const u8,
+                    (block.vertex_buffer.mapped_memory as *mut u8)
+                        .add(range.vertices.offset as usize),
                     vertex_buffer_size as usize,
                 );
-            }
-            let vertex_buffer = super::Buffer::new(
-                Arc::downgrade(&device_handle),
-                vertex_buffer_size,
-                BufferUsageFlags::VERTEX_BUFFER | BufferUsageFlags::TRANSFER_DST,
-                MemoryPropertyFlags::DEVICE_LOCAL,
-                Arc::downgrade(&allocator_handle),
-            );
-            vertices_send
-                .send((vertex_staging, vertex_buffer))
-                .expect("Failed to create vertex buffer.");
-        });
-
-        let device_handle2 = device.clone();
-        let (indices_send, indices_recv) = bounded(5);
-        rayon::spawn(move || {
-            let device_handle = device_handle2;
-            let allocator_handle = allocator;
-            let mut index_staging = super::Buffer::new(
-                Arc::downgrade(&device_handle),
-                index_buffer_size,
-                BufferUsageFlags::TRANSFER_SRC,
-                MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE,
-                Arc::downgrade(&allocator_handle),
-            );
-            let index_mapped = index_staging.map_memory(index_buffer_size, 0);
-            unsafe {
                 std::ptr::copy_nonoverlapping(
-                    indices.as_ptr() as *const c_void,
-                    index_mapped,
+                    indices.as_ptr() as *const u8,
+                    (block.index_buffer.mapped_memory as *mut u8)
+                        .add(range.indices.offset as usize),
                     index_buffer_size as usize,
                 );
-            }
-            let index_buffer = super::Buffer::new(
-                Arc::downgrade(&device_handle),
-                index_buffer_size,
-                BufferUsageFlags::INDEX_BUFFER | BufferUsageFlags::TRANSFER_DST,
-                MemoryPropertyFlags::DEVICE_LOCAL,
-                Arc::downgrade(&allocator_handle),
-            );
-            indices_send
-                .send((index_staging, index_buffer))
-                .expect("Failed to create index buffer.");
-        });
-
-        let (vertex_staging, vertex_buffer) = vertices_recv.recv()?;
-        let (index_staging, index_buffer) = indices_recv.recv()?;
-        let graphics_lock = graphics.read();
-        let pool_lock = command_pool.lock();
-        vertex_buffer.copy_buffer(
-            &vertex_staging,
-            vertex_buffer_size,
-            *pool_lock,
-            *graphics_lock.graphics_queue.lock(),
-            Some(cmd_buffer),
-        );
-        index_buffer.copy_buffer(
-            &index_staging,
-            index_buffer_size,
-            *pool_lock,
-            *graphics_lock.graphics_queue.lock(),
-            Some(cmd_buffer),
-        );
-        end_one_time_command_buffer(
-            cmd_buffer,
-            device.as_ref(),
-            *pool_lock,
-            *graphics_lock.graphics_queue.lock(),
-        );
-        Ok((vertex_buffer, index_buffer))
+            },
+        }
+        Ok(MeshBuffers::Pooled(allocation))
     }
 
-    /// GLTFモデルからテクスチャを生成する。自由関数。<br />
-    /// Create a texture from a GLTF model. Free function.
+    /// GLTFモデルからテクスチャを生成する。`is_streamed`ならRGBAのテクスチャは高いミップを後からストリーミングする。自由関数。<br />
+    /// Create a texture from a GLTF model. With `is_streamed` the high mips of RGBA textures are streamed in later. Free function.
     pub fn create_gltf_textures(
         images: Vec<gltf::image::Data>,
         graphics: Arc<RwLock<ManuallyDrop<Self>>>,
         command_pool: Arc<Mutex<CommandPool>>,
+        is_streamed: bool,
     ) -> anyhow::Result<(Vec<Arc<ShardedLock<super::Image>>>, usize)> {
         let mut textures = vec![];
         let mut texture_handles = vec![];
+        let mut sources = vec![];
+        let texture_streamer = graphics.read().texture_streamer.clone();
+        let is_streamed = is_streamed && texture_streamer.is_enabled();
         use gltf::image::Format;
         for image in images.iter() {
             let buffer_size = image.width * image.height * 4;
@@ -586,32 +919,54 @@ impl Graphics {
             let graphics_clone = graphics.clone();
             let width = image.width;
             let height = image.height;
-            let format = image.format;
-            let pixels = match image.format {
+            let format = match image.format {
+                Format::B8G8R8 => Format::B8G8R8A8,
+                Format::R8G8B8 => Format::R8G8B8A8,
+                format => format,
+            };
+            let mut pixels = match image.format {
                 Format::R8G8B8 | Format::B8G8R8 => {
                     interpolate_alpha(image.pixels.to_vec(), width, height, buffer_size as usize)
                 }
                 _ => image.pixels.to_vec(),
             };
+            // 縮小できるのは8ビットのRGBAだけなので、他の形式は全てのミップを置く。
+            // Only 8-bit RGBA can be downscaled, so other formats keep every mip.
+            let source = if is_streamed && matches!(format, Format::R8G8B8A8 | Format::B8G8R8A8) {
+                Some(StreamedTextureSource {
+                    source: TextureSource::Pixels {
+                        pixels: Arc::new(std::mem::take(&mut pixels)),
+                        format: ImageFormat::GltfFormat(format),
+                    },
+                    width,
+                    height,
+                    sampler_address_mode: SamplerAddressMode::REPEAT,
+                })
+            } else {
+                None
+            };
+            let job_source = source.clone();
+            sources.push(source);
 
             use crossbeam::channel::*;
 
             let (texture_send, texture_recv) = bounded(5);
             rayon::spawn(move || {
-                let result = Initializer::create_image_from_raw(
-                    pixels,
-                    buffer_size as u64,
-                    width,
-                    height,
-                    match format {
-                        Format::B8G8R8 => ImageFormat::GltfFormat(Format::B8G8R8A8),
-                        Format::R8G8B8 => ImageFormat::GltfFormat(Format::R8G8B8A8),
-                        _ => ImageFormat::GltfFormat(format),
-                    },
-                    graphics_clone,
-                    pool,
-                    SamplerAddressMode::REPEAT,
-                );
+                let result = match job_source {
+                    Some(source) => {
+                        source.create_image(source.get_low_res_mip(), graphics_clone, pool)
+                    }
+                    None => Initializer::create_image_from_raw(
+                        pixels,
+                        buffer_size as u64,
+                        width,
+                        height,
+                        ImageFormat::GltfFormat(format),
+                        graphics_clone,
+                        pool,
+                        SamplerAddressMode::REPEAT,
+                    ),
+                };
                 texture_send
                     .send(result)
                     .expect("Failed to send texture result.");
@@ -633,6 +988,12 @@ impl Graphics {
                     .into_iter()
                     .map(|img| rm_lock.add_texture(img))
                     .collect::<Vec<_>>();
+                drop(rm_lock);
+                for (index, source) in sources.into_iter().enumerate() {
+                    if let Some(source) = source {
+                        texture_streamer.register(texture_index_offset + index, source);
+                    }
+                }
                 log::info!("Model texture count: {}", textures_ptrs.len());
                 Ok((textures_ptrs, texture_index_offset))
             }
@@ -642,6 +1003,84 @@ impl Graphics {
         }
     }
 
+    /// スプライトのアトラスをテクスチャにしてアップロードし、画面に重ねるスプライトに使う。自由関数。<br />
+    /// Upload a sprite atlas as a texture and use it for the sprites overlaid on the screen. Free function.
+    pub fn create_sprite_atlas(
+        graphics: Arc<RwLock<ManuallyDrop<Self>>>,
+        atlas: &SpriteAtlas,
+    ) -> anyhow::Result<()> {
+        let command_pool = graphics.read().get_idle_command_pool();
+        let pixels = atlas.get_pixels().to_vec();
+        let buffer_size = pixels.len() as DeviceSize;
+        let image = Initializer::create_image_from_raw(
+            pixels,
+            buffer_size,
+            atlas.get_width(),
+            atlas.get_height(),
+            ImageFormat::ColorType(image::ColorType::Rgba8),
+            graphics.clone(),
+            command_pool,
+            SamplerAddressMode::CLAMP_TO_EDGE,
+        )?;
+        graphics.write().set_sprite_atlas(image)
+    }
+
+    /// 水面の波紋のテクスチャと、テクスチャ配列の中のインデックス。最初に要求された時に平らな水面で作る。自由関数。<br />
+    /// The texture of the ripples on the water surface and its index in the texture array. Created with flat water the first time it's requested. Free function.
+    pub fn get_ripple_texture(
+        graphics: Arc<RwLock<ManuallyDrop<Self>>>,
+        command_pool: Arc<Mutex<CommandPool>>,
+    ) -> anyhow::Result<(Arc<ShardedLock<super::Image>>, usize)> {
+        if let Some(ripple_texture) = graphics.read().ripple_texture.lock().as_ref() {
+            return Ok((ripple_texture.texture.clone(), ripple_texture.texture_index));
+        }
+        let resolution = RIPPLE_RESOLUTION as u32;
+        let pixels = vec![128, 128, 128, 255].repeat(RIPPLE_RESOLUTION * RIPPLE_RESOLUTION);
+        let buffer_size = pixels.len() as DeviceSize;
+        let image = Initializer::create_image_from_raw(
+            pixels,
+            buffer_size,
+            resolution,
+            resolution,
+            ImageFormat::ColorType(image::ColorType::Rgba8),
+            graphics.clone(),
+            command_pool,
+            SamplerAddressMode::CLAMP_TO_EDGE,
+        )?;
+        let lock = graphics.read();
+        let mut ripple_texture = lock.ripple_texture.lock();
+        // 他のスレッドが先に作っていれば、作ったイメージはテクスチャの一覧に加えずに捨てる。
+        // If another thread created one first, the created image is dropped without adding it to the textures.
+        if let Some(ripple_texture) = ripple_texture.as_ref() {
+            return Ok((ripple_texture.texture.clone(), ripple_texture.texture_index));
+        }
+        let resource_manager = lock
+            .resource_manager
+            .upgrade()
+            .with_context(|| "Failed to upgrade resource manager.")?;
+        let (texture, texture_index) = {
+            let mut resource_lock = resource_manager.write();
+            let texture = resource_lock.add_texture(image);
+            (texture, resource_lock.get_texture_count() - 1)
+        };
+        *ripple_texture = Some(RippleTexture::new(
+            texture.clone(),
+            texture_index,
+            lock.logical_device.clone(),
+            Arc::downgrade(&lock.allocator),
+            lock.inflight_buffer_count,
+        ));
+        Ok((texture, texture_index))
+    }
+
+    /// 次のフレームで波紋のテクスチャにアップロードするピクセルを設定する。テクスチャがまだなければ何もしない。<br />
+    /// Set the pixels uploaded to the ripple texture in the next frame. Nothing happens if the texture doesn't exist yet.
+    pub fn set_ripple_pixels(&self, pixels: Vec<u8>) {
+        if let Some(ripple_texture) = self.ripple_texture.lock().as_mut() {
+            ripple_texture.set_pixels(pixels);
+        }
+    }
+
     /// ファイルからイメージを生成する。自由関数。<br />
     /// Create an Image from a file. Free function.
     pub fn create_image_from_file(
@@ -649,8 +1088,15 @@ impl Graphics {
         graphics: Arc<RwLock<ManuallyDrop<Self>>>,
         command_pool: Arc<Mutex<CommandPool>>,
         sampler_address_mode: SamplerAddressMode,
+        is_streamed: bool,
     ) -> anyhow::Result<(Arc<ShardedLock<super::Image>>, usize)> {
-        Initializer::create_image_from_file(file_name, graphics, command_pool, sampler_address_mode)
+        Initializer::create_image_from_file(
+            file_name,
+            graphics,
+            command_pool,
+            sampler_address_mode,
+            is_streamed,
+        )
     }
 
     /// マルチスレッド描画するためのセカンダリーコマンドバッファを生成する。自由関数。<br />
@@ -679,6 +1125,76 @@ impl Graphics {
         }
     }
 
+    /// 取り除いたモデルのセカンダリーコマンドバッファを、それを確保したコマンドプールに返す。<br />
+    /// Free secondary command buffers of removed models back to the command pools they were allocated from.
+    pub fn free_secondary_command_buffers(
+        &self,
+        command_buffers: ReleasedCommandBuffers<CommandBuffer>,
+    ) {
+        for (command_pool, command_buffer) in command_buffers.into_iter() {
+            // 他のスレッドが同じプールで記録しているかもしれないので、プールをロックしてから解放する。
+            // Another thread may be recording with the same pool, so lock the pool before freeing.
+            let pool_lock = command_pool.lock();
+            unsafe {
+                self.logical_device
+                    .free_command_buffers(*pool_lock, &[command_buffer]);
+            }
+        }
+    }
+
+    /// リソースを壊すのを、今のフレームのフェンスが通知するまで遅らせる。GPUを止めずに解放できる。<br />
+    /// Delay destroying a resource until the fence of the current frame signals, so it's released without stalling the GPU.
+    pub fn defer_deletion(&self, deletion: DeferredDeletion) {
+        let frame = self.current_frame.load(Ordering::SeqCst) as u64;
+        self.deletion_queue.lock().push(frame, deletion);
+    }
+
+    /// フェンスを待った後に呼び、使い終わったフレームで予約されたリソースを壊す。<br />
+    /// 今のフレームの枠のフェンスは、一周前のフレームとそれより前に送った全てのフレームが終わったことを示す。<br />
+    /// Called after waiting for the fence, destroying resources scheduled in frames that are finished.<br />
+    /// The fence of the current frame's slot means the frame one cycle ago, and every frame submitted before it, has finished.
+    fn destroy_completed_deletions(&self) {
+        let frame = self.current_frame.load(Ordering::SeqCst) as u64;
+        let inflight_buffer_count = self.inflight_buffer_count as u64;
+        if frame < inflight_buffer_count {
+            return;
+        }
+        let completed = self
+            .deletion_queue
+            .lock()
+            .collect_completed(frame - inflight_buffer_count);
+        self.destroy_deletions(completed);
+    }
+
+    /// GPUが止まっている時に、待っている全てのリソースを壊す。<br />
+    /// Destroy every waiting resource while the GPU is idle.
+    fn destroy_all_deletions(&self) {
+        let deletions = self.deletion_queue.lock().drain();
+        self.destroy_deletions(deletions);
+    }
+
+    fn destroy_deletions(&self, deletions: Vec<DeferredDeletion>) {
+        for deletion in deletions.into_iter() {
+            match deletion {
+                DeferredDeletion::Resource(mut resource) => {
+                    if !resource.is_disposed() {
+                        resource.dispose();
+                    }
+                }
+                DeferredDeletion::Renderable(model) => {
+                    let mut model_lock = model.lock();
+                    let command_buffers = model_lock.take_command_buffers();
+                    model_lock.dispose();
+                    drop(model_lock);
+                    self.free_secondary_command_buffers(command_buffers);
+                }
+                DeferredDeletion::CommandBuffers(command_buffers) => {
+                    self.free_secondary_command_buffers(command_buffers);
+                }
+            }
+        }
+    }
+
     /// コマンドプールを取得する。<br />
     /// Vulkanの仕様によって、コマンドバッファを実行するとき絶対そのコマンドバッファを生成するコマンドプールを使わないといけません。<br />
     /// Get command pool. According to Vulkan's design, when executing a command buffer, it must use the same command pool that creates such command buffer.
@@ -705,15 +1221,30 @@ impl Graphics {
         (pool_handle, command_buffer)
     }
 
-    /// スレッドプールから現在タスクのないスレッドのコマンドプールを取得する。<br />
-    /// Get the command pool of an idling thread that doesn't have any task for the time being from the thread pool.
+    /// 読み込みに使うコマンドプールをスレッドプールから取得する。読み込みのスレッドの上では、そのスレッドのもの。<br />
+    /// Get a command pool for loading from the thread pool. On a streaming thread, it's that thread's own.
     pub fn get_idle_command_pool(&self) -> Arc<Mutex<CommandPool>> {
         self.thread_pool.get_idle_command_pool()
     }
 
+    /// アップロードに使うキューを取得する。グラフィックスのロックを外してから待てるように、Arcで返す。<br />
+    /// Get the queue used for uploads, returned as an Arc so that the graphics lock can be released before waiting.
+    pub fn get_transfer_queue(&self) -> Arc<TransferQueue> {
+        Arc::clone(&*self.transfer_queue)
+    }
+
+    /// ステージングリングを取得する。<br />
+    /// Get the staging ring.
+    pub fn get_staging_ring(&self) -> Arc<StagingRing> {
+        Arc::clone(&*self.staging_ring)
+    }
+
     /// グラフィックパイプラインを初期化。<br />
     /// Initialize graphic pipelines.
     pub fn initialize_pipelines(&mut self) -> anyhow::Result<()> {
+        // 作り直したパイプラインは古いものと同じハンドルになることがあるので、世代を変えて全て記録し直す。
+        // Rebuilt pipelines may get the same handles as the old ones, so the generation changes to record everything again.
+        self.record_generation += 1;
         //self.create_descriptor_set_layout()?;
         //self.allocate_descriptor_set()?;
         self.create_graphics_pipeline(ShaderType::BasicShader)?;
@@ -722,6 +1253,37 @@ impl Graphics {
         self.create_graphics_pipeline(ShaderType::Terrain)?;
         self.create_graphics_pipeline(ShaderType::Water)?;
         self.create_graphics_pipeline(ShaderType::InstanceDraw)?;
+        if let Err(e) = self.create_indirect_pipelines() {
+            log::warn!(
+                "Failed to create indirect draw pipelines. Models are drawn one by one: {}",
+                e
+            );
+        }
+        self.create_post_process_pipelines()?;
+        if let Err(e) = self.create_particle_pipelines() {
+            log::warn!(
+                "Failed to create particle pipelines. Particles are disabled: {}",
+                e
+            );
+        }
+        if let Err(e) = self.create_debug_line_pipeline() {
+            log::warn!(
+                "Failed to create the debug line pipeline. Debug lines are disabled: {}",
+                e
+            );
+        }
+        if let Err(e) = self.create_occlusion_pipeline() {
+            log::warn!(
+                "Failed to create the occlusion pipeline. Occlusion culling is disabled: {}",
+                e
+            );
+        }
+        if let Err(e) = self.create_sprite_pipeline() {
+            log::warn!(
+                "Failed to create the sprite pipeline. Sprites are disabled: {}",
+                e
+            );
+        }
         let width = self.swapchain.extent.width;
         let height = self.swapchain.extent.height;
         self.frame_buffers = Self::create_frame_buffers(
@@ -737,6 +1299,7 @@ impl Graphics {
             &self.swapchain,
             &self.depth_image,
             &self.msaa_image,
+            self.sample_count,
             self.logical_device.as_ref(),
         );
         self.is_initialized = true;
@@ -756,16 +1319,8 @@ impl Graphics {
                 Arc::downgrade(&self.logical_device),
                 Arc::downgrade(&self.allocator),
             )?;
-            let light_x = std::env::var("LIGHT_X").unwrap().parse::<f32>().unwrap();
-            let light_z = std::env::var("LIGHT_Z").unwrap().parse::<f32>().unwrap();
-            let directional_light = Directional::new(
-                Vec4::new(1.0, 1.0, 1.0, 1.0),
-                Vec3A::new(light_x, 20000.0, light_z),
-                0.1,
-                0.5,
-            );
             let directional_light = Initializer::create_directional_light(
-                &directional_light,
+                &Self::get_directional_light(&self.daylight, &self.fog_settings),
                 Arc::downgrade(&self.logical_device),
                 Arc::downgrade(&self.allocator),
             )?;
@@ -788,6 +1343,7 @@ impl Graphics {
         if self.is_initialized {
             unsafe {
                 self.wait_idle();
+                self.destroy_all_deletions();
                 self.set_disposing();
                 if let Some(ui) = self.ui_manager.as_ref() {
                     let ui_manager = ui.upgrade().expect("Failed to upgrade UI handle.");
@@ -805,6 +1361,7 @@ impl Graphics {
         self.camera
             .borrow_mut()
             .update_window(width as f64, height as f64);
+        self.sample_count = self.get_requested_sample_count();
         let window = self
             .window
             .upgrade()
@@ -818,7 +1375,9 @@ impl Graphics {
             &*self.instance,
             Arc::downgrade(&self.logical_device),
             Arc::downgrade(&self.allocator),
-        ));
+            self.graphics_settings.present_mode,
+        )?);
+        self.present_mode = self.graphics_settings.present_mode;
         self.depth_image = ManuallyDrop::new(Initializer::create_depth_image(
             Arc::downgrade(&self.logical_device),
             self.depth_format,
@@ -827,7 +1386,7 @@ impl Graphics {
             *self.graphics_queue.lock(),
             self.sample_count,
             Arc::downgrade(&self.allocator),
-        ));
+        )?);
         self.msaa_image = ManuallyDrop::new(Initializer::create_msaa_image(
             Arc::downgrade(&self.logical_device),
             self.swapchain.format.format,
@@ -836,7 +1395,7 @@ impl Graphics {
             *self.graphics_queue.lock(),
             self.sample_count,
             Arc::downgrade(&self.allocator),
-        ));
+        )?);
         self.pipeline = Arc::new(ShardedLock::new(ManuallyDrop::new(super::Pipeline::new(
             self.logical_device.clone(),
         ))));
@@ -851,10 +1410,11 @@ impl Graphics {
                 self.sample_count,
             );
             pipeline_handle.create_offscreen_renderpass(
-                self.swapchain.format.format,
+                HDR_FORMAT,
                 self.depth_format,
                 self.sample_count,
             )?;
+            pipeline_handle.create_post_process_renderpass(HDR_FORMAT)?;
         }
         let offscreen_renderpass = self
             .pipeline
@@ -866,7 +1426,7 @@ impl Graphics {
             .expect("Failed to get offscreen renderpass.");
         self.offscreen_pass = ManuallyDrop::new(Self::create_offscreen_pass(
             Arc::downgrade(&self.logical_device),
-            self.swapchain.format.format,
+            HDR_FORMAT,
             self.depth_format,
             self.sample_count,
             self.swapchain.swapchain_images.len(),
@@ -880,6 +1440,9 @@ impl Graphics {
         if let Some(ui) = self.ui_manager.as_ref() {
             let ui_manager = ui.upgrade().expect("Failed to upgrade UI handle.");
             let mut borrowed = ui_manager.borrow_mut();
+            unsafe {
+                borrowed.set_sample_count(self.sample_count);
+            }
             borrowed.set_initialized();
         }
         Ok(())
@@ -893,13 +1456,23 @@ impl Graphics {
         }
         unsafe {
             let (current_frame, frame_index) = self.get_current_frame();
-            let fences = vec![current_frame.fence];
+            // 毎フレーム通る道なので、短い配列はヒープではなくスタックに置く。
+            // This runs every frame, so short arrays live on the stack instead of the heap.
+            let fences = [current_frame.fence];
             self.logical_device
-                .wait_for_fences(fences.as_slice(), true, 1_000_000_000)
-                .expect("Failed to wait for fences.");
+                .wait_for_fences(&fences[0..], true, 1_000_000_000)
+                .or_graphics_error("wait for fences")?;
             self.logical_device
-                .reset_fences(fences.as_slice())
-                .expect("Failed to reset fences.");
+                .reset_fences(&fences[0..])
+                .or_graphics_error("reset fences")?;
+            self.destroy_completed_deletions();
+            let gpu_passes = self.gpu_timer.lock().read_results(frame_index);
+            if let Some(renderer) = self.occlusion_renderer.lock().as_mut() {
+                let samples = renderer.read_results(frame_index);
+                self.occlusion_culler
+                    .lock()
+                    .apply_results(frame_index, samples.as_slice());
+            }
             let result: VkResult<(u32, bool)>;
             {
                 let swapchain_loader = &self.swapchain.swapchain_loader;
@@ -910,110 +1483,130 @@ impl Graphics {
                     Fence::null(),
                 );
             }
-            let mut image_index = 0_u32;
-            match result {
-                Ok(index) => {
-                    image_index = index.0;
-                }
-                Err(e) => match e {
-                    ash::vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        println!("Device out of date. (Acquiring image.)");
-                        return Err(anyhow::anyhow!("Swapchain is out of date or suboptimal."));
-                    }
-                    _ => (),
-                },
-            }
+            let image_index = result
+                .or_graphics_error("acquire the next swapchain image")?
+                .0;
             self.logical_device
-                .reset_command_pool(current_frame.command_pool, CommandPoolResetFlags::empty())?;
+                .reset_command_pool(current_frame.command_pool, CommandPoolResetFlags::empty())
+                .or_graphics_error("reset the command pool")?;
 
             let extent = self.swapchain.extent;
-            let viewports = vec![Viewport::builder()
-                .width(extent.width as f32)
-                .height(extent.height as f32)
-                .x(0.0)
-                .y(0.0)
-                .min_depth(0.0)
-                .max_depth(1.0)
-                .build()];
+            let viewports = [Self::get_viewport(extent)];
 
+            self.append_texture_descriptors();
+            self.apply_texture_swaps(frame_index)?;
+            let record_start = Instant::now();
             self.begin_draw(
                 self.frame_buffers[image_index as usize],
                 current_frame,
                 frame_index,
-                viewports.as_slice(),
                 renderables,
             )?;
+            let record_time = record_start.elapsed();
+            let submit_start = Instant::now();
 
-            let wait_stages = vec![PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+            let particle_semaphores = self.simulate_particles(frame_index)?;
+            let wait_stages = [
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                PipelineStageFlags::VERTEX_SHADER,
+            ];
 
-            let command_buffers = vec![current_frame.main_command_buffer];
-            let mut complete_semaphores = vec![current_frame.completed_semaphore];
-            let acquired_semaphores = vec![current_frame.acquired_semaphore];
-            let submit_info = vec![SubmitInfo::builder()
-                .command_buffers(command_buffers.as_slice())
-                .signal_semaphores(complete_semaphores.as_slice())
-                .wait_dst_stage_mask(wait_stages.as_slice())
-                .wait_semaphores(acquired_semaphores.as_slice())
+            let command_buffers = [current_frame.main_command_buffer];
+            let mut complete_semaphores = [current_frame.completed_semaphore];
+            // 粒子を描くフレームは、粒子の計算を待ち、描き終えたことを次の計算に知らせる。
+            // Frames drawing particles wait for their simulation and tell the next simulation when drawing is done.
+            let (wait_semaphores, signal_semaphores, semaphore_count) = match particle_semaphores {
+                Some((simulated, drawn)) => (
+                    [current_frame.acquired_semaphore, simulated],
+                    [current_frame.completed_semaphore, drawn],
+                    2,
+                ),
+                None => (
+                    [current_frame.acquired_semaphore, Semaphore::null()],
+                    [current_frame.completed_semaphore, Semaphore::null()],
+                    1,
+                ),
+            };
+            let submit_info = [SubmitInfo::builder()
+                .command_buffers(&command_buffers[0..])
+                .signal_semaphores(&signal_semaphores[0..semaphore_count])
+                .wait_dst_stage_mask(&wait_stages[0..semaphore_count])
+                .wait_semaphores(&wait_semaphores[0..semaphore_count])
                 .build()];
 
             self.logical_device
-                .queue_submit(
-                    *self.graphics_queue.lock(),
-                    submit_info.as_slice(),
-                    fences[0],
-                )
-                .expect("Failed to submit the queue.");
-
-            let ui_overlay_finished = if let Some(ui) = self.ui_manager.as_ref() {
-                let ui_manager = ui.upgrade().expect("Failed to upgrade UI handle.");
+                .queue_submit(*self.graphics_queue.lock(), &submit_info[0..], fences[0])
+                .or_graphics_error("submit the queue")?;
+            self.particle_dispatches.lock().clear();
+            self.debug_lines.lock().clear();
+            self.sprites.lock().clear();
+
+            let ui_manager = self
+                .ui_manager
+                .as_ref()
+                .filter(|_| self.render_features.contains(RenderFeatures::UI_LAYERS));
+            let ui_overlay_finished = if let Some(ui) = ui_manager {
+                let ui_manager = ui
+                    .upgrade()
+                    .ok_or(GraphicsError::Unavailable("UI system"))?;
                 let mut borrowed = ui_manager.borrow_mut();
                 Some(borrowed.render(
-                    self.frame_buffers[image_index as usize],
-                    viewports[0],
+                    DrawTarget {
+                        framebuffer: self.frame_buffers[image_index as usize],
+                        viewport: viewports[0],
+                        wait_semaphore: complete_semaphores[0],
+                    },
                     nuklear::Vec2 {
                         x: (self.window_width / extent.width) as f32,
                         y: (self.window_height / extent.height) as f32,
                     },
-                    complete_semaphores[0],
                 ))
             } else {
                 None
             };
 
             if let Some(semaphore) = ui_overlay_finished {
-                complete_semaphores = vec![semaphore];
+                complete_semaphores = [semaphore];
             }
-            let image_indices = vec![image_index];
-            let swapchain = vec![self.swapchain.swapchain];
+            // 別のウィンドウが画像を読み終わるまで、メインの画像をプレゼントしない。
+            // Don't present the main image until the other windows have finished reading it.
+            for window in self.secondary_windows.lock().iter_mut() {
+                complete_semaphores = [window.render(
+                    self.swapchain.swapchain_images[image_index as usize].image,
+                    extent,
+                    complete_semaphores[0],
+                )?];
+            }
+            let image_indices = [image_index];
+            let swapchain = [self.swapchain.swapchain];
             let present_info = PresentInfoKHR::builder()
-                .wait_semaphores(complete_semaphores.as_slice())
-                .image_indices(image_indices.as_slice())
-                .swapchains(swapchain.as_slice());
+                .wait_semaphores(&complete_semaphores[0..])
+                .image_indices(&image_indices[0..])
+                .swapchains(&swapchain[0..]);
             {
                 let swapchain_loader = &self.swapchain.swapchain_loader;
                 let result =
                     swapchain_loader.queue_present(*self.present_queue.lock(), &present_info);
-                match result {
-                    Ok(suboptimal) => {
-                        if suboptimal {
-                            let handle = self
-                                .window
-                                .upgrade()
-                                .expect("Failed to upgrade window handle.");
-                            handle.borrow().request_redraw();
-                            return Err(anyhow::anyhow!("Swapchain is suboptimal."));
-                        }
+                let suboptimal = result.or_graphics_error("present")?;
+                if suboptimal {
+                    if let Some(handle) = self.window.upgrade() {
+                        handle.borrow().request_redraw();
                     }
-                    Err(e) => match e {
-                        ash::vk::Result::ERROR_OUT_OF_DATE_KHR
-                        | ash::vk::Result::SUBOPTIMAL_KHR => {
-                            println!("Device out of date. (Presenting.)");
-                            return Err(anyhow::anyhow!("Swapchain is out of date or suboptimal."));
-                        }
-                        _ => panic!("Error when submitting the queue:"),
-                    },
+                    return Err(GraphicsError::SwapchainOutOfDate.into());
                 }
             }
+            *self.frame_timings.lock() = FrameTimings {
+                frame_time: 0.0,
+                cpu_scopes: vec![
+                    ("Record".to_string(), record_time.as_secs_f64() * 1000.0),
+                    (
+                        "Submit".to_string(),
+                        submit_start.elapsed().as_secs_f64() * 1000.0,
+                    ),
+                ],
+                gpu_passes,
+                allocations: 0,
+            };
             self.current_frame.fetch_add(1, Ordering::SeqCst);
             Ok(())
         }
@@ -1049,6 +1642,12 @@ impl Graphics {
             }
         }
         self.update_primary_ssbo(renderables);
+        {
+            let camera = self.camera.borrow();
+            let extent = self.get_render_extent();
+            self.texture_streamer
+                .request_mips(renderables, &camera, extent.height as f32);
+        }
         let mapped = self.uniform_buffers.primary_ssbo.as_ref();
         if let Some(ptr) = mapped {
             unsafe {
@@ -1060,6 +1659,15 @@ impl Graphics {
             }
         }
 
+        if let Some(resource_manager) = self.resource_manager.upgrade() {
+            let disposed = resource_manager.write().take_disposal_queue();
+            for model in disposed.into_iter() {
+                for (texture_index, _) in model.lock().get_texture_usages().into_iter() {
+                    self.texture_streamer.release(texture_index);
+                }
+                self.defer_deletion(DeferredDeletion::Renderable(model));
+            }
+        }
         Ok(())
     }
 
@@ -1094,49 +1702,54 @@ impl Graphics {
             .buffer(ssbo_buffer.buffer)
             .build()];
 
-        let mut texture_info = vec![];
-        {
+        let texture_info = {
             let resource = self
                 .resource_manager
                 .upgrade()
                 .expect("Failed to upgrade resource manager handle.");
             let resource_lock = resource.read();
-            for texture in resource_lock.textures.iter() {
-                let texture_lock = texture
-                    .read()
-                    .expect("Failed to lock texture for creating the descriptor set.");
-                let image_info = DescriptorImageInfo::builder()
-                    .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                    .image_view(texture_lock.image_view)
-                    .sampler(texture_lock.sampler)
-                    .build();
-                texture_info.push(image_info);
-            }
-
-            #[cfg(target_os = "macos")]
-            {
-                let current_length = texture_info.len();
-                let last_texture = resource_lock
-                    .textures
-                    .last()
-                    .expect("Failed to get last texture from resource manager.");
-                let texture_lock = last_texture
-                    .read()
-                    .expect("Failed to lock last texture for writing descriptor set.");
-                let sampler_count = dotenv::var("MACOS_SAMPLER_COUNT")?.parse::<usize>()?;
-                for _ in current_length..sampler_count {
-                    let image_info = DescriptorImageInfo::builder()
+            resource_lock
+                .textures
+                .iter()
+                .map(|texture| {
+                    let texture_lock = texture
+                        .read()
+                        .expect("Failed to lock texture for creating the descriptor set.");
+                    DescriptorImageInfo::builder()
                         .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
                         .image_view(texture_lock.image_view)
                         .sampler(texture_lock.sampler)
-                        .build();
-                    texture_info.push(image_info);
-                }
-            }
+                        .build()
+                })
+                .collect::<Vec<_>>()
+        };
+        let layout = self.texture_limits.plan(texture_info.len())?;
+        if layout.set_count > 1 {
+            log::warn!(
+                "{} textures exceed the limit of {} per descriptor set. They're split into {} descriptor sets.",
+                texture_info.len(),
+                layout.textures_per_set,
+                layout.set_count
+            );
         }
 
-        if let Some((descriptor_set, descriptor_set_layout)) =
-            DescriptorBuilder::builder(&mut *cache, &mut *allocator)
+        // 前の描述子セットはもう使われていないので、そのプールを使い回す。
+        // The previous descriptor sets are no longer in use, so their pools are reused.
+        if self.bindless_capacity > 0 {
+            allocator.reset_update_after_bind_pools();
+        }
+        let mut descriptor_sets = vec![];
+        for set_index in 0..layout.set_count {
+            let range = layout.get_texture_range(set_index, texture_info.len());
+            let mut set_texture_info = texture_info[range].to_vec();
+            // バインドレスでなければ、配列の長さまで最後のテクスチャで埋める。
+            // Without bindless, the array is padded up to its length with the last texture.
+            if self.bindless_capacity == 0 {
+                if let Some(last) = set_texture_info.last().copied() {
+                    set_texture_info.resize(layout.array_length, last);
+                }
+            }
+            let builder = DescriptorBuilder::builder(&mut *cache, &mut *allocator)
                 .bind_buffer(
                     0,
                     None,
@@ -1157,288 +1770,1829 @@ impl Graphics {
                     &ssbo_buffer_info,
                     DescriptorType::STORAGE_BUFFER,
                     ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+                );
+            let builder = if self.bindless_capacity > 0 {
+                builder.bind_image_array(
+                    3,
+                    layout.array_length as u32,
+                    &set_texture_info,
+                    DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    ShaderStageFlags::FRAGMENT,
                 )
-                .bind_image(
+            } else {
+                builder.bind_image(
                     3,
-                    Some(texture_info.len() as u32),
-                    &texture_info,
+                    Some(layout.array_length as u32),
+                    &set_texture_info,
                     DescriptorType::COMBINED_IMAGE_SAMPLER,
                     ShaderStageFlags::FRAGMENT,
                 )
-                .build()
-        {
-            self.descriptor_set = descriptor_set;
-            self.descriptor_set_layout = descriptor_set_layout;
-        } else {
-            panic!("Failed to allocate descriptor set and descriptor set layout.");
+            };
+            // 全てのセットは同じ長さなので、レイアウトはキャッシュから同じものが返る。
+            // Every set has the same length, so the cache returns the same layout for all of them.
+            if let Some((descriptor_set, descriptor_set_layout)) = builder.build() {
+                descriptor_sets.push(descriptor_set);
+                self.descriptor_set_layout = descriptor_set_layout;
+            } else {
+                panic!("Failed to allocate descriptor set and descriptor set layout.");
+            }
         }
+        self.bound_texture_count.store(
+            texture_info.len().min(layout.get_capacity()),
+            Ordering::SeqCst,
+        );
+        self.texture_descriptor_sets =
+            Arc::new(TextureDescriptorSets::new(layout, descriptor_sets));
+        self.record_generation += 1;
 
         Ok(())
     }
 
-    /// 描画開始。<br />
-    /// Draw begins.
-    fn begin_draw(
-        &self,
-        frame_buffer: Framebuffer,
-        current_frame: &FrameData,
-        frame_index: usize,
-        viewports: &[Viewport],
-        renderables: &[LockableRenderable],
-    ) -> anyhow::Result<()> {
-        let clear_color = ClearColorValue {
-            float32: self.sky_color.into(),
-        };
-        let clear_depth = ClearDepthStencilValue::builder().depth(1.0).stencil(0);
-        let clear_values = vec![
+    /// シェーダーが宣言するテクスチャ配列の長さ。MoltenVKのためのmacOSのシェーダーだけが長さを決めている。<br />
+    /// 長さはコンパイルしたシェーダーから読むので、シェーダーの配列を変えても設定を合わせなくてよい。<br />
+    /// Length of the texture array declared by the shaders. Only the macOS shaders for MoltenVK fix the length.<br />
+    /// The length is read from the compiled shaders, so nothing has to be configured when the array in the shaders changes.
+    fn get_shader_texture_array_length() -> anyhow::Result<Option<u32>> {
+        let mut array_length = None;
+        for shader_type in ShaderType::get_all_shader_types() {
+            let file_name = shader_type.get_fragment_shader_file();
+            // 読めないシェーダーはパイプラインを作る時に代わりのシェーダーになるので、ここでは飛ばす。
+            // Shaders that can't be read are replaced when creating the pipelines, so skip them here.
+            let words = match std::fs::File::open(file_name)
+                .map(|mut file| ash::util::read_spv(&mut file))
+            {
+                Ok(Ok(words)) => words,
+                _ => continue,
+            };
+            if let DescriptorArrayLength::Fixed(length) =
+                get_descriptor_array_length(&words, 0, TEXTURE_ARRAY_BINDING)?
+            {
+                match array_length {
+                    Some(other) if other != length => {
+                        return Err(anyhow::anyhow!(
+                            "{} declares {} textures, but the other fragment shaders declare {}.",
+                            file_name,
+                            length,
+                            other
+                        ));
+                    }
+                    _ => array_length = Some(length),
+                }
+            }
+        }
+        Ok(array_length)
+    }
+
+    /// 描述子セットを作った後に読み込まれたテクスチャを、テクスチャ配列の後ろに書き込む。<br />
+    /// 書き込む要素はまだどのコマンドバッファにも使われていないので、描述子セットを作り直したりGPUを待ったりしなくてよい。<br />
+    /// Write the textures loaded after creating the descriptor set to the end of the texture array.<br />
+    /// The written elements aren't used by any command buffer yet, so there's no need to rebuild the descriptor set or wait for the GPU.
+    fn append_texture_descriptors(&self) {
+        let descriptor_sets = &self.texture_descriptor_sets;
+        if self.bindless_capacity == 0 || descriptor_sets.is_empty() {
+            return;
+        }
+        let resource_manager = match self.resource_manager.upgrade() {
+            Some(resource_manager) => resource_manager,
+            None => return,
+        };
+        let resource_lock = resource_manager.read();
+        let bound_count = self.bound_texture_count.load(Ordering::SeqCst);
+        let texture_count = resource_lock.get_texture_count();
+        if texture_count <= bound_count {
+            return;
+        }
+        let layout = descriptor_sets.layout;
+        let end = texture_count.min(layout.get_capacity());
+        if end < texture_count {
+            log::warn!(
+                "The bindless texture arrays are full. {} textures can't be used until the descriptor sets are rebuilt.",
+                texture_count - end
+            );
+        }
+        // 上限に達した後も警告を繰り返さないように、書き込めなかったテクスチャも数える。
+        // Textures that couldn't be written are counted too, so the warning isn't repeated after reaching the limit.
+        self.bound_texture_count
+            .store(texture_count, Ordering::SeqCst);
+        // 新しいテクスチャは複数のセットにまたがることがあるので、セットごとに書き込む。
+        // New textures may span several sets, so they're written set by set.
+        let texture_info = descriptor_sets
+            .get_sets()
+            .iter()
+            .enumerate()
+            .filter_map(|(set_index, set)| {
+                let range = layout.get_texture_range(set_index, end);
+                let start = range.start.max(bound_count);
+                if start >= range.end {
+                    return None;
+                }
+                let image_info = resource_lock.textures[start..range.end]
+                    .iter()
+                    .map(|texture| {
+                        let texture_lock = texture
+                            .read()
+                            .expect("Failed to lock texture for writing the descriptor set.");
+                        DescriptorImageInfo::builder()
+                            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                            .image_view(texture_lock.image_view)
+                            .sampler(texture_lock.sampler)
+                            .build()
+                    })
+                    .collect::<Vec<_>>();
+                Some((*set, start - range.start, image_info))
+            })
+            .collect::<Vec<_>>();
+        let writes = texture_info
+            .iter()
+            .map(|(set, array_element, image_info)| {
+                WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(3)
+                    .dst_array_element(*array_element as u32)
+                    .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(image_info)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        if writes.is_empty() {
+            return;
+        }
+        unsafe {
+            self.logical_device.update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    /// ストリーミングで作り直したイメージを、テクスチャ配列の要素と入れ替える。<br />
+    /// 要素は前のフレームがまだ使っているかもしれないので、他のフレームのフェンスを待ってから書き換える。古いイメージは使い終わってから壊す。<br />
+    /// Swap the images created again by streaming into the elements of the texture array.<br />
+    /// Previous frames may still be using the elements, so the fences of the other frames are waited on before rewriting them. Old images are destroyed once they're no longer in use.
+    fn apply_texture_swaps(&self, frame_index: usize) -> anyhow::Result<()> {
+        let swaps = self.texture_streamer.take_swaps();
+        if swaps.is_empty() {
+            return Ok(());
+        }
+        let resource_manager = match self.resource_manager.upgrade() {
+            Some(resource_manager) => resource_manager,
+            None => return Ok(()),
+        };
+        let fences = self
+            .frame_data
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != frame_index)
+            .map(|(_, frame)| frame.fence)
+            .collect::<Vec<_>>();
+        if !fences.is_empty() {
+            unsafe {
+                self.logical_device
+                    .wait_for_fences(&fences, true, 1_000_000_000)
+                    .or_graphics_error("wait for fences before swapping textures")?;
+            }
+        }
+        let descriptor_sets = &self.texture_descriptor_sets;
+        let bound_count = self
+            .bound_texture_count
+            .load(Ordering::SeqCst)
+            .min(descriptor_sets.layout.get_capacity());
+        let resource_lock = resource_manager.read();
+        let mut image_info = vec![];
+        for swap in swaps.into_iter() {
+            let TextureSwap {
+                texture_index,
+                mip,
+                image,
+            } = swap;
+            let texture = match resource_lock.textures.get(texture_index) {
+                Some(texture) => texture,
+                None => {
+                    self.texture_streamer.cancel(texture_index);
+                    continue;
+                }
+            };
+            if texture_index < bound_count {
+                let (set, array_element) = descriptor_sets.locate(texture_index);
+                let info = DescriptorImageInfo::builder()
+                    .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .image_view(image.image_view)
+                    .sampler(image.sampler)
+                    .build();
+                image_info.push((set, array_element, [info]));
+            }
+            let previous = std::mem::replace(
+                &mut *texture
+                    .write()
+                    .expect("Failed to lock texture for swapping its mips."),
+                image,
+            );
+            self.defer_deletion(DeferredDeletion::Resource(Box::new(previous)));
+            self.texture_streamer.complete(texture_index, mip);
+        }
+        let writes = image_info
+            .iter()
+            .map(|(set, array_element, info)| {
+                WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(3)
+                    .dst_array_element(*array_element as u32)
+                    .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(info)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        if !writes.is_empty() {
+            unsafe {
+                self.logical_device.update_descriptor_sets(&writes, &[]);
+            }
+        }
+        Ok(())
+    }
+
+    /// レンダーグラフにパスを登録する。`begin_draw`を変えずに新しいパスを足せる。<br />
+    /// Register a pass to the render graph. New passes can be added without changing `begin_draw`.
+    pub fn register_render_pass(
+        &mut self,
+        node: RenderPassNode,
+        target: PassTarget,
+    ) -> anyhow::Result<()> {
+        let name = node.name.clone();
+        self.render_graph.add_pass(node)?;
+        self.pass_targets.insert(name, target);
+        Ok(())
+    }
+
+    /// レンダーグラフのパスを有効・無効にする。<br />
+    /// Enable or disable a pass of the render graph.
+    pub fn set_render_pass_enabled(&mut self, name: &str, is_enabled: bool) -> anyhow::Result<()> {
+        self.render_graph.set_enabled(name, is_enabled)
+    }
+
+    /// ポストプロセスの設定を変えて、レンダーグラフのポストプロセスのパスを組み直す。<br />
+    /// Change the post-processing settings and rebuild the post-processing passes of the render graph.
+    pub fn set_post_process_settings(
+        &mut self,
+        settings: PostProcessSettings,
+    ) -> anyhow::Result<()> {
+        self.rebuild_post_process_passes(settings, self.render_features)
+    }
+
+    /// シーンが使う描画の機能を設定する。使わない機能のパスは実行せず、ポストエフェクトのパスは組み直す。<br />
+    /// Set the rendering features the scene uses. Passes of unused features aren't executed, and the post effect passes are rebuilt.
+    pub fn set_render_features(&mut self, features: RenderFeatures) -> anyhow::Result<()> {
+        if features == self.render_features {
+            return Ok(());
+        }
+        self.rebuild_post_process_passes(self.post_process_settings, features)?;
+        self.render_graph.set_features(features)
+    }
+
+    pub fn get_render_features(&self) -> RenderFeatures {
+        self.render_features
+    }
+
+    /// 平行光源の水平な位置を変える。次に`set_daylight`で光を変えた時から使う。<br />
+    /// Change the horizontal position of the directional light, used from the next time the light is changed with `set_daylight`.
+    pub fn set_light_position(&mut self, light_x: f32, light_z: f32) {
+        self.light_position = (light_x, light_z);
+    }
+
+    /// 平行光源と空の色を変え、ユニフォームバッファーに書き込む。<br />
+    /// Change the directional light and the sky color, writing them into the uniform buffer.
+    pub fn set_daylight(&mut self, daylight: Daylight) {
+        self.daylight = daylight;
+        self.sky_color = daylight.sky_color;
+        self.push_constant.sky_color = daylight.sky_color;
+        self.write_directional_light();
+    }
+
+    pub fn get_fog_settings(&self) -> FogSettings {
+        self.fog_settings
+    }
+
+    /// 霧の設定を変え、ユニフォームバッファーに書き込む。<br />
+    /// Change the fog settings, writing them into the uniform buffer.
+    pub fn set_fog_settings(&mut self, fog_settings: FogSettings) {
+        self.fog_settings = fog_settings;
+        self.write_directional_light();
+    }
+
+    fn write_directional_light(&self) {
+        let directional_light = Self::get_directional_light(&self.daylight, &self.fog_settings);
+        let mapped = self.uniform_buffers.directional_light.mapped_memory;
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                &directional_light as *const _ as *const c_void,
+                mapped,
+                std::mem::size_of::<Directional>(),
+            );
+        }
+    }
+
+    /// 平行光源の水平な位置。最初は設定の`light_x`と`light_z`を使う。<br />
+    /// Horizontal position of the directional light, starting from `light_x` and `light_z` in the settings.
+    pub fn get_light_position(&self) -> (f32, f32) {
+        self.light_position
+    }
+
+    fn get_directional_light(daylight: &Daylight, fog_settings: &FogSettings) -> Directional {
+        Directional::new(
+            daylight.light_color,
+            daylight.light_position,
+            daylight.ambient_intensity,
+            0.5,
+        )
+        .with_fog(fog_settings, FAR_PLANE)
+    }
+
+    /// シーンの機能に合わせて実際に使うポストプロセスの設定。<br />
+    /// The post-processing settings actually in use, matching the scene's features.
+    fn get_active_post_process_settings(&self) -> PostProcessSettings {
+        self.post_process_settings
+            .with_features(self.render_features)
+    }
+
+    fn rebuild_post_process_passes(
+        &mut self,
+        settings: PostProcessSettings,
+        features: RenderFeatures,
+    ) -> anyhow::Result<()> {
+        // 読まれているパスは取り除けないので、後ろのパスから取り除く。
+        // Passes still being read can't be removed, so they are removed from the back.
+        for step in self
+            .get_active_post_process_settings()
+            .get_steps()
+            .into_iter()
+            .rev()
+        {
+            self.render_graph.remove_pass(step.get_pass_name())?;
+            self.pass_targets.remove(step.get_pass_name());
+        }
+        let active_settings = settings.with_features(features);
+        for (node, target) in Self::get_post_process_passes(&active_settings).into_iter() {
+            self.register_render_pass(node, target)?;
+        }
+        self.post_process_settings = settings;
+        self.render_features = features;
+        Ok(())
+    }
+
+    /// 合成のパスで画面の縁を赤くする強さを設定する。0で消える。<br />
+    /// Set how strongly the composite pass reddens the edges of the screen. 0 turns it off.
+    pub fn set_boundary_warning(&mut self, boundary_warning: f32) {
+        self.boundary_warning = boundary_warning;
+    }
+
+    /// 次のフレームに動かして描く粒子の発生源を設定する。<br />
+    /// Set the particle emitters simulated and drawn in the next frame.
+    pub fn set_particle_dispatches(&mut self, dispatches: Vec<ParticleDispatch>) {
+        *self.particle_dispatches.get_mut() = dispatches;
+    }
+
+    /// 次のフレームにシーンの中に描くデバッグ用の線を加える。<br />
+    /// Add debug lines drawn inside the scene in the next frame.
+    pub fn add_debug_lines(&mut self, vertices: Vec<DebugVertex>) {
+        self.debug_lines.get_mut().extend(vertices);
+    }
+
+    /// 次のフレームに画面に重ねるスプライトを加える。<br />
+    /// Add sprites overlaid on the screen in the next frame.
+    pub fn add_sprites(&mut self, sprites: Vec<Sprite>) {
+        self.sprites.get_mut().extend(sprites);
+    }
+
+    /// スプライトのアトラスのテクスチャを設定する。前のアトラスは使い終わってから壊す。<br />
+    /// Set the texture of the sprite atlas. The previous atlas is destroyed once it's no longer in use.
+    pub fn set_sprite_atlas(&mut self, atlas: super::Image) -> anyhow::Result<()> {
+        let image_info = [DescriptorImageInfo::builder()
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(atlas.image_view)
+            .sampler(atlas.sampler)
+            .build()];
+        let (descriptor_set, descriptor_set_layout) = {
+            let mut cache = self.descriptor_layout_cache.lock();
+            let mut allocator = self.descriptor_allocator.lock();
+            DescriptorBuilder::builder(&mut *cache, &mut *allocator)
+                .bind_image(
+                    0,
+                    None,
+                    &image_info,
+                    DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    ShaderStageFlags::FRAGMENT,
+                )
+                .build()
+                .ok_or_else(|| anyhow::anyhow!("Failed to allocate the sprite descriptor set."))?
+        };
+        let renderer = self.sprite_renderer.get_mut();
+        if renderer.is_none() {
+            *renderer = Some(SpriteRenderer::new(
+                self.logical_device.clone(),
+                Arc::downgrade(&self.allocator),
+                self.inflight_buffer_count,
+            ));
+        }
+        let previous = renderer
+            .as_mut()
+            .and_then(|renderer| renderer.set_atlas(atlas, descriptor_set, descriptor_set_layout));
+        if let Some(previous) = previous {
+            self.defer_deletion(DeferredDeletion::Resource(Box::new(previous)));
+        }
+        let has_pipeline = self
+            .pipeline
+            .read()
+            .expect("Failed to lock pipeline for checking the sprite pipeline.")
+            .sprite_pipeline
+            != ash::vk::Pipeline::null();
+        if !has_pipeline {
+            self.create_sprite_pipeline()?;
+        }
+        Ok(())
+    }
+
+    pub fn get_graphics_settings(&self) -> GraphicsSettings {
+        self.graphics_settings
+    }
+
+    pub fn get_supported_msaa_samples(&self) -> &[u32] {
+        &self.supported_msaa_samples
+    }
+
+    /// 別のウィンドウを開き、メインのウィンドウの画像を写し始める。<br />
+    /// デバイスを作り直すとグラフィックスと一緒に閉じる。<br />
+    /// Open another window and start copying the image of the main window to it.<br />
+    /// It's closed along with the graphics when the device is recreated.
+    pub fn open_secondary_window(
+        &self,
+        window: winit::window::Window,
+    ) -> anyhow::Result<winit::window::WindowId> {
+        if !self
+            .swapchain
+            .image_usage
+            .contains(ImageUsageFlags::TRANSFER_SRC)
+        {
+            return Err(anyhow::anyhow!(
+                "The main swapchain can't be copied to another window."
+            ));
+        }
+        let window = SecondaryWindow::new(
+            window,
+            &self.entry,
+            self.instance.clone(),
+            self.physical_device,
+            self.logical_device.clone(),
+            Arc::downgrade(&self.allocator),
+            self.graphics_queue.clone(),
+            self.present_queue.clone(),
+        )?;
+        let id = window.id();
+        self.secondary_windows.lock().push(window);
+        Ok(id)
+    }
+
+    pub fn close_secondary_window(&self, id: winit::window::WindowId) {
+        self.secondary_windows
+            .lock()
+            .retain(|window| window.id() != id);
+    }
+
+    pub fn has_secondary_window(&self, id: winit::window::WindowId) -> bool {
+        self.secondary_windows
+            .lock()
+            .iter()
+            .any(|window| window.id() == id)
+    }
+
+    /// 別のウィンドウの大きさが変わったので、次の描画でスワップチェーンを作り直す。<br />
+    /// The size of another window changed, so its swapchain is recreated on the next render.
+    pub fn resize_secondary_window(&self, id: winit::window::WindowId) {
+        if let Some(window) = self
+            .secondary_windows
+            .lock()
+            .iter_mut()
+            .find(|window| window.id() == id)
+        {
+            window.set_out_of_date();
+        }
+    }
+
+    /// グラフィックスの設定を変える。MSAAのサンプル数、プレゼントモードと描画解像度の倍率は次にスワップチェーンを作り直す時に反映される。<br />
+    /// Change the graphics settings. The MSAA sample count, the present mode and the render scale take effect the next time the swapchain is recreated.
+    pub fn set_graphics_settings(&mut self, settings: GraphicsSettings) {
+        self.graphics_settings = settings;
+    }
+
+    /// 前回の描画で測った時間を取り出す。<br />
+    /// Take the times measured by the last render.
+    pub fn take_frame_timings(&self) -> FrameTimings {
+        std::mem::take(&mut *self.frame_timings.lock())
+    }
+
+    /// 設定を反映するためにスワップチェーンを作り直す必要があるかどうか。<br />
+    /// Whether the swapchain has to be recreated to apply the settings.
+    pub fn needs_recreation(&self) -> bool {
+        self.get_requested_sample_count() != self.sample_count
+            || self.graphics_settings.present_mode != self.present_mode
+            || self.graphics_settings.render_scale != self.render_scale
+    }
+
+    /// シーンを描画する解像度。スワップチェーンの大きさに描画解像度の倍率を掛けたもの。<br />
+    /// Resolution the scene is rendered at, which is the size of the swapchain multiplied by the render scale.
+    pub fn get_render_extent(&self) -> Extent2D {
+        let (width, height) = self
+            .graphics_settings
+            .get_render_extent(self.swapchain.extent.width, self.swapchain.extent.height);
+        Extent2D { width, height }
+    }
+
+    /// VMAで確保しているメモリのバイト数と、確保の数。<br />
+    /// Bytes of memory allocated through VMA, and the number of allocations.
+    pub fn get_memory_usage(&self) -> Result<(u64, usize), GraphicsError> {
+        self.memory_stats()
+            .map(|stats| (stats.used_bytes, stats.allocation_count))
+    }
+
+    /// VMAの統計をヒープごとにまとめ、デバイスの予算と合わせて返す。<br />
+    /// 予算を報告できないドライバーでは、VMAと同じくヒープの大きさの8割を予算と見なす。<br />
+    /// Gather the VMA statistics per heap together with the budget of the device.<br />
+    /// With drivers that can't report the budget, 80% of the heap size is taken as the budget, the same as VMA does.
+    pub fn memory_stats(&self) -> Result<MemoryStats, GraphicsError> {
+        let stats = self
+            .allocator
+            .read()
+            .unwrap()
+            .calculate_stats()
+            .map_err(|e| GraphicsError::allocation("calculate VMA statistics", e))?;
+        let physical_device = self.physical_device.physical_device;
+        let memory_properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(physical_device)
+        };
+        let mut budget = PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        if self.physical_device.supports_memory_budget {
+            let mut properties2 = PhysicalDeviceMemoryProperties2 {
+                p_next: &mut budget as *mut _ as *mut c_void,
+                ..Default::default()
+            };
+            unsafe {
+                self.instance
+                    .get_physical_device_memory_properties2(physical_device, &mut properties2);
+            }
+        }
+        let heaps = (0..memory_properties.memory_heap_count as usize)
+            .map(|heap_index| {
+                let heap = memory_properties.memory_heaps[heap_index];
+                let info = stats.memoryHeap[heap_index];
+                let block_bytes = info.usedBytes + info.unusedBytes;
+                let (budget_bytes, usage_bytes) = if self.physical_device.supports_memory_budget {
+                    (
+                        budget.heap_budget[heap_index],
+                        budget.heap_usage[heap_index],
+                    )
+                } else {
+                    (heap.size / 10 * 8, block_bytes)
+                };
+                HeapUsage {
+                    heap_index,
+                    is_device_local: heap.flags.contains(MemoryHeapFlags::DEVICE_LOCAL),
+                    size: heap.size,
+                    block_bytes,
+                    used_bytes: info.usedBytes,
+                    allocation_count: info.allocationCount as usize,
+                    block_count: info.blockCount as usize,
+                    budget_bytes,
+                    usage_bytes,
+                }
+            })
+            .collect();
+        Ok(MemoryStats {
+            heaps,
+            block_bytes: stats.total.usedBytes + stats.total.unusedBytes,
+            used_bytes: stats.total.usedBytes,
+            allocation_count: stats.total.allocationCount as usize,
+            block_count: stats.total.blockCount as usize,
+        })
+    }
+
+    /// 検証レイヤーのメッセージ、最後のフレームのコマンドバッファ、パイプライン、VMAの統計をクラッシュレポートに書き出す。<br />
+    /// デバイスを失った後でも読めるものだけを使う。<br />
+    /// Write validation layer messages, the last frame's command buffers, pipelines and VMA statistics into a crash report.<br />
+    /// Only what can still be read after losing the device is used.
+    pub fn write_crash_report(&self, reason: &str) -> anyhow::Result<PathBuf> {
+        let memory_statistics = match self.allocator.read() {
+            Ok(allocator) => allocator.build_stats_string(true).ok(),
+            Err(_) => None,
+        };
+        let pipelines = self
+            .pipeline
+            .read()
+            .map(|pipeline| pipeline.get_pipeline_names())
+            .unwrap_or_default();
+        let report = CrashReport {
+            reason: reason.to_string(),
+            frame: self.frame_submission.lock().clone(),
+            validation_messages: get_validation_messages(),
+            pipelines,
+            checkpoints: self.get_checkpoints(),
+            memory_statistics,
+        };
+        report.write(Path::new(CRASH_REPORT_DIRECTORY))
+    }
+
+    /// 診断のチェックポイントを置く。マーカーはパスの番号で、読む時にパスの名前に戻す。<br />
+    /// Place a diagnostic checkpoint. The marker is the pass number, turned back into the pass name when read.
+    fn set_checkpoint(&self, command_buffer: CommandBuffer, pass_number: usize) {
+        if let Some(checkpoints) = self.checkpoints.as_ref() {
+            unsafe {
+                checkpoints.cmd_set_checkpoint_nv(command_buffer, pass_number as *const c_void);
+            }
+        }
+    }
+
+    /// グラフィックスキューでGPUが最後に通ったチェックポイントを読む。<br />
+    /// Read the checkpoints the GPU passed last on the graphics queue.
+    fn get_checkpoints(&self) -> Vec<String> {
+        let checkpoints = match self.checkpoints.as_ref() {
+            Some(checkpoints) => checkpoints,
+            None => return vec![],
+        };
+        let queue = *self.graphics_queue.lock();
+        let mut count = 0;
+        let mut data = vec![];
+        unsafe {
+            checkpoints.get_queue_checkpoint_data_nv(queue, &mut count, std::ptr::null_mut());
+            data.resize(count as usize, CheckpointDataNV::default());
+            checkpoints.get_queue_checkpoint_data_nv(queue, &mut count, data.as_mut_ptr());
+        }
+        data.truncate(count as usize);
+        let frame_submission = self.frame_submission.lock();
+        data.iter()
+            .map(|checkpoint| {
+                let pass_number = checkpoint.p_checkpoint_marker as usize;
+                let pass_name = frame_submission
+                    .passes
+                    .get(pass_number.wrapping_sub(1))
+                    .map(|pass| pass.name.as_str())
+                    .unwrap_or("Unknown");
+                format!("{:?}: {}", checkpoint.stage, pass_name)
+            })
+            .collect()
+    }
+
+    /// 描述子配置器が作ったプールの数。<br />
+    /// Number of pools created by the descriptor allocator.
+    pub fn get_descriptor_pool_count(&self) -> usize {
+        self.descriptor_allocator.lock().get_pool_count()
+    }
+
+    fn get_requested_sample_count(&self) -> SampleCountFlags {
+        Initializer::get_sample_count(
+            self.graphics_settings
+                .get_msaa_samples(&self.supported_msaa_samples),
+        )
+    }
+
+    /// グラフィックスとポストプロセスの設定を調整パネルに登録する。<br />
+    /// Register the graphics and post-processing settings to the tweak panel.
+    pub fn register_tweaks(&mut self, tweaks: &mut TweakRegistry) {
+        let supported_samples = self.supported_msaa_samples.clone();
+        self.graphics_settings
+            .register_tweaks(tweaks, &supported_samples);
+        let mut settings = self.post_process_settings;
+        settings.register_tweaks(tweaks);
+        self.apply_post_process_settings(settings);
+        let mut fog_settings = self.fog_settings;
+        fog_settings.register_tweaks(tweaks);
+        self.set_fog_settings(fog_settings);
+        self.occlusion_culler.get_mut().register_tweaks(tweaks);
+    }
+
+    pub fn apply_tweaks(&mut self, tweaks: &TweakRegistry) {
+        let supported_samples = self.supported_msaa_samples.clone();
+        self.graphics_settings
+            .apply_tweaks(tweaks, &supported_samples);
+        let mut settings = self.post_process_settings;
+        settings.apply_tweaks(tweaks);
+        self.apply_post_process_settings(settings);
+        let mut fog_settings = self.fog_settings;
+        fog_settings.apply_tweaks(tweaks);
+        if fog_settings != self.fog_settings {
+            self.set_fog_settings(fog_settings);
+        }
+        self.occlusion_culler.get_mut().apply_tweaks(tweaks);
+    }
+
+    fn apply_post_process_settings(&mut self, settings: PostProcessSettings) {
+        if settings == self.post_process_settings {
+            return;
+        }
+        if let Err(e) = self.set_post_process_settings(settings) {
+            log::error!("Failed to apply post-processing settings: {}", e);
+        }
+    }
+
+    /// 既定のパスでレンダーグラフを作る。水面はまだ実装していないので、反射と屈折のパスは無効にしておく。<br />
+    /// シーンはHDRで描画し、ポストプロセスのパスがスワップチェーンに書く。<br />
+    /// Create the render graph with the default passes. Water isn't implemented yet, so the reflection and refraction passes start disabled.<br />
+    /// The scene is rendered in HDR, and the post-processing passes write the swapchain.
+    fn create_render_graph(
+        settings: &PostProcessSettings,
+    ) -> anyhow::Result<(RenderGraph, HashMap<String, PassTarget>)> {
+        let mut passes = vec![
+            (
+                RenderPassNode::new("Reflection", PassOutput::Sampled)
+                    .write("Reflection")
+                    .requires(RenderFeatures::WATER)
+                    .enabled(false),
+                PassTarget {
+                    render_pass_type: RenderPassType::Offscreen,
+                    framebuffer: FramebufferSource::Offscreen(0),
+                    extent: Some(Extent2D {
+                        width: REFLECTION_WIDTH,
+                        height: REFLECTION_HEIGHT,
+                    }),
+                    post_process_step: None,
+                },
+            ),
+            (
+                RenderPassNode::new("Refraction", PassOutput::Sampled)
+                    .write("Refraction")
+                    .requires(RenderFeatures::WATER)
+                    .enabled(false),
+                PassTarget {
+                    render_pass_type: RenderPassType::Offscreen,
+                    framebuffer: FramebufferSource::Offscreen(1),
+                    extent: Some(Extent2D {
+                        width: REFRACTION_WIDTH,
+                        height: REFRACTION_HEIGHT,
+                    }),
+                    post_process_step: None,
+                },
+            ),
+            (
+                RenderPassNode::new("Scene", PassOutput::Sampled).write(HDR_SCENE_ATTACHMENT),
+                PassTarget {
+                    render_pass_type: RenderPassType::Offscreen,
+                    framebuffer: FramebufferSource::Scene,
+                    extent: None,
+                    post_process_step: None,
+                },
+            ),
+        ];
+        passes.append(&mut Self::get_post_process_passes(settings));
+        let mut render_graph = RenderGraph::new();
+        let mut pass_targets = HashMap::new();
+        for (node, target) in passes.into_iter() {
+            pass_targets.insert(node.name.clone(), target);
+            render_graph.add_pass(node)?;
+        }
+        Ok((render_graph, pass_targets))
+    }
+
+    fn get_post_process_passes(
+        settings: &PostProcessSettings,
+    ) -> Vec<(RenderPassNode, PassTarget)> {
+        settings
+            .get_render_pass_nodes()
+            .into_iter()
+            .map(|(step, node)| {
+                let (render_pass_type, framebuffer) = get_post_process_target(step, settings);
+                (
+                    node,
+                    PassTarget {
+                        render_pass_type,
+                        framebuffer,
+                        extent: None,
+                        post_process_step: Some(step),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// 描画開始。レンダーグラフが決めた順番でパスを記録する。<br />
+    /// Draw begins, recording passes in the order decided by the render graph.
+    fn begin_draw(
+        &self,
+        frame_buffer: Framebuffer,
+        current_frame: &FrameData,
+        frame_index: usize,
+        renderables: &[LockableRenderable],
+    ) -> anyhow::Result<()> {
+        // Begin command buffer
+        unsafe {
+            let result = self.logical_device.begin_command_buffer(
+                current_frame.main_command_buffer,
+                &CommandBufferBeginInfo::builder().flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            );
+            if let Err(e) = result {
+                log::error!("Error beginning command buffer: {}", e.to_string());
+            }
+        }
+
+        self.frame_submission.lock().begin(frame_index);
+        let mut gpu_timer = self.gpu_timer.lock();
+        unsafe {
+            gpu_timer.begin_frame(current_frame.main_command_buffer, frame_index);
+            if let Some(renderer) = self.occlusion_renderer.lock().as_mut() {
+                renderer.begin_frame(current_frame.main_command_buffer, frame_index);
+            }
+        }
+        // 波紋はレンダーパスの外でしかコピーできないので、パスを始める前にアップロードする。
+        // Ripples can only be copied outside render passes, so they're uploaded before the passes begin.
+        if let Some(ripple_texture) = self.ripple_texture.lock().as_mut() {
+            ripple_texture.record_upload(frame_index, current_frame.main_command_buffer)?;
+        }
+        for node in self.render_graph.get_execution_order() {
+            let target = self
+                .pass_targets
+                .get(&node.name)
+                .copied()
+                .expect("Failed to get the target of the render pass.");
+            let (framebuffer, extent) = match target.framebuffer {
+                FramebufferSource::Swapchain => (frame_buffer, self.swapchain.extent),
+                FramebufferSource::Offscreen(index) => {
+                    let offscreen = &self.offscreen_pass.framebuffers[index];
+                    (
+                        offscreen.framebuffer[frame_index],
+                        Extent2D {
+                            width: offscreen.width,
+                            height: offscreen.height,
+                        },
+                    )
+                }
+                source => self
+                    .post_process_targets
+                    .as_ref()
+                    .and_then(|targets| targets.get_framebuffer(source))
+                    .expect("Failed to get the post-processing framebuffer."),
+            };
+            let extent = target.extent.unwrap_or(extent);
+            let pass_number = {
+                let mut frame_submission = self.frame_submission.lock();
+                frame_submission.add_pass(&node.name);
+                frame_submission.passes.len()
+            };
+            self.set_checkpoint(current_frame.main_command_buffer, pass_number);
+            let result = unsafe {
+                gpu_timer.time_pass(
+                    current_frame.main_command_buffer,
+                    frame_index,
+                    &node.name,
+                    || match target.post_process_step {
+                        Some(step) => self.record_post_process_pass(
+                            step,
+                            target.render_pass_type,
+                            framebuffer,
+                            extent,
+                            current_frame,
+                            frame_index,
+                        ),
+                        None => self.record_pass(
+                            target,
+                            framebuffer,
+                            extent,
+                            current_frame,
+                            frame_index,
+                            renderables,
+                        ),
+                    },
+                )
+            };
+            result?;
+        }
+        drop(gpu_timer);
+
+        unsafe {
+            let result = self
+                .logical_device
+                .end_command_buffer(current_frame.main_command_buffer);
+            if let Err(e) = result {
+                log::error!("Error ending command buffer: {}", e.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn get_clear_values(&self) -> [ClearValue; 2] {
+        let clear_color = ClearColorValue {
+            float32: self.sky_color.into(),
+        };
+        let clear_depth = ClearDepthStencilValue::builder().depth(1.0).stencil(0);
+        [
             ClearValue { color: clear_color },
             ClearValue {
                 depth_stencil: *clear_depth,
             },
+        ]
+    }
+
+    fn get_viewport(extent: Extent2D) -> Viewport {
+        Viewport::builder()
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .x(0.0)
+            .y(0.0)
+            .min_depth(0.0)
+            .max_depth(1.0)
+            .build()
+    }
+
+    fn get_render_pass(&self, render_pass_type: RenderPassType) -> RenderPass {
+        self.pipeline
+            .read()
+            .expect("Failed to lock pipeline for beginning the renderpass.")
+            .render_pass
+            .get(&render_pass_type)
+            .copied()
+            .expect("Failed to get the renderpass of the render graph pass.")
+    }
+
+    /// レンダーグラフの一つのパスを記録する。描画するモデルは二次コマンドバッファに記録する。<br />
+    /// Record a single pass of the render graph. Renderables are recorded into secondary command buffers.
+    fn record_pass(
+        &self,
+        target: PassTarget,
+        framebuffer: Framebuffer,
+        extent: Extent2D,
+        current_frame: &FrameData,
+        frame_index: usize,
+        renderables: &[LockableRenderable],
+    ) -> anyhow::Result<()> {
+        let clear_values = self.get_clear_values();
+        let render_area = Rect2D::builder().extent(extent).offset(Offset2D::default());
+        let viewport = Self::get_viewport(extent);
+        let render_pass = self.get_render_pass(target.render_pass_type);
+        let renderpass_begin_info = RenderPassBeginInfo::builder()
+            .render_pass(render_pass)
+            .clear_values(&clear_values[0..])
+            .render_area(*render_area)
+            .framebuffer(framebuffer);
+
+        let inheritance_ptr = {
+            let inheritance_info = Box::new(
+                CommandBufferInheritanceInfo::builder()
+                    .framebuffer(framebuffer)
+                    .render_pass(render_pass)
+                    .build(),
+            );
+            AtomicPtr::new(Box::into_raw(inheritance_info))
+        };
+        let inheritance_handle = Arc::new(inheritance_ptr);
+        let is_scene = target.framebuffer == FramebufferSource::Scene;
+        unsafe {
+            // 間接描画を間引く計算シェーダーはパスの外で記録するので、モデルの記録はパスを始める前に済ませる。
+            // The compute shader culling indirect draws is recorded outside the pass, so models are recorded before it begins.
+            let indirect_draws = if is_scene {
+                self.begin_indirect_draws()
+            } else {
+                None
+            };
+            self.update_secondary_command_buffers(
+                inheritance_handle,
+                viewport,
+                *render_area,
+                frame_index,
+                renderables,
+                indirect_draws,
+            )?;
+            if is_scene {
+                self.cull_indirect_draws(current_frame.main_command_buffer, frame_index)?;
+            }
+            self.logical_device.cmd_begin_render_pass(
+                current_frame.main_command_buffer,
+                &renderpass_begin_info,
+                SubpassContents::SECONDARY_COMMAND_BUFFERS,
+            );
+            let mut command_buffers = self.secondary_command_buffers.lock();
+            if is_scene {
+                let inheritance_info = CommandBufferInheritanceInfo::builder()
+                    .framebuffer(framebuffer)
+                    .render_pass(render_pass);
+                if let Some(command_buffer) = self.record_indirect_draws(
+                    &inheritance_info,
+                    viewport,
+                    *render_area,
+                    frame_index,
+                )? {
+                    command_buffers.push(command_buffer);
+                }
+                if let Some(command_buffer) = self.record_occlusion_queries(
+                    &inheritance_info,
+                    viewport,
+                    *render_area,
+                    frame_index,
+                )? {
+                    command_buffers.push(command_buffer);
+                }
+                if let Some(command_buffer) =
+                    self.record_particles(&inheritance_info, viewport, *render_area, frame_index)?
+                {
+                    command_buffers.push(command_buffer);
+                }
+                if let Some(command_buffer) =
+                    self.record_debug_lines(&inheritance_info, viewport, *render_area, frame_index)?
+                {
+                    command_buffers.push(command_buffer);
+                }
+            }
+            self.frame_submission
+                .lock()
+                .add_secondary_command_buffers(command_buffers.len());
+            if !command_buffers.is_empty() {
+                self.logical_device.cmd_execute_commands(
+                    current_frame.main_command_buffer,
+                    command_buffers.as_slice(),
+                );
+            }
+            self.logical_device
+                .cmd_end_render_pass(current_frame.main_command_buffer);
+        }
+        Ok(())
+    }
+
+    /// フルスクリーンのポストプロセスのパスを記録する。三角形一つだけなので、主なコマンドバッファに直接記録する。<br />
+    /// スワップチェーンに書く最後のパスでは、その後にスプライトを描く。<br />
+    /// Record a fullscreen post-processing pass. It's a single triangle, so it's recorded straight into the main command buffer.<br />
+    /// The final pass writing the swapchain draws the sprites after it.
+    fn record_post_process_pass(
+        &self,
+        step: PostProcessStep,
+        render_pass_type: RenderPassType,
+        framebuffer: Framebuffer,
+        extent: Extent2D,
+        current_frame: &FrameData,
+        frame_index: usize,
+    ) -> anyhow::Result<()> {
+        let targets = self
+            .post_process_targets
+            .as_ref()
+            .expect("Post-processing targets don't exist.");
+        let (pipeline, pipeline_layout) = {
+            let pipeline = self
+                .pipeline
+                .read()
+                .expect("Failed to lock pipeline for recording post-processing.");
+            (
+                pipeline.get_post_process_pipeline(step, render_pass_type),
+                pipeline.post_process_layout,
+            )
+        };
+        let clear_values = self.get_clear_values();
+        let render_area = Rect2D::builder().extent(extent).offset(Offset2D::default());
+        let renderpass_begin_info = RenderPassBeginInfo::builder()
+            .render_pass(self.get_render_pass(render_pass_type))
+            .clear_values(&clear_values[0..])
+            .render_area(*render_area)
+            .framebuffer(framebuffer);
+        // テクセルの大きさは書く先ではなく読むイメージから求める。シーンの解像度が違っても正しく拡大縮小できる。
+        // The texel size comes from the images read rather than the target, so sampling stays correct when the scene resolution differs.
+        let input_extent = targets.get_input_extent(step);
+        let settings = self.get_active_post_process_settings();
+        let push_constant =
+            PostProcessPushConstant::new(&settings, step, input_extent.width, input_extent.height)
+                .with_boundary_warning(self.boundary_warning);
+        let descriptor_sets = [targets.get_descriptor_set(step, &settings)];
+        let command_buffer = current_frame.main_command_buffer;
+        unsafe {
+            self.logical_device.cmd_begin_render_pass(
+                command_buffer,
+                &renderpass_begin_info,
+                SubpassContents::INLINE,
+            );
+            self.logical_device
+                .cmd_set_viewport(command_buffer, 0, &[Self::get_viewport(extent)]);
+            self.logical_device
+                .cmd_set_scissor(command_buffer, 0, &[*render_area]);
+            self.logical_device.cmd_bind_pipeline(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline,
+            );
+            self.logical_device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &descriptor_sets[0..],
+                &[],
+            );
+            self.logical_device.cmd_push_constants(
+                command_buffer,
+                pipeline_layout,
+                ShaderStageFlags::FRAGMENT,
+                0,
+                std::slice::from_raw_parts(
+                    &push_constant as *const _ as *const u8,
+                    std::mem::size_of::<PostProcessPushConstant>(),
+                ),
+            );
+            self.logical_device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+        if render_pass_type == RenderPassType::Primary {
+            self.record_sprites(command_buffer, extent, frame_index)?;
+        }
+        unsafe {
+            self.logical_device.cmd_end_render_pass(command_buffer);
+        }
+        Ok(())
+    }
+
+    /// フレームバッファを生成する。戻り値は`Vec<Framebuffer>`の理由はこのプログラムにはトリプルバッファリングを使うから。<br />
+    /// Create framebuffers. The reason that the return value is `Vec<Framebuffer>` is that this program utilizes triple buffering.
+    fn create_frame_buffers(
+        frame_width: u32,
+        frame_height: u32,
+        renderpass: RenderPass,
+        swapchain: &super::Swapchain,
+        depth_image: &super::Image,
+        msaa_image: &super::Image,
+        sample_count: SampleCountFlags,
+        device: &Device,
+    ) -> Vec<Framebuffer> {
+        let mut frame_buffers = vec![];
+        let image_count = swapchain.swapchain_images.len();
+        for i in 0..image_count {
+            let image_views = Initializer::get_framebuffer_attachments(
+                sample_count,
+                msaa_image.image_view,
+                depth_image.image_view,
+                swapchain.swapchain_images[i].image_view,
+            );
+            let frame_buffer_info = FramebufferCreateInfo::builder()
+                .height(frame_height)
+                .width(frame_width)
+                .layers(1)
+                .attachments(image_views.as_slice())
+                .render_pass(renderpass);
+            unsafe {
+                frame_buffers.push(
+                    device
+                        .create_framebuffer(&frame_buffer_info, None)
+                        .expect("Failed to create framebuffer."),
+                );
+            }
+        }
+        frame_buffers
+    }
+
+    /// 読み込みの失敗を画面に表示する警告の一覧に載せる。<br />
+    /// Add a load failure to the list of warnings shown on screen.
+    pub fn report_asset_warning(&self, kind: AssetKind, path: &str, error: &anyhow::Error) {
+        match self.resource_manager.upgrade() {
+            Some(rm) => {
+                let asset_warnings = rm.read().asset_warnings.clone();
+                asset_warnings
+                    .lock()
+                    .report(kind, path, &format!("{:#}", error));
+            }
+            None => log::error!("{} {} failed to load: {:#}", kind, path, error),
+        }
+    }
+
+    /// シェーダーを読み込む。読み込めなければ警告を出し、代わりのシェーダーを読み込む。<br />
+    /// Load a shader. If it can't be loaded, a warning is reported and the fallback shader is loaded instead.
+    fn load_shader(
+        &self,
+        file_name: &str,
+        fallback: &str,
+        stage_flag: ShaderStageFlags,
+    ) -> anyhow::Result<super::Shader> {
+        match super::Shader::new(self.logical_device.clone(), file_name, stage_flag) {
+            Ok(shader) => Ok(shader),
+            Err(e) if file_name != fallback => {
+                self.report_asset_warning(AssetKind::Shader, file_name, &e);
+                super::Shader::new(self.logical_device.clone(), fallback, stage_flag)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// シェーダーのタイプに応じてグラフィックパイプラインを生成する。<br />
+    /// Create graphic pipelines according to the shader type.
+    fn create_graphics_pipeline(&mut self, shader_type: ShaderType) -> anyhow::Result<()> {
+        let shaders = vec![
+            self.load_shader(
+                shader_type.get_vertex_shader_file(),
+                FALLBACK_VERTEX_SHADER,
+                ShaderStageFlags::VERTEX,
+            )?,
+            self.load_shader(
+                shader_type.get_fragment_shader_file(),
+                FALLBACK_FRAGMENT_SHADER,
+                ShaderStageFlags::FRAGMENT,
+            )?,
         ];
-        let extent = self.swapchain.extent;
-        let mut render_area = Rect2D::builder()
-            .extent(Extent2D {
-                width: REFLECTION_WIDTH,
-                height: REFLECTION_HEIGHT,
+
+        let mut descriptor_set_layout = vec![self.descriptor_set_layout];
+        match shader_type {
+            ShaderType::AnimatedModel => {
+                descriptor_set_layout.push(self.ssbo_descriptor_set_layout);
+            }
+            _ => (),
+        }
+        self.pipeline
+            .write()
+            .expect("Failed to lock pipeline when creating the pipeline.")
+            .create_graphic_pipelines(
+                descriptor_set_layout.as_slice(),
+                self.sample_count,
+                shaders,
+                shader_type,
+            )?;
+        Ok(())
+    }
+
+    /// ポストプロセスのレンダーターゲットとパイプラインを生成する。<br />
+    /// Create the render targets and pipelines of post-processing.
+    fn create_post_process_pipelines(&mut self) -> anyhow::Result<()> {
+        let (scene_renderpass, post_process_renderpass) = (
+            self.get_render_pass(RenderPassType::Offscreen),
+            self.get_render_pass(RenderPassType::PostProcess),
+        );
+        let targets = {
+            let mut cache = self.descriptor_layout_cache.lock();
+            let mut allocator = self.descriptor_allocator.lock();
+            PostProcessTargets::new(
+                Arc::downgrade(&self.logical_device),
+                self.get_render_extent(),
+                self.depth_format,
+                self.sample_count,
+                scene_renderpass,
+                post_process_renderpass,
+                self.frame_data[0].command_pool,
+                *self.graphics_queue.lock(),
+                Arc::downgrade(&self.allocator),
+                &mut *cache,
+                &mut *allocator,
+            )?
+        };
+        let shaders = PostProcessStep::get_all_steps()
+            .into_iter()
+            .map(|step| {
+                let shaders = vec![
+                    super::Shader::new(
+                        self.logical_device.clone(),
+                        POST_PROCESS_VERTEX_SHADER,
+                        ShaderStageFlags::VERTEX,
+                    )?,
+                    super::Shader::new(
+                        self.logical_device.clone(),
+                        step.get_fragment_shader_file(),
+                        ShaderStageFlags::FRAGMENT,
+                    )?,
+                ];
+                Ok((step, shaders))
             })
-            .offset(Offset2D::default());
-        let scissors = vec![Rect2D::builder()
-            .extent(extent)
-            .offset(Offset2D::default())
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.pipeline
+            .write()
+            .expect("Failed to lock pipeline when creating post-processing pipelines.")
+            .create_post_process_pipelines(
+                targets.descriptor_set_layout,
+                self.sample_count,
+                shaders,
+            )?;
+        self.post_process_targets = Some(targets);
+        self.render_scale = self.graphics_settings.render_scale;
+        Ok(())
+    }
+
+    /// 粒子のパイプラインと、粒子バッファと視点の行列を繋ぐ描述子セットを生成する。<br />
+    /// Create the particle pipelines and the descriptor set binding the particle buffer and the view-projection matrices.
+    fn create_particle_pipelines(&mut self) -> anyhow::Result<()> {
+        let renderer = self.particle_renderer.get_mut();
+        if renderer.is_none() {
+            *renderer = Some(ParticleRenderer::new(
+                self.logical_device.clone(),
+                &self.physical_device,
+                Arc::downgrade(&self.allocator),
+                self.graphics_queue.clone(),
+                self.compute_queue.clone(),
+                self.inflight_buffer_count,
+            )?);
+        }
+        let renderer = renderer
+            .as_mut()
+            .expect("Failed to get the particle renderer.");
+        // パイプラインが揃うまでは粒子を動かさない。
+        // Particles aren't simulated until the pipelines are ready.
+        renderer.descriptor_set = DescriptorSet::null();
+        let particle_buffer_info = [renderer.get_buffer_info()];
+        let vp_buffer_info = [DescriptorBufferInfo::builder()
+            .buffer(self.uniform_buffers.view_projection.buffer)
+            .offset(0)
+            .range(self.uniform_buffers.view_projection.buffer_size)
             .build()];
-        let offscreen_renderpass = self
+        let (descriptor_set, descriptor_set_layout) = {
+            let mut cache = self.descriptor_layout_cache.lock();
+            let mut allocator = self.descriptor_allocator.lock();
+            DescriptorBuilder::builder(&mut *cache, &mut *allocator)
+                .bind_buffer(
+                    0,
+                    None,
+                    &particle_buffer_info,
+                    DescriptorType::STORAGE_BUFFER,
+                    ShaderStageFlags::COMPUTE | ShaderStageFlags::VERTEX,
+                )
+                .bind_buffer(
+                    1,
+                    None,
+                    &vp_buffer_info,
+                    DescriptorType::UNIFORM_BUFFER,
+                    ShaderStageFlags::VERTEX,
+                )
+                .build()
+                .ok_or_else(|| anyhow::anyhow!("Failed to allocate the particle descriptor set."))?
+        };
+        let compute_shader = super::Shader::new(
+            self.logical_device.clone(),
+            PARTICLE_COMPUTE_SHADER,
+            ShaderStageFlags::COMPUTE,
+        )?;
+        let shaders = vec![
+            super::Shader::new(
+                self.logical_device.clone(),
+                PARTICLE_VERTEX_SHADER,
+                ShaderStageFlags::VERTEX,
+            )?,
+            super::Shader::new(
+                self.logical_device.clone(),
+                PARTICLE_FRAGMENT_SHADER,
+                ShaderStageFlags::FRAGMENT,
+            )?,
+        ];
+        self.pipeline
+            .write()
+            .expect("Failed to lock pipeline when creating particle pipelines.")
+            .create_particle_pipelines(
+                descriptor_set_layout,
+                self.sample_count,
+                compute_shader,
+                shaders,
+            )?;
+        if let Some(renderer) = self.particle_renderer.get_mut().as_mut() {
+            renderer.descriptor_set = descriptor_set;
+        }
+        Ok(())
+    }
+
+    /// このフレームの粒子を計算キューで動かす。粒子がなければ何も提出しない。<br />
+    /// Simulate this frame's particles on the compute queue. Nothing is submitted without particles.
+    fn simulate_particles(
+        &self,
+        frame_index: usize,
+    ) -> anyhow::Result<Option<(Semaphore, Semaphore)>> {
+        let mut renderer = self.particle_renderer.lock();
+        let renderer = match renderer.as_mut() {
+            Some(renderer) => renderer,
+            None => return Ok(None),
+        };
+        let pipeline = self
             .pipeline
             .read()
-            .expect("Failed to lock pipeline for beginning the renderpass.")
-            .render_pass
-            .get(&RenderPassType::Offscreen)
-            .copied()
-            .expect("Failed to get offscreen renderpass.");
-        let primary_renderpass = self
+            .expect("Failed to lock pipeline for simulating particles.");
+        renderer.simulate(
+            frame_index,
+            self.particle_dispatches.lock().as_slice(),
+            pipeline.particle_simulation_layout,
+            pipeline.particle_simulation_pipeline,
+        )
+    }
+
+    /// シーンのパスに加える、粒子を描く二次コマンドバッファを記録する。<br />
+    /// Record the secondary command buffer drawing particles, added to the scene pass.
+    fn record_particles(
+        &self,
+        inheritance_info: &CommandBufferInheritanceInfo,
+        viewport: Viewport,
+        scissor: Rect2D,
+        frame_index: usize,
+    ) -> anyhow::Result<Option<CommandBuffer>> {
+        let renderer = self.particle_renderer.lock();
+        let renderer = match renderer.as_ref() {
+            Some(renderer) => renderer,
+            None => return Ok(None),
+        };
+        let pipeline = self
+            .pipeline
+            .read()
+            .expect("Failed to lock pipeline for drawing particles.");
+        renderer.record_draw(
+            frame_index,
+            inheritance_info,
+            viewport,
+            scissor,
+            self.particle_dispatches.lock().as_slice(),
+            pipeline.particle_layout,
+            pipeline.particle_pipeline,
+        )
+    }
+
+    /// デバッグ用の線のパイプラインと、視点の行列を繋ぐ描述子セットを生成する。<br />
+    /// Create the debug line pipeline and the descriptor set binding the view-projection matrices.
+    fn create_debug_line_pipeline(&mut self) -> anyhow::Result<()> {
+        let renderer = self.debug_draw_renderer.get_mut();
+        if renderer.is_none() {
+            *renderer = Some(DebugDrawRenderer::new(
+                self.logical_device.clone(),
+                &self.physical_device,
+                Arc::downgrade(&self.allocator),
+                self.inflight_buffer_count,
+            )?);
+        }
+        let vp_buffer_info = [DescriptorBufferInfo::builder()
+            .buffer(self.uniform_buffers.view_projection.buffer)
+            .offset(0)
+            .range(self.uniform_buffers.view_projection.buffer_size)
+            .build()];
+        let (descriptor_set, descriptor_set_layout) = {
+            let mut cache = self.descriptor_layout_cache.lock();
+            let mut allocator = self.descriptor_allocator.lock();
+            DescriptorBuilder::builder(&mut *cache, &mut *allocator)
+                .bind_buffer(
+                    0,
+                    None,
+                    &vp_buffer_info,
+                    DescriptorType::UNIFORM_BUFFER,
+                    ShaderStageFlags::VERTEX,
+                )
+                .build()
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Failed to allocate the debug line descriptor set.")
+                })?
+        };
+        let shaders = vec![
+            super::Shader::new(
+                self.logical_device.clone(),
+                DEBUG_LINE_VERTEX_SHADER,
+                ShaderStageFlags::VERTEX,
+            )?,
+            super::Shader::new(
+                self.logical_device.clone(),
+                DEBUG_LINE_FRAGMENT_SHADER,
+                ShaderStageFlags::FRAGMENT,
+            )?,
+        ];
+        self.pipeline
+            .write()
+            .expect("Failed to lock pipeline when creating the debug line pipeline.")
+            .create_debug_line_pipeline(descriptor_set_layout, self.sample_count, shaders)?;
+        if let Some(renderer) = self.debug_draw_renderer.get_mut().as_mut() {
+            renderer.descriptor_set = descriptor_set;
+        }
+        Ok(())
+    }
+
+    /// シーンのパスに加える、デバッグ用の線を描く二次コマンドバッファを記録する。<br />
+    /// Record the secondary command buffer drawing debug lines, added to the scene pass.
+    fn record_debug_lines(
+        &self,
+        inheritance_info: &CommandBufferInheritanceInfo,
+        viewport: Viewport,
+        scissor: Rect2D,
+        frame_index: usize,
+    ) -> anyhow::Result<Option<CommandBuffer>> {
+        let mut renderer = self.debug_draw_renderer.lock();
+        let renderer = match renderer.as_mut() {
+            Some(renderer) => renderer,
+            None => return Ok(None),
+        };
+        let pipeline = self
+            .pipeline
+            .read()
+            .expect("Failed to lock pipeline for drawing debug lines.");
+        renderer.record_draw(
+            frame_index,
+            inheritance_info,
+            viewport,
+            scissor,
+            self.debug_lines.lock().as_slice(),
+            pipeline.debug_line_layout,
+            pipeline.debug_line_pipeline,
+        )
+    }
+
+    /// 境界ボックスのパイプラインと、視点の行列を繋ぐ描述子セットを生成する。<br />
+    /// Create the bounding box pipeline and the descriptor set binding the view-projection matrices.
+    fn create_occlusion_pipeline(&mut self) -> anyhow::Result<()> {
+        let renderer = self.occlusion_renderer.get_mut();
+        if renderer.is_none() {
+            *renderer = Some(OcclusionRenderer::new(
+                self.logical_device.clone(),
+                &self.physical_device,
+                self.inflight_buffer_count,
+            )?);
+        }
+        let vp_buffer_info = [DescriptorBufferInfo::builder()
+            .buffer(self.uniform_buffers.view_projection.buffer)
+            .offset(0)
+            .range(self.uniform_buffers.view_projection.buffer_size)
+            .build()];
+        let (descriptor_set, descriptor_set_layout) = {
+            let mut cache = self.descriptor_layout_cache.lock();
+            let mut allocator = self.descriptor_allocator.lock();
+            DescriptorBuilder::builder(&mut *cache, &mut *allocator)
+                .bind_buffer(
+                    0,
+                    None,
+                    &vp_buffer_info,
+                    DescriptorType::UNIFORM_BUFFER,
+                    ShaderStageFlags::VERTEX,
+                )
+                .build()
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Failed to allocate the occlusion descriptor set.")
+                })?
+        };
+        let shaders = vec![
+            super::Shader::new(
+                self.logical_device.clone(),
+                OCCLUSION_VERTEX_SHADER,
+                ShaderStageFlags::VERTEX,
+            )?,
+            super::Shader::new(
+                self.logical_device.clone(),
+                OCCLUSION_FRAGMENT_SHADER,
+                ShaderStageFlags::FRAGMENT,
+            )?,
+        ];
+        self.pipeline
+            .write()
+            .expect("Failed to lock pipeline when creating the occlusion pipeline.")
+            .create_occlusion_pipeline(descriptor_set_layout, self.sample_count, shaders)?;
+        if let Some(renderer) = self.occlusion_renderer.get_mut().as_mut() {
+            renderer.descriptor_set = descriptor_set;
+        }
+        Ok(())
+    }
+
+    /// シーンのパスに加える、並べたオクルージョンクエリの二次コマンドバッファを記録する。<br />
+    /// Record the secondary command buffer of the queued occlusion queries, added to the scene pass.
+    fn record_occlusion_queries(
+        &self,
+        inheritance_info: &CommandBufferInheritanceInfo,
+        viewport: Viewport,
+        scissor: Rect2D,
+        frame_index: usize,
+    ) -> anyhow::Result<Option<CommandBuffer>> {
+        let queries = self.occlusion_culler.lock().take_queries(frame_index);
+        let mut renderer = self.occlusion_renderer.lock();
+        let renderer = match renderer.as_mut() {
+            Some(renderer) => renderer,
+            None => return Ok(None),
+        };
+        let pipeline = self
             .pipeline
             .read()
-            .expect("Failed to lock pipeline for beginning the renderpass.")
-            .render_pass
-            .get(&RenderPassType::Primary)
-            .copied()
-            .expect("Failed to get primary renderpass.");
+            .expect("Failed to lock pipeline for occlusion queries.");
+        renderer.record_queries(
+            frame_index,
+            inheritance_info,
+            viewport,
+            scissor,
+            queries.as_slice(),
+            pipeline.occlusion_layout,
+            pipeline.occlusion_pipeline,
+        )
+    }
 
-        // Begin command buffer
-        unsafe {
-            let result = self.logical_device.begin_command_buffer(
-                current_frame.main_command_buffer,
-                &CommandBufferBeginInfo::builder().flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT),
-            );
-            if let Err(e) = result {
-                log::error!("Error beginning command buffer: {}", e.to_string());
-            }
+    /// 視錐台の中のエンティティの境界ボックスを次のフレームで調べるよう並べ、隠れていると分かったものを返す。<br />
+    /// 描画に使えるオクルージョンクエリがなければ、何も隠れていないことにする。<br />
+    /// Queue the bounding boxes of the entities inside the frustum to be tested in the next frame, returning the ones known to be hidden.<br />
+    /// Without usable occlusion queries, nothing counts as hidden.
+    pub fn queue_occlusion_queries(
+        &self,
+        candidates: &[(DefaultKey, Bounds)],
+        eye: Vec3A,
+    ) -> HashSet<DefaultKey> {
+        if self.occlusion_renderer.lock().is_none() {
+            return HashSet::new();
         }
+        self.occlusion_culler.lock().queue(candidates, eye)
+    }
 
-        let mut all_command_buffers = vec![];
-        // First renderpass
-        let mut renderpass_begin_info = RenderPassBeginInfo::builder()
-            .render_pass(offscreen_renderpass)
-            .clear_values(clear_values.as_slice())
-            .render_area(*render_area)
-            .framebuffer(self.offscreen_pass.framebuffers[0].framebuffer[frame_index]);
+    /// 間接描画のパイプラインを作成する。デバイスが間接描画に対応しなければ何もしない。<br />
+    /// Create the indirect draw pipelines. Nothing is done if the device doesn't support indirect draws.
+    fn create_indirect_pipelines(&mut self) -> anyhow::Result<()> {
+        if !self
+            .physical_device
+            .feature_support
+            .supports_indirect_draw()
+        {
+            log::info!("Indirect draws aren't supported. Models are drawn one by one.");
+            return Ok(());
+        }
+        let renderer = self.indirect_renderer.get_mut();
+        if renderer.is_none() {
+            *renderer = Some(IndirectRenderer::new(
+                self.logical_device.clone(),
+                &self.physical_device,
+                Arc::downgrade(&self.allocator),
+                self.inflight_buffer_count,
+            )?);
+        }
+        let renderer = renderer
+            .as_mut()
+            .expect("Failed to get the indirect renderer.");
+        // パイプラインが揃うまではモデルごとに描く。
+        // Models are drawn one by one until the pipelines are ready.
+        renderer.descriptor_sets.clear();
+        let mut descriptor_sets = vec![];
+        let mut descriptor_set_layout = DescriptorSetLayout::null();
+        for frame_index in 0..self.inflight_buffer_count {
+            let (info_buffer_info, argument_buffer_info) = renderer.get_buffer_infos(frame_index);
+            let mut cache = self.descriptor_layout_cache.lock();
+            let mut allocator = self.descriptor_allocator.lock();
+            let (descriptor_set, layout) = DescriptorBuilder::builder(&mut *cache, &mut *allocator)
+                .bind_buffer(
+                    0,
+                    None,
+                    &info_buffer_info,
+                    DescriptorType::STORAGE_BUFFER,
+                    ShaderStageFlags::VERTEX | ShaderStageFlags::COMPUTE,
+                )
+                .bind_buffer(
+                    1,
+                    None,
+                    &argument_buffer_info,
+                    DescriptorType::STORAGE_BUFFER,
+                    ShaderStageFlags::COMPUTE,
+                )
+                .build()
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Failed to allocate the indirect draw descriptor set.")
+                })?;
+            descriptor_sets.push(descriptor_set);
+            descriptor_set_layout = layout;
+        }
+        let compute_shader = super::Shader::new(
+            self.logical_device.clone(),
+            INDIRECT_CULL_COMPUTE_SHADER,
+            ShaderStageFlags::COMPUTE,
+        )?;
+        let mut pipeline = self
+            .pipeline
+            .write()
+            .expect("Failed to lock pipeline when creating indirect draw pipelines.");
+        pipeline.create_indirect_cull_pipeline(descriptor_set_layout, compute_shader)?;
+        // 代わりのシェーダーは描画の情報を読まないので、読めなければ間接描画を使わない。
+        // Fallback shaders don't read the draw information, so indirect draws are disabled if these can't be read.
+        for shader_type in [ShaderType::IndirectDraw, ShaderType::InstanceIndirectDraw].iter() {
+            let shaders = vec![
+                super::Shader::new(
+                    self.logical_device.clone(),
+                    shader_type.get_vertex_shader_file(),
+                    ShaderStageFlags::VERTEX,
+                )?,
+                super::Shader::new(
+                    self.logical_device.clone(),
+                    shader_type.get_fragment_shader_file(),
+                    ShaderStageFlags::FRAGMENT,
+                )?,
+            ];
+            pipeline.create_graphic_pipelines(
+                &[self.descriptor_set_layout, descriptor_set_layout],
+                self.sample_count,
+                shaders,
+                *shader_type,
+            )?;
+        }
+        drop(pipeline);
+        if let Some(renderer) = self.indirect_renderer.get_mut().as_mut() {
+            renderer.descriptor_sets = descriptor_sets;
+        }
+        Ok(())
+    }
 
-        // この分は元々水面を描画するため書いたコードです。
-        // 水面の描画はまだ実装していませんのでコメントしました。
+    /// 間接描画が使えれば、描画を集めるリストを空にして返す。<br />
+    /// Empty and return the list collecting draws if indirect draws are usable.
+    fn begin_indirect_draws(&self) -> Option<Arc<Mutex<IndirectDrawList<IndirectBatchKey>>>> {
+        let is_ready = self
+            .indirect_renderer
+            .lock()
+            .as_ref()
+            .map(|renderer| renderer.is_ready())
+            .unwrap_or(false);
+        if !is_ready {
+            return None;
+        }
+        self.indirect_draws.lock().clear();
+        Some(self.indirect_draws.clone())
+    }
 
-        /*let inheritance_ptr = {
-            let inheritance_info = Box::new(
-                CommandBufferInheritanceInfo::builder()
-                    .framebuffer(self.offscreen_pass.framebuffers[0].framebuffer[frame_index])
-                    .render_pass(offscreen_renderpass)
-                    .build(),
-            );
-            AtomicPtr::new(Box::into_raw(inheritance_info))
+    /// 集めた間接描画を書き込み、視錐台の外の描画を間引く計算シェーダーを記録する。<br />
+    /// Write the collected indirect draws and record the compute shader culling draws outside the frustum.
+    fn cull_indirect_draws(
+        &self,
+        command_buffer: CommandBuffer,
+        frame_index: usize,
+    ) -> anyhow::Result<()> {
+        let mut renderer = self.indirect_renderer.lock();
+        let renderer = match renderer.as_mut() {
+            Some(renderer) if renderer.is_ready() => renderer,
+            _ => return Ok(()),
         };
-        let inheritance_handle = Arc::new(inheritance_ptr);
-        unsafe {
-            self.logical_device.cmd_begin_render_pass(
-                current_frame.main_command_buffer,
-                &renderpass_begin_info,
-                SubpassContents::SECONDARY_COMMAND_BUFFERS,
-            );
-            self.update_secondary_command_buffers(
-                inheritance_handle,
-                viewports[0],
-                scissors[0],
-                frame_index,
-                scene_type,
-            )?;
-            self.logical_device
-                .cmd_end_render_pass(current_frame.main_command_buffer);
-            //all_command_buffers.append(&mut command_buffers);
-        }*/
-
-        // Second renderpass
-        /*renderpass_begin_info = renderpass_begin_info
-            .framebuffer(self.offscreen_pass.framebuffers[1].framebuffer[frame_index]);
-        render_area = render_area.extent(Extent2D {
-            width: REFRACTION_WIDTH,
-            height: REFRACTION_HEIGHT,
-        });
-        let inheritance_ptr = {
-            let inheritance_info = Box::new(
-                CommandBufferInheritanceInfo::builder()
-                    .framebuffer(self.offscreen_pass.framebuffers[1].framebuffer[frame_index])
-                    .render_pass(offscreen_renderpass)
-                    .build(),
-            );
-            AtomicPtr::new(Box::into_raw(inheritance_info))
+        renderer.upload(frame_index, &*self.indirect_draws.lock())?;
+        let frustum = {
+            let camera = self.camera.borrow();
+            Frustum::new(camera.get_projection_matrix() * camera.get_view_matrix())
         };
-        let inheritance_handle = Arc::new(inheritance_ptr);
+        let pipeline = self
+            .pipeline
+            .read()
+            .expect("Failed to lock pipeline for culling indirect draws.");
         unsafe {
-            self.logical_device.cmd_begin_render_pass(
-                current_frame.main_command_buffer,
-                &renderpass_begin_info,
-                SubpassContents::SECONDARY_COMMAND_BUFFERS,
-            );
-            self.update_secondary_command_buffers(
-                inheritance_handle,
-                viewports[0],
-                scissors[0],
+            renderer.record_culling(
+                command_buffer,
                 frame_index,
-                scene_type,
-            )?;
-            self.logical_device
-                .cmd_end_render_pass(current_frame.main_command_buffer);
-            //all_command_buffers.append(&mut command_buffers);
-        }*/
-
-        // Primary renderpass
-        render_area = render_area.extent(extent);
-        renderpass_begin_info = renderpass_begin_info
-            .render_area(*render_area)
-            .framebuffer(frame_buffer)
-            .render_pass(primary_renderpass);
-
-        let inheritance_ptr = {
-            let inheritance_info = Box::new(
-                CommandBufferInheritanceInfo::builder()
-                    .framebuffer(frame_buffer)
-                    .render_pass(primary_renderpass)
-                    .build(),
-            );
-            AtomicPtr::new(Box::into_raw(inheritance_info))
-        };
-        let inheritance_handle = Arc::new(inheritance_ptr);
-        unsafe {
-            self.logical_device.cmd_begin_render_pass(
-                current_frame.main_command_buffer,
-                &renderpass_begin_info,
-                SubpassContents::SECONDARY_COMMAND_BUFFERS,
+                &frustum.planes,
+                pipeline.indirect_cull_layout,
+                pipeline.indirect_cull_pipeline,
             );
-            let mut command_buffers = self.update_secondary_command_buffers(
-                inheritance_handle,
-                viewports[0],
-                scissors[0],
-                frame_index,
-                renderables,
-            )?;
-            all_command_buffers.append(&mut command_buffers);
-            if !all_command_buffers.is_empty() {
-                self.logical_device.cmd_execute_commands(
-                    current_frame.main_command_buffer,
-                    all_command_buffers.as_slice(),
-                );
-            }
-            self.logical_device
-                .cmd_end_render_pass(current_frame.main_command_buffer);
-            let result = self
-                .logical_device
-                .end_command_buffer(current_frame.main_command_buffer);
-            if let Err(e) = result {
-                log::error!("Error ending command buffer: {}", e.to_string());
-            }
         }
         Ok(())
     }
 
-    /// フレームバッファを生成する。戻り値は`Vec<Framebuffer>`の理由はこのプログラムにはトリプルバッファリングを使うから。<br />
-    /// Create framebuffers. The reason that the return value is `Vec<Framebuffer>` is that this program utilizes triple buffering.
-    fn create_frame_buffers(
-        frame_width: u32,
-        frame_height: u32,
-        renderpass: RenderPass,
-        swapchain: &super::Swapchain,
-        depth_image: &super::Image,
-        msaa_image: &super::Image,
-        device: &Device,
-    ) -> Vec<Framebuffer> {
-        let mut frame_buffers = vec![];
-        let image_count = swapchain.swapchain_images.len();
-        for i in 0..image_count {
-            let image_views = vec![
-                msaa_image.image_view,
-                depth_image.image_view,
-                swapchain.swapchain_images[i].image_view,
-            ];
-            let frame_buffer_info = FramebufferCreateInfo::builder()
-                .height(frame_height)
-                .width(frame_width)
-                .layers(1)
-                .attachments(image_views.as_slice())
-                .render_pass(renderpass);
-            unsafe {
-                frame_buffers.push(
-                    device
-                        .create_framebuffer(&frame_buffer_info, None)
-                        .expect("Failed to create framebuffer."),
-                );
-            }
-        }
-        frame_buffers
+    /// シーンのパスに加える、間接描画の二次コマンドバッファを記録する。<br />
+    /// Record the secondary command buffer of the indirect draws, added to the scene pass.
+    fn record_indirect_draws(
+        &self,
+        inheritance_info: &CommandBufferInheritanceInfo,
+        viewport: Viewport,
+        scissor: Rect2D,
+        frame_index: usize,
+    ) -> anyhow::Result<Option<CommandBuffer>> {
+        let mut renderer = self.indirect_renderer.lock();
+        let renderer = match renderer.as_mut() {
+            Some(renderer) => renderer,
+            None => return Ok(None),
+        };
+        let pipeline = self
+            .pipeline
+            .read()
+            .expect("Failed to lock pipeline for indirect draws.");
+        renderer.record_draws(
+            frame_index,
+            inheritance_info,
+            viewport,
+            scissor,
+            self.push_constant,
+            &pipeline,
+        )
     }
 
-    /// シェーダーのタイプに応じてグラフィックパイプラインを生成する。<br />
-    /// Create graphic pipelines according to the shader type.
-    fn create_graphics_pipeline(&mut self, shader_type: ShaderType) -> anyhow::Result<()> {
+    /// スプライトのパイプラインを作成する。アトラスがまだなければ、描述子セットのレイアウトがないので後で作る。<br />
+    /// Create the sprite pipeline. Without an atlas yet there's no descriptor set layout, so it's created later.
+    fn create_sprite_pipeline(&mut self) -> anyhow::Result<()> {
+        let descriptor_set_layout = match self.sprite_renderer.get_mut().as_ref() {
+            Some(renderer) => renderer.descriptor_set_layout,
+            None => return Ok(()),
+        };
         let shaders = vec![
             super::Shader::new(
                 self.logical_device.clone(),
-                match shader_type {
-                    ShaderType::AnimatedModel => "./shaders/basicShader_animated.spv",
-                    ShaderType::Terrain => "./shaders/terrain_vert.spv",
-                    ShaderType::InstanceDraw => "./shaders/instance_vert.spv",
-                    _ => "./shaders/vert.spv",
-                },
+                SPRITE_VERTEX_SHADER,
                 ShaderStageFlags::VERTEX,
-            ),
+            )?,
             super::Shader::new(
                 self.logical_device.clone(),
-                match shader_type {
-                    ShaderType::BasicShader => "./shaders/frag.spv",
-                    ShaderType::BasicShaderWithoutTexture => "./shaders/basicShader_noTexture.spv",
-                    ShaderType::Terrain => "./shaders/terrain_frag.spv",
-                    ShaderType::Water => "./shaders/water_frag.spv",
-                    ShaderType::InstanceDraw => "./shaders/instance_frag.spv",
-                    _ => "./shaders/frag.spv",
-                },
+                SPRITE_FRAGMENT_SHADER,
                 ShaderStageFlags::FRAGMENT,
-            ),
+            )?,
         ];
-
-        let mut descriptor_set_layout = vec![self.descriptor_set_layout];
-        match shader_type {
-            ShaderType::AnimatedModel => {
-                descriptor_set_layout.push(self.ssbo_descriptor_set_layout);
-            }
-            _ => (),
-        }
         self.pipeline
             .write()
-            .expect("Failed to lock pipeline when creating the pipeline.")
-            .create_graphic_pipelines(
-                descriptor_set_layout.as_slice(),
-                self.sample_count,
-                shaders,
-                shader_type,
-            )?;
-        Ok(())
+            .expect("Failed to lock pipeline when creating the sprite pipeline.")
+            .create_sprite_pipeline(descriptor_set_layout, self.sample_count, shaders)
+    }
+
+    /// このフレームのスプライトを、開いている最後のパスの中に一回の描画で記録する。<br />
+    /// Record this frame's sprites with a single draw inside the open final pass.
+    fn record_sprites(
+        &self,
+        command_buffer: CommandBuffer,
+        extent: Extent2D,
+        frame_index: usize,
+    ) -> anyhow::Result<()> {
+        let mut renderer = self.sprite_renderer.lock();
+        let renderer = match renderer.as_mut() {
+            Some(renderer) => renderer,
+            None => return Ok(()),
+        };
+        let vertices = build_sprite_vertices(
+            self.sprites.lock().as_slice(),
+            extent.width as f32,
+            extent.height as f32,
+        );
+        let pipeline = self
+            .pipeline
+            .read()
+            .expect("Failed to lock pipeline for drawing sprites.");
+        renderer.record_draw(
+            frame_index,
+            command_buffer,
+            &vertices,
+            pipeline.sprite_layout,
+            pipeline.sprite_pipeline,
+        )
     }
 
     /// オフスクリーンレンダパースを生成する。<br />
@@ -1468,7 +3622,7 @@ impl Graphics {
             1,
             ImageAspectFlags::COLOR,
             allocator.clone(),
-        );
+        )?;
 
         let reflection_depth_image = Initializer::create_depth_image(
             device.clone(),
@@ -1481,7 +3635,7 @@ impl Graphics {
             graphics_queue,
             sample_count,
             allocator.clone(),
-        );
+        )?;
 
         let reflection_msaa_image = Initializer::create_msaa_image(
             device.clone(),
@@ -1494,13 +3648,14 @@ impl Graphics {
             graphics_queue,
             sample_count,
             allocator.clone(),
-        );
+        )?;
 
-        let image_views = vec![
+        let image_views = Initializer::get_framebuffer_attachments(
+            sample_count,
             reflection_msaa_image.image_view,
             reflection_depth_image.image_view,
             reflection_image.image_view,
-        ];
+        );
 
         let framebuffer_info = FramebufferCreateInfo::builder()
             .width(REFLECTION_WIDTH)
@@ -1540,7 +3695,7 @@ impl Graphics {
                 1,
                 ImageAspectFlags::COLOR,
                 allocator.clone(),
-            );
+            )?;
 
             let refraction_depth_image = Initializer::create_depth_image(
                 device.clone(),
@@ -1553,7 +3708,7 @@ impl Graphics {
                 graphics_queue,
                 sample_count,
                 allocator.clone(),
-            );
+            )?;
 
             let refraction_msaa_image = Initializer::create_msaa_image(
                 device,
@@ -1566,13 +3721,14 @@ impl Graphics {
                 graphics_queue,
                 sample_count,
                 allocator,
-            );
+            )?;
 
-            let image_views = vec![
+            let image_views = Initializer::get_framebuffer_attachments(
+                sample_count,
                 refraction_msaa_image.image_view,
                 refraction_depth_image.image_view,
                 refraction_image.image_view,
-            ];
+            );
 
             let framebuffer_info = FramebufferCreateInfo::builder()
                 .width(REFRACTION_WIDTH)
@@ -1636,7 +3792,7 @@ impl Graphics {
             BufferUsageFlags::STORAGE_BUFFER,
             MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
             Arc::downgrade(&self.allocator),
-        );
+        )?;
         unsafe {
             let mapped = buffer.map_memory(buffer_size as u64, 0);
             std::ptr::copy_nonoverlapping(
@@ -1662,6 +3818,7 @@ impl Graphics {
             }
         }
         ManuallyDrop::drop(&mut self.offscreen_pass);
+        self.post_process_targets = None;
 
         {
             let pipeline = &mut *self
@@ -1712,39 +3869,44 @@ impl Graphics {
         scissor: Rect2D,
         frame_index: usize,
         renderables: &[LockableRenderable],
-    ) -> anyhow::Result<Vec<CommandBuffer>> {
+        indirect_draws: Option<Arc<Mutex<IndirectDrawList<IndirectBatchKey>>>>,
+    ) -> anyhow::Result<()> {
         {
-            let push_constant = self.push_constant;
-            let ptr = inheritance_info;
+            let context = RenderContext {
+                inheritance_info,
+                viewport,
+                scissor,
+                device: self.logical_device.clone(),
+                pipeline: self.pipeline.clone(),
+                descriptor_sets: self.texture_descriptor_sets.clone(),
+                thread_pool: self.thread_pool.clone(),
+                record_generation: self.record_generation,
+                indirect_draws,
+            };
+            let frame_info = FrameInfo {
+                push_constant: self.push_constant,
+                frame_index,
+            };
             for model in renderables.iter() {
-                let ptr_clone = ptr.clone();
-                let device_clone = self.logical_device.clone();
-                let pipeline_clone = self.pipeline.clone();
-                let descriptor_set = self.descriptor_set;
-                model.lock().render(
-                    ptr_clone,
-                    push_constant,
-                    viewport,
-                    scissor,
-                    device_clone,
-                    pipeline_clone,
-                    descriptor_set,
-                    self.thread_pool.clone(),
-                    frame_index,
-                );
+                model.lock().render(&context, frame_info);
             }
         }
-        self.thread_pool.wait()?;
-        let command_buffers = renderables
-            .iter()
-            .map(|r| r.lock().get_command_buffers(frame_index))
-            .flatten()
-            .collect::<Vec<_>>();
-        Ok(command_buffers)
+        self.thread_pool.wait();
+        let mut command_buffers = self.secondary_command_buffers.lock();
+        command_buffers.clear();
+        for renderable in renderables.iter() {
+            renderable
+                .lock()
+                .append_command_buffers(frame_index, &mut command_buffers);
+        }
+        Ok(())
     }
 }
 
 impl GraphicsBase<super::Buffer, CommandBuffer, super::Image> for Graphics {
+    type UiDrawer = Drawer;
+    type RenderContext = RenderContext;
+
     fn is_initialized(&self) -> bool {
         self.is_initialized
     }
@@ -1756,9 +3918,14 @@ impl GraphicsBase<super::Buffer, CommandBuffer, super::Image> for Graphics {
     unsafe fn wait_idle(&self) {
         for frame in self.frame_data.iter() {
             let fence = [frame.fence];
-            self.logical_device
+            // デバイスを失った後は待てないが、解放は続ける。
+            // Waiting fails once the device is lost, but releasing carries on.
+            if let Err(e) = self
+                .logical_device
                 .wait_for_fences(&fence[0..], true, u64::MAX)
-                .expect("Failed to wait for fences to complete.");
+            {
+                log::warn!("Failed to wait for fences to complete: {}", e);
+            }
         }
     }
 }
@@ -1769,15 +3936,24 @@ unsafe impl Sync for Graphics {}
 impl Drop for Graphics {
     fn drop(&mut self) {
         unsafe {
-            self.logical_device
-                .device_wait_idle()
-                .expect("Failed to wait for device to idle.");
-            self.dispose().expect("Failed to dispose graphics.");
+            // デバイスを失った時も作り直すために解放するので、待てなくても続ける。
+            // A lost device is also released in order to recreate it, so failing to wait isn't fatal.
+            if let Err(e) = self.logical_device.device_wait_idle() {
+                log::warn!("Failed to wait for device to idle: {}", e);
+            }
+            self.secondary_windows.get_mut().clear();
+            *self.particle_renderer.get_mut() = None;
+            *self.debug_draw_renderer.get_mut() = None;
+            *self.occlusion_renderer.get_mut() = None;
+            *self.indirect_renderer.get_mut() = None;
+            *self.sprite_renderer.get_mut() = None;
+            *self.ripple_texture.get_mut() = None;
+            self.destroy_all_deletions();
+            if let Err(e) = self.dispose() {
+                log::error!("Failed to dispose graphics: {}", e);
+            }
+            self.mesh_buffer_pools.clear();
             for frame in self.frame_data.iter() {
-                let fences = [frame.fence];
-                self.logical_device
-                    .wait_for_fences(&fences[0..], true, u64::MAX)
-                    .expect("Failed to wait for fences of graphics.");
                 self.logical_device
                     .destroy_semaphore(frame.completed_semaphore, None);
                 self.logical_device
@@ -1789,7 +3965,12 @@ impl Drop for Graphics {
                 self.logical_device
                     .destroy_command_pool(frame.command_pool, None);
             }
-            for thread in self.thread_pool.threads.iter() {
+            for thread in self
+                .thread_pool
+                .threads
+                .iter()
+                .chain(self.thread_pool.streaming_threads.iter())
+            {
                 for pool in thread.command_pools.iter() {
                     self.logical_device.destroy_command_pool(*pool.lock(), None);
                 }
@@ -1798,6 +3979,9 @@ impl Drop for Graphics {
                 .destroy_descriptor_set_layout(self.ssbo_descriptor_set_layout, None);
             ManuallyDrop::drop(&mut *self.descriptor_layout_cache.lock());
             ManuallyDrop::drop(&mut *self.descriptor_allocator.lock());
+            ManuallyDrop::drop(&mut *self.gpu_timer.lock());
+            ManuallyDrop::drop(&mut self.transfer_queue);
+            ManuallyDrop::drop(&mut self.staging_ring);
             self.allocator
                 .write()
                 .expect("Failed to lock the memory allocator.")
@@ -8,6 +8,7 @@ use vk_mem::{
     Allocation, AllocationCreateFlags, AllocationCreateInfo, AllocationInfo, Allocator, MemoryUsage,
 };
 
+use crate::game::graphics::vk::{GraphicsError, VkResultExt};
 use crate::game::shared::traits::disposable::Disposable;
 use crate::game::shared::traits::mappable::Mappable;
 use crate::game::util::{end_one_time_command_buffer, get_single_time_command_buffer};
@@ -45,7 +46,7 @@ impl Image {
         mip_levels: u32,
         aspect_flags: ImageAspectFlags,
         allocator: Weak<ShardedLock<Allocator>>,
-    ) -> Self {
+    ) -> Result<Self, GraphicsError> {
         let extent = Extent3D::builder()
             .height(extent.height)
             .width(extent.width)
@@ -81,15 +82,19 @@ impl Image {
             pool: None,
             user_data: None,
         };
-        let arc = allocator.upgrade().unwrap();
+        let arc = allocator
+            .upgrade()
+            .ok_or(GraphicsError::Unavailable("memory allocator"))?;
         let lock = arc.read().unwrap();
         let (image, allocation, allocation_info) = lock
             .create_image(&create_info, &allocation_info)
-            .expect("Failed to create image using the VMA allocator.");
+            .map_err(|e| GraphicsError::allocation("create image using the VMA allocator", e))?;
         drop(lock);
         let device_memory = allocation_info.get_device_memory();
         let mapped = allocation_info.get_mapped_data();
-        let _device = device.upgrade().unwrap();
+        let _device = device
+            .upgrade()
+            .ok_or(GraphicsError::Unavailable("logical device"))?;
         let mut image = Image {
             image,
             logical_device: device,
@@ -104,8 +109,8 @@ impl Image {
             allocation,
             allocation_info: Some(allocation_info),
         };
-        image.create_image_view(_device.as_ref(), format, aspect_flags, mip_levels);
-        image
+        image.create_image_view(_device.as_ref(), format, aspect_flags, mip_levels)?;
+        Ok(image)
     }
 
     /// 別のVkImageに基づいて新しいVkImageを生成する。<br />
@@ -117,8 +122,10 @@ impl Image {
         aspect_flags: ImageAspectFlags,
         mip_levels: u32,
         allocator: Weak<ShardedLock<Allocator>>,
-    ) -> Self {
-        let _device = device.upgrade().unwrap();
+    ) -> Result<Self, GraphicsError> {
+        let _device = device
+            .upgrade()
+            .ok_or(GraphicsError::Unavailable("logical device"))?;
         let mut image = Image {
             image,
             logical_device: device,
@@ -133,14 +140,14 @@ impl Image {
             height: 0,
             allocation_info: None,
         };
-        image.create_image_view(_device.as_ref(), format, aspect_flags, mip_levels);
-        image
+        image.create_image_view(_device.as_ref(), format, aspect_flags, mip_levels)?;
+        Ok(image)
     }
 
     /// イメージのレイアウトを転換する。<br />
     /// Transition the layout of this image.
     pub fn transition_layout(
-        &mut self,
+        &self,
         old_layout: ImageLayout,
         new_layout: ImageLayout,
         command_pool: CommandPool,
@@ -189,6 +196,13 @@ impl Image {
                 old_stage = PipelineStageFlags::TOP_OF_PIPE;
                 new_stage = PipelineStageFlags::TRANSFER;
             }
+            (ImageLayout::TRANSFER_DST_OPTIMAL, ImageLayout::SHADER_READ_ONLY_OPTIMAL) => {
+                barrier = barrier
+                    .src_access_mask(AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(AccessFlags::SHADER_READ);
+                old_stage = PipelineStageFlags::TRANSFER;
+                new_stage = PipelineStageFlags::FRAGMENT_SHADER;
+            }
             (_, _) => (),
         }
 
@@ -221,7 +235,11 @@ impl Image {
 
     /// サンプラーを作成する。<br />
     /// Create sampler.
-    pub fn create_sampler(&mut self, mip_levels: u32, sampler_address_mode: SamplerAddressMode) {
+    pub fn create_sampler(
+        &mut self,
+        mip_levels: u32,
+        sampler_address_mode: SamplerAddressMode,
+    ) -> Result<(), GraphicsError> {
         let create_info = SamplerCreateInfo::builder()
             .address_mode_u(sampler_address_mode)
             .address_mode_v(sampler_address_mode)
@@ -239,15 +257,17 @@ impl Image {
             .mipmap_mode(SamplerMipmapMode::LINEAR)
             .unnormalized_coordinates(false)
             .build();
+        let device = self
+            .logical_device
+            .upgrade()
+            .ok_or(GraphicsError::Unavailable("logical device"))?;
         unsafe {
-            self.sampler = self
-                .logical_device
-                .upgrade()
-                .unwrap()
+            self.sampler = device
                 .create_sampler(&create_info, None)
-                .expect("Failed to create sampler.");
+                .or_graphics_error("create sampler")?;
             log::info!("Successfully created sampler.");
         }
+        Ok(())
     }
 
     /// バッファからイメージのデータをコピーする。<br />
@@ -305,7 +325,7 @@ impl Image {
         format: Format,
         aspect_flags: ImageAspectFlags,
         mip_levels: u32,
-    ) {
+    ) -> Result<(), GraphicsError> {
         let create_info = ImageViewCreateInfo::builder()
             .image(self.image)
             .format(format)
@@ -332,12 +352,13 @@ impl Image {
         unsafe {
             self.image_view = device
                 .create_image_view(&create_info, None)
-                .expect("Failed to create image view.");
+                .or_graphics_error("create image view")?;
         }
+        Ok(())
     }
 
     pub unsafe fn generate_mipmap(
-        &mut self,
+        &self,
         aspect_flags: ImageAspectFlags,
         mip_levels: u32,
         command_pool: CommandPool,
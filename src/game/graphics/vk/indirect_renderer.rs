@@ -0,0 +1,410 @@
+use ash::{version::DeviceV1_0, vk::*, Device};
+use crossbeam::sync::ShardedLock;
+use std::ffi::c_void;
+use std::sync::{Arc, Weak};
+use vk_mem::Allocator;
+
+use super::VkResultExt;
+use crate::game::shared::enums::ShaderType;
+use crate::game::shared::structs::{
+    DrawArguments, DrawInfo, IndirectBatch, IndirectCullPushConstant, IndirectDrawList,
+    PushConstant, INDIRECT_CULL_WORKGROUP_SIZE,
+};
+use glam::Vec4;
+
+/// 最初に確保する描画の数。足りなくなれば倍にする。<br />
+/// Number of draws allocated at first, doubled whenever it runs out.
+const INITIAL_DRAW_COUNT: usize = 1024;
+
+/// 一回の間接描画にまとめられる描画を分けるキー。同じキーの描画は同じパイプラインとバッファで描く。<br />
+/// Key separating the draws that can share an indirect draw. Draws with the same key use the same pipeline and buffers.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct IndirectBatchKey {
+    pub shader_type: ShaderType,
+    pub descriptor_set: DescriptorSet,
+    pub vertex_buffer: Buffer,
+    /// インスタンスのバッファ。インスタンス描画でなければヌル。<br />
+    /// The instance buffer. Null unless drawing instances.
+    pub instance_buffer: Buffer,
+    pub index_buffer: Buffer,
+}
+
+/// フレームごとの、描画の引数と情報のバッファ。<br />
+/// Buffers of draw arguments and information for a frame.
+struct FrameBuffers {
+    arguments: super::Buffer,
+    infos: super::Buffer,
+    capacity: usize,
+}
+
+/// シーンのパスで集めた描画を、少ない間接描画で描く。<br />
+/// 引数はフレームごとのバッファに書き込み、計算シェーダーが視錐台の外の描画のインスタンスの数を0にしてから描く。<br />
+/// Draws the draws collected in the scene pass with a few indirect draws.<br />
+/// Arguments are written into a buffer for each frame, and a compute shader zeroes the instance count of draws outside the frustum before drawing.
+pub struct IndirectRenderer {
+    /// フレームごとの描述子セット。パイプラインが揃うまでは空。<br />
+    /// Descriptor sets for each frame, empty until the pipelines are ready.
+    pub descriptor_sets: Vec<DescriptorSet>,
+    logical_device: Arc<Device>,
+    allocator: Weak<ShardedLock<Allocator>>,
+    max_draw_count: u32,
+    frame_buffers: Vec<FrameBuffers>,
+    /// フレームごとに、書き込んだ間接描画。<br />
+    /// Indirect draws written in each frame.
+    batches: Vec<Vec<IndirectBatch<IndirectBatchKey>>>,
+    draw_counts: Vec<u32>,
+    command_pools: Vec<CommandPool>,
+    command_buffers: Vec<CommandBuffer>,
+}
+
+unsafe impl Send for IndirectRenderer {}
+unsafe impl Sync for IndirectRenderer {}
+
+impl IndirectRenderer {
+    pub fn new(
+        logical_device: Arc<Device>,
+        physical_device: &super::PhysicalDevice,
+        allocator: Weak<ShardedLock<Allocator>>,
+        frame_count: usize,
+    ) -> anyhow::Result<Self> {
+        let graphics_family = physical_device
+            .queue_indices
+            .graphics_family
+            .ok_or_else(|| anyhow::anyhow!("The device doesn't have a graphics queue."))?;
+        let pool_info = CommandPoolCreateInfo::builder().queue_family_index(graphics_family);
+        let mut renderer = IndirectRenderer {
+            descriptor_sets: vec![],
+            logical_device,
+            allocator,
+            max_draw_count: physical_device
+                .device_properties
+                .limits
+                .max_draw_indirect_count,
+            frame_buffers: vec![],
+            batches: vec![vec![]; frame_count],
+            draw_counts: vec![0; frame_count],
+            command_pools: vec![],
+            command_buffers: vec![],
+        };
+        for _ in 0..frame_count {
+            let frame_buffers = renderer.create_frame_buffers(INITIAL_DRAW_COUNT)?;
+            renderer.frame_buffers.push(frame_buffers);
+        }
+        unsafe {
+            for _ in 0..frame_count {
+                let pool = renderer
+                    .logical_device
+                    .create_command_pool(&pool_info, None)
+                    .or_graphics_error("create command pool for indirect draws")?;
+                renderer.command_pools.push(pool);
+                let allocate_info = CommandBufferAllocateInfo::builder()
+                    .command_pool(pool)
+                    .command_buffer_count(1)
+                    .level(CommandBufferLevel::SECONDARY);
+                renderer.command_buffers.push(
+                    renderer
+                        .logical_device
+                        .allocate_command_buffers(&allocate_info)
+                        .or_graphics_error("allocate command buffers for indirect draws")?[0],
+                );
+            }
+        }
+        log::info!("Indirect renderer successfully created.");
+        Ok(renderer)
+    }
+
+    /// 描述子セットが参照する、描画の情報のバッファと引数のバッファ。<br />
+    /// The draw information buffer and the argument buffer referenced by the descriptor set.
+    pub fn get_buffer_infos(
+        &self,
+        frame_index: usize,
+    ) -> ([DescriptorBufferInfo; 1], [DescriptorBufferInfo; 1]) {
+        let frame_buffers = &self.frame_buffers[frame_index];
+        (
+            [DescriptorBufferInfo::builder()
+                .buffer(frame_buffers.infos.buffer)
+                .offset(0)
+                .range(frame_buffers.infos.buffer_size)
+                .build()],
+            [DescriptorBufferInfo::builder()
+                .buffer(frame_buffers.arguments.buffer)
+                .offset(0)
+                .range(frame_buffers.arguments.buffer_size)
+                .build()],
+        )
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !self.descriptor_sets.is_empty()
+    }
+
+    /// 集めた描画をまとめ、このフレームのバッファに書き込む。<br />
+    /// フレームのフェンスを待った後に呼ぶので、小さすぎるバッファはその場で作り直して描述子セットを更新できる。<br />
+    /// Group the collected draws and write them into this frame's buffers.<br />
+    /// It's called after waiting for the frame's fence, so a buffer that's too small can be replaced and the descriptor set updated right away.
+    pub fn upload(
+        &mut self,
+        frame_index: usize,
+        draws: &IndirectDrawList<IndirectBatchKey>,
+    ) -> anyhow::Result<()> {
+        let data = draws.build(self.max_draw_count);
+        let draw_count = data.arguments.len();
+        if draw_count > self.frame_buffers[frame_index].capacity {
+            let mut capacity = INITIAL_DRAW_COUNT;
+            while capacity < draw_count {
+                capacity *= 2;
+            }
+            self.frame_buffers[frame_index] = self.create_frame_buffers(capacity)?;
+            self.update_descriptor_set(frame_index);
+        }
+        let frame_buffers = &self.frame_buffers[frame_index];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.arguments.as_ptr() as *const c_void,
+                frame_buffers.arguments.mapped_memory,
+                std::mem::size_of::<DrawArguments>() * draw_count,
+            );
+            std::ptr::copy_nonoverlapping(
+                data.infos.as_ptr() as *const c_void,
+                frame_buffers.infos.mapped_memory,
+                std::mem::size_of::<DrawInfo>() * draw_count,
+            );
+        }
+        self.batches[frame_index] = data.batches;
+        self.draw_counts[frame_index] = draw_count as u32;
+        Ok(())
+    }
+
+    /// 視錐台の外の描画を間引く計算シェーダーを、シーンのパスの前に主なコマンドバッファに記録する。<br />
+    /// Record the compute shader culling draws outside the frustum into the main command buffer, ahead of the scene pass.
+    pub unsafe fn record_culling(
+        &self,
+        command_buffer: CommandBuffer,
+        frame_index: usize,
+        planes: &[Vec4; 6],
+        pipeline_layout: PipelineLayout,
+        pipeline: ash::vk::Pipeline,
+    ) {
+        let draw_count = self.draw_counts[frame_index];
+        if draw_count == 0 || !self.is_ready() || pipeline == ash::vk::Pipeline::null() {
+            return;
+        }
+        let push_constant = IndirectCullPushConstant::new(planes, draw_count);
+        self.logical_device
+            .cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, pipeline);
+        self.logical_device.cmd_bind_descriptor_sets(
+            command_buffer,
+            PipelineBindPoint::COMPUTE,
+            pipeline_layout,
+            0,
+            &[self.descriptor_sets[frame_index]],
+            &[],
+        );
+        self.logical_device.cmd_push_constants(
+            command_buffer,
+            pipeline_layout,
+            ShaderStageFlags::COMPUTE,
+            0,
+            std::slice::from_raw_parts(
+                &push_constant as *const _ as *const u8,
+                std::mem::size_of::<IndirectCullPushConstant>(),
+            ),
+        );
+        self.logical_device.cmd_dispatch(
+            command_buffer,
+            (draw_count + INDIRECT_CULL_WORKGROUP_SIZE - 1) / INDIRECT_CULL_WORKGROUP_SIZE,
+            1,
+            1,
+        );
+        let barrier = BufferMemoryBarrier::builder()
+            .src_access_mask(AccessFlags::SHADER_WRITE)
+            .dst_access_mask(AccessFlags::INDIRECT_COMMAND_READ)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .buffer(self.frame_buffers[frame_index].arguments.buffer)
+            .offset(0)
+            .size(WHOLE_SIZE)
+            .build();
+        self.logical_device.cmd_pipeline_barrier(
+            command_buffer,
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::DRAW_INDIRECT,
+            DependencyFlags::empty(),
+            &[],
+            &[barrier],
+            &[],
+        );
+    }
+
+    /// 書き込んだ間接描画を二次コマンドバッファに記録する。<br />
+    /// プッシュコンスタントのモデルの番号は、その間接描画の最初の描画の番号になる。<br />
+    /// Record the written indirect draws into a secondary command buffer.<br />
+    /// The model index of the push constant becomes the index of the first draw of each indirect draw.
+    pub fn record_draws(
+        &mut self,
+        frame_index: usize,
+        inheritance_info: &CommandBufferInheritanceInfo,
+        viewport: Viewport,
+        scissor: Rect2D,
+        push_constant: PushConstant,
+        pipeline: &super::Pipeline,
+    ) -> anyhow::Result<Option<CommandBuffer>> {
+        if self.batches[frame_index].is_empty() || !self.is_ready() {
+            return Ok(None);
+        }
+        let command_buffer = self.command_buffers[frame_index];
+        let argument_buffer = self.frame_buffers[frame_index].arguments.buffer;
+        let stride = std::mem::size_of::<DrawArguments>() as u32;
+        let mut push_constant = push_constant;
+        unsafe {
+            self.logical_device
+                .reset_command_pool(
+                    self.command_pools[frame_index],
+                    CommandPoolResetFlags::empty(),
+                )
+                .or_graphics_error("reset the indirect draw command pool")?;
+            let begin_info = CommandBufferBeginInfo::builder()
+                .flags(
+                    CommandBufferUsageFlags::ONE_TIME_SUBMIT
+                        | CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+                )
+                .inheritance_info(inheritance_info);
+            self.logical_device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .or_graphics_error("begin the indirect draw command buffer")?;
+            self.logical_device
+                .cmd_set_viewport(command_buffer, 0, &[viewport]);
+            self.logical_device
+                .cmd_set_scissor(command_buffer, 0, &[scissor]);
+            let mut bound_shader_type = None;
+            for batch in self.batches[frame_index].iter() {
+                let key = batch.key;
+                let pipeline_layout = pipeline.get_pipeline_layout(key.shader_type);
+                if bound_shader_type != Some(key.shader_type) {
+                    self.logical_device.cmd_bind_pipeline(
+                        command_buffer,
+                        PipelineBindPoint::GRAPHICS,
+                        pipeline.get_pipeline(key.shader_type, 0),
+                    );
+                    bound_shader_type = Some(key.shader_type);
+                }
+                self.logical_device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    PipelineBindPoint::GRAPHICS,
+                    pipeline_layout,
+                    0,
+                    &[key.descriptor_set, self.descriptor_sets[frame_index]],
+                    &[],
+                );
+                push_constant.model_index = batch.first_draw as usize;
+                let casted = bytemuck::cast::<PushConstant, [u8; 32]>(push_constant);
+                self.logical_device.cmd_push_constants(
+                    command_buffer,
+                    pipeline_layout,
+                    ShaderStageFlags::FRAGMENT | ShaderStageFlags::VERTEX,
+                    0,
+                    &casted[0..],
+                );
+                if key.instance_buffer == Buffer::null() {
+                    self.logical_device.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        0,
+                        &[key.vertex_buffer],
+                        &[0],
+                    );
+                } else {
+                    self.logical_device.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        0,
+                        &[key.vertex_buffer, key.instance_buffer],
+                        &[0, 0],
+                    );
+                }
+                self.logical_device.cmd_bind_index_buffer(
+                    command_buffer,
+                    key.index_buffer,
+                    0,
+                    IndexType::UINT32,
+                );
+                self.logical_device.cmd_draw_indexed_indirect(
+                    command_buffer,
+                    argument_buffer,
+                    (batch.first_draw * stride) as DeviceSize,
+                    batch.draw_count,
+                    stride,
+                );
+            }
+            self.logical_device
+                .end_command_buffer(command_buffer)
+                .or_graphics_error("end the indirect draw command buffer")?;
+        }
+        Ok(Some(command_buffer))
+    }
+
+    fn create_frame_buffers(&self, capacity: usize) -> anyhow::Result<FrameBuffers> {
+        let arguments = super::Buffer::new(
+            Arc::downgrade(&self.logical_device),
+            (std::mem::size_of::<DrawArguments>() * capacity) as DeviceSize,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::INDIRECT_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            self.allocator.clone(),
+        )?;
+        let infos = super::Buffer::new(
+            Arc::downgrade(&self.logical_device),
+            (std::mem::size_of::<DrawInfo>() * capacity) as DeviceSize,
+            BufferUsageFlags::STORAGE_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            self.allocator.clone(),
+        )?;
+        Ok(FrameBuffers {
+            arguments,
+            infos,
+            capacity,
+        })
+    }
+
+    /// 作り直したバッファを、このフレームの描述子セットに書き込む。<br />
+    /// Write the recreated buffers into this frame's descriptor set.
+    fn update_descriptor_set(&self, frame_index: usize) {
+        let descriptor_set = match self.descriptor_sets.get(frame_index) {
+            Some(descriptor_set) => *descriptor_set,
+            None => return,
+        };
+        let (info_buffer_info, argument_buffer_info) = self.get_buffer_infos(frame_index);
+        let writes = [
+            WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&info_buffer_info)
+                .build(),
+            WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&argument_buffer_info)
+                .build(),
+        ];
+        unsafe {
+            self.logical_device.update_descriptor_sets(&writes, &[]);
+        }
+    }
+}
+
+impl Drop for IndirectRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.logical_device.device_wait_idle() {
+                log::warn!("Failed to wait for device to idle: {}", e);
+            }
+            for (pool, command_buffer) in self.command_pools.iter().zip(self.command_buffers.iter())
+            {
+                self.logical_device
+                    .free_command_buffers(*pool, &[*command_buffer]);
+                self.logical_device.destroy_command_pool(*pool, None);
+            }
+        }
+        self.frame_buffers.clear();
+        log::info!("Indirect renderer successfully dropped.");
+    }
+}
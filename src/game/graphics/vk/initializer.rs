@@ -1,10 +1,15 @@
-use crate::game::enums::ImageFormat;
-use crate::game::graphics::vk::Graphics;
-use crate::game::structs::{Directional, ViewProjection};
-use crate::game::traits::Mappable;
-use crate::game::util::{
-    end_one_time_command_buffer, get_single_time_command_buffer, interpolate_alpha,
+use crate::game::enums::{ImageFormat, PresentMode};
+use crate::game::graphics::vk::staging_ring::STAGING_ALIGNMENT;
+use crate::game::graphics::vk::{
+    Graphics, ImageLevel, StreamedTextureSource, TextureSource, VkResultExt,
+};
+use crate::game::structs::{
+    create_checker_pixels, record_validation_message, AssetKind, Directional, Ktx2Texture,
+    TranscodeTarget, ValidationSeverity, ViewProjection, MSAA_SAMPLE_COUNTS, PLACEHOLDER_CELL_SIZE,
+    PLACEHOLDER_TEXTURE_SIZE,
 };
+use crate::game::traits::Mappable;
+use crate::game::util::interpolate_alpha;
 use crate::game::Camera;
 use anyhow::Context;
 use ash::extensions::ext::DebugUtils;
@@ -62,13 +67,16 @@ impl Initializer {
         unsafe {
             let instance = entry
                 .create_instance(&instance_info, None)
-                .expect("Failed to create Vulkan instance.");
+                .with_context(|| "Failed to create Vulkan instance.")?;
             log::info!("Vulkan instance successfully created.");
             Ok(instance)
         }
     }
 
-    pub fn create_debug_messenger(instance: &Instance, entry: &Entry) -> DebugUtilsMessengerEXT {
+    pub fn create_debug_messenger(
+        instance: &Instance,
+        entry: &Entry,
+    ) -> anyhow::Result<DebugUtilsMessengerEXT> {
         let create_info = DebugUtilsMessengerCreateInfoEXT::builder()
             .message_severity(
                 DebugUtilsMessageSeverityFlagsEXT::ERROR
@@ -81,9 +89,9 @@ impl Initializer {
         unsafe {
             let messenger = debug_utils_loader
                 .create_debug_utils_messenger(&create_info, None)
-                .expect("Failed to create debug messenger.");
+                .or_graphics_error("create debug messenger")?;
             log::info!("Debug messenger successfully created.");
-            messenger
+            Ok(messenger)
         }
     }
 
@@ -104,15 +112,22 @@ impl Initializer {
         physical_device: &super::PhysicalDevice,
         enabled_layers: &[CString],
         debug: bool,
-    ) -> (ash::Device, Queue, Queue, Queue) {
+    ) -> anyhow::Result<(ash::Device, Queue, Queue, Queue, Queue)> {
         let layers = enabled_layers
             .iter()
             .map(|s| s.as_ptr())
             .collect::<Vec<_>>();
-        let extensions = vec![Swapchain::name()];
-        /*if debug {
+        let mut extensions = vec![Swapchain::name()];
+        // 描述子インデックスの機能は拡張機能がある時だけ問い合わせたので、どれかが使えれば拡張機能もある。
+        // Descriptor indexing features were only queried when the extension exists, so any of them being available means the extension is too.
+        if physical_device.uses_descriptor_indexing_extension
+            && physical_device.feature_support.runtime_descriptor_array
+        {
+            extensions.push(ash::vk::ExtDescriptorIndexingFn::name());
+        }
+        if debug && physical_device.supports_diagnostic_checkpoints {
             extensions.push(ash::vk::NvDeviceDiagnosticCheckpointsFn::name());
-        }*/
+        }
         let extensions = extensions.iter().map(|e| e.as_ptr()).collect::<Vec<_>>();
         let features = PhysicalDeviceFeatures::builder()
             .tessellation_shader(physical_device.feature_support.tessellation_shader)
@@ -124,14 +139,41 @@ impl Initializer {
             .sampler_anisotropy(physical_device.feature_support.sampler_anisotropy)
             .sample_rate_shading(physical_device.feature_support.sample_rate_shading)
             .geometry_shader(physical_device.feature_support.geometry_shader)
-            .shader_clip_distance(physical_device.feature_support.shader_clip_distance);
+            .multi_draw_indirect(physical_device.feature_support.multi_draw_indirect)
+            .shader_clip_distance(physical_device.feature_support.shader_clip_distance)
+            .texture_compression_bc(physical_device.feature_support.texture_compression_bc)
+            .texture_compression_astc_ldr(
+                physical_device.feature_support.texture_compression_astc_ldr,
+            );
         let mut indexing_features = PhysicalDeviceDescriptorIndexingFeatures::builder()
             .runtime_descriptor_array(physical_device.feature_support.runtime_descriptor_array)
             .descriptor_binding_partially_bound(
                 physical_device
                     .feature_support
                     .descriptor_binding_partially_bound,
+            )
+            .shader_sampled_image_array_non_uniform_indexing(
+                physical_device
+                    .feature_support
+                    .shader_sampled_image_array_non_uniform_indexing,
+            )
+            .descriptor_binding_sampled_image_update_after_bind(
+                physical_device
+                    .feature_support
+                    .descriptor_binding_sampled_image_update_after_bind,
+            )
+            .descriptor_binding_variable_descriptor_count(
+                physical_device
+                    .feature_support
+                    .descriptor_binding_variable_descriptor_count,
+            )
+            .descriptor_binding_update_unused_while_pending(
+                physical_device
+                    .feature_support
+                    .descriptor_binding_update_unused_while_pending,
             );
+        let mut draw_parameters_features = PhysicalDeviceShaderDrawParametersFeatures::builder()
+            .shader_draw_parameters(physical_device.feature_support.shader_draw_parameters);
         let mut queue_create_infos = vec![];
         let mut unique_indices = HashSet::new();
         unique_indices.insert(
@@ -152,6 +194,9 @@ impl Initializer {
                 .compute_family
                 .unwrap_or_default(),
         );
+        if let Some(transfer_family) = physical_device.queue_indices.transfer_family {
+            unique_indices.insert(transfer_family);
+        }
         let priority = [1.0_f32];
         for index in unique_indices.iter() {
             let queue_create_info = DeviceQueueCreateInfo::builder()
@@ -164,6 +209,7 @@ impl Initializer {
             .enabled_extension_names(extensions.as_slice())
             .enabled_features(&features)
             .push_next(&mut indexing_features)
+            .push_next(&mut draw_parameters_features)
             .queue_create_infos(queue_create_infos.as_ref());
         if debug {
             create_info = create_info.enabled_layer_names(layers.as_slice());
@@ -171,7 +217,7 @@ impl Initializer {
         unsafe {
             let device = instance
                 .create_device(physical_device.physical_device, &create_info, None)
-                .expect("Failed to create logical device.");
+                .or_graphics_error("create logical device")?;
             let graphics_queue = device.get_device_queue(
                 physical_device
                     .queue_indices
@@ -193,9 +239,21 @@ impl Initializer {
                     .unwrap_or_default(),
                 0,
             );
+            // 専用の転送キューがなければ、グラフィックスキューでアップロードする。
+            // Without a dedicated transfer queue, uploads go through the graphics queue.
+            let transfer_queue = match physical_device.queue_indices.transfer_family {
+                Some(transfer_family) => device.get_device_queue(transfer_family, 0),
+                None => graphics_queue,
+            };
             log::info!("Device queue successfully acquired.");
             log::info!("Logical device successfully created.");
-            (device, graphics_queue, present_queue, compute_queue)
+            Ok((
+                device,
+                graphics_queue,
+                present_queue,
+                compute_queue,
+                transfer_queue,
+            ))
         }
     }
 
@@ -207,7 +265,8 @@ impl Initializer {
         instance: &Instance,
         device: Weak<ash::Device>,
         allocator: Weak<ShardedLock<Allocator>>,
-    ) -> super::Swapchain {
+        present_mode: PresentMode,
+    ) -> anyhow::Result<super::Swapchain> {
         super::Swapchain::new(
             surface_loader,
             surface,
@@ -217,7 +276,17 @@ impl Initializer {
             instance,
             device,
             allocator,
+            Self::get_present_mode(present_mode),
         )
+        .map_err(anyhow::Error::from)
+    }
+
+    pub fn get_present_mode(present_mode: PresentMode) -> PresentModeKHR {
+        match present_mode {
+            PresentMode::Fifo => PresentModeKHR::FIFO,
+            PresentMode::Mailbox => PresentModeKHR::MAILBOX,
+            PresentMode::Immediate => PresentModeKHR::IMMEDIATE,
+        }
     }
 
     pub fn choose_depth_format(
@@ -274,8 +343,8 @@ impl Initializer {
         graphics_queue: Queue,
         sample_count: SampleCountFlags,
         allocator: Weak<ShardedLock<Allocator>>,
-    ) -> super::Image {
-        let mut image = super::Image::new(
+    ) -> anyhow::Result<super::Image> {
+        let image = super::Image::new(
             device,
             ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
             MemoryPropertyFlags::DEVICE_LOCAL,
@@ -286,7 +355,7 @@ impl Initializer {
             1,
             ImageAspectFlags::DEPTH,
             allocator,
-        );
+        )?;
         image.transition_layout(
             ImageLayout::UNDEFINED,
             ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
@@ -297,40 +366,57 @@ impl Initializer {
             None,
         );
         log::info!("Depth image successfully created.");
-        image
+        Ok(image)
     }
 
-    pub fn get_sample_count(
+    /// カラーと深度の両方のフレームバッファーが対応している、設定で選べるサンプル数を取得する。<br />
+    /// Get the selectable sample counts supported by both color and depth framebuffers.
+    pub fn get_supported_msaa_samples(
         instance: &Instance,
         physical_device: &super::PhysicalDevice,
-    ) -> SampleCountFlags {
+    ) -> Vec<u32> {
         unsafe {
             let properties =
                 instance.get_physical_device_properties(physical_device.physical_device);
-            let sample_count: SampleCountFlags =
-                properties.limits.sampled_image_color_sample_counts;
-            let supported_samples: SampleCountFlags;
-            supported_samples =
-                if (sample_count & SampleCountFlags::TYPE_64) == SampleCountFlags::TYPE_64 {
-                    SampleCountFlags::TYPE_64
-                } else if (sample_count & SampleCountFlags::TYPE_32) == SampleCountFlags::TYPE_32 {
-                    SampleCountFlags::TYPE_32
-                } else if (sample_count & SampleCountFlags::TYPE_16) == SampleCountFlags::TYPE_16 {
-                    SampleCountFlags::TYPE_16
-                } else if (sample_count & SampleCountFlags::TYPE_8) == SampleCountFlags::TYPE_8 {
-                    SampleCountFlags::TYPE_8
-                } else if (sample_count & SampleCountFlags::TYPE_4) == SampleCountFlags::TYPE_4 {
-                    SampleCountFlags::TYPE_4
-                } else if (sample_count & SampleCountFlags::TYPE_32) == SampleCountFlags::TYPE_2 {
-                    SampleCountFlags::TYPE_2
-                } else {
-                    SampleCountFlags::TYPE_1
-                };
-            log::info!("Sample count: {:?}", supported_samples);
+            let sample_counts = properties.limits.framebuffer_color_sample_counts
+                & properties.limits.framebuffer_depth_sample_counts;
+            let supported_samples = MSAA_SAMPLE_COUNTS
+                .iter()
+                .copied()
+                .filter(|samples| sample_counts.contains(Self::get_sample_count(*samples)))
+                .collect::<Vec<_>>();
+            log::info!("Supported sample counts: {:?}", supported_samples);
             supported_samples
         }
     }
 
+    pub fn get_sample_count(samples: u32) -> SampleCountFlags {
+        match samples {
+            64 => SampleCountFlags::TYPE_64,
+            32 => SampleCountFlags::TYPE_32,
+            16 => SampleCountFlags::TYPE_16,
+            8 => SampleCountFlags::TYPE_8,
+            4 => SampleCountFlags::TYPE_4,
+            2 => SampleCountFlags::TYPE_2,
+            _ => SampleCountFlags::TYPE_1,
+        }
+    }
+
+    /// フレームバッファーのアタッチメントを並べる。MSAAを使う時は描画先に解決する。<br />
+    /// Arrange the attachments of a framebuffer. With MSAA the multisampled image is resolved into the target.
+    pub fn get_framebuffer_attachments(
+        sample_count: SampleCountFlags,
+        msaa_view: ImageView,
+        depth_view: ImageView,
+        target_view: ImageView,
+    ) -> Vec<ImageView> {
+        if sample_count == SampleCountFlags::TYPE_1 {
+            vec![target_view, depth_view]
+        } else {
+            vec![msaa_view, depth_view, target_view]
+        }
+    }
+
     pub fn create_msaa_image(
         device: Weak<ash::Device>,
         format: Format,
@@ -339,8 +425,8 @@ impl Initializer {
         graphics_queue: Queue,
         sample_count: SampleCountFlags,
         allocator: Weak<ShardedLock<Allocator>>,
-    ) -> super::Image {
-        let mut image = super::image::Image::new(
+    ) -> anyhow::Result<super::Image> {
+        let image = super::image::Image::new(
             device,
             ImageUsageFlags::TRANSIENT_ATTACHMENT | ImageUsageFlags::COLOR_ATTACHMENT,
             MemoryPropertyFlags::DEVICE_LOCAL,
@@ -351,7 +437,7 @@ impl Initializer {
             1,
             ImageAspectFlags::COLOR,
             allocator,
-        );
+        )?;
         image.transition_layout(
             ImageLayout::UNDEFINED,
             ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
@@ -362,7 +448,7 @@ impl Initializer {
             None,
         );
         log::info!("Msaa image successfully created.");
-        image
+        Ok(image)
     }
 
     pub fn create_view_projection(
@@ -380,7 +466,7 @@ impl Initializer {
                 BufferUsageFlags::UNIFORM_BUFFER,
                 MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
                 allocator,
-            );
+            )?;
             let mapped = vp_buffer.map_memory(u64::try_from(vp_size)?, 0);
             std::ptr::copy_nonoverlapping(
                 &view_projection as *const _ as *const c_void,
@@ -404,14 +490,16 @@ impl Initializer {
                 BufferUsageFlags::UNIFORM_BUFFER,
                 MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE,
                 allocator,
-            );
+            )?;
             let mapped = dl_buffer.map_memory(u64::try_from(dl_size)?, 0);
             std::ptr::copy(directional as *const _ as *const c_void, mapped, dl_size);
             Ok(dl_buffer)
         }
     }
 
-    pub fn create_ssbo_descriptor_set_layout(device: &ash::Device) -> DescriptorSetLayout {
+    pub fn create_ssbo_descriptor_set_layout(
+        device: &ash::Device,
+    ) -> anyhow::Result<DescriptorSetLayout> {
         let layout_bindings = vec![DescriptorSetLayoutBinding::builder()
             .binding(0)
             .descriptor_count(1)
@@ -423,9 +511,9 @@ impl Initializer {
         unsafe {
             let descriptor_set_layout = device
                 .create_descriptor_set_layout(&create_info, None)
-                .expect("Failed to create descriptor set layout for ssbo.");
+                .or_graphics_error("create descriptor set layout for ssbo")?;
             log::info!("Descriptor set layout for ssbo successfully created.");
-            descriptor_set_layout
+            Ok(descriptor_set_layout)
         }
     }
 
@@ -433,47 +521,178 @@ impl Initializer {
         device: &ash::Device,
         command_pool: CommandPool,
         image_count: u32,
-    ) -> Vec<CommandBuffer> {
+    ) -> anyhow::Result<Vec<CommandBuffer>> {
         let command_buffer_info = CommandBufferAllocateInfo::builder()
             .command_pool(command_pool)
             .command_buffer_count(image_count)
             .level(CommandBufferLevel::PRIMARY);
         unsafe {
-            device
+            Ok(device
                 .allocate_command_buffers(&command_buffer_info)
-                .expect("Failed to allocate command buffers.")
+                .or_graphics_error("allocate command buffers")?)
         }
     }
 
-    pub fn create_sync_object(device: &ash::Device) -> (Fence, Semaphore, Semaphore) {
+    pub fn create_sync_object(
+        device: &ash::Device,
+    ) -> anyhow::Result<(Fence, Semaphore, Semaphore)> {
         let fence_info = FenceCreateInfo::builder().flags(FenceCreateFlags::SIGNALED);
         let semaphore_info = SemaphoreCreateInfo::builder();
         unsafe {
             let fence = device
                 .create_fence(&fence_info, None)
-                .expect("Failed to create fence.");
+                .or_graphics_error("create fence")?;
             let acquired_semaphore = device
                 .create_semaphore(&semaphore_info, None)
-                .expect("Failed to create semaphore.");
+                .or_graphics_error("create semaphore")?;
             let completed_semaphore = device
                 .create_semaphore(&semaphore_info, None)
-                .expect("Failed to create semaphore.");
+                .or_graphics_error("create semaphore")?;
             log::info!("Sync objects successfully created.");
-            (fence, acquired_semaphore, completed_semaphore)
+            Ok((fence, acquired_semaphore, completed_semaphore))
         }
     }
 
+    /// ファイルからイメージを生成する。読み込めなければ警告を出し、代わりのテクスチャを返す。<br />
+    /// `is_streamed`なら低い解像度のミップだけで作り、高いミップは後からストリーミングする。<br />
+    /// Create an image from a file. If it can't be loaded, a warning is reported and the placeholder texture is returned.<br />
+    /// With `is_streamed` it's created from the low resolution mips only, streaming the higher mips in later.
     pub fn create_image_from_file(
         file_name: &str,
         graphics: Arc<RwLock<ManuallyDrop<Graphics>>>,
         command_pool: Arc<Mutex<CommandPool>>,
         sampler_address_mode: SamplerAddressMode,
+        is_streamed: bool,
     ) -> anyhow::Result<(Arc<ShardedLock<super::Image>>, usize)> {
-        let resource_manager = graphics.read().resource_manager.clone();
+        match Self::load_image_from_file(
+            file_name,
+            graphics.clone(),
+            command_pool.clone(),
+            sampler_address_mode,
+            is_streamed,
+        ) {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                graphics
+                    .read()
+                    .report_asset_warning(AssetKind::Texture, file_name, &e);
+                Self::get_placeholder_texture(graphics, command_pool)
+            }
+        }
+    }
+
+    /// 全ての読み込みの失敗で共有する、マゼンタのチェッカーのテクスチャ。最初に要求された時に作る。<br />
+    /// Magenta checker texture shared by every failed load. Created the first time it's requested.
+    pub fn get_placeholder_texture(
+        graphics: Arc<RwLock<ManuallyDrop<Graphics>>>,
+        command_pool: Arc<Mutex<CommandPool>>,
+    ) -> anyhow::Result<(Arc<ShardedLock<super::Image>>, usize)> {
+        let resource_manager = graphics
+            .read()
+            .resource_manager
+            .upgrade()
+            .with_context(|| "Failed to upgrade resource manager.")?;
+        if let Some(placeholder) = resource_manager.read().get_placeholder_texture() {
+            return Ok(placeholder);
+        }
+        let texture = Self::create_placeholder_image(graphics, command_pool)?;
+        let placeholder = resource_manager.write().set_placeholder_texture(texture);
+        Ok(placeholder)
+    }
+
+    /// 共有せずに、新しくマゼンタのチェッカーのイメージを作る。<br />
+    /// Create a new magenta checker image that isn't shared.
+    pub fn create_placeholder_image(
+        graphics: Arc<RwLock<ManuallyDrop<Graphics>>>,
+        command_pool: Arc<Mutex<CommandPool>>,
+    ) -> anyhow::Result<super::Image> {
+        let pixels = create_checker_pixels(PLACEHOLDER_TEXTURE_SIZE, PLACEHOLDER_CELL_SIZE);
+        let buffer_size = pixels.len() as DeviceSize;
+        Self::create_image_from_raw(
+            pixels,
+            buffer_size,
+            PLACEHOLDER_TEXTURE_SIZE,
+            PLACEHOLDER_TEXTURE_SIZE,
+            ImageFormat::ColorType(image::ColorType::Rgba8),
+            graphics,
+            command_pool,
+            SamplerAddressMode::REPEAT,
+        )
+    }
+
+    fn load_image_from_file(
+        file_name: &str,
+        graphics: Arc<RwLock<ManuallyDrop<Graphics>>>,
+        command_pool: Arc<Mutex<CommandPool>>,
+        sampler_address_mode: SamplerAddressMode,
+        is_streamed: bool,
+    ) -> anyhow::Result<(Arc<ShardedLock<super::Image>>, usize)> {
+        let (resource_manager, texture_streamer) = {
+            let lock = graphics.read();
+            (lock.resource_manager.clone(), lock.texture_streamer.clone())
+        };
         let resource_manager = match resource_manager.upgrade() {
             None => panic!("Failed to upgrade resource manager."),
             Some(rm) => rm,
         };
+        let is_streamed = is_streamed && texture_streamer.is_enabled();
+        if file_name.to_lowercase().ends_with(".ktx2") {
+            let source = if is_streamed {
+                let bytes = std::fs::read(file_name)
+                    .with_context(|| format!("Failed to read KTX2 texture: {}", file_name))?;
+                let texture = Ktx2Texture::parse(&bytes)
+                    .with_context(|| format!("Failed to parse KTX2 texture: {}", file_name))?;
+                Some(StreamedTextureSource {
+                    source: TextureSource::Ktx2 {
+                        path: file_name.to_string(),
+                        mip_count: texture.levels.len() as u32,
+                    },
+                    width: texture.width,
+                    height: texture.height,
+                    sampler_address_mode,
+                })
+            } else {
+                None
+            };
+            let first_level = source
+                .as_ref()
+                .map(StreamedTextureSource::get_low_res_mip)
+                .unwrap_or_default();
+            let texture = Self::create_image_from_ktx2(
+                file_name,
+                first_level as usize,
+                graphics,
+                command_pool,
+                sampler_address_mode,
+            )?;
+            let mut rm_lock = resource_manager.write();
+            let image = rm_lock.add_texture(texture);
+            let texture_index = rm_lock.get_texture_count() - 1;
+            drop(rm_lock);
+            if let Some(source) = source {
+                texture_streamer.register(texture_index, source);
+            }
+            return Ok((image, texture_index));
+        }
+        if is_streamed {
+            let (width, height) = image::image_dimensions(file_name)?;
+            let source = StreamedTextureSource {
+                source: TextureSource::File(file_name.to_string()),
+                width,
+                height,
+                sampler_address_mode,
+            };
+            let low_res_mip = source.get_low_res_mip();
+            if low_res_mip > 0 {
+                let texture = source.create_image(low_res_mip, graphics, command_pool)?;
+                let mut rm_lock = resource_manager.write();
+                let image = rm_lock.add_texture(texture);
+                let texture_index = rm_lock.get_texture_count() - 1;
+                drop(rm_lock);
+                texture_streamer.register(texture_index, source);
+                return Ok((image, texture_index));
+            }
+        }
         let image = image::open(file_name)?;
         let buffer_size;
         let bytes = match image.color() {
@@ -534,40 +753,31 @@ impl Initializer {
         command_pool: Arc<Mutex<ash::vk::CommandPool>>,
         sampler_address_mode: SamplerAddressMode,
     ) -> anyhow::Result<super::Image> {
+        if let ImageFormat::Compressed(_) = format {
+            let mut image_data = image_data;
+            image_data.truncate(buffer_size as usize);
+            return Self::create_image_from_levels(
+                image_data,
+                vec![ImageLevel {
+                    offset: 0,
+                    width,
+                    height,
+                }],
+                format,
+                graphics,
+                command_pool,
+                sampler_address_mode,
+            );
+        }
         let lock = graphics.read();
         let device = lock.logical_device.clone();
         let allocator = lock.allocator.clone();
-        let image_format = match format {
-            ImageFormat::GltfFormat(gltf_format) => match gltf_format {
-                gltf::image::Format::B8G8R8A8 => ash::vk::Format::B8G8R8A8_UNORM,
-                gltf::image::Format::R8G8B8A8 => ash::vk::Format::R8G8B8A8_UNORM,
-                _ => lock.swapchain.format.format,
-            },
-            ImageFormat::VkFormat(vk_format) => vk_format,
-            ImageFormat::ColorType(color_type) => match color_type {
-                image::ColorType::Bgra8 => ash::vk::Format::B8G8R8A8_UNORM,
-                image::ColorType::Rgba8 => ash::vk::Format::R8G8B8A8_UNORM,
-                image::ColorType::L16 => ash::vk::Format::R16_UNORM,
-                _ => lock.swapchain.format.format,
-            },
-        };
-        let cmd_buffer = get_single_time_command_buffer(device.as_ref(), *command_pool.lock());
+        let image_format = Self::get_vk_format(format, &lock);
+        let transfer_queue = lock.get_transfer_queue();
+        let staging_ring = lock.get_staging_ring();
+        drop(lock);
 
-        let mut staging = super::Buffer::new(
-            Arc::downgrade(&device),
-            buffer_size,
-            BufferUsageFlags::TRANSFER_SRC,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-            Arc::downgrade(&allocator),
-        );
-        unsafe {
-            let mapped = staging.map_memory(buffer_size, 0);
-            std::ptr::copy_nonoverlapping(
-                image_data.as_ptr() as *const c_void,
-                mapped,
-                buffer_size as usize,
-            );
-        }
+        let staging = staging_ring.write(&image_data[..buffer_size as usize])?;
         let width = width as f32;
         let height = height as f32;
         let mip_levels = width.max(height).log2().floor() as u32;
@@ -587,42 +797,196 @@ impl Initializer {
             mip_levels,
             ImageAspectFlags::COLOR,
             Arc::downgrade(&allocator),
-        );
-        let pool_lock = command_pool.lock();
-        image.transition_layout(
-            ImageLayout::UNDEFINED,
-            ImageLayout::TRANSFER_DST_OPTIMAL,
-            *pool_lock,
-            *lock.graphics_queue.lock(),
-            ImageAspectFlags::COLOR,
-            mip_levels,
-            Some(cmd_buffer),
-        );
-        image.copy_buffer_to_image(
-            staging.buffer,
-            width as u32,
-            height as u32,
-            *pool_lock,
-            *lock.graphics_queue.lock(),
-            Some(cmd_buffer),
-        );
-        unsafe {
-            image.generate_mipmap(
+        )?;
+        // 描画を止めないよう、転送キューでアップロードしてこのテクスチャのフェンスだけを待つ。
+        // Upload on the transfer queue and only wait for this texture's fence so rendering isn't stalled.
+        transfer_queue
+            .upload_image(
+                &command_pool,
+                &image,
+                &staging,
                 ImageAspectFlags::COLOR,
                 mip_levels,
-                *pool_lock,
-                *lock.graphics_queue.lock(),
-                Some(cmd_buffer),
+            )
+            .with_context(|| "Failed to upload texture through the transfer queue.")?;
+        image.create_sampler(mip_levels, sampler_address_mode)?;
+        Ok(image)
+    }
+
+    /// 全てのミップレベルが揃ったデータからイメージを作る。圧縮テクスチャはこちらでアップロードする。<br />
+    /// Create an image from data that already contains every mip level. Compressed textures are uploaded through here.
+    pub fn create_image_from_levels(
+        image_data: Vec<u8>,
+        levels: Vec<ImageLevel>,
+        format: ImageFormat,
+        graphics: Arc<RwLock<ManuallyDrop<Graphics>>>,
+        command_pool: Arc<Mutex<ash::vk::CommandPool>>,
+        sampler_address_mode: SamplerAddressMode,
+    ) -> anyhow::Result<super::Image> {
+        let base_level = *levels
+            .first()
+            .with_context(|| "Failed to create an image without mip levels.")?;
+        let lock = graphics.read();
+        let device = lock.logical_device.clone();
+        let allocator = lock.allocator.clone();
+        let image_format = Self::get_vk_format(format, &lock);
+        let transfer_queue = lock.get_transfer_queue();
+        let staging_ring = lock.get_staging_ring();
+        drop(lock);
+
+        let staging = staging_ring.write(&image_data)?;
+        let mip_levels = levels.len() as u32;
+        let mut image = super::Image::new(
+            Arc::downgrade(&device),
+            ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::SAMPLED,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            image_format,
+            SampleCountFlags::TYPE_1,
+            Extent2D::builder()
+                .width(base_level.width)
+                .height(base_level.height)
+                .build(),
+            ImageType::TYPE_2D,
+            mip_levels,
+            ImageAspectFlags::COLOR,
+            Arc::downgrade(&allocator),
+        )?;
+        transfer_queue
+            .upload_image_levels(
+                &command_pool,
+                &image,
+                &staging,
+                &levels,
+                ImageAspectFlags::COLOR,
+            )
+            .with_context(|| "Failed to upload texture levels through the transfer queue.")?;
+        image.create_sampler(mip_levels, sampler_address_mode)?;
+        Ok(image)
+    }
+
+    /// KTX2のテクスチャを読み込む。<br />
+    /// UASTCはデバイスが対応する中で最も小さい形式に変換し、圧縮テクスチャに対応しなければRGBAに展開する。<br />
+    /// それ以外の形式はファイルのまま、デバイスが対応している場合だけアップロードする。`first_level`より前のレベルは読み込まない。<br />
+    /// Load a KTX2 texture.<br />
+    /// UASTC is transcoded into the smallest format the device supports, or unpacked to RGBA without compressed texture support.<br />
+    /// Other formats are uploaded as stored in the file, only when the device supports them. Levels before `first_level` aren't loaded.
+    pub fn create_image_from_ktx2(
+        file_name: &str,
+        first_level: usize,
+        graphics: Arc<RwLock<ManuallyDrop<Graphics>>>,
+        command_pool: Arc<Mutex<CommandPool>>,
+        sampler_address_mode: SamplerAddressMode,
+    ) -> anyhow::Result<super::Image> {
+        let bytes = std::fs::read(file_name)
+            .with_context(|| format!("Failed to read KTX2 texture: {}", file_name))?;
+        let texture = Ktx2Texture::parse(&bytes)
+            .with_context(|| format!("Failed to parse KTX2 texture: {}", file_name))?;
+        let (supports_bc7, supports_astc, supports_file_format) = {
+            let lock = graphics.read();
+            let feature_support = lock.physical_device.feature_support;
+            (
+                feature_support.texture_compression_bc
+                    && Self::supports_sampling(&lock, Format::BC7_UNORM_BLOCK),
+                feature_support.texture_compression_astc_ldr
+                    && Self::supports_sampling(&lock, Format::ASTC_4X4_UNORM_BLOCK),
+                Self::supports_sampling(&lock, texture.format),
+            )
+        };
+
+        let target = TranscodeTarget::choose(supports_bc7, supports_astc);
+        let (format, is_compressed) = if texture.is_uastc {
+            (target.get_format(), target.is_compressed())
+        } else if supports_file_format {
+            (texture.format, true)
+        } else {
+            anyhow::bail!(
+                "The device can't sample {:?}, the format of {}.",
+                texture.format,
+                file_name
+            );
+        };
+        if first_level >= texture.levels.len() {
+            anyhow::bail!(
+                "{} has no mip level {}. It only has {} levels.",
+                file_name,
+                first_level,
+                texture.levels.len()
             );
         }
-        image.create_sampler(mip_levels, sampler_address_mode);
-        end_one_time_command_buffer(
-            cmd_buffer,
-            device.as_ref(),
-            *pool_lock,
-            *lock.graphics_queue.lock(),
+        log::info!(
+            "Loading {} as {:?} with {} mip levels.",
+            file_name,
+            format,
+            texture.levels.len() - first_level
         );
-        Ok(image)
+
+        let mut image_data = vec![];
+        let mut levels = vec![];
+        for level in first_level..texture.levels.len() {
+            let (width, height) = texture.get_level_extent(level);
+            // 各レベルをステージングの境界に揃える。
+            // Align each level to the staging alignment.
+            let alignment = STAGING_ALIGNMENT as usize;
+            image_data.resize(
+                (image_data.len() + alignment - 1) / alignment * alignment,
+                0,
+            );
+            levels.push(ImageLevel {
+                offset: image_data.len() as DeviceSize,
+                width,
+                height,
+            });
+            if texture.is_uastc {
+                image_data.extend(texture.transcode_level(&bytes, level, target)?);
+            } else {
+                image_data.extend_from_slice(&texture.get_level_data(&bytes, level)?);
+            }
+        }
+
+        Self::create_image_from_levels(
+            image_data,
+            levels,
+            if is_compressed {
+                ImageFormat::Compressed(format)
+            } else {
+                ImageFormat::VkFormat(format)
+            },
+            graphics,
+            command_pool,
+            sampler_address_mode,
+        )
+    }
+
+    fn supports_sampling(graphics: &Graphics, format: Format) -> bool {
+        if format == Format::UNDEFINED {
+            return false;
+        }
+        let properties = unsafe {
+            graphics.instance.get_physical_device_format_properties(
+                graphics.physical_device.physical_device,
+                format,
+            )
+        };
+        properties
+            .optimal_tiling_features
+            .contains(FormatFeatureFlags::SAMPLED_IMAGE)
+    }
+
+    fn get_vk_format(format: ImageFormat, graphics: &Graphics) -> Format {
+        match format {
+            ImageFormat::GltfFormat(gltf_format) => match gltf_format {
+                gltf::image::Format::B8G8R8A8 => ash::vk::Format::B8G8R8A8_UNORM,
+                gltf::image::Format::R8G8B8A8 => ash::vk::Format::R8G8B8A8_UNORM,
+                _ => graphics.swapchain.format.format,
+            },
+            ImageFormat::VkFormat(vk_format) | ImageFormat::Compressed(vk_format) => vk_format,
+            ImageFormat::ColorType(color_type) => match color_type {
+                image::ColorType::Bgra8 => ash::vk::Format::B8G8R8A8_UNORM,
+                image::ColorType::Rgba8 => ash::vk::Format::R8G8B8A8_UNORM,
+                image::ColorType::L16 => ash::vk::Format::R16_UNORM,
+                _ => graphics.swapchain.format.format,
+            },
+        }
     }
 
     fn get_required_extensions(
@@ -638,11 +1002,13 @@ impl Initializer {
             .collect::<Vec<_>>();
         if debug {
             let instance_extensions = entry.enumerate_instance_extension_properties()?;
-            let _nv_checkpoint_extension =
+            // 診断のチェックポイントの拡張機能は、Vulkan 1.0ではこのインスタンスの拡張機能が要る。
+            // The diagnostic checkpoint extension needs this instance extension on Vulkan 1.0.
+            let nv_checkpoint_extension =
                 std::ffi::CString::new("VK_KHR_get_physical_device_properties2")
                     .expect("Failed to construct extension name.");
-            let required_debug_extensions = vec![DebugUtils::name().to_owned()];
-            //required_debug_extensions.push(nv_checkpoint_extension);
+            let required_debug_extensions =
+                vec![DebugUtils::name().to_owned(), nv_checkpoint_extension];
             for extension in instance_extensions.iter() {
                 let extension_name = extension.extension_name.as_ptr();
                 unsafe {
@@ -672,8 +1038,14 @@ impl Initializer {
             }
             match severity {
                 DebugUtilsMessageSeverityFlagsEXT::VERBOSE => log::info!("{}", msg),
-                DebugUtilsMessageSeverityFlagsEXT::WARNING => log::warn!("{}", msg),
-                DebugUtilsMessageSeverityFlagsEXT::ERROR => log::error!("{}", msg),
+                DebugUtilsMessageSeverityFlagsEXT::WARNING => {
+                    record_validation_message(ValidationSeverity::Warning, msg);
+                    log::warn!("{}", msg);
+                }
+                DebugUtilsMessageSeverityFlagsEXT::ERROR => {
+                    record_validation_message(ValidationSeverity::Error, msg);
+                    log::error!("{}", msg);
+                }
                 _ => (),
             }
         }
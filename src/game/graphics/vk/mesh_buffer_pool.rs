@@ -0,0 +1,460 @@
+use ash::vk::{BufferUsageFlags, DeviceSize, MemoryPropertyFlags};
+use ash::Device;
+use crossbeam::sync::ShardedLock;
+use parking_lot::Mutex;
+use std::sync::{Arc, Weak};
+use vk_mem::Allocator;
+
+use super::GraphicsError;
+
+/// インデックス一つの大きさ。<br />
+/// Size of a single index.
+pub const INDEX_SIZE: DeviceSize = std::mem::size_of::<u32>() as DeviceSize;
+
+/// メッシュのバッファを切り出すプールの種類。<br />
+/// Kinds of pools mesh buffers are cut out of.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MeshPool {
+    /// 読み込みで作り、ほとんど変わらないメッシュ。デバイスローカルのメモリーに転送キューでアップロードする。<br />
+    /// Meshes created while loading that rarely change, uploaded into device local memory on the transfer queue.
+    Static,
+    /// プレイ中に作って捨てるメッシュ。ホストから見えるメモリーに直接書き込む。<br />
+    /// 静的なプールを断片化させないように分けている。<br />
+    /// Meshes created and thrown away during play, written directly into host visible memory.<br />
+    /// Kept apart so that they don't fragment the static pool.
+    Dynamic,
+}
+
+impl MeshPool {
+    /// ブロックごとの頂点バッファとインデックスバッファの大きさ。<br />
+    /// Sizes of the vertex buffer and the index buffer of each block.
+    pub fn get_block_sizes(self) -> (DeviceSize, DeviceSize) {
+        match self {
+            MeshPool::Static => (64 * 1024 * 1024, 16 * 1024 * 1024),
+            MeshPool::Dynamic => (8 * 1024 * 1024, 2 * 1024 * 1024),
+        }
+    }
+
+    pub fn get_memory_properties(self) -> MemoryPropertyFlags {
+        match self {
+            MeshPool::Static => MemoryPropertyFlags::DEVICE_LOCAL,
+            MeshPool::Dynamic => {
+                MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT
+            }
+        }
+    }
+}
+
+/// バッファの中の範囲。<br />
+/// A range inside a buffer.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct BufferRange {
+    pub offset: DeviceSize,
+    pub size: DeviceSize,
+}
+
+impl BufferRange {
+    pub fn new(offset: DeviceSize, size: DeviceSize) -> Self {
+        BufferRange { offset, size }
+    }
+
+    pub fn get_end(&self) -> DeviceSize {
+        self.offset + self.size
+    }
+}
+
+/// 空いている範囲のリストで、一つのバッファの中の範囲を管理する。<br />
+/// 最初に収まる範囲から切り出し、返された範囲は隣の空きとまとめる。<br />
+/// Keeps track of ranges inside one buffer with a list of free ranges.<br />
+/// Ranges are cut out of the first free range they fit in, and returned ranges are merged with their free neighbours.
+#[derive(Clone, Debug)]
+pub struct FreeListAllocator {
+    capacity: DeviceSize,
+    /// オフセットの順に並べた空いている範囲。<br />
+    /// Free ranges sorted by offset.
+    free_ranges: Vec<BufferRange>,
+}
+
+impl FreeListAllocator {
+    pub fn new(capacity: DeviceSize) -> Self {
+        FreeListAllocator {
+            capacity,
+            free_ranges: vec![BufferRange::new(0, capacity)],
+        }
+    }
+
+    pub fn get_capacity(&self) -> DeviceSize {
+        self.capacity
+    }
+
+    pub fn get_free_size(&self) -> DeviceSize {
+        self.free_ranges.iter().map(|range| range.size).sum()
+    }
+
+    pub fn get_largest_free_size(&self) -> DeviceSize {
+        self.free_ranges
+            .iter()
+            .map(|range| range.size)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// 範囲を確保する。境界は2の累乗でなくてもいい。収まる空きがなければ`None`を返す。<br />
+    /// Allocate a range. The alignment doesn't have to be a power of two. Returns `None` if no free range fits.
+    pub fn allocate(&mut self, size: DeviceSize, alignment: DeviceSize) -> Option<BufferRange> {
+        if size == 0 || size > self.capacity {
+            return None;
+        }
+        let alignment = alignment.max(1);
+        let index = self.free_ranges.iter().position(|range| {
+            let offset = align(range.offset, alignment);
+            offset + size <= range.get_end()
+        })?;
+        let free_range = self.free_ranges.remove(index);
+        let offset = align(free_range.offset, alignment);
+        let allocated = BufferRange::new(offset, size);
+        // 揃えた分の前の空きと、後ろに残った空きは空いたままにする。
+        // The space skipped by the alignment and the space left behind stay free.
+        if allocated.get_end() < free_range.get_end() {
+            self.free_ranges.insert(
+                index,
+                BufferRange::new(
+                    allocated.get_end(),
+                    free_range.get_end() - allocated.get_end(),
+                ),
+            );
+        }
+        if offset > free_range.offset {
+            self.free_ranges.insert(
+                index,
+                BufferRange::new(free_range.offset, offset - free_range.offset),
+            );
+        }
+        Some(allocated)
+    }
+
+    /// 範囲を返す。隣の空きと繋がっていれば一つにまとめる。<br />
+    /// Return a range, merging it with the free ranges it touches.
+    pub fn free(&mut self, range: BufferRange) {
+        if range.size == 0 {
+            return;
+        }
+        let index = match self
+            .free_ranges
+            .binary_search_by_key(&range.offset, |free_range| free_range.offset)
+        {
+            Ok(_) => {
+                log::warn!("Range at offset {} is already free.", range.offset);
+                return;
+            }
+            Err(index) => index,
+        };
+        self.free_ranges.insert(index, range);
+        if index + 1 < self.free_ranges.len()
+            && self.free_ranges[index].get_end() == self.free_ranges[index + 1].offset
+        {
+            self.free_ranges[index].size += self.free_ranges[index + 1].size;
+            self.free_ranges.remove(index + 1);
+        }
+        if index > 0 && self.free_ranges[index - 1].get_end() == self.free_ranges[index].offset {
+            self.free_ranges[index - 1].size += self.free_ranges[index].size;
+            self.free_ranges.remove(index);
+        }
+    }
+}
+
+fn align(offset: DeviceSize, alignment: DeviceSize) -> DeviceSize {
+    (offset + alignment - 1) / alignment * alignment
+}
+
+/// 一つのメッシュが使う、ブロックの中の頂点とインデックスの範囲。<br />
+/// The vertex and index ranges inside a block used by a single mesh.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct MeshRange {
+    pub block_index: usize,
+    pub vertices: BufferRange,
+    pub indices: BufferRange,
+    pub vertex_stride: DeviceSize,
+}
+
+impl MeshRange {
+    /// 描画の`vertexOffset`に渡す、最初の頂点の番号。<br />
+    /// Index of the first vertex, passed to the draw's `vertexOffset`.
+    pub fn get_first_vertex(&self) -> i32 {
+        (self.vertices.offset / self.vertex_stride) as i32
+    }
+
+    /// 描画の`firstIndex`に渡す、最初のインデックスの番号。<br />
+    /// Number of the first index, passed to the draw's `firstIndex`.
+    pub fn get_first_index(&self) -> u32 {
+        (self.indices.offset / INDEX_SIZE) as u32
+    }
+}
+
+/// 頂点とインデックスのブロックの組から、メッシュの範囲を切り出す。<br />
+/// どのブロックにも収まらなければ、新しいブロックを足す。<br />
+/// Cuts mesh ranges out of pairs of vertex and index blocks.<br />
+/// A new block is added when no block has room.
+#[derive(Clone, Debug)]
+pub struct MeshRangeAllocator {
+    vertex_block_size: DeviceSize,
+    index_block_size: DeviceSize,
+    blocks: Vec<(FreeListAllocator, FreeListAllocator)>,
+}
+
+impl MeshRangeAllocator {
+    pub fn new(vertex_block_size: DeviceSize, index_block_size: DeviceSize) -> Self {
+        MeshRangeAllocator {
+            vertex_block_size,
+            index_block_size,
+            blocks: vec![],
+        }
+    }
+
+    pub fn get_block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// 頂点が`vertex_stride`の倍数のオフセットに来るように範囲を確保する。<br />
+    /// メッシュがブロックより大きければ`None`を返す。<br />
+    /// Allocate ranges so that the vertices start at a multiple of `vertex_stride`.<br />
+    /// Returns `None` if the mesh is larger than a block.
+    pub fn allocate(
+        &mut self,
+        vertex_size: DeviceSize,
+        vertex_stride: DeviceSize,
+        index_size: DeviceSize,
+    ) -> Option<MeshRange> {
+        if vertex_size == 0
+            || index_size == 0
+            || vertex_size > self.vertex_block_size
+            || index_size > self.index_block_size
+        {
+            return None;
+        }
+        let vertex_stride = vertex_stride.max(1);
+        let found = self.blocks.iter_mut().enumerate().find_map(
+            |(block_index, (vertex_block, index_block))| {
+                allocate_in_block(
+                    vertex_block,
+                    index_block,
+                    vertex_size,
+                    vertex_stride,
+                    index_size,
+                )
+                .map(|ranges| (block_index, ranges))
+            },
+        );
+        let (block_index, (vertices, indices)) = match found {
+            Some(found) => found,
+            None => {
+                self.blocks.push((
+                    FreeListAllocator::new(self.vertex_block_size),
+                    FreeListAllocator::new(self.index_block_size),
+                ));
+                let block_index = self.blocks.len() - 1;
+                let (vertex_block, index_block) = &mut self.blocks[block_index];
+                (
+                    block_index,
+                    allocate_in_block(
+                        vertex_block,
+                        index_block,
+                        vertex_size,
+                        vertex_stride,
+                        index_size,
+                    )?,
+                )
+            }
+        };
+        Some(MeshRange {
+            block_index,
+            vertices,
+            indices,
+            vertex_stride,
+        })
+    }
+
+    pub fn free(&mut self, range: &MeshRange) {
+        if let Some((vertex_block, index_block)) = self.blocks.get_mut(range.block_index) {
+            vertex_block.free(range.vertices);
+            index_block.free(range.indices);
+        }
+    }
+}
+
+/// 一つのブロックで範囲を確保する。インデックスの範囲が確保できなければ、頂点の範囲も解放する。<br />
+/// Allocate ranges in one block. If the index range can't be allocated, the vertex range is freed again.
+fn allocate_in_block(
+    vertex_block: &mut FreeListAllocator,
+    index_block: &mut FreeListAllocator,
+    vertex_size: DeviceSize,
+    vertex_stride: DeviceSize,
+    index_size: DeviceSize,
+) -> Option<(BufferRange, BufferRange)> {
+    let vertices = vertex_block.allocate(vertex_size, vertex_stride)?;
+    match index_block.allocate(index_size, INDEX_SIZE) {
+        Some(indices) => Some((vertices, indices)),
+        None => {
+            vertex_block.free(vertices);
+            None
+        }
+    }
+}
+
+/// 多くのメッシュで共有する、一組の頂点バッファとインデックスバッファ。<br />
+/// A pair of vertex and index buffers shared by many meshes.
+pub struct MeshBufferBlock {
+    pub vertex_buffer: super::Buffer,
+    pub index_buffer: super::Buffer,
+}
+
+unsafe impl Send for MeshBufferBlock {}
+unsafe impl Sync for MeshBufferBlock {}
+
+/// 大きな頂点バッファとインデックスバッファのブロックから、メッシュごとの範囲を切り出す。<br />
+/// 同じブロックのメッシュは同じバッファをバインドし、オフセットだけを変えて描く。<br />
+/// Sub-allocates per-mesh ranges out of blocks of large vertex and index buffers.<br />
+/// Meshes in the same block bind the same buffers and are drawn with different offsets only.
+pub struct MeshBufferPool {
+    pool: MeshPool,
+    ranges: MeshRangeAllocator,
+    blocks: Vec<Arc<MeshBufferBlock>>,
+    logical_device: Weak<Device>,
+    allocator: Weak<ShardedLock<Allocator>>,
+}
+
+impl MeshBufferPool {
+    pub fn new(
+        pool: MeshPool,
+        device: Weak<Device>,
+        allocator: Weak<ShardedLock<Allocator>>,
+    ) -> Self {
+        let (vertex_block_size, index_block_size) = pool.get_block_sizes();
+        MeshBufferPool {
+            pool,
+            ranges: MeshRangeAllocator::new(vertex_block_size, index_block_size),
+            blocks: vec![],
+            logical_device: device,
+            allocator,
+        }
+    }
+
+    pub fn get_pool(&self) -> MeshPool {
+        self.pool
+    }
+
+    pub fn get_block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// メッシュの範囲を確保する。足りなければ新しいブロックのバッファを作る。<br />
+    /// メッシュがブロックより大きければ`None`を返すので、専用のバッファを作ること。<br />
+    /// Allocate a mesh's ranges, creating the buffers of a new block if needed.<br />
+    /// Returns `None` if the mesh is larger than a block, in which case dedicated buffers should be created.
+    pub fn allocate(
+        pool: &Arc<Mutex<Self>>,
+        vertex_size: DeviceSize,
+        vertex_stride: DeviceSize,
+        index_size: DeviceSize,
+    ) -> Result<Option<MeshBufferAllocation>, GraphicsError> {
+        let mut lock = pool.lock();
+        let range = match lock.ranges.allocate(vertex_size, vertex_stride, index_size) {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+        if range.block_index >= lock.blocks.len() {
+            match lock.create_block() {
+                Ok(block) => lock.blocks.push(Arc::new(block)),
+                Err(e) => {
+                    lock.ranges.free(&range);
+                    return Err(e);
+                }
+            }
+            log::info!(
+                "Added a block to the {:?} mesh buffer pool. Block count: {}",
+                lock.pool,
+                lock.blocks.len()
+            );
+        }
+        let block = lock.blocks[range.block_index].clone();
+        Ok(Some(MeshBufferAllocation(Arc::new(AllocationHandle {
+            pool: Arc::downgrade(pool),
+            block,
+            range,
+        }))))
+    }
+
+    fn create_block(&self) -> Result<MeshBufferBlock, GraphicsError> {
+        let (vertex_block_size, index_block_size) = self.pool.get_block_sizes();
+        let transfer_usage = match self.pool {
+            MeshPool::Static => BufferUsageFlags::TRANSFER_DST,
+            MeshPool::Dynamic => BufferUsageFlags::empty(),
+        };
+        let vertex_buffer = super::Buffer::new(
+            self.logical_device.clone(),
+            vertex_block_size,
+            BufferUsageFlags::VERTEX_BUFFER | transfer_usage,
+            self.pool.get_memory_properties(),
+            self.allocator.clone(),
+        )?;
+        let index_buffer = super::Buffer::new(
+            self.logical_device.clone(),
+            index_block_size,
+            BufferUsageFlags::INDEX_BUFFER | transfer_usage,
+            self.pool.get_memory_properties(),
+            self.allocator.clone(),
+        )?;
+        Ok(MeshBufferBlock {
+            vertex_buffer,
+            index_buffer,
+        })
+    }
+
+    fn free(&mut self, range: &MeshRange) {
+        self.ranges.free(range);
+    }
+}
+
+struct AllocationHandle {
+    pool: Weak<Mutex<MeshBufferPool>>,
+    block: Arc<MeshBufferBlock>,
+    range: MeshRange,
+}
+
+impl Drop for AllocationHandle {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.upgrade() {
+            pool.lock().free(&self.range);
+        }
+    }
+}
+
+/// プールから切り出したメッシュの範囲。最後の複製がドロップされるとプールに戻る。<br />
+/// A mesh's ranges cut out of a pool, returned to the pool when the last clone is dropped.
+#[derive(Clone)]
+pub struct MeshBufferAllocation(Arc<AllocationHandle>);
+
+impl MeshBufferAllocation {
+    pub fn get_block(&self) -> &MeshBufferBlock {
+        self.0.block.as_ref()
+    }
+
+    pub fn get_range(&self) -> &MeshRange {
+        &self.0.range
+    }
+
+    pub fn get_vertex_buffer(&self) -> ash::vk::Buffer {
+        self.0.block.vertex_buffer.buffer
+    }
+
+    pub fn get_index_buffer(&self) -> ash::vk::Buffer {
+        self.0.block.index_buffer.buffer
+    }
+}
+
+/// メッシュの頂点バッファとインデックスバッファ。プールの範囲か、専用のバッファ。<br />
+/// A mesh's vertex and index buffers, either ranges of a pool or dedicated buffers.
+pub enum MeshBuffers {
+    Pooled(MeshBufferAllocation),
+    Dedicated(super::Buffer, super::Buffer),
+}
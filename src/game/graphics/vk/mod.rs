@@ -1,24 +1,61 @@
 pub mod buffer;
+pub mod debug_draw_renderer;
 pub mod descriptor;
 pub mod dynamic_object;
+pub mod error;
+pub mod gpu_timer;
 pub mod graphics;
 pub mod image;
+pub mod indirect_renderer;
 pub mod initializer;
+pub mod mesh_buffer_pool;
+pub mod occlusion_renderer;
+pub mod particle_renderer;
 pub mod physical_device;
 pub mod pipeline;
+pub mod post_process;
+pub mod render_context;
+pub mod render_graph;
+pub mod ripple_texture;
+pub mod secondary_window;
 pub mod shader;
+pub mod sprite_renderer;
+pub mod staging_ring;
 pub mod swapchain;
+pub mod texture_streamer;
 pub mod thread;
+pub mod transfer_queue;
 pub mod uniform_buffers;
 pub use self::image::Image;
 pub use buffer::Buffer;
+pub use debug_draw_renderer::DebugDrawRenderer;
 pub use descriptor::*;
 pub use dynamic_object::*;
-pub use graphics::Graphics;
+pub use error::{GraphicsError, VkResultExt};
+pub use gpu_timer::GpuTimer;
+pub use graphics::{DeferredDeletion, Graphics};
+pub use indirect_renderer::{IndirectBatchKey, IndirectRenderer};
 pub use initializer::Initializer;
+pub use mesh_buffer_pool::{
+    BufferRange, MeshBufferAllocation, MeshBufferBlock, MeshBufferPool, MeshBuffers, MeshPool,
+    MeshRange,
+};
+pub use occlusion_renderer::OcclusionRenderer;
+pub use particle_renderer::ParticleRenderer;
 pub use physical_device::PhysicalDevice;
 pub use pipeline::{Pipeline, RenderPassType};
+pub use post_process::{
+    get_post_process_render_passes, get_post_process_target, PostProcessTargets, HDR_FORMAT,
+};
+pub use render_context::RenderContext;
+pub use render_graph::{derive_subpass_dependencies, FramebufferSource, PassTarget};
+pub use ripple_texture::RippleTexture;
+pub use secondary_window::SecondaryWindow;
 pub use shader::Shader;
+pub use sprite_renderer::SpriteRenderer;
+pub use staging_ring::{StagingRegion, StagingRing, STAGING_RING_SIZE};
 pub use swapchain::Swapchain;
+pub use texture_streamer::{StreamedTextureSource, TextureSource, TextureStreamer, TextureSwap};
 pub use thread::*;
+pub use transfer_queue::{BufferUpload, ImageLevel, TransferQueue};
 pub use uniform_buffers::UniformBuffers;
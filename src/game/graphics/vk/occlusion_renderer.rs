@@ -0,0 +1,213 @@
+use ash::{version::DeviceV1_0, vk::*, Device};
+use std::sync::Arc;
+
+use super::VkResultExt;
+use crate::game::shared::structs::{OcclusionPushConstant, OcclusionQuery, MAX_OCCLUSION_QUERIES};
+
+/// シーンのパスで境界ボックスを描き、オクルージョンクエリで描いたサンプルを数える。<br />
+/// モデルの二次コマンドバッファの後に実行するので、深度はもう書き込まれている。<br />
+/// Draws bounding boxes in the scene pass and counts the samples drawn with occlusion queries.<br />
+/// It's executed after the secondary command buffers of models, so the depth has already been written.
+pub struct OcclusionRenderer {
+    pub descriptor_set: DescriptorSet,
+    logical_device: Arc<Device>,
+    query_pools: Vec<QueryPool>,
+    /// フレームごとに、記録したクエリの数。<br />
+    /// Number of queries recorded in each frame.
+    query_counts: Vec<usize>,
+    command_pools: Vec<CommandPool>,
+    command_buffers: Vec<CommandBuffer>,
+}
+
+unsafe impl Send for OcclusionRenderer {}
+unsafe impl Sync for OcclusionRenderer {}
+
+impl OcclusionRenderer {
+    pub fn new(
+        logical_device: Arc<Device>,
+        physical_device: &super::PhysicalDevice,
+        frame_count: usize,
+    ) -> anyhow::Result<Self> {
+        let graphics_family = physical_device
+            .queue_indices
+            .graphics_family
+            .ok_or_else(|| anyhow::anyhow!("The device doesn't have a graphics queue."))?;
+        let pool_info = CommandPoolCreateInfo::builder().queue_family_index(graphics_family);
+        let query_pool_info = QueryPoolCreateInfo::builder()
+            .query_type(QueryType::OCCLUSION)
+            .query_count(MAX_OCCLUSION_QUERIES as u32);
+        let mut renderer = OcclusionRenderer {
+            descriptor_set: DescriptorSet::null(),
+            logical_device,
+            query_pools: vec![],
+            query_counts: vec![0; frame_count],
+            command_pools: vec![],
+            command_buffers: vec![],
+        };
+        unsafe {
+            for _ in 0..frame_count {
+                let query_pool = renderer
+                    .logical_device
+                    .create_query_pool(&query_pool_info, None)
+                    .or_graphics_error("create query pool for occlusion queries")?;
+                renderer.query_pools.push(query_pool);
+                let pool = renderer
+                    .logical_device
+                    .create_command_pool(&pool_info, None)
+                    .or_graphics_error("create command pool for occlusion queries")?;
+                renderer.command_pools.push(pool);
+                let allocate_info = CommandBufferAllocateInfo::builder()
+                    .command_pool(pool)
+                    .command_buffer_count(1)
+                    .level(CommandBufferLevel::SECONDARY);
+                renderer.command_buffers.push(
+                    renderer
+                        .logical_device
+                        .allocate_command_buffers(&allocate_info)
+                        .or_graphics_error("allocate command buffers for occlusion queries")?[0],
+                );
+            }
+        }
+        log::info!("Occlusion renderer successfully created.");
+        Ok(renderer)
+    }
+
+    /// 前回このフレームで記録したクエリの、描いたサンプルの数を読む。フレームのフェンスを待ってから呼び出す。<br />
+    /// Read the number of samples drawn by the queries last recorded in this frame. Called after waiting for the frame's fence.
+    pub unsafe fn read_results(&mut self, frame_index: usize) -> Vec<u64> {
+        let query_count = std::mem::take(&mut self.query_counts[frame_index]);
+        if query_count == 0 {
+            return vec![];
+        }
+        let mut samples = vec![0_u64; query_count];
+        let result = self.logical_device.get_query_pool_results(
+            self.query_pools[frame_index],
+            0,
+            query_count as u32,
+            samples.as_mut_slice(),
+            QueryResultFlags::TYPE_64,
+        );
+        if result.is_err() {
+            return vec![];
+        }
+        samples
+    }
+
+    /// フレームのクエリをリセットする。レンダーパスの外で、コマンドバッファの最初に記録する。<br />
+    /// Reset the queries of the frame, recorded at the beginning of the command buffer outside any render pass.
+    pub unsafe fn begin_frame(&mut self, command_buffer: CommandBuffer, frame_index: usize) {
+        self.query_counts[frame_index] = 0;
+        self.logical_device.cmd_reset_query_pool(
+            command_buffer,
+            self.query_pools[frame_index],
+            0,
+            MAX_OCCLUSION_QUERIES as u32,
+        );
+    }
+
+    /// クエリごとに境界ボックスを描く二次コマンドバッファを記録する。<br />
+    /// Record the secondary command buffer drawing a bounding box for each query.
+    pub fn record_queries(
+        &mut self,
+        frame_index: usize,
+        inheritance_info: &CommandBufferInheritanceInfo,
+        viewport: Viewport,
+        scissor: Rect2D,
+        queries: &[OcclusionQuery],
+        pipeline_layout: PipelineLayout,
+        pipeline: ash::vk::Pipeline,
+    ) -> anyhow::Result<Option<CommandBuffer>> {
+        if queries.is_empty()
+            || self.descriptor_set == DescriptorSet::null()
+            || pipeline == ash::vk::Pipeline::null()
+        {
+            return Ok(None);
+        }
+        let queries = &queries[..queries.len().min(MAX_OCCLUSION_QUERIES)];
+        let command_buffer = self.command_buffers[frame_index];
+        let query_pool = self.query_pools[frame_index];
+        unsafe {
+            self.logical_device
+                .reset_command_pool(
+                    self.command_pools[frame_index],
+                    CommandPoolResetFlags::empty(),
+                )
+                .or_graphics_error("reset the occlusion command pool")?;
+            let begin_info = CommandBufferBeginInfo::builder()
+                .flags(
+                    CommandBufferUsageFlags::ONE_TIME_SUBMIT
+                        | CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+                )
+                .inheritance_info(inheritance_info);
+            self.logical_device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .or_graphics_error("begin the occlusion command buffer")?;
+            self.logical_device
+                .cmd_set_viewport(command_buffer, 0, &[viewport]);
+            self.logical_device
+                .cmd_set_scissor(command_buffer, 0, &[scissor]);
+            self.logical_device.cmd_bind_pipeline(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline,
+            );
+            self.logical_device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            for (index, query) in queries.iter().enumerate() {
+                let push_constant = OcclusionPushConstant::from(&query.bounds);
+                self.logical_device.cmd_push_constants(
+                    command_buffer,
+                    pipeline_layout,
+                    ShaderStageFlags::VERTEX,
+                    0,
+                    std::slice::from_raw_parts(
+                        &push_constant as *const _ as *const u8,
+                        std::mem::size_of::<OcclusionPushConstant>(),
+                    ),
+                );
+                self.logical_device.cmd_begin_query(
+                    command_buffer,
+                    query_pool,
+                    index as u32,
+                    QueryControlFlags::empty(),
+                );
+                // 頂点シェーダーが番号から立方体の12個の三角形を作る。
+                // The vertex shader builds the 12 triangles of a cube from the vertex index.
+                self.logical_device.cmd_draw(command_buffer, 36, 1, 0, 0);
+                self.logical_device
+                    .cmd_end_query(command_buffer, query_pool, index as u32);
+            }
+            self.logical_device
+                .end_command_buffer(command_buffer)
+                .or_graphics_error("end the occlusion command buffer")?;
+        }
+        self.query_counts[frame_index] = queries.len();
+        Ok(Some(command_buffer))
+    }
+}
+
+impl Drop for OcclusionRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.logical_device.device_wait_idle() {
+                log::warn!("Failed to wait for device to idle: {}", e);
+            }
+            for (pool, command_buffer) in self.command_pools.iter().zip(self.command_buffers.iter())
+            {
+                self.logical_device
+                    .free_command_buffers(*pool, &[*command_buffer]);
+                self.logical_device.destroy_command_pool(*pool, None);
+            }
+            for query_pool in self.query_pools.iter() {
+                self.logical_device.destroy_query_pool(*query_pool, None);
+            }
+        }
+        log::info!("Occlusion renderer successfully dropped.");
+    }
+}
@@ -0,0 +1,338 @@
+use ash::{version::DeviceV1_0, vk::*, Device};
+use crossbeam::sync::ShardedLock;
+use parking_lot::Mutex;
+use std::mem::ManuallyDrop;
+use std::sync::{Arc, Weak};
+use vk_mem::Allocator;
+
+use super::{Initializer, VkResultExt};
+use crate::game::shared::structs::{GpuParticle, ParticleDispatch, MAX_PARTICLES};
+use crate::game::util::{end_one_time_command_buffer, get_single_time_command_buffer};
+
+/// GPUで粒子を動かして描く。<br />
+/// 粒子は全ての発生源で共有する一つのストレージバッファにあり、計算キューで動かしてからシーンのパスで板ポリゴンとして描く。<br />
+/// 計算の提出は前のフレームの描画を待ち、グラフィックの提出は同じフレームの計算を待つ。<br />
+/// Simulates and draws particles on the GPU.<br />
+/// Particles live in a single storage buffer shared by every emitter; they are simulated on the compute queue, then drawn as billboards in the scene pass.<br />
+/// The compute submit waits for the previous frame's draw, and the graphics submit waits for the same frame's simulation.
+pub struct ParticleRenderer {
+    pub particle_buffer: ManuallyDrop<super::Buffer>,
+    pub descriptor_set: DescriptorSet,
+    logical_device: Arc<Device>,
+    compute_queue: Arc<Mutex<Queue>>,
+    compute_command_pool: CommandPool,
+    compute_command_buffers: Vec<CommandBuffer>,
+    draw_command_pools: Vec<CommandPool>,
+    draw_command_buffers: Vec<CommandBuffer>,
+    simulated_semaphores: Vec<Semaphore>,
+    drawn_semaphores: Vec<Semaphore>,
+
+    /// 最後に粒子を描いた提出が合図するセマフォ。次の計算の提出が一度だけ待つ。<br />
+    /// Semaphore signalled by the last submit that drew particles, waited on once by the next compute submit.
+    last_drawn: Option<Semaphore>,
+}
+
+unsafe impl Send for ParticleRenderer {}
+unsafe impl Sync for ParticleRenderer {}
+
+impl ParticleRenderer {
+    pub fn new(
+        logical_device: Arc<Device>,
+        physical_device: &super::PhysicalDevice,
+        allocator: Weak<ShardedLock<Allocator>>,
+        graphics_queue: Arc<Mutex<Queue>>,
+        compute_queue: Arc<Mutex<Queue>>,
+        frame_count: usize,
+    ) -> anyhow::Result<Self> {
+        let graphics_family = physical_device
+            .queue_indices
+            .graphics_family
+            .ok_or_else(|| anyhow::anyhow!("The device doesn't have a graphics queue."))?;
+        let compute_family = physical_device
+            .queue_indices
+            .compute_family
+            .ok_or_else(|| anyhow::anyhow!("The device doesn't have a compute queue."))?;
+        let buffer_size = (std::mem::size_of::<GpuParticle>() * MAX_PARTICLES as usize) as u64;
+        let particle_buffer = super::Buffer::with_queue_families(
+            Arc::downgrade(&logical_device),
+            buffer_size,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            allocator,
+            &[graphics_family, compute_family],
+        )?;
+
+        unsafe {
+            let compute_pool_info = CommandPoolCreateInfo::builder()
+                .flags(CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                .queue_family_index(compute_family);
+            let compute_command_pool = logical_device
+                .create_command_pool(&compute_pool_info, None)
+                .or_graphics_error("create command pool for particles")?;
+            let compute_command_buffers = Initializer::allocate_command_buffers(
+                logical_device.as_ref(),
+                compute_command_pool,
+                frame_count as u32,
+            )?;
+
+            // 描く二次コマンドバッファはフレームごとのプールから取り、プールごとまとめてリセットする。
+            // Secondary draw buffers come from a pool per frame, and the whole pool is reset at once.
+            let draw_pool_info =
+                CommandPoolCreateInfo::builder().queue_family_index(graphics_family);
+            let mut draw_command_pools = vec![];
+            let mut draw_command_buffers = vec![];
+            let mut simulated_semaphores = vec![];
+            let mut drawn_semaphores = vec![];
+            let semaphore_info = SemaphoreCreateInfo::builder();
+            for _ in 0..frame_count {
+                let pool = logical_device
+                    .create_command_pool(&draw_pool_info, None)
+                    .or_graphics_error("create command pool for particles")?;
+                let allocate_info = CommandBufferAllocateInfo::builder()
+                    .command_pool(pool)
+                    .command_buffer_count(1)
+                    .level(CommandBufferLevel::SECONDARY);
+                draw_command_buffers.push(
+                    logical_device
+                        .allocate_command_buffers(&allocate_info)
+                        .or_graphics_error("allocate command buffers for particles")?[0],
+                );
+                draw_command_pools.push(pool);
+                simulated_semaphores.push(
+                    logical_device
+                        .create_semaphore(&semaphore_info, None)
+                        .or_graphics_error("create semaphore")?,
+                );
+                drawn_semaphores.push(
+                    logical_device
+                        .create_semaphore(&semaphore_info, None)
+                        .or_graphics_error("create semaphore")?,
+                );
+            }
+
+            // 寿命が0の粒子は死んでいるので、バッファを0で埋めれば全ての粒子が死んだ状態で始まる。
+            // Particles with a lifetime of zero are dead, so filling the buffer with zeros starts every particle dead.
+            let command_buffer =
+                get_single_time_command_buffer(logical_device.as_ref(), draw_command_pools[0]);
+            logical_device.cmd_fill_buffer(
+                command_buffer,
+                particle_buffer.buffer,
+                0,
+                WHOLE_SIZE,
+                0,
+            );
+            end_one_time_command_buffer(
+                command_buffer,
+                logical_device.as_ref(),
+                draw_command_pools[0],
+                *graphics_queue.lock(),
+            );
+
+            log::info!("Particle renderer successfully created.");
+            Ok(ParticleRenderer {
+                particle_buffer: ManuallyDrop::new(particle_buffer),
+                descriptor_set: DescriptorSet::null(),
+                logical_device,
+                compute_queue,
+                compute_command_pool,
+                compute_command_buffers,
+                draw_command_pools,
+                draw_command_buffers,
+                simulated_semaphores,
+                drawn_semaphores,
+                last_drawn: None,
+            })
+        }
+    }
+
+    pub fn get_buffer_info(&self) -> DescriptorBufferInfo {
+        DescriptorBufferInfo::builder()
+            .buffer(self.particle_buffer.buffer)
+            .offset(0)
+            .range(self.particle_buffer.buffer_size)
+            .build()
+    }
+
+    /// 計算キューで粒子を動かす。<br />
+    /// 戻り値は、同じフレームのグラフィックの提出が待つセマフォと、粒子を描き終えた時に合図するセマフォ。<br />
+    /// Simulate particles on the compute queue.<br />
+    /// Returns the semaphore the graphics submit of the same frame waits on, and the one it signals after drawing the particles.
+    pub fn simulate(
+        &mut self,
+        frame_index: usize,
+        dispatches: &[ParticleDispatch],
+        pipeline_layout: PipelineLayout,
+        pipeline: ash::vk::Pipeline,
+    ) -> anyhow::Result<Option<(Semaphore, Semaphore)>> {
+        if dispatches.is_empty() || self.descriptor_set == DescriptorSet::null() {
+            return Ok(None);
+        }
+        let command_buffer = self.compute_command_buffers[frame_index];
+        unsafe {
+            let begin_info =
+                CommandBufferBeginInfo::builder().flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            self.logical_device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .or_graphics_error("begin the particle command buffer")?;
+            self.logical_device.cmd_bind_pipeline(
+                command_buffer,
+                PipelineBindPoint::COMPUTE,
+                pipeline,
+            );
+            self.logical_device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::COMPUTE,
+                pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            // 発生源ごとの範囲は重ならないので、ディスパッチの間にバリアはいらない。
+            // Ranges of emitters never overlap, so no barriers are needed between dispatches.
+            for dispatch in dispatches.iter() {
+                let push_constant = dispatch.simulation;
+                self.logical_device.cmd_push_constants(
+                    command_buffer,
+                    pipeline_layout,
+                    ShaderStageFlags::COMPUTE,
+                    0,
+                    std::slice::from_raw_parts(
+                        &push_constant as *const _ as *const u8,
+                        std::mem::size_of_val(&push_constant),
+                    ),
+                );
+                self.logical_device.cmd_dispatch(
+                    command_buffer,
+                    dispatch.get_workgroup_count(),
+                    1,
+                    1,
+                );
+            }
+            self.logical_device
+                .end_command_buffer(command_buffer)
+                .or_graphics_error("end the particle command buffer")?;
+
+            let command_buffers = [command_buffer];
+            let signal_semaphores = [self.simulated_semaphores[frame_index]];
+            let wait_semaphores = self.last_drawn.take().into_iter().collect::<Vec<_>>();
+            let wait_stages = vec![PipelineStageFlags::COMPUTE_SHADER; wait_semaphores.len()];
+            let submit_info = [SubmitInfo::builder()
+                .command_buffers(&command_buffers[0..])
+                .signal_semaphores(&signal_semaphores[0..])
+                .wait_semaphores(wait_semaphores.as_slice())
+                .wait_dst_stage_mask(wait_stages.as_slice())
+                .build()];
+            self.logical_device
+                .queue_submit(*self.compute_queue.lock(), &submit_info[0..], Fence::null())
+                .or_graphics_error("submit the particle simulation")?;
+        }
+        let drawn = self.drawn_semaphores[frame_index];
+        self.last_drawn = Some(drawn);
+        Ok(Some((self.simulated_semaphores[frame_index], drawn)))
+    }
+
+    /// シーンのパスで実行する、粒子を描く二次コマンドバッファを記録する。<br />
+    /// Record the secondary command buffer drawing particles, executed in the scene pass.
+    pub fn record_draw(
+        &self,
+        frame_index: usize,
+        inheritance_info: &CommandBufferInheritanceInfo,
+        viewport: Viewport,
+        scissor: Rect2D,
+        dispatches: &[ParticleDispatch],
+        pipeline_layout: PipelineLayout,
+        pipeline: ash::vk::Pipeline,
+    ) -> anyhow::Result<Option<CommandBuffer>> {
+        if dispatches.is_empty() || self.descriptor_set == DescriptorSet::null() {
+            return Ok(None);
+        }
+        let command_buffer = self.draw_command_buffers[frame_index];
+        unsafe {
+            self.logical_device
+                .reset_command_pool(
+                    self.draw_command_pools[frame_index],
+                    CommandPoolResetFlags::empty(),
+                )
+                .or_graphics_error("reset the particle command pool")?;
+            let begin_info = CommandBufferBeginInfo::builder()
+                .flags(
+                    CommandBufferUsageFlags::ONE_TIME_SUBMIT
+                        | CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+                )
+                .inheritance_info(inheritance_info);
+            self.logical_device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .or_graphics_error("begin the particle command buffer")?;
+            self.logical_device
+                .cmd_set_viewport(command_buffer, 0, &[viewport]);
+            self.logical_device
+                .cmd_set_scissor(command_buffer, 0, &[scissor]);
+            self.logical_device.cmd_bind_pipeline(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline,
+            );
+            self.logical_device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            for dispatch in dispatches.iter() {
+                let push_constant = dispatch.draw;
+                self.logical_device.cmd_push_constants(
+                    command_buffer,
+                    pipeline_layout,
+                    ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+                    0,
+                    std::slice::from_raw_parts(
+                        &push_constant as *const _ as *const u8,
+                        std::mem::size_of_val(&push_constant),
+                    ),
+                );
+                // 粒子一つにつき板ポリゴンの頂点を6つ、インスタンスを一つ使う。
+                // Each particle is one instance with the six vertices of a billboard.
+                self.logical_device
+                    .cmd_draw(command_buffer, 6, push_constant.capacity, 0, 0);
+            }
+            self.logical_device
+                .end_command_buffer(command_buffer)
+                .or_graphics_error("end the particle command buffer")?;
+        }
+        Ok(Some(command_buffer))
+    }
+}
+
+impl Drop for ParticleRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.logical_device.device_wait_idle() {
+                log::warn!("Failed to wait for device to idle: {}", e);
+            }
+            for semaphore in self
+                .simulated_semaphores
+                .iter()
+                .chain(self.drawn_semaphores.iter())
+            {
+                self.logical_device.destroy_semaphore(*semaphore, None);
+            }
+            self.logical_device
+                .free_command_buffers(self.compute_command_pool, &self.compute_command_buffers);
+            self.logical_device
+                .destroy_command_pool(self.compute_command_pool, None);
+            for (pool, command_buffer) in self
+                .draw_command_pools
+                .iter()
+                .zip(self.draw_command_buffers.iter())
+            {
+                self.logical_device
+                    .free_command_buffers(*pool, &[*command_buffer]);
+                self.logical_device.destroy_command_pool(*pool, None);
+            }
+            ManuallyDrop::drop(&mut self.particle_buffer);
+            log::info!("Particle renderer successfully dropped.");
+        }
+    }
+}
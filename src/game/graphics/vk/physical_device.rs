@@ -3,8 +3,11 @@ use ash::{
     extensions::khr::{Surface, Swapchain},
     version::{InstanceV1_0, InstanceV1_1},
     vk::{
-        PhysicalDeviceDescriptorIndexingFeatures, PhysicalDeviceFeatures2,
-        PhysicalDeviceProperties, PhysicalDeviceType, QueueFlags, SurfaceKHR,
+        version_minor, ExtDescriptorIndexingFn, ExtMemoryBudgetFn, NvDeviceDiagnosticCheckpointsFn,
+        PhysicalDeviceDescriptorIndexingFeatures, PhysicalDeviceDescriptorIndexingProperties,
+        PhysicalDeviceFeatures2, PhysicalDeviceProperties, PhysicalDeviceProperties2,
+        PhysicalDeviceShaderDrawParametersFeatures, PhysicalDeviceType, QueueFamilyProperties,
+        QueueFlags, SurfaceKHR,
     },
     Instance,
 };
@@ -17,6 +20,9 @@ pub struct QueueIndices {
     pub graphics_family: Option<u32>,
     pub present_family: Option<u32>,
     pub compute_family: Option<u32>,
+    /// グラフィックスに対応しない、アップロード専用のキューファミリー。なければ`None`。<br />
+    /// Queue family dedicated to uploads, without graphics support. `None` if there's none.
+    pub transfer_family: Option<u32>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -28,8 +34,38 @@ pub struct FeatureSupport {
     pub shader_sampled_image_array_dynamic_indexing: bool,
     pub runtime_descriptor_array: bool,
     pub descriptor_binding_partially_bound: bool,
+    pub shader_sampled_image_array_non_uniform_indexing: bool,
+    pub descriptor_binding_sampled_image_update_after_bind: bool,
+    pub descriptor_binding_variable_descriptor_count: bool,
+    pub descriptor_binding_update_unused_while_pending: bool,
     pub multi_draw_indirect: bool,
+    /// シェーダーで`gl_DrawIDARB`などの描画の引数を読めるかどうか。<br />
+    /// Whether shaders can read draw parameters such as `gl_DrawIDARB`.
+    pub shader_draw_parameters: bool,
     pub shader_clip_distance: bool,
+    pub texture_compression_bc: bool,
+    pub texture_compression_astc_ldr: bool,
+}
+
+impl FeatureSupport {
+    /// テクスチャを実行中に描述子セットへ足せるかどうか（バインドレス）。<br />
+    /// Whether textures can be appended to a descriptor set at runtime (bindless).
+    pub fn supports_bindless(&self) -> bool {
+        self.runtime_descriptor_array
+            && self.descriptor_binding_partially_bound
+            && self.shader_sampled_image_array_non_uniform_indexing
+            && self.descriptor_binding_sampled_image_update_after_bind
+            && self.descriptor_binding_variable_descriptor_count
+            && self.descriptor_binding_update_unused_while_pending
+    }
+
+    /// 多くの描画を一回の間接描画にまとめられるかどうか。<br />
+    /// シェーダーは描画の番号で描画ごとの情報を引くので、描画の引数も読めないといけない。<br />
+    /// Whether many draws can be merged into a single indirect draw.<br />
+    /// Shaders look up per-draw information by the draw index, so they also have to read draw parameters.
+    pub fn supports_indirect_draw(&self) -> bool {
+        self.multi_draw_indirect && self.shader_draw_parameters
+    }
 }
 
 /// 実体装置のラッパー構造体。<br />
@@ -42,6 +78,21 @@ pub struct PhysicalDevice {
     pub queue_indices: QueueIndices,
     pub device_properties: PhysicalDeviceProperties,
     pub feature_support: FeatureSupport,
+    /// デバイスがVulkan 1.2より古く、描述子インデックスを拡張機能で使うかどうか。<br />
+    /// Whether the device is older than Vulkan 1.2 and descriptor indexing is used through the extension.
+    pub uses_descriptor_indexing_extension: bool,
+    /// GPUが最後に通ったチェックポイントをデバイスの喪失の後に読めるかどうか。<br />
+    /// Whether the checkpoints the GPU passed last can be read after the device is lost.
+    pub supports_diagnostic_checkpoints: bool,
+    /// ドライバーがヒープごとの予算と使用量を報告できるかどうか。<br />
+    /// Whether the driver can report the budget and usage of each heap.
+    pub supports_memory_budget: bool,
+    /// 更新後バインドの描述子セットに入れられるテクスチャの最大数。<br />
+    /// Maximum number of textures in an update-after-bind descriptor set.
+    pub max_update_after_bind_textures: u32,
+    /// 通常の描述子セットでフラグメントシェーダーに渡せるテクスチャの最大数。<br />
+    /// Maximum number of textures passed to the fragment stage in a regular descriptor set.
+    pub max_textures_per_stage: u32,
 }
 
 impl Default for QueueIndices {
@@ -56,9 +107,30 @@ impl QueueIndices {
             graphics_family: None,
             present_family: None,
             compute_family: None,
+            transfer_family: None,
         }
     }
 
+    /// グラフィックスに対応しない、アップロード専用のキューファミリーを探す。<br />
+    /// 計算にも対応しないファミリーは、DMAエンジンに対応していることが多いので優先する。<br />
+    /// Find a queue family dedicated to uploads, without graphics support.<br />
+    /// Families without compute support either usually map to a DMA engine, so they're preferred.
+    pub fn find_transfer_family(queue_families: &[QueueFamilyProperties]) -> Option<u32> {
+        let is_transfer_only = |flags: QueueFlags, allows_compute: bool| {
+            flags.contains(QueueFlags::TRANSFER)
+                && !flags.contains(QueueFlags::GRAPHICS)
+                && (allows_compute || !flags.contains(QueueFlags::COMPUTE))
+        };
+        [false, true].iter().find_map(|allows_compute| {
+            queue_families
+                .iter()
+                .position(|family| {
+                    family.queue_count > 0 && is_transfer_only(family.queue_flags, *allows_compute)
+                })
+                .map(|index| index as u32)
+        })
+    }
+
     pub fn is_ready(&self) -> bool {
         self.graphics_family.is_some()
             && self.present_family.is_some()
@@ -73,12 +145,33 @@ impl PhysicalDevice {
         unsafe {
             let features = instance.get_physical_device_features(device);
 
-            let mut indexing_feature = PhysicalDeviceDescriptorIndexingFeatures::default();
-            let mut features2 = PhysicalDeviceFeatures2 {
-                p_next: &mut indexing_feature as *mut _ as *mut std::ffi::c_void,
+            // Vulkan 1.2より古いデバイスでは、拡張機能がなければ描述子インデックスの機能を問い合わせられない。
+            // Devices older than Vulkan 1.2 can't be queried for descriptor indexing features without the extension.
+            let uses_descriptor_indexing_extension = version_minor(properties.api_version) < 2;
+            let has_descriptor_indexing = !uses_descriptor_indexing_extension
+                || PhysicalDevice::is_extension_supported(
+                    instance,
+                    device,
+                    ExtDescriptorIndexingFn::name(),
+                );
+            let mut draw_parameters_feature = PhysicalDeviceShaderDrawParametersFeatures::default();
+            let mut indexing_feature = PhysicalDeviceDescriptorIndexingFeatures {
+                p_next: &mut draw_parameters_feature as *mut _ as *mut std::ffi::c_void,
                 ..Default::default()
             };
-            instance.get_physical_device_features2(device, &mut features2);
+            let mut indexing_properties = PhysicalDeviceDescriptorIndexingProperties::default();
+            if has_descriptor_indexing {
+                let mut features2 = PhysicalDeviceFeatures2 {
+                    p_next: &mut indexing_feature as *mut _ as *mut std::ffi::c_void,
+                    ..Default::default()
+                };
+                instance.get_physical_device_features2(device, &mut features2);
+                let mut properties2 = PhysicalDeviceProperties2 {
+                    p_next: &mut indexing_properties as *mut _ as *mut std::ffi::c_void,
+                    ..Default::default()
+                };
+                instance.get_physical_device_properties2(device, &mut properties2);
+            }
 
             let feature_support = FeatureSupport {
                 geometry_shader: features.geometry_shader == TRUE,
@@ -92,8 +185,23 @@ impl PhysicalDevice {
                 descriptor_binding_partially_bound: indexing_feature
                     .descriptor_binding_partially_bound
                     == TRUE,
+                shader_sampled_image_array_non_uniform_indexing: indexing_feature
+                    .shader_sampled_image_array_non_uniform_indexing
+                    == TRUE,
+                descriptor_binding_sampled_image_update_after_bind: indexing_feature
+                    .descriptor_binding_sampled_image_update_after_bind
+                    == TRUE,
+                descriptor_binding_variable_descriptor_count: indexing_feature
+                    .descriptor_binding_variable_descriptor_count
+                    == TRUE,
+                descriptor_binding_update_unused_while_pending: indexing_feature
+                    .descriptor_binding_update_unused_while_pending
+                    == TRUE,
                 multi_draw_indirect: features.multi_draw_indirect == TRUE,
+                shader_draw_parameters: draw_parameters_feature.shader_draw_parameters == TRUE,
                 shader_clip_distance: features.shader_clip_distance == TRUE,
+                texture_compression_bc: features.texture_compression_bc == TRUE,
+                texture_compression_astc_ldr: features.texture_compression_astc_ldr == TRUE,
             };
 
             log::info!("Geometry shader: {}", feature_support.geometry_shader);
@@ -118,20 +226,47 @@ impl PhysicalDevice {
                 "Descriptor binding partially bound: {}",
                 feature_support.descriptor_binding_partially_bound
             );
+            log::info!("Bindless textures: {}", feature_support.supports_bindless());
             log::info!(
-                "Multi draw indirect: {}",
-                feature_support.multi_draw_indirect
+                "Multi draw indirect: {}, shader draw parameters: {}",
+                feature_support.multi_draw_indirect,
+                feature_support.shader_draw_parameters
             );
             log::info!(
                 "Shader clip distance: {}",
                 feature_support.shader_clip_distance
             );
+            log::info!(
+                "Texture compression BC: {}, ASTC LDR: {}",
+                feature_support.texture_compression_bc,
+                feature_support.texture_compression_astc_ldr
+            );
 
             PhysicalDevice {
                 physical_device: device,
                 queue_indices,
                 device_properties: properties,
                 feature_support,
+                uses_descriptor_indexing_extension,
+                supports_diagnostic_checkpoints: PhysicalDevice::is_extension_supported(
+                    instance,
+                    device,
+                    NvDeviceDiagnosticCheckpointsFn::name(),
+                ),
+                supports_memory_budget: PhysicalDevice::is_extension_supported(
+                    instance,
+                    device,
+                    ExtMemoryBudgetFn::name(),
+                ),
+                max_update_after_bind_textures: indexing_properties
+                    .max_per_stage_descriptor_update_after_bind_samplers
+                    .min(indexing_properties.max_descriptor_set_update_after_bind_sampled_images),
+                max_textures_per_stage: properties
+                    .limits
+                    .max_per_stage_descriptor_samplers
+                    .min(properties.limits.max_per_stage_descriptor_sampled_images)
+                    .min(properties.limits.max_descriptor_set_samplers)
+                    .min(properties.limits.max_descriptor_set_sampled_images),
             }
         }
     }
@@ -171,10 +306,30 @@ impl PhysicalDevice {
                     break;
                 }
             }
+
+            queue_indices.transfer_family = QueueIndices::find_transfer_family(&queue_families);
         }
         queue_indices
     }
 
+    fn is_extension_supported(
+        instance: &Instance,
+        device: ash::vk::PhysicalDevice,
+        extension_name: &CStr,
+    ) -> bool {
+        unsafe {
+            instance
+                .enumerate_device_extension_properties(device)
+                .map(|extensions| {
+                    extensions.iter().any(|extension| {
+                        CStr::from_ptr(extension.extension_name.as_ptr() as *const c_char)
+                            == extension_name
+                    })
+                })
+                .unwrap_or(false)
+        }
+    }
+
     fn check_extension_support(instance: &Instance, device: ash::vk::PhysicalDevice) -> bool {
         let mut required_extension = HashSet::new();
         required_extension.insert(Swapchain::name());
@@ -6,14 +6,21 @@ use std::ffi::CString;
 use std::sync::Arc;
 
 use crate::game::enums::ShaderType;
-use crate::game::graphics::vk::Shader;
-use crate::game::shared::structs::{InstanceData, InstancedVertex, SkinnedVertex};
+use crate::game::graphics::vk::{
+    derive_subpass_dependencies, get_post_process_render_passes, Shader,
+};
+use crate::game::shared::structs::{
+    DebugVertex, IndirectCullPushConstant, InstanceData, InstancedVertex, OcclusionPushConstant,
+    ParticleDrawPushConstant, ParticleSimulationPushConstant, PassOutput, PostProcessPushConstant,
+    PostProcessStep, SkinnedVertex, SpriteVertex,
+};
 use crate::game::structs::{BlendMode, PushConstant, Vertex};
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub enum RenderPassType {
     Primary,
     Offscreen,
+    PostProcess,
 }
 
 /// パイプラインのラッパー構造体。中にはグラフィックパイプラインと計算パイプラインを含めています。<br />
@@ -23,6 +30,20 @@ pub struct Pipeline {
     pub render_pass: HashMap<RenderPassType, RenderPass>,
     pub pipeline_layouts: HashMap<ShaderType, PipelineLayout>,
     pub graphic_pipelines: HashMap<ShaderType, Vec<ash::vk::Pipeline>>,
+    pub post_process_layout: PipelineLayout,
+    pub post_process_pipelines: HashMap<(PostProcessStep, RenderPassType), ash::vk::Pipeline>,
+    pub particle_layout: PipelineLayout,
+    pub particle_pipeline: ash::vk::Pipeline,
+    pub particle_simulation_layout: PipelineLayout,
+    pub particle_simulation_pipeline: ash::vk::Pipeline,
+    pub debug_line_layout: PipelineLayout,
+    pub debug_line_pipeline: ash::vk::Pipeline,
+    pub occlusion_layout: PipelineLayout,
+    pub occlusion_pipeline: ash::vk::Pipeline,
+    pub sprite_layout: PipelineLayout,
+    pub sprite_pipeline: ash::vk::Pipeline,
+    pub indirect_cull_layout: PipelineLayout,
+    pub indirect_cull_pipeline: ash::vk::Pipeline,
     logical_device: Arc<Device>,
     owned_renderpass: bool,
     pipeline_caches: HashMap<ShaderType, Arc<RwLock<Vec<Vec<u8>>>>>,
@@ -66,25 +87,46 @@ impl Pipeline {
             render_pass: HashMap::new(),
             pipeline_layouts: HashMap::new(),
             graphic_pipelines: HashMap::new(),
+            post_process_layout: PipelineLayout::null(),
+            post_process_pipelines: HashMap::new(),
+            particle_layout: PipelineLayout::null(),
+            particle_pipeline: ash::vk::Pipeline::null(),
+            particle_simulation_layout: PipelineLayout::null(),
+            particle_simulation_pipeline: ash::vk::Pipeline::null(),
+            debug_line_layout: PipelineLayout::null(),
+            debug_line_pipeline: ash::vk::Pipeline::null(),
+            occlusion_layout: PipelineLayout::null(),
+            occlusion_pipeline: ash::vk::Pipeline::null(),
+            sprite_layout: PipelineLayout::null(),
+            sprite_pipeline: ash::vk::Pipeline::null(),
+            indirect_cull_layout: PipelineLayout::null(),
+            indirect_cull_pipeline: ash::vk::Pipeline::null(),
             owned_renderpass: false,
             pipeline_caches,
             shader_types,
         }
     }
 
-    /// オフスクリーンレンダパスを生成する。未実装の水面用。<br />
-    /// Create offscreen renderpass, used for the unimplemented water surface.
+    /// オフスクリーンレンダパスを生成する。HDRのシーンと未実装の水面用。<br />
+    /// Create offscreen renderpass, used for the HDR scene and the unimplemented water surface.
     pub fn create_offscreen_renderpass(
         &mut self,
         graphics_format: Format,
         depth_format: Format,
         sample_count: SampleCountFlags,
     ) -> anyhow::Result<()> {
+        // MSAAを使わない時は解決せず、カラーアタッチメントをそのまま読む。
+        // Without MSAA nothing is resolved and the color attachment is sampled directly.
+        let is_multisampled = sample_count != SampleCountFlags::TYPE_1;
         let mut attachment_descriptions = vec![];
         attachment_descriptions.push(
             AttachmentDescription::builder()
                 .format(graphics_format)
-                .final_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .final_layout(if is_multisampled {
+                    ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+                } else {
+                    ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                })
                 .initial_layout(ImageLayout::UNDEFINED)
                 .load_op(AttachmentLoadOp::CLEAR)
                 .samples(sample_count)
@@ -106,18 +148,20 @@ impl Pipeline {
                 .build(),
         );
 
-        attachment_descriptions.push(
-            AttachmentDescription::builder()
-                .format(graphics_format)
-                .final_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .initial_layout(ImageLayout::UNDEFINED)
-                .load_op(AttachmentLoadOp::DONT_CARE)
-                .samples(SampleCountFlags::TYPE_1)
-                .stencil_load_op(AttachmentLoadOp::DONT_CARE)
-                .stencil_store_op(AttachmentStoreOp::DONT_CARE)
-                .store_op(AttachmentStoreOp::STORE)
-                .build(),
-        );
+        if is_multisampled {
+            attachment_descriptions.push(
+                AttachmentDescription::builder()
+                    .format(graphics_format)
+                    .final_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .initial_layout(ImageLayout::UNDEFINED)
+                    .load_op(AttachmentLoadOp::DONT_CARE)
+                    .samples(SampleCountFlags::TYPE_1)
+                    .stencil_load_op(AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(AttachmentStoreOp::DONT_CARE)
+                    .store_op(AttachmentStoreOp::STORE)
+                    .build(),
+            );
+        }
 
         let color_reference = vec![AttachmentReference::builder()
             .attachment(0)
@@ -131,37 +175,17 @@ impl Pipeline {
             .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build()];
 
-        let subpass_description = vec![SubpassDescription::builder()
+        let mut subpass_description = SubpassDescription::builder()
             .color_attachments(color_reference.as_slice())
             .depth_stencil_attachment(&depth_reference)
-            .pipeline_bind_point(PipelineBindPoint::GRAPHICS)
-            .resolve_attachments(msaa_reference.as_slice())
-            .build()];
-
-        let mut subpass_dependencies = vec![];
-        subpass_dependencies.push(
-            SubpassDependency::builder()
-                .dependency_flags(DependencyFlags::BY_REGION)
-                .dst_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE)
-                .dst_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-                .dst_subpass(0)
-                .src_access_mask(AccessFlags::SHADER_READ)
-                .src_stage_mask(PipelineStageFlags::FRAGMENT_SHADER)
-                .src_subpass(SUBPASS_EXTERNAL)
-                .build(),
-        );
+            .pipeline_bind_point(PipelineBindPoint::GRAPHICS);
+        if is_multisampled {
+            subpass_description =
+                subpass_description.resolve_attachments(msaa_reference.as_slice());
+        }
+        let subpass_description = vec![subpass_description.build()];
 
-        subpass_dependencies.push(
-            SubpassDependency::builder()
-                .dependency_flags(DependencyFlags::BY_REGION)
-                .dst_access_mask(AccessFlags::SHADER_READ)
-                .dst_stage_mask(PipelineStageFlags::FRAGMENT_SHADER)
-                .dst_subpass(SUBPASS_EXTERNAL)
-                .src_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE)
-                .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-                .src_subpass(0)
-                .build(),
-        );
+        let subpass_dependencies = derive_subpass_dependencies(PassOutput::Sampled);
 
         let renderpass_create_info = RenderPassCreateInfo::builder()
             .attachments(attachment_descriptions.as_slice())
@@ -185,13 +209,18 @@ impl Pipeline {
         depth_format: Format,
         sample_count: SampleCountFlags,
     ) {
+        let is_multisampled = sample_count != SampleCountFlags::TYPE_1;
         let mut attachment_descriptions = vec![];
         attachment_descriptions.push(
             AttachmentDescription::builder()
                 .format(graphics_format)
                 .samples(sample_count)
                 .initial_layout(ImageLayout::UNDEFINED)
-                .final_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .final_layout(if is_multisampled {
+                    ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+                } else {
+                    ImageLayout::PRESENT_SRC_KHR
+                })
                 .load_op(AttachmentLoadOp::CLEAR)
                 .stencil_load_op(AttachmentLoadOp::DONT_CARE)
                 .stencil_store_op(AttachmentStoreOp::DONT_CARE)
@@ -212,47 +241,22 @@ impl Pipeline {
                 .build(),
         );
 
-        attachment_descriptions.push(
-            AttachmentDescription::builder()
-                .format(graphics_format)
-                .samples(SampleCountFlags::TYPE_1)
-                .initial_layout(ImageLayout::UNDEFINED)
-                .final_layout(ImageLayout::PRESENT_SRC_KHR)
-                .load_op(AttachmentLoadOp::DONT_CARE)
-                .stencil_load_op(AttachmentLoadOp::DONT_CARE)
-                .stencil_store_op(AttachmentStoreOp::DONT_CARE)
-                .store_op(AttachmentStoreOp::STORE)
-                .build(),
-        );
-
-        let mut subpass_dependency = vec![];
-        subpass_dependency.push(
-            SubpassDependency::builder()
-                .dst_access_mask(
-                    AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE,
-                )
-                .src_access_mask(AccessFlags::MEMORY_READ)
-                .dst_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-                .src_stage_mask(PipelineStageFlags::BOTTOM_OF_PIPE)
-                .dst_subpass(0)
-                .src_subpass(SUBPASS_EXTERNAL)
-                .dependency_flags(DependencyFlags::BY_REGION)
-                .build(),
-        );
+        if is_multisampled {
+            attachment_descriptions.push(
+                AttachmentDescription::builder()
+                    .format(graphics_format)
+                    .samples(SampleCountFlags::TYPE_1)
+                    .initial_layout(ImageLayout::UNDEFINED)
+                    .final_layout(ImageLayout::PRESENT_SRC_KHR)
+                    .load_op(AttachmentLoadOp::DONT_CARE)
+                    .stencil_load_op(AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(AttachmentStoreOp::DONT_CARE)
+                    .store_op(AttachmentStoreOp::STORE)
+                    .build(),
+            );
+        }
 
-        subpass_dependency.push(
-            SubpassDependency::builder()
-                .dst_access_mask(AccessFlags::MEMORY_READ)
-                .src_access_mask(
-                    AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE,
-                )
-                .dst_stage_mask(PipelineStageFlags::BOTTOM_OF_PIPE)
-                .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-                .dst_subpass(SUBPASS_EXTERNAL)
-                .src_subpass(0)
-                .dependency_flags(DependencyFlags::BY_REGION)
-                .build(),
-        );
+        let subpass_dependency = derive_subpass_dependencies(PassOutput::Present);
 
         let color_reference = vec![AttachmentReference::builder()
             .attachment(0)
@@ -268,12 +272,15 @@ impl Pipeline {
             .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build()];
 
-        let subpass_description = vec![SubpassDescription::builder()
+        let mut subpass_description = SubpassDescription::builder()
             .color_attachments(color_reference.as_slice())
             .depth_stencil_attachment(&depth_reference)
-            .pipeline_bind_point(PipelineBindPoint::GRAPHICS)
-            .resolve_attachments(resolve_reference.as_slice())
-            .build()];
+            .pipeline_bind_point(PipelineBindPoint::GRAPHICS);
+        if is_multisampled {
+            subpass_description =
+                subpass_description.resolve_attachments(resolve_reference.as_slice());
+        }
+        let subpass_description = vec![subpass_description.build()];
 
         let renderpass_info = RenderPassCreateInfo::builder()
             .attachments(attachment_descriptions.as_slice())
@@ -289,8 +296,51 @@ impl Pipeline {
         }
     }
 
-    /// マルチスレッドでグラフィックパイプラインを作成する。<br />
-    /// Multi-threadedly create graphic pipeline.
+    /// ポストプロセス用のレンダパスを生成する。一枚のカラーアタッチメントだけを持ち、後のパスが読む。<br />
+    /// Create the renderpass for post-processing. It has a single color attachment read by later passes.
+    pub fn create_post_process_renderpass(
+        &mut self,
+        graphics_format: Format,
+    ) -> anyhow::Result<()> {
+        let attachment_descriptions = vec![AttachmentDescription::builder()
+            .format(graphics_format)
+            .samples(SampleCountFlags::TYPE_1)
+            .initial_layout(ImageLayout::UNDEFINED)
+            .final_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .load_op(AttachmentLoadOp::DONT_CARE)
+            .stencil_load_op(AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(AttachmentStoreOp::DONT_CARE)
+            .store_op(AttachmentStoreOp::STORE)
+            .build()];
+
+        let color_reference = vec![AttachmentReference::builder()
+            .attachment(0)
+            .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+
+        let subpass_description = vec![SubpassDescription::builder()
+            .color_attachments(color_reference.as_slice())
+            .pipeline_bind_point(PipelineBindPoint::GRAPHICS)
+            .build()];
+
+        let subpass_dependencies = derive_subpass_dependencies(PassOutput::Sampled);
+
+        let renderpass_create_info = RenderPassCreateInfo::builder()
+            .attachments(attachment_descriptions.as_slice())
+            .dependencies(subpass_dependencies.as_slice())
+            .subpasses(subpass_description.as_slice());
+        unsafe {
+            let renderpass = self
+                .logical_device
+                .create_render_pass(&renderpass_create_info, None)?;
+            self.render_pass
+                .insert(RenderPassType::PostProcess, renderpass);
+        }
+        Ok(())
+    }
+
+    /// マルチスレッドでグラフィックパイプラインを作成する。シーンはHDRのオフスクリーンレンダパスに描画する。<br />
+    /// Multi-threadedly create graphic pipeline. The scene is rendered into the HDR offscreen renderpass.
     pub fn create_graphic_pipelines(
         &mut self,
         descriptor_set_layout: &[DescriptorSetLayout],
@@ -413,7 +463,7 @@ impl Pipeline {
                 let pipeline_layout = *self.pipeline_layouts.get(&shader_type).unwrap();
                 let render_pass = self
                     .render_pass
-                    .get(&RenderPassType::Primary)
+                    .get(&RenderPassType::Offscreen)
                     .cloned()
                     .unwrap();
                 let device = self.logical_device.clone();
@@ -422,7 +472,9 @@ impl Pipeline {
                 rayon::spawn(move || {
                     let attr_desc = match shader_type {
                         ShaderType::AnimatedModel => SkinnedVertex::get_attribute_description(0),
-                        ShaderType::InstanceDraw => InstancedVertex::get_attribute_description(0),
+                        ShaderType::InstanceDraw | ShaderType::InstanceIndirectDraw => {
+                            InstancedVertex::get_attribute_description(0)
+                        }
                         _ => Vertex::get_attribute_description(0),
                     };
                     let binding_desc = match shader_type {
@@ -430,7 +482,7 @@ impl Pipeline {
                             0,
                             VertexInputRate::VERTEX,
                         )],
-                        ShaderType::InstanceDraw => vec![
+                        ShaderType::InstanceDraw | ShaderType::InstanceIndirectDraw => vec![
                             Vertex::get_binding_description(
                                 0,
                                 std::mem::size_of::<Vertex>() as u32,
@@ -557,6 +609,632 @@ impl Pipeline {
         Ok(())
     }
 
+    /// フルスクリーンのポストプロセスのパイプラインを作成する。頂点入力はなく、三角形一つで画面を覆う。<br />
+    /// Create the pipelines of the fullscreen post-processing steps. There is no vertex input; a single triangle covers the screen.
+    pub fn create_post_process_pipelines(
+        &mut self,
+        descriptor_set_layout: DescriptorSetLayout,
+        sample_count: SampleCountFlags,
+        shaders: Vec<(PostProcessStep, Vec<Shader>)>,
+    ) -> anyhow::Result<()> {
+        let push_constant_range = vec![PushConstantRange::builder()
+            .stage_flags(ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(std::mem::size_of::<PostProcessPushConstant>() as u32)
+            .build()];
+        let set_layouts = vec![descriptor_set_layout];
+        let layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(set_layouts.as_slice())
+            .push_constant_ranges(push_constant_range.as_slice());
+        unsafe {
+            self.post_process_layout = self
+                .logical_device
+                .create_pipeline_layout(&layout_info, None)?;
+        }
+
+        let color_attachment = vec![PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                ColorComponentFlags::R
+                    | ColorComponentFlags::G
+                    | ColorComponentFlags::B
+                    | ColorComponentFlags::A,
+            )
+            .blend_enable(false)
+            .build()];
+        let vi_info = PipelineVertexInputStateCreateInfo::builder();
+        let ia_info = PipelineInputAssemblyStateCreateInfo::builder()
+            .primitive_restart_enable(false)
+            .topology(PrimitiveTopology::TRIANGLE_LIST);
+        let rs_info = PipelineRasterizationStateCreateInfo::builder()
+            .cull_mode(CullModeFlags::NONE)
+            .depth_bias_enable(false)
+            .depth_clamp_enable(false)
+            .front_face(FrontFace::CLOCKWISE)
+            .line_width(1.0)
+            .polygon_mode(PolygonMode::FILL)
+            .rasterizer_discard_enable(false);
+        let vp_info = PipelineViewportStateCreateInfo::builder()
+            .scissor_count(1)
+            .viewport_count(1);
+        let color_blend_info = PipelineColorBlendStateCreateInfo::builder()
+            .logic_op(LogicOp::COPY)
+            .attachments(color_attachment.as_slice())
+            .logic_op_enable(false);
+        let depth_info = PipelineDepthStencilStateCreateInfo::builder()
+            .depth_bounds_test_enable(false)
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .stencil_test_enable(false);
+        let dynamic_states = vec![DynamicState::SCISSOR, DynamicState::VIEWPORT];
+        let dynamic_info =
+            PipelineDynamicStateCreateInfo::builder().dynamic_states(dynamic_states.as_slice());
+        let name = CString::new("main")?;
+
+        for (step, step_shaders) in shaders.iter() {
+            let mut stage_infos = step_shaders
+                .iter()
+                .map(|s| s.shader_stage_info)
+                .collect::<Vec<_>>();
+            stage_infos.iter_mut().for_each(|s| {
+                s.p_name = name.as_ptr();
+            });
+            for render_pass_type in get_post_process_render_passes(*step).iter() {
+                // 最後のパスはスワップチェーンのマルチサンプルのアタッチメントに書く。
+                // The final pass writes the multisampled attachment of the swapchain.
+                let samples = match render_pass_type {
+                    RenderPassType::Primary => sample_count,
+                    _ => SampleCountFlags::TYPE_1,
+                };
+                let msaa_info = PipelineMultisampleStateCreateInfo::builder()
+                    .rasterization_samples(samples)
+                    .sample_shading_enable(false);
+                let render_pass = self
+                    .render_pass
+                    .get(render_pass_type)
+                    .copied()
+                    .expect("Failed to get the renderpass of the post-processing step.");
+                let pipeline_info = vec![GraphicsPipelineCreateInfo::builder()
+                    .layout(self.post_process_layout)
+                    .base_pipeline_index(-1)
+                    .base_pipeline_handle(ash::vk::Pipeline::null())
+                    .color_blend_state(&color_blend_info)
+                    .depth_stencil_state(&depth_info)
+                    .dynamic_state(&dynamic_info)
+                    .input_assembly_state(&ia_info)
+                    .multisample_state(&msaa_info)
+                    .rasterization_state(&rs_info)
+                    .render_pass(render_pass)
+                    .subpass(0)
+                    .vertex_input_state(&vi_info)
+                    .viewport_state(&vp_info)
+                    .stages(stage_infos.as_slice())
+                    .build()];
+                unsafe {
+                    let pipeline = self
+                        .logical_device
+                        .create_graphics_pipelines(
+                            PipelineCache::null(),
+                            pipeline_info.as_slice(),
+                            None,
+                        )
+                        .map_err(|(_, e)| e)?;
+                    self.post_process_pipelines
+                        .insert((*step, *render_pass_type), pipeline[0]);
+                }
+            }
+        }
+        log::info!("Post-processing pipelines successfully created.");
+        Ok(())
+    }
+
+    /// 粒子を動かす計算パイプラインと、粒子を板ポリゴンで描くパイプラインを作成する。<br />
+    /// 粒子は頂点バッファを使わず、シェーダーがストレージバッファから読む。深度は比べるが書き込まない。<br />
+    /// Create the compute pipeline simulating particles and the pipeline drawing them as billboards.<br />
+    /// Particles use no vertex buffer and are read from the storage buffer by the shaders. Depth is tested but not written.
+    pub fn create_particle_pipelines(
+        &mut self,
+        descriptor_set_layout: DescriptorSetLayout,
+        sample_count: SampleCountFlags,
+        compute_shader: Shader,
+        shaders: Vec<Shader>,
+    ) -> anyhow::Result<()> {
+        let set_layouts = vec![descriptor_set_layout];
+        let name = CString::new("main")?;
+
+        let simulation_range = vec![PushConstantRange::builder()
+            .stage_flags(ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(std::mem::size_of::<ParticleSimulationPushConstant>() as u32)
+            .build()];
+        let simulation_layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(set_layouts.as_slice())
+            .push_constant_ranges(simulation_range.as_slice());
+        let mut compute_stage = compute_shader.shader_stage_info;
+        compute_stage.p_name = name.as_ptr();
+        unsafe {
+            self.particle_simulation_layout = self
+                .logical_device
+                .create_pipeline_layout(&simulation_layout_info, None)?;
+            let pipeline_info = vec![ComputePipelineCreateInfo::builder()
+                .layout(self.particle_simulation_layout)
+                .stage(compute_stage)
+                .base_pipeline_index(-1)
+                .base_pipeline_handle(ash::vk::Pipeline::null())
+                .build()];
+            let pipeline = self
+                .logical_device
+                .create_compute_pipelines(PipelineCache::null(), pipeline_info.as_slice(), None)
+                .map_err(|(_, e)| e)?;
+            self.particle_simulation_pipeline = pipeline[0];
+        }
+
+        let draw_range = vec![PushConstantRange::builder()
+            .stage_flags(ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(std::mem::size_of::<ParticleDrawPushConstant>() as u32)
+            .build()];
+        let draw_layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(set_layouts.as_slice())
+            .push_constant_ranges(draw_range.as_slice());
+        unsafe {
+            self.particle_layout = self
+                .logical_device
+                .create_pipeline_layout(&draw_layout_info, None)?;
+        }
+
+        // シェーダーは乗算済みのアルファを出すので、加算合成もアルファを0にするだけで同じパイプラインで描ける。
+        // The shaders output premultiplied alpha, so additive particles share this pipeline by writing zero alpha.
+        let color_attachment = vec![PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                ColorComponentFlags::R
+                    | ColorComponentFlags::G
+                    | ColorComponentFlags::B
+                    | ColorComponentFlags::A,
+            )
+            .blend_enable(true)
+            .src_color_blend_factor(BlendFactor::ONE)
+            .dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(BlendOp::ADD)
+            .src_alpha_blend_factor(BlendFactor::ONE)
+            .dst_alpha_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(BlendOp::ADD)
+            .build()];
+        let vi_info = PipelineVertexInputStateCreateInfo::builder();
+        let ia_info = PipelineInputAssemblyStateCreateInfo::builder()
+            .primitive_restart_enable(false)
+            .topology(PrimitiveTopology::TRIANGLE_LIST);
+        let rs_info = PipelineRasterizationStateCreateInfo::builder()
+            .cull_mode(CullModeFlags::NONE)
+            .depth_bias_enable(false)
+            .depth_clamp_enable(false)
+            .front_face(FrontFace::CLOCKWISE)
+            .line_width(1.0)
+            .polygon_mode(PolygonMode::FILL)
+            .rasterizer_discard_enable(false);
+        let vp_info = PipelineViewportStateCreateInfo::builder()
+            .scissor_count(1)
+            .viewport_count(1);
+        let msaa_info = PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(sample_count)
+            .sample_shading_enable(false);
+        let color_blend_info = PipelineColorBlendStateCreateInfo::builder()
+            .logic_op(LogicOp::COPY)
+            .attachments(color_attachment.as_slice())
+            .logic_op_enable(false);
+        let depth_info = PipelineDepthStencilStateCreateInfo::builder()
+            .depth_bounds_test_enable(false)
+            .depth_compare_op(CompareOp::LESS_OR_EQUAL)
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .stencil_test_enable(false);
+        let dynamic_states = vec![DynamicState::SCISSOR, DynamicState::VIEWPORT];
+        let dynamic_info =
+            PipelineDynamicStateCreateInfo::builder().dynamic_states(dynamic_states.as_slice());
+        let mut stage_infos = shaders
+            .iter()
+            .map(|s| s.shader_stage_info)
+            .collect::<Vec<_>>();
+        stage_infos.iter_mut().for_each(|s| {
+            s.p_name = name.as_ptr();
+        });
+        let render_pass = self
+            .render_pass
+            .get(&RenderPassType::Offscreen)
+            .copied()
+            .expect("Failed to get the offscreen renderpass for particles.");
+        let pipeline_info = vec![GraphicsPipelineCreateInfo::builder()
+            .layout(self.particle_layout)
+            .base_pipeline_index(-1)
+            .base_pipeline_handle(ash::vk::Pipeline::null())
+            .color_blend_state(&color_blend_info)
+            .depth_stencil_state(&depth_info)
+            .dynamic_state(&dynamic_info)
+            .input_assembly_state(&ia_info)
+            .multisample_state(&msaa_info)
+            .rasterization_state(&rs_info)
+            .render_pass(render_pass)
+            .subpass(0)
+            .vertex_input_state(&vi_info)
+            .viewport_state(&vp_info)
+            .stages(stage_infos.as_slice())
+            .build()];
+        unsafe {
+            let pipeline = self
+                .logical_device
+                .create_graphics_pipelines(PipelineCache::null(), pipeline_info.as_slice(), None)
+                .map_err(|(_, e)| e)?;
+            self.particle_pipeline = pipeline[0];
+        }
+        log::info!("Particle pipelines successfully created.");
+        Ok(())
+    }
+
+    /// デバッグ用の線を描くパイプラインを作成する。<br />
+    /// 骨格やコライダーがメッシュに隠れないように、深度は比べない。<br />
+    /// Create the pipeline drawing debug lines.<br />
+    /// Depth isn't tested so that skeletons and colliders aren't hidden behind meshes.
+    pub fn create_debug_line_pipeline(
+        &mut self,
+        descriptor_set_layout: DescriptorSetLayout,
+        sample_count: SampleCountFlags,
+        shaders: Vec<Shader>,
+    ) -> anyhow::Result<()> {
+        let set_layouts = vec![descriptor_set_layout];
+        let name = CString::new("main")?;
+        let layout_info = PipelineLayoutCreateInfo::builder().set_layouts(set_layouts.as_slice());
+        unsafe {
+            self.debug_line_layout = self
+                .logical_device
+                .create_pipeline_layout(&layout_info, None)?;
+        }
+
+        let color_attachment = vec![PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                ColorComponentFlags::R
+                    | ColorComponentFlags::G
+                    | ColorComponentFlags::B
+                    | ColorComponentFlags::A,
+            )
+            .blend_enable(true)
+            .src_color_blend_factor(BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(BlendOp::ADD)
+            .src_alpha_blend_factor(BlendFactor::ONE)
+            .dst_alpha_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(BlendOp::ADD)
+            .build()];
+        let binding_description = vec![DebugVertex::get_binding_description(0)];
+        let attribute_descriptions = DebugVertex::get_attribute_description(0);
+        let vi_info = PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(binding_description.as_slice())
+            .vertex_attribute_descriptions(attribute_descriptions.as_slice());
+        let ia_info = PipelineInputAssemblyStateCreateInfo::builder()
+            .primitive_restart_enable(false)
+            .topology(PrimitiveTopology::LINE_LIST);
+        let rs_info = PipelineRasterizationStateCreateInfo::builder()
+            .cull_mode(CullModeFlags::NONE)
+            .depth_bias_enable(false)
+            .depth_clamp_enable(false)
+            .front_face(FrontFace::CLOCKWISE)
+            .line_width(1.0)
+            .polygon_mode(PolygonMode::FILL)
+            .rasterizer_discard_enable(false);
+        let vp_info = PipelineViewportStateCreateInfo::builder()
+            .scissor_count(1)
+            .viewport_count(1);
+        let msaa_info = PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(sample_count)
+            .sample_shading_enable(false);
+        let color_blend_info = PipelineColorBlendStateCreateInfo::builder()
+            .logic_op(LogicOp::COPY)
+            .attachments(color_attachment.as_slice())
+            .logic_op_enable(false);
+        let depth_info = PipelineDepthStencilStateCreateInfo::builder()
+            .depth_bounds_test_enable(false)
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .stencil_test_enable(false);
+        let dynamic_states = vec![DynamicState::SCISSOR, DynamicState::VIEWPORT];
+        let dynamic_info =
+            PipelineDynamicStateCreateInfo::builder().dynamic_states(dynamic_states.as_slice());
+        let mut stage_infos = shaders
+            .iter()
+            .map(|s| s.shader_stage_info)
+            .collect::<Vec<_>>();
+        stage_infos.iter_mut().for_each(|s| {
+            s.p_name = name.as_ptr();
+        });
+        let render_pass = self
+            .render_pass
+            .get(&RenderPassType::Offscreen)
+            .copied()
+            .expect("Failed to get the offscreen renderpass for debug lines.");
+        let pipeline_info = vec![GraphicsPipelineCreateInfo::builder()
+            .layout(self.debug_line_layout)
+            .base_pipeline_index(-1)
+            .base_pipeline_handle(ash::vk::Pipeline::null())
+            .color_blend_state(&color_blend_info)
+            .depth_stencil_state(&depth_info)
+            .dynamic_state(&dynamic_info)
+            .input_assembly_state(&ia_info)
+            .multisample_state(&msaa_info)
+            .rasterization_state(&rs_info)
+            .render_pass(render_pass)
+            .subpass(0)
+            .vertex_input_state(&vi_info)
+            .viewport_state(&vp_info)
+            .stages(stage_infos.as_slice())
+            .build()];
+        unsafe {
+            let pipeline = self
+                .logical_device
+                .create_graphics_pipelines(PipelineCache::null(), pipeline_info.as_slice(), None)
+                .map_err(|(_, e)| e)?;
+            self.debug_line_pipeline = pipeline[0];
+        }
+        log::info!("Debug line pipeline successfully created.");
+        Ok(())
+    }
+
+    /// オクルージョンクエリで境界ボックスを描くパイプラインを作成する。<br />
+    /// 頂点は頂点シェーダーが番号から作る。深度を比べるだけで、色も深度も書かない。<br />
+    /// Create the pipeline drawing bounding boxes for occlusion queries.<br />
+    /// The vertex shader builds the vertices from their index. Depth is only tested, and neither color nor depth is written.
+    pub fn create_occlusion_pipeline(
+        &mut self,
+        descriptor_set_layout: DescriptorSetLayout,
+        sample_count: SampleCountFlags,
+        shaders: Vec<Shader>,
+    ) -> anyhow::Result<()> {
+        let push_constant_range = vec![PushConstantRange::builder()
+            .stage_flags(ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(std::mem::size_of::<OcclusionPushConstant>() as u32)
+            .build()];
+        let set_layouts = vec![descriptor_set_layout];
+        let name = CString::new("main")?;
+        let layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(set_layouts.as_slice())
+            .push_constant_ranges(push_constant_range.as_slice());
+        unsafe {
+            self.occlusion_layout = self
+                .logical_device
+                .create_pipeline_layout(&layout_info, None)?;
+        }
+
+        let color_attachment = vec![PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(ColorComponentFlags::empty())
+            .blend_enable(false)
+            .build()];
+        let vi_info = PipelineVertexInputStateCreateInfo::builder();
+        let ia_info = PipelineInputAssemblyStateCreateInfo::builder()
+            .primitive_restart_enable(false)
+            .topology(PrimitiveTopology::TRIANGLE_LIST);
+        // カメラに背を向けた面も数えるよう、裏面は捨てない。
+        // Back faces aren't culled, so faces turned away from the camera count too.
+        let rs_info = PipelineRasterizationStateCreateInfo::builder()
+            .cull_mode(CullModeFlags::NONE)
+            .depth_bias_enable(false)
+            .depth_clamp_enable(false)
+            .front_face(FrontFace::CLOCKWISE)
+            .line_width(1.0)
+            .polygon_mode(PolygonMode::FILL)
+            .rasterizer_discard_enable(false);
+        let vp_info = PipelineViewportStateCreateInfo::builder()
+            .scissor_count(1)
+            .viewport_count(1);
+        let msaa_info = PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(sample_count)
+            .sample_shading_enable(false);
+        let color_blend_info = PipelineColorBlendStateCreateInfo::builder()
+            .logic_op(LogicOp::COPY)
+            .attachments(color_attachment.as_slice())
+            .logic_op_enable(false);
+        let depth_info = PipelineDepthStencilStateCreateInfo::builder()
+            .depth_bounds_test_enable(false)
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(CompareOp::LESS_OR_EQUAL)
+            .stencil_test_enable(false);
+        let dynamic_states = vec![DynamicState::SCISSOR, DynamicState::VIEWPORT];
+        let dynamic_info =
+            PipelineDynamicStateCreateInfo::builder().dynamic_states(dynamic_states.as_slice());
+        let mut stage_infos = shaders
+            .iter()
+            .map(|s| s.shader_stage_info)
+            .collect::<Vec<_>>();
+        stage_infos.iter_mut().for_each(|s| {
+            s.p_name = name.as_ptr();
+        });
+        let render_pass = self
+            .render_pass
+            .get(&RenderPassType::Offscreen)
+            .copied()
+            .expect("Failed to get the offscreen renderpass for occlusion queries.");
+        let pipeline_info = vec![GraphicsPipelineCreateInfo::builder()
+            .layout(self.occlusion_layout)
+            .base_pipeline_index(-1)
+            .base_pipeline_handle(ash::vk::Pipeline::null())
+            .color_blend_state(&color_blend_info)
+            .depth_stencil_state(&depth_info)
+            .dynamic_state(&dynamic_info)
+            .input_assembly_state(&ia_info)
+            .multisample_state(&msaa_info)
+            .rasterization_state(&rs_info)
+            .render_pass(render_pass)
+            .subpass(0)
+            .vertex_input_state(&vi_info)
+            .viewport_state(&vp_info)
+            .stages(stage_infos.as_slice())
+            .build()];
+        unsafe {
+            let pipeline = self
+                .logical_device
+                .create_graphics_pipelines(PipelineCache::null(), pipeline_info.as_slice(), None)
+                .map_err(|(_, e)| e)?;
+            self.occlusion_pipeline = pipeline[0];
+        }
+        log::info!("Occlusion pipeline successfully created.");
+        Ok(())
+    }
+
+    /// 画面に重ねるスプライトを描くパイプラインを作成する。<br />
+    /// スワップチェーンに書く最後のパスで、ポストプロセスの後に描く。<br />
+    /// Create the pipeline drawing sprites overlaid on the screen.<br />
+    /// They're drawn in the final pass writing the swapchain, after post-processing.
+    pub fn create_sprite_pipeline(
+        &mut self,
+        descriptor_set_layout: DescriptorSetLayout,
+        sample_count: SampleCountFlags,
+        shaders: Vec<Shader>,
+    ) -> anyhow::Result<()> {
+        let set_layouts = vec![descriptor_set_layout];
+        let name = CString::new("main")?;
+        let layout_info = PipelineLayoutCreateInfo::builder().set_layouts(set_layouts.as_slice());
+        unsafe {
+            self.sprite_layout = self
+                .logical_device
+                .create_pipeline_layout(&layout_info, None)?;
+        }
+
+        let color_attachment = vec![PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(
+                ColorComponentFlags::R
+                    | ColorComponentFlags::G
+                    | ColorComponentFlags::B
+                    | ColorComponentFlags::A,
+            )
+            .blend_enable(true)
+            .src_color_blend_factor(BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(BlendOp::ADD)
+            .src_alpha_blend_factor(BlendFactor::ONE)
+            .dst_alpha_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(BlendOp::ADD)
+            .build()];
+        let binding_description = vec![SpriteVertex::get_binding_description(0)];
+        let attribute_descriptions = SpriteVertex::get_attribute_description(0);
+        let vi_info = PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(binding_description.as_slice())
+            .vertex_attribute_descriptions(attribute_descriptions.as_slice());
+        let ia_info = PipelineInputAssemblyStateCreateInfo::builder()
+            .primitive_restart_enable(false)
+            .topology(PrimitiveTopology::TRIANGLE_LIST);
+        let rs_info = PipelineRasterizationStateCreateInfo::builder()
+            .cull_mode(CullModeFlags::NONE)
+            .depth_bias_enable(false)
+            .depth_clamp_enable(false)
+            .front_face(FrontFace::CLOCKWISE)
+            .line_width(1.0)
+            .polygon_mode(PolygonMode::FILL)
+            .rasterizer_discard_enable(false);
+        let vp_info = PipelineViewportStateCreateInfo::builder()
+            .scissor_count(1)
+            .viewport_count(1);
+        let msaa_info = PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(sample_count)
+            .sample_shading_enable(false);
+        let color_blend_info = PipelineColorBlendStateCreateInfo::builder()
+            .logic_op(LogicOp::COPY)
+            .attachments(color_attachment.as_slice())
+            .logic_op_enable(false);
+        let depth_info = PipelineDepthStencilStateCreateInfo::builder()
+            .depth_bounds_test_enable(false)
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .stencil_test_enable(false);
+        let dynamic_states = vec![DynamicState::SCISSOR, DynamicState::VIEWPORT];
+        let dynamic_info =
+            PipelineDynamicStateCreateInfo::builder().dynamic_states(dynamic_states.as_slice());
+        let mut stage_infos = shaders
+            .iter()
+            .map(|s| s.shader_stage_info)
+            .collect::<Vec<_>>();
+        stage_infos.iter_mut().for_each(|s| {
+            s.p_name = name.as_ptr();
+        });
+        let render_pass = self
+            .render_pass
+            .get(&RenderPassType::Primary)
+            .copied()
+            .expect("Failed to get the primary renderpass for sprites.");
+        let pipeline_info = vec![GraphicsPipelineCreateInfo::builder()
+            .layout(self.sprite_layout)
+            .base_pipeline_index(-1)
+            .base_pipeline_handle(ash::vk::Pipeline::null())
+            .color_blend_state(&color_blend_info)
+            .depth_stencil_state(&depth_info)
+            .dynamic_state(&dynamic_info)
+            .input_assembly_state(&ia_info)
+            .multisample_state(&msaa_info)
+            .rasterization_state(&rs_info)
+            .render_pass(render_pass)
+            .subpass(0)
+            .vertex_input_state(&vi_info)
+            .viewport_state(&vp_info)
+            .stages(stage_infos.as_slice())
+            .build()];
+        unsafe {
+            let pipeline = self
+                .logical_device
+                .create_graphics_pipelines(PipelineCache::null(), pipeline_info.as_slice(), None)
+                .map_err(|(_, e)| e)?;
+            self.sprite_pipeline = pipeline[0];
+        }
+        log::info!("Sprite pipeline successfully created.");
+        Ok(())
+    }
+
+    /// 視錐台の外の間接描画を間引く計算パイプラインを作成する。<br />
+    /// Create the compute pipeline culling indirect draws outside the frustum.
+    pub fn create_indirect_cull_pipeline(
+        &mut self,
+        descriptor_set_layout: DescriptorSetLayout,
+        compute_shader: Shader,
+    ) -> anyhow::Result<()> {
+        let push_constant_range = vec![PushConstantRange::builder()
+            .stage_flags(ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(std::mem::size_of::<IndirectCullPushConstant>() as u32)
+            .build()];
+        let set_layouts = vec![descriptor_set_layout];
+        let name = CString::new("main")?;
+        let layout_info = PipelineLayoutCreateInfo::builder()
+            .set_layouts(set_layouts.as_slice())
+            .push_constant_ranges(push_constant_range.as_slice());
+        let mut compute_stage = compute_shader.shader_stage_info;
+        compute_stage.p_name = name.as_ptr();
+        unsafe {
+            self.indirect_cull_layout = self
+                .logical_device
+                .create_pipeline_layout(&layout_info, None)?;
+            let pipeline_info = vec![ComputePipelineCreateInfo::builder()
+                .layout(self.indirect_cull_layout)
+                .stage(compute_stage)
+                .base_pipeline_index(-1)
+                .base_pipeline_handle(ash::vk::Pipeline::null())
+                .build()];
+            let pipeline = self
+                .logical_device
+                .create_compute_pipelines(PipelineCache::null(), pipeline_info.as_slice(), None)
+                .map_err(|(_, e)| e)?;
+            self.indirect_cull_pipeline = pipeline[0];
+        }
+        log::info!("Indirect cull pipeline successfully created.");
+        Ok(())
+    }
+
+    pub fn get_post_process_pipeline(
+        &self,
+        step: PostProcessStep,
+        render_pass_type: RenderPassType,
+    ) -> ash::vk::Pipeline {
+        *self
+            .post_process_pipelines
+            .get(&(step, render_pass_type))
+            .expect("Failed to get the post-processing pipeline.")
+    }
+
     pub fn get_pipeline(&self, shader_type: ShaderType, index: usize) -> ash::vk::Pipeline {
         let pipelines = self.graphic_pipelines.get(&shader_type).unwrap();
         *pipelines.get(index).unwrap()
@@ -566,6 +1244,40 @@ impl Pipeline {
         *self.pipeline_layouts.get(&shader_type).unwrap()
     }
 
+    /// 作ってあるパイプラインの名前。診断のレポートに使う。<br />
+    /// Names of the pipelines that have been created, used in diagnostic reports.
+    pub fn get_pipeline_names(&self) -> Vec<String> {
+        let mut names = self
+            .shader_types
+            .iter()
+            .filter_map(|(shader_type, type_name)| {
+                self.graphic_pipelines
+                    .get(shader_type)
+                    .map(|pipelines| format!("{} ({} pipelines)", type_name, pipelines.len()))
+            })
+            .collect::<Vec<_>>();
+        names.extend(
+            self.post_process_pipelines
+                .keys()
+                .map(|(step, render_pass_type)| format!("{:?} ({:?})", step, render_pass_type)),
+        );
+        for (name, pipeline) in [
+            ("Particle", self.particle_pipeline),
+            ("ParticleSimulation", self.particle_simulation_pipeline),
+            ("DebugLine", self.debug_line_pipeline),
+            ("Occlusion", self.occlusion_pipeline),
+            ("Sprite", self.sprite_pipeline),
+            ("IndirectCull", self.indirect_cull_pipeline),
+        ]
+        .iter()
+        {
+            if *pipeline != ash::vk::Pipeline::null() {
+                names.push(name.to_string());
+            }
+        }
+        names
+    }
+
     /// パイプラインのキャッシュを書き出して、次回プログラムを実行する際にパイプラインの作成を加速する。<br />
     /// Write out pipeline cache to accelerate the creation of pipelines next time when the program boots up.
     fn write_cache_data(&self) {
@@ -597,6 +1309,44 @@ impl Drop for Pipeline {
             }
             self.graphic_pipelines.clear();
 
+            for (_, pipeline) in self.post_process_pipelines.iter() {
+                self.logical_device.destroy_pipeline(*pipeline, None);
+            }
+            self.post_process_pipelines.clear();
+            if self.post_process_layout != PipelineLayout::null() {
+                self.logical_device
+                    .destroy_pipeline_layout(self.post_process_layout, None);
+            }
+
+            for pipeline in [
+                self.particle_pipeline,
+                self.particle_simulation_pipeline,
+                self.debug_line_pipeline,
+                self.occlusion_pipeline,
+                self.sprite_pipeline,
+                self.indirect_cull_pipeline,
+            ]
+            .iter()
+            {
+                if *pipeline != ash::vk::Pipeline::null() {
+                    self.logical_device.destroy_pipeline(*pipeline, None);
+                }
+            }
+            for layout in [
+                self.particle_layout,
+                self.particle_simulation_layout,
+                self.debug_line_layout,
+                self.occlusion_layout,
+                self.sprite_layout,
+                self.indirect_cull_layout,
+            ]
+            .iter()
+            {
+                if *layout != PipelineLayout::null() {
+                    self.logical_device.destroy_pipeline_layout(*layout, None);
+                }
+            }
+
             for (_, layout) in self.pipeline_layouts.iter() {
                 self.logical_device.destroy_pipeline_layout(*layout, None);
             }
@@ -0,0 +1,339 @@
+use ash::prelude::VkResult;
+use ash::version::DeviceV1_0;
+use ash::{vk::*, Device};
+use crossbeam::sync::ShardedLock;
+use std::collections::HashMap;
+use std::sync::Weak;
+use vk_mem::Allocator;
+
+use crate::game::graphics::vk::{
+    DescriptorAllocator, DescriptorBuilder, DescriptorLayoutCache, FramebufferSource,
+    GraphicsError, Initializer, RenderPassType,
+};
+use crate::game::shared::structs::{PostProcessSettings, PostProcessStep};
+use crate::game::traits::Disposable;
+
+/// シーンとブルームを描画するHDRのフォーマット。<br />
+/// The HDR format the scene and bloom are rendered in.
+pub const HDR_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+
+/// フルスクリーンのパスが読むイメージ。<br />
+/// Images read by the fullscreen passes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+enum PostProcessInput {
+    Scene,
+    Bloom(usize),
+    Ldr,
+}
+
+/// ステップのパイプラインを作るレンダパス。合成はFXAAの有無で描画先が変わるので二つ作る。<br />
+/// Renderpasses the pipelines of a step are created for. The composite needs both because its target depends on whether FXAA is on.
+pub fn get_post_process_render_passes(step: PostProcessStep) -> &'static [RenderPassType] {
+    match step {
+        PostProcessStep::BrightPass
+        | PostProcessStep::BlurHorizontal
+        | PostProcessStep::BlurVertical => &[RenderPassType::PostProcess],
+        PostProcessStep::Composite => &[RenderPassType::PostProcess, RenderPassType::Primary],
+        PostProcessStep::Fxaa => &[RenderPassType::Primary],
+    }
+}
+
+/// ステップが書くフレームバッファとそのレンダパス。最後のステップはスワップチェーンに書く。<br />
+/// The framebuffer a step writes and its renderpass. The final step writes the swapchain.
+pub fn get_post_process_target(
+    step: PostProcessStep,
+    settings: &PostProcessSettings,
+) -> (RenderPassType, FramebufferSource) {
+    if step == settings.get_final_step() {
+        return (RenderPassType::Primary, FramebufferSource::Swapchain);
+    }
+    let framebuffer = match step {
+        PostProcessStep::BrightPass | PostProcessStep::BlurVertical => FramebufferSource::Bloom(0),
+        PostProcessStep::BlurHorizontal => FramebufferSource::Bloom(1),
+        PostProcessStep::Composite | PostProcessStep::Fxaa => FramebufferSource::Ldr,
+    };
+    (RenderPassType::PostProcess, framebuffer)
+}
+
+/// ステップが読む二つのイメージ。ブルームを使わない合成は、未描画のイメージを読まないようにシーンを二回読む。<br />
+/// The two images a step reads. Without bloom, the composite reads the scene twice so it never samples an image that wasn't rendered.
+fn get_inputs(
+    step: PostProcessStep,
+    settings: &PostProcessSettings,
+) -> (PostProcessInput, PostProcessInput) {
+    match step {
+        PostProcessStep::BrightPass => (PostProcessInput::Scene, PostProcessInput::Scene),
+        PostProcessStep::BlurHorizontal => (PostProcessInput::Bloom(0), PostProcessInput::Bloom(0)),
+        PostProcessStep::BlurVertical => (PostProcessInput::Bloom(1), PostProcessInput::Bloom(1)),
+        PostProcessStep::Composite if settings.is_bloom_enabled => {
+            (PostProcessInput::Scene, PostProcessInput::Bloom(0))
+        }
+        PostProcessStep::Composite => (PostProcessInput::Scene, PostProcessInput::Scene),
+        PostProcessStep::Fxaa => (PostProcessInput::Ldr, PostProcessInput::Ldr),
+    }
+}
+
+/// ポストプロセスのレンダーターゲット。シーンをHDRで描画し、ブルームは半分の解像度で処理する。<br />
+/// シーンの解像度はスワップチェーンと違ってもよく、最後のステップがスワップチェーンの大きさに拡大縮小する。<br />
+/// Render targets for post-processing. The scene is rendered in HDR and bloom is processed at half resolution.<br />
+/// The scene resolution may differ from the swapchain, and the final step scales it to the size of the swapchain.
+pub struct PostProcessTargets {
+    pub descriptor_set_layout: DescriptorSetLayout,
+    scene_framebuffer: Framebuffer,
+    bloom_framebuffers: [Framebuffer; 2],
+    ldr_framebuffer: Framebuffer,
+    descriptor_sets: HashMap<(PostProcessInput, PostProcessInput), DescriptorSet>,
+    extent: Extent2D,
+    bloom_extent: Extent2D,
+    scene_msaa_image: super::Image,
+    scene_depth_image: super::Image,
+    scene_image: super::Image,
+    bloom_images: [super::Image; 2],
+    ldr_image: super::Image,
+    logical_device: Weak<Device>,
+}
+
+impl PostProcessTargets {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: Weak<Device>,
+        extent: Extent2D,
+        depth_format: Format,
+        sample_count: SampleCountFlags,
+        scene_renderpass: RenderPass,
+        post_process_renderpass: RenderPass,
+        command_pool: CommandPool,
+        graphics_queue: Queue,
+        allocator: Weak<ShardedLock<Allocator>>,
+        layout_cache: &mut DescriptorLayoutCache,
+        descriptor_allocator: &mut DescriptorAllocator,
+    ) -> anyhow::Result<Self> {
+        let bloom_extent = Extent2D {
+            width: (extent.width / 2).max(1),
+            height: (extent.height / 2).max(1),
+        };
+        let create_color_image = |extent: Extent2D| -> Result<super::Image, GraphicsError> {
+            let mut image = super::Image::new(
+                device.clone(),
+                ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+                HDR_FORMAT,
+                SampleCountFlags::TYPE_1,
+                extent,
+                ImageType::TYPE_2D,
+                1,
+                ImageAspectFlags::COLOR,
+                allocator.clone(),
+            )?;
+            image.create_sampler(1, SamplerAddressMode::CLAMP_TO_EDGE)?;
+            Ok(image)
+        };
+        let scene_image = create_color_image(extent)?;
+        let bloom_images = [
+            create_color_image(bloom_extent)?,
+            create_color_image(bloom_extent)?,
+        ];
+        let ldr_image = create_color_image(extent)?;
+        let scene_msaa_image = Initializer::create_msaa_image(
+            device.clone(),
+            HDR_FORMAT,
+            extent,
+            command_pool,
+            graphics_queue,
+            sample_count,
+            allocator.clone(),
+        )?;
+        let scene_depth_image = Initializer::create_depth_image(
+            device.clone(),
+            depth_format,
+            extent,
+            command_pool,
+            graphics_queue,
+            sample_count,
+            allocator,
+        )?;
+
+        let device_arc = device
+            .upgrade()
+            .expect("Failed to upgrade logical device to create post-processing framebuffers.");
+        let create_framebuffer = |renderpass: RenderPass,
+                                  image_views: &[ImageView],
+                                  extent: Extent2D|
+         -> VkResult<Framebuffer> {
+            let framebuffer_info = FramebufferCreateInfo::builder()
+                .width(extent.width)
+                .height(extent.height)
+                .render_pass(renderpass)
+                .attachments(image_views)
+                .layers(1);
+            unsafe { device_arc.create_framebuffer(&framebuffer_info, None) }
+        };
+        let scene_framebuffer = create_framebuffer(
+            scene_renderpass,
+            &Initializer::get_framebuffer_attachments(
+                sample_count,
+                scene_msaa_image.image_view,
+                scene_depth_image.image_view,
+                scene_image.image_view,
+            ),
+            extent,
+        )?;
+        let bloom_framebuffers = [
+            create_framebuffer(
+                post_process_renderpass,
+                &[bloom_images[0].image_view],
+                bloom_extent,
+            )?,
+            create_framebuffer(
+                post_process_renderpass,
+                &[bloom_images[1].image_view],
+                bloom_extent,
+            )?,
+        ];
+        let ldr_framebuffer =
+            create_framebuffer(post_process_renderpass, &[ldr_image.image_view], extent)?;
+
+        let mut targets = PostProcessTargets {
+            descriptor_set_layout: DescriptorSetLayout::null(),
+            scene_framebuffer,
+            bloom_framebuffers,
+            ldr_framebuffer,
+            descriptor_sets: HashMap::new(),
+            extent,
+            bloom_extent,
+            scene_msaa_image,
+            scene_depth_image,
+            scene_image,
+            bloom_images,
+            ldr_image,
+            logical_device: device,
+        };
+        targets.allocate_descriptors(layout_cache, descriptor_allocator)?;
+        log::info!("Post-processing targets successfully created.");
+        Ok(targets)
+    }
+
+    /// フレームバッファとその大きさを取得する。スワップチェーンとオフスクリーンのものは持たない。<br />
+    /// Get a framebuffer and its extent. Swapchain and offscreen framebuffers aren't owned here.
+    pub fn get_framebuffer(&self, source: FramebufferSource) -> Option<(Framebuffer, Extent2D)> {
+        match source {
+            FramebufferSource::Scene => Some((self.scene_framebuffer, self.extent)),
+            FramebufferSource::Bloom(index) => self
+                .bloom_framebuffers
+                .get(index)
+                .map(|framebuffer| (*framebuffer, self.bloom_extent)),
+            FramebufferSource::Ldr => Some((self.ldr_framebuffer, self.extent)),
+            FramebufferSource::Swapchain | FramebufferSource::Offscreen(_) => None,
+        }
+    }
+
+    /// ステップが読むイメージの大きさ。ぼかしはブルームを、それ以外はシーンの解像度のイメージを読む。<br />
+    /// Extent of the images a step reads. Blurring reads bloom, and every other step reads images at the scene resolution.
+    pub fn get_input_extent(&self, step: PostProcessStep) -> Extent2D {
+        match step {
+            PostProcessStep::BlurHorizontal | PostProcessStep::BlurVertical => self.bloom_extent,
+            _ => self.extent,
+        }
+    }
+
+    pub fn get_descriptor_set(
+        &self,
+        step: PostProcessStep,
+        settings: &PostProcessSettings,
+    ) -> DescriptorSet {
+        *self
+            .descriptor_sets
+            .get(&get_inputs(step, settings))
+            .expect("Failed to get the descriptor set of the post-processing step.")
+    }
+
+    fn get_input_image(&self, input: PostProcessInput) -> &super::Image {
+        match input {
+            PostProcessInput::Scene => &self.scene_image,
+            PostProcessInput::Bloom(index) => &self.bloom_images[index],
+            PostProcessInput::Ldr => &self.ldr_image,
+        }
+    }
+
+    /// 全ての入力の組み合わせに描述子セットを配置する。設定が変わっても配置し直さずに済む。<br />
+    /// Allocate a descriptor set for every combination of inputs, so nothing is reallocated when the settings change.
+    fn allocate_descriptors(
+        &mut self,
+        layout_cache: &mut DescriptorLayoutCache,
+        descriptor_allocator: &mut DescriptorAllocator,
+    ) -> anyhow::Result<()> {
+        let with_bloom = PostProcessSettings {
+            is_bloom_enabled: true,
+            ..PostProcessSettings::default()
+        };
+        let without_bloom = PostProcessSettings {
+            is_bloom_enabled: false,
+            ..PostProcessSettings::default()
+        };
+        for step in PostProcessStep::get_all_steps().into_iter() {
+            for settings in [with_bloom, without_bloom].iter() {
+                let inputs = get_inputs(step, settings);
+                if self.descriptor_sets.contains_key(&inputs) {
+                    continue;
+                }
+                let image_info = [inputs.0, inputs.1]
+                    .iter()
+                    .map(|input| {
+                        let image = self.get_input_image(*input);
+                        vec![DescriptorImageInfo::builder()
+                            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                            .image_view(image.image_view)
+                            .sampler(image.sampler)
+                            .build()]
+                    })
+                    .collect::<Vec<_>>();
+                let (descriptor_set, descriptor_set_layout) =
+                    DescriptorBuilder::builder(layout_cache, descriptor_allocator)
+                        .bind_image(
+                            0,
+                            None,
+                            &image_info[0],
+                            DescriptorType::COMBINED_IMAGE_SAMPLER,
+                            ShaderStageFlags::FRAGMENT,
+                        )
+                        .bind_image(
+                            1,
+                            None,
+                            &image_info[1],
+                            DescriptorType::COMBINED_IMAGE_SAMPLER,
+                            ShaderStageFlags::FRAGMENT,
+                        )
+                        .build()
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("Failed to allocate post-processing descriptor set.")
+                        })?;
+                self.descriptor_sets.insert(inputs, descriptor_set);
+                self.descriptor_set_layout = descriptor_set_layout;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PostProcessTargets {
+    fn drop(&mut self) {
+        if let Some(device) = self.logical_device.upgrade() {
+            unsafe {
+                device.destroy_framebuffer(self.scene_framebuffer, None);
+                for framebuffer in self.bloom_framebuffers.iter() {
+                    device.destroy_framebuffer(*framebuffer, None);
+                }
+                device.destroy_framebuffer(self.ldr_framebuffer, None);
+            }
+        }
+        // フレームバッファを壊してからイメージを解放する。
+        // The images are released after the framebuffers using them are destroyed.
+        self.scene_msaa_image.dispose();
+        self.scene_depth_image.dispose();
+        self.scene_image.dispose();
+        for image in self.bloom_images.iter_mut() {
+            image.dispose();
+        }
+        self.ldr_image.dispose();
+    }
+}
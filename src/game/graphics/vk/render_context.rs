@@ -0,0 +1,124 @@
+use crate::game::graphics::vk::{
+    Buffer, Image, IndirectBatchKey, Pipeline, TextureDescriptorSets, ThreadPool,
+};
+use crate::game::shared::enums::ShaderType;
+use crate::game::shared::structs::{DrawArguments, DrawInfo, IndirectDrawList, Mesh, RecordKey};
+use ash::vk::{CommandBuffer, CommandBufferInheritanceInfo, Rect2D, Viewport};
+use ash::Device;
+use crossbeam::sync::ShardedLock;
+use glam::Mat4;
+use parking_lot::Mutex;
+use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Arc;
+
+/// Vulkanでモデルのセカンダリーコマンドバッファを記録するのに必要なもの。<br />
+/// What models need to record their secondary command buffers with Vulkan.
+#[derive(Clone)]
+pub struct RenderContext {
+    pub inheritance_info: Arc<AtomicPtr<CommandBufferInheritanceInfo>>,
+    pub viewport: Viewport,
+    pub scissor: Rect2D,
+    pub device: Arc<Device>,
+    pub pipeline: Arc<ShardedLock<ManuallyDrop<Pipeline>>>,
+    pub descriptor_sets: Arc<TextureDescriptorSets>,
+    pub thread_pool: Arc<ThreadPool>,
+    /// パイプラインや描述子セットを作り直すたびに変わる世代。<br />
+    /// Generation that changes whenever pipelines or descriptor sets are rebuilt.
+    pub record_generation: u64,
+    /// 間接描画で描けるなら、描画を集めるリスト。シーンのパスで間接描画が使える時だけある。<br />
+    /// The list collecting draws that can be drawn indirectly. Only present in the scene pass while indirect draws are usable.
+    pub indirect_draws: Option<Arc<Mutex<IndirectDrawList<IndirectBatchKey>>>>,
+}
+
+impl RenderContext {
+    /// パスと描述子セットから、記録する内容のキーを作り始める。モデルはパイプラインやバッファなどを加える。<br />
+    /// Start a key of the recorded content from the pass and the descriptor sets. Models add their pipelines, buffers and so on.
+    pub fn get_record_key(&self) -> RecordKey {
+        let pass = unsafe {
+            self.inheritance_info
+                .load(Ordering::SeqCst)
+                .as_ref()
+                .map(|inheritance| (inheritance.render_pass, inheritance.framebuffer))
+        };
+        let viewport = [
+            self.viewport.x,
+            self.viewport.y,
+            self.viewport.width,
+            self.viewport.height,
+            self.viewport.min_depth,
+            self.viewport.max_depth,
+        ];
+        RecordKey::new(self.record_generation)
+            .with(pass)
+            .with(viewport.iter().map(|v| v.to_bits()).collect::<Vec<_>>())
+            .with((
+                self.scissor.offset.x,
+                self.scissor.offset.y,
+                self.scissor.extent.width,
+                self.scissor.extent.height,
+            ))
+            .with(self.descriptor_sets.get_sets())
+    }
+
+    /// メッシュのプリミティブを間接描画に加える。加えたなら、メッシュは自分のコマンドバッファを記録しなくてよい。<br />
+    /// ワールド行列があれば、ワールド空間の境界で視錐台の外の描画を間引く。<br />
+    /// Add the primitives of a mesh to indirect draws. If added, the mesh doesn't have to record its own command buffer.<br />
+    /// With a world matrix, draws outside the frustum are culled by their bounds in world space.
+    pub fn push_indirect_draws(
+        &self,
+        mesh: &Mesh<Buffer, CommandBuffer, Image>,
+        shader_type: ShaderType,
+        model_index: usize,
+        instances: Option<(ash::vk::Buffer, u32)>,
+        world_matrix: Option<Mat4>,
+    ) -> bool {
+        let indirect_draws = match self.indirect_draws.as_ref() {
+            Some(indirect_draws) => indirect_draws,
+            None => return false,
+        };
+        let indirect_shader_type = match shader_type.get_indirect_shader_type() {
+            Some(shader_type) => shader_type,
+            None => return false,
+        };
+        if !mesh.has_buffers() {
+            return false;
+        }
+        let vertex_buffer = mesh.get_vertex_buffer();
+        let index_buffer = mesh.get_index_buffer();
+        let (instance_buffer, instance_count) = instances.unwrap_or((ash::vk::Buffer::null(), 1));
+        let bounds = match (mesh.bounds.as_ref(), world_matrix) {
+            (Some(bounds), Some(world_matrix)) => Some(bounds.transform(world_matrix)),
+            _ => None,
+        };
+        let mut indirect_draws = indirect_draws.lock();
+        let mut vertex_offset = mesh.get_first_vertex();
+        let mut first_index = mesh.get_first_index();
+        for primitive in mesh.primitives.iter() {
+            let (descriptor_set, texture_index) = self
+                .descriptor_sets
+                .locate(primitive.texture_index.unwrap_or_default());
+            let key = IndirectBatchKey {
+                shader_type: indirect_shader_type,
+                descriptor_set,
+                vertex_buffer,
+                instance_buffer,
+                index_buffer,
+            };
+            let arguments = DrawArguments::new(
+                primitive.indices.len() as u32,
+                instance_count,
+                first_index,
+                vertex_offset,
+            );
+            let mut info = DrawInfo::new(model_index as u32, texture_index as u32, instance_count);
+            if let Some(bounds) = bounds.as_ref() {
+                info = info.with_bounds(bounds);
+            }
+            indirect_draws.push(key, arguments, info);
+            vertex_offset += primitive.vertices.len() as i32;
+            first_index += primitive.indices.len() as u32;
+        }
+        true
+    }
+}
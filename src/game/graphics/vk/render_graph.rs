@@ -0,0 +1,85 @@
+use ash::vk::*;
+
+use crate::game::graphics::vk::RenderPassType;
+use crate::game::shared::structs::{PassOutput, PostProcessStep};
+
+/// パスが描画するフレームバッファ。<br />
+/// The framebuffer a pass renders into.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FramebufferSource {
+    /// 今のフレームのスワップチェーンのフレームバッファ。<br />
+    /// The swapchain framebuffer of the current frame.
+    Swapchain,
+    /// オフスクリーンのフレームバッファの番号。<br />
+    /// Index of an offscreen framebuffer.
+    Offscreen(usize),
+    /// HDRでシーンを描画するフレームバッファ。<br />
+    /// The framebuffer the scene is rendered into in HDR.
+    Scene,
+    /// ブルームのフレームバッファの番号。<br />
+    /// Index of a bloom framebuffer.
+    Bloom(usize),
+    /// トーンマッピングした後、FXAAを掛ける前のフレームバッファ。<br />
+    /// The framebuffer after tonemapping and before FXAA.
+    Ldr,
+}
+
+/// レンダーグラフのパスを実行するためのVulkanのリソース。<br />
+/// Vulkan resources for executing a pass of the render graph.
+#[derive(Copy, Clone, Debug)]
+pub struct PassTarget {
+    pub render_pass_type: RenderPassType,
+    pub framebuffer: FramebufferSource,
+    /// `None`の場合はフレームバッファの大きさを使う。<br />
+    /// The extent of the framebuffer is used if `None`.
+    pub extent: Option<Extent2D>,
+    /// `None`の場合はモデルを描画し、それ以外はフルスクリーンのポストプロセスを実行する。<br />
+    /// Renderables are drawn if `None`; otherwise a fullscreen post-processing step runs.
+    pub post_process_step: Option<PostProcessStep>,
+}
+
+/// パスの結果を使う段階とアクセス。<br />
+/// The stage and access that consume the result of a pass.
+fn get_consumer(output: PassOutput) -> (PipelineStageFlags, AccessFlags) {
+    match output {
+        PassOutput::Sampled => (
+            PipelineStageFlags::FRAGMENT_SHADER,
+            AccessFlags::SHADER_READ,
+        ),
+        PassOutput::Present => (PipelineStageFlags::BOTTOM_OF_PIPE, AccessFlags::MEMORY_READ),
+    }
+}
+
+/// パスの結果の使い方からサブパスの依存関係を導く。<br />
+/// パスの前では前のフレームの読み込みを待ち、パスの後では書き込みを読む側に見せる。<br />
+/// Derive the subpass dependencies from how the result of a pass is used.<br />
+/// Before the pass, the reads of the previous frame are waited for; after it, the writes are made visible to the readers.
+pub fn derive_subpass_dependencies(output: PassOutput) -> Vec<SubpassDependency> {
+    let (consumer_stage, consumer_access) = get_consumer(output);
+    let attachment_access = match output {
+        PassOutput::Sampled => AccessFlags::COLOR_ATTACHMENT_WRITE,
+        PassOutput::Present => {
+            AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE
+        }
+    };
+    vec![
+        SubpassDependency::builder()
+            .src_subpass(SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(consumer_stage)
+            .dst_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(consumer_access)
+            .dst_access_mask(attachment_access)
+            .dependency_flags(DependencyFlags::BY_REGION)
+            .build(),
+        SubpassDependency::builder()
+            .src_subpass(0)
+            .dst_subpass(SUBPASS_EXTERNAL)
+            .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_stage_mask(consumer_stage)
+            .src_access_mask(attachment_access)
+            .dst_access_mask(consumer_access)
+            .dependency_flags(DependencyFlags::BY_REGION)
+            .build(),
+    ]
+}
@@ -0,0 +1,177 @@
+use ash::{version::DeviceV1_0, vk::*, Device};
+use crossbeam::sync::ShardedLock;
+use std::ffi::c_void;
+use std::sync::{Arc, Weak};
+use vk_mem::Allocator;
+
+/// 水面の波紋のテクスチャ。テクスチャ配列に登録したイメージと、フレームごとのホストから見えるステージングバッファを持つ。<br />
+/// ミップは作り直さないので、シェーダーは一番上のミップだけを読む。<br />
+/// The texture of the ripples on the water surface. Owns the image registered in the texture array and a host-visible staging buffer for each frame.<br />
+/// Mips aren't generated again, so shaders only read the top mip.
+pub struct RippleTexture {
+    pub texture: Arc<ShardedLock<super::Image>>,
+    pub texture_index: usize,
+    logical_device: Arc<Device>,
+    allocator: Weak<ShardedLock<Allocator>>,
+    staging_buffers: Vec<Option<super::Buffer>>,
+    pixels: Option<Vec<u8>>,
+}
+
+unsafe impl Send for RippleTexture {}
+unsafe impl Sync for RippleTexture {}
+
+impl RippleTexture {
+    pub fn new(
+        texture: Arc<ShardedLock<super::Image>>,
+        texture_index: usize,
+        logical_device: Arc<Device>,
+        allocator: Weak<ShardedLock<Allocator>>,
+        frame_count: usize,
+    ) -> Self {
+        log::info!("Ripple texture successfully created.");
+        RippleTexture {
+            texture,
+            texture_index,
+            logical_device,
+            allocator,
+            staging_buffers: (0..frame_count).map(|_| None).collect(),
+            pixels: None,
+        }
+    }
+
+    /// 次のフレームでアップロードするピクセルを設定する。前のピクセルがまだなら置き換える。<br />
+    /// Set the pixels uploaded in the next frame, replacing the previous ones if they're still pending.
+    pub fn set_pixels(&mut self, pixels: Vec<u8>) {
+        self.pixels = Some(pixels);
+    }
+
+    /// ピクセルをこのフレームのバッファに書き込み、レンダーパスの前にイメージへのコピーを記録する。<br />
+    /// Write the pixels into this frame's buffer and record the copy to the image before the render passes.
+    pub fn record_upload(
+        &mut self,
+        frame_index: usize,
+        command_buffer: CommandBuffer,
+    ) -> anyhow::Result<()> {
+        let pixels = match self.pixels.take() {
+            Some(pixels) => pixels,
+            None => return Ok(()),
+        };
+        let texture = self
+            .texture
+            .read()
+            .expect("Failed to lock the ripple texture.");
+        let data_size = (texture.width * texture.height * 4) as DeviceSize;
+        if pixels.len() as DeviceSize != data_size {
+            return Err(anyhow::anyhow!(
+                "Ripple pixels don't match the texture. Expected {} bytes, got {}.",
+                data_size,
+                pixels.len()
+            ));
+        }
+        if self.staging_buffers[frame_index].is_none() {
+            self.staging_buffers[frame_index] = Some(super::Buffer::new(
+                Arc::downgrade(&self.logical_device),
+                data_size,
+                BufferUsageFlags::TRANSFER_SRC,
+                MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+                self.allocator.clone(),
+            )?);
+        }
+        let staging_buffer = self.staging_buffers[frame_index]
+            .as_ref()
+            .expect("Failed to get the ripple staging buffer.");
+        let subresource_range = ImageSubresourceRange::builder()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build();
+        let to_transfer = ImageMemoryBarrier::builder()
+            .image(texture.image)
+            .src_access_mask(AccessFlags::SHADER_READ)
+            .dst_access_mask(AccessFlags::TRANSFER_WRITE)
+            .old_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .new_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .subresource_range(subresource_range)
+            .build();
+        let to_shader = ImageMemoryBarrier::builder()
+            .image(texture.image)
+            .src_access_mask(AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(AccessFlags::SHADER_READ)
+            .old_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+            .subresource_range(subresource_range)
+            .build();
+        let copy_info = BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(
+                ImageSubresourceLayers::builder()
+                    .aspect_mask(ImageAspectFlags::COLOR)
+                    .mip_level(0)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .build(),
+            )
+            .image_offset(Offset3D::default())
+            .image_extent(Extent3D {
+                width: texture.width,
+                height: texture.height,
+                depth: 1,
+            })
+            .build();
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                pixels.as_ptr() as *const c_void,
+                staging_buffer.mapped_memory,
+                data_size as usize,
+            );
+            // 前のフレームが読み終わってから書き、書き終わってからフラグメントシェーダーで読む。
+            // Write after the previous frame has finished reading, and read in fragment shaders after writing.
+            self.logical_device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::FRAGMENT_SHADER,
+                PipelineStageFlags::TRANSFER,
+                DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+            self.logical_device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging_buffer.buffer,
+                texture.image,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[copy_info],
+            );
+            self.logical_device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::FRAGMENT_SHADER,
+                DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_shader],
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RippleTexture {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.logical_device.device_wait_idle() {
+                log::warn!("Failed to wait for device to idle: {}", e);
+            }
+        }
+        self.staging_buffers.clear();
+        log::info!("Ripple texture successfully dropped.");
+    }
+}
@@ -0,0 +1,492 @@
+use ash::{extensions::khr::Surface, version::DeviceV1_0, vk::*, Device, Entry, Instance};
+use crossbeam::sync::ShardedLock;
+use parking_lot::Mutex;
+use std::mem::ManuallyDrop;
+use std::sync::{Arc, Weak};
+use vk_mem::Allocator;
+use winit::window::{Window, WindowId};
+
+use super::{GraphicsError, Initializer, VkResultExt};
+use crate::game::shared::enums::PresentMode;
+
+/// 同じ画像を写すウィンドウのクリアの色。縦横比が違う時に余る部分の色になる。<br />
+/// Clear color of a window mirroring the same image, which fills the parts left over when the aspect ratios differ.
+const LETTERBOX_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+/// 描画元の縦横比を保ったまま、描画先に収まる領域を計算する。戻り値はブリットの始点と終点。<br />
+/// Compute the region fitting in the target while keeping the aspect ratio of the source. Returns the start and end offsets of the blit.
+pub fn get_letterbox_region(source: Extent2D, target: Extent2D) -> [Offset3D; 2] {
+    if source.width == 0 || source.height == 0 {
+        return [Offset3D::default(), Offset3D::default()];
+    }
+    let scale = (target.width as f32 / source.width as f32)
+        .min(target.height as f32 / source.height as f32);
+    let width = ((source.width as f32 * scale).round() as u32).min(target.width);
+    let height = ((source.height as f32 * scale).round() as u32).min(target.height);
+    let x = (target.width - width) / 2;
+    let y = (target.height - height) / 2;
+    [
+        Offset3D {
+            x: x as i32,
+            y: y as i32,
+            z: 0,
+        },
+        Offset3D {
+            x: (x + width) as i32,
+            y: (y + height) as i32,
+            z: 1,
+        },
+    ]
+}
+
+/// メインのウィンドウとは別のウィンドウ。<br />
+/// 論理デバイスとキューはメインのグラフィックスと共有し、サーフェスとスワップチェーン、同期オブジェクトだけを持つ。<br />
+/// 今はメインのウィンドウに描いた画像をそのまま写すので、デバッグ用の画面やマップの画面の土台になる。<br />
+/// A window besides the main one.<br />
+/// The logical device and queues are shared with the main graphics; it only owns a surface, a swapchain and sync objects.<br />
+/// For now it mirrors the image drawn to the main window, serving as the base for debug or map views.
+pub struct SecondaryWindow {
+    pub window: Window,
+    swapchain: ManuallyDrop<super::Swapchain>,
+    surface: SurfaceKHR,
+    surface_loader: Surface,
+    logical_device: Arc<Device>,
+    instance: Arc<Instance>,
+    physical_device: super::PhysicalDevice,
+    allocator: Weak<ShardedLock<Allocator>>,
+    graphics_queue: Arc<Mutex<Queue>>,
+    present_queue: Arc<Mutex<Queue>>,
+    command_pool: CommandPool,
+    command_buffer: CommandBuffer,
+    fence: Fence,
+    acquired_semaphore: Semaphore,
+
+    /// 自分のスワップチェーンのプレゼントが待つセマフォ。<br />
+    /// Semaphore the present of its own swapchain waits on.
+    blit_finished_semaphore: Semaphore,
+
+    /// メインのスワップチェーンのプレゼントが待つセマフォ。描画元の画像を読み終わるまで、メインの画像をプレゼントさせない。<br />
+    /// Semaphore the present of the main swapchain waits on, so the main image isn't presented before the source image has been read.
+    source_released_semaphore: Semaphore,
+
+    /// サイズが変わったか期限が切れたので、次の描画の前にスワップチェーンを作り直すかどうか。<br />
+    /// Whether the swapchain is recreated before the next render because the size changed or it went out of date.
+    is_out_of_date: bool,
+}
+
+impl SecondaryWindow {
+    pub fn new(
+        window: Window,
+        entry: &Entry,
+        instance: Arc<Instance>,
+        physical_device: super::PhysicalDevice,
+        logical_device: Arc<Device>,
+        allocator: Weak<ShardedLock<Allocator>>,
+        graphics_queue: Arc<Mutex<Queue>>,
+        present_queue: Arc<Mutex<Queue>>,
+    ) -> anyhow::Result<Self> {
+        let surface_loader = Surface::new(entry, &*instance);
+        let surface = Initializer::create_surface(&window, entry, &*instance)?;
+        let present_family = physical_device
+            .queue_indices
+            .present_family
+            .ok_or_else(|| anyhow::anyhow!("The device doesn't have a present queue."))?;
+        let is_supported = unsafe {
+            surface_loader
+                .get_physical_device_surface_support(
+                    physical_device.physical_device,
+                    present_family,
+                    surface,
+                )
+                .unwrap_or(false)
+        };
+        if !is_supported {
+            unsafe {
+                surface_loader.destroy_surface(surface, None);
+            }
+            return Err(anyhow::anyhow!(
+                "The present queue can't present to the new window."
+            ));
+        }
+        let swapchain = match Self::create_swapchain(
+            &surface_loader,
+            surface,
+            &physical_device,
+            &window,
+            &instance,
+            &logical_device,
+            allocator.clone(),
+        ) {
+            Ok(swapchain) => swapchain,
+            Err(e) => {
+                unsafe {
+                    surface_loader.destroy_surface(surface, None);
+                }
+                return Err(e);
+            }
+        };
+
+        let command_pool_info = CommandPoolCreateInfo::builder()
+            .queue_family_index(physical_device.queue_indices.graphics_family.unwrap_or(0));
+        let command_pool = unsafe {
+            logical_device
+                .create_command_pool(&command_pool_info, None)
+                .or_graphics_error("create command pool for a secondary window")?
+        };
+        let command_buffer =
+            Initializer::allocate_command_buffers(logical_device.as_ref(), command_pool, 1)?[0];
+        let (fence, acquired_semaphore, blit_finished_semaphore) =
+            Initializer::create_sync_object(logical_device.as_ref())?;
+        let source_released_semaphore = unsafe {
+            logical_device
+                .create_semaphore(&SemaphoreCreateInfo::builder(), None)
+                .or_graphics_error("create semaphore")?
+        };
+        log::info!("Secondary window successfully created.");
+        Ok(SecondaryWindow {
+            window,
+            swapchain: ManuallyDrop::new(swapchain),
+            surface,
+            surface_loader,
+            logical_device,
+            instance,
+            physical_device,
+            allocator,
+            graphics_queue,
+            present_queue,
+            command_pool,
+            command_buffer,
+            fence,
+            acquired_semaphore,
+            blit_finished_semaphore,
+            source_released_semaphore,
+            is_out_of_date: false,
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// 次の描画の前にスワップチェーンを作り直す。<br />
+    /// Recreate the swapchain before the next render.
+    pub fn set_out_of_date(&mut self) {
+        self.is_out_of_date = true;
+    }
+
+    /// 描画元の画像を自分のスワップチェーンに写してプレゼントする。<br />
+    /// 描画元の画像は`PRESENT_SRC_KHR`で渡し、同じレイアウトに戻す。メインのプレゼントは戻り値のセマフォを待つ。<br />
+    /// 最小化中やスワップチェーンを作り直す時は何もせず、`wait_semaphore`をそのまま返す。<br />
+    /// Copy the source image to its own swapchain and present it.<br />
+    /// The source image is passed in `PRESENT_SRC_KHR` and returned to the same layout. The main present waits on the returned semaphore.<br />
+    /// Nothing is done while minimized or when the swapchain is recreated, and `wait_semaphore` is returned as is.
+    pub fn render(
+        &mut self,
+        source_image: Image,
+        source_extent: Extent2D,
+        wait_semaphore: Semaphore,
+    ) -> anyhow::Result<Semaphore> {
+        let size = self.window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return Ok(wait_semaphore);
+        }
+        if self.is_out_of_date {
+            self.recreate_swapchain()?;
+            return Ok(wait_semaphore);
+        }
+        unsafe {
+            let fences = [self.fence];
+            self.logical_device
+                .wait_for_fences(&fences[0..], true, u64::MAX)
+                .or_graphics_error("wait for the fence of a secondary window")?;
+            let result = self.swapchain.swapchain_loader.acquire_next_image(
+                self.swapchain.swapchain,
+                u64::MAX,
+                self.acquired_semaphore,
+                Fence::null(),
+            );
+            let image_index = match result.or_graphics_error("acquire a secondary window image") {
+                Ok((image_index, _)) => image_index,
+                Err(GraphicsError::SwapchainOutOfDate) => {
+                    self.is_out_of_date = true;
+                    return Ok(wait_semaphore);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            self.logical_device
+                .reset_fences(&fences[0..])
+                .or_graphics_error("reset the fence of a secondary window")?;
+            self.logical_device
+                .reset_command_pool(self.command_pool, CommandPoolResetFlags::empty())
+                .or_graphics_error("reset the command pool of a secondary window")?;
+            let target_image = self.swapchain.swapchain_images[image_index as usize].image;
+            self.record_blit(source_image, source_extent, target_image)?;
+
+            let command_buffers = [self.command_buffer];
+            let wait_semaphores = [wait_semaphore, self.acquired_semaphore];
+            let wait_stages = [PipelineStageFlags::TRANSFER, PipelineStageFlags::TRANSFER];
+            let signal_semaphores = [self.blit_finished_semaphore, self.source_released_semaphore];
+            let submit_info = [SubmitInfo::builder()
+                .command_buffers(&command_buffers[0..])
+                .wait_semaphores(&wait_semaphores[0..])
+                .wait_dst_stage_mask(&wait_stages[0..])
+                .signal_semaphores(&signal_semaphores[0..])
+                .build()];
+            self.logical_device
+                .queue_submit(*self.graphics_queue.lock(), &submit_info[0..], self.fence)
+                .or_graphics_error("submit the blit of a secondary window")?;
+
+            let present_semaphores = [self.blit_finished_semaphore];
+            let image_indices = [image_index];
+            let swapchains = [self.swapchain.swapchain];
+            let present_info = PresentInfoKHR::builder()
+                .wait_semaphores(&present_semaphores[0..])
+                .image_indices(&image_indices[0..])
+                .swapchains(&swapchains[0..]);
+            // このウィンドウの期限切れでメインのスワップチェーンを作り直さないように、ここで扱う。
+            // Handle this window going out of date here so that the main swapchain isn't recreated for it.
+            match self
+                .swapchain
+                .swapchain_loader
+                .queue_present(*self.present_queue.lock(), &present_info)
+                .or_graphics_error("present a secondary window")
+            {
+                Ok(false) => (),
+                Ok(true) | Err(GraphicsError::SwapchainOutOfDate) => self.is_out_of_date = true,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(self.source_released_semaphore)
+    }
+
+    /// 描画元の画像を、縦横比を保ったまま自分の画像にブリットするコマンドを記録する。<br />
+    /// Record the commands blitting the source image to its own image while keeping the aspect ratio.
+    unsafe fn record_blit(
+        &self,
+        source_image: Image,
+        source_extent: Extent2D,
+        target_image: Image,
+    ) -> Result<(), GraphicsError> {
+        let device = self.logical_device.as_ref();
+        let command_buffer = self.command_buffer;
+        let begin_info =
+            CommandBufferBeginInfo::builder().flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        device
+            .begin_command_buffer(command_buffer, &begin_info)
+            .or_graphics_error("begin the command buffer of a secondary window")?;
+
+        let subresource_range = ImageSubresourceRange::builder()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build();
+        let barrier = |image: Image,
+                       old_layout: ImageLayout,
+                       new_layout: ImageLayout,
+                       src_access: AccessFlags,
+                       dst_access: AccessFlags| {
+            ImageMemoryBarrier::builder()
+                .image(image)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .src_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(QUEUE_FAMILY_IGNORED)
+                .subresource_range(subresource_range)
+                .build()
+        };
+
+        let barriers = [
+            barrier(
+                source_image,
+                ImageLayout::PRESENT_SRC_KHR,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                AccessFlags::empty(),
+                AccessFlags::TRANSFER_READ,
+            ),
+            barrier(
+                target_image,
+                ImageLayout::UNDEFINED,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                AccessFlags::empty(),
+                AccessFlags::TRANSFER_WRITE,
+            ),
+        ];
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::TRANSFER,
+            DependencyFlags::empty(),
+            &[],
+            &[],
+            &barriers[0..],
+        );
+        device.cmd_clear_color_image(
+            command_buffer,
+            target_image,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            &ClearColorValue {
+                float32: LETTERBOX_COLOR,
+            },
+            &[subresource_range],
+        );
+        // クリアした後にブリットで上書きする。
+        // Overwrite the cleared image with the blit afterwards.
+        let barriers = [barrier(
+            target_image,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            AccessFlags::TRANSFER_WRITE,
+            AccessFlags::TRANSFER_WRITE,
+        )];
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::TRANSFER,
+            DependencyFlags::empty(),
+            &[],
+            &[],
+            &barriers[0..],
+        );
+
+        let subresource = ImageSubresourceLayers::builder()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build();
+        let regions = [ImageBlit::builder()
+            .src_subresource(subresource)
+            .src_offsets([
+                Offset3D::default(),
+                Offset3D {
+                    x: source_extent.width as i32,
+                    y: source_extent.height as i32,
+                    z: 1,
+                },
+            ])
+            .dst_subresource(subresource)
+            .dst_offsets(get_letterbox_region(source_extent, self.swapchain.extent))
+            .build()];
+        device.cmd_blit_image(
+            command_buffer,
+            source_image,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            target_image,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            &regions[0..],
+            Filter::LINEAR,
+        );
+
+        let barriers = [
+            barrier(
+                source_image,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                ImageLayout::PRESENT_SRC_KHR,
+                AccessFlags::TRANSFER_READ,
+                AccessFlags::empty(),
+            ),
+            barrier(
+                target_image,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                ImageLayout::PRESENT_SRC_KHR,
+                AccessFlags::TRANSFER_WRITE,
+                AccessFlags::empty(),
+            ),
+        ];
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::BOTTOM_OF_PIPE,
+            DependencyFlags::empty(),
+            &[],
+            &[],
+            &barriers[0..],
+        );
+        device
+            .end_command_buffer(command_buffer)
+            .or_graphics_error("end the command buffer of a secondary window")
+    }
+
+    fn recreate_swapchain(&mut self) -> anyhow::Result<()> {
+        unsafe {
+            self.logical_device
+                .device_wait_idle()
+                .or_graphics_error("wait for device to idle")?;
+            ManuallyDrop::drop(&mut self.swapchain);
+        }
+        let swapchain = Self::create_swapchain(
+            &self.surface_loader,
+            self.surface,
+            &self.physical_device,
+            &self.window,
+            &self.instance,
+            &self.logical_device,
+            self.allocator.clone(),
+        )?;
+        self.swapchain = ManuallyDrop::new(swapchain);
+        self.is_out_of_date = false;
+        Ok(())
+    }
+
+    /// 別のウィンドウのスワップチェーンを作る。<br />
+    /// メインのウィンドウの垂直同期を待たせないように、対応していればメールボックスを使う。<br />
+    /// Create the swapchain of a secondary window.<br />
+    /// Mailbox is used if supported so that the main window doesn't wait for another vertical sync.
+    fn create_swapchain(
+        surface_loader: &Surface,
+        surface: SurfaceKHR,
+        physical_device: &super::PhysicalDevice,
+        window: &Window,
+        instance: &Instance,
+        logical_device: &Arc<Device>,
+        allocator: Weak<ShardedLock<Allocator>>,
+    ) -> anyhow::Result<super::Swapchain> {
+        let swapchain = Initializer::create_swapchain(
+            surface_loader,
+            surface,
+            physical_device,
+            window,
+            instance,
+            Arc::downgrade(logical_device),
+            allocator,
+            PresentMode::Mailbox,
+        )?;
+        if !swapchain
+            .image_usage
+            .contains(ImageUsageFlags::TRANSFER_DST)
+        {
+            return Err(anyhow::anyhow!(
+                "The swapchain of the new window can't be a transfer destination."
+            ));
+        }
+        Ok(swapchain)
+    }
+}
+
+impl Drop for SecondaryWindow {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.logical_device.device_wait_idle() {
+                log::warn!("Failed to wait for device to idle: {}", e);
+            }
+            self.logical_device
+                .destroy_semaphore(self.source_released_semaphore, None);
+            self.logical_device
+                .destroy_semaphore(self.blit_finished_semaphore, None);
+            self.logical_device
+                .destroy_semaphore(self.acquired_semaphore, None);
+            self.logical_device.destroy_fence(self.fence, None);
+            self.logical_device
+                .free_command_buffers(self.command_pool, &[self.command_buffer]);
+            self.logical_device
+                .destroy_command_pool(self.command_pool, None);
+            ManuallyDrop::drop(&mut self.swapchain);
+            self.surface_loader.destroy_surface(self.surface, None);
+            log::info!("Secondary window successfully dropped.");
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::Context;
 use ash::version::DeviceV1_0;
 use ash::{
     util::read_spv,
@@ -21,10 +22,16 @@ unsafe impl Send for Shader {}
 unsafe impl Sync for Shader {}
 
 impl Shader {
-    pub fn new(device: Arc<Device>, file_name: &str, stage_flag: ShaderStageFlags) -> Self {
+    pub fn new(
+        device: Arc<Device>,
+        file_name: &str,
+        stage_flag: ShaderStageFlags,
+    ) -> anyhow::Result<Self> {
         let name = CString::new("main").unwrap();
-        let mut file = std::fs::File::open(file_name).unwrap();
-        let bytes = read_spv(&mut file).unwrap();
+        let mut file = std::fs::File::open(file_name)
+            .with_context(|| format!("Failed to open shader file {}.", file_name))?;
+        let bytes = read_spv(&mut file)
+            .with_context(|| format!("Failed to read SPIR-V from {}.", file_name))?;
         let module_info = ShaderModuleCreateInfo::builder()
             .code(bytes.as_slice())
             .build();
@@ -32,20 +39,20 @@ impl Shader {
         unsafe {
             let shader_module = device
                 .create_shader_module(&module_info, None)
-                .expect("Failed to create shader module.");
+                .with_context(|| format!("Failed to create shader module from {}.", file_name))?;
             let mut shader_stage_info = PipelineShaderStageCreateInfo::builder()
                 .module(shader_module)
                 .stage(stage_flag)
                 .build();
             shader_stage_info.p_name = name.as_ptr();
 
-            Shader {
+            Ok(Shader {
                 logical_device: device,
                 file_name: file_name.to_string(),
                 shader_module,
                 shader_stage_info,
                 is_disposed: false,
-            }
+            })
         }
     }
 }
@@ -0,0 +1,139 @@
+use ash::{version::DeviceV1_0, vk::*, Device};
+use crossbeam::sync::ShardedLock;
+use std::ffi::c_void;
+use std::sync::{Arc, Weak};
+use vk_mem::Allocator;
+
+use crate::game::shared::structs::SpriteVertex;
+
+/// 最初に確保する頂点の数。足りなくなれば倍にする。<br />
+/// Number of vertices allocated at first, doubled whenever it runs out.
+const INITIAL_VERTEX_COUNT: usize = 1536;
+
+/// 3Dのパスの後、スワップチェーンに書く最後のパスでスプライトを一回の描画で描く。<br />
+/// アトラスのテクスチャと、フレームごとのホストから見える頂点バッファを持つ。<br />
+/// Draws sprites with a single draw in the final pass writing the swapchain, after the 3D passes.<br />
+/// Owns the atlas texture and a host-visible vertex buffer for each frame.
+pub struct SpriteRenderer {
+    pub descriptor_set: DescriptorSet,
+    pub descriptor_set_layout: DescriptorSetLayout,
+    logical_device: Arc<Device>,
+    allocator: Weak<ShardedLock<Allocator>>,
+    vertex_buffers: Vec<Option<super::Buffer>>,
+    atlas: Option<super::Image>,
+}
+
+unsafe impl Send for SpriteRenderer {}
+unsafe impl Sync for SpriteRenderer {}
+
+impl SpriteRenderer {
+    pub fn new(
+        logical_device: Arc<Device>,
+        allocator: Weak<ShardedLock<Allocator>>,
+        frame_count: usize,
+    ) -> Self {
+        log::info!("Sprite renderer successfully created.");
+        SpriteRenderer {
+            descriptor_set: DescriptorSet::null(),
+            descriptor_set_layout: DescriptorSetLayout::null(),
+            logical_device,
+            allocator,
+            vertex_buffers: (0..frame_count).map(|_| None).collect(),
+            atlas: None,
+        }
+    }
+
+    /// アトラスのテクスチャとそれを指す描述子セットを差し替え、前のテクスチャを返す。<br />
+    /// Replace the atlas texture and the descriptor set pointing at it, returning the previous texture.
+    pub fn set_atlas(
+        &mut self,
+        atlas: super::Image,
+        descriptor_set: DescriptorSet,
+        descriptor_set_layout: DescriptorSetLayout,
+    ) -> Option<super::Image> {
+        self.descriptor_set = descriptor_set;
+        self.descriptor_set_layout = descriptor_set_layout;
+        self.atlas.replace(atlas)
+    }
+
+    /// 頂点をこのフレームのバッファに書き込み、開いているレンダーパスの中に直接描画を記録する。<br />
+    /// Write the vertices into this frame's buffer and record the draw straight into the open render pass.
+    pub fn record_draw(
+        &mut self,
+        frame_index: usize,
+        command_buffer: CommandBuffer,
+        vertices: &[SpriteVertex],
+        pipeline_layout: PipelineLayout,
+        pipeline: ash::vk::Pipeline,
+    ) -> anyhow::Result<()> {
+        if vertices.is_empty()
+            || self.descriptor_set == DescriptorSet::null()
+            || pipeline == ash::vk::Pipeline::null()
+        {
+            return Ok(());
+        }
+        let data_size = (std::mem::size_of::<SpriteVertex>() * vertices.len()) as DeviceSize;
+        let is_too_small = self.vertex_buffers[frame_index]
+            .as_ref()
+            .map(|buffer| buffer.buffer_size < data_size)
+            .unwrap_or(true);
+        if is_too_small {
+            let mut vertex_count = INITIAL_VERTEX_COUNT;
+            while vertex_count < vertices.len() {
+                vertex_count *= 2;
+            }
+            self.vertex_buffers[frame_index] = Some(super::Buffer::new(
+                Arc::downgrade(&self.logical_device),
+                (std::mem::size_of::<SpriteVertex>() * vertex_count) as DeviceSize,
+                BufferUsageFlags::VERTEX_BUFFER,
+                MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+                self.allocator.clone(),
+            )?);
+        }
+        let vertex_buffer = self.vertex_buffers[frame_index]
+            .as_ref()
+            .expect("Failed to get the sprite vertex buffer.");
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                vertices.as_ptr() as *const c_void,
+                vertex_buffer.mapped_memory,
+                data_size as usize,
+            );
+            self.logical_device.cmd_bind_pipeline(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline,
+            );
+            self.logical_device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            self.logical_device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[vertex_buffer.buffer],
+                &[0],
+            );
+            self.logical_device
+                .cmd_draw(command_buffer, vertices.len() as u32, 1, 0, 0);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SpriteRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.logical_device.device_wait_idle() {
+                log::warn!("Failed to wait for device to idle: {}", e);
+            }
+        }
+        self.vertex_buffers.clear();
+        self.atlas = None;
+        log::info!("Sprite renderer successfully dropped.");
+    }
+}
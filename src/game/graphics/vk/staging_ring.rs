@@ -0,0 +1,215 @@
+use ash::vk::{BufferUsageFlags, DeviceSize, MemoryPropertyFlags};
+use ash::Device;
+use crossbeam::sync::ShardedLock;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::ffi::c_void;
+use std::sync::Weak;
+use vk_mem::Allocator;
+
+use super::GraphicsError;
+use crate::game::shared::traits::Mappable;
+
+/// ステージング用のリングバッファの大きさ。<br />
+/// Size of the staging ring buffer.
+pub const STAGING_RING_SIZE: DeviceSize = 32 * 1024 * 1024;
+
+/// 領域の先頭を揃える境界。どのテクセルの大きさでもイメージへのコピーに使える。<br />
+/// Alignment of the start of each region, which works for copies into images of any texel size.
+pub const STAGING_ALIGNMENT: DeviceSize = 16;
+
+/// リングバッファの中の領域の位置だけを管理する。<br />
+/// 領域は確保した順に回収するので、後の領域が先に返されても、それより前の領域が返されるまで再利用しない。<br />
+/// Keeps track of the positions of regions inside a ring buffer.<br />
+/// Regions are reclaimed in allocation order, so a region returned early isn't reused until every region before it is returned.
+#[derive(Clone, Debug)]
+pub struct RingAllocator {
+    capacity: DeviceSize,
+    head: DeviceSize,
+    /// 確保した順の領域。`(オフセット, 大きさ, 返されたか)`。<br />
+    /// Regions in allocation order as `(offset, size, is_released)`.
+    regions: VecDeque<(DeviceSize, DeviceSize, bool)>,
+}
+
+impl RingAllocator {
+    pub fn new(capacity: DeviceSize) -> Self {
+        RingAllocator {
+            capacity,
+            head: 0,
+            regions: VecDeque::new(),
+        }
+    }
+
+    pub fn get_capacity(&self) -> DeviceSize {
+        self.capacity
+    }
+
+    pub fn get_region_count(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// 領域を確保してオフセットを返す。空きが足りなければ`None`を返す。<br />
+    /// Allocate a region and return its offset, or `None` if there isn't enough free space.
+    pub fn allocate(&mut self, size: DeviceSize, alignment: DeviceSize) -> Option<DeviceSize> {
+        if size == 0 || size > self.capacity {
+            return None;
+        }
+        let offset = match (self.regions.front(), self.regions.back()) {
+            (Some(&(tail, _, _)), Some(&(last, _, _))) => {
+                let aligned = align(self.head, alignment);
+                if last < tail {
+                    // 先頭に折り返した後は、最も古い領域までしか使えない。
+                    // After wrapping around, only the space up to the oldest region is free.
+                    Some(aligned).filter(|offset| offset + size <= tail)
+                } else if aligned + size <= self.capacity {
+                    Some(aligned)
+                } else {
+                    Some(0).filter(|_| size <= tail)
+                }
+            }
+            _ => Some(0),
+        }?;
+        self.head = offset + size;
+        self.regions.push_back((offset, size, false));
+        Some(offset)
+    }
+
+    /// 領域を返す。最も古い領域から順に、返された分だけ再利用できるようにする。<br />
+    /// Return a region. Returned regions become reusable in order, starting from the oldest one.
+    pub fn release(&mut self, offset: DeviceSize) {
+        if let Some(region) = self
+            .regions
+            .iter_mut()
+            .find(|(region_offset, _, is_released)| *region_offset == offset && !*is_released)
+        {
+            region.2 = true;
+        }
+        while let Some(&(_, _, true)) = self.regions.front() {
+            self.regions.pop_front();
+        }
+        if self.regions.is_empty() {
+            self.head = 0;
+        }
+    }
+}
+
+fn align(offset: DeviceSize, alignment: DeviceSize) -> DeviceSize {
+    (offset + alignment - 1) / alignment * alignment
+}
+
+/// 常にマップしている一つのステージングバッファから、アップロードごとの領域を切り出す。<br />
+/// 領域はアップロードのフェンスを待った後にドロップされ、リングに戻る。<br />
+/// リングに空きがない時は、そのアップロードだけ専用のステージングバッファを作る。<br />
+/// Sub-allocates per-upload regions out of one persistently mapped staging buffer.<br />
+/// Regions are dropped after the upload's fence has been waited on, which returns them to the ring.<br />
+/// When the ring is full, a dedicated staging buffer is created for that upload alone.
+pub struct StagingRing {
+    buffer: super::Buffer,
+    ring: Mutex<RingAllocator>,
+    logical_device: Weak<Device>,
+    allocator: Weak<ShardedLock<Allocator>>,
+}
+
+unsafe impl Send for StagingRing {}
+unsafe impl Sync for StagingRing {}
+
+impl StagingRing {
+    pub fn new(
+        device: Weak<Device>,
+        allocator: Weak<ShardedLock<Allocator>>,
+        capacity: DeviceSize,
+    ) -> Result<Self, GraphicsError> {
+        let mut buffer = super::Buffer::new(
+            device.clone(),
+            capacity,
+            BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            allocator.clone(),
+        )?;
+        buffer.map_memory(capacity, 0);
+        Ok(StagingRing {
+            buffer,
+            ring: Mutex::new(RingAllocator::new(capacity)),
+            logical_device: device,
+            allocator,
+        })
+    }
+
+    /// 領域を確保する。<br />
+    /// Allocate a region.
+    pub fn allocate(&self, size: DeviceSize) -> Result<StagingRegion, GraphicsError> {
+        let offset = self.ring.lock().allocate(size, STAGING_ALIGNMENT);
+        if let Some(offset) = offset {
+            return Ok(StagingRegion {
+                ring: self,
+                dedicated: None,
+                buffer: self.buffer.buffer,
+                offset,
+                size,
+                mapped: unsafe { (self.buffer.mapped_memory as *mut u8).add(offset as usize) }
+                    as *mut c_void,
+            });
+        }
+
+        log::warn!(
+            "Staging ring is full. Creating a dedicated staging buffer of {} bytes.",
+            size
+        );
+        let mut dedicated = super::Buffer::new(
+            self.logical_device.clone(),
+            size,
+            BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            self.allocator.clone(),
+        )?;
+        let mapped = dedicated.map_memory(size, 0);
+        Ok(StagingRegion {
+            ring: self,
+            buffer: dedicated.buffer,
+            dedicated: Some(dedicated),
+            offset: 0,
+            size,
+            mapped,
+        })
+    }
+
+    /// 領域を確保し、データをコピーする。<br />
+    /// Allocate a region and copy the data into it.
+    pub fn write<T>(&self, data: &[T]) -> Result<StagingRegion, GraphicsError> {
+        let size = std::mem::size_of::<T>() * data.len();
+        let region = self.allocate(size as DeviceSize)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr() as *const c_void, region.mapped, size);
+        }
+        Ok(region)
+    }
+}
+
+/// ステージングリングから切り出した領域。ドロップするとリングに戻る。<br />
+/// A region cut out of the staging ring, returned to the ring when dropped.
+pub struct StagingRegion<'a> {
+    ring: &'a StagingRing,
+    dedicated: Option<super::Buffer>,
+    pub buffer: ash::vk::Buffer,
+    pub offset: DeviceSize,
+    pub size: DeviceSize,
+    mapped: *mut c_void,
+}
+
+impl<'a> StagingRegion<'a> {
+    pub fn get_mapped(&self) -> *mut c_void {
+        self.mapped
+    }
+}
+
+impl<'a> Drop for StagingRegion<'a> {
+    fn drop(&mut self) {
+        if self.dedicated.is_none() {
+            self.ring.ring.lock().release(self.offset);
+        }
+    }
+}
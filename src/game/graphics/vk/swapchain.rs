@@ -4,6 +4,7 @@ use std::sync::Weak;
 use vk_mem::Allocator;
 
 use super::physical_device::QueueIndices;
+use super::{GraphicsError, VkResultExt};
 
 #[derive(Clone)]
 pub struct Swapchain {
@@ -13,6 +14,10 @@ pub struct Swapchain {
     pub present_mode: PresentModeKHR,
     pub swapchain_images: Vec<super::Image>,
     pub swapchain_loader: ash::extensions::khr::Swapchain,
+
+    /// スワップチェーンの画像の用途。別のウィンドウに写せるように、対応していれば転送にも使う。<br />
+    /// Usage of the swapchain images. Transfers are included if supported, so that they can be copied to other windows.
+    pub image_usage: ImageUsageFlags,
     capabilities: SurfaceCapabilitiesKHR,
 }
 
@@ -28,28 +33,34 @@ impl Swapchain {
         instance: &ash::Instance,
         device: Weak<ash::Device>,
         allocator: Weak<ShardedLock<Allocator>>,
-    ) -> Self {
+        requested_present_mode: PresentModeKHR,
+    ) -> Result<Self, GraphicsError> {
         let (capabilities, formats, present_modes) =
-            Swapchain::get_swapchain_details(surface_loader, surface, physical_device);
-        let logical_device = device.upgrade().unwrap();
+            Swapchain::get_swapchain_details(surface_loader, surface, physical_device)?;
+        let logical_device = device
+            .upgrade()
+            .ok_or(GraphicsError::Unavailable("logical device"))?;
         let mut swapchain = Swapchain {
             swapchain: SwapchainKHR::null(),
             capabilities,
             extent: Swapchain::choose_extent(&capabilities, window),
             format: Swapchain::choose_format(&formats),
-            present_mode: Swapchain::choose_present_mode(&present_modes),
+            present_mode: Swapchain::choose_present_mode(&present_modes, requested_present_mode),
             swapchain_loader: ash::extensions::khr::Swapchain::new(
                 instance,
                 logical_device.as_ref(),
             ),
             swapchain_images: vec![],
+            image_usage: ImageUsageFlags::COLOR_ATTACHMENT
+                | (capabilities.supported_usage_flags
+                    & (ImageUsageFlags::TRANSFER_SRC | ImageUsageFlags::TRANSFER_DST)),
         };
-        swapchain.create_swapchain(surface, queue_indices);
+        swapchain.create_swapchain(surface, queue_indices)?;
         unsafe {
             let images = swapchain
                 .swapchain_loader
                 .get_swapchain_images(swapchain.swapchain)
-                .expect("Failed to acquire swapchain images.");
+                .or_graphics_error("acquire swapchain images")?;
             let format = swapchain.format.format;
             for image in images.into_iter() {
                 let img = super::Image::from_image(
@@ -59,11 +70,11 @@ impl Swapchain {
                     ImageAspectFlags::COLOR,
                     1,
                     allocator.clone(),
-                );
+                )?;
                 swapchain.swapchain_images.push(img);
             }
         }
-        swapchain
+        Ok(swapchain)
     }
 
     /// ハードウェアに基づいてスワップチェーンのフォーマットを選択する。<br />
@@ -115,25 +126,20 @@ impl Swapchain {
         }
     }
 
-    /// ハードウェアに基づいてスワップチェーンのプレゼントモードを選択する。<br />
-    /// Select the present mode of the swapchain based on hardware.
-    fn choose_present_mode(present_modes: &[PresentModeKHR]) -> PresentModeKHR {
-        let mut fifo_support = false;
-        for mode in present_modes.iter() {
-            match *mode {
-                PresentModeKHR::MAILBOX => {
-                    return *mode;
-                }
-                PresentModeKHR::FIFO => {
-                    fifo_support = true;
-                }
-                _ => (),
-            }
-        }
-        if fifo_support {
-            PresentModeKHR::FIFO
+    /// 希望するプレゼントモードに対応していればそれを使い、そうでなければ必ず対応しているFIFOを使う。<br />
+    /// Use the requested present mode if it's supported, falling back to FIFO which is always supported.
+    fn choose_present_mode(
+        present_modes: &[PresentModeKHR],
+        requested_present_mode: PresentModeKHR,
+    ) -> PresentModeKHR {
+        if present_modes.contains(&requested_present_mode) {
+            requested_present_mode
         } else {
-            PresentModeKHR::IMMEDIATE
+            log::warn!(
+                "Present mode {:?} is not supported. Falling back to FIFO.",
+                requested_present_mode
+            );
+            PresentModeKHR::FIFO
         }
     }
 
@@ -143,26 +149,33 @@ impl Swapchain {
         surface_loader: &Surface,
         surface: SurfaceKHR,
         physical_device: PhysicalDevice,
-    ) -> (
-        SurfaceCapabilitiesKHR,
-        Vec<SurfaceFormatKHR>,
-        Vec<PresentModeKHR>,
-    ) {
+    ) -> Result<
+        (
+            SurfaceCapabilitiesKHR,
+            Vec<SurfaceFormatKHR>,
+            Vec<PresentModeKHR>,
+        ),
+        GraphicsError,
+    > {
         unsafe {
             let capabilities = surface_loader
                 .get_physical_device_surface_capabilities(physical_device, surface)
-                .expect("Failed to get surface capabilities");
+                .or_graphics_error("get surface capabilities")?;
             let formats = surface_loader
                 .get_physical_device_surface_formats(physical_device, surface)
-                .expect("Failed to get surface formats.");
+                .or_graphics_error("get surface formats")?;
             let present_modes = surface_loader
                 .get_physical_device_surface_present_modes(physical_device, surface)
-                .expect("Failed to get available present modes.");
-            (capabilities, formats, present_modes)
+                .or_graphics_error("get available present modes")?;
+            Ok((capabilities, formats, present_modes))
         }
     }
 
-    fn create_swapchain(&mut self, surface: SurfaceKHR, queue_indices: QueueIndices) {
+    fn create_swapchain(
+        &mut self,
+        surface: SurfaceKHR,
+        queue_indices: QueueIndices,
+    ) -> Result<(), GraphicsError> {
         let min_image_count = if self.capabilities.max_image_count == 0 {
             self.capabilities.min_image_count + 1
         } else if self.capabilities.min_image_count + 1 > self.capabilities.max_image_count {
@@ -187,7 +200,7 @@ impl Swapchain {
             .image_extent(self.extent)
             .image_format(self.format.format)
             .pre_transform(self.capabilities.current_transform)
-            .image_usage(ImageUsageFlags::COLOR_ATTACHMENT);
+            .image_usage(self.image_usage);
 
         let indices = vec![
             queue_indices.graphics_family.unwrap(),
@@ -206,8 +219,9 @@ impl Swapchain {
             self.swapchain = self
                 .swapchain_loader
                 .create_swapchain(&create_info, None)
-                .expect("Failed to create swapchain.");
+                .or_graphics_error("create swapchain")?;
         }
+        Ok(())
     }
 }
 
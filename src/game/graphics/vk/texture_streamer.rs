@@ -0,0 +1,288 @@
+use crate::game::enums::ImageFormat;
+use crate::game::graphics::vk::{Buffer, Graphics, Image, Initializer};
+use crate::game::shared::structs::{
+    get_desired_mip, get_low_res_mip, get_mip_count, get_mip_extent, get_screen_coverage,
+    MemoryStats, StreamedTexture, TextureResidency, STREAMING_RESUME_PRESSURE,
+};
+use crate::game::shared::types::LockableRenderable;
+use crate::game::Camera;
+use anyhow::Context;
+use ash::vk::{CommandBuffer, CommandPool, DeviceSize, SamplerAddressMode};
+use image::imageops::FilterType;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+
+/// ミップを作り直すための元のデータ。<br />
+/// Original data to create the mips from again.
+#[derive(Clone, Debug)]
+pub enum TextureSource {
+    /// 展開したRGBAのピクセル。glTFに埋め込まれたテクスチャは読み直せないので、メインメモリーに残す。<br />
+    /// Unpacked RGBA pixels. Textures embedded in glTF can't be read again, so they're kept in main memory.
+    Pixels {
+        pixels: Arc<Vec<u8>>,
+        format: ImageFormat,
+    },
+    /// 画像のファイル。ミップを作るたびに展開し直す。<br />
+    /// An image file, unpacked again each time mips are created.
+    File(String),
+    /// KTX2のファイル。必要なレベルだけを読み込む。<br />
+    /// A KTX2 file. Only the required levels are loaded.
+    Ktx2 { path: String, mip_count: u32 },
+}
+
+/// ストリーミングするテクスチャの元のデータと大きさ。<br />
+/// Original data and size of a streamed texture.
+#[derive(Clone, Debug)]
+pub struct StreamedTextureSource {
+    pub source: TextureSource,
+    pub width: u32,
+    pub height: u32,
+    pub sampler_address_mode: SamplerAddressMode,
+}
+
+impl StreamedTextureSource {
+    pub fn get_mip_count(&self) -> u32 {
+        match self.source {
+            TextureSource::Ktx2 { mip_count, .. } => mip_count,
+            _ => get_mip_count(self.width, self.height),
+        }
+    }
+
+    /// メモリーの見積もりに使う一テクセルの大きさ。KTX2はブロック圧縮されているものと見なす。<br />
+    /// Size of a texel used for estimating memory. KTX2 is assumed to be block compressed.
+    pub fn get_bytes_per_texel(&self) -> u64 {
+        match self.source {
+            TextureSource::Ktx2 { .. } => 1,
+            _ => 4,
+        }
+    }
+
+    pub fn get_low_res_mip(&self) -> u32 {
+        get_low_res_mip(self.width, self.height, self.get_mip_count())
+    }
+
+    /// `mip`から最後のミップまでを持つイメージを作る。<br />
+    /// Create an image holding the mips from `mip` to the last one.
+    pub fn create_image(
+        &self,
+        mip: u32,
+        graphics: Arc<RwLock<ManuallyDrop<Graphics>>>,
+        command_pool: Arc<Mutex<CommandPool>>,
+    ) -> anyhow::Result<Image> {
+        let (pixels, format) = match &self.source {
+            TextureSource::Pixels { pixels, format } => (pixels.as_ref().clone(), *format),
+            TextureSource::File(path) => (
+                image::open(path)
+                    .with_context(|| format!("Failed to open streamed texture: {}", path))?
+                    .to_rgba8()
+                    .into_raw(),
+                ImageFormat::ColorType(image::ColorType::Rgba8),
+            ),
+            TextureSource::Ktx2 { path, .. } => {
+                return Initializer::create_image_from_ktx2(
+                    path,
+                    mip as usize,
+                    graphics,
+                    command_pool,
+                    self.sampler_address_mode,
+                );
+            }
+        };
+        let (pixels, width, height) = if mip == 0 {
+            (pixels, self.width, self.height)
+        } else {
+            let (width, height) = get_mip_extent(self.width, self.height, mip);
+            let image = image::RgbaImage::from_raw(self.width, self.height, pixels)
+                .with_context(|| "Failed to wrap the pixels of a streamed texture.")?;
+            let resized = image::imageops::resize(&image, width, height, FilterType::Triangle);
+            (resized.into_raw(), width, height)
+        };
+        let buffer_size = pixels.len() as DeviceSize;
+        Initializer::create_image_from_raw(
+            pixels,
+            buffer_size,
+            width,
+            height,
+            format,
+            graphics,
+            command_pool,
+            self.sampler_address_mode,
+        )
+    }
+}
+
+/// 読み込みの終わった、テクスチャ配列の要素と入れ替えるイメージ。<br />
+/// A finished image to swap into an element of the texture array.
+pub struct TextureSwap {
+    pub texture_index: usize,
+    pub mip: u32,
+    pub image: Image,
+}
+
+/// テクスチャの高いミップを画面で占める大きさに合わせて読み込み、メモリーが逼迫すれば捨てる。<br />
+/// テクスチャ配列の要素を書き換えるので、バインドレスのテクスチャが使える時だけ働く。<br />
+/// Streams in the high mips of textures according to how much of the screen they cover, evicting them under memory pressure.<br />
+/// It rewrites elements of the texture array, so it only works with bindless textures.
+pub struct TextureStreamer {
+    is_enabled: bool,
+    residency: Mutex<TextureResidency>,
+    sources: Mutex<HashMap<usize, StreamedTextureSource>>,
+    swaps: Mutex<Vec<TextureSwap>>,
+}
+
+impl TextureStreamer {
+    pub fn new(is_enabled: bool) -> Self {
+        TextureStreamer {
+            is_enabled,
+            residency: Mutex::new(TextureResidency::new()),
+            sources: Mutex::new(HashMap::new()),
+            swaps: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    /// 低い解像度のミップから作ったテクスチャを登録する。ミップが元々低い解像度なら何もしない。<br />
+    /// Register a texture created from its low resolution mip. Nothing is done if it's low resolution to begin with.
+    pub fn register(&self, texture_index: usize, source: StreamedTextureSource) {
+        let low_res_mip = source.get_low_res_mip();
+        if !self.is_enabled || low_res_mip == 0 {
+            return;
+        }
+        let texture = StreamedTexture::new(
+            source.width,
+            source.height,
+            source.get_mip_count(),
+            source.get_bytes_per_texel(),
+            low_res_mip,
+        );
+        self.residency.lock().register(texture_index, texture);
+        self.sources.lock().insert(texture_index, source);
+    }
+
+    /// 使うモデルがなくなったテクスチャを手放す。<br />
+    /// Let go of a texture no model uses anymore.
+    pub fn release(&self, texture_index: usize) {
+        let mut residency = self.residency.lock();
+        residency.release(texture_index);
+        if !residency.contains(texture_index) {
+            self.sources.lock().remove(&texture_index);
+        }
+    }
+
+    pub fn set_memory_stats(&self, stats: &MemoryStats) {
+        self.residency.lock().set_budget(
+            stats.get_device_local_pressure(),
+            stats.get_device_local_headroom(STREAMING_RESUME_PRESSURE),
+        );
+    }
+
+    /// 描画するモデルのテクスチャに、カメラから見た大きさに足りるミップを要求する。<br />
+    /// Request mips enough for the size seen from the camera for the textures of the rendered models.
+    pub fn request_mips(
+        &self,
+        renderables: &[LockableRenderable<Graphics, Buffer, CommandBuffer, Image>],
+        camera: &Camera,
+        viewport_height: f32,
+    ) {
+        let mut residency = self.residency.lock();
+        if residency.is_empty() {
+            return;
+        }
+        let field_of_view = camera.field_of_view.to_radians();
+        residency.begin_requests();
+        for renderable in renderables.iter() {
+            let usages = renderable.lock().get_texture_usages();
+            for (texture_index, bounds) in usages.into_iter() {
+                let texture = match residency.get(texture_index) {
+                    Some(texture) => *texture,
+                    None => continue,
+                };
+                let mip = match bounds {
+                    Some(bounds) => {
+                        let center = bounds.get_center();
+                        let coverage = get_screen_coverage(
+                            (bounds.max - center).length(),
+                            (center - camera.position).length(),
+                            field_of_view,
+                            viewport_height,
+                        );
+                        get_desired_mip(texture.width, texture.height, texture.mip_count, coverage)
+                    }
+                    None => 0,
+                };
+                residency.request(texture_index, mip);
+            }
+        }
+    }
+
+    /// 読み込むミップと捨てるミップを決め、読み込みのスレッドでイメージを作る。毎フレーム呼ぶ。自由関数。<br />
+    /// Decide the mips to stream in and to evict, creating the images on the streaming threads. Called every frame. Free function.
+    pub fn update(graphics: &Arc<RwLock<ManuallyDrop<Graphics>>>) {
+        let lock = graphics.read();
+        let streamer = lock.texture_streamer.clone();
+        if !streamer.is_enabled {
+            return;
+        }
+        let changes = streamer.residency.lock().plan();
+        if changes.is_empty() {
+            return;
+        }
+        let thread_pool = lock.thread_pool.clone();
+        drop(lock);
+        for change in changes.into_iter() {
+            let texture_index = change.get_texture_index();
+            let mip = change.get_mip();
+            let source = match streamer.sources.lock().get(&texture_index).cloned() {
+                Some(source) => source,
+                None => {
+                    streamer.cancel(texture_index);
+                    continue;
+                }
+            };
+            let graphics = graphics.clone();
+            let streamer = streamer.clone();
+            thread_pool.spawn_streaming(move || {
+                let command_pool = graphics.read().get_idle_command_pool();
+                match source.create_image(mip, graphics, command_pool) {
+                    Ok(image) => streamer.swaps.lock().push(TextureSwap {
+                        texture_index,
+                        mip,
+                        image,
+                    }),
+                    Err(e) => {
+                        log::warn!(
+                            "Failed to stream mip {} of texture {}: {}",
+                            mip,
+                            texture_index,
+                            e
+                        );
+                        streamer.cancel(texture_index);
+                    }
+                }
+            });
+        }
+    }
+
+    pub fn take_swaps(&self) -> Vec<TextureSwap> {
+        std::mem::take(&mut *self.swaps.lock())
+    }
+
+    /// 入れ替えが終わったミップを置いているミップにする。<br />
+    /// Make a swapped mip the resident one.
+    pub fn complete(&self, texture_index: usize, mip: u32) {
+        let mut residency = self.residency.lock();
+        residency.complete(texture_index, mip);
+        if !residency.contains(texture_index) {
+            self.sources.lock().remove(&texture_index);
+        }
+    }
+
+    pub fn cancel(&self, texture_index: usize) {
+        self.residency.lock().cancel(texture_index);
+    }
+}
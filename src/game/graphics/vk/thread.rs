@@ -1,105 +1,77 @@
 use ash::version::DeviceV1_0;
 use ash::vk::{CommandPool, CommandPoolCreateFlags};
-use crossbeam::channel::*;
-use crossbeam::queue::ArrayQueue;
 use parking_lot::Mutex;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
 
-/// 自定義のスレッド。マルチスレッド描画用。<br />
-/// A custom thread for multi-threaded rendering.
-#[allow(dead_code)]
+use crate::game::shared::structs::{JobPriority, JobQueue, SyncPoint};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+thread_local! {
+    /// 今のスレッドがワーカーなら、そのワーカーのコマンドプール。<br />
+    /// The command pool of the worker, if the current thread is one.
+    static WORKER_COMMAND_POOL: RefCell<Option<Arc<Mutex<CommandPool>>>> = RefCell::new(None);
+}
+
+/// 自定義のスレッド。マルチスレッド描画と読み込み用。<br />
+/// キューからジョブを取り出して実行し、自分のコマンドプールを持つ。<br />
+/// A custom thread for multi-threaded rendering and loading.<br />
+/// It takes jobs from a queue and runs them, owning its own command pools.
 pub struct Thread {
     pub command_pools: Vec<Arc<Mutex<ash::vk::CommandPool>>>,
-    pub work_received: AtomicBool,
-    destroying: Arc<AtomicBool>,
-    notify: Receiver<()>,
-    worker: Option<JoinHandle<anyhow::Result<()>>>,
-    task_queue: Arc<ArrayQueue<Box<dyn FnOnce() + Send + 'static>>>,
-    work_sender: Sender<()>,
+    queue: Arc<JobQueue<Job>>,
+    worker: Option<JoinHandle<()>>,
 }
 
 impl Thread {
-    pub fn new(device: &ash::Device, queue_index: u32, inflight_frame_count: usize) -> Self {
-        let task_queue = Arc::new(ArrayQueue::new(1000));
-        let (sender, receiver) = bounded(1000);
-        let destroying = Arc::new(AtomicBool::new(false));
+    pub fn new(
+        device: &ash::Device,
+        queue_index: u32,
+        command_pool_count: usize,
+        queue: Arc<JobQueue<Job>>,
+    ) -> Self {
         let pool_info = ash::vk::CommandPoolCreateInfo::builder()
             .flags(CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
             .queue_family_index(queue_index)
             .build();
-        let (notify_sender, notify_receiver) = bounded(1000);
-        let sender_clone = sender.clone();
-        let destroying_clone = destroying.clone();
-        let queue = task_queue.clone();
+        let mut command_pools = vec![];
         unsafe {
-            let mut command_pools = vec![];
-            for _ in 0..inflight_frame_count {
+            for _ in 0..command_pool_count {
                 let command_pool = device
                     .create_command_pool(&pool_info, None)
                     .expect("Failed to create command pool for thread.");
                 command_pools.push(Arc::new(Mutex::new(command_pool)));
             }
-            Thread {
-                destroying,
-                work_received: AtomicBool::new(false),
-                notify: notify_receiver,
-                worker: Some(std::thread::spawn(move || {
-                    let sender = sender_clone;
-                    let receiver = receiver;
-                    let destroying = destroying_clone;
-                    'outer: loop {
-                        let mut work: Option<Box<dyn FnOnce() + Send>>;
-                        while receiver.recv().is_ok() {
-                            if destroying.load(Ordering::SeqCst) {
-                                break 'outer;
-                            }
-                            work = queue.pop();
-                            if let Some(job) = work {
-                                job();
-                                sender.send(())?;
-                            } else {
-                                notify_sender.send(())?;
-                                break;
-                            }
-                        }
-                    }
-                    Ok(())
-                })),
-                task_queue: task_queue.clone(),
-                work_sender: sender,
-                command_pools,
-            }
         }
-    }
-
-    /// 新しい描画のタスクを追加し、チャンネルを通じてスレッドを通知する。<br />
-    /// Add a new rendering task and notify the threads via channels.
-    pub fn add_job(&self, work: impl FnOnce() + Send + 'static) -> anyhow::Result<()> {
-        match self.task_queue.push(Box::new(work)) {
-            Ok(_) => (),
-            Err(_) => log::error!("Failed to push work into the queue."),
+        let worker_pool = command_pools[0].clone();
+        let worker_queue = queue.clone();
+        Thread {
+            command_pools,
+            queue,
+            worker: Some(std::thread::spawn(move || {
+                WORKER_COMMAND_POOL.with(|pool| *pool.borrow_mut() = Some(worker_pool));
+                while let Some(job) = worker_queue.pop() {
+                    job();
+                }
+            })),
         }
-        self.work_sender.send(())?;
-        self.work_received.store(true, Ordering::SeqCst);
-        Ok(())
     }
 
-    /// チャンネルを通じて描画完了する際に通知を受けます。<br />
-    /// Receive rendering complete notifications via channels.
-    pub fn wait(&self) -> anyhow::Result<()> {
-        self.notify.recv()?;
-        Ok(())
+    /// 新しいタスクを追加する。<br />
+    /// Add a new task.
+    fn add_job(&self, priority: JobPriority, work: Job) {
+        if !self.queue.push(priority, work) {
+            log::error!("Failed to push work into the queue.");
+        }
     }
 }
 
 impl Drop for Thread {
     fn drop(&mut self) {
-        self.destroying.store(true, Ordering::SeqCst);
-        self.work_sender
-            .send(())
-            .expect("Failed to send work to the worker thread.");
+        self.queue.close();
         if let Some(worker) = self.worker.take() {
             match worker.join() {
                 Ok(_) => (),
@@ -109,30 +81,65 @@ impl Drop for Thread {
     }
 }
 
-/// 自定義のスレッドプール。マルチスレッド描画用。<br />
-/// A custom thread pool for multi-threaded rendering.
+/// 終わった時に、パニックした時も同期点に知らせる。<br />
+/// Notifies the synchronization point when finished, including when panicking.
+struct FrameJobGuard(Arc<SyncPoint>);
+
+impl Drop for FrameJobGuard {
+    fn drop(&mut self) {
+        self.0.done();
+    }
+}
+
+/// 自定義のスレッドプール。マルチスレッド描画と読み込み用。<br />
+/// 描画のスレッドはモデルごとに決まったスレッドでコマンドを記録し、そのスレッドのコマンドプールを使う。<br />
+/// 読み込みは専用のスレッドで実行するので、フレームの記録を待たせない。専用のスレッドがなければ、描画のスレッドが描画のジョブの後に実行する。<br />
+/// A custom thread pool for multi-threaded rendering and loading.<br />
+/// Commands of each model are recorded on the same rendering thread, using that thread's command pools.<br />
+/// Loading runs on dedicated streaming threads so it doesn't hold frame recording back. Without them, rendering threads run it after rendering jobs.
 pub struct ThreadPool {
     pub threads: Vec<Thread>,
+    pub streaming_threads: Vec<Thread>,
     pub thread_count: usize,
     pub inflight_frame_count: usize,
+    streaming_queue: Arc<JobQueue<Job>>,
+    frame_jobs: Arc<SyncPoint>,
+    next_streaming_thread: AtomicUsize,
 }
 
 impl ThreadPool {
     pub fn new(
         thread_count: usize,
+        streaming_thread_count: usize,
         inflight_frame_count: usize,
         device: &ash::Device,
         queue_index: u32,
     ) -> Self {
-        let mut threads = vec![];
-        for _ in 0..thread_count {
-            threads.push(Thread::new(device, queue_index, inflight_frame_count));
-        }
-        ThreadPool {
-            threads,
+        let streaming_queue = Arc::new(JobQueue::new());
+        let mut thread_pool = ThreadPool {
+            threads: vec![],
+            streaming_threads: vec![],
             thread_count,
             inflight_frame_count,
+            streaming_queue: streaming_queue.clone(),
+            frame_jobs: Arc::new(SyncPoint::new()),
+            next_streaming_thread: AtomicUsize::new(0),
+        };
+        thread_pool.set_thread_count(
+            thread_count as u32,
+            inflight_frame_count,
+            device,
+            queue_index,
+        );
+        for _ in 0..streaming_thread_count {
+            thread_pool.streaming_threads.push(Thread::new(
+                device,
+                queue_index,
+                1,
+                streaming_queue.clone(),
+            ));
         }
+        thread_pool
     }
 
     pub fn set_thread_count(
@@ -142,44 +149,77 @@ impl ThreadPool {
         device: &ash::Device,
         queue_index: u32,
     ) {
+        self.wait();
         self.threads.clear();
         for _ in 0..thread_count {
-            self.threads
-                .push(Thread::new(device, queue_index, inflight_frame_count));
+            self.threads.push(Thread::new(
+                device,
+                queue_index,
+                inflight_frame_count,
+                Arc::new(JobQueue::new()),
+            ));
         }
+        self.thread_count = thread_count as usize;
+        self.inflight_frame_count = inflight_frame_count;
+    }
+
+    /// モデルのコマンドを記録するジョブを、そのモデルのスレッドに追加する。フレームの同期点で待つ。<br />
+    /// Add a job recording a model's commands to that model's thread, waited on at the frame's synchronization point.
+    pub fn add_render_job(&self, model_index: usize, work: impl FnOnce() + Send + 'static) {
+        let guard = FrameJobGuard(self.frame_jobs.clone());
+        self.frame_jobs.add();
+        self.threads[model_index % self.thread_count].add_job(
+            JobPriority::RenderCritical,
+            Box::new(move || {
+                let _guard = guard;
+                work();
+            }),
+        );
     }
 
-    /// スレッドプールの中の全部のスレッドを待ちます。<br />
-    /// Wait for all threads in the thread pool.
-    pub fn wait(&self) -> anyhow::Result<()> {
-        for thread in self.threads.iter() {
-            if !thread.work_received.load(Ordering::SeqCst) {
-                continue;
+    /// 読み込みのジョブを追加する。フレームはこのジョブを待たない。<br />
+    /// Add a loading job. Frames don't wait for it.
+    pub fn spawn_streaming(&self, work: impl FnOnce() + Send + 'static) {
+        if !self.streaming_threads.is_empty() {
+            if !self
+                .streaming_queue
+                .push(JobPriority::Streaming, Box::new(work))
+            {
+                log::error!("Failed to push work into the streaming queue.");
             }
-            thread.wait()?;
-            thread.work_received.store(false, Ordering::SeqCst);
+            return;
         }
-        Ok(())
+        let index = self.next_streaming_thread.fetch_add(1, Ordering::Relaxed);
+        self.threads[index % self.thread_count].add_job(JobPriority::Streaming, Box::new(work));
+    }
+
+    /// このフレームの描画のジョブが全て終わるまで待つ。読み込みのジョブは待たない。<br />
+    /// Wait until every rendering job of this frame is done. Loading jobs aren't waited on.
+    pub fn wait(&self) {
+        self.frame_jobs.wait();
     }
 
-    /// タスクのない、忙しくないコマンドプールを取得する。<br />
-    /// Get a command pool that doesn't have any task or isn't busy.
+    /// 読み込みに使うコマンドプールを取得する。ワーカーの上ではそのワーカーのもの、それ以外では読み込みのスレッドのもの。<br />
+    /// Get a command pool for loading: the worker's own on a worker thread, and a streaming thread's otherwise.
     pub fn get_idle_command_pool(&self) -> Arc<Mutex<CommandPool>> {
-        loop {
-            if let Some(thread) = self
-                .threads
-                .iter()
-                .find(|thread| (*thread).task_queue.is_empty())
-            {
-                return thread.command_pools[0].clone();
-            }
+        if let Some(pool) = WORKER_COMMAND_POOL.with(|pool| pool.borrow().clone()) {
+            return pool;
+        }
+        let index = self.next_streaming_thread.fetch_add(1, Ordering::Relaxed);
+        match self
+            .streaming_threads
+            .get(index % self.streaming_threads.len().max(1))
+        {
+            Some(thread) => thread.command_pools[0].clone(),
+            None => self.threads[index % self.thread_count].command_pools[0].clone(),
         }
     }
 }
 
 impl Drop for ThreadPool {
     fn drop(&mut self) {
-        self.wait().expect("Failed to wait on all threads.");
+        self.wait();
         self.threads.clear();
+        self.streaming_threads.clear();
     }
 }
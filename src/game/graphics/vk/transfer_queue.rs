@@ -0,0 +1,553 @@
+use anyhow::Context;
+use ash::version::DeviceV1_0;
+use ash::{vk::*, Device};
+use parking_lot::Mutex;
+use std::sync::{Arc, Weak};
+
+use crate::game::util::get_single_time_command_buffer;
+
+/// 転送キューでコピーするバッファ。<br />
+/// A buffer copied on the transfer queue.
+pub struct BufferUpload<'a> {
+    pub source: &'a super::StagingRegion<'a>,
+    pub destination: &'a super::Buffer,
+    /// コピー先のバッファの中のオフセット。<br />
+    /// Offset inside the destination buffer.
+    pub dst_offset: DeviceSize,
+    pub size: DeviceSize,
+    /// グラフィックスキューで所有権を得た後にバッファを読むアクセスとステージ。<br />
+    /// Access and stage reading the buffer after the graphics queue acquires it.
+    pub dst_access: AccessFlags,
+    pub dst_stage: PipelineStageFlags,
+}
+
+/// ステージングの領域の中の一つのミップレベル。<br />
+/// One mip level inside a staging region.
+#[derive(Copy, Clone, Debug)]
+pub struct ImageLevel {
+    /// 領域の先頭からのオフセット。<br />
+    /// Offset from the start of the region.
+    pub offset: DeviceSize,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// ステージングバッファからのコピーを専用の転送キューで実行し、描画と並行してアップロードする。<br />
+/// 転送キューで解放したリソースの所有権は、セマフォを待つグラフィックスキューのバリアで取得する。<br />
+/// そのコマンドは呼び出し側のグラフィックス用のコマンドプールから割り当てる。<br />
+/// アップロードごとにフェンスを持つので、待つのは呼び出したスレッドだけで、描画は止まらない。<br />
+/// 専用のキューファミリーがない場合は、グラフィックスキューで同じコマンドを実行する。<br />
+/// Runs copies from staging buffers on a dedicated transfer queue, uploading concurrently with rendering.<br />
+/// Ownership of resources released on the transfer queue is acquired with a barrier on the graphics queue, which waits on a semaphore.<br />
+/// Those commands are allocated from the caller's graphics command pool.<br />
+/// Each upload has its own fence, so only the calling thread waits and rendering isn't stalled.<br />
+/// Without a dedicated queue family, the same commands are run on the graphics queue.
+pub struct TransferQueue {
+    queue: Arc<Mutex<Queue>>,
+    graphics_queue: Arc<Mutex<Queue>>,
+    family_index: u32,
+    graphics_family_index: u32,
+    /// 転送キューのコマンドプール。専用の転送キューがある時だけ作る。<br />
+    /// Command pool of the transfer queue, only created with a dedicated transfer queue.
+    command_pool: Mutex<CommandPool>,
+    logical_device: Weak<Device>,
+}
+
+impl TransferQueue {
+    pub fn new(
+        device: Weak<Device>,
+        physical_device: &super::PhysicalDevice,
+        graphics_queue: Arc<Mutex<Queue>>,
+        transfer_queue: Queue,
+    ) -> anyhow::Result<Self> {
+        let graphics_family_index = physical_device
+            .queue_indices
+            .graphics_family
+            .unwrap_or_default();
+        let (queue, family_index) = match physical_device.queue_indices.transfer_family {
+            Some(family_index) if family_index != graphics_family_index => {
+                log::info!("Using queue family {} for uploads.", family_index);
+                (Arc::new(Mutex::new(transfer_queue)), family_index)
+            }
+            _ => {
+                log::info!("No dedicated transfer queue. Uploads use the graphics queue.");
+                (graphics_queue.clone(), graphics_family_index)
+            }
+        };
+        let command_pool = if family_index != graphics_family_index {
+            let logical_device = device
+                .upgrade()
+                .expect("Failed to upgrade logical device to create the transfer queue.");
+            Self::create_command_pool(logical_device.as_ref(), family_index)?
+        } else {
+            CommandPool::null()
+        };
+        Ok(TransferQueue {
+            queue,
+            graphics_queue,
+            family_index,
+            graphics_family_index,
+            command_pool: Mutex::new(command_pool),
+            logical_device: device,
+        })
+    }
+
+    pub fn is_dedicated(&self) -> bool {
+        self.family_index != self.graphics_family_index
+    }
+
+    /// ステージングバッファをコピーし、完了するまで待つ。<br />
+    /// Copy staging buffers and wait until they're finished.
+    pub fn upload_buffers(
+        &self,
+        command_pool: &Mutex<CommandPool>,
+        uploads: &[BufferUpload],
+    ) -> anyhow::Result<()> {
+        let (src_family, dst_family) = self.get_ownership_families();
+        let barriers = |is_release: bool| {
+            uploads
+                .iter()
+                .map(|upload| {
+                    // 同じキューファミリーでは、取得の代わりに書き込みを見えるようにする。
+                    // Within the same queue family, the writes are made visible instead of acquired.
+                    let (src_access, dst_access) = if is_release {
+                        (AccessFlags::TRANSFER_WRITE, AccessFlags::empty())
+                    } else if self.is_dedicated() {
+                        (AccessFlags::empty(), upload.dst_access)
+                    } else {
+                        (AccessFlags::TRANSFER_WRITE, upload.dst_access)
+                    };
+                    BufferMemoryBarrier::builder()
+                        .buffer(upload.destination.buffer)
+                        .offset(upload.dst_offset)
+                        .size(upload.size)
+                        .src_access_mask(src_access)
+                        .dst_access_mask(dst_access)
+                        .src_queue_family_index(src_family)
+                        .dst_queue_family_index(dst_family)
+                        .build()
+                })
+                .collect::<Vec<_>>()
+        };
+        let dst_stage = uploads
+            .iter()
+            .fold(PipelineStageFlags::empty(), |stage, upload| {
+                stage | upload.dst_stage
+            });
+        unsafe {
+            self.submit(
+                command_pool,
+                |device, command_buffer| {
+                    for upload in uploads.iter() {
+                        let copy_info = BufferCopy::builder()
+                            .src_offset(upload.source.offset)
+                            .dst_offset(upload.dst_offset)
+                            .size(upload.size)
+                            .build();
+                        device.cmd_copy_buffer(
+                            command_buffer,
+                            upload.source.buffer,
+                            upload.destination.buffer,
+                            &[copy_info],
+                        );
+                    }
+                    if self.is_dedicated() {
+                        device.cmd_pipeline_barrier(
+                            command_buffer,
+                            PipelineStageFlags::TRANSFER,
+                            PipelineStageFlags::BOTTOM_OF_PIPE,
+                            DependencyFlags::empty(),
+                            &[],
+                            barriers(true).as_slice(),
+                            &[],
+                        );
+                    }
+                },
+                |device, command_buffer| {
+                    device.cmd_pipeline_barrier(
+                        command_buffer,
+                        PipelineStageFlags::TRANSFER,
+                        dst_stage,
+                        DependencyFlags::empty(),
+                        &[],
+                        barriers(false).as_slice(),
+                        &[],
+                    );
+                },
+            )
+        }
+    }
+
+    /// ステージングバッファからイメージの最初のミップにコピーする。<br />
+    /// ブリットは転送キューで使えないので、所有権を取得した後にグラフィックスキューでミップマップを生成する。<br />
+    /// Copy a staging buffer to the first mip of an image.<br />
+    /// Blits aren't available on the transfer queue, so mipmaps are generated on the graphics queue after acquiring ownership.
+    pub fn upload_image(
+        &self,
+        command_pool: &Mutex<CommandPool>,
+        image: &super::Image,
+        staging: &super::StagingRegion,
+        aspect_flags: ImageAspectFlags,
+        mip_levels: u32,
+    ) -> anyhow::Result<()> {
+        let copy_info =
+            Self::get_level_copy(staging.offset, image.width, image.height, 0, aspect_flags);
+        self.upload_image_regions(
+            command_pool,
+            image,
+            staging,
+            &[copy_info],
+            aspect_flags,
+            mip_levels,
+            |command_buffer| {
+                image.generate_mipmap(
+                    aspect_flags,
+                    mip_levels,
+                    CommandPool::null(),
+                    Queue::null(),
+                    Some(command_buffer),
+                );
+            },
+        )
+    }
+
+    /// 全てのミップレベルをステージングバッファからそのままコピーする。<br />
+    /// 圧縮テクスチャはブリットで縮小できないので、ファイルに含まれるミップを使う。<br />
+    /// Copy every mip level from a staging buffer as is.<br />
+    /// Compressed textures can't be downscaled with blits, so the mips contained in the file are used.
+    pub fn upload_image_levels(
+        &self,
+        command_pool: &Mutex<CommandPool>,
+        image: &super::Image,
+        staging: &super::StagingRegion,
+        levels: &[ImageLevel],
+        aspect_flags: ImageAspectFlags,
+    ) -> anyhow::Result<()> {
+        let mip_levels = levels.len() as u32;
+        let copies = levels
+            .iter()
+            .enumerate()
+            .map(|(mip_level, level)| {
+                Self::get_level_copy(
+                    staging.offset + level.offset,
+                    level.width,
+                    level.height,
+                    mip_level as u32,
+                    aspect_flags,
+                )
+            })
+            .collect::<Vec<_>>();
+        self.upload_image_regions(
+            command_pool,
+            image,
+            staging,
+            &copies,
+            aspect_flags,
+            mip_levels,
+            |command_buffer| {
+                image.transition_layout(
+                    ImageLayout::TRANSFER_DST_OPTIMAL,
+                    ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    CommandPool::null(),
+                    Queue::null(),
+                    aspect_flags,
+                    mip_levels,
+                    Some(command_buffer),
+                );
+            },
+        )
+    }
+
+    fn get_level_copy(
+        buffer_offset: DeviceSize,
+        width: u32,
+        height: u32,
+        mip_level: u32,
+        aspect_flags: ImageAspectFlags,
+    ) -> BufferImageCopy {
+        BufferImageCopy::builder()
+            .buffer_offset(buffer_offset)
+            .image_extent(
+                Extent3D::builder()
+                    .width(width)
+                    .height(height)
+                    .depth(1)
+                    .build(),
+            )
+            .image_subresource(
+                ImageSubresourceLayers::builder()
+                    .aspect_mask(aspect_flags)
+                    .mip_level(mip_level)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .build(),
+            )
+            .build()
+    }
+
+    /// 転送キューで領域をコピーし、所有権を取得した後のグラフィックスキューで`finish`を記録する。<br />
+    /// Copy the regions on the transfer queue, and record `finish` on the graphics queue after acquiring ownership.
+    fn upload_image_regions<F>(
+        &self,
+        command_pool: &Mutex<CommandPool>,
+        image: &super::Image,
+        staging: &super::StagingRegion,
+        copies: &[BufferImageCopy],
+        aspect_flags: ImageAspectFlags,
+        mip_levels: u32,
+        finish: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnOnce(CommandBuffer),
+    {
+        let (src_family, dst_family) = self.get_ownership_families();
+        let ownership_barrier = |is_release: bool| {
+            let (src_access, dst_access) = if is_release {
+                (AccessFlags::TRANSFER_WRITE, AccessFlags::empty())
+            } else {
+                (AccessFlags::empty(), AccessFlags::TRANSFER_WRITE)
+            };
+            ImageMemoryBarrier::builder()
+                .image(image.image)
+                .old_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .src_queue_family_index(src_family)
+                .dst_queue_family_index(dst_family)
+                .subresource_range(
+                    ImageSubresourceRange::builder()
+                        .aspect_mask(aspect_flags)
+                        .base_array_layer(0)
+                        .layer_count(1)
+                        .base_mip_level(0)
+                        .level_count(mip_levels)
+                        .build(),
+                )
+                .build()
+        };
+        let release = ownership_barrier(true);
+        let acquire = ownership_barrier(false);
+        unsafe {
+            self.submit(
+                command_pool,
+                |device, command_buffer| {
+                    image.transition_layout(
+                        ImageLayout::UNDEFINED,
+                        ImageLayout::TRANSFER_DST_OPTIMAL,
+                        CommandPool::null(),
+                        Queue::null(),
+                        aspect_flags,
+                        mip_levels,
+                        Some(command_buffer),
+                    );
+                    device.cmd_copy_buffer_to_image(
+                        command_buffer,
+                        staging.buffer,
+                        image.image,
+                        ImageLayout::TRANSFER_DST_OPTIMAL,
+                        copies,
+                    );
+                    if self.is_dedicated() {
+                        device.cmd_pipeline_barrier(
+                            command_buffer,
+                            PipelineStageFlags::TRANSFER,
+                            PipelineStageFlags::BOTTOM_OF_PIPE,
+                            DependencyFlags::empty(),
+                            &[],
+                            &[],
+                            &[release],
+                        );
+                    }
+                },
+                |device, command_buffer| {
+                    if self.is_dedicated() {
+                        device.cmd_pipeline_barrier(
+                            command_buffer,
+                            PipelineStageFlags::TRANSFER,
+                            PipelineStageFlags::TRANSFER,
+                            DependencyFlags::empty(),
+                            &[],
+                            &[],
+                            &[acquire],
+                        );
+                    }
+                    finish(command_buffer);
+                },
+            )
+        }
+    }
+
+    fn create_command_pool(device: &Device, family_index: u32) -> anyhow::Result<CommandPool> {
+        let create_info = CommandPoolCreateInfo::builder()
+            .queue_family_index(family_index)
+            .flags(CommandPoolCreateFlags::TRANSIENT);
+        unsafe {
+            device
+                .create_command_pool(&create_info, None)
+                .with_context(|| "Failed to create command pool for uploads.")
+        }
+    }
+
+    /// 所有権を移す時の転送元と転送先のキューファミリー。同じファミリーなら移さない。<br />
+    /// Source and destination queue families when transferring ownership. Nothing is transferred within the same family.
+    fn get_ownership_families(&self) -> (u32, u32) {
+        if self.is_dedicated() {
+            (self.family_index, self.graphics_family_index)
+        } else {
+            (QUEUE_FAMILY_IGNORED, QUEUE_FAMILY_IGNORED)
+        }
+    }
+
+    /// 転送とグラフィックスのコマンドを記録して送信し、このアップロードのフェンスだけを待つ。<br />
+    /// Record and submit the transfer and graphics commands, waiting only on this upload's fence.
+    unsafe fn submit<T, G>(
+        &self,
+        command_pool: &Mutex<CommandPool>,
+        record_transfer: T,
+        record_graphics: G,
+    ) -> anyhow::Result<()>
+    where
+        T: FnOnce(&Device, CommandBuffer),
+        G: FnOnce(&Device, CommandBuffer),
+    {
+        let device = self
+            .logical_device
+            .upgrade()
+            .ok_or_else(|| anyhow::anyhow!("Failed to upgrade logical device for uploads."))?;
+        let fence = device
+            .create_fence(&FenceCreateInfo::default(), None)
+            .with_context(|| "Failed to create fence for uploads.")?;
+        let result = if self.is_dedicated() {
+            self.submit_dedicated(
+                device.as_ref(),
+                command_pool,
+                fence,
+                record_transfer,
+                record_graphics,
+            )
+        } else {
+            self.submit_shared(
+                device.as_ref(),
+                command_pool,
+                fence,
+                record_transfer,
+                record_graphics,
+            )
+        };
+        device.destroy_fence(fence, None);
+        result
+    }
+
+    /// 転送キューで解放し、セマフォを待つグラフィックスキューで取得する。<br />
+    /// Release on the transfer queue, and acquire on the graphics queue waiting on a semaphore.
+    unsafe fn submit_dedicated<T, G>(
+        &self,
+        device: &Device,
+        command_pool: &Mutex<CommandPool>,
+        fence: Fence,
+        record_transfer: T,
+        record_graphics: G,
+    ) -> anyhow::Result<()>
+    where
+        T: FnOnce(&Device, CommandBuffer),
+        G: FnOnce(&Device, CommandBuffer),
+    {
+        let semaphore = device
+            .create_semaphore(&SemaphoreCreateInfo::default(), None)
+            .with_context(|| "Failed to create semaphore for uploads.")?;
+        let transfer_command_buffer = Self::record(device, &self.command_pool, record_transfer)?;
+        let graphics_command_buffer = Self::record(device, command_pool, record_graphics)?;
+
+        let transfer_command_buffers = [transfer_command_buffer];
+        let semaphores = [semaphore];
+        let transfer_submit = SubmitInfo::builder()
+            .command_buffers(&transfer_command_buffers)
+            .signal_semaphores(&semaphores)
+            .build();
+        let graphics_command_buffers = [graphics_command_buffer];
+        let wait_stages = [PipelineStageFlags::TRANSFER];
+        let graphics_submit = SubmitInfo::builder()
+            .command_buffers(&graphics_command_buffers)
+            .wait_semaphores(&semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .build();
+        let result = device
+            .queue_submit(*self.queue.lock(), &[transfer_submit], Fence::null())
+            .and_then(|_| {
+                device.queue_submit(*self.graphics_queue.lock(), &[graphics_submit], fence)
+            })
+            .and_then(|_| device.wait_for_fences(&[fence], true, u64::MAX))
+            .with_context(|| "Failed to submit uploads to the transfer queue.");
+        if result.is_err() {
+            // 送信に失敗した場合、送信済みのコマンドが終わるまで解放できない。
+            // When a submission failed, commands already submitted have to finish before being freed.
+            device.queue_wait_idle(*self.queue.lock()).ok();
+        }
+        device.free_command_buffers(*self.command_pool.lock(), &transfer_command_buffers);
+        device.free_command_buffers(*command_pool.lock(), &graphics_command_buffers);
+        device.destroy_semaphore(semaphore, None);
+        result
+    }
+
+    /// 同じキューファミリーなので、一つのコマンドバッファで記録してグラフィックスキューに送る。<br />
+    /// Within the same queue family, everything is recorded into a single command buffer and sent to the graphics queue.
+    unsafe fn submit_shared<T, G>(
+        &self,
+        device: &Device,
+        command_pool: &Mutex<CommandPool>,
+        fence: Fence,
+        record_transfer: T,
+        record_graphics: G,
+    ) -> anyhow::Result<()>
+    where
+        T: FnOnce(&Device, CommandBuffer),
+        G: FnOnce(&Device, CommandBuffer),
+    {
+        let command_buffer = Self::record(device, command_pool, |device, command_buffer| {
+            record_transfer(device, command_buffer);
+            record_graphics(device, command_buffer);
+        })?;
+        let command_buffers = [command_buffer];
+        let submit_info = SubmitInfo::builder()
+            .command_buffers(&command_buffers)
+            .build();
+        let result = device
+            .queue_submit(*self.graphics_queue.lock(), &[submit_info], fence)
+            .and_then(|_| device.wait_for_fences(&[fence], true, u64::MAX))
+            .with_context(|| "Failed to submit uploads to the graphics queue.");
+        device.free_command_buffers(*command_pool.lock(), &command_buffers);
+        result
+    }
+
+    /// プールをロックしている間にコマンドバッファを記録する。<br />
+    /// Record a command buffer while the pool is locked.
+    unsafe fn record<F>(
+        device: &Device,
+        command_pool: &Mutex<CommandPool>,
+        record: F,
+    ) -> anyhow::Result<CommandBuffer>
+    where
+        F: FnOnce(&Device, CommandBuffer),
+    {
+        let pool = command_pool.lock();
+        let command_buffer = get_single_time_command_buffer(device, *pool);
+        record(device, command_buffer);
+        device
+            .end_command_buffer(command_buffer)
+            .with_context(|| "Failed to end command buffer for uploads.")?;
+        Ok(command_buffer)
+    }
+}
+
+impl Drop for TransferQueue {
+    fn drop(&mut self) {
+        if let Some(device) = self.logical_device.upgrade() {
+            if self.is_dedicated() {
+                unsafe {
+                    device.destroy_command_pool(*self.command_pool.lock(), None);
+                }
+            }
+        }
+    }
+}
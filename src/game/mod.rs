@@ -7,8 +7,8 @@ pub use shared::*;
 pub use ui::*;
 
 use ash::vk::CommandBuffer;
+use glam::Vec3A;
 use parking_lot::RwLock;
-use slotmap::{DefaultKey, SlotMap};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::mem::ManuallyDrop;
@@ -16,23 +16,78 @@ use std::rc::Rc;
 use std::sync::Arc;
 #[cfg(target_os = "windows")]
 use winapi::um::d3d12::ID3D12GraphicsCommandList;
-use winit::{event_loop::EventLoop, window::WindowBuilder};
+use winit::{
+    event::WindowEvent,
+    event_loop::{EventLoop, EventLoopWindowTarget},
+    window::{Fullscreen, WindowBuilder, WindowId},
+};
 #[cfg(target_os = "windows")]
 use wio::com::ComPtr;
 
 #[cfg(target_os = "windows")]
 use crate::game::graphics::dx12 as DX12;
-use crate::game::graphics::vk::{Buffer, Graphics, Image};
+use crate::game::graphics::vk::{Buffer, Graphics, GraphicsError, Image, TextureStreamer};
 use crate::game::scenes::title_scene::TitleScene;
-use crate::game::shared::enums::SceneType;
+use crate::game::shared::enums::{SceneType, WindowMode};
+use crate::game::shared::structs::games::match_result::{
+    get_scoreboard, MatchResults, MatchTracker,
+};
+use crate::game::shared::structs::games::player_settings::get_remote_nameplates;
+use crate::game::shared::structs::games::terrain_patch::TerrainPatch;
+use crate::game::shared::structs::{
+    get_distance_alpha, project_point, AssetPreview, DebugDraw, FixedTimestep, FrameProfiler,
+    GraphicsSettings, LoadingProgress, MemoryMonitor, PresenceActivity, Primitive,
+    ResourceCounts, SaveGame, Settings, SkeletonPose, TimeOfDay, QUICK_SAVE_PATH,
+};
+#[cfg(debug_assertions)]
+use crate::game::shared::structs::{Inspector, InspectorEdit, InspectorEntry, PositionInfo};
 use crate::game::shared::traits::GraphicsBase;
-use crate::game::shared::util::get_random_string;
+use crate::game::shared::util::{get_allocation_count, get_live_allocation_count};
 use crate::game::traits::Disposable;
-use crate::game::{Camera, GameScene, ResourceManager, SceneManager};
-use rand::prelude::IteratorRandom;
+use crate::game::ui::{Console, ConsoleCommand, ConsoleReply, TweakRegistry, TWEAK_CONFIG_PATH};
+#[cfg(debug_assertions)]
+use crate::game::{Animator, Transform};
+use crate::game::{
+    Camera, GameScene, NetworkReplicated, PreviewScene, RenderableRef, ResourceManager,
+    SceneManager, World,
+};
+#[cfg(debug_assertions)]
+use slotmap::DefaultKey;
 use winit::dpi::PhysicalSize;
 use winit::event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode};
 
+/// 最小化している間の1フレームの時間。何も表示しないので垂直同期で待たず、更新だけをゆっくり続ける。<br />
+/// Frame time while minimized. Nothing is presented so vertical sync doesn't throttle the loop, and only updates keep running slowly.
+const MINIMIZED_FRAME_TIME: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// タッチパッドのピクセル単位のスクロールを、ホイールの一段として扱う量。<br />
+/// Amount of pixel-based touchpad scrolling treated as one wheel step.
+const PIXELS_PER_SCROLL_STEP: f64 = 50.0;
+
+/// 全ての名札を隠す、クライアント側の設定。<br />
+/// Client-side setting that hides all nameplates.
+const HIDE_NAMEPLATES_TWEAK: &str = "Interface/Hide Nameplates";
+
+/// プレイヤーの位置から名札までの高さ。<br />
+/// Height of nameplates above the position of a player.
+const NAMEPLATE_HEIGHT: f32 = 2.2;
+
+/// メインの画面を写すウィンドウの、開いた時の大きさ。<br />
+/// Size of the window mirroring the main screen when it's opened.
+const MIRROR_WINDOW_SIZE: (f64, f64) = (640.0, 360.0);
+
+/// 今のシーンの状態をクイックセーブするキー。<br />
+/// Key that quick-saves the state of the current scene.
+const QUICK_SAVE_KEY: VirtualKeyCode = VirtualKeyCode::F8;
+
+/// クイックセーブした状態を読み込むキー。<br />
+/// Key that loads the quick-saved state.
+const QUICK_LOAD_KEY: VirtualKeyCode = VirtualKeyCode::F9;
+
+/// 試合の地形のチャンクの位置。<br />
+/// Grid position of the terrain chunk of a match.
+const TERRAIN_GRID: (f32, f32) = (-0.5, -0.5);
+
 pub struct Game<GraphicsType, BufferType, CommandType, TextureType>
 where
     GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
@@ -48,10 +103,72 @@ where
     pub current_scene: SceneType,
     pub is_terminating: bool,
     resource_manager: ResourceManagerHandle<GraphicsType, BufferType, CommandType, TextureType>,
-    entities: Rc<RefCell<SlotMap<DefaultKey, usize>>>,
+    entities: Rc<RefCell<World<GraphicsType, BufferType, CommandType, TextureType>>>,
     network_system: Arc<tokio::sync::RwLock<NetworkSystem>>,
+    physics_system: Rc<RefCell<PhysicsSystem>>,
+    audio_system: Rc<RefCell<AudioSystem>>,
+    timeline_system: Rc<RefCell<TimelineSystem>>,
+    presence_system: PresenceSystem,
+    profiler: FrameProfiler,
+    memory_monitor: MemoryMonitor,
     scenes: HashMap<SceneType, usize>,
     room_state_receiver: Option<crossbeam::channel::Receiver<bool>>,
+    tweaks: TweakRegistry,
+    /// ウィンドウに今反映しているモード。<br />
+    /// The mode currently applied to the window.
+    window_mode: WindowMode,
+    /// Alt+Enterで戻るフルスクリーンのモード。<br />
+    /// The fullscreen mode Alt+Enter returns to.
+    fullscreen_mode: WindowMode,
+    /// セーフモードで変えた設定の説明。セーフモードでなければ`None`。<br />
+    /// Descriptions of the settings changed by safe mode, or `None` outside safe mode.
+    safe_mode_changes: Option<Vec<String>>,
+    /// 読み込み中のシーンの進み具合。読み込み中でなければ`None`。<br />
+    /// Progress of the scene being loaded, or `None` when nothing is loading.
+    loading_progress: Option<LoadingProgress>,
+    /// 読み込みを始めた時のテクスチャの数。アップロードしたテクスチャを数えるのに使う。<br />
+    /// Number of textures when loading started, used to count the uploaded textures.
+    loading_texture_count: usize,
+    /// インスペクターで選んでいるエンティティ。<br />
+    /// The entity selected in the inspector.
+    #[cfg(debug_assertions)]
+    inspector: Inspector,
+    console: Console,
+    /// `--preview`で開いた素材。ゲームの代わりにプレビューのシーンで表示する。<br />
+    /// The asset opened with `--preview`, shown in the preview scene instead of the game.
+    preview: Option<Rc<RefCell<AssetPreview>>>,
+    /// 起動時に読み込んだ設定。<br />
+    /// Settings read at startup.
+    settings: Settings,
+    /// リモートのエンティティの補間を画面に描くかどうか。<br />
+    /// Whether the interpolation of remote entities is drawn on the screen.
+    is_network_debug_view_open: bool,
+    /// 骨付きモデルの骨格と関節の名前を描くかどうか。<br />
+    /// Whether the skeletons and joint names of skinned models are drawn.
+    is_skeleton_view_open: bool,
+    are_nameplates_hidden: bool,
+    /// 描画中にデバイスを失ったかどうか。次の更新でデバイスを作り直す。<br />
+    /// Whether the device was lost while rendering. The device is recreated on the next update.
+    is_device_lost: bool,
+    /// 最後に生成した地形。デバイスを作り直した時に同じ地形を生成し直す。<br />
+    /// The terrain generated last, generated again when the device is recreated.
+    last_terrain: Option<Primitive>,
+    /// 手続き的に生成した、またはホストから受け取った時の地形。セーブする地形のパッチはこれとの差分。<br />
+    /// The terrain as procedurally generated or received from the host. Saved terrain patches are the difference from it.
+    terrain_baseline: Option<Primitive>,
+    /// メインの画面を写している別のウィンドウ。<br />
+    /// The other window mirroring the main screen.
+    mirror_window: Option<WindowId>,
+    /// フレームの時間を溜めて、シミュレーションを固定の間隔で進める。<br />
+    /// Accumulates frame time to advance the simulation at a fixed interval.
+    fixed_timestep: FixedTimestep,
+    match_tracker: MatchTracker,
+    /// 決着した試合の結果。結果の画面を出している間だけ`Some`。<br />
+    /// Results of the decided match, `Some` only while the results screen is shown.
+    match_results: Option<MatchResults>,
+    /// ゲームの中の時間。ゲームのシーンでだけ進み、毎フレーム平行光源と空に反映する。<br />
+    /// Time of day in the game. It only advances in the game scene, and is applied to the directional light and the sky every frame.
+    time_of_day: TimeOfDay,
 }
 
 impl Game<Graphics, Buffer, CommandBuffer, Image> {
@@ -61,21 +178,24 @@ impl Game<Graphics, Buffer, CommandBuffer, Image> {
         height: f64,
         event_loop: &EventLoop<()>,
         network_system: NetworkSystem,
+        settings: &Settings,
     ) -> anyhow::Result<Self> {
         let window = Rc::new(RefCell::new(
             WindowBuilder::new()
                 .with_title(title)
                 .with_inner_size(winit::dpi::LogicalSize::new(width, height))
-                .with_resizable(false)
                 .build(event_loop)
                 .expect("Failed to create window."),
         ));
         let camera = Rc::new(RefCell::new(Camera::new(width, height)));
-        let resource_manager = Arc::new(RwLock::new(ManuallyDrop::new(ResourceManager::new())));
+        let resource_manager = Arc::new(RwLock::new(ManuallyDrop::new(ResourceManager::new(
+            settings.inflight_buffer_count,
+        ))));
         let graphics = Graphics::new(
             std::rc::Rc::downgrade(&window),
             camera.clone(),
             Arc::downgrade(&resource_manager),
+            settings,
         )?;
         Ok(Game {
             window,
@@ -84,19 +204,54 @@ impl Game<Graphics, Buffer, CommandBuffer, Image> {
             graphics: Arc::new(RwLock::new(ManuallyDrop::new(graphics))),
             scene_manager: SceneManager::new(),
             ui_system: None,
-            entities: Rc::new(RefCell::new(SlotMap::new())),
+            entities: Rc::new(RefCell::new(World::new())),
             network_system: Arc::new(tokio::sync::RwLock::new(network_system)),
+            physics_system: Rc::new(RefCell::new(PhysicsSystem::new())),
+            audio_system: Rc::new(RefCell::new(AudioSystem::new())),
+            timeline_system: Rc::new(RefCell::new(TimelineSystem::new())),
+            presence_system: PresenceSystem::new(settings),
+            profiler: FrameProfiler::new(),
+            memory_monitor: MemoryMonitor::new(),
+            safe_mode_changes: None,
+            loading_progress: None,
+            loading_texture_count: 0,
+            #[cfg(debug_assertions)]
+            inspector: Inspector::new(),
+            console: Console::new(),
+            preview: None,
+            settings: settings.clone(),
+            is_network_debug_view_open: false,
+            is_skeleton_view_open: false,
+            are_nameplates_hidden: false,
+            is_device_lost: false,
+            last_terrain: None,
+            terrain_baseline: None,
+            mirror_window: None,
+            fixed_timestep: FixedTimestep::default(),
+            match_tracker: MatchTracker::new(),
+            match_results: None,
+            time_of_day: TimeOfDay::new(settings.day_length),
             scenes: HashMap::new(),
             current_scene: SceneType::TITLE,
             room_state_receiver: None,
             is_terminating: false,
+            tweaks: TweakRegistry::new(),
+            window_mode: WindowMode::Windowed,
+            fullscreen_mode: WindowMode::Borderless,
         })
     }
 
-    pub fn end_input(&self) {
-        if let Some(ui) = self.ui_system.as_ref() {
-            ui.borrow_mut().end_input();
-        }
+    /// セーフモードで起動する。保存した設定を読み込まず、UIを作った時に変えた設定を説明する。初期化の前に呼び出す。<br />
+    /// Start in safe mode. Saved settings aren't loaded, and the changed settings are explained once the UI is created. Called before initializing.
+    pub fn enter_safe_mode(&mut self, changes: Vec<String>) {
+        self.safe_mode_changes = Some(changes);
+    }
+
+    /// ゲームの代わりに素材のプレビューを開く。初期化の前に呼び出す。<br />
+    /// Open an asset preview instead of the game. Called before initializing.
+    pub fn enter_preview_mode(&mut self, preview: AssetPreview) {
+        self.preview = Some(Rc::new(RefCell::new(preview)));
+        self.current_scene = SceneType::PREVIEW;
     }
 
     pub fn initialize(&mut self) -> bool {
@@ -111,63 +266,216 @@ impl Game<Graphics, Buffer, CommandBuffer, Image> {
             Rc::downgrade(&self.entities),
             Arc::downgrade(&self.network_system),
             Rc::downgrade(&self.camera),
+            Rc::downgrade(&self.physics_system),
+            Rc::downgrade(&self.audio_system),
+            Rc::downgrade(&self.timeline_system),
+            &self.settings,
         );
         let title_scene_index = self.scene_manager.register_scene(title_scene);
         let game_scene_index = self.scene_manager.register_scene(game_scene);
-        self.scene_manager.switch_scene(title_scene_index);
         self.scenes.insert(SceneType::TITLE, title_scene_index);
+        self.scenes.insert(SceneType::LOBBY, title_scene_index);
         self.scenes.insert(SceneType::GAME, game_scene_index);
+        if let Some(preview) = self.preview.as_ref() {
+            let preview_scene = PreviewScene::new(
+                Arc::downgrade(&self.resource_manager),
+                Arc::downgrade(&self.graphics),
+                Rc::downgrade(&self.entities),
+                Rc::downgrade(&self.camera),
+                Rc::downgrade(preview),
+            );
+            let preview_scene_index = self.scene_manager.register_scene(preview_scene);
+            self.scenes.insert(SceneType::PREVIEW, preview_scene_index);
+        }
+        let scene_index = self.scenes[&self.current_scene];
+        self.scene_manager
+            .switch_scene(scene_index)
+            .expect("Failed to switch to the first scene.");
+        self.register_tweaks();
+        self.register_commands();
+        self.apply_window_mode();
         true
     }
 
-    pub fn input_button(&self, button: MouseButton, x: f64, y: f64, element_state: ElementState) {
-        if let Some(ui) = self.ui_system.as_ref() {
-            ui.borrow_mut().input_button(button, x, y, element_state);
-        }
+    /// 現在のシーンを読み込み、全てのタスクが終わるまで待つ。<br />
+    /// 起動時はまだ読み込み画面を描画できないので、こちらを使う。<br />
+    /// Load the current scene and wait until all tasks finish.<br />
+    /// Used at startup, where the loading screen can't be rendered yet.
+    pub async fn load_content(&mut self) -> anyhow::Result<()> {
+        self.begin_loading().await?;
+        self.scene_manager.wait_for_all_tasks()?;
+        self.loading_progress = None;
+        self.finish_loading()
     }
 
-    pub async fn input_key(&self, key: VirtualKeyCode, element_state: ElementState) {
-        if let Some(ui) = self.ui_system.as_ref() {
-            ui.borrow_mut().input_key(key, element_state);
+    /// ソークテストで記録するリソースの数を数える。<br />
+    /// Count the resources recorded by the soak test.
+    pub fn get_resource_counts(&self) -> anyhow::Result<ResourceCounts> {
+        let graphics = self.graphics.read();
+        let (vram_bytes, vram_allocations) = graphics.get_memory_usage()?;
+        let descriptor_pools = graphics.get_descriptor_pool_count();
+        drop(graphics);
+        let resource_manager = self.resource_manager.read();
+        Ok(ResourceCounts {
+            vram_bytes,
+            vram_allocations,
+            descriptor_pools,
+            models: resource_manager.get_model_count(),
+            textures: resource_manager.get_texture_count(),
+            entities: self.entities.borrow().get_entity_count(),
+            heap_allocations: get_live_allocation_count(),
+        })
+    }
+
+    /// 結果の画面を出さずに試合を終えてタイトルに戻る。ゲームのシーンを片付けてすぐにタイトルを読み込み直す。<br />
+    /// どちらのシーンも片付けるので、戻った後のリソースの数は起動した時と比べられる。<br />
+    /// End the match without the results screen and return to the title. The game scene is torn down and the title is reloaded right away.<br />
+    /// Both scenes are torn down, so the resource counts after returning can be compared with those at startup.
+    pub async fn return_to_title(&mut self) -> anyhow::Result<()> {
+        self.unload_match_scenes()?;
+        self.switch_scene(SceneType::TITLE).await
+    }
+
+    /// 結果の画面からロビーに戻る。部屋を出て、ロビーの背景になるタイトルのシーンを読み込み直す。<br />
+    /// Return to the lobby from the results screen. The room is left, and the title scene serving as the lobby's backdrop is reloaded.
+    async fn return_to_lobby(&mut self) -> anyhow::Result<()> {
+        self.network_system.write().await.leave_room().await;
+        if let Some(ui_system) = self.ui_system.as_ref() {
+            ui_system.borrow_mut().set_scoreboard_visible(false);
         }
-        self.scene_manager.input_key(key, element_state).await;
+        self.unload_match_scenes()?;
+        self.switch_scene(SceneType::LOBBY).await
     }
 
-    pub fn input_motion(&self, x: f64, y: f64) {
-        if let Some(ui) = self.ui_system.as_ref() {
-            ui.borrow_mut().input_motion(x, y);
+    /// ゲームとタイトルのシーンを片付け、試合の決着も忘れる。<br />
+    /// Tear down the game and title scenes, and forget the outcome of the match as well.
+    fn unload_match_scenes(&mut self) -> anyhow::Result<()> {
+        unsafe {
+            self.graphics.read().wait_idle();
         }
+        for scene_type in [SceneType::GAME, SceneType::TITLE].iter() {
+            if let Some(index) = self.scenes.get(scene_type) {
+                self.scene_manager.unload_scene(*index)?;
+            }
+        }
+        self.last_terrain = None;
+        self.terrain_baseline = None;
+        self.match_tracker.reset();
+        self.match_results = None;
+        Ok(())
     }
 
-    pub fn input_scroll(&self, mouse_scroll_delta: MouseScrollDelta) {
-        if let Some(ui) = self.ui_system.as_ref() {
-            ui.borrow_mut().input_scroll(mouse_scroll_delta);
+    /// 試合の決着を確かめ、決着が付いたら結果を報告して結果の画面を出す。<br />
+    /// 報告に失敗しても、結果の画面からロビーには戻れる。<br />
+    /// Check whether the match has been decided, and if so, report the result and show the results screen.<br />
+    /// Even if reporting fails, the results screen still leads back to the lobby.
+    async fn update_match(&mut self) {
+        if self.current_scene != SceneType::GAME || self.match_results.is_some() {
+            return;
         }
+        let mut ns = self.network_system.write().await;
+        if ns.is_offline() {
+            return;
+        }
+        let room_state = ns.room_state.lock().await.clone();
+        let outcome = match self.match_tracker.update(&room_state) {
+            Some(outcome) => outcome,
+            None => return,
+        };
+        log::info!("The match has been decided: {:?}", outcome);
+        let results = match ns.report_match_result(&outcome).await {
+            Ok(results) => results,
+            Err(e) => {
+                log::error!("{}", e);
+                let player = match ns.logged_user.as_ref() {
+                    Some(player) => player.lock().await.clone(),
+                    None => Default::default(),
+                };
+                MatchResults {
+                    report_error: Some(e.to_string()),
+                    ..MatchResults::new(outcome, &room_state, &player, 0)
+                }
+            }
+        };
+        self.match_results = Some(results);
     }
 
-    pub fn input_unicode(&self, c: char) {
-        if let Some(ui) = self.ui_system.as_ref() {
-            ui.borrow_mut().input_unicode(c);
+    /// ソークテストで部屋に加えた、合成したプレイヤーを動かす。<br />
+    /// Move the synthetic players added to the room by the soak test.
+    pub async fn move_synthetic_players(&mut self, delta_time: f64) {
+        if self.current_scene == SceneType::GAME && !self.is_loading() {
+            self.network_system
+                .write()
+                .await
+                .move_synthetic_players(delta_time)
+                .await;
         }
     }
 
-    pub async fn load_content(&mut self) -> anyhow::Result<()> {
+    /// 現在のシーンの読み込みを始める。タスクは`update`で毎フレーム確かめる。<br />
+    /// Start loading the current scene. The tasks are checked every frame in `update`.
+    async fn begin_loading(&mut self) -> anyhow::Result<()> {
+        self.loading_texture_count = self.resource_manager.read().get_texture_count();
         self.scene_manager.load_content().await?;
+        self.loading_progress = Some(LoadingProgress::default());
+        Ok(())
+    }
+
+    /// シーンを読み込み直さずに、後から追加したモデルの読み込みを待つ。<br />
+    /// Wait for models added afterwards to load, without reloading the scene.
+    fn begin_loading_added_models(&mut self) {
+        if self.is_loading() {
+            return;
+        }
+        self.loading_texture_count = self.resource_manager.read().get_texture_count();
+        self.loading_progress = Some(LoadingProgress::default());
+    }
+
+    /// 読み込みのタスクを待たずに確かめ、全て終わったらシーンの読み込みを仕上げる。<br />
+    /// Check the loading tasks without waiting, and finish loading the scene once all of them are done.
+    fn update_loading(&mut self) -> anyhow::Result<()> {
+        let mut progress = self.scene_manager.poll_tasks()?;
+        progress.textures_uploaded = self
+            .resource_manager
+            .read()
+            .get_texture_count()
+            .saturating_sub(self.loading_texture_count);
+        if let Some(ui_system) = self.ui_system.as_ref() {
+            ui_system.borrow_mut().draw_loading_screen(&progress);
+        }
+        if !progress.is_complete() {
+            self.loading_progress = Some(progress);
+            return Ok(());
+        }
+        // 全ての受信側が終わっているので、ここではもう待たない。
+        // All receivers are done, so this no longer waits.
         self.scene_manager.wait_for_all_tasks()?;
+        self.loading_progress = None;
+        self.finish_loading()
+    }
+
+    /// 読み込んだモデルを登録した後、シーンに合わせて描画のリソースを作る。<br />
+    /// Create the rendering resources for the scene after the loaded models are registered.
+    fn finish_loading(&mut self) -> anyhow::Result<()> {
         if self.ui_system.is_none() {
             let graphics_lock = self.graphics.read();
             let ui_manager = Rc::new(RefCell::new(ManuallyDrop::new(UISystem::new(
                 &*graphics_lock,
-            ))));
+                &self.settings,
+            )?)));
             drop(graphics_lock);
             let mut graphics_lock = self.graphics.write();
             graphics_lock.ui_manager = Some(Rc::downgrade(&ui_manager));
+            if self.safe_mode_changes.is_some() {
+                ui_manager.borrow_mut().show_safe_mode_dialog();
+            }
             self.ui_system = Some(ui_manager);
         }
 
         {
             let PhysicalSize { width, height } = self.window.borrow().inner_size();
             let mut graphics_lock = self.graphics.write();
+            graphics_lock.set_render_features(self.scene_manager.get_render_features())?;
             let is_initialized = graphics_lock.is_initialized();
             if !is_initialized {
                 graphics_lock.initialize_scene_resource(self.current_scene, false)?;
@@ -183,18 +491,197 @@ impl Game<Graphics, Buffer, CommandBuffer, Image> {
         Ok(())
     }
 
+    /// ウィンドウの大きさに合わせてスワップチェーンと、それに依存するリソースを作り直す。<br />
+    /// Recreate the swapchain and the resources depending on it to fit the window size.
+    pub fn recreate_swapchain(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        self.graphics
+            .write()
+            .recreate_swapchain(width, height, self.current_scene)?;
+        if width > 0 && height > 0 {
+            self.scene_manager.create_ssbo()?;
+        }
+        Ok(())
+    }
+
+    /// 失ったデバイスを作り直す。<br />
+    /// シーンとUI、リソースマネージャーとグラフィックスを全て解放してから作り直し、今のシーンを読み込み直す。<br />
+    /// グラフィックスを作り直せなかった場合は、終了するように印を付ける。<br />
+    /// Recreate the lost device.<br />
+    /// The scenes, the UI, the resource manager and the graphics are all released before being created again, and the current scene is loaded again.<br />
+    /// If the graphics can't be created again, the game is marked as terminating.
+    async fn recreate_device(&mut self) -> anyhow::Result<()> {
+        self.is_device_lost = false;
+        self.graphics.write().set_disposing();
+        for index in self.scenes.values() {
+            self.scene_manager.unload_scene(*index)?;
+        }
+        if let Some(ui_system) = self.ui_system.take() {
+            unsafe {
+                ManuallyDrop::drop(&mut *ui_system.borrow_mut());
+            }
+        }
+        {
+            let mut resource_manager = self.resource_manager.write();
+            unsafe {
+                ManuallyDrop::drop(&mut *resource_manager);
+            }
+            *resource_manager =
+                ManuallyDrop::new(ResourceManager::new(self.settings.inflight_buffer_count));
+        }
+        {
+            let mut graphics = self.graphics.write();
+            unsafe {
+                ManuallyDrop::drop(&mut *graphics);
+            }
+            // 別のウィンドウはグラフィックスと一緒に閉じる。
+            self.mirror_window = None;
+            // ここで失敗すると解放済みのグラフィックスが残るので、続けずに終了する。
+            // Failing here would leave the released graphics behind, so shut down instead of carrying on.
+            let new_graphics = match Graphics::new(
+                Rc::downgrade(&self.window),
+                self.camera.clone(),
+                Arc::downgrade(&self.resource_manager),
+                &self.settings,
+            ) {
+                Ok(g) => g,
+                Err(e) => {
+                    log::error!(
+                        "Failed to recreate graphics after the device was lost. Shutting down: {:#}",
+                        e
+                    );
+                    self.is_terminating = true;
+                    return Ok(());
+                }
+            };
+            *graphics = ManuallyDrop::new(new_graphics);
+        }
+        self.apply_tweaks();
+        if self.current_scene == SceneType::GAME {
+            if let Some(primitive) = self.last_terrain.clone() {
+                self.scene_manager.generate_terrain(
+                    TERRAIN_GRID.0,
+                    TERRAIN_GRID.1,
+                    Some(primitive),
+                )?;
+            }
+        }
+        log::info!("Recreated the device. Reloading the current scene.");
+        self.begin_loading().await
+    }
+
+    /// ウィンドウモードとフルスクリーンを切り替える。設定パネルの値を変えるので、次の更新で反映される。<br />
+    /// Toggle between windowed and fullscreen. The value in the settings panel is changed, so it takes effect on the next update.
+    pub fn toggle_fullscreen(&mut self) {
+        let window_mode = if self.window_mode.is_fullscreen() {
+            WindowMode::Windowed
+        } else {
+            self.fullscreen_mode
+        };
+        GraphicsSettings::set_window_mode(&mut self.tweaks, window_mode);
+    }
+
+    /// メインの画面を写すウィンドウを開くか、開いていれば閉じる。<br />
+    /// Open the window mirroring the main screen, or close it if it's open.
+    pub fn toggle_mirror_window(
+        &mut self,
+        event_loop: &EventLoopWindowTarget<()>,
+    ) -> anyhow::Result<()> {
+        if let Some(id) = self.mirror_window.take() {
+            self.graphics.read().close_secondary_window(id);
+            return Ok(());
+        }
+        let (width, height) = MIRROR_WINDOW_SIZE;
+        let window = WindowBuilder::new()
+            .with_title("Demo Engine / Mirror")
+            .with_inner_size(winit::dpi::LogicalSize::new(width, height))
+            .build(event_loop)?;
+        self.mirror_window = Some(self.graphics.read().open_secondary_window(window)?);
+        Ok(())
+    }
+
+    /// 別のウィンドウのイベントを処理する。入力はメインのウィンドウでしか受けない。<br />
+    /// Handle an event of another window. Input is only taken from the main window.
+    pub fn handle_secondary_window_event(&mut self, id: WindowId, event: &WindowEvent) {
+        let graphics = self.graphics.read();
+        if !graphics.has_secondary_window(id) {
+            return;
+        }
+        match event {
+            WindowEvent::CloseRequested => {
+                graphics.close_secondary_window(id);
+                if self.mirror_window == Some(id) {
+                    self.mirror_window = None;
+                }
+            }
+            WindowEvent::Resized(_) => graphics.resize_secondary_window(id),
+            _ => (),
+        }
+    }
+
+    /// 設定されたフレームレートの上限から、1フレームに掛ける最短の時間を取得する。<br />
+    /// Get the minimum time spent on a frame from the configured frame rate cap.
+    pub fn get_frame_time_limit(&self) -> Option<std::time::Duration> {
+        if self.is_minimized() {
+            return Some(MINIMIZED_FRAME_TIME);
+        }
+        self.graphics
+            .read()
+            .get_graphics_settings()
+            .get_frame_time_limit()
+    }
+
     pub fn render(&mut self, delta_time: f64) -> anyhow::Result<()> {
-        if self.is_terminating {
+        if self.is_terminating || self.is_minimized() {
             return Ok(());
         }
-        self.scene_manager.render(delta_time)?;
+        if let Err(e) = self.scene_manager.render(delta_time) {
+            match GraphicsError::find(&e) {
+                Some(GraphicsError::SwapchainOutOfDate) => {
+                    let PhysicalSize { width, height } = self.window.borrow().inner_size();
+                    self.recreate_swapchain(width, height)?;
+                }
+                Some(error) if error.is_device_lost() => {
+                    self.write_crash_report(&e);
+                    log::error!("{} Recreating the device.", error);
+                    self.is_device_lost = true;
+                }
+                Some(_) => {
+                    self.write_crash_report(&e);
+                    return Err(e);
+                }
+                None => return Err(e),
+            }
+            return Ok(());
+        }
+        self.profiler
+            .add_timings(self.graphics.read().take_frame_timings());
+        self.profiler.record_allocations(get_allocation_count());
+        self.profiler.end_frame(delta_time);
+        if self.memory_monitor.tick(delta_time) {
+            let stats = {
+                let graphics = self.graphics.read();
+                let stats = graphics.memory_stats()?;
+                graphics.texture_streamer.set_memory_stats(&stats);
+                stats
+            };
+            for warning in self.memory_monitor.set_stats(stats).iter() {
+                log::warn!("{}", warning);
+            }
+        }
+        TextureStreamer::update(&self.graphics);
         Ok(())
     }
 
-    pub fn start_input(&self) {
-        if let Some(ui) = self.ui_system.as_ref() {
-            let mut borrowed = ui.borrow_mut();
-            borrowed.start_input();
+    /// 描画のエラーの診断をクラッシュレポートに書き出す。書き出せなくても、元のエラーの処理は続ける。<br />
+    /// Write diagnostics of a rendering error into a crash report. The original error is handled even if writing fails.
+    fn write_crash_report(&self, error: &anyhow::Error) {
+        let result = self
+            .graphics
+            .read()
+            .write_crash_report(&format!("{:#}", error));
+        match result {
+            Ok(path) => log::error!("Crash report written to {}.", path.display()),
+            Err(e) => log::error!("Failed to write the crash report: {}", e),
         }
     }
 
@@ -202,29 +689,215 @@ impl Game<Graphics, Buffer, CommandBuffer, Image> {
         if self.is_terminating {
             return Ok(());
         }
+        if self.is_device_lost {
+            self.recreate_device().await?;
+            if self.is_terminating {
+                return Ok(());
+            }
+        }
+        let update_start = std::time::Instant::now();
+        {
+            let mut network_system = self.network_system.write().await;
+            network_system.maintain_connection().await;
+            for event in network_system.connection.poll_events() {
+                log::info!("Connection event: {:?}", event);
+            }
+        }
+        // 読み込み中はシーンを更新せず、読み込み画面だけを描画する。
+        // While loading, the scene isn't updated and only the loading screen is drawn.
+        if self.is_loading() {
+            self.update_loading()?;
+            self.profiler.record_cpu("Update", update_start.elapsed());
+            return Ok(());
+        }
         let old_scene = self.current_scene;
         let mut new_scene = self.current_scene;
+        let mut is_returning_to_lobby = false;
+        let is_offline = self.network_system.read().await.is_offline();
         if let Some(ui_system) = self.ui_system.as_ref() {
             let mut borrowed = ui_system.borrow_mut();
             match old_scene {
+                // オフラインではログインと部屋を飛ばして、すぐにゲームを始める。
+                // Offline, login and rooms are skipped and the game starts right away.
+                SceneType::TITLE if is_offline => (),
                 SceneType::TITLE => {
                     let player = borrowed.draw_title_ui(self.network_system.clone()).await?;
                     if let Some(p) = player {
                         log::info!("Successfully logged in as {}.", &p.email);
-                        new_scene = SceneType::GAME;
+                        new_scene = SceneType::LOBBY;
+                    }
+                }
+                SceneType::LOBBY => {
+                    let receiver = borrowed.draw_lobby_ui(self.network_system.clone()).await?;
+                    if receiver.is_some() {
+                        self.room_state_receiver = receiver;
+                    }
+                }
+                SceneType::GAME => {
+                    borrowed.draw_game_ui(self.network_system.clone()).await?;
+                    // F6で補間のデバッグ表示を切り替える。閉じている間は記録もしない。
+                    // F6 toggles the interpolation debug view. Nothing is recorded while it's closed.
+                    let state_queues = self.network_system.read().await.state_queues.clone();
+                    let mut debug_draw = DebugDraw::new();
+                    {
+                        let mut queues = state_queues.lock().await;
+                        queues
+                            .debug_view
+                            .set_enabled(self.is_network_debug_view_open);
+                        queues.debug_view.draw(&mut debug_draw);
+                    }
+                    let (nameplates, scoreboard) = {
+                        let ns = self.network_system.read().await;
+                        let local_player_id = match ns.logged_user.as_ref() {
+                            Some(player) => player.lock().await.player_id.clone(),
+                            None => String::new(),
+                        };
+                        let room_state = ns.room_state.lock().await;
+                        let nameplates = get_remote_nameplates(
+                            &room_state,
+                            &local_player_id,
+                            self.are_nameplates_hidden,
+                        );
+                        (nameplates, get_scoreboard(&room_state))
+                    };
+                    let positions = state_queues.lock().await.get_rendered_positions().clone();
+                    let camera = self.camera.borrow();
+                    let view_projection = camera.get_projection_matrix() * camera.get_view_matrix();
+                    let (width, height) = (camera.width as f32, camera.height as f32);
+                    let eye = camera.position;
+                    drop(camera);
+                    borrowed.draw_debug_shapes(&debug_draw.project(view_projection, width, height));
+                    if self.is_skeleton_view_open {
+                        let poses = self.get_skeleton_poses();
+                        for pose in poses.iter() {
+                            pose.draw(&mut debug_draw);
+                        }
+                        self.graphics
+                            .write()
+                            .add_debug_lines(debug_draw.take_line_vertices());
+                        let labels = poses
+                            .iter()
+                            .flat_map(|pose| pose.joints.iter())
+                            .filter_map(|joint| {
+                                project_point(view_projection, joint.position, width, height)
+                                    .map(|screen_position| (screen_position, joint.name.clone()))
+                            })
+                            .collect::<Vec<_>>();
+                        borrowed.draw_joint_names(&labels);
+                    }
+                    // 位置がまだ届いていないプレイヤーの名札は出さない。遠いものは薄くし、地形などに隠れたものは出さない。
+                    // Nameplates of players whose positions haven't arrived yet aren't shown.
+                    // Distant ones fade out, and those hidden behind terrain or the like aren't shown.
+                    let nameplates = {
+                        let entities = self.entities.borrow();
+                        let physics_system = self.physics_system.borrow();
+                        let replicated = entities
+                            .iter::<NetworkReplicated>()
+                            .map(|(entity, replicated)| (entity, replicated.clone()))
+                            .collect::<Vec<_>>();
+                        nameplates
+                            .into_iter()
+                            .filter_map(|nameplate| {
+                                let position = *positions.get(&nameplate.player_id)?
+                                    + Vec3A::new(0.0, NAMEPLATE_HEIGHT, 0.0);
+                                let alpha = get_distance_alpha((position - eye).length());
+                                if alpha <= 0.0 {
+                                    return None;
+                                }
+                                let ignored = replicated
+                                    .iter()
+                                    .filter(|(_, r)| {
+                                        r.is_local || r.player_id == nameplate.player_id
+                                    })
+                                    .map(|(entity, _)| *entity)
+                                    .collect::<Vec<_>>();
+                                if entities.is_line_blocked(eye, position, &ignored)
+                                    || physics_system.is_line_blocked_by_terrain(eye, position)
+                                {
+                                    return None;
+                                }
+                                project_point(view_projection, position, width, height)
+                                    .map(|screen_position| (screen_position, alpha, nameplate))
+                            })
+                            .collect::<Vec<_>>()
+                    };
+                    borrowed.draw_nameplates(&nameplates);
+                    borrowed.draw_fade(self.timeline_system.borrow().get_fade());
+                    borrowed.draw_scoreboard(&scoreboard);
+                    if let Some(results) = self.match_results.as_ref() {
+                        is_returning_to_lobby = borrowed.draw_match_results(results);
+                    }
+                }
+                SceneType::PREVIEW => {
+                    if let Some(preview) = self.preview.as_ref() {
+                        borrowed.draw_preview_panel(&mut preview.borrow_mut());
                     }
                 }
-                SceneType::GAME => borrowed.draw_game_ui(self.network_system.clone()).await?,
                 _ => (),
             }
+            borrowed
+                .draw_connection_ui(self.network_system.clone())
+                .await;
+            borrowed.draw_settings_panel(&mut self.tweaks);
+            borrowed.draw_profiler_panel(&self.profiler, self.memory_monitor.get_stats());
+            let asset_warnings = self.resource_manager.read().asset_warnings.clone();
+            borrowed.draw_asset_warnings(asset_warnings.lock().get_warnings());
+            if let Some(changes) = self.safe_mode_changes.as_ref() {
+                borrowed.draw_safe_mode_dialog(changes);
+            }
+            #[cfg(debug_assertions)]
+            {
+                borrowed.draw_tweak_panel(&mut self.tweaks);
+                let entries = {
+                    let entities = self.entities.borrow();
+                    let animators = entities
+                        .iter::<Animator>()
+                        .map(|(entity, animator)| {
+                            (
+                                entities.get_name(entity).unwrap_or_default().to_string(),
+                                animator.state_machine.get_debug_info(),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    borrowed.draw_animation_panel(&animators);
+                    self.get_inspector_entries(&entities)
+                };
+                let edits = borrowed.draw_inspector_panel(&mut self.inspector, &entries);
+                self.apply_inspector_edits(edits);
+            }
+            borrowed.draw_console(&mut self.console);
+        }
+        self.execute_console_commands();
+        self.update_preview(delta_time).await?;
+        self.update_presence().await;
+        if is_returning_to_lobby {
+            self.return_to_lobby().await?;
+            self.profiler.record_cpu("Update", update_start.elapsed());
+            return Ok(());
+        }
+        self.update_match().await;
+
+        if !self.tweaks.take_changed().is_empty() {
+            self.apply_tweaks();
+            // MSAAのサンプル数、プレゼントモードや描画解像度の倍率を変えた時はスワップチェーンを作り直す。
+            // The swapchain is recreated when the MSAA sample count, the present mode or the render scale has changed.
+            if self.graphics.read().needs_recreation() {
+                let PhysicalSize { width, height } = self.window.borrow().inner_size();
+                self.recreate_swapchain(width, height)?;
+            }
         }
 
         let load_game = if let Some(recv) = self.room_state_receiver.as_ref() {
             recv.try_recv().is_ok()
         } else {
-            false
+            is_offline && old_scene == SceneType::TITLE
         };
         if load_game {
+            // 地形はゲームシーンに生成するので、先にシーンを切り替える。
+            // The terrain is generated in the game scene, so switch to it first.
+            self.room_state_receiver = None;
+            self.switch_scene(SceneType::GAME).await?;
+            new_scene = SceneType::GAME;
             let is_owner = {
                 let ns = self.network_system.read().await;
                 if let Some(player) = ns.logged_user.as_ref() {
@@ -241,88 +914,758 @@ impl Game<Graphics, Buffer, CommandBuffer, Image> {
             {
                 let mut ns = self.network_system.write().await;
                 if is_owner {
-                    let primitive = self.scene_manager.generate_terrain(-0.5, -0.5, None)?;
+                    let primitive = self.scene_manager.generate_terrain(
+                        TERRAIN_GRID.0,
+                        TERRAIN_GRID.1,
+                        None,
+                    )?;
+                    self.last_terrain = Some(primitive.clone());
+                    self.terrain_baseline = Some(primitive.clone());
                     ns.start_game(primitive).await?;
                 } else {
-                    self.scene_manager.generate_terrain(
-                        -0.5,
-                        -0.5,
+                    let primitive = self.scene_manager.generate_terrain(
+                        TERRAIN_GRID.0,
+                        TERRAIN_GRID.1,
                         Some(ns.get_terrain().await?),
                     )?;
+                    self.last_terrain = Some(primitive.clone());
+                    self.terrain_baseline = Some(primitive);
                 }
                 ns.progress_game().await?;
             }
-            self.load_content().await?;
+            self.begin_loading().await?;
         }
 
         if old_scene != new_scene {
-            let receiver = match new_scene {
-                SceneType::GAME => {
-                    let mut network_system = self.network_system.write().await;
-                    let rooms = network_system.get_rooms().await?;
-                    if rooms.is_empty() {
-                        let room_id = get_random_string(7);
-                        Some(
-                            network_system
-                                .register_player(room_id, "Test Room".into(), true)
-                                .await?,
-                        )
-                    } else {
-                        let available_rooms = rooms
-                            .iter()
-                            .filter(|r| !r.started && r.current_players < r.max_players)
-                            .collect::<Vec<_>>();
-                        let randomly_selected_room = {
-                            let mut rng = rand::thread_rng();
-                            available_rooms.iter().choose(&mut rng)
-                        };
+            if new_scene == SceneType::LOBBY {
+                let mut network_system = self.network_system.write().await;
+                if let Err(e) = network_system.start_chat().await {
+                    log::warn!("Failed to start chat: {}", e);
+                }
+            }
+            if self.current_scene != new_scene {
+                self.switch_scene(new_scene).await?;
+            }
+        }
 
-                        if let Some(room) = randomly_selected_room {
-                            Some(
-                                network_system
-                                    .register_player(
-                                        room.room_id.clone(),
-                                        room.room_name.clone(),
-                                        false,
-                                    )
-                                    .await?,
-                            )
-                        } else {
-                            let room_id = get_random_string(7);
-                            Some(
-                                network_system
-                                    .register_player(room_id, "Test Room".into(), true)
-                                    .await?,
-                            )
-                        }
+        // 読み込みを始めたばかりのシーンは、タスクが終わるまで動かさない。
+        // A scene that just started loading doesn't run until its tasks finish.
+        if self.is_loading() {
+            self.profiler.record_cpu("Update", update_start.elapsed());
+            return Ok(());
+        }
+
+        // 他のプレイヤーが持つエンティティの代理を、シミュレーションを進める前に揃える。
+        // Bring the proxies of entities owned by other players up to date before advancing the simulation.
+        if self.current_scene == SceneType::GAME {
+            let replication = self.network_system.read().await.replication.clone();
+            let events = replication.lock().await.take_events();
+            if !events.is_empty() && self.scene_manager.apply_replication(events)? {
+                self.begin_loading_added_models();
+                self.profiler.record_cpu("Update", update_start.elapsed());
+                return Ok(());
+            }
+        }
+
+        // シミュレーションは固定の間隔で進め、描画はその間を補間する。
+        // The simulation advances at a fixed interval, and rendering interpolates in between.
+        let steps = self.fixed_timestep.advance(delta_time);
+        let timestep = self.fixed_timestep.get_timestep();
+        for _ in 0..steps {
+            if self.current_scene == SceneType::GAME {
+                let mut entities = self.entities.borrow_mut();
+                entities.begin_simulation_step();
+                self.physics_system.borrow_mut().step(&mut *entities);
+                // 物理演算の後に動かし、カットシーンのトランスフォームが上書きされないようにする。
+                // Move after physics so the transforms of the cutscene aren't overwritten.
+                self.timeline_system.borrow_mut().update(
+                    timestep,
+                    &mut *self.camera.borrow_mut(),
+                    &mut *entities,
+                );
+            }
+            self.scene_manager.fixed_update(timestep).await?;
+        }
+        self.entities
+            .borrow_mut()
+            .set_interpolation_alpha(self.fixed_timestep.get_alpha());
+
+        // 時間はゲームの中でだけ進めるが、光と空は毎フレーム書き込み、コンソールで変えた時間もすぐに映す。
+        // Time only advances in the game, but the light and the sky are written every frame so times changed from the console show right away.
+        if self.current_scene == SceneType::GAME {
+            self.time_of_day.advance(delta_time as f32);
+        }
+        {
+            let mut graphics_lock = self.graphics.write();
+            let (light_x, light_z) = graphics_lock.get_light_position();
+            let daylight = self.time_of_day.get_daylight(light_x, light_z);
+            graphics_lock.set_daylight(daylight);
+        }
+
+        self.scene_manager.update(delta_time).await?;
+        self.audio_system
+            .borrow_mut()
+            .update(&*self.camera.borrow());
+        self.profiler.record_cpu("Update", update_start.elapsed());
+        Ok(())
+    }
+
+    /// プレビューしている素材のファイルが変わったか、読み込み直すよう頼まれたら、シーンを片付けて読み込み直す。<br />
+    /// 読めなくなったファイルでは今のモデルを残し、パネルにエラーを表示する。<br />
+    /// Tear down and reload the scene when the previewed asset's files change or a reload is requested.<br />
+    /// If the file can no longer be read, the current model is kept and the panel shows the error.
+    async fn update_preview(&mut self, delta_time: f64) -> anyhow::Result<()> {
+        let preview = match self.preview.as_ref() {
+            Some(preview) => preview.clone(),
+            None => return Ok(()),
+        };
+        if self.is_loading() || !preview.borrow_mut().take_reload(delta_time as f32) {
+            return Ok(());
+        }
+        if !preview.borrow_mut().refresh_info() {
+            log::warn!(
+                "Failed to reload {}: {}",
+                &preview.borrow().path,
+                preview.borrow().error.as_deref().unwrap_or_default()
+            );
+            return Ok(());
+        }
+        log::info!("Reloading {}.", &preview.borrow().path);
+        self.scene_manager
+            .unload_scene(self.scene_manager.current_index)?;
+        self.begin_loading().await
+    }
+
+    /// 今の活動を知らせ、ロビーにいる間はフレンドから受け取った部屋に参加する。<br />
+    /// Report the current activity, and join the room received from a friend while in the lobby.
+    async fn update_presence(&mut self) {
+        let activity = {
+            let ns = self.network_system.read().await;
+            let room = ns.room_state.lock().await;
+            match self.current_scene {
+                SceneType::GAME => PresenceActivity::InMatch {
+                    room_id: room.room_id.clone(),
+                    map: room.room_name.clone(),
+                    players: room.current_players,
+                    max_players: room.max_players,
+                },
+                SceneType::LOBBY if self.room_state_receiver.is_some() => {
+                    PresenceActivity::InRoom {
+                        room_id: room.room_id.clone(),
+                        room_name: room.room_name.clone(),
+                        players: room.current_players,
+                        max_players: room.max_players,
+                    }
+                }
+                SceneType::LOBBY => PresenceActivity::InLobby,
+                _ => PresenceActivity::InMenu,
+            }
+        };
+        self.presence_system.update(activity);
+
+        if self.current_scene != SceneType::LOBBY || self.room_state_receiver.is_some() {
+            return;
+        }
+        if let Some(room_id) = self.presence_system.take_join_request() {
+            match self.join_room(&room_id).await {
+                Ok(receiver) => self.room_state_receiver = Some(receiver),
+                Err(e) => log::warn!("Failed to join room {}: {}", room_id, e),
+            }
+        }
+    }
+
+    /// 参加ボタンと同じように、始まった部屋や満員の部屋には入らない。<br />
+    /// Like the join button, rooms that have started or are full aren't entered.
+    async fn join_room(
+        &mut self,
+        room_id: &str,
+    ) -> anyhow::Result<crossbeam::channel::Receiver<bool>> {
+        let mut ns = self.network_system.write().await;
+        let room = ns
+            .get_rooms()
+            .await?
+            .into_iter()
+            .find(|room| room.room_id == room_id)
+            .ok_or_else(|| anyhow::anyhow!("The room doesn't exist."))?;
+        if room.started || room.current_players >= room.max_players {
+            return Err(anyhow::anyhow!("The room has started or is full."));
+        }
+        ns.register_player(room.room_id, room.room_name, false)
+            .await
+    }
+
+    /// 現在のシーンのエンティティの値をインスペクターのために集める。<br />
+    /// Gather the values of the current scene's entities for the inspector.
+    #[cfg(debug_assertions)]
+    fn get_inspector_entries(
+        &self,
+        entities: &World<Graphics, Buffer, CommandBuffer, Image>,
+    ) -> Vec<InspectorEntry> {
+        self.scene_manager
+            .get_entities()
+            .into_iter()
+            .filter_map(|entity| {
+                let renderable = entities
+                    .get::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity)?;
+                let renderable_lock = renderable.0.lock();
+                let position_info = match entities.get::<Transform>(entity) {
+                    Some(transform) => PositionInfo::from(*transform),
+                    None => renderable_lock.get_position_info(),
+                };
+                let entry = InspectorEntry::new(
+                    entity,
+                    entities.get_name(entity).unwrap_or_default(),
+                    position_info,
+                    &renderable_lock.get_model_metadata(),
+                );
+                Some(match entities.get::<Animator>(entity) {
+                    Some(animator) => entry.with_animation(
+                        animator.state_machine.get_state_names(),
+                        animator.state_machine.get_current_state(),
+                    ),
+                    None => entry,
+                })
+            })
+            .collect()
+    }
+
+    /// インスペクターで変えた値をエンティティに書き込む。モデルの値は次の更新でSSBOに送られる。<br />
+    /// Write the values changed in the inspector into the entities. Model values reach the SSBO on the next update.
+    #[cfg(debug_assertions)]
+    fn apply_inspector_edits(&self, edits: Vec<(DefaultKey, InspectorEdit)>) {
+        let mut entities = self.entities.borrow_mut();
+        for (entity, edit) in edits.into_iter() {
+            match edit {
+                InspectorEdit::PositionInfo(position_info) => {
+                    // トランスフォームは毎フレームモデルに書き込まれるので、あればそちらを変える。
+                    // Transforms are written into models every frame, so change the transform if there is one.
+                    if let Some(transform) = entities.get_mut::<Transform>(entity) {
+                        *transform = Transform::from(position_info);
+                    } else if let Some(renderable) =
+                        entities
+                            .get::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity)
+                    {
+                        renderable.0.lock().set_position_info(position_info);
                     }
                 }
-                _ => None,
+                InspectorEdit::Material {
+                    object_color,
+                    reflectivity,
+                    shine_damper,
+                } => {
+                    if let Some(renderable) =
+                        entities
+                            .get::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity)
+                    {
+                        let mut renderable_lock = renderable.0.lock();
+                        let mut metadata = renderable_lock.get_model_metadata();
+                        metadata.object_color = object_color;
+                        metadata.reflectivity = reflectivity;
+                        metadata.shine_damper = shine_damper;
+                        renderable_lock.set_model_metadata(metadata);
+                    }
+                }
+                InspectorEdit::AnimationState(state) => {
+                    if let Some(animator) = entities.get_mut::<Animator>(entity) {
+                        animator.state_machine.play_state(&state);
+                    }
+                }
+            }
+        }
+    }
+
+    /// ゲームと全てのシーンのコマンドをコンソールに登録する。<br />
+    /// Register the commands of the game and every scene to the console.
+    fn register_commands(&mut self) {
+        self.console.register(
+            "set",
+            "<variable> <value>",
+            "Set light_x, light_z or a tweak by name.",
+        );
+        self.console.register(
+            "skeletons",
+            "",
+            "Toggle drawing the skeletons and joint names of skinned models.",
+        );
+        self.console.register(
+            "time",
+            "[<hours> | pause | resume | length <seconds>]",
+            "Show or change the time of day.",
+        );
+        self.console.register("quit", "", "Quit the game.");
+        self.scene_manager.register_commands(&mut self.console);
+    }
+
+    /// コンソールに入力されたコマンドを実行し、結果を表示する。<br />
+    /// Execute the commands typed into the console and print the results.
+    fn execute_console_commands(&mut self) {
+        for command in self.console.take_submitted().into_iter() {
+            let result = match command.name.as_str() {
+                "quit" => {
+                    self.is_terminating = true;
+                    Ok(ConsoleReply::new("Quitting."))
+                }
+                "set" => self.set_console_variable(&command),
+                "skeletons" => Ok(self.toggle_skeleton_view()),
+                "time" => self.execute_time_command(&command),
+                name => self
+                    .scene_manager
+                    .execute_command(&command)
+                    .unwrap_or_else(|| {
+                        Err(anyhow::anyhow!("{} isn't available in this scene.", name))
+                    }),
             };
+            match result {
+                Ok(reply) => {
+                    self.console.print(&reply.message);
+                    // 追加したモデルは読み込み画面を通して登録し、描画のリソースを作り直す。
+                    // Added models are registered through the loading screen, which recreates the rendering resources.
+                    if reply.needs_loading {
+                        self.begin_loading_added_models();
+                    }
+                }
+                Err(e) => self.console.print(&format!("Error: {}", e)),
+            }
+        }
+    }
 
-            self.room_state_receiver = receiver;
-            self.switch_scene(new_scene).await?;
+    /// 一日の時間を表示するか、時間、止めるかどうか、一日の長さを変える。<br />
+    /// Show the time of day, or change the time, whether it's paused, or the length of a day.
+    fn execute_time_command(&mut self, command: &ConsoleCommand) -> anyhow::Result<ConsoleReply> {
+        match command.args.first().map(|arg| arg.to_lowercase()).as_deref() {
+            None => (),
+            Some("pause") => self.time_of_day.set_paused(true),
+            Some("resume") => self.time_of_day.set_paused(false),
+            Some("length") => {
+                let day_length = command.get_f32(1)?;
+                if !day_length.is_finite() || day_length <= 0.0 {
+                    return Err(anyhow::anyhow!("The length of a day must be greater than 0."));
+                }
+                self.time_of_day.set_day_length(day_length);
+            }
+            Some(_) => self.time_of_day.set_hours(command.get_f32(0)?),
         }
+        let hours = self.time_of_day.get_hours();
+        Ok(ConsoleReply::new(&format!(
+            "{:02}:{:02}, {} seconds a day{}",
+            hours.floor() as u32,
+            (hours.fract() * 60.0).floor() as u32,
+            self.time_of_day.get_day_length(),
+            if self.time_of_day.is_paused() {
+                ", paused"
+            } else {
+                ""
+            }
+        )))
+    }
 
-        self.scene_manager.update(delta_time).await?;
-        Ok(())
+    /// 骨格の表示を切り替える。閉じる時は、モデルが姿勢を記録するのもやめさせる。<br />
+    /// Toggle the skeleton view. When it's closed, models also stop recording their poses.
+    fn toggle_skeleton_view(&mut self) -> ConsoleReply {
+        self.is_skeleton_view_open = !self.is_skeleton_view_open;
+        if self.is_skeleton_view_open {
+            return ConsoleReply::new("Skeletons shown.");
+        }
+        let entities = self.entities.borrow();
+        for (_, renderable) in
+            entities.iter::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>()
+        {
+            renderable.0.lock().set_skeleton_recording(false);
+        }
+        ConsoleReply::new("Skeletons hidden.")
+    }
+
+    /// 骨付きモデルが最後の更新で記録した骨格の姿勢を集める。まだ記録していないモデルには記録させる。<br />
+    /// Gather the skeleton poses skinned models recorded on their last update, making models that aren't recording yet start.
+    fn get_skeleton_poses(&self) -> Vec<SkeletonPose> {
+        let entities = self.entities.borrow();
+        entities
+            .iter::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>()
+            .flat_map(|(_, renderable)| {
+                let mut renderable_lock = renderable.0.lock();
+                renderable_lock.set_skeleton_recording(true);
+                renderable_lock.get_skeleton_poses()
+            })
+            .collect()
+    }
+
+    /// 平行光源の位置か、名前の合う調整パラメーターを変える。<br />
+    /// Change the position of the directional light, or the tweak parameter matching the name.
+    fn set_console_variable(&mut self, command: &ConsoleCommand) -> anyhow::Result<ConsoleReply> {
+        let name = command.get_arg(0)?.to_lowercase();
+        let value = command.get_arg(1)?;
+        match name.as_str() {
+            "light_x" | "light_z" => {
+                let value = command.get_f32(1)?;
+                let mut graphics_lock = self.graphics.write();
+                let (mut light_x, mut light_z) = graphics_lock.get_light_position();
+                if name == "light_x" {
+                    light_x = value;
+                } else {
+                    light_z = value;
+                }
+                graphics_lock.set_light_position(light_x, light_z);
+                Ok(ConsoleReply::new(&format!("{} = {}", name, value)))
+            }
+            _ => {
+                let parameter = self.tweaks.set_from_str(&name, value)?;
+                Ok(ConsoleReply::new(&format!("{} = {}", parameter, value)))
+            }
+        }
+    }
+
+    /// 保存した設定を読み込んでから、各システムのパラメーターを調整パネルに登録する。セーフモードでは読み込まない。<br />
+    /// Register the parameters of each system to the tweak panel after loading the saved config, which isn't loaded in safe mode.
+    fn register_tweaks(&mut self) {
+        if self.safe_mode_changes.is_none() && std::path::Path::new(TWEAK_CONFIG_PATH).exists() {
+            if let Err(e) = self.tweaks.load(TWEAK_CONFIG_PATH) {
+                log::warn!("Failed to load tweaks from {}: {}", TWEAK_CONFIG_PATH, e);
+            }
+        }
+        self.camera.borrow_mut().register_tweaks(&mut self.tweaks);
+        self.physics_system
+            .borrow_mut()
+            .register_tweaks(&mut self.tweaks);
+        self.audio_system
+            .borrow_mut()
+            .register_tweaks(&mut self.tweaks);
+        self.graphics.write().register_tweaks(&mut self.tweaks);
+        self.are_nameplates_hidden = self.tweaks.register_bool(HIDE_NAMEPLATES_TWEAK, false);
+        self.tweaks.take_changed();
+    }
+
+    fn apply_tweaks(&mut self) {
+        self.camera.borrow_mut().apply_tweaks(&self.tweaks);
+        self.physics_system.borrow_mut().apply_tweaks(&self.tweaks);
+        self.audio_system.borrow_mut().apply_tweaks(&self.tweaks);
+        self.graphics.write().apply_tweaks(&self.tweaks);
+        if let Some(is_hidden) = self.tweaks.get_bool(HIDE_NAMEPLATES_TWEAK) {
+            self.are_nameplates_hidden = is_hidden;
+        }
+        self.apply_window_mode();
+    }
+
+    /// 設定のウィンドウモードをウィンドウに反映する。大きさが変わるので、スワップチェーンはリサイズのイベントで作り直される。<br />
+    /// Apply the window mode of the settings to the window. The size changes, so the swapchain is recreated by the resize event.
+    fn apply_window_mode(&mut self) {
+        let window_mode = self.graphics.read().get_graphics_settings().window_mode;
+        if window_mode == self.window_mode {
+            return;
+        }
+        let window = self.window.borrow();
+        let monitor = window.current_monitor();
+        let fullscreen = match window_mode {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            WindowMode::Exclusive => {
+                // モニターが対応している一番高い解像度とリフレッシュレートを使う。
+                // Use the highest resolution and refresh rate the monitor supports.
+                let video_mode = monitor.as_ref().and_then(|monitor| {
+                    monitor.video_modes().max_by_key(|video_mode| {
+                        let size = video_mode.size();
+                        (size.width * size.height, video_mode.refresh_rate())
+                    })
+                });
+                match video_mode {
+                    Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                    None => {
+                        log::warn!("No video mode is available. Using borderless fullscreen.");
+                        Some(Fullscreen::Borderless(monitor))
+                    }
+                }
+            }
+        };
+        window.set_fullscreen(fullscreen);
+        self.window_mode = window_mode;
+        if window_mode.is_fullscreen() {
+            self.fullscreen_mode = window_mode;
+        }
     }
 
     async fn switch_scene(&mut self, scene_type: SceneType) -> anyhow::Result<()> {
+        let previous_index = self.scenes.get(&self.current_scene).copied();
         self.current_scene = scene_type;
-        let scene_index = self
+        let scene_index = *self
             .scenes
             .get(&scene_type)
             .expect("Failed to get scene index.");
-        self.scene_manager.switch_scene(*scene_index);
+        // ロビーはタイトルシーンを背景として使い回すので、読み込み直さない。
+        // The lobby reuses the title scene as its backdrop, so it isn't reloaded.
+        if previous_index == Some(scene_index) {
+            return Ok(());
+        }
+        // 前のシーンの読み込みは取り消されるので、その進み具合も捨てる。
+        // The previous scene's loading is cancelled, so its progress is discarded too.
+        self.scene_manager.switch_scene(scene_index)?;
+        self.loading_progress = None;
         if scene_type != SceneType::GAME {
-            self.load_content().await
+            self.begin_loading().await
         } else {
             Ok(())
         }
     }
 }
 
+impl<GraphicsType, BufferType, CommandType, TextureType>
+    Game<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    pub fn end_input(&self) {
+        if let Some(ui) = self.ui_system.as_ref() {
+            ui.borrow_mut().end_input();
+        }
+    }
+
+    pub fn input_button(&self, button: MouseButton, x: f64, y: f64, element_state: ElementState) {
+        if let Some(ui) = self.ui_system.as_ref() {
+            ui.borrow_mut().input_button(button, x, y, element_state);
+        }
+        // プレビューでは右ボタンのドラッグでカメラを回す。
+        // In the preview, dragging with the right button orbits the camera.
+        if let Some(preview) = self.preview.as_ref() {
+            if button == MouseButton::Right {
+                let mut preview_lock = preview.borrow_mut();
+                match element_state {
+                    ElementState::Pressed => preview_lock.orbit.begin_drag(x as f32, y as f32),
+                    ElementState::Released => preview_lock.orbit.end_drag(),
+                }
+            }
+        }
+        if self.is_loading() {
+            return;
+        }
+        self.scene_manager.input_button(button, x, y, element_state);
+    }
+
+    pub async fn input_key(&mut self, key: VirtualKeyCode, element_state: ElementState) {
+        let mut is_console_open = false;
+        if let Some(ui) = self.ui_system.as_ref() {
+            let mut borrowed = ui.borrow_mut();
+            if element_state == ElementState::Pressed {
+                match key {
+                    VirtualKeyCode::F3 => borrowed.toggle_settings_panel(),
+                    VirtualKeyCode::F4 => borrowed.toggle_profiler_panel(),
+                    VirtualKeyCode::F6 => {
+                        self.is_network_debug_view_open = !self.is_network_debug_view_open
+                    }
+                    // F10でネットワークの統計のオーバーレイを開閉する。
+                    // F10 opens and closes the network statistics overlay.
+                    VirtualKeyCode::F10 => borrowed.toggle_network_panel(),
+                    VirtualKeyCode::Grave => borrowed.toggle_console_panel(),
+                    _ => (),
+                }
+            }
+            #[cfg(debug_assertions)]
+            {
+                if element_state == ElementState::Pressed {
+                    match key {
+                        VirtualKeyCode::F1 => borrowed.toggle_tweak_panel(),
+                        VirtualKeyCode::F2 => borrowed.toggle_animation_panel(),
+                        VirtualKeyCode::F5 => borrowed.toggle_inspector_panel(),
+                        _ => (),
+                    }
+                }
+            }
+            is_console_open = borrowed.is_console_open();
+            borrowed.input_key(key, element_state);
+        }
+        // コンソールを開いている間、キーはシーンに渡さない。
+        // While the console is open, keys aren't passed to the scene.
+        if is_console_open {
+            if element_state == ElementState::Pressed {
+                match key {
+                    VirtualKeyCode::Return => self.console.submit(),
+                    VirtualKeyCode::Tab => self.console.complete(),
+                    VirtualKeyCode::Up => self.console.previous_history(),
+                    VirtualKeyCode::Down => self.console.next_history(),
+                    _ => (),
+                }
+            }
+            return;
+        }
+        // Tabを押している間だけ得点表を出す。
+        // The scoreboard is shown only while Tab is held.
+        if key == VirtualKeyCode::Tab && self.current_scene == SceneType::GAME {
+            if let Some(ui) = self.ui_system.as_ref() {
+                ui.borrow_mut()
+                    .set_scoreboard_visible(element_state == ElementState::Pressed);
+            }
+            return;
+        }
+        if self.is_loading() {
+            return;
+        }
+        if element_state == ElementState::Pressed {
+            match key {
+                QUICK_SAVE_KEY => return self.quick_save().await,
+                QUICK_LOAD_KEY => return self.quick_load().await,
+                _ => (),
+            }
+        }
+        self.scene_manager.input_key(key, element_state).await;
+    }
+
+    pub fn input_motion(&self, x: f64, y: f64) {
+        if let Some(ui) = self.ui_system.as_ref() {
+            ui.borrow_mut().input_motion(x, y);
+        }
+        if let Some(preview) = self.preview.as_ref() {
+            preview.borrow_mut().orbit.drag(x as f32, y as f32);
+        }
+    }
+
+    pub fn input_scroll(&self, mouse_scroll_delta: MouseScrollDelta) {
+        if let Some(ui) = self.ui_system.as_ref() {
+            ui.borrow_mut().input_scroll(mouse_scroll_delta);
+        }
+        if let Some(preview) = self.preview.as_ref() {
+            let steps = match mouse_scroll_delta {
+                MouseScrollDelta::LineDelta(_, y) => y,
+                MouseScrollDelta::PixelDelta(position) => {
+                    (position.y / PIXELS_PER_SCROLL_STEP) as f32
+                }
+            };
+            preview.borrow_mut().orbit.zoom(steps);
+        }
+    }
+
+    pub fn input_unicode(&self, c: char) {
+        if let Some(ui) = self.ui_system.as_ref() {
+            ui.borrow_mut().input_unicode(c);
+        }
+    }
+
+    /// シーンがまだ読み込み中かどうか。<br />
+    /// Whether a scene is still loading.
+    pub fn is_loading(&self) -> bool {
+        self.loading_progress.is_some()
+    }
+
+    /// ウィンドウが最小化されているかどうか。最小化したウィンドウの大きさは0になり、スワップチェーンを作れない。<br />
+    /// Whether the window is minimized. A minimized window has a size of 0, so no swapchain can be created.
+    pub fn is_minimized(&self) -> bool {
+        let PhysicalSize { width, height } = self.window.borrow().inner_size();
+        width == 0 || height == 0
+    }
+
+    /// 今のシーンの状態をクイックセーブのファイルに書き込む。<br />
+    /// Write the state of the current scene to the quick-save file.
+    pub async fn quick_save(&mut self) {
+        let result = match self.scene_manager.save_state().await {
+            Ok(mut save) => self
+                .get_terrain_patches()
+                .and_then(|patches| save.set_terrain_patches(&patches))
+                .and_then(|_| save.save(QUICK_SAVE_PATH)),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => {
+                log::info!("Quick-saved to {}.", QUICK_SAVE_PATH);
+                self.console
+                    .print(&format!("Quick-saved to {}.", QUICK_SAVE_PATH));
+            }
+            Err(e) => {
+                log::error!("Failed to quick-save: {:?}", e);
+                self.console.print(&format!("Error: {}", e));
+            }
+        }
+    }
+
+    /// クイックセーブのファイルを今のシーンに読み込む。<br />
+    /// Load the quick-save file into the current scene.
+    pub async fn quick_load(&mut self) {
+        let result = match SaveGame::load(QUICK_SAVE_PATH) {
+            Ok(save) => match self.scene_manager.load_state(&save).await {
+                Ok(_) => self.load_terrain_patches(&save).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => {
+                log::info!("Quick-loaded from {}.", QUICK_SAVE_PATH);
+                self.console
+                    .print(&format!("Quick-loaded from {}.", QUICK_SAVE_PATH));
+            }
+            Err(e) => {
+                log::error!("Failed to quick-load: {:?}", e);
+                self.console.print(&format!("Error: {:#}", e));
+            }
+        }
+    }
+
+    pub fn start_input(&self) {
+        if let Some(ui) = self.ui_system.as_ref() {
+            let mut borrowed = ui.borrow_mut();
+            borrowed.start_input();
+        }
+    }
+
+    /// 変更前の地形から今の地形への差分。試合の地形が無ければ空。<br />
+    /// Difference from the terrain before modification to the current one. Empty without a match terrain.
+    fn get_terrain_patches(&self) -> anyhow::Result<Vec<TerrainPatch>> {
+        match (self.terrain_baseline.as_ref(), self.last_terrain.as_ref()) {
+            (Some(baseline), Some(terrain)) => {
+                let patch = TerrainPatch::diff(
+                    TERRAIN_GRID.0 as i32,
+                    TERRAIN_GRID.1 as i32,
+                    baseline,
+                    terrain,
+                )?;
+                Ok(patch.into_iter().collect())
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// セーブデータの地形のパッチを変更前の地形に当て、今の地形と違えば作り直す。<br />
+    /// 地形はホストが決めるので、ホストでなければ何もしない。作り直した地形は途中から参加するプレイヤーにも配る。<br />
+    /// Apply the terrain patches of the save data to the terrain before modification, and recreate the terrain if it differs from the current one.<br />
+    /// The host decides the terrain, so nothing happens for other players. The recreated terrain is handed out to players joining later as well.
+    async fn load_terrain_patches(&mut self, save: &SaveGame) -> anyhow::Result<()> {
+        let mut terrain = match self.terrain_baseline.clone() {
+            Some(baseline) if self.current_scene == SceneType::GAME => baseline,
+            _ => return Ok(()),
+        };
+        let mut ns = self.network_system.write().await;
+        let is_owner = match ns.logged_user.as_ref() {
+            Some(player) => player
+                .lock()
+                .await
+                .state
+                .as_ref()
+                .map(|state| state.is_owner)
+                .unwrap_or(false),
+            None => false,
+        };
+        if !is_owner && !ns.is_offline() {
+            return Ok(());
+        }
+        let patches = save.get_terrain_patches()?;
+        if patches == self.get_terrain_patches()? {
+            return Ok(());
+        }
+        // 試合の地形は一つのチャンクなので、全てのパッチをそれに当てる。
+        // The terrain of a match is a single chunk, so every patch is applied to it.
+        for patch in patches.iter() {
+            patch.apply(&mut terrain)?;
+        }
+        let terrain =
+            self.scene_manager
+                .generate_terrain(TERRAIN_GRID.0, TERRAIN_GRID.1, Some(terrain))?;
+        self.last_terrain = Some(terrain);
+        self.scene_manager.create_ssbo()?;
+        self.scene_manager.get_command_buffers();
+        ns.update_terrain(&patches).await?;
+        log::info!("Recreated the terrain with {} patches.", patches.len());
+        Ok(())
+    }
+}
+
 #[cfg(target_os = "windows")]
 impl Game<DX12::Graphics, DX12::Resource, ComPtr<ID3D12GraphicsCommandList>, DX12::Resource> {
     pub unsafe fn new(
@@ -331,6 +1674,7 @@ impl Game<DX12::Graphics, DX12::Resource, ComPtr<ID3D12GraphicsCommandList>, DX1
         height: f64,
         event_loop: &EventLoop<()>,
         network_system: NetworkSystem,
+        settings: &Settings,
     ) -> Self {
         let window = WindowBuilder::new()
             .with_title(title)
@@ -338,7 +1682,9 @@ impl Game<DX12::Graphics, DX12::Resource, ComPtr<ID3D12GraphicsCommandList>, DX1
             .build(event_loop)
             .expect("Failed to create window.");
         let camera = Rc::new(RefCell::new(Camera::new(width, height)));
-        let resource_manager = Arc::new(RwLock::new(ManuallyDrop::new(ResourceManager::new())));
+        let resource_manager = Arc::new(RwLock::new(ManuallyDrop::new(ResourceManager::new(
+            settings.inflight_buffer_count,
+        ))));
         let graphics =
             DX12::Graphics::new(&window, camera.clone(), Arc::downgrade(&resource_manager));
         Game {
@@ -348,24 +1694,139 @@ impl Game<DX12::Graphics, DX12::Resource, ComPtr<ID3D12GraphicsCommandList>, DX1
             graphics: Arc::new(RwLock::new(ManuallyDrop::new(graphics))),
             scene_manager: SceneManager::new(),
             ui_system: None,
-            entities: Rc::new(RefCell::new(SlotMap::new())),
+            entities: Rc::new(RefCell::new(World::new())),
             network_system: Arc::new(tokio::sync::RwLock::new(network_system)),
+            physics_system: Rc::new(RefCell::new(PhysicsSystem::new())),
+            audio_system: Rc::new(RefCell::new(AudioSystem::new())),
+            timeline_system: Rc::new(RefCell::new(TimelineSystem::new())),
+            presence_system: PresenceSystem::new(settings),
+            profiler: FrameProfiler::new(),
+            memory_monitor: MemoryMonitor::new(),
+            safe_mode_changes: None,
+            loading_progress: None,
+            loading_texture_count: 0,
+            #[cfg(debug_assertions)]
+            inspector: Inspector::new(),
+            console: Console::new(),
+            preview: None,
+            settings: settings.clone(),
+            is_network_debug_view_open: false,
+            is_skeleton_view_open: false,
+            are_nameplates_hidden: false,
+            is_device_lost: false,
+            last_terrain: None,
+            terrain_baseline: None,
+            mirror_window: None,
+            fixed_timestep: FixedTimestep::default(),
+            match_tracker: MatchTracker::new(),
+            match_results: None,
+            time_of_day: TimeOfDay::new(settings.day_length),
             scenes: HashMap::new(),
             current_scene: SceneType::TITLE,
             room_state_receiver: None,
             is_terminating: false,
+            tweaks: TweakRegistry::new(),
+            window_mode: WindowMode::Windowed,
+            fullscreen_mode: WindowMode::Borderless,
         }
     }
 
     pub fn initialize(&mut self) -> bool {
+        self.console.register("quit", "", "Quit the game.");
         true
     }
 
-    pub fn load_content(&mut self) {}
+    pub fn load_content(&mut self) {
+        if self.ui_system.is_none() {
+            let ui_system = Rc::new(RefCell::new(ManuallyDrop::new(UISystem::new(
+                &*self.graphics.read(),
+                &self.settings,
+            ))));
+            self.graphics.write().ui_manager = Some(Rc::downgrade(&ui_system));
+            self.ui_system = Some(ui_system);
+        }
+    }
 
-    pub fn update(&self) {}
+    /// 画面を消してUIを描画する。<br />
+    /// Clear the screen and draw the UI.
+    pub fn render(&mut self, delta_time: f64) -> anyhow::Result<()> {
+        if self.is_terminating || self.is_minimized() {
+            return Ok(());
+        }
+        unsafe {
+            self.graphics.write().render()?;
+        }
+        self.profiler.record_allocations(get_allocation_count());
+        self.profiler.end_frame(delta_time);
+        Ok(())
+    }
+
+    pub fn recreate_swapchain(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        unsafe { self.graphics.write().recreate_swapchain(width, height) }
+    }
+
+    /// ログインとロビーのUIを更新する。<br />
+    /// 試合のシーンはVulkanでしか描画できないので、ロビーから部屋には入れない。<br />
+    /// Update the login and lobby UI.<br />
+    /// Match scenes are only rendered with Vulkan, so rooms can't be entered from the lobby.
+    pub async fn update(&mut self) -> anyhow::Result<()> {
+        if self.is_terminating {
+            return Ok(());
+        }
+        let update_start = std::time::Instant::now();
+        {
+            let mut network_system = self.network_system.write().await;
+            network_system.maintain_connection().await;
+            for event in network_system.connection.poll_events() {
+                log::info!("Connection event: {:?}", event);
+            }
+        }
+        let mut new_scene = self.current_scene;
+        if let Some(ui_system) = self.ui_system.as_ref() {
+            let mut borrowed = ui_system.borrow_mut();
+            match self.current_scene {
+                SceneType::TITLE => {
+                    let player = borrowed.draw_title_ui(self.network_system.clone()).await?;
+                    if let Some(p) = player {
+                        log::info!("Successfully logged in as {}.", &p.email);
+                        new_scene = SceneType::LOBBY;
+                    }
+                }
+                SceneType::LOBBY => {
+                    borrowed.draw_lobby_ui(self.network_system.clone()).await?;
+                }
+                _ => (),
+            }
+            borrowed
+                .draw_connection_ui(self.network_system.clone())
+                .await;
+            borrowed.draw_profiler_panel(&self.profiler, None);
+            borrowed.draw_console(&mut self.console);
+        }
+        for command in self.console.take_submitted().into_iter() {
+            if command.name == "quit" {
+                self.is_terminating = true;
+                self.console.print("Quitting.");
+            } else {
+                self.console.print(&format!(
+                    "Error: {} isn't available with DX12.",
+                    command.name
+                ));
+            }
+        }
 
-    pub fn render(&self) {}
+        if self.current_scene != new_scene {
+            if new_scene == SceneType::LOBBY {
+                let mut network_system = self.network_system.write().await;
+                if let Err(e) = network_system.start_chat().await {
+                    log::warn!("Failed to start chat: {}", e);
+                }
+            }
+            self.current_scene = new_scene;
+        }
+        self.profiler.record_cpu("Update", update_start.elapsed());
+        Ok(())
+    }
 }
 
 impl<GraphicsType, BufferType, CommandType, TextureType> Drop
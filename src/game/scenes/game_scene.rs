@@ -1,29 +1,87 @@
 use ash::vk::CommandBuffer;
 use async_trait::async_trait;
 use crossbeam::sync::ShardedLock;
-use glam::{Vec3A, Vec4};
+use glam::{Vec2, Vec3A, Vec4};
 use parking_lot::RwLock;
-use slotmap::{DefaultKey, Key, SlotMap};
-use std::cell::RefCell;
+use slotmap::{DefaultKey, Key};
+use std::cell::{Cell, RefCell};
 use std::mem::ManuallyDrop;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Weak};
+use std::time::Instant;
 
 use crate::game::enums::ShaderType;
 use crate::game::graphics::vk::{Buffer, Graphics, Image};
 use crate::game::shared::enums::SceneType;
+use crate::game::shared::structs::games::replication::{EntitySnapshot, ReplicationEvent};
 use crate::game::shared::structs::{
-    Counts, GeometricPrimitive, InstanceData, InstancedModel, Model, PositionInfo, Primitive,
-    PrimitiveType, SkinnedModel, Terrain, WaitableTasks,
+    create_kill_cam, get_cache_key, BoundsResponse, CacheLookup, Counts, DebugDraw, Frustum,
+    GeometricPrimitive, InstanceData, InstancedModel, LifeEvent, LoadingProgress, Minimap,
+    MinimapBlip, Model, PositionInfo, Primitive, PrimitiveType, Ray, RenderFeatures,
+    RenderablePool, SaveGame, SavedCamera, SavedProgress, Settings, SkinnedModel, SpriteAtlas,
+    SpriteBatch, StatusBar, StatusTarget, Terrain, WaitableTasks, WaterEvent, WaterVolume, Wave,
+    WorldBounds, MINIMAP_RESOLUTION, MINIMAP_SPRITE, SPRITE_ATLAS_SIZE, STATUS_BAR_HEIGHT,
 };
 use crate::game::shared::traits::{GraphicsBase, Scene};
+use crate::game::shared::util::math::{direction_from_yaw_pitch, quat_from_euler};
 use crate::game::shared::util::HeightGenerator;
 use crate::game::structs::games::WorldMatrixUdp;
 use crate::game::traits::Disposable;
-use crate::game::{Camera, LockableRenderable, NetworkSystem, ResourceManagerWeak};
-use crate::protos::grpc_service::game_state::WorldMatrix;
-use std::collections::HashMap;
-use winit::event::{ElementState, VirtualKeyCode};
+use crate::game::ui::{Console, ConsoleCommand, ConsoleReply};
+use crate::game::{
+    Animator, AudioSystem, Bounds, Buoyancy, Camera, FootstepSystem, LockableRenderable, Mount,
+    MountSystem, MusicDirector, MusicEvent, NetworkEntity, NetworkReplicated, NetworkSystem,
+    ParticleEffect, ParticleEmitter, ParticleSystem, PhysicsSystem, PlayerController,
+    RenderableRef, ResourceManagerWeak, Rider, RigidBody, SpringBoneRig, TimelineSystem, Transform,
+    World, MOUNT_RANGE,
+};
+use crate::protos::grpc_service::game_state::{EntityState, WorldMatrix};
+use rapier3d::dynamics::BodyStatus;
+use std::collections::{HashMap, HashSet};
+use winit::event::{ElementState, MouseButton, VirtualKeyCode};
+
+/// ピッキングの光線を飛ばす最大距離。カメラの遠クリップ面と同じ。<br />
+/// Maximum distance of picking rays, matching the camera's far plane.
+const PICK_DISTANCE: f32 = 1000.0;
+
+/// クリックで移動先を決めた時の効果音。<br />
+/// Sound effect played when a destination is set by clicking.
+const MOVE_SOUND: &str = "move";
+
+/// 地形の層と材質ごとの足音を定義したデータファイル。<br />
+/// Data file defining the terrain layers and the footsteps of each material.
+pub const SURFACE_MATERIALS: &str = "./audio/surface_materials.json";
+
+/// シーンごとの層になったBGMを定義したデータファイル。<br />
+/// Data file defining the layered music of each scene.
+pub const MUSIC_SETS: &str = "./audio/music.json";
+
+/// 各プレイヤーの横に置く乗り物のモデル。<br />
+/// Model of the mount placed beside each player.
+const MOUNT_MODEL: &str = "./models/tank/tank.gltf";
+
+/// 乗り物に乗り降りするキー。<br />
+/// Key to get on and off mounts.
+const MOUNT_KEY: VirtualKeyCode = VirtualKeyCode::F;
+
+/// `gizmos`で描く境界ボックスと浮力を求める点の色。<br />
+/// Colors of the bounding boxes and buoyancy points drawn by `gizmos`.
+const BOUNDS_GIZMO_COLOR: [u8; 4] = [80, 255, 120, 255];
+const BUOYANCY_GIZMO_COLOR: [u8; 4] = [80, 160, 255, 255];
+
+/// ミニマップに描く点の色。<br />
+/// Colors of the blips drawn on the minimap.
+const LOCAL_BLIP_COLOR: [u8; 4] = [255, 220, 60, 255];
+const REMOTE_BLIP_COLOR: [u8; 4] = [230, 60, 60, 255];
+const MOUNT_BLIP_COLOR: [u8; 4] = [150, 170, 190, 255];
+
+/// 複製されたエンティティの境界ボックスの上端から体力バーまでの隙間。<br />
+/// Gap between the top of the bounding box of a replicated entity and its health bar.
+const ENTITY_BAR_MARGIN: f32 = 0.4;
+
+/// コンソールで出すモデルを探すディレクトリ。<br />
+/// Directory searched for models spawned from the console.
+const MODELS_DIRECTORY: &str = "./models";
 
 /// メインゲームシーン<br />
 /// Main game scene
@@ -41,13 +99,65 @@ where
     counts: Counts,
     height_generator: Arc<ShardedLock<HeightGenerator>>,
     scene_type: SceneType,
-    entities: std::rc::Weak<RefCell<SlotMap<DefaultKey, usize>>>,
+    entities: std::rc::Weak<RefCell<World<GraphicsType, BufferType, CommandType, TextureType>>>,
     terrain_entity: DefaultKey,
-    player_entities: HashMap<String, DefaultKey>,
     render_components: Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>,
+    pools:
+        HashMap<&'static str, RenderablePool<GraphicsType, BufferType, CommandType, TextureType>>,
     waitable_tasks: WaitableTasks<GraphicsType, BufferType, CommandType, TextureType>,
     loaded: bool,
+    render_features: RenderFeatures,
     camera: std::rc::Weak<RefCell<Camera>>,
+    physics_system: std::rc::Weak<RefCell<PhysicsSystem>>,
+    selected_entity: Cell<DefaultKey>,
+    audio_system: std::rc::Weak<RefCell<AudioSystem>>,
+    timeline_system: std::rc::Weak<RefCell<TimelineSystem>>,
+    footstep_system: RefCell<FootstepSystem>,
+    music_director: RefCell<MusicDirector>,
+    mount_system: RefCell<MountSystem>,
+    particle_system: RefCell<ParticleSystem>,
+    /// このフレームに描くデバッグ用の図形。<br />
+    /// Debug shapes drawn this frame.
+    debug_draw: RefCell<DebugDraw>,
+    /// 体力バーやアイコンなど、画面に重ねる画像をまとめたアトラス。<br />
+    /// Atlas packing the images overlaid on the screen, such as health bars and icons.
+    sprite_atlas: SpriteAtlas,
+    /// このフレームに画面に重ねるスプライト。<br />
+    /// Sprites overlaid on the screen this frame.
+    sprite_batch: RefCell<SpriteBatch>,
+    /// 右上に出す地形とプレイヤーの地図。<br />
+    /// Map of the terrain and the players shown at the top right.
+    minimap: Minimap,
+    /// エンティティの境界ボックスなどを線で描くかどうか。<br />
+    /// Whether bounding boxes of entities and the like are drawn as lines.
+    show_gizmos: Cell<bool>,
+    /// 起動時に読み込んだ設定。地形の倍率や水面、最初のカットシーンを決める。<br />
+    /// Settings read at startup, deciding the terrain scale, the water and the first cutscene.
+    settings: Settings,
+    /// ローカルプレイヤーが最初に現れた位置。倒された後はここで復活する。<br />
+    /// Where the local player first appeared. The player respawns here after being defeated.
+    spawn_point: Cell<Option<PositionInfo>>,
+    /// 倒されてから、キルカメラが終わって復活するまでの間。<br />
+    /// Set from being defeated until the kill cam ends and the player respawns.
+    is_respawn_pending: Cell<bool>,
+    /// 同じファイルが読み込み中だったモデル。読み込みが終わったら複製する。<br />
+    /// Models whose file was still loading. They're cloned once loading finishes.
+    pending_clones: Vec<(String, PositionInfo, Vec4, usize, DefaultKey)>,
+    /// 遊べる範囲。設定されていなければ、最初に生成した地形から決める。<br />
+    /// The playable area. If it isn't set, it is derived from the first generated terrain.
+    world_bounds: Cell<Option<WorldBounds>>,
+    /// 代理のエンティティのモデルのパス。同じモデルが何度も現れるので、'staticにしたパスを使い回す。<br />
+    /// Paths of the models of proxy entities. The same models appear again and again, so the 'static paths are reused.
+    proxy_model_paths: HashMap<String, &'static str>,
+    /// ローカルプレイヤーが最後に作った、複製されるエンティティの番号。<br />
+    /// Number of the replicated entity the local player created last.
+    last_entity_number: u32,
+    /// 範囲の外で受けた、まだ体力に反映していない端数のダメージ。<br />
+    /// Fractional damage taken outside the bounds that hasn't been applied to health yet.
+    bounds_damage: Cell<f32>,
+    /// 最後のシミュレーションのステップで決まった、範囲の外にいる警告の強さ。<br />
+    /// Strength of the out-of-bounds warning decided by the last simulation step.
+    boundary_warning: Cell<f32>,
 }
 
 impl<GraphicsType, BufferType, CommandType, TextureType>
@@ -61,9 +171,13 @@ where
     pub fn new(
         resource_manager: ResourceManagerWeak<GraphicsType, BufferType, CommandType, TextureType>,
         graphics: Weak<RwLock<ManuallyDrop<GraphicsType>>>,
-        entities: std::rc::Weak<RefCell<SlotMap<DefaultKey, usize>>>,
+        entities: std::rc::Weak<RefCell<World<GraphicsType, BufferType, CommandType, TextureType>>>,
         network_system: Weak<tokio::sync::RwLock<NetworkSystem>>,
         camera: std::rc::Weak<RefCell<Camera>>,
+        physics_system: std::rc::Weak<RefCell<PhysicsSystem>>,
+        audio_system: std::rc::Weak<RefCell<AudioSystem>>,
+        timeline_system: std::rc::Weak<RefCell<TimelineSystem>>,
+        settings: &Settings,
     ) -> Self {
         GameScene {
             graphics,
@@ -74,17 +188,834 @@ where
             waitable_tasks: WaitableTasks::new(),
             scene_type: SceneType::GAME,
             entities,
-            player_entities: HashMap::new(),
             render_components: Vec::new(),
+            pools: HashMap::new(),
             network_system,
             loaded: false,
+            render_features: RenderFeatures::ALL,
             terrain_entity: DefaultKey::null(),
             camera,
+            physics_system,
+            selected_entity: Cell::new(DefaultKey::null()),
+            audio_system,
+            timeline_system,
+            footstep_system: RefCell::new(FootstepSystem::new()),
+            music_director: RefCell::new(MusicDirector::new()),
+            mount_system: RefCell::new(MountSystem::new()),
+            particle_system: RefCell::new(ParticleSystem::new()),
+            debug_draw: RefCell::new(DebugDraw::new()),
+            sprite_atlas: Self::create_overlay_atlas(),
+            sprite_batch: RefCell::new(SpriteBatch::new()),
+            minimap: Minimap::new(MINIMAP_RESOLUTION),
+            show_gizmos: Cell::new(false),
+            settings: settings.clone(),
+            spawn_point: Cell::new(None),
+            is_respawn_pending: Cell::new(false),
+            pending_clones: vec![],
+            world_bounds: Cell::new(None),
+            proxy_model_paths: HashMap::new(),
+            last_entity_number: 0,
+            bounds_damage: Cell::new(0.0),
+            boundary_warning: Cell::new(0.0),
+        }
+    }
+
+    /// 画面に重ねる画像のアトラスを作る。ミニマップは地形ができてから描くので、場所だけ空けておく。<br />
+    /// Create the atlas of images overlaid on the screen. The minimap is drawn once there's terrain, so only its space is reserved.
+    fn create_overlay_atlas() -> SpriteAtlas {
+        let mut atlas = SpriteAtlas::new(SPRITE_ATLAS_SIZE, SPRITE_ATLAS_SIZE);
+        let blank = vec![0; (MINIMAP_RESOLUTION * MINIMAP_RESOLUTION * 4) as usize];
+        if let Err(e) = atlas.add(
+            MINIMAP_SPRITE,
+            MINIMAP_RESOLUTION,
+            MINIMAP_RESOLUTION,
+            &blank,
+        ) {
+            log::warn!("Failed to reserve the minimap in the sprite atlas: {}", e);
+        }
+        atlas
+    }
+
+    /// 描画するモデルを登録し、エンティティに紐付ける。<br />
+    /// Register a model for rendering and attach it to its entity.
+    fn register_renderable(
+        &mut self,
+        renderable: LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>,
+    ) {
+        let entity = renderable.lock().get_entity();
+        if !entity.is_null() {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            entities
+                .borrow_mut()
+                .insert(entity, RenderableRef(renderable.clone()));
+        }
+        self.render_components.push(renderable);
+    }
+
+    /// 最後にクリックで選ばれたエンティティを取得する。<br />
+    /// Get the entity last selected by clicking.
+    pub fn get_selected_entity(&self) -> DefaultKey {
+        self.selected_entity.get()
+    }
+
+    /// モデルの頂点から境界ボックスを求め、エンティティに追加する。<br />
+    /// Compute a bounding box from the model's vertices and attach it to its entity.
+    fn add_bounds(&self, model: &Model<GraphicsType, BufferType, CommandType, TextureType>) {
+        if model.entity.is_null() {
+            return;
+        }
+        let meshes = model.meshes.iter().map(|m| m.lock()).collect::<Vec<_>>();
+        if let Some(bounds) =
+            Bounds::from_primitives(meshes.iter().flat_map(|m| m.primitives.iter()))
+        {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            entities.borrow_mut().insert(model.entity, bounds);
+        }
+    }
+
+    /// カメラをローカルプレイヤーの後ろに置く。カットシーンの間はカットシーンがカメラを動かす。<br />
+    /// Place the camera behind the local player. During a cutscene, the cutscene moves the camera instead.
+    fn follow_local_player(&self, position: Vec3A) {
+        if self.is_cutscene_playing() {
+            return;
+        }
+        let camera = self
+            .camera
+            .upgrade()
+            .expect("Failed to upgrade camera handle.");
+        let mut borrowed_camera = camera.borrow_mut();
+        borrowed_camera.target = position;
+        borrowed_camera.position = Vec3A::new(position.x, position.y + 10.0, position.z - 10.0);
+    }
+
+    /// サーバーが伝えたローカルプレイヤーの生死の変化を処理する。倒されたらキルカメラを再生する。<br />
+    /// Handle a change in the local player's life reported by the server. Being defeated plays the kill cam.
+    fn handle_life_event(&self, event: LifeEvent) {
+        let timeline_system = self
+            .timeline_system
+            .upgrade()
+            .expect("Failed to upgrade timeline system handle.");
+        match event {
+            LifeEvent::Died(death) => {
+                log::info!(
+                    "Local player was defeated by {}.",
+                    death.attacker_id.as_deref().unwrap_or("nobody")
+                );
+                let kill_cam = {
+                    let camera = self
+                        .camera
+                        .upgrade()
+                        .expect("Failed to upgrade camera handle.");
+                    let camera_lock = camera.borrow();
+                    create_kill_cam(&death, camera_lock.position, camera_lock.target)
+                };
+                timeline_system.borrow_mut().play(kill_cam);
+                self.is_respawn_pending.set(true);
+                let entities = self
+                    .entities
+                    .upgrade()
+                    .expect("Failed to upgrade entities handle.");
+                let mut entities_lock = entities.borrow_mut();
+                let position = entities_lock
+                    .iter::<PlayerController>()
+                    .next()
+                    .and_then(|(entity, _)| entities_lock.get::<Transform>(entity))
+                    .map(|transform| transform.position);
+                if let Some(position) = position {
+                    ParticleSystem::spawn_effect(
+                        &mut *entities_lock,
+                        ParticleEffect::Sparks,
+                        position,
+                    );
+                }
+            }
+            // サーバーが先に復活させたら、キルカメラを飛ばしてすぐに戻る。
+            // If the server respawns the player first, skip the kill cam and return right away.
+            LifeEvent::Respawned => {
+                if self.is_respawn_pending.get() {
+                    timeline_system.borrow_mut().skip();
+                }
+            }
+        }
+    }
+
+    /// ローカルプレイヤーを出現した位置に戻し、移動先を消す。<br />
+    /// Move the local player back to where it spawned and clear its destination.
+    fn respawn_local_player(&self) {
+        self.is_respawn_pending.set(false);
+        let spawn_point = match self.spawn_point.get() {
+            Some(spawn_point) => spawn_point,
+            None => return,
+        };
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        let local_entity = entities_lock
+            .iter::<PlayerController>()
+            .next()
+            .map(|(entity, _)| entity);
+        if let Some(entity) = local_entity {
+            self.mount_system
+                .borrow_mut()
+                .dismount(&mut *entities_lock, entity);
+            if let Some(controller) = entities_lock.get_mut::<PlayerController>(entity) {
+                controller.move_target = None;
+            }
+            if let Some(transform) = entities_lock.get_mut::<Transform>(entity) {
+                transform.position = spawn_point.position;
+                transform.rotation = spawn_point.rotation;
+            }
+        }
+        log::info!("Local player respawned.");
+    }
+
+    /// エンティティのトランスフォームとカメラをセーブデータにする。<br />
+    /// Capture the entity transforms and the camera as save data.
+    fn capture_state(&self) -> SaveGame {
+        let mut save = SaveGame::new(&self.scene_name);
+        self.entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.")
+            .borrow()
+            .save_transforms(&mut save);
+        let camera = self
+            .camera
+            .upgrade()
+            .expect("Failed to upgrade camera handle.");
+        let camera_lock = camera.borrow();
+        save.camera = Some(SavedCamera {
+            position: camera_lock.position,
+            target: camera_lock.target,
+        });
+        save
+    }
+
+    /// セーブデータのトランスフォームとカメラを戻し、剛体も同じ位置に移す。<br />
+    /// 乗り物に乗ったままだと座席に引き戻されるので、先に降ろす。<br />
+    /// Restore the entity transforms and the camera from save data, moving rigid bodies along with them.<br />
+    /// A rider would be pulled back into the seat, so the local player dismounts first.
+    fn restore_state(&self, save: &SaveGame) {
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        let local_entity = entities_lock
+            .iter::<PlayerController>()
+            .next()
+            .map(|(entity, _)| entity);
+        if let Some(entity) = local_entity {
+            self.mount_system
+                .borrow_mut()
+                .dismount(&mut *entities_lock, entity);
+            if let Some(controller) = entities_lock.get_mut::<PlayerController>(entity) {
+                controller.move_target = None;
+            }
+        }
+        let restored = entities_lock.restore_transforms(save);
+        let physics_system = self
+            .physics_system
+            .upgrade()
+            .expect("Failed to upgrade physics system handle.");
+        let mut physics_lock = physics_system.borrow_mut();
+        for entity in restored.iter() {
+            if let Some(transform) = entities_lock.get::<Transform>(*entity) {
+                physics_lock.teleport_body(*entity, transform.position, transform.rotation);
+            }
+        }
+        log::info!("Restored {} entities from the save.", restored.len());
+
+        // 保存したカメラがなければ、ローカルプレイヤーの後ろに置き直す。
+        // Without a saved camera, place it behind the local player again.
+        let camera = self
+            .camera
+            .upgrade()
+            .expect("Failed to upgrade camera handle.");
+        if let Some(saved_camera) = save.camera {
+            let mut camera_lock = camera.borrow_mut();
+            camera_lock.position = saved_camera.position;
+            camera_lock.target = saved_camera.target;
+        } else if let Some(transform) =
+            local_entity.and_then(|entity| entities_lock.get::<Transform>(entity))
+        {
+            let position = transform.position;
+            drop(entities_lock);
+            self.follow_local_player(position);
+        }
+    }
+
+    /// 乗っていれば降り、乗っていなければ近くの空いている乗り物に乗る。<br />
+    /// Dismount if riding, otherwise board the nearest free mount.
+    fn toggle_mount(&self, player_id: &str) {
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        let local_entity = match entities_lock.iter::<PlayerController>().next() {
+            Some((entity, _)) => entity,
+            None => return,
+        };
+        let mut mount_system = self.mount_system.borrow_mut();
+        if mount_system.dismount(&mut *entities_lock, local_entity) {
+            log::info!("Local player dismounted.");
+            return;
+        }
+        match mount_system.try_mount(&mut *entities_lock, local_entity, player_id) {
+            Some(_) => log::info!(
+                "Local player mounted {}.",
+                MountSystem::get_mount_id(&*entities_lock, local_entity)
+            ),
+            None => log::debug!("No free mount in range."),
+        }
+    }
+
+    fn is_local_player_riding(&self) -> bool {
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let entities_lock = entities.borrow();
+        entities_lock
+            .iter::<PlayerController>()
+            .any(|(entity, _)| entities_lock.get::<Rider>(entity).is_some())
+    }
+
+    /// ローカルプレイヤーを遊べる範囲に留める。乗っている間は乗り物を押し戻す。<br />
+    /// 警告の強さと、この更新で体力から引くダメージを返す。<br />
+    /// Keep the local player inside the playable area. While riding, the mount is pushed back instead.<br />
+    /// Returns the warning strength and the damage to subtract from health in this update.
+    fn enforce_world_bounds(
+        &self,
+        bounds: &WorldBounds,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        entity: DefaultKey,
+        delta_time: f32,
+    ) -> (f32, i32) {
+        let target = world
+            .get::<Rider>(entity)
+            .map(|r| r.mount)
+            .unwrap_or(entity);
+        let position = match world.get::<Transform>(target) {
+            Some(transform) => transform.position,
+            None => return (0.0, 0),
+        };
+        if !bounds.contains(position) && bounds.response == BoundsResponse::PushBack {
+            if let Some(transform) = world.get_mut::<Transform>(target) {
+                transform.position = bounds.push_back(position, delta_time);
+            }
+            if let Some(mount) = world.get_mut::<Mount>(target) {
+                mount.speed = 0.0;
+            }
+        }
+        // 範囲の外をクリックしても、境界の手前で止まる。
+        // Clicking outside the area stops the player at the edge.
+        if let Some(controller) = world.get_mut::<PlayerController>(entity) {
+            controller.move_target = controller.move_target.map(|t| bounds.clamp(t));
+        }
+        let damage = self.bounds_damage.get() + bounds.get_damage(position, delta_time);
+        self.bounds_damage.set(damage.fract());
+        (bounds.get_warning(position), damage.trunc() as i32)
+    }
+
+    /// ローカルプレイヤーを指定した位置に移す。`x z`なら高さはそのまま、引数がなければ出現した位置に戻す。<br />
+    /// Move the local player to the given position. With `x z` the height is kept, and without arguments it returns to where it spawned.
+    fn teleport_local_player(&self, command: &ConsoleCommand) -> anyhow::Result<ConsoleReply> {
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        let local_entity = entities_lock
+            .iter::<PlayerController>()
+            .next()
+            .map(|(entity, _)| entity)
+            .ok_or_else(|| anyhow::anyhow!("There is no local player."))?;
+        let current = entities_lock
+            .get::<Transform>(local_entity)
+            .map(|transform| transform.position)
+            .ok_or_else(|| anyhow::anyhow!("The local player has no transform."))?;
+        let position = match command.args.len() {
+            0 => self
+                .spawn_point
+                .get()
+                .map(|spawn_point| spawn_point.position)
+                .ok_or_else(|| anyhow::anyhow!("The local player hasn't spawned yet."))?,
+            2 => Vec3A::new(command.get_f32(0)?, current.y, command.get_f32(1)?),
+            _ => Vec3A::new(
+                command.get_f32(0)?,
+                command.get_f32(1)?,
+                command.get_f32(2)?,
+            ),
+        };
+        self.mount_system
+            .borrow_mut()
+            .dismount(&mut *entities_lock, local_entity);
+        if let Some(controller) = entities_lock.get_mut::<PlayerController>(local_entity) {
+            controller.move_target = None;
+        }
+        if let Some(transform) = entities_lock.get_mut::<Transform>(local_entity) {
+            transform.position = position;
         }
+        drop(entities_lock);
+        self.follow_local_player(position);
+        Ok(ConsoleReply::new(&format!(
+            "Teleported to {:.1}, {:.1}, {:.1}.",
+            position.x, position.y, position.z
+        )))
+    }
+
+    fn toggle_gizmos(&self) -> anyhow::Result<ConsoleReply> {
+        let show_gizmos = !self.show_gizmos.get();
+        self.show_gizmos.set(show_gizmos);
+        Ok(ConsoleReply::new(if show_gizmos {
+            "Gizmos shown."
+        } else {
+            "Gizmos hidden."
+        }))
+    }
+
+    /// 引数がなければミニマップの表示を切り替え、あれば拡大率を変える。<br />
+    /// Toggle the minimap without arguments, or change its zoom with one.
+    fn configure_minimap(&mut self, command: &ConsoleCommand) -> anyhow::Result<ConsoleReply> {
+        if command.args.is_empty() {
+            let is_visible = !self.minimap.is_visible();
+            self.minimap.set_visible(is_visible);
+            return Ok(ConsoleReply::new(if is_visible {
+                "Minimap shown."
+            } else {
+                "Minimap hidden."
+            }));
+        }
+        self.minimap.set_zoom(command.get_f32(0)?);
+        self.minimap.set_visible(true);
+        Ok(ConsoleReply::new(&format!(
+            "Minimap zoom set to {:.1}.",
+            self.minimap.get_zoom()
+        )))
+    }
+
+    /// 地形が変わった時にミニマップを描き直し、アトラスに書き込む。<br />
+    /// Redraw the minimap when the terrain changes and write it into the atlas.
+    fn update_minimap(&mut self, primitive: &Primitive, position: Vec3A) -> anyhow::Result<()> {
+        self.minimap.render_terrain(primitive, position)?;
+        self.sprite_atlas
+            .replace(MINIMAP_SPRITE, self.minimap.get_pixels())?;
+        Ok(())
+    }
+
+    /// 地図の上に出すプレイヤーと乗り物の点を集める。<br />
+    /// Collect the blips of the players and mounts shown on the map.
+    fn collect_minimap_blips(
+        entities: &World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) -> Vec<MinimapBlip> {
+        let mut blips = entities
+            .iter::<Mount>()
+            .filter_map(|(entity, _)| entities.get::<Transform>(entity))
+            .map(|transform| MinimapBlip {
+                position: transform.position,
+                color: MOUNT_BLIP_COLOR,
+            })
+            .collect::<Vec<_>>();
+        // ローカルプレイヤーが他の点に隠れないよう、最後に描く。
+        // The local player is drawn last so other blips don't hide it.
+        let mut players = entities
+            .iter::<NetworkReplicated>()
+            .filter_map(|(entity, replicated)| {
+                entities
+                    .get::<Transform>(entity)
+                    .map(|transform| (replicated.is_local, transform.position))
+            })
+            .collect::<Vec<_>>();
+        players.sort_by_key(|(is_local, _)| *is_local);
+        blips.extend(players.into_iter().map(|(is_local, position)| MinimapBlip {
+            position,
+            color: if is_local {
+                LOCAL_BLIP_COLOR
+            } else {
+                REMOTE_BLIP_COLOR
+            },
+        }));
+        blips
+    }
+
+    /// 頭上にバーを出すリモートのプレイヤーと、複製されたエンティティを集める。<br />
+    /// プレイヤーの体力とSPは部屋のステートから取る。倒れたものには出さない。<br />
+    /// Collect the remote players and replicated entities that get bars above them.<br />
+    /// The HP and SP of players come from the room state. Nothing that's down gets bars.
+    fn collect_status_targets(
+        entities: &World<GraphicsType, BufferType, CommandType, TextureType>,
+        player_states: &HashMap<String, EntityState>,
+    ) -> Vec<(DefaultKey, StatusTarget)> {
+        let bar_offset = Vec3A::new(0.0, STATUS_BAR_HEIGHT, 0.0);
+        let mut targets = entities
+            .iter::<NetworkReplicated>()
+            .filter(|(_, replicated)| !replicated.is_local)
+            .filter_map(|(entity, replicated)| {
+                let state = player_states
+                    .get(&replicated.player_id)
+                    .filter(|state| state.is_alive)?;
+                let transform = entities.get_interpolated_transform(entity)?;
+                Some((
+                    entity,
+                    StatusTarget {
+                        position: transform.position + bar_offset,
+                        current_hp: state.current_hp,
+                        max_hp: state.max_hp,
+                        current_sp: state.current_sp,
+                        max_sp: state.max_sp,
+                    },
+                ))
+            })
+            .collect::<Vec<_>>();
+        targets.extend(
+            entities
+                .iter::<NetworkEntity>()
+                .filter(|(_, network_entity)| network_entity.current_hp > 0)
+                .filter_map(|(entity, network_entity)| {
+                    // 大きさがモデルによって違うので、境界ボックスがあればその上に置く。
+                    // Sizes differ by model, so the bar goes above the bounding box if there is one.
+                    let position = match entities.get_world_bounds(entity) {
+                        Some(bounds) => {
+                            let center = (bounds.min + bounds.max) * 0.5;
+                            Vec3A::new(center.x, bounds.max.y + ENTITY_BAR_MARGIN, center.z)
+                        }
+                        None => entities.get_interpolated_transform(entity)?.position + bar_offset,
+                    };
+                    Some((
+                        entity,
+                        StatusTarget {
+                            position,
+                            current_hp: network_entity.current_hp,
+                            max_hp: network_entity.max_hp,
+                            ..Default::default()
+                        },
+                    ))
+                }),
+        );
+        targets.retain(|(_, target)| target.max_hp > 0);
+        targets
+    }
+
+    /// バーを画面に投影し、地形や他のエンティティに隠れたものを除く。<br />
+    /// バーの持ち主とローカルプレイヤーはカメラとの間にあっても遮らないものとする。<br />
+    /// Project bars onto the screen, leaving out those hidden by terrain or other entities.<br />
+    /// The owner of a bar and the local player don't count as blocking even if they're in front of the camera.
+    fn project_status_bars(&self, targets: &[(DefaultKey, StatusTarget)]) -> (Vec<StatusBar>, f32) {
+        let camera = self
+            .camera
+            .upgrade()
+            .expect("Failed to upgrade camera handle.");
+        let camera = camera.borrow();
+        let view_projection = camera.get_projection_matrix() * camera.get_view_matrix();
+        let (eye, width, height) = (camera.position, camera.width as f32, camera.height as f32);
+        drop(camera);
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let entities_lock = entities.borrow();
+        let physics_system = self
+            .physics_system
+            .upgrade()
+            .expect("Failed to upgrade physics system handle.");
+        let physics_lock = physics_system.borrow();
+        let local_players = entities_lock
+            .iter::<NetworkReplicated>()
+            .filter(|(_, replicated)| replicated.is_local)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        let bars = targets
+            .iter()
+            .filter_map(|(entity, target)| {
+                let bar = StatusBar::project(target, view_projection, eye, width, height)?;
+                let mut ignored = local_players.clone();
+                ignored.push(*entity);
+                if entities_lock.is_line_blocked(eye, target.position, &ignored)
+                    || physics_lock.is_line_blocked_by_terrain(eye, target.position)
+                {
+                    return None;
+                }
+                Some(bar)
+            })
+            .collect::<Vec<_>>();
+        (bars, height)
+    }
+
+    /// エンティティの境界ボックスと、浮力を求める点を線で描く。<br />
+    /// Draw the bounding boxes of entities and the points where buoyancy is sampled as lines.
+    fn draw_gizmos(
+        &self,
+        entities: &World<GraphicsType, BufferType, CommandType, TextureType>,
+        debug_draw: &mut DebugDraw,
+    ) {
+        for (entity, bounds) in entities.iter::<Bounds>() {
+            let bounds = match entities.get::<Transform>(entity) {
+                Some(transform) => bounds.transform(transform.get_world_matrix()),
+                None => *bounds,
+            };
+            debug_draw.draw_aabb(bounds.min, bounds.max, BOUNDS_GIZMO_COLOR);
+        }
+        for (entity, buoyancy) in entities.iter::<Buoyancy>() {
+            if let Some(transform) = entities.get::<Transform>(entity) {
+                let rotation = quat_from_euler(transform.rotation);
+                for point in buoyancy
+                    .get_sample_points(transform.position, rotation)
+                    .iter()
+                {
+                    debug_draw.draw_sphere(*point, 0.1, BUOYANCY_GIZMO_COLOR);
+                }
+            }
+        }
+    }
+
+    fn is_cutscene_playing(&self) -> bool {
+        self.timeline_system
+            .upgrade()
+            .expect("Failed to upgrade timeline system handle.")
+            .borrow()
+            .is_playing()
+    }
+
+    /// 画面上の点から光線を飛ばし、最も近いエンティティと交点を求める。地形は高さで判定する。<br />
+    /// Cast a ray from a point on the screen and find the nearest entity and hit point. Terrains are tested by height.
+    fn pick_hit(&self, x: f64, y: f64) -> Option<(DefaultKey, Vec3A)> {
+        let ray = self
+            .camera
+            .upgrade()
+            .expect("Failed to upgrade camera handle.")
+            .borrow()
+            .screen_point_to_ray(x, y);
+        let entity_hit = Scene::raycast(self, &ray);
+        let terrain_hit = self
+            .physics_system
+            .upgrade()
+            .expect("Failed to upgrade physics system handle.")
+            .borrow()
+            .raycast_terrain(&ray, PICK_DISTANCE);
+        let hit = match (entity_hit, terrain_hit) {
+            (Some(e), Some(t)) => Some(if e.1 <= t.1 { e } else { t }),
+            (e, t) => e.or(t),
+        };
+        hit.map(|(entity, distance)| (entity, ray.get_point(distance)))
     }
 }
 
 impl GameScene<Graphics, Buffer, CommandBuffer, Image> {
+    /// モデルのエンティティが持つコンポーネントに応じて物理演算の剛体を作成する。<br />
+    /// Create a physics body for a model according to the components of its entity.
+    fn add_physics_body(
+        &self,
+        model: &Model<Graphics, Buffer, CommandBuffer, Image>,
+    ) -> anyhow::Result<()> {
+        if model.entity.is_null() {
+            return Ok(());
+        }
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let physics_system = self
+            .physics_system
+            .upgrade()
+            .expect("Failed to upgrade physics system handle.");
+        let entities_lock = entities.borrow();
+        let mut physics_lock = physics_system.borrow_mut();
+        let entity = model.entity;
+        if entities_lock.get::<PlayerController>(entity).is_some() {
+            physics_lock.add_character(entity, model.position_info.position, 1.0, 1.0);
+            return Ok(());
+        }
+        let body_status = if entities_lock.get::<NetworkReplicated>(entity).is_some() {
+            BodyStatus::Kinematic
+        } else {
+            match entities_lock.get::<RigidBody>(entity) {
+                Some(rigid_body) if rigid_body.is_kinematic => BodyStatus::Kinematic,
+                Some(_) => BodyStatus::Dynamic,
+                // 浮く物は水に動かされるので、動的な剛体にする。
+                // Floating objects are moved by water, so they become dynamic bodies.
+                None if entities_lock.get::<Buoyancy>(entity).is_some() => BodyStatus::Dynamic,
+                // 乗り物は乗っている者が動かす。
+                // Mounts are moved by their riders.
+                None if entities_lock.get::<Mount>(entity).is_some() => BodyStatus::Kinematic,
+                None => BodyStatus::Static,
+            }
+        };
+        physics_lock.add_model_collider(
+            entity,
+            model,
+            body_status,
+            body_status != BodyStatus::Static,
+        )
+    }
+
+    /// コンソールからモデルを出す。位置を省くとローカルプレイヤーの位置に置く。<br />
+    /// Spawn a model from the console. Without a position it's placed at the local player.
+    fn spawn_from_console(&mut self, command: &ConsoleCommand) -> anyhow::Result<ConsoleReply> {
+        let file_name = command.get_arg(0)?;
+        let path = find_model_file(file_name)
+            .ok_or_else(|| anyhow::anyhow!("Model {} was not found.", file_name))?;
+        let position = if command.args.len() >= 4 {
+            Vec3A::new(
+                command.get_f32(1)?,
+                command.get_f32(2)?,
+                command.get_f32(3)?,
+            )
+        } else {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            let entities_lock = entities.borrow();
+            entities_lock
+                .iter::<PlayerController>()
+                .next()
+                .and_then(|(entity, _)| entities_lock.get::<Transform>(entity))
+                .map(|transform| transform.position)
+                .unwrap_or_else(Vec3A::zero)
+        };
+        let entity_name = std::path::Path::new(&path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(file_name)
+            .to_string();
+        let entity = self.add_entity(&entity_name);
+        // 読み込みのタスクがファイル名を持ち続けるので、'staticにする。コンソールで出す数は少ないので漏らしてもよい。
+        // Loading tasks keep the file name, so it's made 'static. Few models are spawned from the console, so leaking is fine.
+        let path: &'static str = Box::leak(path.into_boxed_str());
+        self.add_model(
+            path,
+            position,
+            Vec3A::one(),
+            Vec3A::zero(),
+            Vec4::one(),
+            entity,
+        )?;
+        // 部屋にいる間は、出したモデルをローカルプレイヤーのエンティティとして他のプレイヤーにも見せる。
+        // While in a room, spawned models are shown to other players as entities of the local player.
+        if let Some(owner_id) = self.get_local_player_id() {
+            self.last_entity_number += 1;
+            let entity_id = format!("{}:{}", owner_id, self.last_entity_number);
+            let model = std::path::Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(file_name)
+                .to_string();
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            let mut entities_lock = entities.borrow_mut();
+            entities_lock.insert(entity, NetworkEntity::new_local(entity_id, owner_id, model));
+            entities_lock.insert(
+                entity,
+                Transform::from(PositionInfo {
+                    position,
+                    scale: Vec3A::one(),
+                    rotation: Vec3A::zero(),
+                }),
+            );
+        }
+        Ok(ConsoleReply::new(&format!(
+            "Spawned {} at {:.1}, {:.1}, {:.1}.",
+            path, position.x, position.y, position.z
+        ))
+        .with_loading())
+    }
+
+    fn get_local_player_id(&self) -> Option<String> {
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let entities_lock = entities.borrow();
+        entities_lock
+            .iter::<NetworkReplicated>()
+            .find(|(_, replicated)| replicated.is_local)
+            .map(|(_, replicated)| replicated.player_id.clone())
+    }
+
+    /// 他のプレイヤーが持つエンティティの代理を作る。モデルは`models`の下からしか探さない。<br />
+    /// Create a proxy of an entity owned by another player. Models are only looked up under `models`.
+    fn spawn_proxy(&mut self, snapshot: &EntitySnapshot) -> anyhow::Result<()> {
+        let path = find_model_file(&snapshot.model)
+            .filter(|path| path.starts_with(MODELS_DIRECTORY))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Model {} of entity {} was not found.",
+                    snapshot.model,
+                    snapshot.entity_id
+                )
+            })?;
+        let path = match self.proxy_model_paths.get(&path) {
+            Some(path) => *path,
+            None => {
+                let leaked: &'static str = Box::leak(path.clone().into_boxed_str());
+                self.proxy_model_paths.insert(path, leaked);
+                leaked
+            }
+        };
+        let position_info = PositionInfo::from(&WorldMatrixUdp::from(snapshot.world_matrix));
+        let entity = self.add_entity(&snapshot.entity_id);
+        {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            let mut entities_lock = entities.borrow_mut();
+            entities_lock.insert(entity, NetworkEntity::from(snapshot));
+            entities_lock.insert(entity, Transform::from(position_info));
+        }
+        let rotation = position_info.rotation;
+        self.add_model(
+            path,
+            position_info.position,
+            position_info.scale,
+            Vec3A::new(
+                rotation.x.to_degrees(),
+                rotation.y.to_degrees(),
+                rotation.z.to_degrees(),
+            ),
+            Vec4::one(),
+            entity,
+        )
+    }
+
+    /// キャッシュされたモデルを複製し、メッシュとテクスチャを共有する新しいインスタンスとして追加する。<br />
+    /// Clone a cached model and add it as a new instance sharing its meshes and textures.
+    fn add_model_clone(
+        &mut self,
+        source: &LockableRenderable<Graphics, Buffer, CommandBuffer, Image>,
+        position_info: PositionInfo,
+        color: Vec4,
+        ssbo_index: usize,
+        entity: DefaultKey,
+    ) {
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let mut model = source.lock().box_clone();
+        model.set_position_info(position_info);
+        let mut metadata = model.get_model_metadata();
+        metadata.world_matrix = model.get_world_matrix();
+        metadata.object_color = color;
+        model.set_model_metadata(metadata);
+        model.set_ssbo_index(ssbo_index);
+        model.set_entity(entity);
+        model.update_model_indices(self.counts.model_count.clone());
+        let renderable = resource_manager.write().add_clone(self.scene_type, model);
+        self.register_renderable(renderable);
+    }
+
     /// インスタンス描画のモデルを追加する。<br />
     /// Add instance rendering models.
     pub fn add_instanced_model(
@@ -97,7 +1028,7 @@ impl GameScene<Graphics, Buffer, CommandBuffer, Image> {
         instance_count: usize,
         entity: DefaultKey,
     ) -> anyhow::Result<()> {
-        let ssbo_index = self.counts.ssbo_count.fetch_add(1, Ordering::SeqCst);
+        let ssbo_index = self.counts.acquire_ssbo_index();
         let mut instance_data = vec![];
         instance_data.resize(instance_count, InstanceData::default());
         let mut x_offset = 0.0;
@@ -128,6 +1059,116 @@ impl GameScene<Graphics, Buffer, CommandBuffer, Image> {
         Ok(())
     }
 
+    /// 頻繁に生成・削除されるモデルのプールを作成する。<br />
+    /// Create a pool for models that are frequently spawned and despawned.
+    pub fn create_pool(
+        &mut self,
+        file_name: &'static str,
+        instance_count: usize,
+    ) -> anyhow::Result<()> {
+        if instance_count == 0 || self.pools.contains_key(file_name) {
+            return Ok(());
+        }
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+
+        let template_entity = entities.borrow_mut().spawn(file_name);
+        let template = Model::new(
+            file_name,
+            self.graphics.clone(),
+            Vec3A::zero(),
+            Vec3A::one(),
+            Vec3A::zero(),
+            Vec4::one(),
+            self.counts.model_count.clone(),
+            self.counts.acquire_ssbo_index(),
+            true,
+            template_entity,
+        )?
+        .wait()?;
+
+        let mut resource_lock = resource_manager.write();
+        let template = resource_lock.add_model(self.scene_type, template);
+        let mut instances = vec![template.clone()];
+        for _ in 1..instance_count {
+            let entity = entities.borrow_mut().spawn(file_name);
+            let mut model = template.lock().box_clone();
+            model.set_ssbo_index(self.counts.acquire_ssbo_index());
+            model.set_entity(entity);
+            model.update_model_indices(self.counts.model_count.clone());
+            instances.push(resource_lock.add_clone(self.scene_type, model));
+        }
+        drop(resource_lock);
+        self.pools
+            .insert(file_name, RenderablePool::new(file_name, instances));
+        Ok(())
+    }
+
+    /// プールからモデルを一つ取り出してシーンに表示する。<br />
+    /// Take a model out of the pool and show it in the scene.
+    pub fn acquire_pooled(
+        &mut self,
+        file_name: &'static str,
+        position: Vec3A,
+        scale: Vec3A,
+        rotation: Vec3A,
+    ) -> Option<DefaultKey> {
+        let pool = self.pools.get_mut(file_name)?;
+        let (entity, renderable) = pool.acquire(PositionInfo {
+            position,
+            scale,
+            rotation,
+        })?;
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        entities_lock.insert(entity, RenderableRef(renderable.clone()));
+        entities_lock.insert(
+            entity,
+            Transform {
+                position,
+                scale,
+                rotation,
+            },
+        );
+        drop(entities_lock);
+        self.render_components.push(renderable);
+        Some(entity)
+    }
+
+    /// 使用中のモデルをプールに戻し、シーンから隠す。<br />
+    /// Return an active model to its pool and hide it from the scene.
+    pub fn release_pooled(&mut self, entity: DefaultKey) -> bool {
+        let released = self
+            .pools
+            .values_mut()
+            .find(|p| p.is_active(entity))
+            .and_then(|p| p.release(entity));
+        if let Some(renderable) = released {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            let mut entities_lock = entities.borrow_mut();
+            entities_lock.remove::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity);
+            entities_lock.remove::<Transform>(entity);
+            drop(entities_lock);
+            self.render_components
+                .retain(|r| !Arc::ptr_eq(r, &renderable));
+            true
+        } else {
+            false
+        }
+    }
+
     /// 骨付きの動的なモデルを追加する。<br />
     /// Add skinned models.
     fn add_skinned_model(
@@ -137,8 +1178,9 @@ impl GameScene<Graphics, Buffer, CommandBuffer, Image> {
         scale: Vec3A,
         rotation: Vec3A,
         color: Vec4,
+        entity: DefaultKey,
     ) -> anyhow::Result<()> {
-        let ssbo_index = self.counts.ssbo_count.fetch_add(1, Ordering::SeqCst);
+        let ssbo_index = self.counts.acquire_ssbo_index();
         let resource_manager = self.resource_manager.upgrade();
         if resource_manager.is_none() {
             return Err(anyhow::anyhow!("Resource manager has been destroyed."));
@@ -169,9 +1211,11 @@ impl GameScene<Graphics, Buffer, CommandBuffer, Image> {
             metadata.object_color = color;
             model.set_model_metadata(metadata);
             model.set_ssbo_index(ssbo_index);
+            model.set_entity(entity);
             model.update_model_indices(self.counts.model_count.clone());
             let mut lock = resource_manager.write();
-            lock.add_clone(self.scene_type, model);
+            let renderable = lock.add_clone(self.scene_type, model);
+            self.register_renderable(renderable);
             drop(lock);
         } else {
             let task = SkinnedModel::new(
@@ -183,6 +1227,7 @@ impl GameScene<Graphics, Buffer, CommandBuffer, Image> {
                 color,
                 ssbo_index,
                 self.counts.model_count.clone(),
+                entity,
             )?;
             self.waitable_tasks.skinned_model_tasks.push(task);
         }
@@ -204,7 +1249,7 @@ impl GameScene<Graphics, Buffer, CommandBuffer, Image> {
         entity: DefaultKey,
     ) -> anyhow::Result<()> {
         let model_index = self.counts.model_count.fetch_add(1, Ordering::SeqCst);
-        let ssbo_index = self.counts.ssbo_count.fetch_add(1, Ordering::SeqCst);
+        let ssbo_index = self.counts.acquire_ssbo_index();
         let task = GeometricPrimitive::new(
             self.graphics.clone(),
             primitive_type,
@@ -221,6 +1266,30 @@ impl GameScene<Graphics, Buffer, CommandBuffer, Image> {
         self.waitable_tasks.geometric_primitive_tasks.push(task);
         Ok(())
     }
+
+    /// データファイルのアニメーションの状態機械をエンティティに付ける。<br />
+    /// Attach the animation state machine in a data file to an entity.
+    fn attach_animator(&self, entity: DefaultKey, file_name: &str) -> anyhow::Result<()> {
+        let animator = Animator::load(file_name)?;
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        entities.borrow_mut().insert(entity, animator);
+        Ok(())
+    }
+
+    /// データファイルの揺れる骨の設定をエンティティに付ける。<br />
+    /// Attach the spring-bone settings in a data file to an entity.
+    fn attach_spring_bones(&self, entity: DefaultKey, file_name: &str) -> anyhow::Result<()> {
+        let rig = SpringBoneRig::load(file_name)?;
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        entities.borrow_mut().insert(entity, rig);
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -231,10 +1300,7 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
             .upgrade()
             .expect("Failed to upgrade entities handle.");
         let mut entities_lock = entities.borrow_mut();
-        let entity = entities_lock.insert(self.counts.entity_count);
-        self.counts.entity_count += 1;
-        self.player_entities.insert(entity_name.to_string(), entity);
-        entity
+        entities_lock.spawn(entity_name)
     }
 
     fn add_model(
@@ -246,60 +1312,87 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
         color: Vec4,
         entity: DefaultKey,
     ) -> anyhow::Result<()> {
-        let ssbo_index = self.counts.ssbo_count.fetch_add(1, Ordering::SeqCst);
+        let ssbo_index = self.counts.acquire_ssbo_index();
         let resource_manager = self.resource_manager.upgrade();
         if resource_manager.is_none() {
             return Err(anyhow::anyhow!("Resource manager has been destroyed."));
         }
         let resource_manager = resource_manager.unwrap();
-        let mut lock = resource_manager.write();
-        let current_model_queue = lock
-            .model_queue
-            .entry(self.scene_type)
-            .or_insert_with(Vec::new);
-        let item = current_model_queue
-            .iter()
-            .find(|m| (*m).lock().get_name() == file_name)
-            .cloned();
-        drop(lock);
-        if let Some(m) = item {
-            let mut model = (*m.lock()).clone();
-            let x: f32 = rotation.x;
-            let y: f32 = rotation.y;
-            let z: f32 = rotation.z;
-            model.set_position_info(PositionInfo {
-                position,
-                scale,
-                rotation: Vec3A::new(x.to_radians(), y.to_radians(), z.to_radians()),
-            });
-            let mut metadata = model.get_model_metadata();
-            metadata.world_matrix = model.get_world_matrix();
-            metadata.object_color = color;
-            model.set_model_metadata(metadata);
-            model.set_ssbo_index(ssbo_index);
-            model.update_model_indices(self.counts.model_count.clone());
-            let mut lock = resource_manager.write();
-            lock.add_clone(self.scene_type, model);
-            drop(lock);
-        } else {
-            let task = Model::new(
-                file_name,
-                self.graphics.clone(),
-                position,
-                scale,
-                rotation,
-                color,
-                self.counts.model_count.clone(),
-                ssbo_index,
-                true,
-                entity,
-            )?;
-            self.waitable_tasks.model_tasks.push(task);
-        }
+        let key = get_cache_key(file_name);
+        let lookup = resource_manager.write().model_cache.acquire(&key);
         drop(resource_manager);
+        let x: f32 = rotation.x;
+        let y: f32 = rotation.y;
+        let z: f32 = rotation.z;
+        let position_info = PositionInfo {
+            position,
+            scale,
+            rotation: Vec3A::new(x.to_radians(), y.to_radians(), z.to_radians()),
+        };
+        match lookup {
+            CacheLookup::Hit(source) => {
+                self.add_model_clone(&source, position_info, color, ssbo_index, entity)
+            }
+            CacheLookup::Pending => {
+                self.pending_clones
+                    .push((key, position_info, color, ssbo_index, entity));
+            }
+            CacheLookup::Miss => {
+                let task = Model::new(
+                    file_name,
+                    self.graphics.clone(),
+                    position,
+                    scale,
+                    rotation,
+                    color,
+                    self.counts.model_count.clone(),
+                    ssbo_index,
+                    true,
+                    entity,
+                )?;
+                self.waitable_tasks.model_tasks.push(task);
+            }
+        }
         Ok(())
     }
 
+    fn apply_replication(&mut self, events: Vec<ReplicationEvent>) -> anyhow::Result<bool> {
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut needs_loading = false;
+        for event in events.into_iter() {
+            match event {
+                ReplicationEvent::Spawned(snapshot) => match self.spawn_proxy(&snapshot) {
+                    Ok(()) => needs_loading = true,
+                    Err(e) => log::warn!("Failed to spawn a proxy entity: {}", e),
+                },
+                ReplicationEvent::Updated(snapshot) => {
+                    let mut entities_lock = entities.borrow_mut();
+                    if let Some(entity) = entities_lock.find_network_entity(&snapshot.entity_id) {
+                        entities_lock.apply_entity_snapshot(entity, &snapshot);
+                    }
+                }
+                ReplicationEvent::Despawned(entity_id) => {
+                    let entity = entities.borrow().find_network_entity(&entity_id);
+                    if let Some(entity) = entity {
+                        self.remove_entity(entity)?;
+                    }
+                }
+            }
+        }
+        Ok(needs_loading)
+    }
+
+    fn cancel_tasks(&mut self) -> anyhow::Result<()> {
+        if self.waitable_tasks.is_empty() {
+            return Ok(());
+        }
+        self.waitable_tasks.cancel();
+        self.unload()
+    }
+
     fn create_ssbo(&self) -> anyhow::Result<()> {
         for renderable in self.render_components.iter() {
             renderable.lock().create_ssbo()?;
@@ -307,14 +1400,231 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
         Ok(())
     }
 
+    fn execute_command(
+        &mut self,
+        command: &ConsoleCommand,
+    ) -> Option<anyhow::Result<ConsoleReply>> {
+        match command.name.as_str() {
+            "spawn" => Some(self.spawn_from_console(command)),
+            "teleport" => Some(self.teleport_local_player(command)),
+            "gizmos" => Some(self.toggle_gizmos()),
+            "minimap" => Some(self.configure_minimap(command)),
+            _ => None,
+        }
+    }
+
+    async fn fixed_update(&self, delta_time: f64) -> anyhow::Result<()> {
+        if !self.loaded {
+            return Ok(());
+        }
+        let network_system = self
+            .network_system
+            .upgrade()
+            .expect("Failed to upgrade network system handle.");
+
+        // リモートのプレイヤーは少し過去の状態を補間して描画し、ローカルプレイヤーは予測をサーバーの状態で補正する。
+        // Remote players are rendered interpolated slightly in the past, and the local player's prediction is reconciled with the server state.
+        let now = Instant::now();
+        let (state_queues, life_tracker, mount_owners, replication) = {
+            let ns = network_system.read().await;
+            (
+                ns.state_queues.clone(),
+                ns.life_tracker.clone(),
+                ns.mount_owners.clone(),
+                ns.replication.clone(),
+            )
+        };
+        let (mut remote_states, correction) = {
+            let mut queues = state_queues.lock().await;
+            let remote_states = queues.sample_remote(now);
+            let correction = queues
+                .take_local_server_states()
+                .iter()
+                .filter_map(|state| queues.prediction.reconcile(state))
+                .last();
+            (remote_states, correction)
+        };
+        let life_events = life_tracker.lock().await.take_events();
+        let mount_owners = mount_owners.lock().await.get();
+        for event in life_events.into_iter() {
+            self.handle_life_event(event);
+        }
+        // キルカメラが終わったら復活の流れに戻る。
+        // Once the kill cam ends, return to the respawn flow.
+        let is_respawning = self.is_respawn_pending.get() && !self.is_cutscene_playing();
+        if is_respawning {
+            self.respawn_local_player();
+        }
+
+        // 壊れたクライアントが範囲の外に現れないように、リモートの位置を範囲に収める。
+        // Clamp remote positions to the bounds so that glitched clients can't appear outside the map.
+        let world_bounds = self.world_bounds.get();
+        if let Some(bounds) = world_bounds.as_ref() {
+            for state in remote_states.values_mut() {
+                state.position = bounds.clamp(state.position);
+            }
+        }
+
+        let (local_transform, local_mount_id, bounds_damage, owned_entities) = {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            let mut entities_lock = entities.borrow_mut();
+            entities_lock.apply_network_state(&remote_states);
+            let local_entity = entities_lock
+                .iter::<PlayerController>()
+                .next()
+                .map(|(entity, _)| entity);
+            if let Some(state) = correction {
+                if let Some(transform) =
+                    local_entity.and_then(|e| entities_lock.get_mut::<Transform>(e))
+                {
+                    log::debug!(
+                        "Correcting local player from {:?} to {:?}.",
+                        transform.position,
+                        state.position
+                    );
+                    transform.position = state.position;
+                    transform.rotation = state.rotation;
+                }
+            }
+            {
+                // 持ち主をサーバーに合わせてから、乗り物を動かして座席に乗せる。
+                // Match the owners to the server first, then drive the mounts and seat their riders.
+                let physics_system = self
+                    .physics_system
+                    .upgrade()
+                    .expect("Failed to upgrade physics system handle.");
+                MountSystem::apply_owners(&mut *entities_lock, &mount_owners);
+                self.mount_system.borrow().update(
+                    &mut *entities_lock,
+                    &physics_system.borrow(),
+                    delta_time,
+                );
+            }
+            let (boundary_warning, bounds_damage) = match (world_bounds, local_entity) {
+                (Some(bounds), Some(entity)) if !self.is_respawn_pending.get() => self
+                    .enforce_world_bounds(&bounds, &mut *entities_lock, entity, delta_time as f32),
+                _ => (0.0, 0),
+            };
+            self.boundary_warning.set(boundary_warning);
+            entities_lock.update_animators(delta_time);
+            let events = entities_lock.take_animation_events();
+            if !events.is_empty() {
+                let physics_system = self
+                    .physics_system
+                    .upgrade()
+                    .expect("Failed to upgrade physics system handle.");
+                let audio_system = self
+                    .audio_system
+                    .upgrade()
+                    .expect("Failed to upgrade audio system handle.");
+                self.footstep_system.borrow_mut().update(
+                    &events,
+                    &*entities_lock,
+                    &physics_system.borrow(),
+                    &mut audio_system.borrow_mut(),
+                );
+            }
+            for puff in self.footstep_system.borrow_mut().take_puffs() {
+                ParticleSystem::spawn_emitter(
+                    &mut *entities_lock,
+                    ParticleEmitter::from(puff.puff),
+                    puff.position,
+                );
+            }
+            // 水に飛び込んだ所と上がった所に飛沫を上げる。
+            // Throw up spray where things jumped into or climbed out of water.
+            let water_events = self
+                .physics_system
+                .upgrade()
+                .expect("Failed to upgrade physics system handle.")
+                .borrow_mut()
+                .take_water_events();
+            for event in water_events.into_iter() {
+                let position = match event {
+                    WaterEvent::Entered { position, .. } | WaterEvent::Exited { position, .. } => {
+                        position
+                    }
+                };
+                ParticleSystem::spawn_effect(&mut *entities_lock, ParticleEffect::Dust, position);
+            }
+            (
+                local_entity.and_then(|e| entities_lock.get::<Transform>(e).copied()),
+                local_entity
+                    .map(|e| MountSystem::get_mount_id(&*entities_lock, e))
+                    .unwrap_or_default(),
+                bounds_damage,
+                entities_lock.collect_owned_entities(),
+            )
+        };
+        replication.lock().await.set_owned_entities(owned_entities);
+
+        // クリックによる移動は物理演算で進むため、その結果をサーバーに送る状態に書き戻す。
+        // Click-to-move is advanced by physics, so write the result back into the state sent to the server.
+        if let Some(transform) = local_transform {
+            state_queues
+                .lock()
+                .await
+                .prediction
+                .record(now, PositionInfo::from(transform));
+            let player = network_system.read().await.logged_user.clone();
+            if let Some(player) = player {
+                let mut player_lock = player.lock().await;
+                let world_matrix = player_lock
+                    .state
+                    .as_mut()
+                    .and_then(|s| s.state.as_mut())
+                    .and_then(|s| s.world_matrix.as_mut());
+                if let Some(wm) = world_matrix {
+                    *wm = WorldMatrix::from(PositionInfo::from(transform));
+                }
+                // 倒されている間はサーバーの生死に合わせ、復活したら体力を戻す。
+                // Follow the server while defeated, and restore health on respawning.
+                if let Some(state) = player_lock.state.as_mut().and_then(|s| s.state.as_mut()) {
+                    state.mount_id = local_mount_id;
+                    if is_respawning {
+                        state.is_alive = true;
+                        state.current_hp = state.max_hp;
+                        state.last_attacker_id.clear();
+                    } else if self.is_respawn_pending.get() {
+                        state.is_alive = false;
+                        state.current_hp = 0;
+                    } else if bounds_damage > 0 && state.is_alive {
+                        state.current_hp = (state.current_hp - bounds_damage).max(0);
+                        if state.current_hp == 0 {
+                            log::info!("Local player was defeated outside the world bounds.");
+                            self.is_respawn_pending.set(true);
+                        }
+                    }
+                }
+                if let Some(state) = player_lock.state.as_ref().and_then(|s| s.state.as_ref()) {
+                    self.music_director
+                        .borrow_mut()
+                        .handle_event(MusicEvent::HealthChanged {
+                            current: state.current_hp,
+                            max: state.max_hp,
+                        });
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn generate_terrain(
         &mut self,
         grid_x: f32,
         grid_z: f32,
         primitive: Option<Primitive>,
     ) -> anyhow::Result<Primitive> {
+        // パッチを当てた地形に作り直す時は、前の地形を先に取り除く。
+        // When recreating the terrain with patches applied, the previous terrain is removed first.
+        if !self.terrain_entity.is_null() {
+            self.remove_entity(self.terrain_entity)?;
+        }
         let model_index = self.counts.model_count.fetch_add(1, Ordering::SeqCst);
-        let ssbo_index = self.counts.ssbo_count.fetch_add(1, Ordering::SeqCst);
+        let ssbo_index = self.counts.acquire_ssbo_index();
         let mut height_generator = self
             .height_generator
             .write()
@@ -322,19 +1632,32 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
         let vertex_count = Terrain::<Graphics, Buffer, CommandBuffer, Image>::VERTEX_COUNT;
         height_generator.set_offsets(grid_x as i32, grid_z as i32, vertex_count as i32);
         drop(height_generator);
-        let ratio = std::env::var("RATIO").unwrap().parse::<f32>().unwrap();
+        let ratio = self.settings.ratio;
+        if self.world_bounds.get().is_none() {
+            let terrain_size = Terrain::<Graphics, Buffer, CommandBuffer, Image>::SIZE * ratio;
+            self.world_bounds.set(Some(WorldBounds::from_terrain(
+                Vec2::new(grid_x * terrain_size, grid_z * terrain_size),
+                terrain_size,
+                self.settings.world_border,
+            )));
+        }
 
         let entity = {
             let entities = self
                 .entities
                 .upgrade()
                 .expect("Failed to upgrade entities handle.");
-            let entity_count = self.counts.entity_count;
-            let key = entities.borrow_mut().insert(entity_count);
-            key
+            let mut entities_lock = entities.borrow_mut();
+            entities_lock.spawn(&format!("Terrain ({}, {})", grid_x, grid_z))
         };
+        self.terrain_entity = entity;
 
-        let terrain = Terrain::new(
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let terrain_manager = resource_manager.read().terrain_manager.clone();
+        let mut terrain = Terrain::new(
             grid_x,
             grid_z,
             model_index,
@@ -346,22 +1669,42 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
             ratio,
             primitive.clone(),
             entity,
+            terrain_manager,
         )?;
         //self.waitable_tasks.terrain_tasks.push(terrain);
-        let resource_manager = self
-            .resource_manager
-            .upgrade()
-            .expect("Failed to upgrade resource manager handle.");
-        let result = terrain.recv()?;
+        let result = terrain.wait()?;
         let primitive = if let Some(p) = primitive {
             p
         } else {
             result.model.meshes[0].lock().primitives[0].clone()
         };
+        if let Err(e) = self.update_minimap(&primitive, result.model.position_info.position) {
+            log::warn!("Failed to draw the minimap: {}", e);
+        } else if self.loaded {
+            if let Err(e) = Graphics::create_sprite_atlas(
+                self.graphics
+                    .upgrade()
+                    .expect("Failed to upgrade graphics handle."),
+                &self.sprite_atlas,
+            ) {
+                log::warn!("Failed to upload the sprite atlas: {}", e);
+            }
+        }
+        {
+            let physics_system = self
+                .physics_system
+                .upgrade()
+                .expect("Failed to upgrade physics system handle.");
+            physics_system.borrow_mut().add_terrain_collider(
+                entity,
+                &primitive,
+                result.model.position_info.position,
+            )?;
+        }
         {
             let mut write_lock = resource_manager.write();
-            self.render_components
-                .push(write_lock.add_model(self.scene_type, result));
+            let renderable = write_lock.add_model(self.scene_type, result);
+            self.register_renderable(renderable);
         }
         Ok(primitive)
     }
@@ -375,10 +1718,22 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
         resource_lock.get_all_command_buffers(self.scene_type);
     }
 
+    fn get_entities(&self) -> Vec<DefaultKey> {
+        self.render_components
+            .iter()
+            .map(|r| r.lock().get_entity())
+            .filter(|entity| !entity.is_null())
+            .collect()
+    }
+
     fn get_model_count(&self) -> Arc<AtomicUsize> {
         self.counts.model_count.clone()
     }
 
+    fn get_render_features(&self) -> RenderFeatures {
+        self.render_features
+    }
+
     fn get_scene_name(&self) -> &str {
         self.scene_name.as_str()
     }
@@ -457,7 +1812,57 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
         *world_matrix = new_world_matrix;
     }*/
 
+    fn input_button(&self, button: MouseButton, x: f64, y: f64, element_state: ElementState) {
+        if !self.loaded
+            || button != MouseButton::Left
+            || element_state != ElementState::Pressed
+            || self.is_cutscene_playing()
+        {
+            return;
+        }
+        let (entity, point) = match self.pick_hit(x, y) {
+            Some(hit) => hit,
+            None => return,
+        };
+        if entity != self.terrain_entity {
+            self.selected_entity.set(entity);
+            return;
+        }
+        // 乗っている間は乗り物をキーで操作するので、クリックで移動しない。
+        // While riding, the mount is steered with keys, so clicking doesn't move the player.
+        if self.is_local_player_riding() {
+            return;
+        }
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        if let Some((_, controller)) = entities_lock.iter_mut::<PlayerController>().next() {
+            controller.move_target = Some(point);
+            let audio_system = self
+                .audio_system
+                .upgrade()
+                .expect("Failed to upgrade audio system handle.");
+            if let Err(e) = audio_system.borrow_mut().play_sound_at(MOVE_SOUND, point) {
+                log::debug!("Failed to play sound: {}", e);
+            }
+        }
+    }
+
     async fn input_key(&self, key: VirtualKeyCode, element_state: ElementState) {
+        // カットシーンの間は操作を受け付けず、Escapeで飛ばせる。
+        // Controls are ignored during a cutscene, which can be skipped with Escape.
+        if self.is_cutscene_playing() {
+            if (key, element_state) == (VirtualKeyCode::Escape, ElementState::Pressed) {
+                self.timeline_system
+                    .upgrade()
+                    .expect("Failed to upgrade timeline system handle.")
+                    .borrow_mut()
+                    .skip();
+            }
+            return;
+        }
         let player = {
             let network_system = self
                 .network_system
@@ -470,52 +1875,63 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
         };
 
         let mut player_lock = player.lock().await;
+        if (key, element_state) == (MOUNT_KEY, ElementState::Pressed) {
+            self.toggle_mount(&player_lock.player_id);
+            return;
+        }
+        if self.is_local_player_riding()
+            && self
+                .mount_system
+                .borrow_mut()
+                .handle_key(key, element_state)
+        {
+            return;
+        }
         if let Some(state) = player_lock.state.as_mut() {
             let world_matrix = state.state.as_mut().and_then(|e| e.world_matrix.as_mut());
             if let Some(wm) = world_matrix {
-                let (rotation_x, mut rotation_y, rotation_z) =
-                    (wm.rotation[0], wm.rotation[1], wm.rotation[2]);
-                let (mut x, y, mut z) = (wm.position[0], wm.position[1], wm.position[2]);
-                let scale = wm.scale.clone();
+                let mut position_info = PositionInfo::from(&*wm);
+                let forward = direction_from_yaw_pitch(position_info.rotation.y, 0.0);
                 match (key, element_state) {
                     (VirtualKeyCode::A, ElementState::Pressed) => {
-                        rotation_y -= 1.0_f32.to_radians();
+                        position_info.rotation.y -= 1.0_f32.to_radians();
                     }
                     (VirtualKeyCode::D, ElementState::Pressed) => {
-                        rotation_y += 1.0_f32.to_radians();
+                        position_info.rotation.y += 1.0_f32.to_radians();
                     }
                     (VirtualKeyCode::W, ElementState::Pressed) => {
-                        x += rotation_y.sin();
-                        z += rotation_y.cos();
+                        position_info.position += forward;
                     }
                     (VirtualKeyCode::S, ElementState::Pressed) => {
-                        x -= rotation_y.sin();
-                        z -= rotation_y.cos();
+                        position_info.position -= forward;
                     }
                     _ => {}
                 }
 
                 {
-                    let camera = self
-                        .camera
+                    // 高さは物理演算が決めるため、ローカルプレイヤーのトランスフォームから取る。
+                    // The height is decided by physics, so take it from the local player's transform.
+                    let entities = self
+                        .entities
                         .upgrade()
-                        .expect("Failed to upgrade camera handle.");
-                    let mut borrowed_camera = camera.borrow_mut();
-                    borrowed_camera.target.x = x;
-                    borrowed_camera.target.y = y;
-                    borrowed_camera.target.z = z;
-                    borrowed_camera.position.x = x;
-                    borrowed_camera.position.y = y + 10.0;
-                    borrowed_camera.position.z = z - 10.0;
+                        .expect("Failed to upgrade entities handle.");
+                    let mut entities_lock = entities.borrow_mut();
+                    let local_entity = entities_lock
+                        .iter::<PlayerController>()
+                        .next()
+                        .map(|(entity, _)| entity);
+                    if let Some(transform) =
+                        local_entity.and_then(|e| entities_lock.get_mut::<Transform>(e))
+                    {
+                        position_info.position.y = transform.position.y;
+                        transform.position = position_info.position;
+                        transform.rotation = position_info.rotation;
+                    }
                 }
 
-                let new_position = vec![x, y, z];
-                let world_matrix = WorldMatrix {
-                    position: new_position,
-                    scale,
-                    rotation: vec![rotation_x, rotation_y, rotation_z],
-                };
-                *wm = world_matrix;
+                self.follow_local_player(position_info.position);
+
+                *wm = WorldMatrix::from(position_info);
             }
         }
     }
@@ -525,19 +1941,23 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
             .network_system
             .upgrade()
             .expect("Failed to upgrade network system handle.");
-        let mut room_state = None;
-        loop {
-            let mut ns_lock = network_system.write().await;
-            if let Some(recv) = ns_lock.progress_recv.as_mut() {
-                if let Ok(state) = recv.await {
-                    room_state = Some(state);
-                    break;
-                }
-            }
-        }
-
-        let room_state = room_state.expect("Failed to get room state from receiver.");
+        // ロックを持ったまま待つと他のシステムが止まってしまうため、受信側を取り出してから待つ。
+        // Take the receiver out before waiting, so other systems aren't blocked on the lock.
+        let receiver = network_system.write().await.progress_recv.take();
+        let room_state = match receiver {
+            Some(recv) => recv.await.map_err(|_| {
+                anyhow::anyhow!("Connection to the server was lost before the game started.")
+            })?,
+            None => return Err(anyhow::anyhow!("Game progress has not been started.")),
+        };
         let players = room_state.players;
+        let local_player_id = {
+            let ns = network_system.read().await;
+            match ns.logged_user.as_ref() {
+                Some(user) => user.lock().await.player_id.clone(),
+                None => String::new(),
+            }
+        };
         /*for (player_no, player) in players.iter().enumerate() {
             let world_matrix = &player.state.state.world_matrix;
             let position: Vec3A = Vec3A::new(
@@ -570,22 +1990,35 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
             if let Some(state) = player.state.as_ref() {
                 if let Some(entity_state) = state.state.as_ref() {
                     if let Some(world_matrix) = entity_state.world_matrix.as_ref() {
-                        let position: Vec3A = Vec3A::new(
-                            world_matrix.position[0],
-                            world_matrix.position[1],
-                            world_matrix.position[2],
-                        );
-                        let scale: Vec3A = Vec3A::new(
-                            world_matrix.scale[0],
-                            world_matrix.scale[1],
-                            world_matrix.scale[2],
-                        );
-                        let rotation: Vec3A = Vec3A::new(
-                            world_matrix.rotation[0],
-                            world_matrix.rotation[1],
-                            world_matrix.rotation[2],
+                        let PositionInfo {
+                            position,
+                            scale,
+                            rotation,
+                        } = PositionInfo::from(world_matrix);
+                        let rotation = Vec3A::new(
+                            rotation.x.to_degrees(),
+                            rotation.y.to_degrees(),
+                            rotation.z.to_degrees(),
                         );
                         let entity = self.add_entity(&format!("Player {}", player_no + 1));
+                        let is_local = player.player_id == local_player_id;
+                        {
+                            let entities = self
+                                .entities
+                                .upgrade()
+                                .expect("Failed to upgrade entities handle.");
+                            let mut entities_lock = entities.borrow_mut();
+                            entities_lock.insert(
+                                entity,
+                                NetworkReplicated::new(player.player_id.clone(), is_local),
+                            );
+                            entities_lock
+                                .insert(entity, Transform::from(PositionInfo::from(world_matrix)));
+                            if is_local {
+                                entities_lock.insert(entity, PlayerController::new());
+                                self.spawn_point.set(Some(PositionInfo::from(world_matrix)));
+                            }
+                        }
                         self.add_model(
                             "./models/tank/tank.gltf",
                             position,
@@ -599,6 +2032,45 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
             }
         }
 
+        // 各プレイヤーの横に乗り物を置く。IDは部屋の並び順で決まるので、全てのクライアントで同じになる。
+        // Place a mount beside each player. IDs follow the order in the room, so they match on every client.
+        for (player_no, player) in players.iter().enumerate() {
+            let world_matrix = match player
+                .state
+                .as_ref()
+                .and_then(|s| s.state.as_ref())
+                .and_then(|s| s.world_matrix.as_ref())
+            {
+                Some(wm) => wm,
+                None => continue,
+            };
+            let mut position_info = PositionInfo::from(world_matrix);
+            position_info.position.x += MOUNT_RANGE * 0.5;
+            let entity = self.add_entity(&format!("Mount {}", player_no + 1));
+            {
+                let entities = self
+                    .entities
+                    .upgrade()
+                    .expect("Failed to upgrade entities handle.");
+                let mut entities_lock = entities.borrow_mut();
+                entities_lock.insert(entity, Mount::new(&format!("mount-{}", player_no + 1)));
+                entities_lock.insert(entity, Transform::from(position_info));
+            }
+            let rotation = position_info.rotation;
+            self.add_model(
+                MOUNT_MODEL,
+                position_info.position,
+                position_info.scale,
+                Vec3A::new(
+                    rotation.x.to_degrees(),
+                    rotation.y.to_degrees(),
+                    rotation.z.to_degrees(),
+                ),
+                Vec4::new(0.6, 0.6, 0.6, 1.0),
+                entity,
+            )?;
+        }
+
         //let mr_incredible = self.add_entity("Mr.Incredible");
         /*self.add_model(
             "./models/mr.incredible/Mr.Incredible.glb",
@@ -617,38 +2089,319 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
             Vec4::new(1.0, 1.0, 1.0, 1.0),
             bison,
         )?;
+        let cesium_man = self.add_entity("Cesium Man");
         self.add_skinned_model(
             "./models/cesiumMan/CesiumMan.glb",
             Vec3A::new(5.0, 0.0, 5.0),
             Vec3A::new(2.0, 2.0, 2.0),
             Vec3A::new(0.0, 180.0, 0.0),
             Vec4::new(1.0, 1.0, 1.0, 1.0),
-        )?;*/
-        //let water_pos = std::env::var("WATER_POS")?.parse::<f32>()?;
-        //let water_height = std::env::var("WATER_HEIGHT")?.parse::<f32>()?;
-        //let water_scale = std::env::var("WATER_SCALE")?.parse::<f32>()?;
-        /*self.add_geometric_primitive(
-            PrimitiveType::Rect,
-            None,
-            Vec3A::new(water_pos, water_height, water_pos),
-            Vec3A::new(water_scale, 1.0, water_scale),
-            Vec3A::zero(),
-            Vec4::new(0.0, 0.0, 1.0, 1.0),
-            Some(ShaderType::Water),
-        )?;*/
+            cesium_man,
+        )?;
+        self.attach_animator(cesium_man, "./models/cesiumMan/animator.json")?;*/
+        // 水面は大きさが設定されている時だけ置く。
+        // The water surface is only placed when its scale is set.
+        if let Some((water_pos, water_height, water_scale)) = self.settings.get_water() {
+            let water = self.add_entity("Water");
+            self.add_geometric_primitive(
+                PrimitiveType::Rect,
+                None,
+                Vec3A::new(water_pos, water_height, water_pos),
+                Vec3A::new(water_scale, 1.0, water_scale),
+                Vec3A::zero(),
+                Vec4::new(0.0, 0.0, 1.0, 1.0),
+                Some(ShaderType::Water),
+                water,
+            )?;
+            self.physics_system
+                .upgrade()
+                .expect("Failed to upgrade physics system handle.")
+                .borrow_mut()
+                .add_water_volume(
+                    WaterVolume::new(
+                        Vec2::new(water_pos - water_scale, water_pos - water_scale),
+                        Vec2::new(water_pos + water_scale, water_pos + water_scale),
+                        water_height,
+                    )
+                    .with_wave(Wave::new(0.3, 12.0, 2.0, Vec2::new(1.0, 0.4))),
+                );
+        }
         //self.generate_terrain(0, 0)?;
+
+        {
+            // 音声は任意のため、読み込めなくてもゲームは続ける。
+            // Audio is optional, so the game continues even if it fails to load.
+            let audio_system = self
+                .audio_system
+                .upgrade()
+                .expect("Failed to upgrade audio system handle.");
+            let mut audio_lock = audio_system.borrow_mut();
+            if let Err(e) = audio_lock.load_sound(MOVE_SOUND, "./audio/move.ogg") {
+                log::warn!("Failed to load sound effect: {}", e);
+            }
+            if let Err(e) = self
+                .footstep_system
+                .get_mut()
+                .load(SURFACE_MATERIALS, &mut audio_lock)
+            {
+                log::warn!("Failed to load surface materials: {}", e);
+            }
+            // 層になったBGMがなければ、一曲だけのBGMを流す。
+            // Without layered music, a single background track is played instead.
+            let music_director = self.music_director.get_mut();
+            if let Err(e) = music_director.load(MUSIC_SETS, &self.scene_name, &mut audio_lock) {
+                log::warn!("Failed to load layered music: {}", e);
+                if let Err(e) = audio_lock.play_music("./audio/game.ogg", true) {
+                    log::warn!("Failed to play background music: {}", e);
+                }
+            }
+        }
+        {
+            let timeline_system = self
+                .timeline_system
+                .upgrade()
+                .expect("Failed to upgrade timeline system handle.");
+            // 起動時に`--scene`で指定したカットシーンがあれば、導入の代わりに再生する。
+            // A cutscene given with `--scene` at launch is played instead of the intro.
+            let cutscene = &self.settings.scene_file;
+            if let Err(e) = timeline_system.borrow_mut().play_file(cutscene) {
+                log::warn!("Failed to play cutscene {}: {}", cutscene, e);
+            }
+        }
+        {
+            let graphics = self
+                .graphics
+                .upgrade()
+                .expect("Failed to upgrade graphics handle.");
+            if let Err(e) = Graphics::create_sprite_atlas(graphics, &self.sprite_atlas) {
+                log::warn!("Failed to upload the sprite atlas: {}", e);
+            }
+        }
         self.loaded = true;
         Ok(())
     }
 
+    async fn load_state(&self, save: &SaveGame) -> anyhow::Result<()> {
+        if !self.loaded {
+            return Err(anyhow::anyhow!("{} is still loading.", self.scene_name));
+        }
+        if save.scene_name != self.scene_name {
+            return Err(anyhow::anyhow!(
+                "The save is for {}, not {}.",
+                save.scene_name,
+                self.scene_name
+            ));
+        }
+        if let Some(progress) = save.progress {
+            let network_system = self
+                .network_system
+                .upgrade()
+                .expect("Failed to upgrade network system handle.");
+            let player = network_system.read().await.logged_user.clone();
+            if let Some(player) = player {
+                let mut player_lock = player.lock().await;
+                let state = player_lock
+                    .state
+                    .as_mut()
+                    .and_then(|player_state| player_state.state.as_mut());
+                if let Some(state) = state {
+                    state.max_hp = progress.max_hp;
+                    state.current_hp = progress.get_loaded_hp();
+                    state.max_sp = progress.max_sp;
+                    state.current_sp = progress.current_sp.min(progress.max_sp);
+                    state.is_alive = true;
+                }
+            }
+        }
+        self.is_respawn_pending.set(false);
+        self.restore_state(save);
+        Ok(())
+    }
+
+    fn pick(&self, x: f64, y: f64) -> Option<DefaultKey> {
+        self.pick_hit(x, y).map(|(entity, _)| entity)
+    }
+
+    fn poll_tasks(&mut self) -> anyhow::Result<LoadingProgress> {
+        self.waitable_tasks.poll_tasks()
+    }
+
+    fn raycast(&self, ray: &Ray) -> Option<(DefaultKey, f32)> {
+        self.entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.")
+            .borrow()
+            .raycast(ray)
+    }
+
+    fn register_commands(&self, console: &mut Console) {
+        console.register(
+            "spawn",
+            "<model> [x y z]",
+            "Spawn a model at a position or at the local player.",
+        );
+        console.register(
+            "teleport",
+            "[x z | x y z]",
+            "Move the local player, or back to where it spawned.",
+        );
+        console.register(
+            "gizmos",
+            "",
+            "Toggle drawing bounding boxes and buoyancy points.",
+        );
+        console.register(
+            "minimap",
+            "[zoom]",
+            "Toggle the minimap, or set its zoom from 1 to 8.",
+        );
+    }
+
+    fn remove_entity(&mut self, entity: DefaultKey) -> anyhow::Result<()> {
+        if entity.is_null() {
+            return Err(anyhow::anyhow!("Cannot remove a null entity."));
+        }
+        // プールのモデルは解放せずプールに戻す。
+        // Pooled models are returned to their pool instead of being disposed.
+        if self.release_pooled(entity) {
+            return Ok(());
+        }
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        entities.borrow_mut().despawn(entity);
+        if let Some(physics_system) = self.physics_system.upgrade() {
+            physics_system.borrow_mut().remove_body(entity);
+        }
+
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let mut resource_lock = resource_manager.write();
+        let removed = resource_lock.remove_model(self.scene_type, entity);
+        self.render_components
+            .retain(|r| r.lock().get_entity() != entity);
+        for renderable in removed.iter() {
+            self.counts
+                .release_ssbo_index(renderable.lock().get_ssbo_index());
+        }
+        resource_lock.get_all_command_buffers(self.scene_type);
+        Ok(())
+    }
+
+    fn remove_renderable(&mut self, ssbo_index: usize) -> anyhow::Result<()> {
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let mut resource_lock = resource_manager.write();
+        let removed = resource_lock
+            .remove_renderable(self.scene_type, ssbo_index)
+            .ok_or_else(|| anyhow::anyhow!("No model uses SSBO index {}.", ssbo_index))?;
+        self.render_components.retain(|r| !Arc::ptr_eq(r, &removed));
+        self.counts.release_ssbo_index(ssbo_index);
+        resource_lock.get_all_command_buffers(self.scene_type);
+        drop(resource_lock);
+
+        // エンティティが取り除いたモデルを指したままにならないようにする。
+        // Make sure the entity doesn't keep pointing at the removed model.
+        let entity = removed.lock().get_entity();
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        let is_attached = entities_lock
+            .get::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity)
+            .map(|r| Arc::ptr_eq(&r.0, &removed))
+            .unwrap_or(false);
+        if is_attached {
+            entities_lock.remove::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity);
+        }
+        Ok(())
+    }
+
+    async fn save_state(&self) -> anyhow::Result<SaveGame> {
+        if !self.loaded {
+            return Err(anyhow::anyhow!("{} is still loading.", self.scene_name));
+        }
+        let mut save = self.capture_state();
+        let network_system = self
+            .network_system
+            .upgrade()
+            .expect("Failed to upgrade network system handle.");
+        let player = network_system.read().await.logged_user.clone();
+        if let Some(player) = player {
+            let player_lock = player.lock().await;
+            save.progress = player_lock
+                .state
+                .as_ref()
+                .and_then(|player_state| player_state.state.as_ref())
+                .map(|state| SavedProgress {
+                    current_hp: state.current_hp,
+                    max_hp: state.max_hp,
+                    current_sp: state.current_sp,
+                    max_sp: state.max_sp,
+                });
+        }
+        Ok(save)
+    }
+
     fn render(&self, _delta_time: f64) -> anyhow::Result<()> {
         let graphics = self
             .graphics
             .upgrade()
             .expect("Failed to upgrade Weak of Graphics for rendering.");
+        let (view_projection, eye) = {
+            let camera = self
+                .camera
+                .upgrade()
+                .expect("Failed to upgrade camera handle.");
+            let camera_lock = camera.borrow();
+            (
+                camera_lock.get_projection_matrix() * camera_lock.get_view_matrix(),
+                camera_lock.position,
+            )
+        };
+        let frustum = Frustum::new(view_projection);
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let visible = {
+            let entities_lock = entities.borrow();
+            // 空間索引は一度だけ調べ、その結果でオクルージョンクエリの候補と描画するモデルを選ぶ。
+            // The spatial index is queried once, and its result picks both the occlusion query candidates and the models to draw.
+            let visible_entities = entities_lock.get_spatial_index().query_frustum(&frustum);
+            let candidates = visible_entities
+                .iter()
+                .filter_map(|entity| {
+                    entities_lock
+                        .get_world_bounds(*entity)
+                        .map(|bounds| (*entity, bounds))
+                })
+                .collect::<Vec<_>>();
+            // 地形などに隠れたモデルは、前のフレームのオクルージョンクエリの結果で描画を省く。
+            // Models hidden behind terrain and such are skipped using the results of earlier frames' occlusion queries.
+            let occluded = graphics
+                .read()
+                .queue_occlusion_queries(candidates.as_slice(), eye);
+            let visible_entities = visible_entities.into_iter().collect::<HashSet<_>>();
+            self.render_components
+                .iter()
+                .filter(|r| {
+                    let entity = r.lock().get_entity();
+                    !entities_lock.is_culled(entity, &visible_entities)
+                        && !occluded.contains(&entity)
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
         {
             let graphics_lock = graphics.read();
-            graphics_lock.render(&self.render_components)?;
+            graphics_lock.render(&visible)?;
         }
         Ok(())
     }
@@ -657,6 +2410,52 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
         self.scene_name = scene_name.to_string();
     }
 
+    fn set_world_bounds(&mut self, world_bounds: Option<WorldBounds>) {
+        self.world_bounds.set(world_bounds);
+        self.bounds_damage.set(0.0);
+        self.boundary_warning.set(0.0);
+    }
+
+    fn unload(&mut self) -> anyhow::Result<()> {
+        // 読み込み中のモデルが片付けた後に追加されないように、先に全てのタスクを待つ。
+        // Wait for all tasks first so that models still loading aren't added after the teardown.
+        self.wait_for_all_tasks()?;
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let removed = resource_manager
+            .write()
+            .remove_scene_models(self.scene_type);
+
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        for renderable in removed.iter() {
+            let entity = renderable.lock().get_entity();
+            if !entity.is_null() && entities_lock.despawn(entity) {
+                if let Some(physics_system) = self.physics_system.upgrade() {
+                    physics_system.borrow_mut().remove_body(entity);
+                }
+            }
+        }
+        drop(entities_lock);
+
+        self.render_components.clear();
+        self.pools.clear();
+        self.pending_clones.clear();
+        self.terrain_entity = DefaultKey::null();
+        self.selected_entity.set(DefaultKey::null());
+        self.world_bounds.set(None);
+        self.bounds_damage.set(0.0);
+        self.boundary_warning.set(0.0);
+        self.counts.reset();
+        self.loaded = false;
+        Ok(())
+    }
+
     async fn update(&self, delta_time: f64) -> anyhow::Result<()> {
         if !self.loaded {
             return Ok(());
@@ -665,54 +2464,100 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
             .graphics
             .upgrade()
             .expect("Failed to upgrade graphics handle.");
-        let network_system = self
-            .network_system
-            .upgrade()
-            .expect("Failed to upgrade network system handle.");
 
-        for (index, (_, key)) in self.player_entities.iter().enumerate() {
-            let model = self
-                .render_components
-                .iter()
-                .find(|r| r.lock().get_entity() == *key);
-            if let Some(r) = model.as_ref() {
+        let player_states = match self.network_system.upgrade() {
+            Some(network_system) => {
                 let ns = network_system.read().await;
                 let room_state = ns.room_state.lock().await;
-                let mut locked_renderable = r.lock();
-                let player = room_state
+                room_state
                     .players
-                    .get(index)
-                    .expect("Failed to get player.");
-                let player_state = player.state.as_ref().expect("Failed to get player state.");
-                let entity_state = player_state
-                    .state
-                    .as_ref()
-                    .expect("Failed to get entity state.");
-                let world_matrix = entity_state
-                    .world_matrix
-                    .as_ref()
-                    .expect("Failed to get world matrix.");
-                locked_renderable.set_position_info(PositionInfo {
-                    position: Vec3A::new(
-                        world_matrix.position[0],
-                        world_matrix.position[1],
-                        world_matrix.position[2],
-                    ),
-                    scale: Vec3A::new(
-                        world_matrix.scale[0],
-                        world_matrix.scale[1],
-                        world_matrix.scale[2],
-                    ),
-                    rotation: Vec3A::new(
-                        world_matrix.rotation[0],
-                        world_matrix.rotation[1],
-                        world_matrix.rotation[2],
-                    ),
-                });
+                    .iter()
+                    .filter_map(|player| {
+                        let state = player.state.as_ref()?.state.clone()?;
+                        Some((player.player_id.clone(), state))
+                    })
+                    .collect::<HashMap<_, _>>()
+            }
+            None => HashMap::new(),
+        };
+
+        let minimap_blips;
+        let status_targets;
+        let (local_position, particle_dispatches) = {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            let mut entities_lock = entities.borrow_mut();
+            let particle_dispatches = self
+                .particle_system
+                .borrow_mut()
+                .update(&mut *entities_lock, delta_time as f32);
+            if self.show_gizmos.get() {
+                self.draw_gizmos(&*entities_lock, &mut self.debug_draw.borrow_mut());
             }
+            entities_lock.sync_renderables();
+            entities_lock.update_spatial_index();
+            minimap_blips = Self::collect_minimap_blips(&*entities_lock);
+            status_targets = Self::collect_status_targets(&*entities_lock, &player_states);
+            // カメラは補間した位置を追い、シミュレーションの間隔でがたつかないようにする。
+            // The camera follows the interpolated position so it doesn't stutter at the simulation rate.
+            let local_position = entities_lock
+                .iter::<PlayerController>()
+                .next()
+                .and_then(|(entity, _)| entities_lock.get_interpolated_transform(entity))
+                .map(|transform| transform.position);
+            (local_position, particle_dispatches)
+        };
+        if let Some(position) = local_position {
+            self.follow_local_player(position);
+        }
+        // カメラが動いた後に投影する。
+        // Projected after the camera has moved.
+        let (status_bars, screen_height) = self.project_status_bars(&status_targets);
+        {
+            let audio_system = self
+                .audio_system
+                .upgrade()
+                .expect("Failed to upgrade audio system handle.");
+            self.music_director
+                .borrow_mut()
+                .update(delta_time, &mut audio_system.borrow_mut());
         }
 
+        // 波紋のテクスチャは一枚なので、最初の水面の波紋を描く。
+        // There's a single ripple texture, so the ripples of the first water surface are drawn.
+        let ripple_pixels = self.physics_system.upgrade().and_then(|physics_system| {
+            let physics_lock = physics_system.borrow();
+            physics_lock
+                .get_water_surfaces()
+                .first()
+                .map(|surface| surface.get_ripples().get_pixels())
+        });
+
         let mut graphics_lock = graphics.write();
+        graphics_lock.set_boundary_warning(self.boundary_warning.get());
+        if let Some(pixels) = ripple_pixels {
+            graphics_lock.set_ripple_pixels(pixels);
+        }
+        graphics_lock.set_particle_dispatches(particle_dispatches);
+        graphics_lock.add_debug_lines(self.debug_draw.borrow_mut().take_line_vertices());
+        {
+            // 地図はローカルプレイヤーを中心にし、まだいなければ地図の真ん中を見せる。
+            // The map is centered on the local player, or shows the middle of the map until there is one.
+            let center = local_position.unwrap_or_else(|| self.minimap.get_center());
+            let mut sprite_batch = self.sprite_batch.borrow_mut();
+            for status_bar in status_bars.iter() {
+                status_bar.draw(&mut sprite_batch, &self.sprite_atlas, screen_height);
+            }
+            self.minimap.draw(
+                &mut sprite_batch,
+                &self.sprite_atlas,
+                center,
+                &minimap_blips,
+            );
+            graphics_lock.add_sprites(sprite_batch.take_sprites());
+        }
         graphics_lock.update(delta_time, &self.render_components)?;
         Ok(())
     }
@@ -781,26 +2626,45 @@ impl Scene for GameScene<Graphics, Buffer, CommandBuffer, Image> {
         let mut lock = rm.write();
 
         for model in completed_tasks.models.into_iter() {
-            self.render_components
-                .push(lock.add_model(self.scene_type, model));
+            self.add_bounds(&model);
+            self.add_physics_body(&model)?;
+            let key = get_cache_key(&model.model_name);
+            let renderable = lock.add_model(self.scene_type, model);
+            lock.model_cache.insert(&key, renderable.clone());
+            self.register_renderable(renderable);
         }
         for model in completed_tasks.skinned_models.into_iter() {
-            self.render_components
-                .push(lock.add_model(self.scene_type, model));
+            let renderable = lock.add_model(self.scene_type, model);
+            self.register_renderable(renderable);
         }
         for terrain in completed_tasks.terrains.into_iter() {
-            self.render_components
-                .push(lock.add_model(self.scene_type, terrain));
+            let renderable = lock.add_model(self.scene_type, terrain);
+            self.register_renderable(renderable);
         }
         for primitive in completed_tasks.geometric_primitives.into_iter() {
-            self.render_components
-                .push(lock.add_model(self.scene_type, primitive));
+            let renderable = lock.add_model(self.scene_type, primitive);
+            self.register_renderable(renderable);
         }
         for instance in completed_tasks.instances.into_iter() {
-            self.render_components
-                .push(lock.add_model(self.scene_type, instance));
+            let renderable = lock.add_model(self.scene_type, instance);
+            self.register_renderable(renderable);
         }
         drop(lock);
+
+        for (key, position_info, color, ssbo_index, entity) in
+            std::mem::take(&mut self.pending_clones).into_iter()
+        {
+            let source = rm.read().model_cache.get(&key);
+            match source {
+                Some(source) => {
+                    self.add_model_clone(&source, position_info, color, ssbo_index, entity)
+                }
+                None => log::warn!(
+                    "Model {} was never loaded, so its instance was skipped.",
+                    key
+                ),
+            }
+        }
         drop(rm);
         self.waitable_tasks.clear();
         Ok(())
@@ -826,3 +2690,17 @@ where
     TextureType: 'static + Clone + Disposable,
 {
 }
+
+/// モデルのファイルを探す。そのままのパスになければ、モデルのディレクトリの下にある同じ名前のファイルを使う。<br />
+/// Find a model file. If the path doesn't exist as given, a file with the same name under the models directory is used.
+fn find_model_file(file_name: &str) -> Option<String> {
+    if std::path::Path::new(file_name).is_file() {
+        return Some(file_name.to_string());
+    }
+    std::fs::read_dir(MODELS_DIRECTORY)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join(file_name))
+        .find(|path| path.is_file())
+        .and_then(|path| path.to_str().map(|path| path.to_string()))
+}
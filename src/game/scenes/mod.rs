@@ -1,3 +1,5 @@
 pub mod game_scene;
+pub mod preview_scene;
 pub mod title_scene;
 pub use game_scene::GameScene;
+pub use preview_scene::PreviewScene;
@@ -0,0 +1,580 @@
+use crate::game::graphics::vk::{Buffer, Graphics, Image};
+use crate::game::shared::enums::SceneType;
+use crate::game::shared::structs::{
+    AnimationPose, AssetPreview, GeometricPrimitive, LoadingProgress, PreviewAssetKind,
+    PreviewClip, PrimitiveType, RenderFeatures, SkinnedModel, WaitableTasks,
+};
+use crate::game::structs::{Counts, Model};
+use crate::game::traits::{Disposable, GraphicsBase, Renderable, Scene};
+use crate::game::ui::{Console, ConsoleCommand, ConsoleReply};
+use crate::game::{Bounds, Camera, LockableRenderable, RenderableRef, ResourceManagerWeak, World};
+use ash::vk::CommandBuffer;
+use async_trait::async_trait;
+use glam::f32::{Vec3A, Vec4};
+use parking_lot::RwLock;
+use slotmap::{DefaultKey, Key};
+use std::cell::RefCell;
+use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+
+/// `--preview`で開いた素材を一つだけ表示するシーン。<br />
+/// Scene showing the single asset opened with `--preview`.
+pub struct PreviewScene<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    graphics: Weak<RwLock<ManuallyDrop<GraphicsType>>>,
+    resource_manager: ResourceManagerWeak<GraphicsType, BufferType, CommandType, TextureType>,
+    scene_name: String,
+    counts: Counts,
+    waitable_tasks: WaitableTasks<GraphicsType, BufferType, CommandType, TextureType>,
+    scene_type: SceneType,
+    entities: std::rc::Weak<RefCell<World<GraphicsType, BufferType, CommandType, TextureType>>>,
+    camera: std::rc::Weak<RefCell<Camera>>,
+    preview: std::rc::Weak<RefCell<AssetPreview>>,
+    render_components: Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>,
+    /// モデルの読み込みには`'static`な名前が要るので、最初に読み込む時に一度だけリークする。<br />
+    /// Loading models needs a `'static` name, so the path is leaked once on the first load.
+    asset_path: Option<&'static str>,
+    loaded: bool,
+    /// 地形も水面もないので、影や水面のパスを実行しない。<br />
+    /// There's neither terrain nor water, so shadow and water passes aren't executed.
+    render_features: RenderFeatures,
+}
+
+impl<GraphicsType, BufferType, CommandType, TextureType>
+    PreviewScene<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    pub fn new(
+        resource_manager: ResourceManagerWeak<GraphicsType, BufferType, CommandType, TextureType>,
+        graphics: Weak<RwLock<ManuallyDrop<GraphicsType>>>,
+        entities: std::rc::Weak<RefCell<World<GraphicsType, BufferType, CommandType, TextureType>>>,
+        camera: std::rc::Weak<RefCell<Camera>>,
+        preview: std::rc::Weak<RefCell<AssetPreview>>,
+    ) -> Self {
+        PreviewScene {
+            graphics,
+            resource_manager,
+            scene_name: String::from("PREVIEW_SCENE"),
+            counts: Counts::new(),
+            waitable_tasks: WaitableTasks::new(),
+            scene_type: SceneType::PREVIEW,
+            entities,
+            camera,
+            preview,
+            render_components: vec![],
+            asset_path: None,
+            loaded: false,
+            render_features: RenderFeatures::POST_EFFECTS | RenderFeatures::UI_LAYERS,
+        }
+    }
+
+    /// 描画するモデルを登録し、エンティティに紐付ける。<br />
+    /// Register a model for rendering and attach it to its entity.
+    fn register_renderable(
+        &mut self,
+        renderable: LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>,
+    ) {
+        let entity = renderable.lock().get_entity();
+        if !entity.is_null() {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            entities
+                .borrow_mut()
+                .insert(entity, RenderableRef(renderable.clone()));
+        }
+        self.render_components.push(renderable);
+    }
+
+    /// 読み込んだ素材の境界ボックスをエンティティに追加し、プレビューに知らせる。<br />
+    /// Attach the bounding box of the loaded asset to its entity and pass it to the preview.
+    fn set_bounds(&self, entity: DefaultKey, bounds: Bounds) {
+        let field_of_view = self
+            .camera
+            .upgrade()
+            .expect("Failed to upgrade camera handle.")
+            .borrow()
+            .field_of_view;
+        self.preview
+            .upgrade()
+            .expect("Failed to upgrade preview handle.")
+            .borrow_mut()
+            .set_bounds(bounds, field_of_view);
+        if !entity.is_null() {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            entities.borrow_mut().insert(entity, bounds);
+        }
+    }
+}
+
+impl PreviewScene<Graphics, Buffer, CommandBuffer, Image> {
+    /// モデルの頂点を囲む境界ボックスを、モデルの位置と大きさで変換して求める。<br />
+    /// Get the bounding box of the model's vertices, transformed by the model's position and scale.
+    fn get_model_bounds(model: &Model<Graphics, Buffer, CommandBuffer, Image>) -> Option<Bounds> {
+        let meshes = model.meshes.iter().map(|m| m.lock()).collect::<Vec<_>>();
+        Bounds::from_primitives(meshes.iter().flat_map(|m| m.primitives.iter()))
+            .map(|bounds| bounds.transform(model.get_world_matrix()))
+    }
+
+    /// アニメーションを持つglTFはスキンモデルとして読み込む。<br />
+    /// glTFs with animations are loaded as skinned models.
+    fn add_skinned_model(
+        &mut self,
+        file_name: &'static str,
+        entity: DefaultKey,
+    ) -> anyhow::Result<()> {
+        let ssbo_index = self.counts.acquire_ssbo_index();
+        let task = SkinnedModel::new(
+            file_name,
+            self.graphics.clone(),
+            Vec3A::zero(),
+            Vec3A::one(),
+            Vec3A::zero(),
+            Vec4::one(),
+            ssbo_index,
+            self.counts.model_count.clone(),
+            entity,
+        )?;
+        self.waitable_tasks.skinned_model_tasks.push(task);
+        Ok(())
+    }
+
+    /// テクスチャを縦横比に合わせた板に貼って表示する。<br />
+    /// Show a texture on a quad matching its aspect ratio.
+    fn add_texture_quad(
+        &mut self,
+        file_name: &'static str,
+        size: (u32, u32),
+        entity: DefaultKey,
+    ) -> anyhow::Result<()> {
+        let (width, height) = (size.0.max(1) as f32, size.1.max(1) as f32);
+        let scale = if width >= height {
+            Vec3A::new(1.0, 1.0, height / width)
+        } else {
+            Vec3A::new(width / height, 1.0, 1.0)
+        };
+        let model_index = self.counts.model_count.fetch_add(1, Ordering::SeqCst);
+        let ssbo_index = self.counts.acquire_ssbo_index();
+        let task = GeometricPrimitive::new(
+            self.graphics.clone(),
+            PrimitiveType::Rect,
+            Some(file_name),
+            model_index,
+            ssbo_index,
+            Vec3A::zero(),
+            scale,
+            Vec3A::zero(),
+            Vec4::one(),
+            None,
+            entity,
+        )?;
+        self.waitable_tasks.geometric_primitive_tasks.push(task);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Scene for PreviewScene<Graphics, Buffer, CommandBuffer, Image> {
+    fn add_entity(&mut self, entity_name: &str) -> DefaultKey {
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        entities_lock.spawn(entity_name)
+    }
+
+    /// 読み込み直した時に古いモデルを使い回さないように、モデルキューを探さずに必ずファイルから読み込む。<br />
+    /// Always load from the file without searching the model queue, so reloading never reuses the old model.
+    fn add_model(
+        &mut self,
+        file_name: &'static str,
+        position: Vec3A,
+        scale: Vec3A,
+        rotation: Vec3A,
+        color: Vec4,
+        entity: DefaultKey,
+    ) -> anyhow::Result<()> {
+        let ssbo_index = self.counts.acquire_ssbo_index();
+        let task = Model::new(
+            file_name,
+            self.graphics.clone(),
+            position,
+            scale,
+            rotation,
+            color,
+            self.counts.model_count.clone(),
+            ssbo_index,
+            true,
+            entity,
+        )?;
+        self.waitable_tasks.model_tasks.push(task);
+        Ok(())
+    }
+
+    fn cancel_tasks(&mut self) -> anyhow::Result<()> {
+        if self.waitable_tasks.is_empty() {
+            return Ok(());
+        }
+        self.waitable_tasks.cancel();
+        self.unload()
+    }
+
+    fn create_ssbo(&self) -> anyhow::Result<()> {
+        for renderable in self.render_components.iter() {
+            renderable.lock().create_ssbo()?;
+        }
+        Ok(())
+    }
+
+    fn execute_command(
+        &mut self,
+        command: &ConsoleCommand,
+    ) -> Option<anyhow::Result<ConsoleReply>> {
+        match command.name.as_str() {
+            "reload" => {
+                self.preview
+                    .upgrade()
+                    .expect("Failed to upgrade preview handle.")
+                    .borrow_mut()
+                    .request_reload();
+                Some(Ok(ConsoleReply::new("Reloading the asset.")))
+            }
+            _ => None,
+        }
+    }
+
+    fn get_command_buffers(&self) {
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let mut resource_lock = resource_manager.write();
+        resource_lock.get_all_command_buffers(self.scene_type);
+    }
+
+    fn get_entities(&self) -> Vec<DefaultKey> {
+        self.render_components
+            .iter()
+            .map(|r| r.lock().get_entity())
+            .filter(|entity| !entity.is_null())
+            .collect()
+    }
+
+    fn get_model_count(&self) -> Arc<AtomicUsize> {
+        self.counts.model_count.clone()
+    }
+
+    fn get_render_features(&self) -> RenderFeatures {
+        self.render_features
+    }
+
+    fn get_scene_name(&self) -> &str {
+        &self.scene_name
+    }
+
+    fn get_scene_type(&self) -> SceneType {
+        self.scene_type
+    }
+
+    fn initialize(&mut self) {}
+
+    fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    async fn load_content(&mut self) -> anyhow::Result<()> {
+        let preview = self
+            .preview
+            .upgrade()
+            .expect("Failed to upgrade preview handle.");
+        let (path, info) = {
+            let preview_lock = preview.borrow();
+            (preview_lock.path.clone(), preview_lock.info.clone())
+        };
+        let file_name = match self.asset_path {
+            Some(file_name) => file_name,
+            None => {
+                let file_name: &'static str = Box::leak(path.into_boxed_str());
+                self.asset_path = Some(file_name);
+                file_name
+            }
+        };
+        let entity = self.add_entity("PreviewAsset");
+        match info.kind {
+            PreviewAssetKind::Model if info.animation_count > 0 => {
+                self.add_skinned_model(file_name, entity)?
+            }
+            PreviewAssetKind::Model => self.add_model(
+                file_name,
+                Vec3A::zero(),
+                Vec3A::one(),
+                Vec3A::zero(),
+                Vec4::one(),
+                entity,
+            )?,
+            PreviewAssetKind::Texture => {
+                self.add_texture_quad(file_name, info.texture_size.unwrap_or((1, 1)), entity)?
+            }
+        }
+        self.loaded = true;
+        Ok(())
+    }
+
+    fn poll_tasks(&mut self) -> anyhow::Result<LoadingProgress> {
+        self.waitable_tasks.poll_tasks()
+    }
+
+    fn register_commands(&self, console: &mut Console) {
+        console.register("reload", "", "Reload the previewed asset from its file.");
+    }
+
+    fn remove_entity(&mut self, entity: DefaultKey) -> anyhow::Result<()> {
+        if entity.is_null() {
+            return Err(anyhow::anyhow!("Cannot remove a null entity."));
+        }
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        entities.borrow_mut().despawn(entity);
+
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let mut resource_lock = resource_manager.write();
+        let removed = resource_lock.remove_model(self.scene_type, entity);
+        self.render_components
+            .retain(|r| r.lock().get_entity() != entity);
+        for renderable in removed.iter() {
+            self.counts
+                .release_ssbo_index(renderable.lock().get_ssbo_index());
+        }
+        resource_lock.get_all_command_buffers(self.scene_type);
+        Ok(())
+    }
+
+    fn remove_renderable(&mut self, ssbo_index: usize) -> anyhow::Result<()> {
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let mut resource_lock = resource_manager.write();
+        let removed = resource_lock
+            .remove_renderable(self.scene_type, ssbo_index)
+            .ok_or_else(|| anyhow::anyhow!("No model uses SSBO index {}.", ssbo_index))?;
+        self.render_components.retain(|r| !Arc::ptr_eq(r, &removed));
+        self.counts.release_ssbo_index(ssbo_index);
+        resource_lock.get_all_command_buffers(self.scene_type);
+        drop(resource_lock);
+
+        let entity = removed.lock().get_entity();
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        let is_attached = entities_lock
+            .get::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity)
+            .map(|r| Arc::ptr_eq(&r.0, &removed))
+            .unwrap_or(false);
+        if is_attached {
+            entities_lock.remove::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity);
+        }
+        Ok(())
+    }
+
+    fn render(&self, _delta_time: f64) -> anyhow::Result<()> {
+        let graphics = self
+            .graphics
+            .upgrade()
+            .expect("Failed to upgrade Weak of Graphics for rendering.");
+        {
+            let graphics_lock = graphics.read();
+            graphics_lock.render(&self.render_components)?;
+        }
+        Ok(())
+    }
+
+    fn set_scene_name(&mut self, scene_name: &str) {
+        self.scene_name = scene_name.to_string();
+    }
+
+    fn unload(&mut self) -> anyhow::Result<()> {
+        self.wait_for_all_tasks()?;
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let removed = resource_manager
+            .write()
+            .remove_scene_models(self.scene_type);
+
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        for renderable in removed.iter() {
+            let entity = renderable.lock().get_entity();
+            if !entity.is_null() {
+                entities_lock.despawn(entity);
+            }
+        }
+        drop(entities_lock);
+
+        self.render_components.clear();
+        self.counts.reset();
+        self.loaded = false;
+        Ok(())
+    }
+
+    /// アニメーションを進めてポーズをモデルに渡し、カメラをオービットカメラの位置に置く。<br />
+    /// Advance the animation and pass the pose to the model, then place the camera at the orbit camera's position.
+    async fn update(&self, delta_time: f64) -> anyhow::Result<()> {
+        if !self.loaded {
+            return Ok(());
+        }
+        let (sample, orbit) = {
+            let preview = self
+                .preview
+                .upgrade()
+                .expect("Failed to upgrade preview handle.");
+            let mut preview_lock = preview.borrow_mut();
+            preview_lock.playback.advance(delta_time as f32);
+            (preview_lock.playback.get_sample(), preview_lock.orbit)
+        };
+        if let Some(sample) = sample {
+            for renderable in self.render_components.iter() {
+                renderable.lock().set_animation_pose(AnimationPose {
+                    current: sample.clone(),
+                    previous: None,
+                    blend_weight: 1.0,
+                });
+            }
+        }
+        {
+            let camera = self
+                .camera
+                .upgrade()
+                .expect("Failed to upgrade camera handle.");
+            let mut camera_lock = camera.borrow_mut();
+            camera_lock.position = orbit.get_position();
+            camera_lock.target = orbit.target;
+        }
+        let graphics = self
+            .graphics
+            .upgrade()
+            .expect("Failed to upgrade graphics handle.");
+        let mut graphics_lock = graphics.write();
+        graphics_lock.update(delta_time, &self.render_components)?;
+        Ok(())
+    }
+
+    fn wait_for_all_tasks(&mut self) -> anyhow::Result<()> {
+        let completed_tasks = self.waitable_tasks.wait_for_all_tasks()?;
+        let rm = self.resource_manager.upgrade();
+        if rm.is_none() {
+            return Err(anyhow::anyhow!(
+                "Failed to lock resource manager for waiting tasks."
+            ));
+        }
+        let rm = rm.unwrap();
+        let mut lock = rm.write();
+        for model in completed_tasks.models.into_iter() {
+            if let Some(bounds) = Self::get_model_bounds(&model) {
+                self.set_bounds(model.entity, bounds);
+            }
+            let renderable = lock.add_model(self.scene_type, model);
+            self.register_renderable(renderable);
+        }
+        for model in completed_tasks.skinned_models.into_iter() {
+            let world_matrix = model.get_world_matrix();
+            let bounds = model
+                .skinned_meshes
+                .iter()
+                .flat_map(|mesh| {
+                    let mesh_lock = mesh.lock();
+                    let transform = world_matrix * mesh_lock.transform;
+                    mesh_lock
+                        .primitives
+                        .iter()
+                        .flat_map(|p| p.vertices.iter())
+                        .map(|v| Vec3A::from(transform.transform_point3(v.vertex.position.into())))
+                        .collect::<Vec<_>>()
+                })
+                .fold(None, |bounds: Option<Bounds>, position| {
+                    Some(match bounds {
+                        Some(b) => Bounds::new(b.min.min(position), b.max.max(position)),
+                        None => Bounds::new(position, position),
+                    })
+                });
+            if let Some(bounds) = bounds {
+                self.set_bounds(model.entity, bounds);
+            }
+            let clips = model
+                .animations
+                .iter()
+                .map(|(name, animation)| PreviewClip {
+                    name: name.clone(),
+                    duration: animation.get_end_time(),
+                })
+                .collect();
+            self.preview
+                .upgrade()
+                .expect("Failed to upgrade preview handle.")
+                .borrow_mut()
+                .playback
+                .set_clips(clips);
+            let renderable = lock.add_model(self.scene_type, model);
+            self.register_renderable(renderable);
+        }
+        for primitive in completed_tasks.geometric_primitives.into_iter() {
+            let bounds = primitive.model.as_ref().and_then(Self::get_model_bounds);
+            if let (Some(model), Some(bounds)) = (primitive.model.as_ref(), bounds) {
+                self.set_bounds(model.entity, bounds);
+            }
+            let renderable = lock.add_model(self.scene_type, primitive);
+            self.register_renderable(renderable);
+        }
+        drop(lock);
+        drop(rm);
+        self.waitable_tasks.clear();
+        Ok(())
+    }
+}
+
+unsafe impl<GraphicsType, BufferType, CommandType, TextureType> Send
+    for PreviewScene<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+}
+
+unsafe impl<GraphicsType, BufferType, CommandType, TextureType> Sync
+    for PreviewScene<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+}
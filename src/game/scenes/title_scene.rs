@@ -1,18 +1,17 @@
 use crate::game::graphics::vk::{Buffer, Graphics, Image};
 use crate::game::shared::enums::SceneType;
-use crate::game::shared::structs::WaitableTasks;
+use crate::game::shared::structs::{LoadingProgress, RenderFeatures, WaitableTasks};
 use crate::game::structs::{Counts, Model, PositionInfo};
 use crate::game::traits::{Disposable, GraphicsBase, Scene};
-use crate::game::{LockableRenderable, ResourceManagerWeak};
+use crate::game::{LockableRenderable, RenderableRef, ResourceManagerWeak, World};
 use ash::vk::CommandBuffer;
 use async_trait::async_trait;
 use glam::f32::{Vec3A, Vec4};
 use parking_lot::RwLock;
-use slotmap::{DefaultKey, SlotMap};
+use slotmap::{DefaultKey, Key};
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::mem::ManuallyDrop;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, Weak};
 
 /// タイトルシーン<br />
@@ -30,10 +29,12 @@ where
     counts: Counts,
     waitable_tasks: WaitableTasks<GraphicsType, BufferType, CommandType, TextureType>,
     scene_type: SceneType,
-    entities: std::rc::Weak<RefCell<SlotMap<DefaultKey, usize>>>,
-    current_entities: HashMap<String, DefaultKey>,
+    entities: std::rc::Weak<RefCell<World<GraphicsType, BufferType, CommandType, TextureType>>>,
     render_components: Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>,
     loaded: bool,
+    /// タイトルには地形も水面もないので、影や水面のパスを実行しない。<br />
+    /// The title has neither terrain nor water, so shadow and water passes aren't executed.
+    render_features: RenderFeatures,
 }
 
 impl<GraphicsType, BufferType, CommandType, TextureType>
@@ -47,7 +48,7 @@ where
     pub fn new(
         resource_manager: ResourceManagerWeak<GraphicsType, BufferType, CommandType, TextureType>,
         graphics: Weak<RwLock<ManuallyDrop<GraphicsType>>>,
-        entities: std::rc::Weak<RefCell<SlotMap<DefaultKey, usize>>>,
+        entities: std::rc::Weak<RefCell<World<GraphicsType, BufferType, CommandType, TextureType>>>,
     ) -> Self {
         TitleScene {
             graphics,
@@ -58,10 +59,29 @@ where
             scene_type: SceneType::TITLE,
             entities,
             render_components: vec![],
-            current_entities: HashMap::new(),
             loaded: false,
+            render_features: RenderFeatures::POST_EFFECTS | RenderFeatures::UI_LAYERS,
         }
     }
+
+    /// 描画するモデルを登録し、エンティティに紐付ける。<br />
+    /// Register a model for rendering and attach it to its entity.
+    fn register_renderable(
+        &mut self,
+        renderable: LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>,
+    ) {
+        let entity = renderable.lock().get_entity();
+        if !entity.is_null() {
+            let entities = self
+                .entities
+                .upgrade()
+                .expect("Failed to upgrade entities handle.");
+            entities
+                .borrow_mut()
+                .insert(entity, RenderableRef(renderable.clone()));
+        }
+        self.render_components.push(renderable);
+    }
 }
 
 impl TitleScene<Graphics, Buffer, CommandBuffer, Image> {}
@@ -73,12 +93,8 @@ impl Scene for TitleScene<Graphics, Buffer, CommandBuffer, Image> {
             .entities
             .upgrade()
             .expect("Failed to upgrade entities handle.");
-        self.counts.entity_count += 1;
         let mut entities_lock = entities.borrow_mut();
-        let entity = entities_lock.insert(self.counts.entity_count);
-        self.current_entities
-            .insert(entity_name.to_string(), entity);
-        entity
+        entities_lock.spawn(entity_name)
     }
 
     fn add_model(
@@ -90,7 +106,7 @@ impl Scene for TitleScene<Graphics, Buffer, CommandBuffer, Image> {
         color: Vec4,
         entity: DefaultKey,
     ) -> anyhow::Result<()> {
-        let ssbo_index = self.counts.ssbo_count.fetch_add(1, Ordering::SeqCst);
+        let ssbo_index = self.counts.acquire_ssbo_index();
         let resource_manager = self.resource_manager.upgrade();
         if resource_manager.is_none() {
             return Err(anyhow::anyhow!("Resource manager has been destroyed."));
@@ -121,9 +137,11 @@ impl Scene for TitleScene<Graphics, Buffer, CommandBuffer, Image> {
             metadata.object_color = color;
             model.set_model_metadata(metadata);
             model.set_ssbo_index(ssbo_index);
+            model.set_entity(entity);
             model.update_model_indices(self.counts.model_count.clone());
             let mut lock = resource_manager.write();
-            lock.add_clone(self.scene_type, model);
+            let renderable = lock.add_clone(self.scene_type, model);
+            self.register_renderable(renderable);
             drop(lock);
         } else {
             let task = Model::new(
@@ -144,6 +162,14 @@ impl Scene for TitleScene<Graphics, Buffer, CommandBuffer, Image> {
         Ok(())
     }
 
+    fn cancel_tasks(&mut self) -> anyhow::Result<()> {
+        if self.waitable_tasks.is_empty() {
+            return Ok(());
+        }
+        self.waitable_tasks.cancel();
+        self.unload()
+    }
+
     fn create_ssbo(&self) -> anyhow::Result<()> {
         for renderable in self.render_components.iter() {
             renderable.lock().create_ssbo()?;
@@ -160,10 +186,22 @@ impl Scene for TitleScene<Graphics, Buffer, CommandBuffer, Image> {
         resource_lock.get_all_command_buffers(self.scene_type);
     }
 
+    fn get_entities(&self) -> Vec<DefaultKey> {
+        self.render_components
+            .iter()
+            .map(|r| r.lock().get_entity())
+            .filter(|entity| !entity.is_null())
+            .collect()
+    }
+
     fn get_model_count(&self) -> Arc<AtomicUsize> {
         self.counts.model_count.clone()
     }
 
+    fn get_render_features(&self) -> RenderFeatures {
+        self.render_features
+    }
+
     fn get_scene_name(&self) -> &str {
         &self.scene_name
     }
@@ -192,6 +230,68 @@ impl Scene for TitleScene<Graphics, Buffer, CommandBuffer, Image> {
         Ok(())
     }
 
+    fn poll_tasks(&mut self) -> anyhow::Result<LoadingProgress> {
+        self.waitable_tasks.poll_tasks()
+    }
+
+    fn remove_entity(&mut self, entity: DefaultKey) -> anyhow::Result<()> {
+        if entity.is_null() {
+            return Err(anyhow::anyhow!("Cannot remove a null entity."));
+        }
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        entities.borrow_mut().despawn(entity);
+
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let mut resource_lock = resource_manager.write();
+        let removed = resource_lock.remove_model(self.scene_type, entity);
+        self.render_components
+            .retain(|r| r.lock().get_entity() != entity);
+        for renderable in removed.iter() {
+            self.counts
+                .release_ssbo_index(renderable.lock().get_ssbo_index());
+        }
+        resource_lock.get_all_command_buffers(self.scene_type);
+        Ok(())
+    }
+
+    fn remove_renderable(&mut self, ssbo_index: usize) -> anyhow::Result<()> {
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let mut resource_lock = resource_manager.write();
+        let removed = resource_lock
+            .remove_renderable(self.scene_type, ssbo_index)
+            .ok_or_else(|| anyhow::anyhow!("No model uses SSBO index {}.", ssbo_index))?;
+        self.render_components.retain(|r| !Arc::ptr_eq(r, &removed));
+        self.counts.release_ssbo_index(ssbo_index);
+        resource_lock.get_all_command_buffers(self.scene_type);
+        drop(resource_lock);
+
+        // エンティティが取り除いたモデルを指したままにならないようにする。
+        // Make sure the entity doesn't keep pointing at the removed model.
+        let entity = removed.lock().get_entity();
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        let is_attached = entities_lock
+            .get::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity)
+            .map(|r| Arc::ptr_eq(&r.0, &removed))
+            .unwrap_or(false);
+        if is_attached {
+            entities_lock.remove::<RenderableRef<Graphics, Buffer, CommandBuffer, Image>>(entity);
+        }
+        Ok(())
+    }
+
     fn render(&self, _delta_time: f64) -> anyhow::Result<()> {
         let graphics = self
             .graphics
@@ -208,6 +308,37 @@ impl Scene for TitleScene<Graphics, Buffer, CommandBuffer, Image> {
         self.scene_name = scene_name.to_string();
     }
 
+    fn unload(&mut self) -> anyhow::Result<()> {
+        // 読み込み中のモデルが片付けた後に追加されないように、先に全てのタスクを待つ。
+        // Wait for all tasks first so that models still loading aren't added after the teardown.
+        self.wait_for_all_tasks()?;
+        let resource_manager = self
+            .resource_manager
+            .upgrade()
+            .expect("Failed to upgrade resource manager handle.");
+        let removed = resource_manager
+            .write()
+            .remove_scene_models(self.scene_type);
+
+        let entities = self
+            .entities
+            .upgrade()
+            .expect("Failed to upgrade entities handle.");
+        let mut entities_lock = entities.borrow_mut();
+        for renderable in removed.iter() {
+            let entity = renderable.lock().get_entity();
+            if !entity.is_null() {
+                entities_lock.despawn(entity);
+            }
+        }
+        drop(entities_lock);
+
+        self.render_components.clear();
+        self.counts.reset();
+        self.loaded = false;
+        Ok(())
+    }
+
     async fn update(&self, delta_time: f64) -> anyhow::Result<()> {
         if !self.loaded {
             return Ok(());
@@ -232,8 +363,8 @@ impl Scene for TitleScene<Graphics, Buffer, CommandBuffer, Image> {
         let rm = rm.unwrap();
         let mut lock = rm.write();
         for model in completed_tasks.models.into_iter() {
-            self.render_components
-                .push(lock.add_model(self.scene_type, model));
+            let renderable = lock.add_model(self.scene_type, model);
+            self.register_renderable(renderable);
         }
         drop(lock);
         drop(rm);
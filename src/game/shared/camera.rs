@@ -1,10 +1,18 @@
-use glam::{Mat4, Vec3, Vec3A};
+use crate::game::shared::structs::Ray;
+use crate::game::ui::TweakRegistry;
+use glam::{Mat4, Vec3, Vec3A, Vec4};
 use winit::event::VirtualKeyCode;
 
 const MIN_DISTANCE: f32 = 5.0;
 const MAX_DISTANCE: f32 = 15.0;
 const DISTANCE: f32 = 12.0;
 const HEIGHT: f32 = 0.75;
+const FIELD_OF_VIEW: f32 = 70.0;
+const FIELD_OF_VIEW_TWEAK: &str = "Camera/Field of View";
+
+/// 透視投影のファークリップ面までの距離。霧はここまでに空の色になる。<br />
+/// Distance to the far plane of the perspective projection. The fog reaches the sky color by then.
+pub const FAR_PLANE: f32 = 1000.0;
 
 #[derive(Copy, Clone, Debug)]
 pub enum CameraType {
@@ -22,6 +30,9 @@ pub struct Camera {
     pub height: f64,
     pub current_type: CameraType,
     pub projection: Mat4,
+    /// 縦の視野角（度）。<br />
+    /// Vertical field of view, in degrees.
+    pub field_of_view: f32,
     default_position: Vec3A,
 }
 
@@ -34,9 +45,10 @@ impl Camera {
             height,
             current_type: CameraType::Watch(Vec3A::new(0.0, 0.0, 0.0)),
             projection: Mat4::identity(),
+            field_of_view: FIELD_OF_VIEW,
             default_position: Vec3A::new(0.0, 10.0, -15.0),
         };
-        camera.set_perspective(70.0_f32.to_radians(), (width / height) as f32, 0.1, 1000.0);
+        camera.update_perspective();
         camera
     }
 
@@ -52,6 +64,21 @@ impl Camera {
         )
     }
 
+    /// 画面上の点（ピクセル）からカメラを通る光線を求める。<br />
+    /// Compute the ray through the camera for a point on the screen, in pixels.
+    pub fn screen_point_to_ray(&self, x: f64, y: f64) -> Ray {
+        let ndc_x = (2.0 * x / self.width - 1.0) as f32;
+        let ndc_y = (2.0 * y / self.height - 1.0) as f32;
+        let inverse = (self.projection * self.get_view_matrix()).inverse();
+        let unproject = |depth: f32| {
+            let point = inverse * Vec4::new(ndc_x, ndc_y, depth, 1.0);
+            Vec3A::from(point.truncate() / point.w)
+        };
+        let near = unproject(0.0);
+        let far = unproject(1.0);
+        Ray::new(near, far - near)
+    }
+
     pub fn set_orthographic(&mut self, width: f32, height: f32, near: f32, far: f32) -> Mat4 {
         self.projection = Mat4::orthographic_rh(0.0, width, height, 0.0, near, far);
         self.projection
@@ -76,7 +103,32 @@ impl Camera {
     pub fn update_window(&mut self, width: f64, height: f64) {
         self.width = width;
         self.height = height;
-        self.set_perspective(70.0_f32.to_radians(), (width / height) as f32, 0.1, 1000.0);
+        self.update_perspective();
+    }
+
+    /// 視野角を調整パネルに登録する。<br />
+    /// Register the field of view to the tweak panel.
+    pub fn register_tweaks(&mut self, tweaks: &mut TweakRegistry) {
+        tweaks.register_f32(FIELD_OF_VIEW_TWEAK, self.field_of_view, 30.0, 120.0);
+        self.apply_tweaks(tweaks);
+    }
+
+    pub fn apply_tweaks(&mut self, tweaks: &TweakRegistry) {
+        if let Some(field_of_view) = tweaks.get_f32(FIELD_OF_VIEW_TWEAK) {
+            self.set_field_of_view(field_of_view);
+        }
+    }
+
+    /// 縦の視野角（度）を変えて、投影行列を作り直す。<br />
+    /// Change the vertical field of view in degrees and rebuild the projection matrix.
+    pub fn set_field_of_view(&mut self, field_of_view: f32) {
+        self.field_of_view = field_of_view;
+        self.update_perspective();
+    }
+
+    fn update_perspective(&mut self) {
+        let aspect = (self.width / self.height) as f32;
+        self.set_perspective(self.field_of_view.to_radians(), aspect, 0.1, FAR_PLANE);
     }
 
     fn chase(&mut self, player_pos: Vec3A) {
@@ -0,0 +1,44 @@
+use crate::game::shared::structs::AnimationStateMachine;
+use glam::Vec3A;
+
+/// 移動の速さを受け取るパラメーターの名前。<br />
+/// Name of the parameter that receives the movement speed.
+pub const SPEED_PARAMETER: &str = "speed";
+
+/// 乗り物に乗っているかどうかを受け取るパラメーターの名前。<br />
+/// Name of the parameter that receives whether the entity is riding a mount.
+pub const RIDING_PARAMETER: &str = "riding";
+
+/// エンティティのアニメーションを状態機械で決める。<br />
+/// Decides the animation of an entity with a state machine.
+#[derive(Clone, Debug)]
+pub struct Animator {
+    pub state_machine: AnimationStateMachine,
+    last_position: Option<Vec3A>,
+}
+
+impl Animator {
+    pub fn new(state_machine: AnimationStateMachine) -> Self {
+        Animator {
+            state_machine,
+            last_position: None,
+        }
+    }
+
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        Ok(Self::new(AnimationStateMachine::load(file_name)?))
+    }
+
+    /// 前の位置からの速さを`speed`パラメーターに書き込み、状態機械を進める。<br />
+    /// Write the speed since the last position into the `speed` parameter and advance the state machine.
+    pub fn update(&mut self, position: Vec3A, delta_time: f32) {
+        if let Some(last_position) = self.last_position {
+            if delta_time > 0.0 {
+                let speed = (position - last_position).length() / delta_time;
+                self.state_machine.set_float(SPEED_PARAMETER, speed);
+            }
+        }
+        self.last_position = Some(position);
+        self.state_machine.update(delta_time);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::game::shared::structs::Primitive;
+use glam::{Mat4, Vec3, Vec3A};
+
+/// エンティティのローカル空間での軸平行境界ボックス。<br />
+/// Axis-aligned bounding box of an entity in local space.
+#[derive(Copy, Clone, Debug)]
+pub struct Bounds {
+    pub min: Vec3A,
+    pub max: Vec3A,
+}
+
+impl Bounds {
+    pub fn new(min: Vec3A, max: Vec3A) -> Self {
+        Bounds { min, max }
+    }
+
+    /// 頂点を全て囲む境界ボックスを作成する。頂点がない場合は`None`。<br />
+    /// Create a bounding box enclosing all vertices. Returns `None` if there are no vertices.
+    pub fn from_primitives<'a, I>(primitives: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = &'a Primitive>,
+    {
+        let mut bounds: Option<Bounds> = None;
+        for vertex in primitives.into_iter().flat_map(|p| p.vertices.iter()) {
+            bounds = Some(match bounds {
+                Some(b) => Bounds::new(b.min.min(vertex.position), b.max.max(vertex.position)),
+                None => Bounds::new(vertex.position, vertex.position),
+            });
+        }
+        bounds
+    }
+
+    pub fn get_center(&self) -> Vec3A {
+        (self.min + self.max) * 0.5
+    }
+
+    /// 行列で変換した後の八つの角を囲む境界ボックスを求める。<br />
+    /// Get the bounding box enclosing the eight corners after transforming them by a matrix.
+    pub fn transform(&self, matrix: Mat4) -> Self {
+        let corners = [
+            Vec3::new(self.min.x, self.min.y, self.min.z),
+            Vec3::new(self.max.x, self.min.y, self.min.z),
+            Vec3::new(self.min.x, self.max.y, self.min.z),
+            Vec3::new(self.max.x, self.max.y, self.min.z),
+            Vec3::new(self.min.x, self.min.y, self.max.z),
+            Vec3::new(self.max.x, self.min.y, self.max.z),
+            Vec3::new(self.min.x, self.max.y, self.max.z),
+            Vec3::new(self.max.x, self.max.y, self.max.z),
+        ];
+        let first = Vec3A::from(matrix.transform_point3(corners[0]));
+        corners[1..]
+            .iter()
+            .fold(Bounds::new(first, first), |bounds, corner| {
+                let corner = Vec3A::from(matrix.transform_point3(*corner));
+                Bounds::new(bounds.min.min(corner), bounds.max.max(corner))
+            })
+    }
+}
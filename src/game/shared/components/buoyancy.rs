@@ -0,0 +1,58 @@
+use glam::{Quat, Vec3A};
+
+/// 水に浮く剛体。浮力は箱の底の四隅で求めるので、波に合わせて傾く。<br />
+/// A rigid body that floats on water. Buoyancy is sampled at the four bottom corners of a box, so it tilts with the waves.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Buoyancy {
+    pub half_extents: Vec3A,
+    pub linear_drag: f32,
+    pub angular_drag: f32,
+    /// 真ならキャラクターが上に立てて、動きに合わせて運ばれる。<br />
+    /// If true, characters can stand on top and are carried along with its motion.
+    pub is_platform: bool,
+}
+
+impl Buoyancy {
+    pub fn new(half_extents: Vec3A) -> Self {
+        Buoyancy {
+            half_extents,
+            linear_drag: 1.0,
+            angular_drag: 1.5,
+            is_platform: false,
+        }
+    }
+
+    pub fn platform(half_extents: Vec3A) -> Self {
+        Buoyancy {
+            is_platform: true,
+            ..Self::new(half_extents)
+        }
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.half_extents.x * self.half_extents.y * self.half_extents.z * 8.0
+    }
+
+    /// ワールド空間での箱の底の四隅。<br />
+    /// The four bottom corners of the box in world space.
+    pub fn get_sample_points(&self, position: Vec3A, rotation: Quat) -> [Vec3A; 4] {
+        let (x, y, z) = (
+            self.half_extents.x,
+            self.half_extents.y,
+            self.half_extents.z,
+        );
+        let corner = |cx: f32, cz: f32| position + rotation * Vec3A::new(cx, -y, cz);
+        [corner(-x, -z), corner(x, -z), corner(-x, z), corner(x, z)]
+    }
+
+    /// 指定した位置の真下にある甲板の高さ。甲板の外なら`None`。<br />
+    /// Height of the deck directly below the given point. `None` outside the deck.
+    pub fn get_deck_height(&self, position: Vec3A, rotation: Quat, point: Vec3A) -> Option<f32> {
+        let local = rotation.conjugate() * (point - position);
+        if local.x.abs() > self.half_extents.x || local.z.abs() > self.half_extents.z {
+            return None;
+        }
+        let deck = position + rotation * Vec3A::new(local.x, self.half_extents.y, local.z);
+        Some(deck.y)
+    }
+}
@@ -1,2 +1,30 @@
+pub mod animator;
+pub mod bounds;
+pub mod buoyancy;
+pub mod mount;
+pub mod network_entity;
+pub mod network_replicated;
+pub mod particle_emitter;
+pub mod player_controller;
 pub mod render_component;
+pub mod renderable_ref;
+pub mod rider;
+pub mod rigid_body;
+pub mod spring_bone_rig;
+pub mod surface;
+pub mod transform;
+pub use animator::{Animator, RIDING_PARAMETER, SPEED_PARAMETER};
+pub use bounds::Bounds;
+pub use buoyancy::Buoyancy;
+pub use mount::Mount;
+pub use network_entity::{NetworkEntity, DEFAULT_ENTITY_HP};
+pub use network_replicated::NetworkReplicated;
+pub use particle_emitter::{ParticleEffect, ParticleEmitter};
+pub use player_controller::PlayerController;
 pub use render_component::RenderComponent;
+pub use renderable_ref::RenderableRef;
+pub use rider::Rider;
+pub use rigid_body::RigidBody;
+pub use spring_bone_rig::SpringBoneRig;
+pub use surface::Surface;
+pub use transform::Transform;
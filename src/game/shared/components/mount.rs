@@ -0,0 +1,103 @@
+use crate::game::shared::util::math::direction_from_yaw_pitch;
+use glam::{Quat, Vec3A};
+use slotmap::DefaultKey;
+
+/// プレイヤーが乗れる乗り物。速さは一気に変わらず、曲がる時は回転半径に沿って向きを変える。<br />
+/// A mount the player can ride. Its speed changes gradually, and it turns along its turning radius.
+#[derive(Clone, Debug)]
+pub struct Mount {
+    /// 全てのクライアントで同じになる乗り物のID。乗っているプレイヤーの状態と一緒に送る。<br />
+    /// ID of the mount that is the same on every client. It is sent along with the state of its rider.
+    pub mount_id: String,
+    pub max_speed: f32,
+    pub reverse_speed: f32,
+    pub acceleration: f32,
+    pub braking: f32,
+    /// アクセルを離している間の減速。<br />
+    /// Deceleration while the throttle is released.
+    pub drag: f32,
+    pub turning_radius: f32,
+    /// 乗り物から見た座席の位置。<br />
+    /// Position of the seat relative to the mount.
+    pub seat_offset: Vec3A,
+    /// 乗り物から見た、降りた時に立つ位置。<br />
+    /// Position relative to the mount where the rider stands after dismounting.
+    pub dismount_offset: Vec3A,
+    /// 前向きの速さ。後ろに進む時は負になる。<br />
+    /// Forward speed, negative while reversing.
+    pub speed: f32,
+    /// -1から1のアクセル。<br />
+    /// Throttle from -1 to 1.
+    pub throttle: f32,
+    /// -1（左）から1（右）のハンドル。<br />
+    /// Steering from -1 (left) to 1 (right).
+    pub steering: f32,
+    pub rider: Option<DefaultKey>,
+    /// 乗っているプレイヤーのID。空いていれば空。<br />
+    /// ID of the player riding it. Empty while the mount is free.
+    pub owner_id: String,
+}
+
+impl Mount {
+    pub fn new(mount_id: &str) -> Self {
+        Mount {
+            mount_id: mount_id.to_string(),
+            max_speed: 120.0,
+            reverse_speed: 30.0,
+            acceleration: 40.0,
+            braking: 90.0,
+            drag: 15.0,
+            turning_radius: 12.0,
+            seat_offset: Vec3A::new(0.0, 2.0, 0.0),
+            dismount_offset: Vec3A::new(-4.0, 0.0, 0.0),
+            speed: 0.0,
+            throttle: 0.0,
+            steering: 0.0,
+            rider: None,
+            owner_id: String::new(),
+        }
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.rider.is_none() && self.owner_id.is_empty()
+    }
+
+    /// 速さをアクセルに近づけ、向きを回転半径に沿って変えて、水平に進む。<br />
+    /// Move the speed towards the throttle, turn along the turning radius and advance horizontally.
+    pub fn step(&mut self, position: &mut Vec3A, yaw: &mut f32, delta_time: f32) {
+        let throttle = self.throttle.max(-1.0).min(1.0);
+        let target = if throttle >= 0.0 {
+            throttle * self.max_speed
+        } else {
+            throttle * self.reverse_speed
+        };
+        // 進んでいる向きと逆にアクセルを踏んだらブレーキとして扱う。
+        // Throttling against the current direction of travel counts as braking.
+        let rate = if throttle == 0.0 {
+            self.drag
+        } else if throttle * self.speed < 0.0 {
+            self.braking
+        } else {
+            self.acceleration
+        };
+        let change = target - self.speed;
+        let max_change = rate * delta_time;
+        self.speed += change.max(-max_change).min(max_change);
+
+        if self.turning_radius > 0.0 {
+            let steering = self.steering.max(-1.0).min(1.0);
+            *yaw += self.speed / self.turning_radius * steering * delta_time;
+        }
+        *position += direction_from_yaw_pitch(*yaw, 0.0) * self.speed * delta_time;
+    }
+
+    /// 乗り物の位置と向きから座席の位置を求める。<br />
+    /// Get the seat position from the position and heading of the mount.
+    pub fn get_seat_position(&self, position: Vec3A, yaw: f32) -> Vec3A {
+        position + Quat::from_rotation_y(yaw) * self.seat_offset
+    }
+
+    pub fn get_dismount_position(&self, position: Vec3A, yaw: f32) -> Vec3A {
+        position + Quat::from_rotation_y(yaw) * self.dismount_offset
+    }
+}
@@ -0,0 +1,49 @@
+use crate::game::shared::structs::games::replication::EntitySnapshot;
+
+/// 持ち主のいないエンティティを作った時の体力。<br />
+/// Health of entities created without an owner's state.
+pub const DEFAULT_ENTITY_HP: i32 = 100;
+
+/// プレイヤーが持ち、ネットワークで複製されるエンティティ。<br />
+/// 持ち主のクライアントが状態を送り、他のクライアントは届いた状態を代理のエンティティに反映する。<br />
+/// An entity owned by a player and replicated over the network.<br />
+/// The owning client sends its state, and other clients apply the received state to a proxy entity.
+#[derive(Clone, Debug)]
+pub struct NetworkEntity {
+    /// 全てのクライアントで同じになるID。<br />
+    /// ID that is the same on every client.
+    pub entity_id: String,
+    pub owner_id: String,
+    pub model: String,
+    /// ローカルプレイヤーが持ち主なら`true`。`false`なら代理。<br />
+    /// `true` if the local player is the owner, `false` for proxies.
+    pub is_local: bool,
+    pub current_hp: i32,
+    pub max_hp: i32,
+}
+
+impl NetworkEntity {
+    pub fn new_local(entity_id: String, owner_id: String, model: String) -> Self {
+        NetworkEntity {
+            entity_id,
+            owner_id,
+            model,
+            is_local: true,
+            current_hp: DEFAULT_ENTITY_HP,
+            max_hp: DEFAULT_ENTITY_HP,
+        }
+    }
+}
+
+impl From<&EntitySnapshot> for NetworkEntity {
+    fn from(snapshot: &EntitySnapshot) -> Self {
+        NetworkEntity {
+            entity_id: snapshot.entity_id.clone(),
+            owner_id: snapshot.owner_id.clone(),
+            model: snapshot.model.clone(),
+            is_local: false,
+            current_hp: snapshot.current_hp,
+            max_hp: snapshot.max_hp,
+        }
+    }
+}
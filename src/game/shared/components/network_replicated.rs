@@ -0,0 +1,16 @@
+/// サーバーから状態が同期されるエンティティ。<br />
+/// An entity whose state is replicated from the server.
+#[derive(Clone, Debug)]
+pub struct NetworkReplicated {
+    pub player_id: String,
+    pub is_local: bool,
+}
+
+impl NetworkReplicated {
+    pub fn new(player_id: String, is_local: bool) -> Self {
+        NetworkReplicated {
+            player_id,
+            is_local,
+        }
+    }
+}
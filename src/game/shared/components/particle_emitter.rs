@@ -0,0 +1,183 @@
+use crate::game::shared::structs::{ColorKey, ColorOverLife, DustPuff};
+use glam::{Vec3A, Vec4};
+
+/// ゲームの処理から出せる既製の効果。<br />
+/// Ready-made effects that gameplay code can spawn.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ParticleEffect {
+    Dust,
+    Fire,
+    Sparks,
+}
+
+/// 粒子を出し続ける発生源。位置はエンティティのトランスフォームに従う。<br />
+/// An emitter spawning particles. Its position follows the transform of the entity.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParticleEmitter {
+    /// 一秒に生む粒子の数。<br />
+    /// Number of particles spawned per second.
+    pub spawn_rate: f32,
+    /// 粒子の寿命（秒）。<br />
+    /// Lifetime of the particles in seconds.
+    pub lifetime: f32,
+    /// 生まれた時の速さの最小と最大（メートル毎秒）。<br />
+    /// Minimum and maximum speed at birth, in meters per second.
+    pub speed: (f32, f32),
+    /// 放出する円錐の半角（ラジアン）。<br />
+    /// Half angle of the emission cone in radians.
+    pub cone_angle: f32,
+    pub direction: Vec3A,
+    /// 下向きの加速度（メートル毎秒毎秒）。負なら上がっていく。<br />
+    /// Downward acceleration in meters per second squared. Negative values make particles rise.
+    pub gravity: f32,
+    /// 板ポリゴンの半分の大きさ（メートル）。<br />
+    /// Half the size of a billboard, in meters.
+    pub size: f32,
+    pub color_over_life: ColorOverLife,
+    /// 粒子バッファから借りる粒子の数。同時に生きている粒子はこれを超えない。<br />
+    /// Number of particles borrowed from the particle buffer. No more particles than this are alive at once.
+    pub max_particles: u32,
+    /// 最初のフレームに一度に生む粒子の数。<br />
+    /// Number of particles spawned at once on the first frame.
+    pub burst: u32,
+    /// 粒子を生み続ける時間（秒）。`None`なら消されるまで生み続ける。<br />
+    /// How long particles keep being spawned, in seconds. `None` spawns until the emitter is removed.
+    pub duration: Option<f32>,
+    pub is_additive: bool,
+    elapsed: f32,
+    accumulator: f32,
+    has_burst: bool,
+}
+
+impl ParticleEmitter {
+    pub fn new(spawn_rate: f32, lifetime: f32, color_over_life: ColorOverLife) -> Self {
+        ParticleEmitter {
+            spawn_rate,
+            lifetime,
+            speed: (0.5, 1.0),
+            cone_angle: std::f32::consts::FRAC_PI_4,
+            direction: Vec3A::unit_y(),
+            gravity: 0.0,
+            size: 0.05,
+            color_over_life,
+            max_particles: (spawn_rate * lifetime).ceil().max(1.0) as u32,
+            burst: 0,
+            duration: None,
+            is_additive: false,
+            elapsed: 0.0,
+            accumulator: 0.0,
+            has_burst: false,
+        }
+    }
+
+    /// 一度だけ粒子を生む発生源。粒子が全て消えると終わる。<br />
+    /// An emitter spawning particles only once. It finishes when all of them have died.
+    pub fn burst(count: u32, lifetime: f32, color_over_life: ColorOverLife) -> Self {
+        ParticleEmitter {
+            max_particles: count.max(1),
+            burst: count,
+            duration: Some(0.0),
+            ..Self::new(0.0, lifetime, color_over_life)
+        }
+    }
+
+    pub fn from_effect(effect: ParticleEffect) -> Self {
+        match effect {
+            ParticleEffect::Dust => Self::from(DustPuff {
+                color: [0.6, 0.55, 0.45, 0.6],
+                count: 12,
+                speed: 0.6,
+                lifetime: 0.8,
+            }),
+            ParticleEffect::Fire => ParticleEmitter {
+                speed: (0.4, 1.0),
+                cone_angle: 0.3,
+                gravity: -1.5,
+                size: 0.12,
+                is_additive: true,
+                ..Self::new(
+                    80.0,
+                    1.2,
+                    ColorOverLife::new(&[
+                        ColorKey::new(0.0, Vec4::new(1.0, 0.9, 0.4, 1.0)),
+                        ColorKey::new(0.4, Vec4::new(1.0, 0.4, 0.1, 0.8)),
+                        ColorKey::new(1.0, Vec4::new(0.2, 0.05, 0.0, 0.0)),
+                    ]),
+                )
+            },
+            ParticleEffect::Sparks => ParticleEmitter {
+                speed: (2.0, 5.0),
+                cone_angle: std::f32::consts::FRAC_PI_2,
+                gravity: 9.8,
+                size: 0.03,
+                is_additive: true,
+                ..Self::burst(
+                    48,
+                    0.6,
+                    ColorOverLife::new(&[
+                        ColorKey::new(0.0, Vec4::new(1.0, 1.0, 0.8, 1.0)),
+                        ColorKey::new(0.5, Vec4::new(1.0, 0.6, 0.2, 1.0)),
+                        ColorKey::new(1.0, Vec4::new(1.0, 0.2, 0.0, 0.0)),
+                    ]),
+                )
+            },
+        }
+    }
+
+    /// 時間を進め、このフレームに生む粒子の数を返す。端数は次のフレームに持ち越す。<br />
+    /// Advance time and return the number of particles to spawn this frame. Fractions carry over to the next frame.
+    pub fn update(&mut self, delta_time: f32) -> u32 {
+        let mut count = 0;
+        if !self.has_burst {
+            self.has_burst = true;
+            count += self.burst;
+        }
+        let is_emitting = self
+            .duration
+            .map(|duration| self.elapsed < duration)
+            .unwrap_or(true);
+        if is_emitting {
+            let emitting_time = match self.duration {
+                Some(duration) => delta_time.min(duration - self.elapsed),
+                None => delta_time,
+            };
+            self.accumulator += self.spawn_rate * emitting_time.max(0.0);
+            let spawned = self.accumulator.floor();
+            self.accumulator -= spawned;
+            count += spawned as u32;
+        }
+        self.elapsed += delta_time;
+        count
+    }
+
+    /// 粒子を生み終え、最後の粒子も消えたかどうか。<br />
+    /// Whether the emitter has stopped spawning and its last particle has died.
+    pub fn is_finished(&self) -> bool {
+        match self.duration {
+            Some(duration) => self.has_burst && self.elapsed >= duration + self.lifetime,
+            None => false,
+        }
+    }
+
+    pub fn get_elapsed(&self) -> f32 {
+        self.elapsed
+    }
+}
+
+impl From<DustPuff> for ParticleEmitter {
+    /// 足音の土煙を一度だけ出す発生源にする。<br />
+    /// Turn a footstep dust puff into an emitter that spawns once.
+    fn from(puff: DustPuff) -> Self {
+        ParticleEmitter {
+            speed: (puff.speed * 0.5, puff.speed),
+            cone_angle: 1.2,
+            gravity: -0.2,
+            size: 0.08,
+            ..Self::burst(
+                puff.count,
+                puff.lifetime,
+                ColorOverLife::fade_out(Vec4::from(puff.color)),
+            )
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use glam::Vec3A;
+
+/// ローカルプレイヤーが操作するエンティティ。<br />
+/// An entity controlled by the local player.
+#[derive(Copy, Clone, Debug)]
+pub struct PlayerController {
+    /// 一秒あたりの移動距離（メートル）。<br />
+    /// Movement distance per second, in meters.
+    pub move_speed: f32,
+
+    /// 一秒あたりの回転角度（ラジアン）。<br />
+    /// Turning angle per second, in radians.
+    pub turn_speed: f32,
+
+    /// クリックで指定された移動先。到着すると`None`に戻る。<br />
+    /// Destination set by clicking. Reset to `None` on arrival.
+    pub move_target: Option<Vec3A>,
+}
+
+impl Default for PlayerController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlayerController {
+    pub fn new() -> Self {
+        PlayerController {
+            move_speed: 60.0,
+            turn_speed: 60.0_f32.to_radians(),
+            move_target: None,
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use crate::game::traits::{Disposable, GraphicsBase};
+use crate::game::LockableRenderable;
+
+/// エンティティが描画するモデルへの参照。<br />
+/// Reference to the model an entity renders.
+pub struct RenderableRef<GraphicsType, BufferType, CommandType, TextureType>(
+    pub LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>,
+)
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable;
+
+impl<GraphicsType, BufferType, CommandType, TextureType> Clone
+    for RenderableRef<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    fn clone(&self) -> Self {
+        RenderableRef(self.0.clone())
+    }
+}
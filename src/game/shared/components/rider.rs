@@ -0,0 +1,14 @@
+use slotmap::DefaultKey;
+
+/// 乗り物に乗っているエンティティ。乗っている間は乗り物が位置を決める。<br />
+/// An entity riding a mount. While riding, the mount decides its position.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rider {
+    pub mount: DefaultKey,
+}
+
+impl Rider {
+    pub fn new(mount: DefaultKey) -> Self {
+        Rider { mount }
+    }
+}
@@ -0,0 +1,26 @@
+use glam::Vec3A;
+
+/// 物理演算で動かされるエンティティの状態。<br />
+/// State of an entity moved by the physics simulation.
+#[derive(Copy, Clone, Debug)]
+pub struct RigidBody {
+    pub velocity: Vec3A,
+    pub mass: f32,
+    pub is_kinematic: bool,
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RigidBody {
+    pub fn new() -> Self {
+        RigidBody {
+            velocity: Vec3A::zero(),
+            mass: 1.0,
+            is_kinematic: false,
+        }
+    }
+}
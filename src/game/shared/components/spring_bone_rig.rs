@@ -0,0 +1,19 @@
+use crate::game::shared::structs::SpringBoneConfig;
+use std::sync::Arc;
+
+/// エンティティのモデルに揺れる骨の設定を付ける。設定は同じキャラクターの間で共有する。<br />
+/// Attaches spring-bone settings to the model of an entity. The settings are shared between characters of the same kind.
+#[derive(Clone, Debug)]
+pub struct SpringBoneRig {
+    pub config: Arc<SpringBoneConfig>,
+}
+
+impl SpringBoneRig {
+    pub fn new(config: Arc<SpringBoneConfig>) -> Self {
+        SpringBoneRig { config }
+    }
+
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        Ok(Self::new(Arc::new(SpringBoneConfig::load(file_name)?)))
+    }
+}
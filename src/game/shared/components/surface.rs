@@ -0,0 +1,14 @@
+use crate::game::shared::enums::SurfaceMaterial;
+
+/// 小物の材質。境界ボックスの上に立つキャラクターは地形の代わりにこの材質を踏む。<br />
+/// Material of a prop. Characters standing on top of its bounding box step on this material instead of the terrain.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Surface {
+    pub material: SurfaceMaterial,
+}
+
+impl Surface {
+    pub fn new(material: SurfaceMaterial) -> Self {
+        Surface { material }
+    }
+}
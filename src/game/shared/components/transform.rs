@@ -0,0 +1,73 @@
+use crate::game::shared::structs::games::interpolation::interpolate;
+use crate::game::shared::structs::PositionInfo;
+use glam::{Mat4, Vec3A};
+
+/// エンティティの位置・大きさ・回転。回転はラジアンのオイラー角。<br />
+/// Position, scale and rotation of an entity. Rotation is stored as Euler angles in radians.
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    pub position: Vec3A,
+    pub scale: Vec3A,
+    pub rotation: Vec3A,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Transform {
+            position: Vec3A::zero(),
+            scale: Vec3A::one(),
+            rotation: Vec3A::zero(),
+        }
+    }
+
+    /// ワールド行列を計算する。<br />
+    /// Compute the world matrix.
+    pub fn get_world_matrix(&self) -> Mat4 {
+        let scale = Mat4::from_scale(glam::Vec3::from(self.scale));
+        let translation = Mat4::from_translation(glam::Vec3::from(self.position));
+        let rotate = Mat4::from_rotation_ypr(self.rotation.y, self.rotation.x, self.rotation.z);
+        translation * rotate * scale
+    }
+
+    /// 前のシミュレーションのステップのトランスフォームとの間を補間する。変わっていなければそのまま返す。<br />
+    /// Interpolate from the transform of the previous simulation step. It's returned as is if nothing changed.
+    pub fn interpolate_from(&self, previous: &Transform, alpha: f32) -> Transform {
+        let is_unchanged = self.position == previous.position
+            && self.rotation == previous.rotation
+            && self.scale == previous.scale;
+        if is_unchanged || alpha >= 1.0 {
+            return *self;
+        }
+        Transform::from(interpolate(
+            &PositionInfo::from(*previous),
+            &PositionInfo::from(*self),
+            alpha,
+        ))
+    }
+}
+
+impl From<PositionInfo> for Transform {
+    fn from(position_info: PositionInfo) -> Self {
+        Transform {
+            position: position_info.position,
+            scale: position_info.scale,
+            rotation: position_info.rotation,
+        }
+    }
+}
+
+impl From<Transform> for PositionInfo {
+    fn from(transform: Transform) -> Self {
+        PositionInfo {
+            position: transform.position,
+            scale: transform.scale,
+            rotation: transform.rotation,
+        }
+    }
+}
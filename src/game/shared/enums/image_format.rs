@@ -3,4 +3,7 @@ pub enum ImageFormat {
     GltfFormat(gltf::image::Format),
     VkFormat(ash::vk::Format),
     ColorType(image::ColorType),
+    /// ブロック圧縮された形式。ミップマップは生成できないので、全てのレベルを用意してアップロードする。<br />
+    /// A block compressed format. Mipmaps can't be generated, so every level is prepared and uploaded.
+    Compressed(ash::vk::Format),
 }
@@ -1,8 +1,16 @@
 pub mod image_format;
+pub mod music_layer;
+pub mod present_mode;
 pub mod sampler_resource;
 pub mod scene_type;
 pub mod shader_type;
+pub mod surface_material;
+pub mod window_mode;
 pub use image_format::*;
+pub use music_layer::MusicLayer;
+pub use present_mode::PresentMode;
 pub use sampler_resource::*;
 pub use scene_type::SceneType;
 pub use shader_type::ShaderType;
+pub use surface_material::SurfaceMaterial;
+pub use window_mode::WindowMode;
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// 重ねて再生するBGMの層。後の層ほど緊迫している。<br />
+/// Layers of music played on top of each other. Later layers are more intense.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum MusicLayer {
+    Exploration,
+    Tension,
+    Combat,
+}
+
+impl MusicLayer {
+    pub const ALL: [MusicLayer; 3] = [
+        MusicLayer::Exploration,
+        MusicLayer::Tension,
+        MusicLayer::Combat,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Default for MusicLayer {
+    fn default() -> Self {
+        MusicLayer::Exploration
+    }
+}
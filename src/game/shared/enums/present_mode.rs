@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+/// 画面に表示する方法。FIFOは垂直同期を待ち、MAILBOXは待たずに最新のフレームだけを表示し、IMMEDIATEはすぐに表示する。<br />
+/// How frames are presented. FIFO waits for vertical sync, MAILBOX shows only the latest frame without waiting, and IMMEDIATE presents right away.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum PresentMode {
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl PresentMode {
+    pub const ALL: [PresentMode; 3] = [
+        PresentMode::Fifo,
+        PresentMode::Mailbox,
+        PresentMode::Immediate,
+    ];
+
+    /// 設定ファイルと設定画面で使う名前。<br />
+    /// Name used by the settings file and the settings page.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            PresentMode::Fifo => "FIFO",
+            PresentMode::Mailbox => "MAILBOX",
+            PresentMode::Immediate => "IMMEDIATE",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.get_name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl Default for PresentMode {
+    fn default() -> Self {
+        PresentMode::Mailbox
+    }
+}
@@ -7,6 +7,7 @@ impl SceneType {
     pub(crate) const TITLE: Self = Self(0);
     pub(crate) const LOBBY: Self = Self(1);
     pub(crate) const GAME: Self = Self(2);
+    pub(crate) const PREVIEW: Self = Self(3);
 }
 
 impl PartialEq<u32> for SceneType {
@@ -8,6 +8,12 @@ pub enum ShaderType {
     Terrain,
     Water,
     InstanceDraw,
+    /// 間接描画でまとめて描く静的なモデル。<br />
+    /// Static models drawn together by indirect draws.
+    IndirectDraw,
+    /// 間接描画でまとめて描くインスタンス描画のモデル。<br />
+    /// Instanced models drawn together by indirect draws.
+    InstanceIndirectDraw,
 }
 
 impl ToString for ShaderType {
@@ -19,6 +25,8 @@ impl ToString for ShaderType {
             ShaderType::Terrain => "Terrain".to_string(),
             ShaderType::Water => "Water".to_string(),
             ShaderType::InstanceDraw => "InstanceDraw".to_string(),
+            ShaderType::IndirectDraw => "IndirectDraw".to_string(),
+            ShaderType::InstanceIndirectDraw => "InstanceIndirectDraw".to_string(),
         }
     }
 }
@@ -32,9 +40,50 @@ impl ShaderType {
             ShaderType::Terrain,
             ShaderType::Water,
             ShaderType::InstanceDraw,
+            ShaderType::IndirectDraw,
+            ShaderType::InstanceIndirectDraw,
         ]
     }
 
+    /// このタイプのメッシュを間接描画で描く時のタイプ。間接描画に対応しなければ`None`。<br />
+    /// The type used when drawing meshes of this type with indirect draws. `None` if indirect draws aren't supported.
+    pub fn get_indirect_shader_type(&self) -> Option<ShaderType> {
+        match self {
+            ShaderType::BasicShader => Some(ShaderType::IndirectDraw),
+            ShaderType::InstanceDraw => Some(ShaderType::InstanceIndirectDraw),
+            _ => None,
+        }
+    }
+
+    /// このタイプのパイプラインが使う頂点シェーダーのファイル。<br />
+    /// Vertex shader file used by the pipeline of this type.
+    pub fn get_vertex_shader_file(&self) -> &'static str {
+        match self {
+            ShaderType::AnimatedModel => "./shaders/basicShader_animated.spv",
+            ShaderType::Terrain => "./shaders/terrain_vert.spv",
+            ShaderType::InstanceDraw => "./shaders/instance_vert.spv",
+            ShaderType::IndirectDraw => "./shaders/indirect_vert.spv",
+            ShaderType::InstanceIndirectDraw => "./shaders/instance_indirect_vert.spv",
+            _ => "./shaders/vert.spv",
+        }
+    }
+
+    /// このタイプのパイプラインが使うフラグメントシェーダーのファイル。<br />
+    /// Fragment shader file used by the pipeline of this type.
+    pub fn get_fragment_shader_file(&self) -> &'static str {
+        match self {
+            ShaderType::BasicShader => "./shaders/frag.spv",
+            ShaderType::BasicShaderWithoutTexture => "./shaders/basicShader_noTexture.spv",
+            ShaderType::Terrain => "./shaders/terrain_frag.spv",
+            ShaderType::Water => "./shaders/water_frag.spv",
+            ShaderType::InstanceDraw => "./shaders/instance_frag.spv",
+            ShaderType::IndirectDraw | ShaderType::InstanceIndirectDraw => {
+                "./shaders/indirect_frag.spv"
+            }
+            _ => "./shaders/frag.spv",
+        }
+    }
+
     pub fn get_all_shader_type_pairs() -> Vec<(ShaderType, String)> {
         let shader_types = ShaderType::get_all_shader_types();
         let shader_type_names = shader_types
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// 地面や小物の材質。足音と土煙の種類を決める。<br />
+/// Material of the ground or a prop, deciding the kind of footstep sound and dust puff.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum SurfaceMaterial {
+    Grass,
+    Rock,
+    Wood,
+}
+
+impl Default for SurfaceMaterial {
+    fn default() -> Self {
+        SurfaceMaterial::Grass
+    }
+}
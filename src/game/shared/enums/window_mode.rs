@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// ウィンドウの表示方法。ボーダーレスはデスクトップの解像度のまま画面を覆い、排他的フルスクリーンはモニターのビデオモードを切り替える。<br />
+/// How the window is shown. Borderless covers the screen at the desktop resolution, and exclusive fullscreen switches the video mode of the monitor.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum WindowMode {
+    Windowed,
+    Borderless,
+    Exclusive,
+}
+
+impl WindowMode {
+    pub const ALL: [WindowMode; 3] = [
+        WindowMode::Windowed,
+        WindowMode::Borderless,
+        WindowMode::Exclusive,
+    ];
+
+    pub fn get_name(self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "Windowed",
+            WindowMode::Borderless => "Borderless",
+            WindowMode::Exclusive => "Exclusive",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.get_name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_fullscreen(self) -> bool {
+        self != WindowMode::Windowed
+    }
+}
+
+impl Default for WindowMode {
+    fn default() -> Self {
+        WindowMode::Windowed
+    }
+}
@@ -8,6 +8,7 @@ pub mod systems;
 pub mod traits;
 pub mod types;
 pub mod util;
+pub mod world;
 
 pub use camera::Camera;
 pub use components::*;
@@ -15,3 +16,4 @@ pub use resource_manager::ResourceManager;
 pub use scene_manager::SceneManager;
 pub use systems::*;
 pub use types::*;
+pub use world::World;
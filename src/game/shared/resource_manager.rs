@@ -1,11 +1,13 @@
 use ash::vk::CommandBuffer;
 use crossbeam::sync::ShardedLock;
 use parking_lot::Mutex;
+use slotmap::DefaultKey;
 use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::game::enums::SceneType;
 use crate::game::graphics::vk::{Buffer, Graphics, Image};
+use crate::game::shared::structs::{get_cache_key, AssetWarnings, ModelCache, TerrainManager};
 use crate::game::shared::traits::disposable::Disposable;
 use crate::game::shared::traits::Renderable;
 use crate::game::shared::util::get_random_string;
@@ -25,7 +27,32 @@ where
         SceneType,
         Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>,
     >,
+
+    /// 解放待ちのモデル。GPUがまだ使っている可能性があるので、グラフィックスの削除キューに渡してフェンスを待ってから解放する。<br />
+    /// Models waiting to be disposed. The GPU may still be using them, so they are handed to the deletion queue of the graphics and disposed after their fences signal.
+    pub disposal_queue: Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>,
+
+    /// ファイルのパスごとに読み込んだモデル。同じファイルのモデルはメッシュとテクスチャを共有する。<br />
+    /// Models loaded per file path. Models of the same file share meshes and textures.
+    pub model_cache:
+        ModelCache<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>,
+
+    /// 地形のチャンクが共有するバッファ。チャンクのモデルより後に解放する。<br />
+    /// Buffers shared by terrain chunks, disposed after the models of the chunks.
+    pub terrain_manager: Arc<TerrainManager<BufferType>>,
+
+    /// 代わりの素材で置き換えた読み込みの失敗。ワーカースレッドからも報告される。<br />
+    /// Load failures that were replaced with placeholders. Worker threads report into it as well.
+    pub asset_warnings: Arc<Mutex<AssetWarnings>>,
+
+    /// 読み込めなかったテクスチャの代わりに共有するチェッカーと、そのテクスチャの番号。<br />
+    /// Checker shared in place of textures that failed to load, and its texture index.
+    placeholder_texture: Option<(Arc<ShardedLock<TextureType>>, usize)>,
     resource: Vec<Arc<Mutex<Box<dyn Disposable>>>>,
+
+    /// 同時に処理するフレームの数。フレームごとのコマンドバッファをこの数だけ集める。<br />
+    /// Number of frames in flight. Command buffers are collected for this many frames.
+    inflight_buffer_count: usize,
 }
 
 unsafe impl<GraphicsType, BufferType, CommandType, TextureType> Send
@@ -68,12 +95,18 @@ where
     CommandType: 'static + Clone,
     TextureType: 'static + Clone + Disposable,
 {
-    pub fn new() -> Self {
+    pub fn new(inflight_buffer_count: usize) -> Self {
         ResourceManager {
             resource: vec![],
             textures: vec![],
             command_buffers: HashMap::new(),
             model_queue: HashMap::new(),
+            disposal_queue: vec![],
+            model_cache: ModelCache::new(),
+            terrain_manager: Arc::new(TerrainManager::new()),
+            asset_warnings: Arc::new(Mutex::new(AssetWarnings::new())),
+            placeholder_texture: None,
+            inflight_buffer_count,
         }
     }
 
@@ -103,6 +136,29 @@ where
         texture_wrapped
     }
 
+    /// 代わりのテクスチャ。まだ作っていなければNone。<br />
+    /// The placeholder texture. None if it hasn't been created yet.
+    pub fn get_placeholder_texture(&self) -> Option<(Arc<ShardedLock<TextureType>>, usize)> {
+        self.placeholder_texture.clone()
+    }
+
+    /// 代わりのテクスチャを登録する。他のスレッドが先に登録していれば、そちらを返す。<br />
+    /// Register the placeholder texture. If another thread registered one first, that one is returned.
+    pub fn set_placeholder_texture(
+        &mut self,
+        texture: TextureType,
+    ) -> (Arc<ShardedLock<TextureType>>, usize) {
+        // 先に登録されていれば、作ったテクスチャはテクスチャの一覧に加えずに捨てる。
+        // If one was registered first, the created texture is dropped without adding it to the textures.
+        if let Some(placeholder) = self.placeholder_texture.as_ref() {
+            return placeholder.clone();
+        }
+        let texture = self.add_texture(texture);
+        let texture_index = self.get_texture_count() - 1;
+        self.placeholder_texture = Some((texture.clone(), texture_index));
+        (texture, texture_index)
+    }
+
     pub fn get_model_count(&self) -> usize {
         let mut count = 0;
         self.model_queue
@@ -132,6 +188,90 @@ where
         }
     }
 
+    /// エンティティに配属されたモデルをモデルキューから取り除き、遅延解放のキューに入れる。<br />
+    /// Remove models belonging to an entity from the model queue and push them into the deferred disposal queue.
+    pub fn remove_model(
+        &mut self,
+        scene_type: SceneType,
+        entity: DefaultKey,
+    ) -> Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>> {
+        self.remove_models_where(scene_type, |model| model.lock().get_entity() == entity)
+    }
+
+    /// SSBOの番号でモデルを一つだけ取り除く。エンティティを持たないモデルにも使える。<br />
+    /// Remove a single model by its SSBO index. This works for models without an entity as well.
+    pub fn remove_renderable(
+        &mut self,
+        scene_type: SceneType,
+        ssbo_index: usize,
+    ) -> Option<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>> {
+        self.remove_models_where(scene_type, |model| {
+            model.lock().get_ssbo_index() == ssbo_index
+        })
+        .pop()
+    }
+
+    /// シーンの全てのモデルを取り除く。シーンを片付ける時に使う。<br />
+    /// Remove all models of a scene. Used when tearing down the scene.
+    pub fn remove_scene_models(
+        &mut self,
+        scene_type: SceneType,
+    ) -> Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>> {
+        self.command_buffers.remove(&scene_type);
+        self.remove_models_where(scene_type, |_| true)
+    }
+
+    /// 条件に合うモデルをモデルキューから取り除き、残りのモデルの順番を保ったまま詰める。<br />
+    /// Remove models matching the predicate from the model queue, compacting the remaining models while keeping their order.
+    fn remove_models_where<F>(
+        &mut self,
+        scene_type: SceneType,
+        predicate: F,
+    ) -> Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>
+    where
+        F: Fn(&LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>) -> bool,
+    {
+        let model_queue = match self.model_queue.get_mut(&scene_type) {
+            Some(queue) => queue,
+            None => return vec![],
+        };
+        let (removed, remaining): (Vec<_>, Vec<_>) = model_queue.drain(..).partition(predicate);
+        *model_queue = remaining;
+
+        for model in removed.iter() {
+            // キャッシュされたモデルは、最後のインスタンスが消えた時にGPUのリソースを持つモデルを解放する。
+            // For cached models, the model owning the GPU resources is disposed when the last instance is removed.
+            let model_lock = model.lock();
+            let key = get_cache_key(model_lock.get_name());
+            if self.model_cache.contains(&key) {
+                drop(model_lock);
+                if let Some(owner) = self.model_cache.release(&key) {
+                    self.disposal_queue.push(owner);
+                }
+                continue;
+            }
+            // クローンされたモデルはメッシュを共有しているので、他のモデルがまだ使っているなら解放しない。
+            // Cloned models share meshes, so do not dispose them if other models are still using them.
+            let is_shared = model_queue
+                .iter()
+                .any(|m| m.lock().get_name() == model_lock.get_name());
+            if model_lock.is_disposed() || is_shared {
+                continue;
+            }
+            drop(model_lock);
+            self.disposal_queue.push(model.clone());
+        }
+        removed
+    }
+
+    /// 解放待ちのモデルを取り出す。毎フレーム一回呼び出され、グラフィックスの削除キューに入れられる。<br />
+    /// Take out the models waiting to be disposed. Invoked once per frame, and the models go into the deletion queue of the graphics.
+    pub fn take_disposal_queue(
+        &mut self,
+    ) -> Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>> {
+        std::mem::take(&mut self.disposal_queue)
+    }
+
     pub fn remove_resource(&mut self, resource_name: &str) {
         let mut res: Option<&Arc<Mutex<Box<dyn Disposable>>>> = None;
         let mut _index = 0_usize;
@@ -162,10 +302,7 @@ impl ResourceManager<Graphics, Buffer, CommandBuffer, Image> {
     }
 
     pub fn get_all_command_buffers(&mut self, scene_type: SceneType) {
-        let inflight_frame_count = std::env::var("INFLIGHT_BUFFER_COUNT")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let inflight_frame_count = self.inflight_buffer_count;
         let current_model_queue = self
             .model_queue
             .get(&scene_type)
@@ -236,6 +373,23 @@ where
             }
         }
 
+        for model in self.disposal_queue.iter() {
+            let mut model_lock = model.lock();
+            if model_lock.is_disposed() {
+                continue;
+            }
+            model_lock.dispose();
+        }
+
+        for model in self.model_cache.drain().iter() {
+            let mut model_lock = model.lock();
+            if model_lock.is_disposed() {
+                continue;
+            }
+            model_lock.dispose();
+        }
+        self.terrain_manager.clear();
+
         for resource in self.resource.iter() {
             let mut resource_lock = resource.lock();
             if resource_lock.is_disposed() {
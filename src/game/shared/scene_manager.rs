@@ -1,10 +1,14 @@
-use crate::game::shared::structs::Primitive;
+use crate::game::shared::structs::games::replication::ReplicationEvent;
+use crate::game::shared::structs::{
+    LoadingProgress, Primitive, Ray, RenderFeatures, SaveGame, WorldBounds,
+};
 use crate::game::shared::traits::Scene;
+use crate::game::ui::{Console, ConsoleCommand, ConsoleReply};
 use slotmap::DefaultKey;
 use std::cell::RefCell;
 use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
-use winit::event::{ElementState, VirtualKeyCode};
+use winit::event::{ElementState, MouseButton, VirtualKeyCode};
 
 pub struct SceneManager {
     pub current_index: usize,
@@ -36,6 +40,16 @@ impl SceneManager {
         entity
     }
 
+    /// 現在のシーンに複製されたエンティティの変化を反映する。モデルの読み込みが必要なら`true`を返す。<br />
+    /// Apply changes of replicated entities to the current scene. Returns `true` if models need loading.
+    pub fn apply_replication(&self, events: Vec<ReplicationEvent>) -> anyhow::Result<bool> {
+        let current_index = self.current_index;
+        match self.scenes.get(current_index) {
+            Some(scene) => scene.borrow_mut().apply_replication(events),
+            None => Ok(false),
+        }
+    }
+
     pub fn create_ssbo(&self) -> anyhow::Result<()> {
         let current_index = self.current_index;
         self.scenes
@@ -46,6 +60,26 @@ impl SceneManager {
         Ok(())
     }
 
+    /// 現在のシーンでコンソールのコマンドを実行する。シーンが扱わなければ`None`を返す。<br />
+    /// Execute a console command in the current scene. Returns `None` if the scene doesn't handle it.
+    pub fn execute_command(
+        &self,
+        command: &ConsoleCommand,
+    ) -> Option<anyhow::Result<ConsoleReply>> {
+        let current_index = self.current_index;
+        self.scenes
+            .get(current_index)
+            .and_then(|scene| scene.borrow_mut().execute_command(command))
+    }
+
+    pub async fn fixed_update(&self, delta_time: f64) -> anyhow::Result<()> {
+        let current_index = self.current_index;
+        if let Some(scene) = self.scenes.get(current_index) {
+            scene.borrow().fixed_update(delta_time).await?;
+        }
+        Ok(())
+    }
+
     pub fn generate_terrain(
         &self,
         grid_x: f32,
@@ -71,6 +105,26 @@ impl SceneManager {
             .get_command_buffers();
     }
 
+    /// 現在のシーンで描画しているモデルを持つエンティティを取得する。<br />
+    /// Get the entities owning the models rendered in the current scene.
+    pub fn get_entities(&self) -> Vec<DefaultKey> {
+        let current_index = self.current_index;
+        self.scenes
+            .get(current_index)
+            .map(|scene| scene.borrow().get_entities())
+            .unwrap_or_default()
+    }
+
+    /// 現在のシーンが使う描画の機能を取得する。シーンがなければ全ての機能を使う。<br />
+    /// Get the rendering features the current scene uses. Every feature is used without a scene.
+    pub fn get_render_features(&self) -> RenderFeatures {
+        let current_index = self.current_index;
+        self.scenes
+            .get(current_index)
+            .map(|scene| scene.borrow().get_render_features())
+            .unwrap_or(RenderFeatures::ALL)
+    }
+
     pub fn get_scene_model_count(&self) -> Arc<AtomicUsize> {
         let current_index = self.current_index;
         self.scenes
@@ -87,6 +141,13 @@ impl SceneManager {
         }
     }
 
+    pub fn input_button(&self, button: MouseButton, x: f64, y: f64, element_state: ElementState) {
+        let current_index = self.current_index;
+        if let Some(scene) = self.scenes.get(current_index) {
+            scene.borrow().input_button(button, x, y, element_state);
+        }
+    }
+
     pub async fn input_key(&self, key: VirtualKeyCode, element_state: ElementState) {
         let current_index = self.current_index;
         if let Some(scene) = self.scenes.get(current_index) {
@@ -102,6 +163,52 @@ impl SceneManager {
         Ok(())
     }
 
+    /// 現在のシーンにセーブデータを読み込む。<br />
+    /// Load save data into the current scene.
+    pub async fn load_state(&self, save: &SaveGame) -> anyhow::Result<()> {
+        let current_index = self.current_index;
+        match self.scenes.get(current_index) {
+            Some(scene) => scene.borrow().load_state(save).await,
+            None => Err(anyhow::anyhow!("There is no scene to load into.")),
+        }
+    }
+
+    /// 現在のシーンで画面上の点の下にあるエンティティを選ぶ。<br />
+    /// Pick the entity under a point on the screen in the current scene.
+    pub fn pick(&self, x: f64, y: f64) -> Option<DefaultKey> {
+        let current_index = self.current_index;
+        self.scenes
+            .get(current_index)
+            .and_then(|scene| scene.borrow().pick(x, y))
+    }
+
+    /// 現在のシーンで光線と交差する最も近いエンティティと、その距離を求める。<br />
+    /// Find the nearest entity intersecting the ray and its distance in the current scene.
+    pub fn raycast(&self, ray: &Ray) -> Option<(DefaultKey, f32)> {
+        let current_index = self.current_index;
+        self.scenes
+            .get(current_index)
+            .and_then(|scene| scene.borrow().raycast(ray))
+    }
+
+    /// 現在のシーンの読み込みの進み具合を待たずに取得する。<br />
+    /// Get the loading progress of the current scene without waiting.
+    pub fn poll_tasks(&self) -> anyhow::Result<LoadingProgress> {
+        let current_index = self.current_index;
+        match self.scenes.get(current_index) {
+            Some(scene) => scene.borrow_mut().poll_tasks(),
+            None => Ok(LoadingProgress::default()),
+        }
+    }
+
+    /// 全てのシーンのコマンドを登録する。今のシーンが扱わないコマンドは実行した時に知らせる。<br />
+    /// Register the commands of every scene. Commands the current scene doesn't handle are reported when executed.
+    pub fn register_commands(&self, console: &mut Console) {
+        for scene in self.scenes.iter() {
+            scene.borrow().register_commands(console);
+        }
+    }
+
     pub fn register_scene<T>(&mut self, scene: T) -> usize
     where
         T: Scene + 'static,
@@ -111,6 +218,26 @@ impl SceneManager {
         index
     }
 
+    pub fn remove_entity(&self, entity: DefaultKey) -> anyhow::Result<()> {
+        let current_index = self.current_index;
+        self.scenes
+            .get(current_index)
+            .expect("Failed to get current scene.")
+            .borrow_mut()
+            .remove_entity(entity)?;
+        Ok(())
+    }
+
+    pub fn remove_renderable(&self, ssbo_index: usize) -> anyhow::Result<()> {
+        let current_index = self.current_index;
+        self.scenes
+            .get(current_index)
+            .expect("Failed to get current scene.")
+            .borrow_mut()
+            .remove_renderable(ssbo_index)?;
+        Ok(())
+    }
+
     pub fn render(&self, delta_time: f64) -> anyhow::Result<()> {
         let current_index = self.current_index;
         if let Some(scene) = self.scenes.get(current_index) {
@@ -119,6 +246,16 @@ impl SceneManager {
         Ok(())
     }
 
+    /// 現在のシーンの状態をセーブデータにする。<br />
+    /// Capture the state of the current scene as save data.
+    pub async fn save_state(&self) -> anyhow::Result<SaveGame> {
+        let current_index = self.current_index;
+        match self.scenes.get(current_index) {
+            Some(scene) => scene.borrow().save_state().await,
+            None => Err(anyhow::anyhow!("There is no scene to save.")),
+        }
+    }
+
     pub fn set_current_scene_by_index(&mut self, index: usize) {
         self.current_index = index;
     }
@@ -140,9 +277,33 @@ impl SceneManager {
         }
     }
 
-    pub fn switch_scene(&mut self, index: usize) {
+    pub fn set_world_bounds(&self, world_bounds: Option<WorldBounds>) {
+        let current_index = self.current_index;
+        if let Some(scene) = self.scenes.get(current_index) {
+            scene.borrow_mut().set_world_bounds(world_bounds);
+        }
+    }
+
+    /// シーンを切り替える。前のシーンがまだ読み込み中なら、その読み込みを取り消す。<br />
+    /// Switch scenes. If the previous scene is still loading, its loading is cancelled.
+    pub fn switch_scene(&mut self, index: usize) -> anyhow::Result<()> {
+        if index != self.current_index {
+            if let Some(scene) = self.scenes.get(self.current_index) {
+                scene.borrow_mut().cancel_tasks()?;
+            }
+        }
         self.set_current_scene_by_index(index);
         self.initialize();
+        Ok(())
+    }
+
+    /// 指定したシーンを片付ける。現在のシーンから切り替えた後に古いシーンを解放する時に使う。<br />
+    /// Tear down the specified scene. Used to release the old scene after switching away from it.
+    pub fn unload_scene(&self, index: usize) -> anyhow::Result<()> {
+        if let Some(scene) = self.scenes.get(index) {
+            scene.borrow_mut().unload()?;
+        }
+        Ok(())
     }
 
     pub async fn update(&self, delta_time: f64) -> anyhow::Result<()> {
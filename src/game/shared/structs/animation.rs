@@ -10,6 +10,9 @@ pub enum ChannelOutputs {
     Translations(Vec<Vec3A>),
     Rotations(Vec<Quat>),
     Scales(Vec<Vec3A>),
+    /// キーフレームごとに全てのモーフターゲットの重みを並べたもの。<br />
+    /// Weights of every morph target laid out per keyframe.
+    MorphTargetWeights(Vec<f32>),
 }
 
 #[derive(Clone, Debug)]
@@ -37,6 +40,30 @@ macro_rules! interpolate {
     }};
 }
 
+impl Animation {
+    /// 最後のキーフレームの時間（秒）。<br />
+    /// Time of the last keyframe, in seconds.
+    pub fn get_end_time(&self) -> f32 {
+        self.channels
+            .iter()
+            .filter_map(|c| c.inputs.last().copied())
+            .fold(0.0, f32::max)
+    }
+
+    /// ループする場合は最初に戻し、しない場合は最後で止めた時間を求める。<br />
+    /// Get the time wrapped back to the start when looping, or held at the end otherwise.
+    pub fn get_clip_time(&self, time: f32, is_looping: bool) -> f32 {
+        let end_time = self.get_end_time();
+        if end_time <= 0.0 {
+            0.0
+        } else if is_looping {
+            time.rem_euclid(end_time)
+        } else {
+            time.max(0.0).min(end_time)
+        }
+    }
+}
+
 pub fn generate_joint_transforms(
     animation: &Animation,
     frame: f32,
@@ -44,6 +71,63 @@ pub fn generate_joint_transforms(
     local_transform: Mat4,
     buffer: &mut [Mat4; 500],
 ) {
+    write_joint_transforms(
+        &|joint| sample_joint_pose(animation, frame, joint),
+        root_joint,
+        local_transform,
+        buffer,
+    );
+}
+
+/// 二つのアニメーションを`weight`で混ぜて関節の変換を求める。`weight`が1なら`to`だけになる。<br />
+/// Generate joint transforms blending two animations by `weight`. A `weight` of 1 uses only `to`.
+pub fn generate_blended_joint_transforms(
+    from: (&Animation, f32),
+    to: (&Animation, f32),
+    weight: f32,
+    root_joint: &Joint,
+    local_transform: Mat4,
+    buffer: &mut [Mat4; 500],
+) {
+    let blend = |joint: &Joint| {
+        let (from_translation, from_rotation, from_scale) =
+            sample_joint_pose(from.0, from.1, joint);
+        let (to_translation, to_rotation, to_scale) = sample_joint_pose(to.0, to.1, joint);
+        (
+            from_translation.lerp(to_translation, weight),
+            from_rotation.slerp(to_rotation, weight).normalize(),
+            from_scale.lerp(to_scale, weight),
+        )
+    };
+    write_joint_transforms(&blend, root_joint, local_transform, buffer);
+}
+
+fn write_joint_transforms(
+    pose: &dyn Fn(&Joint) -> (Vec3A, Quat, Vec3A),
+    root_joint: &Joint,
+    local_transform: Mat4,
+    buffer: &mut [Mat4; 500],
+) {
+    let (translation, rotation, scale) = pose(root_joint);
+    let rotation = Mat4::from_quat(rotation);
+    let transform = local_transform
+        * Mat4::from_translation(glam::Vec3::from(translation))
+        * rotation
+        * Mat4::from_scale(glam::Vec3::from(scale));
+    let final_transform = transform * root_joint.inverse_bind_matrices;
+    buffer[root_joint.index] = final_transform;
+    for child in root_joint.children.iter() {
+        write_joint_transforms(pose, child, transform, buffer);
+    }
+}
+
+/// 関節の移動・回転・拡大縮小をアニメーションの指定した時間で求める。<br />
+/// Sample the translation, rotation and scale of a joint at the given time of an animation.
+fn sample_joint_pose(
+    animation: &Animation,
+    frame: f32,
+    root_joint: &Joint,
+) -> (Vec3A, Quat, Vec3A) {
     let mut translation = root_joint.translation;
     let mut rotation = root_joint.rotation;
     let mut scale = root_joint.scale;
@@ -163,19 +247,93 @@ pub fn generate_joint_transforms(
                         }
                     };
                 }
+                (ChannelOutputs::MorphTargetWeights(_), _) => (),
             }
         }
     }
-    let rotation = Mat4::from_quat(rotation);
-    let transform = local_transform
-        * Mat4::from_translation(glam::Vec3::from(translation))
-        * rotation
-        * Mat4::from_scale(glam::Vec3::from(scale));
-    let final_transform = transform * root_joint.inverse_bind_matrices;
-    buffer[root_joint.index] = final_transform;
-    for child in root_joint.children.iter() {
-        generate_joint_transforms(animation, frame, child, transform, buffer);
+    (translation, rotation, scale)
+}
+
+/// ノードのモーフターゲットの重みをアニメーションの指定した時間で求める。チャンネルがなければ既定の重みのまま。<br />
+/// Sample the morph target weights of a node at the given time of an animation. Without a channel, the default weights are kept.
+pub fn sample_morph_weights(
+    animation: &Animation,
+    frame: f32,
+    node_index: usize,
+    default_weights: &[f32],
+) -> Vec<f32> {
+    let mut weights = default_weights.to_vec();
+    let count = weights.len();
+    if count == 0 {
+        return weights;
+    }
+    let get_keyframe =
+        |outputs: &[f32], index: usize| outputs.get(index * count..(index + 1) * count);
+    for channel in animation.channels.iter() {
+        let outputs = match &channel.outputs {
+            ChannelOutputs::MorphTargetWeights(outputs)
+                if channel.target_node_index == node_index =>
+            {
+                outputs
+            }
+            _ => continue,
+        };
+        match channel.interpolation {
+            Interpolation::Linear => {
+                let (index_prev, index_next, amount) = index_linear(channel, frame);
+                if let (Some(prev), Some(next)) = (
+                    get_keyframe(outputs, index_prev),
+                    get_keyframe(outputs, index_next),
+                ) {
+                    for (i, weight) in weights.iter_mut().enumerate() {
+                        *weight = prev[i] + (next[i] - prev[i]) * amount;
+                    }
+                }
+            }
+            Interpolation::Step => {
+                if let Some(values) = get_keyframe(outputs, index_step(channel, frame)) {
+                    weights.copy_from_slice(values);
+                }
+            }
+            // キーフレームごとに入りの接線、値、出の接線の順に並ぶ。
+            // Each keyframe holds the in-tangent, the value and the out-tangent in this order.
+            Interpolation::CubicSpline => match index_cubic_spline(channel, frame) {
+                CubicSplineIndex::Clamped { index } => {
+                    if let Some(values) = get_keyframe(outputs, index * 3 + 1) {
+                        weights.copy_from_slice(values);
+                    }
+                }
+                CubicSplineIndex::Interpolate {
+                    index_prev,
+                    index_next,
+                    time,
+                    range,
+                } => {
+                    if let (Some(p0), Some(p1), Some(m0), Some(m1)) = (
+                        get_keyframe(outputs, index_prev * 3 + 1),
+                        get_keyframe(outputs, index_next * 3 + 1),
+                        get_keyframe(outputs, index_prev * 3 + 2),
+                        get_keyframe(outputs, index_next * 3),
+                    ) {
+                        for (i, weight) in weights.iter_mut().enumerate() {
+                            *weight =
+                                interpolate!(p0[i], p1[i], m0[i] * range, m1[i] * range, time);
+                        }
+                    }
+                }
+            },
+        }
     }
+    weights
+}
+
+/// 二つのモーフターゲットの重みを`weight`で混ぜる。`weight`が1なら`to`だけになる。<br />
+/// Blend two sets of morph target weights by `weight`. A `weight` of 1 uses only `to`.
+pub fn blend_morph_weights(from: &[f32], to: &[f32], weight: f32) -> Vec<f32> {
+    from.iter()
+        .zip(to.iter())
+        .map(|(from, to)| from + (to - from) * weight)
+        .collect()
 }
 
 fn index_step(channel: &Channel, frame: f32) -> usize {
@@ -0,0 +1,480 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+/// 状態機械のパラメーターの値。<br />
+/// Value of a parameter of a state machine.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParameterValue {
+    Bool(bool),
+    Float(f32),
+}
+
+impl ParameterValue {
+    fn is_same_kind(&self, other: &ParameterValue) -> bool {
+        matches!(
+            (self, other),
+            (ParameterValue::Bool(_), ParameterValue::Bool(_))
+                | (ParameterValue::Float(_), ParameterValue::Float(_))
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Comparison {
+    Greater,
+    Less,
+    Equal,
+    NotEqual,
+}
+
+/// 遷移の条件。パラメーターの今の値と`value`を比べる。真偽値は`Equal`と`NotEqual`だけで比べられる。<br />
+/// Condition of a transition, comparing the current value of a parameter with `value`. Booleans can only be compared with `Equal` and `NotEqual`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    pub parameter: String,
+    pub comparison: Comparison,
+    pub value: ParameterValue,
+}
+
+impl Condition {
+    pub fn is_met(&self, current: ParameterValue) -> bool {
+        match (current, self.value) {
+            (ParameterValue::Float(current), ParameterValue::Float(value)) => {
+                match self.comparison {
+                    Comparison::Greater => current > value,
+                    Comparison::Less => current < value,
+                    Comparison::Equal => (current - value).abs() < f32::EPSILON,
+                    Comparison::NotEqual => (current - value).abs() >= f32::EPSILON,
+                }
+            }
+            (ParameterValue::Bool(current), ParameterValue::Bool(value)) => match self.comparison {
+                Comparison::Equal => current == value,
+                Comparison::NotEqual => current != value,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+fn default_is_looping() -> bool {
+    true
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+/// 状態に入ってから決まった時間に起きるイベント。足音などをアニメーションに合わせるのに使う。<br />
+/// An event fired at a fixed time after entering a state, used to line up footsteps and the like with the animation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationEvent {
+    pub name: String,
+    /// 状態の時間（秒）。<br />
+    /// Time of the state, in seconds.
+    pub time: f32,
+}
+
+/// 状態と、その間に再生するアニメーションのクリップ。<br />
+/// A state and the animation clip played while in it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationState {
+    pub name: String,
+    pub clip: String,
+    #[serde(default = "default_is_looping")]
+    pub is_looping: bool,
+    /// 再生の速さの倍率。<br />
+    /// Multiplier of the playback speed.
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    /// クリップの長さ（秒）。ループする状態はこの長さごとにイベントを繰り返す。<br />
+    /// Length of the clip in seconds. Looping states repeat their events every this long.
+    #[serde(default)]
+    pub duration: Option<f32>,
+    #[serde(default)]
+    pub events: Vec<AnimationEvent>,
+}
+
+impl AnimationState {
+    /// 状態の時間が`[from, to)`の間に起きたイベントの名前を集める。<br />
+    /// Collect the names of the events that happened while the state time went over `[from, to)`.
+    fn collect_events(&self, from: f32, to: f32, events: &mut Vec<String>) {
+        if self.events.is_empty() || to <= from {
+            return;
+        }
+        let duration = match self.duration.filter(|_| self.is_looping) {
+            Some(duration) => duration,
+            None => {
+                events.extend(
+                    self.events
+                        .iter()
+                        .filter(|e| e.time >= from && e.time < to)
+                        .map(|e| e.name.clone()),
+                );
+                return;
+            }
+        };
+        let first_loop = (from / duration).floor() as i64;
+        let last_loop = (to / duration).floor() as i64;
+        for current_loop in first_loop..=last_loop {
+            let offset = current_loop as f32 * duration;
+            events.extend(
+                self.events
+                    .iter()
+                    .filter(|e| {
+                        let time = offset + e.time;
+                        time >= from && time < to
+                    })
+                    .map(|e| e.name.clone()),
+            );
+        }
+    }
+}
+
+/// 状態の間の遷移。`from`がない遷移はどの状態からでも起きる。<br />
+/// A transition between states. Transitions without `from` can happen from any state.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationTransition {
+    #[serde(default)]
+    pub from: Option<String>,
+    pub to: String,
+    /// 全ての条件が満たされた時に遷移する。<br />
+    /// The transition happens when all conditions are met.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// 前の状態から混ぜる時間（秒）。<br />
+    /// Time to blend from the previous state, in seconds.
+    #[serde(default)]
+    pub blend_time: f32,
+}
+
+/// キャラクターごとにデータファイルから読み込むアニメーションの状態機械の定義。<br />
+/// Definition of an animation state machine, loaded from a data file per character.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationStateMachineData {
+    pub name: String,
+    pub initial_state: String,
+    /// パラメーターの名前と初期値。<br />
+    /// Names and initial values of the parameters.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, ParameterValue>,
+    pub states: Vec<AnimationState>,
+    #[serde(default)]
+    pub transitions: Vec<AnimationTransition>,
+}
+
+impl AnimationStateMachineData {
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(file_name)?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let data: AnimationStateMachineData = serde_json::from_str(json)?;
+        data.validate()?;
+        Ok(data)
+    }
+
+    fn find_state(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|s| s.name == name)
+    }
+
+    /// 状態の名前が重ならず、イベントが状態の時間に収まり、遷移と条件が存在する状態とパラメーターを指していることを確かめる。<br />
+    /// Check that state names are unique, that events fit in their states, and that transitions and conditions refer to existing states and parameters.
+    fn validate(&self) -> anyhow::Result<()> {
+        let mut names = HashSet::new();
+        for state in self.states.iter() {
+            if !names.insert(state.name.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Duplicate state {} in animation state machine {}.",
+                    &state.name,
+                    &self.name
+                ));
+            }
+        }
+        for state in self.states.iter() {
+            let is_valid_duration = match state.duration {
+                Some(duration) => duration > 0.0,
+                None => !state.is_looping || state.events.is_empty(),
+            };
+            if !is_valid_duration {
+                return Err(anyhow::anyhow!(
+                    "Looping state {} with events needs a positive duration in animation state machine {}.",
+                    &state.name,
+                    &self.name
+                ));
+            }
+            let is_in_range = |time: f32| {
+                time >= 0.0
+                    && state
+                        .duration
+                        .map(|duration| time < duration)
+                        .unwrap_or(true)
+            };
+            if let Some(event) = state.events.iter().find(|e| !is_in_range(e.time)) {
+                return Err(anyhow::anyhow!(
+                    "Event {} of state {} is out of range in animation state machine {}.",
+                    &event.name,
+                    &state.name,
+                    &self.name
+                ));
+            }
+        }
+        if self.find_state(&self.initial_state).is_none() {
+            return Err(anyhow::anyhow!(
+                "Initial state {} doesn't exist in animation state machine {}.",
+                &self.initial_state,
+                &self.name
+            ));
+        }
+        for transition in self.transitions.iter() {
+            let states = transition
+                .from
+                .iter()
+                .chain(std::iter::once(&transition.to));
+            for state in states {
+                if self.find_state(state).is_none() {
+                    return Err(anyhow::anyhow!(
+                        "Transition refers to unknown state {} in animation state machine {}.",
+                        state,
+                        &self.name
+                    ));
+                }
+            }
+            for condition in transition.conditions.iter() {
+                match self.parameters.get(&condition.parameter) {
+                    Some(value) if value.is_same_kind(&condition.value) => (),
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "Condition on undeclared or mismatched parameter {} in animation state machine {}.",
+                            &condition.parameter,
+                            &self.name
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 再生するクリップとその時間。<br />
+/// A clip to play and its time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClipSample {
+    pub clip: String,
+    pub time: f32,
+    pub is_looping: bool,
+}
+
+/// 状態機械が決めたポーズ。遷移の途中では前の状態のクリップと混ぜる。<br />
+/// Pose decided by a state machine. During a transition it's blended with the clip of the previous state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationPose {
+    pub current: ClipSample,
+    pub previous: Option<ClipSample>,
+    /// 今の状態のクリップの重み。0から1まで。<br />
+    /// Weight of the clip of the current state, from 0 to 1.
+    pub blend_weight: f32,
+}
+
+/// デバッグパネルに表示する状態機械の様子。<br />
+/// Snapshot of a state machine shown in the debug panel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationStateInfo {
+    pub machine_name: String,
+    pub state: String,
+    pub state_time: f32,
+    pub previous_state: Option<String>,
+    pub blend_weight: f32,
+    pub parameters: Vec<(String, ParameterValue)>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Blend {
+    from_state: usize,
+    from_time: f32,
+    elapsed: f32,
+    duration: f32,
+}
+
+/// データで定義した状態機械を動かす。定義は同じキャラクターの間で共有する。<br />
+/// Runs a state machine defined by data. The definition is shared between characters of the same kind.
+#[derive(Clone, Debug)]
+pub struct AnimationStateMachine {
+    data: Arc<AnimationStateMachineData>,
+    parameters: BTreeMap<String, ParameterValue>,
+    current_state: usize,
+    state_time: f32,
+    blend: Option<Blend>,
+    events: Vec<String>,
+}
+
+impl AnimationStateMachine {
+    pub fn new(data: Arc<AnimationStateMachineData>) -> Self {
+        let current_state = data
+            .find_state(&data.initial_state)
+            .expect("Failed to find the initial state.");
+        AnimationStateMachine {
+            parameters: data.parameters.clone(),
+            data,
+            current_state,
+            state_time: 0.0,
+            blend: None,
+            events: vec![],
+        }
+    }
+
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        Ok(Self::new(Arc::new(AnimationStateMachineData::load(
+            file_name,
+        )?)))
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) -> bool {
+        self.set_parameter(name, ParameterValue::Float(value))
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) -> bool {
+        self.set_parameter(name, ParameterValue::Bool(value))
+    }
+
+    /// パラメーターを設定する。定義にないか種類が違う場合は`false`を返す。<br />
+    /// Set a parameter. Returns `false` if it isn't declared or the kind differs.
+    pub fn set_parameter(&mut self, name: &str, value: ParameterValue) -> bool {
+        match self.parameters.get_mut(name) {
+            Some(current) if current.is_same_kind(&value) => {
+                *current = value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get_parameter(&self, name: &str) -> Option<ParameterValue> {
+        self.parameters.get(name).copied()
+    }
+
+    pub fn get_current_state(&self) -> &str {
+        &self.data.states[self.current_state].name
+    }
+
+    pub fn get_state_names(&self) -> Vec<String> {
+        self.data.states.iter().map(|s| s.name.clone()).collect()
+    }
+
+    /// 遷移を通らずに、指定した状態を最初から再生する。混ぜている途中なら止める。<br />
+    /// 次の更新で遷移の条件が満たされていれば、そのまま別の状態に移る。存在しない状態なら`false`を返す。<br />
+    /// Play the given state from the start without going through a transition, stopping any blend in progress.<br />
+    /// If a transition's conditions are met on the next update, it moves on to another state as usual. Returns `false` for an unknown state.
+    pub fn play_state(&mut self, name: &str) -> bool {
+        match self.data.find_state(name) {
+            Some(state) => {
+                self.current_state = state;
+                self.state_time = 0.0;
+                self.blend = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 時間を進めてから遷移を調べる。一度の更新で遷移するのは一回まで。<br />
+    /// 今の状態で起きたイベントは`take_events`で取り出すまで溜めておく。混ぜている前の状態のイベントは起きない。<br />
+    /// Advance the time and then check transitions. At most one transition happens per update.<br />
+    /// Events of the current state are kept until `take_events` is called. The previous state being blended out fires no events.
+    pub fn update(&mut self, delta_time: f32) {
+        let states = &self.data.states;
+        let previous_time = self.state_time;
+        self.state_time += delta_time * states[self.current_state].speed;
+        states[self.current_state].collect_events(previous_time, self.state_time, &mut self.events);
+        if let Some(blend) = self.blend.as_mut() {
+            blend.from_time += delta_time * states[blend.from_state].speed;
+            blend.elapsed += delta_time;
+            if blend.elapsed >= blend.duration {
+                self.blend = None;
+            }
+        }
+
+        let current_name = &states[self.current_state].name;
+        let parameters = &self.parameters;
+        let transition = self.data.transitions.iter().find(|t| {
+            t.from
+                .as_ref()
+                .map(|from| from == current_name)
+                .unwrap_or(true)
+                && &t.to != current_name
+                && t.conditions.iter().all(|c| {
+                    parameters
+                        .get(&c.parameter)
+                        .map(|value| c.is_met(*value))
+                        .unwrap_or(false)
+                })
+        });
+        if let Some(transition) = transition {
+            let next_state = self
+                .data
+                .find_state(&transition.to)
+                .expect("Failed to find the next state.");
+            self.blend = if transition.blend_time > 0.0 {
+                Some(Blend {
+                    from_state: self.current_state,
+                    from_time: self.state_time,
+                    elapsed: 0.0,
+                    duration: transition.blend_time,
+                })
+            } else {
+                None
+            };
+            self.current_state = next_state;
+            self.state_time = 0.0;
+        }
+    }
+
+    /// 前に取り出してから起きたイベントの名前を、起きた順番で取り出す。<br />
+    /// Take the names of the events that happened since the last call, in the order they happened.
+    pub fn take_events(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn get_pose(&self) -> AnimationPose {
+        let sample = |state: usize, time: f32| {
+            let state = &self.data.states[state];
+            ClipSample {
+                clip: state.clip.clone(),
+                time,
+                is_looping: state.is_looping,
+            }
+        };
+        AnimationPose {
+            current: sample(self.current_state, self.state_time),
+            previous: self.blend.map(|b| sample(b.from_state, b.from_time)),
+            blend_weight: self.get_blend_weight(),
+        }
+    }
+
+    pub fn get_debug_info(&self) -> AnimationStateInfo {
+        AnimationStateInfo {
+            machine_name: self.data.name.clone(),
+            state: self.get_current_state().to_string(),
+            state_time: self.state_time,
+            previous_state: self
+                .blend
+                .map(|b| self.data.states[b.from_state].name.clone()),
+            blend_weight: self.get_blend_weight(),
+            parameters: self
+                .parameters
+                .iter()
+                .map(|(name, value)| (name.clone(), *value))
+                .collect(),
+        }
+    }
+
+    fn get_blend_weight(&self) -> f32 {
+        self.blend
+            .map(|b| (b.elapsed / b.duration).min(1.0))
+            .unwrap_or(1.0)
+    }
+}
@@ -0,0 +1,511 @@
+use crate::game::shared::components::Bounds;
+use crate::game::shared::structs::{ClipSample, Ktx2Texture};
+use anyhow::Context;
+use glam::Vec3A;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const MODEL_EXTENSIONS: [&str; 2] = ["gltf", "glb"];
+const TEXTURE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "tga", "ktx2"];
+
+/// 素材のファイルが変わったかどうかを確かめる間隔（秒）。<br />
+/// Interval in seconds between checks for changes to the asset's files.
+const PREVIEW_POLL_INTERVAL: f32 = 0.5;
+
+/// マウスを1ピクセル動かした時に回るオービットカメラの角度（ラジアン）。<br />
+/// Angle in radians the orbit camera turns per pixel of mouse movement.
+const ORBIT_SENSITIVITY: f32 = 0.01;
+
+/// 真上や真下を越えて裏返らないように、上下の角度をこの範囲に収める。<br />
+/// The pitch is kept within this range so the camera doesn't flip over the top or the bottom.
+const MAX_ORBIT_PITCH: f32 = 1.5;
+
+const MIN_ORBIT_DISTANCE: f32 = 0.1;
+
+/// ホイール一段で近づく割合。<br />
+/// Fraction of the distance closed per wheel step.
+const ORBIT_ZOOM_STEP: f32 = 0.1;
+
+/// プレビューする素材の種類。<br />
+/// Kind of the asset being previewed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PreviewAssetKind {
+    Model,
+    Texture,
+}
+
+impl PreviewAssetKind {
+    /// 拡張子から種類を決める。プレビューできない拡張子は`None`。<br />
+    /// Decide the kind from the extension. `None` for extensions that can't be previewed.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        let matches =
+            |extensions: &[&str]| extensions.iter().any(|x| extension.eq_ignore_ascii_case(x));
+        if matches(&MODEL_EXTENSIONS) {
+            Some(PreviewAssetKind::Model)
+        } else if matches(&TEXTURE_EXTENSIONS) {
+            Some(PreviewAssetKind::Texture)
+        } else {
+            None
+        }
+    }
+}
+
+/// プレビューのパネルに表示するマテリアルの値。<br />
+/// Values of a material shown in the preview panel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreviewMaterial {
+    pub name: String,
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    /// ベースカラーのテクスチャのURIか名前。<br />
+    /// URI or name of the base color texture.
+    pub base_color_texture: Option<String>,
+    pub alpha_mode: String,
+    pub is_double_sided: bool,
+}
+
+/// 描画せずにファイルから読める素材の情報。<br />
+/// Information about an asset that can be read from its file without rendering it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreviewAssetInfo {
+    pub kind: PreviewAssetKind,
+    pub materials: Vec<PreviewMaterial>,
+    pub animation_count: usize,
+    pub texture_size: Option<(u32, u32)>,
+    /// 素材と一緒に変更を見張る、外部のバッファーと画像のファイル。<br />
+    /// External buffer and image files watched for changes along with the asset.
+    pub dependencies: Vec<PathBuf>,
+}
+
+impl PreviewAssetInfo {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let kind = PreviewAssetKind::from_path(path)
+            .ok_or_else(|| anyhow::anyhow!("{} can't be previewed.", path.display()))?;
+        match kind {
+            PreviewAssetKind::Model => Self::read_gltf(path),
+            PreviewAssetKind::Texture => Self::read_texture(path),
+        }
+    }
+
+    /// glTFのJSONだけを解析し、マテリアル、アニメーションの数と外部のファイルを集める。<br />
+    /// Parse only the JSON of a glTF, collecting its materials, animation count and external files.
+    fn read_gltf(path: &Path) -> anyhow::Result<Self> {
+        let gltf = gltf::Gltf::open(path).with_context(|| "Failed to parse glTF.")?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let materials = gltf
+            .materials()
+            .enumerate()
+            .map(|(index, material)| {
+                let pbr = material.pbr_metallic_roughness();
+                let base_color_texture = pbr.base_color_texture().map(|info| {
+                    let image = info.texture().source();
+                    match (image.name(), image.source()) {
+                        (Some(name), _) => name.to_string(),
+                        (None, gltf::image::Source::Uri { uri, .. })
+                            if !uri.starts_with("data:") =>
+                        {
+                            uri.to_string()
+                        }
+                        (None, _) => format!("#{}", image.index()),
+                    }
+                });
+                PreviewMaterial {
+                    name: material
+                        .name()
+                        .map(|name| name.to_string())
+                        .unwrap_or_else(|| format!("Material {}", index)),
+                    base_color: pbr.base_color_factor(),
+                    metallic: pbr.metallic_factor(),
+                    roughness: pbr.roughness_factor(),
+                    base_color_texture,
+                    alpha_mode: format!("{:?}", material.alpha_mode()),
+                    is_double_sided: material.double_sided(),
+                }
+            })
+            .collect();
+        let buffer_uris = gltf.buffers().filter_map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Uri(uri) => Some(uri),
+            gltf::buffer::Source::Bin => None,
+        });
+        let image_uris = gltf.images().filter_map(|image| match image.source() {
+            gltf::image::Source::Uri { uri, .. } => Some(uri),
+            gltf::image::Source::View { .. } => None,
+        });
+        let dependencies = buffer_uris
+            .chain(image_uris)
+            .filter(|uri| !uri.starts_with("data:"))
+            .map(|uri| directory.join(uri))
+            .collect();
+        Ok(PreviewAssetInfo {
+            kind: PreviewAssetKind::Model,
+            materials,
+            animation_count: gltf.animations().count(),
+            texture_size: None,
+            dependencies,
+        })
+    }
+
+    fn read_texture(path: &Path) -> anyhow::Result<Self> {
+        let is_ktx2 = path
+            .extension()
+            .map(|e| e.eq_ignore_ascii_case("ktx2"))
+            .unwrap_or(false);
+        let texture_size = if is_ktx2 {
+            let bytes = std::fs::read(path).with_context(|| "Failed to read KTX2 texture.")?;
+            let texture = Ktx2Texture::parse(&bytes)?;
+            (texture.width, texture.height)
+        } else {
+            image::image_dimensions(path).with_context(|| "Failed to read texture.")?
+        };
+        Ok(PreviewAssetInfo {
+            kind: PreviewAssetKind::Texture,
+            materials: vec![],
+            animation_count: 0,
+            texture_size: Some(texture_size),
+            dependencies: vec![],
+        })
+    }
+}
+
+/// 注視点の周りを回るカメラ。<br />
+/// A camera circling around its target.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrbitCamera {
+    pub target: Vec3A,
+    /// Y軸の周りの角度（ラジアン）。0で+Zの側から見る。<br />
+    /// Angle around the Y axis in radians. At 0 the target is seen from the +Z side.
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    /// ドラッグ中の最後のカーソルの位置。ドラッグしていなければ`None`。<br />
+    /// Last cursor position while dragging, or `None` when not dragging.
+    drag_position: Option<(f32, f32)>,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrbitCamera {
+    pub fn new() -> Self {
+        OrbitCamera {
+            target: Vec3A::zero(),
+            yaw: 0.0,
+            pitch: 0.3,
+            distance: 10.0,
+            drag_position: None,
+        }
+    }
+
+    pub fn get_position(&self) -> Vec3A {
+        let offset = Vec3A::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        self.target + offset * self.distance
+    }
+
+    /// マウスの移動量（ピクセル）だけ回す。<br />
+    /// Turn by the mouse movement in pixels.
+    pub fn rotate(&mut self, delta_x: f32, delta_y: f32) {
+        self.yaw -= delta_x * ORBIT_SENSITIVITY;
+        self.pitch = (self.pitch + delta_y * ORBIT_SENSITIVITY)
+            .max(-MAX_ORBIT_PITCH)
+            .min(MAX_ORBIT_PITCH);
+    }
+
+    pub fn begin_drag(&mut self, x: f32, y: f32) {
+        self.drag_position = Some((x, y));
+    }
+
+    /// ドラッグ中なら、前の位置からのカーソルの移動量だけ回す。<br />
+    /// While dragging, turn by the cursor movement since the previous position.
+    pub fn drag(&mut self, x: f32, y: f32) {
+        if let Some((last_x, last_y)) = self.drag_position {
+            self.rotate(x - last_x, y - last_y);
+            self.drag_position = Some((x, y));
+        }
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag_position = None;
+    }
+
+    /// ホイールの段数だけ近づく。負の値なら離れる。<br />
+    /// Move closer by the number of wheel steps, or away for negative values.
+    pub fn zoom(&mut self, steps: f32) {
+        self.distance =
+            (self.distance * (1.0 - ORBIT_ZOOM_STEP).powf(steps)).max(MIN_ORBIT_DISTANCE);
+    }
+
+    /// 境界ボックス全体が画角に収まるように、中心を見て距離を決める。画角は度で指定する。<br />
+    /// Look at the center and pick the distance so the whole bounding box fits in the field of view, given in degrees.
+    pub fn frame(&mut self, bounds: &Bounds, field_of_view: f32) {
+        let radius = (bounds.max - bounds.min).length() * 0.5;
+        let half_angle = (field_of_view.to_radians() * 0.5).max(0.01);
+        self.target = bounds.get_center();
+        self.distance = (radius / half_angle.sin()).max(MIN_ORBIT_DISTANCE);
+    }
+}
+
+/// 素材とそれが参照するファイルの更新日時を見張る。<br />
+/// 書き込み中のファイルを読まないように、変わった日時が次に確かめた時も同じなら変更とする。<br />
+/// Watch the modification times of an asset and the files it references.<br />
+/// To avoid reading files still being written, a change is reported once the new times are the same on the next check.
+#[derive(Clone, Debug)]
+pub struct FileWatcher {
+    paths: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    pending: Option<Vec<Option<SystemTime>>>,
+}
+
+impl FileWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let modified = Self::read_modified(&paths);
+        FileWatcher {
+            paths,
+            modified,
+            pending: None,
+        }
+    }
+
+    pub fn get_paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// ファイルの更新日時を読み、変更が落ち着いたかどうかを返す。<br />
+    /// Read the modification times of the files and return whether a change has settled.
+    pub fn poll(&mut self) -> bool {
+        let modified = Self::read_modified(&self.paths);
+        self.update(modified)
+    }
+
+    /// 読んだ更新日時を渡し、変更が落ち着いたかどうかを返す。<br />
+    /// Pass the modification times that were read and return whether a change has settled.
+    pub fn update(&mut self, modified: Vec<Option<SystemTime>>) -> bool {
+        if modified == self.modified {
+            self.pending = None;
+            return false;
+        }
+        if self.pending.as_ref() == Some(&modified) {
+            self.modified = modified;
+            self.pending = None;
+            return true;
+        }
+        self.pending = Some(modified);
+        false
+    }
+
+    fn read_modified(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+        paths
+            .iter()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+/// プレビューで再生できるアニメーション。<br />
+/// An animation that can be played in the preview.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreviewClip {
+    pub name: String,
+    pub duration: f32,
+}
+
+/// プレビューで選んでいるアニメーションの再生状態。<br />
+/// Playback state of the animation selected in the preview.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationPlayback {
+    clips: Vec<PreviewClip>,
+    selected: Option<usize>,
+    pub time: f32,
+    pub is_playing: bool,
+    pub is_looping: bool,
+    pub speed: f32,
+}
+
+impl Default for AnimationPlayback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnimationPlayback {
+    pub fn new() -> Self {
+        AnimationPlayback {
+            clips: vec![],
+            selected: None,
+            time: 0.0,
+            is_playing: true,
+            is_looping: true,
+            speed: 1.0,
+        }
+    }
+
+    /// クリップを名前順に並べて設定する。読み込み直した後も、同じ名前のクリップがあれば選んだままにする。<br />
+    /// Set the clips sorted by name. After reloading, the selection is kept if a clip with the same name exists.
+    pub fn set_clips(&mut self, mut clips: Vec<PreviewClip>) {
+        clips.sort_by(|a, b| a.name.cmp(&b.name));
+        let selected_name = self.get_selected().map(|clip| clip.name.clone());
+        self.selected = selected_name
+            .and_then(|name| clips.iter().position(|clip| clip.name == name))
+            .or_else(|| if clips.is_empty() { None } else { Some(0) });
+        self.clips = clips;
+        self.time = match self.get_selected() {
+            Some(clip) => self.time.max(0.0).min(clip.duration),
+            None => 0.0,
+        };
+    }
+
+    pub fn get_clips(&self) -> &[PreviewClip] {
+        &self.clips
+    }
+
+    pub fn get_selected_index(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn get_selected(&self) -> Option<&PreviewClip> {
+        self.selected.and_then(|index| self.clips.get(index))
+    }
+
+    /// クリップを選び、最初から再生する。<br />
+    /// Select a clip and play it from the start.
+    pub fn select(&mut self, index: usize) {
+        if index < self.clips.len() {
+            self.selected = Some(index);
+            self.time = 0.0;
+            self.is_playing = true;
+        }
+    }
+
+    /// 指定した時間に移り、再生を止める。<br />
+    /// Move to the given time and pause.
+    pub fn scrub(&mut self, time: f32) {
+        let duration = self.get_selected().map(|clip| clip.duration).unwrap_or(0.0);
+        self.time = time.max(0.0).min(duration);
+        self.is_playing = false;
+    }
+
+    /// 再生中なら時間を進める。ループしないクリップは最後で止まる。<br />
+    /// Advance the time while playing. Clips that don't loop stop at the end.
+    pub fn advance(&mut self, delta_time: f32) {
+        let duration = match self.get_selected() {
+            Some(clip) if self.is_playing => clip.duration,
+            _ => return,
+        };
+        self.time += delta_time * self.speed;
+        if duration <= 0.0 {
+            self.time = 0.0;
+        } else if self.is_looping {
+            self.time = self.time.rem_euclid(duration);
+        } else if self.time >= duration || self.time <= 0.0 {
+            self.time = self.time.max(0.0).min(duration);
+            self.is_playing = false;
+        }
+    }
+
+    pub fn get_sample(&self) -> Option<ClipSample> {
+        self.get_selected().map(|clip| ClipSample {
+            clip: clip.name.clone(),
+            time: self.time,
+            is_looping: self.is_looping,
+        })
+    }
+}
+
+/// `--preview`で開いた素材のプレビューの状態。ゲーム、プレビューのシーンとUIで共有する。<br />
+/// State of the asset preview opened with `--preview`, shared by the game, the preview scene and the UI.
+#[derive(Clone, Debug)]
+pub struct AssetPreview {
+    pub path: String,
+    pub info: PreviewAssetInfo,
+    pub orbit: OrbitCamera,
+    pub playback: AnimationPlayback,
+    /// 読み込んだ素材のローカル空間での境界ボックス。<br />
+    /// Bounding box of the loaded asset in local space.
+    pub bounds: Option<Bounds>,
+    /// 最後に読み込み直した時の失敗。パネルに表示する。<br />
+    /// The failure of the last reload, shown in the panel.
+    pub error: Option<String>,
+    watcher: FileWatcher,
+    poll_timer: f32,
+    is_reload_requested: bool,
+    /// 最初に読み込んだ時だけ素材に合わせてカメラを置き、読み込み直してもカメラは動かさない。<br />
+    /// The camera is fitted to the asset only on the first load, and reloading doesn't move it.
+    is_framed: bool,
+}
+
+impl AssetPreview {
+    pub fn new(path: &str) -> anyhow::Result<Self> {
+        let info = PreviewAssetInfo::read(Path::new(path))?;
+        let watcher = Self::create_watcher(path, &info);
+        Ok(AssetPreview {
+            path: path.to_string(),
+            info,
+            orbit: OrbitCamera::new(),
+            playback: AnimationPlayback::new(),
+            bounds: None,
+            error: None,
+            watcher,
+            poll_timer: 0.0,
+            is_reload_requested: false,
+            is_framed: false,
+        })
+    }
+
+    /// 素材の情報を読み直す。失敗したら前の情報を残し、エラーを表示する。<br />
+    /// Read the asset information again. On failure the previous information is kept and the error is shown.
+    pub fn refresh_info(&mut self) -> bool {
+        match PreviewAssetInfo::read(Path::new(&self.path)) {
+            Ok(info) => {
+                self.watcher = Self::create_watcher(&self.path, &info);
+                self.info = info;
+                self.error = None;
+                true
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// 読み込んだ素材の境界ボックスを設定する。最初の一回だけカメラを合わせる。<br />
+    /// Set the bounding box of the loaded asset. The camera is fitted only the first time.
+    pub fn set_bounds(&mut self, bounds: Bounds, field_of_view: f32) {
+        if !self.is_framed {
+            self.orbit.frame(&bounds, field_of_view);
+            self.is_framed = true;
+        }
+        self.bounds = Some(bounds);
+    }
+
+    pub fn request_reload(&mut self) {
+        self.is_reload_requested = true;
+    }
+
+    /// 読み込み直すべきかどうか。ファイルは一定の間隔でしか確かめない。<br />
+    /// Whether the asset should be reloaded. The files are only checked at a fixed interval.
+    pub fn take_reload(&mut self, delta_time: f32) -> bool {
+        self.poll_timer += delta_time;
+        if self.poll_timer >= PREVIEW_POLL_INTERVAL {
+            self.poll_timer = 0.0;
+            if self.watcher.poll() {
+                self.is_reload_requested = true;
+            }
+        }
+        std::mem::replace(&mut self.is_reload_requested, false)
+    }
+
+    fn create_watcher(path: &str, info: &PreviewAssetInfo) -> FileWatcher {
+        let mut paths = vec![PathBuf::from(path)];
+        paths.extend(info.dependencies.iter().cloned());
+        FileWatcher::new(paths)
+    }
+}
@@ -0,0 +1,379 @@
+use crate::game::scenes::game_scene::{MUSIC_SETS, SURFACE_MATERIALS};
+use crate::game::shared::enums::ShaderType;
+use crate::game::shared::structs::{
+    AnimationStateMachineData, AssetKind, Cutscene, Ktx2Texture, MusicSet, PostProcessStep,
+    SurfaceMaterialMap, POST_PROCESS_VERTEX_SHADER,
+};
+use crate::game::ui::{UI_FRAGMENT_SHADER, UI_VERTEX_SHADER};
+use anyhow::Context;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// コードから直接参照される素材の参照元。<br />
+/// Source of assets referenced directly from code.
+pub const ENGINE_SOURCE: &str = "engine";
+
+/// SPIR-Vのファイルの先頭にあるマジックナンバー。<br />
+/// Magic number at the beginning of a SPIR-V file.
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// SPIR-Vのヘッダーの大きさ（バイト）。<br />
+/// Size of the SPIR-V header in bytes.
+const SPIRV_HEADER_SIZE: usize = 20;
+
+const CUTSCENE_DIRECTORY: &str = "./cutscenes";
+const MODEL_DIRECTORY: &str = "./models";
+const TEXTURE_DIRECTORY: &str = "./textures";
+const MODEL_EXTENSIONS: [&str; 3] = ["gltf", "glb", "obj"];
+const TEXTURE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "tga", "ktx2"];
+
+/// 参照元のファイルから参照される一つの素材。<br />
+/// An asset referenced from a source file.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct AssetReference {
+    pub source: String,
+    pub kind: AssetKind,
+    pub path: String,
+}
+
+impl AssetReference {
+    pub fn new(source: &str, kind: AssetKind, path: &str) -> Self {
+        AssetReference {
+            source: source.to_string(),
+            kind,
+            path: path.to_string(),
+        }
+    }
+}
+
+/// 見つからない、または読み込めない参照。<br />
+/// A reference that is missing or fails to parse.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrokenReference {
+    pub reference: AssetReference,
+    pub message: String,
+}
+
+/// 素材の依存関係のグラフ。辺は参照元から参照先への参照。<br />
+/// Dependency graph of assets. Each edge is a reference from a source to the referenced asset.
+#[derive(Clone, Debug, Default)]
+pub struct AssetGraph {
+    references: Vec<AssetReference>,
+}
+
+impl AssetGraph {
+    pub fn new() -> Self {
+        AssetGraph { references: vec![] }
+    }
+
+    pub fn add(&mut self, reference: AssetReference) {
+        if !self.references.contains(&reference) {
+            self.references.push(reference);
+        }
+    }
+
+    pub fn get_references(&self) -> &[AssetReference] {
+        &self.references
+    }
+
+    /// 指定したファイルが直接参照している素材。<br />
+    /// Assets directly referenced by the given file.
+    pub fn get_dependencies<'a>(
+        &'a self,
+        source: &'a str,
+    ) -> impl Iterator<Item = &'a AssetReference> + 'a {
+        self.references.iter().filter(move |r| r.source == source)
+    }
+}
+
+/// 素材の検証の結果。<br />
+/// Result of validating the assets.
+#[derive(Clone, Debug, Default)]
+pub struct AssetReport {
+    pub graph: AssetGraph,
+    pub broken_references: Vec<BrokenReference>,
+}
+
+impl AssetReport {
+    pub fn is_valid(&self) -> bool {
+        self.broken_references.is_empty()
+    }
+}
+
+/// ゲームのディレクトリにある素材を辿り、全ての参照が存在して読み込めることを確かめる。<br />
+/// Walk the assets in the game directory and check that every reference exists and parses.
+pub fn validate_assets(root: &Path) -> AssetReport {
+    let mut report = AssetReport::default();
+    let mut visited = HashSet::new();
+    let mut pending = collect_root_references(root);
+    pending.reverse();
+    while let Some(reference) = pending.pop() {
+        report.graph.add(reference.clone());
+        if !visited.insert((reference.kind, reference.path.clone())) {
+            continue;
+        }
+        match check_reference(root, &reference) {
+            Ok(dependencies) => pending.extend(dependencies.into_iter().rev()),
+            Err(e) => report.broken_references.push(BrokenReference {
+                reference,
+                message: format!("{:#}", e),
+            }),
+        }
+    }
+    report
+}
+
+/// コードが参照するシェーダーと、素材のディレクトリにあるファイルを起点にする。<br />
+/// Shaders referenced by code and the files in the asset directories are the starting points.
+fn collect_root_references(root: &Path) -> Vec<AssetReference> {
+    let mut shaders = vec![
+        POST_PROCESS_VERTEX_SHADER,
+        UI_VERTEX_SHADER,
+        UI_FRAGMENT_SHADER,
+    ];
+    for shader_type in ShaderType::get_all_shader_types().iter() {
+        shaders.push(shader_type.get_vertex_shader_file());
+        shaders.push(shader_type.get_fragment_shader_file());
+    }
+    for step in PostProcessStep::get_all_steps().iter() {
+        shaders.push(step.get_fragment_shader_file());
+    }
+    let mut references = shaders
+        .into_iter()
+        .map(|shader| AssetReference::new(ENGINE_SOURCE, AssetKind::Shader, shader))
+        .collect::<Vec<_>>();
+    references.push(AssetReference::new(
+        ENGINE_SOURCE,
+        AssetKind::Material,
+        SURFACE_MATERIALS,
+    ));
+    references.push(AssetReference::new(
+        ENGINE_SOURCE,
+        AssetKind::Music,
+        MUSIC_SETS,
+    ));
+
+    let directories = [
+        (CUTSCENE_DIRECTORY, AssetKind::Scene),
+        (MODEL_DIRECTORY, AssetKind::Model),
+        (TEXTURE_DIRECTORY, AssetKind::Texture),
+    ];
+    for (directory, kind) in directories.iter() {
+        let mut files = vec![];
+        collect_files(root, Path::new(directory), &mut files);
+        files.sort();
+        for file in files.iter() {
+            let kind = match kind {
+                AssetKind::Scene if has_extension(file, &["json"]) => AssetKind::Scene,
+                AssetKind::Model if has_extension(file, &["json"]) => AssetKind::Animator,
+                AssetKind::Model if has_extension(file, &MODEL_EXTENSIONS) => AssetKind::Model,
+                AssetKind::Texture if has_extension(file, &TEXTURE_EXTENSIONS) => {
+                    AssetKind::Texture
+                }
+                _ => continue,
+            };
+            references.push(AssetReference::new(
+                directory,
+                kind,
+                &file.to_string_lossy(),
+            ));
+        }
+    }
+    references
+}
+
+/// ディレクトリの中のファイルを再帰的に集める。パスは`root`からの相対パスのまま。<br />
+/// Recursively collect the files in a directory. Paths stay relative to `root`.
+fn collect_files(root: &Path, directory: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(root.join(directory)) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = directory.join(entry.file_name());
+        if entry.path().is_dir() {
+            collect_files(root, &path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| extensions.iter().any(|x| e.eq_ignore_ascii_case(x)))
+        .unwrap_or(false)
+}
+
+/// 参照先が存在して読み込めるか確かめ、さらに参照している素材を返す。<br />
+/// Check that the referenced asset exists and parses, and return the assets it references in turn.
+fn check_reference(root: &Path, reference: &AssetReference) -> anyhow::Result<Vec<AssetReference>> {
+    let full_path = root.join(&reference.path);
+    if !full_path.is_file() {
+        anyhow::bail!("File not found.");
+    }
+    let file_name = full_path.to_string_lossy();
+    let source = reference.path.as_str();
+    match reference.kind {
+        AssetKind::Shader => {
+            check_spirv(&std::fs::read(&full_path)?)?;
+            Ok(vec![])
+        }
+        AssetKind::Texture => {
+            if has_extension(&full_path, &["ktx2"]) {
+                Ktx2Texture::parse(&std::fs::read(&full_path)?)?;
+            } else {
+                image::image_dimensions(&full_path)?;
+            }
+            Ok(vec![])
+        }
+        AssetKind::Model if has_extension(&full_path, &["mtl"]) => {
+            get_mtl_dependencies(&full_path, source)
+        }
+        // glTFが参照する外部のバッファーは、存在すれば良い。
+        // External buffers referenced by a glTF only need to exist.
+        AssetKind::Model if !has_extension(&full_path, &MODEL_EXTENSIONS) => Ok(vec![]),
+        AssetKind::Model if has_extension(&full_path, &["obj"]) => {
+            get_obj_dependencies(&full_path, source)
+        }
+        AssetKind::Model => get_gltf_dependencies(&full_path, source),
+        AssetKind::Audio => {
+            rodio::Decoder::new(BufReader::new(File::open(&full_path)?))
+                .with_context(|| "Failed to decode audio.")?;
+            Ok(vec![])
+        }
+        AssetKind::Scene => {
+            Cutscene::load(&file_name)?;
+            Ok(vec![])
+        }
+        AssetKind::Animator => {
+            AnimationStateMachineData::load(&file_name)?;
+            Ok(vec![])
+        }
+        AssetKind::Material => {
+            let map = SurfaceMaterialMap::load(&file_name)?;
+            Ok(map
+                .get_sound_files()
+                .map(|sound| AssetReference::new(source, AssetKind::Audio, sound))
+                .collect())
+        }
+        AssetKind::Music => {
+            let sets = MusicSet::from_json_all(&std::fs::read_to_string(&full_path)?)?;
+            let mut scene_names = sets.keys().collect::<Vec<_>>();
+            scene_names.sort();
+            Ok(scene_names
+                .into_iter()
+                .flat_map(|scene_name| sets[scene_name].get_stem_files())
+                .map(|stem| AssetReference::new(source, AssetKind::Audio, stem))
+                .collect())
+        }
+    }
+}
+
+/// SPIR-Vのマジックナンバーと、ワード単位の長さを確かめる。<br />
+/// Check the SPIR-V magic number and that the length is a whole number of words.
+pub fn check_spirv(bytes: &[u8]) -> anyhow::Result<()> {
+    if bytes.len() < SPIRV_HEADER_SIZE || bytes.len() % 4 != 0 {
+        anyhow::bail!("Invalid SPIR-V length: {} bytes.", bytes.len());
+    }
+    let magic = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    if magic != SPIRV_MAGIC {
+        anyhow::bail!("Invalid SPIR-V magic number: {:#010x}.", magic);
+    }
+    Ok(())
+}
+
+/// glTFを解析し、外部のバッファーと画像への参照を返す。埋め込まれたデータは参照にならない。<br />
+/// Parse a glTF and return references to its external buffers and images. Embedded data isn't a reference.
+fn get_gltf_dependencies(full_path: &Path, source: &str) -> anyhow::Result<Vec<AssetReference>> {
+    let gltf = gltf::Gltf::open(full_path).with_context(|| "Failed to parse glTF.")?;
+    let directory = Path::new(source).parent().unwrap_or_else(|| Path::new(""));
+    let mut references = vec![];
+    for buffer in gltf.buffers() {
+        if let gltf::buffer::Source::Uri(uri) = buffer.source() {
+            if !uri.starts_with("data:") {
+                let path = directory.join(uri);
+                references.push(AssetReference::new(
+                    source,
+                    AssetKind::Model,
+                    &path.to_string_lossy(),
+                ));
+            }
+        }
+    }
+    for image in gltf.images() {
+        if let gltf::image::Source::Uri { uri, .. } = image.source() {
+            if !uri.starts_with("data:") {
+                let path = directory.join(uri);
+                references.push(AssetReference::new(
+                    source,
+                    AssetKind::Texture,
+                    &path.to_string_lossy(),
+                ));
+            }
+        }
+    }
+    Ok(references)
+}
+
+/// OBJを解析し、`mtllib`で参照されるマテリアルのファイルを返す。<br />
+/// Parse an OBJ and return the material libraries it references with `mtllib`.
+fn get_obj_dependencies(full_path: &Path, source: &str) -> anyhow::Result<Vec<AssetReference>> {
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ..Default::default()
+    };
+    // マテリアルは参照として別に確かめる。
+    // Materials are checked separately as references.
+    tobj::load_obj_buf(
+        &mut BufReader::new(File::open(full_path)?),
+        &options,
+        |_| Ok(Default::default()),
+    )
+    .with_context(|| "Failed to parse OBJ.")?;
+    let directory = Path::new(source).parent().unwrap_or_else(|| Path::new(""));
+    let content = std::fs::read_to_string(full_path)?;
+    Ok(content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("mtllib"))
+        .flat_map(|names| names.split_whitespace())
+        .map(|name| {
+            AssetReference::new(
+                source,
+                AssetKind::Model,
+                &directory.join(name).to_string_lossy(),
+            )
+        })
+        .collect())
+}
+
+/// マテリアルのファイルを解析し、テクスチャへの参照を返す。<br />
+/// Parse a material library and return references to its textures.
+fn get_mtl_dependencies(full_path: &Path, source: &str) -> anyhow::Result<Vec<AssetReference>> {
+    let (materials, _) = tobj::load_mtl(full_path).with_context(|| "Failed to parse MTL.")?;
+    let directory = Path::new(source).parent().unwrap_or_else(|| Path::new(""));
+    let mut references = vec![];
+    for material in materials.iter() {
+        let textures = [
+            &material.ambient_texture,
+            &material.diffuse_texture,
+            &material.specular_texture,
+            &material.normal_texture,
+            &material.shininess_texture,
+            &material.dissolve_texture,
+        ];
+        for texture in textures.iter().filter(|t| !t.is_empty()) {
+            let path = directory.join(texture);
+            let reference =
+                AssetReference::new(source, AssetKind::Texture, &path.to_string_lossy());
+            if !references.contains(&reference) {
+                references.push(reference);
+            }
+        }
+    }
+    Ok(references)
+}
@@ -0,0 +1,80 @@
+/// 測り始める前に捨てる時間（秒）。シェーダーやパイプラインの準備で最初のフレームは遅い。<br />
+/// Time discarded before measuring, in seconds. The first frames are slow while shaders and pipelines warm up.
+pub const BENCHMARK_WARMUP: f64 = 3.0;
+
+/// フレーム時間を測る長さ（秒）。<br />
+/// How long frame times are measured for, in seconds.
+pub const BENCHMARK_DURATION: f64 = 30.0;
+
+/// ベンチマークの結果。時間は全てミリ秒。<br />
+/// Result of a benchmark. All times are in milliseconds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BenchmarkReport {
+    pub frames: usize,
+    pub average_fps: f64,
+    pub average_frame_time: f64,
+    pub min_frame_time: f64,
+    pub max_frame_time: f64,
+    /// 遅い方から1%のフレームの時間。<br />
+    /// Frame time of the slowest one percent of frames.
+    pub percentile_99_frame_time: f64,
+}
+
+/// フレーム時間を集め、決まった時間が経ったら結果をまとめる。<br />
+/// Collects frame times and summarizes them once a fixed time has passed.
+#[derive(Clone, Debug)]
+pub struct Benchmark {
+    warmup: f64,
+    duration: f64,
+    elapsed: f64,
+    frame_times: Vec<f64>,
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Self::new(BENCHMARK_WARMUP, BENCHMARK_DURATION)
+    }
+}
+
+impl Benchmark {
+    pub fn new(warmup: f64, duration: f64) -> Self {
+        Benchmark {
+            warmup,
+            duration,
+            elapsed: 0.0,
+            frame_times: vec![],
+        }
+    }
+
+    pub fn record(&mut self, delta_time: f64) {
+        if self.elapsed >= self.warmup && !self.is_finished() {
+            self.frame_times.push(delta_time);
+        }
+        self.elapsed += delta_time;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.warmup + self.duration
+    }
+
+    /// 測ったフレームがなければ`None`。<br />
+    /// `None` if no frame has been measured.
+    pub fn get_report(&self) -> Option<BenchmarkReport> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+        let mut frame_times = self.frame_times.clone();
+        frame_times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let total = frame_times.iter().sum::<f64>();
+        let frames = frame_times.len();
+        let percentile_index = ((frames as f64 * 0.99).ceil() as usize).max(1).min(frames) - 1;
+        Some(BenchmarkReport {
+            frames,
+            average_fps: frames as f64 / total,
+            average_frame_time: total / frames as f64 * 1000.0,
+            min_frame_time: frame_times[0] * 1000.0,
+            max_frame_time: frame_times[frames - 1] * 1000.0,
+            percentile_99_frame_time: frame_times[percentile_index] * 1000.0,
+        })
+    }
+}
@@ -0,0 +1,394 @@
+use crate::game::shared::components::Bounds;
+use crate::game::shared::structs::{Frustum, Ray};
+use glam::Vec3A;
+use slotmap::DefaultKey;
+use std::collections::HashMap;
+
+/// 葉の境界ボックスを広げる余白（メートル）。少し動いただけなら木を直さずに済む。<br />
+/// Margin in meters that leaf boxes are enlarged by, so that small movements don't touch the tree.
+pub const BVH_MARGIN: f32 = 0.1;
+
+#[derive(Copy, Clone, Debug)]
+struct BvhNode {
+    bounds: Bounds,
+    parent: Option<usize>,
+    children: Option<[usize; 2]>,
+    entity: Option<DefaultKey>,
+    /// 葉からの高さ。葉は0。<br />
+    /// Height above the leaves, 0 for a leaf.
+    height: usize,
+}
+
+/// エンティティの境界ボックスを入れる動的な境界ボリューム階層。<br />
+/// 動いたエンティティは葉の箱を広げて祖先を合わせ直すだけで、木全体は作り直さない。<br />
+/// 合わせ直す時に回転で木の釣り合いを取る。<br />
+/// A dynamic bounding volume hierarchy holding the bounding boxes of entities.<br />
+/// A moved entity only refits its leaf and the ancestors, without rebuilding the whole tree.<br />
+/// The tree is kept balanced with rotations while refitting.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    free_nodes: Vec<usize>,
+    root: Option<usize>,
+    leaves: HashMap<DefaultKey, usize>,
+    margin: f32,
+}
+
+impl Default for Bvh {
+    fn default() -> Self {
+        Self::new(BVH_MARGIN)
+    }
+}
+
+impl Bvh {
+    pub fn new(margin: f32) -> Self {
+        Bvh {
+            nodes: vec![],
+            free_nodes: vec![],
+            root: None,
+            leaves: HashMap::new(),
+            margin: margin.max(0.0),
+        }
+    }
+
+    pub fn contains(&self, entity: DefaultKey) -> bool {
+        self.leaves.contains_key(&entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = DefaultKey> + '_ {
+        self.leaves.keys().copied()
+    }
+
+    /// 葉に入っている余白付きの境界ボックスを取得する。<br />
+    /// Get the bounding box with the margin stored in the leaf.
+    pub fn get_bounds(&self, entity: DefaultKey) -> Option<Bounds> {
+        self.leaves
+            .get(&entity)
+            .map(|index| self.nodes[*index].bounds)
+    }
+
+    /// 根から最も深い葉までの節の数。空なら0。<br />
+    /// Number of nodes from the root to the deepest leaf, or 0 when empty.
+    pub fn get_height(&self) -> usize {
+        self.root
+            .map(|root| self.nodes[root].height + 1)
+            .unwrap_or(0)
+    }
+
+    /// エンティティを入れる。既に入っていれば境界ボックスを更新する。<br />
+    /// Insert an entity, or update its bounding box if it's already inside.
+    pub fn insert(&mut self, entity: DefaultKey, bounds: Bounds) {
+        if self.contains(entity) {
+            self.update(entity, bounds);
+            return;
+        }
+        let leaf = self.allocate_node(BvhNode {
+            bounds: self.enlarge(&bounds),
+            parent: None,
+            children: None,
+            entity: Some(entity),
+            height: 0,
+        });
+        self.leaves.insert(entity, leaf);
+        self.insert_leaf(leaf);
+    }
+
+    /// エンティティの新しい境界ボックスを反映する。余白の中に収まっていれば何もしない。<br />
+    /// 木を直した場合は`true`を返す。<br />
+    /// Apply the new bounding box of an entity. Nothing happens if it still fits inside the margin.<br />
+    /// Returns `true` if the tree was refitted.
+    pub fn update(&mut self, entity: DefaultKey, bounds: Bounds) -> bool {
+        let leaf = match self.leaves.get(&entity) {
+            Some(leaf) => *leaf,
+            None => {
+                self.insert(entity, bounds);
+                return true;
+            }
+        };
+        if contains_bounds(&self.nodes[leaf].bounds, &bounds) {
+            return false;
+        }
+        self.nodes[leaf].bounds = self.enlarge(&bounds);
+        self.refit(self.nodes[leaf].parent);
+        true
+    }
+
+    pub fn remove(&mut self, entity: DefaultKey) -> bool {
+        let leaf = match self.leaves.remove(&entity) {
+            Some(leaf) => leaf,
+            None => return false,
+        };
+        let parent = match self.nodes[leaf].parent {
+            Some(parent) => parent,
+            None => {
+                self.root = None;
+                self.free_node(leaf);
+                return true;
+            }
+        };
+        let [left, right] = self.nodes[parent]
+            .children
+            .expect("Failed to get the children of a BVH node.");
+        let sibling = if left == leaf { right } else { left };
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(grandparent) => {
+                self.replace_child(grandparent, parent, sibling);
+                self.free_node(parent);
+                self.refit(Some(grandparent));
+            }
+            None => {
+                self.root = Some(sibling);
+                self.free_node(parent);
+            }
+        }
+        self.free_node(leaf);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free_nodes.clear();
+        self.leaves.clear();
+        self.root = None;
+    }
+
+    /// 境界ボックスが条件を満たす葉のエンティティを集める。条件を満たさない節の下は調べない。<br />
+    /// Collect the entities of leaves whose bounding boxes pass the test. Nodes failing it aren't descended into.
+    pub fn query<F>(&self, mut test: F) -> Vec<DefaultKey>
+    where
+        F: FnMut(&Bounds) -> bool,
+    {
+        let mut entities = vec![];
+        let mut stack = self.root.into_iter().collect::<Vec<_>>();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !test(&node.bounds) {
+                continue;
+            }
+            match (node.children, node.entity) {
+                (Some([left, right]), _) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+                (None, Some(entity)) => entities.push(entity),
+                (None, None) => (),
+            }
+        }
+        entities
+    }
+
+    /// 視錐台と重なるかもしれないエンティティを集める。<br />
+    /// Collect the entities that may overlap the frustum.
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<DefaultKey> {
+        self.query(|bounds| frustum.check_bounds(bounds))
+    }
+
+    /// 光線と交差する最も近いエンティティを求める。<br />
+    /// 葉の箱に当たったエンティティは`hit`で正確に判定し、それより遠い節は調べない。<br />
+    /// Find the nearest entity intersecting the ray.<br />
+    /// Entities whose leaf box is hit are tested exactly by `hit`, and nodes farther than the nearest hit are skipped.
+    pub fn raycast<F>(&self, ray: &Ray, mut hit: F) -> Option<(DefaultKey, f32)>
+    where
+        F: FnMut(DefaultKey) -> Option<f32>,
+    {
+        let mut nearest: Option<(DefaultKey, f32)> = None;
+        let mut stack = vec![];
+        if let Some(root) = self.root {
+            if let Some(distance) = ray.intersect_bounds(&self.nodes[root].bounds) {
+                stack.push((root, distance));
+            }
+        }
+        while let Some((index, distance)) = stack.pop() {
+            if nearest.map(|(_, d)| distance > d).unwrap_or(false) {
+                continue;
+            }
+            let node = &self.nodes[index];
+            match (node.children, node.entity) {
+                (Some(children), _) => {
+                    let mut hits = children
+                        .iter()
+                        .filter_map(|child| {
+                            ray.intersect_bounds(&self.nodes[*child].bounds)
+                                .map(|d| (*child, d))
+                        })
+                        .collect::<Vec<_>>();
+                    // 近い方を先に調べるので、遠い方は後ろに積む。
+                    // The nearer child is visited first, so the farther one goes onto the stack first.
+                    hits.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+                    stack.extend(hits);
+                }
+                (None, Some(entity)) => {
+                    if let Some(d) = hit(entity) {
+                        if nearest.map(|(_, nearest)| d < nearest).unwrap_or(true) {
+                            nearest = Some((entity, d));
+                        }
+                    }
+                }
+                (None, None) => (),
+            }
+        }
+        nearest
+    }
+
+    fn enlarge(&self, bounds: &Bounds) -> Bounds {
+        let margin = Vec3A::splat(self.margin);
+        Bounds::new(bounds.min - margin, bounds.max + margin)
+    }
+
+    fn allocate_node(&mut self, node: BvhNode) -> usize {
+        match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn free_node(&mut self, index: usize) {
+        self.nodes[index].parent = None;
+        self.nodes[index].children = None;
+        self.nodes[index].entity = None;
+        self.nodes[index].height = 0;
+        self.free_nodes.push(index);
+    }
+
+    fn replace_child(&mut self, parent: usize, old_child: usize, new_child: usize) {
+        if let Some(children) = self.nodes[parent].children.as_mut() {
+            for child in children.iter_mut() {
+                if *child == old_child {
+                    *child = new_child;
+                }
+            }
+        }
+    }
+
+    /// 葉を入れる兄弟を、囲む箱の表面積が最も増えない方へ降りて選ぶ。<br />
+    /// Choose the sibling for a leaf by descending towards the child whose surface area grows the least.
+    fn insert_leaf(&mut self, leaf: usize) {
+        let mut sibling = match self.root {
+            Some(root) => root,
+            None => {
+                self.root = Some(leaf);
+                return;
+            }
+        };
+        let leaf_bounds = self.nodes[leaf].bounds;
+        while let Some([left, right]) = self.nodes[sibling].children {
+            let cost = |index: usize| {
+                let bounds = &self.nodes[index].bounds;
+                get_surface_area(&union(bounds, &leaf_bounds)) - get_surface_area(bounds)
+            };
+            sibling = if cost(left) <= cost(right) {
+                left
+            } else {
+                right
+            };
+        }
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.allocate_node(BvhNode {
+            bounds: union(&self.nodes[sibling].bounds, &leaf_bounds),
+            parent: old_parent,
+            children: Some([sibling, leaf]),
+            entity: None,
+            height: 0,
+        });
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+        match old_parent {
+            Some(old_parent) => self.replace_child(old_parent, sibling, parent),
+            None => self.root = Some(parent),
+        }
+        self.refit(Some(parent));
+    }
+
+    /// 節から根までの箱を子を囲むように合わせ直し、途中で釣り合いを取る。<br />
+    /// Refit the boxes from a node up to the root so that they enclose their children, balancing along the way.
+    fn refit(&mut self, mut index: Option<usize>) {
+        while let Some(current) = index {
+            let current = self.balance(current);
+            self.update_node(current);
+            index = self.nodes[current].parent;
+        }
+    }
+
+    fn update_node(&mut self, index: usize) {
+        if let Some([left, right]) = self.nodes[index].children {
+            self.nodes[index].bounds = union(&self.nodes[left].bounds, &self.nodes[right].bounds);
+            self.nodes[index].height = 1 + self.nodes[left].height.max(self.nodes[right].height);
+        }
+    }
+
+    /// 片方の子が二段以上高ければ、その子を持ち上げる。持ち上げた後にこの場所にある節を返す。<br />
+    /// Lift a child up if it's two or more levels taller than the other. Returns the node now in this place.
+    fn balance(&mut self, index: usize) -> usize {
+        let [left, right] = match self.nodes[index].children {
+            Some(children) => children,
+            None => return index,
+        };
+        let difference = self.nodes[right].height as isize - self.nodes[left].height as isize;
+        if difference > 1 {
+            self.rotate(index, right, 1)
+        } else if difference < -1 {
+            self.rotate(index, left, 0)
+        } else {
+            index
+        }
+    }
+
+    /// `slot`番目の子`child`を`index`の場所に持ち上げる。<br />
+    /// `child`の低い方の孫を`index`に渡し、高い方の孫は`child`に残す。<br />
+    /// Lift `child`, the child in `slot`, into the place of `index`.<br />
+    /// The shorter grandchild is handed over to `index` and the taller one stays with `child`.
+    fn rotate(&mut self, index: usize, child: usize, slot: usize) -> usize {
+        let [first, second] = self.nodes[child]
+            .children
+            .expect("Failed to get the children of a BVH node.");
+        let (taller, shorter) = if self.nodes[first].height > self.nodes[second].height {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let parent = self.nodes[index].parent;
+        self.nodes[child].children = Some([index, taller]);
+        self.nodes[child].parent = parent;
+        self.nodes[index].parent = Some(child);
+        match parent {
+            Some(parent) => self.replace_child(parent, index, child),
+            None => self.root = Some(child),
+        }
+        if let Some(children) = self.nodes[index].children.as_mut() {
+            children[slot] = shorter;
+        }
+        self.nodes[shorter].parent = Some(index);
+        self.update_node(index);
+        self.update_node(child);
+        child
+    }
+}
+
+fn union(a: &Bounds, b: &Bounds) -> Bounds {
+    Bounds::new(a.min.min(b.min), a.max.max(b.max))
+}
+
+fn contains_bounds(outer: &Bounds, inner: &Bounds) -> bool {
+    outer.min.cmple(inner.min).all() && outer.max.cmpge(inner.max).all()
+}
+
+fn get_surface_area(bounds: &Bounds) -> f32 {
+    let size = bounds.max - bounds.min;
+    2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+}
@@ -1,10 +1,13 @@
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 pub struct Counts {
     pub model_count: Arc<AtomicUsize>,
     pub ssbo_count: AtomicUsize,
-    pub entity_count: usize,
+
+    /// 削除されたエンティティから解放されたSSBOのインデックス。<br />
+    /// SSBO indices released by removed entities.
+    pub free_ssbo_indices: Vec<usize>,
 }
 
 impl Default for Counts {
@@ -18,7 +21,33 @@ impl Counts {
         Counts {
             model_count: Arc::new(AtomicUsize::new(0)),
             ssbo_count: AtomicUsize::new(0),
-            entity_count: 0,
+            free_ssbo_indices: vec![],
         }
     }
+
+    /// SSBOのインデックスを取得する。解放されたインデックスがあれば優先的に再利用する。<br />
+    /// Acquire an SSBO index. Released indices are reused first.
+    pub fn acquire_ssbo_index(&mut self) -> usize {
+        if let Some(index) = self.free_ssbo_indices.pop() {
+            index
+        } else {
+            self.ssbo_count.fetch_add(1, Ordering::SeqCst)
+        }
+    }
+
+    /// SSBOのインデックスを解放し、次のモデルに再利用させる。<br />
+    /// Release an SSBO index so that the next model can reuse it.
+    pub fn release_ssbo_index(&mut self, ssbo_index: usize) {
+        if !self.free_ssbo_indices.contains(&ssbo_index) {
+            self.free_ssbo_indices.push(ssbo_index);
+        }
+    }
+
+    /// 全てのインデックスを返して、最初から数え直す。シーンを片付けた後に使う。<br />
+    /// Give back every index and start counting from the beginning. Used after a scene is torn down.
+    pub fn reset(&mut self) {
+        self.model_count.store(0, Ordering::SeqCst);
+        self.ssbo_count.store(0, Ordering::SeqCst);
+        self.free_ssbo_indices.clear();
+    }
 }
@@ -0,0 +1,318 @@
+use ash::vk::{
+    Format, VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate,
+};
+use glam::{Mat4, Vec2, Vec3A, Vec4};
+use std::convert::TryFrom;
+
+/// 同次座標のwがこれより小さい点はカメラの後ろとみなす。<br />
+/// Points whose homogeneous w is smaller than this are considered behind the camera.
+const MIN_CLIP_W: f32 = 1e-3;
+
+/// 球を描く円一つの線分の数。<br />
+/// Number of segments in each circle drawn for a sphere.
+pub const SPHERE_SEGMENTS: usize = 16;
+
+pub const DEBUG_LINE_VERTEX_SHADER: &str = "./shaders/debug_line_vert.spv";
+pub const DEBUG_LINE_FRAGMENT_SHADER: &str = "./shaders/debug_line_frag.spv";
+
+/// 線のパイプラインに渡す頂点。色はR8G8B8A8_UNORMとして読む。<br />
+/// A vertex passed to the line pipeline. The color is read as R8G8B8A8_UNORM.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [u8; 4],
+}
+
+impl DebugVertex {
+    pub fn new(position: Vec3A, color: [u8; 4]) -> Self {
+        DebugVertex {
+            position: [position.x, position.y, position.z],
+            color,
+        }
+    }
+
+    pub fn get_binding_description(binding: u32) -> VertexInputBindingDescription {
+        VertexInputBindingDescription::builder()
+            .binding(binding)
+            .input_rate(VertexInputRate::VERTEX)
+            .stride(std::mem::size_of::<DebugVertex>() as u32)
+            .build()
+    }
+
+    pub fn get_attribute_description(binding: u32) -> Vec<VertexInputAttributeDescription> {
+        vec![
+            VertexInputAttributeDescription::builder()
+                .binding(binding)
+                .offset(0)
+                .format(Format::R32G32B32_SFLOAT)
+                .location(0)
+                .build(),
+            VertexInputAttributeDescription::builder()
+                .binding(binding)
+                .offset(u32::try_from(memoffset::offset_of!(DebugVertex, color)).unwrap())
+                .format(Format::R8G8B8A8_UNORM)
+                .location(1)
+                .build(),
+        ]
+    }
+}
+
+/// ワールド座標で描くデバッグ用の図形。<br />
+/// A debug shape drawn in world space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DebugShape {
+    Point {
+        position: Vec3A,
+        radius: f32,
+        color: [u8; 4],
+    },
+    Line {
+        from: Vec3A,
+        to: Vec3A,
+        thickness: f32,
+        color: [u8; 4],
+    },
+}
+
+/// 画面に投影したデバッグ用の図形。座標はピクセル単位。<br />
+/// A debug shape projected onto the screen. Coordinates are in pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScreenShape {
+    Circle {
+        center: Vec2,
+        radius: f32,
+        color: [u8; 4],
+    },
+    Line {
+        from: Vec2,
+        to: Vec2,
+        thickness: f32,
+        color: [u8; 4],
+    },
+}
+
+/// 一フレームの間に集めるデバッグ用の図形。<br />
+/// `point`と`line`で描いた図形はUIの上に重ね、`draw_`で始まるものはシーンの中に線のリストとして描く。<br />
+/// Debug shapes gathered during a frame.<br />
+/// Shapes from `point` and `line` are drawn on top of the UI, and those from the `draw_` methods as a line list inside the scene.
+#[derive(Clone, Debug, Default)]
+pub struct DebugDraw {
+    shapes: Vec<DebugShape>,
+    /// 線のリストの頂点。二つで一本の線になる。<br />
+    /// Vertices of the line list, two for each line.
+    line_vertices: Vec<DebugVertex>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        DebugDraw {
+            shapes: vec![],
+            line_vertices: vec![],
+        }
+    }
+
+    /// 点を描く。`radius`はピクセル単位。<br />
+    /// Draw a point. `radius` is in pixels.
+    pub fn point(&mut self, position: Vec3A, radius: f32, color: [u8; 4]) {
+        self.shapes.push(DebugShape::Point {
+            position,
+            radius,
+            color,
+        });
+    }
+
+    /// 線を描く。`thickness`はピクセル単位。<br />
+    /// Draw a line. `thickness` is in pixels.
+    pub fn line(&mut self, from: Vec3A, to: Vec3A, thickness: f32, color: [u8; 4]) {
+        self.shapes.push(DebugShape::Line {
+            from,
+            to,
+            thickness,
+            color,
+        });
+    }
+
+    /// 点を順番に繋いだ折れ線を描く。<br />
+    /// Draw a polyline connecting the points in order.
+    pub fn line_strip(&mut self, points: &[Vec3A], thickness: f32, color: [u8; 4]) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], thickness, color);
+        }
+    }
+
+    /// シーンの中に線を描く。太さは常に1ピクセル。<br />
+    /// Draw a line inside the scene. It's always one pixel thick.
+    pub fn draw_line(&mut self, from: Vec3A, to: Vec3A, color: [u8; 4]) {
+        self.line_vertices.push(DebugVertex::new(from, color));
+        self.line_vertices.push(DebugVertex::new(to, color));
+    }
+
+    /// 軸に沿った箱の12本の辺を描く。<br />
+    /// Draw the twelve edges of an axis-aligned box.
+    pub fn draw_aabb(&mut self, min: Vec3A, max: Vec3A, color: [u8; 4]) {
+        let corners = (0..8)
+            .map(|i| {
+                Vec3A::new(
+                    if i & 1 == 0 { min.x } else { max.x },
+                    if i & 2 == 0 { min.y } else { max.y },
+                    if i & 4 == 0 { min.z } else { max.z },
+                )
+            })
+            .collect::<Vec<_>>();
+        self.draw_box_edges(&corners, color);
+    }
+
+    /// 軸ごとの三つの円で球を描く。<br />
+    /// Draw a sphere as three circles, one around each axis.
+    pub fn draw_sphere(&mut self, center: Vec3A, radius: f32, color: [u8; 4]) {
+        let step = std::f32::consts::PI * 2.0 / SPHERE_SEGMENTS as f32;
+        let point_on = |axis: usize, angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            let offset = match axis {
+                0 => Vec3A::new(0.0, cos, sin),
+                1 => Vec3A::new(cos, 0.0, sin),
+                _ => Vec3A::new(cos, sin, 0.0),
+            };
+            center + offset * radius
+        };
+        for axis in 0..3 {
+            for segment in 0..SPHERE_SEGMENTS {
+                let from = point_on(axis, step * segment as f32);
+                let to = point_on(axis, step * (segment + 1) as f32);
+                self.draw_line(from, to, color);
+            }
+        }
+    }
+
+    /// 視点と投影の行列が映す範囲を描く。深度はVulkanと同じく0から1。<br />
+    /// Draw the volume seen through a view-projection matrix. Depth goes from 0 to 1 as in Vulkan.
+    pub fn draw_frustum(&mut self, view_projection: Mat4, color: [u8; 4]) {
+        let inverse = view_projection.inverse();
+        let corners = (0..8)
+            .map(|i| {
+                let ndc = Vec4::new(
+                    if i & 1 == 0 { -1.0 } else { 1.0 },
+                    if i & 2 == 0 { -1.0 } else { 1.0 },
+                    if i & 4 == 0 { 0.0 } else { 1.0 },
+                    1.0,
+                );
+                let world = inverse * ndc;
+                Vec3A::new(world.x, world.y, world.z) / world.w
+            })
+            .collect::<Vec<_>>();
+        self.draw_box_edges(&corners, color);
+    }
+
+    /// 番号のビットがx、y、zの順に対応する8つの角を繋ぐ。<br />
+    /// Connect eight corners whose index bits correspond to x, y and z in that order.
+    fn draw_box_edges(&mut self, corners: &[Vec3A], color: [u8; 4]) {
+        for i in 0..8 {
+            for bit in [1, 2, 4].iter() {
+                if i & bit == 0 {
+                    self.draw_line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    pub fn get_shapes(&self) -> &[DebugShape] {
+        &self.shapes
+    }
+
+    pub fn get_line_vertices(&self) -> &[DebugVertex] {
+        &self.line_vertices
+    }
+
+    /// 線のリストの頂点を取り出し、空にする。<br />
+    /// Take the vertices of the line list out, leaving it empty.
+    pub fn take_line_vertices(&mut self) -> Vec<DebugVertex> {
+        std::mem::take(&mut self.line_vertices)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty() && self.line_vertices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+        self.line_vertices.clear();
+    }
+
+    /// 図形を画面に投影する。カメラの後ろの点は捨て、後ろにはみ出す線はカメラの前で切る。<br />
+    /// Project the shapes onto the screen. Points behind the camera are dropped, and lines reaching behind it are cut in front of the camera.
+    pub fn project(&self, view_projection: Mat4, width: f32, height: f32) -> Vec<ScreenShape> {
+        let to_clip =
+            |position: Vec3A| view_projection * Vec4::new(position.x, position.y, position.z, 1.0);
+        // 描画とピックの光線と同じく、NDCのyは画面の下向き。
+        // As with rendering and picking rays, NDC y points down the screen.
+        let to_screen = |clip: Vec4| {
+            Vec2::new(
+                (clip.x / clip.w + 1.0) * 0.5 * width,
+                (clip.y / clip.w + 1.0) * 0.5 * height,
+            )
+        };
+        self.shapes
+            .iter()
+            .filter_map(|shape| match *shape {
+                DebugShape::Point {
+                    position,
+                    radius,
+                    color,
+                } => project_point(view_projection, position, width, height).map(|center| {
+                    ScreenShape::Circle {
+                        center,
+                        radius,
+                        color,
+                    }
+                }),
+                DebugShape::Line {
+                    from,
+                    to,
+                    thickness,
+                    color,
+                } => {
+                    let (mut from, mut to) = (to_clip(from), to_clip(to));
+                    if from.w < MIN_CLIP_W && to.w < MIN_CLIP_W {
+                        return None;
+                    }
+                    // クリップ空間では線形なので、wが最小値になる所で切る。
+                    // Clip space is linear, so the line is cut where w reaches the minimum.
+                    let cut = |inside: Vec4, outside: Vec4| {
+                        let t = (inside.w - MIN_CLIP_W) / (inside.w - outside.w);
+                        inside + (outside - inside) * t
+                    };
+                    if from.w < MIN_CLIP_W {
+                        from = cut(to, from);
+                    } else if to.w < MIN_CLIP_W {
+                        to = cut(from, to);
+                    }
+                    Some(ScreenShape::Line {
+                        from: to_screen(from),
+                        to: to_screen(to),
+                        thickness,
+                        color,
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+/// ワールド座標の点を画面に投影する。カメラの後ろにあれば`None`を返す。<br />
+/// Project a point in world space onto the screen. Returns `None` if it's behind the camera.
+pub fn project_point(
+    view_projection: Mat4,
+    position: Vec3A,
+    width: f32,
+    height: f32,
+) -> Option<Vec2> {
+    let clip = view_projection * Vec4::new(position.x, position.y, position.z, 1.0);
+    if clip.w < MIN_CLIP_W {
+        return None;
+    }
+    Some(Vec2::new(
+        (clip.x / clip.w + 1.0) * 0.5 * width,
+        (clip.y / clip.w + 1.0) * 0.5 * height,
+    ))
+}
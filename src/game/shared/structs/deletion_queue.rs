@@ -0,0 +1,59 @@
+use std::collections::VecDeque;
+
+/// フレームの番号ごとに削除を待たせるキュー。<br />
+/// 削除は予約した時のフレームを使い終わるまで取り出されない。フレームの番号は増える一方とする。<br />
+/// A queue holding deletions back per frame number.<br />
+/// A deletion isn't taken out until the frame it was scheduled in is finished. Frame numbers only ever increase.
+#[derive(Debug)]
+pub struct DeletionQueue<T> {
+    pending: VecDeque<(u64, T)>,
+}
+
+impl<T> Default for DeletionQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> DeletionQueue<T> {
+    pub fn new() -> Self {
+        DeletionQueue {
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// 指定したフレームがまだ使っているかもしれないリソースの削除を予約する。<br />
+    /// Schedule the deletion of a resource the given frame may still be using.
+    pub fn push(&mut self, frame: u64, item: T) {
+        self.pending.push_back((frame, item));
+    }
+
+    /// `completed_frame`までのフレームが終わったので、それまでに予約した削除を取り出す。<br />
+    /// Frames up to `completed_frame` are finished, so take out the deletions scheduled until then.
+    pub fn collect_completed(&mut self, completed_frame: u64) -> Vec<T> {
+        let mut completed = vec![];
+        while let Some((frame, _)) = self.pending.front() {
+            if *frame > completed_frame {
+                break;
+            }
+            if let Some((_, item)) = self.pending.pop_front() {
+                completed.push(item);
+            }
+        }
+        completed
+    }
+
+    /// GPUが止まっている時に、全ての削除を取り出す。<br />
+    /// Take out every deletion while the GPU is idle.
+    pub fn drain(&mut self) -> Vec<T> {
+        self.pending.drain(..).map(|(_, item)| item).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
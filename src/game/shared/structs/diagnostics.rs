@@ -0,0 +1,223 @@
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 覚えておく検証レイヤーのメッセージの数。<br />
+/// Number of validation layer messages kept around.
+pub const VALIDATION_MESSAGE_CAPACITY: usize = 64;
+
+/// クラッシュレポートを書き出すフォルダー。<br />
+/// Folder crash reports are written to.
+pub const CRASH_REPORT_DIRECTORY: &str = "./crash_reports";
+
+static VALIDATION_LOG: OnceCell<Mutex<ValidationLog>> = OnceCell::new();
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ValidationSeverity {
+    Warning,
+    Error,
+}
+
+impl ValidationSeverity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ValidationSeverity::Warning => "WARNING",
+            ValidationSeverity::Error => "ERROR",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationMessage {
+    pub severity: ValidationSeverity,
+    pub message: String,
+}
+
+/// 最後の数件の検証レイヤーのメッセージ。一杯になれば古いものから捨てる。<br />
+/// The last few validation layer messages. The oldest ones are thrown away once full.
+#[derive(Clone, Debug)]
+pub struct ValidationLog {
+    capacity: usize,
+    messages: VecDeque<ValidationMessage>,
+}
+
+impl ValidationLog {
+    pub fn new(capacity: usize) -> Self {
+        ValidationLog {
+            capacity,
+            messages: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, severity: ValidationSeverity, message: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.messages.len() >= self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(ValidationMessage {
+            severity,
+            message: message.to_string(),
+        });
+    }
+
+    /// 古い順のメッセージ。<br />
+    /// Messages from the oldest.
+    pub fn get_messages(&self) -> Vec<ValidationMessage> {
+        self.messages.iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// 検証レイヤーのメッセージを覚えておく。デバッグのコールバックから呼ぶ。<br />
+/// Keep a validation layer message around. Called from the debug callback.
+pub fn record_validation_message(severity: ValidationSeverity, message: &str) {
+    VALIDATION_LOG
+        .get_or_init(|| Mutex::new(ValidationLog::new(VALIDATION_MESSAGE_CAPACITY)))
+        .lock()
+        .push(severity, message);
+}
+
+pub fn get_validation_messages() -> Vec<ValidationMessage> {
+    VALIDATION_LOG
+        .get()
+        .map(|log| log.lock().get_messages())
+        .unwrap_or_default()
+}
+
+/// 一つのパスで実行した二次コマンドバッファの数。<br />
+/// Number of secondary command buffers executed in a single pass.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PassSubmission {
+    pub name: String,
+    pub secondary_command_buffers: usize,
+}
+
+/// 今のフレームで記録したパスと、そのコマンドバッファの概要。<br />
+/// Summary of the passes recorded in the current frame and their command buffers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameSubmission {
+    pub frame_index: usize,
+    pub passes: Vec<PassSubmission>,
+}
+
+impl FrameSubmission {
+    pub fn begin(&mut self, frame_index: usize) {
+        self.frame_index = frame_index;
+        self.passes.clear();
+    }
+
+    pub fn add_pass(&mut self, name: &str) {
+        self.passes.push(PassSubmission {
+            name: name.to_string(),
+            secondary_command_buffers: 0,
+        });
+    }
+
+    /// 最後に始めたパスに二次コマンドバッファを数える。<br />
+    /// Count secondary command buffers towards the pass begun last.
+    pub fn add_secondary_command_buffers(&mut self, count: usize) {
+        if let Some(pass) = self.passes.last_mut() {
+            pass.secondary_command_buffers += count;
+        }
+    }
+
+    pub fn get_secondary_command_buffer_count(&self) -> usize {
+        self.passes
+            .iter()
+            .map(|pass| pass.secondary_command_buffers)
+            .sum()
+    }
+}
+
+/// Vulkanのエラーやデバイスの喪失の時に書き出す診断の情報。<br />
+/// Diagnostic information written out on a Vulkan error or a lost device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CrashReport {
+    pub reason: String,
+    pub frame: FrameSubmission,
+    pub validation_messages: Vec<ValidationMessage>,
+    /// 作ってあるパイプラインの名前。<br />
+    /// Names of the pipelines that have been created.
+    pub pipelines: Vec<String>,
+    /// GPUが最後に通ったチェックポイント。拡張機能がなければ空。<br />
+    /// Checkpoints the GPU passed last. Empty without the extension.
+    pub checkpoints: Vec<String>,
+    /// VMAの統計のJSON。<br />
+    /// VMA statistics as JSON.
+    pub memory_statistics: Option<String>,
+}
+
+impl std::fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Reason: {}", self.reason)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "[Frame {}] {} secondary command buffers",
+            self.frame.frame_index,
+            self.frame.get_secondary_command_buffer_count()
+        )?;
+        for pass in self.frame.passes.iter() {
+            writeln!(
+                f,
+                "  {}: {} secondary command buffers",
+                pass.name, pass.secondary_command_buffers
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "[Pipelines]")?;
+        for pipeline in self.pipelines.iter() {
+            writeln!(f, "  {}", pipeline)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "[Checkpoints]")?;
+        if self.checkpoints.is_empty() {
+            writeln!(f, "  Unavailable")?;
+        }
+        for checkpoint in self.checkpoints.iter() {
+            writeln!(f, "  {}", checkpoint)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "[Validation messages] {}",
+            self.validation_messages.len()
+        )?;
+        for message in self.validation_messages.iter() {
+            writeln!(f, "  {}: {}", message.severity.as_str(), message.message)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "[Memory]")?;
+        writeln!(
+            f,
+            "{}",
+            self.memory_statistics.as_deref().unwrap_or("Unavailable")
+        )
+    }
+}
+
+impl CrashReport {
+    /// フォルダーにレポートを書き出し、そのファイルのパスを返す。<br />
+    /// Write the report into a folder and return the path of the file.
+    pub fn write(&self, directory: &Path) -> anyhow::Result<PathBuf> {
+        std::fs::create_dir_all(directory)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+        let path = directory.join(format!("crash_{}.txt", timestamp));
+        std::fs::write(&path, self.to_string())?;
+        Ok(path)
+    }
+}
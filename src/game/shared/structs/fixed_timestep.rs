@@ -0,0 +1,61 @@
+/// シミュレーションを一回進める時間。<br />
+/// Time advanced by a single simulation step.
+pub const SIMULATION_TIMESTEP: f64 = 1.0 / 60.0;
+
+/// 一フレームで進めるシミュレーションの最大の回数。<br />
+/// Maximum number of simulation steps run in a single frame.
+pub const MAX_SIMULATION_STEPS: u32 = 5;
+
+/// フレームの経過時間を溜め、固定の間隔でシミュレーションを何回進めるかを決める。<br />
+/// 残った時間の割合で、前の状態と今の状態の間を補間して描画する。<br />
+/// Accumulates frame time and decides how many times the simulation advances at a fixed interval.<br />
+/// The fraction of time left over is used to render interpolated between the previous and the current state.
+#[derive(Copy, Clone, Debug)]
+pub struct FixedTimestep {
+    timestep: f64,
+    max_steps: u32,
+    accumulator: f64,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(SIMULATION_TIMESTEP, MAX_SIMULATION_STEPS)
+    }
+}
+
+impl FixedTimestep {
+    pub fn new(timestep: f64, max_steps: u32) -> Self {
+        FixedTimestep {
+            timestep,
+            max_steps,
+            accumulator: 0.0,
+        }
+    }
+
+    /// 経過時間を加え、進めるステップの数を返す。<br />
+    /// 最大の回数で追いつけない分の時間は捨て、遅いフレームの後に何度も進め続けないようにする。<br />
+    /// Add the elapsed time and return the number of steps to run.<br />
+    /// Time that can't be caught up within the maximum steps is dropped, so a slow frame doesn't cause a spiral of steps.
+    pub fn advance(&mut self, delta_time: f64) -> u32 {
+        self.accumulator += delta_time.max(0.0);
+        let mut steps = 0;
+        while self.accumulator >= self.timestep && steps < self.max_steps {
+            self.accumulator -= self.timestep;
+            steps += 1;
+        }
+        if self.accumulator >= self.timestep {
+            self.accumulator = 0.0;
+        }
+        steps
+    }
+
+    /// 最後のステップから次のステップまでの進み具合。0なら最後のステップの状態そのもの。<br />
+    /// Progress from the last step towards the next one. Zero means exactly the state of the last step.
+    pub fn get_alpha(&self) -> f32 {
+        (self.accumulator / self.timestep).max(0.0).min(1.0) as f32
+    }
+
+    pub fn get_timestep(&self) -> f64 {
+        self.timestep
+    }
+}
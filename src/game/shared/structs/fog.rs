@@ -0,0 +1,86 @@
+use crate::game::ui::TweakRegistry;
+
+const FOG_TWEAK: &str = "Lighting/Fog";
+const FOG_DENSITY_TWEAK: &str = "Lighting/Fog Density";
+const FOG_HEIGHT_FALLOFF_TWEAK: &str = "Lighting/Fog Height Falloff";
+const FOG_BASE_HEIGHT_TWEAK: &str = "Lighting/Fog Base Height";
+
+/// 霧を空の色に溶け込ませ始める、ファークリップ面までの距離の割合。`lighting.glsl`と合わせる。<br />
+/// Fraction of the distance to the far plane where the fog starts blending into the sky color. Kept in sync with `lighting.glsl`.
+pub const FOG_EDGE_START: f32 = 0.8;
+
+/// 高さと距離による指数関数的な霧の設定。色は空の色を使う。<br />
+/// Settings of the exponential height and distance fog. Its color is the sky color.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FogSettings {
+    pub is_enabled: bool,
+    /// 基準の高さでの、一メートルごとの霧の濃さ。<br />
+    /// Fog density per meter at the base height.
+    pub density: f32,
+    /// 基準の高さより上で、一メートル上がるごとに霧が薄くなる割合。<br />
+    /// How quickly the fog thins out for every meter above the base height.
+    pub height_falloff: f32,
+    /// これより下では霧が一番濃い。<br />
+    /// The fog is thickest below this height.
+    pub base_height: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        FogSettings {
+            is_enabled: true,
+            density: 0.0025,
+            height_falloff: 0.02,
+            base_height: 0.0,
+        }
+    }
+}
+
+impl FogSettings {
+    /// カメラから`distance`離れた、高さ`height`の点が霧を通して見える割合。シェーダーと同じ式を使う。<br />
+    /// 霧を使う時は、ファークリップ面に届く前に空の色に溶け込ませ、遠くの地形が急に消えないようにする。<br />
+    /// How much of a point `distance` away from the camera at `height` is visible through the fog, using the same formula as the shaders.<br />
+    /// With the fog enabled, points blend into the sky color before reaching the far plane so distant terrain doesn't pop out.
+    pub fn get_visibility(&self, distance: f32, height: f32, far_plane: f32) -> f32 {
+        if !self.is_enabled {
+            return 1.0;
+        }
+        let height = (height - self.base_height).max(0.0);
+        let density = self.density * (-self.height_falloff * height).exp();
+        let visibility = (-distance * density).exp();
+        let edge = 1.0 - smooth_step(far_plane * FOG_EDGE_START, far_plane, distance);
+        visibility.min(edge).max(0.0).min(1.0)
+    }
+
+    /// 設定を調整パネルに登録する。<br />
+    /// Register the settings to the tweak panel.
+    pub fn register_tweaks(&mut self, tweaks: &mut TweakRegistry) {
+        tweaks.register_bool(FOG_TWEAK, self.is_enabled);
+        tweaks.register_f32(FOG_DENSITY_TWEAK, self.density, 0.0, 0.05);
+        tweaks.register_f32(FOG_HEIGHT_FALLOFF_TWEAK, self.height_falloff, 0.0, 0.5);
+        tweaks.register_f32(FOG_BASE_HEIGHT_TWEAK, self.base_height, -50.0, 100.0);
+        self.apply_tweaks(tweaks);
+    }
+
+    pub fn apply_tweaks(&mut self, tweaks: &TweakRegistry) {
+        if let Some(is_enabled) = tweaks.get_bool(FOG_TWEAK) {
+            self.is_enabled = is_enabled;
+        }
+        if let Some(density) = tweaks.get_f32(FOG_DENSITY_TWEAK) {
+            self.density = density;
+        }
+        if let Some(height_falloff) = tweaks.get_f32(FOG_HEIGHT_FALLOFF_TWEAK) {
+            self.height_falloff = height_falloff;
+        }
+        if let Some(base_height) = tweaks.get_f32(FOG_BASE_HEIGHT_TWEAK) {
+            self.base_height = base_height;
+        }
+    }
+}
+
+/// GLSLの`smoothstep`と同じ。<br />
+/// Same as `smoothstep` in GLSL.
+fn smooth_step(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
+    t * t * (3.0 - 2.0 * t)
+}
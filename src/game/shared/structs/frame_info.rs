@@ -0,0 +1,11 @@
+use crate::game::shared::structs::PushConstant;
+
+/// どのグラフィックスAPIでも同じ、一フレームの描画の情報。<br />
+/// Per-frame rendering information, the same for every graphics API.
+#[derive(Copy, Clone, Debug)]
+pub struct FrameInfo {
+    pub push_constant: PushConstant,
+    /// 描画中のフレームの番号。モデルはこれでフレームごとのコマンドバッファを選ぶ。<br />
+    /// Index of the frame being rendered, used by models to pick their per-frame command buffers.
+    pub frame_index: usize,
+}
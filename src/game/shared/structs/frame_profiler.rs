@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// グラフに表示するフレームの数。<br />
+/// Number of frames shown in the graph.
+pub const PROFILER_HISTORY: usize = 120;
+
+/// 一フレームの計測結果。時間は全てミリ秒。<br />
+/// Measurements of a single frame. All times are in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameTimings {
+    pub frame_time: f64,
+    /// CPUの区間（更新、記録、送信など）の時間。<br />
+    /// Times of CPU scopes, such as update, record and submit.
+    pub cpu_scopes: Vec<(String, f64)>,
+    /// レンダーパスごとのGPUの時間。<br />
+    /// GPU time of each render pass.
+    pub gpu_passes: Vec<(String, f64)>,
+    /// このフレームのヒープの確保の回数。<br />
+    /// Number of heap allocations in this frame.
+    pub allocations: usize,
+}
+
+impl FrameTimings {
+    pub fn get_gpu_time(&self) -> f64 {
+        self.gpu_passes.iter().map(|(_, time)| *time).sum()
+    }
+}
+
+/// CPUとGPUの計測を集め、最近のフレームの履歴を残す。<br />
+/// Collects CPU and GPU measurements, keeping a history of recent frames.
+#[derive(Clone, Debug, Default)]
+pub struct FrameProfiler {
+    history: VecDeque<FrameTimings>,
+    current: FrameTimings,
+    /// 前のフレームを締めた時点の確保の総数。<br />
+    /// Total allocation count when the previous frame was closed.
+    last_allocation_count: Option<usize>,
+}
+
+impl FrameProfiler {
+    pub fn new() -> Self {
+        FrameProfiler {
+            history: VecDeque::with_capacity(PROFILER_HISTORY),
+            current: FrameTimings::default(),
+            last_allocation_count: None,
+        }
+    }
+
+    pub fn record_cpu(&mut self, name: &str, duration: Duration) {
+        self.current
+            .cpu_scopes
+            .push((name.to_string(), duration.as_secs_f64() * 1000.0));
+    }
+
+    /// グラフィックスが計測した区間とパスを加える。GPUの結果は数フレーム遅れて届く。<br />
+    /// Add the scopes and passes measured by the graphics. GPU results arrive a few frames late.
+    pub fn add_timings(&mut self, timings: FrameTimings) {
+        self.current.cpu_scopes.extend(timings.cpu_scopes);
+        self.current.gpu_passes.extend(timings.gpu_passes);
+    }
+
+    /// 起動してからの確保の総数から、このフレームの確保の回数を求める。最初のフレームは基準にするだけ。<br />
+    /// Derive the allocations of this frame from the total count since startup. The first frame only sets the baseline.
+    pub fn record_allocations(&mut self, total_allocations: usize) {
+        if let Some(last) = self.last_allocation_count {
+            self.current.allocations = total_allocations.saturating_sub(last);
+        }
+        self.last_allocation_count = Some(total_allocations);
+    }
+
+    /// フレームを締めて履歴に加える。<br />
+    /// Close the frame and add it to the history.
+    pub fn end_frame(&mut self, delta_time: f64) {
+        let mut timings = std::mem::take(&mut self.current);
+        timings.frame_time = delta_time * 1000.0;
+        if self.history.len() == PROFILER_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(timings);
+    }
+
+    pub fn get_history(&self) -> &VecDeque<FrameTimings> {
+        &self.history
+    }
+
+    pub fn get_average_frame_time(&self) -> f64 {
+        if self.history.is_empty() {
+            return 0.0;
+        }
+        self.history.iter().map(|t| t.frame_time).sum::<f64>() / self.history.len() as f64
+    }
+
+    pub fn get_average_allocations(&self) -> f64 {
+        if self.history.is_empty() {
+            return 0.0;
+        }
+        self.history
+            .iter()
+            .map(|t| t.allocations as f64)
+            .sum::<f64>()
+            / self.history.len() as f64
+    }
+
+    /// 履歴の中で平均したCPUの区間の時間。最初に現れた順に並べる。<br />
+    /// Times of CPU scopes averaged over the history, in the order they first appeared.
+    pub fn get_cpu_averages(&self) -> Vec<(String, f64)> {
+        Self::get_averages(self.history.iter().map(|t| &t.cpu_scopes))
+    }
+
+    pub fn get_gpu_averages(&self) -> Vec<(String, f64)> {
+        Self::get_averages(self.history.iter().map(|t| &t.gpu_passes))
+    }
+
+    /// 名前ごとに、計測があったフレームだけで平均する。<br />
+    /// Average by name, over only the frames that have a measurement.
+    fn get_averages<'a, I>(frames: I) -> Vec<(String, f64)>
+    where
+        I: Iterator<Item = &'a Vec<(String, f64)>>,
+    {
+        let mut totals: Vec<(String, f64, usize)> = vec![];
+        for measurements in frames {
+            for (name, time) in measurements.iter() {
+                match totals.iter_mut().find(|(n, _, _)| n == name) {
+                    Some((_, total, count)) => {
+                        *total += *time;
+                        *count += 1;
+                    }
+                    None => totals.push((name.clone(), *time, 1)),
+                }
+            }
+        }
+        totals
+            .into_iter()
+            .map(|(name, total, count)| (name, total / count as f64))
+            .collect()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::game::shared::components::Bounds;
 use glam::{Mat4, Vec3, Vec4};
 
 #[repr(C)]
@@ -19,6 +20,16 @@ pub struct Frustum {
 }
 
 impl Frustum {
+    /// ビュー投影行列から視錐台を作る。<br />
+    /// Create a frustum from a view-projection matrix.
+    pub fn new(matrix: Mat4) -> Self {
+        let mut frustum = Frustum {
+            planes: [Vec4::zero(); 6],
+        };
+        frustum.update(matrix);
+        frustum
+    }
+
     pub fn update(&mut self, matrix: Mat4) {
         let vectors = matrix.to_cols_array_2d();
         self.planes[FrustumSide::LEFT.0] = Vec4::new(
@@ -74,4 +85,30 @@ impl Frustum {
         }
         true
     }
+
+    /// 境界ボックスが視錐台と重なるかもしれないかどうか。面ごとに最も内側の角だけを調べる。<br />
+    /// Whether a bounding box may overlap the frustum. Only the innermost corner is tested against each plane.
+    pub fn check_bounds(&self, bounds: &Bounds) -> bool {
+        for plane in self.planes.iter() {
+            let x = if plane.x >= 0.0 {
+                bounds.max.x
+            } else {
+                bounds.min.x
+            };
+            let y = if plane.y >= 0.0 {
+                bounds.max.y
+            } else {
+                bounds.min.y
+            };
+            let z = if plane.z >= 0.0 {
+                bounds.max.z
+            } else {
+                bounds.min.z
+            };
+            if plane.x * x + plane.y * y + plane.z * z + plane.w < 0.0 {
+                return false;
+            }
+        }
+        true
+    }
 }
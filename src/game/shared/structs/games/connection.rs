@@ -0,0 +1,128 @@
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use parking_lot::Mutex;
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 再接続の最初の待ち時間。<br />
+/// Delay before the first reconnection attempt.
+const BASE_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// 再接続の待ち時間の上限。<br />
+/// Upper bound of the delay between reconnection attempts.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(16);
+
+/// 諦めるまでの再接続の回数。<br />
+/// Number of reconnection attempts before giving up.
+pub const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+
+/// サーバーとの接続の状態。<br />
+/// State of the connection to the server.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConnectionState {
+    Connected,
+    /// 接続が切れて再接続を試みている。`attempt`は失敗した回数。<br />
+    /// The connection was lost and is being re-established. `attempt` is the number of failed attempts.
+    Reconnecting {
+        attempt: u32,
+    },
+    /// 再接続を諦めた。<br />
+    /// Reconnection was given up.
+    Disconnected,
+}
+
+/// UIに知らせる接続の出来事。<br />
+/// Connection events surfaced to the UI.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionEvent {
+    ConnectionLost { reason: String },
+    ReconnectFailed { attempt: u32, retry_in: Duration },
+    Reconnected,
+    GaveUp,
+}
+
+/// 試行回数に応じた指数的な待ち時間を求める。同時に再接続が集中しないように揺らぎを加える。<br />
+/// Compute the exponential delay for an attempt, with jitter so clients don't reconnect all at once.
+pub fn get_backoff_delay(attempt: u32) -> Duration {
+    let delay = BASE_RECONNECT_DELAY
+        .checked_mul(1 << attempt.min(16))
+        .unwrap_or(MAX_RECONNECT_DELAY)
+        .min(MAX_RECONNECT_DELAY);
+    let jitter = rand::thread_rng().gen_range(0.8..1.2);
+    delay.mul_f64(jitter)
+}
+
+/// 接続の状態を保持し、バックグラウンドのタスクからの切断の報告を受け取る。<br />
+/// Holds the connection state and receives connection loss reports from background tasks.
+#[derive(Clone)]
+pub struct ConnectionMonitor {
+    state: Arc<Mutex<ConnectionState>>,
+    event_sender: Sender<ConnectionEvent>,
+    event_receiver: Receiver<ConnectionEvent>,
+}
+
+impl Default for ConnectionMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectionMonitor {
+    pub fn new() -> Self {
+        let (event_sender, event_receiver) = unbounded();
+        ConnectionMonitor {
+            state: Arc::new(Mutex::new(ConnectionState::Connected)),
+            event_sender,
+            event_receiver,
+        }
+    }
+
+    pub fn get_state(&self) -> ConnectionState {
+        *self.state.lock()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.get_state() == ConnectionState::Connected
+    }
+
+    /// 接続が切れたことを報告する。既に再接続中なら何もしない。<br />
+    /// Report that the connection was lost. Nothing happens if it is already being re-established.
+    pub fn report_lost(&self, reason: &str) {
+        let mut state = self.state.lock();
+        if *state != ConnectionState::Connected {
+            return;
+        }
+        log::warn!("Connection to the server was lost: {}", reason);
+        *state = ConnectionState::Reconnecting { attempt: 0 };
+        self.send(ConnectionEvent::ConnectionLost {
+            reason: reason.to_string(),
+        });
+    }
+
+    /// gRPCのエラーが接続の切断によるものなら報告する。<br />
+    /// Report the connection as lost if a gRPC error was caused by it.
+    pub fn check_status(&self, status: &tonic::Status) {
+        match status.code() {
+            tonic::Code::Unavailable | tonic::Code::Unknown | tonic::Code::Cancelled => {
+                self.report_lost(status.message())
+            }
+            _ => (),
+        }
+    }
+
+    pub fn set_state(&self, state: ConnectionState) {
+        *self.state.lock() = state;
+    }
+
+    pub fn send(&self, event: ConnectionEvent) {
+        if self.event_sender.send(event).is_err() {
+            log::warn!("Failed to send connection event.");
+        }
+    }
+
+    /// まだ処理していない出来事を全て取り出す。<br />
+    /// Take all events that haven't been handled yet.
+    pub fn poll_events(&self) -> Vec<ConnectionEvent> {
+        self.event_receiver.try_iter().collect()
+    }
+}
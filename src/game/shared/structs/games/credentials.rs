@@ -0,0 +1,182 @@
+use anyhow::Context;
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// 「ログインしたままにする」を選んだ時にログインの情報を保存するファイル。<br />
+/// File the login is saved to when "Remember me" is chosen.
+pub const SESSION_PATH: &str = "./session.json";
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// 入力欄に入る最大のバイト数。<br />
+/// Maximum number of bytes that fit in an input field.
+pub const MAX_CREDENTIAL_LENGTH: usize = 63;
+
+/// トークンが失効するこの時間前に、新しいトークンを取得する。<br />
+/// A new token is acquired this long before the current one expires.
+pub const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+static USERNAME_REGEX: OnceCell<Regex> = OnceCell::new();
+static EMAIL_REGEX: OnceCell<Regex> = OnceCell::new();
+
+/// 入力されたログインや登録の情報が受け付けられない理由。画面にそのまま表示する。<br />
+/// Why the entered login or registration details can't be accepted. Shown on the screen as is.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum CredentialError {
+    #[error("{0} is required.")]
+    Missing(&'static str),
+    #[error("{0} can't be longer than {1} bytes.")]
+    TooLong(&'static str, usize),
+    #[error("{0} can only contain letters, digits, '.', '-' and '_'.")]
+    InvalidName(&'static str),
+    #[error("The email address isn't valid.")]
+    InvalidEmail,
+    #[error("The password needs at least {0} characters.")]
+    PasswordTooShort(usize),
+    #[error("The passwords don't match.")]
+    PasswordMismatch,
+}
+
+fn validate_field(name: &'static str, value: &str) -> Result<(), CredentialError> {
+    if value.trim().is_empty() {
+        return Err(CredentialError::Missing(name));
+    }
+    if value.len() > MAX_CREDENTIAL_LENGTH {
+        return Err(CredentialError::TooLong(name, MAX_CREDENTIAL_LENGTH));
+    }
+    Ok(())
+}
+
+/// ログインの入力を確かめる。アカウントはユーザー名でもメールアドレスでもよい。<br />
+/// Check the login inputs. The account may be either a username or an email address.
+pub fn validate_login(account: &str, password: &str) -> Result<(), CredentialError> {
+    validate_field("Username or email", account)?;
+    validate_field("Password", password)
+}
+
+/// 登録の入力を確かめ、最初に見つかった問題を返す。<br />
+/// Check the registration inputs, returning the first problem found.
+pub fn validate_registration(
+    username: &str,
+    nickname: &str,
+    email: &str,
+    password: &str,
+    confirmation: &str,
+) -> Result<(), CredentialError> {
+    validate_field("Username", username)?;
+    let username_regex = USERNAME_REGEX.get_or_init(|| {
+        Regex::new(r"^[A-Za-z0-9._-]+$").expect("Failed to initialize regular expression.")
+    });
+    if !username_regex.is_match(username.trim()) {
+        return Err(CredentialError::InvalidName("Username"));
+    }
+    validate_field("Nickname", nickname)?;
+    validate_field("Email", email)?;
+    let email_regex = EMAIL_REGEX.get_or_init(|| {
+        Regex::new(r"^[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(\.[A-Za-z0-9-]+)+$")
+            .expect("Failed to initialize regular expression.")
+    });
+    if !email_regex.is_match(email.trim()) {
+        return Err(CredentialError::InvalidEmail);
+    }
+    validate_field("Password", password)?;
+    if password.trim().chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(CredentialError::PasswordTooShort(MIN_PASSWORD_LENGTH));
+    }
+    if password != confirmation {
+        return Err(CredentialError::PasswordMismatch);
+    }
+    Ok(())
+}
+
+/// JWTのペイロードの`exp`から、トークンが失効する時刻を読む。読めなければ`None`。<br />
+/// Read when a token expires from the `exp` claim of the JWT payload. `None` if it can't be read.
+pub fn get_token_expiry(token: &str) -> Option<SystemTime> {
+    let payload = token.split('.').nth(1)?;
+    let bytes =
+        base64::decode_config(payload.trim_end_matches('='), base64::URL_SAFE_NO_PAD).ok()?;
+    let claims = serde_json::from_slice::<serde_json::Value>(&bytes).ok()?;
+    let expiry = claims.get("exp")?.as_f64()?;
+    if !expiry.is_finite() || expiry < 0.0 {
+        return None;
+    }
+    Some(UNIX_EPOCH + Duration::from_secs_f64(expiry))
+}
+
+/// トークンがもうすぐ失効するかどうか。失効する時刻が分からないトークンは、サーバーに断られるまで使い続ける。<br />
+/// Whether a token is about to expire. Tokens without a known expiry are used until the server rejects them.
+pub fn is_token_expiring(token: &str, now: SystemTime) -> bool {
+    match get_token_expiry(token) {
+        Some(expiry) => now + TOKEN_REFRESH_MARGIN >= expiry,
+        None => false,
+    }
+}
+
+/// 次に起動した時に自動でログインするための情報。パスワードは保存しない。<br />
+/// トークンだけでもなりすましに使えるので、ファイルは書くたびに持ち主だけが読めるようにする。<br />
+/// Details used to log in automatically on the next launch. The password is never saved.<br />
+/// The tokens alone are enough to impersonate the player, so the file is made readable by its owner only on every write.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SavedSession {
+    /// トークンを発行したサーバー。他のサーバーではこのセッションを使わない。<br />
+    /// Server that issued the tokens. The session isn't used with other servers.
+    pub server_endpoint: String,
+    pub account: String,
+    /// 最後に取得したJWTトークン。失効していなければ、起動した時に取得し直さずに使う。<br />
+    /// The JWT token acquired last. It's used on launch without acquiring a new one if it hasn't expired.
+    pub token: String,
+    /// サーバーが最後のログインで発行したリフレッシュトークン。使うたびに新しいものに置き換わる。<br />
+    /// Refresh token the server issued on the last login. It's replaced with a new one every time it's used.
+    pub refresh_token: String,
+}
+
+impl SavedSession {
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}.", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}.", path.display()))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self)?;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("Failed to open {}.", path.display()))?;
+        // 既にあったファイルには作成時のモードが使われないので、書く前に毎回権限を絞る。
+        // The creation mode isn't applied to a file that already existed, so narrow the permissions before every write.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("Failed to set permissions of {}.", path.display()))?;
+        }
+        file.write_all(content.as_bytes())
+            .with_context(|| format!("Failed to write {}.", path.display()))
+    }
+
+    /// 保存した情報を消す。ファイルがなくてもエラーにしない。<br />
+    /// Forget the saved details. A missing file isn't an error.
+    pub fn delete<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        match std::fs::remove_file(path) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("Failed to delete {}.", path.display())),
+        }
+    }
+}
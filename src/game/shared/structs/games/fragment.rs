@@ -0,0 +1,217 @@
+use crate::game::shared::structs::games::network_stats::TrafficStats;
+use crate::game::shared::structs::games::payload::PayloadError;
+use crate::game::shared::structs::games::protocol::{
+    decode_packet, encode_packet, PACKET_HEADER_SIZE,
+};
+use crate::game::shared::structs::games::udp_session::SESSION_HEADER_SIZE;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+/// 一つのデータグラムの最大サイズ。経路のMTUを超えないように余裕を持たせている。<br />
+/// Maximum size of a datagram, kept well below the path MTU.
+pub const MAX_DATAGRAM_SIZE: usize = 1200;
+
+/// フラグメントヘッダーの長さ。シーケンス番号、フラグメントの番号と数、フラグ。<br />
+/// Length of the fragment header: sequence, fragment index and count, and flags.
+pub const FRAGMENT_HEADER_SIZE: usize = 7;
+
+/// 一つのフラグメントに入るペイロードの最大サイズ。UDPのセッションのヘッダーの分も空けておく。<br />
+/// Maximum payload size that fits into a single fragment. Room is left for the UDP session header as well.
+pub const MAX_FRAGMENT_PAYLOAD: usize =
+    MAX_DATAGRAM_SIZE - PACKET_HEADER_SIZE - SESSION_HEADER_SIZE - FRAGMENT_HEADER_SIZE;
+
+/// 一つのメッセージを分割できる最大のフラグメント数。<br />
+/// Maximum number of fragments a message can be split into.
+pub const MAX_FRAGMENT_COUNT: usize = 16;
+
+/// 展開後のメッセージの最大サイズ。圧縮爆弾を防ぐ。<br />
+/// Maximum size of a decompressed message, guarding against compression bombs.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// 同時に組み立て中にできるメッセージの数。古いものから捨てる。<br />
+/// Number of messages that can be reassembled at once. The oldest ones are dropped first.
+const MAX_PENDING_MESSAGES: usize = 8;
+
+const FLAG_COMPRESSED: u8 = 1;
+
+/// メッセージを圧縮し、MTUに収まるデータグラムに分割する。圧縮しても小さくならない場合はそのまま送る。<br />
+/// Compress a message and split it into datagrams that fit into the MTU. The message is sent as is if compression doesn't make it smaller.
+pub fn split_message(
+    sequence: u32,
+    message: &[u8],
+    stats: &mut TrafficStats,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    let compressed = compress(message)?;
+    let (payload, flags) = if compressed.len() < message.len() {
+        (compressed.as_slice(), FLAG_COMPRESSED)
+    } else {
+        (message, 0)
+    };
+    let fragment_count = ((payload.len() + MAX_FRAGMENT_PAYLOAD - 1) / MAX_FRAGMENT_PAYLOAD).max(1);
+    if fragment_count > MAX_FRAGMENT_COUNT {
+        return Err(anyhow::anyhow!(
+            "Message of {} bytes needs {} fragments, exceeding the limit of {}.",
+            payload.len(),
+            fragment_count,
+            MAX_FRAGMENT_COUNT
+        ));
+    }
+
+    let datagrams = (0..fragment_count)
+        .map(|index| {
+            let start = index * MAX_FRAGMENT_PAYLOAD;
+            let end = (start + MAX_FRAGMENT_PAYLOAD).min(payload.len());
+            let mut fragment = Vec::with_capacity(FRAGMENT_HEADER_SIZE + end - start);
+            fragment.extend_from_slice(&sequence.to_le_bytes());
+            fragment.push(index as u8);
+            fragment.push(fragment_count as u8);
+            fragment.push(flags);
+            fragment.extend_from_slice(&payload[start..end]);
+            encode_packet(&fragment)
+        })
+        .collect::<Vec<_>>();
+    stats.record_sent(message.len(), &datagrams);
+    Ok(datagrams)
+}
+
+fn compress(message: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(message.len()), Compression::fast());
+    encoder.write_all(message)?;
+    Ok(encoder.finish()?)
+}
+
+fn decompress(payload: &[u8]) -> Result<Vec<u8>, PayloadError> {
+    let mut message = vec![];
+    DeflateDecoder::new(payload)
+        .take(MAX_MESSAGE_SIZE as u64 + 1)
+        .read_to_end(&mut message)
+        .map_err(|e| PayloadError::Malformed(e.to_string()))?;
+    if message.len() > MAX_MESSAGE_SIZE {
+        return Err(PayloadError::TooLarge {
+            size: message.len(),
+            limit: MAX_MESSAGE_SIZE,
+        });
+    }
+    Ok(message)
+}
+
+/// 組み立て中のメッセージ。<br />
+/// A message being reassembled.
+struct PendingMessage {
+    sequence: u32,
+    flags: u8,
+    fragments: Vec<Option<Vec<u8>>>,
+    received_count: usize,
+    received_bytes: usize,
+}
+
+/// 受け取ったフラグメントからメッセージを組み立て直す。<br />
+/// Reassembles messages from received fragments.
+#[derive(Default)]
+pub struct FragmentAssembler {
+    pending: VecDeque<PendingMessage>,
+}
+
+impl FragmentAssembler {
+    pub fn new() -> Self {
+        FragmentAssembler {
+            pending: VecDeque::with_capacity(MAX_PENDING_MESSAGES),
+        }
+    }
+
+    /// データグラムを受け取る。メッセージが揃ったら展開して返す。<br />
+    /// Receive a datagram. Returns the decompressed message once all of its fragments have arrived.
+    pub fn receive(
+        &mut self,
+        datagram: &[u8],
+        stats: &mut TrafficStats,
+    ) -> Result<Option<Vec<u8>>, PayloadError> {
+        let fragment = decode_packet(datagram)?;
+        if fragment.len() < FRAGMENT_HEADER_SIZE {
+            return Err(PayloadError::InvalidHeader);
+        }
+        let sequence = u32::from_le_bytes(
+            fragment[0..4]
+                .try_into()
+                .expect("Failed to read fragment sequence."),
+        );
+        let index = fragment[4] as usize;
+        let count = fragment[5] as usize;
+        let flags = fragment[6];
+        let payload = &fragment[FRAGMENT_HEADER_SIZE..];
+        if count == 0 || count > MAX_FRAGMENT_COUNT || index >= count {
+            return Err(PayloadError::InvalidFragment {
+                sequence,
+                reason: format!("Fragment {} of {}.", index, count),
+            });
+        }
+        if flags & !FLAG_COMPRESSED != 0 {
+            return Err(PayloadError::InvalidFragment {
+                sequence,
+                reason: format!("Unknown flags {:#04x}.", flags),
+            });
+        }
+        if index + 1 < count && payload.len() != MAX_FRAGMENT_PAYLOAD {
+            return Err(PayloadError::InvalidFragment {
+                sequence,
+                reason: format!("Fragment {} is truncated.", index),
+            });
+        }
+
+        let position = match self.pending.iter().position(|p| p.sequence == sequence) {
+            Some(position) => position,
+            None => {
+                if self.pending.len() == MAX_PENDING_MESSAGES {
+                    self.pending.pop_front();
+                }
+                self.pending.push_back(PendingMessage {
+                    sequence,
+                    flags,
+                    fragments: vec![None; count],
+                    received_count: 0,
+                    received_bytes: 0,
+                });
+                self.pending.len() - 1
+            }
+        };
+        let pending = &mut self.pending[position];
+        if pending.fragments.len() != count || pending.flags != flags {
+            self.pending.remove(position);
+            return Err(PayloadError::InvalidFragment {
+                sequence,
+                reason: "Headers are inconsistent.".to_string(),
+            });
+        }
+        if pending.fragments[index].is_some() {
+            return Ok(None);
+        }
+        pending.fragments[index] = Some(payload.to_vec());
+        pending.received_count += 1;
+        pending.received_bytes += datagram.len();
+        if pending.received_count < count {
+            return Ok(None);
+        }
+
+        let pending = self
+            .pending
+            .remove(position)
+            .expect("Failed to remove completed message.");
+        let payload = pending
+            .fragments
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect::<Vec<_>>();
+        let message = if pending.flags & FLAG_COMPRESSED != 0 {
+            decompress(&payload)?
+        } else {
+            payload
+        };
+        stats.record_received(message.len(), count, pending.received_bytes);
+        Ok(Some(message))
+    }
+}
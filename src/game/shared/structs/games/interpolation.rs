@@ -0,0 +1,297 @@
+use crate::game::shared::structs::games::interpolation_debug::InterpolationDebugView;
+use crate::game::shared::structs::PositionInfo;
+use crate::game::shared::util::math::{euler_from_quat, quat_from_euler};
+use glam::Vec3A;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// リモートのエンティティを描画する遅れ。この間に次の状態が届けば補間できる。<br />
+/// How far in the past remote entities are rendered. States arriving within this window can be interpolated.
+pub const INTERPOLATION_DELAY: Duration = Duration::from_millis(100);
+
+/// 一つのエンティティについて保持する状態の最大数。<br />
+/// Maximum number of states buffered per entity.
+const MAX_BUFFERED_STATES: usize = 32;
+
+/// 溜めておくローカルプレイヤーのサーバーの状態の最大数。<br />
+/// Maximum number of server states of the local player kept until the scene handles them.
+const MAX_LOCAL_SERVER_STATES: usize = 16;
+
+/// 予測した状態を保持する時間。<br />
+/// How long predicted states are kept.
+const MAX_PREDICTION_AGE: Duration = Duration::from_secs(2);
+
+/// サーバーの状態と予測がこれ以上離れていたら補正する。<br />
+/// Predictions further than this from the server state get corrected.
+pub const RECONCILIATION_TOLERANCE: f32 = 0.5;
+
+/// 二つの状態の間を補間する。位置と大きさは線形補間、回転は球面線形補間。<br />
+/// Interpolate between two states. Position and scale are lerped, rotation is slerped.
+pub fn interpolate(from: &PositionInfo, to: &PositionInfo, t: f32) -> PositionInfo {
+    let rotation = quat_from_euler(from.rotation).slerp(quat_from_euler(to.rotation), t);
+    PositionInfo {
+        position: from.position.lerp(to.position, t),
+        scale: from.scale.lerp(to.scale, t),
+        rotation: euler_from_quat(rotation),
+    }
+}
+
+/// 受け取った時刻付きのリモートのエンティティの状態を保持し、過去の時点の状態を補間で求める。<br />
+/// Buffers timestamped states of a remote entity and interpolates its state at a point in the past.
+#[derive(Clone, Debug, Default)]
+pub struct InterpolationBuffer {
+    states: VecDeque<(Instant, PositionInfo)>,
+}
+
+impl InterpolationBuffer {
+    pub fn new() -> Self {
+        InterpolationBuffer {
+            states: VecDeque::with_capacity(MAX_BUFFERED_STATES),
+        }
+    }
+
+    /// 状態を追加する。前の状態より古い時刻のものは捨てる。<br />
+    /// Push a state. States older than the previous one are discarded.
+    pub fn push(&mut self, time: Instant, state: PositionInfo) {
+        if let Some((last_time, _)) = self.states.back() {
+            if time < *last_time {
+                return;
+            }
+        }
+        if self.states.len() == MAX_BUFFERED_STATES {
+            self.states.pop_front();
+        }
+        self.states.push_back((time, state));
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// 最後に受け取った状態。<br />
+    /// The most recently received state.
+    pub fn get_latest(&self) -> Option<PositionInfo> {
+        self.states.back().map(|(_, state)| *state)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// 指定した時刻の状態を求める。範囲外なら最も近い端の状態を使う。<br />
+    /// 補間に使われなくなった古い状態はここで捨てる。<br />
+    /// Get the state at the given time, clamping to the nearest end if it's out of range.<br />
+    /// Old states no longer needed for interpolation are discarded here.
+    pub fn sample(&mut self, render_time: Instant) -> Option<PositionInfo> {
+        while self.states.len() > 2 && self.states[1].0 <= render_time {
+            self.states.pop_front();
+        }
+        let (from_time, from) = self.states.front()?;
+        if render_time <= *from_time || self.states.len() == 1 {
+            return Some(*from);
+        }
+        let (to_time, to) = &self.states[1];
+        if render_time >= *to_time {
+            return Some(*to);
+        }
+        let span = to_time.duration_since(*from_time).as_secs_f32();
+        let t = render_time.duration_since(*from_time).as_secs_f32() / span;
+        Some(interpolate(from, to, t))
+    }
+
+    /// 最後の`sample`で補間に使った二つの状態。状態が一つしかなければ同じ状態を二つ返す。<br />
+    /// The two states used for interpolation by the last `sample`. With a single state, it's returned twice.
+    pub fn get_window(&self) -> Option<(PositionInfo, PositionInfo)> {
+        let (_, from) = self.states.front()?;
+        let to = self.states.get(1).map_or(from, |(_, state)| state);
+        Some((*from, *to))
+    }
+
+    /// 指定した時刻より新しい状態がなく、最後の状態で止まっているかどうか。<br />
+    /// Whether no state is newer than the given time, so the entity is stuck at the last state.
+    pub fn is_starved(&self, render_time: Instant) -> bool {
+        self.states
+            .back()
+            .map_or(true, |(time, _)| render_time >= *time)
+    }
+}
+
+/// ローカルプレイヤーの予測した状態の履歴。サーバーから戻ってきた状態と照らし合わせて補正する。<br />
+/// History of the local player's predicted states, checked against the state echoed back by the server.
+#[derive(Clone, Debug, Default)]
+pub struct PredictionHistory {
+    states: VecDeque<(Instant, PositionInfo)>,
+}
+
+impl PredictionHistory {
+    pub fn new() -> Self {
+        PredictionHistory {
+            states: VecDeque::new(),
+        }
+    }
+
+    /// 予測した状態を記録する。<br />
+    /// Record a predicted state.
+    pub fn record(&mut self, time: Instant, state: PositionInfo) {
+        while let Some((oldest, _)) = self.states.front() {
+            if time.duration_since(*oldest) > MAX_PREDICTION_AGE {
+                self.states.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.states.push_back((time, state));
+    }
+
+    /// サーバーの状態を予測と照らし合わせる。<br />
+    /// 一致する予測があれば、それより古い予測は確定したものとして捨てる。<br />
+    /// どの予測とも一致しなければ、サーバーが状態を上書きしたとみなし、補正先の状態を返す。<br />
+    /// Check the server state against the predictions.<br />
+    /// If a prediction matches, older predictions are considered confirmed and discarded.<br />
+    /// If none match, the server is considered to have overridden the state, and the state to correct to is returned.
+    pub fn reconcile(&mut self, server_state: &PositionInfo) -> Option<PositionInfo> {
+        if self.states.is_empty() {
+            return None;
+        }
+        let matched = self.states.iter().rposition(|(_, predicted)| {
+            (predicted.position - server_state.position).length() <= RECONCILIATION_TOLERANCE
+        });
+        match matched {
+            Some(index) => {
+                self.states.drain(0..index);
+                None
+            }
+            None => {
+                self.states.clear();
+                Some(*server_state)
+            }
+        }
+    }
+}
+
+/// ネットワークから届いたプレイヤーの位置のキュー。受信するタスクが追加し、シーンが毎フレーム取り出す。<br />
+/// Queues of player positions arriving from the network. Receiving tasks push into them and the scene takes them out every frame.
+#[derive(Clone, Debug, Default)]
+pub struct StateQueues {
+    remote_states: HashMap<String, InterpolationBuffer>,
+    local_server_states: VecDeque<PositionInfo>,
+    pub prediction: PredictionHistory,
+    last_local_state: Option<PositionInfo>,
+    /// 最後に`sample_remote`で求めたリモートのプレイヤーの位置。名札を出す所に使う。<br />
+    /// Positions of remote players last found by `sample_remote`, used to place their nameplates.
+    rendered_positions: HashMap<String, Vec3A>,
+    pub debug_view: InterpolationDebugView,
+}
+
+impl StateQueues {
+    pub fn new() -> Self {
+        StateQueues {
+            remote_states: HashMap::new(),
+            local_server_states: VecDeque::new(),
+            prediction: PredictionHistory::new(),
+            last_local_state: None,
+            rendered_positions: HashMap::new(),
+            debug_view: InterpolationDebugView::new(),
+        }
+    }
+
+    /// 全ての状態を捨てる。デバッグ表示が有効かどうかは残す。<br />
+    /// Discard all states. Whether the debug view is enabled is kept.
+    pub fn clear(&mut self) {
+        let is_debug_view_enabled = self.debug_view.is_enabled();
+        *self = Self::new();
+        self.debug_view.set_enabled(is_debug_view_enabled);
+    }
+
+    /// 部屋の全プレイヤーの状態を追加する。部屋にいなくなったプレイヤーのバッファーは捨てる。<br />
+    /// Push the states of all players in the room. Buffers of players who left the room are discarded.
+    pub fn push_room_state(
+        &mut self,
+        time: Instant,
+        states: Vec<(String, PositionInfo)>,
+        local_player_id: &str,
+    ) {
+        self.remote_states
+            .retain(|player_id, _| states.iter().any(|(id, _)| id == player_id));
+        self.debug_view
+            .retain_players(|player_id| states.iter().any(|(id, _)| id == player_id));
+        for (player_id, state) in states.into_iter() {
+            if player_id == local_player_id {
+                if self.local_server_states.len() == MAX_LOCAL_SERVER_STATES {
+                    self.local_server_states.pop_front();
+                }
+                self.local_server_states.push_back(state);
+                self.last_local_state = Some(state);
+            } else {
+                self.debug_view.record_received(&player_id, state.position);
+                self.remote_states
+                    .entry(player_id)
+                    .or_insert_with(InterpolationBuffer::new)
+                    .push(time, state);
+            }
+        }
+    }
+
+    /// `INTERPOLATION_DELAY`だけ過去のリモートのプレイヤーの状態を求める。<br />
+    /// デバッグ表示が有効なら、補間に使った状態も記録する。<br />
+    /// Get the states of remote players `INTERPOLATION_DELAY` in the past.<br />
+    /// When the debug view is enabled, the states used for interpolation are recorded too.
+    pub fn sample_remote(&mut self, now: Instant) -> HashMap<String, PositionInfo> {
+        let render_time = now.checked_sub(INTERPOLATION_DELAY).unwrap_or(now);
+        let debug_view = &mut self.debug_view;
+        let states = self
+            .remote_states
+            .iter_mut()
+            .filter_map(|(player_id, buffer)| {
+                let state = buffer.sample(render_time)?;
+                if let Some((from, to)) = buffer.get_window() {
+                    debug_view.record_rendered(
+                        player_id,
+                        state.position,
+                        (from.position, to.position),
+                        buffer.is_starved(render_time),
+                    );
+                }
+                Some((player_id.clone(), state))
+            })
+            .collect::<HashMap<_, _>>();
+        self.rendered_positions = states
+            .iter()
+            .map(|(player_id, state)| (player_id.clone(), state.position))
+            .collect();
+        states
+    }
+
+    /// リモートのプレイヤーごとの補間バッファーに溜まった状態の数の、最小と最大。リモートのプレイヤーがいなければ`None`。<br />
+    /// The smallest and largest number of states held in the interpolation buffers of remote players. `None` without remote players.
+    pub fn get_buffer_depths(&self) -> Option<(usize, usize)> {
+        let depths = self.remote_states.values().map(|buffer| buffer.len());
+        let min = depths.clone().min()?;
+        let max = depths.max()?;
+        Some((min, max))
+    }
+
+    pub fn get_rendered_positions(&self) -> &HashMap<String, Vec3A> {
+        &self.rendered_positions
+    }
+
+    /// リモートのプレイヤーについて最後に受け取った状態。<br />
+    /// The most recently received state of a remote player.
+    pub fn get_latest_remote(&self, player_id: &str) -> Option<PositionInfo> {
+        self.remote_states
+            .get(player_id)
+            .and_then(|buffer| buffer.get_latest())
+    }
+
+    /// サーバーが最後に伝えたローカルプレイヤーの状態。<br />
+    /// The last state of the local player reported by the server.
+    pub fn get_last_local_state(&self) -> Option<PositionInfo> {
+        self.last_local_state
+    }
+
+    /// まだ処理していないローカルプレイヤーのサーバーの状態を全て取り出す。<br />
+    /// Take all server states of the local player that haven't been handled yet.
+    pub fn take_local_server_states(&mut self) -> Vec<PositionInfo> {
+        self.local_server_states.drain(..).collect()
+    }
+}
@@ -0,0 +1,132 @@
+use crate::game::shared::structs::DebugDraw;
+use glam::Vec3A;
+use std::collections::{HashMap, VecDeque};
+
+/// エンティティごとに残す、受け取った状態の位置の数。<br />
+/// Number of received positions kept per entity.
+const MAX_RECEIVED_POSITIONS: usize = 32;
+
+/// エンティティごとに残す、描画した位置の数。60FPSで約2秒分。<br />
+/// Number of rendered positions kept per entity, about two seconds at 60 FPS.
+const MAX_RENDERED_POSITIONS: usize = 120;
+
+const RECEIVED_COLOR: [u8; 4] = [255, 200, 0, 255];
+const WINDOW_COLOR: [u8; 4] = [0, 200, 255, 255];
+const RENDERED_COLOR: [u8; 4] = [0, 255, 120, 255];
+/// 次の状態が届かず、最後の状態で止まっている時の色。<br />
+/// Color used while no newer state has arrived and the entity is stuck at the last one.
+const STARVED_COLOR: [u8; 4] = [255, 60, 60, 255];
+
+/// 一つのリモートのエンティティについて、届いた状態と描画した位置の記録。<br />
+/// Record of the states received and the positions rendered for a single remote entity.
+#[derive(Clone, Debug, Default)]
+pub struct InterpolationTrace {
+    pub received: VecDeque<Vec3A>,
+    pub rendered: VecDeque<Vec3A>,
+    /// 最後に補間に使った二つの状態の位置。<br />
+    /// Positions of the two states last used for interpolation.
+    pub window: Option<(Vec3A, Vec3A)>,
+    pub is_starved: bool,
+}
+
+/// 補間の遅れや揺れを調べるため、リモートのエンティティの補間の様子を記録して描く。<br />
+/// 有効な間だけ記録する。<br />
+/// Records and draws how remote entities are interpolated, to tune the replication delay and find sources of jitter.<br />
+/// Only records while enabled.
+#[derive(Clone, Debug, Default)]
+pub struct InterpolationDebugView {
+    is_enabled: bool,
+    traces: HashMap<String, InterpolationTrace>,
+}
+
+impl InterpolationDebugView {
+    pub fn new() -> Self {
+        InterpolationDebugView {
+            is_enabled: false,
+            traces: HashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    /// 記録するかどうかを設定する。無効にしたら記録を捨てる。<br />
+    /// Set whether to record. The records are discarded when disabled.
+    pub fn set_enabled(&mut self, is_enabled: bool) {
+        self.is_enabled = is_enabled;
+        if !is_enabled {
+            self.traces.clear();
+        }
+    }
+
+    /// 受け取った状態の位置を記録する。<br />
+    /// Record the position of a received state.
+    pub fn record_received(&mut self, player_id: &str, position: Vec3A) {
+        if !self.is_enabled {
+            return;
+        }
+        let trace = self.traces.entry(player_id.to_string()).or_default();
+        if trace.received.len() == MAX_RECEIVED_POSITIONS {
+            trace.received.pop_front();
+        }
+        trace.received.push_back(position);
+    }
+
+    /// 補間して描画する位置と、補間に使った二つの状態の位置を記録する。<br />
+    /// Record the interpolated position to render and the positions of the two states used for it.
+    pub fn record_rendered(
+        &mut self,
+        player_id: &str,
+        position: Vec3A,
+        window: (Vec3A, Vec3A),
+        is_starved: bool,
+    ) {
+        if !self.is_enabled {
+            return;
+        }
+        let trace = self.traces.entry(player_id.to_string()).or_default();
+        if trace.rendered.len() == MAX_RENDERED_POSITIONS {
+            trace.rendered.pop_front();
+        }
+        trace.rendered.push_back(position);
+        trace.window = Some(window);
+        trace.is_starved = is_starved;
+    }
+
+    /// 部屋にいなくなったプレイヤーの記録を捨てる。<br />
+    /// Discard the records of players who left the room.
+    pub fn retain_players<F>(&mut self, is_in_room: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.traces.retain(|player_id, _| is_in_room(player_id));
+    }
+
+    pub fn get_trace(&self, player_id: &str) -> Option<&InterpolationTrace> {
+        self.traces.get(player_id)
+    }
+
+    /// 受け取った位置を点、補間の範囲を太い線、描画した位置の軌跡を細い線で描く。<br />
+    /// Draw the received positions as dots, the interpolation window as a thick line, and the trail of rendered positions as a thin line.
+    pub fn draw(&self, debug_draw: &mut DebugDraw) {
+        for trace in self.traces.values() {
+            for position in trace.received.iter() {
+                debug_draw.point(*position, 3.0, RECEIVED_COLOR);
+            }
+            if let Some((from, to)) = trace.window {
+                debug_draw.line(from, to, 3.0, WINDOW_COLOR);
+            }
+            let rendered = trace.rendered.iter().copied().collect::<Vec<_>>();
+            debug_draw.line_strip(&rendered, 1.5, RENDERED_COLOR);
+            if let Some(position) = rendered.last() {
+                let color = if trace.is_starved {
+                    STARVED_COLOR
+                } else {
+                    RENDERED_COLOR
+                };
+                debug_draw.point(*position, 5.0, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::game::shared::structs::games::payload::{
+    check_range, deserialize_bounded, PayloadError,
+};
+use crate::game::shared::structs::games::protocol::PROTOCOL_VERSION;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// LANにホストを告知するブロードキャストを送受信するポート。<br />
+/// Port on which broadcasts announcing hosts on the LAN are sent and received.
+pub const DISCOVERY_PORT: u16 = 47810;
+
+/// ホストが自分を告知する間隔。<br />
+/// Interval at which a host announces itself.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 告知の先頭に付けるマジックナンバー。<br />
+/// Magic number at the beginning of every announcement.
+const ANNOUNCEMENT_MAGIC: &[u8; 4] = b"DGLA";
+
+/// マジックナンバーとプロトコルバージョンの長さ。<br />
+/// Length of the magic number and the protocol version.
+const ANNOUNCEMENT_HEADER_SIZE: usize = 8;
+
+/// 告知が途絶えたホストを一覧から外すまでの時間。告知を数回落としても消えないようにする。<br />
+/// How long until a host that stopped announcing is removed from the list. A few dropped announcements don't remove it.
+pub const HOST_EXPIRY: Duration = Duration::from_secs(5);
+
+/// 直接接続する時、UDPのポートはgRPCのポートの次の番号とみなす。リッスンサーバーの既定のポートと同じ並び。<br />
+/// When connecting directly, the UDP port is assumed to follow the gRPC port, the same as the listen server's default ports.
+pub const DIRECT_UDP_PORT_OFFSET: u16 = 1;
+
+/// 告知に含められるサーバー名の最大の長さ。<br />
+/// Maximum length of the server name in an announcement.
+pub const MAX_SERVER_NAME_LENGTH: usize = 64;
+
+/// LANにいるリッスンサーバーの告知。接続先のポートと部屋の状況を知らせる。<br />
+/// Announcement of a listen server on the LAN, telling where to connect and how its rooms are doing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LanAnnouncement {
+    pub server_name: String,
+    pub build_version: String,
+    pub grpc_port: u16,
+    pub udp_port: u16,
+    /// まだ始まっていない部屋の数。<br />
+    /// Number of rooms that haven't started yet.
+    pub open_rooms: u32,
+    pub current_players: u32,
+}
+
+impl LanAnnouncement {
+    /// 告知をブロードキャストするデータグラムにエンコードする。<br />
+    /// Encode the announcement into a datagram for broadcasting.
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let serialized = bincode::serialize(self)?;
+        let mut bytes = Vec::with_capacity(ANNOUNCEMENT_HEADER_SIZE + serialized.len());
+        bytes.extend_from_slice(ANNOUNCEMENT_MAGIC);
+        bytes.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&serialized);
+        Ok(bytes)
+    }
+
+    /// 受け取った告知をデコードする。プロトコルバージョンが違うホストは接続できないので拒否する。<br />
+    /// Decode a received announcement. Hosts with a different protocol version can't be joined, so they are rejected.
+    pub fn decode(bytes: &[u8]) -> Result<Self, PayloadError> {
+        if bytes.len() < ANNOUNCEMENT_HEADER_SIZE || !bytes.starts_with(ANNOUNCEMENT_MAGIC) {
+            return Err(PayloadError::InvalidHeader);
+        }
+        let version = u32::from_le_bytes(
+            bytes[ANNOUNCEMENT_MAGIC.len()..ANNOUNCEMENT_HEADER_SIZE]
+                .try_into()
+                .expect("Failed to read protocol version."),
+        );
+        if version != PROTOCOL_VERSION {
+            return Err(PayloadError::VersionMismatch {
+                expected: PROTOCOL_VERSION,
+                actual: version,
+            });
+        }
+        let announcement =
+            deserialize_bounded::<LanAnnouncement>(&bytes[ANNOUNCEMENT_HEADER_SIZE..])?;
+        check_range(
+            "server_name",
+            announcement.server_name.len(),
+            MAX_SERVER_NAME_LENGTH,
+        )?;
+        Ok(announcement)
+    }
+
+    /// 告知を送ったホストのgRPCのエンドポイント。<br />
+    /// gRPC endpoint of the host that sent the announcement.
+    pub fn get_server_endpoint(&self, host: IpAddr) -> String {
+        HostAddress::from_announcement(host, self).get_server_endpoint()
+    }
+
+    /// 告知を送ったホストのUDPのエンドポイント。<br />
+    /// UDP endpoint of the host that sent the announcement.
+    pub fn get_udp_endpoint(&self, host: IpAddr) -> String {
+        HostAddress::from_announcement(host, self).get_udp_endpoint()
+    }
+}
+
+/// 接続するホストのアドレスとポート。LANの告知か、直接入力されたアドレスから作る。<br />
+/// Address and ports of a host to connect to, made from a LAN announcement or an address typed in directly.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HostAddress {
+    pub ip: IpAddr,
+    pub grpc_port: u16,
+    pub udp_port: u16,
+}
+
+impl HostAddress {
+    pub fn from_announcement(ip: IpAddr, announcement: &LanAnnouncement) -> Self {
+        HostAddress {
+            ip,
+            grpc_port: announcement.grpc_port,
+            udp_port: announcement.udp_port,
+        }
+    }
+
+    /// `ip:port`の形の入力を解析する。ポートはgRPCのポート。<br />
+    /// Parse an input of the form `ip:port`. The port is the gRPC port.
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let address = input
+            .trim()
+            .parse::<SocketAddr>()
+            .map_err(|_| anyhow::anyhow!("Invalid address: {}. Expected ip:port.", input.trim()))?;
+        if address.port() == 0 {
+            anyhow::bail!("Port 0 can't be connected to.");
+        }
+        let udp_port = address
+            .port()
+            .checked_add(DIRECT_UDP_PORT_OFFSET)
+            .with_context(|| format!("Port {} has no UDP port after it.", address.port()))?;
+        Ok(HostAddress {
+            ip: address.ip(),
+            grpc_port: address.port(),
+            udp_port,
+        })
+    }
+
+    pub fn get_server_endpoint(&self) -> String {
+        format!("http://{}", SocketAddr::new(self.ip, self.grpc_port))
+    }
+
+    pub fn get_udp_endpoint(&self) -> String {
+        SocketAddr::new(self.ip, self.udp_port).to_string()
+    }
+}
+
+/// LANで見つかったホストと、最後に告知を受け取った時刻。<br />
+/// A host found on the LAN, and when its last announcement was received.
+#[derive(Clone, Debug)]
+pub struct DiscoveredHost {
+    pub ip: IpAddr,
+    pub announcement: LanAnnouncement,
+    pub last_seen: Instant,
+}
+
+impl DiscoveredHost {
+    pub fn get_address(&self) -> HostAddress {
+        HostAddress::from_announcement(self.ip, &self.announcement)
+    }
+}
+
+/// LANで見つかったホストの一覧。同じホストの告知は最新のもので置き換える。<br />
+/// List of hosts found on the LAN. Announcements from the same host replace the older one.
+#[derive(Clone, Debug, Default)]
+pub struct DiscoveredHosts {
+    hosts: Vec<DiscoveredHost>,
+}
+
+impl DiscoveredHosts {
+    pub fn new() -> Self {
+        DiscoveredHosts { hosts: vec![] }
+    }
+
+    pub fn update(&mut self, ip: IpAddr, announcement: LanAnnouncement, now: Instant) {
+        let address = HostAddress::from_announcement(ip, &announcement);
+        match self.hosts.iter_mut().find(|h| h.get_address() == address) {
+            Some(host) => {
+                host.announcement = announcement;
+                host.last_seen = now;
+            }
+            None => self.hosts.push(DiscoveredHost {
+                ip,
+                announcement,
+                last_seen: now,
+            }),
+        }
+    }
+
+    /// `HOST_EXPIRY`より長く告知がないホストを外す。<br />
+    /// Remove hosts that haven't announced themselves for longer than `HOST_EXPIRY`.
+    pub fn remove_stale(&mut self, now: Instant) {
+        self.hosts
+            .retain(|h| now.saturating_duration_since(h.last_seen) <= HOST_EXPIRY);
+    }
+
+    pub fn get_hosts(&self) -> &[DiscoveredHost] {
+        &self.hosts
+    }
+}
@@ -0,0 +1,227 @@
+use crate::protos::grpc_service::game_state::{Player, RoomState};
+
+/// 勝ったプレイヤーがもらうクレジット。<br />
+/// Credits earned by the winner.
+pub const WIN_CREDITS: i32 = 100;
+
+/// 負けたり引き分けたりしたプレイヤーも、参加した分のクレジットをもらう。<br />
+/// Players who lost or drew still earn credits for taking part.
+pub const PARTICIPATION_CREDITS: i32 = 20;
+
+/// 決着を付けるのに必要な人数。一人で始めた試合は終わらない。<br />
+/// Number of players needed to decide a match. A match started alone never ends.
+const MIN_MATCH_PLAYERS: usize = 2;
+
+/// 試合の決着。<br />
+/// How a match ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatchOutcome {
+    /// 最後まで立っていたプレイヤーが勝った。<br />
+    /// The last player standing won.
+    Victory { winner_id: String },
+    /// 全員が同時に倒れ、勝者がいない。<br />
+    /// Everyone went down at the same time and there's no winner.
+    Draw,
+}
+
+impl MatchOutcome {
+    /// gRPCで送られる勝者のIDから作る。空なら引き分け。<br />
+    /// Create from the winner ID sent over gRPC. Empty means a draw.
+    pub fn from_winner_id(winner_id: &str) -> Self {
+        if winner_id.is_empty() {
+            MatchOutcome::Draw
+        } else {
+            MatchOutcome::Victory {
+                winner_id: winner_id.to_string(),
+            }
+        }
+    }
+
+    pub fn get_winner_id(&self) -> Option<&str> {
+        match self {
+            MatchOutcome::Victory { winner_id } => Some(winner_id),
+            MatchOutcome::Draw => None,
+        }
+    }
+}
+
+/// 倒れていないプレイヤー？状態がまだ届いていないプレイヤーは倒れたとは見なさない。<br />
+/// Is the player still standing? Players whose state hasn't arrived yet aren't considered down.
+fn is_standing(player: &Player) -> bool {
+    player
+        .state
+        .as_ref()
+        .and_then(|s| s.state.as_ref())
+        .map(|s| s.is_alive && s.current_hp > 0)
+        .unwrap_or(true)
+}
+
+/// 部屋のステートを見て、試合の決着を一度だけ知らせる。<br />
+/// 立っているプレイヤーが一人になればその人の勝ち、誰もいなくなれば引き分け。<br />
+/// 途中で部屋を出たプレイヤーは倒れたのと同じに扱うので、試合に加わった人数を覚えておく。<br />
+/// Watches the room state and reports the outcome of the match once.<br />
+/// When only one player is left standing they win, and when no one is it's a draw.<br />
+/// Players who left the room midway count as down, so the number of players who took part is remembered.
+#[derive(Clone, Debug, Default)]
+pub struct MatchTracker {
+    player_count: usize,
+    outcome: Option<MatchOutcome>,
+}
+
+impl MatchTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 最新の部屋のステートを確かめる。決着が付いたその時だけ結果を返す。<br />
+    /// Check the latest room state. The outcome is only returned at the moment the match is decided.
+    pub fn update(&mut self, room_state: &RoomState) -> Option<MatchOutcome> {
+        if self.outcome.is_some() || !room_state.started {
+            return None;
+        }
+        self.player_count = self.player_count.max(room_state.players.len());
+        if self.player_count < MIN_MATCH_PLAYERS {
+            return None;
+        }
+        let standing = room_state
+            .players
+            .iter()
+            .filter(|p| is_standing(p))
+            .collect::<Vec<_>>();
+        let outcome = match standing.as_slice() {
+            [] => MatchOutcome::Draw,
+            [winner] => MatchOutcome::Victory {
+                winner_id: winner.player_id.clone(),
+            },
+            _ => return None,
+        };
+        self.outcome = Some(outcome.clone());
+        Some(outcome)
+    }
+
+    pub fn get_outcome(&self) -> Option<&MatchOutcome> {
+        self.outcome.as_ref()
+    }
+
+    /// 次の試合に備えて忘れる。<br />
+    /// Forget everything in preparation for the next match.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// 得点表の一行。<br />
+/// A row of the scoreboard.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreboardRow {
+    pub player_id: String,
+    pub nickname: String,
+    pub current_hp: i32,
+    pub max_hp: i32,
+    pub is_standing: bool,
+    pub win_count: i32,
+    pub lose_count: i32,
+}
+
+/// 部屋のプレイヤーを、立っている人、HPの多い人、勝ち数の多い人の順に並べる。<br />
+/// List the players in the room with standing players first, then by HP and by win count.
+pub fn get_scoreboard(room_state: &RoomState) -> Vec<ScoreboardRow> {
+    let mut rows = room_state
+        .players
+        .iter()
+        .map(|p| {
+            let state = p.state.as_ref().and_then(|s| s.state.as_ref());
+            ScoreboardRow {
+                player_id: p.player_id.clone(),
+                nickname: p.nickname.clone(),
+                current_hp: state.map(|s| s.current_hp.max(0)).unwrap_or(0),
+                max_hp: state.map(|s| s.max_hp).unwrap_or(0),
+                is_standing: is_standing(p),
+                win_count: p.win_count,
+                lose_count: p.lose_count,
+            }
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| {
+        b.is_standing
+            .cmp(&a.is_standing)
+            .then(b.current_hp.cmp(&a.current_hp))
+            .then(b.win_count.cmp(&a.win_count))
+            .then(a.nickname.cmp(&b.nickname))
+    });
+    rows
+}
+
+/// 試合の結果をアカウントに反映し、得たクレジットを返す。引き分けでは勝敗の数は変わらない。<br />
+/// Apply the outcome of a match to an account and return the earned credits. A draw doesn't change the win or lose counts.
+pub fn settle_player(player: &mut Player, outcome: &MatchOutcome) -> i32 {
+    let earned_credits = match outcome.get_winner_id() {
+        Some(winner_id) if winner_id == player.player_id => {
+            player.win_count += 1;
+            WIN_CREDITS
+        }
+        Some(_) => {
+            player.lose_count += 1;
+            PARTICIPATION_CREDITS
+        }
+        None => PARTICIPATION_CREDITS,
+    };
+    player.credits = player.credits.saturating_add(earned_credits);
+    earned_credits
+}
+
+/// 結果の画面に出す試合の結果。<br />
+/// The result of a match shown on the results screen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchResults {
+    pub outcome: MatchOutcome,
+    /// 勝者の表示名。引き分けなら`None`。<br />
+    /// Display name of the winner, or `None` for a draw.
+    pub winner_name: Option<String>,
+    pub is_winner: bool,
+    pub earned_credits: i32,
+    pub credits: i32,
+    pub win_count: i32,
+    pub lose_count: i32,
+    /// 結果を報告できなかった理由。その場合、クレジットと勝敗の数は試合の前のまま。<br />
+    /// Why the result couldn't be reported. In that case, the credits and win counts are the ones from before the match.
+    pub report_error: Option<String>,
+}
+
+impl MatchResults {
+    /// 決着と、結果を反映した後のアカウントから作る。勝者の名前は部屋のステートから探す。<br />
+    /// Create from the outcome and the account after the result was applied. The winner's name is looked up in the room state.
+    pub fn new(
+        outcome: MatchOutcome,
+        room_state: &RoomState,
+        player: &Player,
+        earned_credits: i32,
+    ) -> Self {
+        let winner_name = outcome.get_winner_id().map(|winner_id| {
+            room_state
+                .players
+                .iter()
+                .find(|p| p.player_id == winner_id)
+                .map(|p| p.nickname.clone())
+                .unwrap_or_else(|| winner_id.to_string())
+        });
+        MatchResults {
+            is_winner: outcome.get_winner_id() == Some(player.player_id.as_str()),
+            outcome,
+            winner_name,
+            earned_credits,
+            credits: player.credits,
+            win_count: player.win_count,
+            lose_count: player.lose_count,
+            report_error: None,
+        }
+    }
+
+    pub fn get_title(&self) -> &'static str {
+        match self.outcome {
+            MatchOutcome::Draw => "Draw",
+            MatchOutcome::Victory { .. } if self.is_winner => "Victory",
+            MatchOutcome::Victory { .. } => "Defeat",
+        }
+    }
+}
@@ -1,3 +1,20 @@
+pub mod connection;
+pub mod credentials;
+pub mod fragment;
+pub mod interpolation;
+pub mod interpolation_debug;
+pub mod lan_discovery;
+pub mod match_result;
+pub mod network_stats;
+pub mod payload;
+pub mod player_settings;
+pub mod protocol;
+pub mod replication;
+pub mod snapshot;
+pub mod terrain_patch;
+pub mod terrain_payload;
+pub mod udp_session;
+
 use crate::protos::grpc_service::game_state::{
     EntityState, Player, PlayerState, RoomState, WorldMatrix,
 };
@@ -18,6 +35,8 @@ pub struct EntityStateUdp {
     pub max_sp: i32,
     pub is_alive: bool,
     pub world_matrix: WorldMatrixUdp,
+    pub last_attacker_id: String,
+    pub mount_id: String,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -95,6 +114,8 @@ impl EntityStateUdp {
             max_sp: 0,
             is_alive: false,
             world_matrix: WorldMatrixUdp::default(),
+            last_attacker_id: String::new(),
+            mount_id: String::new(),
         }
     }
 }
@@ -111,6 +132,8 @@ impl From<EntityState> for EntityStateUdp {
                 None => WorldMatrixUdp::default(),
                 Some(w) => WorldMatrixUdp::from(w),
             },
+            last_attacker_id: state.last_attacker_id,
+            mount_id: state.mount_id,
         }
     }
 }
@@ -0,0 +1,168 @@
+use crate::game::shared::structs::games::udp_session::SessionStats;
+use std::time::{Duration, Instant};
+
+/// 秒間の通信量を求め直す間隔。<br />
+/// Interval at which the per-second traffic is recalculated.
+pub const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 一方向の通信量の統計。<br />
+/// Traffic statistics in one direction.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TrafficStats {
+    /// スナップショットなどのメッセージの数。<br />
+    /// Number of messages such as snapshots.
+    pub messages: u64,
+    pub datagrams: u64,
+    /// 複数のデータグラムに分割されたメッセージの数。<br />
+    /// Number of messages split into multiple datagrams.
+    pub fragmented_messages: u64,
+    /// ヘッダーを含むデータグラムの合計サイズ。<br />
+    /// Total size of datagrams, headers included.
+    pub bytes: u64,
+    /// 圧縮前のメッセージの合計サイズ。<br />
+    /// Total size of messages before compression.
+    pub uncompressed_bytes: u64,
+    pub last_message_size: usize,
+    pub last_datagram_count: usize,
+}
+
+impl TrafficStats {
+    /// 送ったメッセージを記録する。<br />
+    /// Record a sent message.
+    pub fn record_sent(&mut self, message_size: usize, datagrams: &[Vec<u8>]) {
+        let bytes = datagrams.iter().map(|d| d.len()).sum::<usize>();
+        self.record(message_size, datagrams.len(), bytes);
+    }
+
+    /// 組み立て終わった受信メッセージを記録する。<br />
+    /// Record a received message once it has been reassembled.
+    pub fn record_received(&mut self, message_size: usize, datagram_count: usize, bytes: usize) {
+        self.record(message_size, datagram_count, bytes);
+    }
+
+    fn record(&mut self, message_size: usize, datagram_count: usize, bytes: usize) {
+        self.messages += 1;
+        self.datagrams += datagram_count as u64;
+        if datagram_count > 1 {
+            self.fragmented_messages += 1;
+        }
+        self.bytes += bytes as u64;
+        self.uncompressed_bytes += message_size as u64;
+        self.last_message_size = message_size;
+        self.last_datagram_count = datagram_count;
+    }
+
+    /// 圧縮前に対する実際に送受信したサイズの比率。<br />
+    /// Ratio of the bytes actually transferred to the bytes before compression.
+    pub fn get_compression_ratio(&self) -> f32 {
+        if self.uncompressed_bytes == 0 {
+            1.0
+        } else {
+            self.bytes as f32 / self.uncompressed_bytes as f32
+        }
+    }
+}
+
+/// 一秒あたりのデータグラムの数とバイト数。<br />
+/// Datagrams and bytes per second.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TrafficRates {
+    pub datagrams_sent: f64,
+    pub datagrams_received: f64,
+    pub bytes_sent: f64,
+    pub bytes_received: f64,
+}
+
+/// 累計の通信量の差から、秒間の通信量を求める。<br />
+/// 数値が毎フレーム揺れないよう、`RATE_INTERVAL`ごとにしか求め直さない。<br />
+/// Derives the per-second traffic from differences of the cumulative traffic.<br />
+/// It's only recalculated every `RATE_INTERVAL` so the numbers don't jitter every frame.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RateMeter {
+    last_sample: Option<(Instant, TrafficStats, TrafficStats)>,
+    rates: TrafficRates,
+}
+
+impl RateMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 今の累計を記録し、最新の秒間の通信量を返す。<br />
+    /// Record the current totals and return the latest per-second traffic.
+    pub fn sample(
+        &mut self,
+        now: Instant,
+        sent: &TrafficStats,
+        received: &TrafficStats,
+    ) -> TrafficRates {
+        let (last_time, last_sent, last_received) = match self.last_sample {
+            Some(sample) => sample,
+            None => {
+                self.last_sample = Some((now, *sent, *received));
+                return self.rates;
+            }
+        };
+        let elapsed = now.saturating_duration_since(last_time);
+        if elapsed < RATE_INTERVAL {
+            return self.rates;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let per_second = |current: u64, last: u64| current.saturating_sub(last) as f64 / seconds;
+        self.rates = TrafficRates {
+            datagrams_sent: per_second(sent.datagrams, last_sent.datagrams),
+            datagrams_received: per_second(received.datagrams, last_received.datagrams),
+            bytes_sent: per_second(sent.bytes, last_sent.bytes),
+            bytes_received: per_second(received.bytes, last_received.bytes),
+        };
+        self.last_sample = Some((now, *sent, *received));
+        self.rates
+    }
+}
+
+/// gRPCの呼び出しにかかった時間。<br />
+/// Time taken by gRPC calls.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CallLatency {
+    /// 最後に呼び出したメソッドの名前。<br />
+    /// Name of the method called last.
+    pub last_call: Option<&'static str>,
+    pub last: Option<Duration>,
+    /// 往復時間と同じく、新しい値を1/8の重みで混ぜた平均。<br />
+    /// Average mixing in new values with a weight of 1/8, like the round-trip time.
+    pub average: Option<Duration>,
+    pub calls: u64,
+    pub failures: u64,
+}
+
+impl CallLatency {
+    pub fn record(&mut self, call: &'static str, duration: Duration, is_success: bool) {
+        self.last_call = Some(call);
+        self.last = Some(duration);
+        self.average = Some(match self.average {
+            Some(average) => (average * 7 + duration) / 8,
+            None => duration,
+        });
+        self.calls += 1;
+        if !is_success {
+            self.failures += 1;
+        }
+    }
+}
+
+/// ネットワークの統計。ネットワークのオーバーレイに表示する。<br />
+/// Statistics of the network traffic, shown in the network overlay.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NetworkStats {
+    pub sent: TrafficStats,
+    pub received: TrafficStats,
+    /// 壊れていたり組み立てられなかったりして捨てたデータグラムの数。<br />
+    /// Number of datagrams dropped because they were malformed or could not be reassembled.
+    pub dropped_datagrams: u64,
+    /// セッションのピングで測った往復時間。<br />
+    /// Round-trip time measured by the pings of the session.
+    pub round_trip_time: Option<Duration>,
+    pub session: SessionStats,
+    pub rates: TrafficRates,
+    pub grpc: CallLatency,
+}
@@ -0,0 +1,202 @@
+use crate::game::shared::structs::{Primitive, Vertex};
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+
+/// 受け取る地形データの最大サイズ。<br />
+/// Maximum size of received terrain data.
+pub const MAX_TERRAIN_SIZE: usize = 32 * 1024 * 1024;
+
+/// 受け取る地形の最大頂点数。<br />
+/// Maximum number of vertices of a received terrain.
+pub const MAX_TERRAIN_VERTICES: usize = 1 << 20;
+
+/// 一つのスナップショットに含められる最大のプレイヤー数。<br />
+/// Maximum number of players in a single snapshot.
+pub const MAX_SNAPSHOT_PLAYERS: usize = 64;
+
+/// 一つのスナップショットに含められる最大のエンティティ数。全て変わっても分割の上限に収まるようにする。<br />
+/// Maximum number of entities in a single snapshot, so that it fits in the fragment limit even when all of them change.
+pub const MAX_SNAPSHOT_ENTITIES: usize = 128;
+
+/// プレイヤーIDの最大の長さ。<br />
+/// Maximum length of a player ID.
+pub const MAX_PLAYER_ID_LENGTH: usize = 64;
+
+/// 受け取ったペイロードを拒否した理由。<br />
+/// Reason a received payload was rejected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PayloadError {
+    /// ヘッダーが短すぎるか、マジックナンバーが違う。<br />
+    /// The header is too short or has the wrong magic number.
+    InvalidHeader,
+    VersionMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// フラグメントの番号や数が正しくない、もしくは同じメッセージの中で矛盾している。<br />
+    /// Fragment index or count is invalid, or inconsistent within the same message.
+    InvalidFragment {
+        sequence: u32,
+        reason: String,
+    },
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+    /// デシリアライズできなかった。<br />
+    /// Deserialization failed.
+    Malformed(String),
+    /// 有限ではない浮動小数点数を含んでいる。<br />
+    /// Contains a floating point number that is not finite.
+    NonFinite(&'static str),
+    OutOfRange {
+        field: &'static str,
+        value: usize,
+        limit: usize,
+    },
+    /// 差分の基準となるスナップショットがもうない。<br />
+    /// The base snapshot of a delta is no longer available.
+    MissingBase(u32),
+    /// 知らないセッションのパケット。<br />
+    /// A packet for an unknown session.
+    UnknownSession(u32),
+}
+
+impl Display for PayloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayloadError::InvalidHeader => write!(f, "Received a packet without a valid header."),
+            PayloadError::VersionMismatch { expected, actual } => write!(
+                f,
+                "Protocol version mismatch. Client: {}, packet: {}.",
+                expected, actual
+            ),
+            PayloadError::InvalidFragment { sequence, reason } => {
+                write!(f, "Invalid fragment for sequence {}: {}", sequence, reason)
+            }
+            PayloadError::TooLarge { size, limit } => write!(
+                f,
+                "Payload of {} bytes exceeds the limit of {} bytes.",
+                size, limit
+            ),
+            PayloadError::Malformed(reason) => write!(f, "Malformed payload: {}", reason),
+            PayloadError::NonFinite(field) => write!(f, "Field {} is not finite.", field),
+            PayloadError::OutOfRange {
+                field,
+                value,
+                limit,
+            } => write!(
+                f,
+                "Field {} is out of range: {} (limit: {}).",
+                field, value, limit
+            ),
+            PayloadError::MissingBase(sequence) => {
+                write!(f, "Base snapshot {} is no longer available.", sequence)
+            }
+            PayloadError::UnknownSession(session_id) => {
+                write!(f, "Packet belongs to unknown session {}.", session_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PayloadError {}
+
+/// 値が上限を超えていないか確認する。<br />
+/// Check that a value doesn't exceed its limit.
+pub fn check_range(field: &'static str, value: usize, limit: usize) -> Result<(), PayloadError> {
+    if value > limit {
+        Err(PayloadError::OutOfRange {
+            field,
+            value,
+            limit,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// 長さのプレフィックスで巨大なメモリを確保しないよう、入力のサイズを上限にしてバイナリをデシリアライズする。<br />
+/// 末尾に余分なバイトがある場合も拒否する。<br />
+/// Deserialize binary data, bounding allocations by the input size so a length prefix cannot claim huge amounts of memory.<br />
+/// Trailing bytes are rejected as well.
+pub fn deserialize_bounded<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, PayloadError> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(bytes.len() as u64)
+        .deserialize(bytes)
+        .map_err(|e| PayloadError::Malformed(e.to_string()))
+}
+
+/// 可変長の整数でエンコードされたバイナリを、`deserialize_bounded`と同じ制限でデシリアライズする。<br />
+/// Deserialize binary data encoded with variable-length integers, under the same bounds as `deserialize_bounded`.
+pub fn deserialize_compact<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, PayloadError> {
+    bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .with_limit(bytes.len() as u64)
+        .deserialize(bytes)
+        .map_err(|e| PayloadError::Malformed(e.to_string()))
+}
+
+fn check_vertex(vertex: &Vertex) -> Result<(), PayloadError> {
+    let is_finite = |values: &[f32]| values.iter().all(|v| v.is_finite());
+    let Vertex {
+        position,
+        normal,
+        uv,
+    } = vertex;
+    if !is_finite(&[position.x, position.y, position.z]) {
+        return Err(PayloadError::NonFinite("position"));
+    }
+    if !is_finite(&[normal.x, normal.y, normal.z]) {
+        return Err(PayloadError::NonFinite("normal"));
+    }
+    if !is_finite(&[uv.x, uv.y]) {
+        return Err(PayloadError::NonFinite("uv"));
+    }
+    Ok(())
+}
+
+/// サーバーから受け取った地形のデータを解析し、頂点とインデックスを検証する。<br />
+/// テクスチャの番号は送り手のものなので破棄する。<br />
+/// Parse terrain data received from the server, validating its vertices and indices.<br />
+/// The texture index belongs to the sender, so it is discarded.
+pub fn parse_terrain(bytes: &[u8]) -> Result<Primitive, PayloadError> {
+    if bytes.len() > MAX_TERRAIN_SIZE {
+        return Err(PayloadError::TooLarge {
+            size: bytes.len(),
+            limit: MAX_TERRAIN_SIZE,
+        });
+    }
+    let mut primitive = serde_json::from_slice::<Primitive>(bytes)
+        .map_err(|e| PayloadError::Malformed(e.to_string()))?;
+    check_range("vertices", primitive.vertices.len(), MAX_TERRAIN_VERTICES)?;
+    if primitive.vertices.is_empty() || primitive.indices.is_empty() {
+        return Err(PayloadError::Malformed("Terrain is empty.".to_string()));
+    }
+    if primitive.indices.len() % 3 != 0 {
+        return Err(PayloadError::Malformed(format!(
+            "Index count {} is not a multiple of 3.",
+            primitive.indices.len()
+        )));
+    }
+    let vertex_count = primitive.vertices.len();
+    if let Some(index) = primitive
+        .indices
+        .iter()
+        .find(|i| **i as usize >= vertex_count)
+    {
+        return Err(PayloadError::OutOfRange {
+            field: "indices",
+            value: *index as usize,
+            limit: vertex_count - 1,
+        });
+    }
+    for vertex in primitive.vertices.iter() {
+        check_vertex(vertex)?;
+    }
+    primitive.texture_index = None;
+    primitive.is_disposed = false;
+    Ok(primitive)
+}
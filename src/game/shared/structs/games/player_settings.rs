@@ -0,0 +1,145 @@
+use crate::protos::grpc_service::game_state::{PlayerSettings, RoomState};
+
+/// 称号の最大の文字数。<br />
+/// Maximum number of characters in a title.
+pub const MAX_TITLE_LENGTH: usize = 24;
+
+/// バッジの最大の文字数。<br />
+/// Maximum number of characters in a badge.
+pub const MAX_BADGE_LENGTH: usize = 8;
+
+/// 色を選んでいないプレイヤーの名札の色。<br />
+/// Nameplate color of players who haven't picked one.
+pub const DEFAULT_NAMEPLATE_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+/// 名札が背景に溶け込まないよう、これより透明な色は使わない。<br />
+/// Colors more transparent than this aren't used, so nameplates don't blend into the background.
+const MIN_NAMEPLATE_ALPHA: u8 = 128;
+
+/// RGBAの色を`0xRRGGBBAA`の形に詰める。<br />
+/// Pack an RGBA color as `0xRRGGBBAA`.
+pub fn pack_color(color: [u8; 4]) -> u32 {
+    u32::from_be_bytes(color)
+}
+
+pub fn unpack_color(color: u32) -> [u8; 4] {
+    color.to_be_bytes()
+}
+
+/// `#RRGGBB`か`#RRGGBBAA`の形の色を読む。アルファを省くと不透明になる。<br />
+/// Parse a color written as `#RRGGBB` or `#RRGGBBAA`. The color is opaque if alpha is left out.
+pub fn parse_color(text: &str) -> anyhow::Result<[u8; 4]> {
+    let digits = text.trim().trim_start_matches('#');
+    if (digits.len() != 6 && digits.len() != 8) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!(
+            "{} is not a color in the form of #RRGGBB or #RRGGBBAA.",
+            text
+        ));
+    }
+    let mut color = [255; 4];
+    for (i, channel) in color.iter_mut().take(digits.len() / 2).enumerate() {
+        *channel = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)?;
+    }
+    Ok(color)
+}
+
+/// 他のプレイヤーに配る前に、設定を表示できる形に整える。<br />
+/// 称号とバッジは前後の空白と制御文字を除いて切り詰め、透明すぎる色は見える濃さにする。<br />
+/// Tidy up settings into a displayable form before handing them to other players.<br />
+/// Titles and badges are trimmed, stripped of control characters and truncated, and colors that are too transparent are made visible.
+pub fn sanitize_settings(settings: &mut PlayerSettings) {
+    let clean = |text: &str, max_length: usize| {
+        text.chars()
+            .filter(|c| !c.is_control())
+            .collect::<String>()
+            .trim()
+            .chars()
+            .take(max_length)
+            .collect::<String>()
+    };
+    settings.title = clean(&settings.title, MAX_TITLE_LENGTH);
+    settings.badge = clean(&settings.badge, MAX_BADGE_LENGTH);
+    // 0は色を選んでいないことを表すので、そのまま残す。
+    // 0 means no color was picked, so it's left as is.
+    if settings.nameplate_color != 0 {
+        let mut color = unpack_color(settings.nameplate_color);
+        color[3] = color[3].max(MIN_NAMEPLATE_ALPHA);
+        settings.nameplate_color = pack_color(color);
+    }
+}
+
+/// プレイヤーの名前の上に表示する名札。<br />
+/// A nameplate shown above a player.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Nameplate {
+    pub player_id: String,
+    /// バッジがあれば、名前の前に付けた表示名。<br />
+    /// The displayed name, prefixed with the badge if there is one.
+    pub label: String,
+    pub title: String,
+    pub color: [u8; 4],
+    pub is_chat_muted: bool,
+}
+
+impl Nameplate {
+    pub fn new(player_id: &str, nickname: &str, settings: Option<&PlayerSettings>) -> Self {
+        let settings = settings.cloned().unwrap_or_default();
+        let label = if settings.badge.is_empty() {
+            nickname.to_string()
+        } else {
+            format!("[{}] {}", settings.badge, nickname)
+        };
+        Nameplate {
+            player_id: player_id.to_string(),
+            label,
+            title: settings.title,
+            color: if settings.nameplate_color == 0 {
+                DEFAULT_NAMEPLATE_COLOR
+            } else {
+                unpack_color(settings.nameplate_color)
+            },
+            is_chat_muted: settings.mute_chat,
+        }
+    }
+}
+
+/// 部屋にいるプレイヤーの名札を、部屋に入った順に並べる。<br />
+/// Get the nameplates of the players in a room, in the order they joined.
+pub fn get_room_nameplates(room_state: &RoomState) -> Vec<Nameplate> {
+    room_state
+        .players
+        .iter()
+        .map(|p| {
+            Nameplate::new(
+                &p.player_id,
+                &p.nickname,
+                room_state.player_settings.get(&p.player_id),
+            )
+        })
+        .collect()
+}
+
+/// ゲームの中でリモートのプレイヤーの上に出す名札。<br />
+/// 自分の名札、名札を隠すことを選んだプレイヤーの名札は除き、`hide_all`なら何も出さない。<br />
+/// Nameplates shown above remote players in game.<br />
+/// The local player's own nameplate and those of players who chose to hide theirs are left out, and nothing is shown when `hide_all` is set.
+pub fn get_remote_nameplates(
+    room_state: &RoomState,
+    local_player_id: &str,
+    hide_all: bool,
+) -> Vec<Nameplate> {
+    if hide_all {
+        return vec![];
+    }
+    get_room_nameplates(room_state)
+        .into_iter()
+        .filter(|nameplate| {
+            nameplate.player_id != local_player_id
+                && !room_state
+                    .player_settings
+                    .get(&nameplate.player_id)
+                    .map(|s| s.hide_nameplate)
+                    .unwrap_or(false)
+        })
+        .collect()
+}
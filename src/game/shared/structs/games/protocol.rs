@@ -0,0 +1,71 @@
+use crate::game::shared::structs::games::payload::PayloadError;
+use std::convert::TryInto;
+
+/// クライアントとサーバーの間の通信プロトコルのバージョン。互換性のない変更をする度に上げる。<br />
+/// Version of the protocol between the client and the server. Bump it on every incompatible change.
+pub const PROTOCOL_VERSION: u32 = 6;
+
+/// クライアントのビルドバージョン。<br />
+/// Build version of the client.
+pub const BUILD_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// UDPパケットの先頭に付けるマジックナンバー。<br />
+/// Magic number prepended to every UDP packet.
+pub const PACKET_MAGIC: [u8; 2] = *b"DG";
+
+/// UDPパケットのヘッダーの長さ。マジックナンバーとプロトコルバージョン。<br />
+/// Length of the UDP packet header: magic number and protocol version.
+pub const PACKET_HEADER_SIZE: usize = 6;
+
+/// サーバーとの互換性。<br />
+/// Compatibility with the server.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProtocolCompatibility {
+    /// まだハンドシェイクしていない。<br />
+    /// The handshake has not been done yet.
+    Unchecked,
+    Compatible,
+    /// クライアントを更新しないとサーバーに接続できない。<br />
+    /// The client has to be updated before connecting to the server.
+    UpdateRequired {
+        server_protocol_version: u32,
+        server_build_version: String,
+        message: String,
+    },
+}
+
+impl ProtocolCompatibility {
+    pub fn is_update_required(&self) -> bool {
+        matches!(self, ProtocolCompatibility::UpdateRequired { .. })
+    }
+}
+
+/// ペイロードにバージョン付きのヘッダーを付ける。<br />
+/// Prepend the versioned header to a payload.
+pub fn encode_packet(payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(PACKET_HEADER_SIZE + payload.len());
+    packet.extend_from_slice(&PACKET_MAGIC);
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// ヘッダーを検証してペイロードを取り出す。バージョンが違う場合はデコードせずにエラーを返す。<br />
+/// Validate the header and extract the payload. Returns an error without decoding if the version differs.
+pub fn decode_packet(packet: &[u8]) -> Result<&[u8], PayloadError> {
+    if packet.len() < PACKET_HEADER_SIZE || packet[0..2] != PACKET_MAGIC {
+        return Err(PayloadError::InvalidHeader);
+    }
+    let version = u32::from_le_bytes(
+        packet[2..PACKET_HEADER_SIZE]
+            .try_into()
+            .expect("Failed to read protocol version."),
+    );
+    if version != PROTOCOL_VERSION {
+        return Err(PayloadError::VersionMismatch {
+            expected: PROTOCOL_VERSION,
+            actual: version,
+        });
+    }
+    Ok(&packet[PACKET_HEADER_SIZE..])
+}
@@ -0,0 +1,247 @@
+use crate::game::shared::structs::games::payload::{
+    check_range, PayloadError, MAX_PLAYER_ID_LENGTH, MAX_SNAPSHOT_ENTITIES,
+};
+use crate::game::shared::structs::games::snapshot::QuantizedWorldMatrix;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// 一人のプレイヤーが持てるエンティティの最大数。<br />
+/// Maximum number of entities a single player can own.
+pub const MAX_OWNED_ENTITIES: usize = 32;
+
+/// エンティティのIDの最大の長さ。IDは持ち主のIDに番号を付けたもの。<br />
+/// Maximum length of an entity ID. IDs are the ID of the owner followed by a number.
+pub const MAX_ENTITY_ID_LENGTH: usize = MAX_PLAYER_ID_LENGTH + 16;
+
+/// モデルのファイル名の最大の長さ。<br />
+/// Maximum length of the file name of a model.
+pub const MAX_MODEL_NAME_LENGTH: usize = 128;
+
+/// 持ち主のプレイヤーが状態を決め、他のクライアントに複製されるエンティティ。<br />
+/// An entity whose state is decided by its owning player and replicated to other clients.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct EntitySnapshot {
+    pub entity_id: String,
+    pub owner_id: String,
+    /// `models`の下にあるモデルのファイル名。持ち主以外のクライアントは同じモデルで代理のエンティティを作る。<br />
+    /// File name of a model under `models`. Clients other than the owner create proxy entities with the same model.
+    pub model: String,
+    pub world_matrix: QuantizedWorldMatrix,
+    pub current_hp: i32,
+    pub max_hp: i32,
+}
+
+impl EntitySnapshot {
+    /// 受け取ったエンティティを確かめる。モデルはディレクトリを含まないファイル名でなければならない。<br />
+    /// Check a received entity. The model has to be a file name without directories.
+    pub fn validate(&self) -> Result<(), PayloadError> {
+        if self.entity_id.is_empty() || self.owner_id.is_empty() {
+            return Err(PayloadError::Malformed(
+                "Entity or owner ID is empty.".to_string(),
+            ));
+        }
+        check_range("entity_id", self.entity_id.len(), MAX_ENTITY_ID_LENGTH)?;
+        check_range("owner_id", self.owner_id.len(), MAX_PLAYER_ID_LENGTH)?;
+        check_range("model", self.model.len(), MAX_MODEL_NAME_LENGTH)?;
+        let is_file_name = !self.model.is_empty()
+            && !self.model.starts_with('.')
+            && !self.model.contains(|c| c == '/' || c == '\\' || c == ':');
+        if !is_file_name {
+            return Err(PayloadError::Malformed(format!(
+                "Entity {} has an invalid model: {}.",
+                self.entity_id, self.model
+            )));
+        }
+        if self.current_hp < 0 || self.max_hp < 0 {
+            return Err(PayloadError::Malformed(format!(
+                "Entity {} has negative HP.",
+                self.entity_id
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// 代理のエンティティに対する変化。<br />
+/// A change to a proxy entity.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplicationEvent {
+    Spawned(EntitySnapshot),
+    Updated(EntitySnapshot),
+    Despawned(String),
+}
+
+/// サーバー側で、部屋の中のエンティティとその持ち主を管理する。<br />
+/// 状態を送れるのは持ち主だけで、他のプレイヤーのエンティティを名乗る更新は捨てる。<br />
+/// Keeps track of the entities in a room and their owners on the server side.<br />
+/// Only owners can send state, and updates claiming the entities of other players are dropped.
+#[derive(Clone, Debug, Default)]
+pub struct EntityRegistry {
+    entities: HashMap<String, EntitySnapshot>,
+}
+
+impl EntityRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 持ち主が送ったエンティティで、その持ち主のエンティティを置き換える。送られなかったエンティティは消える。<br />
+    /// 持ち主が違うものや、他のプレイヤーが持つIDのもの、上限を超えた分は受け付けず、その数を返す。<br />
+    /// Replace the entities of an owner with the ones they sent. Entities that weren't sent are removed.<br />
+    /// Entities with another owner, with an ID owned by another player, or over the limits are rejected and their number is returned.
+    pub fn accept(&mut self, owner_id: &str, sent: Vec<EntitySnapshot>) -> usize {
+        let others = self
+            .entities
+            .values()
+            .filter(|e| e.owner_id != owner_id)
+            .count();
+        let limit = MAX_OWNED_ENTITIES.min(MAX_SNAPSHOT_ENTITIES.saturating_sub(others));
+        let mut rejected = 0;
+        let mut accepted = HashMap::new();
+        for entity in sent.into_iter() {
+            let is_taken = self
+                .entities
+                .get(&entity.entity_id)
+                .map(|e| e.owner_id != owner_id)
+                .unwrap_or(false);
+            if entity.owner_id != owner_id || is_taken || accepted.len() >= limit {
+                rejected += 1;
+                continue;
+            }
+            accepted.insert(entity.entity_id.clone(), entity);
+        }
+        self.entities.retain(|_, e| e.owner_id != owner_id);
+        self.entities.extend(accepted);
+        rejected
+    }
+
+    /// いなくなったプレイヤーのエンティティを消す。<br />
+    /// Remove the entities of a player who left.
+    pub fn remove_owner(&mut self, owner_id: &str) {
+        self.entities.retain(|_, e| e.owner_id != owner_id);
+    }
+
+    /// 全てのエンティティをIDの順に返す。<br />
+    /// Return all entities ordered by ID.
+    pub fn get_entities(&self) -> Vec<EntitySnapshot> {
+        let mut entities = self.entities.values().cloned().collect::<Vec<_>>();
+        entities.sort_by(|a, b| a.entity_id.cmp(&b.entity_id));
+        entities
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+/// クライアント側で、他のプレイヤーが持つエンティティの代理を追う。<br />
+/// 届いたエンティティの一覧を前の一覧と比べ、出現・更新・消滅のイベントにする。<br />
+/// Tracks the proxies of entities owned by other players on the client side.<br />
+/// Each received list of entities is compared with the previous one and turned into spawn, update and despawn events.
+#[derive(Clone, Debug, Default)]
+pub struct ProxyTracker {
+    proxies: HashMap<String, EntitySnapshot>,
+}
+
+impl ProxyTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 届いたエンティティの一覧を反映する。ローカルプレイヤーのエンティティは自分で動かしているので無視する。<br />
+    /// Apply a received list of entities. Entities of the local player are driven locally, so they're ignored.
+    pub fn update(
+        &mut self,
+        entities: &[EntitySnapshot],
+        local_player_id: &str,
+    ) -> Vec<ReplicationEvent> {
+        let remote = entities
+            .iter()
+            .filter(|e| e.owner_id != local_player_id)
+            .collect::<Vec<_>>();
+        let mut events = vec![];
+        let despawned = self
+            .proxies
+            .keys()
+            .filter(|id| !remote.iter().any(|e| &e.entity_id == *id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for entity_id in despawned.into_iter() {
+            self.proxies.remove(&entity_id);
+            events.push(ReplicationEvent::Despawned(entity_id));
+        }
+        for entity in remote.into_iter() {
+            match self.proxies.get(&entity.entity_id) {
+                // モデルが変わったら同じエンティティとして扱えないので、作り直す。
+                // A changed model can't be treated as the same entity, so it's recreated.
+                Some(proxy) if proxy.model != entity.model => {
+                    events.push(ReplicationEvent::Despawned(entity.entity_id.clone()));
+                    events.push(ReplicationEvent::Spawned(entity.clone()));
+                }
+                Some(proxy) if proxy == entity => continue,
+                Some(_) => events.push(ReplicationEvent::Updated(entity.clone())),
+                None => events.push(ReplicationEvent::Spawned(entity.clone())),
+            }
+            self.proxies
+                .insert(entity.entity_id.clone(), entity.clone());
+        }
+        events
+    }
+
+    /// 全ての代理を消すイベントを返す。部屋を出た時に使う。<br />
+    /// Return events despawning every proxy. Used when leaving the room.
+    pub fn clear(&mut self) -> Vec<ReplicationEvent> {
+        self.proxies
+            .drain()
+            .map(|(entity_id, _)| ReplicationEvent::Despawned(entity_id))
+            .collect()
+    }
+}
+
+/// クライアント側の複製のキュー。シーンが持っているエンティティを書き込み、受信するタスクが代理のイベントを積む。<br />
+/// Replication queues on the client side. The scene writes the entities it owns, and the receiving task queues events for the proxies.
+#[derive(Clone, Debug, Default)]
+pub struct ReplicationQueues {
+    /// ローカルプレイヤーが持ち、サーバーに送るエンティティ。シーンが毎ステップ書き換える。<br />
+    /// Entities owned by the local player and sent to the server. The scene rewrites them every step.
+    owned_entities: Vec<EntitySnapshot>,
+    proxies: ProxyTracker,
+    events: VecDeque<ReplicationEvent>,
+}
+
+impl ReplicationQueues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_owned_entities(&mut self, entities: Vec<EntitySnapshot>) {
+        self.owned_entities = entities;
+    }
+
+    pub fn get_owned_entities(&self) -> Vec<EntitySnapshot> {
+        self.owned_entities.clone()
+    }
+
+    /// サーバーから届いたエンティティを代理と比べ、出現・更新・消滅のイベントを積む。<br />
+    /// Compare the entities from the server with the proxies, and queue spawn, update and despawn events.
+    pub fn push_entities(&mut self, entities: &[EntitySnapshot], local_player_id: &str) {
+        let events = self.proxies.update(entities, local_player_id);
+        self.events.extend(events);
+    }
+
+    pub fn take_events(&mut self) -> Vec<ReplicationEvent> {
+        self.events.drain(..).collect()
+    }
+
+    /// 全ての状態を捨てる。シーンに残っている代理のエンティティは、消すイベントを積んでおく。<br />
+    /// Discard all states. Despawn events are queued for the proxy entities still in the scene.
+    pub fn clear(&mut self) {
+        let despawned = self.proxies.clear();
+        *self = Self::new();
+        self.events.extend(despawned);
+    }
+}
@@ -0,0 +1,748 @@
+use crate::game::shared::structs::games::fragment::{split_message, FragmentAssembler};
+use crate::game::shared::structs::games::network_stats::TrafficStats;
+use crate::game::shared::structs::games::payload::{
+    check_range, deserialize_compact, PayloadError, MAX_PLAYER_ID_LENGTH, MAX_SNAPSHOT_ENTITIES,
+    MAX_SNAPSHOT_PLAYERS,
+};
+use crate::game::shared::structs::games::replication::EntitySnapshot;
+use crate::game::shared::structs::games::{PlayerUdp, RoomStateUdp, WorldMatrixUdp};
+use crate::game::shared::util::math::{decode_rotation, encode_rotation, vec3_from_slice};
+use bincode::Options;
+use glam::Quat;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+
+/// 位置の量子化の単位。1ミリメートル。<br />
+/// Quantization unit of positions: one millimeter.
+const POSITION_SCALE: f32 = 1000.0;
+
+/// 大きさの量子化の単位。<br />
+/// Quantization unit of scales.
+const SCALE_SCALE: f32 = 100.0;
+
+/// 正規化したクォータニオンの各成分の量子化の単位。<br />
+/// Quantization unit of each component of a normalized quaternion.
+const ROTATION_SCALE: f32 = i16::MAX as f32;
+
+/// 差分の基準として保持するスナップショットの数。<br />
+/// Number of snapshots kept as bases for deltas.
+const SNAPSHOT_HISTORY_SIZE: usize = 32;
+
+/// 量子化したワールド行列。<br />
+/// A quantized world matrix.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuantizedWorldMatrix {
+    pub position: [i32; 3],
+    pub scale: [u16; 3],
+    pub rotation: [i16; 4],
+}
+
+impl From<&WorldMatrixUdp> for QuantizedWorldMatrix {
+    fn from(world_matrix: &WorldMatrixUdp) -> Self {
+        let position = vec3_from_slice(&world_matrix.position, 0.0) * POSITION_SCALE;
+        let scale = vec3_from_slice(&world_matrix.scale, 1.0) * SCALE_SCALE;
+        let rotation = encode_rotation(decode_rotation(&world_matrix.rotation));
+        let quantize_scale = |v: f32| v.round().max(0.0).min(u16::MAX as f32) as u16;
+        let quantize_rotation = |v: f32| (v * ROTATION_SCALE).round() as i16;
+        QuantizedWorldMatrix {
+            position: [
+                position.x.round() as i32,
+                position.y.round() as i32,
+                position.z.round() as i32,
+            ],
+            scale: [
+                quantize_scale(scale.x),
+                quantize_scale(scale.y),
+                quantize_scale(scale.z),
+            ],
+            rotation: [
+                quantize_rotation(rotation[0]),
+                quantize_rotation(rotation[1]),
+                quantize_rotation(rotation[2]),
+                quantize_rotation(rotation[3]),
+            ],
+        }
+    }
+}
+
+impl From<QuantizedWorldMatrix> for WorldMatrixUdp {
+    fn from(world_matrix: QuantizedWorldMatrix) -> Self {
+        let [x, y, z, w] = world_matrix.rotation;
+        let rotation = Quat::from_xyzw(
+            x as f32 / ROTATION_SCALE,
+            y as f32 / ROTATION_SCALE,
+            z as f32 / ROTATION_SCALE,
+            w as f32 / ROTATION_SCALE,
+        );
+        WorldMatrixUdp {
+            position: world_matrix
+                .position
+                .iter()
+                .map(|v| *v as f32 / POSITION_SCALE)
+                .collect(),
+            scale: world_matrix
+                .scale
+                .iter()
+                .map(|v| *v as f32 / SCALE_SCALE)
+                .collect(),
+            rotation: encode_rotation(rotation),
+        }
+    }
+}
+
+/// ネットワークで同期するプレイヤーの状態。名前やパスワードなどの変わらない情報は含まない。<br />
+/// Replicated state of a player. Static data such as the name or the password is not included.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PlayerSnapshot {
+    pub player_id: String,
+    pub is_in_game: bool,
+    pub is_owner: bool,
+    pub current_hp: i32,
+    pub max_hp: i32,
+    pub current_sp: i32,
+    pub max_sp: i32,
+    pub is_alive: bool,
+    pub world_matrix: QuantizedWorldMatrix,
+    /// 最後にダメージを与えたプレイヤーのID。キルカメラがキラーを探すのに使う。<br />
+    /// ID of the player who last dealt damage. Used by the kill cam to find the killer.
+    pub last_attacker_id: String,
+    /// 乗っている乗り物のID。徒歩なら空。<br />
+    /// ID of the mount being ridden. Empty while on foot.
+    pub mount_id: String,
+}
+
+impl From<&PlayerUdp> for PlayerSnapshot {
+    fn from(player: &PlayerUdp) -> Self {
+        let state = &player.state.state;
+        PlayerSnapshot {
+            player_id: player.player_id.clone(),
+            is_in_game: player.state.is_in_game,
+            is_owner: player.state.is_owner,
+            current_hp: state.current_hp,
+            max_hp: state.max_hp,
+            current_sp: state.current_sp,
+            max_sp: state.max_sp,
+            is_alive: state.is_alive,
+            world_matrix: QuantizedWorldMatrix::from(&state.world_matrix),
+            last_attacker_id: state.last_attacker_id.clone(),
+            mount_id: state.mount_id.clone(),
+        }
+    }
+}
+
+impl PlayerSnapshot {
+    /// スナップショットの内容をプレイヤーに書き込む。<br />
+    /// Write the contents of the snapshot into a player.
+    pub fn apply_to(&self, player: &mut PlayerUdp) {
+        player.player_id = self.player_id.clone();
+        player.state.is_in_game = self.is_in_game;
+        player.state.is_owner = self.is_owner;
+        let state = &mut player.state.state;
+        state.current_hp = self.current_hp;
+        state.max_hp = self.max_hp;
+        state.current_sp = self.current_sp;
+        state.max_sp = self.max_sp;
+        state.is_alive = self.is_alive;
+        state.world_matrix = WorldMatrixUdp::from(self.world_matrix);
+        state.last_attacker_id = self.last_attacker_id.clone();
+        state.mount_id = self.mount_id.clone();
+    }
+}
+
+/// ある時点の全てのプレイヤーと、プレイヤーが持つエンティティの状態。<br />
+/// The state of all players, and of the entities they own, at a point in time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Snapshot {
+    pub sequence: u32,
+    pub started: bool,
+    pub players: Vec<PlayerSnapshot>,
+    pub entities: Vec<EntitySnapshot>,
+}
+
+impl Snapshot {
+    /// スナップショットの内容を部屋のステートに書き込む。スナップショットにいないプレイヤーは削除する。<br />
+    /// Write the snapshot into a room state. Players missing from the snapshot are removed.
+    pub fn apply_to(&self, room_state: &mut RoomStateUdp) {
+        room_state.started = self.started;
+        room_state
+            .players
+            .retain(|p| self.players.iter().any(|s| s.player_id == p.player_id));
+        for snapshot in self.players.iter() {
+            match room_state
+                .players
+                .iter_mut()
+                .find(|p| p.player_id == snapshot.player_id)
+            {
+                Some(player) => snapshot.apply_to(player),
+                None => {
+                    let mut player = PlayerUdp::default();
+                    snapshot.apply_to(&mut player);
+                    room_state.players.push(player);
+                }
+            }
+        }
+        room_state.current_players = room_state.players.len() as i32;
+    }
+}
+
+const DIRTY_IS_IN_GAME: u16 = 1 << 0;
+const DIRTY_IS_OWNER: u16 = 1 << 1;
+const DIRTY_CURRENT_HP: u16 = 1 << 2;
+const DIRTY_MAX_HP: u16 = 1 << 3;
+const DIRTY_CURRENT_SP: u16 = 1 << 4;
+const DIRTY_MAX_SP: u16 = 1 << 5;
+const DIRTY_IS_ALIVE: u16 = 1 << 6;
+const DIRTY_POSITION: u16 = 1 << 7;
+const DIRTY_SCALE: u16 = 1 << 8;
+const DIRTY_ROTATION: u16 = 1 << 9;
+
+/// 変更フラグと、それぞれのフィールドが持つ値の数。値はこの順に並ぶ。<br />
+/// Dirty flags and the number of values each field holds. Values are laid out in this order.
+const DIRTY_FIELDS: [(u16, usize); 10] = [
+    (DIRTY_IS_IN_GAME, 1),
+    (DIRTY_IS_OWNER, 1),
+    (DIRTY_CURRENT_HP, 1),
+    (DIRTY_MAX_HP, 1),
+    (DIRTY_CURRENT_SP, 1),
+    (DIRTY_MAX_SP, 1),
+    (DIRTY_IS_ALIVE, 1),
+    (DIRTY_POSITION, 3),
+    (DIRTY_SCALE, 3),
+    (DIRTY_ROTATION, 4),
+];
+
+const DIRTY_ALL: u16 = (1 << DIRTY_FIELDS.len()) - 1;
+
+fn get_value_count(dirty: u16) -> usize {
+    DIRTY_FIELDS
+        .iter()
+        .filter(|(flag, _)| dirty & flag != 0)
+        .map(|(_, count)| count)
+        .sum()
+}
+
+fn narrow<T: TryFrom<i32>>(field: &'static str, value: i32) -> Result<T, PayloadError> {
+    T::try_from(value).map_err(|_| {
+        PayloadError::Malformed(format!("Field {} has an invalid value: {}.", field, value))
+    })
+}
+
+/// 基準のスナップショットから変わったフィールドだけを持つプレイヤーの差分。<br />
+/// `dirty`のビットが立っているフィールドの値だけが`values`に並ぶ。位置は基準からの差で送るため、可変長の整数で小さく収まる。<br />
+/// Delta of a player that only holds the fields changed since the base snapshot.<br />
+/// Only the values of fields whose bit is set in `dirty` are laid out in `values`. Positions are sent as the difference from the base, so they stay small as variable-length integers.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct PlayerDelta {
+    player_id: String,
+    dirty: u16,
+    values: Vec<i32>,
+    /// 整数にならないため、攻撃したプレイヤーは変わった時だけ別に送る。<br />
+    /// The attacker doesn't fit in an integer, so it is sent separately and only when it changes.
+    last_attacker_id: Option<String>,
+    /// 乗り物も同じく、乗り降りした時だけ送る。<br />
+    /// Likewise the mount is only sent when the player gets on or off.
+    mount_id: Option<String>,
+}
+
+impl PlayerDelta {
+    fn new(current: &PlayerSnapshot, base: Option<&PlayerSnapshot>) -> Self {
+        // 新しいプレイヤーは既定値との差分として送る。
+        // New players are sent as a delta against the default values.
+        let default = PlayerSnapshot::default();
+        let base = base.unwrap_or(&default);
+        let mut delta = PlayerDelta {
+            player_id: current.player_id.clone(),
+            dirty: 0,
+            values: vec![],
+            last_attacker_id: None,
+            mount_id: None,
+        };
+        if current.last_attacker_id != base.last_attacker_id {
+            delta.last_attacker_id = Some(current.last_attacker_id.clone());
+        }
+        if current.mount_id != base.mount_id {
+            delta.mount_id = Some(current.mount_id.clone());
+        }
+        delta.push_field(
+            DIRTY_IS_IN_GAME,
+            &[current.is_in_game as i32],
+            &[base.is_in_game as i32],
+        );
+        delta.push_field(
+            DIRTY_IS_OWNER,
+            &[current.is_owner as i32],
+            &[base.is_owner as i32],
+        );
+        delta.push_field(DIRTY_CURRENT_HP, &[current.current_hp], &[base.current_hp]);
+        delta.push_field(DIRTY_MAX_HP, &[current.max_hp], &[base.max_hp]);
+        delta.push_field(DIRTY_CURRENT_SP, &[current.current_sp], &[base.current_sp]);
+        delta.push_field(DIRTY_MAX_SP, &[current.max_sp], &[base.max_sp]);
+        delta.push_field(
+            DIRTY_IS_ALIVE,
+            &[current.is_alive as i32],
+            &[base.is_alive as i32],
+        );
+
+        let position = current.world_matrix.position;
+        let base_position = base.world_matrix.position;
+        if position != base_position {
+            delta.dirty |= DIRTY_POSITION;
+            delta.values.extend(
+                position
+                    .iter()
+                    .zip(base_position.iter())
+                    .map(|(c, b)| c.wrapping_sub(*b)),
+            );
+        }
+
+        let widen_scale = |s: &PlayerSnapshot| {
+            s.world_matrix
+                .scale
+                .iter()
+                .map(|v| *v as i32)
+                .collect::<Vec<_>>()
+        };
+        let widen_rotation = |s: &PlayerSnapshot| {
+            s.world_matrix
+                .rotation
+                .iter()
+                .map(|v| *v as i32)
+                .collect::<Vec<_>>()
+        };
+        delta.push_field(DIRTY_SCALE, &widen_scale(current), &widen_scale(base));
+        delta.push_field(
+            DIRTY_ROTATION,
+            &widen_rotation(current),
+            &widen_rotation(base),
+        );
+        delta
+    }
+
+    fn push_field(&mut self, flag: u16, current: &[i32], base: &[i32]) {
+        if current != base {
+            self.dirty |= flag;
+            self.values.extend_from_slice(current);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.dirty == 0 && self.last_attacker_id.is_none() && self.mount_id.is_none()
+    }
+
+    /// 受け取った差分のIDと、変更フラグと値の数が合っているか確認する。<br />
+    /// Check the ID of a received delta, and that its dirty flags match the number of values.
+    fn validate(&self) -> Result<(), PayloadError> {
+        if self.player_id.is_empty() {
+            return Err(PayloadError::Malformed("Player ID is empty.".to_string()));
+        }
+        check_range("player_id", self.player_id.len(), MAX_PLAYER_ID_LENGTH)?;
+        if let Some(attacker_id) = self.last_attacker_id.as_ref() {
+            check_range("last_attacker_id", attacker_id.len(), MAX_PLAYER_ID_LENGTH)?;
+        }
+        if let Some(mount_id) = self.mount_id.as_ref() {
+            check_range("mount_id", mount_id.len(), MAX_PLAYER_ID_LENGTH)?;
+        }
+        if self.dirty & !DIRTY_ALL != 0 {
+            return Err(PayloadError::Malformed(format!(
+                "Player {} has unknown dirty flags: {:#06x}.",
+                self.player_id, self.dirty
+            )));
+        }
+        let expected = get_value_count(self.dirty);
+        if self.values.len() != expected {
+            return Err(PayloadError::Malformed(format!(
+                "Player {} has {} values, but its dirty flags need {}.",
+                self.player_id,
+                self.values.len(),
+                expected
+            )));
+        }
+        Ok(())
+    }
+
+    /// 変更フラグが立っているフィールドの値を取り出す。<br />
+    /// Take the values of a field if its dirty flag is set.
+    fn read<'a>(&self, values: &mut &'a [i32], flag: u16, count: usize) -> Option<&'a [i32]> {
+        if self.dirty & flag == 0 || values.len() < count {
+            return None;
+        }
+        let (head, tail) = values.split_at(count);
+        *values = tail;
+        Some(head)
+    }
+
+    fn apply(&self, base: Option<&PlayerSnapshot>) -> Result<PlayerSnapshot, PayloadError> {
+        let mut snapshot = base.cloned().unwrap_or_default();
+        snapshot.player_id = self.player_id.clone();
+        if let Some(attacker_id) = self.last_attacker_id.as_ref() {
+            snapshot.last_attacker_id = attacker_id.clone();
+        }
+        if let Some(mount_id) = self.mount_id.as_ref() {
+            snapshot.mount_id = mount_id.clone();
+        }
+        let mut values = self.values.as_slice();
+        if let Some(v) = self.read(&mut values, DIRTY_IS_IN_GAME, 1) {
+            snapshot.is_in_game = v[0] != 0;
+        }
+        if let Some(v) = self.read(&mut values, DIRTY_IS_OWNER, 1) {
+            snapshot.is_owner = v[0] != 0;
+        }
+        if let Some(v) = self.read(&mut values, DIRTY_CURRENT_HP, 1) {
+            snapshot.current_hp = v[0];
+        }
+        if let Some(v) = self.read(&mut values, DIRTY_MAX_HP, 1) {
+            snapshot.max_hp = v[0];
+        }
+        if let Some(v) = self.read(&mut values, DIRTY_CURRENT_SP, 1) {
+            snapshot.current_sp = v[0];
+        }
+        if let Some(v) = self.read(&mut values, DIRTY_MAX_SP, 1) {
+            snapshot.max_sp = v[0];
+        }
+        if let Some(v) = self.read(&mut values, DIRTY_IS_ALIVE, 1) {
+            snapshot.is_alive = v[0] != 0;
+        }
+        if let Some(v) = self.read(&mut values, DIRTY_POSITION, 3) {
+            for (position, difference) in snapshot.world_matrix.position.iter_mut().zip(v) {
+                *position = position.wrapping_add(*difference);
+            }
+        }
+        if let Some(v) = self.read(&mut values, DIRTY_SCALE, 3) {
+            for (scale, value) in snapshot.world_matrix.scale.iter_mut().zip(v) {
+                *scale = narrow("scale", *value)?;
+            }
+        }
+        if let Some(v) = self.read(&mut values, DIRTY_ROTATION, 4) {
+            for (rotation, value) in snapshot.world_matrix.rotation.iter_mut().zip(v) {
+                *rotation = narrow("rotation", *value)?;
+            }
+        }
+
+        let hp_and_sp = [
+            snapshot.current_hp,
+            snapshot.max_hp,
+            snapshot.current_sp,
+            snapshot.max_sp,
+        ];
+        if hp_and_sp.iter().any(|v| *v < 0) {
+            return Err(PayloadError::Malformed(format!(
+                "Player {} has negative HP or SP.",
+                self.player_id
+            )));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// スナップショットの確認応答。<br />
+/// 最後に受け取った番号と、その前の32個のスナップショットを受け取ったかどうかのビットを持つ。<br />
+/// Acknowledgement of snapshots.<br />
+/// Holds the last received sequence, and a bit for each of the 32 snapshots before it telling whether it was received.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SnapshotAck {
+    pub sequence: u32,
+    /// `n`番目のビットは`sequence - n - 1`のスナップショットを表す。<br />
+    /// Bit `n` stands for the snapshot `sequence - n - 1`.
+    pub bits: u32,
+}
+
+impl SnapshotAck {
+    pub fn contains(&self, sequence: u32) -> bool {
+        let distance = self.sequence.wrapping_sub(sequence);
+        match distance {
+            0 => true,
+            1..=32 => self.bits & (1 << (distance - 1)) != 0,
+            _ => false,
+        }
+    }
+}
+
+/// UDPで送るスナップショットのパケット。<br />
+/// A snapshot packet sent over UDP.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct SnapshotPacket {
+    sequence: u32,
+    /// 差分の基準となるスナップショットの番号。Noneなら完全なスナップショット。<br />
+    /// Sequence of the base snapshot of the delta. None for a full snapshot.
+    base_sequence: Option<u32>,
+    /// 相手から受け取ったスナップショットの確認応答。<br />
+    /// Acknowledgement of the snapshots received from the other side.
+    ack: Option<SnapshotAck>,
+    started: bool,
+    players: Vec<PlayerDelta>,
+    removed_players: Vec<String>,
+    /// 基準から現れたか変わったエンティティ。基準になければ出現を表す。<br />
+    /// Entities that appeared or changed since the base. Those missing from the base are spawned.
+    entities: Vec<EntitySnapshot>,
+    /// 基準から消えたエンティティのID。<br />
+    /// IDs of entities despawned since the base.
+    removed_entities: Vec<String>,
+}
+
+fn push_history<T>(history: &mut VecDeque<T>, item: T) {
+    if history.len() == SNAPSHOT_HISTORY_SIZE {
+        history.pop_front();
+    }
+    history.push_back(item);
+}
+
+/// 送ったスナップショットと、相手が受け取ったかどうか。<br />
+/// A sent snapshot, and whether the other side received it.
+struct SentSnapshot {
+    snapshot: Snapshot,
+    is_acked: bool,
+}
+
+/// 確認済みの最新のスナップショットとの差分でパケットを作る。<br />
+/// Encodes packets as deltas against the newest acknowledged snapshot.
+#[derive(Default)]
+pub struct SnapshotEncoder {
+    sequence: u32,
+    history: VecDeque<SentSnapshot>,
+    stats: TrafficStats,
+}
+
+impl SnapshotEncoder {
+    pub fn new() -> Self {
+        SnapshotEncoder {
+            sequence: 0,
+            history: VecDeque::with_capacity(SNAPSHOT_HISTORY_SIZE),
+            stats: TrafficStats::default(),
+        }
+    }
+
+    pub fn get_stats(&self) -> TrafficStats {
+        self.stats
+    }
+
+    /// 相手が受け取ったスナップショットを記録する。確認応答が失われても、後の応答のビットで補える。<br />
+    /// Record the snapshots received by the other side. A lost acknowledgement is made up for by the bits of a later one.
+    pub fn acknowledge(&mut self, ack: SnapshotAck) {
+        for sent in self.history.iter_mut() {
+            if ack.contains(sent.snapshot.sequence) {
+                sent.is_acked = true;
+            }
+        }
+    }
+
+    /// スナップショットをバイナリにエンコードする。変わった全てのプレイヤーとエンティティを一つのメッセージにまとめ、MTUを超える場合は分割する。<br />
+    /// Encode a snapshot into binary. All changed players and entities are packed into one message, which is fragmented if it exceeds the MTU.
+    pub fn encode(
+        &mut self,
+        started: bool,
+        players: Vec<PlayerSnapshot>,
+        entities: Vec<EntitySnapshot>,
+        ack: Option<SnapshotAck>,
+    ) -> anyhow::Result<Vec<Vec<u8>>> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let base = self
+            .history
+            .iter()
+            .rev()
+            .find(|h| h.is_acked)
+            .map(|h| &h.snapshot);
+        let mut packet = SnapshotPacket {
+            sequence: self.sequence,
+            base_sequence: base.map(|b| b.sequence),
+            ack,
+            started,
+            players: vec![],
+            removed_players: vec![],
+            entities: vec![],
+            removed_entities: vec![],
+        };
+        for player in players.iter() {
+            let base_player =
+                base.and_then(|b| b.players.iter().find(|p| p.player_id == player.player_id));
+            let delta = PlayerDelta::new(player, base_player);
+            if base_player.is_none() || !delta.is_empty() {
+                packet.players.push(delta);
+            }
+        }
+        if let Some(b) = base {
+            packet.removed_players = b
+                .players
+                .iter()
+                .filter(|p| !players.iter().any(|c| c.player_id == p.player_id))
+                .map(|p| p.player_id.clone())
+                .collect();
+        }
+        // エンティティは小さいので、変わったものをそのまま送る。
+        // Entities are small, so changed ones are sent as they are.
+        packet.entities = entities
+            .iter()
+            .filter(|entity| {
+                base.and_then(|b| b.entities.iter().find(|e| e.entity_id == entity.entity_id))
+                    != Some(*entity)
+            })
+            .cloned()
+            .collect();
+        if let Some(b) = base {
+            packet.removed_entities = b
+                .entities
+                .iter()
+                .filter(|e| !entities.iter().any(|c| c.entity_id == e.entity_id))
+                .map(|e| e.entity_id.clone())
+                .collect();
+        }
+
+        let bytes = bincode::DefaultOptions::new()
+            .with_varint_encoding()
+            .serialize(&packet)?;
+        let datagrams = split_message(self.sequence, &bytes, &mut self.stats)?;
+        push_history(
+            &mut self.history,
+            SentSnapshot {
+                snapshot: Snapshot {
+                    sequence: self.sequence,
+                    started,
+                    players,
+                    entities,
+                },
+                is_acked: false,
+            },
+        );
+        Ok(datagrams)
+    }
+}
+
+/// 受け取ったパケットを基準のスナップショットに適用して完全なスナップショットに戻す。<br />
+/// Restores full snapshots by applying received packets to their base snapshots.
+#[derive(Default)]
+pub struct SnapshotDecoder {
+    latest_sequence: Option<u32>,
+    history: VecDeque<Snapshot>,
+    assembler: FragmentAssembler,
+    stats: TrafficStats,
+}
+
+impl SnapshotDecoder {
+    pub fn new() -> Self {
+        SnapshotDecoder {
+            latest_sequence: None,
+            history: VecDeque::with_capacity(SNAPSHOT_HISTORY_SIZE),
+            assembler: FragmentAssembler::new(),
+            stats: TrafficStats::default(),
+        }
+    }
+
+    pub fn get_stats(&self) -> TrafficStats {
+        self.stats
+    }
+
+    /// 相手へ送る確認応答。まだ何も受け取っていなければNone。<br />
+    /// Acknowledgement to send to the other side. None if nothing has been received yet.
+    pub fn get_ack(&self) -> Option<SnapshotAck> {
+        let sequence = self.latest_sequence?;
+        let bits =
+            self.history
+                .iter()
+                .fold(0_u32, |bits, h| match sequence.wrapping_sub(h.sequence) {
+                    distance @ 1..=32 => bits | 1 << (distance - 1),
+                    _ => bits,
+                });
+        Some(SnapshotAck { sequence, bits })
+    }
+
+    /// データグラムをデコードする。フラグメントが揃っていない場合や古いパケットはNoneを返す。<br />
+    /// 戻り値は完全なスナップショットと、相手からの確認応答。<br />
+    /// Decode a datagram. Returns None while fragments are missing, or for stale packets.<br />
+    /// Returns the full snapshot and the acknowledgement from the other side.
+    pub fn decode(
+        &mut self,
+        datagram: &[u8],
+    ) -> Result<Option<(Snapshot, Option<SnapshotAck>)>, PayloadError> {
+        let message = match self.assembler.receive(datagram, &mut self.stats)? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        let packet: SnapshotPacket = deserialize_compact(&message)?;
+        check_range("players", packet.players.len(), MAX_SNAPSHOT_PLAYERS)?;
+        check_range(
+            "removed_players",
+            packet.removed_players.len(),
+            MAX_SNAPSHOT_PLAYERS,
+        )?;
+        check_range("entities", packet.entities.len(), MAX_SNAPSHOT_ENTITIES)?;
+        check_range(
+            "removed_entities",
+            packet.removed_entities.len(),
+            MAX_SNAPSHOT_ENTITIES,
+        )?;
+        for delta in packet.players.iter() {
+            delta.validate()?;
+        }
+        for entity in packet.entities.iter() {
+            entity.validate()?;
+        }
+        if self
+            .latest_sequence
+            .map(|s| packet.sequence <= s)
+            .unwrap_or(false)
+        {
+            return Ok(None);
+        }
+
+        let base = match packet.base_sequence {
+            Some(sequence) => Some(
+                self.history
+                    .iter()
+                    .find(|h| h.sequence == sequence)
+                    .ok_or(PayloadError::MissingBase(sequence))?,
+            ),
+            None => None,
+        };
+
+        let mut players = base
+            .map(|b| {
+                b.players
+                    .iter()
+                    .filter(|p| !packet.removed_players.contains(&p.player_id))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        for delta in packet.players.iter() {
+            match players.iter_mut().find(|p| p.player_id == delta.player_id) {
+                Some(player) => {
+                    let updated = delta.apply(Some(&*player))?;
+                    *player = updated;
+                }
+                None => players.push(delta.apply(None)?),
+            }
+        }
+
+        check_range("players", players.len(), MAX_SNAPSHOT_PLAYERS)?;
+
+        let mut entities = base
+            .map(|b| {
+                b.entities
+                    .iter()
+                    .filter(|e| !packet.removed_entities.contains(&e.entity_id))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        for entity in packet.entities.into_iter() {
+            match entities
+                .iter_mut()
+                .find(|e| e.entity_id == entity.entity_id)
+            {
+                Some(existing) => *existing = entity,
+                None => entities.push(entity),
+            }
+        }
+        check_range("entities", entities.len(), MAX_SNAPSHOT_ENTITIES)?;
+
+        let snapshot = Snapshot {
+            sequence: packet.sequence,
+            started: packet.started,
+            players,
+            entities,
+        };
+        self.latest_sequence = Some(packet.sequence);
+        push_history(&mut self.history, snapshot.clone());
+        Ok(Some((snapshot, packet.ack)))
+    }
+}
@@ -0,0 +1,236 @@
+use crate::game::shared::structs::games::payload::{
+    check_range, deserialize_bounded, PayloadError, MAX_TERRAIN_SIZE, MAX_TERRAIN_VERTICES,
+};
+use crate::game::shared::structs::Primitive;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use glam::Vec3A;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// 地形のパッチの先頭に付けるマジックナンバー。<br />
+/// Magic number at the beginning of encoded terrain patches.
+const TERRAIN_PATCH_MAGIC: &[u8; 4] = b"DGTP";
+
+/// 地形のパッチの形式のバージョン。<br />
+/// Version of the terrain patch format.
+pub const TERRAIN_PATCH_VERSION: u32 = 1;
+
+/// これより小さい高さの違いは変更と見なさない。<br />
+/// Height differences smaller than this aren't treated as modifications.
+pub const HEIGHT_TOLERANCE: f32 = 1e-3;
+
+/// 連続した頂点の新しい高さ。<br />
+/// New heights of consecutive vertices.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HeightRun {
+    pub start: u32,
+    pub heights: Vec<f32>,
+}
+
+/// 手続き的に生成した地形のチャンクとの差分。変更された頂点の高さだけを持つ。<br />
+/// Difference from a procedurally generated terrain chunk, holding only the heights of modified vertices.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TerrainPatch {
+    pub grid_x: i32,
+    pub grid_z: i32,
+    /// 一辺の頂点数。<br />
+    /// Number of vertices per side.
+    pub vertex_count: u32,
+    pub runs: Vec<HeightRun>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TerrainPatchFile {
+    version: u32,
+    patches: Vec<TerrainPatch>,
+}
+
+impl TerrainPatch {
+    /// 基準の地形と変更された地形を比べ、パッチを作る。変更が無ければ`None`を返す。<br />
+    /// Compare the baseline and modified terrains and create a patch. Returns `None` if nothing changed.
+    pub fn diff(
+        grid_x: i32,
+        grid_z: i32,
+        baseline: &Primitive,
+        modified: &Primitive,
+    ) -> Result<Option<Self>, PayloadError> {
+        let vertex_count = get_grid_size(baseline)?;
+        if modified.vertices.len() != baseline.vertices.len() {
+            return Err(PayloadError::Malformed(format!(
+                "Modified terrain has {} vertices, but the baseline has {}.",
+                modified.vertices.len(),
+                baseline.vertices.len()
+            )));
+        }
+
+        let mut runs: Vec<HeightRun> = vec![];
+        for (i, (base, vertex)) in baseline
+            .vertices
+            .iter()
+            .zip(modified.vertices.iter())
+            .enumerate()
+        {
+            let height = vertex.position.y;
+            if !height.is_finite() {
+                return Err(PayloadError::NonFinite("position"));
+            }
+            if (height - base.position.y).abs() <= HEIGHT_TOLERANCE {
+                continue;
+            }
+            let index = i as u32;
+            match runs.last_mut() {
+                Some(run) if run.start + run.heights.len() as u32 == index => {
+                    run.heights.push(height)
+                }
+                _ => runs.push(HeightRun {
+                    start: index,
+                    heights: vec![height],
+                }),
+            }
+        }
+
+        if runs.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(TerrainPatch {
+                grid_x,
+                grid_z,
+                vertex_count: vertex_count as u32,
+                runs,
+            }))
+        }
+    }
+
+    pub fn get_modified_count(&self) -> usize {
+        self.runs.iter().map(|run| run.heights.len()).sum()
+    }
+
+    /// 基準の地形にパッチを当て、変更された頂点とその隣のノーマルを計算し直す。<br />
+    /// Apply the patch to a baseline terrain, recalculating the normals of modified vertices and their neighbours.
+    pub fn apply(&self, primitive: &mut Primitive) -> Result<(), PayloadError> {
+        let vertex_count = get_grid_size(primitive)?;
+        if vertex_count != self.vertex_count as usize {
+            return Err(PayloadError::Malformed(format!(
+                "Patch is for {} vertices per side, but the terrain has {}.",
+                self.vertex_count, vertex_count
+            )));
+        }
+        self.validate()?;
+
+        let mut touched = vec![false; primitive.vertices.len()];
+        for run in self.runs.iter() {
+            for (offset, height) in run.heights.iter().enumerate() {
+                let index = run.start as usize + offset;
+                primitive.vertices[index].position.y = *height;
+                let (x, z) = (index % vertex_count, index / vertex_count);
+                let neighbours = [
+                    (x, z),
+                    (x.wrapping_sub(1), z),
+                    (x + 1, z),
+                    (x, z.wrapping_sub(1)),
+                    (x, z + 1),
+                ];
+                for (nx, nz) in neighbours.iter() {
+                    if *nx < vertex_count && *nz < vertex_count {
+                        touched[nz * vertex_count + nx] = true;
+                    }
+                }
+            }
+        }
+
+        let heights = primitive
+            .vertices
+            .iter()
+            .map(|v| v.position.y)
+            .collect::<Vec<_>>();
+        let get_height = |x: usize, z: usize| {
+            heights[z.min(vertex_count - 1) * vertex_count + x.min(vertex_count - 1)]
+        };
+        for (index, _) in touched.iter().enumerate().filter(|(_, t)| **t) {
+            let (x, z) = (index % vertex_count, index / vertex_count);
+            let height_l = get_height(x.saturating_sub(1), z);
+            let height_r = get_height(x + 1, z);
+            let height_d = get_height(x, z.saturating_sub(1));
+            let height_u = get_height(x, z + 1);
+            primitive.vertices[index].normal =
+                Vec3A::new(height_l - height_r, 2.0, height_d - height_u).normalize();
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), PayloadError> {
+        let vertex_count = self.vertex_count as usize;
+        let total = vertex_count.saturating_mul(vertex_count);
+        check_range("vertex_count", total, MAX_TERRAIN_VERTICES)?;
+        check_range("heights", self.get_modified_count(), total)?;
+        for run in self.runs.iter() {
+            let end = run.start as usize + run.heights.len();
+            check_range("runs", end, total)?;
+            if run.heights.iter().any(|h| !h.is_finite()) {
+                return Err(PayloadError::NonFinite("heights"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// パッチをまとめてシリアライズし、圧縮する。セーブファイルや途中参加のプレイヤーへの送信に使う。<br />
+/// Serialize and compress a set of patches, for the save file or for sending to late joiners.
+pub fn encode_patches(patches: &[TerrainPatch]) -> anyhow::Result<Vec<u8>> {
+    let file = TerrainPatchFile {
+        version: TERRAIN_PATCH_VERSION,
+        patches: patches.to_vec(),
+    };
+    let serialized = bincode::serialize(&file)?;
+    let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+    encoder.write_all(&serialized)?;
+    let compressed = encoder.finish()?;
+    let mut bytes = Vec::with_capacity(TERRAIN_PATCH_MAGIC.len() + compressed.len());
+    bytes.extend_from_slice(TERRAIN_PATCH_MAGIC);
+    bytes.extend_from_slice(&compressed);
+    Ok(bytes)
+}
+
+/// 保存または受信したパッチを展開し、検証する。<br />
+/// Decompress saved or received patches and validate them.
+pub fn decode_patches(bytes: &[u8]) -> Result<Vec<TerrainPatch>, PayloadError> {
+    if !bytes.starts_with(TERRAIN_PATCH_MAGIC) {
+        return Err(PayloadError::InvalidHeader);
+    }
+    let mut serialized = vec![];
+    DeflateDecoder::new(&bytes[TERRAIN_PATCH_MAGIC.len()..])
+        .take(MAX_TERRAIN_SIZE as u64 + 1)
+        .read_to_end(&mut serialized)
+        .map_err(|e| PayloadError::Malformed(e.to_string()))?;
+    if serialized.len() > MAX_TERRAIN_SIZE {
+        return Err(PayloadError::TooLarge {
+            size: serialized.len(),
+            limit: MAX_TERRAIN_SIZE,
+        });
+    }
+
+    let file = deserialize_bounded::<TerrainPatchFile>(&serialized)?;
+    if file.version != TERRAIN_PATCH_VERSION {
+        return Err(PayloadError::VersionMismatch {
+            expected: TERRAIN_PATCH_VERSION,
+            actual: file.version,
+        });
+    }
+    for patch in file.patches.iter() {
+        patch.validate()?;
+    }
+    Ok(file.patches)
+}
+
+fn get_grid_size(primitive: &Primitive) -> Result<usize, PayloadError> {
+    let vertex_count = (primitive.vertices.len() as f64).sqrt() as usize;
+    if vertex_count < 2 || vertex_count * vertex_count != primitive.vertices.len() {
+        return Err(PayloadError::Malformed(format!(
+            "{} vertices don't form a square grid.",
+            primitive.vertices.len()
+        )));
+    }
+    Ok(vertex_count)
+}
@@ -0,0 +1,262 @@
+use crate::game::shared::structs::games::payload::{
+    check_range, deserialize_bounded, parse_terrain, PayloadError, MAX_TERRAIN_SIZE,
+    MAX_TERRAIN_VERTICES,
+};
+use crate::game::shared::structs::{Primitive, Vertex};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use glam::{Vec2, Vec3A};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// 地形のペイロードの先頭に付けるマジックナンバー。<br />
+/// Magic number at the beginning of a terrain payload.
+const TERRAIN_PAYLOAD_MAGIC: &[u8; 4] = b"DGTR";
+
+/// 地形のペイロードの形式のバージョン。形式を変えたら上げる。<br />
+/// Version of the terrain payload format. Bump it whenever the format changes.
+pub const TERRAIN_PAYLOAD_VERSION: u32 = 1;
+
+/// 高さを量子化する段階の数。<br />
+/// Number of steps heights are quantized into.
+const HEIGHT_LEVELS: f32 = u16::MAX as f32;
+
+/// 格子状の地形を、頂点ではなく量子化した高さで表すペイロード。<br />
+/// 位置のX・Z、UV、インデックス、ノーマルは全て受け取った側で高さから組み立て直す。<br />
+/// Payload representing a grid terrain with quantized heights instead of full vertices.<br />
+/// X and Z positions, UVs, indices and normals are all reconstructed from the heights by the receiver.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TerrainPayload {
+    pub version: u32,
+    /// 一辺の頂点数。<br />
+    /// Number of vertices per side.
+    pub vertex_count: u32,
+    pub size_x: f32,
+    pub size_z: f32,
+    pub min_height: f32,
+    pub height_step: f32,
+    /// 行ごとに並べた量子化した高さ。<br />
+    /// Quantized heights, row by row.
+    pub heights: Vec<u16>,
+    pub checksum: u32,
+}
+
+impl TerrainPayload {
+    /// 格子状の地形からペイロードを作る。<br />
+    /// Create a payload from a grid terrain.
+    pub fn from_primitive(primitive: &Primitive) -> Result<Self, PayloadError> {
+        let vertex_count = (primitive.vertices.len() as f64).sqrt() as usize;
+        if vertex_count < 2 || vertex_count * vertex_count != primitive.vertices.len() {
+            return Err(PayloadError::Malformed(format!(
+                "{} vertices don't form a square grid.",
+                primitive.vertices.len()
+            )));
+        }
+        if primitive.indices != generate_grid_indices(vertex_count as u32) {
+            return Err(PayloadError::Malformed(
+                "Indices don't match a grid terrain.".to_string(),
+            ));
+        }
+
+        let heights = primitive
+            .vertices
+            .iter()
+            .map(|v| v.position.y)
+            .collect::<Vec<_>>();
+        if heights.iter().any(|h| !h.is_finite()) {
+            return Err(PayloadError::NonFinite("position"));
+        }
+        let min_height = heights.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max_height = heights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let height_step = (max_height - min_height) / HEIGHT_LEVELS;
+        let heights = heights
+            .iter()
+            .map(|h| {
+                if height_step > 0.0 {
+                    ((h - min_height) / height_step).round() as u16
+                } else {
+                    0
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut payload = TerrainPayload {
+            version: TERRAIN_PAYLOAD_VERSION,
+            vertex_count: vertex_count as u32,
+            size_x: primitive.vertices[vertex_count - 1].position.x,
+            size_z: primitive.vertices[vertex_count * (vertex_count - 1)]
+                .position
+                .z,
+            min_height,
+            height_step,
+            heights,
+            checksum: 0,
+        };
+        payload.checksum = payload.compute_checksum();
+        Ok(payload)
+    }
+
+    /// 送信するためにシリアライズして圧縮する。<br />
+    /// Serialize and compress the payload for sending.
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let serialized = bincode::serialize(self)?;
+        let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+        encoder.write_all(&serialized)?;
+        let compressed = encoder.finish()?;
+        let mut bytes = Vec::with_capacity(TERRAIN_PAYLOAD_MAGIC.len() + compressed.len());
+        bytes.extend_from_slice(TERRAIN_PAYLOAD_MAGIC);
+        bytes.extend_from_slice(&compressed);
+        Ok(bytes)
+    }
+
+    /// 受け取ったデータを展開し、バージョンとチェックサムを検証する。<br />
+    /// Decompress received data, validating its version and checksum.
+    pub fn decode(bytes: &[u8]) -> Result<Self, PayloadError> {
+        if bytes.len() > MAX_TERRAIN_SIZE {
+            return Err(PayloadError::TooLarge {
+                size: bytes.len(),
+                limit: MAX_TERRAIN_SIZE,
+            });
+        }
+        if !bytes.starts_with(TERRAIN_PAYLOAD_MAGIC) {
+            return Err(PayloadError::InvalidHeader);
+        }
+        let mut serialized = vec![];
+        DeflateDecoder::new(&bytes[TERRAIN_PAYLOAD_MAGIC.len()..])
+            .take(MAX_TERRAIN_SIZE as u64 + 1)
+            .read_to_end(&mut serialized)
+            .map_err(|e| PayloadError::Malformed(e.to_string()))?;
+        if serialized.len() > MAX_TERRAIN_SIZE {
+            return Err(PayloadError::TooLarge {
+                size: serialized.len(),
+                limit: MAX_TERRAIN_SIZE,
+            });
+        }
+
+        let payload = deserialize_bounded::<TerrainPayload>(&serialized)?;
+        if payload.version != TERRAIN_PAYLOAD_VERSION {
+            return Err(PayloadError::VersionMismatch {
+                expected: TERRAIN_PAYLOAD_VERSION,
+                actual: payload.version,
+            });
+        }
+        let vertex_count = payload.vertex_count as usize;
+        check_range(
+            "vertex_count",
+            vertex_count.saturating_mul(vertex_count),
+            MAX_TERRAIN_VERTICES,
+        )?;
+        if vertex_count < 2 || payload.heights.len() != vertex_count * vertex_count {
+            return Err(PayloadError::Malformed(format!(
+                "Expected {} heights for {} vertices per side, got {}.",
+                vertex_count * vertex_count,
+                vertex_count,
+                payload.heights.len()
+            )));
+        }
+        let fields = [
+            ("size_x", payload.size_x),
+            ("size_z", payload.size_z),
+            ("min_height", payload.min_height),
+            ("height_step", payload.height_step),
+        ];
+        if let Some((field, _)) = fields.iter().find(|(_, v)| !v.is_finite()) {
+            return Err(PayloadError::NonFinite(*field));
+        }
+        if payload.checksum != payload.compute_checksum() {
+            return Err(PayloadError::Malformed(
+                "Terrain checksum mismatch.".to_string(),
+            ));
+        }
+        Ok(payload)
+    }
+
+    /// 高さから地形の頂点とインデックスを組み立て直す。<br />
+    /// Reconstruct the vertices and indices of the terrain from the heights.
+    pub fn to_primitive(&self) -> Primitive {
+        let vertex_count = self.vertex_count as usize;
+        let last = (vertex_count - 1) as f32;
+        let get_height = |x: usize, z: usize| {
+            let x = x.min(vertex_count - 1);
+            let z = z.min(vertex_count - 1);
+            self.min_height + self.heights[z * vertex_count + x] as f32 * self.height_step
+        };
+
+        let mut vertices = Vec::with_capacity(vertex_count * vertex_count);
+        for i in 0..vertex_count {
+            for j in 0..vertex_count {
+                // 端の頂点は隣の高さが無いので、端の高さで代用する。
+                // Vertices on the edge have no neighbour, so the edge height stands in for it.
+                let height_l = get_height(j.saturating_sub(1), i);
+                let height_r = get_height(j + 1, i);
+                let height_d = get_height(j, i.saturating_sub(1));
+                let height_u = get_height(j, i + 1);
+                vertices.push(Vertex {
+                    position: Vec3A::new(
+                        (j as f32 / last) * self.size_x,
+                        get_height(j, i),
+                        (i as f32 / last) * self.size_z,
+                    ),
+                    normal: Vec3A::new(height_l - height_r, 2.0, height_d - height_u).normalize(),
+                    uv: Vec2::new(j as f32 / last, i as f32 / last),
+                });
+            }
+        }
+
+        Primitive {
+            vertices,
+            indices: generate_grid_indices(self.vertex_count),
+            texture_index: None,
+            is_disposed: false,
+        }
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        let mut crc = Crc::new();
+        crc.update(&self.version.to_le_bytes());
+        crc.update(&self.vertex_count.to_le_bytes());
+        for value in [self.size_x, self.size_z, self.min_height, self.height_step].iter() {
+            crc.update(&value.to_le_bytes());
+        }
+        for height in self.heights.iter() {
+            crc.update(&height.to_le_bytes());
+        }
+        crc.sum()
+    }
+}
+
+/// 一辺に`vertex_count`個の頂点を持つ格子のインデックスを生成する。<br />
+/// Generate indices of a grid with `vertex_count` vertices per side.
+pub fn generate_grid_indices(vertex_count: u32) -> Vec<u32> {
+    let cell_count = vertex_count.saturating_sub(1);
+    let mut indices = Vec::with_capacity((6 * cell_count * cell_count) as usize);
+    for gz in 0..cell_count {
+        for gx in 0..cell_count {
+            let top_left = (gz * vertex_count) + gx;
+            let top_right = top_left + 1;
+            let bottom_left = ((gz + 1) * vertex_count) + gx;
+            let bottom_right = bottom_left + 1;
+            indices.extend_from_slice(&[
+                top_left,
+                bottom_left,
+                top_right,
+                top_right,
+                bottom_left,
+                bottom_right,
+            ]);
+        }
+    }
+    indices
+}
+
+/// サーバーから受け取った地形のデータを解析する。<br />
+/// 古いクライアントが送ったJSONの頂点データも受け付ける。<br />
+/// Parse terrain data received from the server.<br />
+/// JSON vertex data sent by older clients is accepted as well.
+pub fn parse_terrain_payload(bytes: &[u8]) -> Result<Primitive, PayloadError> {
+    if bytes.first() == Some(&b'{') {
+        return parse_terrain(bytes);
+    }
+    TerrainPayload::decode(bytes).map(|payload| payload.to_primitive())
+}
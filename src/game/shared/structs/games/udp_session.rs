@@ -0,0 +1,682 @@
+use crate::game::shared::structs::games::payload::{
+    check_range, deserialize_compact, PayloadError, MAX_PLAYER_ID_LENGTH,
+};
+use crate::game::shared::structs::games::protocol::{decode_packet, encode_packet};
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// データのパケットに付けるセッションのヘッダーの長さ。種類、セッションID、パケットの番号。<br />
+/// Length of the session header of data packets: kind, session ID and packet sequence.
+pub const SESSION_HEADER_SIZE: usize = 9;
+
+/// ハンドシェイクのパケットを送り直す間隔。<br />
+/// Interval between resends of handshake packets.
+pub const HANDSHAKE_RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 諦めるまでにハンドシェイクのパケットを送る回数。<br />
+/// Number of handshake packets sent before giving up.
+pub const MAX_HANDSHAKE_ATTEMPTS: u32 = 20;
+
+/// キープアライブのピングの間隔。NATの対応付けが消えないよう、一般的なタイムアウトより十分短くする。<br />
+/// Interval of keep-alive pings. Kept well below common NAT timeouts so the mapping stays open.
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 何も受け取らないまま、この時間が過ぎたらセッションが切れたとみなす。<br />
+/// A session is considered lost once nothing has been received for this long.
+pub const SESSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MAX_TOKEN_LENGTH: usize = 4096;
+const MAX_REASON_LENGTH: usize = 256;
+
+/// 返事を待つピングの最大の数。<br />
+/// Maximum number of pings awaiting a reply.
+const MAX_PENDING_PINGS: usize = 8;
+
+/// 重複を見分けるために覚えておく、最新より前のパケットの数。<br />
+/// Number of packets before the newest one remembered to detect duplicates.
+const REPLAY_WINDOW_SIZE: u32 = 64;
+
+const KIND_DATA: u8 = 0;
+const KIND_CONTROL: u8 = 1;
+
+/// セッションを確立し、維持するための制御メッセージ。<br />
+/// Control messages that establish and maintain a session.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ControlMessage {
+    /// クライアントが最初に送る。NATに外向きの対応付けを作るのもこのパケット。<br />
+    /// Sent first by the client. This is also the packet that opens the outbound NAT mapping.
+    Hello {
+        token: String,
+        player_id: String,
+        client_nonce: u64,
+    },
+    /// サーバーからの問いかけ。クッキーは送り元のアドレスに結び付いているので、偽のアドレスからはセッションを作れない。<br />
+    /// Challenge from the server. The cookie is bound to the source address, so sessions can't be opened from spoofed addresses.
+    Challenge {
+        client_nonce: u64,
+        cookie: u64,
+    },
+    ChallengeResponse {
+        client_nonce: u64,
+        player_id: String,
+        cookie: u64,
+    },
+    Accepted {
+        session_id: u32,
+    },
+    Rejected {
+        reason: String,
+    },
+    Ping {
+        session_id: u32,
+        id: u32,
+    },
+    Pong {
+        session_id: u32,
+        id: u32,
+    },
+}
+
+impl ControlMessage {
+    fn validate(&self) -> Result<(), PayloadError> {
+        match self {
+            ControlMessage::Hello {
+                token, player_id, ..
+            } => {
+                check_range("token", token.len(), MAX_TOKEN_LENGTH)?;
+                check_range("player_id", player_id.len(), MAX_PLAYER_ID_LENGTH)
+            }
+            ControlMessage::ChallengeResponse { player_id, .. } => {
+                check_range("player_id", player_id.len(), MAX_PLAYER_ID_LENGTH)
+            }
+            ControlMessage::Rejected { reason } => {
+                check_range("reason", reason.len(), MAX_REASON_LENGTH)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// セッションのヘッダーを外したパケット。<br />
+/// A packet with its session header removed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionPacket {
+    Control(ControlMessage),
+    /// スナップショットのデータグラム。フラグメントの組み立てにそのまま渡せるよう、パケットのヘッダーを付け直してある。<br />
+    /// A snapshot datagram. The packet header is put back so it can be passed straight to fragment reassembly.
+    Data {
+        session_id: u32,
+        sequence: u32,
+        datagram: Vec<u8>,
+    },
+}
+
+pub fn encode_control(message: &ControlMessage) -> Vec<u8> {
+    let mut payload = vec![KIND_CONTROL];
+    payload.extend(
+        bincode::DefaultOptions::new()
+            .with_varint_encoding()
+            .serialize(message)
+            .expect("Failed to serialize control message."),
+    );
+    encode_packet(&payload)
+}
+
+/// スナップショットのデータグラムにセッションのヘッダーを挟む。パケットのヘッダーは一つにまとめる。<br />
+/// Insert the session header into a snapshot datagram. Only one packet header is kept.
+pub fn encode_data(
+    session_id: u32,
+    sequence: u32,
+    datagram: &[u8],
+) -> Result<Vec<u8>, PayloadError> {
+    let fragment = decode_packet(datagram)?;
+    let mut payload = Vec::with_capacity(SESSION_HEADER_SIZE + fragment.len());
+    payload.push(KIND_DATA);
+    payload.extend_from_slice(&session_id.to_le_bytes());
+    payload.extend_from_slice(&sequence.to_le_bytes());
+    payload.extend_from_slice(fragment);
+    Ok(encode_packet(&payload))
+}
+
+pub fn decode_session_packet(packet: &[u8]) -> Result<SessionPacket, PayloadError> {
+    let payload = decode_packet(packet)?;
+    match payload.first() {
+        Some(&KIND_DATA) if payload.len() >= SESSION_HEADER_SIZE => {
+            let session_id = u32::from_le_bytes(
+                payload[1..5]
+                    .try_into()
+                    .expect("Failed to read session ID."),
+            );
+            let sequence = u32::from_le_bytes(
+                payload[5..SESSION_HEADER_SIZE]
+                    .try_into()
+                    .expect("Failed to read packet sequence."),
+            );
+            Ok(SessionPacket::Data {
+                session_id,
+                sequence,
+                datagram: encode_packet(&payload[SESSION_HEADER_SIZE..]),
+            })
+        }
+        Some(&KIND_CONTROL) => {
+            let message: ControlMessage = deserialize_compact(&payload[1..])?;
+            message.validate()?;
+            Ok(SessionPacket::Control(message))
+        }
+        _ => Err(PayloadError::InvalidHeader),
+    }
+}
+
+/// 最新のパケットに対して、受け取ったパケットが届いた順番。<br />
+/// Where a received packet arrived relative to the newest one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PacketOrder {
+    InOrder,
+    /// 最新より前の番号だが、まだ受け取っていなかった。<br />
+    /// Older than the newest packet, but not received before.
+    Reordered,
+    Duplicate,
+    /// 古すぎて重複かどうか分からないので捨てる。<br />
+    /// Too old to tell whether it's a duplicate, so it's dropped.
+    TooOld,
+}
+
+/// 受け取ったパケットの番号を覚え、重複と古すぎるパケットを見分ける。<br />
+/// Remembers the sequences of received packets to detect duplicates and packets that are too old.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ReplayWindow {
+    latest: Option<u32>,
+    /// `n`番目のビットは`latest - n - 1`のパケットを受け取ったかどうか。<br />
+    /// Bit `n` tells whether the packet `latest - n - 1` was received.
+    bits: u64,
+}
+
+impl ReplayWindow {
+    /// パケットの番号を記録し、届いた順番を返す。重複と古すぎるパケットは記録しない。<br />
+    /// Record the sequence of a packet and return where it arrived. Duplicates and packets that are too old aren't recorded.
+    pub fn accept(&mut self, sequence: u32) -> PacketOrder {
+        let latest = match self.latest {
+            Some(latest) => latest,
+            None => {
+                self.latest = Some(sequence);
+                return PacketOrder::InOrder;
+            }
+        };
+        let ahead = sequence.wrapping_sub(latest);
+        if ahead != 0 && ahead < u32::MAX / 2 {
+            self.bits = if ahead > REPLAY_WINDOW_SIZE {
+                0
+            } else {
+                // 今の最新も、ずらした後のビットに入れる。
+                // The current newest packet also goes into the shifted bits.
+                ((self.bits << 1) | 1) << (ahead - 1)
+            };
+            self.latest = Some(sequence);
+            return PacketOrder::InOrder;
+        }
+        let behind = latest.wrapping_sub(sequence);
+        if behind == 0 {
+            return PacketOrder::Duplicate;
+        }
+        if behind > REPLAY_WINDOW_SIZE {
+            return PacketOrder::TooOld;
+        }
+        let bit = 1 << (behind - 1);
+        if self.bits & bit != 0 {
+            PacketOrder::Duplicate
+        } else {
+            self.bits |= bit;
+            PacketOrder::Reordered
+        }
+    }
+}
+
+/// セッションを通ったパケットの統計。<br />
+/// Statistics of packets that went through a session.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SessionStats {
+    pub duplicate_packets: u64,
+    pub reordered_packets: u64,
+    pub stale_packets: u64,
+}
+
+impl SessionStats {
+    fn record(&mut self, order: PacketOrder) {
+        match order {
+            PacketOrder::InOrder => {}
+            PacketOrder::Reordered => self.reordered_packets += 1,
+            PacketOrder::Duplicate => self.duplicate_packets += 1,
+            PacketOrder::TooOld => self.stale_packets += 1,
+        }
+    }
+}
+
+/// 受け取ったパケットを処理した結果。<br />
+/// Result of handling a received packet.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Received {
+    /// 制御メッセージを処理したか、重複などで捨てた。<br />
+    /// A control message was handled, or the packet was dropped as a duplicate or similar.
+    Nothing,
+    /// すぐに送り返すパケット。<br />
+    /// A packet to send back right away.
+    Reply(Vec<u8>),
+    /// スナップショットのデコーダーに渡すデータグラム。<br />
+    /// A datagram to pass to the snapshot decoder.
+    Datagram(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionState {
+    Connecting,
+    Established {
+        session_id: u32,
+    },
+    /// ハンドシェイクに失敗したか、サーバーから応答がなくなった。<br />
+    /// The handshake failed, or the server stopped answering.
+    Failed(String),
+}
+
+/// クライアント側のUDPセッション。<br />
+/// JWTトークンを添えたハローから始め、サーバーの問いかけに答えてセッションIDをもらう。<br />
+/// 確立した後はピングで往復時間を測りながらNATの対応付けを保ち、データのパケットの重複を捨てる。<br />
+/// Client side of a UDP session.<br />
+/// Starts with a hello carrying the JWT token, and gets a session ID by answering the server's challenge.<br />
+/// Once established, pings keep the NAT mapping open while measuring the round-trip time, and duplicate data packets are dropped.
+pub struct ClientSession {
+    token: String,
+    player_id: String,
+    client_nonce: u64,
+    state: SessionState,
+    cookie: Option<u64>,
+    handshake_attempts: u32,
+    next_send_at: Instant,
+    last_received_at: Instant,
+    send_sequence: u32,
+    replay_window: ReplayWindow,
+    next_ping_id: u32,
+    pending_pings: VecDeque<(u32, Instant)>,
+    round_trip_time: Option<Duration>,
+    stats: SessionStats,
+}
+
+impl ClientSession {
+    pub fn new(token: &str, player_id: &str, client_nonce: u64, now: Instant) -> Self {
+        ClientSession {
+            token: token.to_string(),
+            player_id: player_id.to_string(),
+            client_nonce,
+            state: SessionState::Connecting,
+            cookie: None,
+            handshake_attempts: 0,
+            next_send_at: now,
+            last_received_at: now,
+            send_sequence: 0,
+            replay_window: ReplayWindow::default(),
+            next_ping_id: 0,
+            pending_pings: VecDeque::with_capacity(MAX_PENDING_PINGS),
+            round_trip_time: None,
+            stats: SessionStats::default(),
+        }
+    }
+
+    pub fn get_state(&self) -> &SessionState {
+        &self.state
+    }
+
+    pub fn is_established(&self) -> bool {
+        matches!(self.state, SessionState::Established { .. })
+    }
+
+    /// 平滑化した往復時間。まだ測っていなければNone。<br />
+    /// Smoothed round-trip time. None until it has been measured.
+    pub fn get_round_trip_time(&self) -> Option<Duration> {
+        self.round_trip_time
+    }
+
+    pub fn get_stats(&self) -> SessionStats {
+        self.stats
+    }
+
+    /// 今送るべき制御パケットを返す。ハンドシェイクの再送とキープアライブのピング、タイムアウトの判定をする。毎回の送信の前に呼び出す。<br />
+    /// Return the control packet to send now, handling handshake resends, keep-alive pings and timeouts. Called before every send.
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if now.saturating_duration_since(self.last_received_at) > SESSION_TIMEOUT
+            && self.is_established()
+        {
+            self.state = SessionState::Failed("The server stopped answering.".to_string());
+        }
+        if now < self.next_send_at {
+            return None;
+        }
+        let message = match self.state {
+            SessionState::Connecting => {
+                if self.handshake_attempts >= MAX_HANDSHAKE_ATTEMPTS {
+                    self.state = SessionState::Failed(
+                        "The server didn't answer the session handshake.".to_string(),
+                    );
+                    return None;
+                }
+                self.handshake_attempts += 1;
+                self.next_send_at = now + HANDSHAKE_RETRY_INTERVAL;
+                match self.cookie {
+                    Some(cookie) => ControlMessage::ChallengeResponse {
+                        client_nonce: self.client_nonce,
+                        player_id: self.player_id.clone(),
+                        cookie,
+                    },
+                    None => ControlMessage::Hello {
+                        token: self.token.clone(),
+                        player_id: self.player_id.clone(),
+                        client_nonce: self.client_nonce,
+                    },
+                }
+            }
+            SessionState::Established { session_id } => {
+                self.next_send_at = now + KEEP_ALIVE_INTERVAL;
+                let id = self.next_ping_id;
+                self.next_ping_id = self.next_ping_id.wrapping_add(1);
+                if self.pending_pings.len() == MAX_PENDING_PINGS {
+                    self.pending_pings.pop_front();
+                }
+                self.pending_pings.push_back((id, now));
+                ControlMessage::Ping { session_id, id }
+            }
+            SessionState::Failed(_) => return None,
+        };
+        Some(encode_control(&message))
+    }
+
+    /// スナップショットのデータグラムをセッションのパケットに包む。<br />
+    /// Wrap a snapshot datagram into a session packet.
+    pub fn wrap(&mut self, datagram: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let session_id = match self.state {
+            SessionState::Established { session_id } => session_id,
+            _ => anyhow::bail!("The UDP session hasn't been established."),
+        };
+        self.send_sequence = self.send_sequence.wrapping_add(1);
+        Ok(encode_data(session_id, self.send_sequence, datagram)?)
+    }
+
+    pub fn receive(&mut self, packet: &[u8], now: Instant) -> Result<Received, PayloadError> {
+        let packet = decode_session_packet(packet)?;
+        let session_id = match self.state {
+            SessionState::Established { session_id } => Some(session_id),
+            _ => None,
+        };
+        let received = match packet {
+            SessionPacket::Data {
+                session_id: id,
+                sequence,
+                datagram,
+            } => {
+                if session_id != Some(id) {
+                    return Err(PayloadError::UnknownSession(id));
+                }
+                let order = self.replay_window.accept(sequence);
+                self.stats.record(order);
+                match order {
+                    PacketOrder::InOrder | PacketOrder::Reordered => Received::Datagram(datagram),
+                    PacketOrder::Duplicate | PacketOrder::TooOld => Received::Nothing,
+                }
+            }
+            SessionPacket::Control(message) => match message {
+                ControlMessage::Challenge {
+                    client_nonce,
+                    cookie,
+                } if client_nonce == self.client_nonce && session_id.is_none() => {
+                    // サーバーが応答したので、答えを送る回数を数え直す。
+                    // The server answered, so the attempts to send the response are counted anew.
+                    self.cookie = Some(cookie);
+                    self.handshake_attempts = 0;
+                    self.next_send_at = now;
+                    match self.poll(now) {
+                        Some(reply) => Received::Reply(reply),
+                        None => Received::Nothing,
+                    }
+                }
+                ControlMessage::Accepted { session_id: id }
+                    if session_id.is_none() && self.cookie.is_some() =>
+                {
+                    self.state = SessionState::Established { session_id: id };
+                    // すぐにピングを送り、往復時間を測り始める。
+                    // Send a ping right away to start measuring the round-trip time.
+                    self.next_send_at = now;
+                    Received::Nothing
+                }
+                ControlMessage::Rejected { reason } => {
+                    if session_id.is_some() {
+                        // NATの対応付けが変わると、サーバーはこちらのアドレスを知らない。ハンドシェイクからやり直す。
+                        // The server doesn't know our address once the NAT mapping changes, so start over from the handshake.
+                        self.restart(now);
+                    } else {
+                        self.state = SessionState::Failed(reason);
+                    }
+                    Received::Nothing
+                }
+                ControlMessage::Ping {
+                    session_id: id,
+                    id: ping_id,
+                } if session_id == Some(id) => {
+                    Received::Reply(encode_control(&ControlMessage::Pong {
+                        session_id: id,
+                        id: ping_id,
+                    }))
+                }
+                ControlMessage::Pong {
+                    session_id: id,
+                    id: ping_id,
+                } if session_id == Some(id) => {
+                    if let Some(position) =
+                        self.pending_pings.iter().position(|(p, _)| *p == ping_id)
+                    {
+                        let (_, sent_at) = self.pending_pings[position];
+                        self.pending_pings.drain(..=position);
+                        self.record_round_trip(now.saturating_duration_since(sent_at));
+                    }
+                    Received::Nothing
+                }
+                // 古いハンドシェイクへの返事など、今の状態に関係のないメッセージ。
+                // Messages that don't apply to the current state, such as answers to an earlier handshake.
+                _ => Received::Nothing,
+            },
+        };
+        self.last_received_at = now;
+        Ok(received)
+    }
+
+    fn restart(&mut self, now: Instant) {
+        self.state = SessionState::Connecting;
+        self.cookie = None;
+        self.handshake_attempts = 0;
+        self.next_send_at = now;
+        self.replay_window = ReplayWindow::default();
+        self.pending_pings.clear();
+    }
+
+    /// TCPと同じく、新しい測定を1/8の重みで平滑化する。<br />
+    /// Smooth new samples with a weight of 1/8, the same as TCP does.
+    fn record_round_trip(&mut self, sample: Duration) {
+        self.round_trip_time = Some(match self.round_trip_time {
+            Some(rtt) => (rtt * 7 + sample) / 8,
+            None => sample,
+        });
+    }
+}
+
+/// サーバー側のセッション。<br />
+/// A session on the server side.
+#[derive(Clone, Debug)]
+pub struct ServerSession {
+    pub session_id: u32,
+    /// セッションを開いたプレイヤー。このプレイヤーの状態しか更新させない。<br />
+    /// The player who opened the session. Only this player's state may be updated through it.
+    pub player_id: String,
+    send_sequence: u32,
+    replay_window: ReplayWindow,
+    last_received_at: Instant,
+}
+
+/// サーバーが送り元のアドレスごとに持つセッションの一覧。<br />
+/// 問いかけのクッキーは秘密の値とアドレスから作るので、セッションが確立するまでは何も覚えない。<br />
+/// Sessions held by the server, keyed by source address.<br />
+/// Challenge cookies are derived from a secret and the address, so nothing is stored until a session is established.
+pub struct SessionTable {
+    secret: u64,
+    sessions: HashMap<SocketAddr, ServerSession>,
+    stats: SessionStats,
+}
+
+impl SessionTable {
+    pub fn new(secret: u64) -> Self {
+        SessionTable {
+            secret,
+            sessions: HashMap::new(),
+            stats: SessionStats::default(),
+        }
+    }
+
+    pub fn get(&self, address: &SocketAddr) -> Option<&ServerSession> {
+        self.sessions.get(address)
+    }
+
+    pub fn get_stats(&self) -> SessionStats {
+        self.stats
+    }
+
+    /// 受け取ったパケットを処理する。`is_token_valid`でハローのJWTトークンがそのプレイヤーのものか確かめる。<br />
+    /// Handle a received packet. `is_token_valid` checks that the JWT token of a hello belongs to its player.
+    pub fn receive<F>(
+        &mut self,
+        address: SocketAddr,
+        packet: &[u8],
+        now: Instant,
+        is_token_valid: F,
+    ) -> Result<Received, PayloadError>
+    where
+        F: FnOnce(&str, &str) -> bool,
+    {
+        let packet = decode_session_packet(packet)?;
+        let reject = |reason: &str| {
+            Received::Reply(encode_control(&ControlMessage::Rejected {
+                reason: reason.to_string(),
+            }))
+        };
+        let message = match packet {
+            SessionPacket::Data {
+                session_id,
+                sequence,
+                datagram,
+            } => {
+                let session = match self.sessions.get_mut(&address) {
+                    Some(session) if session.session_id == session_id => session,
+                    _ => return Ok(reject("Unknown session.")),
+                };
+                session.last_received_at = now;
+                let order = session.replay_window.accept(sequence);
+                self.stats.record(order);
+                return Ok(match order {
+                    PacketOrder::InOrder | PacketOrder::Reordered => Received::Datagram(datagram),
+                    PacketOrder::Duplicate | PacketOrder::TooOld => Received::Nothing,
+                });
+            }
+            SessionPacket::Control(message) => message,
+        };
+        let reply = match message {
+            ControlMessage::Hello {
+                token,
+                player_id,
+                client_nonce,
+            } => {
+                if !is_token_valid(&token, &player_id) {
+                    return Ok(reject("The JWT token was rejected."));
+                }
+                ControlMessage::Challenge {
+                    client_nonce,
+                    cookie: self.get_cookie(address, client_nonce, &player_id),
+                }
+            }
+            ControlMessage::ChallengeResponse {
+                client_nonce,
+                player_id,
+                cookie,
+            } => {
+                if cookie != self.get_cookie(address, client_nonce, &player_id) {
+                    return Ok(reject("The challenge cookie doesn't match."));
+                }
+                // 返事が失われて送り直された場合は、同じセッションをもう一度伝える。
+                // If the answer was lost and the response resent, the same session is told again.
+                let session = self
+                    .sessions
+                    .entry(address)
+                    .or_insert_with(|| ServerSession {
+                        session_id: rand::random(),
+                        player_id: player_id.clone(),
+                        send_sequence: 0,
+                        replay_window: ReplayWindow::default(),
+                        last_received_at: now,
+                    });
+                if session.player_id != player_id {
+                    *session = ServerSession {
+                        session_id: rand::random(),
+                        player_id,
+                        send_sequence: 0,
+                        replay_window: ReplayWindow::default(),
+                        last_received_at: now,
+                    };
+                }
+                session.last_received_at = now;
+                ControlMessage::Accepted {
+                    session_id: session.session_id,
+                }
+            }
+            ControlMessage::Ping { session_id, id } => match self.sessions.get_mut(&address) {
+                Some(session) if session.session_id == session_id => {
+                    session.last_received_at = now;
+                    ControlMessage::Pong { session_id, id }
+                }
+                _ => return Ok(reject("Unknown session.")),
+            },
+            _ => return Ok(Received::Nothing),
+        };
+        Ok(Received::Reply(encode_control(&reply)))
+    }
+
+    /// アドレスのセッションでスナップショットのデータグラムを包む。セッションがなければNone。<br />
+    /// Wrap a snapshot datagram with the session of an address. None if there is no session.
+    pub fn wrap(&mut self, address: &SocketAddr, datagram: &[u8]) -> Option<Vec<u8>> {
+        let session = self.sessions.get_mut(address)?;
+        session.send_sequence = session.send_sequence.wrapping_add(1);
+        encode_data(session.session_id, session.send_sequence, datagram).ok()
+    }
+
+    /// しばらく何も送ってこないセッションを閉じ、閉じたセッションを返す。<br />
+    /// Close sessions that haven't sent anything for a while, returning the closed ones.
+    pub fn remove_expired(&mut self, now: Instant) -> Vec<(SocketAddr, ServerSession)> {
+        let expired = self
+            .sessions
+            .iter()
+            .filter(|(_, s)| now.saturating_duration_since(s.last_received_at) > SESSION_TIMEOUT)
+            .map(|(address, _)| *address)
+            .collect::<Vec<_>>();
+        expired
+            .into_iter()
+            .filter_map(|address| self.sessions.remove(&address).map(|s| (address, s)))
+            .collect()
+    }
+
+    fn get_cookie(&self, address: SocketAddr, client_nonce: u64, player_id: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.secret.hash(&mut hasher);
+        address.hash(&mut hasher);
+        client_nonce.hash(&mut hasher);
+        player_id.hash(&mut hasher);
+        hasher.finish()
+    }
+}
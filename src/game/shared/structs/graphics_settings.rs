@@ -0,0 +1,228 @@
+use crate::game::shared::enums::{PresentMode, WindowMode};
+use crate::game::shared::structs::Settings;
+use crate::game::ui::{TweakRegistry, TweakValue};
+use std::time::Duration;
+
+const MSAA_TWEAK: &str = "Graphics/MSAA";
+const PRESENT_MODE_TWEAK: &str = "Graphics/Present Mode";
+const MAX_FPS_TWEAK: &str = "Graphics/Max FPS";
+const WINDOW_MODE_TWEAK: &str = "Graphics/Window Mode";
+const RENDER_SCALE_TWEAK: &str = "Graphics/Render Scale";
+const UNLIMITED_FPS: &str = "Unlimited";
+
+/// 設定で選べるMSAAのサンプル数。1はMSAAを使わない。<br />
+/// MSAA sample counts selectable in the settings. 1 means MSAA is off.
+pub const MSAA_SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+/// 設定で選べるフレームレートの上限。0は上限なし。<br />
+/// Frame rate caps selectable in the settings. 0 means no cap.
+pub const MAX_FPS_OPTIONS: [u32; 6] = [0, 30, 60, 120, 144, 240];
+
+/// 設定で選べる描画解像度の倍率（パーセント）。<br />
+/// Render resolution scales selectable in the settings, in percent.
+pub const RENDER_SCALE_OPTIONS: [u32; 6] = [50, 75, 100, 125, 150, 200];
+
+/// プレイヤーが選べるグラフィックスの設定。グラフィックスAPIに依存しない。<br />
+/// Graphics settings the player can choose, independent of the graphics API.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct GraphicsSettings {
+    /// 希望するMSAAのサンプル数。デバイスが対応していない場合は下げる。<br />
+    /// Requested MSAA sample count, lowered if the device doesn't support it.
+    pub msaa_samples: u32,
+    /// 希望するプレゼントモード。対応していない場合はFIFOを使う。<br />
+    /// Requested present mode. FIFO is used if it isn't supported.
+    pub present_mode: PresentMode,
+    /// 垂直同期を使わない時のフレームレートの上限。0は上限なし。<br />
+    /// Frame rate cap when vertical sync is off. 0 means no cap.
+    pub max_fps: u32,
+    pub window_mode: WindowMode,
+    /// ウィンドウの大きさに対するシーンの描画解像度の倍率（パーセント）。ポストプロセスでスワップチェーンの大きさに拡大縮小する。<br />
+    /// Resolution the scene is rendered at relative to the window size, in percent. Post-processing scales it to the size of the swapchain.
+    pub render_scale: u32,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            msaa_samples: 4,
+            present_mode: PresentMode::default(),
+            max_fps: 0,
+            window_mode: WindowMode::default(),
+            render_scale: 100,
+        }
+    }
+}
+
+impl GraphicsSettings {
+    /// 一番低い品質の設定。どのデバイスでも使えるものだけを選ぶ。<br />
+    /// The lowest quality settings, choosing only what every device supports.
+    pub fn lowest() -> Self {
+        GraphicsSettings {
+            msaa_samples: 1,
+            present_mode: PresentMode::Fifo,
+            max_fps: 0,
+            window_mode: WindowMode::Windowed,
+            render_scale: RENDER_SCALE_OPTIONS[0],
+        }
+    }
+
+    /// 既定の設定を起動時の設定の`present_mode`、`max_fps`と`window_mode`で上書きする。保存した設定はさらにそれを上書きする。<br />
+    /// `safe_mode`の時は、何も上書きせずに一番低い品質の設定を使う。<br />
+    /// Override the default settings with `present_mode`, `max_fps` and `window_mode` of the startup settings. Saved settings override them in turn.<br />
+    /// In `safe_mode`, the lowest quality settings are used without overriding anything.
+    pub fn from_settings(settings: &Settings) -> Self {
+        if settings.safe_mode {
+            return Self::lowest();
+        }
+        GraphicsSettings {
+            present_mode: PresentMode::from_name(&settings.present_mode).unwrap_or_default(),
+            max_fps: settings.max_fps,
+            window_mode: WindowMode::from_name(&settings.window_mode).unwrap_or_default(),
+            ..GraphicsSettings::default()
+        }
+    }
+
+    /// 対応しているサンプル数のうち、希望を超えない最大のものを取得する。<br />
+    /// Get the largest supported sample count that doesn't exceed the requested one.
+    pub fn get_msaa_samples(&self, supported_samples: &[u32]) -> u32 {
+        supported_samples
+            .iter()
+            .copied()
+            .filter(|samples| *samples <= self.msaa_samples)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// 1フレームに掛ける最短の時間。垂直同期を使う時や上限がない時は`None`。<br />
+    /// Minimum time spent on a frame, or `None` with vertical sync or without a cap.
+    pub fn get_frame_time_limit(&self) -> Option<Duration> {
+        if self.present_mode == PresentMode::Fifo || self.max_fps == 0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(1.0 / self.max_fps as f64))
+        }
+    }
+
+    /// 倍率を掛けたシーンの描画解像度。どちらの辺も1ピクセルより小さくしない。<br />
+    /// Resolution the scene is rendered at after scaling. Neither side goes below a pixel.
+    pub fn get_render_extent(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = |size: u32| ((size as u64 * self.render_scale as u64 / 100) as u32).max(1);
+        (scale(width), scale(height))
+    }
+
+    /// 調整パネルのウィンドウモードを変える。Alt+Enterで切り替える時に使い、変更は次に設定を適用する時に反映される。<br />
+    /// Change the window mode in the tweak panel. Used by the Alt+Enter toggle, and takes effect the next time the settings are applied.
+    pub fn set_window_mode(tweaks: &mut TweakRegistry, window_mode: WindowMode) -> bool {
+        let index = WindowMode::ALL
+            .iter()
+            .position(|mode| *mode == window_mode)
+            .unwrap_or(0);
+        let value = TweakValue::Enum {
+            index,
+            options: vec![],
+        };
+        tweaks.set(WINDOW_MODE_TWEAK, value)
+    }
+
+    /// 設定を調整パネルに登録する。MSAAの選択肢は対応しているサンプル数だけにする。<br />
+    /// Register the settings to the tweak panel. Only supported sample counts are offered for MSAA.
+    pub fn register_tweaks(&mut self, tweaks: &mut TweakRegistry, supported_samples: &[u32]) {
+        let options = supported_samples
+            .iter()
+            .map(|samples| format!("{}x", samples))
+            .collect::<Vec<_>>();
+        let options = options.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let samples = self.get_msaa_samples(supported_samples);
+        let index = supported_samples
+            .iter()
+            .position(|s| *s == samples)
+            .unwrap_or(0);
+        tweaks.register_enum(MSAA_TWEAK, &options, index);
+
+        let options = PresentMode::ALL
+            .iter()
+            .map(|mode| mode.get_name())
+            .collect::<Vec<_>>();
+        let index = PresentMode::ALL
+            .iter()
+            .position(|mode| *mode == self.present_mode)
+            .unwrap_or(0);
+        tweaks.register_enum(PRESENT_MODE_TWEAK, &options, index);
+
+        // 起動時の設定で選択肢にない上限が指定された場合も選べるようにする。
+        // A cap from the startup settings that isn't among the options stays selectable.
+        let mut max_fps_options = MAX_FPS_OPTIONS.to_vec();
+        if !max_fps_options.contains(&self.max_fps) {
+            max_fps_options.push(self.max_fps);
+            max_fps_options.sort();
+        }
+        let options = max_fps_options
+            .iter()
+            .map(|max_fps| match max_fps {
+                0 => UNLIMITED_FPS.to_string(),
+                _ => max_fps.to_string(),
+            })
+            .collect::<Vec<_>>();
+        let options = options.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let index = max_fps_options
+            .iter()
+            .position(|max_fps| *max_fps == self.max_fps)
+            .unwrap_or(0);
+        tweaks.register_enum(MAX_FPS_TWEAK, &options, index);
+
+        let options = WindowMode::ALL
+            .iter()
+            .map(|mode| mode.get_name())
+            .collect::<Vec<_>>();
+        let index = WindowMode::ALL
+            .iter()
+            .position(|mode| *mode == self.window_mode)
+            .unwrap_or(0);
+        tweaks.register_enum(WINDOW_MODE_TWEAK, &options, index);
+
+        let options = RENDER_SCALE_OPTIONS
+            .iter()
+            .map(|scale| format!("{}%", scale))
+            .collect::<Vec<_>>();
+        let options = options.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let index = RENDER_SCALE_OPTIONS
+            .iter()
+            .position(|scale| *scale == self.render_scale)
+            .unwrap_or(0);
+        tweaks.register_enum(RENDER_SCALE_TWEAK, &options, index);
+        self.apply_tweaks(tweaks, supported_samples);
+    }
+
+    pub fn apply_tweaks(&mut self, tweaks: &TweakRegistry, supported_samples: &[u32]) {
+        if let Some(samples) = tweaks
+            .get_enum(MSAA_TWEAK)
+            .and_then(|index| supported_samples.get(index))
+        {
+            self.msaa_samples = *samples;
+        }
+        if let Some(present_mode) = tweaks
+            .get_enum(PRESENT_MODE_TWEAK)
+            .and_then(|index| PresentMode::ALL.get(index))
+        {
+            self.present_mode = *present_mode;
+        }
+        if let Some(TweakValue::Enum { index, options }) = tweaks.get(MAX_FPS_TWEAK) {
+            match options[*index].as_str() {
+                UNLIMITED_FPS => self.max_fps = 0,
+                max_fps => self.max_fps = max_fps.parse().unwrap_or(self.max_fps),
+            }
+        }
+        if let Some(window_mode) = tweaks
+            .get_enum(WINDOW_MODE_TWEAK)
+            .and_then(|index| WindowMode::ALL.get(index))
+        {
+            self.window_mode = *window_mode;
+        }
+        if let Some(render_scale) = tweaks
+            .get_enum(RENDER_SCALE_TWEAK)
+            .and_then(|index| RENDER_SCALE_OPTIONS.get(index))
+        {
+            self.render_scale = *render_scale;
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::game::shared::components::Bounds;
+use glam::Vec4;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// 視錐台で描画を間引く計算シェーダーのワークグループの大きさ。<br />
+/// Size of a workgroup of the compute shader culling draws against the frustum.
+pub const INDIRECT_CULL_WORKGROUP_SIZE: u32 = 64;
+
+pub const INDIRECT_CULL_COMPUTE_SHADER: &str = "./shaders/indirect_cull_comp.spv";
+
+/// `vkCmdDrawIndexedIndirect`が読む一回の描画の引数。並びはVulkanの構造体と同じ。<br />
+/// Arguments of a single draw read by `vkCmdDrawIndexedIndirect`, laid out like the Vulkan struct.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DrawArguments {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub vertex_offset: i32,
+    pub first_instance: u32,
+}
+
+impl DrawArguments {
+    pub fn new(
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+    ) -> Self {
+        DrawArguments {
+            index_count,
+            instance_count,
+            first_index,
+            vertex_offset,
+            first_instance: 0,
+        }
+    }
+}
+
+/// シェーダーが描画ごとに読む情報。プッシュコンスタントの代わりに、描画の番号で引く。<br />
+/// Per-draw information read by the shaders, looked up by the draw index instead of push constants.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DrawInfo {
+    pub model_index: u32,
+    pub texture_index: u32,
+    /// 見える時のインスタンスの数。視錐台の外なら計算シェーダーが引数を0にする。<br />
+    /// Number of instances while visible. The compute shader sets the argument to zero outside the frustum.
+    pub instance_count: u32,
+    pub padding: u32,
+    /// ワールド空間の境界球。`w`が負なら間引かない。<br />
+    /// Bounding sphere in world space. Never culled while `w` is negative.
+    pub bounding_sphere: [f32; 4],
+}
+
+impl DrawInfo {
+    pub fn new(model_index: u32, texture_index: u32, instance_count: u32) -> Self {
+        DrawInfo {
+            model_index,
+            texture_index,
+            instance_count,
+            padding: 0,
+            bounding_sphere: [0.0, 0.0, 0.0, -1.0],
+        }
+    }
+
+    /// ワールド空間の境界ボックスを囲む球で間引くようにする。<br />
+    /// Cull by the sphere enclosing a bounding box in world space.
+    pub fn with_bounds(mut self, bounds: &Bounds) -> Self {
+        let center = bounds.get_center();
+        let radius = (bounds.max - center).length();
+        self.bounding_sphere = [center.x, center.y, center.z, radius];
+        self
+    }
+}
+
+/// 描画を間引く計算シェーダーのプッシュコンスタント。<br />
+/// Push constant of the compute shader culling draws.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct IndirectCullPushConstant {
+    pub planes: [[f32; 4]; 6],
+    pub draw_count: u32,
+    pub padding: [u32; 3],
+}
+
+impl IndirectCullPushConstant {
+    pub fn new(planes: &[Vec4; 6], draw_count: u32) -> Self {
+        let mut push_constant = IndirectCullPushConstant {
+            draw_count,
+            ..Default::default()
+        };
+        for (target, plane) in push_constant.planes.iter_mut().zip(planes.iter()) {
+            *target = [plane.x, plane.y, plane.z, plane.w];
+        }
+        push_constant
+    }
+}
+
+/// 同じバッファとパイプラインで続けて描ける描画の範囲。一回の間接描画になる。<br />
+/// A range of draws that share buffers and pipelines, issued as a single indirect draw.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IndirectBatch<K> {
+    pub key: K,
+    pub first_draw: u32,
+    pub draw_count: u32,
+}
+
+/// 引数のバッファと情報のバッファに書き込む内容と、それを描く間接描画。<br />
+/// What goes into the argument and information buffers, and the indirect draws issuing them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndirectDrawData<K> {
+    pub arguments: Vec<DrawArguments>,
+    pub infos: Vec<DrawInfo>,
+    pub batches: Vec<IndirectBatch<K>>,
+}
+
+/// シーンのパスで集めた描画。バインドするものを表すキーでまとめ、少ない間接描画にする。<br />
+/// Draws collected in the scene pass, grouped by a key of what is bound so that they become a few indirect draws.
+#[derive(Clone, Debug)]
+pub struct IndirectDrawList<K> {
+    draws: Vec<(K, DrawArguments, DrawInfo)>,
+}
+
+impl<K> Default for IndirectDrawList<K> {
+    fn default() -> Self {
+        IndirectDrawList { draws: vec![] }
+    }
+}
+
+impl<K> IndirectDrawList<K>
+where
+    K: Copy + Eq + Hash,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, key: K, arguments: DrawArguments, info: DrawInfo) {
+        self.draws.push((key, arguments, info));
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+
+    /// 同じキーの描画を並べ、キーが最初に現れた順で間接描画に分ける。<br />
+    /// 一回の間接描画が`max_draw_count`を超えれば、その先は次の間接描画にする。<br />
+    /// Place draws with the same key next to each other and split them into indirect draws, in the order each key first appeared.<br />
+    /// Draws beyond `max_draw_count` in one indirect draw continue in the next one.
+    pub fn build(&self, max_draw_count: u32) -> IndirectDrawData<K> {
+        let max_draw_count = max_draw_count.max(1);
+        let mut order = vec![];
+        let mut groups: HashMap<K, Vec<usize>> = HashMap::new();
+        for (index, (key, _, _)) in self.draws.iter().enumerate() {
+            groups
+                .entry(*key)
+                .or_insert_with(|| {
+                    order.push(*key);
+                    vec![]
+                })
+                .push(index);
+        }
+        let mut data = IndirectDrawData {
+            arguments: Vec::with_capacity(self.draws.len()),
+            infos: Vec::with_capacity(self.draws.len()),
+            batches: vec![],
+        };
+        for key in order.into_iter() {
+            let indices = groups.remove(&key).unwrap_or_default();
+            for chunk in indices.chunks(max_draw_count as usize) {
+                data.batches.push(IndirectBatch {
+                    key,
+                    first_draw: data.arguments.len() as u32,
+                    draw_count: chunk.len() as u32,
+                });
+                for index in chunk.iter() {
+                    let (_, arguments, info) = self.draws[*index];
+                    data.arguments.push(arguments);
+                    data.infos.push(info);
+                }
+            }
+        }
+        data
+    }
+}
@@ -0,0 +1,127 @@
+use crate::game::shared::structs::{ModelMetaData, PositionInfo};
+use glam::Vec4;
+use slotmap::DefaultKey;
+
+/// インスペクターに表示するエンティティの値。<br />
+/// Values of an entity shown in the inspector.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InspectorEntry {
+    pub entity: DefaultKey,
+    pub name: String,
+    pub position_info: PositionInfo,
+    pub object_color: Vec4,
+    pub reflectivity: f32,
+    pub shine_damper: f32,
+    /// アニメーターの状態の名前。アニメーターがなければ空。<br />
+    /// Names of the animator's states, empty without an animator.
+    pub animation_states: Vec<String>,
+    pub animation_state: Option<usize>,
+}
+
+/// インスペクターで変えられた値。<br />
+/// A value changed in the inspector.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InspectorEdit {
+    PositionInfo(PositionInfo),
+    Material {
+        object_color: Vec4,
+        reflectivity: f32,
+        shine_damper: f32,
+    },
+    AnimationState(String),
+}
+
+impl InspectorEntry {
+    pub fn new(
+        entity: DefaultKey,
+        name: &str,
+        position_info: PositionInfo,
+        model_metadata: &ModelMetaData,
+    ) -> Self {
+        InspectorEntry {
+            entity,
+            name: name.to_string(),
+            position_info,
+            object_color: model_metadata.object_color,
+            reflectivity: model_metadata.reflectivity,
+            shine_damper: model_metadata.shine_damper,
+            animation_states: vec![],
+            animation_state: None,
+        }
+    }
+
+    pub fn with_animation(mut self, states: Vec<String>, current_state: &str) -> Self {
+        self.animation_state = states.iter().position(|state| state == current_state);
+        self.animation_states = states;
+        self
+    }
+
+    /// 編集した写しと比べて、変わった値を返す。<br />
+    /// Compare with an edited copy and return the values that changed.
+    pub fn get_edits(&self, edited: &InspectorEntry) -> Vec<InspectorEdit> {
+        let mut edits = vec![];
+        if edited.position_info != self.position_info {
+            edits.push(InspectorEdit::PositionInfo(edited.position_info));
+        }
+        if edited.object_color != self.object_color
+            || edited.reflectivity != self.reflectivity
+            || edited.shine_damper != self.shine_damper
+        {
+            edits.push(InspectorEdit::Material {
+                object_color: edited.object_color,
+                reflectivity: edited.reflectivity,
+                shine_damper: edited.shine_damper,
+            });
+        }
+        if edited.animation_state != self.animation_state {
+            let state = edited
+                .animation_state
+                .and_then(|index| edited.animation_states.get(index));
+            if let Some(state) = state {
+                edits.push(InspectorEdit::AnimationState(state.clone()));
+            }
+        }
+        edits
+    }
+}
+
+/// デバッグ用のインスペクターで選んでいるエンティティ。<br />
+/// The entity selected in the debug inspector.
+#[derive(Clone, Debug, Default)]
+pub struct Inspector {
+    selected: Option<DefaultKey>,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Inspector { selected: None }
+    }
+
+    pub fn get_selected(&self) -> Option<DefaultKey> {
+        self.selected
+    }
+
+    /// エンティティを選ぶ。選んでいるエンティティをもう一度選ぶと選択を外す。<br />
+    /// Select an entity. Selecting the selected entity again clears the selection.
+    pub fn toggle(&mut self, entity: DefaultKey) {
+        self.selected = if self.selected == Some(entity) {
+            None
+        } else {
+            Some(entity)
+        };
+    }
+
+    /// 選んでいるエンティティの値。シーンから消えていれば選択を外す。<br />
+    /// Values of the selected entity. The selection is cleared if it has left the scene.
+    pub fn get_selected_entry<'a>(
+        &mut self,
+        entries: &'a [InspectorEntry],
+    ) -> Option<&'a InspectorEntry> {
+        let selected = self.selected?;
+        let entry = entries.iter().find(|entry| entry.entity == selected);
+        if entry.is_none() {
+            self.selected = None;
+        }
+        entry
+    }
+}
@@ -0,0 +1,167 @@
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+
+/// ジョブの優先度。<br />
+/// Priority of a job.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum JobPriority {
+    /// フレームが待っている、コマンドの記録などのジョブ。<br />
+    /// Jobs the frame is waiting on, such as recording commands.
+    RenderCritical,
+    /// モデルやテクスチャの読み込みなど、フレームを待たせないジョブ。<br />
+    /// Jobs the frame doesn't wait on, such as loading models and textures.
+    Streaming,
+}
+
+struct QueueState<T> {
+    render_critical: VecDeque<T>,
+    streaming: VecDeque<T>,
+    is_closed: bool,
+}
+
+/// 優先度ごとに並べるジョブのキュー。描画に必要なジョブを先に取り出すので、読み込みが溜まってもフレームの記録は待たされない。<br />
+/// 複数のワーカーが同じキューから取り出せる。<br />
+/// A queue of jobs ordered by priority. Jobs needed for rendering are taken first, so piled-up loading doesn't hold frame recording back.<br />
+/// Several workers can take jobs from the same queue.
+pub struct JobQueue<T> {
+    state: Mutex<QueueState<T>>,
+    condvar: Condvar,
+}
+
+impl<T> JobQueue<T> {
+    pub fn new() -> Self {
+        JobQueue {
+            state: Mutex::new(QueueState {
+                render_critical: VecDeque::new(),
+                streaming: VecDeque::new(),
+                is_closed: false,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// ジョブを加える。閉じたキューには加えず、`false`を返す。<br />
+    /// Add a job. A closed queue doesn't take it and returns `false`.
+    pub fn push(&self, priority: JobPriority, job: T) -> bool {
+        let mut state = self.state.lock();
+        if state.is_closed {
+            return false;
+        }
+        match priority {
+            JobPriority::RenderCritical => state.render_critical.push_back(job),
+            JobPriority::Streaming => state.streaming.push_back(job),
+        }
+        drop(state);
+        self.condvar.notify_one();
+        true
+    }
+
+    /// 次のジョブを待って取り出す。閉じられたら`None`を返し、残ったジョブは実行しない。<br />
+    /// Wait for the next job and take it. Returns `None` once closed, leaving the remaining jobs unrun.
+    pub fn pop(&self) -> Option<T> {
+        let mut state = self.state.lock();
+        loop {
+            if state.is_closed {
+                return None;
+            }
+            if let Some(job) = Self::take_next(&mut state) {
+                return Some(job);
+            }
+            self.condvar.wait(&mut state);
+        }
+    }
+
+    /// 待たずに次のジョブを取り出す。<br />
+    /// Take the next job without waiting.
+    pub fn try_pop(&self) -> Option<T> {
+        let mut state = self.state.lock();
+        if state.is_closed {
+            return None;
+        }
+        Self::take_next(&mut state)
+    }
+
+    pub fn len(&self) -> usize {
+        let state = self.state.lock();
+        state.render_critical.len() + state.streaming.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// キューを閉じ、待っているワーカーを全て起こす。<br />
+    /// Close the queue and wake up every waiting worker.
+    pub fn close(&self) {
+        let mut state = self.state.lock();
+        state.is_closed = true;
+        state.render_critical.clear();
+        state.streaming.clear();
+        drop(state);
+        self.condvar.notify_all();
+    }
+
+    fn take_next(state: &mut QueueState<T>) -> Option<T> {
+        state
+            .render_critical
+            .pop_front()
+            .or_else(|| state.streaming.pop_front())
+    }
+}
+
+impl<T> Default for JobQueue<T> {
+    fn default() -> Self {
+        JobQueue::new()
+    }
+}
+
+/// フレームの同期点。フレームが待つジョブを数え、全て終わるまで待てる。<br />
+/// A synchronization point of a frame. Counts the jobs the frame waits on, and can wait until all of them are done.
+pub struct SyncPoint {
+    pending: Mutex<usize>,
+    condvar: Condvar,
+}
+
+impl SyncPoint {
+    pub fn new() -> Self {
+        SyncPoint {
+            pending: Mutex::new(0),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// 待つジョブを一つ加える。ジョブを並べる前に呼ぶ。<br />
+    /// Add a job to wait for, called before queueing the job.
+    pub fn add(&self) {
+        *self.pending.lock() += 1;
+    }
+
+    /// ジョブが一つ終わった。<br />
+    /// A job has finished.
+    pub fn done(&self) {
+        let mut pending = self.pending.lock();
+        *pending = pending.saturating_sub(1);
+        if *pending == 0 {
+            self.condvar.notify_all();
+        }
+    }
+
+    /// 加えたジョブが全て終わるまで待つ。<br />
+    /// Wait until every added job has finished.
+    pub fn wait(&self) {
+        let mut pending = self.pending.lock();
+        while *pending > 0 {
+            self.condvar.wait(&mut pending);
+        }
+    }
+
+    pub fn get_pending(&self) -> usize {
+        *self.pending.lock()
+    }
+}
+
+impl Default for SyncPoint {
+    fn default() -> Self {
+        SyncPoint::new()
+    }
+}
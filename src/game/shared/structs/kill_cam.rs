@@ -0,0 +1,269 @@
+use crate::game::shared::structs::games::interpolation::StateQueues;
+use crate::game::shared::structs::{CameraTrack, Cutscene, Easing, Keyframe, PositionInfo};
+use crate::game::shared::util::math::{direction_from_yaw_pitch, yaw_pitch_from_direction};
+use glam::Vec3A;
+use std::collections::VecDeque;
+
+/// キルカメラの長さ（秒）。終わったら復活する。<br />
+/// Length of the kill cam in seconds. The player respawns once it ends.
+pub const KILL_CAM_DURATION: f32 = 5.0;
+
+/// 今のカメラの位置から最初の視点まで飛ぶ時間（秒）。<br />
+/// Time in seconds to fly from the current camera position to the first viewpoint.
+const FLY_DURATION: f32 = 1.2;
+
+/// 最後に画面を暗くする時間（秒）。<br />
+/// Time in seconds the screen takes to fade to black at the end.
+const FADE_DURATION: f32 = 0.6;
+
+/// キラーの視点は、キラーのこれだけ後ろで上に置く。<br />
+/// The killer's viewpoint is placed this far behind and above the killer.
+const VIEWPOINT_DISTANCE: f32 = 6.0;
+const VIEWPOINT_HEIGHT: f32 = 3.0;
+
+/// 倒れた位置を回る軌道の半径と高さ。<br />
+/// Radius and height of the orbit around the death location.
+const ORBIT_RADIUS: f32 = 12.0;
+const ORBIT_HEIGHT: f32 = 6.0;
+
+/// 軌道を一周する間のキーフレームの数。<br />
+/// Number of keyframes placed along one lap of the orbit.
+const ORBIT_KEYFRAMES: usize = 12;
+
+/// 軌道を回る速さ（度/秒）。<br />
+/// Speed of the orbit in degrees per second.
+const ORBIT_SPEED: f32 = 45.0;
+
+/// ローカルプレイヤーが倒された時の状況。<br />
+/// Circumstances of the local player being defeated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeathEvent {
+    /// 倒したプレイヤーのID。自滅や不明な場合は`None`。<br />
+    /// ID of the player who landed the kill. `None` for self-inflicted or unknown deaths.
+    pub attacker_id: Option<String>,
+    pub position: PositionInfo,
+    pub attacker_position: Option<PositionInfo>,
+}
+
+/// サーバーが伝えたローカルプレイヤーの生死の変化。<br />
+/// A change in whether the local player is alive, as reported by the server.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LifeEvent {
+    Died(DeathEvent),
+    Respawned,
+}
+
+/// サーバーが伝えたローカルプレイヤーの生死を追い、変化をイベントとして積む。シーンが毎フレーム取り出す。<br />
+/// Tracks whether the server reports the local player alive, and queues the changes as events. The scene takes them out every frame.
+#[derive(Clone, Debug, Default)]
+pub struct LifeTracker {
+    /// サーバーが最後に伝えたローカルプレイヤーの生死。まだ届いていなければ`None`。<br />
+    /// Whether the local player was last reported alive by the server. `None` until the first report.
+    is_local_alive: Option<bool>,
+    events: VecDeque<LifeEvent>,
+}
+
+impl LifeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// サーバーが伝えたローカルプレイヤーの生死を記録する。生きていた状態から倒れたら、倒した相手と位置を残す。<br />
+    /// 位置は`states`から取るので、同じ部屋のステートを`states`に追加した後に呼ぶ。<br />
+    /// Record whether the server reports the local player alive. When it goes down from alive, the attacker and the positions are kept.<br />
+    /// The positions are taken from `states`, so call this after pushing the same room state into `states`.
+    pub fn push(
+        &mut self,
+        is_alive: bool,
+        last_attacker_id: &str,
+        local_player_id: &str,
+        states: &StateQueues,
+    ) {
+        let was_alive = self.is_local_alive.replace(is_alive);
+        match (was_alive, is_alive) {
+            (Some(true), false) => {
+                let attacker_id = Some(last_attacker_id)
+                    .filter(|id| !id.is_empty() && *id != local_player_id)
+                    .map(|id| id.to_string());
+                let attacker_position = attacker_id
+                    .as_ref()
+                    .and_then(|id| states.get_latest_remote(id));
+                self.events.push_back(LifeEvent::Died(DeathEvent {
+                    attacker_id,
+                    position: states.get_last_local_state().unwrap_or_default(),
+                    attacker_position,
+                }));
+            }
+            (Some(false), true) => self.events.push_back(LifeEvent::Respawned),
+            _ => (),
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<LifeEvent> {
+        self.events.drain(..).collect()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// ローカルプレイヤーが倒された時に再生するカットシーンを作る。<br />
+/// キラーの位置が分かればキラーの後ろから倒れた位置を見せ、分からなければ倒れた位置の周りを回る。<br />
+/// Create the cutscene played when the local player is defeated.<br />
+/// If the killer's position is known, the death location is shown from behind the killer. Otherwise the camera orbits the death location.
+pub fn create_kill_cam(
+    death: &DeathEvent,
+    camera_position: Vec3A,
+    camera_target: Vec3A,
+) -> Cutscene {
+    let death_position = death.position.position;
+    let (start_yaw, start_pitch) = get_yaw_pitch(camera_position, camera_target);
+    let mut camera = CameraTrack::default();
+    push_camera_keyframe(
+        &mut camera,
+        0.0,
+        camera_position,
+        (start_yaw, start_pitch),
+        Easing::Linear,
+    );
+
+    match death.attacker_position.as_ref() {
+        Some(attacker) => {
+            let viewpoint =
+                get_killer_viewpoint(attacker.position, attacker.rotation.y, death_position);
+            let (yaw, pitch) = get_yaw_pitch(viewpoint, death_position);
+            let yaw = unwrap_angle(start_yaw, yaw);
+            push_camera_keyframe(
+                &mut camera,
+                FLY_DURATION,
+                viewpoint,
+                (yaw, pitch),
+                Easing::EaseInOut,
+            );
+            push_camera_keyframe(
+                &mut camera,
+                KILL_CAM_DURATION,
+                viewpoint,
+                (yaw, pitch),
+                Easing::Linear,
+            );
+        }
+        None => {
+            // 今のカメラがある側から回り始める。
+            // The orbit starts from the side the camera is currently on.
+            let offset = camera_position - death_position;
+            let start_angle = offset.x.atan2(offset.z).to_degrees();
+            let orbit_time = KILL_CAM_DURATION - FLY_DURATION;
+            let mut previous_yaw = start_yaw;
+            for step in 0..=ORBIT_KEYFRAMES {
+                let t = step as f32 / ORBIT_KEYFRAMES as f32;
+                let angle = start_angle + ORBIT_SPEED * orbit_time * t;
+                let position = get_orbit_position(death_position, angle);
+                let (yaw, pitch) = get_yaw_pitch(position, death_position);
+                let yaw = unwrap_angle(previous_yaw, yaw);
+                previous_yaw = yaw;
+                let easing = if step == 0 {
+                    Easing::EaseInOut
+                } else {
+                    Easing::Linear
+                };
+                push_camera_keyframe(
+                    &mut camera,
+                    FLY_DURATION + orbit_time * t,
+                    position,
+                    (yaw, pitch),
+                    easing,
+                );
+            }
+        }
+    }
+
+    camera.fade = vec![
+        Keyframe {
+            time: KILL_CAM_DURATION - FADE_DURATION,
+            value: 0.0,
+            easing: Easing::Linear,
+        },
+        Keyframe {
+            time: KILL_CAM_DURATION,
+            value: 1.0,
+            easing: Easing::EaseIn,
+        },
+    ];
+
+    let name = match death.attacker_id.as_ref() {
+        Some(attacker_id) => format!("Kill cam ({})", attacker_id),
+        None => "Kill cam".to_string(),
+    };
+    Cutscene {
+        name,
+        camera,
+        entities: vec![],
+    }
+}
+
+/// キラーの後ろの上から、倒れた位置の方を見る視点。二人が重なっていればキラーの向きを使う。<br />
+/// A viewpoint behind and above the killer, facing the death location. The killer's facing is used if both are at the same spot.
+fn get_killer_viewpoint(killer_position: Vec3A, killer_yaw: f32, death_position: Vec3A) -> Vec3A {
+    let mut direction = death_position - killer_position;
+    direction.y = 0.0;
+    let direction = if direction.length_squared() < f32::EPSILON {
+        direction_from_yaw_pitch(killer_yaw, 0.0)
+    } else {
+        direction.normalize()
+    };
+    killer_position - direction * VIEWPOINT_DISTANCE + Vec3A::new(0.0, VIEWPOINT_HEIGHT, 0.0)
+}
+
+fn get_orbit_position(center: Vec3A, angle: f32) -> Vec3A {
+    let angle = angle.to_radians();
+    center
+        + Vec3A::new(
+            angle.sin() * ORBIT_RADIUS,
+            ORBIT_HEIGHT,
+            angle.cos() * ORBIT_RADIUS,
+        )
+}
+
+/// 位置から目標を見るヨーとピッチ（度）。<br />
+/// Yaw and pitch in degrees looking from a position at a target.
+fn get_yaw_pitch(position: Vec3A, target: Vec3A) -> (f32, f32) {
+    let direction = target - position;
+    if direction.length_squared() < f32::EPSILON {
+        return (0.0, 0.0);
+    }
+    let (yaw, pitch) = yaw_pitch_from_direction(direction);
+    (yaw.to_degrees(), pitch.to_degrees())
+}
+
+/// キーフレームの間で回転が短い方向に回るよう、角度を前の角度から180度以内に直す。<br />
+/// Bring an angle within 180 degrees of the previous one, so rotations between keyframes take the short way round.
+fn unwrap_angle(previous: f32, angle: f32) -> f32 {
+    let mut difference = (angle - previous) % 360.0;
+    if difference > 180.0 {
+        difference -= 360.0;
+    } else if difference < -180.0 {
+        difference += 360.0;
+    }
+    previous + difference
+}
+
+fn push_camera_keyframe(
+    camera: &mut CameraTrack,
+    time: f32,
+    position: Vec3A,
+    (yaw, pitch): (f32, f32),
+    easing: Easing,
+) {
+    camera.position.push(Keyframe {
+        time,
+        value: [position.x, position.y, position.z],
+        easing,
+    });
+    camera.rotation.push(Keyframe {
+        time,
+        value: [yaw, pitch],
+        easing,
+    });
+}
@@ -0,0 +1,290 @@
+use anyhow::Context;
+use ash::vk::Format;
+use basis_universal::{
+    transcoder_init, DecodeFlags, LowLevelUastcTranscoder, SliceParametersUastc,
+    TranscoderBlockFormat,
+};
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::sync::Once;
+
+/// 変換器の初期化は一度だけでいい。<br />
+/// The transcoder only has to be initialized once.
+static TRANSCODER_INIT: Once = Once::new();
+
+/// KTX2ファイルの先頭の識別子。<br />
+/// Identifier at the beginning of every KTX2 file.
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// 識別子、ヘッダーとインデックスの長さ。この後にレベルのインデックスが続く。<br />
+/// Length of the identifier, the header and the index, which are followed by the level index.
+const KTX2_HEADER_SIZE: usize = 80;
+
+/// レベルのインデックスの一つの要素の長さ。<br />
+/// Length of one entry of the level index.
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+/// データフォーマット記述子のカラーモデル。<br />
+/// Color models of the data format descriptor.
+const KHR_DF_MODEL_ETC1S: u8 = 163;
+const KHR_DF_MODEL_UASTC: u8 = 166;
+
+/// UASTCのチャンネルのうち、アルファを含むもの。RGBAとRRRG。<br />
+/// UASTC channels that include alpha: RGBA and RRRG.
+const UASTC_ALPHA_CHANNELS: [u8; 2] = [3, 5];
+
+/// 一つのブロックが覆うテクセルの幅と高さ。<br />
+/// Width and height of the texels covered by one block.
+const BLOCK_SIZE: u32 = 4;
+
+/// KTX2のスーパー圧縮の方式。<br />
+/// Supercompression scheme of a KTX2 file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SupercompressionScheme {
+    None,
+    BasisLz,
+    Zstandard,
+}
+
+/// UASTCを変換する先の形式。デバイスが対応するものの中で最も小さいものを選ぶ。<br />
+/// Format UASTC is transcoded into. The smallest one the device supports is chosen.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TranscodeTarget {
+    Bc7,
+    Astc4x4,
+    /// 圧縮テクスチャに対応しないデバイスのための、非圧縮のフォールバック。<br />
+    /// Uncompressed fallback for devices without compressed texture support.
+    Rgba8,
+}
+
+impl TranscodeTarget {
+    pub fn choose(supports_bc7: bool, supports_astc: bool) -> Self {
+        if supports_bc7 {
+            TranscodeTarget::Bc7
+        } else if supports_astc {
+            TranscodeTarget::Astc4x4
+        } else {
+            TranscodeTarget::Rgba8
+        }
+    }
+
+    pub fn get_format(&self) -> Format {
+        match self {
+            TranscodeTarget::Bc7 => Format::BC7_UNORM_BLOCK,
+            TranscodeTarget::Astc4x4 => Format::ASTC_4X4_UNORM_BLOCK,
+            TranscodeTarget::Rgba8 => Format::R8G8B8A8_UNORM,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        *self != TranscodeTarget::Rgba8
+    }
+
+    fn get_block_format(&self) -> TranscoderBlockFormat {
+        match self {
+            TranscodeTarget::Bc7 => TranscoderBlockFormat::BC7,
+            TranscodeTarget::Astc4x4 => TranscoderBlockFormat::ASTC_4x4,
+            TranscodeTarget::Rgba8 => TranscoderBlockFormat::RGBA32,
+        }
+    }
+}
+
+/// ファイルの中の一つのミップレベルの位置。<br />
+/// Location of one mip level inside the file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Ktx2Level {
+    pub offset: usize,
+    pub length: usize,
+    pub uncompressed_length: usize,
+}
+
+/// KTX2ファイルのヘッダーとレベルのインデックス。データ自体はファイルのバイト列から読む。<br />
+/// 対応するのは配列でもキューブマップでもない2Dテクスチャのみ。<br />
+/// Header and level index of a KTX2 file. The data itself is read from the bytes of the file.<br />
+/// Only 2D textures that are neither arrays nor cubemaps are supported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ktx2Texture {
+    /// ファイルに書かれたVulkanの形式。Basis Universalの場合は`UNDEFINED`。<br />
+    /// Vulkan format written in the file. `UNDEFINED` for Basis Universal.
+    pub format: Format,
+    pub width: u32,
+    pub height: u32,
+    pub supercompression: SupercompressionScheme,
+    pub is_uastc: bool,
+    pub has_alpha: bool,
+    pub levels: Vec<Ktx2Level>,
+}
+
+impl Ktx2Texture {
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < KTX2_HEADER_SIZE || bytes[0..12] != KTX2_IDENTIFIER {
+            anyhow::bail!("Not a KTX2 file.");
+        }
+        let format = Format::from_raw(read_u32(bytes, 12)? as i32);
+        let width = read_u32(bytes, 20)?;
+        let height = read_u32(bytes, 24)?;
+        let depth = read_u32(bytes, 28)?;
+        let layer_count = read_u32(bytes, 32)?;
+        let face_count = read_u32(bytes, 36)?;
+        let level_count = read_u32(bytes, 40)?.max(1);
+        if width == 0 || height == 0 || depth > 0 || layer_count > 0 || face_count != 1 {
+            anyhow::bail!(
+                "Only 2D KTX2 textures are supported. Size: {}x{}x{}, layers: {}, faces: {}.",
+                width,
+                height,
+                depth,
+                layer_count,
+                face_count
+            );
+        }
+        if level_count > 32 - width.max(height).leading_zeros() {
+            anyhow::bail!("KTX2 file has too many mip levels: {}.", level_count);
+        }
+        let supercompression = match read_u32(bytes, 44)? {
+            0 => SupercompressionScheme::None,
+            1 => SupercompressionScheme::BasisLz,
+            2 => SupercompressionScheme::Zstandard,
+            scheme => anyhow::bail!("Unsupported KTX2 supercompression scheme: {}.", scheme),
+        };
+
+        let dfd_offset = read_u32(bytes, 48)? as usize;
+        // 基本の記述子ブロックのカラーモデルと、最初のサンプルのチャンネル。
+        // Color model of the basic descriptor block, and the channel of its first sample.
+        let color_model = *bytes
+            .get(dfd_offset + 12)
+            .with_context(|| "KTX2 data format descriptor is out of range.")?;
+        let channel = *bytes
+            .get(dfd_offset + 31)
+            .with_context(|| "KTX2 data format descriptor is out of range.")?
+            & 0x0F;
+        let is_uastc = format == Format::UNDEFINED && color_model == KHR_DF_MODEL_UASTC;
+        if format == Format::UNDEFINED && !is_uastc {
+            if color_model == KHR_DF_MODEL_ETC1S {
+                anyhow::bail!("ETC1S is not supported. Encode the texture as UASTC instead.");
+            }
+            anyhow::bail!("KTX2 file has an unknown color model: {}.", color_model);
+        }
+
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count as usize {
+            let entry = KTX2_HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+            let offset = read_u64(bytes, entry)? as usize;
+            let length = read_u64(bytes, entry + 8)? as usize;
+            let uncompressed_length = read_u64(bytes, entry + 16)? as usize;
+            if length == 0
+                || offset
+                    .checked_add(length)
+                    .map_or(true, |end| end > bytes.len())
+            {
+                anyhow::bail!("Mip level {} is out of range of the KTX2 file.", level);
+            }
+            levels.push(Ktx2Level {
+                offset,
+                length,
+                uncompressed_length,
+            });
+        }
+
+        Ok(Ktx2Texture {
+            format,
+            width,
+            height,
+            supercompression,
+            is_uastc,
+            has_alpha: is_uastc && UASTC_ALPHA_CHANNELS.contains(&channel),
+            levels,
+        })
+    }
+
+    /// ミップレベルの幅と高さ。<br />
+    /// Width and height of a mip level.
+    pub fn get_level_extent(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// スーパー圧縮を展開したミップレベルのデータ。<br />
+    /// Data of a mip level, with supercompression undone.
+    pub fn get_level_data<'a>(
+        &self,
+        bytes: &'a [u8],
+        level: usize,
+    ) -> anyhow::Result<Cow<'a, [u8]>> {
+        let entry = self
+            .levels
+            .get(level)
+            .with_context(|| format!("KTX2 file has no mip level {}.", level))?;
+        let data = &bytes[entry.offset..entry.offset + entry.length];
+        match self.supercompression {
+            SupercompressionScheme::None => Ok(Cow::Borrowed(data)),
+            SupercompressionScheme::Zstandard => {
+                let decompressed = zstd::stream::decode_all(data)
+                    .with_context(|| format!("Failed to decompress mip level {}.", level))?;
+                if decompressed.len() != entry.uncompressed_length {
+                    anyhow::bail!(
+                        "Mip level {} decompressed into {} bytes instead of {}.",
+                        level,
+                        decompressed.len(),
+                        entry.uncompressed_length
+                    );
+                }
+                Ok(Cow::Owned(decompressed))
+            }
+            SupercompressionScheme::BasisLz => {
+                anyhow::bail!("BasisLZ supercompression is not supported.")
+            }
+        }
+    }
+
+    /// UASTCのミップレベルを指定された形式に変換する。<br />
+    /// Transcode a UASTC mip level into the given format.
+    pub fn transcode_level(
+        &self,
+        bytes: &[u8],
+        level: usize,
+        target: TranscodeTarget,
+    ) -> anyhow::Result<Vec<u8>> {
+        if !self.is_uastc {
+            anyhow::bail!("Only UASTC textures can be transcoded.");
+        }
+        let data = self.get_level_data(bytes, level)?;
+        let (width, height) = self.get_level_extent(level);
+        let parameters = SliceParametersUastc {
+            num_blocks_x: get_block_count(width),
+            num_blocks_y: get_block_count(height),
+            has_alpha: self.has_alpha,
+            original_width: width,
+            original_height: height,
+        };
+        TRANSCODER_INIT.call_once(transcoder_init);
+        LowLevelUastcTranscoder::new()
+            .transcode_slice(
+                &data,
+                parameters,
+                DecodeFlags::empty(),
+                target.get_block_format(),
+            )
+            .map_err(|e| anyhow::anyhow!("Failed to transcode mip level {}: {:?}", level, e))
+    }
+}
+
+/// 幅か高さを覆うのに必要な4x4のブロックの数。<br />
+/// Number of 4x4 blocks needed to cover a width or a height.
+pub fn get_block_count(size: u32) -> u32 {
+    (size + BLOCK_SIZE - 1) / BLOCK_SIZE
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let value = bytes
+        .get(offset..offset + 4)
+        .with_context(|| "KTX2 header is truncated.")?;
+    Ok(u32::from_le_bytes(value.try_into()?))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> anyhow::Result<u64> {
+    let value = bytes
+        .get(offset..offset + 8)
+        .with_context(|| "KTX2 level index is truncated.")?;
+    Ok(u64::from_le_bytes(value.try_into()?))
+}
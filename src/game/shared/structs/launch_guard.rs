@@ -0,0 +1,83 @@
+use crate::game::shared::enums::WindowMode;
+use crate::game::shared::structs::Settings;
+use std::path::{Path, PathBuf};
+
+/// 起動中であることを示すファイル。正常に終了した時に消し、残っていれば前回の起動は失敗している。<br />
+/// File marking a launch in progress. It's removed on a clean exit, so if it remains, the last launch failed.
+pub const LAUNCH_GUARD_PATH: &str = "./launch.lock";
+
+/// セーフモードで起動するまでに続けて失敗した起動の回数。<br />
+/// Number of consecutive failed launches before starting in safe mode.
+pub const SAFE_MODE_THRESHOLD: u32 = 3;
+
+/// セーフモードのウィンドウの大きさ。<br />
+/// Window size in safe mode.
+pub const SAFE_MODE_WINDOW_SIZE: (u32, u32) = (1280, 720);
+
+/// 続けて失敗した起動を数え、悪い設定でずっと起動できなくならないようにセーフモードを決める。<br />
+/// Counts consecutive failed launches, deciding on safe mode so that a bad setting can't keep the game from ever starting.
+#[derive(Clone, Debug)]
+pub struct LaunchGuard {
+    path: PathBuf,
+    failed_launches: u32,
+}
+
+impl LaunchGuard {
+    /// 前回までに失敗した回数を読み込み、今回の起動を記録する。<br />
+    /// Read the number of launches that have failed so far, and record this launch.
+    pub fn begin<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let failed_launches = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| content.trim().parse::<u32>().ok())
+            .unwrap_or(0);
+        if let Err(e) = std::fs::write(&path, (failed_launches + 1).to_string()) {
+            log::warn!("Failed to write the launch guard {}: {}", path.display(), e);
+        }
+        LaunchGuard {
+            path,
+            failed_launches,
+        }
+    }
+
+    pub fn get_failed_launches(&self) -> u32 {
+        self.failed_launches
+    }
+
+    pub fn is_safe_mode(&self) -> bool {
+        self.failed_launches >= SAFE_MODE_THRESHOLD
+    }
+
+    /// セーフモードの設定を起動の設定に書き込み、変えた内容を説明する文を返す。<br />
+    /// Write the safe mode settings to the startup settings, returning sentences describing what was changed.
+    pub fn apply_safe_mode(&self, settings: &mut Settings) -> Vec<String> {
+        settings.safe_mode = true;
+        settings.window_mode = WindowMode::Windowed.get_name().to_string();
+        settings.offline = true;
+        settings.debug = false;
+        let (width, height) = SAFE_MODE_WINDOW_SIZE;
+        vec![
+            format!(
+                "The game failed to start {} times in a row.",
+                self.failed_launches
+            ),
+            "Graphics are set to the lowest quality.".to_string(),
+            format!("The window is {}x{} and not fullscreen.", width, height),
+            "Validation layers are turned off.".to_string(),
+            "You are playing offline.".to_string(),
+            "Saved settings are ignored until they're saved again.".to_string(),
+        ]
+    }
+
+    /// 正常に終了した時に呼び出し、失敗の回数を消す。<br />
+    /// Called on a clean exit, clearing the number of failures.
+    pub fn finish(&self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!(
+                "Failed to remove the launch guard {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
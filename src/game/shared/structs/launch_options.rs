@@ -0,0 +1,204 @@
+use crate::game::shared::enums::{PresentMode, WindowMode};
+use crate::game::shared::structs::{Settings, SOAK_DURATION_HOURS};
+use clap::{App, Arg, ArgMatches};
+use std::ffi::OsString;
+
+/// コマンドラインで指定する起動設定。指定したものは設定ファイルと環境変数を上書きする。<br />
+/// Launch configuration given on the command line. Anything given overrides the settings file and environment variables.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LaunchOptions {
+    /// 描画API。`VULKAN`か`DX12`。<br />
+    /// Graphics API, either `VULKAN` or `DX12`.
+    pub api: Option<String>,
+    pub server_endpoint: Option<String>,
+    pub is_offline: bool,
+    /// ゲームが始まった時に再生するカットシーンのファイル。<br />
+    /// Cutscene file played when the game starts.
+    pub scene_file: Option<String>,
+    pub window_size: Option<(u32, u32)>,
+    pub is_benchmark: bool,
+    /// 組み込みのリッスンサーバーでLANに試合をホストする。<br />
+    /// Host a match on the LAN with the embedded listen server.
+    pub is_hosting: bool,
+    /// ゲームを起動せず、素材の参照を確かめて終了する。<br />
+    /// Check the asset references and exit without starting the game.
+    pub is_validating_assets: bool,
+    /// ゲームの代わりに開く、プレビューする素材のファイル。<br />
+    /// Asset file previewed instead of starting the game.
+    pub preview_asset: Option<String>,
+    /// ソークテストを続ける時間。ソークテストをしなければ`None`。<br />
+    /// Hours to run the soak test for, or `None` without a soak test.
+    pub soak_hours: Option<f64>,
+}
+
+impl LaunchOptions {
+    /// プロセスの引数を読み込む。引数が間違っている時や`--help`の時は使い方を表示して終了する。<br />
+    /// Parse the arguments of the process. Usage is printed and the process exits on invalid arguments or `--help`.
+    pub fn from_process_args() -> Self {
+        Self::from_matches(&Self::create_app().get_matches())
+    }
+
+    pub fn from_args<I, T>(args: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::create_app().get_matches_from_safe(args)?;
+        Ok(Self::from_matches(&matches))
+    }
+
+    /// 指定された設定を起動時の設定に書き込む。設定ファイルと環境変数を読み込んだ後に呼び出し、その値を上書きする。<br />
+    /// Write the given settings to the startup settings. Called after reading the settings file and environment variables, overriding their values.
+    pub fn apply_to(&self, settings: &mut Settings) {
+        if let Some(api) = self.api.as_ref() {
+            settings.api = api.clone();
+        }
+        if let Some(server_endpoint) = self.server_endpoint.as_ref() {
+            settings.server_endpoint = server_endpoint.clone();
+        }
+        if self.is_offline
+            || self.is_benchmark
+            || self.preview_asset.is_some()
+            || self.soak_hours.is_some()
+        {
+            settings.offline = true;
+        }
+        if let Some(scene_file) = self.scene_file.as_ref() {
+            settings.scene_file = scene_file.clone();
+        }
+        if self.window_size.is_some() {
+            settings.window_mode = WindowMode::Windowed.get_name().to_string();
+        }
+        // ベンチマークは垂直同期とフレームレートの上限を外して測る。
+        // Benchmarks are measured without vertical sync or a frame rate cap.
+        if self.is_benchmark {
+            settings.present_mode = PresentMode::Immediate.get_name().to_string();
+            settings.max_fps = 0;
+        }
+    }
+
+    fn create_app() -> App<'static, 'static> {
+        App::new("Demo game")
+            .arg(
+                Arg::with_name("api")
+                    .long("api")
+                    .takes_value(true)
+                    .possible_values(&["vulkan", "dx12"])
+                    .case_insensitive(true)
+                    .help("Graphics API to render with"),
+            )
+            .arg(
+                Arg::with_name("server")
+                    .long("server")
+                    .takes_value(true)
+                    .value_name("URL")
+                    .help("Endpoint of the game server"),
+            )
+            .arg(
+                Arg::with_name("offline")
+                    .long("offline")
+                    .help("Play offline without connecting to the server"),
+            )
+            .arg(
+                Arg::with_name("scene")
+                    .long("scene")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Cutscene file played when the game starts"),
+            )
+            .arg(
+                Arg::with_name("windowed")
+                    .long("windowed")
+                    .takes_value(true)
+                    .value_name("WxH")
+                    .validator(|size| match parse_window_size(&size) {
+                        Some(_) => Ok(()),
+                        None => Err(format!("Invalid window size: {}", size)),
+                    })
+                    .help("Start windowed with the given size, e.g. 1280x720"),
+            )
+            .arg(
+                Arg::with_name("benchmark")
+                    .long("benchmark")
+                    .help("Play offline without vertical sync, report frame times and exit"),
+            )
+            .arg(
+                Arg::with_name("host")
+                    .long("host")
+                    .conflicts_with_all(&["server", "offline", "benchmark"])
+                    .help("Host a match on the local network with the embedded listen server"),
+            )
+            .arg(
+                Arg::with_name("validate-assets")
+                    .long("validate-assets")
+                    .help("Check that every referenced asset exists and parses, then exit"),
+            )
+            .arg(
+                Arg::with_name("preview")
+                    .long("preview")
+                    .takes_value(true)
+                    .value_name("ASSET")
+                    .conflicts_with_all(&["server", "host", "benchmark", "scene"])
+                    .help("Open a glTF model or a texture in the asset viewer instead of the game"),
+            )
+            .arg(
+                Arg::with_name("soak")
+                    .long("soak")
+                    .takes_value(true)
+                    .min_values(0)
+                    .value_name("HOURS")
+                    .validator(|hours| match parse_soak_hours(&hours) {
+                        Some(_) => Ok(()),
+                        None => Err(format!("Invalid soak duration: {}", hours)),
+                    })
+                    .conflicts_with_all(&["server", "host", "benchmark", "preview"])
+                    .help("Cycle offline between the title and matches with synthetic players for hours, recording resource counts to a CSV and checking for leaks"),
+            )
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Self {
+        LaunchOptions {
+            api: matches.value_of("api").map(|api| api.to_uppercase()),
+            server_endpoint: matches.value_of("server").map(|s| s.to_string()),
+            is_offline: matches.is_present("offline"),
+            scene_file: matches.value_of("scene").map(|s| s.to_string()),
+            window_size: matches.value_of("windowed").and_then(parse_window_size),
+            is_benchmark: matches.is_present("benchmark"),
+            is_hosting: matches.is_present("host"),
+            is_validating_assets: matches.is_present("validate-assets"),
+            preview_asset: matches.value_of("preview").map(|s| s.to_string()),
+            soak_hours: if matches.is_present("soak") {
+                Some(
+                    matches
+                        .value_of("soak")
+                        .and_then(parse_soak_hours)
+                        .unwrap_or(SOAK_DURATION_HOURS),
+                )
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// `1280x720`の形式のウィンドウの大きさを読み込む。<br />
+/// Parse a window size in the form of `1280x720`.
+pub fn parse_window_size(size: &str) -> Option<(u32, u32)> {
+    let mut parts = size.trim().splitn(2, |c| c == 'x' || c == 'X');
+    let width = parts.next()?.parse::<u32>().ok()?;
+    let height = parts.next()?.parse::<u32>().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+/// ソークテストの時間を読み込む。正の有限の数だけを受け付ける。<br />
+/// Parse the hours of a soak test. Only positive finite numbers are accepted.
+pub fn parse_soak_hours(hours: &str) -> Option<f64> {
+    hours
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|hours| hours.is_finite() && *hours > 0.0)
+}
@@ -1,5 +1,7 @@
 use glam::{Vec3A, Vec4};
 
+use crate::game::shared::structs::FogSettings;
+
 /// 指向性ライト<br />
 /// Directional lighting
 #[repr(C, align(16))]
@@ -9,9 +11,17 @@ pub struct Directional {
     light_position: Vec3A,
     ambient_intensity: f32,
     specular_intensity: f32,
+    fog_density: f32,
+    fog_height_falloff: f32,
+    fog_base_height: f32,
+    /// 霧が空の色になりきる距離。<br />
+    /// Distance at which the fog becomes the sky color entirely.
+    fog_end: f32,
 }
 
 impl Directional {
+    /// 霧なしで作る。<br />
+    /// Create it without fog.
     pub fn new(
         diffuse: Vec4,
         light_position: Vec3A,
@@ -23,6 +33,22 @@ impl Directional {
             light_position,
             ambient_intensity,
             specular_intensity,
+            fog_density: 0.0,
+            fog_height_falloff: 0.0,
+            fog_base_height: 0.0,
+            fog_end: f32::MAX,
+        }
+    }
+
+    /// 霧の設定を加える。霧を使う時は、ファークリップ面で空の色になりきる。<br />
+    /// Add the fog settings. When the fog is enabled, it becomes the sky color entirely at the far plane.
+    pub fn with_fog(mut self, fog: &FogSettings, far_plane: f32) -> Self {
+        if fog.is_enabled {
+            self.fog_density = fog.density;
+            self.fog_height_falloff = fog.height_falloff;
+            self.fog_base_height = fog.base_height;
+            self.fog_end = far_plane;
         }
+        self
     }
 }
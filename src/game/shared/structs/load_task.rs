@@ -0,0 +1,195 @@
+use parking_lot::{Condvar, Mutex};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use thiserror::Error;
+
+/// 読み込みのタスクが結果を返さなかった理由。<br />
+/// Reasons a loading task didn't produce a result.
+#[derive(Copy, Clone, Debug, Error, PartialEq)]
+pub enum LoadTaskError {
+    #[error("The loading task was cancelled.")]
+    Cancelled,
+    /// 結果を送る前に送信側が捨てられた。読み込みのスレッドがパニックした時など。<br />
+    /// The sender was dropped before sending a result, such as when the loading thread panicked.
+    #[error("The loading task ended without a result.")]
+    Abandoned,
+}
+
+struct TaskState<T> {
+    result: Option<T>,
+    is_finished: bool,
+    is_cancelled: bool,
+    waker: Option<Waker>,
+}
+
+struct SharedState<T> {
+    state: Mutex<TaskState<T>>,
+    condvar: Condvar,
+}
+
+impl<T> SharedState<T> {
+    fn finish(&self, result: Option<T>) {
+        let mut state = self.state.lock();
+        if state.is_finished {
+            return;
+        }
+        state.is_finished = true;
+        // 取り消された結果は、ロックを放してから捨てる。
+        // A cancelled result is dropped after the lock is released.
+        let discarded = if state.is_cancelled {
+            result
+        } else {
+            state.result = result;
+            None
+        };
+        let waker = state.waker.take();
+        drop(state);
+        self.condvar.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        drop(discarded);
+    }
+}
+
+/// 読み込みのスレッドが結果を送るための送信側。結果を送らずに捨てると、タスクは`Abandoned`で終わる。<br />
+/// The sending half used by a loading thread to deliver its result. Dropping it without sending ends the task with `Abandoned`.
+pub struct LoadSender<T> {
+    shared: Arc<SharedState<T>>,
+}
+
+impl<T> LoadSender<T> {
+    /// 結果を送る。タスクが取り消されていれば、結果はここで捨てる。<br />
+    /// Send the result. If the task was cancelled, the result is dropped here.
+    pub fn complete(self, result: T) {
+        self.shared.finish(Some(result));
+    }
+
+    /// タスクが取り消されたかどうか。重い処理の前に確かめ、無駄な読み込みを省く。<br />
+    /// Whether the task was cancelled. Checked before heavy work to skip needless loading.
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.state.lock().is_cancelled
+    }
+}
+
+impl<T> Drop for LoadSender<T> {
+    fn drop(&mut self) {
+        self.shared.finish(None);
+    }
+}
+
+/// スレッドで読み込んでいるモデルなどの結果。<br />
+/// `Future`としてtokioのランタイムで待つことも、待たずに確かめることも、ブロックして待つこともできる。<br />
+/// 捨てるとタスクは取り消される。<br />
+/// The result of a model or other resource being loaded on a thread.<br />
+/// It can be awaited as a `Future` on the tokio runtime, checked without waiting, or waited on by blocking.<br />
+/// Dropping it cancels the task.
+pub struct LoadTask<T> {
+    shared: Arc<SharedState<T>>,
+}
+
+impl<T> LoadTask<T> {
+    /// 送信側とタスクの組を作る。<br />
+    /// Create a pair of a sender and a task.
+    pub fn channel() -> (LoadSender<T>, LoadTask<T>) {
+        let shared = Arc::new(SharedState {
+            state: Mutex::new(TaskState {
+                result: None,
+                is_finished: false,
+                is_cancelled: false,
+                waker: None,
+            }),
+            condvar: Condvar::new(),
+        });
+        (
+            LoadSender {
+                shared: shared.clone(),
+            },
+            LoadTask { shared },
+        )
+    }
+
+    /// タスクを取り消す。既に届いている結果も捨てる。<br />
+    /// Cancel the task. A result that has already arrived is dropped as well.
+    pub fn cancel(&self) {
+        let mut state = self.shared.state.lock();
+        if state.is_cancelled {
+            return;
+        }
+        state.is_cancelled = true;
+        let discarded = state.result.take();
+        let waker = state.waker.take();
+        drop(state);
+        self.shared.condvar.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        drop(discarded);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.state.lock().is_cancelled
+    }
+
+    /// 結果が届いたか、タスクが終わったかどうか。<br />
+    /// Whether the result has arrived or the task has otherwise ended.
+    pub fn is_finished(&self) -> bool {
+        let state = self.shared.state.lock();
+        state.is_finished || state.is_cancelled
+    }
+
+    /// 待たずに結果を確かめる。まだ届いていなければ`None`を返す。<br />
+    /// Check the result without waiting. Returns `None` if it hasn't arrived yet.
+    pub fn try_take(&mut self) -> anyhow::Result<Option<T>> {
+        let mut state = self.shared.state.lock();
+        Self::take_result(&mut state).transpose()
+    }
+
+    /// 結果が届くまでスレッドをブロックして待つ。<br />
+    /// Block the thread until the result arrives.
+    pub fn wait(&mut self) -> anyhow::Result<T> {
+        let mut state = self.shared.state.lock();
+        loop {
+            if let Some(result) = Self::take_result(&mut state) {
+                return result;
+            }
+            self.shared.condvar.wait(&mut state);
+        }
+    }
+
+    fn take_result(state: &mut TaskState<T>) -> Option<anyhow::Result<T>> {
+        if state.is_cancelled {
+            return Some(Err(LoadTaskError::Cancelled.into()));
+        }
+        if let Some(result) = state.result.take() {
+            return Some(Ok(result));
+        }
+        if state.is_finished {
+            return Some(Err(LoadTaskError::Abandoned.into()));
+        }
+        None
+    }
+}
+
+impl<T> Future for LoadTask<T> {
+    type Output = anyhow::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock();
+        match Self::take_result(&mut state) {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for LoadTask<T> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
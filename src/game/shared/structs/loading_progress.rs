@@ -0,0 +1,31 @@
+/// シーンの読み込みの進み具合。読み込み画面のプログレスバーに使う。<br />
+/// Progress of loading a scene, used for the progress bar of the loading screen.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct LoadingProgress {
+    pub completed_tasks: usize,
+    pub total_tasks: usize,
+    pub textures_uploaded: usize,
+}
+
+impl LoadingProgress {
+    pub fn new(completed_tasks: usize, total_tasks: usize) -> Self {
+        LoadingProgress {
+            completed_tasks,
+            total_tasks,
+            textures_uploaded: 0,
+        }
+    }
+
+    /// 終わったタスクの割合。タスクがなければ読み込みは終わっている。<br />
+    /// Fraction of the finished tasks. Without any task, loading is already finished.
+    pub fn get_fraction(&self) -> f32 {
+        if self.total_tasks == 0 {
+            return 1.0;
+        }
+        (self.completed_tasks.min(self.total_tasks) as f32) / (self.total_tasks as f32)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.completed_tasks >= self.total_tasks
+    }
+}
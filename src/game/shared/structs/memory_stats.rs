@@ -0,0 +1,172 @@
+/// 予算に対する使用量がこの割合を超えれば警告する。<br />
+/// Warn once the usage exceeds this ratio of the budget.
+pub const MEMORY_BUDGET_WARNING_RATIO: f64 = 0.9;
+
+/// VMAの統計を取り直す間隔（秒）。統計の計算は全てのブロックを回るので毎フレームはしない。<br />
+/// Interval in seconds between refreshes of the VMA statistics. Calculating them walks every block, so it isn't done every frame.
+pub const MEMORY_STATS_INTERVAL: f64 = 1.0;
+
+const BYTES_PER_MEBIBYTE: f64 = 1024.0 * 1024.0;
+
+/// 一つのメモリーヒープの使用量と予算。<br />
+/// Usage and budget of a single memory heap.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct HeapUsage {
+    pub heap_index: usize,
+    pub is_device_local: bool,
+    pub size: u64,
+    /// VMAが確保したブロックの合計。<br />
+    /// Total of the blocks allocated by VMA.
+    pub block_bytes: u64,
+    /// ブロックの中で割り当てに使われている量。<br />
+    /// Amount in the blocks used by allocations.
+    pub used_bytes: u64,
+    pub allocation_count: usize,
+    pub block_count: usize,
+    /// このプロセスが使える見込みの量。<br />
+    /// Amount this process is expected to be able to use.
+    pub budget_bytes: u64,
+    /// 予算と比べるこのプロセスの使用量。VMAの外の確保も含むことがある。<br />
+    /// Usage of this process compared against the budget. May include allocations outside VMA.
+    pub usage_bytes: u64,
+}
+
+impl HeapUsage {
+    pub fn get_budget_ratio(&self) -> f64 {
+        if self.budget_bytes == 0 {
+            0.0
+        } else {
+            self.usage_bytes as f64 / self.budget_bytes as f64
+        }
+    }
+
+    pub fn is_near_budget(&self) -> bool {
+        self.budget_bytes > 0 && self.get_budget_ratio() >= MEMORY_BUDGET_WARNING_RATIO
+    }
+
+    pub fn get_label(&self) -> String {
+        if self.is_device_local {
+            format!("Heap {} (device local)", self.heap_index)
+        } else {
+            format!("Heap {} (host)", self.heap_index)
+        }
+    }
+}
+
+/// VMAの統計をヒープごとにまとめたもの。ストリーミングがメモリーの逼迫を判断するのに使う。<br />
+/// VMA statistics gathered per heap. Used by streaming to judge memory pressure.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryStats {
+    pub heaps: Vec<HeapUsage>,
+    pub block_bytes: u64,
+    pub used_bytes: u64,
+    pub allocation_count: usize,
+    pub block_count: usize,
+}
+
+impl MemoryStats {
+    /// デバイスローカルのヒープの中で、予算に対して一番使われている割合。<br />
+    /// The highest ratio of usage to budget among the device local heaps.
+    pub fn get_device_local_pressure(&self) -> f64 {
+        self.heaps
+            .iter()
+            .filter(|heap| heap.is_device_local)
+            .map(HeapUsage::get_budget_ratio)
+            .fold(0.0, f64::max)
+    }
+
+    /// デバイスローカルのヒープが予算の`ratio`に達するまでに使える量。一番余裕のないヒープで決まる。<br />
+    /// Amount the device local heaps can take before reaching `ratio` of their budget, decided by the tightest heap.
+    pub fn get_device_local_headroom(&self, ratio: f64) -> u64 {
+        self.heaps
+            .iter()
+            .filter(|heap| heap.is_device_local)
+            .map(|heap| {
+                ((heap.budget_bytes as f64 * ratio) as u64).saturating_sub(heap.usage_bytes)
+            })
+            .min()
+            .unwrap_or_default()
+    }
+
+    pub fn is_near_budget(&self) -> bool {
+        self.heaps.iter().any(HeapUsage::is_near_budget)
+    }
+
+    /// 予算に近づいたヒープごとの警告。<br />
+    /// A warning for each heap nearing its budget.
+    pub fn get_warnings(&self) -> Vec<String> {
+        self.heaps
+            .iter()
+            .filter(|heap| heap.is_near_budget())
+            .map(|heap| {
+                format!(
+                    "{} is at {:.0}% of its budget ({} / {}).",
+                    heap.get_label(),
+                    heap.get_budget_ratio() * 100.0,
+                    format_mebibytes(heap.usage_bytes),
+                    format_mebibytes(heap.budget_bytes)
+                )
+            })
+            .collect()
+    }
+}
+
+pub fn format_mebibytes(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / BYTES_PER_MEBIBYTE)
+}
+
+/// 一定の間隔でメモリーの統計を取り直し、予算に近づいた時だけ警告を出す。<br />
+/// Refreshes the memory statistics at a fixed interval and raises warnings only when nearing the budget.
+#[derive(Clone, Debug)]
+pub struct MemoryMonitor {
+    elapsed: f64,
+    stats: Option<MemoryStats>,
+    is_near_budget: bool,
+}
+
+impl Default for MemoryMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryMonitor {
+    /// 最初の`tick`ですぐに統計を取る。<br />
+    /// The statistics are taken right away on the first `tick`.
+    pub fn new() -> Self {
+        MemoryMonitor {
+            elapsed: MEMORY_STATS_INTERVAL,
+            stats: None,
+            is_near_budget: false,
+        }
+    }
+
+    /// 時間を進め、統計を取り直す時かどうかを返す。<br />
+    /// Advance the time and return whether the statistics should be refreshed.
+    pub fn tick(&mut self, delta_time: f64) -> bool {
+        self.elapsed += delta_time;
+        if self.elapsed < MEMORY_STATS_INTERVAL {
+            return false;
+        }
+        self.elapsed = 0.0;
+        true
+    }
+
+    /// 新しい統計を覚え、予算に近づいたばかりならその警告を返す。<br />
+    /// Keep the new statistics, returning their warnings if the budget has just been neared.
+    pub fn set_stats(&mut self, stats: MemoryStats) -> Vec<String> {
+        let was_near_budget = self.is_near_budget;
+        self.is_near_budget = stats.is_near_budget();
+        let warnings = if self.is_near_budget && !was_near_budget {
+            stats.get_warnings()
+        } else {
+            vec![]
+        };
+        self.stats = Some(stats);
+        warnings
+    }
+
+    pub fn get_stats(&self) -> Option<&MemoryStats> {
+        self.stats.as_ref()
+    }
+}
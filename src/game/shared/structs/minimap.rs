@@ -0,0 +1,243 @@
+use glam::{Vec2, Vec3A};
+
+use crate::game::shared::structs::{
+    Anchor, AtlasRegion, Primitive, Sprite, SpriteAtlas, SpriteBatch,
+};
+
+/// アトラスの中のミニマップの画像の名前。<br />
+/// Name of the minimap image inside the atlas.
+pub const MINIMAP_SPRITE: &str = "minimap";
+
+/// ミニマップの画像の一辺のピクセル数。地形の一辺の頂点数と同じにしている。<br />
+/// Number of pixels per side of the minimap image, matching the number of vertices per side of the terrain.
+pub const MINIMAP_RESOLUTION: u32 = 128;
+
+/// 基準の高さでの、画面に描くミニマップの一辺の大きさ。<br />
+/// Length of a side of the minimap drawn on the screen, at the reference height.
+pub const MINIMAP_SCREEN_SIZE: f32 = 180.0;
+
+/// 画面の右上の角からの隙間。<br />
+/// Gap from the top right corner of the screen.
+pub const MINIMAP_MARGIN: f32 = 16.0;
+
+pub const MINIMAP_MIN_ZOOM: f32 = 1.0;
+pub const MINIMAP_MAX_ZOOM: f32 = 8.0;
+pub const MINIMAP_DEFAULT_ZOOM: f32 = 2.0;
+
+/// 基準の高さでの点の一辺の大きさ。<br />
+/// Length of a side of a blip, at the reference height.
+const BLIP_SIZE: f32 = 6.0;
+const FRAME_WIDTH: f32 = 2.0;
+const FRAME_COLOR: [u8; 4] = [16, 16, 16, 200];
+const LOW_COLOR: [f32; 3] = [0.16, 0.32, 0.12];
+const HIGH_COLOR: [f32; 3] = [0.86, 0.84, 0.78];
+
+/// ミニマップに描くエンティティの点。<br />
+/// A blip of an entity drawn on the minimap.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MinimapBlip {
+    pub position: Vec3A,
+    pub color: [u8; 4],
+}
+
+/// 地形を真上から見た小さな地図。地形は変わった時だけ描き、エンティティの点は毎フレーム重ねる。<br />
+/// A small map of the terrain seen from straight above. The terrain is only drawn when it changes, while entity blips are overlaid every frame.
+#[derive(Clone, Debug)]
+pub struct Minimap {
+    resolution: u32,
+    pixels: Vec<u8>,
+    /// 画像の左上に当たるワールドのXZ座標。<br />
+    /// World XZ coordinates at the top left of the image.
+    origin: Vec2,
+    /// 画像が覆うワールドの一辺の長さ。<br />
+    /// Length of a side of the world covered by the image.
+    extent: f32,
+    zoom: f32,
+    is_visible: bool,
+    has_terrain: bool,
+}
+
+impl Minimap {
+    pub fn new(resolution: u32) -> Self {
+        Minimap {
+            resolution,
+            pixels: vec![0; (resolution * resolution * 4) as usize],
+            origin: Vec2::zero(),
+            extent: 1.0,
+            zoom: MINIMAP_DEFAULT_ZOOM,
+            is_visible: true,
+            has_terrain: false,
+        }
+    }
+
+    /// 格子状の地形を真上から描く。低い所は緑、高い所は明るい色にし、傾きで陰を付ける。<br />
+    /// Draw a grid terrain from straight above. Low ground is green and high ground bright, shaded by the slope.
+    pub fn render_terrain(&mut self, primitive: &Primitive, position: Vec3A) -> anyhow::Result<()> {
+        let vertex_count = (primitive.vertices.len() as f32).sqrt().round() as usize;
+        if vertex_count < 2 || vertex_count * vertex_count != primitive.vertices.len() {
+            return Err(anyhow::anyhow!(
+                "The terrain has {} vertices, which isn't a square grid.",
+                primitive.vertices.len()
+            ));
+        }
+        let first = primitive.vertices[0].position;
+        let last = primitive.vertices[primitive.vertices.len() - 1].position;
+        let (min_height, max_height) = primitive
+            .vertices
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), vertex| {
+                (min.min(vertex.position.y), max.max(vertex.position.y))
+            });
+        let height_range = (max_height - min_height).max(f32::EPSILON);
+        let height_at = |x: f32, z: f32| -> f32 {
+            let max_index = (vertex_count - 1) as f32;
+            let x = (x * max_index).max(0.0).min(max_index);
+            let z = (z * max_index).max(0.0).min(max_index);
+            let (x0, z0) = (x.floor() as usize, z.floor() as usize);
+            let (x1, z1) = (
+                (x0 + 1).min(vertex_count - 1),
+                (z0 + 1).min(vertex_count - 1),
+            );
+            let (tx, tz) = (x.fract(), z.fract());
+            let height = |x: usize, z: usize| primitive.vertices[z * vertex_count + x].position.y;
+            let top = height(x0, z0) + (height(x1, z0) - height(x0, z0)) * tx;
+            let bottom = height(x0, z1) + (height(x1, z1) - height(x0, z1)) * tx;
+            top + (bottom - top) * tz
+        };
+
+        let low_color = Vec3A::new(LOW_COLOR[0], LOW_COLOR[1], LOW_COLOR[2]);
+        let high_color = Vec3A::new(HIGH_COLOR[0], HIGH_COLOR[1], HIGH_COLOR[2]);
+        let resolution = self.resolution as usize;
+        let texel = 1.0 / self.resolution as f32;
+        for y in 0..resolution {
+            for x in 0..resolution {
+                let u = (x as f32 + 0.5) * texel;
+                let v = (y as f32 + 0.5) * texel;
+                let height = height_at(u, v);
+                let t = (height - min_height) / height_range;
+                // 左上から光が当たるように、左上の隣との高さの差で明るさを変える。
+                // Vary the brightness by the height difference to the upper left neighbour, as if lit from the upper left.
+                let slope = (height - height_at(u - texel, v - texel)) / height_range;
+                let shade = (1.0 + slope * 8.0).max(0.6).min(1.3);
+                let color = (low_color + (high_color - low_color) * t) * shade;
+                let offset = (y * resolution + x) * 4;
+                self.pixels[offset] = (color.x.min(1.0) * 255.0) as u8;
+                self.pixels[offset + 1] = (color.y.min(1.0) * 255.0) as u8;
+                self.pixels[offset + 2] = (color.z.min(1.0) * 255.0) as u8;
+                self.pixels[offset + 3] = 255;
+            }
+        }
+        self.origin = Vec2::new(position.x + first.x, position.z + first.z);
+        self.extent = (last.x - first.x).max(last.z - first.z).max(f32::EPSILON);
+        self.has_terrain = true;
+        Ok(())
+    }
+
+    /// ワールドの位置を、地図全体に対する割合にする。<br />
+    /// Convert a world position to a fraction of the whole map.
+    pub fn world_to_map(&self, position: Vec3A) -> Vec2 {
+        (Vec2::new(position.x, position.z) - self.origin) / self.extent
+    }
+
+    /// 地図の真ん中のワールドの位置。高さは0。<br />
+    /// World position at the middle of the map, at a height of zero.
+    pub fn get_center(&self) -> Vec3A {
+        let center = self.origin + Vec2::splat(self.extent * 0.5);
+        Vec3A::new(center.x, 0.0, center.y)
+    }
+
+    /// 中心の周りで見える範囲を、地図全体に対する割合で返す。地図の外は見せないように寄せる。<br />
+    /// The visible window around the center as fractions of the whole map. It's shifted so nothing outside the map is shown.
+    pub fn get_view(&self, center: Vec3A) -> (Vec2, Vec2) {
+        let size = 1.0 / self.zoom;
+        let half = Vec2::splat(size * 0.5);
+        let center = self.world_to_map(center).max(half).min(Vec2::one() - half);
+        (center - half, center + half)
+    }
+
+    /// 地図と、見える範囲にある点をスプライトとして加える。<br />
+    /// Add the map and the blips inside the visible window as sprites.
+    pub fn draw(
+        &self,
+        batch: &mut SpriteBatch,
+        atlas: &SpriteAtlas,
+        center: Vec3A,
+        blips: &[MinimapBlip],
+    ) {
+        if !self.is_visible || !self.has_terrain {
+            return;
+        }
+        let region = match atlas.get_region(MINIMAP_SPRITE) {
+            Some(region) => region,
+            None => return,
+        };
+        let (view_min, view_max) = self.get_view(center);
+        let uv_size = region.uv_max - region.uv_min;
+        let view_region = AtlasRegion {
+            uv_min: region.uv_min + uv_size * view_min,
+            uv_max: region.uv_min + uv_size * view_max,
+            ..region
+        };
+        // 右上の角に合わせた地図の左上。点はここからの割合で置く。
+        // Top left of the map placed at the top right corner. Blips are placed as fractions from here.
+        let top_left = Vec2::new(-MINIMAP_MARGIN - MINIMAP_SCREEN_SIZE, MINIMAP_MARGIN);
+        let size = Vec2::splat(MINIMAP_SCREEN_SIZE);
+        batch.fill_rect(
+            atlas,
+            Anchor::TopRight,
+            Vec2::new(-MINIMAP_MARGIN + FRAME_WIDTH, MINIMAP_MARGIN - FRAME_WIDTH),
+            size + Vec2::splat(FRAME_WIDTH * 2.0),
+            FRAME_COLOR,
+        );
+        batch.draw(
+            Sprite::new(view_region)
+                .anchored(Anchor::TopRight, top_left)
+                .with_pivot(Vec2::zero())
+                .with_size(size),
+        );
+        let view_size = view_max - view_min;
+        for blip in blips.iter() {
+            let fraction = (self.world_to_map(blip.position) - view_min) / view_size;
+            if fraction.cmplt(Vec2::zero()).any() || fraction.cmpgt(Vec2::one()).any() {
+                continue;
+            }
+            batch.draw(
+                Sprite::new(atlas.get_white_region())
+                    .anchored(Anchor::TopRight, top_left + fraction * size)
+                    .with_pivot(Vec2::splat(0.5))
+                    .with_size(Vec2::splat(BLIP_SIZE))
+                    .with_color(blip.color),
+            );
+        }
+    }
+
+    pub fn get_pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn get_resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    pub fn get_zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// 拡大率を設定する。一倍で地図全体が見える。<br />
+    /// Set the zoom. The whole map is visible at one.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.max(MINIMAP_MIN_ZOOM).min(MINIMAP_MAX_ZOOM);
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    pub fn set_visible(&mut self, is_visible: bool) {
+        self.is_visible = is_visible;
+    }
+
+    pub fn has_terrain(&self) -> bool {
+        self.has_terrain
+    }
+}
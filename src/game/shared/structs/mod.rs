@@ -1,38 +1,140 @@
 pub mod animation;
+pub mod animation_state_machine;
+pub mod asset_preview;
+pub mod asset_validation;
+pub mod benchmark;
 pub mod blend_mode;
+pub mod bvh;
 pub mod completed_tasks;
 pub mod counts;
+pub mod debug_draw;
+pub mod deletion_queue;
+pub mod diagnostics;
+pub mod fixed_timestep;
+pub mod fog;
+pub mod frame_info;
+pub mod frame_profiler;
 pub mod frustum;
 pub mod games;
+pub mod graphics_settings;
+pub mod indirect_draw;
+pub mod inspector;
+pub mod job_queue;
+pub mod kill_cam;
+pub mod ktx2_texture;
+pub mod launch_guard;
+pub mod launch_options;
 pub mod lighting;
+pub mod load_task;
+pub mod loading_progress;
+pub mod memory_stats;
+pub mod minimap;
+pub mod model_cache;
 pub mod models;
+pub mod music_set;
+pub mod occlusion_culling;
+pub mod particle;
+pub mod placeholder_assets;
 pub mod player;
+pub mod post_process;
+pub mod presence;
 pub mod primitives;
 pub mod push_constant;
+pub mod ray;
+pub mod recorded_commands;
+pub mod render_features;
+pub mod render_graph;
+pub mod renderable_pool;
+pub mod save_game;
+pub mod settings;
+pub mod skeleton_pose;
+pub mod soak;
+pub mod spring_bone;
+pub mod sprite_batch;
+pub mod status_bar;
+pub mod surface_material_map;
 pub mod terrain;
+pub mod texture_streaming;
+pub mod time_of_day;
+pub mod timeline;
 pub mod view_projection;
 pub mod waitable_tasks;
+pub mod water_surface;
+pub mod water_volume;
+pub mod world_bounds;
 
 pub use animation::*;
+pub use animation_state_machine::*;
+pub use asset_preview::*;
+pub use asset_validation::*;
+pub use benchmark::*;
 pub use blend_mode::BlendMode;
+pub use bvh::*;
 pub use completed_tasks::CompletedTasks;
 pub use counts::Counts;
+pub use debug_draw::*;
+pub use deletion_queue::DeletionQueue;
+pub use diagnostics::*;
+pub use fixed_timestep::*;
+pub use fog::*;
+pub use frame_info::FrameInfo;
+pub use frame_profiler::*;
+pub use frustum::Frustum;
+pub use graphics_settings::*;
+pub use indirect_draw::*;
+pub use inspector::*;
+pub use job_queue::*;
+pub use kill_cam::*;
+pub use ktx2_texture::*;
+pub use launch_guard::*;
+pub use launch_options::*;
 pub use lighting::*;
+pub use load_task::*;
+pub use loading_progress::LoadingProgress;
+pub use memory_stats::*;
+pub use minimap::*;
+pub use model_cache::*;
 pub use models::instanced_model::InstancedModel;
 pub use models::instanced_vertex::*;
 pub use models::joint::Joint;
 pub use models::mesh::*;
 pub use models::model::Model;
 pub use models::model_metadata::ModelMetaData;
+pub use models::morph_targets::*;
 pub use models::position_info::PositionInfo;
 pub use models::skinned_mesh::*;
 pub use models::skinned_model::*;
 pub use models::skinned_vertex::SkinnedVertex;
 pub use models::ssbo::SSBO;
 pub use models::vertex::Vertex;
+pub use music_set::MusicSet;
+pub use occlusion_culling::*;
+pub use particle::*;
+pub use placeholder_assets::*;
 pub use player::Player;
+pub use post_process::*;
+pub use presence::*;
 pub use primitives::*;
 pub use push_constant::PushConstant;
+pub use ray::Ray;
+pub use recorded_commands::*;
+pub use render_features::RenderFeatures;
+pub use render_graph::{PassOutput, RenderGraph, RenderPassNode};
+pub use renderable_pool::RenderablePool;
+pub use save_game::*;
+pub use settings::*;
+pub use skeleton_pose::*;
+pub use soak::*;
+pub use spring_bone::*;
+pub use sprite_batch::*;
+pub use status_bar::*;
+pub use surface_material_map::*;
 pub use terrain::*;
+pub use texture_streaming::*;
+pub use time_of_day::*;
+pub use timeline::*;
 pub use view_projection::ViewProjection;
 pub use waitable_tasks::WaitableTasks;
+pub use water_surface::*;
+pub use water_volume::*;
+pub use world_bounds::*;
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+/// キャッシュを引いた結果。<br />
+/// Result of looking up the cache.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CacheLookup<T> {
+    /// 読み込み済みのモデル。これを複製して使う。<br />
+    /// A loaded model. Clone it to use it.
+    Hit(T),
+    /// 他のインスタンスのために読み込み中。終わってから複製する。<br />
+    /// Being loaded for another instance. Clone it once that finishes.
+    Pending,
+    /// キャッシュにない。呼び出し側が読み込む。<br />
+    /// Not in the cache. The caller loads it.
+    Miss,
+}
+
+#[derive(Clone, Debug)]
+struct CacheEntry<T> {
+    model: Option<T>,
+    reference_count: usize,
+}
+
+/// ファイルのパスをキーにした読み込み済みモデルのキャッシュ。<br />
+/// GPUのリソースを持つ最初のモデルを保持し、最後のインスタンスが消えた時にそれを返して解放させる。<br />
+/// Cache of loaded models keyed by file path.<br />
+/// It keeps the first model, which owns the GPU resources, and hands it back for disposal when the last instance is removed.
+#[derive(Clone, Debug)]
+pub struct ModelCache<T> {
+    entries: HashMap<String, CacheEntry<T>>,
+}
+
+impl<T> Default for ModelCache<T>
+where
+    T: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ModelCache<T>
+where
+    T: Clone,
+{
+    pub fn new() -> Self {
+        ModelCache {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// インスタンスを一つ増やす。キャッシュになければ読み込み中として登録し、`Miss`を返す。<br />
+    /// Add an instance. If the model isn't cached, it's registered as pending and `Miss` is returned.
+    pub fn acquire(&mut self, key: &str) -> CacheLookup<T> {
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.reference_count += 1;
+                match entry.model.as_ref() {
+                    Some(model) => CacheLookup::Hit(model.clone()),
+                    None => CacheLookup::Pending,
+                }
+            }
+            None => {
+                self.entries.insert(
+                    key.to_string(),
+                    CacheEntry {
+                        model: None,
+                        reference_count: 1,
+                    },
+                );
+                CacheLookup::Miss
+            }
+        }
+    }
+
+    /// 読み込みが終わったモデルを登録する。読み込み中の項目がなければ何もせず`false`を返す。<br />
+    /// Register a model that finished loading. Returns `false` without doing anything if no entry is pending.
+    pub fn insert(&mut self, key: &str, model: T) -> bool {
+        match self.entries.get_mut(key) {
+            Some(entry) if entry.model.is_none() => {
+                entry.model = Some(model);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<T> {
+        self.entries.get(key).and_then(|e| e.model.clone())
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn get_reference_count(&self, key: &str) -> usize {
+        self.entries
+            .get(key)
+            .map(|e| e.reference_count)
+            .unwrap_or_default()
+    }
+
+    /// インスタンスを一つ減らす。最後のインスタンスなら項目を消し、解放するモデルを返す。<br />
+    /// Remove an instance. For the last instance the entry is removed and the model to dispose is returned.
+    pub fn release(&mut self, key: &str) -> Option<T> {
+        let entry = self.entries.get_mut(key)?;
+        entry.reference_count = entry.reference_count.saturating_sub(1);
+        if entry.reference_count > 0 {
+            return None;
+        }
+        self.entries.remove(key).and_then(|e| e.model)
+    }
+
+    /// 全ての項目を消し、残っていたモデルを返す。<br />
+    /// Remove every entry and return the models that were left.
+    pub fn drain(&mut self) -> Vec<T> {
+        self.entries.drain().filter_map(|(_, e)| e.model).collect()
+    }
+}
+
+/// キャッシュのキー。同じファイルを指す違う書き方のパスが同じキーになるよう、正規化する。<br />
+/// Key of the cache. Paths are canonicalized so different spellings of the same file share a key.
+pub fn get_cache_key(file_name: &str) -> String {
+    match std::fs::canonicalize(Path::new(file_name)) {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(_) => file_name.to_string(),
+    }
+}
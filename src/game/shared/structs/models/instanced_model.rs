@@ -1,25 +1,21 @@
-use crate::game::graphics::vk::{Buffer, Graphics, Image, Pipeline, ThreadPool};
+use crate::game::graphics::vk::{Buffer, BufferUpload, Graphics, Image, RenderContext};
+use crate::game::shared::components::Bounds;
 use crate::game::shared::enums::ShaderType;
 use crate::game::shared::structs::{
-    InstanceData, Model, ModelMetaData, PositionInfo, PushConstant,
+    FrameInfo, InstanceData, LoadTask, Model, ModelMetaData, PositionInfo, PushConstant,
 };
 use crate::game::structs::Vertex;
-use crate::game::traits::{Disposable, GraphicsBase, Mappable, Renderable};
-use crate::game::CommandData;
+use crate::game::traits::{Disposable, GraphicsBase, Renderable};
+use crate::game::{CommandData, ReleasedCommandBuffers};
 use ash::version::DeviceV1_0;
 use ash::vk::{
-    BufferUsageFlags, CommandBuffer, CommandBufferBeginInfo, CommandBufferInheritanceInfo,
-    CommandBufferUsageFlags, DescriptorSet, IndexType, MemoryPropertyFlags, PipelineBindPoint,
-    Rect2D, ShaderStageFlags, Viewport,
+    AccessFlags, BufferUsageFlags, CommandBuffer, CommandBufferBeginInfo, CommandBufferUsageFlags,
+    IndexType, MemoryPropertyFlags, PipelineBindPoint, PipelineStageFlags, ShaderStageFlags,
 };
-use ash::Device;
-use crossbeam::channel::*;
-use crossbeam::sync::ShardedLock;
 use glam::{Vec3A, Vec4};
 use parking_lot::RwLock;
 use slotmap::DefaultKey;
 use std::mem::ManuallyDrop;
-use std::sync::atomic::AtomicPtr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Weak};
 
@@ -57,13 +53,18 @@ impl InstancedModel<Graphics, Buffer, CommandBuffer, Image> {
         ssbo_index: usize,
         instance_data: Vec<InstanceData>,
         entity: DefaultKey,
-    ) -> anyhow::Result<Receiver<Self>> {
+    ) -> anyhow::Result<LoadTask<Self>> {
         log::info!("Loading instanced model: {}...", file_name);
         let graphics_arc = graphics
             .upgrade()
             .expect("Failed to upgrade graphics handle for model.");
-        let (model_send, model_recv) = bounded(0);
+        let (model_send, model_recv) = LoadTask::channel();
+        // モデル自体は読み込みのスレッドで読むので、それを待つこのタスクが読み込みのスレッドを塞がないよう、別のスレッドで待つ。
+        // The model itself is loaded on a streaming thread, so this task waiting for it runs elsewhere instead of blocking one.
         rayon::spawn(move || {
+            if model_send.is_cancelled() {
+                return;
+            }
             let loaded_model = Model::new(
                 file_name,
                 graphics,
@@ -77,14 +78,11 @@ impl InstancedModel<Graphics, Buffer, CommandBuffer, Image> {
                 entity,
             )
             .expect("Failed to load instanced model data.")
-            .recv()
+            .wait()
             .expect("Failed to receive instanced model data.");
             let model_index = model_index.fetch_add(1, Ordering::SeqCst);
-            let inflight_frame_count = std::env::var("INFLIGHT_BUFFER_COUNT")
-                .unwrap()
-                .parse::<usize>()
-                .unwrap();
             let graphics_lock = graphics_arc.read();
+            let inflight_frame_count = graphics_lock.inflight_buffer_count;
             let mut command_data = std::collections::HashMap::new();
             for i in 0..inflight_frame_count {
                 let (command_pool, command_buffer) =
@@ -118,9 +116,7 @@ impl InstancedModel<Graphics, Buffer, CommandBuffer, Image> {
             /*loaded_instance
             .create_vertex_and_index_buffer(graphics_arc)
             .expect("Failed to create vertex and index buffer for instance.");*/
-            model_send
-                .send(loaded_instance)
-                .expect("Failed to send instanced model.");
+            model_send.complete(loaded_instance);
         });
         Ok(model_recv)
     }
@@ -164,36 +160,29 @@ impl InstancedModel<Graphics, Buffer, CommandBuffer, Image> {
     ) -> anyhow::Result<Buffer> {
         let buffer_size = (std::mem::size_of::<InstanceData>() * instance_data.len()) as u64;
         let graphics_lock = graphics.read();
-        let mut staging_buffer = Buffer::new(
-            Arc::downgrade(&graphics_lock.logical_device),
-            buffer_size,
-            BufferUsageFlags::TRANSFER_SRC,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-            Arc::downgrade(&graphics_lock.allocator),
-        );
-        unsafe {
-            let mapped = staging_buffer.map_memory(buffer_size, 0);
-            std::ptr::copy_nonoverlapping(
-                instance_data.as_ptr() as *const std::ffi::c_void,
-                mapped,
-                buffer_size as usize,
-            );
-        }
+        let staging_ring = graphics_lock.get_staging_ring();
+        let staging = staging_ring.write(instance_data)?;
         let instance_buffer = Buffer::new(
             Arc::downgrade(&graphics_lock.logical_device),
             buffer_size,
             BufferUsageFlags::VERTEX_BUFFER | BufferUsageFlags::TRANSFER_DST,
             MemoryPropertyFlags::DEVICE_LOCAL,
             Arc::downgrade(&graphics_lock.allocator),
-        );
+        )?;
         let cmd_pool = graphics_lock.get_idle_command_pool();
-        instance_buffer.copy_buffer(
-            &staging_buffer,
-            buffer_size,
-            *cmd_pool.lock(),
-            *graphics_lock.graphics_queue.lock(),
-            None,
-        );
+        let transfer_queue = graphics_lock.get_transfer_queue();
+        drop(graphics_lock);
+        transfer_queue.upload_buffers(
+            &cmd_pool,
+            &[BufferUpload {
+                source: &staging,
+                destination: &instance_buffer,
+                dst_offset: 0,
+                size: buffer_size,
+                dst_access: AccessFlags::VERTEX_ATTRIBUTE_READ,
+                dst_stage: PipelineStageFlags::VERTEX_INPUT,
+            }],
+        )?;
         Ok(instance_buffer)
     }
 }
@@ -234,6 +223,23 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         self.model.get_command_buffers(frame_index)
     }
 
+    fn append_command_buffers(&self, frame_index: usize, command_buffers: &mut Vec<CommandBuffer>) {
+        self.model
+            .append_command_buffers(frame_index, command_buffers);
+    }
+
+    fn take_command_buffers(&mut self) -> ReleasedCommandBuffers<CommandBuffer> {
+        self.model.take_command_buffers()
+    }
+
+    fn invalidate_command_buffers(&mut self) {
+        self.model.invalidate_command_buffers();
+    }
+
+    fn get_entity(&self) -> DefaultKey {
+        self.model.entity
+    }
+
     fn get_model_metadata(&self) -> ModelMetaData {
         self.model.get_model_metadata()
     }
@@ -246,19 +252,31 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         self.ssbo_index
     }
 
-    fn render(
-        &self,
-        inheritance_info: Arc<AtomicPtr<CommandBufferInheritanceInfo>>,
-        push_constant: PushConstant,
-        viewport: Viewport,
-        scissor: Rect2D,
-        device: Arc<Device>,
-        pipeline: Arc<ShardedLock<ManuallyDrop<Pipeline>>>,
-        descriptor_set: DescriptorSet,
-        thread_pool: Arc<ThreadPool>,
-        frame_index: usize,
-    ) {
-        let thread_count = thread_pool.thread_count;
+    fn get_texture_usages(&self) -> Vec<(usize, Option<Bounds>)> {
+        // インスタンスは広く散らばるので、境界を渡さずに一番高い解像度を要求する。
+        // Instances are spread widely, so the highest resolution is requested without bounds.
+        self.model
+            .get_texture_usages()
+            .into_iter()
+            .map(|(texture_index, _)| (texture_index, None))
+            .collect()
+    }
+
+    fn render(&self, context: &RenderContext, frame_info: FrameInfo) {
+        let RenderContext {
+            inheritance_info,
+            viewport,
+            scissor,
+            device,
+            pipeline,
+            descriptor_sets,
+            thread_pool,
+            ..
+        } = context.clone();
+        let FrameInfo {
+            push_constant,
+            frame_index,
+        } = frame_info;
         let mut push_constant = push_constant;
         push_constant.model_index = self.ssbo_index;
         let instance_buffer = self.instance_buffer.buffer;
@@ -266,10 +284,24 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         unsafe {
             for mesh in self.model.meshes.iter() {
                 let mesh_clone = mesh.clone();
-                let mesh_lock = mesh_clone.lock();
+                let mut mesh_lock = mesh_clone.lock();
                 let model_index = mesh_lock.model_index;
                 //let shader_type = mesh_lock.shader_type;
-                drop(mesh_lock);
+                // インスタンスは散らばっているので、境界で間引かずに間接描画に加える。
+                // Instances are spread out, so they're added to indirect draws without culling by bounds.
+                let is_drawn_indirectly = context.push_indirect_draws(
+                    &mesh_lock,
+                    ShaderType::InstanceDraw,
+                    self.ssbo_index,
+                    Some((instance_buffer, instance_count as u32)),
+                    None,
+                );
+                mesh_lock
+                    .recorded_commands
+                    .set_drawn_indirectly(is_drawn_indirectly);
+                if is_drawn_indirectly {
+                    continue;
+                }
                 let pipeline_layout = pipeline
                     .read()
                     .expect("Failed to lock pipeline when acquiring pipeline layout.")
@@ -278,89 +310,113 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
                     .read()
                     .expect("Failed to lock pipeline when getting the graphics pipeline.")
                     .get_pipeline(ShaderType::InstanceDraw, 0);
+                let record_key = Model::<Graphics, Buffer, CommandBuffer, Image>::get_record_key(
+                    context,
+                    &mesh_lock,
+                    pipeline,
+                    push_constant,
+                )
+                .with(instance_buffer)
+                .with(instance_count)
+                .finish();
+                if mesh_lock
+                    .recorded_commands
+                    .is_up_to_date(frame_index, record_key)
+                {
+                    continue;
+                }
+                drop(mesh_lock);
                 let inheritance_clone = inheritance_info.clone();
                 let device_clone = device.clone();
+                let descriptor_sets_clone = descriptor_sets.clone();
                 let vertex_buffer_offsets = vec![0, 0];
-                thread_pool.threads[model_index % thread_count]
-                    .add_job(move || {
-                        let device_clone = device_clone;
-                        let inheritance =
-                            inheritance_clone.load(Ordering::SeqCst).as_ref().unwrap();
-                        let mesh = mesh_clone;
-                        let mesh_lock = mesh.lock();
-                        let command_buffer_begin_info = CommandBufferBeginInfo::builder()
-                            .inheritance_info(inheritance)
-                            .flags(CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
-                            .build();
-                        let (_, command_buffer) = mesh_lock.command_data.get(&frame_index).unwrap();
-                        let command_buffer = *command_buffer;
-                        let result = device_clone
-                            .begin_command_buffer(command_buffer, &command_buffer_begin_info);
-                        if let Err(e) = result {
-                            log::error!(
-                                "Error beginning secondary command buffer: {}",
-                                e.to_string()
+                thread_pool.add_render_job(model_index, move || {
+                    let device_clone = device_clone;
+                    let inheritance = inheritance_clone.load(Ordering::SeqCst).as_ref().unwrap();
+                    let mesh = mesh_clone;
+                    let mut mesh_lock = mesh.lock();
+                    let command_buffer_begin_info = CommandBufferBeginInfo::builder()
+                        .inheritance_info(inheritance)
+                        .flags(CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
+                        .build();
+                    let (_, command_buffer) = mesh_lock.command_data.get(&frame_index).unwrap();
+                    let command_buffer = *command_buffer;
+                    let result = device_clone
+                        .begin_command_buffer(command_buffer, &command_buffer_begin_info);
+                    if let Err(e) = result {
+                        log::error!(
+                            "Error beginning secondary command buffer: {}",
+                            e.to_string()
+                        );
+                    }
+                    device_clone.cmd_set_viewport(command_buffer, 0, &[viewport]);
+                    device_clone.cmd_set_scissor(command_buffer, 0, &[scissor]);
+                    device_clone.cmd_bind_pipeline(
+                        command_buffer,
+                        PipelineBindPoint::GRAPHICS,
+                        pipeline,
+                    );
+                    let vertex_buffers = [mesh_lock.get_vertex_buffer(), instance_buffer];
+                    let index_buffer = mesh_lock.get_index_buffer();
+                    let mut vertex_offset_index = mesh_lock.get_first_vertex();
+                    let mut index_offset_index = mesh_lock.get_first_index();
+                    let mut bound_set = None;
+                    for primitive in mesh_lock.primitives.iter() {
+                        // テクスチャが別の描述子セットにあれば、そのセットをバインドし直す。
+                        // Rebind when the texture lives in another descriptor set.
+                        let (descriptor_set, texture_index) = descriptor_sets_clone
+                            .locate(primitive.texture_index.unwrap_or_default());
+                        if bound_set != Some(descriptor_set) {
+                            device_clone.cmd_bind_descriptor_sets(
+                                command_buffer,
+                                PipelineBindPoint::GRAPHICS,
+                                pipeline_layout,
+                                0,
+                                &[descriptor_set],
+                                &[],
                             );
+                            bound_set = Some(descriptor_set);
                         }
-                        device_clone.cmd_set_viewport(command_buffer, 0, &[viewport]);
-                        device_clone.cmd_set_scissor(command_buffer, 0, &[scissor]);
-                        device_clone.cmd_bind_pipeline(
+                        push_constant.texture_index = texture_index;
+                        let casted = bytemuck::cast::<PushConstant, [u8; 32]>(push_constant);
+                        device_clone.cmd_push_constants(
                             command_buffer,
-                            PipelineBindPoint::GRAPHICS,
-                            pipeline,
+                            pipeline_layout,
+                            ShaderStageFlags::FRAGMENT | ShaderStageFlags::VERTEX,
+                            0,
+                            &casted[0..],
                         );
-                        device_clone.cmd_bind_descriptor_sets(
+                        device_clone.cmd_bind_vertex_buffers(
                             command_buffer,
-                            PipelineBindPoint::GRAPHICS,
-                            pipeline_layout,
                             0,
-                            &[descriptor_set],
-                            &[],
+                            &vertex_buffers[0..],
+                            vertex_buffer_offsets.as_slice(),
                         );
-                        let vertex_buffers = [mesh_lock.get_vertex_buffer(), instance_buffer];
-                        let index_buffer = mesh_lock.get_index_buffer();
-                        let mut vertex_offset_index = 0;
-                        let mut index_offset_index = 0;
-                        for primitive in mesh_lock.primitives.iter() {
-                            push_constant.texture_index =
-                                primitive.texture_index.unwrap_or_default();
-                            let casted = bytemuck::cast::<PushConstant, [u8; 32]>(push_constant);
-                            device_clone.cmd_push_constants(
-                                command_buffer,
-                                pipeline_layout,
-                                ShaderStageFlags::FRAGMENT | ShaderStageFlags::VERTEX,
-                                0,
-                                &casted[0..],
-                            );
-                            device_clone.cmd_bind_vertex_buffers(
-                                command_buffer,
-                                0,
-                                &vertex_buffers[0..],
-                                vertex_buffer_offsets.as_slice(),
-                            );
-                            device_clone.cmd_bind_index_buffer(
-                                command_buffer,
-                                index_buffer,
-                                0,
-                                IndexType::UINT32,
-                            );
-                            device_clone.cmd_draw_indexed(
-                                command_buffer,
-                                primitive.indices.len() as u32,
-                                instance_count as u32,
-                                index_offset_index,
-                                vertex_offset_index,
-                                0,
-                            );
-                            vertex_offset_index += primitive.vertices.len() as i32;
-                            index_offset_index += primitive.indices.len() as u32;
-                        }
-                        let result = device_clone.end_command_buffer(command_buffer);
-                        if let Err(e) = result {
-                            log::error!("Error ending command buffer: {}", e.to_string());
-                        }
-                    })
-                    .expect("Failed to push work into the worker thread.");
+                        device_clone.cmd_bind_index_buffer(
+                            command_buffer,
+                            index_buffer,
+                            0,
+                            IndexType::UINT32,
+                        );
+                        device_clone.cmd_draw_indexed(
+                            command_buffer,
+                            primitive.indices.len() as u32,
+                            instance_count as u32,
+                            index_offset_index,
+                            vertex_offset_index,
+                            0,
+                        );
+                        vertex_offset_index += primitive.vertices.len() as i32;
+                        index_offset_index += primitive.indices.len() as u32;
+                    }
+                    let result = device_clone.end_command_buffer(command_buffer);
+                    match result {
+                        Ok(_) => mesh_lock
+                            .recorded_commands
+                            .set_recorded(frame_index, record_key),
+                        Err(e) => log::error!("Error ending command buffer: {}", e.to_string()),
+                    }
+                });
             }
         }
     }
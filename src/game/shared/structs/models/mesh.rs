@@ -3,7 +3,9 @@ use serde::{Deserialize, Serialize};
 use std::mem::ManuallyDrop;
 use std::sync::Arc;
 
+use crate::game::shared::components::Bounds;
 use crate::game::shared::enums::ShaderType;
+use crate::game::shared::structs::RecordedCommands;
 use crate::game::shared::traits::disposable::Disposable;
 use crate::game::structs::Vertex;
 use crate::game::{graphics, CommandData};
@@ -31,9 +33,18 @@ where
     pub primitives: Vec<Primitive>,
     pub vertex_buffer: Option<ManuallyDrop<BufferType>>,
     pub index_buffer: Option<ManuallyDrop<BufferType>>,
+    /// 共有のプールから切り出した頂点とインデックスの範囲。あれば専用のバッファより優先する。<br />
+    /// Vertex and index ranges cut out of a shared pool, preferred over dedicated buffers when present.
+    pub buffer_allocation: Option<graphics::vk::MeshBufferAllocation>,
     pub texture: Vec<Arc<ShardedLock<TextureType>>>,
     pub is_disposed: bool,
     pub command_data: CommandData<CommandType>,
+    /// コマンドバッファを最後に記録した時のキー。<br />
+    /// Keys the command buffers were last recorded with.
+    pub recorded_commands: RecordedCommands,
+    /// 全てのプリミティブの頂点を囲むローカル空間の境界ボックス。頂点がなければ`None`。<br />
+    /// Bounding box in local space enclosing the vertices of every primitive. `None` without vertices.
+    pub bounds: Option<Bounds>,
     pub shader_type: ShaderType,
     pub model_index: usize,
 }
@@ -41,19 +52,24 @@ where
 impl Mesh<graphics::vk::Buffer, ash::vk::CommandBuffer, graphics::vk::Image> {
     pub fn new(primitives: Vec<Primitive>) -> Self {
         Mesh {
+            bounds: Bounds::from_primitives(&primitives),
             primitives,
             vertex_buffer: None,
             index_buffer: None,
+            buffer_allocation: None,
             is_disposed: false,
             texture: vec![],
             shader_type: ShaderType::BasicShader,
             model_index: 0,
             command_data: std::collections::HashMap::new(),
+            recorded_commands: RecordedCommands::new(),
         }
     }
 
     pub fn get_vertex_buffer(&self) -> ash::vk::Buffer {
-        if let Some(buffer) = self.vertex_buffer.as_ref() {
+        if let Some(allocation) = self.buffer_allocation.as_ref() {
+            allocation.get_vertex_buffer()
+        } else if let Some(buffer) = self.vertex_buffer.as_ref() {
             buffer.buffer
         } else {
             panic!("Vertex buffer is not yet created.");
@@ -61,12 +77,52 @@ impl Mesh<graphics::vk::Buffer, ash::vk::CommandBuffer, graphics::vk::Image> {
     }
 
     pub fn get_index_buffer(&self) -> ash::vk::Buffer {
-        if let Some(buffer) = self.index_buffer.as_ref() {
+        if let Some(allocation) = self.buffer_allocation.as_ref() {
+            allocation.get_index_buffer()
+        } else if let Some(buffer) = self.index_buffer.as_ref() {
             buffer.buffer
         } else {
             panic!("Index buffer is not yet created.");
         }
     }
+
+    /// 頂点バッファの中でこのメッシュの頂点が始まる番号。専用のバッファなら0。<br />
+    /// Number of the vertex this mesh starts at inside the vertex buffer, 0 for dedicated buffers.
+    pub fn get_first_vertex(&self) -> i32 {
+        self.buffer_allocation
+            .as_ref()
+            .map(|allocation| allocation.get_range().get_first_vertex())
+            .unwrap_or_default()
+    }
+
+    /// インデックスバッファの中でこのメッシュのインデックスが始まる番号。専用のバッファなら0。<br />
+    /// Number of the index this mesh starts at inside the index buffer, 0 for dedicated buffers.
+    pub fn get_first_index(&self) -> u32 {
+        self.buffer_allocation
+            .as_ref()
+            .map(|allocation| allocation.get_range().get_first_index())
+            .unwrap_or_default()
+    }
+
+    pub fn has_buffers(&self) -> bool {
+        self.buffer_allocation.is_some()
+            || (self.vertex_buffer.is_some() && self.index_buffer.is_some())
+    }
+
+    /// 作ったバッファを設定し、記録したコマンドを無効にする。<br />
+    /// Set the created buffers and invalidate the recorded commands.
+    pub fn set_buffers(&mut self, buffers: graphics::vk::MeshBuffers) {
+        match buffers {
+            graphics::vk::MeshBuffers::Pooled(allocation) => {
+                self.buffer_allocation = Some(allocation);
+            }
+            graphics::vk::MeshBuffers::Dedicated(vertex_buffer, index_buffer) => {
+                self.vertex_buffer = Some(ManuallyDrop::new(vertex_buffer));
+                self.index_buffer = Some(ManuallyDrop::new(index_buffer));
+            }
+        }
+        self.recorded_commands.invalidate();
+    }
 }
 
 unsafe impl<BufferType, CommandType, TextureType> Send
@@ -111,6 +167,7 @@ where
                 ManuallyDrop::drop(buffer);
             }
         }
+        self.buffer_allocation = None;
         self.is_disposed = true;
         log::info!("Successfully disposed mesh.");
     }
@@ -4,6 +4,7 @@ pub mod joint;
 pub mod mesh;
 pub mod model;
 pub mod model_metadata;
+pub mod morph_targets;
 pub mod position_info;
 pub mod skinned_mesh;
 pub mod skinned_model;
@@ -1,39 +1,38 @@
 use ash::version::DeviceV1_0;
 use ash::vk::{
-    CommandBuffer, CommandBufferBeginInfo, CommandBufferInheritanceInfo, CommandBufferUsageFlags,
-    CommandPool, DescriptorSet, IndexType, PipelineBindPoint, Rect2D, ShaderStageFlags, Viewport,
+    CommandBuffer, CommandBufferBeginInfo, CommandBufferUsageFlags, CommandPool, IndexType,
+    PipelineBindPoint, ShaderStageFlags,
 };
 use crossbeam::channel::*;
 use crossbeam::sync::ShardedLock;
-use glam::{Mat4, Quat, Vec2, Vec3, Vec3A, Vec4};
-use gltf::{Node, Scene};
+use glam::{Mat4, Vec3A, Vec4};
 use parking_lot::{Mutex, RwLock};
 use std::convert::TryFrom;
 use std::mem::ManuallyDrop;
 use std::sync::{
-    atomic::{AtomicPtr, AtomicUsize, Ordering},
+    atomic::{AtomicUsize, Ordering},
     Arc, Weak,
 };
 
-use crate::game::graphics::vk::{Buffer, Graphics, Image, Pipeline, ThreadPool};
+use crate::game::graphics::vk::{Buffer, Graphics, Image, MeshPool, RenderContext};
+use crate::game::shared::components::Bounds;
 use crate::game::shared::enums::ShaderType;
 use crate::game::shared::structs::{
-    Mesh, ModelMetaData, PositionInfo, Primitive, PushConstant, Vertex,
+    create_unit_cube, AssetKind, FrameInfo, LoadTask, Mesh, ModelMetaData, PositionInfo, Primitive,
+    PushConstant, RecordKey, RecordedCommands,
 };
 use crate::game::shared::traits::disposable::Disposable;
 use crate::game::shared::traits::Renderable;
 use crate::game::traits::GraphicsBase;
-use crate::game::util::read_raw_data;
-use ash::Device;
+use crate::game::util::model_importer::{import_model, ImportedMesh, ImportedModel};
+use crate::game::ReleasedCommandBuffers;
 use slotmap::DefaultKey;
 use std::collections::HashMap;
 
 /// 最も一般的なモデル。<br />
-/// GLTFのサポートは少ないため、この構造体の中にはモデルの読み込みコードも含めています。<br />
-/// 詳しくはGLTFの仕様書を参照。<br />
+/// ファイルの読み込みは`model_importer`が行い、glTFとOBJに対応しています。<br />
 /// The most common models.<br />
-/// Since the support for GLTF format is lacking, this struct also contains codes for reading models.<br />
-/// Please refer to GLTF's documentation to learn more.
+/// Files are read by `model_importer`, which supports glTF and OBJ.
 pub struct Model<GraphicsType, BufferType, CommandType, TextureType>
 where
     GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
@@ -64,8 +63,7 @@ where
     fn create_model(
         file_name: &str,
         model_index: Arc<AtomicUsize>,
-        document: gltf::Document,
-        buffers: Vec<gltf::buffer::Data>,
+        imported_meshes: Vec<ImportedMesh>,
         images: Vec<Arc<ShardedLock<TextureType>>>,
         graphics: Weak<RwLock<ManuallyDrop<GraphicsType>>>,
         position_info: PositionInfo,
@@ -74,15 +72,11 @@ where
         ssbo_index: usize,
         entity: DefaultKey,
     ) -> Self {
-        let meshes = Self::process_model(
-            &document,
-            &buffers,
-            images,
-            texture_index_offset,
-            model_index,
-        );
-        let meshes = meshes
+        let meshes = imported_meshes
             .into_iter()
+            .map(|mesh| {
+                Self::process_mesh(mesh, &images, texture_index_offset, model_index.clone())
+            })
             .map(|m| Arc::new(Mutex::new(m)))
             .collect::<Vec<_>>();
 
@@ -103,159 +97,76 @@ where
         }
     }
 
-    fn process_model(
-        document: &gltf::Document,
-        buffers: &[gltf::buffer::Data],
-        images: Vec<Arc<ShardedLock<TextureType>>>,
-        texture_index_offset: usize,
+    /// 読み込めなかったモデルの代わりに、テクスチャの無い立方体を作る。<br />
+    /// Create an untextured cube in place of a model that failed to load.
+    fn create_placeholder(
+        file_name: &str,
         model_index: Arc<AtomicUsize>,
-    ) -> Vec<Mesh<BufferType, CommandType, TextureType>> {
-        let meshes = if let Some(scene) = document.default_scene() {
-            Self::process_root_nodes(scene, buffers, images, texture_index_offset, model_index)
-        } else {
-            Self::process_root_nodes(
-                document.scenes().next().unwrap(),
-                buffers,
-                images,
-                texture_index_offset,
-                model_index,
-            )
+        graphics: Weak<RwLock<ManuallyDrop<GraphicsType>>>,
+        position_info: PositionInfo,
+        color: Vec4,
+        ssbo_index: usize,
+        entity: DefaultKey,
+    ) -> Self {
+        let (vertices, indices) = create_unit_cube();
+        let primitives = vec![Primitive {
+            vertices,
+            indices,
+            texture_index: None,
+            is_disposed: false,
+        }];
+        let mesh = Mesh {
+            bounds: Bounds::from_primitives(&primitives),
+            primitives,
+            vertex_buffer: None,
+            index_buffer: None,
+            texture: vec![],
+            is_disposed: false,
+            command_data: HashMap::new(),
+            recorded_commands: RecordedCommands::new(),
+            shader_type: ShaderType::BasicShaderWithoutTexture,
+            model_index: model_index.fetch_add(1, Ordering::SeqCst),
         };
-        meshes
-    }
-
-    fn process_root_nodes(
-        scene: Scene,
-        buffers: &[gltf::buffer::Data],
-        images: Vec<Arc<ShardedLock<TextureType>>>,
-        texture_index_offset: usize,
-        model_index: Arc<AtomicUsize>,
-    ) -> Vec<Mesh<BufferType, CommandType, TextureType>> {
-        let mut meshes = Vec::with_capacity(150);
-        for node in scene.nodes() {
-            let mut submeshes = Self::process_node(
-                node,
-                buffers,
-                &images,
-                Mat4::identity(),
-                texture_index_offset,
-                model_index.clone(),
-            );
-            meshes.append(&mut submeshes);
-        }
-        meshes
-    }
-
-    fn process_node(
-        node: Node,
-        buffers: &[gltf::buffer::Data],
-        images: &[Arc<ShardedLock<TextureType>>],
-        local_transform: Mat4,
-        texture_index_offset: usize,
-        model_index: Arc<AtomicUsize>,
-    ) -> Vec<Mesh<BufferType, CommandType, TextureType>> {
-        let mut meshes = Vec::with_capacity(10);
-        let (t, r, s) = node.transform().decomposed();
-        let transform =
-            Mat4::from_scale_rotation_translation(Vec3::from(s), Quat::from(r), Vec3::from(t));
-        let transform = local_transform * transform;
-        if let Some(mesh) = node.mesh() {
-            meshes.push(Self::process_mesh(
-                mesh,
-                buffers,
-                transform,
-                images,
-                texture_index_offset,
-                model_index.clone(),
-            ));
-        }
-        for _node in node.children() {
-            let mut submeshes = Self::process_node(
-                _node,
-                buffers,
-                images,
-                transform,
-                texture_index_offset,
-                model_index.clone(),
-            );
-            meshes.append(&mut submeshes);
+        Model {
+            position_info,
+            model_metadata: ModelMetaData {
+                world_matrix: Mat4::identity(),
+                object_color: color,
+                reflectivity: 1.0,
+                shine_damper: 10.0,
+            },
+            graphics,
+            meshes: vec![Arc::new(Mutex::new(mesh))],
+            is_disposed: false,
+            model_name: file_name.to_string(),
+            ssbo_index,
+            entity,
         }
-        meshes
     }
 
+    /// 読み込んだメッシュをエンジンのメッシュにし、テクスチャの番号をずらす。<br />
+    /// Turn an imported mesh into an engine mesh and shift its texture indices.
     fn process_mesh(
-        mesh: gltf::Mesh,
-        buffers: &[gltf::buffer::Data],
-        local_transform: Mat4,
+        mesh: ImportedMesh,
         images: &[Arc<ShardedLock<TextureType>>],
         texture_index_offset: usize,
         model_index: Arc<AtomicUsize>,
     ) -> Mesh<BufferType, CommandType, TextureType> {
-        let mut primitives = Vec::with_capacity(5);
+        let mut primitives = Vec::with_capacity(mesh.primitives.len());
         let mut textures = Vec::with_capacity(5);
-        for primitive in mesh.primitives() {
-            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-
-            let positions = reader.read_positions();
-            let normals = reader.read_normals();
-            let uvs = reader.read_tex_coords(0);
-            let indices = reader
-                .read_indices()
-                .unwrap()
-                .into_u32()
-                .collect::<Vec<_>>();
-
-            let mut vertices = match (positions, normals, uvs) {
-                (Some(positions), Some(normals), Some(uvs)) => {
-                    let _vertices = positions
-                        .zip(normals)
-                        .zip(uvs.into_f32())
-                        .map(|((pos, normal), uv)| Vertex {
-                            position: Vec3A::from(pos),
-                            normal: Vec3A::from(normal),
-                            uv: Vec2::from(uv),
-                        })
-                        .collect::<Vec<_>>();
-                    _vertices
-                }
-                (Some(positions), Some(normals), None) => positions
-                    .zip(normals)
-                    .map(|(pos, normal)| Vertex {
-                        position: Vec3A::from(pos),
-                        normal: Vec3A::from(normal),
-                        uv: Vec2::new(0.0, 0.0),
-                    })
-                    .collect::<Vec<_>>(),
-                (positions, normals, uvs) => {
-                    unimplemented!(
-                        "Unsupported combination of values. Positions: {}, Normals: {}, UVs: {}",
-                        positions.is_some(),
-                        normals.is_some(),
-                        uvs.is_some()
-                    );
-                }
-            };
-
-            let texture_index = primitive
-                .material()
-                .pbr_metallic_roughness()
-                .base_color_texture()
-                .map(|x| x.texture().index());
-            let texture = texture_index.and_then(|index| images.get(index).cloned());
+        for primitive in mesh.primitives.into_iter() {
+            let texture = primitive
+                .texture_index
+                .and_then(|index| images.get(index).cloned());
             if let Some(t) = texture {
                 textures.push(t);
             }
-            let texture_index = texture_index.map(|index| index + texture_index_offset);
-
-            for vertex in vertices.iter_mut() {
-                vertex.position =
-                    Vec3A::from(local_transform.transform_point3(Vec3::from(vertex.position)));
-            }
-
             primitives.push(Primitive {
-                vertices,
-                indices,
-                texture_index,
+                vertices: primitive.vertices,
+                indices: primitive.indices,
+                texture_index: primitive
+                    .texture_index
+                    .map(|index| index + texture_index_offset),
                 is_disposed: false,
             });
         }
@@ -266,12 +177,14 @@ where
             ShaderType::BasicShader
         };
         Mesh {
+            bounds: Bounds::from_primitives(&primitives),
             primitives,
             vertex_buffer: None,
             index_buffer: None,
             texture: textures,
             is_disposed: false,
             command_data: std::collections::HashMap::new(),
+            recorded_commands: RecordedCommands::new(),
             shader_type,
             model_index: model_index.fetch_add(1, Ordering::SeqCst),
         }
@@ -292,13 +205,19 @@ impl Model<Graphics, Buffer, CommandBuffer, Image> {
         ssbo_index: usize,
         create_buffers: bool,
         entity: DefaultKey,
-    ) -> anyhow::Result<Receiver<Self>> {
+    ) -> anyhow::Result<LoadTask<Self>> {
         log::info!("Loading model {}...", file_name);
         let graphics_arc = graphics
             .upgrade()
             .expect("Failed to upgrade graphics handle for model.");
-        let (model_send, model_recv) = bounded(5);
-        rayon::spawn(move || {
+        let (model_send, model_recv) = LoadTask::channel();
+        let thread_pool = graphics_arc.read().thread_pool.clone();
+        thread_pool.spawn_streaming(move || {
+            // 待っている間にシーンを切り替えたら、ファイルを読まずに終わる。
+            // If the scene was switched while this was queued, finish without reading the file.
+            if model_send.is_cancelled() {
+                return;
+            }
             let graphics_arc = graphics_arc;
             let command_pool: Arc<Mutex<CommandPool>>;
             {
@@ -306,38 +225,55 @@ impl Model<Graphics, Buffer, CommandBuffer, Image> {
                 command_pool = graphics.get_idle_command_pool();
             }
             log::info!("Model index: {}", ssbo_index);
-            let (document, buffers, images) =
-                read_raw_data(file_name).expect("Failed to read raw data from glTF.");
-            let (textures, texture_index_offset) =
-                Graphics::create_gltf_textures(images, graphics_arc.clone(), command_pool)
-                    .expect("Failed to create glTF textures.");
             let x: f32 = rotation.x;
             let y: f32 = rotation.y;
             let z: f32 = rotation.z;
-            let mut loaded_model = Self::create_model(
-                file_name,
-                model_index,
-                document,
-                buffers,
-                textures,
-                graphics,
-                PositionInfo {
-                    position,
-                    scale,
-                    rotation: Vec3A::new(x.to_radians(), y.to_radians(), z.to_radians()),
-                },
-                color,
-                texture_index_offset,
-                ssbo_index,
-                entity,
-            );
+            let position_info = PositionInfo {
+                position,
+                scale,
+                rotation: Vec3A::new(x.to_radians(), y.to_radians(), z.to_radians()),
+            };
+            let loaded = import_model(file_name).and_then(|ImportedModel { meshes, images }| {
+                let (textures, texture_index_offset) = Graphics::create_gltf_textures(
+                    images,
+                    graphics_arc.clone(),
+                    command_pool,
+                    true,
+                )?;
+                Ok((meshes, textures, texture_index_offset))
+            });
+            let mut loaded_model = match loaded {
+                Ok((meshes, textures, texture_index_offset)) => Self::create_model(
+                    file_name,
+                    model_index,
+                    meshes,
+                    textures,
+                    graphics,
+                    position_info,
+                    color,
+                    texture_index_offset,
+                    ssbo_index,
+                    entity,
+                ),
+                Err(e) => {
+                    graphics_arc
+                        .read()
+                        .report_asset_warning(AssetKind::Model, file_name, &e);
+                    Self::create_placeholder(
+                        file_name,
+                        model_index,
+                        graphics,
+                        position_info,
+                        color,
+                        ssbo_index,
+                        entity,
+                    )
+                }
+            };
             loaded_model.model_metadata.world_matrix = loaded_model.get_world_matrix();
             {
                 let graphics_lock = graphics_arc.read();
-                let inflight_frame_count = std::env::var("INFLIGHT_BUFFER_COUNT")
-                    .unwrap()
-                    .parse::<usize>()
-                    .unwrap();
+                let inflight_frame_count = graphics_lock.inflight_buffer_count;
                 for mesh in loaded_model.meshes.iter_mut() {
                     let mut mesh_lock = mesh.lock();
                     for i in 0..inflight_frame_count {
@@ -361,13 +297,46 @@ impl Model<Graphics, Buffer, CommandBuffer, Image> {
                     .create_buffers(graphics_arc)
                     .expect("Failed to create buffers for model.");
             }
-            model_send
-                .send(loaded_model)
-                .expect("Failed to send model result.");
+            model_send.complete(loaded_model);
         });
         Ok(model_recv)
     }
 
+    /// メッシュのコマンドバッファに記録する内容のキーを作る。インスタンス描画のモデルも使う。<br />
+    /// Build the key of what goes into a mesh's command buffer, also used by instanced models.
+    pub fn get_record_key(
+        context: &RenderContext,
+        mesh: &Mesh<Buffer, CommandBuffer, Image>,
+        pipeline: ash::vk::Pipeline,
+        push_constant: PushConstant,
+    ) -> RecordKey {
+        context
+            .get_record_key()
+            .with(pipeline)
+            .with(bytemuck::cast::<PushConstant, [u8; 32]>(push_constant))
+            .with(mesh.vertex_buffer.as_ref().map(|buffer| buffer.buffer))
+            .with(mesh.index_buffer.as_ref().map(|buffer| buffer.buffer))
+            .with(mesh.buffer_allocation.as_ref().map(|allocation| {
+                (
+                    allocation.get_vertex_buffer(),
+                    allocation.get_index_buffer(),
+                    *allocation.get_range(),
+                )
+            }))
+            .with(
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| {
+                        (
+                            primitive.vertices.len(),
+                            primitive.indices.len(),
+                            primitive.texture_index,
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+    }
+
     /// モデルのバッファを作成する。<br />
     /// Create buffers for the model.
     fn create_buffers(
@@ -400,8 +369,9 @@ impl Model<Graphics, Buffer, CommandBuffer, Image> {
             let g = graphics.clone();
             let (buffer_send, buffer_recv) = bounded(5);
             rayon::spawn(move || {
-                let result = Graphics::create_vertex_and_index_buffer(g, vertices, indices, pool)
-                    .expect("Failed to create buffers for model.");
+                let result =
+                    Graphics::create_mesh_buffers(g, vertices, indices, pool, MeshPool::Static)
+                        .expect("Failed to create buffers for model.");
                 buffer_send
                     .send(result)
                     .expect("Failed to send buffer result.");
@@ -410,10 +380,8 @@ impl Model<Graphics, Buffer, CommandBuffer, Image> {
         }
         for (index, mesh) in self.meshes.iter_mut().enumerate() {
             if let Some(result) = handles.get_mut(&index) {
-                let mut mesh_lock = mesh.lock();
-                let (vertex_buffer, index_buffer) = result.recv()?;
-                mesh_lock.vertex_buffer = Some(ManuallyDrop::new(vertex_buffer));
-                mesh_lock.index_buffer = Some(ManuallyDrop::new(index_buffer));
+                let buffers = result.recv()?;
+                mesh.lock().set_buffers(buffers);
             }
         }
         Ok(())
@@ -484,7 +452,8 @@ where
         loop {
             let is_buffer_completed = self.meshes.iter().all(|m| {
                 let mesh_lock = m.lock();
-                mesh_lock.vertex_buffer.is_some() && mesh_lock.index_buffer.is_some()
+                mesh_lock.buffer_allocation.is_some()
+                    || (mesh_lock.vertex_buffer.is_some() && mesh_lock.index_buffer.is_some())
             });
             if is_buffer_completed {
                 break;
@@ -525,6 +494,37 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         buffers
     }
 
+    fn append_command_buffers(&self, frame_index: usize, command_buffers: &mut Vec<CommandBuffer>) {
+        for mesh in self.meshes.iter() {
+            let mesh_lock = mesh.lock();
+            if mesh_lock.recorded_commands.is_drawn_indirectly() {
+                continue;
+            }
+            if let Some((_, buffer)) = mesh_lock.command_data.get(&frame_index) {
+                command_buffers.push(*buffer);
+            }
+        }
+    }
+
+    fn take_command_buffers(&mut self) -> ReleasedCommandBuffers<CommandBuffer> {
+        self.meshes
+            .iter()
+            .flat_map(|m| {
+                m.lock()
+                    .command_data
+                    .drain()
+                    .filter_map(|(_, (pool, buffer))| pool.map(|p| (p, buffer)))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn invalidate_command_buffers(&mut self) {
+        for mesh in self.meshes.iter() {
+            mesh.lock().recorded_commands.invalidate();
+        }
+    }
+
     fn get_entity(&self) -> DefaultKey {
         self.entity
     }
@@ -541,28 +541,64 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         self.ssbo_index
     }
 
-    fn render(
-        &self,
-        inheritance_info: Arc<AtomicPtr<CommandBufferInheritanceInfo>>,
-        push_constant: PushConstant,
-        viewport: Viewport,
-        scissor: Rect2D,
-        device: Arc<Device>,
-        pipeline: Arc<ShardedLock<ManuallyDrop<Pipeline>>>,
-        descriptor_set: DescriptorSet,
-        thread_pool: Arc<ThreadPool>,
-        frame_index: usize,
-    ) {
-        let thread_count = thread_pool.thread_count;
+    fn get_texture_usages(&self) -> Vec<(usize, Option<Bounds>)> {
+        let world_matrix = self.model_metadata.world_matrix;
+        self.meshes
+            .iter()
+            .flat_map(|mesh| {
+                let mesh_lock = mesh.lock();
+                let bounds = mesh_lock
+                    .bounds
+                    .as_ref()
+                    .map(|bounds| bounds.transform(world_matrix));
+                mesh_lock
+                    .primitives
+                    .iter()
+                    .filter_map(|primitive| primitive.texture_index)
+                    .map(|texture_index| (texture_index, bounds))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn render(&self, context: &RenderContext, frame_info: FrameInfo) {
+        let RenderContext {
+            inheritance_info,
+            viewport,
+            scissor,
+            device,
+            pipeline,
+            descriptor_sets,
+            thread_pool,
+            ..
+        } = context.clone();
+        let FrameInfo {
+            push_constant,
+            frame_index,
+        } = frame_info;
         let mut push_constant = push_constant;
         push_constant.model_index = self.ssbo_index;
         unsafe {
             for mesh in self.meshes.iter() {
                 let mesh_clone = mesh.clone();
-                let mesh_lock = mesh_clone.lock();
+                let mut mesh_lock = mesh_clone.lock();
                 let model_index = mesh_lock.model_index;
                 let shader_type = mesh_lock.shader_type;
-                drop(mesh_lock);
+                // 間接描画に加えたメッシュは、自分の二次コマンドバッファを記録しない。
+                // Meshes added to indirect draws don't record their own secondary command buffers.
+                let is_drawn_indirectly = context.push_indirect_draws(
+                    &mesh_lock,
+                    shader_type,
+                    self.ssbo_index,
+                    None,
+                    Some(self.model_metadata.world_matrix),
+                );
+                mesh_lock
+                    .recorded_commands
+                    .set_drawn_indirectly(is_drawn_indirectly);
+                if is_drawn_indirectly {
+                    continue;
+                }
                 let pipeline_layout = pipeline
                     .read()
                     .expect("Failed to lock pipeline when acquiring pipeline layout.")
@@ -571,88 +607,107 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
                     .read()
                     .expect("Failed to lock pipeline when getting the graphics pipeline.")
                     .get_pipeline(shader_type, 0);
+                let record_key =
+                    Self::get_record_key(context, &mesh_lock, pipeline, push_constant).finish();
+                // 記録した時と何も変わっていなければ、前に記録したコマンドバッファをそのまま使う。
+                // If nothing changed since recording, the previously recorded command buffer is used as-is.
+                if mesh_lock
+                    .recorded_commands
+                    .is_up_to_date(frame_index, record_key)
+                {
+                    continue;
+                }
+                drop(mesh_lock);
                 let inheritance_clone = inheritance_info.clone();
                 let device_clone = device.clone();
-                thread_pool.threads[model_index % thread_count]
-                    .add_job(move || {
-                        let device_clone = device_clone;
-                        let inheritance =
-                            inheritance_clone.load(Ordering::SeqCst).as_ref().unwrap();
-                        let mesh = mesh_clone;
-                        let mesh_lock = mesh.lock();
-                        let command_buffer_begin_info = CommandBufferBeginInfo::builder()
-                            .inheritance_info(inheritance)
-                            .flags(CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
-                            .build();
-                        let (_, command_buffer) = mesh_lock.command_data.get(&frame_index).unwrap();
-                        let command_buffer = *command_buffer;
-                        let result = device_clone
-                            .begin_command_buffer(command_buffer, &command_buffer_begin_info);
-                        if let Err(e) = result {
-                            log::error!(
-                                "Error beginning secondary command buffer: {}",
-                                e.to_string()
+                let descriptor_sets_clone = descriptor_sets.clone();
+                thread_pool.add_render_job(model_index, move || {
+                    let device_clone = device_clone;
+                    let inheritance = inheritance_clone.load(Ordering::SeqCst).as_ref().unwrap();
+                    let mesh = mesh_clone;
+                    let mut mesh_lock = mesh.lock();
+                    let command_buffer_begin_info = CommandBufferBeginInfo::builder()
+                        .inheritance_info(inheritance)
+                        .flags(CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
+                        .build();
+                    let (_, command_buffer) = mesh_lock.command_data.get(&frame_index).unwrap();
+                    let command_buffer = *command_buffer;
+                    let result = device_clone
+                        .begin_command_buffer(command_buffer, &command_buffer_begin_info);
+                    if let Err(e) = result {
+                        log::error!(
+                            "Error beginning secondary command buffer: {}",
+                            e.to_string()
+                        );
+                    }
+                    device_clone.cmd_set_viewport(command_buffer, 0, &[viewport]);
+                    device_clone.cmd_set_scissor(command_buffer, 0, &[scissor]);
+                    device_clone.cmd_bind_pipeline(
+                        command_buffer,
+                        PipelineBindPoint::GRAPHICS,
+                        pipeline,
+                    );
+                    let vertex_buffers = [mesh_lock.get_vertex_buffer()];
+                    let index_buffer = mesh_lock.get_index_buffer();
+                    let mut vertex_offset_index = mesh_lock.get_first_vertex();
+                    let mut index_offset_index = mesh_lock.get_first_index();
+                    let mut bound_set = None;
+                    for primitive in mesh_lock.primitives.iter() {
+                        // テクスチャが別の描述子セットにあれば、そのセットをバインドし直す。
+                        // Rebind when the texture lives in another descriptor set.
+                        let (descriptor_set, texture_index) = descriptor_sets_clone
+                            .locate(primitive.texture_index.unwrap_or_default());
+                        if bound_set != Some(descriptor_set) {
+                            device_clone.cmd_bind_descriptor_sets(
+                                command_buffer,
+                                PipelineBindPoint::GRAPHICS,
+                                pipeline_layout,
+                                0,
+                                &[descriptor_set],
+                                &[],
                             );
+                            bound_set = Some(descriptor_set);
                         }
-                        device_clone.cmd_set_viewport(command_buffer, 0, &[viewport]);
-                        device_clone.cmd_set_scissor(command_buffer, 0, &[scissor]);
-                        device_clone.cmd_bind_pipeline(
+                        push_constant.texture_index = texture_index;
+                        let casted = bytemuck::cast::<PushConstant, [u8; 32]>(push_constant);
+                        device_clone.cmd_push_constants(
                             command_buffer,
-                            PipelineBindPoint::GRAPHICS,
-                            pipeline,
+                            pipeline_layout,
+                            ShaderStageFlags::FRAGMENT | ShaderStageFlags::VERTEX,
+                            0,
+                            &casted[0..],
                         );
-                        device_clone.cmd_bind_descriptor_sets(
+                        device_clone.cmd_bind_vertex_buffers(
                             command_buffer,
-                            PipelineBindPoint::GRAPHICS,
-                            pipeline_layout,
                             0,
-                            &[descriptor_set],
-                            &[],
+                            &vertex_buffers[0..],
+                            &[0],
                         );
-                        let vertex_buffers = [mesh_lock.get_vertex_buffer()];
-                        let index_buffer = mesh_lock.get_index_buffer();
-                        let mut vertex_offset_index = 0;
-                        let mut index_offset_index = 0;
-                        for primitive in mesh_lock.primitives.iter() {
-                            push_constant.texture_index =
-                                primitive.texture_index.unwrap_or_default();
-                            let casted = bytemuck::cast::<PushConstant, [u8; 32]>(push_constant);
-                            device_clone.cmd_push_constants(
-                                command_buffer,
-                                pipeline_layout,
-                                ShaderStageFlags::FRAGMENT | ShaderStageFlags::VERTEX,
-                                0,
-                                &casted[0..],
-                            );
-                            device_clone.cmd_bind_vertex_buffers(
-                                command_buffer,
-                                0,
-                                &vertex_buffers[0..],
-                                &[0],
-                            );
-                            device_clone.cmd_bind_index_buffer(
-                                command_buffer,
-                                index_buffer,
-                                0,
-                                IndexType::UINT32,
-                            );
-                            device_clone.cmd_draw_indexed(
-                                command_buffer,
-                                u32::try_from(primitive.indices.len()).unwrap(),
-                                1,
-                                index_offset_index,
-                                vertex_offset_index,
-                                0,
-                            );
-                            vertex_offset_index += primitive.vertices.len() as i32;
-                            index_offset_index += primitive.indices.len() as u32;
-                        }
-                        let result = device_clone.end_command_buffer(command_buffer);
-                        if let Err(e) = result {
-                            log::error!("Error ending command buffer: {}", e.to_string());
-                        }
-                    })
-                    .expect("Failed to push work into the worker thread.");
+                        device_clone.cmd_bind_index_buffer(
+                            command_buffer,
+                            index_buffer,
+                            0,
+                            IndexType::UINT32,
+                        );
+                        device_clone.cmd_draw_indexed(
+                            command_buffer,
+                            u32::try_from(primitive.indices.len()).unwrap(),
+                            1,
+                            index_offset_index,
+                            vertex_offset_index,
+                            0,
+                        );
+                        vertex_offset_index += primitive.vertices.len() as i32;
+                        index_offset_index += primitive.indices.len() as u32;
+                    }
+                    let result = device_clone.end_command_buffer(command_buffer);
+                    match result {
+                        Ok(_) => mesh_lock
+                            .recorded_commands
+                            .set_recorded(frame_index, record_key),
+                        Err(e) => log::error!("Error ending command buffer: {}", e.to_string()),
+                    }
+                });
             }
         }
     }
@@ -665,6 +720,10 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         self.position_info = position_info;
     }
 
+    fn set_entity(&mut self, entity: DefaultKey) {
+        self.entity = entity;
+    }
+
     fn set_ssbo_index(&mut self, ssbo_index: usize) {
         self.ssbo_index = ssbo_index;
     }
@@ -0,0 +1,106 @@
+use glam::{Vec3A, Vec4};
+
+/// 一つのメッシュで混ぜられるモーフターゲットの最大数。シェーダーの`MAX_MORPH_TARGETS`と同じ。<br />
+/// Maximum number of morph targets blended on a mesh. Same as `MAX_MORPH_TARGETS` in the shader.
+pub const MAX_MORPH_TARGETS: usize = 8;
+
+/// プリミティブのモーフターゲット。ターゲットごとに、全ての頂点の位置と法線の差分を持つ。<br />
+/// Morph targets of a primitive. Each target holds the position and normal deltas of every vertex.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MorphTargets {
+    pub vertex_count: usize,
+    pub position_deltas: Vec<Vec<Vec3A>>,
+    pub normal_deltas: Vec<Vec<Vec3A>>,
+}
+
+impl MorphTargets {
+    pub fn new(vertex_count: usize) -> Self {
+        MorphTargets {
+            vertex_count,
+            position_deltas: vec![],
+            normal_deltas: vec![],
+        }
+    }
+
+    /// ターゲットを追加する。差分のない頂点は0で埋める。最大数を超えたら追加せず`false`を返す。<br />
+    /// Add a target. Vertices without deltas are filled with zero. Returns `false` without adding once the maximum is reached.
+    pub fn add_target(&mut self, positions: Vec<Vec3A>, normals: Option<Vec<Vec3A>>) -> bool {
+        if self.get_target_count() >= MAX_MORPH_TARGETS {
+            return false;
+        }
+        let mut positions = positions;
+        positions.resize(self.vertex_count, Vec3A::zero());
+        let mut normals = normals.unwrap_or_default();
+        normals.resize(self.vertex_count, Vec3A::zero());
+        self.position_deltas.push(positions);
+        self.normal_deltas.push(normals);
+        true
+    }
+
+    pub fn get_target_count(&self) -> usize {
+        self.position_deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position_deltas.is_empty()
+    }
+
+    /// 重みで差分を足した頂点の位置と法線。シェーダーと同じ計算。<br />
+    /// Position and normal of a vertex with the deltas added by weight. Same calculation as the shader.
+    pub fn apply(
+        &self,
+        vertex_index: usize,
+        position: Vec3A,
+        normal: Vec3A,
+        weights: &[f32],
+    ) -> (Vec3A, Vec3A) {
+        let mut position = position;
+        let mut normal = normal;
+        for (target, weight) in weights.iter().enumerate().take(self.get_target_count()) {
+            position += self.position_deltas[target][vertex_index] * *weight;
+            normal += self.normal_deltas[target][vertex_index] * *weight;
+        }
+        (position, normal)
+    }
+
+    /// GPUに送るデータ。先頭はターゲット数と頂点数のヘッダーで、ターゲットと頂点の順に位置と法線が続く。<br />
+    /// Data sent to the GPU. A header of the target and vertex counts comes first, followed by positions and normals ordered by target and vertex.
+    pub fn get_gpu_data(&self) -> Vec<Vec4> {
+        let mut data = Vec::with_capacity(1 + self.get_target_count() * self.vertex_count * 2);
+        data.push(Vec4::new(
+            self.get_target_count() as f32,
+            self.vertex_count as f32,
+            0.0,
+            0.0,
+        ));
+        for (positions, normals) in self.position_deltas.iter().zip(self.normal_deltas.iter()) {
+            for (position, normal) in positions.iter().zip(normals.iter()) {
+                data.push(Vec4::new(position.x, position.y, position.z, 0.0));
+                data.push(Vec4::new(normal.x, normal.y, normal.z, 0.0));
+            }
+        }
+        data
+    }
+}
+
+/// メッシュの全てのプリミティブのモーフターゲットを一つのバッファーにまとめ、それぞれの先頭の位置を返す。<br />
+/// 位置0は、モーフターゲットのないプリミティブが指す空のヘッダー。<br />
+/// Pack the morph targets of every primitive of a mesh into one buffer and return where each one starts.<br />
+/// Position 0 is an empty header pointed to by primitives without morph targets.
+pub fn pack_morph_targets<'a, I>(targets: I) -> (Vec<Vec4>, Vec<u32>)
+where
+    I: IntoIterator<Item = Option<&'a MorphTargets>>,
+{
+    let mut data = vec![Vec4::zero()];
+    let mut offsets = vec![];
+    for target in targets {
+        match target.filter(|t| !t.is_empty()) {
+            Some(target) => {
+                offsets.push(data.len() as u32);
+                data.append(&mut target.get_gpu_data());
+            }
+            None => offsets.push(0),
+        }
+    }
+    (data, offsets)
+}
@@ -1,6 +1,6 @@
 use glam::Vec3A;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct PositionInfo {
     pub position: Vec3A,
     pub scale: Vec3A,
@@ -1,11 +1,13 @@
 use crossbeam::sync::ShardedLock;
-use glam::Mat4;
+use glam::{Mat4, Vec4};
 use std::mem::ManuallyDrop;
 use std::sync::Arc;
 
 use crate::game::graphics::vk::{Buffer, Image};
 use crate::game::shared::enums::{SamplerResource, ShaderType};
-use crate::game::shared::structs::{Joint, SkinnedVertex, SSBO};
+use crate::game::shared::structs::{
+    pack_morph_targets, Joint, MorphTargets, RecordedCommands, SkinnedVertex, SSBO,
+};
 use crate::game::traits::Disposable;
 use crate::game::CommandData;
 use ash::vk::CommandBuffer;
@@ -30,6 +32,10 @@ where
     pub command_data: CommandData<CommandType>,
     pub sampler_resource: Option<SamplerResource>,
     pub shader_type: ShaderType,
+    pub morph_targets: Option<MorphTargets>,
+    /// メッシュのSSBOの中で、このプリミティブのモーフターゲットが始まる位置。<br />
+    /// Where the morph targets of this primitive start in the SSBO of the mesh.
+    pub morph_offset: u32,
 }
 
 unsafe impl<BufferType, CommandType, TextureType> Send
@@ -120,6 +126,28 @@ where
     pub root_joint: Option<Joint>,
     pub ssbo: Option<SSBO>,
     pub model_index: usize,
+    pub node_index: usize,
+    /// アニメーションがない時のモーフターゲットの重み。<br />
+    /// Morph target weights used when no animation drives them.
+    pub morph_weights: Vec<f32>,
+    /// プリミティブのコマンドバッファを最後に記録した時のキー。<br />
+    /// Keys the command buffers of the primitives were last recorded with.
+    pub recorded_commands: RecordedCommands,
+}
+
+impl<BufferType, CommandType, TextureType> SkinnedMesh<BufferType, CommandType, TextureType>
+where
+    BufferType: 'static + Clone + Disposable,
+    CommandType: 'static,
+    TextureType: 'static + Clone + Disposable,
+{
+    /// SSBOに送る、全てのプリミティブのモーフターゲットの差分。<br />
+    /// Morph target deltas of every primitive to be sent to the SSBO.
+    pub fn get_morph_deltas(&self) -> Vec<Vec4> {
+        let (data, _) =
+            pack_morph_targets(self.primitives.iter().map(|p| p.morph_targets.as_ref()));
+        data
+    }
 }
 
 impl<BufferType, CommandType, TextureType> Drop
@@ -1,6 +1,6 @@
 use ash::vk::{
-    CommandBuffer, CommandBufferBeginInfo, CommandBufferInheritanceInfo, CommandBufferUsageFlags,
-    CommandPool, DescriptorSet, IndexType, PipelineBindPoint, Rect2D, ShaderStageFlags, Viewport,
+    CommandBuffer, CommandBufferBeginInfo, CommandBufferUsageFlags, CommandPool, IndexType,
+    PipelineBindPoint, ShaderStageFlags,
 };
 use crossbeam::channel::*;
 use crossbeam::sync::ShardedLock;
@@ -12,19 +12,24 @@ use std::collections::HashMap;
 use std::mem::ManuallyDrop;
 use std::sync::{Arc, Weak};
 
-use crate::game::graphics::vk::{Buffer, Graphics, Image, Pipeline, ThreadPool};
+use crate::game::graphics::vk::{Buffer, Graphics, Image, RenderContext};
 use crate::game::shared::enums::ShaderType;
 use crate::game::shared::structs::{
-    generate_joint_transforms, Animation, Channel, ChannelOutputs, ModelMetaData, PositionInfo,
-    SkinnedMesh, SkinnedPrimitive, SkinnedVertex, Vertex, SSBO,
+    blend_morph_weights, create_unit_cube, generate_blended_joint_transforms,
+    generate_joint_transforms, pack_morph_targets, sample_morph_weights, Animation, AnimationPose,
+    AssetKind, Channel, ChannelOutputs, ClipSample, FrameInfo, LoadTask, ModelMetaData,
+    MorphTargets, PositionInfo, RecordKey, RecordedCommands, SkeletonPose, SkinnedMesh,
+    SkinnedPrimitive, SkinnedVertex, SpringBoneConfig, SpringBones, Vertex, MAX_MORPH_TARGETS,
+    SSBO,
 };
 use crate::game::shared::traits::Renderable;
 use crate::game::structs::{Joint, PushConstant};
 use crate::game::traits::{Disposable, GraphicsBase};
 use crate::game::util::read_raw_data;
+use crate::game::ReleasedCommandBuffers;
 use ash::version::DeviceV1_0;
-use ash::Device;
-use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use slotmap::DefaultKey;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// 骨付きのモデル。モデルと同じ、コードの中身はGLTFの読み込みを含めています。<br />
 /// 詳しくはGLTFの仕様書を参照。<br />
@@ -44,6 +49,16 @@ where
     pub model_name: String,
     pub ssbo_index: usize,
     pub animations: HashMap<String, Animation>,
+    pub entity: DefaultKey,
+    /// 状態機械が決めたポーズ。ない場合は最初のアニメーションをループする。<br />
+    /// Pose decided by a state machine. Without one, the first animation is looped.
+    animation_pose: Option<AnimationPose>,
+    /// メッシュごとの揺れる骨。`skinned_meshes`と同じ順番。<br />
+    /// Spring bones per mesh, in the same order as `skinned_meshes`.
+    spring_bones: Vec<Option<SpringBones>>,
+    /// 骨格を表示している間、更新ごとに記録するメッシュごとの姿勢。表示していなければ`None`。<br />
+    /// Poses per mesh recorded on every update while the skeleton is shown, or `None` when it isn't.
+    skeleton_poses: Option<Vec<SkeletonPose>>,
     graphics: Weak<RwLock<ManuallyDrop<GraphicsType>>>,
 }
 
@@ -55,7 +70,9 @@ where
     CommandType: 'static + Clone,
     TextureType: 'static + Clone + Disposable,
 {
-    fn create_model(
+    /// 読み込んだglTFのドキュメントからスキンモデルを作成する。GPUのバッファはまだ作らない。<br />
+    /// Create a skinned model from a loaded glTF document. GPU buffers aren't created yet.
+    pub fn create_model(
         file_name: &str,
         model_index: Arc<AtomicUsize>,
         ssbo_index: usize,
@@ -66,6 +83,7 @@ where
         position_info: PositionInfo,
         color: Vec4,
         texture_index_offset: usize,
+        entity: DefaultKey,
     ) -> Self {
         let meshes = Self::process_model(
             &document,
@@ -94,6 +112,75 @@ where
             model_name: file_name.to_string(),
             ssbo_index,
             animations,
+            entity,
+            animation_pose: None,
+            spring_bones: vec![],
+            skeleton_poses: None,
+            graphics,
+            position_info,
+        }
+    }
+
+    /// 読み込めなかったモデルの代わりに、骨もテクスチャも無い立方体を作る。<br />
+    /// Create a cube without bones or textures in place of a model that failed to load.
+    fn create_placeholder(
+        file_name: &str,
+        model_index: Arc<AtomicUsize>,
+        ssbo_index: usize,
+        graphics: Weak<RwLock<ManuallyDrop<GraphicsType>>>,
+        position_info: PositionInfo,
+        color: Vec4,
+        entity: DefaultKey,
+    ) -> Self {
+        let (vertices, indices) = create_unit_cube();
+        let primitive = SkinnedPrimitive {
+            vertices: vertices
+                .into_iter()
+                .map(|vertex| SkinnedVertex {
+                    vertex,
+                    joints: Vec4::zero(),
+                    weights: Vec4::zero(),
+                })
+                .collect(),
+            indices,
+            vertex_buffer: None,
+            index_buffer: None,
+            texture: None,
+            texture_index: 0,
+            is_disposed: false,
+            command_data: HashMap::new(),
+            sampler_resource: None,
+            shader_type: ShaderType::BasicShaderWithoutTexture,
+            morph_targets: None,
+            morph_offset: 0,
+        };
+        let mesh = SkinnedMesh {
+            primitives: vec![primitive],
+            is_disposed: false,
+            transform: Mat4::identity(),
+            root_joint: None,
+            ssbo: None,
+            model_index: model_index.fetch_add(1, Ordering::SeqCst),
+            node_index: 0,
+            morph_weights: vec![],
+            recorded_commands: RecordedCommands::new(),
+        };
+        SkinnedModel {
+            model_metadata: ModelMetaData {
+                world_matrix: Mat4::identity(),
+                object_color: color,
+                reflectivity: 1.0,
+                shine_damper: 10.0,
+            },
+            skinned_meshes: vec![Arc::new(Mutex::new(mesh))],
+            is_disposed: false,
+            model_name: file_name.to_string(),
+            ssbo_index,
+            animations: HashMap::new(),
+            entity,
+            animation_pose: None,
+            spring_bones: vec![],
+            skeleton_poses: None,
             graphics,
             position_info,
         }
@@ -274,6 +361,16 @@ where
                 }
             };
 
+            let morph_targets = Self::process_morph_targets(
+                reader.read_morph_targets().map(|(positions, normals, _)| {
+                    (
+                        positions.map(|p| p.map(Vec3A::from).collect()),
+                        normals.map(|n| n.map(Vec3A::from).collect()),
+                    )
+                }),
+                skinned_vertices.len(),
+            );
+
             let texture_index = primitive
                 .material()
                 .pbr_metallic_roughness()
@@ -298,10 +395,28 @@ where
                 command_data: std::collections::HashMap::new(),
                 sampler_resource: None,
                 shader_type,
+                morph_targets,
+                morph_offset: 0,
             };
             skinned_primitives.push(skinned_primitive);
         }
 
+        let (_, morph_offsets) =
+            pack_morph_targets(skinned_primitives.iter().map(|p| p.morph_targets.as_ref()));
+        for (primitive, offset) in skinned_primitives.iter_mut().zip(morph_offsets) {
+            primitive.morph_offset = offset;
+        }
+        // ターゲットの数はプリミティブの中で一番多いものに合わせる。
+        // The number of weights follows the primitive with the most targets.
+        let target_count = skinned_primitives
+            .iter()
+            .filter_map(|p| p.morph_targets.as_ref())
+            .map(|t| t.get_target_count())
+            .max()
+            .unwrap_or_default();
+        let mut morph_weights = mesh.weights().map(|w| w.to_vec()).unwrap_or_default();
+        morph_weights.resize(target_count, 0.0);
+
         SkinnedMesh {
             primitives: skinned_primitives,
             is_disposed: false,
@@ -309,6 +424,30 @@ where
             root_joint,
             ssbo: None,
             model_index: model_index.fetch_add(1, Ordering::SeqCst),
+            node_index: node.index(),
+            morph_weights,
+            recorded_commands: RecordedCommands::new(),
+        }
+    }
+
+    /// プリミティブのモーフターゲットを読み込む。位置の差分がなければ`None`。<br />
+    /// Read the morph targets of a primitive. `None` without any position delta.
+    fn process_morph_targets(
+        targets: impl Iterator<Item = (Option<Vec<Vec3A>>, Option<Vec<Vec3A>>)>,
+        vertex_count: usize,
+    ) -> Option<MorphTargets> {
+        let mut morph_targets = MorphTargets::new(vertex_count);
+        for (positions, normals) in targets {
+            let positions = positions.unwrap_or_else(|| vec![Vec3A::zero(); vertex_count]);
+            if !morph_targets.add_target(positions, normals) {
+                log::warn!("Morph targets beyond {} are ignored.", MAX_MORPH_TARGETS);
+                break;
+            }
+        }
+        if morph_targets.is_empty() {
+            None
+        } else {
+            Some(morph_targets)
         }
     }
 
@@ -400,8 +539,8 @@ where
                     ReadOutputs::Scales(scales) => {
                         ChannelOutputs::Scales(scales.map(Vec3A::from).collect())
                     }
-                    ReadOutputs::MorphTargetWeights(_) => {
-                        unimplemented!("glTF property::MorphTargetWeights is unimplemented.")
+                    ReadOutputs::MorphTargetWeights(weights) => {
+                        ChannelOutputs::MorphTargetWeights(weights.into_f32().collect())
                     }
                 };
                 channels.push(Channel {
@@ -436,11 +575,16 @@ impl SkinnedModel<Graphics, Buffer, CommandBuffer, Image> {
         color: Vec4,
         ssbo_index: usize,
         model_index: Arc<AtomicUsize>,
-    ) -> anyhow::Result<Receiver<Self>> {
+        entity: DefaultKey,
+    ) -> anyhow::Result<LoadTask<Self>> {
         log::info!("Loading skinned model from glTF {}...", file_name);
         let graphics_arc = graphics.upgrade().unwrap();
-        let (model_send, model_recv) = bounded(5);
-        rayon::spawn(move || {
+        let (model_send, model_recv) = LoadTask::channel();
+        let thread_pool = graphics_arc.read().thread_pool.clone();
+        thread_pool.spawn_streaming(move || {
+            if model_send.is_cancelled() {
+                return;
+            }
             let graphics_arc = graphics_arc;
             let command_pool: Arc<Mutex<CommandPool>>;
             {
@@ -448,37 +592,56 @@ impl SkinnedModel<Graphics, Buffer, CommandBuffer, Image> {
                 command_pool = graphics.get_idle_command_pool();
             }
             log::info!("Skinned model index: {}", ssbo_index);
-            let (document, buffers, images) =
-                read_raw_data(file_name).expect("Failed to read raw data from glTF.");
-            let (textures, texture_index_offset) =
-                Graphics::create_gltf_textures(images, graphics_arc.clone(), command_pool)
-                    .expect("Failed to create glTF textures.");
             let x: f32 = rotation.x;
             let y: f32 = rotation.y;
             let z: f32 = rotation.z;
-            let mut loaded_model = Self::create_model(
-                file_name,
-                model_index,
-                ssbo_index,
-                document,
-                buffers,
-                textures,
-                graphics,
-                PositionInfo {
-                    position,
-                    scale,
-                    rotation: Vec3A::new(x.to_radians(), y.to_radians(), z.to_radians()),
-                },
-                color,
-                texture_index_offset,
-            );
+            let position_info = PositionInfo {
+                position,
+                scale,
+                rotation: Vec3A::new(x.to_radians(), y.to_radians(), z.to_radians()),
+            };
+            let loaded = read_raw_data(file_name).and_then(|(document, buffers, images)| {
+                let (textures, texture_index_offset) = Graphics::create_gltf_textures(
+                    images,
+                    graphics_arc.clone(),
+                    command_pool,
+                    false,
+                )?;
+                Ok((document, buffers, textures, texture_index_offset))
+            });
+            let mut loaded_model = match loaded {
+                Ok((document, buffers, textures, texture_index_offset)) => Self::create_model(
+                    file_name,
+                    model_index,
+                    ssbo_index,
+                    document,
+                    buffers,
+                    textures,
+                    graphics,
+                    position_info,
+                    color,
+                    texture_index_offset,
+                    entity,
+                ),
+                Err(e) => {
+                    graphics_arc
+                        .read()
+                        .report_asset_warning(AssetKind::Model, file_name, &e);
+                    Self::create_placeholder(
+                        file_name,
+                        model_index,
+                        ssbo_index,
+                        graphics,
+                        position_info,
+                        color,
+                        entity,
+                    )
+                }
+            };
             loaded_model.model_metadata.world_matrix = loaded_model.get_world_matrix();
             {
                 let graphics_lock = graphics_arc.read();
-                let inflight_frame_count = std::env::var("INFLIGHT_BUFFER_COUNT")
-                    .unwrap()
-                    .parse::<usize>()
-                    .unwrap();
+                let inflight_frame_count = graphics_lock.inflight_buffer_count;
                 for mesh in loaded_model.skinned_meshes.iter_mut() {
                     let mut mesh_lock = mesh.lock();
                     let model_index = mesh_lock.model_index;
@@ -502,9 +665,7 @@ impl SkinnedModel<Graphics, Buffer, CommandBuffer, Image> {
             loaded_model
                 .create_buffers(graphics_arc)
                 .expect("Failed to create buffers for skinned model.");
-            model_send
-                .send(loaded_model)
-                .expect("Failed to send model result.");
+            model_send.complete(loaded_model);
         });
         Ok(model_recv)
     }
@@ -556,9 +717,39 @@ impl SkinnedModel<Graphics, Buffer, CommandBuffer, Image> {
                 primitive.vertex_buffer = Some(ManuallyDrop::new(vertex_buffer));
                 primitive.index_buffer = Some(ManuallyDrop::new(index_buffer));
             }
+            mesh_lock.recorded_commands.invalidate();
         }
         Ok(())
     }
+
+    /// メッシュの全てのプリミティブのコマンドバッファに記録する内容のキーを作る。<br />
+    /// Build the key of what goes into the command buffers of every primitive of a mesh.
+    pub fn get_record_key(
+        context: &RenderContext,
+        mesh: &SkinnedMesh<Buffer, CommandBuffer, Image>,
+        pipeline: ash::vk::Pipeline,
+        push_constant: PushConstant,
+    ) -> RecordKey {
+        context
+            .get_record_key()
+            .with(pipeline)
+            .with(bytemuck::cast::<PushConstant, [u8; 32]>(push_constant))
+            .with(mesh.ssbo.as_ref().map(|ssbo| ssbo.descriptor_set))
+            .with(
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| {
+                        (
+                            primitive.vertex_buffer.as_ref().map(|buffer| buffer.buffer),
+                            primitive.index_buffer.as_ref().map(|buffer| buffer.buffer),
+                            primitive.indices.len(),
+                            primitive.texture_index,
+                            primitive.morph_offset,
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+    }
 }
 
 /*impl<GraphicsType, BufferType, CommandType, TextureType>
@@ -646,6 +837,10 @@ where
             model_name: self.model_name.clone(),
             ssbo_index: 0,
             animations: self.animations.clone(),
+            entity: self.entity,
+            animation_pose: None,
+            spring_bones: vec![],
+            skeleton_poses: None,
             graphics: self.graphics.clone(),
         }
     }
@@ -664,14 +859,15 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
             .graphics
             .upgrade()
             .expect("Failed to upgrade graphics handle.");
-        for (index, _) in self.skinned_meshes.iter().enumerate() {
+        for (index, mesh) in self.skinned_meshes.iter().enumerate() {
             let entry = ssbo_handles.entry(index).or_insert_with(Vec::new);
             let graphics_clone = graphics.clone();
+            let morph_deltas = mesh.lock().get_morph_deltas();
             let (ssbo_send, ssbo_recv) = bounded(5);
             rayon::spawn(move || {
                 let buffer = [Mat4::identity(); 500];
                 ssbo_send
-                    .send(SSBO::new(graphics_clone, &buffer))
+                    .send(SSBO::new(graphics_clone, &buffer, &morph_deltas))
                     .expect("Failed to send SSBO result.");
             });
             entry.push(ssbo_recv);
@@ -684,7 +880,9 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
                 .get_mut(0)
                 .expect("Failed to get ssbo result.")
                 .recv()??;
-            mesh.lock().ssbo = Some(item);
+            let mut mesh_lock = mesh.lock();
+            mesh_lock.ssbo = Some(item);
+            mesh_lock.recorded_commands.invalidate();
         }
         Ok(())
     }
@@ -710,6 +908,41 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         buffers
     }
 
+    fn append_command_buffers(&self, frame_index: usize, command_buffers: &mut Vec<CommandBuffer>) {
+        for mesh in self.skinned_meshes.iter() {
+            let mesh_lock = mesh.lock();
+            for primitive in mesh_lock.primitives.iter() {
+                if let Some((_, buffer)) = primitive.command_data.get(&frame_index) {
+                    command_buffers.push(*buffer);
+                }
+            }
+        }
+    }
+
+    fn take_command_buffers(&mut self) -> ReleasedCommandBuffers<CommandBuffer> {
+        self.skinned_meshes
+            .iter()
+            .flat_map(|m| {
+                m.lock()
+                    .primitives
+                    .iter_mut()
+                    .flat_map(|p| p.command_data.drain())
+                    .filter_map(|(_, (pool, buffer))| pool.map(|p| (p, buffer)))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn invalidate_command_buffers(&mut self) {
+        for mesh in self.skinned_meshes.iter() {
+            mesh.lock().recorded_commands.invalidate();
+        }
+    }
+
+    fn get_entity(&self) -> DefaultKey {
+        self.entity
+    }
+
     fn get_model_metadata(&self) -> ModelMetaData {
         self.model_metadata
     }
@@ -718,23 +951,29 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         self.position_info
     }
 
+    fn get_skeleton_poses(&self) -> Vec<SkeletonPose> {
+        self.skeleton_poses.clone().unwrap_or_default()
+    }
+
     fn get_ssbo_index(&self) -> usize {
         self.ssbo_index
     }
 
-    fn render(
-        &self,
-        inheritance_info: Arc<AtomicPtr<CommandBufferInheritanceInfo>>,
-        push_constant: PushConstant,
-        viewport: Viewport,
-        scissor: Rect2D,
-        device: Arc<Device>,
-        pipeline: Arc<ShardedLock<ManuallyDrop<Pipeline>>>,
-        descriptor_set: DescriptorSet,
-        thread_pool: Arc<ThreadPool>,
-        frame_index: usize,
-    ) {
-        let thread_count = thread_pool.thread_count;
+    fn render(&self, context: &RenderContext, frame_info: FrameInfo) {
+        let RenderContext {
+            inheritance_info,
+            viewport,
+            scissor,
+            device,
+            pipeline,
+            descriptor_sets,
+            thread_pool,
+            ..
+        } = context.clone();
+        let FrameInfo {
+            push_constant,
+            frame_index,
+        } = frame_info;
         let pipeline_layout = pipeline
             .read()
             .expect("Failed to lock pipeline when acquiring pipeline layout.")
@@ -750,99 +989,151 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
                 let mesh_clone = mesh.clone();
                 let mesh_lock = mesh.lock();
                 let model_index = mesh_lock.model_index;
+                let record_key =
+                    Self::get_record_key(context, &mesh_lock, pipeline, push_constant).finish();
+                // 前に記録したコマンドがまだ使えれば、記録し直さない。
+                // Skip recording when the previously recorded commands are still valid.
+                if mesh_lock
+                    .recorded_commands
+                    .is_up_to_date(frame_index, record_key)
+                {
+                    continue;
+                }
                 drop(mesh_lock);
                 let inheritance_clone = inheritance_info.clone();
                 let device_clone = device.clone();
-                thread_pool.threads[model_index % thread_count]
-                    .add_job(move || {
-                        let device = device_clone;
-                        let inheritance =
-                            inheritance_clone.load(Ordering::SeqCst).as_ref().unwrap();
-                        let mesh = mesh_clone;
-                        let mesh_lock = mesh.lock();
-                        for primitive in mesh_lock.primitives.iter() {
-                            let command_buffer_begin_info = CommandBufferBeginInfo::builder()
-                                .inheritance_info(inheritance)
-                                .flags(CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
-                                .build();
-                            let (_, command_buffer) =
-                                primitive.command_data.get(&frame_index).unwrap();
-                            let command_buffer = *command_buffer;
-                            let result = device
-                                .begin_command_buffer(command_buffer, &command_buffer_begin_info);
-                            if let Err(e) = result {
-                                log::error!(
-                                    "Error beginning secondary command buffer: {}",
-                                    e.to_string()
-                                );
-                            }
-                            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
-                            device.cmd_set_scissor(command_buffer, 0, &[scissor]);
-                            device.cmd_bind_pipeline(
-                                command_buffer,
-                                PipelineBindPoint::GRAPHICS,
-                                pipeline,
+                let descriptor_sets_clone = descriptor_sets.clone();
+                thread_pool.add_render_job(model_index, move || {
+                    let device = device_clone;
+                    let inheritance = inheritance_clone.load(Ordering::SeqCst).as_ref().unwrap();
+                    let mesh = mesh_clone;
+                    let mut mesh_lock = mesh.lock();
+                    let mut is_recorded = true;
+                    for primitive in mesh_lock.primitives.iter() {
+                        let command_buffer_begin_info = CommandBufferBeginInfo::builder()
+                            .inheritance_info(inheritance)
+                            .flags(CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
+                            .build();
+                        let (_, command_buffer) = primitive.command_data.get(&frame_index).unwrap();
+                        let command_buffer = *command_buffer;
+                        let result =
+                            device.begin_command_buffer(command_buffer, &command_buffer_begin_info);
+                        if let Err(e) = result {
+                            log::error!(
+                                "Error beginning secondary command buffer: {}",
+                                e.to_string()
                             );
+                        }
+                        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+                        device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+                        device.cmd_bind_pipeline(
+                            command_buffer,
+                            PipelineBindPoint::GRAPHICS,
+                            pipeline,
+                        );
+                        let (descriptor_set, texture_index) =
+                            descriptor_sets_clone.locate(primitive.texture_index);
+                        device.cmd_bind_descriptor_sets(
+                            command_buffer,
+                            PipelineBindPoint::GRAPHICS,
+                            pipeline_layout,
+                            0,
+                            &[descriptor_set],
+                            &[],
+                        );
+                        push_constant.texture_index = texture_index;
+                        let casted = bytemuck::cast::<PushConstant, [u8; 32]>(push_constant);
+                        device.cmd_push_constants(
+                            command_buffer,
+                            pipeline_layout,
+                            ShaderStageFlags::FRAGMENT | ShaderStageFlags::VERTEX,
+                            0,
+                            &casted[0..],
+                        );
+                        let vertex_buffers = [primitive.get_vertex_buffer()];
+                        let index_buffer = primitive.get_index_buffer();
+                        if let Some(ssbo) = mesh_lock.ssbo.as_ref() {
                             device.cmd_bind_descriptor_sets(
                                 command_buffer,
                                 PipelineBindPoint::GRAPHICS,
                                 pipeline_layout,
-                                0,
-                                &[descriptor_set],
-                                &[],
-                            );
-                            push_constant.texture_index = primitive.texture_index;
-                            let casted = bytemuck::cast::<PushConstant, [u8; 32]>(push_constant);
-                            device.cmd_push_constants(
-                                command_buffer,
-                                pipeline_layout,
-                                ShaderStageFlags::FRAGMENT | ShaderStageFlags::VERTEX,
-                                0,
-                                &casted[0..],
-                            );
-                            let vertex_buffers = [primitive.get_vertex_buffer()];
-                            let index_buffer = primitive.get_index_buffer();
-                            if let Some(ssbo) = mesh_lock.ssbo.as_ref() {
-                                device.cmd_bind_descriptor_sets(
-                                    command_buffer,
-                                    PipelineBindPoint::GRAPHICS,
-                                    pipeline_layout,
-                                    1,
-                                    &[ssbo.descriptor_set],
-                                    &[],
-                                );
-                            }
-                            device.cmd_bind_vertex_buffers(
-                                command_buffer,
-                                0,
-                                &vertex_buffers[0..],
-                                &[0],
-                            );
-                            device.cmd_bind_index_buffer(
-                                command_buffer,
-                                index_buffer,
-                                0,
-                                IndexType::UINT32,
-                            );
-                            device.cmd_draw_indexed(
-                                command_buffer,
-                                primitive.indices.len() as u32,
                                 1,
-                                0,
-                                0,
-                                0,
+                                &[ssbo.descriptor_set],
+                                &[],
                             );
-                            let result = device.end_command_buffer(command_buffer);
-                            if let Err(e) = result {
-                                log::error!("Error ending command buffer: {}", e.to_string());
-                            }
                         }
-                    })
-                    .expect("Failed to push work into the worker thread.");
+                        device.cmd_bind_vertex_buffers(
+                            command_buffer,
+                            0,
+                            &vertex_buffers[0..],
+                            &[0],
+                        );
+                        device.cmd_bind_index_buffer(
+                            command_buffer,
+                            index_buffer,
+                            0,
+                            IndexType::UINT32,
+                        );
+                        device.cmd_draw_indexed(
+                            command_buffer,
+                            primitive.indices.len() as u32,
+                            1,
+                            0,
+                            0,
+                            // シェーダーはインスタンスの番号からモーフターゲットの位置を得る。
+                            // The shader gets where the morph targets start from the instance index.
+                            primitive.morph_offset,
+                        );
+                        let result = device.end_command_buffer(command_buffer);
+                        if let Err(e) = result {
+                            log::error!("Error ending command buffer: {}", e.to_string());
+                            is_recorded = false;
+                        }
+                    }
+                    if is_recorded {
+                        mesh_lock
+                            .recorded_commands
+                            .set_recorded(frame_index, record_key);
+                    }
+                });
             }
         }
     }
 
+    fn set_animation_pose(&mut self, pose: AnimationPose) {
+        self.animation_pose = Some(pose);
+    }
+
+    fn set_spring_bones(&mut self, config: Arc<SpringBoneConfig>) {
+        let is_same = self
+            .spring_bones
+            .iter()
+            .flatten()
+            .next()
+            .map(|s| Arc::ptr_eq(s.get_config(), &config))
+            .unwrap_or(false);
+        if is_same {
+            return;
+        }
+        self.spring_bones = self
+            .skinned_meshes
+            .iter()
+            .map(|mesh| {
+                mesh.lock()
+                    .root_joint
+                    .as_ref()
+                    .map(|joint| SpringBones::new(config.clone(), joint))
+                    .filter(|spring_bones| !spring_bones.is_empty())
+            })
+            .collect();
+    }
+
+    fn set_skeleton_recording(&mut self, is_recording: bool) {
+        if is_recording != self.skeleton_poses.is_some() {
+            self.skeleton_poses = if is_recording { Some(vec![]) } else { None };
+        }
+    }
+
     fn set_model_metadata(&mut self, model_metadata: ModelMetaData) {
         self.model_metadata = model_metadata;
     }
@@ -851,42 +1142,113 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         self.position_info = position_info;
     }
 
+    fn set_entity(&mut self, entity: DefaultKey) {
+        self.entity = entity;
+    }
+
     fn set_ssbo_index(&mut self, ssbo_index: usize) {
         self.ssbo_index = ssbo_index;
     }
 
     fn update(&mut self, delta_time: f64) {
-        let mut keys = self.animations.keys();
-        let animation_name = keys.next().cloned().unwrap();
+        // 代わりの立方体のように、アニメーションのないモデルは動かさない。
+        // Models without animations, such as the placeholder cube, aren't animated.
+        let animation_name = match self.animations.keys().next() {
+            Some(animation_name) => animation_name.clone(),
+            None => return,
+        };
         let animation = self.animations.get_mut(&animation_name).unwrap();
         animation.current_time += delta_time as f32;
         let animation_end_time = *animation.channels.last().unwrap().inputs.last().unwrap();
         if animation.current_time > animation_end_time {
             animation.current_time -= animation_end_time;
         }
-        let buffer_size = std::mem::size_of::<Mat4>() * 500;
-        for mesh in self.skinned_meshes.iter() {
+        let animations = &self.animations;
+        let find_clip = |clip: &ClipSample| {
+            animations
+                .get(&clip.clip)
+                .map(|a| (a, a.get_clip_time(clip.time, clip.is_looping)))
+        };
+        let posed = self.animation_pose.as_ref().and_then(|pose| {
+            let current = find_clip(&pose.current);
+            if current.is_none() {
+                log::debug!("Animation clip {} was not found.", &pose.current.clip);
+            }
+            current.map(|current| {
+                let previous = pose
+                    .previous
+                    .as_ref()
+                    .and_then(|p| find_clip(p))
+                    .map(|previous| (previous, pose.blend_weight));
+                (current, previous)
+            })
+        });
+        // 状態機械がないか、そのクリップが見つからなければ、既定のアニメーションをループする。
+        // Without a state machine, or if its clip isn't found, loop the default animation.
+        let (current, previous) = posed.unwrap_or_else(|| {
+            let animation = &animations[&animation_name];
+            ((animation, animation.current_time), None)
+        });
+        let world_matrix = self.model_metadata.world_matrix;
+        if let Some(skeleton_poses) = self.skeleton_poses.as_mut() {
+            skeleton_poses.clear();
+        }
+        for (index, mesh) in self.skinned_meshes.iter().enumerate() {
             let mesh_lock = mesh.lock();
+            // モーフターゲットは骨がなくても動かせる。
+            // Morph targets can be animated even without a skeleton.
+            if !mesh_lock.morph_weights.is_empty() {
+                let node_index = mesh_lock.node_index;
+                let default_weights = mesh_lock.morph_weights.as_slice();
+                let current_weights =
+                    sample_morph_weights(current.0, current.1, node_index, default_weights);
+                let weights = match previous {
+                    Some((previous, weight)) => blend_morph_weights(
+                        &sample_morph_weights(previous.0, previous.1, node_index, default_weights),
+                        &current_weights,
+                        weight,
+                    ),
+                    None => current_weights,
+                };
+                mesh_lock
+                    .ssbo
+                    .as_ref()
+                    .unwrap()
+                    .write_morph_weights(&weights);
+            }
             let mut buffer = [Mat4::identity(); 500];
             let local_transform = mesh_lock.transform;
-            match mesh_lock.root_joint.as_ref() {
-                Some(joint) => generate_joint_transforms(
-                    animation,
-                    animation.current_time,
+            match (mesh_lock.root_joint.as_ref(), previous) {
+                (Some(joint), Some((previous, weight))) => generate_blended_joint_transforms(
+                    previous,
+                    current,
+                    weight,
+                    joint,
+                    local_transform,
+                    &mut buffer,
+                ),
+                (Some(joint), None) => generate_joint_transforms(
+                    current.0,
+                    current.1,
                     joint,
                     local_transform,
                     &mut buffer,
                 ),
-                None => continue,
+                (None, _) => continue,
+            }
+            if let Some(Some(spring_bones)) = self.spring_bones.get_mut(index) {
+                spring_bones.update(delta_time as f32, world_matrix, &mut buffer);
             }
-            let mapped = mesh_lock.ssbo.as_ref().unwrap().buffer.mapped_memory;
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    buffer.as_ptr() as *const std::ffi::c_void,
-                    mapped,
-                    buffer_size,
-                );
+            if let (Some(skeleton_poses), Some(joint)) =
+                (self.skeleton_poses.as_mut(), mesh_lock.root_joint.as_ref())
+            {
+                skeleton_poses.push(SkeletonPose::new(joint, &buffer, world_matrix));
             }
+            mesh_lock
+                .ssbo
+                .as_ref()
+                .unwrap()
+                .write_joint_matrices(&buffer);
         }
     }
 
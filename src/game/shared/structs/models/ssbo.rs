@@ -2,17 +2,23 @@ use ash::vk::{
     BufferUsageFlags, DescriptorBufferInfo, DescriptorSet, DescriptorType, MemoryPropertyFlags,
     ShaderStageFlags,
 };
-use glam::Mat4;
+use glam::{Mat4, Vec4};
 use parking_lot::RwLock;
 use std::mem::ManuallyDrop;
 use std::sync::Arc;
 
 use crate::game::graphics::vk::{Buffer, DescriptorBuilder, Graphics};
+use crate::game::shared::structs::MAX_MORPH_TARGETS;
 use crate::game::shared::traits::Disposable;
 use crate::game::traits::Mappable;
 
+const JOINT_MATRICES_SIZE: usize = std::mem::size_of::<Mat4>() * 500;
+const MORPH_WEIGHTS_SIZE: usize = std::mem::size_of::<f32>() * MAX_MORPH_TARGETS;
+
 /// これは主なSSBOではなく、骨付きのモデルの頂点情報を保存するためのSSBOです。<br />
-/// This is not the primary SSBO. This is the SSBO for storing all vertices information of a skinned model.
+/// 骨の行列、モーフターゲットの重み、モーフターゲットの差分の順に並びます。<br />
+/// This is not the primary SSBO. This is the SSBO for storing all vertices information of a skinned model.<br />
+/// Joint matrices, morph target weights and morph target deltas are laid out in this order.
 #[derive(Clone)]
 pub struct SSBO {
     pub buffer: Buffer,
@@ -24,12 +30,14 @@ impl SSBO {
     pub fn new(
         graphics: Arc<RwLock<ManuallyDrop<Graphics>>>,
         data: &[Mat4; 500],
+        morph_deltas: &[Vec4],
     ) -> anyhow::Result<Self> {
         let graphics_lock = graphics.read();
         let device = graphics_lock.logical_device.clone();
         let allocator = graphics_lock.allocator.clone();
         drop(graphics_lock);
-        let buffer_size = std::mem::size_of::<Mat4>() * 500;
+        let morph_deltas_size = std::mem::size_of::<Vec4>() * morph_deltas.len();
+        let buffer_size = JOINT_MATRICES_SIZE + MORPH_WEIGHTS_SIZE + morph_deltas_size;
         //let descriptor_set_layout = graphics_lock.ssbo_descriptor_set_layout;
         let mut buffer = Buffer::new(
             Arc::downgrade(&device),
@@ -37,13 +45,15 @@ impl SSBO {
             BufferUsageFlags::STORAGE_BUFFER,
             MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
             Arc::downgrade(&allocator),
-        );
-        let mapped = buffer.map_memory(buffer_size as u64, 0);
+        )?;
+        let mapped = buffer.map_memory(buffer_size as u64, 0) as *mut u8;
         unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, mapped, JOINT_MATRICES_SIZE);
+            std::ptr::write_bytes(mapped.add(JOINT_MATRICES_SIZE), 0, MORPH_WEIGHTS_SIZE);
             std::ptr::copy_nonoverlapping(
-                data.as_ptr() as *const std::ffi::c_void,
-                mapped,
-                buffer_size,
+                morph_deltas.as_ptr() as *const u8,
+                mapped.add(JOINT_MATRICES_SIZE + MORPH_WEIGHTS_SIZE),
+                morph_deltas_size,
             );
         }
         //let layouts = vec![descriptor_set_layout];
@@ -96,6 +106,32 @@ impl SSBO {
             .build()];
         device.update_descriptor_sets(write_descriptor.as_slice(), &[]);*/
     }
+
+    pub fn write_joint_matrices(&self, data: &[Mat4; 500]) {
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                self.buffer.mapped_memory as *mut u8,
+                JOINT_MATRICES_SIZE,
+            );
+        }
+    }
+
+    /// モーフターゲットの重みを書き込む。足りない重みは0になる。<br />
+    /// Write the morph target weights. Missing weights become zero.
+    pub fn write_morph_weights(&self, weights: &[f32]) {
+        let mut data = [0.0_f32; MAX_MORPH_TARGETS];
+        for (dst, src) in data.iter_mut().zip(weights.iter()) {
+            *dst = *src;
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                (self.buffer.mapped_memory as *mut u8).add(JOINT_MATRICES_SIZE),
+                MORPH_WEIGHTS_SIZE,
+            );
+        }
+    }
 }
 
 impl Drop for SSBO {
@@ -0,0 +1,109 @@
+use crate::game::shared::enums::MusicLayer;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+fn default_beats_per_bar() -> u32 {
+    4
+}
+
+fn default_crossfade_beats() -> f32 {
+    4.0
+}
+
+fn default_tension_distance() -> f32 {
+    30.0
+}
+
+fn default_low_hp_ratio() -> f32 {
+    0.3
+}
+
+/// シーンのBGM。層ごとの音源は同じテンポと長さで作り、同時に再生して音量だけを変える。<br />
+/// Music of a scene. The stems of every layer share the same tempo and length, and are played together with only their volumes changing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MusicSet {
+    /// 一分間の拍数。<br />
+    /// Beats per minute.
+    pub tempo: f32,
+    #[serde(default = "default_beats_per_bar")]
+    pub beats_per_bar: u32,
+    /// 層を切り替える時に掛けるクロスフェードの長さ（拍）。<br />
+    /// Length of the crossfade when switching layers, in beats.
+    #[serde(default = "default_crossfade_beats")]
+    pub crossfade_beats: f32,
+    /// 敵がこの距離より近いと緊張の層にする（メートル）。<br />
+    /// Enemies closer than this distance switch to the tension layer, in meters.
+    #[serde(default = "default_tension_distance")]
+    pub tension_distance: f32,
+    /// HPの割合がこれ以下だと緊張の層にする。<br />
+    /// The tension layer is used when the ratio of HP is at or below this.
+    #[serde(default = "default_low_hp_ratio")]
+    pub low_hp_ratio: f32,
+    pub stems: HashMap<MusicLayer, String>,
+}
+
+impl MusicSet {
+    /// シーンの名前ごとのBGMを定義したファイルから、指定したシーンのものを読み込む。<br />
+    /// Load the music of a scene from a file defining the music of each scene by name.
+    pub fn load(file_name: &str, scene_name: &str) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(file_name)?;
+        Self::from_json(&json, scene_name)
+    }
+
+    pub fn from_json(json: &str, scene_name: &str) -> anyhow::Result<Self> {
+        let mut sets: HashMap<String, MusicSet> = serde_json::from_str(json)?;
+        let set = sets
+            .remove(scene_name)
+            .ok_or_else(|| anyhow::anyhow!("No music is defined for scene {}.", scene_name))?;
+        set.validate()?;
+        Ok(set)
+    }
+
+    /// ファイルに定義された全てのシーンのBGMを読み込む。<br />
+    /// Load the music of every scene defined in the file.
+    pub fn from_json_all(json: &str) -> anyhow::Result<HashMap<String, Self>> {
+        let sets: HashMap<String, MusicSet> = serde_json::from_str(json)?;
+        for (scene_name, set) in sets.iter() {
+            set.validate()
+                .with_context(|| format!("Invalid music for scene {}.", scene_name))?;
+        }
+        Ok(sets)
+    }
+
+    /// テンポと拍子が正で、全ての層に音源があることを確かめる。<br />
+    /// Check that the tempo and the time signature are positive, and that every layer has a stem.
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.tempo <= 0.0 || self.beats_per_bar == 0 {
+            return Err(anyhow::anyhow!(
+                "Music tempo and beats per bar must be positive."
+            ));
+        }
+        if let Some(layer) = MusicLayer::ALL
+            .iter()
+            .find(|layer| !self.stems.contains_key(layer))
+        {
+            return Err(anyhow::anyhow!("Music layer {:?} has no stem.", layer));
+        }
+        Ok(())
+    }
+
+    /// 一拍の長さ（秒）。<br />
+    /// Length of a beat, in seconds.
+    pub fn get_beat_length(&self) -> f64 {
+        60.0 / self.tempo as f64
+    }
+
+    pub fn get_bar_length(&self) -> f64 {
+        self.get_beat_length() * self.beats_per_bar as f64
+    }
+
+    /// 層の順番に並べた音源のファイル。<br />
+    /// Files of the stems in the order of the layers.
+    pub fn get_stem_files(&self) -> Vec<&str> {
+        MusicLayer::ALL
+            .iter()
+            .filter_map(|layer| self.stems.get(layer).map(|s| s.as_str()))
+            .collect()
+    }
+}
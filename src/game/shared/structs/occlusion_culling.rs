@@ -0,0 +1,177 @@
+use glam::Vec3A;
+use slotmap::DefaultKey;
+use std::collections::HashSet;
+
+use crate::game::shared::components::Bounds;
+use crate::game::ui::TweakRegistry;
+
+const OCCLUSION_CULLING_TWEAK: &str = "Graphics/Occlusion Culling";
+
+/// 境界ボックスを描いて隠れているかを調べる、オクルージョンクエリのシェーダー。<br />
+/// Shaders of the occlusion queries, which draw bounding boxes to find out whether they're hidden.
+pub const OCCLUSION_VERTEX_SHADER: &str = "./shaders/occlusion_box_vert.spv";
+pub const OCCLUSION_FRAGMENT_SHADER: &str = "./shaders/occlusion_box_frag.spv";
+
+/// 一フレームで調べる境界ボックスの最大数。超えた分は調べずに描画する。<br />
+/// Maximum number of bounding boxes tested in a frame. Any beyond it are drawn without testing.
+pub const MAX_OCCLUSION_QUERIES: usize = 512;
+
+/// 境界ボックスを広げる量。ボックスが自分のメッシュの深度と重なって隠れたことにならないようにする。<br />
+/// How much bounding boxes are grown, so a box doesn't count as hidden by the depth of its own mesh.
+const BOUNDS_PADDING: f32 = 0.05;
+
+/// カメラがこの距離より境界ボックスに近ければ、ボックスがニアクリップ面で切れるので調べずに描く。<br />
+/// Bounding boxes closer to the camera than this are clipped by the near plane, so they're drawn without testing.
+const CAMERA_MARGIN: f32 = 0.5;
+
+/// 一つのオクルージョンクエリで描く境界ボックス。<br />
+/// A bounding box drawn by a single occlusion query.
+#[derive(Copy, Clone, Debug)]
+pub struct OcclusionQuery {
+    pub entity: DefaultKey,
+    pub bounds: Bounds,
+}
+
+/// 境界ボックスを描くプッシュコンスタント。<br />
+/// Push constant drawing a bounding box.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OcclusionPushConstant {
+    pub bounds_min: [f32; 4],
+    pub bounds_max: [f32; 4],
+}
+
+impl From<&Bounds> for OcclusionPushConstant {
+    fn from(bounds: &Bounds) -> Self {
+        OcclusionPushConstant {
+            bounds_min: [bounds.min.x, bounds.min.y, bounds.min.z, 1.0],
+            bounds_max: [bounds.max.x, bounds.max.y, bounds.max.z, 1.0],
+        }
+    }
+}
+
+/// オクルージョンクエリの結果から、地形や他のモデルに隠れたエンティティを覚えておく。<br />
+/// 結果はフレームのフェンスを待った後に読むので、描画に反映されるのは処理中のフレームの数だけ遅れる。<br />
+/// 隠れたエンティティも境界ボックスは毎フレーム調べ、見えるようになればまた描く。<br />
+/// Remembers the entities hidden behind terrain or other models from the results of occlusion queries.<br />
+/// Results are read after waiting for the frame's fence, so they reach rendering as many frames late as there are frames in flight.<br />
+/// Bounding boxes of hidden entities are still tested every frame, so they're drawn again once they show up.
+#[derive(Clone, Debug)]
+pub struct OcclusionCuller {
+    is_enabled: bool,
+    queued: Vec<OcclusionQuery>,
+    /// フレームごとに、クエリを記録したエンティティ。<br />
+    /// Entities whose queries were recorded in each frame.
+    pending: Vec<Vec<DefaultKey>>,
+    occluded: HashSet<DefaultKey>,
+}
+
+impl OcclusionCuller {
+    pub fn new(frame_count: usize) -> Self {
+        OcclusionCuller {
+            is_enabled: true,
+            queued: vec![],
+            pending: vec![vec![]; frame_count],
+            occluded: HashSet::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    /// 無効にすると、覚えている結果も忘れて全て描く。<br />
+    /// Disabling it also forgets the remembered results, drawing everything.
+    pub fn set_enabled(&mut self, is_enabled: bool) {
+        self.is_enabled = is_enabled;
+        if !is_enabled {
+            self.queued.clear();
+            self.pending.iter_mut().for_each(|pending| pending.clear());
+            self.occluded.clear();
+        }
+    }
+
+    /// 視錐台の中のエンティティを次のフレームで調べるよう並べ、隠れているものを返す。<br />
+    /// 候補にないエンティティは視錐台から出たので忘れ、また入ってきた時は調べ終わるまで描く。<br />
+    /// Queue the entities inside the frustum to be tested in the next frame, returning the ones that are hidden.<br />
+    /// Entities that aren't candidates left the frustum and are forgotten, so they're drawn until tested again when they come back.
+    pub fn queue(&mut self, candidates: &[(DefaultKey, Bounds)], eye: Vec3A) -> HashSet<DefaultKey> {
+        self.queued.clear();
+        if !self.is_enabled {
+            return HashSet::new();
+        }
+        let mut candidate_keys = HashSet::with_capacity(candidates.len());
+        for (entity, bounds) in candidates.iter() {
+            candidate_keys.insert(*entity);
+            let padding = Vec3A::splat(BOUNDS_PADDING);
+            let bounds = Bounds::new(bounds.min - padding, bounds.max + padding);
+            let margin = Vec3A::splat(CAMERA_MARGIN);
+            let is_near_camera = (bounds.min - margin).cmple(eye).all()
+                && eye.cmple(bounds.max + margin).all();
+            if is_near_camera || self.queued.len() >= MAX_OCCLUSION_QUERIES {
+                self.occluded.remove(entity);
+                continue;
+            }
+            self.queued.push(OcclusionQuery {
+                entity: *entity,
+                bounds,
+            });
+        }
+        self.occluded.retain(|entity| candidate_keys.contains(entity));
+        self.occluded.clone()
+    }
+
+    /// 並べたクエリをこのフレームに記録するために取り出す。<br />
+    /// Take the queued queries to record them in this frame.
+    pub fn take_queries(&mut self, frame_index: usize) -> Vec<OcclusionQuery> {
+        let queries = std::mem::take(&mut self.queued);
+        if let Some(pending) = self.pending.get_mut(frame_index) {
+            *pending = queries.iter().map(|query| query.entity).collect();
+        }
+        queries
+    }
+
+    /// 前回このフレームで記録したクエリの、描いたサンプルの数を反映する。<br />
+    /// 結果のないエンティティは前の状態のまま。<br />
+    /// Apply the number of samples drawn by the queries last recorded in this frame.<br />
+    /// Entities without a result keep their previous state.
+    pub fn apply_results(&mut self, frame_index: usize, samples: &[u64]) {
+        let pending = match self.pending.get_mut(frame_index) {
+            Some(pending) => std::mem::take(pending),
+            None => return,
+        };
+        if !self.is_enabled {
+            return;
+        }
+        for (entity, samples) in pending.into_iter().zip(samples.iter()) {
+            if *samples == 0 {
+                self.occluded.insert(entity);
+            } else {
+                self.occluded.remove(&entity);
+            }
+        }
+    }
+
+    pub fn is_occluded(&self, entity: DefaultKey) -> bool {
+        self.occluded.contains(&entity)
+    }
+
+    pub fn get_occluded_count(&self) -> usize {
+        self.occluded.len()
+    }
+
+    /// 設定を調整パネルに登録する。<br />
+    /// Register the settings to the tweak panel.
+    pub fn register_tweaks(&mut self, tweaks: &mut TweakRegistry) {
+        tweaks.register_bool(OCCLUSION_CULLING_TWEAK, self.is_enabled);
+        self.apply_tweaks(tweaks);
+    }
+
+    pub fn apply_tweaks(&mut self, tweaks: &TweakRegistry) {
+        if let Some(is_enabled) = tweaks.get_bool(OCCLUSION_CULLING_TWEAK) {
+            if is_enabled != self.is_enabled {
+                self.set_enabled(is_enabled);
+            }
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use glam::{Vec3A, Vec4};
+
+/// 全ての発生源で共有する粒子バッファの粒子数。<br />
+/// Number of particles in the particle buffer shared by every emitter.
+pub const MAX_PARTICLES: u32 = 16384;
+
+/// 色の変化に使えるキーの数。シェーダーのプッシュコンスタントに合わせる。<br />
+/// Number of keys usable for the color change, matching the push constant of the shaders.
+pub const MAX_COLOR_KEYS: usize = 4;
+
+/// 計算シェーダーのワークグループの大きさ。<br />
+/// Size of a workgroup of the compute shader.
+pub const PARTICLE_WORKGROUP_SIZE: u32 = 64;
+
+pub const PARTICLE_VERTEX_SHADER: &str = "./shaders/particle_vert.spv";
+pub const PARTICLE_FRAGMENT_SHADER: &str = "./shaders/particle_frag.spv";
+pub const PARTICLE_COMPUTE_SHADER: &str = "./shaders/particle_comp.spv";
+
+/// 範囲の粒子を消してから動かす。前の発生源の粒子が残っている範囲を使う時に立てる。<br />
+/// Kill the particles of the range before simulating. Set when reusing a range that may hold particles of a previous emitter.
+pub const PARTICLE_FLAG_RESET: u32 = 1;
+
+/// GPUの粒子バッファの一つの粒子。`position.w`は経った時間、`velocity.w`は寿命。<br />
+/// A particle in the GPU particle buffer. `position.w` is its age and `velocity.w` its lifetime.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GpuParticle {
+    pub position: [f32; 4],
+    pub velocity: [f32; 4],
+}
+
+/// 寿命に対する割合と、その時の色。<br />
+/// A fraction of the lifetime and the color at that point.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorKey {
+    pub time: f32,
+    pub color: Vec4,
+}
+
+impl ColorKey {
+    pub fn new(time: f32, color: Vec4) -> Self {
+        ColorKey { time, color }
+    }
+}
+
+/// 寿命に合わせて変わる粒子の色。<br />
+/// キーは`MAX_COLOR_KEYS`個に揃え、足りない分は最後のキーで埋める。シェーダーも同じ計算をする。<br />
+/// The color of particles changing over their lifetime.<br />
+/// Keys are padded to `MAX_COLOR_KEYS` with the last key, and the shaders do the same calculation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorOverLife {
+    keys: [ColorKey; MAX_COLOR_KEYS],
+}
+
+impl ColorOverLife {
+    /// キーを時間の順に並べて作る。多すぎるキーは捨て、キーがなければ白になる。<br />
+    /// Create from keys sorted by time. Extra keys are dropped, and no keys means white.
+    pub fn new(keys: &[ColorKey]) -> Self {
+        let mut sorted = keys
+            .iter()
+            .take(MAX_COLOR_KEYS)
+            .map(|key| ColorKey::new(key.time.max(0.0).min(1.0), key.color))
+            .collect::<Vec<_>>();
+        sorted.sort_by(|a, b| {
+            a.time
+                .partial_cmp(&b.time)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let last = sorted
+            .last()
+            .copied()
+            .unwrap_or_else(|| ColorKey::new(0.0, Vec4::one()));
+        sorted.resize(MAX_COLOR_KEYS, last);
+        let mut padded = [last; MAX_COLOR_KEYS];
+        padded.copy_from_slice(&sorted);
+        ColorOverLife { keys: padded }
+    }
+
+    pub fn constant(color: Vec4) -> Self {
+        Self::new(&[ColorKey::new(0.0, color)])
+    }
+
+    /// 同じ色のまま、最後に透明になる。<br />
+    /// Keep the same color and fade out at the end.
+    pub fn fade_out(color: Vec4) -> Self {
+        let mut transparent = color;
+        transparent.w = 0.0;
+        Self::new(&[ColorKey::new(0.0, color), ColorKey::new(1.0, transparent)])
+    }
+
+    /// 寿命に対する割合`t`の色。<br />
+    /// The color at `t`, a fraction of the lifetime.
+    pub fn evaluate(&self, t: f32) -> Vec4 {
+        let mut color = self.keys[0].color;
+        for pair in self.keys.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            if t > start.time {
+                let fraction = if end.time > start.time {
+                    ((t - start.time) / (end.time - start.time))
+                        .max(0.0)
+                        .min(1.0)
+                } else {
+                    1.0
+                };
+                color = start.color.lerp(end.color, fraction);
+            }
+        }
+        color
+    }
+
+    pub fn get_keys(&self) -> &[ColorKey; MAX_COLOR_KEYS] {
+        &self.keys
+    }
+}
+
+/// 粒子を動かす計算シェーダーのプッシュコンスタント。発生源ごとに一回ディスパッチする。<br />
+/// Push constant of the compute shader simulating particles, dispatched once per emitter.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ParticleSimulationPushConstant {
+    /// 発生源の位置。`w`は放出する円錐の半角（ラジアン）。<br />
+    /// Position of the emitter. `w` is the half angle of the emission cone in radians.
+    pub origin: [f32; 4],
+    /// 放出する方向。`w`は重力の加速度。<br />
+    /// Direction of emission. `w` is the gravitational acceleration.
+    pub direction: [f32; 4],
+    pub base: u32,
+    pub capacity: u32,
+    /// 今回生まれる粒子の、範囲の中の最初の番号。<br />
+    /// Index in the range of the first particle born this time.
+    pub spawn_start: u32,
+    pub spawn_count: u32,
+    pub speed_min: f32,
+    pub speed_max: f32,
+    pub lifetime: f32,
+    pub delta_time: f32,
+    pub seed: u32,
+    pub flags: u32,
+    pub padding: [u32; 2],
+}
+
+/// 粒子を板ポリゴンで描くパイプラインのプッシュコンスタント。<br />
+/// Push constant of the pipeline drawing particles as billboards.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ParticleDrawPushConstant {
+    pub colors: [[f32; 4]; MAX_COLOR_KEYS],
+    pub color_times: [f32; MAX_COLOR_KEYS],
+    pub base: u32,
+    pub capacity: u32,
+    /// 板ポリゴンの半分の大きさ（メートル）。<br />
+    /// Half the size of a billboard, in meters.
+    pub size: f32,
+    /// 1なら加算合成、0ならアルファ合成。<br />
+    /// 1 for additive blending, 0 for alpha blending.
+    pub additive: f32,
+}
+
+impl ParticleDrawPushConstant {
+    pub fn new(
+        color_over_life: &ColorOverLife,
+        base: u32,
+        capacity: u32,
+        size: f32,
+        is_additive: bool,
+    ) -> Self {
+        let keys = color_over_life.get_keys();
+        let mut push_constant = ParticleDrawPushConstant {
+            base,
+            capacity,
+            size,
+            additive: if is_additive { 1.0 } else { 0.0 },
+            ..Default::default()
+        };
+        for (index, key) in keys.iter().enumerate() {
+            push_constant.colors[index] = key.color.into();
+            push_constant.color_times[index] = key.time;
+        }
+        push_constant
+    }
+}
+
+/// 一つの発生源を一フレーム動かして描くための情報。<br />
+/// What's needed to simulate and draw a single emitter for a frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParticleDispatch {
+    pub simulation: ParticleSimulationPushConstant,
+    pub draw: ParticleDrawPushConstant,
+}
+
+impl ParticleDispatch {
+    /// 範囲の全ての粒子を扱うのに必要なワークグループの数。<br />
+    /// Number of workgroups needed to cover every particle of the range.
+    pub fn get_workgroup_count(&self) -> u32 {
+        (self.simulation.capacity + PARTICLE_WORKGROUP_SIZE - 1) / PARTICLE_WORKGROUP_SIZE
+    }
+}
+
+/// 粒子バッファの中で発生源に割り当てた範囲。粒子は範囲の中を輪のように順番に生まれる。<br />
+/// A range of the particle buffer assigned to an emitter. Particles are born in turn, wrapping around the range.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParticleRange {
+    pub base: u32,
+    pub capacity: u32,
+    head: u32,
+    is_new: bool,
+}
+
+impl ParticleRange {
+    pub fn new(base: u32, capacity: u32) -> Self {
+        ParticleRange {
+            base,
+            capacity,
+            head: 0,
+            is_new: true,
+        }
+    }
+
+    /// `count`個の粒子を生む場所を進め、最初の番号と実際に生む数を返す。範囲より多くは生めない。<br />
+    /// Advance where `count` particles are born, returning the first index and the number actually born. No more than the range can be born.
+    pub fn advance(&mut self, count: u32) -> (u32, u32) {
+        let count = count.min(self.capacity);
+        let start = self.head;
+        if self.capacity > 0 {
+            self.head = (self.head + count) % self.capacity;
+        }
+        (start, count)
+    }
+
+    /// 割り当てたばかりで、まだ一度も動かしていないかどうか。一度だけ`true`を返す。<br />
+    /// Whether the range was just assigned and hasn't been simulated yet. Returns `true` only once.
+    pub fn take_is_new(&mut self) -> bool {
+        std::mem::replace(&mut self.is_new, false)
+    }
+}
+
+/// 粒子バッファを発生源ごとの範囲に分ける。空いている範囲から最初に収まる所を選ぶ。<br />
+/// Splits the particle buffer into ranges for each emitter, choosing the first free range that fits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParticleRangeAllocator {
+    capacity: u32,
+    /// 空いている範囲の始まりと長さ。始まりの順に並べる。<br />
+    /// Starts and lengths of the free ranges, sorted by start.
+    free_ranges: Vec<(u32, u32)>,
+}
+
+impl ParticleRangeAllocator {
+    pub fn new(capacity: u32) -> Self {
+        ParticleRangeAllocator {
+            capacity,
+            free_ranges: if capacity > 0 {
+                vec![(0, capacity)]
+            } else {
+                vec![]
+            },
+        }
+    }
+
+    pub fn allocate(&mut self, count: u32) -> Option<ParticleRange> {
+        if count == 0 {
+            return None;
+        }
+        let index = self
+            .free_ranges
+            .iter()
+            .position(|(_, length)| *length >= count)?;
+        let (start, length) = self.free_ranges[index];
+        if length == count {
+            self.free_ranges.remove(index);
+        } else {
+            self.free_ranges[index] = (start + count, length - count);
+        }
+        Some(ParticleRange::new(start, count))
+    }
+
+    /// 範囲を返す。隣の空いている範囲とは一つにまとめる。<br />
+    /// Return a range, merging it with the neighbouring free ranges.
+    pub fn free(&mut self, range: &ParticleRange) {
+        let index = self
+            .free_ranges
+            .iter()
+            .position(|(start, _)| *start > range.base)
+            .unwrap_or(self.free_ranges.len());
+        self.free_ranges.insert(index, (range.base, range.capacity));
+        if index + 1 < self.free_ranges.len() {
+            let (start, length) = self.free_ranges[index];
+            let (next_start, next_length) = self.free_ranges[index + 1];
+            if start + length == next_start {
+                self.free_ranges[index] = (start, length + next_length);
+                self.free_ranges.remove(index + 1);
+            }
+        }
+        if index > 0 {
+            let (previous_start, previous_length) = self.free_ranges[index - 1];
+            let (start, length) = self.free_ranges[index];
+            if previous_start + previous_length == start {
+                self.free_ranges[index - 1] = (previous_start, previous_length + length);
+                self.free_ranges.remove(index);
+            }
+        }
+    }
+
+    pub fn get_capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn get_free_count(&self) -> u32 {
+        self.free_ranges.iter().map(|(_, length)| *length).sum()
+    }
+}
+
+/// 方向を正規化する。長さがなければ上を向く。<br />
+/// Normalize a direction, pointing up if it has no length.
+pub fn get_emission_direction(direction: Vec3A) -> Vec3A {
+    if direction.length_squared() > f32::EPSILON {
+        direction.normalize()
+    } else {
+        Vec3A::unit_y()
+    }
+}
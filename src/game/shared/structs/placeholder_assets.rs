@@ -0,0 +1,151 @@
+use crate::game::shared::structs::Vertex;
+use glam::{Vec2, Vec3A};
+use std::fmt::{Display, Formatter};
+
+/// 読み込めなかったテクスチャの代わりに使うチェッカーの幅と高さ。<br />
+/// Width and height of the checker used in place of textures that failed to load.
+pub const PLACEHOLDER_TEXTURE_SIZE: u32 = 64;
+
+/// チェッカーの一マスの幅。<br />
+/// Width of one cell of the checker.
+pub const PLACEHOLDER_CELL_SIZE: u32 = 8;
+
+/// 目立つように、マゼンタと黒を交互に並べる。<br />
+/// Magenta and black alternate so that the placeholder stands out.
+const PLACEHOLDER_COLORS: [[u8; 4]; 2] = [[255, 0, 255, 255], [0, 0, 0, 255]];
+
+/// 読み込みに失敗した素材の種類。<br />
+/// Kind of an asset that failed to load.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum AssetKind {
+    Texture,
+    Model,
+    Shader,
+    Audio,
+    /// カットシーンのファイル。<br />
+    /// A cutscene file.
+    Scene,
+    /// 地形の層と足音の材質の定義。<br />
+    /// Definition of terrain layers and footstep materials.
+    Material,
+    Music,
+    Animator,
+}
+
+impl Display for AssetKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AssetKind::Texture => "Texture",
+            AssetKind::Model => "Model",
+            AssetKind::Shader => "Shader",
+            AssetKind::Audio => "Audio",
+            AssetKind::Scene => "Scene",
+            AssetKind::Material => "Material",
+            AssetKind::Music => "Music",
+            AssetKind::Animator => "Animator",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 代わりの素材で置き換えた読み込みの失敗。<br />
+/// A load failure that was replaced with a placeholder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetWarning {
+    pub kind: AssetKind,
+    pub path: String,
+    pub message: String,
+}
+
+/// 画面に表示する、読み込みに失敗した素材の一覧。同じ素材は一度だけ載せる。<br />
+/// List of assets that failed to load, shown on screen. Each asset is only listed once.
+#[derive(Clone, Debug, Default)]
+pub struct AssetWarnings {
+    warnings: Vec<AssetWarning>,
+}
+
+impl AssetWarnings {
+    pub fn new() -> Self {
+        AssetWarnings { warnings: vec![] }
+    }
+
+    /// 失敗を記録する。既に載っている素材なら何もせず`false`を返す。<br />
+    /// Record a failure. Returns `false` without doing anything if the asset is already listed.
+    pub fn report(&mut self, kind: AssetKind, path: &str, message: &str) -> bool {
+        if self
+            .warnings
+            .iter()
+            .any(|w| w.kind == kind && w.path == path)
+        {
+            return false;
+        }
+        log::error!(
+            "{} {} failed to load and was replaced with a placeholder: {}",
+            kind,
+            path,
+            message
+        );
+        self.warnings.push(AssetWarning {
+            kind,
+            path: path.to_string(),
+            message: message.to_string(),
+        });
+        true
+    }
+
+    pub fn get_warnings(&self) -> &[AssetWarning] {
+        &self.warnings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.warnings.clear();
+    }
+}
+
+/// マゼンタと黒のチェッカーのRGBAのピクセル。<br />
+/// RGBA pixels of a magenta and black checker.
+pub fn create_checker_pixels(size: u32, cell_size: u32) -> Vec<u8> {
+    let cell_size = cell_size.max(1);
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let index = ((x / cell_size + y / cell_size) % 2) as usize;
+            pixels.extend_from_slice(&PLACEHOLDER_COLORS[index]);
+        }
+    }
+    pixels
+}
+
+/// 読み込めなかったモデルの代わりに使う、一辺が1の立方体。面ごとに頂点を分けて法線を平らにする。<br />
+/// Cube with sides of 1, used in place of models that failed to load. Each face has its own vertices so normals stay flat.
+pub fn create_unit_cube() -> (Vec<Vertex>, Vec<u32>) {
+    let faces = [
+        (Vec3A::unit_x(), Vec3A::unit_y()),
+        (-Vec3A::unit_x(), Vec3A::unit_y()),
+        (Vec3A::unit_y(), Vec3A::unit_z()),
+        (-Vec3A::unit_y(), Vec3A::unit_z()),
+        (Vec3A::unit_z(), Vec3A::unit_y()),
+        (-Vec3A::unit_z(), Vec3A::unit_y()),
+    ];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    let mut vertices = Vec::with_capacity(faces.len() * corners.len());
+    let mut indices = Vec::with_capacity(faces.len() * 6);
+    for (normal, up) in faces.iter() {
+        let right = up.cross(*normal);
+        let base = vertices.len() as u32;
+        for (u, v) in corners.iter() {
+            let position = (*normal + right * *u + *up * *v) * 0.5;
+            vertices.push(Vertex::new(
+                position,
+                *normal,
+                Vec2::new((u + 1.0) * 0.5, (1.0 - v) * 0.5),
+            ));
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+    }
+    (vertices, indices)
+}
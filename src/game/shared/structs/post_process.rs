@@ -0,0 +1,271 @@
+use crate::game::shared::structs::{PassOutput, RenderFeatures, RenderPassNode};
+use crate::game::ui::TweakRegistry;
+
+const BLOOM_TWEAK: &str = "Post Process/Bloom";
+const BLOOM_THRESHOLD_TWEAK: &str = "Post Process/Bloom Threshold";
+const BLOOM_INTENSITY_TWEAK: &str = "Post Process/Bloom Intensity";
+const TONEMAP_TWEAK: &str = "Post Process/Tonemap";
+const EXPOSURE_TWEAK: &str = "Post Process/Exposure";
+const FXAA_TWEAK: &str = "Post Process/FXAA";
+
+/// シーンのパスが書くHDRのアタッチメントの名前。<br />
+/// Name of the HDR attachment written by the scene pass.
+pub const HDR_SCENE_ATTACHMENT: &str = "HDR Scene";
+
+/// 全てのポストプロセスのパスが使う、画面を覆う三角形の頂点シェーダー。<br />
+/// Vertex shader of the fullscreen triangle used by every post-processing pass.
+pub const POST_PROCESS_VERTEX_SHADER: &str = "./shaders/fullscreen_vert.spv";
+
+/// 合成のプッシュコンスタントのフラグ。トーンマッピングを使う。<br />
+/// Flag of the composite push constant. Tonemapping is applied.
+pub const POST_PROCESS_TONEMAP: u32 = 1;
+
+/// 合成のプッシュコンスタントのフラグ。ブルームを足す。<br />
+/// Flag of the composite push constant. Bloom is added.
+pub const POST_PROCESS_BLOOM: u32 = 1 << 1;
+
+/// シーンを描画した後に実行するフルスクリーンのパス。<br />
+/// Fullscreen passes executed after the scene is rendered.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum PostProcessStep {
+    BrightPass,
+    BlurHorizontal,
+    BlurVertical,
+    Composite,
+    Fxaa,
+}
+
+impl PostProcessStep {
+    pub fn get_all_steps() -> Vec<PostProcessStep> {
+        vec![
+            PostProcessStep::BrightPass,
+            PostProcessStep::BlurHorizontal,
+            PostProcessStep::BlurVertical,
+            PostProcessStep::Composite,
+            PostProcessStep::Fxaa,
+        ]
+    }
+
+    /// このパスのフラグメントシェーダーのファイル。<br />
+    /// Fragment shader file of this pass.
+    pub fn get_fragment_shader_file(&self) -> &'static str {
+        match self {
+            PostProcessStep::BrightPass => "./shaders/bright_pass_frag.spv",
+            PostProcessStep::BlurHorizontal | PostProcessStep::BlurVertical => {
+                "./shaders/blur_frag.spv"
+            }
+            PostProcessStep::Composite => "./shaders/composite_frag.spv",
+            PostProcessStep::Fxaa => "./shaders/fxaa_frag.spv",
+        }
+    }
+
+    /// レンダーグラフに登録するパスの名前。<br />
+    /// Name of the pass registered to the render graph.
+    pub fn get_pass_name(&self) -> &'static str {
+        match self {
+            PostProcessStep::BrightPass => "Bloom Bright Pass",
+            PostProcessStep::BlurHorizontal => "Bloom Blur Horizontal",
+            PostProcessStep::BlurVertical => "Bloom Blur Vertical",
+            PostProcessStep::Composite => "Composite",
+            PostProcessStep::Fxaa => "FXAA",
+        }
+    }
+}
+
+/// ポストプロセスの設定。比較できるように、それぞれのエフェクトは調整パネルで切り替えられる。<br />
+/// Post-processing settings. Each effect can be toggled in the tweak panel for comparison.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PostProcessSettings {
+    pub is_bloom_enabled: bool,
+    /// この輝度を超えた部分だけがブルームになる。<br />
+    /// Only the parts brighter than this luminance bloom.
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub is_tonemap_enabled: bool,
+    pub exposure: f32,
+    pub is_fxaa_enabled: bool,
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        PostProcessSettings {
+            is_bloom_enabled: true,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.5,
+            is_tonemap_enabled: true,
+            exposure: 1.0,
+            is_fxaa_enabled: true,
+        }
+    }
+}
+
+impl PostProcessSettings {
+    /// 有効なステップを実行する順番で取得する。合成はいつも実行する。<br />
+    /// Get the enabled steps in execution order. The composite always runs.
+    pub fn get_steps(&self) -> Vec<PostProcessStep> {
+        PostProcessStep::get_all_steps()
+            .into_iter()
+            .filter(|step| match step {
+                PostProcessStep::BrightPass
+                | PostProcessStep::BlurHorizontal
+                | PostProcessStep::BlurVertical => self.is_bloom_enabled,
+                PostProcessStep::Composite => true,
+                PostProcessStep::Fxaa => self.is_fxaa_enabled,
+            })
+            .collect()
+    }
+
+    /// シーンが使う機能に合わせた設定。ポストエフェクトを使わなければ、ブルームとFXAAを止めて合成だけを実行する。<br />
+    /// Settings matching the features the scene uses. Without post effects, bloom and FXAA are turned off and only the composite runs.
+    pub fn with_features(mut self, features: RenderFeatures) -> Self {
+        if !features.contains(RenderFeatures::POST_EFFECTS) {
+            self.is_bloom_enabled = false;
+            self.is_fxaa_enabled = false;
+        }
+        self
+    }
+
+    /// スワップチェーンに書くステップ。<br />
+    /// The step writing the swapchain.
+    pub fn get_final_step(&self) -> PostProcessStep {
+        if self.is_fxaa_enabled {
+            PostProcessStep::Fxaa
+        } else {
+            PostProcessStep::Composite
+        }
+    }
+
+    /// 有効なステップをレンダーグラフのパスにする。最後のステップだけが表示用のパスになる。<br />
+    /// Turn the enabled steps into render graph passes. Only the final step presents.
+    pub fn get_render_pass_nodes(&self) -> Vec<(PostProcessStep, RenderPassNode)> {
+        let final_step = self.get_final_step();
+        self.get_steps()
+            .into_iter()
+            .map(|step| {
+                let output = if step == final_step {
+                    PassOutput::Present
+                } else {
+                    PassOutput::Sampled
+                };
+                let node = RenderPassNode::new(step.get_pass_name(), output);
+                let node = match step {
+                    PostProcessStep::BrightPass => {
+                        node.read(HDR_SCENE_ATTACHMENT).write("Bloom Bright")
+                    }
+                    PostProcessStep::BlurHorizontal => {
+                        node.read("Bloom Bright").write("Bloom Half Blurred")
+                    }
+                    PostProcessStep::BlurVertical => {
+                        node.read("Bloom Half Blurred").write("Bloom Blurred")
+                    }
+                    PostProcessStep::Composite => {
+                        let node = node.read(HDR_SCENE_ATTACHMENT);
+                        if self.is_bloom_enabled {
+                            node.read("Bloom Blurred")
+                        } else {
+                            node
+                        }
+                    }
+                    PostProcessStep::Fxaa => node.read("LDR Scene"),
+                };
+                let node = if step == final_step {
+                    node.write("Backbuffer")
+                } else if step == PostProcessStep::Composite {
+                    node.write("LDR Scene")
+                } else {
+                    node
+                };
+                (step, node)
+            })
+            .collect()
+    }
+
+    /// 設定を調整パネルに登録する。<br />
+    /// Register the settings to the tweak panel.
+    pub fn register_tweaks(&mut self, tweaks: &mut TweakRegistry) {
+        tweaks.register_bool(BLOOM_TWEAK, self.is_bloom_enabled);
+        tweaks.register_f32(BLOOM_THRESHOLD_TWEAK, self.bloom_threshold, 0.0, 4.0);
+        tweaks.register_f32(BLOOM_INTENSITY_TWEAK, self.bloom_intensity, 0.0, 2.0);
+        tweaks.register_bool(TONEMAP_TWEAK, self.is_tonemap_enabled);
+        tweaks.register_f32(EXPOSURE_TWEAK, self.exposure, 0.1, 4.0);
+        tweaks.register_bool(FXAA_TWEAK, self.is_fxaa_enabled);
+        self.apply_tweaks(tweaks);
+    }
+
+    pub fn apply_tweaks(&mut self, tweaks: &TweakRegistry) {
+        if let Some(is_enabled) = tweaks.get_bool(BLOOM_TWEAK) {
+            self.is_bloom_enabled = is_enabled;
+        }
+        if let Some(threshold) = tweaks.get_f32(BLOOM_THRESHOLD_TWEAK) {
+            self.bloom_threshold = threshold;
+        }
+        if let Some(intensity) = tweaks.get_f32(BLOOM_INTENSITY_TWEAK) {
+            self.bloom_intensity = intensity;
+        }
+        if let Some(is_enabled) = tweaks.get_bool(TONEMAP_TWEAK) {
+            self.is_tonemap_enabled = is_enabled;
+        }
+        if let Some(exposure) = tweaks.get_f32(EXPOSURE_TWEAK) {
+            self.exposure = exposure;
+        }
+        if let Some(is_enabled) = tweaks.get_bool(FXAA_TWEAK) {
+            self.is_fxaa_enabled = is_enabled;
+        }
+    }
+}
+
+/// フルスクリーンのパスのプッシュコンスタント。<br />
+/// Push constant of the fullscreen passes.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PostProcessPushConstant {
+    /// 描画先の1テクセルの大きさ。<br />
+    /// Size of a single texel of the render target.
+    pub texel_size: [f32; 2],
+    /// ぼかしの方向。<br />
+    /// Direction of the blur.
+    pub direction: [f32; 2],
+    pub exposure: f32,
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub flags: u32,
+    /// 遊べる範囲の境界に近づいた時に画面の縁を赤くする強さ。合成だけが使う。<br />
+    /// Strength of the red vignette shown near the world bounds. Only the composite uses it.
+    pub boundary_warning: f32,
+}
+
+impl PostProcessPushConstant {
+    pub fn new(
+        settings: &PostProcessSettings,
+        step: PostProcessStep,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let direction = match step {
+            PostProcessStep::BlurHorizontal => [1.0, 0.0],
+            PostProcessStep::BlurVertical => [0.0, 1.0],
+            _ => [0.0, 0.0],
+        };
+        let mut flags = 0;
+        if settings.is_tonemap_enabled {
+            flags |= POST_PROCESS_TONEMAP;
+        }
+        if settings.is_bloom_enabled {
+            flags |= POST_PROCESS_BLOOM;
+        }
+        PostProcessPushConstant {
+            texel_size: [1.0 / width.max(1) as f32, 1.0 / height.max(1) as f32],
+            direction,
+            exposure: settings.exposure,
+            bloom_threshold: settings.bloom_threshold,
+            bloom_intensity: settings.bloom_intensity,
+            flags,
+            boundary_warning: 0.0,
+        }
+    }
+
+    pub fn with_boundary_warning(mut self, boundary_warning: f32) -> Self {
+        self.boundary_warning = boundary_warning.max(0.0).min(1.0);
+        self
+    }
+}
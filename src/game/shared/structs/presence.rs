@@ -0,0 +1,92 @@
+/// 参加リンクの接頭辞。Discordの参加シークレットとSteamの`connect`にこのリンクを使う。<br />
+/// Prefix of join links. The links are used as Discord join secrets and as Steam's `connect` value.
+pub const JOIN_LINK_PREFIX: &str = "demo-game://join/";
+
+/// フレンドに見せる今の活動。<br />
+/// The current activity shown to friends.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PresenceActivity {
+    InMenu,
+    InLobby,
+    /// 部屋に入って、ゲームが始まるのを待っている。<br />
+    /// Waiting in a room for the game to start.
+    InRoom {
+        room_id: String,
+        room_name: String,
+        players: i32,
+        max_players: i32,
+    },
+    /// 部屋ごとに地形を一つだけ生成するので、マップの名前には部屋の名前を使う。<br />
+    /// Each room generates a single terrain, so the name of the room is used as the map.
+    InMatch {
+        room_id: String,
+        map: String,
+        players: i32,
+        max_players: i32,
+    },
+}
+
+impl PresenceActivity {
+    pub fn get_details(&self) -> String {
+        match self {
+            PresenceActivity::InMenu => "In Menu".to_string(),
+            PresenceActivity::InLobby | PresenceActivity::InRoom { .. } => "In Lobby".to_string(),
+            PresenceActivity::InMatch { map, .. } => format!("In Match on {}", map),
+        }
+    }
+
+    /// 部屋の人数。部屋に入っていなければ`None`。<br />
+    /// Number of players in the room, or `None` outside of rooms.
+    pub fn get_state(&self) -> Option<String> {
+        self.get_party()
+            .map(|(_, players, max_players)| format!("{}/{} players", players, max_players))
+    }
+
+    /// パーティーのIDと人数、最大人数。<br />
+    /// ID of the party, its number of players and its maximum number of players.
+    pub fn get_party(&self) -> Option<(&str, i32, i32)> {
+        match self {
+            PresenceActivity::InRoom {
+                room_id,
+                players,
+                max_players,
+                ..
+            }
+            | PresenceActivity::InMatch {
+                room_id,
+                players,
+                max_players,
+                ..
+            } => Some((room_id.as_str(), *players, *max_players)),
+            _ => None,
+        }
+    }
+
+    /// フレンドが参加するためのリンク。始まる前の満員でない部屋にしか参加できない。<br />
+    /// Link for friends to join with. Only rooms that haven't started and aren't full can be joined.
+    pub fn get_join_link(&self) -> Option<String> {
+        match self {
+            PresenceActivity::InRoom {
+                room_id,
+                players,
+                max_players,
+                ..
+            } if players < max_players => Some(get_join_link(room_id)),
+            _ => None,
+        }
+    }
+}
+
+pub fn get_join_link(room_id: &str) -> String {
+    format!("{}{}", JOIN_LINK_PREFIX, room_id)
+}
+
+/// 参加リンクから部屋のIDを取り出す。<br />
+/// Extract the room ID from a join link.
+pub fn parse_join_link(link: &str) -> Option<String> {
+    let room_id = link.trim().strip_prefix(JOIN_LINK_PREFIX)?;
+    if room_id.is_empty() || !room_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some(room_id.to_string())
+}
@@ -1,24 +1,22 @@
-use crate::game::graphics::vk::{Buffer, Graphics, Image, Pipeline, ThreadPool};
+use crate::game::graphics::vk::{Buffer, Graphics, Image, MeshPool, RenderContext};
+use crate::game::shared::components::Bounds;
 use crate::game::shared::enums::ShaderType;
-use crate::game::shared::structs::{Mesh, PositionInfo, Primitive, PushConstant, Vertex};
+use crate::game::shared::structs::{
+    FrameInfo, LoadTask, Mesh, PositionInfo, Primitive, RecordedCommands, Vertex,
+};
 use crate::game::shared::traits::Renderable;
 use crate::game::shared::util::get_random_string;
 use crate::game::structs::{Model, ModelMetaData};
 use crate::game::traits::{Disposable, GraphicsBase};
-use crate::game::CommandData;
-use ash::vk::{
-    CommandBuffer, CommandBufferInheritanceInfo, DescriptorSet, Rect2D, SamplerAddressMode,
-    Viewport,
-};
-use ash::Device;
-use crossbeam::channel::*;
+use crate::game::{CommandData, ReleasedCommandBuffers};
+use ash::vk::{CommandBuffer, SamplerAddressMode};
 use crossbeam::sync::ShardedLock;
 use glam::{Mat4, Vec2, Vec3A, Vec4};
 use parking_lot::{Mutex, RwLock};
 use slotmap::DefaultKey;
 use std::collections::HashMap;
 use std::mem::ManuallyDrop;
-use std::sync::atomic::{AtomicPtr, AtomicUsize};
+use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, Weak};
 
 /// 簡単なシェイプ。後程他のしぇいぷも追加する予定なので構造体ではなく`enum`にしました。
@@ -134,13 +132,16 @@ where
         } else {
             shader_type.unwrap_or(ShaderType::BasicShader)
         };
+        let primitives = vec![primitive];
         Mesh {
-            primitives: vec![primitive],
+            bounds: Bounds::from_primitives(&primitives),
+            primitives,
             vertex_buffer: None,
             index_buffer: None,
             texture,
             is_disposed: false,
             command_data,
+            recorded_commands: RecordedCommands::new(),
             shader_type: final_shader_type,
             model_index,
         }
@@ -162,7 +163,7 @@ impl GeometricPrimitive<Graphics, Buffer, CommandBuffer, Image> {
         color: Vec4,
         shader_type: Option<ShaderType>,
         entity: DefaultKey,
-    ) -> anyhow::Result<Receiver<Self>> {
+    ) -> anyhow::Result<LoadTask<Self>> {
         log::info!(
             "Generating geometric primitive...Model index: {}",
             model_index
@@ -170,13 +171,11 @@ impl GeometricPrimitive<Graphics, Buffer, CommandBuffer, Image> {
         let graphics_arc = graphics
             .upgrade()
             .expect("Failed to upgrade graphics handle.");
-        let (primitive_send, primitive_recv) = bounded(5);
-        rayon::spawn(move || {
+        let (primitive_send, primitive_recv) = LoadTask::channel();
+        let thread_pool = graphics_arc.read().thread_pool.clone();
+        thread_pool.spawn_streaming(move || {
             let graphics_arc = graphics_arc;
-            let inflight_frame_count = std::env::var("INFLIGHT_BUFFER_COUNT")
-                .unwrap()
-                .parse::<usize>()
-                .unwrap();
+            let inflight_frame_count = graphics_arc.read().inflight_buffer_count;
             let mut command_data = HashMap::new();
             for i in 0..inflight_frame_count {
                 let (command_pool, command_buffer) =
@@ -191,6 +190,18 @@ impl GeometricPrimitive<Graphics, Buffer, CommandBuffer, Image> {
                 *entry = (Some(command_pool), command_buffer);
             }
             let texture_data = match texture_name {
+                // 水面は波紋のテクスチャを読む。
+                // Water surfaces read the ripple texture.
+                None if shader_type == Some(ShaderType::Water) => Some(
+                    Graphics::get_ripple_texture(
+                        graphics_arc.clone(),
+                        command_data
+                            .get(&0)
+                            .map(|(pool, _)| pool.clone().unwrap())
+                            .unwrap(),
+                    )
+                    .expect("Failed to create the ripple texture for geometric primitive."),
+                ),
                 None => None,
                 Some(file_name) => Some(
                     Graphics::create_image_from_file(
@@ -201,6 +212,7 @@ impl GeometricPrimitive<Graphics, Buffer, CommandBuffer, Image> {
                             .map(|(pool, _)| pool.clone().unwrap())
                             .unwrap(),
                         SamplerAddressMode::REPEAT,
+                        false,
                     )
                     .expect("Failed to create texture for geometric primitive."),
                 ),
@@ -233,9 +245,7 @@ impl GeometricPrimitive<Graphics, Buffer, CommandBuffer, Image> {
             generated_mesh
                 .create_buffer(graphics_arc)
                 .expect("Failed to create buffer for geometric primitive.");
-            primitive_send
-                .send(generated_mesh)
-                .expect("Failed to send geometric primitive.");
+            primitive_send.complete(generated_mesh);
         });
         Ok(primitive_recv)
     }
@@ -258,10 +268,14 @@ impl GeometricPrimitive<Graphics, Buffer, CommandBuffer, Image> {
             .get(&0)
             .map(|(pool, _)| pool.clone().unwrap())
             .unwrap();
-        let (vertex_buffer, index_buffer) =
-            Graphics::create_vertex_and_index_buffer(graphics, vertices, indices, command_pool)?;
-        mesh.vertex_buffer = Some(ManuallyDrop::new(vertex_buffer));
-        mesh.index_buffer = Some(ManuallyDrop::new(index_buffer));
+        let buffers = Graphics::create_mesh_buffers(
+            graphics,
+            vertices,
+            indices,
+            command_pool,
+            MeshPool::Dynamic,
+        )?;
+        mesh.set_buffers(buffers);
         Ok(())
     }
 }
@@ -316,6 +330,29 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
             .get_command_buffers(frame_index)
     }
 
+    fn append_command_buffers(&self, frame_index: usize, command_buffers: &mut Vec<CommandBuffer>) {
+        if let Some(model) = self.model.as_ref() {
+            model.append_command_buffers(frame_index, command_buffers);
+        }
+    }
+
+    fn take_command_buffers(&mut self) -> ReleasedCommandBuffers<CommandBuffer> {
+        self.model
+            .as_mut()
+            .map(|m| m.take_command_buffers())
+            .unwrap_or_default()
+    }
+
+    fn invalidate_command_buffers(&mut self) {
+        if let Some(model) = self.model.as_mut() {
+            model.invalidate_command_buffers();
+        }
+    }
+
+    fn get_entity(&self) -> DefaultKey {
+        self.model.as_ref().unwrap().entity
+    }
+
     fn get_model_metadata(&self) -> ModelMetaData {
         self.model.as_ref().unwrap().model_metadata
     }
@@ -328,30 +365,9 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         self.model.as_ref().unwrap().ssbo_index
     }
 
-    fn render(
-        &self,
-        inheritance_info: Arc<AtomicPtr<CommandBufferInheritanceInfo>>,
-        push_constant: PushConstant,
-        viewport: Viewport,
-        scissor: Rect2D,
-        device: Arc<Device>,
-        pipeline: Arc<ShardedLock<ManuallyDrop<Pipeline>>>,
-        descriptor_set: DescriptorSet,
-        thread_pool: Arc<ThreadPool>,
-        frame_index: usize,
-    ) {
+    fn render(&self, context: &RenderContext, frame_info: FrameInfo) {
         let model = self.model.as_ref().unwrap();
-        model.render(
-            inheritance_info,
-            push_constant,
-            viewport,
-            scissor,
-            device,
-            pipeline,
-            descriptor_set,
-            thread_pool,
-            frame_index,
-        );
+        model.render(context, frame_info);
     }
 
     fn set_model_metadata(&mut self, model_metadata: ModelMetaData) {
@@ -361,6 +377,10 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
             .set_model_metadata(model_metadata);
     }
 
+    fn set_entity(&mut self, entity: DefaultKey) {
+        self.model.as_mut().unwrap().entity = entity;
+    }
+
     fn set_position_info(&mut self, position_info: PositionInfo) {
         self.model.as_mut().unwrap().position_info = position_info;
     }
@@ -0,0 +1,70 @@
+use crate::game::shared::components::Bounds;
+use glam::Vec3A;
+
+/// 始点と正規化された方向を持つ光線。ピッキングに使う。<br />
+/// A ray with an origin and a normalized direction, used for picking.
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Vec3A,
+    pub direction: Vec3A,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3A, direction: Vec3A) -> Self {
+        Ray {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// 始点から指定した距離にある点を求める。<br />
+    /// Get the point at the given distance from the origin.
+    pub fn get_point(&self, distance: f32) -> Vec3A {
+        self.origin + self.direction * distance
+    }
+
+    /// 境界ボックスとの交差判定（スラブ法）。交差した場合は始点からの距離を返す。<br />
+    /// Intersect with a bounding box using the slab method. Returns the distance from the origin on hit.
+    pub fn intersect_bounds(&self, bounds: &Bounds) -> Option<f32> {
+        let inverse = Vec3A::one() / self.direction;
+        let t0 = (bounds.min - self.origin) * inverse;
+        let t1 = (bounds.max - self.origin) * inverse;
+        let near = t0.min(t1);
+        let far = t0.max(t1);
+        let t_near = near.x.max(near.y).max(near.z);
+        let t_far = far.x.min(far.y).min(far.z);
+        if t_near > t_far || t_far < 0.0 {
+            return None;
+        }
+        Some(t_near.max(0.0))
+    }
+
+    /// 三角形との交差判定（Möller–Trumbore法）。交差した場合は始点からの距離を返す。<br />
+    /// Intersect with a triangle using the Möller–Trumbore algorithm. Returns the distance from the origin on hit.
+    pub fn intersect_triangle(&self, a: Vec3A, b: Vec3A, c: Vec3A) -> Option<f32> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+        let s = self.origin - a;
+        let u = s.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = edge2.dot(q) * inverse_determinant;
+        if distance < 0.0 {
+            None
+        } else {
+            Some(distance)
+        }
+    }
+}
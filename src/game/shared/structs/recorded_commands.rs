@@ -0,0 +1,76 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// 二次コマンドバッファに記録する内容を表すキー。パイプライン、バッファ、描述子セットなど、記録に使う全てを加える。<br />
+/// A key describing what goes into a secondary command buffer. Everything used for recording is added, such as pipelines, buffers and descriptor sets.
+#[derive(Clone, Debug)]
+pub struct RecordKey {
+    hasher: DefaultHasher,
+}
+
+impl RecordKey {
+    /// 世代から始める。パイプラインや描述子セットを作り直すと世代が変わり、同じハンドルが使い回されても記録し直す。<br />
+    /// Start from a generation. It changes whenever pipelines or descriptor sets are rebuilt, so commands are recorded again even if the same handles are reused.
+    pub fn new(generation: u64) -> Self {
+        let mut hasher = DefaultHasher::new();
+        generation.hash(&mut hasher);
+        RecordKey { hasher }
+    }
+
+    pub fn with<T: Hash>(mut self, value: T) -> Self {
+        value.hash(&mut self.hasher);
+        self
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hasher.finish()
+    }
+}
+
+/// フレームごとに、二次コマンドバッファを最後に記録した時のキーを覚える。キーが同じなら記録し直さずにそのまま使う。<br />
+/// Remembers, for each frame, the key the secondary command buffers were last recorded with. They're reused as-is while the key stays the same.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordedCommands {
+    keys: Vec<Option<u64>>,
+    is_drawn_indirectly: bool,
+}
+
+impl RecordedCommands {
+    pub fn new() -> Self {
+        RecordedCommands {
+            keys: vec![],
+            is_drawn_indirectly: false,
+        }
+    }
+
+    /// 今のパスでは間接描画に任せたので、二次コマンドバッファを実行しないかどうか。<br />
+    /// Whether the current pass leaves drawing to indirect draws, so the secondary command buffers aren't executed.
+    pub fn is_drawn_indirectly(&self) -> bool {
+        self.is_drawn_indirectly
+    }
+
+    pub fn set_drawn_indirectly(&mut self, is_drawn_indirectly: bool) {
+        self.is_drawn_indirectly = is_drawn_indirectly;
+    }
+
+    /// このフレームのコマンドバッファが、同じキーで記録済みかどうか。<br />
+    /// Whether this frame's command buffers have been recorded with the same key.
+    pub fn is_up_to_date(&self, frame_index: usize, key: u64) -> bool {
+        self.keys.get(frame_index).copied().flatten() == Some(key)
+    }
+
+    /// このフレームのコマンドバッファをキーで記録した。<br />
+    /// This frame's command buffers have been recorded with the key.
+    pub fn set_recorded(&mut self, frame_index: usize, key: u64) {
+        if self.keys.len() <= frame_index {
+            self.keys.resize(frame_index + 1, None);
+        }
+        self.keys[frame_index] = Some(key);
+    }
+
+    /// 全てのフレームのコマンドバッファを、次の描画で記録し直すようにする。<br />
+    /// Make every frame's command buffers be recorded again on the next render.
+    pub fn invalidate(&mut self) {
+        self.keys.clear();
+    }
+}
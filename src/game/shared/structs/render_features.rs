@@ -0,0 +1,54 @@
+use std::ops::{BitOr, BitOrAssign};
+
+/// シーンが使う描画の機能の組み合わせ。レンダーグラフは使わない機能のパスを実行しない。<br />
+/// The set of rendering features a scene uses. The render graph doesn't execute passes of unused features.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RenderFeatures(u32);
+
+impl RenderFeatures {
+    pub const NONE: Self = Self(0);
+    pub const SHADOWS: Self = Self(1);
+    pub const WATER: Self = Self(1 << 1);
+    pub const VEGETATION: Self = Self(1 << 2);
+    pub const POST_EFFECTS: Self = Self(1 << 3);
+    pub const UI_LAYERS: Self = Self(1 << 4);
+    pub const ALL: Self = Self((1 << 5) - 1);
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// `features`の全てを含むかどうか。空の組み合わせはいつも含む。<br />
+    /// Whether all of `features` are included. The empty set is always included.
+    pub fn contains(&self, features: RenderFeatures) -> bool {
+        self.0 & features.0 == features.0
+    }
+
+    pub fn with(self, features: RenderFeatures) -> Self {
+        Self(self.0 | features.0)
+    }
+
+    pub fn without(self, features: RenderFeatures) -> Self {
+        Self(self.0 & !features.0)
+    }
+}
+
+impl Default for RenderFeatures {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for RenderFeatures {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.with(rhs)
+    }
+}
+
+impl BitOrAssign for RenderFeatures {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.with(rhs);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::game::shared::structs::RenderFeatures;
+use std::collections::HashSet;
+
+/// パスの結果がフレームの後でどう使われるか。バリアとサブパスの依存関係はここから導く。<br />
+/// How the result of a pass is used later in the frame. Barriers and subpass dependencies are derived from this.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum PassOutput {
+    /// 後のパスのシェーダーが読む。<br />
+    /// Read by shaders of later passes.
+    Sampled,
+    /// スワップチェーンに表示する。<br />
+    /// Presented to the swapchain.
+    Present,
+}
+
+/// レンダーグラフの一つのパス。読む・書くアタッチメントを名前で宣言する。<br />
+/// A single pass of the render graph, declaring the attachments it reads and writes by name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderPassNode {
+    pub name: String,
+    pub reads: Vec<String>,
+    pub writes: Vec<String>,
+    pub output: PassOutput,
+    pub is_enabled: bool,
+    /// このパスを実行するのにシーンが使っていなければならない機能。<br />
+    /// Features the scene must use for this pass to be executed.
+    pub required_features: RenderFeatures,
+}
+
+impl RenderPassNode {
+    pub fn new(name: &str, output: PassOutput) -> Self {
+        RenderPassNode {
+            name: name.to_string(),
+            reads: vec![],
+            writes: vec![],
+            output,
+            is_enabled: true,
+            required_features: RenderFeatures::NONE,
+        }
+    }
+
+    pub fn read(mut self, attachment: &str) -> Self {
+        self.reads.push(attachment.to_string());
+        self
+    }
+
+    pub fn write(mut self, attachment: &str) -> Self {
+        self.writes.push(attachment.to_string());
+        self
+    }
+
+    pub fn enabled(mut self, is_enabled: bool) -> Self {
+        self.is_enabled = is_enabled;
+        self
+    }
+
+    pub fn requires(mut self, features: RenderFeatures) -> Self {
+        self.required_features = self.required_features.with(features);
+        self
+    }
+}
+
+/// パスを登録し、アタッチメントの依存関係から実行の順番を決める。<br />
+/// Registers passes and decides their execution order from the dependencies between attachments.
+#[derive(Clone, Debug, Default)]
+pub struct RenderGraph {
+    nodes: Vec<RenderPassNode>,
+    order: Vec<usize>,
+    features: RenderFeatures,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        RenderGraph {
+            nodes: vec![],
+            order: vec![],
+            features: RenderFeatures::ALL,
+        }
+    }
+
+    /// パスを登録して、グラフを組み直す。組み直せない場合は登録しない。<br />
+    /// Register a pass and rebuild the graph. The pass isn't registered if the graph can't be rebuilt.
+    pub fn add_pass(&mut self, node: RenderPassNode) -> anyhow::Result<()> {
+        if self.nodes.iter().any(|n| n.name == node.name) {
+            return Err(anyhow::anyhow!(
+                "Render pass {} is already registered.",
+                &node.name
+            ));
+        }
+        self.nodes.push(node);
+        if let Err(e) = self.compile() {
+            self.nodes.pop();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// パスを取り除いて、グラフを組み直す。他のパスがまだ読んでいる場合は取り除かない。<br />
+    /// Remove a pass and rebuild the graph. The pass isn't removed if other passes still read it.
+    pub fn remove_pass(&mut self, name: &str) -> anyhow::Result<RenderPassNode> {
+        let index = self
+            .nodes
+            .iter()
+            .position(|n| n.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Render pass {} isn't registered.", name))?;
+        let node = self.nodes.remove(index);
+        if let Err(e) = self.compile() {
+            self.nodes.insert(index, node);
+            return Err(e);
+        }
+        Ok(node)
+    }
+
+    /// パスを有効・無効にする。無効になったパスは実行されない。<br />
+    /// Enable or disable a pass. Disabled passes aren't executed.
+    pub fn set_enabled(&mut self, name: &str, is_enabled: bool) -> anyhow::Result<()> {
+        let index = self
+            .nodes
+            .iter()
+            .position(|n| n.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Render pass {} isn't registered.", name))?;
+        let was_enabled = self.nodes[index].is_enabled;
+        self.nodes[index].is_enabled = is_enabled;
+        if let Err(e) = self.compile() {
+            self.nodes[index].is_enabled = was_enabled;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// 今のシーンが使う機能を設定して、グラフを組み直す。組み直せない場合は元に戻す。<br />
+    /// Set the features the current scene uses and rebuild the graph. They are restored if the graph can't be rebuilt.
+    pub fn set_features(&mut self, features: RenderFeatures) -> anyhow::Result<()> {
+        let previous_features = self.features;
+        self.features = features;
+        if let Err(e) = self.compile() {
+            self.features = previous_features;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub fn get_features(&self) -> RenderFeatures {
+        self.features
+    }
+
+    pub fn get_pass(&self, name: &str) -> Option<&RenderPassNode> {
+        self.nodes.iter().find(|n| n.name == name)
+    }
+
+    /// 有効で、必要な機能が使われているパスを実行する順番で取得する。<br />
+    /// Get the enabled passes whose required features are in use, in execution order.
+    pub fn get_execution_order(&self) -> impl Iterator<Item = &RenderPassNode> {
+        self.order.iter().map(move |i| &self.nodes[*i])
+    }
+
+    /// パスが読むアタッチメントを書く、有効なパスの名前を取得する。<br />
+    /// Get the names of the enabled passes writing the attachments a pass reads.
+    pub fn get_dependencies(&self, name: &str) -> Vec<&str> {
+        match self.get_pass(name) {
+            Some(node) => self
+                .get_writers(node)
+                .map(|i| self.nodes[i].name.as_str())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn get_writers<'a>(&'a self, node: &'a RenderPassNode) -> impl Iterator<Item = usize> + 'a {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(move |(_, other)| {
+                self.is_active(other)
+                    && other.name != node.name
+                    && other.writes.iter().any(|w| node.reads.contains(w))
+            })
+            .map(|(i, _)| i)
+    }
+
+    fn is_active(&self, node: &RenderPassNode) -> bool {
+        node.is_enabled && self.features.contains(node.required_features)
+    }
+
+    /// 依存関係を満たす順番を求める。依存関係のないパスは登録の順番を保つ。<br />
+    /// Find an order satisfying the dependencies. Passes without dependencies keep their registration order.
+    fn compile(&mut self) -> anyhow::Result<()> {
+        let enabled = (0..self.nodes.len())
+            .filter(|i| self.is_active(&self.nodes[*i]))
+            .collect::<Vec<_>>();
+        for i in enabled.iter() {
+            let node = &self.nodes[*i];
+            for attachment in node.reads.iter() {
+                let is_written = enabled
+                    .iter()
+                    .any(|j| j != i && self.nodes[*j].writes.contains(attachment));
+                if !is_written {
+                    return Err(anyhow::anyhow!(
+                        "Render pass {} reads attachment {}, which no enabled pass writes.",
+                        &node.name,
+                        attachment
+                    ));
+                }
+            }
+        }
+
+        let mut order = vec![];
+        let mut visited = HashSet::new();
+        while order.len() < enabled.len() {
+            let next = enabled.iter().copied().find(|i| {
+                !visited.contains(i)
+                    && self
+                        .get_writers(&self.nodes[*i])
+                        .all(|writer| visited.contains(&writer))
+            });
+            match next {
+                Some(i) => {
+                    visited.insert(i);
+                    order.push(i);
+                }
+                None => {
+                    let remaining = enabled
+                        .iter()
+                        .filter(|i| !visited.contains(i))
+                        .map(|i| self.nodes[*i].name.as_str())
+                        .collect::<Vec<_>>();
+                    return Err(anyhow::anyhow!(
+                        "Render passes {} depend on each other.",
+                        remaining.join(", ")
+                    ));
+                }
+            }
+        }
+        self.order = order;
+        Ok(())
+    }
+}
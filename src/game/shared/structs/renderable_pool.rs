@@ -0,0 +1,89 @@
+use crate::game::shared::structs::PositionInfo;
+use crate::game::traits::{Disposable, GraphicsBase};
+use crate::game::LockableRenderable;
+use slotmap::DefaultKey;
+use std::collections::HashMap;
+
+/// 頻繁に生成・削除されるモデル（弾、パーティクルなど）のためのオブジェクトプール。<br />
+/// 全てのインスタンスは事前に作成され、SSBOのインデックスも予約済みなので、実行中にバッファや描述子を生成・解放することはありません。<br />
+/// Object pool for frequently spawned and despawned models, such as projectiles and particles.<br />
+/// All instances are created beforehand with reserved SSBO indices, so no buffers or descriptors are created or released at runtime.
+pub struct RenderablePool<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    pub file_name: &'static str,
+    available: Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>,
+    active:
+        HashMap<DefaultKey, LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>,
+}
+
+impl<GraphicsType, BufferType, CommandType, TextureType>
+    RenderablePool<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    pub fn new(
+        file_name: &'static str,
+        instances: Vec<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>>,
+    ) -> Self {
+        RenderablePool {
+            file_name,
+            available: instances,
+            active: HashMap::new(),
+        }
+    }
+
+    /// プールからインスタンスを一つ取り出し、位置情報を設定する。空いているインスタンスがなければ`None`を返す。<br />
+    /// Take an instance out of the pool and set its position info. Returns `None` if no instance is available.
+    pub fn acquire(
+        &mut self,
+        position_info: PositionInfo,
+    ) -> Option<(
+        DefaultKey,
+        LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>,
+    )> {
+        let renderable = self.available.pop()?;
+        let entity = {
+            let mut renderable_lock = renderable.lock();
+            renderable_lock.set_position_info(position_info);
+            let mut metadata = renderable_lock.get_model_metadata();
+            metadata.world_matrix = renderable_lock.get_world_matrix();
+            renderable_lock.set_model_metadata(metadata);
+            renderable_lock.get_entity()
+        };
+        self.active.insert(entity, renderable.clone());
+        Some((entity, renderable))
+    }
+
+    /// 使用中のインスタンスをプールに戻す。<br />
+    /// Return an active instance to the pool.
+    pub fn release(
+        &mut self,
+        entity: DefaultKey,
+    ) -> Option<LockableRenderable<GraphicsType, BufferType, CommandType, TextureType>> {
+        let renderable = self.active.remove(&entity)?;
+        self.available.push(renderable.clone());
+        Some(renderable)
+    }
+
+    /// このエンティティはこのプールの使用中のインスタンスなのかどうか。<br />
+    /// Is this entity an active instance of this pool?
+    pub fn is_active(&self, entity: DefaultKey) -> bool {
+        self.active.contains_key(&entity)
+    }
+
+    pub fn get_active_count(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn get_available_count(&self) -> usize {
+        self.available.len()
+    }
+}
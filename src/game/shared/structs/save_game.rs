@@ -0,0 +1,223 @@
+use crate::game::shared::structs::games::payload::{deserialize_bounded, PayloadError};
+use crate::game::shared::structs::games::terrain_patch::{
+    decode_patches, encode_patches, TerrainPatch,
+};
+use anyhow::Context;
+use glam::Vec3A;
+use serde::{Deserialize, Serialize};
+use slotmap::DefaultKey;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// クイックセーブとクイックロードで使うファイル。<br />
+/// File used by quick-save and quick-load.
+pub const QUICK_SAVE_PATH: &str = "./quicksave.sav";
+
+/// セーブファイルの先頭に付けるマジックナンバー。<br />
+/// Magic number at the beginning of save files.
+const SAVE_MAGIC: &[u8; 4] = b"DGSV";
+
+/// 今書き込むセーブファイルの形式のバージョン。<br />
+/// 形式を変える時はこれを上げ、前の形式の構造体と変換を`decode`に残して古いセーブも読めるようにする。<br />
+/// Version of the save file format written now.<br />
+/// When the format changes, bump this and keep the previous format's struct and conversion in `decode` so older saves still load.
+pub const SAVE_VERSION: u32 = 2;
+
+/// 保存したエンティティの位置。エンティティの番号は読み込むたびに変わるので、名前で見つける。<br />
+/// Saved placement of an entity. Entity keys change with every load, so it's found by name instead.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedTransform {
+    pub name: String,
+    pub position: Vec3A,
+    pub rotation: Vec3A,
+    pub scale: Vec3A,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedCamera {
+    pub position: Vec3A,
+    pub target: Vec3A,
+}
+
+/// ローカルプレイヤーの進み具合。倒されている間にセーブしても、読み込んだ時は体力が全快する。<br />
+/// Progress of the local player. Saving while defeated loads with full health.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedProgress {
+    pub current_hp: i32,
+    pub max_hp: i32,
+    pub current_sp: i32,
+    pub max_sp: i32,
+}
+
+impl SavedProgress {
+    /// 読み込む時の体力。倒された時の体力なら全快させる。<br />
+    /// Health on loading. Health from being defeated is fully restored.
+    pub fn get_loaded_hp(&self) -> i32 {
+        if self.current_hp > 0 {
+            self.current_hp.min(self.max_hp)
+        } else {
+            self.max_hp
+        }
+    }
+}
+
+/// シーンのローカルな状態をまとめたセーブデータ。<br />
+/// Save data holding the local state of a scene.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveGame {
+    /// 保存したシーン。別のシーンには読み込まない。<br />
+    /// The scene that was saved. It isn't loaded into a different scene.
+    pub scene_name: String,
+    pub transforms: Vec<SavedTransform>,
+    pub camera: Option<SavedCamera>,
+    pub progress: Option<SavedProgress>,
+    /// 手続き的に生成した地形から変更されたチャンクの、圧縮したパッチ。変更が無ければ空。<br />
+    /// Compressed patches of the chunks modified from the procedurally generated terrain. Empty if nothing changed.
+    pub terrain_patches: Vec<u8>,
+}
+
+/// 地形のパッチが無かった、バージョン1のセーブデータ。<br />
+/// Save data of version 1, which had no terrain patches.
+#[derive(Deserialize)]
+struct SaveGameV1 {
+    scene_name: String,
+    transforms: Vec<SavedTransform>,
+    camera: Option<SavedCamera>,
+    progress: Option<SavedProgress>,
+}
+
+impl From<SaveGameV1> for SaveGame {
+    fn from(save: SaveGameV1) -> Self {
+        SaveGame {
+            scene_name: save.scene_name,
+            transforms: save.transforms,
+            camera: save.camera,
+            progress: save.progress,
+            terrain_patches: vec![],
+        }
+    }
+}
+
+impl SaveGame {
+    pub fn new(scene_name: &str) -> Self {
+        SaveGame {
+            scene_name: scene_name.to_string(),
+            transforms: vec![],
+            camera: None,
+            progress: None,
+            terrain_patches: vec![],
+        }
+    }
+
+    /// 保存した地形のパッチを展開する。変更が無ければ空。<br />
+    /// Decompress the saved terrain patches. Empty if nothing changed.
+    pub fn get_terrain_patches(&self) -> Result<Vec<TerrainPatch>, PayloadError> {
+        if self.terrain_patches.is_empty() {
+            Ok(vec![])
+        } else {
+            decode_patches(&self.terrain_patches)
+        }
+    }
+
+    /// 地形のパッチを圧縮して保存する。<br />
+    /// Compress and store the terrain patches.
+    pub fn set_terrain_patches(&mut self, patches: &[TerrainPatch]) -> anyhow::Result<()> {
+        self.terrain_patches = if patches.is_empty() {
+            vec![]
+        } else {
+            encode_patches(patches)?
+        };
+        Ok(())
+    }
+
+    /// マジックナンバーとバージョンを付けてシリアライズする。<br />
+    /// Serialize with the magic number and the version in front.
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let serialized = bincode::serialize(self)?;
+        let mut bytes = Vec::with_capacity(SAVE_MAGIC.len() + 4 + serialized.len());
+        bytes.extend_from_slice(SAVE_MAGIC);
+        bytes.extend_from_slice(&SAVE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&serialized);
+        Ok(bytes)
+    }
+
+    /// ヘッダーを確かめ、バージョンに合わせてデシリアライズする。今より新しいバージョンは読めない。<br />
+    /// Check the header and deserialize according to the version. Versions newer than the current one can't be read.
+    pub fn decode(bytes: &[u8]) -> Result<Self, PayloadError> {
+        let header_size = SAVE_MAGIC.len() + 4;
+        if bytes.len() < header_size || !bytes.starts_with(SAVE_MAGIC) {
+            return Err(PayloadError::InvalidHeader);
+        }
+        let version = u32::from_le_bytes(
+            bytes[SAVE_MAGIC.len()..header_size]
+                .try_into()
+                .map_err(|_| PayloadError::InvalidHeader)?,
+        );
+        let payload = &bytes[header_size..];
+        let save = match version {
+            1 => SaveGame::from(deserialize_bounded::<SaveGameV1>(payload)?),
+            SAVE_VERSION => deserialize_bounded::<SaveGame>(payload)?,
+            _ => {
+                return Err(PayloadError::VersionMismatch {
+                    expected: SAVE_VERSION,
+                    actual: version,
+                })
+            }
+        };
+        save.validate()?;
+        Ok(save)
+    }
+
+    pub fn save(&self, file_name: &str) -> anyhow::Result<()> {
+        std::fs::write(file_name, self.encode()?)
+            .with_context(|| format!("Failed to write {}.", file_name))
+    }
+
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        let bytes =
+            std::fs::read(file_name).with_context(|| format!("Failed to read {}.", file_name))?;
+        Self::decode(&bytes).with_context(|| format!("Failed to load {}.", file_name))
+    }
+
+    /// 保存したトランスフォームを今のエンティティに割り当てる。同じ名前のエンティティは出てくる順に対応させる。<br />
+    /// Assign the saved transforms to the current entities. Entities sharing a name are matched in the order they appear.
+    pub fn match_transforms<'a, I>(&self, entities: I) -> Vec<(DefaultKey, &SavedTransform)>
+    where
+        I: IntoIterator<Item = (DefaultKey, &'a str)>,
+    {
+        let mut saved_by_name: HashMap<&str, Vec<&SavedTransform>> = HashMap::new();
+        for saved in self.transforms.iter().rev() {
+            saved_by_name
+                .entry(saved.name.as_str())
+                .or_default()
+                .push(saved);
+        }
+        entities
+            .into_iter()
+            .filter_map(|(entity, name)| {
+                saved_by_name
+                    .get_mut(name)
+                    .and_then(|saved| saved.pop())
+                    .map(|saved| (entity, saved))
+            })
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), PayloadError> {
+        let is_finite = |v: Vec3A| v.x.is_finite() && v.y.is_finite() && v.z.is_finite();
+        let transforms_are_finite = self
+            .transforms
+            .iter()
+            .all(|t| is_finite(t.position) && is_finite(t.rotation) && is_finite(t.scale));
+        if !transforms_are_finite {
+            return Err(PayloadError::NonFinite("transforms"));
+        }
+        if let Some(camera) = self.camera.as_ref() {
+            if !is_finite(camera.position) || !is_finite(camera.target) {
+                return Err(PayloadError::NonFinite("camera"));
+            }
+        }
+        self.get_terrain_patches()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,468 @@
+use crate::game::shared::enums::{PresentMode, WindowMode};
+use crate::game::shared::structs::games::lan_discovery::MAX_SERVER_NAME_LENGTH;
+use crate::game::shared::structs::games::player_settings::{
+    pack_color, parse_color, sanitize_settings,
+};
+use crate::game::shared::structs::DEFAULT_DAY_LENGTH;
+use crate::protos::grpc_service::game_state::PlayerSettings;
+use anyhow::Context;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// 起動時に読み込む設定ファイル。なければ既定の設定を使う。<br />
+/// Settings file read at startup. The default settings are used if it doesn't exist.
+pub const SETTINGS_PATH: &str = "./settings.toml";
+
+/// 使える描画API。<br />
+/// Graphics APIs that can be used.
+pub const SUPPORTED_APIS: [&str; 2] = ["VULKAN", "DX12"];
+
+/// 同時に処理できるフレームの最大数。<br />
+/// Maximum number of frames that can be in flight at once.
+pub const MAX_INFLIGHT_BUFFER_COUNT: usize = 4;
+
+/// リッスンサーバーが既定で使うgRPCとUDPのポート。<br />
+/// Default gRPC and UDP ports of the listen server.
+pub const LISTEN_SERVER_GRPC_PORT: u16 = 50051;
+pub const LISTEN_SERVER_UDP_PORT: u16 = 50052;
+
+/// 地形の縁から遊べる範囲までの既定の幅。<br />
+/// Default width between the edge of the terrain and the playable area.
+pub const DEFAULT_WORLD_BORDER: f32 = 40.0;
+
+/// ゲームが始まった時に既定で再生する、地形の上を飛ぶカットシーン。<br />
+/// Cutscene flying over the terrain, played by default when the game starts.
+pub const INTRO_CUTSCENE: &str = "./cutscenes/intro.json";
+
+/// UDPのソケットを既定で結び付けるアドレス。<br />
+/// Address UDP sockets are bound to by default.
+pub const DEFAULT_UDP_BIND_POINT: &str = "0.0.0.0:0";
+
+const LOG_LEVELS: [&str; 6] = ["trace", "debug", "info", "warn", "error", "off"];
+
+/// Unicodeの最後の文字。<br />
+/// The last character of Unicode.
+const MAX_CODE_POINT: u32 = 0x10FFFF;
+
+/// 起動に必要な設定。`settings.toml`から読み込み、同じ名前の大文字の環境変数で上書きできる。<br />
+/// Settings needed to start the game. They're read from `settings.toml`, and environment variables with the same name in upper case override them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// ログの詳しさ。`trace`、`debug`、`info`、`warn`、`error`か`off`。<br />
+    /// How verbose the log is: `trace`, `debug`, `info`, `warn`, `error` or `off`.
+    pub log: String,
+    pub api: String,
+    /// 検証レイヤーを使うかどうか。<br />
+    /// Whether the validation layers are used.
+    pub debug: bool,
+    pub inflight_buffer_count: usize,
+    /// 平行光源の水平な位置。<br />
+    /// Horizontal position of the directional light.
+    pub light_x: f32,
+    pub light_z: f32,
+    /// 昼と夜が一巡りする秒数。<br />
+    /// Number of seconds for a full cycle of day and night.
+    pub day_length: f32,
+    /// 地形の一枚の大きさに掛ける倍率。<br />
+    /// Scale applied to the size of a single terrain patch.
+    pub ratio: f32,
+    /// 他のプレイヤーに見せる名札の色。`#RRGGBB`か`#RRGGBBAA`。<br />
+    /// Color of the nameplate shown to other players, as `#RRGGBB` or `#RRGGBBAA`.
+    pub nameplate_color: String,
+    /// 名札の下に出す称号。空なら出さない。<br />
+    /// Title shown under the nameplate. Nothing is shown if it's empty.
+    pub title: String,
+    pub badge: String,
+    /// 自分の名札を他のプレイヤーに見せないかどうか。<br />
+    /// Whether to hide the player's own nameplate from other players.
+    pub hide_nameplate: bool,
+    /// チャットを読まないことを他のプレイヤーに知らせる。<br />
+    /// Let other players know that the player doesn't read chat.
+    pub mute_chat: bool,
+    /// UIのフォント。<br />
+    /// Font of the UI.
+    pub ui_font: String,
+    /// UIのフォントにない文字を前から順に探す、代わりのフォント。<br />
+    /// Fallback fonts searched in order for characters missing from the UI font.
+    pub ui_fallback_fonts: Vec<String>,
+    /// 使われる前から最初にベイクしておく文字の範囲。両端を含む。他の文字は使われた時にベイクする。<br />
+    /// Glyph ranges baked from the start before they're used, both ends inclusive. Other characters are baked when they're used.
+    pub ui_glyph_ranges: Vec<(u32, u32)>,
+    /// 続けて起動に失敗した時と同じく、一番低い品質のグラフィックスで起動する。<br />
+    /// Start with the lowest quality graphics, the same as after failing to start repeatedly.
+    pub safe_mode: bool,
+    /// 希望するプレゼントモード。`FIFO`、`MAILBOX`か`IMMEDIATE`。<br />
+    /// Requested present mode: `FIFO`, `MAILBOX` or `IMMEDIATE`.
+    pub present_mode: String,
+    /// 垂直同期を使わない時のフレームレートの上限。0は上限なし。<br />
+    /// Frame rate cap when vertical sync is off. 0 means no cap.
+    pub max_fps: u32,
+    /// `Windowed`、`Borderless`か`Exclusive`。<br />
+    /// `Windowed`, `Borderless` or `Exclusive`.
+    pub window_mode: String,
+    /// サーバーに接続せずにオフラインで遊ぶ。<br />
+    /// Play offline without connecting to the server.
+    pub offline: bool,
+    /// gRPCサーバーのエンドポイント。空ならオフラインで遊ぶ。<br />
+    /// Endpoint of the gRPC server. The game is played offline if it's empty.
+    pub server_endpoint: String,
+    /// UDPのソケットを結び付けるアドレス。`UDP_BINDPOINT`で上書きする。<br />
+    /// Address the UDP socket is bound to, overridden with `UDP_BINDPOINT`.
+    pub udp_bind_point: String,
+    /// ゲームを推進する時に接続するUDPのエンドポイント。空ならゲームが始まった時にサーバーから受け取る。<br />
+    /// UDP endpoint connected to when progressing the game. If it's empty, it's received from the server when the game starts.
+    pub udp_endpoint: String,
+    /// JWTトークンを取得する時の名前とパスワード。<br />
+    /// Name and password used to acquire JWT tokens.
+    pub login_name: String,
+    pub login_pass: String,
+    /// 地形の縁から遊べる範囲までの幅。<br />
+    /// Width between the edge of the terrain and the playable area.
+    pub world_border: f32,
+    /// 水面の中心の位置と高さと大きさ。大きさが0なら水面を置かない。<br />
+    /// Position of the center, height and scale of the water surface. No water is placed if the scale is 0.
+    pub water_pos: f32,
+    pub water_height: f32,
+    pub water_scale: f32,
+    /// ゲームが始まった時に再生するカットシーンのファイル。<br />
+    /// Cutscene file played when the game starts.
+    pub scene_file: String,
+    /// DiscordのアプリケーションID。0ならDiscordのRich Presenceを使わない。<br />
+    /// Application ID on Discord. Discord Rich Presence isn't used if it's 0.
+    pub discord_client_id: u64,
+    /// ホストする時にLANに告知するサーバーの名前と、リッスンサーバーのポート。<br />
+    /// Name of the server announced on the LAN when hosting, and the ports of the listen server.
+    pub host_name: String,
+    pub host_grpc_port: u16,
+    pub host_udp_port: u16,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            log: "info".to_string(),
+            api: "VULKAN".to_string(),
+            debug: false,
+            inflight_buffer_count: 2,
+            light_x: 10000.0,
+            light_z: 10000.0,
+            day_length: DEFAULT_DAY_LENGTH,
+            ratio: 1.0,
+            nameplate_color: "#FFFFFF".to_string(),
+            title: String::new(),
+            badge: String::new(),
+            hide_nameplate: false,
+            mute_chat: false,
+            ui_font: "resource/Comfortaa-Regular.ttf".to_string(),
+            ui_fallback_fonts: vec![
+                "resource/Roboto-Regular.ttf".to_string(),
+                "resource/HiraMaruProN-W4.otf".to_string(),
+            ],
+            ui_glyph_ranges: vec![],
+            safe_mode: false,
+            present_mode: PresentMode::default().get_name().to_string(),
+            max_fps: 0,
+            window_mode: WindowMode::default().get_name().to_string(),
+            offline: false,
+            server_endpoint: String::new(),
+            udp_bind_point: DEFAULT_UDP_BIND_POINT.to_string(),
+            udp_endpoint: String::new(),
+            login_name: String::new(),
+            login_pass: String::new(),
+            world_border: DEFAULT_WORLD_BORDER,
+            water_pos: 0.0,
+            water_height: 0.0,
+            water_scale: 0.0,
+            scene_file: INTRO_CUTSCENE.to_string(),
+            discord_client_id: 0,
+            host_name: "Demo game".to_string(),
+            host_grpc_port: LISTEN_SERVER_GRPC_PORT,
+            host_udp_port: LISTEN_SERVER_UDP_PORT,
+        }
+    }
+}
+
+impl Settings {
+    /// 設定ファイルを読み込み、環境変数で上書きしてから確かめる。<br />
+    /// Read the settings file, override it with environment variables, and validate the result.
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        let mut settings = match std::fs::read_to_string(file_name) {
+            Ok(content) => {
+                Self::parse(&content).with_context(|| format!("Failed to parse {}.", file_name))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}.", file_name));
+            }
+        };
+        settings.apply_overrides(|key| dotenv::var(key).ok())?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// 書かれていない項目は既定の値にする。<br />
+    /// Items that aren't written get their default values.
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// `LOG`や`INFLIGHT_BUFFER_COUNT`のような、項目の名前を大文字にした変数で上書きする。<br />
+    /// `udp_bind_point`だけは、以前からの`UDP_BINDPOINT`で上書きする。<br />
+    /// Override the items with variables named after them in upper case, such as `LOG` or `INFLIGHT_BUFFER_COUNT`.<br />
+    /// Only `udp_bind_point` is overridden with `UDP_BINDPOINT`, the name it has always had.
+    pub fn apply_overrides<F>(&mut self, get_var: F) -> anyhow::Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(log) = get_var("LOG") {
+            self.log = log;
+        }
+        if let Some(api) = get_var("API") {
+            self.api = api;
+        }
+        override_value(&get_var, "DEBUG", &mut self.debug)?;
+        override_value(
+            &get_var,
+            "INFLIGHT_BUFFER_COUNT",
+            &mut self.inflight_buffer_count,
+        )?;
+        override_value(&get_var, "LIGHT_X", &mut self.light_x)?;
+        override_value(&get_var, "LIGHT_Z", &mut self.light_z)?;
+        override_value(&get_var, "DAY_LENGTH", &mut self.day_length)?;
+        override_value(&get_var, "RATIO", &mut self.ratio)?;
+        if let Some(color) = get_var("NAMEPLATE_COLOR") {
+            self.nameplate_color = color;
+        }
+        if let Some(title) = get_var("TITLE") {
+            self.title = title;
+        }
+        if let Some(badge) = get_var("BADGE") {
+            self.badge = badge;
+        }
+        override_value(&get_var, "HIDE_NAMEPLATE", &mut self.hide_nameplate)?;
+        override_value(&get_var, "MUTE_CHAT", &mut self.mute_chat)?;
+        if let Some(font) = get_var("UI_FONT") {
+            self.ui_font = font;
+        }
+        if let Some(fonts) = get_var("UI_FALLBACK_FONTS") {
+            self.ui_fallback_fonts = fonts
+                .split(',')
+                .map(str::trim)
+                .filter(|font| !font.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        if let Some(ranges) = get_var("UI_GLYPH_RANGES") {
+            self.ui_glyph_ranges = parse_glyph_ranges(&ranges)
+                .with_context(|| format!("{} can't be read from UI_GLYPH_RANGES.", ranges))?;
+        }
+        override_value(&get_var, "SAFE_MODE", &mut self.safe_mode)?;
+        if let Some(present_mode) = get_var("PRESENT_MODE") {
+            self.present_mode = present_mode;
+        }
+        override_value(&get_var, "MAX_FPS", &mut self.max_fps)?;
+        if let Some(window_mode) = get_var("WINDOW_MODE") {
+            self.window_mode = window_mode;
+        }
+        override_value(&get_var, "OFFLINE", &mut self.offline)?;
+        if let Some(server_endpoint) = get_var("SERVER_ENDPOINT") {
+            self.server_endpoint = server_endpoint;
+        }
+        if let Some(udp_bind_point) = get_var("UDP_BINDPOINT") {
+            self.udp_bind_point = udp_bind_point;
+        }
+        if let Some(udp_endpoint) = get_var("UDP_ENDPOINT") {
+            self.udp_endpoint = udp_endpoint;
+        }
+        if let Some(login_name) = get_var("LOGIN_NAME") {
+            self.login_name = login_name;
+        }
+        if let Some(login_pass) = get_var("LOGIN_PASS") {
+            self.login_pass = login_pass;
+        }
+        override_value(&get_var, "WORLD_BORDER", &mut self.world_border)?;
+        override_value(&get_var, "WATER_POS", &mut self.water_pos)?;
+        override_value(&get_var, "WATER_HEIGHT", &mut self.water_height)?;
+        override_value(&get_var, "WATER_SCALE", &mut self.water_scale)?;
+        if let Some(scene_file) = get_var("SCENE_FILE") {
+            self.scene_file = scene_file;
+        }
+        override_value(&get_var, "DISCORD_CLIENT_ID", &mut self.discord_client_id)?;
+        if let Some(host_name) = get_var("HOST_NAME") {
+            self.host_name = host_name;
+        }
+        override_value(&get_var, "HOST_GRPC_PORT", &mut self.host_grpc_port)?;
+        override_value(&get_var, "HOST_UDP_PORT", &mut self.host_udp_port)?;
+        self.log = self.log.to_lowercase();
+        self.api = self.api.to_uppercase();
+        Ok(())
+    }
+
+    /// 起動する前に、使えない値がないかを確かめる。<br />
+    /// Check for unusable values before starting.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !LOG_LEVELS.contains(&self.log.as_str()) {
+            return Err(anyhow::anyhow!(
+                "log is {}, but it must be one of {}.",
+                self.log,
+                LOG_LEVELS.join(", ")
+            ));
+        }
+        if !SUPPORTED_APIS.contains(&self.api.as_str()) {
+            return Err(anyhow::anyhow!(
+                "api is {}, but it must be one of {}.",
+                self.api,
+                SUPPORTED_APIS.join(", ")
+            ));
+        }
+        if self.inflight_buffer_count == 0 || self.inflight_buffer_count > MAX_INFLIGHT_BUFFER_COUNT
+        {
+            return Err(anyhow::anyhow!(
+                "inflight_buffer_count is {}, but it must be between 1 and {}.",
+                self.inflight_buffer_count,
+                MAX_INFLIGHT_BUFFER_COUNT
+            ));
+        }
+        if !self.light_x.is_finite() || !self.light_z.is_finite() {
+            return Err(anyhow::anyhow!(
+                "light_x and light_z must be finite numbers."
+            ));
+        }
+        if !self.day_length.is_finite() || self.day_length <= 0.0 {
+            return Err(anyhow::anyhow!(
+                "day_length is {}, but it must be greater than 0.",
+                self.day_length
+            ));
+        }
+        if !self.ratio.is_finite() || self.ratio <= 0.0 {
+            return Err(anyhow::anyhow!(
+                "ratio is {}, but it must be greater than 0.",
+                self.ratio
+            ));
+        }
+        parse_color(&self.nameplate_color).context("nameplate_color can't be used as a color.")?;
+        if self.ui_font.is_empty() {
+            return Err(anyhow::anyhow!("ui_font must not be empty."));
+        }
+        if let Some((start, end)) = self
+            .ui_glyph_ranges
+            .iter()
+            .find(|(start, end)| start > end || *end > MAX_CODE_POINT)
+        {
+            return Err(anyhow::anyhow!(
+                "ui_glyph_ranges has {:#X}-{:#X}, but ranges must go upwards and end by {:#X}.",
+                start,
+                end,
+                MAX_CODE_POINT
+            ));
+        }
+        if PresentMode::from_name(&self.present_mode).is_none() {
+            return Err(anyhow::anyhow!(
+                "present_mode is {}, but it must be FIFO, MAILBOX or IMMEDIATE.",
+                self.present_mode
+            ));
+        }
+        if WindowMode::from_name(&self.window_mode).is_none() {
+            return Err(anyhow::anyhow!(
+                "window_mode is {}, but it must be Windowed, Borderless or Exclusive.",
+                self.window_mode
+            ));
+        }
+        if !self.world_border.is_finite() || self.world_border < 0.0 {
+            return Err(anyhow::anyhow!(
+                "world_border is {}, but it must be 0 or greater.",
+                self.world_border
+            ));
+        }
+        if !self.water_pos.is_finite()
+            || !self.water_height.is_finite()
+            || !self.water_scale.is_finite()
+            || self.water_scale < 0.0
+        {
+            return Err(anyhow::anyhow!(
+                "water_pos, water_height and water_scale must be finite, and water_scale can't be negative."
+            ));
+        }
+        if self.host_name.is_empty() || self.host_name.len() > MAX_SERVER_NAME_LENGTH {
+            return Err(anyhow::anyhow!(
+                "host_name must be between 1 and {} bytes long.",
+                MAX_SERVER_NAME_LENGTH
+            ));
+        }
+        Ok(())
+    }
+
+    /// 水面の中心の位置、高さと大きさ。水面を置かなければ`None`。<br />
+    /// Position of the center, height and scale of the water surface, or `None` if no water is placed.
+    pub fn get_water(&self) -> Option<(f32, f32, f32)> {
+        if self.water_scale > 0.0 {
+            Some((self.water_pos, self.water_height, self.water_scale))
+        } else {
+            None
+        }
+    }
+
+    /// 部屋に登録する時に送る、名札などの設定を作る。<br />
+    /// Create the settings such as the nameplate, sent when registering to a room.
+    pub fn get_player_settings(&self) -> PlayerSettings {
+        let mut settings = PlayerSettings {
+            nameplate_color: parse_color(&self.nameplate_color)
+                .map(pack_color)
+                .unwrap_or(0),
+            title: self.title.clone(),
+            badge: self.badge.clone(),
+            hide_nameplate: self.hide_nameplate,
+            mute_chat: self.mute_chat,
+        };
+        sanitize_settings(&mut settings);
+        settings
+    }
+
+    pub fn get_log_level(&self) -> LevelFilter {
+        match self.log.as_str() {
+            "trace" => LevelFilter::Trace,
+            "debug" => LevelFilter::Debug,
+            "info" => LevelFilter::Info,
+            "warn" => LevelFilter::Warn,
+            "error" => LevelFilter::Error,
+            _ => LevelFilter::Off,
+        }
+    }
+}
+
+/// `3040-30FF,4E00-9FFF`のように、十六進数の範囲をカンマで区切って並べたもの。<br />
+/// Hexadecimal ranges separated by commas, such as `3040-30FF,4E00-9FFF`.
+fn parse_glyph_ranges(ranges: &str) -> anyhow::Result<Vec<(u32, u32)>> {
+    ranges
+        .split(',')
+        .map(str::trim)
+        .filter(|range| !range.is_empty())
+        .map(|range| {
+            let (start, end) = match range.find('-') {
+                Some(index) => (&range[..index], &range[index + 1..]),
+                None => (range, range),
+            };
+            let parse = |value: &str| {
+                u32::from_str_radix(value.trim().trim_start_matches("0x"), 16)
+                    .with_context(|| format!("{} isn't a hexadecimal character.", value))
+            };
+            Ok((parse(start)?, parse(end)?))
+        })
+        .collect()
+}
+
+fn override_value<F, T>(get_var: &F, key: &str, value: &mut T) -> anyhow::Result<()>
+where
+    F: Fn(&str) -> Option<String>,
+    T: FromStr,
+{
+    if let Some(var) = get_var(key) {
+        *value = var
+            .trim()
+            .parse::<T>()
+            .map_err(|_| anyhow::anyhow!("{} can't be read from {}.", var, key))?;
+    }
+    Ok(())
+}
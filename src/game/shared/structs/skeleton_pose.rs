@@ -0,0 +1,76 @@
+use crate::game::shared::structs::{DebugDraw, Joint};
+use glam::{Mat4, Vec3, Vec3A};
+
+/// 骨を描く線の色。<br />
+/// Color of the lines drawn for bones.
+pub const BONE_COLOR: [u8; 4] = [255, 200, 60, 255];
+
+/// 関節の印の色と、印の腕の長さ（メートル）。<br />
+/// Color of the joint markers and the length of their arms in meters.
+pub const JOINT_COLOR: [u8; 4] = [255, 80, 80, 255];
+pub const JOINT_MARKER_SIZE: f32 = 0.03;
+
+/// ポーズを取った関節のワールド空間での位置。<br />
+/// The position of a posed joint in world space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PosedJoint {
+    pub name: String,
+    pub position: Vec3A,
+    /// 親の関節の`joints`の中での番号。根なら`None`。<br />
+    /// Index of the parent joint in `joints`, or `None` for the root.
+    pub parent: Option<usize>,
+}
+
+/// 一フレームの骨格の姿勢。骨付きモデルを更新した時の関節の行列から求める。<br />
+/// The pose of a skeleton for a frame, derived from the joint matrices of the last skinned model update.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SkeletonPose {
+    pub joints: Vec<PosedJoint>,
+}
+
+impl SkeletonPose {
+    /// 関節の行列は逆バインド行列を掛けたものなので、バインド行列を掛けて関節の変換に戻す。<br />
+    /// Joint matrices include the inverse bind matrix, so the bind matrix is applied to get the joint transform back.
+    pub fn new(root_joint: &Joint, joint_matrices: &[Mat4], world_matrix: Mat4) -> Self {
+        let mut joints = vec![];
+        Self::add_joint(root_joint, None, joint_matrices, world_matrix, &mut joints);
+        SkeletonPose { joints }
+    }
+
+    fn add_joint(
+        joint: &Joint,
+        parent: Option<usize>,
+        joint_matrices: &[Mat4],
+        world_matrix: Mat4,
+        joints: &mut Vec<PosedJoint>,
+    ) {
+        let joint_matrix = match joint_matrices.get(joint.index) {
+            Some(matrix) => *matrix,
+            None => return,
+        };
+        let transform = world_matrix * joint_matrix * joint.inverse_bind_matrices.inverse();
+        let index = joints.len();
+        joints.push(PosedJoint {
+            name: joint.name.clone(),
+            position: Vec3A::from(transform.transform_point3(Vec3::zero())),
+            parent,
+        });
+        for child in joint.children.iter() {
+            Self::add_joint(child, Some(index), joint_matrices, world_matrix, joints);
+        }
+    }
+
+    /// 親と子を繋ぐ骨の線と、関節ごとの十字の印を描く。<br />
+    /// Draw a line for each bone from parent to child, and a cross marking each joint.
+    pub fn draw(&self, debug_draw: &mut DebugDraw) {
+        for joint in self.joints.iter() {
+            if let Some(parent) = joint.parent {
+                debug_draw.draw_line(self.joints[parent].position, joint.position, BONE_COLOR);
+            }
+            for axis in [Vec3A::unit_x(), Vec3A::unit_y(), Vec3A::unit_z()].iter() {
+                let arm = *axis * JOINT_MARKER_SIZE;
+                debug_draw.draw_line(joint.position - arm, joint.position + arm, JOINT_COLOR);
+            }
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use glam::Vec3A;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+
+/// `--soak`で時間を指定しなかった時に続ける長さ（時間）。<br />
+/// How long a soak test runs when `--soak` is given without hours.
+pub const SOAK_DURATION_HOURS: f64 = 4.0;
+
+/// 一回の試合を続ける長さ（秒）。この後タイトルに戻る。<br />
+/// How long each match lasts in seconds before returning to the title.
+pub const SOAK_GAME_DURATION: f64 = 60.0;
+
+/// CSVに記録する間隔（秒）。<br />
+/// Interval between rows written to the CSV, in seconds.
+pub const SOAK_SAMPLE_INTERVAL: f64 = 5.0;
+
+/// 記録を書き出すCSVのファイル。<br />
+/// The CSV file samples are written to.
+pub const SOAK_CSV_PATH: &str = "./soak.csv";
+
+/// ローカルのプレイヤーの他に部屋に加える、動き回るだけのプレイヤーの数。<br />
+/// Number of players that only move around, added to the room besides the local player.
+pub const SOAK_SYNTHETIC_PLAYERS: usize = 7;
+
+/// 合成したプレイヤーが回る円の半径。<br />
+/// Radius of the circle synthetic players move along.
+const SYNTHETIC_ORBIT_RADIUS: f32 = 10.0;
+
+/// 合成したプレイヤーが回る速さ（ラジアン毎秒）。<br />
+/// Speed synthetic players move around the circle at, in radians per second.
+const SYNTHETIC_ORBIT_SPEED: f32 = 0.5;
+
+/// ステージングバッファやUIのバッファが伸び縮みする分として、VRAMの使用量に許す差（バイト）。<br />
+/// Difference in VRAM usage allowed for staging and UI buffers growing and shrinking, in bytes.
+const VRAM_TOLERANCE: u64 = 4 * 1024 * 1024;
+
+/// 生きているヒープの確保の数に許す割合。ログや文字列のバッファで多少は揺れる。<br />
+/// Ratio allowed for the number of live heap allocations, which wobbles a little with logs and string buffers.
+const HEAP_TOLERANCE_RATIO: f64 = 0.05;
+
+const CSV_HEADER: &str = "elapsed_seconds,cycle,scene,fps,vram_bytes,vram_allocations,descriptor_pools,models,textures,entities,heap_allocations";
+
+/// 一回の記録で数えるリソースの数。<br />
+/// Resource counts taken for each sample.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ResourceCounts {
+    /// VMAが確保しているVRAMのバイト数。<br />
+    /// Bytes of VRAM allocated through VMA.
+    pub vram_bytes: u64,
+    pub vram_allocations: usize,
+    /// 作った描述子プールの数。プールは使い回すので、増え続けるならどこかで漏れている。<br />
+    /// Number of descriptor pools created. Pools are reused, so a steady increase means something leaks.
+    pub descriptor_pools: usize,
+    pub models: usize,
+    pub textures: usize,
+    pub entities: usize,
+    /// まだ解放されていないヒープの確保の数。<br />
+    /// Number of heap allocations not freed yet.
+    pub heap_allocations: usize,
+}
+
+impl ResourceCounts {
+    /// 基準より増えたまま戻らなかった数を探す。<br />
+    /// Find the counts that grew past the baseline and didn't come back.
+    pub fn find_leaks(&self, baseline: &ResourceCounts) -> Vec<SoakLeak> {
+        let heap_tolerance = (baseline.heap_allocations as f64 * HEAP_TOLERANCE_RATIO) as u64;
+        let counts = [
+            (
+                "VRAM bytes",
+                baseline.vram_bytes,
+                self.vram_bytes,
+                VRAM_TOLERANCE,
+            ),
+            (
+                "VRAM allocations",
+                baseline.vram_allocations as u64,
+                self.vram_allocations as u64,
+                0,
+            ),
+            (
+                "Descriptor pools",
+                baseline.descriptor_pools as u64,
+                self.descriptor_pools as u64,
+                0,
+            ),
+            ("Models", baseline.models as u64, self.models as u64, 0),
+            (
+                "Textures",
+                baseline.textures as u64,
+                self.textures as u64,
+                0,
+            ),
+            (
+                "Entities",
+                baseline.entities as u64,
+                self.entities as u64,
+                0,
+            ),
+            (
+                "Heap allocations",
+                baseline.heap_allocations as u64,
+                self.heap_allocations as u64,
+                heap_tolerance,
+            ),
+        ];
+        counts
+            .iter()
+            .filter(|(_, baseline, current, tolerance)| *current > baseline + tolerance)
+            .map(|(name, baseline, current, _)| SoakLeak {
+                name: *name,
+                baseline: *baseline,
+                current: *current,
+            })
+            .collect()
+    }
+}
+
+/// 基準に戻らなかったリソース。<br />
+/// A resource that didn't return to the baseline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoakLeak {
+    pub name: &'static str,
+    pub baseline: u64,
+    pub current: u64,
+}
+
+impl Display for SoakLeak {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} (+{})",
+            self.name,
+            self.baseline,
+            self.current,
+            self.current - self.baseline
+        )
+    }
+}
+
+/// 記録した時にゲームがしていたこと。<br />
+/// What the game was doing when a sample was taken.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SoakState {
+    Title,
+    Loading,
+    Game,
+}
+
+impl SoakState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SoakState::Title => "title",
+            SoakState::Loading => "loading",
+            SoakState::Game => "game",
+        }
+    }
+}
+
+/// ソークテストがゲームに頼むこと。<br />
+/// What the soak test asks the game to do.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SoakAction {
+    Continue,
+    /// 試合を終えてタイトルに戻る。<br />
+    /// End the match and return to the title.
+    ReturnToTitle,
+    /// 時間が経ったか、漏れが見つかったので終了する。<br />
+    /// Exit because the time is up or a leak was found.
+    Finish,
+}
+
+/// タイトルと試合を繰り返しながらリソースの数とFPSをCSVに記録し、一巡ごとに基準に戻ったかを確かめる。<br />
+/// 最初の一巡はキャッシュやパイプラインを温めるので、その後のタイトルの数を基準にする。<br />
+/// Records resource counts and FPS to a CSV while cycling between the title and matches, and checks that they return to the baseline after each cycle.<br />
+/// The first cycle warms up caches and pipelines, so the counts on the title after it become the baseline.
+pub struct SoakTest<W: Write> {
+    writer: W,
+    duration: f64,
+    game_duration: f64,
+    sample_interval: f64,
+    elapsed: f64,
+    sample_elapsed: f64,
+    sample_frames: usize,
+    game_elapsed: f64,
+    cycle: usize,
+    is_returning: bool,
+    baseline: Option<ResourceCounts>,
+    leaks: Vec<SoakLeak>,
+}
+
+impl<W: Write> SoakTest<W> {
+    /// CSVの見出しを書き込んでテストを始める。`duration`は秒。<br />
+    /// Write the CSV header and start the test. `duration` is in seconds.
+    pub fn new(writer: W, duration: f64) -> anyhow::Result<Self> {
+        Self::with_intervals(writer, duration, SOAK_GAME_DURATION, SOAK_SAMPLE_INTERVAL)
+    }
+
+    pub fn with_intervals(
+        mut writer: W,
+        duration: f64,
+        game_duration: f64,
+        sample_interval: f64,
+    ) -> anyhow::Result<Self> {
+        writeln!(writer, "{}", CSV_HEADER)?;
+        writer.flush()?;
+        Ok(SoakTest {
+            writer,
+            duration,
+            game_duration,
+            sample_interval,
+            elapsed: 0.0,
+            sample_elapsed: 0.0,
+            sample_frames: 0,
+            game_elapsed: 0.0,
+            cycle: 0,
+            is_returning: false,
+            baseline: None,
+            leaks: vec![],
+        })
+    }
+
+    /// 毎フレーム呼び出す。リソースを数えるのは重いので、記録する時と一巡した時だけ`get_counts`を呼ぶ。<br />
+    /// Called every frame. Counting resources is expensive, so `get_counts` is only called when sampling and after each cycle.
+    pub fn update<F>(
+        &mut self,
+        delta_time: f64,
+        state: SoakState,
+        mut get_counts: F,
+    ) -> anyhow::Result<SoakAction>
+    where
+        F: FnMut() -> ResourceCounts,
+    {
+        self.elapsed += delta_time;
+        self.sample_elapsed += delta_time;
+        self.sample_frames += 1;
+        if self.sample_elapsed >= self.sample_interval {
+            let fps = self.sample_frames as f64 / self.sample_elapsed;
+            self.write_sample(state, fps, &get_counts())?;
+            self.sample_elapsed = 0.0;
+            self.sample_frames = 0;
+        }
+
+        match state {
+            SoakState::Game if !self.is_returning => {
+                self.game_elapsed += delta_time;
+                if self.game_elapsed >= self.game_duration {
+                    self.game_elapsed = 0.0;
+                    self.is_returning = true;
+                    return Ok(SoakAction::ReturnToTitle);
+                }
+            }
+            SoakState::Title if self.is_returning => {
+                self.is_returning = false;
+                self.cycle += 1;
+                let counts = get_counts();
+                match self.baseline.as_ref() {
+                    Some(baseline) => {
+                        self.leaks = counts.find_leaks(baseline);
+                        // 漏れは巡るごとに積み重なるだけなので、最初に見つかった所で止める。
+                        // Leaks only pile up with each cycle, so stop at the first one found.
+                        if !self.leaks.is_empty() {
+                            return Ok(SoakAction::Finish);
+                        }
+                    }
+                    None => self.baseline = Some(counts),
+                }
+            }
+            _ => (),
+        }
+
+        if self.elapsed >= self.duration {
+            Ok(SoakAction::Finish)
+        } else {
+            Ok(SoakAction::Continue)
+        }
+    }
+
+    /// 終わった巡りの数。<br />
+    /// Number of completed cycles.
+    pub fn get_cycle(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn get_baseline(&self) -> Option<&ResourceCounts> {
+        self.baseline.as_ref()
+    }
+
+    pub fn get_leaks(&self) -> &[SoakLeak] {
+        &self.leaks
+    }
+
+    pub fn is_passed(&self) -> bool {
+        self.leaks.is_empty()
+    }
+
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+
+    fn write_sample(
+        &mut self,
+        state: SoakState,
+        fps: f64,
+        counts: &ResourceCounts,
+    ) -> anyhow::Result<()> {
+        writeln!(
+            self.writer,
+            "{:.1},{},{},{:.1},{},{},{},{},{},{},{}",
+            self.elapsed,
+            self.cycle,
+            state.as_str(),
+            fps,
+            counts.vram_bytes,
+            counts.vram_allocations,
+            counts.descriptor_pools,
+            counts.models,
+            counts.textures,
+            counts.entities,
+            counts.heap_allocations
+        )?;
+        // 途中で落ちても、そこまでの記録が残るようにする。
+        // Keep the samples so far even if the game crashes partway through.
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// 合成したプレイヤーの位置。全員が同じ円の上を等間隔に並んで回る。<br />
+/// Position of a synthetic player. All of them move around the same circle, evenly spaced.
+pub fn get_synthetic_player_position(index: usize, count: usize, time: f64) -> Vec3A {
+    let phase = std::f32::consts::PI * 2.0 * index as f32 / count.max(1) as f32;
+    let angle = phase + time as f32 * SYNTHETIC_ORBIT_SPEED;
+    Vec3A::new(
+        angle.cos() * SYNTHETIC_ORBIT_RADIUS,
+        0.0,
+        angle.sin() * SYNTHETIC_ORBIT_RADIUS,
+    )
+}
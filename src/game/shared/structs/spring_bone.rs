@@ -0,0 +1,388 @@
+use crate::game::shared::structs::Joint;
+use glam::{Mat4, Quat, Vec3, Vec3A};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 一度に進める最長の時間。フレームが落ちた時に揺れが暴れないようにする。<br />
+/// Longest time advanced in one step, so the motion doesn't explode when frames drop.
+const MAX_STEP: f32 = 1.0 / 30.0;
+
+fn default_gravity() -> [f32; 3] {
+    [0.0, -9.8, 0.0]
+}
+
+fn default_stiffness() -> f32 {
+    0.1
+}
+
+fn default_damping() -> f32 {
+    0.1
+}
+
+fn default_gravity_scale() -> f32 {
+    1.0
+}
+
+/// 揺れる骨の鎖の設定。最初の関節はアニメーションのまま動き、残りが揺れる。<br />
+/// Settings of a chain of swinging bones. The first joint follows the animation and the rest swing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpringChainConfig {
+    pub name: String,
+    /// 根元から先端までの関節の名前。<br />
+    /// Names of the joints from the root to the tip.
+    pub joints: Vec<String>,
+    /// アニメーションの姿勢に戻ろうとする強さ。0から1まで。<br />
+    /// How strongly the chain returns to the animated pose, from 0 to 1.
+    #[serde(default = "default_stiffness")]
+    pub stiffness: f32,
+    /// 毎ステップ失う速度の割合。0から1まで。<br />
+    /// Fraction of the velocity lost every step, from 0 to 1.
+    #[serde(default = "default_damping")]
+    pub damping: f32,
+    #[serde(default = "default_gravity_scale")]
+    pub gravity_scale: f32,
+    /// コライダーとの当たりに使う関節の半径（ワールド単位）。<br />
+    /// Radius of the joints used against colliders, in world units.
+    #[serde(default)]
+    pub radius: f32,
+}
+
+/// 関節に付けるカプセルのコライダー。両端は関節の空間、半径はワールド単位。<br />
+/// Capsule collider attached to a joint. Both ends are in the joint's space, and the radius is in world units.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CapsuleColliderConfig {
+    pub joint: String,
+    #[serde(default)]
+    pub start: [f32; 3],
+    #[serde(default)]
+    pub end: [f32; 3],
+    pub radius: f32,
+}
+
+/// キャラクターごとにデータファイルから読み込む揺れる骨の設定。<br />
+/// Spring-bone settings, loaded from a data file per character.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpringBoneConfig {
+    #[serde(default = "default_gravity")]
+    pub gravity: [f32; 3],
+    pub chains: Vec<SpringChainConfig>,
+    #[serde(default)]
+    pub colliders: Vec<CapsuleColliderConfig>,
+}
+
+impl SpringBoneConfig {
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(file_name)?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let config: SpringBoneConfig = serde_json::from_str(json)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        let is_ratio = |value: f32| (0.0..=1.0).contains(&value);
+        for chain in self.chains.iter() {
+            if chain.joints.len() < 2 {
+                return Err(anyhow::anyhow!(
+                    "Spring chain {} needs at least two joints.",
+                    &chain.name
+                ));
+            }
+            if !is_ratio(chain.stiffness) || !is_ratio(chain.damping) || chain.radius < 0.0 {
+                return Err(anyhow::anyhow!(
+                    "Spring chain {} has out-of-range stiffness, damping or radius.",
+                    &chain.name
+                ));
+            }
+        }
+        if let Some(collider) = self.colliders.iter().find(|c| c.radius <= 0.0) {
+            return Err(anyhow::anyhow!(
+                "Collider on joint {} must have a positive radius.",
+                &collider.joint
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 線分に半径を持たせたカプセル。<br />
+/// A capsule: a line segment with a radius.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Capsule {
+    pub start: Vec3A,
+    pub end: Vec3A,
+    pub radius: f32,
+}
+
+impl Capsule {
+    pub fn get_closest_point(&self, point: Vec3A) -> Vec3A {
+        let segment = self.end - self.start;
+        let length_squared = segment.dot(segment);
+        if length_squared <= f32::EPSILON {
+            return self.start;
+        }
+        let t = ((point - self.start).dot(segment) / length_squared)
+            .max(0.0)
+            .min(1.0);
+        self.start + segment * t
+    }
+
+    /// 半径`radius`の球がカプセルに入り込んでいれば、表面まで押し出す。<br />
+    /// Push a sphere of `radius` out to the surface if it's inside the capsule.
+    pub fn push_out(&self, point: Vec3A, radius: f32) -> Vec3A {
+        let closest = self.get_closest_point(point);
+        let offset = point - closest;
+        let distance = offset.length();
+        let min_distance = self.radius + radius;
+        if distance >= min_distance || distance <= f32::EPSILON {
+            point
+        } else {
+            closest + offset / distance * min_distance
+        }
+    }
+}
+
+/// 一つの鎖の揺れの状態。粒子をベルレ積分で動かし、骨の長さを保つ。<br />
+/// Swinging state of a single chain. Particles are moved by Verlet integration, keeping the bone lengths.
+#[derive(Clone, Debug)]
+pub struct SpringChain {
+    config: SpringChainConfig,
+    current: Vec<Vec3A>,
+    previous: Vec<Vec3A>,
+}
+
+impl SpringChain {
+    pub fn new(config: SpringChainConfig) -> Self {
+        SpringChain {
+            config,
+            current: vec![],
+            previous: vec![],
+        }
+    }
+
+    pub fn get_config(&self) -> &SpringChainConfig {
+        &self.config
+    }
+
+    /// アニメーションが決めた関節の位置から揺れた位置を求める。最初の関節はアニメーションのまま。<br />
+    /// Compute the swung positions from the joint positions decided by the animation. The first joint stays animated.
+    pub fn simulate(
+        &mut self,
+        animated: &[Vec3A],
+        delta_time: f32,
+        gravity: Vec3A,
+        colliders: &[Capsule],
+    ) -> &[Vec3A] {
+        if self.current.len() != animated.len() {
+            self.current = animated.to_vec();
+            self.previous = animated.to_vec();
+            return &self.current;
+        }
+        let delta_time = delta_time.max(0.0).min(MAX_STEP);
+        let acceleration = gravity * self.config.gravity_scale * delta_time * delta_time;
+        self.current[0] = animated[0];
+        self.previous[0] = animated[0];
+        for i in 1..animated.len() {
+            let parent = self.current[i - 1];
+            let rest = animated[i] - animated[i - 1];
+            let target = parent + rest;
+            let current = self.current[i];
+            let velocity = (current - self.previous[i]) * (1.0 - self.config.damping);
+            let mut next =
+                current + velocity + (target - current) * self.config.stiffness + acceleration;
+            let direction = next - parent;
+            next = if direction.length() > f32::EPSILON {
+                parent + direction.normalize() * rest.length()
+            } else {
+                target
+            };
+            for collider in colliders.iter() {
+                next = collider.push_out(next, self.config.radius);
+            }
+            self.previous[i] = current;
+            self.current[i] = next;
+        }
+        &self.current
+    }
+}
+
+/// 二つの方向の間の回転。<br />
+/// Rotation between two directions.
+fn get_rotation_between(from: Vec3A, to: Vec3A) -> Quat {
+    if from.length() <= f32::EPSILON || to.length() <= f32::EPSILON {
+        return Quat::identity();
+    }
+    let from = from.normalize();
+    let to = to.normalize();
+    let axis = from.cross(to);
+    if axis.length() <= 1e-6 {
+        return Quat::identity();
+    }
+    let angle = from.dot(to).max(-1.0).min(1.0).acos();
+    Quat::from_axis_angle(Vec3::from(axis.normalize()), angle)
+}
+
+fn collect_joints<'a>(joint: &'a Joint, joints: &mut HashMap<&'a str, &'a Joint>) {
+    joints.insert(joint.name.as_str(), joint);
+    for child in joint.children.iter() {
+        collect_joints(child, joints);
+    }
+}
+
+fn collect_subtree(joint: &Joint, indices: &mut Vec<usize>) {
+    indices.push(joint.index);
+    for child in joint.children.iter() {
+        collect_subtree(child, indices);
+    }
+}
+
+/// 揺れる鎖の関節。<br />
+/// A joint of a swinging chain.
+#[derive(Clone, Debug)]
+struct ChainJoint {
+    index: usize,
+    bind_matrix: Mat4,
+    /// この関節とその子孫の番号。回転させる時に一緒に動かす。<br />
+    /// Indices of this joint and its descendants, moved together when it's rotated.
+    subtree: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Collider {
+    index: usize,
+    bind_matrix: Mat4,
+    start: Vec3,
+    end: Vec3,
+    radius: f32,
+}
+
+/// 一つのメッシュの骨に揺れを加える。アニメーションで関節の変換を求めた後に使う。<br />
+/// Adds secondary motion to the bones of a mesh. Used after the joint transforms are sampled from the animation.
+#[derive(Clone, Debug)]
+pub struct SpringBones {
+    config: Arc<SpringBoneConfig>,
+    chains: Vec<(Vec<ChainJoint>, SpringChain)>,
+    colliders: Vec<Collider>,
+}
+
+impl SpringBones {
+    /// 設定の関節の名前を骨から探す。このメッシュにない関節を使う鎖とコライダーは飛ばす。<br />
+    /// Look up the joint names of the settings in the skeleton. Chains and colliders using joints missing from this mesh are skipped.
+    pub fn new(config: Arc<SpringBoneConfig>, root_joint: &Joint) -> Self {
+        let mut joints = HashMap::new();
+        collect_joints(root_joint, &mut joints);
+        let bind_matrix = |joint: &Joint| joint.inverse_bind_matrices.inverse();
+        let chains = config
+            .chains
+            .iter()
+            .filter_map(|chain| {
+                let chain_joints = chain
+                    .joints
+                    .iter()
+                    .map(|name| joints.get(name.as_str()).copied())
+                    .collect::<Option<Vec<_>>>();
+                if chain_joints.is_none() {
+                    log::debug!("Skipping spring chain {} for this mesh.", &chain.name);
+                }
+                chain_joints.map(|chain_joints| {
+                    let chain_joints = chain_joints
+                        .into_iter()
+                        .map(|joint| {
+                            let mut subtree = vec![];
+                            collect_subtree(joint, &mut subtree);
+                            ChainJoint {
+                                index: joint.index,
+                                bind_matrix: bind_matrix(joint),
+                                subtree,
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    (chain_joints, SpringChain::new(chain.clone()))
+                })
+            })
+            .collect::<Vec<_>>();
+        let colliders = config
+            .colliders
+            .iter()
+            .filter_map(|collider| {
+                joints.get(collider.joint.as_str()).map(|joint| Collider {
+                    index: joint.index,
+                    bind_matrix: bind_matrix(joint),
+                    start: Vec3::from(collider.start),
+                    end: Vec3::from(collider.end),
+                    radius: collider.radius,
+                })
+            })
+            .collect::<Vec<_>>();
+        SpringBones {
+            config,
+            chains,
+            colliders,
+        }
+    }
+
+    pub fn get_config(&self) -> &Arc<SpringBoneConfig> {
+        &self.config
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+
+    /// 揺れを進めて、関節の変換に書き戻す。揺れはワールド空間で求めるので、キャラクターの移動にも反応する。<br />
+    /// Advance the motion and write it back into the joint transforms. It's computed in world space, so it also reacts to the character moving.
+    pub fn update(&mut self, delta_time: f32, world_matrix: Mat4, buffer: &mut [Mat4; 500]) {
+        let inverse_world = world_matrix.inverse();
+        let gravity = Vec3A::from(self.config.gravity);
+        let colliders = self
+            .colliders
+            .iter()
+            .map(|collider| {
+                let matrix = world_matrix * buffer[collider.index] * collider.bind_matrix;
+                Capsule {
+                    start: Vec3A::from(matrix.transform_point3(collider.start)),
+                    end: Vec3A::from(matrix.transform_point3(collider.end)),
+                    radius: collider.radius,
+                }
+            })
+            .collect::<Vec<_>>();
+        let get_position = |buffer: &[Mat4; 500], joint: &ChainJoint| {
+            Vec3A::from((buffer[joint.index] * joint.bind_matrix).transform_point3(Vec3::zero()))
+        };
+
+        for (joints, chain) in self.chains.iter_mut() {
+            let animated = joints
+                .iter()
+                .map(|joint| {
+                    Vec3A::from(
+                        world_matrix.transform_point3(Vec3::from(get_position(buffer, joint))),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let simulated = chain
+                .simulate(&animated, delta_time, gravity, &colliders)
+                .to_vec();
+
+            // 根元から順に、子の関節が揺れた位置を向くように回す。
+            // From the root, rotate each joint so its child points at the swung position.
+            for i in 0..joints.len() - 1 {
+                let pivot = get_position(buffer, &joints[i]);
+                let child = get_position(buffer, &joints[i + 1]);
+                let desired =
+                    Vec3A::from(inverse_world.transform_point3(Vec3::from(simulated[i + 1])));
+                let rotation = get_rotation_between(child - pivot, desired - pivot);
+                let pivot = Vec3::from(pivot);
+                let correction = Mat4::from_translation(pivot)
+                    * Mat4::from_quat(rotation)
+                    * Mat4::from_translation(-pivot);
+                for index in joints[i].subtree.iter() {
+                    buffer[*index] = correction * buffer[*index];
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use ash::vk::{
+    Format, VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate,
+};
+use glam::Vec2;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+pub const SPRITE_VERTEX_SHADER: &str = "./shaders/sprite_vert.spv";
+pub const SPRITE_FRAGMENT_SHADER: &str = "./shaders/sprite_frag.spv";
+
+/// アトラスの既定の一辺の大きさ（ピクセル）。<br />
+/// Default length of a side of the atlas, in pixels.
+pub const SPRITE_ATLAS_SIZE: u32 = 1024;
+
+/// 隣の画像の色が滲まないように、画像の周りに空ける隙間（ピクセル）。<br />
+/// Gap left around each image in pixels, so that colors of neighbouring images don't bleed.
+pub const SPRITE_ATLAS_PADDING: u32 = 1;
+
+/// 単色の四角形に使う、アトラスに必ずある白い領域の名前。<br />
+/// Name of the white region every atlas has, used for solid rectangles.
+pub const WHITE_SPRITE: &str = "white";
+
+/// スプライトの大きさとオフセットを決めた時の画面の高さ。実際の画面の高さに合わせて拡大縮小する。<br />
+/// Screen height that sizes and offsets of sprites are designed for. They're scaled to the actual screen height.
+pub const SPRITE_REFERENCE_HEIGHT: f32 = 720.0;
+
+/// スプライトのパイプラインに渡す頂点。位置は正規化デバイス座標。<br />
+/// A vertex passed to the sprite pipeline. The position is in normalized device coordinates.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SpriteVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [u8; 4],
+}
+
+impl SpriteVertex {
+    pub fn get_binding_description(binding: u32) -> VertexInputBindingDescription {
+        VertexInputBindingDescription::builder()
+            .binding(binding)
+            .input_rate(VertexInputRate::VERTEX)
+            .stride(std::mem::size_of::<SpriteVertex>() as u32)
+            .build()
+    }
+
+    pub fn get_attribute_description(binding: u32) -> Vec<VertexInputAttributeDescription> {
+        vec![
+            VertexInputAttributeDescription::builder()
+                .binding(binding)
+                .offset(0)
+                .format(Format::R32G32_SFLOAT)
+                .location(0)
+                .build(),
+            VertexInputAttributeDescription::builder()
+                .binding(binding)
+                .offset(u32::try_from(memoffset::offset_of!(SpriteVertex, uv)).unwrap())
+                .format(Format::R32G32_SFLOAT)
+                .location(1)
+                .build(),
+            VertexInputAttributeDescription::builder()
+                .binding(binding)
+                .offset(u32::try_from(memoffset::offset_of!(SpriteVertex, color)).unwrap())
+                .format(Format::R8G8B8A8_UNORM)
+                .location(2)
+                .build(),
+        ]
+    }
+}
+
+/// 画面のどこを基準にスプライトを置くか。<br />
+/// Where on the screen a sprite is positioned from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// 画面の大きさに対する基準点の割合。左上が(0, 0)、右下が(1, 1)。<br />
+    /// The anchor point as a fraction of the screen size. The top left is (0, 0) and the bottom right (1, 1).
+    pub fn get_factor(&self) -> Vec2 {
+        match self {
+            Anchor::TopLeft => Vec2::new(0.0, 0.0),
+            Anchor::Top => Vec2::new(0.5, 0.0),
+            Anchor::TopRight => Vec2::new(1.0, 0.0),
+            Anchor::Left => Vec2::new(0.0, 0.5),
+            Anchor::Center => Vec2::new(0.5, 0.5),
+            Anchor::Right => Vec2::new(1.0, 0.5),
+            Anchor::BottomLeft => Vec2::new(0.0, 1.0),
+            Anchor::Bottom => Vec2::new(0.5, 1.0),
+            Anchor::BottomRight => Vec2::new(1.0, 1.0),
+        }
+    }
+}
+
+/// アトラスの中の一つの画像の場所。<br />
+/// Where a single image is inside the atlas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 小さな画像を一枚のテクスチャに詰める。画像は高さごとの棚に左から順に並べる。<br />
+/// Packs small images into a single texture. Images are placed from the left on shelves by height.
+#[derive(Clone, Debug)]
+pub struct SpriteAtlas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    regions: HashMap<String, AtlasRegion>,
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_height: u32,
+}
+
+impl Default for SpriteAtlas {
+    fn default() -> Self {
+        Self::new(SPRITE_ATLAS_SIZE, SPRITE_ATLAS_SIZE)
+    }
+}
+
+impl SpriteAtlas {
+    /// 空のアトラスを作り、単色の四角形に使う白い領域を入れる。<br />
+    /// Create an empty atlas holding the white region used for solid rectangles.
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut atlas = SpriteAtlas {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+            regions: HashMap::new(),
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_height: 0,
+        };
+        // 縁の補間で色が変わらないよう、三ピクセル四方を塗って真ん中だけを使う。
+        // Fill three pixels square and only use the center, so filtering at the edges doesn't change the color.
+        if let Ok(region) = atlas.add(WHITE_SPRITE, 3, 3, &[255; 3 * 3 * 4]) {
+            let center = (region.uv_min + region.uv_max) * 0.5;
+            atlas.regions.insert(
+                WHITE_SPRITE.to_string(),
+                AtlasRegion {
+                    uv_min: center,
+                    uv_max: center,
+                    width: 1,
+                    height: 1,
+                },
+            );
+        }
+        atlas
+    }
+
+    /// RGBA8の画像を空いている場所に詰める。同じ名前の画像は置き換えずにエラーにする。<br />
+    /// Pack an RGBA8 image into free space. An image with the same name is an error instead of being replaced.
+    pub fn add(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> anyhow::Result<AtlasRegion> {
+        if self.regions.contains_key(name) {
+            return Err(anyhow::anyhow!(
+                "The atlas already has a sprite named {}.",
+                name
+            ));
+        }
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(anyhow::anyhow!(
+                "The sprite {} has {} bytes, but {}x{} RGBA8 pixels need {}.",
+                name,
+                pixels.len(),
+                width,
+                height,
+                width * height * 4
+            ));
+        }
+        let padded_width = width + SPRITE_ATLAS_PADDING * 2;
+        let padded_height = height + SPRITE_ATLAS_PADDING * 2;
+        if self.shelf_x + padded_width > self.width {
+            self.shelf_x = 0;
+            self.shelf_y += self.shelf_height;
+            self.shelf_height = 0;
+        }
+        if self.shelf_x + padded_width > self.width || self.shelf_y + padded_height > self.height {
+            return Err(anyhow::anyhow!(
+                "The atlas has no room left for the sprite {}.",
+                name
+            ));
+        }
+        let x = self.shelf_x + SPRITE_ATLAS_PADDING;
+        let y = self.shelf_y + SPRITE_ATLAS_PADDING;
+        self.write_pixels(x, y, width, height, pixels);
+        self.shelf_x += padded_width;
+        self.shelf_height = self.shelf_height.max(padded_height);
+
+        let atlas_size = Vec2::new(self.width as f32, self.height as f32);
+        let region = AtlasRegion {
+            uv_min: Vec2::new(x as f32, y as f32) / atlas_size,
+            uv_max: Vec2::new((x + width) as f32, (y + height) as f32) / atlas_size,
+            width,
+            height,
+        };
+        self.regions.insert(name.to_string(), region);
+        Ok(region)
+    }
+
+    /// 詰めてある画像のピクセルを同じ大きさの新しいピクセルで書き換える。<br />
+    /// Overwrite the pixels of a packed image with new pixels of the same size.
+    pub fn replace(&mut self, name: &str, pixels: &[u8]) -> anyhow::Result<AtlasRegion> {
+        let region = self
+            .get_region(name)
+            .ok_or_else(|| anyhow::anyhow!("The atlas has no sprite named {}.", name))?;
+        if pixels.len() != (region.width * region.height * 4) as usize {
+            return Err(anyhow::anyhow!(
+                "The sprite {} has {} bytes, but {}x{} RGBA8 pixels need {}.",
+                name,
+                pixels.len(),
+                region.width,
+                region.height,
+                region.width * region.height * 4
+            ));
+        }
+        let x = (region.uv_min.x * self.width as f32).round() as u32;
+        let y = (region.uv_min.y * self.height as f32).round() as u32;
+        self.write_pixels(x, y, region.width, region.height, pixels);
+        Ok(region)
+    }
+
+    /// 画像ファイルを読み込んで詰める。<br />
+    /// Load an image file and pack it.
+    pub fn add_file(&mut self, name: &str, file_name: &str) -> anyhow::Result<AtlasRegion> {
+        let image = image::open(file_name)?.to_rgba8();
+        let (width, height) = image.dimensions();
+        self.add(name, width, height, &image.into_raw())
+    }
+
+    fn write_pixels(&mut self, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) {
+        let row_size = (width * 4) as usize;
+        for row in 0..height {
+            let source = (row * width * 4) as usize;
+            let destination = (((y + row) * self.width + x) * 4) as usize;
+            self.pixels[destination..destination + row_size]
+                .copy_from_slice(&pixels[source..source + row_size]);
+        }
+    }
+
+    pub fn get_region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+
+    pub fn get_white_region(&self) -> AtlasRegion {
+        self.regions
+            .get(WHITE_SPRITE)
+            .copied()
+            .expect("Failed to get the white region of the atlas.")
+    }
+
+    pub fn get_pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+}
+
+/// 画面に描く一枚のスプライト。大きさとオフセットは基準の高さでのピクセル単位。<br />
+/// A sprite drawn on the screen. Its size and offset are in pixels at the reference height.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprite {
+    pub region: AtlasRegion,
+    pub anchor: Anchor,
+    pub offset: Vec2,
+    pub size: Vec2,
+    /// スプライトの大きさに対する、基準点に合わせる点の割合。既定では基準点と同じ。<br />
+    /// The point placed at the anchor, as a fraction of the sprite size. It matches the anchor by default.
+    pub pivot: Vec2,
+    pub color: [u8; 4],
+}
+
+impl Sprite {
+    /// 画像と同じ大きさで、画面の左上に置くスプライトを作る。<br />
+    /// Create a sprite the size of its image, placed at the top left of the screen.
+    pub fn new(region: AtlasRegion) -> Self {
+        Sprite {
+            region,
+            anchor: Anchor::TopLeft,
+            offset: Vec2::zero(),
+            size: Vec2::new(region.width as f32, region.height as f32),
+            pivot: Anchor::TopLeft.get_factor(),
+            color: [255, 255, 255, 255],
+        }
+    }
+
+    /// 基準点とそこからのオフセットを決める。中心も基準点に合わせる。<br />
+    /// Set the anchor and the offset from it. The pivot is matched to the anchor as well.
+    pub fn anchored(mut self, anchor: Anchor, offset: Vec2) -> Self {
+        self.anchor = anchor;
+        self.offset = offset;
+        self.pivot = anchor.get_factor();
+        self
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_pivot(mut self, pivot: Vec2) -> Self {
+        self.pivot = pivot;
+        self
+    }
+
+    pub fn with_color(mut self, color: [u8; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// 画面の大きさに合わせた、スプライトの左上と右下のピクセル座標。<br />
+    /// The top left and bottom right of the sprite in pixels, fitted to the screen size.
+    pub fn get_screen_rect(&self, screen_width: f32, screen_height: f32) -> (Vec2, Vec2) {
+        let scale = get_sprite_scale(screen_height);
+        let screen_size = Vec2::new(screen_width, screen_height);
+        let size = self.size * scale;
+        let top_left =
+            self.anchor.get_factor() * screen_size + self.offset * scale - self.pivot * size;
+        (top_left, top_left + size)
+    }
+}
+
+/// 基準の高さに対する画面の高さの比。<br />
+/// Ratio of the screen height to the reference height.
+pub fn get_sprite_scale(screen_height: f32) -> f32 {
+    if screen_height > 0.0 {
+        screen_height / SPRITE_REFERENCE_HEIGHT
+    } else {
+        1.0
+    }
+}
+
+/// 一フレームに描くスプライトを集める。描いた順に重ねる。<br />
+/// Collects the sprites drawn in a frame. They're layered in the order drawn.
+#[derive(Clone, Debug, Default)]
+pub struct SpriteBatch {
+    sprites: Vec<Sprite>,
+}
+
+impl SpriteBatch {
+    pub fn new() -> Self {
+        SpriteBatch { sprites: vec![] }
+    }
+
+    pub fn draw(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    /// 単色の四角形を描く。<br />
+    /// Draw a solid rectangle.
+    pub fn fill_rect(
+        &mut self,
+        atlas: &SpriteAtlas,
+        anchor: Anchor,
+        offset: Vec2,
+        size: Vec2,
+        color: [u8; 4],
+    ) {
+        self.draw(
+            Sprite::new(atlas.get_white_region())
+                .anchored(anchor, offset)
+                .with_size(size)
+                .with_color(color),
+        );
+    }
+
+    pub fn get_sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    pub fn take_sprites(&mut self) -> Vec<Sprite> {
+        std::mem::take(&mut self.sprites)
+    }
+}
+
+/// スプライトを一回の描画で描ける三角形のリストにする。一枚につき六つの頂点を使う。<br />
+/// Turn sprites into a triangle list drawn in a single draw. Each sprite uses six vertices.
+pub fn build_sprite_vertices(
+    sprites: &[Sprite],
+    screen_width: f32,
+    screen_height: f32,
+) -> Vec<SpriteVertex> {
+    if screen_width <= 0.0 || screen_height <= 0.0 {
+        return vec![];
+    }
+    let screen_size = Vec2::new(screen_width, screen_height);
+    let mut vertices = Vec::with_capacity(sprites.len() * 6);
+    for sprite in sprites.iter() {
+        let (top_left, bottom_right) = sprite.get_screen_rect(screen_width, screen_height);
+        let min = top_left / screen_size * 2.0 - Vec2::one();
+        let max = bottom_right / screen_size * 2.0 - Vec2::one();
+        let (uv_min, uv_max) = (sprite.region.uv_min, sprite.region.uv_max);
+        let vertex = |x: f32, y: f32, u: f32, v: f32| SpriteVertex {
+            position: [x, y],
+            uv: [u, v],
+            color: sprite.color,
+        };
+        let top_left = vertex(min.x, min.y, uv_min.x, uv_min.y);
+        let top_right = vertex(max.x, min.y, uv_max.x, uv_min.y);
+        let bottom_left = vertex(min.x, max.y, uv_min.x, uv_max.y);
+        let bottom_right = vertex(max.x, max.y, uv_max.x, uv_max.y);
+        vertices.extend_from_slice(&[
+            top_left,
+            bottom_left,
+            top_right,
+            top_right,
+            bottom_left,
+            bottom_right,
+        ]);
+    }
+    vertices
+}
@@ -0,0 +1,170 @@
+use glam::{Mat4, Vec2, Vec3A};
+
+use crate::game::shared::structs::{
+    get_sprite_scale, project_point, Anchor, Sprite, SpriteAtlas, SpriteBatch,
+};
+
+/// プレイヤーの位置から体力バーまでの高さ。名札のすぐ下に来る。<br />
+/// Height of status bars above the position of a player. They sit just below the nameplate.
+pub const STATUS_BAR_HEIGHT: f32 = 1.9;
+
+/// この距離から頭上の表示が薄くなり始める。<br />
+/// Overhead displays start fading out from this distance.
+pub const BILLBOARD_FADE_START: f32 = 25.0;
+
+/// この距離より遠い頭上の表示は描かない。<br />
+/// Overhead displays farther than this distance aren't drawn.
+pub const BILLBOARD_MAX_DISTANCE: f32 = 45.0;
+
+/// 遮るものが表示する点のすぐ手前にある時は隠さない。地面に立つ足元の誤差を吸収する。<br />
+/// Blockers right in front of the displayed point don't hide it, absorbing errors such as feet standing on the ground.
+const OCCLUSION_TOLERANCE: f32 = 0.5;
+
+/// 基準の高さでのバーの大きさ。<br />
+/// Sizes of the bars, at the reference height.
+const BAR_WIDTH: f32 = 56.0;
+const HP_BAR_HEIGHT: f32 = 5.0;
+const SP_BAR_HEIGHT: f32 = 3.0;
+const BAR_BORDER: f32 = 1.0;
+
+const BACKGROUND_COLOR: [u8; 4] = [16, 16, 16, 190];
+const HP_COLOR: [u8; 4] = [220, 56, 48, 255];
+const SP_COLOR: [u8; 4] = [64, 128, 232, 255];
+
+/// 頭上にバーを出すキャラクターの状態。<br />
+/// State of a character that has bars shown above it.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct StatusTarget {
+    /// バーを置くワールドの位置。<br />
+    /// World position the bars are placed at.
+    pub position: Vec3A,
+    pub current_hp: i32,
+    pub max_hp: i32,
+    /// SPを持たないキャラクターは`max_sp`が0で、SPのバーを出さない。<br />
+    /// Characters without SP have a `max_sp` of zero and get no SP bar.
+    pub current_sp: i32,
+    pub max_sp: i32,
+}
+
+/// 画面に投影した体力とSPのバー。<br />
+/// HP and SP bars projected onto the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StatusBar {
+    /// バーの下端の中央のピクセル座標。<br />
+    /// Pixel coordinates of the bottom center of the bars.
+    pub screen_position: Vec2,
+    pub hp_fill: f32,
+    pub sp_fill: Option<f32>,
+    pub alpha: f32,
+}
+
+impl StatusBar {
+    /// キャラクターの状態を画面に投影する。カメラの後ろや画面の外、遠すぎるものは`None`。<br />
+    /// Project the state of a character onto the screen. `None` if it's behind the camera, off screen or too far.
+    pub fn project(
+        target: &StatusTarget,
+        view_projection: Mat4,
+        eye: Vec3A,
+        width: f32,
+        height: f32,
+    ) -> Option<Self> {
+        let alpha = get_distance_alpha((target.position - eye).length());
+        if alpha <= 0.0 {
+            return None;
+        }
+        let screen_position = project_point(view_projection, target.position, width, height)?;
+        if screen_position.x < 0.0
+            || screen_position.y < 0.0
+            || screen_position.x > width
+            || screen_position.y > height
+        {
+            return None;
+        }
+        Some(StatusBar {
+            screen_position,
+            hp_fill: get_fill(target.current_hp, target.max_hp),
+            sp_fill: if target.max_sp > 0 {
+                Some(get_fill(target.current_sp, target.max_sp))
+            } else {
+                None
+            },
+            alpha,
+        })
+    }
+
+    /// 枠と中身のバーをスプライトとして加える。<br />
+    /// Add the frame and the filled bars as sprites.
+    pub fn draw(&self, batch: &mut SpriteBatch, atlas: &SpriteAtlas, screen_height: f32) {
+        let bars = match self.sp_fill {
+            Some(sp_fill) => vec![
+                (self.hp_fill, HP_BAR_HEIGHT, HP_COLOR),
+                (sp_fill, SP_BAR_HEIGHT, SP_COLOR),
+            ],
+            None => vec![(self.hp_fill, HP_BAR_HEIGHT, HP_COLOR)],
+        };
+        let inner_height =
+            bars.iter().map(|(_, h, _)| *h).sum::<f32>() + BAR_BORDER * (bars.len() - 1) as f32;
+        let frame_size = Vec2::new(BAR_WIDTH, inner_height) + Vec2::splat(BAR_BORDER * 2.0);
+        // 投影した位置はピクセル単位なので、基準の高さでの座標に戻してから置く。
+        // The projected position is in pixels, so it's converted back to the reference height before placing.
+        let bottom_center = self.screen_position / get_sprite_scale(screen_height);
+        let top_left = bottom_center - Vec2::new(frame_size.x * 0.5, frame_size.y);
+        let white = atlas.get_white_region();
+        let mut fill_rect = |offset: Vec2, size: Vec2, color: [u8; 4]| {
+            batch.draw(
+                Sprite::new(white)
+                    .anchored(Anchor::TopLeft, offset)
+                    .with_size(size)
+                    .with_color(fade_color(color, self.alpha)),
+            );
+        };
+        fill_rect(top_left, frame_size, BACKGROUND_COLOR);
+        let mut y = top_left.y + BAR_BORDER;
+        for (fill, bar_height, color) in bars.into_iter() {
+            if fill > 0.0 {
+                fill_rect(
+                    Vec2::new(top_left.x + BAR_BORDER, y),
+                    Vec2::new(BAR_WIDTH * fill, bar_height),
+                    color,
+                );
+            }
+            y += bar_height + BAR_BORDER;
+        }
+    }
+}
+
+/// 最大値に対する今の値の割合。最大値がなければ0。<br />
+/// The current value as a fraction of the maximum. Zero without a maximum.
+pub fn get_fill(current: i32, max: i32) -> f32 {
+    if max <= 0 {
+        return 0.0;
+    }
+    (current as f32 / max as f32).max(0.0).min(1.0)
+}
+
+/// カメラからの距離に応じた頭上の表示の不透明度。<br />
+/// Opacity of overhead displays by their distance from the camera.
+pub fn get_distance_alpha(distance: f32) -> f32 {
+    if distance >= BILLBOARD_MAX_DISTANCE {
+        0.0
+    } else if distance <= BILLBOARD_FADE_START {
+        1.0
+    } else {
+        1.0 - (distance - BILLBOARD_FADE_START) / (BILLBOARD_MAX_DISTANCE - BILLBOARD_FADE_START)
+    }
+}
+
+/// 色の不透明度に掛ける。<br />
+/// Multiply the opacity of a color.
+pub fn fade_color(color: [u8; 4], alpha: f32) -> [u8; 4] {
+    let alpha = (color[3] as f32 * alpha.max(0.0).min(1.0)).round() as u8;
+    [color[0], color[1], color[2], alpha]
+}
+
+/// 光線が何かに当たった距離から、その先の点が隠れているかを決める。<br />
+/// Decide whether a point is hidden from the distance at which a ray towards it hit something.
+pub fn is_occluded(hit_distance: Option<f32>, target_distance: f32) -> bool {
+    hit_distance
+        .map(|distance| distance < target_distance - OCCLUSION_TOLERANCE)
+        .unwrap_or(false)
+}
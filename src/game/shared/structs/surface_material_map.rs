@@ -0,0 +1,179 @@
+use crate::game::shared::components::Bounds;
+use crate::game::shared::enums::SurfaceMaterial;
+use glam::Vec3A;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 足が地面に着いた時にアニメーションが起こすイベントの名前。<br />
+/// Name of the event animations fire when a foot touches the ground.
+pub const FOOTSTEP_EVENT: &str = "footstep";
+
+/// 傾きを求める時に周りの高さを調べる距離（メートル）。<br />
+/// Distance to the neighbouring heights sampled to find the slope, in meters.
+const SLOPE_SAMPLE_DISTANCE: f32 = 0.5;
+
+/// 足元からこの距離までの小物の上面は踏んでいるとみなす（メートル）。<br />
+/// Tops of props within this distance of the feet count as being stepped on, in meters.
+const STEP_TOLERANCE: f32 = 0.25;
+
+/// 地形の層。高さと傾き（度）の範囲で材質を決める。範囲を省いた側は制限しない。<br />
+/// A terrain layer, deciding the material by ranges of height and slope in degrees. Omitted bounds are unlimited.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TerrainLayer {
+    pub material: SurfaceMaterial,
+    #[serde(default)]
+    pub min_height: Option<f32>,
+    #[serde(default)]
+    pub max_height: Option<f32>,
+    #[serde(default)]
+    pub min_slope: Option<f32>,
+    #[serde(default)]
+    pub max_slope: Option<f32>,
+}
+
+impl TerrainLayer {
+    pub fn contains(&self, height: f32, slope: f32) -> bool {
+        let is_within = |value: f32, min: Option<f32>, max: Option<f32>| {
+            min.map(|min| value >= min).unwrap_or(true)
+                && max.map(|max| value < max).unwrap_or(true)
+        };
+        is_within(height, self.min_height, self.max_height)
+            && is_within(slope, self.min_slope, self.max_slope)
+    }
+}
+
+/// 足元に出す土煙。<br />
+/// A dust puff spawned at the feet.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DustPuff {
+    pub color: [f32; 4],
+    /// 粒の数。<br />
+    /// Number of particles.
+    pub count: u32,
+    /// 粒が広がる速さ（メートル毎秒）。<br />
+    /// Speed the particles spread at, in meters per second.
+    pub speed: f32,
+    /// 粒が消えるまでの時間（秒）。<br />
+    /// Time until the particles fade out, in seconds.
+    pub lifetime: f32,
+}
+
+/// 材質ごとの足音と土煙。足音は順番に入れ替えて単調にならないようにする。<br />
+/// Footstep sounds and dust puff of a material. Sounds are played in turn so steps don't sound monotonous.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FootstepSet {
+    /// 効果音のファイル。読み込んだ効果音の名前にもなる。<br />
+    /// Files of the sound effects, also used as the names of the loaded sounds.
+    pub sounds: Vec<String>,
+    pub puff: DustPuff,
+}
+
+/// データファイルから読み込む材質の対応表。地形の層と、材質ごとの足音を定義する。<br />
+/// Surface-material map loaded from a data file, defining terrain layers and the footsteps of each material.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SurfaceMaterialMap {
+    /// どの層にも当てはまらない地形の材質。<br />
+    /// Material of terrain no layer applies to.
+    #[serde(default)]
+    pub default_material: SurfaceMaterial,
+    /// 上から順に調べ、最初に当てはまった層の材質を使う。<br />
+    /// Checked from the top; the material of the first layer that applies is used.
+    #[serde(default)]
+    pub terrain_layers: Vec<TerrainLayer>,
+    pub footsteps: HashMap<SurfaceMaterial, FootstepSet>,
+}
+
+impl SurfaceMaterialMap {
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(file_name)?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let map: SurfaceMaterialMap = serde_json::from_str(json)?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// 既定の材質に足音があり、全ての足音に効果音が一つ以上あることを確かめる。<br />
+    /// Check that the default material has footsteps, and that every footstep set has at least one sound.
+    fn validate(&self) -> anyhow::Result<()> {
+        if !self.footsteps.contains_key(&self.default_material) {
+            return Err(anyhow::anyhow!(
+                "Default surface material {:?} has no footsteps.",
+                self.default_material
+            ));
+        }
+        for (material, footstep_set) in self.footsteps.iter() {
+            if footstep_set.sounds.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Footsteps of surface material {:?} have no sounds.",
+                    material
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 高さと傾き（度）から地形の材質を決める。<br />
+    /// Decide the material of terrain from its height and slope in degrees.
+    pub fn get_terrain_material(&self, height: f32, slope: f32) -> SurfaceMaterial {
+        self.terrain_layers
+            .iter()
+            .find(|layer| layer.contains(height, slope))
+            .map(|layer| layer.material)
+            .unwrap_or(self.default_material)
+    }
+
+    /// 指定した位置の地形の材質を求める。傾きは周りの高さから求める。地形がなければ`None`。<br />
+    /// Find the material of terrain at the given position, with the slope taken from the neighbouring heights. Returns `None` if there is no terrain.
+    pub fn get_ground_material<F>(&self, x: f32, z: f32, get_height: F) -> Option<SurfaceMaterial>
+    where
+        F: Fn(f32, f32) -> Option<f32>,
+    {
+        let height = get_height(x, z)?;
+        let sample = |x: f32, z: f32| get_height(x, z).unwrap_or(height);
+        let dx = sample(x + SLOPE_SAMPLE_DISTANCE, z) - sample(x - SLOPE_SAMPLE_DISTANCE, z);
+        let dz = sample(x, z + SLOPE_SAMPLE_DISTANCE) - sample(x, z - SLOPE_SAMPLE_DISTANCE);
+        let gradient = (dx * dx + dz * dz).sqrt() / (SLOPE_SAMPLE_DISTANCE * 2.0);
+        Some(self.get_terrain_material(height, gradient.atan().to_degrees()))
+    }
+
+    /// 材質の足音を取得する。定義がなければ既定の材質のものを使う。<br />
+    /// Get the footsteps of a material, falling back to the ones of the default material.
+    pub fn get_footstep_set(&self, material: SurfaceMaterial) -> Option<&FootstepSet> {
+        self.footsteps
+            .get(&material)
+            .or_else(|| self.footsteps.get(&self.default_material))
+    }
+
+    pub fn get_sound_files(&self) -> impl Iterator<Item = &str> {
+        self.footsteps
+            .values()
+            .flat_map(|footstep_set| footstep_set.sounds.iter().map(|s| s.as_str()))
+    }
+}
+
+/// 足元にある小物のうち、上面が最も高いものの材質を求める。小物はワールド空間の境界ボックスで渡す。<br />
+/// Find the material of the prop under the feet with the highest top. Props are given by their bounding boxes in world space.
+pub fn find_prop_material<I>(props: I, position: Vec3A) -> Option<SurfaceMaterial>
+where
+    I: IntoIterator<Item = (Bounds, SurfaceMaterial)>,
+{
+    props
+        .into_iter()
+        .filter(|(bounds, _)| {
+            position.x >= bounds.min.x
+                && position.x <= bounds.max.x
+                && position.z >= bounds.min.z
+                && position.z <= bounds.max.z
+                && (bounds.max.y - position.y).abs() <= STEP_TOLERANCE
+        })
+        .max_by(|a, b| {
+            a.0.max
+                .y
+                .partial_cmp(&b.0.max.y)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(_, material)| material)
+}
@@ -1,25 +1,27 @@
-use crate::game::graphics::vk::{Buffer, Graphics, Image, Pipeline, ThreadPool};
+pub mod terrain_manager;
+
+pub use terrain_manager::{get_grid_vertex_count, TerrainManager};
+
+use crate::game::graphics::vk::{Buffer, Graphics, Image, MeshBuffers, MeshPool, RenderContext};
+use crate::game::shared::components::Bounds;
 use crate::game::shared::enums::ShaderType;
+use crate::game::shared::structs::games::terrain_payload::generate_grid_indices;
 use crate::game::shared::structs::{
-    Mesh, Model, ModelMetaData, PositionInfo, Primitive, PushConstant, Vertex,
+    FrameInfo, LoadTask, Mesh, Model, ModelMetaData, PositionInfo, Primitive, RecordedCommands,
+    Vertex,
 };
 use crate::game::shared::traits::{Disposable, GraphicsBase, Renderable};
 use crate::game::shared::util::get_random_string;
 use crate::game::shared::util::height_generator::HeightGenerator;
-use crate::game::CommandData;
-use ash::vk::{
-    CommandBuffer, CommandBufferInheritanceInfo, DescriptorSet, Rect2D, SamplerAddressMode,
-    Viewport,
-};
-use ash::Device;
-use crossbeam::channel::*;
+use crate::game::{CommandData, ReleasedCommandBuffers};
+use ash::vk::{CommandBuffer, SamplerAddressMode};
 use crossbeam::sync::ShardedLock;
 use glam::{Mat4, Vec2, Vec3A, Vec4};
 use parking_lot::{Mutex, RwLock};
 use slotmap::DefaultKey;
 use std::collections::HashMap;
 use std::mem::ManuallyDrop;
-use std::sync::atomic::{AtomicPtr, AtomicUsize};
+use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, Weak};
 
 /// パーリンノイズで乱数で生成する地形のモデル<br />
@@ -110,8 +112,6 @@ where
             let count = vertex_count * vertex_count;
             let mut vertices: Vec<Vertex> = vec![];
             vertices.reserve(count as usize);
-            let indices_count = 6 * (vertex_count - 1) * (vertex_count - 1);
-            let mut indices: Vec<u32> = vec![0; indices_count as usize];
             let generator = height_generator
                 .read()
                 .expect("Failed to lock height generator.");
@@ -147,44 +147,24 @@ where
                 vertex.position.y -= diff;
             }
 
-            let mut pointer = 0;
-            for gz in 0..vertex_count - 1 {
-                for gx in 0..vertex_count - 1 {
-                    let top_left = (gz * vertex_count) + gx;
-                    let top_right = top_left + 1;
-                    let bottom_left = ((gz + 1) * vertex_count) + gx;
-                    let bottom_right = bottom_left + 1;
-
-                    indices[pointer] = top_left;
-                    pointer += 1;
-                    indices[pointer] = bottom_left;
-                    pointer += 1;
-                    indices[pointer] = top_right;
-                    pointer += 1;
-                    indices[pointer] = top_right;
-                    pointer += 1;
-                    indices[pointer] = bottom_left;
-                    pointer += 1;
-                    indices[pointer] = bottom_right;
-                    pointer += 1;
-                }
-            }
-
             Primitive {
                 vertices,
-                indices,
+                indices: generate_grid_indices(vertex_count),
                 texture_index: Some(texture_index),
                 is_disposed: false,
             }
         };
 
+        let primitives = vec![primitive];
         let mesh = Mesh {
-            primitives: vec![primitive],
+            bounds: Bounds::from_primitives(&primitives),
+            primitives,
             vertex_buffer: None,
             index_buffer: None,
             texture: vec![texture],
             is_disposed: false,
             command_data,
+            recorded_commands: RecordedCommands::new(),
             shader_type: ShaderType::Terrain,
             model_index,
         };
@@ -237,18 +217,17 @@ impl Terrain<Graphics, Buffer, CommandBuffer, Image> {
         vertex_count_ratio: f32,
         primitive: Option<Primitive>,
         entity: DefaultKey,
-    ) -> anyhow::Result<Receiver<Self>> {
+        terrain_manager: Arc<TerrainManager<Buffer>>,
+    ) -> anyhow::Result<LoadTask<Self>> {
         log::info!("Generating terrain...Model index: {}", model_index);
         let graphics_arc = graphics
             .upgrade()
             .expect("Failed to upgrade graphics handle.");
-        let (terrain_send, terrain_recv) = bounded(5);
-        rayon::spawn(move || {
+        let (terrain_send, terrain_recv) = LoadTask::channel();
+        let thread_pool = graphics_arc.read().thread_pool.clone();
+        thread_pool.spawn_streaming(move || {
             let graphics_arc = graphics_arc;
-            let inflight_frame_count = std::env::var("INFLIGHT_BUFFER_COUNT")
-                .unwrap()
-                .parse::<usize>()
-                .unwrap();
+            let inflight_frame_count = graphics_arc.read().inflight_buffer_count;
             let mut command_data = HashMap::new();
             for i in 0..inflight_frame_count {
                 let (command_pool, command_buffer) =
@@ -270,6 +249,7 @@ impl Terrain<Graphics, Buffer, CommandBuffer, Image> {
                     .map(|(pool, _)| pool.clone().unwrap())
                     .unwrap(),
                 SamplerAddressMode::REPEAT,
+                true,
             )
             .expect("Failed to create image from file.");
             log::info!("Terrain texture successfully created.");
@@ -292,11 +272,9 @@ impl Terrain<Graphics, Buffer, CommandBuffer, Image> {
                 generated_terrain.get_world_matrix();
             log::info!("Terrain successfully generated.");
             generated_terrain
-                .create_buffers(graphics_arc)
+                .create_buffers(graphics_arc, &terrain_manager)
                 .expect("Failed to create buffer for terrain.");
-            terrain_send
-                .send(generated_terrain)
-                .expect("Failed to send terrain.");
+            terrain_send.complete(generated_terrain);
         });
         Ok(terrain_recv)
     }
@@ -306,19 +284,39 @@ impl Terrain<Graphics, Buffer, CommandBuffer, Image> {
     fn create_buffers(
         &mut self,
         graphics: Arc<RwLock<ManuallyDrop<Graphics>>>,
+        terrain_manager: &TerrainManager<Buffer>,
     ) -> anyhow::Result<()> {
         let mut mesh = self.model.meshes[0].lock();
-        let vertices = mesh.primitives[0].vertices.to_vec();
-        let indices = mesh.primitives[0].indices.to_vec();
         let command_pool = mesh
             .command_data
             .get(&0)
             .map(|(pool, _)| pool.clone().unwrap())
             .unwrap();
-        let (vertex_buffer, index_buffer) =
-            Graphics::create_vertex_and_index_buffer(graphics, vertices, indices, command_pool)?;
-        mesh.vertex_buffer = Some(ManuallyDrop::new(vertex_buffer));
-        mesh.index_buffer = Some(ManuallyDrop::new(index_buffer));
+        let primitive = &mesh.primitives[0];
+        let buffers = if let Some(vertex_count) = get_grid_vertex_count(primitive) {
+            // 格子のチャンクは同じ解像度のインデックスバッファを共有し、頂点はマップ済みの領域に直接書く。
+            // Grid chunks share the index buffer of their resolution and write vertices straight into the mapped region.
+            let index_buffer =
+                terrain_manager.get_index_buffer(&graphics, vertex_count, &command_pool)?;
+            let vertex_buffer = terrain_manager.upload_vertices(
+                &graphics,
+                primitive.vertices.len(),
+                &command_pool,
+                |staging| staging.copy_from_slice(&primitive.vertices),
+            )?;
+            MeshBuffers::Dedicated(vertex_buffer, index_buffer)
+        } else {
+            let vertices = primitive.vertices.to_vec();
+            let indices = primitive.indices.to_vec();
+            Graphics::create_mesh_buffers(
+                graphics,
+                vertices,
+                indices,
+                command_pool,
+                MeshPool::Static,
+            )?
+        };
+        mesh.set_buffers(buffers);
         Ok(())
     }
 }
@@ -366,35 +364,18 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
 {
     fn update(&mut self, _delta_time: f64) {}
 
-    fn render(
-        &self,
-        inheritance_info: Arc<AtomicPtr<CommandBufferInheritanceInfo>>,
-        push_constant: PushConstant,
-        viewport: Viewport,
-        scissor: Rect2D,
-        device: Arc<Device>,
-        pipeline: Arc<ShardedLock<ManuallyDrop<Pipeline>>>,
-        descriptor_set: DescriptorSet,
-        thread_pool: Arc<ThreadPool>,
-        frame_index: usize,
-    ) {
-        self.model.render(
-            inheritance_info,
-            push_constant,
-            viewport,
-            scissor,
-            device,
-            pipeline,
-            descriptor_set,
-            thread_pool,
-            frame_index,
-        );
+    fn render(&self, context: &RenderContext, frame_info: FrameInfo) {
+        self.model.render(context, frame_info);
     }
 
     fn get_ssbo_index(&self) -> usize {
         self.model.ssbo_index
     }
 
+    fn get_texture_usages(&self) -> Vec<(usize, Option<Bounds>)> {
+        self.model.get_texture_usages()
+    }
+
     fn get_model_metadata(&self) -> ModelMetaData {
         self.model.model_metadata
     }
@@ -407,6 +388,27 @@ impl Renderable<Graphics, Buffer, CommandBuffer, Image>
         self.model.get_command_buffers(frame_index)
     }
 
+    fn append_command_buffers(&self, frame_index: usize, command_buffers: &mut Vec<CommandBuffer>) {
+        self.model
+            .append_command_buffers(frame_index, command_buffers);
+    }
+
+    fn take_command_buffers(&mut self) -> ReleasedCommandBuffers<CommandBuffer> {
+        self.model.take_command_buffers()
+    }
+
+    fn invalidate_command_buffers(&mut self) {
+        self.model.invalidate_command_buffers();
+    }
+
+    fn get_entity(&self) -> DefaultKey {
+        self.model.entity
+    }
+
+    fn set_entity(&mut self, entity: DefaultKey) {
+        self.model.entity = entity;
+    }
+
     fn set_position_info(&mut self, position_info: PositionInfo) {
         self.model.position_info = position_info;
     }
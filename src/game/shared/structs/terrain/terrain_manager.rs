@@ -0,0 +1,179 @@
+use crate::game::graphics::vk::{Buffer, BufferUpload, Graphics};
+use crate::game::shared::structs::games::terrain_payload::generate_grid_indices;
+use crate::game::shared::structs::{Primitive, Vertex};
+use crate::game::shared::traits::Disposable;
+use ash::vk::{
+    AccessFlags, BufferUsageFlags, CommandPool, DeviceSize, MemoryPropertyFlags, PipelineStageFlags,
+};
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+
+/// 地形のチャンクが共有するGPUのリソースを管理する。<br />
+/// 同じ解像度のチャンクはインデックスが全く同じなので、インデックスバッファは一辺の頂点数（LOD）ごとに一つだけ作る。<br />
+/// Manages the GPU resources shared by terrain chunks.<br />
+/// Chunks of the same resolution have identical indices, so only one index buffer is created per vertex count (LOD level).
+pub struct TerrainManager<BufferType>
+where
+    BufferType: 'static + Disposable + Clone,
+{
+    index_buffers: Mutex<HashMap<u32, BufferType>>,
+}
+
+impl<BufferType> TerrainManager<BufferType>
+where
+    BufferType: 'static + Disposable + Clone,
+{
+    pub fn new() -> Self {
+        TerrainManager {
+            index_buffers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get_index_buffer_count(&self) -> usize {
+        self.index_buffers.lock().len()
+    }
+
+    /// 共有しているバッファを全て解放する。チャンクのメッシュを解放した後に呼ぶこと。<br />
+    /// Dispose all shared buffers. Call this after the meshes of the chunks are disposed.
+    pub fn clear(&self) {
+        for (_, mut buffer) in self.index_buffers.lock().drain() {
+            buffer.dispose();
+        }
+    }
+}
+
+/// 格子状のチャンクなら一辺の頂点数を返す。共有のインデックスバッファと同じ並びのインデックスを持つ場合だけ。<br />
+/// Return the vertex count per side of a grid chunk, only if its indices are laid out like the shared index buffers.
+pub fn get_grid_vertex_count(primitive: &Primitive) -> Option<u32> {
+    let vertex_count = (primitive.vertices.len() as f64).sqrt() as u32;
+    let cell_count = vertex_count.saturating_sub(1) as usize;
+    let is_grid = vertex_count >= 2
+        && (vertex_count * vertex_count) as usize == primitive.vertices.len()
+        && primitive.indices.len() == 6 * cell_count * cell_count;
+    if is_grid {
+        Some(vertex_count)
+    } else {
+        None
+    }
+}
+
+impl<BufferType> Default for TerrainManager<BufferType>
+where
+    BufferType: 'static + Disposable + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerrainManager<Buffer> {
+    /// 一辺の頂点数に合ったインデックスバッファを返す。まだ無ければ作ってアップロードする。<br />
+    /// 返すハンドルはバッファを所有しないので、メッシュを解放しても共有しているバッファは残る。<br />
+    /// Return the index buffer for the vertex count per side, creating and uploading it if it doesn't exist yet.<br />
+    /// The returned handle doesn't own the buffer, so disposing a mesh leaves the shared buffer alive.
+    pub fn get_index_buffer(
+        &self,
+        graphics: &Arc<RwLock<ManuallyDrop<Graphics>>>,
+        vertex_count: u32,
+        command_pool: &Mutex<CommandPool>,
+    ) -> anyhow::Result<Buffer> {
+        let mut index_buffers = self.index_buffers.lock();
+        if let Some(buffer) = index_buffers.get(&vertex_count) {
+            return Ok(Self::share(buffer));
+        }
+
+        let indices = generate_grid_indices(vertex_count);
+        let buffer_size = DeviceSize::try_from(std::mem::size_of::<u32>() * indices.len())?;
+        let graphics_lock = graphics.read();
+        let device = Arc::downgrade(&graphics_lock.logical_device);
+        let allocator = Arc::downgrade(&graphics_lock.allocator);
+        let transfer_queue = graphics_lock.get_transfer_queue();
+        let staging_ring = graphics_lock.get_staging_ring();
+        drop(graphics_lock);
+
+        let staging = staging_ring.write(&indices)?;
+        let index_buffer = Buffer::new(
+            device,
+            buffer_size,
+            BufferUsageFlags::INDEX_BUFFER | BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            allocator,
+        )?;
+        transfer_queue.upload_buffers(
+            command_pool,
+            &[BufferUpload {
+                source: &staging,
+                destination: &index_buffer,
+                dst_offset: 0,
+                size: buffer_size,
+                dst_access: AccessFlags::INDEX_READ,
+                dst_stage: PipelineStageFlags::VERTEX_INPUT,
+            }],
+        )?;
+        log::info!(
+            "Created shared terrain index buffer for {} vertices per side.",
+            vertex_count
+        );
+        let shared = Self::share(&index_buffer);
+        index_buffers.insert(vertex_count, index_buffer);
+        Ok(shared)
+    }
+
+    /// 常にマップしているステージングリングの領域に`write`で頂点を直接書き込み、頂点バッファにアップロードする。<br />
+    /// Let `write` fill the vertices directly into a region of the persistently mapped staging ring, then upload them into a vertex buffer.
+    pub fn upload_vertices<F>(
+        &self,
+        graphics: &Arc<RwLock<ManuallyDrop<Graphics>>>,
+        vertex_count: usize,
+        command_pool: &Mutex<CommandPool>,
+        write: F,
+    ) -> anyhow::Result<Buffer>
+    where
+        F: FnOnce(&mut [Vertex]),
+    {
+        let buffer_size = DeviceSize::try_from(std::mem::size_of::<Vertex>() * vertex_count)?;
+        let graphics_lock = graphics.read();
+        let device = Arc::downgrade(&graphics_lock.logical_device);
+        let allocator = Arc::downgrade(&graphics_lock.allocator);
+        let transfer_queue = graphics_lock.get_transfer_queue();
+        let staging_ring = graphics_lock.get_staging_ring();
+        drop(graphics_lock);
+
+        let staging = staging_ring.allocate(buffer_size)?;
+        unsafe {
+            let mapped = staging.get_mapped() as *mut Vertex;
+            write(std::slice::from_raw_parts_mut(mapped, vertex_count));
+        }
+
+        let vertex_buffer = Buffer::new(
+            device,
+            buffer_size,
+            BufferUsageFlags::VERTEX_BUFFER | BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            allocator,
+        )?;
+        transfer_queue.upload_buffers(
+            command_pool,
+            &[BufferUpload {
+                source: &staging,
+                destination: &vertex_buffer,
+                dst_offset: 0,
+                size: buffer_size,
+                dst_access: AccessFlags::VERTEX_ATTRIBUTE_READ,
+                dst_stage: PipelineStageFlags::VERTEX_INPUT,
+            }],
+        )?;
+        Ok(vertex_buffer)
+    }
+
+    /// バッファを所有しないハンドルを作る。解放済みとして扱うので、ドロップしても実体は消えない。<br />
+    /// Create a handle that doesn't own the buffer. It's treated as already disposed, so dropping it leaves the buffer intact.
+    fn share(buffer: &Buffer) -> Buffer {
+        let mut shared = buffer.clone();
+        shared.is_disposed = true;
+        shared
+    }
+}
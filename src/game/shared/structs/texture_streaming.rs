@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+/// 読み込んですぐに置くミップの一辺の最大の大きさ。高いミップは後から読み込む。<br />
+/// Largest side of the mips placed right after loading. Higher mips are streamed in later.
+pub const STREAMING_LOW_RES_SIZE: u32 = 64;
+
+/// デバイスローカルのメモリーがこの割合まで予算を使えば、高いミップを捨てる。<br />
+/// High mips are evicted once device local memory uses this ratio of its budget.
+pub const STREAMING_EVICTION_PRESSURE: f64 = 0.85;
+
+/// 使用量がこの割合を下回るまで、高いミップを読み込み直さない。捨てては読み込むのを繰り返さないための幅。<br />
+/// High mips aren't streamed in again until the usage drops below this ratio, a margin so evicting and streaming don't alternate.
+pub const STREAMING_RESUME_PRESSURE: f64 = 0.75;
+
+/// 同時に読み込んだり捨てたりするテクスチャの数。<br />
+/// Number of textures being streamed in or evicted at the same time.
+pub const MAX_STREAMING_JOBS: usize = 4;
+
+/// 一辺の長さから、`create_image_from_raw`が作るのと同じ数のミップを返す。<br />
+/// The number of mips `create_image_from_raw` creates for the given sides.
+pub fn get_mip_count(width: u32, height: u32) -> u32 {
+    (width.max(height) as f32).log2().floor().max(1.0) as u32
+}
+
+pub fn get_mip_extent(width: u32, height: u32, mip: u32) -> (u32, u32) {
+    ((width >> mip).max(1), (height >> mip).max(1))
+}
+
+/// 一辺が`STREAMING_LOW_RES_SIZE`以下になる最初のミップ。<br />
+/// The first mip whose sides are `STREAMING_LOW_RES_SIZE` or less.
+pub fn get_low_res_mip(width: u32, height: u32, mip_count: u32) -> u32 {
+    let mut mip = 0;
+    while mip + 1 < mip_count && width.max(height) >> mip > STREAMING_LOW_RES_SIZE {
+        mip += 1;
+    }
+    mip
+}
+
+/// 境界球が画面の縦に占めるピクセル数。カメラが球の中にあれば画面全体を占めると見なす。<br />
+/// Number of pixels the bounding sphere covers along the screen height. With the camera inside the sphere it covers the whole screen.
+pub fn get_screen_coverage(
+    radius: f32,
+    distance: f32,
+    field_of_view: f32,
+    viewport_height: f32,
+) -> f32 {
+    if distance <= radius {
+        return viewport_height;
+    }
+    let ratio = radius / (distance * (field_of_view * 0.5).tan());
+    (ratio * viewport_height).min(viewport_height)
+}
+
+/// 画面で占めるピクセル数に足りる、一番低い解像度のミップ。<br />
+/// The lowest resolution mip that is enough for the pixels covered on screen.
+pub fn get_desired_mip(width: u32, height: u32, mip_count: u32, coverage: f32) -> u32 {
+    let lowest = mip_count.saturating_sub(1);
+    if coverage <= 0.0 {
+        return lowest;
+    }
+    let ratio = width.max(height) as f32 / coverage;
+    if ratio <= 1.0 {
+        return 0;
+    }
+    (ratio.log2().floor() as u32).min(lowest)
+}
+
+/// ストリーミングのために変えるテクスチャの解像度。<br />
+/// A change in the resolution of a texture made by streaming.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ResidencyChange {
+    /// 高いミップを読み込み、`mip`から置く。<br />
+    /// Stream in higher mips, keeping them from `mip`.
+    Stream { texture_index: usize, mip: u32 },
+    /// 高いミップを捨て、`mip`から置く。<br />
+    /// Evict high mips, keeping them from `mip`.
+    Evict { texture_index: usize, mip: u32 },
+}
+
+impl ResidencyChange {
+    pub fn get_texture_index(&self) -> usize {
+        match self {
+            ResidencyChange::Stream { texture_index, .. }
+            | ResidencyChange::Evict { texture_index, .. } => *texture_index,
+        }
+    }
+
+    pub fn get_mip(&self) -> u32 {
+        match self {
+            ResidencyChange::Stream { mip, .. } | ResidencyChange::Evict { mip, .. } => *mip,
+        }
+    }
+}
+
+/// ストリーミングするテクスチャの大きさと、置いているミップ。<br />
+/// ミップの番号が小さいほど解像度が高い。`resident_mip`から最後のミップまでがメモリーにある。<br />
+/// Size of a streamed texture and the mips it keeps.<br />
+/// Smaller mip numbers have higher resolutions. Mips from `resident_mip` to the last one are in memory.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StreamedTexture {
+    pub width: u32,
+    pub height: u32,
+    pub mip_count: u32,
+    pub bytes_per_texel: u64,
+    pub resident_mip: u32,
+    /// このフレームで要求された一番高い解像度のミップ。<br />
+    /// Highest resolution mip requested in this frame.
+    pub desired_mip: u32,
+    pub pending_mip: Option<u32>,
+    /// 使うモデルが全て消えた。低い解像度に戻してから登録を消す。<br />
+    /// Every model using it is gone. It's dropped back to low resolution and then unregistered.
+    pub is_released: bool,
+}
+
+impl StreamedTexture {
+    pub fn new(
+        width: u32,
+        height: u32,
+        mip_count: u32,
+        bytes_per_texel: u64,
+        resident_mip: u32,
+    ) -> Self {
+        StreamedTexture {
+            width,
+            height,
+            mip_count,
+            bytes_per_texel,
+            resident_mip,
+            desired_mip: resident_mip,
+            pending_mip: None,
+            is_released: false,
+        }
+    }
+
+    pub fn get_low_res_mip(&self) -> u32 {
+        get_low_res_mip(self.width, self.height, self.mip_count)
+    }
+
+    /// `first_mip`から最後までのミップの大きさの合計。<br />
+    /// Total size of the mips from `first_mip` to the last one.
+    pub fn get_size(&self, first_mip: u32) -> u64 {
+        (first_mip..self.mip_count)
+            .map(|mip| {
+                let (width, height) = get_mip_extent(self.width, self.height, mip);
+                width as u64 * height as u64 * self.bytes_per_texel
+            })
+            .sum()
+    }
+}
+
+/// テクスチャごとに要求されたミップとメモリーの逼迫から、読み込むミップと捨てるミップを決める。<br />
+/// Decides which mips to stream in and which to evict, from the mips requested per texture and the memory pressure.
+#[derive(Clone, Debug, Default)]
+pub struct TextureResidency {
+    textures: HashMap<usize, StreamedTexture>,
+    pressure: f64,
+    /// 使用量を`STREAMING_RESUME_PRESSURE`に保ったまま読み込める量。統計を取るまでは何も読み込まない。<br />
+    /// Amount that can be streamed in while keeping the usage at `STREAMING_RESUME_PRESSURE`. Nothing is streamed in before statistics are taken.
+    headroom_bytes: u64,
+}
+
+impl TextureResidency {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, texture_index: usize, texture: StreamedTexture) {
+        self.textures.insert(texture_index, texture);
+    }
+
+    pub fn get(&self, texture_index: usize) -> Option<&StreamedTexture> {
+        self.textures.get(&texture_index)
+    }
+
+    pub fn contains(&self, texture_index: usize) -> bool {
+        self.textures.contains_key(&texture_index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    pub fn get_pending_count(&self) -> usize {
+        self.textures
+            .values()
+            .filter(|texture| texture.pending_mip.is_some())
+            .count()
+    }
+
+    pub fn get_resident_bytes(&self) -> u64 {
+        self.textures
+            .values()
+            .map(|texture| texture.get_size(texture.resident_mip))
+            .sum()
+    }
+
+    /// 使うモデルがなくなったテクスチャを手放す。既に低い解像度なら、すぐに登録を消す。<br />
+    /// Let go of a texture no model uses anymore. It's unregistered right away if it's already at low resolution.
+    pub fn release(&mut self, texture_index: usize) {
+        let texture = match self.textures.get_mut(&texture_index) {
+            Some(texture) => texture,
+            None => return,
+        };
+        texture.is_released = true;
+        if texture.pending_mip.is_none() && texture.resident_mip >= texture.get_low_res_mip() {
+            self.textures.remove(&texture_index);
+        }
+    }
+
+    /// メモリーの統計を取り直した時に、逼迫の度合いと読み込める量を設定する。<br />
+    /// Set the pressure and the amount that can be streamed in whenever memory statistics are refreshed.
+    pub fn set_budget(&mut self, pressure: f64, headroom_bytes: u64) {
+        self.pressure = pressure;
+        self.headroom_bytes = headroom_bytes;
+    }
+
+    /// フレームの要求を集め始める。要求されなかったテクスチャは一番低い解像度で足りると見なす。<br />
+    /// Start collecting the requests of a frame. Textures that aren't requested are considered fine at the lowest resolution.
+    pub fn begin_requests(&mut self) {
+        for texture in self.textures.values_mut() {
+            texture.desired_mip = texture.mip_count.saturating_sub(1);
+        }
+    }
+
+    /// テクスチャにミップを要求する。同じテクスチャへの要求は一番高い解像度を取る。<br />
+    /// Request a mip of a texture. Requests for the same texture take the highest resolution.
+    pub fn request(&mut self, texture_index: usize, mip: u32) {
+        if let Some(texture) = self.textures.get_mut(&texture_index) {
+            texture.desired_mip = texture.desired_mip.min(mip);
+        }
+    }
+
+    /// 読み込むミップと捨てるミップを決め、それらを読み込み中にする。<br />
+    /// 手放したテクスチャはいつでも低い解像度に戻す。逼迫していれば余分なミップから捨て、余裕があれば要求された差の大きいものから読み込む。<br />
+    /// Decide the mips to stream in and to evict, marking them as pending.<br />
+    /// Released textures always go back to low resolution. Under pressure surplus mips are evicted first; with room to spare the largest shortfalls are streamed in first.
+    pub fn plan(&mut self) -> Vec<ResidencyChange> {
+        let mut available = MAX_STREAMING_JOBS.saturating_sub(self.get_pending_count());
+        let mut changes = vec![];
+        let mut idle = self
+            .textures
+            .iter()
+            .filter(|(_, texture)| texture.pending_mip.is_none())
+            .map(|(texture_index, texture)| (*texture_index, *texture))
+            .collect::<Vec<_>>();
+        // 同じ条件のテクスチャは番号の順で決める。
+        // Textures under the same conditions are decided in the order of their indices.
+        idle.sort_by_key(|(texture_index, _)| *texture_index);
+
+        for (texture_index, texture) in idle.iter() {
+            let low_res_mip = texture.get_low_res_mip();
+            if available > 0 && texture.is_released && texture.resident_mip < low_res_mip {
+                changes.push(ResidencyChange::Evict {
+                    texture_index: *texture_index,
+                    mip: low_res_mip,
+                });
+                available -= 1;
+            }
+        }
+
+        let mut candidates = idle
+            .into_iter()
+            .filter(|(_, texture)| !texture.is_released)
+            .collect::<Vec<_>>();
+        if self.pressure >= STREAMING_EVICTION_PRESSURE {
+            candidates.retain(|(_, texture)| texture.resident_mip < texture.get_low_res_mip());
+            candidates.sort_by(|(_, a), (_, b)| {
+                let surplus = |texture: &StreamedTexture| {
+                    texture.desired_mip as i64 - texture.resident_mip as i64
+                };
+                surplus(b)
+                    .cmp(&surplus(a))
+                    .then(b.get_size(b.resident_mip).cmp(&a.get_size(a.resident_mip)))
+            });
+            for (texture_index, texture) in candidates.into_iter().take(available) {
+                let mip = if texture.desired_mip > texture.resident_mip {
+                    texture.desired_mip
+                } else {
+                    texture.resident_mip + 1
+                };
+                changes.push(ResidencyChange::Evict {
+                    texture_index,
+                    mip: mip.min(texture.get_low_res_mip()),
+                });
+            }
+        } else if self.pressure < STREAMING_RESUME_PRESSURE {
+            candidates.retain(|(_, texture)| texture.desired_mip < texture.resident_mip);
+            candidates.sort_by_key(|(_, texture)| {
+                std::cmp::Reverse(texture.resident_mip - texture.desired_mip)
+            });
+            for (texture_index, texture) in candidates.into_iter() {
+                if available == 0 {
+                    break;
+                }
+                let extra =
+                    texture.get_size(texture.desired_mip) - texture.get_size(texture.resident_mip);
+                if extra > self.headroom_bytes {
+                    continue;
+                }
+                self.headroom_bytes -= extra;
+                changes.push(ResidencyChange::Stream {
+                    texture_index,
+                    mip: texture.desired_mip,
+                });
+                available -= 1;
+            }
+        }
+
+        for change in changes.iter() {
+            if let Some(texture) = self.textures.get_mut(&change.get_texture_index()) {
+                texture.pending_mip = Some(change.get_mip());
+            }
+        }
+        changes
+    }
+
+    /// 読み込みか破棄が終わった。手放したテクスチャが低い解像度に戻れば、登録を消す。<br />
+    /// A stream or an eviction has finished. Released textures are unregistered once back at low resolution.
+    pub fn complete(&mut self, texture_index: usize, mip: u32) {
+        let texture = match self.textures.get_mut(&texture_index) {
+            Some(texture) => texture,
+            None => return,
+        };
+        texture.resident_mip = mip;
+        texture.pending_mip = None;
+        if texture.is_released && mip >= texture.get_low_res_mip() {
+            self.textures.remove(&texture_index);
+        }
+    }
+
+    /// 読み込みに失敗した。置いているミップはそのまま。<br />
+    /// Streaming failed. The resident mips stay as they are.
+    pub fn cancel(&mut self, texture_index: usize) {
+        if let Some(texture) = self.textures.get_mut(&texture_index) {
+            texture.pending_mip = None;
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use glam::{Vec3A, Vec4};
+
+/// 既定の一日の長さ（秒）。<br />
+/// Default length of a day, in seconds.
+pub const DEFAULT_DAY_LENGTH: f32 = 600.0;
+
+/// 一日の時間。0が真夜中、0.5が正午。<br />
+/// Times of a day, where 0 is midnight and 0.5 is noon.
+pub const NOON: f32 = 0.5;
+pub const HOURS_PER_DAY: f32 = 24.0;
+
+/// 正午の太陽の高さ。水平な位置の`light_x`と`light_z`と合わせて、正午の光の向きを決める。<br />
+/// Height of the sun at noon. Together with the horizontal `light_x` and `light_z`, it decides the direction of light at noon.
+const NOON_SUN_HEIGHT: f32 = 20000.0;
+
+/// 太陽の高さごとの光の色、環境光の強さと空の色。太陽の高さは地平線で0、正午で1。<br />
+/// 間の高さでは補間し、範囲の外では端の値を使う。<br />
+/// Light color, ambient intensity and sky color by the height of the sun, which is 0 at the horizon and 1 at noon.<br />
+/// Heights in between are interpolated, and heights outside the range use the values at the ends.
+const DAYLIGHT_KEYS: [(f32, [f32; 3], f32, [f32; 3]); 3] = [
+    (-0.2, [0.2, 0.25, 0.4], 0.15, [0.03, 0.04, 0.08]),
+    (0.0, [1.0, 0.55, 0.3], 0.3, [0.6, 0.38, 0.3]),
+    (0.25, [1.0, 1.0, 1.0], 0.5, [0.5, 0.5, 0.5]),
+];
+
+/// ある時間の平行光源と空。<br />
+/// The directional light and the sky at a time of day.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Daylight {
+    /// 光源の位置。太陽が沈んでいる間は、反対側の月の位置。<br />
+    /// Position of the light. While the sun is down, it's the position of the moon on the opposite side.
+    pub light_position: Vec3A,
+    pub light_color: Vec4,
+    pub ambient_intensity: f32,
+    /// 画面を消す色で、霧の色にもなる。<br />
+    /// The color the screen is cleared with, also used as the fog color.
+    pub sky_color: Vec4,
+}
+
+/// 一日の時間を進め、太陽の向きと光の色を決める。<br />
+/// Advances the time of day and decides the direction of the sun and the color of light.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeOfDay {
+    time: f32,
+    day_length: f32,
+    is_paused: bool,
+}
+
+impl TimeOfDay {
+    /// 正午から始める。<br />
+    /// Start at noon.
+    pub fn new(day_length: f32) -> Self {
+        let mut time_of_day = TimeOfDay {
+            time: NOON,
+            day_length: DEFAULT_DAY_LENGTH,
+            is_paused: false,
+        };
+        time_of_day.set_day_length(day_length);
+        time_of_day
+    }
+
+    pub fn advance(&mut self, delta_time: f32) {
+        if self.is_paused {
+            return;
+        }
+        self.set_time(self.time + delta_time / self.day_length);
+    }
+
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    /// 一日の中の割合で時間を変える。範囲の外は一日の中に戻す。<br />
+    /// Change the time as a fraction of the day. Values outside the range wrap around into the day.
+    pub fn set_time(&mut self, time: f32) {
+        if !time.is_finite() {
+            return;
+        }
+        self.time = time.rem_euclid(1.0);
+    }
+
+    pub fn get_hours(&self) -> f32 {
+        self.time * HOURS_PER_DAY
+    }
+
+    pub fn set_hours(&mut self, hours: f32) {
+        self.set_time(hours / HOURS_PER_DAY);
+    }
+
+    pub fn get_day_length(&self) -> f32 {
+        self.day_length
+    }
+
+    /// 一日の長さを秒で変える。正の数でなければ変えない。<br />
+    /// Change the length of a day, in seconds. Nothing changes unless it's a positive number.
+    pub fn set_day_length(&mut self, day_length: f32) {
+        if day_length.is_finite() && day_length > 0.0 {
+            self.day_length = day_length;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn set_paused(&mut self, is_paused: bool) {
+        self.is_paused = is_paused;
+    }
+
+    /// 太陽の高さ。6時と18時に地平線の0、正午に1、真夜中に-1。<br />
+    /// Height of the sun: 0 at the horizon at 6 and 18 o'clock, 1 at noon and -1 at midnight.
+    pub fn get_sun_height(&self) -> f32 {
+        self.get_sun_angle().sin()
+    }
+
+    /// 今の時間の光と空。太陽は東の地平線から`light_x`と`light_z`で決まる正午の向きを通り、西に沈む。<br />
+    /// The light and the sky at the current time. The sun rises on the eastern horizon, passes the noon direction given by `light_x` and `light_z`, and sets in the west.
+    pub fn get_daylight(&self, light_x: f32, light_z: f32) -> Daylight {
+        let noon = Vec3A::new(light_x, NOON_SUN_HEIGHT, light_z);
+        let distance = noon.length();
+        let noon_direction = noon / distance;
+        // 正午の向きと直交する水平な向きを東とする。真上から照らす時は東を決められないのでXを使う。
+        // East is the horizontal direction perpendicular to noon. It can't be found when lit from straight above, so X is used.
+        let east = noon_direction.cross(Vec3A::unit_y());
+        let east = if east.length() > 1e-5 {
+            east.normalize()
+        } else {
+            Vec3A::unit_x()
+        };
+        let angle = self.get_sun_angle();
+        let sun_direction = east * angle.cos() + noon_direction * angle.sin();
+        let sun_height = angle.sin();
+        let light_direction = if sun_height >= 0.0 {
+            sun_direction
+        } else {
+            -sun_direction
+        };
+
+        let (light_color, ambient_intensity, sky_color) = get_daylight_key(sun_height);
+        Daylight {
+            light_position: light_direction * distance,
+            light_color: Vec4::new(light_color[0], light_color[1], light_color[2], 1.0),
+            ambient_intensity,
+            sky_color: Vec4::new(sky_color[0], sky_color[1], sky_color[2], 1.0),
+        }
+    }
+
+    fn get_sun_angle(&self) -> f32 {
+        (self.time - 0.25) * 2.0 * std::f32::consts::PI
+    }
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        TimeOfDay::new(DEFAULT_DAY_LENGTH)
+    }
+}
+
+fn get_daylight_key(sun_height: f32) -> ([f32; 3], f32, [f32; 3]) {
+    let first = DAYLIGHT_KEYS[0];
+    let last = DAYLIGHT_KEYS[DAYLIGHT_KEYS.len() - 1];
+    if sun_height <= first.0 {
+        return (first.1, first.2, first.3);
+    }
+    if sun_height >= last.0 {
+        return (last.1, last.2, last.3);
+    }
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let lerp3 = |a: [f32; 3], b: [f32; 3], t: f32| {
+        [
+            lerp(a[0], b[0], t),
+            lerp(a[1], b[1], t),
+            lerp(a[2], b[2], t),
+        ]
+    };
+    for pair in DAYLIGHT_KEYS.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        if sun_height <= to.0 {
+            let t = (sun_height - from.0) / (to.0 - from.0);
+            return (
+                lerp3(from.1, to.1, t),
+                lerp(from.2, to.2, t),
+                lerp3(from.3, to.3, t),
+            );
+        }
+    }
+    (last.1, last.2, last.3)
+}
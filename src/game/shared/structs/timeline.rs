@@ -0,0 +1,310 @@
+use glam::Vec3A;
+use serde::{Deserialize, Serialize};
+
+/// キーフレームの間の補間に使うイージング曲線。<br />
+/// Easing curve used to interpolate between keyframes.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// 次のキーフレームまで前の値を保つ。<br />
+    /// Hold the previous value until the next keyframe.
+    Step,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+impl Easing {
+    /// 0から1までの進み具合に曲線を掛ける。<br />
+    /// Apply the curve to a progress between 0 and 1.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Step => 0.0,
+        }
+    }
+}
+
+/// トラックの中の一つのキーフレーム。イージングはこのキーフレームに向かう区間に使う。<br />
+/// A single keyframe in a track. The easing is used for the segment leading to this keyframe.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+/// キーフレームの値として補間できる型。<br />
+/// Types that can be interpolated as keyframe values.
+pub trait Interpolate: Copy {
+    fn interpolate(from: Self, to: Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(from: Self, to: Self, t: f32) -> Self {
+        from + (to - from) * t
+    }
+}
+
+macro_rules! impl_interpolate_array {
+    ($length: expr) => {
+        impl Interpolate for [f32; $length] {
+            fn interpolate(from: Self, to: Self, t: f32) -> Self {
+                let mut result = from;
+                for (value, target) in result.iter_mut().zip(to.iter()) {
+                    *value = f32::interpolate(*value, *target, t);
+                }
+                result
+            }
+        }
+    };
+}
+
+impl_interpolate_array!(2);
+impl_interpolate_array!(3);
+
+/// トラックの指定した時間の値を求める。最初のキーフレームより前と最後のキーフレームより後は端の値を保つ。<br />
+/// Sample the value of a track at the given time. The values at both ends are held before the first and after the last keyframe.
+pub fn sample_track<T: Interpolate>(keyframes: &[Keyframe<T>], time: f32) -> Option<T> {
+    let first = keyframes.first()?;
+    if time <= first.time {
+        return Some(first.value);
+    }
+    let next_index = match keyframes.iter().position(|k| k.time > time) {
+        Some(index) => index,
+        None => return keyframes.last().map(|k| k.value),
+    };
+    let previous = &keyframes[next_index - 1];
+    let next = &keyframes[next_index];
+    let t = (time - previous.time) / (next.time - previous.time);
+    Some(T::interpolate(
+        previous.value,
+        next.value,
+        next.easing.apply(t),
+    ))
+}
+
+/// カメラを動かすトラック。回転はヨーとピッチ、角度は全て度で書く。<br />
+/// Tracks moving the camera. Rotation is yaw and pitch, and all angles are written in degrees.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraTrack {
+    pub position: Vec<Keyframe<[f32; 3]>>,
+    pub rotation: Vec<Keyframe<[f32; 2]>>,
+    pub field_of_view: Vec<Keyframe<f32>>,
+    /// 画面を黒く覆う割合。0で透明、1で真っ黒。<br />
+    /// How much the screen is covered in black. 0 is transparent and 1 is fully black.
+    pub fade: Vec<Keyframe<f32>>,
+}
+
+/// 名前で指定したエンティティのトランスフォームを動かすトラック。回転はオイラー角（度）。<br />
+/// Tracks moving the transform of an entity given by name. Rotation is in Euler angles, in degrees.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntityTrack {
+    pub entity: String,
+    pub position: Vec<Keyframe<[f32; 3]>>,
+    pub rotation: Vec<Keyframe<[f32; 3]>>,
+    pub scale: Vec<Keyframe<[f32; 3]>>,
+}
+
+/// ある時間のエンティティの姿勢。トラックがない要素は`None`になる。<br />
+/// Pose of an entity at a point in time. Elements without a track are `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityPose {
+    pub entity: String,
+    pub position: Option<Vec3A>,
+    /// ラジアン。<br />
+    /// In radians.
+    pub rotation: Option<Vec3A>,
+    pub scale: Option<Vec3A>,
+}
+
+/// ある時間のカットシーンの状態。<br />
+/// State of a cutscene at a point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CutsceneFrame {
+    pub camera_position: Option<Vec3A>,
+    /// ヨーとピッチ（ラジアン）。<br />
+    /// Yaw and pitch, in radians.
+    pub camera_rotation: Option<(f32, f32)>,
+    /// 度。<br />
+    /// In degrees.
+    pub field_of_view: Option<f32>,
+    pub fade: Option<f32>,
+    pub entities: Vec<EntityPose>,
+}
+
+/// データファイルから読み込むキーフレームのカットシーン。<br />
+/// A keyframed cutscene loaded from a data file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Cutscene {
+    pub name: String,
+    pub camera: CameraTrack,
+    pub entities: Vec<EntityTrack>,
+}
+
+impl Cutscene {
+    /// JSONのファイルからカットシーンを読み込む。<br />
+    /// Load a cutscene from a JSON file.
+    pub fn load(file_name: &str) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(file_name)?;
+        Self::from_json(&json)
+    }
+
+    /// JSONからカットシーンを作る。キーフレームは時間の順に並べ替える。<br />
+    /// Create a cutscene from JSON. Keyframes are sorted by time.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let mut cutscene: Cutscene = serde_json::from_str(json)?;
+        cutscene.validate()?;
+        let CameraTrack {
+            position,
+            rotation,
+            field_of_view,
+            fade,
+        } = &mut cutscene.camera;
+        sort_keyframes(position);
+        sort_keyframes(rotation);
+        sort_keyframes(field_of_view);
+        sort_keyframes(fade);
+        for track in cutscene.entities.iter_mut() {
+            sort_keyframes(&mut track.position);
+            sort_keyframes(&mut track.rotation);
+            sort_keyframes(&mut track.scale);
+        }
+        Ok(cutscene)
+    }
+
+    /// 最後のキーフレームの時間（秒）。<br />
+    /// Time of the last keyframe, in seconds.
+    pub fn get_duration(&self) -> f32 {
+        self.get_keyframe_times().into_iter().fold(0.0, f32::max)
+    }
+
+    pub fn sample(&self, time: f32) -> CutsceneFrame {
+        let to_radians =
+            |[x, y, z]: [f32; 3]| Vec3A::new(x.to_radians(), y.to_radians(), z.to_radians());
+        CutsceneFrame {
+            camera_position: sample_track(&self.camera.position, time).map(Vec3A::from),
+            camera_rotation: sample_track(&self.camera.rotation, time)
+                .map(|[yaw, pitch]| (yaw.to_radians(), pitch.to_radians())),
+            field_of_view: sample_track(&self.camera.field_of_view, time),
+            fade: sample_track(&self.camera.fade, time).map(|fade| fade.max(0.0).min(1.0)),
+            entities: self
+                .entities
+                .iter()
+                .map(|track| EntityPose {
+                    entity: track.entity.clone(),
+                    position: sample_track(&track.position, time).map(Vec3A::from),
+                    rotation: sample_track(&track.rotation, time).map(to_radians),
+                    scale: sample_track(&track.scale, time).map(Vec3A::from),
+                })
+                .collect(),
+        }
+    }
+
+    fn get_keyframe_times(&self) -> Vec<f32> {
+        let camera = &self.camera;
+        let mut times = vec![];
+        times.extend(camera.position.iter().map(|k| k.time));
+        times.extend(camera.rotation.iter().map(|k| k.time));
+        times.extend(camera.field_of_view.iter().map(|k| k.time));
+        times.extend(camera.fade.iter().map(|k| k.time));
+        for track in self.entities.iter() {
+            times.extend(track.position.iter().map(|k| k.time));
+            times.extend(track.rotation.iter().map(|k| k.time));
+            times.extend(track.scale.iter().map(|k| k.time));
+        }
+        times
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        for time in self.get_keyframe_times() {
+            if !time.is_finite() || time < 0.0 {
+                return Err(anyhow::anyhow!(
+                    "Invalid keyframe time {} in cutscene {}.",
+                    time,
+                    self.name
+                ));
+            }
+        }
+        for track in self.entities.iter() {
+            if track.entity.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Entity track without an entity name in cutscene {}.",
+                    self.name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 時間の順にキーフレームを並べ替え、同じ時間のキーフレームは最後のものだけ残す。<br />
+/// Sort keyframes by time, keeping only the last keyframe of those with the same time.
+fn sort_keyframes<T>(keyframes: &mut Vec<Keyframe<T>>) {
+    keyframes.reverse();
+    keyframes.sort_by(|a, b| {
+        a.time
+            .partial_cmp(&b.time)
+            .expect("Failed to compare times.")
+    });
+    keyframes.dedup_by(|later, kept| (later.time - kept.time).abs() < f32::EPSILON);
+}
+
+/// カットシーンの再生位置を進める。<br />
+/// Advances the playback position of a cutscene.
+#[derive(Clone, Debug)]
+pub struct CutscenePlayer {
+    cutscene: Cutscene,
+    time: f32,
+    duration: f32,
+}
+
+impl CutscenePlayer {
+    pub fn new(cutscene: Cutscene) -> Self {
+        let duration = cutscene.get_duration();
+        CutscenePlayer {
+            cutscene,
+            time: 0.0,
+            duration,
+        }
+    }
+
+    /// 時間を進めて、その時間の状態を返す。<br />
+    /// Advance the time and return the state at that time.
+    pub fn advance(&mut self, delta_time: f32) -> CutsceneFrame {
+        self.time = (self.time + delta_time.max(0.0)).min(self.duration);
+        self.cutscene.sample(self.time)
+    }
+
+    /// 最後まで飛ばす。次の`advance`で最後の状態を返す。<br />
+    /// Skip to the end. The next `advance` returns the final state.
+    pub fn skip(&mut self) {
+        self.time = self.duration;
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.cutscene.name
+    }
+
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.time >= self.duration
+    }
+}
@@ -1,7 +1,6 @@
-use crate::game::shared::structs::CompletedTasks;
+use crate::game::shared::structs::{CompletedTasks, LoadTask, LoadingProgress};
 use crate::game::structs::{GeometricPrimitive, InstancedModel, Model, SkinnedModel, Terrain};
 use crate::game::traits::{Disposable, GraphicsBase};
-use crossbeam::channel::*;
 
 /// モデルの読み込み及びシェイプや地形を生成するとき、より効率的に実行するため、読み込み開始の時点は全てをタスク化しました。<br />
 /// 処理完了する際にタスクを待つことができるような仕様です。<br />
@@ -14,14 +13,17 @@ where
     CommandType: 'static + Clone,
     TextureType: 'static + Clone + Disposable,
 {
-    pub model_tasks: Vec<Receiver<Model<GraphicsType, BufferType, CommandType, TextureType>>>,
+    pub model_tasks: Vec<LoadTask<Model<GraphicsType, BufferType, CommandType, TextureType>>>,
     pub skinned_model_tasks:
-        Vec<Receiver<SkinnedModel<GraphicsType, BufferType, CommandType, TextureType>>>,
-    pub terrain_tasks: Vec<Receiver<Terrain<GraphicsType, BufferType, CommandType, TextureType>>>,
+        Vec<LoadTask<SkinnedModel<GraphicsType, BufferType, CommandType, TextureType>>>,
+    pub terrain_tasks: Vec<LoadTask<Terrain<GraphicsType, BufferType, CommandType, TextureType>>>,
     pub geometric_primitive_tasks:
-        Vec<Receiver<GeometricPrimitive<GraphicsType, BufferType, CommandType, TextureType>>>,
+        Vec<LoadTask<GeometricPrimitive<GraphicsType, BufferType, CommandType, TextureType>>>,
     pub instanced_model_tasks:
-        Vec<Receiver<InstancedModel<GraphicsType, BufferType, CommandType, TextureType>>>,
+        Vec<LoadTask<InstancedModel<GraphicsType, BufferType, CommandType, TextureType>>>,
+    /// 待たずに受け取った結果。全てのタスクを待つ時にまとめて返す。<br />
+    /// Results received without waiting. They're returned together when all tasks are waited.
+    completed_tasks: CompletedTasks<GraphicsType, BufferType, CommandType, TextureType>,
 }
 
 impl<GraphicsType, BufferType, CommandType, TextureType> Default
@@ -52,9 +54,38 @@ where
             terrain_tasks: vec![],
             geometric_primitive_tasks: vec![],
             instanced_model_tasks: vec![],
+            completed_tasks: CompletedTasks::new(),
         }
     }
 
+    /// 終わったタスクの結果を待たずに受け取り、進み具合を返す。<br />
+    /// Receive the results of finished tasks without waiting and return the progress.
+    pub fn poll_tasks(&mut self) -> anyhow::Result<LoadingProgress> {
+        let completed = &mut self.completed_tasks;
+        Self::poll_tasks_of(&mut self.model_tasks, &mut completed.models)?;
+        Self::poll_tasks_of(&mut self.skinned_model_tasks, &mut completed.skinned_models)?;
+        Self::poll_tasks_of(&mut self.terrain_tasks, &mut completed.terrains)?;
+        Self::poll_tasks_of(
+            &mut self.geometric_primitive_tasks,
+            &mut completed.geometric_primitives,
+        )?;
+        Self::poll_tasks_of(&mut self.instanced_model_tasks, &mut completed.instances)?;
+        let completed_count = completed.models.len()
+            + completed.skinned_models.len()
+            + completed.terrains.len()
+            + completed.geometric_primitives.len()
+            + completed.instances.len();
+        let pending_count = self.model_tasks.len()
+            + self.skinned_model_tasks.len()
+            + self.terrain_tasks.len()
+            + self.geometric_primitive_tasks.len()
+            + self.instanced_model_tasks.len();
+        Ok(LoadingProgress::new(
+            completed_count,
+            completed_count + pending_count,
+        ))
+    }
+
     pub fn wait_for_all_tasks(
         &mut self,
     ) -> anyhow::Result<CompletedTasks<GraphicsType, BufferType, CommandType, TextureType>> {
@@ -63,29 +94,31 @@ where
         let terrain_tasks = &mut self.terrain_tasks;
         let primitive_tasks = &mut self.geometric_primitive_tasks;
         let instance_tasks = &mut self.instanced_model_tasks;
-        let mut models = vec![];
-        let mut skinned_models = vec![];
-        let mut terrains = vec![];
-        let mut primitives = vec![];
-        let mut instances = vec![];
+        let CompletedTasks {
+            mut models,
+            mut skinned_models,
+            mut terrains,
+            geometric_primitives: mut primitives,
+            mut instances,
+        } = std::mem::take(&mut self.completed_tasks);
         for task in model_tasks.iter_mut() {
-            let model = task.recv()?;
+            let model = task.wait()?;
             models.push(model);
         }
         for task in skinned_model_tasks.iter_mut() {
-            let model = task.recv()?;
+            let model = task.wait()?;
             skinned_models.push(model);
         }
         for task in terrain_tasks.iter_mut() {
-            let terrain = task.recv()?;
+            let terrain = task.wait()?;
             terrains.push(terrain);
         }
         for task in primitive_tasks.iter_mut() {
-            let primitive = task.recv()?;
+            let primitive = task.wait()?;
             primitives.push(primitive);
         }
         for task in instance_tasks.iter_mut() {
-            let instance = task.recv()?;
+            let instance = task.wait()?;
             instances.push(instance);
         }
         Ok(CompletedTasks {
@@ -103,5 +136,55 @@ where
         self.terrain_tasks.clear();
         self.geometric_primitive_tasks.clear();
         self.instanced_model_tasks.clear();
+        self.completed_tasks = CompletedTasks::new();
+    }
+
+    /// 待っているタスクも、まだ登録していない結果もないかどうか。<br />
+    /// Whether there are neither pending tasks nor results waiting to be registered.
+    pub fn is_empty(&self) -> bool {
+        let completed = &self.completed_tasks;
+        self.model_tasks.is_empty()
+            && self.skinned_model_tasks.is_empty()
+            && self.terrain_tasks.is_empty()
+            && self.geometric_primitive_tasks.is_empty()
+            && self.instanced_model_tasks.is_empty()
+            && completed.models.is_empty()
+            && completed.skinned_models.is_empty()
+            && completed.terrains.is_empty()
+            && completed.geometric_primitives.is_empty()
+            && completed.instances.is_empty()
+    }
+
+    /// 全てのタスクを取り消す。まだ登録していない結果も捨てる。シーンを切り替えた時に呼ぶ。<br />
+    /// Cancel every task. Results that haven't been registered yet are dropped as well. Called when the scene is switched.
+    pub fn cancel(&mut self) {
+        let pending_count = self.model_tasks.len()
+            + self.skinned_model_tasks.len()
+            + self.terrain_tasks.len()
+            + self.geometric_primitive_tasks.len()
+            + self.instanced_model_tasks.len();
+        if pending_count > 0 {
+            log::info!("Cancelling {} loading tasks.", pending_count);
+        }
+        // 捨てたタスクは取り消される。
+        // Dropped tasks are cancelled.
+        self.clear();
+    }
+
+    fn poll_tasks_of<T>(
+        tasks: &mut Vec<LoadTask<T>>,
+        completed: &mut Vec<T>,
+    ) -> anyhow::Result<()> {
+        let mut index = 0;
+        while index < tasks.len() {
+            match tasks[index].try_take()? {
+                Some(result) => {
+                    completed.push(result);
+                    tasks.remove(index);
+                }
+                None => index += 1,
+            }
+        }
+        Ok(())
     }
 }
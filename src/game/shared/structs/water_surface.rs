@@ -0,0 +1,277 @@
+use glam::{Vec2, Vec3A};
+use slotmap::DefaultKey;
+use std::collections::HashMap;
+
+use crate::game::shared::structs::WaterVolume;
+
+/// 波紋のテクスチャの一辺のピクセル数。<br />
+/// Number of pixels per side of the ripple texture.
+pub const RIPPLE_RESOLUTION: usize = 64;
+
+/// 波紋を進める間隔（秒）。フレームレートによって広がる速さが変わらないよう固定する。<br />
+/// Interval at which ripples are advanced, in seconds. It's fixed so they spread at the same speed at any frame rate.
+pub const RIPPLE_TIMESTEP: f32 = 1.0 / 60.0;
+
+/// 一度に進める波紋のステップの上限。長く止まった後に追い付こうとして固まらないようにする。<br />
+/// Maximum number of ripple steps taken at once, so catching up after a long pause doesn't stall.
+const MAX_RIPPLE_STEPS: usize = 4;
+
+/// 一ステップごとに波紋に残る割合。<br />
+/// Fraction of the ripples kept every step.
+const RIPPLE_DAMPING: f32 = 0.97;
+
+/// 波紋の高さ1に当たるワールドの高さ。<br />
+/// World height of a ripple with a height of one.
+const RIPPLE_HEIGHT_SCALE: f32 = 0.12;
+
+/// 水面を出入りした時に立つ波紋の強さ。<br />
+/// Strength of the ripples made when crossing the water surface.
+const SPLASH_STRENGTH: f32 = 1.0;
+
+/// 水の中を一メートル動くごとに立つ波紋の強さ。<br />
+/// Strength of the ripples made for every meter moved in water.
+const WAKE_STRENGTH: f32 = 0.25;
+
+/// 水面を出入りしたもの。<br />
+/// Something that crossed the water surface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WaterEvent {
+    Entered { entity: DefaultKey, position: Vec3A },
+    Exited { entity: DefaultKey, position: Vec3A },
+}
+
+/// 水面の波紋の高さの格子。二つの高さの差から次の高さを求める、簡単な波動方程式で広げる。<br />
+/// A grid of ripple heights on the water surface. Ripples spread with a simple wave equation that finds the next heights from the previous two.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RippleField {
+    resolution: usize,
+    previous: Vec<f32>,
+    current: Vec<f32>,
+}
+
+impl RippleField {
+    pub fn new(resolution: usize) -> Self {
+        let resolution = resolution.max(2);
+        RippleField {
+            resolution,
+            previous: vec![0.0; resolution * resolution],
+            current: vec![0.0; resolution * resolution],
+        }
+    }
+
+    pub fn get_resolution(&self) -> usize {
+        self.resolution
+    }
+
+    /// `uv`を中心に、`radius`の範囲を外側ほど弱く押し下げる。どちらも水面全体に対する割合。<br />
+    /// Push down the area within `radius` of `uv`, less towards the edge. Both are fractions of the whole surface.
+    pub fn disturb(&mut self, uv: Vec2, radius: f32, strength: f32) {
+        let size = self.resolution as f32;
+        let center = uv * size - Vec2::splat(0.5);
+        // 格子より小さな波紋も消えないよう、半径は一マス以上にする。
+        // The radius is at least one cell so ripples smaller than the grid don't vanish.
+        let radius = (radius * size).max(1.0);
+        let min = (center - Vec2::splat(radius)).max(Vec2::zero());
+        let max = (center + Vec2::splat(radius)).min(Vec2::splat(size - 1.0));
+        if min.x > max.x || min.y > max.y {
+            return;
+        }
+        for y in min.y.ceil() as usize..=max.y.floor() as usize {
+            for x in min.x.ceil() as usize..=max.x.floor() as usize {
+                let distance = (Vec2::new(x as f32, y as f32) - center).length();
+                if distance <= radius {
+                    self.current[y * self.resolution + x] -= strength * (1.0 - distance / radius);
+                }
+            }
+        }
+    }
+
+    /// 波紋を一ステップ進める。縁の外は静かな水面とする。<br />
+    /// Advance the ripples by a step. Outside the edges is calm water.
+    pub fn step(&mut self) {
+        let resolution = self.resolution;
+        let current = &self.current;
+        let get = |x: isize, y: isize| {
+            if x < 0 || y < 0 || x >= resolution as isize || y >= resolution as isize {
+                0.0
+            } else {
+                current[y as usize * resolution + x as usize]
+            }
+        };
+        for y in 0..resolution {
+            for x in 0..resolution {
+                let (ix, iy) = (x as isize, y as isize);
+                let neighbours =
+                    get(ix - 1, iy) + get(ix + 1, iy) + get(ix, iy - 1) + get(ix, iy + 1);
+                let index = y * resolution + x;
+                self.previous[index] = (neighbours * 0.5 - self.previous[index]) * RIPPLE_DAMPING;
+            }
+        }
+        std::mem::swap(&mut self.previous, &mut self.current);
+    }
+
+    /// 水面全体に対する割合の位置で、波紋の高さを補間して求める。水面の外では0。<br />
+    /// Interpolate the ripple height at a position given as a fraction of the whole surface. Zero outside the surface.
+    pub fn sample(&self, uv: Vec2) -> f32 {
+        if uv.x < 0.0 || uv.y < 0.0 || uv.x > 1.0 || uv.y > 1.0 {
+            return 0.0;
+        }
+        let last = (self.resolution - 1) as f32;
+        let point = (uv * self.resolution as f32 - Vec2::splat(0.5))
+            .max(Vec2::zero())
+            .min(Vec2::splat(last));
+        let (x0, y0) = (point.x.floor() as usize, point.y.floor() as usize);
+        let (x1, y1) = (
+            (x0 + 1).min(self.resolution - 1),
+            (y0 + 1).min(self.resolution - 1),
+        );
+        let (tx, ty) = (point.x - x0 as f32, point.y - y0 as f32);
+        let get = |x: usize, y: usize| self.current[y * self.resolution + x];
+        let top = get(x0, y0) * (1.0 - tx) + get(x1, y0) * tx;
+        let bottom = get(x0, y1) * (1.0 - tx) + get(x1, y1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    /// 波紋をRGBA8のテクスチャにする。Rが高さ、GとBがXとZの傾き。どれも128が平らな水面。<br />
+    /// Turn the ripples into an RGBA8 texture. R is the height, and G and B the slopes along X and Z. 128 is flat water for all of them.
+    pub fn get_pixels(&self) -> Vec<u8> {
+        let resolution = self.resolution;
+        let encode = |value: f32| (128.0 + value.max(-1.0).min(1.0) * 127.0).round() as u8;
+        let get = |x: usize, y: usize| self.current[y * resolution + x];
+        let mut pixels = Vec::with_capacity(resolution * resolution * 4);
+        for y in 0..resolution {
+            for x in 0..resolution {
+                let slope_x = get((x + 1).min(resolution - 1), y) - get(x.saturating_sub(1), y);
+                let slope_z = get(x, (y + 1).min(resolution - 1)) - get(x, y.saturating_sub(1));
+                pixels.extend_from_slice(&[
+                    encode(get(x, y)),
+                    encode(slope_x * 0.5),
+                    encode(slope_z * 0.5),
+                    255,
+                ]);
+            }
+        }
+        pixels
+    }
+
+    pub fn clear(&mut self) {
+        self.previous.iter_mut().for_each(|h| *h = 0.0);
+        self.current.iter_mut().for_each(|h| *h = 0.0);
+    }
+}
+
+/// 波と、水を出入りしたり中を動いたりしたものが立てる波紋を合わせた水面。<br />
+/// A water surface combining its waves with the ripples made by things entering, leaving and moving through the water.
+#[derive(Clone, Debug)]
+pub struct WaterSurface {
+    volume: WaterVolume,
+    ripples: RippleField,
+    time: f32,
+    accumulator: f32,
+    /// 前の更新での位置と、水に沈んでいたかどうか。<br />
+    /// Positions at the last update and whether they were under water.
+    bodies: HashMap<DefaultKey, (Vec3A, bool)>,
+}
+
+impl WaterSurface {
+    pub fn new(volume: WaterVolume) -> Self {
+        WaterSurface {
+            volume,
+            ripples: RippleField::new(RIPPLE_RESOLUTION),
+            time: 0.0,
+            accumulator: 0.0,
+            bodies: HashMap::new(),
+        }
+    }
+
+    pub fn get_volume(&self) -> &WaterVolume {
+        &self.volume
+    }
+
+    pub fn get_ripples(&self) -> &RippleField {
+        &self.ripples
+    }
+
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    /// 指定した位置の今の水面の高さ。波と波紋を含む。範囲外なら`None`。<br />
+    /// Current height of the water surface at the given position, including waves and ripples. `None` outside the water.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let height = self.volume.get_height(x, z, self.time)?;
+        Some(height + self.ripples.sample(self.get_uv(x, z)) * RIPPLE_HEIGHT_SCALE)
+    }
+
+    /// 水面を進め、`bodies`の位置から波紋を立てる。水面を出入りしたものを返す。<br />
+    /// `bodies`は、エンティティ、位置、水面に触れる半径の組。一覧にないものは忘れる。<br />
+    /// Advance the surface and make ripples from the positions of `bodies`. Things that crossed the surface are returned.<br />
+    /// `bodies` are tuples of an entity, its position and the radius it touches the surface with. Anything not listed is forgotten.
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        bodies: &[(DefaultKey, Vec3A, f32)],
+    ) -> Vec<WaterEvent> {
+        self.time += delta_time;
+        let mut events = vec![];
+        for (entity, position, radius) in bodies.iter() {
+            let is_under_water = self
+                .height_at(position.x, position.z)
+                .map(|height| position.y < height)
+                .unwrap_or(false);
+            match self.bodies.get(entity).copied() {
+                Some((_, was_under_water)) if was_under_water != is_under_water => {
+                    self.splash(*position, *radius, SPLASH_STRENGTH);
+                    events.push(if is_under_water {
+                        WaterEvent::Entered {
+                            entity: *entity,
+                            position: *position,
+                        }
+                    } else {
+                        WaterEvent::Exited {
+                            entity: *entity,
+                            position: *position,
+                        }
+                    });
+                }
+                Some((last, true)) => {
+                    let moved = Vec2::new(position.x - last.x, position.z - last.z).length();
+                    if moved > 0.0 {
+                        self.splash(*position, *radius, WAKE_STRENGTH * moved);
+                    }
+                }
+                _ => (),
+            }
+            self.bodies.insert(*entity, (*position, is_under_water));
+        }
+        self.bodies
+            .retain(|entity, _| bodies.iter().any(|(e, _, _)| e == entity));
+
+        self.accumulator += delta_time;
+        let mut steps = 0;
+        while self.accumulator >= RIPPLE_TIMESTEP && steps < MAX_RIPPLE_STEPS {
+            self.ripples.step();
+            self.accumulator -= RIPPLE_TIMESTEP;
+            steps += 1;
+        }
+        if steps == MAX_RIPPLE_STEPS {
+            self.accumulator = self.accumulator.min(RIPPLE_TIMESTEP);
+        }
+        events
+    }
+
+    fn splash(&mut self, position: Vec3A, radius: f32, strength: f32) {
+        let size = self.volume.max - self.volume.min;
+        let extent = size.x.max(size.y);
+        if extent <= 0.0 {
+            return;
+        }
+        let uv = self.get_uv(position.x, position.z);
+        self.ripples.disturb(uv, radius / extent, strength);
+    }
+
+    fn get_uv(&self, x: f32, z: f32) -> Vec2 {
+        let size = (self.volume.max - self.volume.min).max(Vec2::splat(f32::EPSILON));
+        (Vec2::new(x, z) - self.volume.min) / size
+    }
+}
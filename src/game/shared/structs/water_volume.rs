@@ -0,0 +1,99 @@
+use glam::Vec2;
+
+/// 水の密度の既定値。コライダーの密度の既定値は1なので、小物は半分ほど沈んで浮く。<br />
+/// Default density of water. Colliders default to a density of 1, so props float about half submerged.
+pub const DEFAULT_WATER_DENSITY: f32 = 2.0;
+
+/// 水面を動かす一つの正弦波。<br />
+/// A single sine wave moving the water surface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Wave {
+    pub amplitude: f32,
+    pub wavelength: f32,
+    pub speed: f32,
+    pub direction: Vec2,
+}
+
+impl Wave {
+    pub fn new(amplitude: f32, wavelength: f32, speed: f32, direction: Vec2) -> Self {
+        Wave {
+            amplitude,
+            wavelength,
+            speed,
+            direction: if direction.length_squared() > f32::EPSILON {
+                direction.normalize()
+            } else {
+                Vec2::unit_x()
+            },
+        }
+    }
+
+    /// 指定した位置と時間での水面の高さの変化。<br />
+    /// Offset of the water surface at the given position and time.
+    pub fn get_offset(&self, x: f32, z: f32, time: f32) -> f32 {
+        if self.wavelength <= 0.0 {
+            return 0.0;
+        }
+        let k = std::f32::consts::PI * 2.0 / self.wavelength;
+        let distance = self.direction.dot(Vec2::new(x, z));
+        self.amplitude * (k * (distance - self.speed * time)).sin()
+    }
+}
+
+/// 水平な範囲を持つ水。水面の高さは静かな水面に波を足したもの。<br />
+/// A body of water with a horizontal extent. The surface height is the calm surface plus its waves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaterVolume {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub surface_height: f32,
+    pub density: f32,
+    pub waves: Vec<Wave>,
+}
+
+impl WaterVolume {
+    pub fn new(min: Vec2, max: Vec2, surface_height: f32) -> Self {
+        WaterVolume {
+            min: min.min(max),
+            max: min.max(max),
+            surface_height,
+            density: DEFAULT_WATER_DENSITY,
+            waves: vec![],
+        }
+    }
+
+    pub fn with_wave(mut self, wave: Wave) -> Self {
+        self.waves.push(wave);
+        self
+    }
+
+    pub fn contains(&self, x: f32, z: f32) -> bool {
+        (self.min.x..=self.max.x).contains(&x) && (self.min.y..=self.max.y).contains(&z)
+    }
+
+    /// 指定した位置と時間での水面の高さ。範囲外なら`None`。<br />
+    /// Height of the water surface at the given position and time. `None` outside the volume.
+    pub fn get_height(&self, x: f32, z: f32, time: f32) -> Option<f32> {
+        if !self.contains(x, z) {
+            return None;
+        }
+        Some(
+            self.surface_height
+                + self
+                    .waves
+                    .iter()
+                    .map(|w| w.get_offset(x, z, time))
+                    .sum::<f32>(),
+        )
+    }
+}
+
+/// 沈んだ深さに比例する浮力。`height`まで沈むと`volume`の分の水を押しのける。<br />
+/// Buoyant force proportional to the submerged depth. Sinking to `height` displaces `volume` worth of water.
+pub fn get_buoyant_force(density: f32, gravity: f32, volume: f32, depth: f32, height: f32) -> f32 {
+    if height <= 0.0 {
+        return 0.0;
+    }
+    let submerged = (depth / height).max(0.0).min(1.0);
+    density * gravity.abs() * volume * submerged
+}
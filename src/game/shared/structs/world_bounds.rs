@@ -0,0 +1,123 @@
+use glam::{Vec2, Vec3A};
+
+/// 遊べる範囲の外に出た時の扱い。<br />
+/// How leaving the playable area is handled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BoundsResponse {
+    /// 範囲の中にゆっくり押し戻す。<br />
+    /// Push back into the area gradually.
+    PushBack,
+    /// 外にいる間、体力を減らし続ける。<br />
+    /// Keep draining health while outside.
+    Damage,
+}
+
+/// シーンの遊べる水平な範囲。地形の縁は途切れていて落ちたり泳げなくなったりするので、少し内側に取る。<br />
+/// The horizontal playable area of a scene. The edge of the terrain ends abruptly where players could fall or get stuck swimming, so the area is kept slightly inside it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WorldBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+    /// 境界のこの距離まで近づいたら警告を始める。<br />
+    /// Warnings start once the player is this close to the bounds.
+    pub warning_distance: f32,
+    /// 一秒あたりに押し戻す距離。<br />
+    /// Distance pushed back per second.
+    pub push_back_speed: f32,
+    pub damage_per_second: f32,
+    pub response: BoundsResponse,
+}
+
+impl WorldBounds {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        WorldBounds {
+            min: min.min(max),
+            max: min.max(max),
+            warning_distance: 20.0,
+            push_back_speed: 30.0,
+            damage_per_second: 10.0,
+            response: BoundsResponse::PushBack,
+        }
+    }
+
+    /// 地形の範囲から縁の幅だけ内側に入った範囲を作る。縁が広すぎれば中心の一点になる。<br />
+    /// Create the area inset from the terrain by the border width. A border that is too wide collapses it to the center.
+    pub fn from_terrain(origin: Vec2, size: f32, border: f32) -> Self {
+        let border = border.max(0.0).min(size * 0.5);
+        WorldBounds::new(
+            origin + Vec2::new(border, border),
+            origin + Vec2::new(size - border, size - border),
+        )
+    }
+
+    pub fn with_response(mut self, response: BoundsResponse) -> Self {
+        self.response = response;
+        self
+    }
+
+    pub fn contains(&self, position: Vec3A) -> bool {
+        position.x >= self.min.x
+            && position.x <= self.max.x
+            && position.z >= self.min.y
+            && position.z <= self.max.y
+    }
+
+    /// 水平に範囲の中へ収める。高さはそのまま。<br />
+    /// Clamp horizontally into the area. The height is kept.
+    pub fn clamp(&self, position: Vec3A) -> Vec3A {
+        Vec3A::new(
+            position.x.max(self.min.x).min(self.max.x),
+            position.y,
+            position.z.max(self.min.y).min(self.max.y),
+        )
+    }
+
+    /// 一番近い境界までの水平な距離。外にいれば負になる。<br />
+    /// Horizontal distance to the nearest edge, negative while outside.
+    pub fn get_distance_to_edge(&self, position: Vec3A) -> f32 {
+        if self.contains(position) {
+            (position.x - self.min.x)
+                .min(self.max.x - position.x)
+                .min(position.z - self.min.y)
+                .min(self.max.y - position.z)
+        } else {
+            -(self.clamp(position) - position).length()
+        }
+    }
+
+    /// 0から1の警告の強さ。境界に近づくほど強くなり、外にいれば1。<br />
+    /// Warning strength from 0 to 1. It grows towards the edge and is 1 while outside.
+    pub fn get_warning(&self, position: Vec3A) -> f32 {
+        let distance = self.get_distance_to_edge(position);
+        if distance <= 0.0 {
+            1.0
+        } else if self.warning_distance <= 0.0 {
+            0.0
+        } else {
+            (1.0 - distance / self.warning_distance).max(0.0)
+        }
+    }
+
+    /// 押し戻した後の位置。一度に動くのは押し戻す速さの分だけ。<br />
+    /// Position after pushing back. It moves by at most the push back speed at a time.
+    pub fn push_back(&self, position: Vec3A, delta_time: f32) -> Vec3A {
+        let offset = self.clamp(position) - position;
+        let max_distance = self.push_back_speed * delta_time;
+        let distance = offset.length();
+        if distance <= max_distance {
+            position + offset
+        } else {
+            position + offset / distance * max_distance
+        }
+    }
+
+    /// この時間に受けるダメージ。押し戻す設定か、中にいれば0。<br />
+    /// Damage taken over the elapsed time. Zero when pushing back or while inside.
+    pub fn get_damage(&self, position: Vec3A, delta_time: f32) -> f32 {
+        if self.response == BoundsResponse::Damage && !self.contains(position) {
+            self.damage_per_second * delta_time
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use crate::game::shared::util::math::WORLD_UP;
+use crate::game::ui::TweakRegistry;
+use crate::game::Camera;
+use glam::Vec3A;
+use rodio::source::Buffered;
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source, SpatialSink};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+
+/// 耳の間の距離の半分（メートル）。<br />
+/// Half of the distance between the ears, in meters.
+const EAR_OFFSET: f32 = 0.1;
+
+/// この距離までは減衰しない（メートル）。<br />
+/// Sounds are not attenuated within this distance, in meters.
+const REFERENCE_DISTANCE: f32 = 2.0;
+
+/// これより遠い音は再生しない（メートル）。<br />
+/// Sounds farther than this distance are not played, in meters.
+const MAX_DISTANCE: f32 = 60.0;
+
+/// 距離による減衰の強さ。<br />
+/// Strength of the attenuation by distance.
+const ROLLOFF_FACTOR: f32 = 1.0;
+
+const MASTER_VOLUME_TWEAK: &str = "Audio/Master Volume";
+const MUSIC_VOLUME_TWEAK: &str = "Audio/Music Volume";
+const EFFECT_VOLUME_TWEAK: &str = "Audio/Effect Volume";
+
+type SoundSource = Buffered<Decoder<BufReader<File>>>;
+
+/// 再生中の3D効果音。<br />
+/// A 3D sound effect being played.
+struct PositionalSound {
+    sink: SpatialSink,
+    position: Vec3A,
+}
+
+/// 層になったBGMの一つの音源。<br />
+/// A stem of layered music.
+struct MusicStem {
+    sink: Sink,
+    volume: f32,
+}
+
+/// BGMのストリーミング再生、効果音、カメラを基準にした3D効果音を扱う。<br />
+/// オーディオデバイスがない場合は何も再生しない。<br />
+/// Handles streaming background music, sound effects, and 3D sound effects relative to the camera.<br />
+/// Nothing is played if there is no audio device.
+pub struct AudioSystem {
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    music: Option<Sink>,
+    music_stems: Vec<MusicStem>,
+    sounds: HashMap<String, SoundSource>,
+    positional_sounds: Vec<PositionalSound>,
+    listener_position: Vec3A,
+    listener_right: Vec3A,
+    master_volume: f32,
+    music_volume: f32,
+    effect_volume: f32,
+}
+
+impl Default for AudioSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioSystem {
+    pub fn new() -> Self {
+        let output = match OutputStream::try_default() {
+            Ok(output) => Some(output),
+            Err(e) => {
+                log::warn!("Failed to open audio output, audio is disabled: {}", e);
+                None
+            }
+        };
+        AudioSystem {
+            output,
+            music: None,
+            music_stems: vec![],
+            sounds: HashMap::new(),
+            positional_sounds: vec![],
+            listener_position: Vec3A::zero(),
+            listener_right: Vec3A::new(1.0, 0.0, 0.0),
+            master_volume: 1.0,
+            music_volume: 0.5,
+            effect_volume: 1.0,
+        }
+    }
+
+    /// 効果音を読み込んでデコード済みのデータを保持する。<br />
+    /// Load a sound effect and keep its decoded data.
+    pub fn load_sound(&mut self, sound_name: &str, file_name: &str) -> anyhow::Result<()> {
+        let file = File::open(file_name)?;
+        let source = Decoder::new(BufReader::new(file))?.buffered();
+        self.sounds.insert(sound_name.to_string(), source);
+        Ok(())
+    }
+
+    /// BGMをファイルからストリーミング再生する。既に再生中のBGMは止める。<br />
+    /// Stream background music from a file, stopping any music already playing.
+    pub fn play_music(&mut self, file_name: &str, is_looping: bool) -> anyhow::Result<()> {
+        self.stop_music();
+        let handle = match self.output.as_ref() {
+            Some((_, handle)) => handle,
+            None => return Ok(()),
+        };
+        let file = File::open(file_name)?;
+        let source = Decoder::new(BufReader::new(file))?;
+        let sink = Sink::try_new(handle)?;
+        sink.set_volume(self.master_volume * self.music_volume);
+        if is_looping {
+            sink.append(source.repeat_infinite());
+        } else {
+            sink.append(source);
+        }
+        self.music = Some(sink);
+        Ok(())
+    }
+
+    /// 層になったBGMの音源を全てループで同時に再生し始める。最初の音源だけを聞こえる音量にする。<br />
+    /// Start playing all stems of layered music together in a loop. Only the first stem starts audible.
+    pub fn play_music_stems(&mut self, file_names: &[&str]) -> anyhow::Result<()> {
+        self.stop_music();
+        let handle = match self.output.as_ref() {
+            Some((_, handle)) => handle,
+            None => return Ok(()),
+        };
+        let mut stems = vec![];
+        for (i, file_name) in file_names.iter().enumerate() {
+            let file = File::open(file_name)?;
+            let source = Decoder::new(BufReader::new(file))?;
+            let sink = Sink::try_new(handle)?;
+            // 拍がずれないように、全ての音源を用意してから一斉に再生する。
+            // All stems are prepared before playing them at once so their beats stay aligned.
+            sink.pause();
+            sink.append(source.repeat_infinite());
+            stems.push(MusicStem {
+                sink,
+                volume: if i == 0 { 1.0 } else { 0.0 },
+            });
+        }
+        self.music_stems = stems;
+        self.update_music_volume();
+        for stem in self.music_stems.iter() {
+            stem.sink.play();
+        }
+        Ok(())
+    }
+
+    /// 層になったBGMの音源ごとの音量を変える。BGMの音量と掛け合わせる。<br />
+    /// Change the volume of each stem of layered music, multiplied by the music volume.
+    pub fn set_music_stem_volumes(&mut self, volumes: &[f32]) {
+        for (stem, volume) in self.music_stems.iter_mut().zip(volumes.iter()) {
+            stem.volume = volume.max(0.0).min(1.0);
+        }
+        self.update_music_volume();
+    }
+
+    pub fn stop_music(&mut self) {
+        if let Some(music) = self.music.take() {
+            music.stop();
+        }
+        for stem in self.music_stems.drain(..) {
+            stem.sink.stop();
+        }
+    }
+
+    /// 位置を持たない効果音を一度だけ再生する。<br />
+    /// Play a sound effect without a position once.
+    pub fn play_sound(&self, sound_name: &str) -> anyhow::Result<()> {
+        let handle = match self.output.as_ref() {
+            Some((_, handle)) => handle,
+            None => return Ok(()),
+        };
+        let source = self.get_sound(sound_name)?;
+        let sink = Sink::try_new(handle)?;
+        sink.set_volume(self.master_volume * self.effect_volume);
+        sink.append(source);
+        sink.detach();
+        Ok(())
+    }
+
+    /// ワールド内の位置で効果音を一度だけ再生する。遠すぎる場合は再生しない。<br />
+    /// Play a sound effect once at a position in the world. Nothing is played if it is too far away.
+    pub fn play_sound_at(&mut self, sound_name: &str, position: Vec3A) -> anyhow::Result<()> {
+        let handle = match self.output.as_ref() {
+            Some((_, handle)) => handle,
+            None => return Ok(()),
+        };
+        let source = self.get_sound(sound_name)?;
+        if (position - self.listener_position).length() > MAX_DISTANCE {
+            return Ok(());
+        }
+        let (left_ear, right_ear) = self.get_ear_positions();
+        let sink = SpatialSink::try_new(
+            handle,
+            self.get_emitter_position(position),
+            left_ear,
+            right_ear,
+        )?;
+        sink.set_volume(self.get_positional_volume(position));
+        sink.append(source);
+        self.positional_sounds
+            .push(PositionalSound { sink, position });
+        Ok(())
+    }
+
+    /// 音量を調整パネルに登録する。<br />
+    /// Register the volumes to the tweak panel.
+    pub fn register_tweaks(&mut self, tweaks: &mut TweakRegistry) {
+        tweaks.register_f32(MASTER_VOLUME_TWEAK, self.master_volume, 0.0, 1.0);
+        tweaks.register_f32(MUSIC_VOLUME_TWEAK, self.music_volume, 0.0, 1.0);
+        tweaks.register_f32(EFFECT_VOLUME_TWEAK, self.effect_volume, 0.0, 1.0);
+        self.apply_tweaks(tweaks);
+    }
+
+    pub fn apply_tweaks(&mut self, tweaks: &TweakRegistry) {
+        if let Some(volume) = tweaks.get_f32(MASTER_VOLUME_TWEAK) {
+            self.set_master_volume(volume);
+        }
+        if let Some(volume) = tweaks.get_f32(MUSIC_VOLUME_TWEAK) {
+            self.set_music_volume(volume);
+        }
+        if let Some(volume) = tweaks.get_f32(EFFECT_VOLUME_TWEAK) {
+            self.set_effect_volume(volume);
+        }
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.max(0.0).min(1.0);
+        self.update_music_volume();
+    }
+
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.music_volume = volume.max(0.0).min(1.0);
+        self.update_music_volume();
+    }
+
+    pub fn set_effect_volume(&mut self, volume: f32) {
+        self.effect_volume = volume.max(0.0).min(1.0);
+    }
+
+    /// カメラの位置と向きを聞き手として、再生中の3D効果音を更新する。<br />
+    /// Update the 3D sound effects being played, using the camera's position and orientation as the listener.
+    pub fn update(&mut self, camera: &Camera) {
+        self.listener_position = camera.position;
+        let forward = camera.target - camera.position;
+        let right = forward.cross(Vec3A::from(WORLD_UP));
+        if right.length_squared() > f32::EPSILON {
+            self.listener_right = right.normalize();
+        }
+
+        self.positional_sounds.retain(|sound| !sound.sink.empty());
+        let (left_ear, right_ear) = self.get_ear_positions();
+        for sound in self.positional_sounds.iter() {
+            sound.sink.set_left_ear_position(left_ear);
+            sound.sink.set_right_ear_position(right_ear);
+            sound
+                .sink
+                .set_emitter_position(self.get_emitter_position(sound.position));
+            sound
+                .sink
+                .set_volume(self.get_positional_volume(sound.position));
+        }
+    }
+
+    fn get_sound(&self, sound_name: &str) -> anyhow::Result<SoundSource> {
+        self.sounds
+            .get(sound_name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Sound {} is not loaded.", sound_name))
+    }
+
+    fn get_ear_positions(&self) -> ([f32; 3], [f32; 3]) {
+        let left = self.listener_position - self.listener_right * EAR_OFFSET;
+        let right = self.listener_position + self.listener_right * EAR_OFFSET;
+        (left.into(), right.into())
+    }
+
+    /// 減衰は自前で計算するため、音源は方向だけを保って聞き手から1メートルの位置に置く。<br />
+    /// Attenuation is computed here, so the emitter is placed one meter from the listener, keeping only its direction.
+    fn get_emitter_position(&self, position: Vec3A) -> [f32; 3] {
+        let offset = position - self.listener_position;
+        if offset.length_squared() < f32::EPSILON {
+            return self.listener_position.into();
+        }
+        (self.listener_position + offset.normalize()).into()
+    }
+
+    fn get_positional_volume(&self, position: Vec3A) -> f32 {
+        let distance = (position - self.listener_position).length();
+        self.master_volume * self.effect_volume * get_distance_attenuation(distance)
+    }
+
+    fn update_music_volume(&self) {
+        if let Some(music) = self.music.as_ref() {
+            music.set_volume(self.master_volume * self.music_volume);
+        }
+        for stem in self.music_stems.iter() {
+            stem.sink
+                .set_volume(self.master_volume * self.music_volume * stem.volume);
+        }
+    }
+}
+
+/// 逆距離モデルで、聞き手からの距離による音量の倍率を求める。遠すぎる場合は0。<br />
+/// Compute the volume multiplier by distance from the listener with the inverse distance model. Zero if too far away.
+pub fn get_distance_attenuation(distance: f32) -> f32 {
+    if distance > MAX_DISTANCE {
+        return 0.0;
+    }
+    let distance = distance.max(REFERENCE_DISTANCE);
+    REFERENCE_DISTANCE / (REFERENCE_DISTANCE + ROLLOFF_FACTOR * (distance - REFERENCE_DISTANCE))
+}
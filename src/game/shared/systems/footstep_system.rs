@@ -0,0 +1,136 @@
+use crate::game::shared::components::{Bounds, Surface};
+use crate::game::shared::enums::SurfaceMaterial;
+use crate::game::shared::structs::{
+    find_prop_material, DustPuff, SurfaceMaterialMap, FOOTSTEP_EVENT,
+};
+use crate::game::shared::systems::{AudioSystem, PhysicsSystem};
+use crate::game::traits::{Disposable, GraphicsBase};
+use crate::game::{Transform, World};
+use glam::Vec3A;
+use slotmap::DefaultKey;
+use std::collections::{HashMap, VecDeque};
+
+/// 取り出されるまで溜めておく土煙の最大数。古いものから捨てる。<br />
+/// Maximum number of dust puffs kept until they are taken. The oldest ones are dropped first.
+const MAX_PENDING_PUFFS: usize = 64;
+
+/// 足音と一緒に出す土煙。<br />
+/// A dust puff spawned along with a footstep.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FootstepPuff {
+    pub position: Vec3A,
+    pub material: SurfaceMaterial,
+    pub puff: DustPuff,
+}
+
+/// アニメーションの足音のイベントを受けて、足元の材質に合った効果音を鳴らし、土煙を出す。<br />
+/// 小物の材質は地形より優先する。<br />
+/// Receives footstep events from animations, playing the sound and spawning the dust puff matching the material underfoot.<br />
+/// Materials of props take precedence over the terrain.
+pub struct FootstepSystem {
+    surface_map: Option<SurfaceMaterialMap>,
+    /// エンティティごとの歩数。効果音を順番に選ぶのに使う。<br />
+    /// Step count of each entity, used to pick the sounds in turn.
+    step_counts: HashMap<DefaultKey, usize>,
+    puffs: VecDeque<FootstepPuff>,
+}
+
+impl Default for FootstepSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FootstepSystem {
+    pub fn new() -> Self {
+        FootstepSystem {
+            surface_map: None,
+            step_counts: HashMap::new(),
+            puffs: VecDeque::new(),
+        }
+    }
+
+    /// 材質の対応表を読み込み、足音を全て読み込む。読み込めない足音は鳴らさない。<br />
+    /// Load the surface-material map and all of its footstep sounds. Sounds that fail to load stay silent.
+    pub fn load(&mut self, file_name: &str, audio_system: &mut AudioSystem) -> anyhow::Result<()> {
+        let surface_map = SurfaceMaterialMap::load(file_name)?;
+        for sound in surface_map.get_sound_files() {
+            if let Err(e) = audio_system.load_sound(sound, sound) {
+                log::warn!("Failed to load footstep sound {}: {}", sound, e);
+            }
+        }
+        self.surface_map = Some(surface_map);
+        Ok(())
+    }
+
+    /// アニメーションのイベントのうち足音を処理する。<br />
+    /// Handle the footsteps among animation events.
+    pub fn update<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        events: &[(DefaultKey, String)],
+        world: &World<GraphicsType, BufferType, CommandType, TextureType>,
+        physics_system: &PhysicsSystem,
+        audio_system: &mut AudioSystem,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        self.step_counts.retain(|entity, _| world.contains(*entity));
+        let surface_map = match self.surface_map.as_ref() {
+            Some(surface_map) => surface_map,
+            None => return,
+        };
+        for (entity, _) in events.iter().filter(|(_, name)| name == FOOTSTEP_EVENT) {
+            let position = match world.get::<Transform>(*entity) {
+                Some(transform) => transform.position,
+                None => continue,
+            };
+            let props = world
+                .iter::<Surface>()
+                .filter(|(prop, _)| prop != entity)
+                .filter_map(|(prop, surface)| {
+                    let bounds = world.get::<Bounds>(prop)?;
+                    let bounds = match world.get::<Transform>(prop) {
+                        Some(transform) => bounds.transform(transform.get_world_matrix()),
+                        None => *bounds,
+                    };
+                    Some((bounds, surface.material))
+                });
+            let material = find_prop_material(props, position)
+                .or_else(|| {
+                    surface_map.get_ground_material(position.x, position.z, |x, z| {
+                        physics_system.get_ground_height(x, z)
+                    })
+                })
+                .unwrap_or(surface_map.default_material);
+            let footstep_set = match surface_map.get_footstep_set(material) {
+                Some(footstep_set) => footstep_set,
+                None => continue,
+            };
+
+            let step_count = self.step_counts.entry(*entity).or_insert(0);
+            let sound = &footstep_set.sounds[*step_count % footstep_set.sounds.len()];
+            *step_count += 1;
+            if let Err(e) = audio_system.play_sound_at(sound, position) {
+                log::warn!("Failed to play footstep sound {}: {}", sound, e);
+            }
+
+            if self.puffs.len() >= MAX_PENDING_PUFFS {
+                self.puffs.pop_front();
+            }
+            self.puffs.push_back(FootstepPuff {
+                position,
+                material,
+                puff: footstep_set.puff,
+            });
+        }
+    }
+
+    /// 溜まった土煙を出た順番で取り出す。<br />
+    /// Take the pending dust puffs in the order they were spawned.
+    pub fn take_puffs(&mut self) -> Vec<FootstepPuff> {
+        self.puffs.drain(..).collect()
+    }
+}
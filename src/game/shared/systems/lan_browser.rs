@@ -0,0 +1,73 @@
+use crate::game::shared::structs::games::fragment::MAX_DATAGRAM_SIZE;
+use crate::game::shared::structs::games::lan_discovery::{
+    DiscoveredHost, DiscoveredHosts, LanAnnouncement, DISCOVERY_PORT,
+};
+use parking_lot::Mutex;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::UdpSocket;
+use tokio::sync::broadcast;
+
+/// LANのリッスンサーバーの告知を受け取り、見つかったホストを一覧にする。<br />
+/// ドロップすると受信をやめる。<br />
+/// Receives announcements of listen servers on the LAN and lists the hosts found.<br />
+/// Stops listening when it's dropped.
+pub struct LanBrowser {
+    hosts: Arc<Mutex<DiscoveredHosts>>,
+    _shutdown: broadcast::Sender<()>,
+}
+
+impl LanBrowser {
+    /// 告知のポートにバインドし、バックグラウンドのタスクで受信する。<br />
+    /// Bind to the discovery port and listen on a background task.
+    pub async fn start() -> anyhow::Result<Self> {
+        let socket =
+            UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))).await?;
+        let hosts = Arc::new(Mutex::new(DiscoveredHosts::new()));
+        let (shutdown, _) = broadcast::channel(1);
+        tokio::spawn(Self::listen(socket, hosts.clone(), shutdown.subscribe()));
+        log::info!("Browsing LAN hosts on port {}.", DISCOVERY_PORT);
+        Ok(LanBrowser {
+            hosts,
+            _shutdown: shutdown,
+        })
+    }
+
+    /// 今見えているホスト。告知が途絶えたホストはここで外す。<br />
+    /// Hosts currently visible. Hosts that stopped announcing are removed here.
+    pub fn get_hosts(&self) -> Vec<DiscoveredHost> {
+        let mut hosts = self.hosts.lock();
+        hosts.remove_stale(Instant::now());
+        hosts.get_hosts().to_vec()
+    }
+
+    async fn listen(
+        mut socket: UdpSocket,
+        hosts: Arc<Mutex<DiscoveredHosts>>,
+        mut shutdown: broadcast::Receiver<()>,
+    ) {
+        let mut buffer = [0_u8; MAX_DATAGRAM_SIZE];
+        loop {
+            let (size, address) = tokio::select! {
+                result = socket.recv_from(&mut buffer[0..]) => match result {
+                    Ok(r) => r,
+                    Err(e) => {
+                        log::warn!("Failed to receive announcement: {}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown.recv() => break,
+            };
+            // 違うバージョンのホストや関係のないパケットは黙って無視する。
+            // Hosts of a different version and unrelated packets are silently ignored.
+            match LanAnnouncement::decode(&buffer[..size]) {
+                Ok(announcement) => hosts
+                    .lock()
+                    .update(address.ip(), announcement, Instant::now()),
+                Err(e) => log::debug!("Ignored datagram from {}: {}", address, e),
+            }
+        }
+        log::info!("Stopped browsing LAN hosts.");
+    }
+}
@@ -0,0 +1,958 @@
+use crate::game::shared::structs::games::fragment::MAX_DATAGRAM_SIZE;
+use crate::game::shared::structs::games::lan_discovery::{
+    LanAnnouncement, ANNOUNCE_INTERVAL, DISCOVERY_PORT,
+};
+use crate::game::shared::structs::games::match_result::{settle_player, MatchOutcome};
+use crate::game::shared::structs::games::player_settings::sanitize_settings;
+use crate::game::shared::structs::games::protocol::{BUILD_VERSION, PROTOCOL_VERSION};
+use crate::game::shared::structs::games::replication::EntityRegistry;
+use crate::game::shared::structs::games::snapshot::{
+    PlayerSnapshot, SnapshotDecoder, SnapshotEncoder,
+};
+use crate::game::shared::structs::games::terrain_patch::decode_patches;
+use crate::game::shared::structs::games::terrain_payload::parse_terrain_payload;
+use crate::game::shared::structs::games::udp_session::{Received, SessionTable};
+use crate::game::shared::structs::games::PlayerUdp;
+use crate::game::shared::structs::{
+    PositionInfo, Settings, LISTEN_SERVER_GRPC_PORT, LISTEN_SERVER_UDP_PORT,
+};
+use crate::game::shared::util::get_random_string;
+use crate::protos::grpc_service::game_state::{
+    EntityState, GetTerrainReply, GetTerrainRequest, Player, PlayerState, ProgressGameRequest,
+    RegisterPlayerRequest, ReportMatchResultReply, ReportMatchResultRequest, RoomState, Rooms,
+    StartGameRequest, UpdateTerrainRequest, WorldMatrix,
+};
+use crate::protos::grpc_service::grpc_service_server::{GrpcService, GrpcServiceServer};
+use crate::protos::grpc_service::{
+    Empty, HandshakeReply, HandshakeRequest, IncomingMessage, IncomingMessages, LoginReply,
+    LoginRequest, MessageRecord, RegisterReply, RegisterRequest,
+};
+use crate::protos::jwt_token_service::access_reply::user::UserType;
+use crate::protos::jwt_token_service::access_reply::User;
+use crate::protos::jwt_token_service::jwt_token_service_server::{
+    JwtTokenService, JwtTokenServiceServer,
+};
+use crate::protos::jwt_token_service::{AccessReply, AccessRequest};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, mpsc};
+use tonic::{Request, Response, Status, Streaming};
+
+/// 部屋を作る時に人数が指定されなかった場合の最大人数。<br />
+/// Maximum number of players of a room when none is given on creation.
+const DEFAULT_MAX_PLAYERS: i32 = 4;
+
+/// 保持するチャットの履歴の数。<br />
+/// Number of chat messages kept in the history.
+const CHAT_HISTORY_SIZE: usize = 50;
+
+/// 各ストリームの送信キューの長さ。溢れたメッセージは遅いクライアントに届かない。<br />
+/// Length of the send queue of each stream. Messages overflowing it don't reach slow clients.
+const STREAM_CAPACITY: usize = 64;
+
+/// リッスンサーバーの設定。<br />
+/// Settings of the listen server.
+#[derive(Clone, Debug)]
+pub struct ListenServerSettings {
+    /// LANに告知するサーバーの名前。<br />
+    /// Name of the server announced on the LAN.
+    pub server_name: String,
+    pub grpc_port: u16,
+    pub udp_port: u16,
+    /// LANにブロードキャストで告知するか。<br />
+    /// Whether to announce the server on the LAN via broadcast.
+    pub is_announced: bool,
+}
+
+impl Default for ListenServerSettings {
+    fn default() -> Self {
+        ListenServerSettings {
+            server_name: "Demo game".to_string(),
+            grpc_port: LISTEN_SERVER_GRPC_PORT,
+            udp_port: LISTEN_SERVER_UDP_PORT,
+            is_announced: true,
+        }
+    }
+}
+
+impl ListenServerSettings {
+    /// 起動時の設定の`host_name`、`host_grpc_port`と`host_udp_port`から読み込む。<br />
+    /// Read from `host_name`, `host_grpc_port` and `host_udp_port` of the startup settings.
+    pub fn from_settings(settings: &Settings) -> Self {
+        ListenServerSettings {
+            server_name: settings.host_name.clone(),
+            grpc_port: settings.host_grpc_port,
+            udp_port: settings.host_udp_port,
+            ..Self::default()
+        }
+    }
+
+    /// ホストしているクライアント自身が接続するgRPCのエンドポイント。<br />
+    /// gRPC endpoint the hosting client itself connects to.
+    pub fn get_local_server_endpoint(&self) -> String {
+        format!("http://{}:{}", Ipv4Addr::LOCALHOST, self.grpc_port)
+    }
+
+    /// ホストしているクライアント自身が接続するUDPのエンドポイント。<br />
+    /// UDP endpoint the hosting client itself connects to.
+    pub fn get_local_udp_endpoint(&self) -> String {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, self.udp_port)).to_string()
+    }
+}
+
+/// サーバーにいる部屋。<br />
+/// A room on the server.
+struct HostedRoom {
+    state: RoomState,
+    /// ホストが生成した地形。ゲームが始まるまでは空。<br />
+    /// Terrain generated by the host. Empty until the game starts.
+    terrain_vertices: Vec<u8>,
+    /// ゲームが始まった後に変更されたチャンクのパッチ。途中から参加したプレイヤーに地形と一緒に配る。<br />
+    /// Patches of the chunks modified after the game started, handed out with the terrain to players joining later.
+    terrain_patches: Vec<u8>,
+    /// UDPで受け取った各プレイヤーの最新の状態。<br />
+    /// Latest state of each player received over UDP.
+    snapshots: HashMap<String, PlayerSnapshot>,
+    /// プレイヤーが持つエンティティ。持ち主から届いたものだけを受け付ける。<br />
+    /// Entities owned by players. Only the ones sent by their owners are accepted.
+    entities: EntityRegistry,
+    /// 最初に報告された試合の決着。後から報告したプレイヤーもこれに従う。<br />
+    /// Outcome of the match from the first report. Players reporting later follow it as well.
+    outcome: Option<MatchOutcome>,
+    subscribers: Vec<mpsc::Sender<Result<RoomState, Status>>>,
+}
+
+impl HostedRoom {
+    /// 部屋の最新のステートを購読しているクライアントに送る。接続が切れたクライアントは取り除く。<br />
+    /// Send the latest room state to subscribed clients, removing the ones that disconnected.
+    fn broadcast(&mut self) {
+        let state = &self.state;
+        self.subscribers = self
+            .subscribers
+            .drain(..)
+            .filter_map(
+                |mut subscriber| match subscriber.try_send(Ok(state.clone())) {
+                    Err(mpsc::error::TrySendError::Closed(_)) => None,
+                    _ => Some(subscriber),
+                },
+            )
+            .collect();
+    }
+}
+
+/// サーバーの全ての状態。データベースは無く、サーバーを止めると消える。<br />
+/// The whole state of the server. There is no database, so it's gone when the server stops.
+#[derive(Default)]
+struct ServerState {
+    /// ユーザー名をキーにしたアカウント。パスワードは持たず、クライアントにそのまま送れる。<br />
+    /// Accounts keyed by the user name. They don't hold passwords, so they can be sent to clients as they are.
+    players: HashMap<String, Player>,
+    /// ユーザー名をキーにした、ソルト付きのパスワードのハッシュ。<br />
+    /// Salted password hashes keyed by the user name.
+    password_hashes: HashMap<String, String>,
+    rooms: HashMap<String, HostedRoom>,
+    chat_history: VecDeque<IncomingMessage>,
+    chat_subscribers: Vec<mpsc::Sender<Result<IncomingMessage, Status>>>,
+    /// 発行したJWTトークンと、それでログインしたプレイヤーのID。ログインするまでは空。<br />
+    /// Issued JWT tokens and the ID of the player who logged in with each. Empty until logging in.
+    tokens: HashMap<String, String>,
+    /// 発行したリフレッシュトークンと、そのアカウントのユーザー名。一度使うと破棄する。<br />
+    /// Issued refresh tokens and the user name of their account. Each is discarded once used.
+    refresh_tokens: HashMap<String, String>,
+}
+
+impl ServerState {
+    fn find_player(&self, player_id: &str) -> Option<&Player> {
+        self.players.values().find(|p| p.player_id == player_id)
+    }
+
+    /// トークンが発行したものか確かめる。<br />
+    /// Check that the token was issued by this server.
+    fn check_token(&self, token: &str) -> Result<(), Status> {
+        if self.tokens.contains_key(token) {
+            Ok(())
+        } else {
+            Err(Status::unauthenticated("The JWT token was rejected."))
+        }
+    }
+
+    /// トークンでログインしたプレイヤーのIDを返す。<br />
+    /// Return the ID of the player who logged in with the token.
+    fn get_logged_player_id(&self, token: &str) -> Result<&str, Status> {
+        match self.tokens.get(token) {
+            Some(player_id) if !player_id.is_empty() => Ok(player_id),
+            _ => Err(Status::unauthenticated("The player hasn't logged in.")),
+        }
+    }
+
+    fn find_room_of(&self, player_id: &str) -> Option<&str> {
+        self.rooms
+            .iter()
+            .find(|(_, room)| room.state.players.iter().any(|p| p.player_id == player_id))
+            .map(|(room_id, _)| room_id.as_str())
+    }
+
+    fn create_announcement(&self, settings: &ListenServerSettings) -> LanAnnouncement {
+        LanAnnouncement {
+            server_name: settings.server_name.clone(),
+            build_version: BUILD_VERSION.to_string(),
+            grpc_port: settings.grpc_port,
+            udp_port: settings.udp_port,
+            open_rooms: self.rooms.values().filter(|r| !r.state.started).count() as u32,
+            current_players: self
+                .rooms
+                .values()
+                .map(|r| r.state.current_players.max(0) as u32)
+                .sum(),
+        }
+    }
+}
+
+/// 中央のサーバーの代わりに、クライアントの中で動くサーバー。<br />
+/// gRPCのサービスと同じプロトコルを話すので、他のクライアントはそのまま接続できる。<br />
+/// A server running inside the client in place of the central server.<br />
+/// It speaks the same protocol as the gRPC services, so other clients can join without any changes.
+#[derive(Clone)]
+pub struct ListenServer {
+    state: Arc<Mutex<ServerState>>,
+}
+
+/// 動いているリッスンサーバーのハンドル。ドロップするとサーバーが止まる。<br />
+/// Handle of a running listen server. The server stops when it's dropped.
+pub struct ListenServerHandle {
+    pub settings: ListenServerSettings,
+    _shutdown: broadcast::Sender<()>,
+}
+
+impl ListenServer {
+    /// サーバーをLANにバインドし、バックグラウンドのタスクで動かす。<br />
+    /// Bind the server to the LAN and run it on background tasks.
+    pub async fn start(settings: ListenServerSettings) -> anyhow::Result<ListenServerHandle> {
+        let server = ListenServer {
+            state: Arc::new(Mutex::new(ServerState::default())),
+        };
+        let (shutdown, _) = broadcast::channel(1);
+
+        let udp_socket =
+            UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, settings.udp_port))).await?;
+        tokio::spawn(
+            server
+                .clone()
+                .relay_snapshots(udp_socket, shutdown.subscribe()),
+        );
+
+        let grpc_address = SocketAddr::from((Ipv4Addr::UNSPECIFIED, settings.grpc_port));
+        let mut grpc_shutdown = shutdown.subscribe();
+        let router = tonic::transport::Server::builder()
+            .add_service(JwtTokenServiceServer::new(server.clone()))
+            .add_service(GrpcServiceServer::new(server.clone()));
+        tokio::spawn(async move {
+            let result = router
+                .serve_with_shutdown(grpc_address, async move {
+                    grpc_shutdown.recv().await.ok();
+                })
+                .await;
+            if let Err(e) = result {
+                log::error!("Listen server stopped with an error: {}", e);
+            }
+        });
+
+        if settings.is_announced {
+            tokio::spawn(server.announce(settings.clone(), shutdown.subscribe()));
+        }
+        log::info!(
+            "Hosting on gRPC port {} and UDP port {}.",
+            settings.grpc_port,
+            settings.udp_port
+        );
+        Ok(ListenServerHandle {
+            settings,
+            _shutdown: shutdown,
+        })
+    }
+
+    /// クライアントから受け取ったスナップショットを部屋に反映し、部屋の全員の状態を返す。<br />
+    /// クライアントごとにエンコーダーとデコーダーを持つので、差分と確認応答は接続ごとに独立している。<br />
+    /// スナップショットはセッションを開いた後にしか受け付けず、セッションを開いたプレイヤーの状態しか更新しない。<br />
+    /// Apply the snapshots received from clients to their rooms, and reply with the state of everyone in the room.<br />
+    /// Each client has its own encoder and decoder, so deltas and acknowledgements are independent per connection.<br />
+    /// Snapshots are only accepted once a session is open, and only update the player who opened it.
+    async fn relay_snapshots(self, mut socket: UdpSocket, mut shutdown: broadcast::Receiver<()>) {
+        let mut peers: HashMap<SocketAddr, (SnapshotEncoder, SnapshotDecoder)> = HashMap::new();
+        let mut sessions = SessionTable::new(rand::random());
+        let mut buffer = [0_u8; MAX_DATAGRAM_SIZE];
+        loop {
+            let (size, address) = tokio::select! {
+                result = socket.recv_from(&mut buffer[0..]) => match result {
+                    Ok(received) => received,
+                    Err(e) => {
+                        // 相手のポートが閉じていた場合などにも返ってくるので、止めずに続ける。
+                        // This is also returned when the peer's port was closed, so keep going.
+                        log::warn!("Failed to receive UDP packet: {}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown.recv() => break,
+            };
+
+            let now = Instant::now();
+            for (address, session) in sessions.remove_expired(now) {
+                peers.remove(&address);
+                // 切断したプレイヤーのエンティティは、他のクライアントから消える。
+                // Entities of a disconnected player disappear from the other clients.
+                let mut state = self.state.lock();
+                let room_id = state.find_room_of(&session.player_id).map(str::to_string);
+                if let Some(room) = room_id.and_then(|id| state.rooms.get_mut(&id)) {
+                    room.entities.remove_owner(&session.player_id);
+                }
+                drop(state);
+                log::info!(
+                    "Closed the UDP session of {} at {}.",
+                    session.player_id,
+                    address
+                );
+            }
+            let state = &self.state;
+            let received = sessions.receive(address, &buffer[0..size], now, |token, player_id| {
+                state.lock().tokens.get(token).map(String::as_str) == Some(player_id)
+            });
+            let datagram = match received {
+                Ok(Received::Datagram(datagram)) => datagram,
+                Ok(Received::Reply(reply)) => {
+                    if let Err(e) = socket.send_to(&reply, address).await {
+                        log::warn!("Failed to send UDP packet to {}: {}", address, e);
+                    }
+                    continue;
+                }
+                Ok(Received::Nothing) => continue,
+                Err(e) => {
+                    log::warn!(
+                        "Rejected datagram of {} bytes from {}: {}",
+                        size,
+                        address,
+                        e
+                    );
+                    continue;
+                }
+            };
+            let player_id = match sessions.get(&address) {
+                Some(session) => session.player_id.clone(),
+                None => continue,
+            };
+
+            let (encoder, decoder) = peers
+                .entry(address)
+                .or_insert_with(|| (SnapshotEncoder::new(), SnapshotDecoder::new()));
+            let (snapshot, ack) = match decoder.decode(&datagram) {
+                Ok(Some(result)) => result,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::warn!(
+                        "Rejected datagram of {} bytes from {}: {}",
+                        size,
+                        address,
+                        e
+                    );
+                    continue;
+                }
+            };
+            if let Some(ack) = ack {
+                encoder.acknowledge(ack);
+            }
+
+            let (started, players, entities) = {
+                let mut state = self.state.lock();
+                let room_id = match state.find_room_of(&player_id) {
+                    Some(room_id) => room_id.to_string(),
+                    None => continue,
+                };
+                let room = state
+                    .rooms
+                    .get_mut(&room_id)
+                    .expect("Failed to get the room of the player.");
+                // クライアントは自分の状態しか更新できない。
+                // Clients can only update their own state.
+                if let Some(player) = snapshot
+                    .players
+                    .into_iter()
+                    .find(|p| p.player_id == player_id)
+                {
+                    room.snapshots.insert(player_id.clone(), player);
+                }
+                // エンティティも同じく、持ち主のものしか更新できない。
+                // Likewise, only the owner can update an entity.
+                let rejected = room.entities.accept(&player_id, snapshot.entities);
+                if rejected > 0 {
+                    log::warn!(
+                        "Rejected {} entities sent by {}: not owned or over the limit.",
+                        rejected,
+                        player_id
+                    );
+                }
+                let mut players = room.snapshots.values().cloned().collect::<Vec<_>>();
+                players.sort_by(|a, b| a.player_id.cmp(&b.player_id));
+                (room.state.started, players, room.entities.get_entities())
+            };
+
+            let datagrams = match encoder.encode(started, players, entities, decoder.get_ack()) {
+                Ok(d) => d,
+                Err(e) => {
+                    log::error!("Failed to encode snapshot: {}", e);
+                    continue;
+                }
+            };
+            for datagram in datagrams.iter() {
+                let packet = match sessions.wrap(&address, datagram) {
+                    Some(packet) => packet,
+                    None => break,
+                };
+                if let Err(e) = socket.send_to(&packet, address).await {
+                    log::warn!("Failed to send UDP packet to {}: {}", address, e);
+                    break;
+                }
+            }
+        }
+        log::info!("Stopped relaying snapshots.");
+    }
+
+    /// LANのブロードキャストアドレスに定期的にサーバーを告知する。<br />
+    /// Periodically announce the server to the broadcast address of the LAN.
+    async fn announce(self, settings: ListenServerSettings, mut shutdown: broadcast::Receiver<()>) {
+        let mut socket = match UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).await {
+            Ok(socket) => socket,
+            Err(e) => {
+                log::error!("Failed to bind the discovery socket: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = socket.set_broadcast(true) {
+            log::error!("Failed to enable broadcasting: {}", e);
+            return;
+        }
+        let target = SocketAddr::from((Ipv4Addr::BROADCAST, DISCOVERY_PORT));
+        let mut interval = tokio::time::interval(ANNOUNCE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.recv() => break,
+            }
+            let announcement = self.state.lock().create_announcement(&settings);
+            let datagram = match announcement.encode() {
+                Ok(d) => d,
+                Err(e) => {
+                    log::error!("Failed to encode announcement: {}", e);
+                    break;
+                }
+            };
+            if let Err(e) = socket.send_to(&datagram, target).await {
+                log::warn!("Failed to broadcast announcement: {}", e);
+            }
+        }
+    }
+
+    /// アカウントを作る。パスワードはハッシュにして別に保存する。<br />
+    /// Create an account. The password is hashed and stored separately.
+    fn create_player(
+        state: &mut ServerState,
+        user_name: &str,
+        nickname: &str,
+        email: &str,
+        password: &str,
+    ) -> Result<Player, Status> {
+        let salt: [u8; 16] = rand::random();
+        let password_hash =
+            argon2::hash_encoded(password.as_bytes(), &salt, &argon2::Config::default())
+                .map_err(|e| Status::internal(format!("Failed to hash the password: {}", e)))?;
+        let player = Player {
+            player_id: get_random_string(16),
+            user_name: user_name.to_string(),
+            nickname: nickname.to_string(),
+            email: email.to_string(),
+            state: Some(PlayerState {
+                is_in_game: false,
+                room_id: String::new(),
+                is_owner: false,
+                state: Some(EntityState {
+                    current_hp: 100,
+                    max_hp: 100,
+                    current_sp: 100,
+                    max_sp: 100,
+                    is_alive: true,
+                    world_matrix: Some(WorldMatrix::from(PositionInfo::default())),
+                    last_attacker_id: String::new(),
+                    mount_id: String::new(),
+                }),
+            }),
+            ..Default::default()
+        };
+        state
+            .password_hashes
+            .insert(user_name.to_string(), password_hash);
+        state.players.insert(user_name.to_string(), player.clone());
+        Ok(player)
+    }
+}
+
+#[async_trait]
+impl JwtTokenService for ListenServer {
+    /// LANのホストはアカウントを検証しないので、誰にでもトークンを発行する。<br />
+    /// A LAN host doesn't verify accounts, so a token is issued to anyone.
+    async fn access(
+        &self,
+        request: Request<AccessRequest>,
+    ) -> Result<Response<AccessReply>, Status> {
+        let request = request.into_inner();
+        let token = get_random_string(32);
+        self.state
+            .lock()
+            .tokens
+            .insert(token.clone(), String::new());
+        Ok(Response::new(AccessReply {
+            token,
+            user_details: Some(User {
+                r#type: UserType::Bot as i32,
+                user_name: request.user_name,
+                user_role: "Player".to_string(),
+                password: String::new(),
+            }),
+            expiry: String::new(),
+        }))
+    }
+}
+
+#[async_trait]
+impl GrpcService for ListenServer {
+    async fn handshake(
+        &self,
+        request: Request<HandshakeRequest>,
+    ) -> Result<Response<HandshakeReply>, Status> {
+        let request = request.into_inner();
+        let compatible = request.protocol_version == PROTOCOL_VERSION;
+        Ok(Response::new(HandshakeReply {
+            compatible,
+            server_protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: PROTOCOL_VERSION,
+            server_build_version: BUILD_VERSION.to_string(),
+            message: if compatible {
+                String::new()
+            } else {
+                format!(
+                    "The host runs build {}. Update the game to join.",
+                    BUILD_VERSION
+                )
+            },
+        }))
+    }
+
+    async fn register(
+        &self,
+        request: Request<RegisterRequest>,
+    ) -> Result<Response<RegisterReply>, Status> {
+        let request = request.into_inner();
+        let mut state = self.state.lock();
+        state.check_token(&request.jwt_token)?;
+        if state.players.contains_key(&request.user_name) {
+            return Ok(Response::new(RegisterReply {
+                status: false,
+                message: "The user name has already been taken.".to_string(),
+                player: None,
+            }));
+        }
+        let player = Self::create_player(
+            &mut state,
+            &request.user_name,
+            &request.nickname,
+            &request.email,
+            &request.password,
+        )?;
+        Ok(Response::new(RegisterReply {
+            status: true,
+            message: String::new(),
+            player: Some(player),
+        }))
+    }
+
+    /// ホストにまだアカウントが無いプレイヤーは、最初のログインで登録する。<br />
+    /// ログインできたら、以降のリクエストのためにトークンをプレイヤーに結び付ける。<br />
+    /// Players who don't have an account on the host yet are registered on their first login.<br />
+    /// On success, the token is bound to the player for the following requests.
+    async fn login(&self, request: Request<LoginRequest>) -> Result<Response<LoginReply>, Status> {
+        let request = request.into_inner();
+        let mut state = self.state.lock();
+        state.check_token(&request.jwt_token)?;
+        let player = match state.players.get(&request.account).cloned() {
+            Some(player) => {
+                // リフレッシュトークンがあれば、パスワードの代わりにそれを確かめる。
+                // If a refresh token is given, verify it in place of the password.
+                let is_verified = if request.refresh_token.is_empty() {
+                    state
+                        .password_hashes
+                        .get(&request.account)
+                        .and_then(|hash| {
+                            argon2::verify_encoded(hash, request.password.as_bytes()).ok()
+                        })
+                        .unwrap_or(false)
+                } else {
+                    state.refresh_tokens.remove(&request.refresh_token).as_ref()
+                        == Some(&request.account)
+                };
+                if !is_verified {
+                    return Ok(Response::new(LoginReply {
+                        status: false,
+                        message: "Wrong password.".to_string(),
+                        player: None,
+                        refresh_token: String::new(),
+                    }));
+                }
+                player
+            }
+            None if !request.refresh_token.is_empty() => {
+                return Ok(Response::new(LoginReply {
+                    status: false,
+                    message: "The account doesn't exist.".to_string(),
+                    player: None,
+                    refresh_token: String::new(),
+                }));
+            }
+            None => Self::create_player(
+                &mut state,
+                &request.account,
+                &request.account,
+                "",
+                &request.password,
+            )?,
+        };
+        state
+            .tokens
+            .insert(request.jwt_token, player.player_id.clone());
+        let refresh_token = get_random_string(32);
+        state
+            .refresh_tokens
+            .insert(refresh_token.clone(), request.account);
+        Ok(Response::new(LoginReply {
+            status: true,
+            message: String::new(),
+            player: Some(player),
+            refresh_token,
+        }))
+    }
+
+    async fn get_chat_history(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<IncomingMessages>, Status> {
+        let state = self.state.lock();
+        Ok(Response::new(IncomingMessages {
+            messages: state.chat_history.iter().cloned().collect(),
+        }))
+    }
+
+    type ChatStream = mpsc::Receiver<Result<IncomingMessage, Status>>;
+
+    async fn chat(
+        &self,
+        request: Request<Streaming<MessageRecord>>,
+    ) -> Result<Response<Self::ChatStream>, Status> {
+        let mut records = request.into_inner();
+        let (send, recv) = mpsc::channel(STREAM_CAPACITY);
+        self.state.lock().chat_subscribers.push(send);
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            while let Ok(Some(record)) = records.message().await {
+                let mut state_lock = state.lock();
+                let author = match state_lock.find_player(&record.player_id) {
+                    Some(player) => player.nickname.clone(),
+                    None => continue,
+                };
+                let message = IncomingMessage {
+                    author,
+                    message: record.message,
+                };
+                state_lock.chat_history.push_back(message.clone());
+                while state_lock.chat_history.len() > CHAT_HISTORY_SIZE {
+                    state_lock.chat_history.pop_front();
+                }
+                state_lock.chat_subscribers = state_lock
+                    .chat_subscribers
+                    .drain(..)
+                    .filter_map(
+                        |mut subscriber| match subscriber.try_send(Ok(message.clone())) {
+                            Err(mpsc::error::TrySendError::Closed(_)) => None,
+                            _ => Some(subscriber),
+                        },
+                    )
+                    .collect();
+            }
+        });
+        Ok(Response::new(recv))
+    }
+
+    async fn get_rooms(&self, _request: Request<Empty>) -> Result<Response<Rooms>, Status> {
+        let state = self.state.lock();
+        Ok(Response::new(Rooms {
+            rooms: state.rooms.values().map(|r| r.state.clone()).collect(),
+        }))
+    }
+
+    type RegisterPlayerStream = mpsc::Receiver<Result<RoomState, Status>>;
+
+    /// プレイヤーを部屋に入れ、部屋が変わる度にそのステートを送る。部屋が無ければ作る。<br />
+    /// クライアントから受け取るのはプレイヤーの状態だけで、それ以外はトークンでログインしたアカウントから取る。<br />
+    /// Put the player into a room and send its state whenever it changes. The room is created if it doesn't exist.<br />
+    /// Only the player's state is taken from the client; everything else comes from the account logged in with the token.
+    async fn register_player(
+        &self,
+        request: Request<RegisterPlayerRequest>,
+    ) -> Result<Response<Self::RegisterPlayerStream>, Status> {
+        let request = request.into_inner();
+        let sent_player = request
+            .player
+            .ok_or_else(|| Status::invalid_argument("No player was given."))?;
+        let mut state = self.state.lock();
+        let player_id = state.get_logged_player_id(&request.jwt_token)?;
+        if player_id != sent_player.player_id {
+            return Err(Status::permission_denied(
+                "The token wasn't issued to the player.",
+            ));
+        }
+        let mut player = Player {
+            state: sent_player.state,
+            ..state
+                .find_player(player_id)
+                .cloned()
+                .ok_or_else(|| Status::unauthenticated("The player hasn't logged in."))?
+        };
+
+        let room = state
+            .rooms
+            .entry(request.room_id.clone())
+            .or_insert_with(|| HostedRoom {
+                state: RoomState {
+                    room_id: request.room_id.clone(),
+                    room_name: request.room_name.clone(),
+                    current_players: 0,
+                    max_players: if request.max_players > 0 {
+                        request.max_players
+                    } else {
+                        DEFAULT_MAX_PLAYERS
+                    },
+                    started: false,
+                    players: vec![],
+                    message: String::new(),
+                    player_settings: HashMap::new(),
+                },
+                terrain_vertices: vec![],
+                terrain_patches: vec![],
+                snapshots: HashMap::new(),
+                entities: EntityRegistry::new(),
+                outcome: None,
+                subscribers: vec![],
+            });
+        if room.state.started {
+            return Err(Status::failed_precondition("The game has already started."));
+        }
+        let is_member = room
+            .state
+            .players
+            .iter()
+            .any(|p| p.player_id == player.player_id);
+        if !is_member && room.state.current_players >= room.state.max_players {
+            return Err(Status::resource_exhausted("The room is full."));
+        }
+
+        if let Some(player_state) = player.state.as_mut() {
+            player_state.room_id = request.room_id.clone();
+            // 最初に入ったプレイヤーだけが部屋のオーナーになれる。
+            // Only the first player in the room can become its owner.
+            player_state.is_owner = room.state.players.is_empty();
+        }
+        room.state
+            .players
+            .retain(|p| p.player_id != player.player_id);
+        room.snapshots.insert(
+            player.player_id.clone(),
+            PlayerSnapshot::from(&PlayerUdp::from(player.clone())),
+        );
+        // 設定を送らなかったプレイヤーは、前に登録した時の設定を引き継ぐ。
+        // Players who didn't send settings keep the ones from their previous registration.
+        if let Some(mut settings) = request.settings {
+            sanitize_settings(&mut settings);
+            room.state
+                .player_settings
+                .insert(player.player_id.clone(), settings);
+        }
+        room.state.players.push(player);
+        room.state.current_players = room.state.players.len() as i32;
+
+        let (send, recv) = mpsc::channel(STREAM_CAPACITY);
+        room.subscribers.push(send);
+        room.broadcast();
+        Ok(Response::new(recv))
+    }
+
+    /// 部屋を始め、ホストが生成した地形を保存する。<br />
+    /// Start the room and keep the terrain generated by the host.
+    async fn start_game(
+        &self,
+        request: Request<StartGameRequest>,
+    ) -> Result<Response<RoomState>, Status> {
+        let request = request.into_inner();
+        let room_id = request
+            .room_state
+            .map(|r| r.room_id)
+            .ok_or_else(|| Status::invalid_argument("No room was given."))?;
+        let player_id = self
+            .state
+            .lock()
+            .get_logged_player_id(&request.jwt_token)?
+            .to_string();
+        // 他のプレイヤーに配る前に、壊れた地形を弾く。
+        // Reject broken terrains before handing them out to other players.
+        if let Err(e) = parse_terrain_payload(&request.terrain_vertices) {
+            return Err(Status::invalid_argument(format!("Invalid terrain: {}", e)));
+        }
+
+        let mut state = self.state.lock();
+        let room = state
+            .rooms
+            .get_mut(&room_id)
+            .ok_or_else(|| Status::not_found("The room doesn't exist."))?;
+        let is_owner =
+            room.state.players.iter().any(|p| {
+                p.player_id == player_id && p.state.as_ref().map_or(false, |s| s.is_owner)
+            });
+        if !is_owner {
+            return Err(Status::permission_denied(
+                "Only the owner can start the game.",
+            ));
+        }
+        room.terrain_vertices = request.terrain_vertices;
+        room.state.started = true;
+        for player in room.state.players.iter_mut() {
+            if let Some(player_state) = player.state.as_mut() {
+                player_state.is_in_game = true;
+            }
+        }
+        for snapshot in room.snapshots.values_mut() {
+            snapshot.is_in_game = true;
+        }
+        // クライアントは始まったステートの次のメッセージで開始に気付くので、二回送る。
+        // Clients notice the start on the message after the started state, so send it twice.
+        room.broadcast();
+        room.broadcast();
+        room.subscribers.clear();
+        log::info!("Started room {}.", room_id);
+        Ok(Response::new(room.state.clone()))
+    }
+
+    async fn get_terrain(
+        &self,
+        request: Request<GetTerrainRequest>,
+    ) -> Result<Response<GetTerrainReply>, Status> {
+        let request = request.into_inner();
+        let state = self.state.lock();
+        match state.rooms.get(&request.room_id) {
+            Some(room) if room.state.started => Ok(Response::new(GetTerrainReply {
+                terrain_vertices: room.terrain_vertices.clone(),
+                terrain_patches: room.terrain_patches.clone(),
+            })),
+            Some(_) => Err(Status::failed_precondition("The game hasn't started yet.")),
+            None => Err(Status::not_found("The room doesn't exist.")),
+        }
+    }
+
+    /// 始まった部屋の地形のパッチを置き換える。<br />
+    /// Replace the terrain patches of a started room.
+    async fn update_terrain(
+        &self,
+        request: Request<UpdateTerrainRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        let request = request.into_inner();
+        // 他のプレイヤーに配る前に、壊れたパッチを弾く。
+        // Reject broken patches before handing them out to other players.
+        if !request.terrain_patches.is_empty() {
+            if let Err(e) = decode_patches(&request.terrain_patches) {
+                return Err(Status::invalid_argument(format!("Invalid patches: {}", e)));
+            }
+        }
+        let mut state = self.state.lock();
+        match state.rooms.get_mut(&request.room_id) {
+            Some(room) if room.state.started => {
+                room.terrain_patches = request.terrain_patches;
+                Ok(Response::new(Empty {}))
+            }
+            Some(_) => Err(Status::failed_precondition("The game hasn't started yet.")),
+            None => Err(Status::not_found("The room doesn't exist.")),
+        }
+    }
+
+    /// 試合の結果を受け取り、報告したプレイヤーの勝敗とクレジットを更新して部屋から出す。<br />
+    /// 勝者は最初の報告で決まるので、食い違う報告をしても自分の勝ちにはできない。<br />
+    /// Receive the result of a match, update the win counts and credits of the reporting player and remove them from the room.<br />
+    /// The winner is decided by the first report, so a conflicting report can't turn a match into a win.
+    async fn report_match_result(
+        &self,
+        request: Request<ReportMatchResultRequest>,
+    ) -> Result<Response<ReportMatchResultReply>, Status> {
+        let request = request.into_inner();
+        let mut state = self.state.lock();
+        let room = state
+            .rooms
+            .get_mut(&request.room_id)
+            .ok_or_else(|| Status::not_found("The room doesn't exist."))?;
+        if !room.state.started {
+            return Err(Status::failed_precondition("The game hasn't started yet."));
+        }
+        let is_member =
+            |player_id: &str| room.state.players.iter().any(|p| p.player_id == player_id);
+        if !is_member(&request.player_id) {
+            return Err(Status::permission_denied("The player isn't in the room."));
+        }
+        if room.outcome.is_none() && !request.winner_id.is_empty() && !is_member(&request.winner_id)
+        {
+            return Err(Status::invalid_argument("The winner isn't in the room."));
+        }
+        let outcome = room
+            .outcome
+            .get_or_insert_with(|| MatchOutcome::from_winner_id(&request.winner_id))
+            .clone();
+        room.state
+            .players
+            .retain(|p| p.player_id != request.player_id);
+        room.state.current_players = room.state.players.len() as i32;
+        room.snapshots.remove(&request.player_id);
+        room.entities.remove_owner(&request.player_id);
+        if room.state.players.is_empty() {
+            state.rooms.remove(&request.room_id);
+            log::info!("Closed room {}.", request.room_id);
+        }
+
+        let player = state
+            .players
+            .values_mut()
+            .find(|p| p.player_id == request.player_id)
+            .ok_or_else(|| Status::unauthenticated("The player hasn't logged in."))?;
+        let earned_credits = settle_player(player, &outcome);
+        Ok(Response::new(ReportMatchResultReply {
+            player: Some(player.clone()),
+            winner_id: outcome.get_winner_id().unwrap_or_default().to_string(),
+            earned_credits,
+        }))
+    }
+
+    type ProgressGameStream = mpsc::Receiver<Result<RoomState, Status>>;
+
+    async fn progress_game(
+        &self,
+        _request: Request<Streaming<ProgressGameRequest>>,
+    ) -> Result<Response<Self::ProgressGameStream>, Status> {
+        Err(Status::unimplemented(
+            "Games are progressed over UDP on the listen server.",
+        ))
+    }
+}
@@ -1,5 +1,27 @@
+pub mod audio_system;
+pub mod footstep_system;
+pub mod lan_browser;
+#[cfg(feature = "listen-server")]
+pub mod listen_server;
+pub mod mount_system;
+pub mod music_director;
 pub mod network_system;
+pub mod particle_system;
+pub mod physics_system;
+pub mod presence_system;
+pub mod timeline_system;
 pub mod ui_system;
 
+pub use audio_system::*;
+pub use footstep_system::*;
+pub use lan_browser::*;
+#[cfg(feature = "listen-server")]
+pub use listen_server::*;
+pub use mount_system::*;
+pub use music_director::*;
 pub use network_system::*;
+pub use particle_system::*;
+pub use physics_system::*;
+pub use presence_system::*;
+pub use timeline_system::*;
 pub use ui_system::*;
@@ -0,0 +1,394 @@
+use crate::game::shared::components::{
+    Animator, Mount, NetworkReplicated, PlayerController, Rider, Transform, RIDING_PARAMETER,
+};
+use crate::game::shared::systems::PhysicsSystem;
+use crate::game::traits::{Disposable, GraphicsBase};
+use crate::game::World;
+use glam::Vec3A;
+use slotmap::DefaultKey;
+use std::collections::HashMap;
+use winit::event::{ElementState, VirtualKeyCode};
+
+/// 乗り物に乗れる水平方向の距離。<br />
+/// Horizontal distance within which a mount can be boarded.
+pub const MOUNT_RANGE: f32 = 6.0;
+
+/// サーバーが伝えた乗り物の持ち主。受信するタスクが書き換え、シーンが毎フレーム反映する。<br />
+/// Mount owners reported by the server. The receiving task rewrites them and the scene applies them every frame.
+#[derive(Clone, Debug, Default)]
+pub struct MountOwners {
+    /// 乗り物のIDと、それに乗っているプレイヤーのID。<br />
+    /// Mount IDs mapped to the ID of the player riding them.
+    owners: HashMap<String, String>,
+}
+
+impl MountOwners {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 部屋の全プレイヤーの乗っている乗り物を記録する。乗り物に乗っていないプレイヤーは無視する。<br />
+    /// Record the mounts ridden by every player in the room. Players on foot are ignored.
+    pub fn push(&mut self, mounts: Vec<(String, String)>) {
+        self.owners = mounts
+            .into_iter()
+            .filter(|(_, mount_id)| !mount_id.is_empty())
+            .map(|(player_id, mount_id)| (mount_id, player_id))
+            .collect();
+    }
+
+    pub fn get(&self) -> HashMap<String, String> {
+        self.owners.clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.owners.clear();
+    }
+}
+
+/// 乗り降りと、乗っている間の操作を扱う。<br />
+/// ローカルプレイヤーが乗っている乗り物はこのクライアントが動かし、リモートのプレイヤーが乗っている乗り物はそのプレイヤーについて行く。<br />
+/// Handles mounting, dismounting and the controls while riding.<br />
+/// Mounts ridden by the local player are driven by this client, and mounts ridden by remote players follow their rider.
+#[derive(Clone, Debug, Default)]
+pub struct MountSystem {
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+}
+
+impl MountSystem {
+    pub fn new() -> Self {
+        MountSystem::default()
+    }
+
+    /// 乗っている間の操作のキーを記録する。乗り物の操作に使ったキーなら`true`を返す。<br />
+    /// Record the keys that control the mount. Returns `true` if the key is one of them.
+    pub fn handle_key(&mut self, key: VirtualKeyCode, element_state: ElementState) -> bool {
+        let is_pressed = element_state == ElementState::Pressed;
+        match key {
+            VirtualKeyCode::W => self.forward = is_pressed,
+            VirtualKeyCode::S => self.backward = is_pressed,
+            VirtualKeyCode::A => self.left = is_pressed,
+            VirtualKeyCode::D => self.right = is_pressed,
+            _ => return false,
+        }
+        true
+    }
+
+    fn get_axis(negative: bool, positive: bool) -> f32 {
+        (positive as i32 - negative as i32) as f32
+    }
+
+    /// エンティティが乗っている乗り物。<br />
+    /// The mount an entity is riding.
+    pub fn get_mount<GraphicsType, BufferType, CommandType, TextureType>(
+        world: &World<GraphicsType, BufferType, CommandType, TextureType>,
+        rider: DefaultKey,
+    ) -> Option<DefaultKey>
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        world.get::<Rider>(rider).map(|r| r.mount)
+    }
+
+    /// エンティティが乗っている乗り物のID。徒歩なら空。<br />
+    /// ID of the mount an entity is riding. Empty while on foot.
+    pub fn get_mount_id<GraphicsType, BufferType, CommandType, TextureType>(
+        world: &World<GraphicsType, BufferType, CommandType, TextureType>,
+        rider: DefaultKey,
+    ) -> String
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        Self::get_mount(world, rider)
+            .and_then(|mount| world.get::<Mount>(mount))
+            .map(|mount| mount.mount_id.clone())
+            .unwrap_or_default()
+    }
+
+    /// 近くの空いている乗り物に乗る。乗った乗り物を返す。<br />
+    /// Board the nearest free mount in range. Returns the mount that was boarded.
+    pub fn try_mount<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        rider: DefaultKey,
+        owner_id: &str,
+    ) -> Option<DefaultKey>
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        if world.get::<Rider>(rider).is_some() {
+            return None;
+        }
+        let position = world.get::<Transform>(rider)?.position;
+        let horizontal_distance = |other: Vec3A| {
+            let offset = other - position;
+            (offset.x * offset.x + offset.z * offset.z).sqrt()
+        };
+        let (mount_entity, _) = world
+            .iter::<Mount>()
+            .filter(|(_, mount)| mount.is_free())
+            .filter_map(|(entity, _)| {
+                world
+                    .get::<Transform>(entity)
+                    .map(|t| (entity, horizontal_distance(t.position)))
+            })
+            .filter(|(_, distance)| *distance <= MOUNT_RANGE)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))?;
+
+        if let Some(mount) = world.get_mut::<Mount>(mount_entity) {
+            mount.rider = Some(rider);
+            mount.owner_id = owner_id.to_string();
+            mount.speed = 0.0;
+        }
+        world.insert(rider, Rider::new(mount_entity));
+        if let Some(controller) = world.get_mut::<PlayerController>(rider) {
+            controller.move_target = None;
+        }
+        *self = MountSystem::new();
+        Self::seat_rider(world, mount_entity, rider);
+        Some(mount_entity)
+    }
+
+    /// 乗り物から降りて、その横に立つ。<br />
+    /// Get off the mount and stand beside it.
+    pub fn dismount<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        rider: DefaultKey,
+    ) -> bool
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let mount_entity = match world.remove::<Rider>(rider) {
+            Some(r) => r.mount,
+            None => return false,
+        };
+        *self = MountSystem::new();
+        let mount_transform = world.get::<Transform>(mount_entity).copied();
+        let dismount_position = match world.get_mut::<Mount>(mount_entity) {
+            Some(mount) => {
+                mount.rider = None;
+                mount.owner_id.clear();
+                mount.speed = 0.0;
+                mount.throttle = 0.0;
+                mount.steering = 0.0;
+                mount_transform.map(|t| mount.get_dismount_position(t.position, t.rotation.y))
+            }
+            None => None,
+        };
+        if let (Some(position), Some(transform)) =
+            (dismount_position, world.get_mut::<Transform>(rider))
+        {
+            transform.position = position;
+        }
+        true
+    }
+
+    /// ローカルプレイヤーが乗っている乗り物を動かし、乗っている者を座席に置く。<br />
+    /// 物理演算の後に呼ぶ。乗り物の高さは地面に合わせる。<br />
+    /// Drive the mounts ridden by the local player, and put riders on their seats.<br />
+    /// Call this after physics. The height of mounts follows the ground.
+    pub fn update<GraphicsType, BufferType, CommandType, TextureType>(
+        &self,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        physics_system: &PhysicsSystem,
+        delta_time: f64,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        // 乗り物が消えたら、乗っていた者は降りたことにする。
+        // Riders whose mount was removed are treated as having dismounted.
+        let orphaned = world
+            .iter::<Rider>()
+            .filter(|(_, rider)| !world.contains(rider.mount))
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in orphaned.into_iter() {
+            world.remove::<Rider>(entity);
+        }
+        let ridden = world
+            .iter::<Mount>()
+            .filter_map(|(entity, mount)| mount.rider.map(|rider| (entity, rider)))
+            .collect::<Vec<_>>();
+        for (mount_entity, rider) in ridden.into_iter() {
+            if !world.contains(rider) {
+                if let Some(mount) = world.get_mut::<Mount>(mount_entity) {
+                    mount.rider = None;
+                    mount.owner_id.clear();
+                }
+                continue;
+            }
+            if world.get::<PlayerController>(rider).is_some() {
+                self.drive(world, physics_system, mount_entity, delta_time as f32);
+                Self::seat_rider(world, mount_entity, rider);
+            } else {
+                Self::follow_rider(world, mount_entity, rider);
+            }
+        }
+
+        let animated = world
+            .iter::<Animator>()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in animated.into_iter() {
+            let is_riding = world.get::<Rider>(entity).is_some();
+            if let Some(animator) = world.get_mut::<Animator>(entity) {
+                animator.state_machine.set_bool(RIDING_PARAMETER, is_riding);
+            }
+        }
+    }
+
+    /// サーバーが伝えた乗り物の持ち主を反映する。ローカルプレイヤーが乗っている乗り物はこのクライアントが決めるので変えない。<br />
+    /// Apply the mount owners reported by the server. Mounts ridden by the local player are decided by this client, so they're left alone.
+    pub fn apply_owners<GraphicsType, BufferType, CommandType, TextureType>(
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        owners: &HashMap<String, String>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let players = world
+            .iter::<NetworkReplicated>()
+            .map(|(entity, replicated)| {
+                (replicated.player_id.clone(), (entity, replicated.is_local))
+            })
+            .collect::<HashMap<_, _>>();
+        let mounts = world
+            .iter::<Mount>()
+            .map(|(entity, mount)| (entity, mount.mount_id.clone(), mount.rider))
+            .collect::<Vec<_>>();
+        for (mount_entity, mount_id, current_rider) in mounts.into_iter() {
+            let is_local_ride = current_rider
+                .map(|rider| world.get::<PlayerController>(rider).is_some())
+                .unwrap_or(false);
+            if is_local_ride {
+                continue;
+            }
+            let owner = owners
+                .get(&mount_id)
+                .and_then(|player_id| players.get(player_id).map(|p| (player_id, *p)))
+                .filter(|(_, (_, is_local))| !*is_local);
+            let new_rider = owner.map(|(_, (entity, _))| entity);
+            if let Some(rider) = current_rider.filter(|r| Some(*r) != new_rider) {
+                world.remove::<Rider>(rider);
+            }
+            if let Some(rider) = new_rider {
+                world.insert(rider, Rider::new(mount_entity));
+            }
+            if let Some(mount) = world.get_mut::<Mount>(mount_entity) {
+                mount.rider = new_rider;
+                mount.owner_id = owner
+                    .map(|(player_id, _)| player_id.clone())
+                    .unwrap_or_default();
+            }
+        }
+    }
+
+    fn drive<GraphicsType, BufferType, CommandType, TextureType>(
+        &self,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        physics_system: &PhysicsSystem,
+        mount_entity: DefaultKey,
+        delta_time: f32,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let mut transform = match world.get::<Transform>(mount_entity) {
+            Some(t) => *t,
+            None => return,
+        };
+        if let Some(mount) = world.get_mut::<Mount>(mount_entity) {
+            mount.throttle = Self::get_axis(self.backward, self.forward);
+            mount.steering = Self::get_axis(self.left, self.right);
+            mount.step(
+                &mut transform.position,
+                &mut transform.rotation.y,
+                delta_time,
+            );
+        }
+        if let Some(height) =
+            physics_system.get_ground_height(transform.position.x, transform.position.z)
+        {
+            transform.position.y = height;
+        }
+        world.insert(mount_entity, transform);
+    }
+
+    fn seat_rider<GraphicsType, BufferType, CommandType, TextureType>(
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        mount_entity: DefaultKey,
+        rider: DefaultKey,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let mount_transform = match world.get::<Transform>(mount_entity) {
+            Some(t) => *t,
+            None => return,
+        };
+        let seat = match world.get::<Mount>(mount_entity) {
+            Some(mount) => {
+                mount.get_seat_position(mount_transform.position, mount_transform.rotation.y)
+            }
+            None => return,
+        };
+        if let Some(transform) = world.get_mut::<Transform>(rider) {
+            transform.position = seat;
+            transform.rotation.y = mount_transform.rotation.y;
+        }
+    }
+
+    /// リモートのプレイヤーの補間した位置から、乗り物を座席の下に置く。<br />
+    /// Place the mount under the seat, from the interpolated position of a remote player.
+    fn follow_rider<GraphicsType, BufferType, CommandType, TextureType>(
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        mount_entity: DefaultKey,
+        rider: DefaultKey,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let rider_transform = match world.get::<Transform>(rider) {
+            Some(t) => *t,
+            None => return,
+        };
+        let position = match world.get::<Mount>(mount_entity) {
+            Some(mount) => {
+                let yaw = rider_transform.rotation.y;
+                rider_transform.position - mount.get_seat_position(Vec3A::zero(), yaw)
+            }
+            None => return,
+        };
+        if let Some(transform) = world.get_mut::<Transform>(mount_entity) {
+            transform.position = position;
+            transform.rotation.y = rider_transform.rotation.y;
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::game::shared::enums::MusicLayer;
+use crate::game::shared::structs::MusicSet;
+use crate::game::shared::systems::AudioSystem;
+
+/// BGMの層を決めるためのゲーム内の出来事。<br />
+/// Gameplay events deciding the layer of music.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MusicEvent {
+    /// 一番近い敵までの距離（メートル）。近くに敵がいなければ`None`。<br />
+    /// Distance to the nearest enemy in meters, or `None` if no enemy is around.
+    EnemyProximity(Option<f32>),
+    CombatStarted,
+    CombatEnded,
+    HealthChanged {
+        current: i32,
+        max: i32,
+    },
+}
+
+/// ゲーム内の出来事を聞いて、探索・緊張・戦闘の層をクロスフェードで切り替える。<br />
+/// 緊迫する方への切り替えは次の拍で、落ち着く方への切り替えは次の小節で始める。<br />
+/// Listens to gameplay events and crossfades between the exploration, tension and combat layers.<br />
+/// Switching to a more intense layer starts on the next beat, and switching back starts on the next bar.
+pub struct MusicDirector {
+    music_set: Option<MusicSet>,
+    is_in_combat: bool,
+    enemy_distance: Option<f32>,
+    hp_ratio: f32,
+    current_layer: MusicLayer,
+    /// 切り替えを待っている層と、切り替える再生位置。<br />
+    /// The layer waiting to be switched to, and the playback position to switch at.
+    pending_layer: Option<(MusicLayer, f64)>,
+    /// 音源の再生位置（秒）。拍と小節の区切りはここから求める。<br />
+    /// Playback position of the stems in seconds, from which beat and bar boundaries are found.
+    position: f64,
+    volumes: [f32; 3],
+}
+
+impl Default for MusicDirector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MusicDirector {
+    pub fn new() -> Self {
+        MusicDirector {
+            music_set: None,
+            is_in_combat: false,
+            enemy_distance: None,
+            hp_ratio: 1.0,
+            current_layer: MusicLayer::Exploration,
+            pending_layer: None,
+            position: 0.0,
+            volumes: [1.0, 0.0, 0.0],
+        }
+    }
+
+    /// シーンのBGMを読み込み、全ての層を再生し始める。<br />
+    /// Load the music of a scene and start playing all of its layers.
+    pub fn load(
+        &mut self,
+        file_name: &str,
+        scene_name: &str,
+        audio_system: &mut AudioSystem,
+    ) -> anyhow::Result<()> {
+        let music_set = MusicSet::load(file_name, scene_name)?;
+        audio_system.play_music_stems(&music_set.get_stem_files())?;
+        self.set_music_set(music_set);
+        Ok(())
+    }
+
+    /// BGMを差し替えて、探索の層から始め直す。<br />
+    /// Replace the music, starting over from the exploration layer.
+    pub fn set_music_set(&mut self, music_set: MusicSet) {
+        self.music_set = Some(music_set);
+        self.current_layer = MusicLayer::Exploration;
+        self.pending_layer = None;
+        self.position = 0.0;
+        self.volumes = [1.0, 0.0, 0.0];
+    }
+
+    pub fn handle_event(&mut self, event: MusicEvent) {
+        match event {
+            MusicEvent::EnemyProximity(distance) => self.enemy_distance = distance,
+            MusicEvent::CombatStarted => self.is_in_combat = true,
+            MusicEvent::CombatEnded => self.is_in_combat = false,
+            MusicEvent::HealthChanged { current, max } => {
+                self.hp_ratio = if max > 0 {
+                    current as f32 / max as f32
+                } else {
+                    1.0
+                };
+            }
+        }
+    }
+
+    pub fn get_current_layer(&self) -> MusicLayer {
+        self.current_layer
+    }
+
+    /// 今の状況で鳴らすべき層。<br />
+    /// The layer that should be playing in the current situation.
+    pub fn get_target_layer(&self) -> MusicLayer {
+        let music_set = match self.music_set.as_ref() {
+            Some(music_set) => music_set,
+            None => return MusicLayer::Exploration,
+        };
+        let is_enemy_near = self
+            .enemy_distance
+            .map(|distance| distance <= music_set.tension_distance)
+            .unwrap_or(false);
+        if self.is_in_combat {
+            MusicLayer::Combat
+        } else if is_enemy_near || self.hp_ratio <= music_set.low_hp_ratio {
+            MusicLayer::Tension
+        } else {
+            MusicLayer::Exploration
+        }
+    }
+
+    /// 再生位置を進めて層を切り替え、層ごとの音量を返す。<br />
+    /// Advance the playback position, switch layers and return the volume of each layer.
+    pub fn advance(&mut self, delta_time: f64) -> [f32; 3] {
+        let target_layer = self.get_target_layer();
+        let music_set = match self.music_set.as_ref() {
+            Some(music_set) => music_set,
+            None => return self.volumes,
+        };
+        self.position += delta_time;
+
+        if target_layer == self.current_layer {
+            self.pending_layer = None;
+        } else if self.pending_layer.map(|(layer, _)| layer) != Some(target_layer) {
+            let interval = if target_layer > self.current_layer {
+                music_set.get_beat_length()
+            } else {
+                music_set.get_bar_length()
+            };
+            let switch_position = (self.position / interval).ceil() * interval;
+            self.pending_layer = Some((target_layer, switch_position));
+        }
+        if let Some((layer, switch_position)) = self.pending_layer {
+            if self.position >= switch_position {
+                self.current_layer = layer;
+                self.pending_layer = None;
+            }
+        }
+
+        let crossfade = music_set.crossfade_beats as f64 * music_set.get_beat_length();
+        let step = if crossfade > 0.0 {
+            (delta_time / crossfade) as f32
+        } else {
+            1.0
+        };
+        for layer in MusicLayer::ALL.iter() {
+            let volume = &mut self.volumes[layer.index()];
+            if *layer == self.current_layer {
+                *volume = (*volume + step).min(1.0);
+            } else {
+                *volume = (*volume - step).max(0.0);
+            }
+        }
+        self.volumes
+    }
+
+    pub fn update(&mut self, delta_time: f64, audio_system: &mut AudioSystem) {
+        if self.music_set.is_none() {
+            return;
+        }
+        let volumes = self.advance(delta_time);
+        audio_system.set_music_stem_volumes(&volumes);
+    }
+}
@@ -1,24 +1,83 @@
+use crate::game::shared::structs::games::connection::{
+    get_backoff_delay, ConnectionEvent, ConnectionMonitor, ConnectionState, MAX_RECONNECT_ATTEMPTS,
+};
+use crate::game::shared::structs::games::credentials::{
+    is_token_expiring, validate_registration, SavedSession, SESSION_PATH,
+};
+use crate::game::shared::structs::games::fragment::MAX_DATAGRAM_SIZE;
+use crate::game::shared::structs::games::interpolation::StateQueues;
+use crate::game::shared::structs::games::lan_discovery::HostAddress;
+use crate::game::shared::structs::games::match_result::{MatchOutcome, MatchResults};
+use crate::game::shared::structs::games::network_stats::{NetworkStats, RateMeter};
+use crate::game::shared::structs::games::protocol::{
+    ProtocolCompatibility, BUILD_VERSION, PROTOCOL_VERSION,
+};
+use crate::game::shared::structs::games::replication::ReplicationQueues;
+use crate::game::shared::structs::games::snapshot::{
+    PlayerSnapshot, SnapshotDecoder, SnapshotEncoder,
+};
+use crate::game::shared::structs::games::terrain_patch::{
+    decode_patches, encode_patches, TerrainPatch,
+};
+use crate::game::shared::structs::games::terrain_payload::{parse_terrain_payload, TerrainPayload};
+use crate::game::shared::structs::games::udp_session::{
+    ClientSession, Received, SessionState, HANDSHAKE_RETRY_INTERVAL,
+};
 use crate::game::shared::structs::games::{PlayerUdp, RoomStateUdp};
-use crate::game::shared::structs::Primitive;
+use crate::game::shared::structs::{
+    get_synthetic_player_position, LifeTracker, PositionInfo, Primitive, Settings,
+    DEFAULT_UDP_BIND_POINT,
+};
+use crate::game::shared::systems::MountOwners;
+use crate::game::shared::util::get_random_string;
 use crate::protos::grpc_service::game_state::{
-    GetTerrainRequest, Player, ProgressGameRequest, RegisterPlayerRequest, RoomState,
-    StartGameRequest,
+    EntityState, GetTerrainRequest, Player, PlayerSettings, PlayerState, ProgressGameRequest,
+    RegisterPlayerRequest, ReportMatchResultRequest, RoomState, StartGameRequest,
+    UpdateTerrainRequest, WorldMatrix,
 };
 use crate::protos::grpc_service::grpc_service_client::GrpcServiceClient;
-use crate::protos::grpc_service::{Empty, LoginRequest, RegisterRequest};
+use crate::protos::grpc_service::{
+    Empty, HandshakeRequest, IncomingMessage, LoginRequest, MessageRecord, RegisterRequest,
+};
 use crate::protos::jwt_token_service::jwt_token_service_client::JwtTokenServiceClient;
 use crate::protos::jwt_token_service::AccessRequest;
-use once_cell::sync::OnceCell;
-use regex::Regex;
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::net::UdpSocket;
 use tokio::sync::Mutex;
 
-/// ユーザーが入力した内容を検証する正規表現。<br />
-/// Regular expressions used to validate user inputs.
-static USERNAME_REGEX: OnceCell<Regex> = OnceCell::new();
-static EMAIL_REGEX: OnceCell<Regex> = OnceCell::new();
+/// JWTトークンが失効しそうか確かめる間隔。<br />
+/// Interval between checks whether the JWT token is about to expire.
+const TOKEN_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// スナップショットの受信を待つ時間。過ぎたら次のスナップショットを送る。<br />
+/// Time to wait for a snapshot. The next snapshot is sent once it elapses.
+const RECEIVE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// サーバーへの接続を諦めるまでの時間。<br />
+/// Time before giving up connecting to the server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 接続が生きているか確かめるためのHTTP/2のPINGの間隔と、その応答を待つ時間。<br />
+/// Interval of HTTP/2 pings used to check the connection is alive, and how long to wait for the reply.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 保持するチャットメッセージの最大数。<br />
+/// Maximum number of chat messages kept.
+const MAX_CHAT_MESSAGES: usize = 100;
+
+/// オフラインモードのローカルプレイヤーと部屋のID。<br />
+/// IDs of the local player and the room in offline mode.
+const OFFLINE_PLAYER_ID: &str = "offline-player";
+const OFFLINE_ROOM_ID: &str = "offline-room";
+
+/// ソークテストで加える合成したプレイヤーのIDの接頭辞。<br />
+/// Prefix of the IDs of synthetic players added by the soak test.
+const SYNTHETIC_PLAYER_ID_PREFIX: &str = "synthetic-player-";
 
 /// サーバーと通信するためのJWTトークン。<br />
 /// JWT token used to communicate with server.
@@ -42,6 +101,50 @@ struct UserDetails {
     pub user_type: u8,
 }
 
+/// 部屋に登録した時の内容。再接続した後に同じ部屋に登録し直すために保存する。<br />
+/// What the player registered to a room with. Kept to register to the same room again after reconnecting.
+#[derive(Clone)]
+struct RoomRegistration {
+    room_id: String,
+    room_name: String,
+    max_players: i32,
+    start_sender: crossbeam::channel::Sender<bool>,
+}
+
+/// サーバーに接続するための設定。<br />
+/// Settings used to connect to the server.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionSettings {
+    /// gRPCサーバーのエンドポイント。JWTトークンのサービスも同じエンドポイントを使う。<br />
+    /// Endpoint of the gRPC server. The JWT token service shares the same endpoint.
+    pub server_endpoint: String,
+    pub udp_bind_point: String,
+    /// ゲームを推進する時に接続するUDPのエンドポイント。<br />
+    /// UDP endpoint connected to when progressing the game.
+    pub udp_endpoint: String,
+    pub login_name: String,
+    pub login_pass: String,
+}
+
+impl ConnectionSettings {
+    /// 起動時の設定から読み込む。サーバーのエンドポイントがなければ接続できない。<br />
+    /// Read from the startup settings. The server can't be connected to without its endpoint.
+    pub fn from_settings(settings: &Settings) -> anyhow::Result<Self> {
+        if settings.server_endpoint.is_empty() {
+            anyhow::bail!("server_endpoint isn't set.");
+        }
+        Ok(ConnectionSettings {
+            server_endpoint: settings.server_endpoint.clone(),
+            udp_bind_point: settings.udp_bind_point.clone(),
+            // UDPのエンドポイントはゲームが始まるまで要らない。
+            // The UDP endpoint isn't needed until the game starts.
+            udp_endpoint: settings.udp_endpoint.clone(),
+            login_name: settings.login_name.clone(),
+            login_pass: settings.login_pass.clone(),
+        })
+    }
+}
+
 /// ネットワークを処理する主なシステム。<br />
 /// Primary system for handling network.
 pub struct NetworkSystem {
@@ -63,6 +166,91 @@ pub struct NetworkSystem {
 
     pub progress_recv: Option<tokio::sync::oneshot::Receiver<RoomState>>,
 
+    /// UDPでのエンティティの複製を止めるための送り手。落とすと複製が止まる。<br />
+    /// Sender used to stop replicating entities over UDP. Dropping it stops the replication.
+    udp_stop: Option<tokio::sync::oneshot::Sender<()>>,
+
+    /// UDP通信の統計。<br />
+    /// Statistics of the UDP traffic.
+    pub network_stats: Arc<Mutex<NetworkStats>>,
+
+    /// 受け取ったプレイヤーの状態のキュー。リモートのプレイヤーの補間とローカルプレイヤーの補正に使う。<br />
+    /// Queues of received player states, used to interpolate remote players and reconcile the local player.
+    pub state_queues: Arc<Mutex<StateQueues>>,
+
+    /// サーバーが伝えたローカルプレイヤーの生死の変化。<br />
+    /// Changes in whether the local player is alive, as reported by the server.
+    pub life_tracker: Arc<Mutex<LifeTracker>>,
+
+    /// 部屋のステートで届いた乗り物の持ち主。<br />
+    /// Mount owners received with the room state.
+    pub mount_owners: Arc<Mutex<MountOwners>>,
+
+    /// UDPで送るローカルプレイヤーのエンティティと、受け取った代理のイベント。<br />
+    /// Entities of the local player sent over UDP, and events for the received proxies.
+    pub replication: Arc<Mutex<ReplicationQueues>>,
+
+    /// 受け取ったチャットメッセージ。古いものから順に並ぶ。<br />
+    /// Received chat messages, oldest first.
+    pub chat_messages: Arc<Mutex<Vec<IncomingMessage>>>,
+
+    /// チャットのストリームにメッセージを送るためのチャンネル。チャットを始めていないならNone。<br />
+    /// Channel to send messages into the chat stream. None if the chat hasn't been started.
+    chat_sender: Option<tokio::sync::mpsc::UnboundedSender<MessageRecord>>,
+
+    /// 接続時のハンドシェイクで判明したサーバーとの互換性。<br />
+    /// Compatibility with the server found by the handshake at connect time.
+    pub compatibility: ProtocolCompatibility,
+
+    /// サーバーとの接続の状態。バックグラウンドのタスクもこれで切断を報告する。<br />
+    /// State of the connection to the server. Background tasks report connection losses through it as well.
+    pub connection: ConnectionMonitor,
+
+    settings: ConnectionSettings,
+
+    /// 部屋に登録する時に他のプレイヤーに伝える、名札などの設定。<br />
+    /// Settings such as the nameplate, told to other players when registering to a room.
+    player_settings: PlayerSettings,
+
+    /// 再接続した後にログインし直すため、最後にログインできたアカウントとパスワードを保存する。<br />
+    /// The last account and password that logged in successfully, used to log in again after reconnecting.
+    login_data: Option<(String, String)>,
+
+    /// 最後のログインでサーバーが発行したアカウントとリフレッシュトークン。パスワードが分からない時のログインに使う。<br />
+    /// The account and the refresh token the server issued on the last login. Used to log in when the password isn't known.
+    refresh_data: Option<(String, String)>,
+
+    room_registration: Option<RoomRegistration>,
+
+    is_progressing: bool,
+
+    /// ソークテストで部屋に加えた、合成したプレイヤーの数。<br />
+    /// Number of synthetic players added to the room by the soak test.
+    synthetic_player_count: usize,
+
+    /// 合成したプレイヤーが動き始めてからの時間（秒）。<br />
+    /// Time since the synthetic players started moving, in seconds.
+    synthetic_time: f64,
+
+    /// 次に再接続を試みる時刻。Noneならすぐに試みる。<br />
+    /// When to attempt the next reconnection. None to attempt right away.
+    next_reconnect_at: Option<Instant>,
+
+    /// 次にJWTトークンの失効を確かめる時刻。<br />
+    /// When to check the JWT token for expiry next.
+    next_token_check_at: Option<Instant>,
+
+    /// バックグラウンドで接続している途中のチャンネル。<br />
+    /// Channel that is being connected in the background.
+    pending_channel:
+        Option<tokio::sync::oneshot::Receiver<anyhow::Result<tonic::transport::Channel>>>,
+
+    backend: NetworkBackend,
+}
+
+/// サーバーと通信するためのクライアントとソケット。<br />
+/// Clients and socket used to communicate with the server.
+struct ServerBackend {
     /// もらったトークンや検証データを保存するためのフィールド。<br />
     /// A field to store acquired JWT token and authentication data.
     authentication: Authentication,
@@ -78,37 +266,48 @@ pub struct NetworkSystem {
     udp_socket: Arc<Mutex<UdpSocket>>,
 }
 
+/// ネットワークシステムの通信先。<br />
+/// What the network system communicates with.
+enum NetworkBackend {
+    Server(ServerBackend),
+    /// オフラインモード。何も送受信せず、部屋とプレイヤーはローカルで用意する。<br />
+    /// Offline mode. Nothing is sent or received, and the room and the player are prepared locally.
+    Null,
+}
+
+impl NetworkBackend {
+    fn get_server(&mut self) -> anyhow::Result<&mut ServerBackend> {
+        match self {
+            NetworkBackend::Server(server) => Ok(server),
+            NetworkBackend::Null => Err(anyhow::anyhow!(
+                "There is no server to communicate with in offline mode."
+            )),
+        }
+    }
+}
+
 /// ネットワークシステムの実装
 impl NetworkSystem {
-    ///　コンストラクター。<br />
-    /// Constructor.
-    pub async fn new() -> anyhow::Result<Self> {
-        let endpoint = dotenv::var("SERVER_ENDPOINT")?;
-        let mut jwt_client = JwtTokenServiceClient::connect(endpoint.clone()).await?;
-        let grpc_client = GrpcServiceClient::connect(endpoint).await?;
-        let authentication = Self::authenticate(&mut jwt_client).await?;
-
-        // 無効な入力は禁止されているので正規表現で検証する。<br />
-        // Invalid inputs are not allowed, so we use regular expression to validate them.
-        USERNAME_REGEX
-            .set(Regex::new(r".").expect("Failed to initialize regular expression."))
-            .expect("Failed to initialize regular expression.");
-        EMAIL_REGEX
-            .set(
-                Regex::new(r"([a-zA-Z0-9._]+)@{1}([a-zA-Z0-9._]+)")
-                    .expect("Failed to initialize regular expression."),
-            )
-            .expect("Failed to initialize regular expression.");
+    ///　コンストラクター。起動時の設定でサーバーに接続する。<br />
+    /// Constructor. Connects to the server using the startup settings.
+    pub async fn new(settings: &Settings) -> anyhow::Result<Self> {
+        Self::connect(ConnectionSettings::from_settings(settings)?).await
+    }
+
+    /// 指定された設定でサーバーに接続する。<br />
+    /// Connect to the server with the given settings.
+    pub async fn connect(settings: ConnectionSettings) -> anyhow::Result<Self> {
+        let channel = Self::create_channel(settings.server_endpoint.clone()).await?;
+        let mut jwt_client = JwtTokenServiceClient::new(channel.clone());
+        let mut grpc_client = GrpcServiceClient::new(channel);
+        let compatibility = Self::handshake(&mut grpc_client).await?;
+        let authentication = Self::authenticate(&mut jwt_client, &settings).await?;
 
-        let bind_point = dotenv::var("UDP_BINDPOINT")?;
-        let udp_socket = UdpSocket::bind(&bind_point).await?;
+        let udp_socket = UdpSocket::bind(&settings.udp_bind_point).await?;
 
         Ok(NetworkSystem {
-            authentication,
             is_player_login: false,
             logged_user: None,
-            jwt_client,
-            grpc_client,
             // 部屋のデータはサーバーから取得するため、ここで一旦初期化する。<br />
             // We will get room data from the server, so we initialize it first.
             room_state: Arc::new(Mutex::new(RoomState {
@@ -119,19 +318,586 @@ impl NetworkSystem {
                 started: false,
                 players: vec![],
                 message: String::new(),
+                player_settings: HashMap::new(),
             })),
             progress_recv: None,
-            udp_socket: Arc::new(Mutex::new(udp_socket)),
+            udp_stop: None,
+            network_stats: Arc::new(Mutex::new(NetworkStats::default())),
+            state_queues: Arc::new(Mutex::new(StateQueues::new())),
+            life_tracker: Arc::new(Mutex::new(LifeTracker::new())),
+            mount_owners: Arc::new(Mutex::new(MountOwners::new())),
+            replication: Arc::new(Mutex::new(ReplicationQueues::new())),
+            chat_messages: Arc::new(Mutex::new(vec![])),
+            chat_sender: None,
+            compatibility,
+            connection: ConnectionMonitor::new(),
+            settings,
+            player_settings: PlayerSettings::default(),
+            login_data: None,
+            refresh_data: None,
+            room_registration: None,
+            is_progressing: false,
+            synthetic_player_count: 0,
+            synthetic_time: 0.0,
+            next_reconnect_at: None,
+            next_token_check_at: None,
+            pending_channel: None,
             room_state_udp: Arc::new(Mutex::new(RoomStateUdp::default())),
             logged_user_udp: Arc::new(Mutex::new(PlayerUdp::default())),
+            backend: NetworkBackend::Server(ServerBackend {
+                authentication,
+                jwt_client,
+                grpc_client,
+                udp_socket: Arc::new(Mutex::new(udp_socket)),
+            }),
         })
     }
 
+    /// サーバーに接続しないオフラインのネットワークシステムを作る。<br />
+    /// ローカルのプレイヤーが一人だけいる部屋を用意し、最初からログインした状態にする。<br />
+    /// Create an offline network system that never connects to a server.<br />
+    /// A room with only the local player is prepared, and the player starts out logged in.
+    pub fn offline() -> Self {
+        let player = Player {
+            player_id: OFFLINE_PLAYER_ID.to_string(),
+            user_name: "Player".to_string(),
+            nickname: "Player".to_string(),
+            state: Some(PlayerState {
+                is_in_game: true,
+                room_id: OFFLINE_ROOM_ID.to_string(),
+                is_owner: true,
+                state: Some(EntityState {
+                    current_hp: 100,
+                    max_hp: 100,
+                    current_sp: 100,
+                    max_sp: 100,
+                    is_alive: true,
+                    world_matrix: Some(WorldMatrix::from(PositionInfo::default())),
+                    last_attacker_id: String::new(),
+                    mount_id: String::new(),
+                }),
+            }),
+            ..Default::default()
+        };
+        let room_state = RoomState {
+            room_id: OFFLINE_ROOM_ID.to_string(),
+            room_name: "Offline".to_string(),
+            current_players: 1,
+            max_players: 1,
+            started: false,
+            players: vec![player.clone()],
+            message: String::new(),
+            player_settings: HashMap::new(),
+        };
+
+        NetworkSystem {
+            is_player_login: true,
+            room_state_udp: Arc::new(Mutex::new(RoomStateUdp::from(room_state.clone()))),
+            room_state: Arc::new(Mutex::new(room_state)),
+            logged_user_udp: Arc::new(Mutex::new(PlayerUdp::from(player.clone()))),
+            logged_user: Some(Arc::new(Mutex::new(player))),
+            progress_recv: None,
+            udp_stop: None,
+            network_stats: Arc::new(Mutex::new(NetworkStats::default())),
+            state_queues: Arc::new(Mutex::new(StateQueues::new())),
+            life_tracker: Arc::new(Mutex::new(LifeTracker::new())),
+            mount_owners: Arc::new(Mutex::new(MountOwners::new())),
+            replication: Arc::new(Mutex::new(ReplicationQueues::new())),
+            chat_messages: Arc::new(Mutex::new(vec![])),
+            chat_sender: None,
+            compatibility: ProtocolCompatibility::Compatible,
+            connection: ConnectionMonitor::new(),
+            settings: ConnectionSettings::default(),
+            player_settings: PlayerSettings::default(),
+            login_data: None,
+            refresh_data: None,
+            room_registration: None,
+            is_progressing: false,
+            synthetic_player_count: 0,
+            synthetic_time: 0.0,
+            next_reconnect_at: None,
+            next_token_check_at: None,
+            pending_channel: None,
+            backend: NetworkBackend::Null,
+        }
+    }
+
+    /// 次に部屋に登録する時から使う設定を決める。<br />
+    /// Set the settings used from the next registration to a room.
+    pub fn set_player_settings(&mut self, settings: PlayerSettings) {
+        self.player_settings = settings;
+    }
+
+    /// オフラインの部屋に、円を描いて動くだけのプレイヤーを加える。ソークテストで試合を賑やかにするのに使う。<br />
+    /// Add players that only move in a circle to the offline room. Used by the soak test to populate matches.
+    pub async fn add_synthetic_players(&mut self, count: usize) -> anyhow::Result<()> {
+        if !self.is_offline() {
+            return Err(anyhow::anyhow!(
+                "Synthetic players can only be added offline."
+            ));
+        }
+        let template = self
+            .logged_user
+            .as_ref()
+            .expect("Failed to get the offline player.")
+            .lock()
+            .await
+            .clone();
+        let mut room_state = self.room_state.lock().await;
+        for index in 0..count {
+            let mut player = template.clone();
+            player.player_id = format!("{}{}", SYNTHETIC_PLAYER_ID_PREFIX, index + 1);
+            player.user_name = format!("Bot {}", index + 1);
+            player.nickname = player.user_name.clone();
+            if let Some(state) = player.state.as_mut() {
+                state.is_owner = false;
+                if let Some(world_matrix) =
+                    state.state.as_mut().and_then(|s| s.world_matrix.as_mut())
+                {
+                    let position = get_synthetic_player_position(index, count, 0.0);
+                    world_matrix.position = vec![position.x, position.y, position.z];
+                }
+            }
+            room_state.players.push(player);
+        }
+        room_state.current_players = room_state.players.len() as i32;
+        room_state.max_players = room_state.current_players;
+        *self.room_state_udp.lock().await = RoomStateUdp::from(room_state.clone());
+        self.synthetic_player_count += count;
+        Ok(())
+    }
+
+    /// 合成したプレイヤーを円の上で進め、サーバーから届いたかのように状態のキューに入れる。<br />
+    /// Advance the synthetic players along their circle, and push them into the state queues as if they came from a server.
+    pub async fn move_synthetic_players(&mut self, delta_time: f64) {
+        if self.synthetic_player_count == 0 || !self.is_progressing {
+            return;
+        }
+        self.synthetic_time += delta_time;
+        let count = self.synthetic_player_count;
+        let time = self.synthetic_time;
+        let mut room_state = self.room_state.lock().await;
+        let synthetic_players = room_state
+            .players
+            .iter_mut()
+            .filter(|p| p.player_id.starts_with(SYNTHETIC_PLAYER_ID_PREFIX));
+        for (index, player) in synthetic_players.enumerate() {
+            let world_matrix = player
+                .state
+                .as_mut()
+                .and_then(|s| s.state.as_mut())
+                .and_then(|s| s.world_matrix.as_mut());
+            if let Some(world_matrix) = world_matrix {
+                let position = get_synthetic_player_position(index, count, time);
+                world_matrix.position = vec![position.x, position.y, position.z];
+            }
+        }
+        self.get_room_state_queues()
+            .push(&room_state, OFFLINE_PLAYER_ID)
+            .await;
+    }
+
+    /// サーバーに接続せずに動いている？<br />
+    /// Is the system running without a server?
+    pub fn is_offline(&self) -> bool {
+        matches!(self.backend, NetworkBackend::Null)
+    }
+
+    /// 切断を早く検知できるようにキープアライブを有効にしたチャンネルを作る。<br />
+    /// Create a channel with keepalive enabled, so connection losses are detected early.
+    async fn create_channel(endpoint: String) -> anyhow::Result<tonic::transport::Channel> {
+        let endpoint = tonic::transport::Endpoint::from_shared(endpoint)?
+            .http2_keep_alive_interval(KEEP_ALIVE_INTERVAL)
+            .keep_alive_timeout(KEEP_ALIVE_TIMEOUT);
+        let channel = tokio::time::timeout(CONNECT_TIMEOUT, endpoint.connect())
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out connecting to the server."))??;
+        Ok(channel)
+    }
+
+    /// 接続が切れていれば再接続を進める。毎フレーム呼び出す。<br />
+    /// 接続自体はバックグラウンドで行い、ゲームのループを止めないようにする。<br />
+    /// 接続している間は、JWTトークンが失効する前に取得し直す。<br />
+    /// Drive the reconnection if the connection was lost. Called every frame.<br />
+    /// Connecting itself happens in the background, so the game loop isn't blocked.<br />
+    /// While connected, the JWT token is acquired again before it expires.
+    pub async fn maintain_connection(&mut self) {
+        let attempt = match self.connection.get_state() {
+            ConnectionState::Reconnecting { attempt } => attempt,
+            ConnectionState::Connected => {
+                self.refresh_expiring_token().await;
+                return;
+            }
+            _ => return,
+        };
+
+        let channel = match self.pending_channel.as_mut() {
+            Some(recv) => match recv.try_recv() {
+                Ok(result) => {
+                    self.pending_channel = None;
+                    result
+                }
+                Err(tokio::sync::oneshot::error::TryRecvError::Empty) => return,
+                Err(tokio::sync::oneshot::error::TryRecvError::Closed) => {
+                    self.pending_channel = None;
+                    Err(anyhow::anyhow!("Connection task was dropped."))
+                }
+            },
+            None => {
+                if let Some(next_reconnect_at) = self.next_reconnect_at {
+                    if Instant::now() < next_reconnect_at {
+                        return;
+                    }
+                }
+                log::info!("Reconnecting to the server (attempt {})...", attempt + 1);
+                let (send, recv) = tokio::sync::oneshot::channel();
+                let endpoint = self.settings.server_endpoint.clone();
+                tokio::spawn(async move {
+                    let _ = send.send(Self::create_channel(endpoint).await);
+                });
+                self.pending_channel = Some(recv);
+                return;
+            }
+        };
+
+        let result = match channel {
+            Ok(channel) => self.restore_session(channel).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => {
+                log::info!("Successfully reconnected to the server.");
+                self.next_reconnect_at = None;
+                self.connection.set_state(ConnectionState::Connected);
+                self.connection.send(ConnectionEvent::Reconnected);
+            }
+            Err(e) => {
+                let attempt = attempt + 1;
+                if attempt >= MAX_RECONNECT_ATTEMPTS {
+                    log::error!(
+                        "Failed to reconnect after {} attempts. Giving up: {}",
+                        attempt,
+                        e
+                    );
+                    self.next_reconnect_at = None;
+                    self.connection.set_state(ConnectionState::Disconnected);
+                    self.connection.send(ConnectionEvent::GaveUp);
+                } else {
+                    let retry_in = get_backoff_delay(attempt);
+                    log::warn!(
+                        "Failed to reconnect (attempt {}). Retrying in {:?}: {}",
+                        attempt,
+                        retry_in,
+                        e
+                    );
+                    self.next_reconnect_at = Some(Instant::now() + retry_in);
+                    self.connection
+                        .set_state(ConnectionState::Reconnecting { attempt });
+                    self.connection
+                        .send(ConnectionEvent::ReconnectFailed { attempt, retry_in });
+                }
+            }
+        }
+    }
+
+    /// マスターサーバーを通さずに、指定されたホストに接続し直す。<br />
+    /// 今のアカウントでホストにログインし直し、どこかで失敗すれば今の接続を使い続ける。<br />
+    /// Connect to the given host directly, bypassing the master server.<br />
+    /// Logs in to the host with the current account, and keeps using the current connection if anything fails.
+    pub async fn connect_to_host(&mut self, address: HostAddress) -> anyhow::Result<()> {
+        let mut settings = self.settings.clone();
+        settings.server_endpoint = address.get_server_endpoint();
+        settings.udp_endpoint = address.get_udp_endpoint();
+        if settings.udp_bind_point.is_empty() {
+            settings.udp_bind_point = DEFAULT_UDP_BIND_POINT.to_string();
+        }
+        let mut system = Self::connect(settings)
+            .await
+            .with_context(|| format!("Failed to connect to {}.", address.get_server_endpoint()))?;
+        if system.compatibility != ProtocolCompatibility::Compatible {
+            anyhow::bail!("The host runs an incompatible version of the game.");
+        }
+        // リフレッシュトークンは発行したサーバーでしか使えないので、ホストにはパスワードでログインする。
+        // Refresh tokens only work with the server that issued them, so log in to the host with the password.
+        if let Some((account, password)) = self.login_data.clone() {
+            system
+                .login(&account, &password)
+                .await
+                .with_context(|| "Failed to log in to the host.")?;
+        } else if self.refresh_data.is_some() {
+            anyhow::bail!("Log in with the password to join hosted games.");
+        }
+        if self.chat_sender.is_some() {
+            system.start_chat().await?;
+        }
+        log::info!(
+            "Connected to the host at {}.",
+            address.get_server_endpoint()
+        );
+        *self = system;
+        Ok(())
+    }
+
+    /// 再接続を諦めた後、改めて再接続を始める。<br />
+    /// Start reconnecting again after having given up.
+    pub fn retry_connection(&mut self) {
+        if self.connection.get_state() == ConnectionState::Disconnected {
+            self.next_reconnect_at = None;
+            self.connection
+                .set_state(ConnectionState::Reconnecting { attempt: 0 });
+        }
+    }
+
+    /// 新しいチャンネルでセッションを復元する。<br />
+    /// 保存したJWTトークンでログインし直し、失効していればトークンを取得し直す。<br />
+    /// その後、チャットと部屋のストリームを再開する。<br />
+    /// Restore the session over a new channel.<br />
+    /// Logs in again with the stored JWT token, acquiring a new token if it has expired.<br />
+    /// Chat and room streams are then resumed.
+    async fn restore_session(&mut self, channel: tonic::transport::Channel) -> anyhow::Result<()> {
+        let mut grpc_client = GrpcServiceClient::new(channel.clone());
+        self.compatibility = Self::handshake(&mut grpc_client).await?;
+        let server = self.backend.get_server()?;
+        server.grpc_client = grpc_client;
+        server.jwt_client = JwtTokenServiceClient::new(channel);
+
+        if !self.validate_login().await? {
+            log::info!("Stored JWT token was rejected. Acquiring a new one.");
+            self.refresh_token().await?;
+            if !self.validate_login().await? {
+                return Err(anyhow::anyhow!(
+                    "Failed to log in again after reconnecting."
+                ));
+            }
+        }
+
+        if self.chat_sender.take().is_some() {
+            self.start_chat().await?;
+        }
+        if self.room_state.lock().await.started {
+            if self.is_progressing {
+                self.progress_game().await?;
+            }
+        } else if let Some(registration) = self.room_registration.clone() {
+            self.subscribe_room(&registration).await?;
+        }
+        Ok(())
+    }
+
+    /// 保存したトークンでログインできるか確かめる。プレイヤーのデータは置き換えない。<br />
+    /// パスワードが分からなければリフレッシュトークンを使う。ログインしていなければ何もしない。<br />
+    /// Check that logging in with the stored token still works. The player data isn't replaced.<br />
+    /// The refresh token is used if the password isn't known. Nothing is done if the player hasn't logged in.
+    async fn validate_login(&mut self) -> anyhow::Result<bool> {
+        let (account, password, refresh_token) = match (&self.login_data, &self.refresh_data) {
+            (Some((account, password)), _) => (account.clone(), password.clone(), String::new()),
+            (None, Some((account, refresh_token))) => {
+                (account.clone(), String::new(), refresh_token.clone())
+            }
+            (None, None) => return Ok(true),
+        };
+        let server = self.backend.get_server()?;
+        let request = tonic::Request::new(LoginRequest {
+            account: account.clone(),
+            password,
+            jwt_token: server.authentication.token.clone(),
+            refresh_token,
+        });
+        match timed_call(
+            &self.network_stats,
+            "Login",
+            server.grpc_client.login(request),
+        )
+        .await
+        {
+            Ok(response) => {
+                let response = response.into_inner();
+                if response.status {
+                    self.update_refresh_token(&account, response.refresh_token);
+                }
+                Ok(response.status)
+            }
+            Err(status) if status.code() == tonic::Code::Unauthenticated => Ok(false),
+            Err(status) => Err(status.into()),
+        }
+    }
+
+    /// ログインで発行されたリフレッシュトークンを覚え、保存したセッションがあればそれも置き換える。<br />
+    /// 古いリフレッシュトークンはサーバーで破棄されるので、置き換え損ねると次の起動でログインし直すことになる。<br />
+    /// Remember the refresh token issued on a login, replacing the one of the saved session as well if there is one.<br />
+    /// The server discards the old refresh token, so failing to replace it means logging in again on the next launch.
+    fn update_refresh_token(&mut self, account: &str, refresh_token: String) {
+        if refresh_token.is_empty() {
+            return;
+        }
+        self.refresh_data = Some((account.to_string(), refresh_token.clone()));
+        if !std::path::Path::new(SESSION_PATH).exists() {
+            return;
+        }
+        let result = SavedSession::load(SESSION_PATH).and_then(|mut session| {
+            if session.server_endpoint != self.settings.server_endpoint {
+                return Ok(());
+            }
+            session.refresh_token = refresh_token;
+            session.save(SESSION_PATH)
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to update the saved session: {}", e);
+        }
+    }
+
+    /// サーバーとプロトコルのバージョンを交渉する。<br />
+    /// ハンドシェイクを実装していない古いサーバーとは互換性があるものとして扱う。<br />
+    /// Negotiate the protocol version with the server.<br />
+    /// Older servers that don't implement the handshake are treated as compatible.
+    async fn handshake(
+        grpc_client: &mut GrpcServiceClient<tonic::transport::Channel>,
+    ) -> anyhow::Result<ProtocolCompatibility> {
+        let request = tonic::Request::new(HandshakeRequest {
+            protocol_version: PROTOCOL_VERSION,
+            build_version: BUILD_VERSION.to_string(),
+        });
+        let response = match grpc_client.handshake(request).await {
+            Ok(r) => r.into_inner(),
+            Err(status) if status.code() == tonic::Code::Unimplemented => {
+                log::warn!("Server doesn't support protocol handshake. Assuming compatible.");
+                return Ok(ProtocolCompatibility::Compatible);
+            }
+            Err(status) => return Err(status.into()),
+        };
+
+        if response.compatible {
+            log::info!(
+                "Protocol handshake succeeded. Client: {}, server: {}.",
+                PROTOCOL_VERSION,
+                response.server_protocol_version
+            );
+            Ok(ProtocolCompatibility::Compatible)
+        } else {
+            log::error!(
+                "Protocol version mismatch. Client: {}, server: {}, minimum: {}.",
+                PROTOCOL_VERSION,
+                response.server_protocol_version,
+                response.min_protocol_version
+            );
+            Ok(ProtocolCompatibility::UpdateRequired {
+                server_protocol_version: response.server_protocol_version,
+                server_build_version: response.server_build_version,
+                message: response.message,
+            })
+        }
+    }
+
+    /// チャットの履歴を取得し、双方向のストリームでメッセージの送受信を始める。<br />
+    /// Fetch the chat history and start exchanging messages over the bidirectional stream.
+    pub async fn start_chat(&mut self) -> anyhow::Result<()> {
+        if self.chat_sender.is_some() || self.is_offline() {
+            return Ok(());
+        }
+        let server = self.backend.get_server()?;
+        let history = timed_call(
+            &self.network_stats,
+            "GetChatHistory",
+            server
+                .grpc_client
+                .get_chat_history(tonic::Request::new(Empty {})),
+        )
+        .await?
+        .into_inner()
+        .messages;
+        {
+            let mut messages = self.chat_messages.lock().await;
+            *messages = history;
+            let overflow = messages.len().saturating_sub(MAX_CHAT_MESSAGES);
+            messages.drain(0..overflow);
+        }
+
+        let (send, recv) = tokio::sync::mpsc::unbounded_channel();
+        let response = timed_call(
+            &self.network_stats,
+            "Chat",
+            server.grpc_client.chat(tonic::Request::new(recv)),
+        )
+        .await?;
+        let mut inbound = response.into_inner();
+        let chat_messages = self.chat_messages.clone();
+        let connection = self.connection.clone();
+        tokio::spawn(async move {
+            let chat_messages = chat_messages;
+            loop {
+                match inbound.message().await {
+                    Ok(Some(message)) => {
+                        let mut messages = chat_messages.lock().await;
+                        if messages.len() == MAX_CHAT_MESSAGES {
+                            messages.remove(0);
+                        }
+                        messages.push(message);
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::error!("Failed to receive chat message from server: {}", e);
+                        connection.check_status(&e);
+                        break;
+                    }
+                }
+            }
+        });
+        self.chat_sender = Some(send);
+        Ok(())
+    }
+
+    /// チャットのストリームにメッセージを送る。<br />
+    /// Send a message into the chat stream.
+    pub async fn send_chat_message(&self, message: &str) -> anyhow::Result<()> {
+        let message = message.trim();
+        if message.is_empty() {
+            return Ok(());
+        }
+        if self.is_offline() {
+            // 送る相手がいないので、自分のメッセージをそのまま表示する。
+            // There is no one to send to, so our own message is shown as is.
+            let author = match self.logged_user.as_ref() {
+                Some(player) => player.lock().await.nickname.clone(),
+                None => String::new(),
+            };
+            let mut messages = self.chat_messages.lock().await;
+            if messages.len() == MAX_CHAT_MESSAGES {
+                messages.remove(0);
+            }
+            messages.push(IncomingMessage {
+                author,
+                message: message.to_string(),
+            });
+            return Ok(());
+        }
+        let sender = self
+            .chat_sender
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Chat has not been started."))?;
+        let player_id = match self.logged_user.as_ref() {
+            Some(player) => player.lock().await.player_id.clone(),
+            None => return Err(anyhow::anyhow!("Cannot chat without logging in.")),
+        };
+        sender
+            .send(MessageRecord {
+                player_id,
+                message: message.to_string(),
+            })
+            .map_err(|_| anyhow::anyhow!("Chat stream has been closed."))?;
+        Ok(())
+    }
+
     /// 既存の部屋を全て取得する。<br />
     /// Retrieve all existing rooms from server.
     pub async fn get_rooms(&mut self) -> anyhow::Result<Vec<RoomState>> {
         let request = tonic::Request::new(Empty {});
-        let response = self.grpc_client.get_rooms(request).await?;
+        let server = self.backend.get_server()?;
+        let response = timed_call(
+            &self.network_stats,
+            "GetRooms",
+            server.grpc_client.get_rooms(request),
+        )
+        .await?;
         let response = response.into_inner();
         Ok(response.rooms)
     }
@@ -141,137 +907,445 @@ impl NetworkSystem {
     /// サーバーに転送し、そしてサーバーがその地形のデータを同じ部屋にいる他のプレイヤーに配るという形で実現する。<br />
     /// Retrieve vertices and indices of a terrain.<br />
     /// All players must see and exist on the same terrain if they are in the same room, so the host's computer will generate the terrain first.<br />
-    /// The terrain then will be sent to the server, and the server will broadcast that terrain to all other players in the same room.
+    /// The terrain then will be sent to the server, and the server will broadcast that terrain to all other players in the same room.<br />
+    /// ゲームが始まった後に変更されたチャンクのパッチも当てる。<br />
+    /// Patches of the chunks modified after the game started are applied as well.
     pub async fn get_terrain(&mut self) -> anyhow::Result<Primitive> {
         let request = tonic::Request::new(GetTerrainRequest {
             room_id: self.room_state.lock().await.room_id.clone(),
         });
 
-        let response = self.grpc_client.get_terrain(request).await?;
+        let server = self.backend.get_server()?;
+        let response = timed_call(
+            &self.network_stats,
+            "GetTerrain",
+            server.grpc_client.get_terrain(request),
+        )
+        .await?;
         let response = response.into_inner();
-        let primitive = serde_json::from_slice::<Primitive>(&response.terrain_vertices)?;
+        let mut primitive = match parse_terrain_payload(&response.terrain_vertices) {
+            Ok(primitive) => primitive,
+            Err(e) => {
+                log::error!(
+                    "Rejected terrain of {} bytes from server: {}",
+                    response.terrain_vertices.len(),
+                    e
+                );
+                return Err(e.into());
+            }
+        };
+        if response.terrain_patches.is_empty() {
+            return Ok(primitive);
+        }
+        // 試合の地形は一つのチャンクなので、全てのパッチをそれに当てる。
+        // The terrain of a match is a single chunk, so every patch is applied to it.
+        let patches = decode_patches(&response.terrain_patches)
+            .and_then(|patches| {
+                patches
+                    .iter()
+                    .try_for_each(|patch| patch.apply(&mut primitive))
+                    .map(|_| patches)
+            })
+            .map_err(|e| {
+                log::error!(
+                    "Rejected terrain patches of {} bytes from server: {}",
+                    response.terrain_patches.len(),
+                    e
+                );
+                e
+            })?;
+        log::info!("Applied {} terrain patches from server.", patches.len());
         Ok(primitive)
     }
 
+    /// 始まった部屋の地形のパッチを置き換え、途中から参加するプレイヤーに配られるようにする。<br />
+    /// この関数を呼び出せるのはホスト（部屋のオーナー）のみです。<br />
+    /// Replace the terrain patches of the started room, so they're handed out to players joining later.<br />
+    /// This function can only be invoked by the client of the host (the owner of the room).
+    pub async fn update_terrain(&mut self, patches: &[TerrainPatch]) -> anyhow::Result<()> {
+        if self.is_offline() {
+            return Ok(());
+        }
+        let terrain_patches = encode_patches(patches)?;
+        log::info!(
+            "Sending {} terrain patches as {} bytes.",
+            patches.len(),
+            terrain_patches.len()
+        );
+        let request = tonic::Request::new(UpdateTerrainRequest {
+            room_id: self.room_state.lock().await.room_id.clone(),
+            terrain_patches,
+        });
+        let server = self.backend.get_server()?;
+        timed_call(
+            &self.network_stats,
+            "UpdateTerrain",
+            server.grpc_client.update_terrain(request),
+        )
+        .await?;
+        Ok(())
+    }
+
     ///　登録した使用者のデータ、もしくは入力された既存のデータでログインする。<br />
-    /// Using registered player's data or inputted data to login player.
-    pub async fn login(&mut self, login_data: Option<(String, String)>) -> Option<Player> {
-        if let Some((account, password)) = login_data {
+    /// JWTトークンが断られた場合は、取得し直してから一度だけやり直す。<br />
+    /// Using registered player's data or inputted data to login player.<br />
+    /// If the JWT token is rejected, a new one is acquired and the login is retried once.
+    pub async fn login(&mut self, account: &str, password: &str) -> anyhow::Result<Player> {
+        let player = self.send_login(account, password, "").await?;
+        self.login_data = Some((account.to_string(), password.to_string()));
+        Ok(player)
+    }
+
+    /// パスワードかリフレッシュトークンのどちらかでログインし、ログインしたプレイヤーを覚える。<br />
+    /// Log in with either the password or a refresh token, and remember the logged in player.
+    async fn send_login(
+        &mut self,
+        account: &str,
+        password: &str,
+        refresh_token: &str,
+    ) -> anyhow::Result<Player> {
+        let mut is_token_refreshed = false;
+        let mut response = loop {
+            let server = self.backend.get_server()?;
             let request = tonic::Request::new(LoginRequest {
-                account,
-                password,
-                jwt_token: self.authentication.token.clone(),
+                account: account.to_string(),
+                password: password.to_string(),
+                jwt_token: server.authentication.token.clone(),
+                refresh_token: refresh_token.to_string(),
             });
-            let response = self
-                .grpc_client
-                .login(request)
-                .await
-                .expect("Failed to get login reply.");
-            let mut response = response.into_inner();
-            if response.status {
-                let player = response
-                    .player
-                    .take()
-                    .expect("Failed to get player from response.");
-                self.logged_user = Some(Arc::new(Mutex::new(player.clone())));
-                self.is_player_login = true;
-                Some(player)
-            } else {
-                None
+            match timed_call(
+                &self.network_stats,
+                "Login",
+                server.grpc_client.login(request),
+            )
+            .await
+            {
+                Ok(r) => break r.into_inner(),
+                Err(status)
+                    if status.code() == tonic::Code::Unauthenticated && !is_token_refreshed =>
+                {
+                    log::info!("JWT token was rejected. Acquiring a new one.");
+                    self.refresh_token().await?;
+                    is_token_refreshed = true;
+                }
+                Err(status) => {
+                    log::error!("Failed to get login reply: {}", status);
+                    self.connection.check_status(&status);
+                    return Err(anyhow::anyhow!("Failed to log in: {}", status.message()));
+                }
+            }
+        };
+        if !response.status {
+            return Err(anyhow::anyhow!(get_reply_message(
+                &response.message,
+                "The account or the password is incorrect."
+            )));
+        }
+        let player = response
+            .player
+            .take()
+            .context("Failed to get player from response.")?;
+        self.logged_user = Some(Arc::new(Mutex::new(player.clone())));
+        self.is_player_login = true;
+        self.update_refresh_token(account, response.refresh_token);
+        Ok(player)
+    }
+
+    /// 保存したセッションのリフレッシュトークンでログインする。保存したトークンがまだ有効なら、取得し直さずに使う。<br />
+    /// Log in with the refresh token of the saved session. The saved token is used without acquiring a new one if it's still valid.
+    pub async fn resume_session(&mut self) -> anyhow::Result<Player> {
+        let session = SavedSession::load(SESSION_PATH)?;
+        if session.server_endpoint != self.settings.server_endpoint {
+            anyhow::bail!("The saved session belongs to another server. Log in again.");
+        }
+        let server = self.backend.get_server()?;
+        if !session.token.is_empty() && !is_token_expiring(&session.token, SystemTime::now()) {
+            server.authentication.token = session.token.clone();
+        }
+        self.send_login(&session.account, "", &session.refresh_token)
+            .await
+            .context("The saved session has expired. Log in again.")
+    }
+
+    /// 今のログインを次に起動した時のために保存する。`false`なら保存したセッションを消す。<br />
+    /// パスワードは保存せず、サーバーが発行したリフレッシュトークンだけを保存する。<br />
+    /// Save the current login for the next launch. `false` deletes the saved session instead.<br />
+    /// The password isn't saved; only the refresh token issued by the server is.
+    pub fn remember_session(&self, remember: bool) -> anyhow::Result<()> {
+        if !remember {
+            return SavedSession::delete(SESSION_PATH);
+        }
+        let (account, refresh_token) = self
+            .refresh_data
+            .clone()
+            .context("The server didn't issue a refresh token to remember.")?;
+        let token = match &self.backend {
+            NetworkBackend::Server(server) => server.authentication.token.clone(),
+            NetworkBackend::Null => String::new(),
+        };
+        SavedSession {
+            server_endpoint: self.settings.server_endpoint.clone(),
+            account,
+            token,
+            refresh_token,
+        }
+        .save(SESSION_PATH)
+    }
+
+    /// JWTトークンを取得し直す。保存したセッションがあれば、そのトークンも置き換える。<br />
+    /// Acquire a new JWT token. The token of the saved session is replaced as well if there is one.
+    pub async fn refresh_token(&mut self) -> anyhow::Result<()> {
+        let server = self.backend.get_server()?;
+        server.authentication = Self::authenticate(&mut server.jwt_client, &self.settings).await?;
+        if std::path::Path::new(SESSION_PATH).exists() {
+            let mut session = SavedSession::load(SESSION_PATH)?;
+            if session.server_endpoint == self.settings.server_endpoint {
+                session.token = server.authentication.token.clone();
+                session.save(SESSION_PATH)?;
             }
-        } else {
-            None
         }
+        Ok(())
     }
 
-    /*pub async fn progress_game(&mut self) -> anyhow::Result<()> {
-        let player = self.logged_user_udp.clone();
-        let room_state = self.room_state_udp.clone();
-        let udp_socket = self.udp_socket.clone();
-        let remote_addr = dotenv::var("UDP_ENDPOINT")?;
+    /// JWTトークンが失効しそうなら取得し直す。確かめるのは一定の間隔ごと。<br />
+    /// Acquire a new JWT token if the current one is about to expire. Only checked at an interval.
+    async fn refresh_expiring_token(&mut self) {
+        let now = Instant::now();
+        if let Some(next_token_check_at) = self.next_token_check_at {
+            if now < next_token_check_at {
+                return;
+            }
+        }
+        self.next_token_check_at = Some(now + TOKEN_CHECK_INTERVAL);
+        let is_expiring = match &self.backend {
+            NetworkBackend::Server(server) => {
+                is_token_expiring(&server.authentication.token, SystemTime::now())
+            }
+            NetworkBackend::Null => false,
+        };
+        if is_expiring {
+            log::info!("JWT token is about to expire. Acquiring a new one.");
+            if let Err(e) = self.refresh_token().await {
+                log::warn!("Failed to refresh the JWT token: {}", e);
+                return;
+            }
+            // 新しいトークンはまだプレイヤーに結び付いていないので、ログインし直す。
+            // The new token isn't bound to the player yet, so log in again.
+            match self.validate_login().await {
+                Ok(true) => {}
+                Ok(false) => log::warn!("Failed to log in again with the new JWT token."),
+                Err(e) => log::warn!("Failed to log in again with the new JWT token: {}", e),
+            }
+        }
+    }
+
+    /// ゲームの推進と並んで、プレイヤーが持つエンティティをUDPで複製する。状態はバイナリのスナップショットの差分で送受信する。<br />
+    /// 最初にJWTトークンでセッションを開き、全てのパケットをセッションで包んで重複を捨てる。<br />
+    /// プレイヤーの位置と生死、乗り物はgRPCの部屋のステートで届くので、ここではエンティティと通信の統計だけを扱う。<br />
+    /// Replicate the entities owned by players over UDP alongside progressing the game. States are exchanged as binary snapshot deltas.<br />
+    /// A session is opened with the JWT token first, and every packet is wrapped in it so duplicates are dropped.<br />
+    /// Player positions, lives and mounts arrive with the gRPC room state, so only entities and traffic statistics are handled here.
+    async fn start_udp_replication(&mut self) -> anyhow::Result<()> {
+        let player = self
+            .logged_user
+            .clone()
+            .context("Failed to get currently logged in player.")?;
+        let room_state = self.room_state.clone();
+        let room_state_udp = self.room_state_udp.clone();
+        let server = self.backend.get_server()?;
+        let udp_socket = server.udp_socket.clone();
+        let token = server.authentication.token.clone();
+        let remote_addr = self.settings.udp_endpoint.clone();
+        if remote_addr.is_empty() {
+            anyhow::bail!("UDP endpoint is not set.");
+        }
         udp_socket.lock().await.connect(&remote_addr).await?;
         log::info!("Successfully connected to UDP endpoint.");
-        let (send, recv) = tokio::sync::oneshot::channel();
+        let network_stats = self.network_stats.clone();
+        let connection = self.connection.clone();
+        let replication = self.replication.clone();
+        // 前の複製は送り手が落とされると止まる。
+        // The previous replication stops once its sender is dropped.
+        let (stop_sender, mut stop) = tokio::sync::oneshot::channel::<()>();
+        self.udp_stop = Some(stop_sender);
+        let local_player_id = player.lock().await.player_id.clone();
         tokio::spawn(async move {
-            let room_state = room_state;
-            let player = player;
-            let udp_socket = udp_socket;
-            let sender = send;
+            let mut encoder = SnapshotEncoder::new();
+            let mut decoder = SnapshotDecoder::new();
+            let mut dropped_datagrams = 0_u64;
+            let mut rate_meter = RateMeter::new();
+            let mut buffer = [0_u8; MAX_DATAGRAM_SIZE];
+            let mut session =
+                ClientSession::new(&token, &local_player_id, rand::random(), Instant::now());
 
-            loop {
-                let player_state = player.lock().await.clone();
-                let message =
-                    serde_json::to_vec(&player_state).expect("Failed to serialize player.");
-                let mut socket = udp_socket.lock().await;
-                socket
-                    .send(&message)
-                    .await
-                    .expect("Failed to send UDP packet.");
-                let mut buffer = [0_u8; 4096];
-                let size = socket
-                    .recv(&mut buffer[0..])
-                    .await
-                    .expect("Failed to receive UDP packet.");
-                let deserialized: RoomStateUdp = serde_json::from_slice(&buffer[0..size])
-                    .expect("Failed to deserialize incoming packet.");
-                if !deserialized.started {
+            'progress: loop {
+                match stop.try_recv() {
+                    Err(tokio::sync::oneshot::error::TryRecvError::Empty) => (),
+                    _ => break,
+                }
+                let started = room_state.lock().await.started;
+                if !started {
                     break;
                 }
-                if !deserialized.players.is_empty() {
-                    let send_result = sender.send(deserialized.clone());
-                    match send_result {
-                        Ok(_) => {}
-                        Err(_) => {
-                            log::error!("An error occurred when sending via oneshot channel.");
-                        }
+                if !session.is_established() {
+                    let mut socket = udp_socket.lock().await;
+                    if let Err(e) = establish_session(&mut socket, &mut session, &mut buffer).await
+                    {
+                        log::error!("Failed to establish the UDP session: {}", e);
+                        connection.report_lost(&e.to_string());
+                        break;
                     }
+                    log::info!("Established the UDP session.");
+                }
+                let keep_alive = session.poll(Instant::now());
+                if let SessionState::Failed(reason) = session.get_state() {
+                    log::error!("Lost the UDP session: {}", reason);
+                    connection.report_lost(reason);
                     break;
                 }
-                let mut room_state_lock = room_state.lock().await;
-                *room_state_lock = deserialized;
-            }
 
-            loop {
-                let player_state = player.lock().await.clone();
-                let message =
-                    serde_json::to_vec(&player_state).expect("Failed to serialize player.");
+                let player_state =
+                    PlayerSnapshot::from(&PlayerUdp::from(player.lock().await.clone()));
+                let owned_entities = replication.lock().await.get_owned_entities();
+                let datagrams = match encoder.encode(
+                    started,
+                    vec![player_state],
+                    owned_entities,
+                    decoder.get_ack(),
+                ) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        log::error!("Failed to encode snapshot: {}", e);
+                        break;
+                    }
+                };
+
+                let packets = match datagrams
+                    .iter()
+                    .map(|d| session.wrap(d))
+                    .collect::<anyhow::Result<Vec<_>>>()
+                {
+                    Ok(p) => p,
+                    Err(e) => {
+                        log::error!("Failed to wrap snapshot: {}", e);
+                        break;
+                    }
+                };
+
                 let mut socket = udp_socket.lock().await;
-                socket
-                    .send(&message)
-                    .await
-                    .expect("Failed to send UDP packet.");
-                let mut buffer = [0_u8; 4096];
-                let size = socket
-                    .recv(&mut buffer[0..])
-                    .await
-                    .expect("Failed to receive UDP packet.");
-                let deserialized: RoomStateUdp = serde_json::from_slice(&buffer[0..size])
-                    .expect("Failed to deserialize incoming packet.");
-                if !deserialized.started {
-                    break;
+                for packet in keep_alive.iter().chain(packets.iter()) {
+                    if let Err(e) = socket.send(packet).await {
+                        log::error!("Failed to send UDP packet: {}", e);
+                        connection.report_lost(&e.to_string());
+                        break 'progress;
+                    }
+                }
+                // 一つのスナップショットが複数のデータグラムに分かれている場合があるため、揃うまで受け取る。
+                // A snapshot may be split into several datagrams, so keep receiving until it is complete.
+                let received = loop {
+                    let size =
+                        match tokio::time::timeout(RECEIVE_TIMEOUT, socket.recv(&mut buffer[0..]))
+                            .await
+                        {
+                            Ok(Ok(size)) => size,
+                            Ok(Err(e)) => {
+                                log::error!("Failed to receive UDP packet: {}", e);
+                                connection.report_lost(&e.to_string());
+                                break 'progress;
+                            }
+                            Err(_) => break None,
+                        };
+                    let datagram = match session.receive(&buffer[0..size], Instant::now()) {
+                        Ok(Received::Datagram(datagram)) => datagram,
+                        Ok(Received::Reply(reply)) => {
+                            if let Err(e) = socket.send(&reply).await {
+                                log::error!("Failed to send UDP packet: {}", e);
+                                connection.report_lost(&e.to_string());
+                                break 'progress;
+                            }
+                            continue;
+                        }
+                        Ok(Received::Nothing) => continue,
+                        Err(e) => {
+                            dropped_datagrams += 1;
+                            log::warn!(
+                                "Rejected incoming packet of {} bytes ({} rejected so far): {}",
+                                size,
+                                dropped_datagrams,
+                                e
+                            );
+                            continue;
+                        }
+                    };
+                    match decoder.decode(&datagram) {
+                        Ok(Some(result)) => break Some(result),
+                        Ok(None) => continue,
+                        Err(e) => {
+                            dropped_datagrams += 1;
+                            log::warn!(
+                                "Rejected incoming datagram of {} bytes ({} rejected so far): {}",
+                                size,
+                                dropped_datagrams,
+                                e
+                            );
+                        }
+                    }
+                };
+                drop(socket);
+
+                {
+                    // gRPCの呼び出しの時間は別に記録されるので、UDPの統計だけを書き換える。
+                    // The latency of gRPC calls is recorded separately, so only the UDP statistics are replaced.
+                    let mut stats = network_stats.lock().await;
+                    stats.sent = encoder.get_stats();
+                    stats.received = decoder.get_stats();
+                    stats.dropped_datagrams = dropped_datagrams;
+                    stats.round_trip_time = session.get_round_trip_time();
+                    stats.session = session.get_stats();
+                    stats.rates = rate_meter.sample(Instant::now(), &stats.sent, &stats.received);
+                }
+                let (snapshot, ack) = match received {
+                    Some(result) => result,
+                    None => continue,
+                };
+                if let Some(ack) = ack {
+                    encoder.acknowledge(ack);
                 }
-                let mut room_state_lock = room_state.lock().await;
-                *room_state_lock = deserialized;
+                snapshot.apply_to(&mut *room_state_udp.lock().await);
+                replication
+                    .lock()
+                    .await
+                    .push_entities(&snapshot.entities, &local_player_id);
                 tokio::time::delay_for(std::time::Duration::from_millis(30)).await;
             }
         });
-        self.progress_recv = Some(recv);
         Ok(())
-    }*/
+    }
 
     /// ゲームを推進する。<br />
     /// Progress the game.
     pub async fn progress_game(&mut self) -> anyhow::Result<()> {
+        if self.is_offline() {
+            return self.progress_game_offline().await;
+        }
         let room_id = self.room_state.lock().await.room_id.clone();
         let player = self
             .logged_user
             .clone()
-            .expect("Failed to get currently logged in player.");
+            .context("Failed to get currently logged in player.")?;
+        let local_player_id = player.lock().await.player_id.clone();
+        let stream_room_state = self.room_state.clone();
         let request_stream = async_stream::stream! {
             let mut interval = tokio::time::interval(std::time::Duration::from_millis(1));
             let room_id = room_id;
             let player = player;
+            let room_state = stream_room_state;
             while let _ = interval.tick().await {
+                // 部屋を出たら送るのをやめ、サーバーにストリームを閉じてもらう。
+                // Stop sending once the room is left, so that the server closes the stream.
+                if !room_state.lock().await.started {
+                    break;
+                }
                 let player_state = player.lock().await.clone();
                 if let Some(state) = player_state.state.as_ref() {
                     let world_matrix = state.state.as_ref().and_then(|s| s.world_matrix.as_ref());
@@ -287,22 +1361,37 @@ impl NetworkSystem {
             }
         };
 
-        let response = self
-            .grpc_client
-            .progress_game(tonic::Request::new(request_stream))
-            .await?;
+        let server = self.backend.get_server()?;
+        let response = timed_call(
+            &self.network_stats,
+            "ProgressGame",
+            server
+                .grpc_client
+                .progress_game(tonic::Request::new(request_stream)),
+        )
+        .await?;
         let mut inbound = response.into_inner();
         let (send, recv) = tokio::sync::oneshot::channel();
         let room_state = self.room_state.clone();
+        let connection = self.connection.clone();
+        self.clear_queues().await;
+        let queues = self.get_room_state_queues();
         tokio::spawn(async move {
             let room_state = room_state;
             let sender = send;
+            let queues = queues;
 
-            while let Some(state) = inbound
-                .message()
-                .await
-                .expect("Failed to received update room state from server")
-            {
+            loop {
+                let state = match inbound.message().await {
+                    Ok(Some(state)) => state,
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::error!("Failed to receive updated room state from server: {}", e);
+                        connection.check_status(&e);
+                        break;
+                    }
+                };
+                queues.push(&state, &local_player_id).await;
                 let mut state_lock = room_state.lock().await;
                 *state_lock = state;
                 match sender.send(state_lock.clone()) {
@@ -314,57 +1403,93 @@ impl NetworkSystem {
                 break;
             }
 
-            while let Some(state) = inbound
-                .message()
-                .await
-                .expect("Failed to receive updated room state from server.")
-            {
-                let mut state_lock = room_state.lock().await;
-                *state_lock = state;
+            loop {
+                match inbound.message().await {
+                    Ok(Some(state)) => {
+                        let mut state_lock = room_state.lock().await;
+                        // 部屋を出た後に届いた古いステートで、片付けた部屋を戻さない。
+                        // Don't bring back the cleared room with a stale state arriving after leaving.
+                        if !state_lock.started {
+                            break;
+                        }
+                        queues.push(&state, &local_player_id).await;
+                        *state_lock = state;
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::error!("Failed to receive updated room state from server: {}", e);
+                        connection.check_status(&e);
+                        break;
+                    }
+                }
             }
         });
         self.progress_recv = Some(recv);
+        self.is_progressing = true;
+        // UDPのエンドポイントがなければ、エンティティは複製せずに部屋のステートだけで進める。
+        // Without a UDP endpoint, the game progresses on the room state alone without replicating entities.
+        if !self.settings.udp_endpoint.is_empty() {
+            if let Err(e) = self.start_udp_replication().await {
+                log::warn!("Failed to start replicating entities over UDP: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// オフラインでゲームを推進する。更新を送ってくる相手がいないので、ローカルの部屋のステートを一度だけ渡す。<br />
+    /// Progress the game offline. No one sends updates, so the local room state is handed over only once.
+    async fn progress_game_offline(&mut self) -> anyhow::Result<()> {
+        let (send, recv) = tokio::sync::oneshot::channel();
+        self.clear_queues().await;
+        send.send(self.room_state.lock().await.clone())
+            .map_err(|_| anyhow::anyhow!("Failed to send the offline room state."))?;
+        self.progress_recv = Some(recv);
+        self.is_progressing = true;
         Ok(())
     }
 
-    /// ユーザーが入力したデータに基づいてサーバーとデータベースに登録する。<br />
-    /// Register player to the database and server using inputted information.
+    /// ユーザーが入力したデータに基づいてサーバーとデータベースに登録し、そのままログインする。<br />
+    /// Register player to the database and server using inputted information, then log in.
     pub async fn register(
         &mut self,
         username: &str,
         nickname: &str,
         email: &str,
         password: &str,
-    ) -> (bool, Option<Player>) {
-        if !Self::verify(username, nickname, email, password) {
-            (false, None)
-        } else {
-            let encoded_pass = base64::encode(password.trim());
-            let request = tonic::Request::new(RegisterRequest {
-                user_name: username.trim().to_string(),
-                nickname: nickname.trim().to_string(),
-                email: email.trim().to_string(),
-                password: encoded_pass.clone(),
-                jwt_token: self.authentication.token.clone(),
-            });
-
-            let response = self
-                .grpc_client
-                .register(request)
-                .await
-                .expect("Failed to register against the server.");
+        confirmation: &str,
+    ) -> anyhow::Result<Player> {
+        validate_registration(username, nickname, email, password, confirmation)?;
+        let server = self.backend.get_server()?;
+        let encoded_pass = base64::encode(password.trim());
+        let request = tonic::Request::new(RegisterRequest {
+            user_name: username.trim().to_string(),
+            nickname: nickname.trim().to_string(),
+            email: email.trim().to_string(),
+            password: encoded_pass.clone(),
+            jwt_token: server.authentication.token.clone(),
+        });
 
-            let response = response.into_inner();
-            if response.status {
-                if let Some(player) = self.login(Some((username.to_string(), encoded_pass))).await {
-                    (true, Some(player))
-                } else {
-                    (false, None)
-                }
-            } else {
-                (false, None)
+        let response = match timed_call(
+            &self.network_stats,
+            "Register",
+            server.grpc_client.register(request),
+        )
+        .await
+        {
+            Ok(r) => r.into_inner(),
+            Err(status) => {
+                log::error!("Failed to register against the server: {}", status);
+                self.connection.check_status(&status);
+                return Err(anyhow::anyhow!("Failed to register: {}", status.message()));
             }
+        };
+        if !response.status {
+            return Err(anyhow::anyhow!(get_reply_message(
+                &response.message,
+                "The server refused the registration."
+            )));
         }
+        self.login(username.trim(), &encoded_pass).await
     }
 
     /// プレイヤーを部屋に登録する。<br />
@@ -376,6 +1501,28 @@ impl NetworkSystem {
         room_id: String,
         room_name: String,
         is_owner: bool,
+    ) -> anyhow::Result<crossbeam::channel::Receiver<bool>> {
+        self.register_to_room(room_id, room_name, is_owner, 0).await
+    }
+
+    /// 新しい部屋を作り、そのオーナーとして登録する。<br />
+    /// Create a new room and register the player as its owner.
+    pub async fn create_room(
+        &mut self,
+        room_name: String,
+        max_players: i32,
+    ) -> anyhow::Result<crossbeam::channel::Receiver<bool>> {
+        let room_id = get_random_string(7);
+        self.register_to_room(room_id, room_name, true, max_players)
+            .await
+    }
+
+    async fn register_to_room(
+        &mut self,
+        room_id: String,
+        room_name: String,
+        is_owner: bool,
+        max_players: i32,
     ) -> anyhow::Result<crossbeam::channel::Receiver<bool>> {
         if let Some(player) = self.logged_user.as_ref() {
             if let Some(state) = player.lock().await.state.as_mut() {
@@ -383,23 +1530,48 @@ impl NetworkSystem {
                 state.room_id = room_id.to_string();
             }
         }
-        let request = tonic::Request::new(RegisterPlayerRequest {
+        let (send, recv) = crossbeam::channel::bounded(5);
+        let registration = RoomRegistration {
             room_id,
             room_name,
-            player: Some(
-                self.logged_user
-                    .clone()
-                    .expect("Failed to get currently logged in player")
-                    .lock()
-                    .await
-                    .clone(),
-            ),
+            max_players,
+            start_sender: send,
+        };
+        self.subscribe_room(&registration).await?;
+        self.room_registration = Some(registration);
+        Ok(recv)
+    }
+
+    /// 部屋に登録し、ゲームが始まるまで部屋のステートを受け取る。<br />
+    /// Register to a room and receive its state until the game starts.
+    async fn subscribe_room(&mut self, registration: &RoomRegistration) -> anyhow::Result<()> {
+        let player = self
+            .logged_user
+            .clone()
+            .expect("Failed to get currently logged in player")
+            .lock()
+            .await
+            .clone();
+        let server = self.backend.get_server()?;
+        let request = tonic::Request::new(RegisterPlayerRequest {
+            room_id: registration.room_id.clone(),
+            room_name: registration.room_name.clone(),
+            player: Some(player),
+            max_players: registration.max_players,
+            settings: Some(self.player_settings.clone()),
+            jwt_token: server.authentication.token.clone(),
         });
-        let response = self.grpc_client.register_player(request).await?;
+        let response = timed_call(
+            &self.network_stats,
+            "RegisterPlayer",
+            server.grpc_client.register_player(request),
+        )
+        .await?;
         let response = response.into_inner();
         let room_state = self.room_state.clone();
         let room_state_udp = self.room_state_udp.clone();
-        let (send, recv) = crossbeam::channel::bounded(5);
+        let send = registration.start_sender.clone();
+        let connection = self.connection.clone();
         let logged_player = self
             .logged_user
             .clone()
@@ -412,15 +1584,25 @@ impl NetworkSystem {
             let send = send;
             let logged_player = logged_player;
             let logged_player_udp = logged_player_udp;
-            while let Ok(r) = response.message().await {
+            let connection = connection;
+            loop {
+                let message = response.message().await;
                 let mut state = current_room_state.lock().await;
                 if state.started {
                     send.send(true)
                         .expect("Failed to send room state to main thread.");
                     break;
                 }
-                if let Some(actual_state) = r {
-                    *state = actual_state;
+                match message {
+                    Ok(Some(actual_state)) => *state = actual_state,
+                    Ok(None) => break,
+                    Err(e) => {
+                        // 再接続した後に登録し直すので、古いステートで上書きしない。
+                        // We register again after reconnecting, so don't overwrite with a stale state.
+                        log::error!("Failed to receive room state from server: {}", e);
+                        connection.check_status(&e);
+                        return;
+                    }
                 }
             }
             let mut player = logged_player.lock().await;
@@ -437,7 +1619,7 @@ impl NetworkSystem {
             let mut room_state_udp_lock = current_room_state_udp.lock().await;
             *room_state_udp_lock = RoomStateUdp::from(latest_room_state.clone());
         });
-        Ok(recv)
+        Ok(())
     }
 
     /// 部屋を待たないようにして、ゲームを始める。<br />
@@ -445,12 +1627,34 @@ impl NetworkSystem {
     /// Stop waiting in a room and start the game.<br />
     /// This function can only be invoked by the client of the host (the owner of the room).
     pub async fn start_game(&mut self, primitive: Primitive) -> anyhow::Result<()> {
-        let serialized_data = serde_json::to_vec(&primitive)?;
+        if self.is_offline() {
+            // 地形はローカルで生成したものをそのまま使うので、部屋を始めるだけでいい。
+            // The locally generated terrain is used as is, so the room only needs to be started.
+            let mut room_state = self.room_state.lock().await;
+            room_state.started = true;
+            *self.room_state_udp.lock().await = RoomStateUdp::from(room_state.clone());
+            log::info!("Started offline game.");
+            return Ok(());
+        }
+        let serialized_data = TerrainPayload::from_primitive(&primitive)?.encode()?;
+        log::info!(
+            "Sending terrain of {} vertices as {} bytes.",
+            primitive.vertices.len(),
+            serialized_data.len()
+        );
+        let room_state = self.room_state.lock().await.clone();
+        let server = self.backend.get_server()?;
         let request = tonic::Request::new(StartGameRequest {
-            room_state: Some(self.room_state.lock().await.clone()),
+            room_state: Some(room_state),
             terrain_vertices: serialized_data,
+            jwt_token: server.authentication.token.clone(),
         });
-        let new_room_state = self.grpc_client.start_game(request).await?;
+        let new_room_state = timed_call(
+            &self.network_stats,
+            "StartGame",
+            server.grpc_client.start_game(request),
+        )
+        .await?;
         let new_room_state = new_room_state.into_inner();
         {
             let logged_player = self
@@ -477,14 +1681,116 @@ impl NetworkSystem {
         Ok(())
     }
 
+    /// 決着した試合の結果をサーバーに報告し、勝敗とクレジットが反映されたアカウントを受け取る。<br />
+    /// 勝者はサーバーが最初の報告で決めるので、返ってくる結果は報告したものと違うこともある。<br />
+    /// Report the result of a decided match to the server and receive the account with the updated win counts and credits.<br />
+    /// The server decides the winner by the first report, so the returned result may differ from the reported one.
+    pub async fn report_match_result(
+        &mut self,
+        outcome: &MatchOutcome,
+    ) -> anyhow::Result<MatchResults> {
+        let logged_player = self
+            .logged_user
+            .clone()
+            .expect("Failed to get currently logged in player.");
+        let room_state = self.room_state.lock().await.clone();
+        let request = tonic::Request::new(ReportMatchResultRequest {
+            room_id: room_state.room_id.clone(),
+            player_id: logged_player.lock().await.player_id.clone(),
+            winner_id: outcome.get_winner_id().unwrap_or_default().to_string(),
+        });
+        let server = self.backend.get_server()?;
+        let reply = match timed_call(
+            &self.network_stats,
+            "ReportMatchResult",
+            server.grpc_client.report_match_result(request),
+        )
+        .await
+        {
+            Ok(r) => r.into_inner(),
+            Err(status) => {
+                self.connection.check_status(&status);
+                return Err(anyhow::anyhow!(
+                    "Failed to report the match result: {}",
+                    status.message()
+                ));
+            }
+        };
+        let updated_player = reply
+            .player
+            .ok_or_else(|| anyhow::anyhow!("The server didn't return the updated player."))?;
+
+        // 部屋の中の状態はそのままにして、アカウントの数字だけを受け取る。
+        // Keep the in-room state as is, and only take the numbers of the account.
+        let mut player = logged_player.lock().await;
+        player.win_count = updated_player.win_count;
+        player.lose_count = updated_player.lose_count;
+        player.credits = updated_player.credits;
+        *self.logged_user_udp.lock().await = PlayerUdp::from(player.clone());
+        log::info!(
+            "Reported the match result. Earned {} credits.",
+            reply.earned_credits
+        );
+        Ok(MatchResults::new(
+            MatchOutcome::from_winner_id(&reply.winner_id),
+            &room_state,
+            &player,
+            reply.earned_credits,
+        ))
+    }
+
+    /// 試合を終えて部屋を出る。ゲームの推進を止め、次の部屋に入れるようにステートを片付ける。<br />
+    /// Leave the room after a match. Progressing the game stops, and the state is cleared so that another room can be entered.
+    pub async fn leave_room(&mut self) {
+        self.room_registration = None;
+        self.is_progressing = false;
+        self.progress_recv = None;
+        self.udp_stop = None;
+        *self.room_state.lock().await = RoomState::default();
+        *self.room_state_udp.lock().await = RoomStateUdp::default();
+        if let Some(player) = self.logged_user.as_ref() {
+            let mut player = player.lock().await;
+            if let Some(state) = player.state.as_mut() {
+                state.is_in_game = false;
+                state.is_owner = false;
+                state.room_id.clear();
+            }
+            *self.logged_user_udp.lock().await = PlayerUdp::from(player.clone());
+        }
+        self.clear_queues().await;
+        // ゲームのシーンごと片付けるので、代理を消すイベントはいらない。
+        // The game scene is torn down as a whole, so the events despawning proxies aren't needed.
+        self.replication.lock().await.take_events();
+    }
+
+    /// ネットワークから届いた状態のキューを全て片付ける。<br />
+    /// Clear all queues of states arriving from the network.
+    async fn clear_queues(&self) {
+        self.state_queues.lock().await.clear();
+        self.life_tracker.lock().await.clear();
+        self.mount_owners.lock().await.clear();
+        self.replication.lock().await.clear();
+    }
+
+    /// 部屋のステートを書き込むキュー。受信するタスクに渡す。<br />
+    /// The queues a room state is written into, handed to the receiving task.
+    fn get_room_state_queues(&self) -> RoomStateQueues {
+        RoomStateQueues {
+            state_queues: self.state_queues.clone(),
+            life_tracker: self.life_tracker.clone(),
+            mount_owners: self.mount_owners.clone(),
+        }
+    }
+
     /// サーバーと通信するためのJWTトークンを取得する。<br />
     /// Retrieve JWT token for communication with server.
     async fn authenticate(
         client: &mut JwtTokenServiceClient<tonic::transport::Channel>,
+        settings: &ConnectionSettings,
     ) -> anyhow::Result<Authentication> {
         let request = tonic::Request::new(AccessRequest {
-            user_name: dotenv::var("LOGIN_NAME")?,
-            password: dotenv::var("LOGIN_PASS")?,
+            user_name: settings.login_name.clone(),
+            password: settings.login_pass.clone(),
         });
 
         let response = client.access(request).await?;
@@ -503,20 +1809,127 @@ impl NetworkSystem {
             expiry: Some(response.expiry),
         })
     }
+}
 
-    /// 正規表現により入力されたデータを検証する。<br />
-    /// Verify user inputs using regular expressions.
-    fn verify(username: &str, nickname: &str, email: &str, password: &str) -> bool {
-        let names_valid = if let Some(regex) = USERNAME_REGEX.get() {
-            regex.is_match(username) && regex.is_match(nickname) && regex.is_match(password)
-        } else {
-            false
-        };
-        let email_valid = if let Some(regex) = EMAIL_REGEX.get() {
-            regex.is_match(email)
-        } else {
-            false
-        };
-        names_valid && email_valid
+/// UDPのセッションのハンドシェイクを、確立するか失敗するまで進める。<br />
+/// Drive the handshake of the UDP session until it's established or fails.
+async fn establish_session(
+    socket: &mut UdpSocket,
+    session: &mut ClientSession,
+    buffer: &mut [u8],
+) -> anyhow::Result<()> {
+    while !session.is_established() {
+        if let Some(packet) = session.poll(Instant::now()) {
+            socket.send(&packet).await?;
+        }
+        if let SessionState::Failed(reason) = session.get_state() {
+            anyhow::bail!("{}", reason);
+        }
+        let size =
+            match tokio::time::timeout(HANDSHAKE_RETRY_INTERVAL, socket.recv(&mut buffer[0..]))
+                .await
+            {
+                Ok(result) => result?,
+                Err(_) => continue,
+            };
+        match session.receive(&buffer[0..size], Instant::now()) {
+            Ok(Received::Reply(reply)) => {
+                socket.send(&reply).await?;
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Rejected handshake packet of {} bytes: {}", size, e),
+        }
+    }
+    Ok(())
+}
+
+/// gRPCの呼び出しを待ち、かかった時間を失敗したかどうかと一緒にネットワークの統計に記録する。<br />
+/// Await a gRPC call and record how long it took, along with whether it failed, in the network statistics.
+async fn timed_call<T>(
+    network_stats: &Mutex<NetworkStats>,
+    call: &'static str,
+    request: impl std::future::Future<Output = Result<T, tonic::Status>>,
+) -> Result<T, tonic::Status> {
+    let started_at = Instant::now();
+    let result = request.await;
+    network_stats
+        .lock()
+        .await
+        .grpc
+        .record(call, started_at.elapsed(), result.is_ok());
+    result
+}
+
+/// サーバーの返信のメッセージ。空なら代わりの説明を使う。<br />
+/// Message of a reply from the server. The fallback description is used if it's empty.
+fn get_reply_message(message: &str, fallback: &str) -> String {
+    if message.trim().is_empty() {
+        fallback.to_string()
+    } else {
+        message.trim().to_string()
+    }
+}
+
+/// 部屋のステートから、ワールド行列を持つプレイヤーの位置を取り出す。<br />
+/// Extract the positions of players that have a world matrix from a room state.
+fn get_player_states(room_state: &RoomState) -> Vec<(String, PositionInfo)> {
+    room_state
+        .players
+        .iter()
+        .filter_map(|p| {
+            p.state
+                .as_ref()
+                .and_then(|s| s.state.as_ref())
+                .and_then(|s| s.world_matrix.as_ref())
+                .map(|wm| (p.player_id.clone(), PositionInfo::from(wm)))
+        })
+        .collect()
+}
+
+/// 部屋のステートから書き込まれるキュー。<br />
+/// The queues written from a room state.
+#[derive(Clone)]
+struct RoomStateQueues {
+    state_queues: Arc<Mutex<StateQueues>>,
+    life_tracker: Arc<Mutex<LifeTracker>>,
+    mount_owners: Arc<Mutex<MountOwners>>,
+}
+
+impl RoomStateQueues {
+    /// 部屋のステートのプレイヤーの位置と乗り物、ローカルプレイヤーの生死をキューに追加する。<br />
+    /// Push the positions and mounts of the players in a room state, and whether the local player is alive, into the queues.
+    async fn push(&self, room_state: &RoomState, local_player_id: &str) {
+        let mut state_queues = self.state_queues.lock().await;
+        state_queues.push_room_state(
+            Instant::now(),
+            get_player_states(room_state),
+            local_player_id,
+        );
+        let local_state = room_state
+            .players
+            .iter()
+            .find(|p| p.player_id == local_player_id)
+            .and_then(|p| p.state.as_ref())
+            .and_then(|s| s.state.as_ref());
+        if let Some(state) = local_state {
+            self.life_tracker.lock().await.push(
+                state.is_alive,
+                &state.last_attacker_id,
+                local_player_id,
+                &state_queues,
+            );
+        }
+        drop(state_queues);
+        let mounts = room_state
+            .players
+            .iter()
+            .filter_map(|p| {
+                p.state
+                    .as_ref()
+                    .and_then(|s| s.state.as_ref())
+                    .map(|s| (p.player_id.clone(), s.mount_id.clone()))
+            })
+            .collect();
+        self.mount_owners.lock().await.push(mounts);
     }
 }
@@ -0,0 +1,188 @@
+use crate::game::shared::components::{ParticleEffect, ParticleEmitter};
+use crate::game::shared::structs::{
+    get_emission_direction, ParticleDispatch, ParticleDrawPushConstant, ParticleRange,
+    ParticleRangeAllocator, ParticleSimulationPushConstant, MAX_PARTICLES, PARTICLE_FLAG_RESET,
+};
+use crate::game::traits::{Disposable, GraphicsBase};
+use crate::game::{Transform, World};
+use glam::Vec3A;
+use slotmap::DefaultKey;
+use std::collections::HashMap;
+
+/// 発生源に粒子バッファの範囲を割り当て、毎フレームGPUで動かす内容を作る。<br />
+/// 粒子そのものはGPUにしかないので、ここでは生む数と場所だけを決める。<br />
+/// Assigns ranges of the particle buffer to emitters and builds what the GPU simulates every frame.<br />
+/// Particles themselves only live on the GPU, so this only decides how many are born and where.
+pub struct ParticleSystem {
+    allocator: ParticleRangeAllocator,
+    ranges: HashMap<DefaultKey, ParticleRange>,
+    seed: u32,
+}
+
+impl Default for ParticleSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParticleSystem {
+    pub fn new() -> Self {
+        ParticleSystem {
+            allocator: ParticleRangeAllocator::new(MAX_PARTICLES),
+            ranges: HashMap::new(),
+            seed: 0,
+        }
+    }
+
+    /// 既製の効果を出すエンティティを生成する。<br />
+    /// Spawn an entity emitting a ready-made effect.
+    pub fn spawn_effect<GraphicsType, BufferType, CommandType, TextureType>(
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        effect: ParticleEffect,
+        position: Vec3A,
+    ) -> DefaultKey
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        Self::spawn_emitter(world, ParticleEmitter::from_effect(effect), position)
+    }
+
+    pub fn spawn_emitter<GraphicsType, BufferType, CommandType, TextureType>(
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        emitter: ParticleEmitter,
+        position: Vec3A,
+    ) -> DefaultKey
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let entity = world.spawn("Particle Emitter");
+        let mut transform = Transform::new();
+        transform.position = position;
+        world.insert(entity, transform);
+        world.insert(entity, emitter);
+        entity
+    }
+
+    /// 発生源を進めて、このフレームにGPUで動かして描く内容を返す。<br />
+    /// 終わった発生源のエンティティは削除し、範囲を返す。<br />
+    /// Advance emitters and return what the GPU simulates and draws this frame.<br />
+    /// Entities of finished emitters are despawned and their ranges returned.
+    pub fn update<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+        delta_time: f32,
+    ) -> Vec<ParticleDispatch>
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let allocator = &mut self.allocator;
+        self.ranges.retain(|entity, range| {
+            let is_alive = world.get::<ParticleEmitter>(*entity).is_some();
+            if !is_alive {
+                allocator.free(range);
+            }
+            is_alive
+        });
+
+        let mut dispatches = vec![];
+        let mut finished = vec![];
+        let emitters = world
+            .iter::<ParticleEmitter>()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in emitters {
+            let position = world
+                .get::<Transform>(entity)
+                .map(|transform| transform.position)
+                .unwrap_or_else(Vec3A::zero);
+            let emitter = match world.get_mut::<ParticleEmitter>(entity) {
+                Some(emitter) => emitter,
+                None => continue,
+            };
+            let spawn_count = emitter.update(delta_time);
+            if emitter.is_finished() {
+                finished.push(entity);
+                continue;
+            }
+            let emitter = *emitter;
+            if !self.ranges.contains_key(&entity) {
+                match self.allocator.allocate(emitter.max_particles) {
+                    Some(range) => {
+                        self.ranges.insert(entity, range);
+                    }
+                    None => {
+                        log::warn!(
+                            "Not enough particles left for an emitter of {} particles. {} of {} are free.",
+                            emitter.max_particles,
+                            self.allocator.get_free_count(),
+                            self.allocator.get_capacity()
+                        );
+                        continue;
+                    }
+                }
+            }
+            let range = self
+                .ranges
+                .get_mut(&entity)
+                .expect("Failed to get the particle range of the emitter.");
+            let (spawn_start, spawn_count) = range.advance(spawn_count);
+            let direction = get_emission_direction(emitter.direction);
+            self.seed = self.seed.wrapping_add(1);
+            dispatches.push(ParticleDispatch {
+                simulation: ParticleSimulationPushConstant {
+                    origin: [position.x, position.y, position.z, emitter.cone_angle],
+                    direction: [direction.x, direction.y, direction.z, emitter.gravity],
+                    base: range.base,
+                    capacity: range.capacity,
+                    spawn_start,
+                    spawn_count,
+                    speed_min: emitter.speed.0,
+                    speed_max: emitter.speed.1,
+                    lifetime: emitter.lifetime,
+                    delta_time,
+                    seed: self.seed,
+                    flags: if range.take_is_new() {
+                        PARTICLE_FLAG_RESET
+                    } else {
+                        0
+                    },
+                    padding: [0; 2],
+                },
+                draw: ParticleDrawPushConstant::new(
+                    &emitter.color_over_life,
+                    range.base,
+                    range.capacity,
+                    emitter.size,
+                    emitter.is_additive,
+                ),
+            });
+        }
+
+        for entity in finished {
+            if let Some(range) = self.ranges.remove(&entity) {
+                self.allocator.free(&range);
+            }
+            world.despawn(entity);
+        }
+        dispatches
+    }
+
+    /// 今使っている発生源の数。<br />
+    /// Number of emitters currently holding a range.
+    pub fn get_active_count(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn get_free_particle_count(&self) -> u32 {
+        self.allocator.get_free_count()
+    }
+}
@@ -0,0 +1,803 @@
+use crate::game::shared::components::{Buoyancy, PlayerController, Rider, RigidBody, Transform};
+use crate::game::shared::structs::{
+    get_buoyant_force, is_occluded, Model, Primitive, Ray, WaterEvent, WaterSurface, WaterVolume,
+    SIMULATION_TIMESTEP,
+};
+use crate::game::shared::util::math::{euler_from_quat, quat_from_euler};
+use crate::game::traits::{Disposable, GraphicsBase};
+use crate::game::ui::TweakRegistry;
+use crate::game::World;
+use glam::{Quat, Vec3A};
+use rapier3d::dynamics::{
+    BodyStatus, IntegrationParameters, JointSet, RigidBodyBuilder, RigidBodyHandle, RigidBodySet,
+};
+use rapier3d::geometry::{BroadPhase, ColliderBuilder, ColliderSet, NarrowPhase};
+use rapier3d::na::{DMatrix, Isometry3, Point3, Quaternion, Translation3, UnitQuaternion, Vector3};
+use rapier3d::pipeline::PhysicsPipeline;
+use slotmap::DefaultKey;
+use std::collections::HashMap;
+
+const GRAVITY: f32 = -9.81;
+const GRAVITY_TWEAK: &str = "Physics/Gravity";
+const RAYCAST_STEP: f32 = 0.5;
+const RAYCAST_REFINEMENTS: usize = 16;
+
+/// キャラクターが足元からこの高さまでの甲板には乗れる。<br />
+/// Characters can step onto decks up to this height above their feet.
+const PLATFORM_STEP_HEIGHT: f32 = 0.5;
+
+/// 浮かないものが水面に立てる波紋の半径。<br />
+/// Radius of the ripples made by things that don't float.
+const SPLASH_RADIUS: f32 = 0.5;
+
+/// 地面の高さを求めるために保存した地形の高さデータ。<br />
+/// Terrain height data kept for looking up the ground height.
+struct TerrainHeights {
+    origin: Vec3A,
+    size_x: f32,
+    size_z: f32,
+    vertex_count: usize,
+    heights: Vec<f32>,
+}
+
+impl TerrainHeights {
+    /// バイリニア補間で指定した位置の高さを求める。<br />
+    /// Get the height at the given position with bilinear interpolation.
+    fn get_height(&self, x: f32, z: f32) -> Option<f32> {
+        let local_x = (x - self.origin.x) / self.size_x;
+        let local_z = (z - self.origin.z) / self.size_z;
+        if !(0.0..=1.0).contains(&local_x) || !(0.0..=1.0).contains(&local_z) {
+            return None;
+        }
+        let cells = (self.vertex_count - 1) as f32;
+        let grid_x = local_x * cells;
+        let grid_z = local_z * cells;
+        let x0 = (grid_x.floor() as usize).min(self.vertex_count - 2);
+        let z0 = (grid_z.floor() as usize).min(self.vertex_count - 2);
+        let fx = grid_x - x0 as f32;
+        let fz = grid_z - z0 as f32;
+        let height = |i: usize, j: usize| self.heights[i * self.vertex_count + j];
+        let top = height(z0, x0) * (1.0 - fx) + height(z0, x0 + 1) * fx;
+        let bottom = height(z0 + 1, x0) * (1.0 - fx) + height(z0 + 1, x0 + 1) * fx;
+        Some(self.origin.y + top * (1.0 - fz) + bottom * fz)
+    }
+}
+
+/// プレイヤーが操作するキネマティックなキャラクター。<br />
+/// A kinematic character controlled by the player.
+struct CharacterController {
+    entity: DefaultKey,
+    handle: RigidBodyHandle,
+    vertical_velocity: f32,
+    is_grounded: bool,
+    /// 立っている甲板と、前のステップでのその位置と回転。<br />
+    /// The deck being stood on, with its position and rotation at the previous step.
+    platform: Option<(DefaultKey, Vec3A, Quat)>,
+}
+
+/// rapier3dによる物理演算。固定のタイムステップでシミュレーションを進める。<br />
+/// Physics simulation backed by rapier3d, stepped with a fixed timestep.
+pub struct PhysicsSystem {
+    pipeline: PhysicsPipeline,
+    gravity: Vector3<f32>,
+    integration_parameters: IntegrationParameters,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    joints: JointSet,
+    body_handles: HashMap<DefaultKey, RigidBodyHandle>,
+    terrains: HashMap<DefaultKey, TerrainHeights>,
+    water_surfaces: Vec<WaterSurface>,
+    /// まだ取り出されていない、水面を出入りしたもの。<br />
+    /// Crossings of the water surface not taken yet.
+    water_events: Vec<WaterEvent>,
+    character: Option<CharacterController>,
+}
+
+impl Default for PhysicsSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PhysicsSystem {
+    pub const FIXED_TIMESTEP: f64 = SIMULATION_TIMESTEP;
+
+    pub fn new() -> Self {
+        let mut integration_parameters = IntegrationParameters::default();
+        integration_parameters.set_dt(Self::FIXED_TIMESTEP as f32);
+        PhysicsSystem {
+            pipeline: PhysicsPipeline::new(),
+            gravity: Vector3::new(0.0, GRAVITY, 0.0),
+            integration_parameters,
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            joints: JointSet::new(),
+            body_handles: HashMap::new(),
+            terrains: HashMap::new(),
+            water_surfaces: vec![],
+            water_events: vec![],
+            character: None,
+        }
+    }
+
+    /// 地形の頂点からハイトフィールドのコライダーを作成する。<br />
+    /// Build a heightfield collider from the vertices of a terrain.
+    pub fn add_terrain_collider(
+        &mut self,
+        entity: DefaultKey,
+        primitive: &Primitive,
+        position: Vec3A,
+    ) -> anyhow::Result<()> {
+        let vertex_count = (primitive.vertices.len() as f32).sqrt() as usize;
+        if vertex_count < 2 || vertex_count * vertex_count != primitive.vertices.len() {
+            return Err(anyhow::anyhow!(
+                "Terrain vertices do not form a square grid."
+            ));
+        }
+        let last = &primitive.vertices[primitive.vertices.len() - 1].position;
+        let (size_x, size_z) = (last.x, last.z);
+        let heights = primitive
+            .vertices
+            .iter()
+            .map(|v| v.position.y)
+            .collect::<Vec<_>>();
+
+        // ハイトフィールドは原点を中心とするため、地形の中心に移動させる。
+        // Heightfields are centered at the origin, so move it to the center of the terrain.
+        let matrix = DMatrix::from_fn(vertex_count, vertex_count, |i, j| {
+            heights[i * vertex_count + j]
+        });
+        let body = RigidBodyBuilder::new_static()
+            .translation(
+                position.x + size_x * 0.5,
+                position.y,
+                position.z + size_z * 0.5,
+            )
+            .build();
+        let handle = self.bodies.insert(body);
+        let collider =
+            ColliderBuilder::heightfield(matrix, Vector3::new(size_x, 1.0, size_z)).build();
+        self.colliders.insert(collider, handle, &mut self.bodies);
+        self.body_handles.insert(entity, handle);
+        self.terrains.insert(
+            entity,
+            TerrainHeights {
+                origin: position,
+                size_x,
+                size_z,
+                vertex_count,
+                heights,
+            },
+        );
+        Ok(())
+    }
+
+    /// モデルのメッシュからコライダーを作成する。`convex`が真なら凸包を使う。<br />
+    /// Build a collider from the meshes of a model. Uses a convex hull if `convex` is true.
+    pub fn add_model_collider<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        entity: DefaultKey,
+        model: &Model<GraphicsType, BufferType, CommandType, TextureType>,
+        body_status: BodyStatus,
+        convex: bool,
+    ) -> anyhow::Result<()>
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let scale = model.position_info.scale;
+        let mut points = vec![];
+        let mut triangles = vec![];
+        for mesh in model.meshes.iter() {
+            let mesh_lock = mesh.lock();
+            for primitive in mesh_lock.primitives.iter() {
+                let offset = points.len() as u32;
+                points.extend(primitive.vertices.iter().map(|v| {
+                    let p = v.position * scale;
+                    Point3::new(p.x, p.y, p.z)
+                }));
+                triangles.extend(
+                    primitive
+                        .indices
+                        .chunks_exact(3)
+                        .map(|t| [t[0] + offset, t[1] + offset, t[2] + offset]),
+                );
+            }
+        }
+        if points.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Model {} has no vertices.",
+                &model.model_name
+            ));
+        }
+
+        let collider = if convex || body_status == BodyStatus::Dynamic {
+            ColliderBuilder::convex_hull(&points)
+                .ok_or_else(|| anyhow::anyhow!("Failed to build convex hull."))?
+        } else {
+            ColliderBuilder::trimesh(points, triangles)
+        }
+        .build();
+        let body = RigidBodyBuilder::new(body_status)
+            .position(to_isometry(
+                model.position_info.position,
+                quat_from_euler(model.position_info.rotation),
+            ))
+            .build();
+        let handle = self.bodies.insert(body);
+        self.colliders.insert(collider, handle, &mut self.bodies);
+        self.body_handles.insert(entity, handle);
+        Ok(())
+    }
+
+    /// プレイヤーのキャラクターコントローラーを作成する。<br />
+    /// Create the character controller for the player.
+    pub fn add_character(
+        &mut self,
+        entity: DefaultKey,
+        position: Vec3A,
+        radius: f32,
+        half_height: f32,
+    ) {
+        if let Some(character) = self.character.take() {
+            self.remove_body(character.entity);
+        }
+        let body = RigidBodyBuilder::new_kinematic()
+            .translation(position.x, position.y, position.z)
+            .build();
+        let handle = self.bodies.insert(body);
+        // 位置は足元なので、カプセルの底を足元に合わせる。甲板に立っても甲板を押し込まない。
+        // The position is at the feet, so the bottom of the capsule sits there and doesn't push into decks.
+        let collider = ColliderBuilder::capsule_y(half_height, radius)
+            .translation(0.0, half_height + radius, 0.0)
+            .build();
+        self.colliders.insert(collider, handle, &mut self.bodies);
+        self.body_handles.insert(entity, handle);
+        self.character = Some(CharacterController {
+            entity,
+            handle,
+            vertical_velocity: 0.0,
+            is_grounded: false,
+            platform: None,
+        });
+    }
+
+    /// 浮力を与える水を追加する。<br />
+    /// Add a body of water that applies buoyancy.
+    pub fn add_water_volume(&mut self, water_volume: WaterVolume) {
+        self.water_surfaces.push(WaterSurface::new(water_volume));
+    }
+
+    /// 指定した位置の今の水面の高さ。波紋も含む。水がなければ`None`。<br />
+    /// Current height of the water surface at the given position, including ripples. `None` if there is no water.
+    pub fn get_water_height(&self, x: f32, z: f32) -> Option<f32> {
+        self.water_surfaces.iter().find_map(|w| w.height_at(x, z))
+    }
+
+    /// 波紋をテクスチャにするための水面。<br />
+    /// The water surfaces, for turning their ripples into textures.
+    pub fn get_water_surfaces(&self) -> &[WaterSurface] {
+        &self.water_surfaces
+    }
+
+    /// 前に呼んでから水面を出入りしたものを取り出す。<br />
+    /// Take the crossings of the water surface since the last call.
+    pub fn take_water_events(&mut self) -> Vec<WaterEvent> {
+        std::mem::take(&mut self.water_events)
+    }
+
+    /// 重力の強さを調整パネルに登録する。<br />
+    /// Register the strength of gravity to the tweak panel.
+    pub fn register_tweaks(&mut self, tweaks: &mut TweakRegistry) {
+        let gravity = tweaks.register_f32(GRAVITY_TWEAK, GRAVITY, -30.0, 0.0);
+        self.set_gravity(gravity);
+    }
+
+    pub fn apply_tweaks(&mut self, tweaks: &TweakRegistry) {
+        if let Some(gravity) = tweaks.get_f32(GRAVITY_TWEAK) {
+            self.set_gravity(gravity);
+        }
+    }
+
+    pub fn set_gravity(&mut self, gravity: f32) {
+        self.gravity = Vector3::new(0.0, gravity, 0.0);
+    }
+
+    /// キャラクターが地面に立っているかどうか。<br />
+    /// Whether the character is standing on the ground.
+    pub fn is_character_grounded(&self) -> bool {
+        self.character
+            .as_ref()
+            .map(|c| c.is_grounded)
+            .unwrap_or(false)
+    }
+
+    /// 地形から指定した位置の地面の高さを求める。<br />
+    /// Get the ground height at the given position from terrains.
+    pub fn get_ground_height(&self, x: f32, z: f32) -> Option<f32> {
+        self.terrains.values().find_map(|t| t.get_height(x, z))
+    }
+
+    /// 光線と地形の交差を求める。一定の間隔で進めた後、二分探索で交点を絞り込む。<br />
+    /// Intersect a ray with terrains by marching at a fixed interval, then refining the hit with bisection.
+    pub fn raycast_terrain(&self, ray: &Ray, max_distance: f32) -> Option<(DefaultKey, f32)> {
+        let below_ground = |distance: f32| {
+            let point = ray.get_point(distance);
+            self.terrains.iter().find_map(|(entity, terrain)| {
+                terrain
+                    .get_height(point.x, point.z)
+                    .filter(|height| point.y <= *height)
+                    .map(|_| *entity)
+            })
+        };
+        let mut previous = 0.0;
+        let mut distance = RAYCAST_STEP;
+        while distance <= max_distance {
+            if below_ground(distance).is_some() {
+                let (mut above, mut below) = (previous, distance);
+                for _ in 0..RAYCAST_REFINEMENTS {
+                    let middle = (above + below) * 0.5;
+                    if below_ground(middle).is_some() {
+                        below = middle;
+                    } else {
+                        above = middle;
+                    }
+                }
+                return below_ground(below).map(|entity| (entity, below));
+            }
+            previous = distance;
+            distance += RAYCAST_STEP;
+        }
+        None
+    }
+
+    /// 二点の間を地形が遮っているかどうか。<br />
+    /// Whether terrain blocks the line between two points.
+    pub fn is_line_blocked_by_terrain(&self, from: Vec3A, to: Vec3A) -> bool {
+        let distance = (to - from).length();
+        if distance <= 0.0 {
+            return false;
+        }
+        let hit = self.raycast_terrain(&Ray::new(from, to - from), distance);
+        is_occluded(hit.map(|(_, hit)| hit), distance)
+    }
+
+    /// エンティティの剛体を速度を止めて別の位置に移す。セーブデータを読み込んだ時に使う。<br />
+    /// Move the rigid body of an entity to another position, stopping its velocity. Used when loading save data.
+    pub fn teleport_body(&mut self, entity: DefaultKey, position: Vec3A, rotation: Vec3A) {
+        let handle = match self.body_handles.get(&entity) {
+            Some(handle) => *handle,
+            None => return,
+        };
+        if let Some(body) = self.bodies.get_mut(handle) {
+            body.set_position(to_isometry(position, quat_from_euler(rotation)), true);
+            if body.is_dynamic() {
+                body.set_linvel(Vector3::zeros(), true);
+                body.set_angvel(Vector3::zeros(), true);
+            }
+        }
+        if let Some(character) = self.character.as_mut().filter(|c| c.entity == entity) {
+            character.vertical_velocity = 0.0;
+            character.platform = None;
+        }
+    }
+
+    /// エンティティの剛体とコライダーを削除する。<br />
+    /// Remove the rigid body and colliders of an entity.
+    pub fn remove_body(&mut self, entity: DefaultKey) {
+        if let Some(handle) = self.body_handles.remove(&entity) {
+            self.bodies
+                .remove(handle, &mut self.colliders, &mut self.joints);
+        }
+        self.terrains.remove(&entity);
+        if self
+            .character
+            .as_ref()
+            .map(|c| c.entity == entity)
+            .unwrap_or(false)
+        {
+            self.character = None;
+        }
+    }
+
+    /// シミュレーションを`FIXED_TIMESTEP`だけ一回進め、結果をトランスフォームに書き戻す。<br />
+    /// Advance the simulation a single step of `FIXED_TIMESTEP` and write the results back into transforms.
+    pub fn step<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        self.drive_kinematic_bodies(world);
+        self.apply_buoyancy(world);
+        self.move_character(world);
+        self.pipeline.step(
+            &self.gravity,
+            &self.integration_parameters,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.joints,
+            None,
+            None,
+            &(),
+        );
+        self.update_water_surfaces(world);
+        self.sync_dynamic_bodies(world);
+    }
+
+    /// ネットワークで動かされるキネマティックな剛体をトランスフォームの位置に移動する。<br />
+    /// Move network-driven kinematic bodies to their transforms.
+    fn drive_kinematic_bodies<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        world: &World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let character_entity = self.character.as_ref().map(|c| c.entity);
+        for (entity, handle) in self.body_handles.iter() {
+            if Some(*entity) == character_entity {
+                continue;
+            }
+            let transform = match world.get::<Transform>(*entity) {
+                Some(t) => t,
+                None => continue,
+            };
+            if let Some(body) = self.bodies.get_mut(*handle) {
+                if body.is_kinematic() {
+                    body.set_next_kinematic_position(to_isometry(
+                        transform.position,
+                        quat_from_euler(transform.rotation),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// キャラクターを水平方向にはトランスフォームと移動目標に合わせ、垂直方向には重力と地面で動かす。<br />
+    /// Move the character horizontally to its transform and move target, and vertically by gravity and the ground.
+    fn move_character<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let dt = Self::FIXED_TIMESTEP as f32;
+        let entity = match self.character.as_ref() {
+            Some(c) => c.entity,
+            None => return,
+        };
+        // 乗り物に乗っている間は乗り物が位置を決めるので、剛体を合わせるだけにする。
+        // While riding, the mount decides the position, so the body only follows it.
+        if world.get::<Rider>(entity).is_some() {
+            let character = self
+                .character
+                .as_mut()
+                .expect("Failed to get character controller.");
+            character.vertical_velocity = 0.0;
+            character.is_grounded = false;
+            character.platform = None;
+            if let (Some(transform), Some(body)) = (
+                world.get::<Transform>(entity),
+                self.bodies.get_mut(character.handle),
+            ) {
+                body.set_next_kinematic_position(to_isometry(
+                    transform.position,
+                    quat_from_euler(transform.rotation),
+                ));
+            }
+            return;
+        }
+        let lift = self.carry_on_platform(world);
+        Self::move_towards_target(entity, dt, world);
+        let support = match world.get::<Transform>(entity) {
+            Some(t) => self.get_support(t.position, world),
+            None => return,
+        };
+        let character = self
+            .character
+            .as_mut()
+            .expect("Failed to get character controller.");
+        let transform = match world.get_mut::<Transform>(character.entity) {
+            Some(t) => t,
+            None => return,
+        };
+        let body = match self.bodies.get_mut(character.handle) {
+            Some(b) => b,
+            None => return,
+        };
+
+        let current_y = body.position().translation.vector.y + lift;
+        character.vertical_velocity += self.gravity.y * dt;
+        let mut next_position = transform.position;
+        next_position.y = current_y + character.vertical_velocity * dt;
+        character.is_grounded = false;
+        character.platform = None;
+        if let Some((ground, platform)) = support {
+            if next_position.y <= ground {
+                next_position.y = ground;
+                character.vertical_velocity = 0.0;
+                character.is_grounded = true;
+                character.platform = platform;
+            }
+        }
+        transform.position = next_position;
+        body.set_next_kinematic_position(to_isometry(
+            next_position,
+            quat_from_euler(transform.rotation),
+        ));
+    }
+
+    /// 前のステップから甲板が動いた分だけ、その上に立つキャラクターを運ぶ。運んだ高さを返す。<br />
+    /// Carry the character standing on a deck by however much the deck moved since the last step. Returns the height it was carried by.
+    fn carry_on_platform<GraphicsType, BufferType, CommandType, TextureType>(
+        &self,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) -> f32
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let (entity, (platform, previous_position, previous_rotation)) =
+            match self.character.as_ref() {
+                Some(CharacterController {
+                    entity,
+                    platform: Some(platform),
+                    ..
+                }) => (*entity, *platform),
+                _ => return 0.0,
+            };
+        let (position, rotation) = match self
+            .body_handles
+            .get(&platform)
+            .and_then(|handle| self.bodies.get(*handle))
+        {
+            Some(body) => from_isometry(body.position()),
+            None => return 0.0,
+        };
+        let transform = match world.get_mut::<Transform>(entity) {
+            Some(t) => t,
+            None => return 0.0,
+        };
+        let delta = rotation * previous_rotation.conjugate();
+        let carried = position + delta * (transform.position - previous_position);
+        let lift = carried.y - transform.position.y;
+        transform.position = carried;
+        transform.rotation.y += euler_from_quat(delta).y;
+        lift
+    }
+
+    /// 足元にある一番高い地面か甲板の高さ。甲板なら、その位置と回転も返す。<br />
+    /// Height of the highest ground or deck below the feet. For a deck, its position and rotation are returned as well.
+    fn get_support<GraphicsType, BufferType, CommandType, TextureType>(
+        &self,
+        feet: Vec3A,
+        world: &World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) -> Option<(f32, Option<(DefaultKey, Vec3A, Quat)>)>
+    where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let mut support = self
+            .get_ground_height(feet.x, feet.z)
+            .map(|height| (height, None));
+        for (entity, handle) in self.body_handles.iter() {
+            let buoyancy = match world.get::<Buoyancy>(*entity) {
+                Some(b) if b.is_platform => b,
+                _ => continue,
+            };
+            let (position, rotation) = match self.bodies.get(*handle) {
+                Some(body) => from_isometry(body.position()),
+                None => continue,
+            };
+            let height = match buoyancy.get_deck_height(position, rotation, feet) {
+                Some(h) if h <= feet.y + PLATFORM_STEP_HEIGHT => h,
+                _ => continue,
+            };
+            if support.map(|(h, _)| height > h).unwrap_or(true) {
+                support = Some((height, Some((*entity, position, rotation))));
+            }
+        }
+        support
+    }
+
+    /// 水に入った浮く剛体の底の四隅に浮力を加え、沈んだ割合に応じて速度を抑える。<br />
+    /// Apply buoyancy at the bottom corners of floating bodies in water, and damp their velocity by how much of them is submerged.
+    fn apply_buoyancy<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        world: &World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        if self.water_surfaces.is_empty() {
+            return;
+        }
+        let dt = Self::FIXED_TIMESTEP as f32;
+        for (entity, handle) in self.body_handles.iter() {
+            let buoyancy = match world.get::<Buoyancy>(*entity) {
+                Some(b) => *b,
+                None => continue,
+            };
+            let body = match self.bodies.get_mut(*handle) {
+                Some(b) if b.is_dynamic() => b,
+                _ => continue,
+            };
+            let (position, rotation) = from_isometry(body.position());
+            let points = buoyancy.get_sample_points(position, rotation);
+            let volume = buoyancy.get_volume() / points.len() as f32;
+            let height = buoyancy.half_extents.y * 2.0;
+            let mut submerged = 0.0;
+            for point in points.iter() {
+                let (density, water_height) = match self.water_surfaces.iter().find_map(|w| {
+                    w.height_at(point.x, point.z)
+                        .map(|h| (w.get_volume().density, h))
+                }) {
+                    Some(w) => w,
+                    None => continue,
+                };
+                let depth = water_height - point.y;
+                let force = get_buoyant_force(density, self.gravity.y, volume, depth, height);
+                if force > 0.0 {
+                    body.apply_force_at_point(
+                        Vector3::new(0.0, force, 0.0),
+                        Point3::new(point.x, point.y, point.z),
+                        true,
+                    );
+                    submerged += (depth / height).min(1.0) / points.len() as f32;
+                }
+            }
+            if submerged > 0.0 {
+                let linear = (1.0 - buoyancy.linear_drag * submerged * dt).max(0.0);
+                let angular = (1.0 - buoyancy.angular_drag * submerged * dt).max(0.0);
+                let linvel = *body.linvel() * linear;
+                let angvel = *body.angvel() * angular;
+                body.set_linvel(linvel, true);
+                body.set_angvel(angvel, true);
+            }
+        }
+    }
+
+    /// 剛体の位置で水面を進め、水面を出入りしたものを溜める。浮くものは底の広さ、それ以外は決まった半径で波紋を立てる。<br />
+    /// Advance the water surfaces with the positions of the bodies and queue the crossings. Floating things make ripples as wide as their bottom, and everything else with a fixed radius.
+    fn update_water_surfaces<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        world: &World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        if self.water_surfaces.is_empty() {
+            return;
+        }
+        let bodies = self
+            .body_handles
+            .iter()
+            .filter_map(|(entity, handle)| {
+                let (position, _) = from_isometry(self.bodies.get(*handle)?.position());
+                let radius = world
+                    .get::<Buoyancy>(*entity)
+                    .map(|b| b.half_extents.x.max(b.half_extents.z))
+                    .unwrap_or(SPLASH_RADIUS);
+                Some((*entity, position, radius))
+            })
+            .collect::<Vec<_>>();
+        for surface in self.water_surfaces.iter_mut() {
+            let events = surface.update(Self::FIXED_TIMESTEP as f32, &bodies);
+            self.water_events.extend(events);
+        }
+    }
+
+    /// プレイヤーの移動目標に向かってトランスフォームを水平に進める。到着したら目標を消す。<br />
+    /// Advance the transform horizontally towards the player's move target, clearing the target on arrival.
+    fn move_towards_target<GraphicsType, BufferType, CommandType, TextureType>(
+        entity: DefaultKey,
+        dt: f32,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let controller = match world.get::<PlayerController>(entity) {
+            Some(c) => *c,
+            None => return,
+        };
+        let target = match controller.move_target {
+            Some(t) => t,
+            None => return,
+        };
+        let transform = match world.get_mut::<Transform>(entity) {
+            Some(t) => t,
+            None => return,
+        };
+        let offset = Vec3A::new(
+            target.x - transform.position.x,
+            0.0,
+            target.z - transform.position.z,
+        );
+        let distance = offset.length();
+        let step = controller.move_speed * dt;
+        if distance > step {
+            transform.position += offset / distance * step;
+            transform.rotation.y = offset.x.atan2(offset.z);
+            return;
+        }
+        transform.position.x = target.x;
+        transform.position.z = target.z;
+        if let Some(controller) = world.get_mut::<PlayerController>(entity) {
+            controller.move_target = None;
+        }
+    }
+
+    /// 動的な剛体の位置と速度をコンポーネントに書き戻す。<br />
+    /// Write the position and velocity of dynamic bodies back into components.
+    fn sync_dynamic_bodies<GraphicsType, BufferType, CommandType, TextureType>(
+        &self,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        for (entity, handle) in self.body_handles.iter() {
+            let body = match self.bodies.get(*handle) {
+                Some(b) if b.is_dynamic() => b,
+                _ => continue,
+            };
+            let (position, rotation) = from_isometry(body.position());
+            if let Some(transform) = world.get_mut::<Transform>(*entity) {
+                transform.position = position;
+                transform.rotation = euler_from_quat(rotation);
+            }
+            if let Some(rigid_body) = world.get_mut::<RigidBody>(*entity) {
+                let velocity = body.linvel();
+                rigid_body.velocity = Vec3A::new(velocity.x, velocity.y, velocity.z);
+            }
+        }
+    }
+}
+
+fn to_isometry(position: Vec3A, rotation: Quat) -> Isometry3<f32> {
+    Isometry3::from_parts(
+        Translation3::new(position.x, position.y, position.z),
+        UnitQuaternion::from_quaternion(Quaternion::new(
+            rotation.w, rotation.x, rotation.y, rotation.z,
+        )),
+    )
+}
+
+fn from_isometry(isometry: &Isometry3<f32>) -> (Vec3A, Quat) {
+    let translation = isometry.translation.vector;
+    let rotation = isometry.rotation.quaternion();
+    (
+        Vec3A::new(translation.x, translation.y, translation.z),
+        Quat::from_xyzw(rotation.i, rotation.j, rotation.k, rotation.w),
+    )
+}
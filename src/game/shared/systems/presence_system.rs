@@ -0,0 +1,243 @@
+use crate::game::shared::structs::{parse_join_link, PresenceActivity, Settings};
+use crate::game::shared::traits::PresenceProvider;
+
+/// 有効なサービスに今の活動を知らせ、フレンドからの参加を受け取る。<br />
+/// サービスは機能フラグ（`discord`、`steam`）で有効にし、どれも使えなければ何もしない。<br />
+/// Reports the current activity to the enabled services, and receives joins from friends.<br />
+/// Services are enabled with feature flags (`discord`, `steam`), and nothing happens when none are available.
+pub struct PresenceSystem {
+    providers: Vec<Box<dyn PresenceProvider>>,
+    activity: Option<PresenceActivity>,
+    /// 参加する部屋のID。ロビーに戻った時に参加する。<br />
+    /// ID of the room to join, joined once back in the lobby.
+    pending_join: Option<String>,
+}
+
+impl PresenceSystem {
+    /// 機能フラグで有効にしたサービスに接続する。接続できなかったサービスは飛ばす。<br />
+    /// Connect to the services enabled by feature flags, skipping the ones that can't be connected to.
+    #[cfg_attr(not(feature = "discord"), allow(unused_variables))]
+    pub fn new(settings: &Settings) -> Self {
+        #[allow(unused_mut)]
+        let mut providers: Vec<Box<dyn PresenceProvider>> = vec![];
+        #[cfg(feature = "discord")]
+        {
+            match discord::DiscordPresence::new(settings.discord_client_id) {
+                Ok(provider) => providers.push(Box::new(provider)),
+                Err(e) => log::warn!("Discord Rich Presence is unavailable: {}", e),
+            }
+        }
+        #[cfg(feature = "steam")]
+        {
+            match steam::SteamPresence::new() {
+                Ok(provider) => providers.push(Box::new(provider)),
+                Err(e) => log::warn!("Steam Rich Presence is unavailable: {}", e),
+            }
+        }
+        Self::with_providers(providers)
+    }
+
+    pub fn with_providers(providers: Vec<Box<dyn PresenceProvider>>) -> Self {
+        PresenceSystem {
+            providers,
+            activity: None,
+            pending_join: None,
+        }
+    }
+
+    pub fn get_activity(&self) -> Option<&PresenceActivity> {
+        self.activity.as_ref()
+    }
+
+    /// 参加リンクを受け取り、活動が変わった時だけサービスに知らせる。<br />
+    /// Receive join links, and report the activity to the services only when it has changed.
+    pub fn update(&mut self, activity: PresenceActivity) {
+        for provider in self.providers.iter_mut() {
+            for link in provider.poll_join_links() {
+                match parse_join_link(&link) {
+                    Some(room_id) => self.pending_join = Some(room_id),
+                    None => log::warn!("Invalid join link from {}: {}", provider.get_name(), link),
+                }
+            }
+        }
+        if self.activity.as_ref() == Some(&activity) {
+            return;
+        }
+        for provider in self.providers.iter_mut() {
+            if let Err(e) = provider.set_activity(&activity) {
+                log::warn!("Failed to set {} presence: {}", provider.get_name(), e);
+            }
+        }
+        self.activity = Some(activity);
+    }
+
+    /// 参加を待っている部屋のIDを取り出す。<br />
+    /// Take the ID of the room waiting to be joined.
+    pub fn take_join_request(&mut self) -> Option<String> {
+        self.pending_join.take()
+    }
+}
+
+#[cfg(feature = "discord")]
+mod discord {
+    use crate::game::shared::structs::PresenceActivity;
+    use crate::game::shared::traits::PresenceProvider;
+    use crossbeam::channel::Receiver;
+    use discord_rpc_client::models::Event;
+    use discord_rpc_client::Client;
+
+    /// DiscordのRich Presence。アプリケーションIDは設定の`discord_client_id`で指定する。<br />
+    /// Discord Rich Presence. The application ID is set with `discord_client_id` in the settings.
+    pub struct DiscordPresence {
+        client: Client,
+        join_receiver: Receiver<String>,
+        request_receiver: Receiver<u64>,
+    }
+
+    impl DiscordPresence {
+        pub fn new(client_id: u64) -> anyhow::Result<Self> {
+            if client_id == 0 {
+                anyhow::bail!("discord_client_id isn't set.");
+            }
+            let mut client = Client::new(client_id);
+            let (join_sender, join_receiver) = crossbeam::channel::unbounded();
+            let (request_sender, request_receiver) = crossbeam::channel::unbounded();
+            client.on_activity_join(move |ctx| {
+                if let Some(secret) = ctx.event["secret"].as_str() {
+                    join_sender.send(secret.to_string()).ok();
+                }
+            });
+            client.on_activity_join_request(move |ctx| {
+                if let Some(user_id) = ctx.event["user"]["id"]
+                    .as_str()
+                    .and_then(|id| id.parse::<u64>().ok())
+                {
+                    request_sender.send(user_id).ok();
+                }
+            });
+            client.start();
+            client.subscribe(Event::ActivityJoin, |j| j)?;
+            client.subscribe(Event::ActivityJoinRequest, |j| j)?;
+            Ok(DiscordPresence {
+                client,
+                join_receiver,
+                request_receiver,
+            })
+        }
+    }
+
+    impl PresenceProvider for DiscordPresence {
+        fn get_name(&self) -> &str {
+            "Discord"
+        }
+
+        fn set_activity(&mut self, activity: &PresenceActivity) -> anyhow::Result<()> {
+            let details = activity.get_details();
+            let state = activity.get_state();
+            let party = activity
+                .get_party()
+                .map(|(id, players, max_players)| (id.to_string(), players, max_players));
+            let join_link = activity.get_join_link();
+            self.client.set_activity(|mut act| {
+                act = act.details(details);
+                if let Some(state) = state {
+                    act = act.state(state);
+                }
+                if let Some((id, players, max_players)) = party {
+                    act = act.party(|p| p.id(id).size((players as u32, max_players as u32)));
+                }
+                if let Some(join_link) = join_link {
+                    act = act.secrets(|s| s.join(join_link));
+                }
+                act
+            })?;
+            Ok(())
+        }
+
+        fn poll_join_links(&mut self) -> Vec<String> {
+            // 部屋は誰でも入れるので、参加の申し込みはすぐに受け入れる。
+            // Rooms are open to everyone, so join requests are accepted right away.
+            for user_id in self.request_receiver.try_iter() {
+                if let Err(e) = self.client.send_activity_join_invite(user_id) {
+                    log::warn!("Failed to accept the join request of {}: {}", user_id, e);
+                }
+            }
+            self.join_receiver.try_iter().collect()
+        }
+    }
+
+    impl Drop for DiscordPresence {
+        fn drop(&mut self) {
+            self.client.clear_activity().ok();
+        }
+    }
+}
+
+#[cfg(feature = "steam")]
+mod steam {
+    use crate::game::shared::structs::PresenceActivity;
+    use crate::game::shared::traits::PresenceProvider;
+    use crossbeam::channel::Receiver;
+    use steamworks::{CallbackHandle, Client, GameRichPresenceJoinRequested, SingleClient};
+
+    /// SteamworksのRich Presence。Steamのクライアントが起動していなければ使えない。<br />
+    /// Steamworks Rich Presence, unavailable unless the Steam client is running.
+    pub struct SteamPresence {
+        client: Client,
+        single: SingleClient,
+        join_receiver: Receiver<String>,
+        _join_callback: CallbackHandle,
+    }
+
+    impl SteamPresence {
+        pub fn new() -> anyhow::Result<Self> {
+            let (client, single) = Client::init()?;
+            let (join_sender, join_receiver) = crossbeam::channel::unbounded();
+            let join_callback =
+                client.register_callback(move |join: GameRichPresenceJoinRequested| {
+                    join_sender.send(join.connect).ok();
+                });
+            Ok(SteamPresence {
+                client,
+                single,
+                join_receiver,
+                _join_callback: join_callback,
+            })
+        }
+    }
+
+    impl PresenceProvider for SteamPresence {
+        fn get_name(&self) -> &str {
+            "Steam"
+        }
+
+        fn set_activity(&mut self, activity: &PresenceActivity) -> anyhow::Result<()> {
+            let friends = self.client.friends();
+            friends.clear_rich_presence();
+            let status = match activity.get_state() {
+                Some(state) => format!("{} ({})", activity.get_details(), state),
+                None => activity.get_details(),
+            };
+            friends.set_rich_presence("status", Some(&status));
+            if let Some((id, players, _)) = activity.get_party() {
+                friends.set_rich_presence("steam_player_group", Some(id));
+                friends.set_rich_presence("steam_player_group_size", Some(&players.to_string()));
+            }
+            if let Some(join_link) = activity.get_join_link() {
+                friends.set_rich_presence("connect", Some(&join_link));
+            }
+            Ok(())
+        }
+
+        fn poll_join_links(&mut self) -> Vec<String> {
+            self.single.run_callbacks();
+            self.join_receiver.try_iter().collect()
+        }
+    }
+
+    impl Drop for SteamPresence {
+        fn drop(&mut self) {
+            self.client.friends().clear_rich_presence();
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::game::shared::structs::{Cutscene, CutsceneFrame, CutscenePlayer};
+use crate::game::shared::util::math::direction_from_yaw_pitch;
+use crate::game::traits::{Disposable, GraphicsBase};
+use crate::game::{Camera, Transform, World};
+
+/// キーフレームのカットシーンを再生し、カメラとエンティティのトランスフォームを動かす。<br />
+/// Plays keyframed cutscenes, moving the camera and the transforms of entities.
+pub struct TimelineSystem {
+    player: Option<CutscenePlayer>,
+    fade: f32,
+    /// カットシーンが始まる前の視野角。終わったら元に戻す。<br />
+    /// Field of view before the cutscene started, restored when it ends.
+    saved_field_of_view: Option<f32>,
+}
+
+impl Default for TimelineSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimelineSystem {
+    pub fn new() -> Self {
+        TimelineSystem {
+            player: None,
+            fade: 0.0,
+            saved_field_of_view: None,
+        }
+    }
+
+    /// カットシーンを再生する。再生中のカットシーンは置き換える。<br />
+    /// Play a cutscene, replacing the one being played.
+    pub fn play(&mut self, cutscene: Cutscene) {
+        log::info!("Playing cutscene {}.", &cutscene.name);
+        self.player = Some(CutscenePlayer::new(cutscene));
+    }
+
+    /// データファイルからカットシーンを読み込んで再生する。<br />
+    /// Load a cutscene from a data file and play it.
+    pub fn play_file(&mut self, file_name: &str) -> anyhow::Result<()> {
+        let cutscene = Cutscene::load(file_name)?;
+        self.play(cutscene);
+        Ok(())
+    }
+
+    /// 再生中のカットシーンを最後まで飛ばす。<br />
+    /// Skip the cutscene being played to its end.
+    pub fn skip(&mut self) {
+        if let Some(player) = self.player.as_mut() {
+            player.skip();
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.player.is_some()
+    }
+
+    /// 画面を黒く覆う割合。<br />
+    /// How much the screen is covered in black.
+    pub fn get_fade(&self) -> f32 {
+        self.fade
+    }
+
+    /// カットシーンを進めて、カメラとエンティティに反映する。最後まで進んだら再生を終える。<br />
+    /// Advance the cutscene and apply it to the camera and entities. Playback ends once it reaches the end.
+    pub fn update<GraphicsType, BufferType, CommandType, TextureType>(
+        &mut self,
+        delta_time: f64,
+        camera: &mut Camera,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        let player = match self.player.as_mut() {
+            Some(player) => player,
+            None => return,
+        };
+        if self.saved_field_of_view.is_none() {
+            self.saved_field_of_view = Some(camera.field_of_view);
+        }
+        let frame = player.advance(delta_time as f32);
+        let is_finished = player.is_finished();
+        Self::apply_frame(&frame, camera, world);
+        self.fade = frame.fade.unwrap_or(0.0);
+
+        if is_finished {
+            if let Some(player) = self.player.take() {
+                log::info!("Finished cutscene {}.", player.get_name());
+            }
+            if let Some(field_of_view) = self.saved_field_of_view.take() {
+                camera.set_field_of_view(field_of_view);
+            }
+            self.fade = 0.0;
+        }
+    }
+
+    fn apply_frame<GraphicsType, BufferType, CommandType, TextureType>(
+        frame: &CutsceneFrame,
+        camera: &mut Camera,
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) where
+        GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+        BufferType: 'static + Disposable + Clone,
+        CommandType: 'static + Clone,
+        TextureType: 'static + Clone + Disposable,
+    {
+        // 回転のトラックがなければ、位置だけ動かして今の向きを保つ。
+        // Without a rotation track, only the position moves and the current direction is kept.
+        let direction = match frame.camera_rotation {
+            Some((yaw, pitch)) => direction_from_yaw_pitch(yaw, pitch),
+            None => camera.target - camera.position,
+        };
+        if let Some(position) = frame.camera_position {
+            camera.position = position;
+        }
+        camera.target = camera.position + direction;
+        if let Some(field_of_view) = frame.field_of_view {
+            camera.set_field_of_view(field_of_view);
+        }
+
+        for pose in frame.entities.iter() {
+            let entity = match world.find_by_name(&pose.entity) {
+                Some(entity) => entity,
+                None => continue,
+            };
+            if let Some(transform) = world.get_mut::<Transform>(entity) {
+                if let Some(position) = pose.position {
+                    transform.position = position;
+                }
+                if let Some(rotation) = pose.rotation {
+                    transform.rotation = rotation;
+                }
+                if let Some(scale) = pose.scale {
+                    transform.scale = scale;
+                }
+            }
+        }
+    }
+}
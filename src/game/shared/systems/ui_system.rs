@@ -1,17 +1,56 @@
+#[cfg(target_os = "windows")]
+use crate::game::graphics::dx12 as DX12;
 use crate::game::graphics::vk::{Buffer, Graphics, Image};
-use crate::game::traits::{Disposable, GraphicsBase};
-use crate::game::{Drawer, NetworkSystem};
-use crate::protos::grpc_service::game_state::Player;
-use ash::vk::{CommandBuffer, Framebuffer, Semaphore, Viewport};
+use crate::game::shared::structs::games::connection::ConnectionState;
+use crate::game::shared::structs::games::credentials::{validate_login, SESSION_PATH};
+use crate::game::shared::structs::games::lan_discovery::{DiscoveredHost, HostAddress};
+use crate::game::shared::structs::games::match_result::{MatchResults, ScoreboardRow};
+use crate::game::shared::structs::games::network_stats::NetworkStats;
+use crate::game::shared::structs::games::player_settings::{get_room_nameplates, Nameplate};
+use crate::game::shared::structs::games::protocol::{ProtocolCompatibility, PROTOCOL_VERSION};
+use crate::game::shared::structs::{
+    fade_color, format_mebibytes, AssetPreview, AssetWarning, FrameProfiler, LoadingProgress,
+    MemoryStats, PreviewAssetKind, ScreenShape, Settings,
+};
+#[cfg(debug_assertions)]
+use crate::game::shared::structs::{
+    AnimationStateInfo, Inspector, InspectorEdit, InspectorEntry, ParameterValue, PositionInfo,
+};
+use crate::game::traits::{Disposable, GraphicsBase, UiDrawerBackend};
+#[cfg(target_os = "windows")]
+use crate::game::ui::dx12::Drawer as DX12Drawer;
+use crate::game::ui::font::read_font_files;
+use crate::game::ui::rich_text::{
+    parse_message, EMOJI_ATLAS_PATH, EMOJI_NAMES, EMOJI_SIZE, LINK_COLOR,
+};
+use crate::game::ui::{Console, LinkTarget, RichSpan, UiWindow, WindowBounds, WindowManager};
+#[cfg(debug_assertions)]
+use crate::game::ui::{TweakParameter, TweakRegistry, TweakValue, TWEAK_CONFIG_PATH};
+use crate::game::{Drawer, LanBrowser, NetworkSystem};
+use crate::protos::grpc_service::game_state::{Player, RoomState};
+use crate::protos::grpc_service::IncomingMessage;
+use ash::vk::{CommandBuffer, SampleCountFlags};
+use glam::Vec2;
+#[cfg(debug_assertions)]
+use glam::{Vec3A, Vec4};
 use nuklear::{
-    AntiAliasing, Context, ConvertConfig, EditType, Flags, FontAtlas, FontID, LayoutFormat,
-    PanelFlags, TextAlignment, TextEdit,
+    AntiAliasing, ChartType, Context, ConvertConfig, EditType, Flags, FontAtlas, FontID,
+    LayoutFormat, PanelFlags, StyleItem, TextAlignment, TextEdit,
 };
+#[cfg(debug_assertions)]
+use slotmap::DefaultKey;
 use std::marker::PhantomData;
 use std::mem::ManuallyDrop;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+#[cfg(target_os = "windows")]
+use winapi::shared::dxgiformat::DXGI_FORMAT_B8G8R8A8_UNORM;
+#[cfg(target_os = "windows")]
+use winapi::um::d3d12::ID3D12GraphicsCommandList;
 use winit::event::{ElementState, MouseScrollDelta, VirtualKeyCode};
+#[cfg(target_os = "windows")]
+use wio::com::ComPtr;
 
 const MAX_VERTEX_MEMORY: usize = 512 * 1024;
 const MAX_INDEX_MEMORY: usize = 128 * 1024;
@@ -19,6 +58,64 @@ const MAX_COMMANDS_MEMORY: usize = 64 * 1024;
 const RATIO_W: [f32; 2] = [0.15, 0.85];
 const RATIO_WC: [f32; 3] = [0.15, 0.7, 0.15];
 const MOUSE_SENSITIVITY: f64 = 22.0;
+const CHAT_VISIBLE_LINES: usize = 8;
+const CONSOLE_VISIBLE_LINES: usize = 12;
+const MIN_ROOM_PLAYERS: i32 = 2;
+const MAX_ROOM_PLAYERS: i32 = 8;
+const DEFAULT_ROOM_PLAYERS: i32 = 4;
+/// 試合を描画できないグラフィックスAPIで、部屋に入るボタンの代わりに表示する文。<br />
+/// Text shown in place of the buttons entering rooms with graphics APIs that can't render matches.
+const SCENES_UNAVAILABLE_MESSAGE: &str = "Play matches with API=VULKAN.";
+/// 窓の外で使うコンテキストのフォントの大きさ。<br />
+/// Font size of the context outside of windows.
+const DEFAULT_FONT_SIZE: u8 = 16;
+const TWEAK_WINDOW: &str = "Tweaks";
+const ANIMATION_WINDOW: &str = "Animation";
+const INSPECTOR_WINDOW: &str = "Inspector";
+const FADE_WINDOW: &str = "Fade";
+const DEBUG_DRAW_WINDOW: &str = "Debug Draw";
+const NAMEPLATE_WINDOW: &str = "Nameplates";
+const JOINT_NAME_WINDOW: &str = "Joint Names";
+/// 関節の名前を置く枠の大きさ。名前は関節の右に置く。<br />
+/// Size of the box a joint name is placed in. Names are placed to the right of their joints.
+const JOINT_NAME_WIDTH: f32 = 160.0;
+const JOINT_NAME_HEIGHT: f32 = 16.0;
+const JOINT_NAME_COLOR: [u8; 4] = [255, 230, 180, 230];
+/// 名札の一行の幅と高さ。名札は投影した位置を中心に置く。<br />
+/// Width and height of a line of a nameplate. Nameplates are centered on their projected positions.
+const NAMEPLATE_WIDTH: f32 = 240.0;
+const NAMEPLATE_LINE_HEIGHT: f32 = 18.0;
+/// 称号とチャットを読まない印の色。<br />
+/// Color of titles and of the mark for players who don't read chat.
+const NAMEPLATE_NOTE_COLOR: [u8; 4] = [200, 200, 200, 220];
+const SETTINGS_WINDOW: &str = "Settings";
+const PROFILER_WINDOW: &str = "Profiler";
+const SAFE_MODE_WINDOW: &str = "Safe Mode";
+const ASSET_WARNINGS_WINDOW: &str = "Missing Assets";
+const LOADING_WINDOW: &str = "Loading";
+const CONSOLE_WINDOW: &str = "Console";
+const PREVIEW_WINDOW: &str = "Preview";
+const NETWORK_WINDOW: &str = "Network";
+const SCOREBOARD_WINDOW: &str = "Scoreboard";
+const RESULTS_WINDOW: &str = "Results";
+
+/// 得点表の名前、HP、勝敗の列の幅の割合。<br />
+/// Width ratios of the name, HP and win/lose columns of the scoreboard.
+const RATIO_SCOREBOARD: [f32; 3] = [0.5, 0.25, 0.25];
+const DOWN_PLAYER_COLOR: [u8; 4] = [150, 150, 150, 255];
+/// フレーム時間のグラフの最小の上限（ミリ秒）。60FPSのフレームが半分の高さになる。<br />
+/// Minimum upper bound of the frame time graph in milliseconds, so that a 60 FPS frame is drawn at half height.
+const PROFILER_GRAPH_MIN_MAX: f64 = 33.3;
+const MEMORY_WARNING_COLOR: [u8; 4] = [255, 180, 0, 255];
+/// 設定画面に出す調整パラメーターの名前の接頭辞。<br />
+/// Prefixes of the names of tweak parameters shown on the settings page.
+const SETTINGS_PREFIXES: [&str; 2] = ["Graphics/", "Interface/"];
+const RATIO_TWEAK: [f32; 3] = [0.4, 0.45, 0.15];
+/// インスペクターで位置・回転・大きさを表示する順番と、ドラッグ一回で変わる量。<br />
+/// Order in which the inspector shows position, rotation and scale, and the amount changed per drag step.
+#[cfg(debug_assertions)]
+const INSPECTOR_VECTORS: [(&str, f32); 3] =
+    [("Position", 0.1), ("Rotation", 0.01), ("Scale", 0.01)];
 
 struct Media {
     font_14: FontID,
@@ -39,6 +136,11 @@ pub struct RegistrationInputs {
     pub email_length: i32,
     pub password_input: [u8; 64],
     pub password_length: i32,
+    pub confirmation_input: [u8; 64],
+    pub confirmation_length: i32,
+    /// 最後に登録できなかった理由。<br />
+    /// Why the last registration failed.
+    pub registration_error: Option<String>,
 }
 
 impl Default for RegistrationInputs {
@@ -58,6 +160,9 @@ impl RegistrationInputs {
             email_length: 0,
             password_input: [0; 64],
             password_length: 0,
+            confirmation_input: [0; 64],
+            confirmation_length: 0,
+            registration_error: None,
         }
     }
 
@@ -66,10 +171,13 @@ impl RegistrationInputs {
         self.nickname_length = 0;
         self.email_length = 0;
         self.password_length = 0;
+        self.confirmation_length = 0;
         self.username_input = [0; 64];
         self.nickname_input = [0; 64];
         self.email_input = [0; 64];
         self.password_input = [0; 64];
+        self.confirmation_input = [0; 64];
+        self.registration_error = None;
     }
 }
 
@@ -80,6 +188,12 @@ pub struct LoginInputs {
     pub password_input: [u8; 64],
     pub password_length: i32,
     pub actual_password: [u8; 64],
+    /// ログインした後、次に起動した時のためにセッションを保存する？<br />
+    /// Should the session be saved for the next launch after logging in?
+    pub remember_me: bool,
+    /// 最後にログインできなかった理由。<br />
+    /// Why the last login failed.
+    pub login_error: Option<String>,
 }
 
 impl Default for LoginInputs {
@@ -96,18 +210,111 @@ impl LoginInputs {
             password_input: [0; 64],
             password_length: 0,
             actual_password: [0; 64],
+            remember_me: false,
+            login_error: None,
         }
     }
 
+    /// 入力を消す。「ログインしたままにする」の選択は残す。<br />
+    /// Clear the inputs. The "Remember me" choice is kept.
     pub fn clear(&mut self) {
         self.account_input = [0; 64];
         self.account_length = 0;
+        self.clear_password();
+        self.login_error = None;
+    }
+
+    pub fn clear_password(&mut self) {
         self.password_input = [0; 64];
         self.password_length = 0;
         self.actual_password = [0; 64];
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct ChatInputs {
+    pub message_input: [u8; 256],
+    pub message_length: i32,
+}
+
+impl Default for ChatInputs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChatInputs {
+    pub fn new() -> Self {
+        ChatInputs {
+            message_input: [0; 256],
+            message_length: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.message_input = [0; 256];
+        self.message_length = 0;
+    }
+}
+
+/// ロビーの入力と部屋の一覧。<br />
+/// Inputs of the lobby and the list of rooms.
+#[derive(Clone, Debug)]
+pub struct LobbyInputs {
+    pub room_name_input: [u8; 64],
+    pub room_name_length: i32,
+    pub max_players: i32,
+    pub selected_room_id: Option<String>,
+    pub rooms: Vec<RoomState>,
+    /// 次のフレームで部屋の一覧をサーバーから取得し直す？<br />
+    /// Should the list of rooms be fetched from the server again on the next frame?
+    pub is_outdated: bool,
+    /// LANで見つかったホスト。<br />
+    /// Hosts found on the LAN.
+    pub lan_hosts: Vec<DiscoveredHost>,
+    pub selected_host: Option<HostAddress>,
+    pub address_input: [u8; 64],
+    pub address_length: i32,
+    /// 最後にホストへの接続に失敗した理由。<br />
+    /// Why connecting to a host failed the last time.
+    pub connection_error: Option<String>,
+}
+
+impl Default for LobbyInputs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LobbyInputs {
+    pub fn new() -> Self {
+        LobbyInputs {
+            room_name_input: [0; 64],
+            room_name_length: 0,
+            max_players: DEFAULT_ROOM_PLAYERS,
+            selected_room_id: None,
+            rooms: vec![],
+            is_outdated: true,
+            lan_hosts: vec![],
+            selected_host: None,
+            address_input: [0; 64],
+            address_length: 0,
+            connection_error: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.room_name_input = [0; 64];
+        self.room_name_length = 0;
+        self.max_players = DEFAULT_ROOM_PLAYERS;
+        self.selected_room_id = None;
+        self.selected_host = None;
+        self.address_input = [0; 64];
+        self.address_length = 0;
+        self.connection_error = None;
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct UIState {
     pub show_login_box: bool,
@@ -116,6 +323,11 @@ pub struct UIState {
     pub registration_inputs: RegistrationInputs,
     pub logged_in: bool,
     pub login_inputs: LoginInputs,
+    pub chat_inputs: ChatInputs,
+    pub lobby_inputs: LobbyInputs,
+    /// 保存したセッションでログインし直す途中？<br />
+    /// Is logging in again with the saved session pending?
+    pub is_resuming_session: bool,
 }
 
 impl Default for UIState {
@@ -132,7 +344,10 @@ impl UIState {
             show_login_form: false,
             registration_inputs: RegistrationInputs::new(),
             login_inputs: LoginInputs::new(),
+            chat_inputs: ChatInputs::new(),
+            lobby_inputs: LobbyInputs::new(),
             logged_in: false,
+            is_resuming_session: false,
         }
     }
 }
@@ -144,16 +359,34 @@ where
     CommandType: 'static + Clone,
     TextureType: 'static + Clone + Disposable,
 {
-    font_bytes: Vec<u8>,
+    /// UIのフォントと代わりのフォントのデータ。アトラスが参照するので描画器より長く生かす。<br />
+    /// Data of the UI font and its fallback fonts, kept alive longer than the drawer since the atlas refers to it.
+    font_bytes: Vec<Vec<u8>>,
     phantom_1: PhantomData<&'static GraphicsType>,
     phantom_2: PhantomData<&'static BufferType>,
     phantom_3: PhantomData<&'static CommandType>,
     phantom_4: PhantomData<&'static TextureType>,
     context: Context,
     convert_config: ConvertConfig,
-    drawer: ManuallyDrop<Drawer>,
+    drawer: ManuallyDrop<GraphicsType::UiDrawer>,
     is_initialized: bool,
     ui_state: UIState,
+    windows: WindowManager,
+    /// 絵文字のアトラスのテクスチャの番号。読み込めなかった場合は絵文字を文字のまま表示する。<br />
+    /// Texture ID of the emoji atlas. Emojis are shown as text if it couldn't be loaded.
+    emoji_atlas: Option<i32>,
+    /// ロビーにいる間だけLANのホストを探す。<br />
+    /// LAN hosts are only browsed while in the lobby.
+    lan_browser: Option<LanBrowser>,
+    /// 告知のポートが使えなかった？その場合は探し直さない。<br />
+    /// Was the discovery port unavailable? If so, browsing isn't attempted again.
+    is_lan_unavailable: bool,
+    /// 閉じた時点の警告の数。新しい警告が増えたらまた表示する。<br />
+    /// Number of warnings when the list was dismissed. The list is shown again once new warnings arrive.
+    dismissed_asset_warnings: usize,
+    /// コンソールを開いたばかりで、入力欄にフォーカスを移す？<br />
+    /// Was the console just opened, so that the input field should get focus?
+    focus_console_input: bool,
 }
 
 impl<GraphicsType, BufferType, CommandType, TextureType>
@@ -168,6 +401,43 @@ where
         self.context.clear();
     }
 
+    /// サーバーとの接続が切れている間、他の画面の上に再接続の状況を表示する。<br />
+    /// Show the reconnection status on top of other screens while the connection to the server is lost.
+    pub async fn draw_connection_ui(&mut self, network_system: Arc<RwLock<NetworkSystem>>) {
+        if !self.is_initialized {
+            return;
+        }
+        let state = network_system.read().await.connection.get_state();
+        let message = match state {
+            ConnectionState::Connected => return,
+            ConnectionState::Reconnecting { attempt: 0 } => "Reconnecting...".to_string(),
+            ConnectionState::Reconnecting { attempt } => {
+                format!("Reconnecting... (attempt {})", attempt + 1)
+            }
+            ConnectionState::Disconnected => "Disconnected from the server.".to_string(),
+        };
+
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, "Connection") {
+            return;
+        }
+        ctx.layout_row_dynamic(50.0, 1);
+        ctx.text(&message, TextAlignment::Centered as Flags);
+        let mut retry = false;
+        if state == ConnectionState::Disconnected {
+            drawer.set_font_size(ctx, 20);
+            ctx.layout_row_dynamic(40.0, 1);
+            retry = ctx.button_text("Retry");
+        }
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+
+        if retry {
+            network_system.write().await.retry_connection();
+        }
+    }
+
     pub async fn draw_game_ui(
         &mut self,
         network_system: Arc<RwLock<NetworkSystem>>,
@@ -176,107 +446,1034 @@ where
             return Ok(());
         }
 
+        self.draw_network_overlay(&network_system).await;
+        let chat_messages = network_system
+            .read()
+            .await
+            .chat_messages
+            .lock()
+            .await
+            .clone();
+        self.draw_chat_box(&chat_messages, network_system).await;
+
+        Ok(())
+    }
+
+    /// 押している間だけ、部屋のプレイヤーのHPと勝敗の数を一覧で表示する。<br />
+    /// Show the HP and win/lose counts of the players in the room, only while it's held open.
+    pub fn draw_scoreboard(&mut self, rows: &[ScoreboardRow]) {
+        if !self.is_initialized || !self.windows.is_visible(SCOREBOARD_WINDOW) {
+            return;
+        }
         let ctx = &mut self.context;
         let drawer = &mut self.drawer;
-        drawer.set_font_size(ctx, 28);
-        let flags = PanelFlags::Border as Flags | PanelFlags::NoScrollbar as Flags;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, SCOREBOARD_WINDOW) {
+            return;
+        }
+        Self::set_ui_header(drawer, ctx, SCOREBOARD_WINDOW, TextAlignment::Centered);
+        drawer.set_font_size(ctx, 16);
+        ctx.layout_row(LayoutFormat::Dynamic, 24.0, &RATIO_SCOREBOARD);
+        for header in ["Player", "HP", "Won / Lost"].iter() {
+            ctx.text(header, TextAlignment::Left as Flags);
+        }
+        let [r, g, b, a] = DOWN_PLAYER_COLOR;
+        for row in rows.iter() {
+            // 倒れたプレイヤーは灰色で表示する。
+            // Players who are down are shown in gray.
+            let color = if row.is_standing {
+                nuklear::Color {
+                    r: 255,
+                    g: 255,
+                    b: 255,
+                    a: 255,
+                }
+            } else {
+                nuklear::Color { r, g, b, a }
+            };
+            let cells = [
+                row.nickname.clone(),
+                format!("{} / {}", row.current_hp, row.max_hp),
+                format!("{} / {}", row.win_count, row.lose_count),
+            ];
+            ctx.layout_row(LayoutFormat::Dynamic, 24.0, &RATIO_SCOREBOARD);
+            for cell in cells.iter() {
+                drawer.request_glyphs(cell);
+                ctx.text_colored(cell, TextAlignment::Left as Flags, color);
+            }
+        }
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+    }
 
-        let ns = network_system.read().await;
-        let mut room_state = ns.room_state.lock().await;
-        let room_started = room_state.started;
-        if !room_started {
-            ctx.begin(
-                nuklear::nk_string!("WaitBox"),
-                nuklear::Rect {
-                    x: 600.0,
-                    y: 300.0,
-                    w: 400.0,
-                    h: 400.0,
-                },
-                flags,
-            );
-            drawer.set_font_size(ctx, 36);
-            ctx.layout_row_dynamic(50.0, 1);
-            ctx.text("Wait", TextAlignment::Centered as Flags);
-            drawer.set_font_size(ctx, 16);
-            ctx.layout_row_dynamic(50.0, 1);
-            ctx.text("Wait for opponents...", TextAlignment::Centered as Flags);
-            ctx.layout_row_dynamic(50.0, 1);
-            let current_players = format!("Current players: {}", room_state.current_players);
-            ctx.text(&current_players, TextAlignment::Centered as Flags);
-            if let Some(player) = ns.logged_user.as_ref() {
-                if let Some(state) = player.lock().await.state.as_ref() {
-                    let is_owner = state.is_owner;
-                    let is_player_sufficient = room_state.current_players >= 2;
-                    if is_owner && is_player_sufficient {
-                        let ratio = [0.25, 0.5, 0.25];
-                        ctx.layout_row(LayoutFormat::Dynamic, 50.0, &ratio);
-                        ctx.spacing(1);
-                        if ctx.button_text("Start") {
-                            room_state.started = true;
-                        }
-                        ctx.spacing(1);
+    /// 得点表を開閉する。Tabを押している間だけ開く。<br />
+    /// Open or close the scoreboard. It's only open while Tab is held.
+    pub fn set_scoreboard_visible(&mut self, is_visible: bool) {
+        if is_visible {
+            self.windows.show(SCOREBOARD_WINDOW);
+        } else {
+            self.windows.hide(SCOREBOARD_WINDOW);
+        }
+    }
+
+    /// 試合の結果の画面を表示する。ロビーに戻るボタンが押されたら`true`を返す。<br />
+    /// Show the results screen of a match. Returns `true` when the button to return to the lobby is pressed.
+    pub fn draw_match_results(&mut self, results: &MatchResults) -> bool {
+        if !self.is_initialized {
+            return false;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, RESULTS_WINDOW) {
+            return false;
+        }
+        Self::set_ui_header(drawer, ctx, results.get_title(), TextAlignment::Centered);
+        drawer.set_font_size(ctx, 16);
+        let mut lines = vec![match results.winner_name.as_ref() {
+            Some(winner_name) => format!("Winner: {}", winner_name),
+            None => "No one was left standing.".to_string(),
+        }];
+        match results.report_error.as_ref() {
+            Some(error) => lines.push(error.clone()),
+            None => {
+                lines.push(format!(
+                    "Credits: +{} ({} in total)",
+                    results.earned_credits, results.credits
+                ));
+                lines.push(format!(
+                    "Won: {} / Lost: {}",
+                    results.win_count, results.lose_count
+                ));
+            }
+        }
+        for line in lines.iter() {
+            ctx.layout_row_dynamic(26.0, 1);
+            drawer.request_glyphs(line);
+            ctx.text_wrap(line);
+        }
+        drawer.set_font_size(ctx, 20);
+        ctx.layout_row(LayoutFormat::Dynamic, 40.0, &RATIO_WC);
+        ctx.spacing(1);
+        let is_returning = ctx.button_text("Return to Lobby");
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+        if is_returning {
+            self.ui_state.lobby_inputs.is_outdated = true;
+        }
+        is_returning
+    }
+
+    /// カットシーンの暗転として、画面全体を黒で覆う。`fade`は0で透明、1で真っ黒。<br />
+    /// Cover the whole screen in black as the fade of a cutscene. `fade` is transparent at 0 and fully black at 1.
+    pub fn draw_fade(&mut self, fade: f32) {
+        if !self.is_initialized || fade <= 0.0 {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        // 窓の背景の色で覆うため、この窓の間だけ背景を差し替える。
+        // The screen is covered with the window's background, so the background is swapped only for this window.
+        let background = ctx.style_mut().window_mut().fixed_background().clone();
+        let color = nuklear::Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: (fade.min(1.0) * 255.0) as u8,
+        };
+        ctx.style_mut()
+            .window_mut()
+            .set_fixed_background(StyleItem::color(color));
+        if Self::begin_window(&mut self.windows, drawer, ctx, FADE_WINDOW) {
+            ctx.end();
+        }
+        ctx.style_mut()
+            .window_mut()
+            .set_fixed_background(background);
+    }
+
+    /// 画面に投影したデバッグ用の図形を、画面全体を覆う透明な窓に描く。<br />
+    /// Draw debug shapes projected onto the screen in a transparent window covering the whole screen.
+    pub fn draw_debug_shapes(&mut self, shapes: &[ScreenShape]) {
+        if !self.is_initialized || shapes.is_empty() {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        let background = ctx.style_mut().window_mut().fixed_background().clone();
+        ctx.style_mut()
+            .window_mut()
+            .set_fixed_background(StyleItem::color(nuklear::Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            }));
+        if Self::begin_window(&mut self.windows, drawer, ctx, DEBUG_DRAW_WINDOW) {
+            let to_color = |[r, g, b, a]: [u8; 4]| nuklear::Color { r, g, b, a };
+            if let Some(canvas) = ctx.window_get_canvas_mut() {
+                for shape in shapes.iter() {
+                    match *shape {
+                        ScreenShape::Circle {
+                            center,
+                            radius,
+                            color,
+                        } => canvas.fill_circle(
+                            nuklear::Rect {
+                                x: center.x - radius,
+                                y: center.y - radius,
+                                w: radius * 2.0,
+                                h: radius * 2.0,
+                            },
+                            to_color(color),
+                        ),
+                        ScreenShape::Line {
+                            from,
+                            to,
+                            thickness,
+                            color,
+                        } => canvas.stroke_line(
+                            from.x,
+                            from.y,
+                            to.x,
+                            to.y,
+                            thickness,
+                            to_color(color),
+                        ),
                     }
                 }
             }
-            drawer.set_font_size(ctx, 24);
             ctx.end();
         }
+        ctx.style_mut()
+            .window_mut()
+            .set_fixed_background(background);
+    }
 
-        Ok(())
+    /// リモートのプレイヤーの名札を、画面に投影した頭の上の位置に、距離に応じた不透明度で描く。称号があれば名前の下に出す。<br />
+    /// Draw the nameplates of remote players at the positions above their heads projected onto the screen, with an opacity by distance. Titles are shown under the names.
+    pub fn draw_nameplates(&mut self, nameplates: &[(Vec2, f32, Nameplate)]) {
+        if !self.is_initialized || nameplates.is_empty() {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        let background = ctx.style_mut().window_mut().fixed_background().clone();
+        ctx.style_mut()
+            .window_mut()
+            .set_fixed_background(StyleItem::color(nuklear::Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            }));
+        if Self::begin_window(&mut self.windows, drawer, ctx, NAMEPLATE_WINDOW) {
+            let to_color = |[r, g, b, a]: [u8; 4]| nuklear::Color { r, g, b, a };
+            let height = self
+                .windows
+                .get(NAMEPLATE_WINDOW)
+                .map(|w| w.bounds.h)
+                .unwrap_or(0.0);
+            ctx.layout_space_begin(LayoutFormat::Static, height, nameplates.len() as i32 * 2);
+            for (position, alpha, nameplate) in nameplates.iter() {
+                let label = if nameplate.is_chat_muted {
+                    format!("{} (Chat muted)", nameplate.label)
+                } else {
+                    nameplate.label.clone()
+                };
+                let mut lines = vec![(label, fade_color(nameplate.color, *alpha))];
+                if !nameplate.title.is_empty() {
+                    lines.push((
+                        nameplate.title.clone(),
+                        fade_color(NAMEPLATE_NOTE_COLOR, *alpha),
+                    ));
+                }
+                // 名札の下端が頭の上に来るよう、行を上に積む。
+                // Lines are stacked upwards so the bottom of the nameplate sits above the head.
+                let top = position.y - NAMEPLATE_LINE_HEIGHT * lines.len() as f32;
+                for (i, (text, color)) in lines.iter().enumerate() {
+                    drawer.request_glyphs(text);
+                    ctx.layout_space_push(nuklear::Rect {
+                        x: position.x - NAMEPLATE_WIDTH * 0.5,
+                        y: top + NAMEPLATE_LINE_HEIGHT * i as f32,
+                        w: NAMEPLATE_WIDTH,
+                        h: NAMEPLATE_LINE_HEIGHT,
+                    });
+                    ctx.text_colored(text, TextAlignment::Centered as Flags, to_color(*color));
+                }
+            }
+            ctx.layout_space_end();
+            ctx.end();
+        }
+        ctx.style_mut()
+            .window_mut()
+            .set_fixed_background(background);
     }
 
-    pub async fn draw_title_ui(
-        &mut self,
-        network_system: Arc<RwLock<NetworkSystem>>,
-    ) -> anyhow::Result<Option<Player>> {
+    /// 画面に投影した関節の位置の横に、関節の名前を描く。<br />
+    /// Draw the names of joints next to their positions projected onto the screen.
+    pub fn draw_joint_names(&mut self, labels: &[(Vec2, String)]) {
+        if !self.is_initialized || labels.is_empty() {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        let background = ctx.style_mut().window_mut().fixed_background().clone();
+        ctx.style_mut()
+            .window_mut()
+            .set_fixed_background(StyleItem::color(nuklear::Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            }));
+        if Self::begin_window(&mut self.windows, drawer, ctx, JOINT_NAME_WINDOW) {
+            let [r, g, b, a] = JOINT_NAME_COLOR;
+            let color = nuklear::Color { r, g, b, a };
+            let height = self
+                .windows
+                .get(JOINT_NAME_WINDOW)
+                .map(|w| w.bounds.h)
+                .unwrap_or(0.0);
+            ctx.layout_space_begin(LayoutFormat::Static, height, labels.len() as i32);
+            for (position, name) in labels.iter() {
+                drawer.request_glyphs(name);
+                ctx.layout_space_push(nuklear::Rect {
+                    x: position.x + 4.0,
+                    y: position.y - JOINT_NAME_HEIGHT * 0.5,
+                    w: JOINT_NAME_WIDTH,
+                    h: JOINT_NAME_HEIGHT,
+                });
+                ctx.text_colored(name, TextAlignment::Left as Flags, color);
+            }
+            ctx.layout_space_end();
+            ctx.end();
+        }
+        ctx.style_mut()
+            .window_mut()
+            .set_fixed_background(background);
+    }
+
+    /// 開いている間、画面の上にコンソールの出力と入力欄を表示する。入力欄の文字列はコンソールに書き戻す。<br />
+    /// Show the console output and the input field at the top of the screen while open. The text of the input field is written back to the console.
+    pub fn draw_console(&mut self, console: &mut Console) {
         if !self.is_initialized {
-            return Ok(None);
+            return;
         }
         let ctx = &mut self.context;
         let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, CONSOLE_WINDOW) {
+            return;
+        }
+        let lines = console.get_output().collect::<Vec<_>>();
+        let first_visible = lines.len().saturating_sub(CONSOLE_VISIBLE_LINES);
+        for line in lines[first_visible..].iter() {
+            ctx.layout_row_dynamic(18.0, 1);
+            drawer.request_glyphs(line);
+            ctx.text(line, TextAlignment::Left as Flags);
+        }
+        for _ in lines.len() - first_visible..CONSOLE_VISIBLE_LINES {
+            ctx.layout_row_dynamic(18.0, 1);
+            ctx.spacing(1);
+        }
+        let mut input = [0_u8; 256];
+        let text = console.get_input().as_bytes();
+        let length = text.len().min(input.len());
+        input[..length].copy_from_slice(&text[..length]);
+        let mut length = length as i32;
+        ctx.layout_row_dynamic(26.0, 1);
+        if self.focus_console_input {
+            ctx.edit_focus(EditType::Field as Flags);
+            self.focus_console_input = false;
+        }
+        ctx.edit_string_custom_filter(
+            EditType::Field as Flags,
+            input.as_mut(),
+            &mut length,
+            Self::free_type_filter,
+        );
         drawer.set_font_size(ctx, 24);
-        let flags = PanelFlags::Border as Flags | PanelFlags::NoScrollbar as Flags;
+        ctx.end();
+        console.set_input(&String::from_utf8_lossy(&input[..length as usize]));
+    }
 
-        ctx.begin(
-            nuklear::nk_string!("User Interface"),
-            nuklear::Rect {
-                x: 0.0,
-                y: 0.0,
-                w: 300.0,
-                h: 900.0,
-            },
-            flags,
+    /// プレビューしている素材の境界ボックスとマテリアルを表示し、アニメーションの再生と読み込み直しを操作させる。<br />
+    /// Show the bounding box and materials of the previewed asset, with controls for animation playback and reloading.
+    pub fn draw_preview_panel(&mut self, preview: &mut AssetPreview) {
+        if !self.is_initialized {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, PREVIEW_WINDOW) {
+            return;
+        }
+        ctx.layout_row_dynamic(20.0, 1);
+        drawer.request_glyphs(&preview.path);
+        ctx.text(&preview.path, TextAlignment::Left as Flags);
+        ctx.layout_row(LayoutFormat::Dynamic, 18.0, &RATIO_TWEAK[..2]);
+        ctx.text("Kind", TextAlignment::Left as Flags);
+        let kind = match (preview.info.kind, preview.info.texture_size) {
+            (PreviewAssetKind::Texture, Some((width, height))) => {
+                format!("Texture ({}x{})", width, height)
+            }
+            (kind, _) => format!("{:?}", kind),
+        };
+        ctx.text(&kind, TextAlignment::Left as Flags);
+        if let Some(bounds) = preview.bounds.as_ref() {
+            let size = bounds.max - bounds.min;
+            let rows = [
+                ("Bounds min", bounds.min),
+                ("Bounds max", bounds.max),
+                ("Size", size),
+            ];
+            for (label, value) in rows.iter() {
+                ctx.text(label, TextAlignment::Left as Flags);
+                ctx.text(
+                    &format!("{:.2}, {:.2}, {:.2}", value.x, value.y, value.z),
+                    TextAlignment::Left as Flags,
+                );
+            }
+        }
+        if let Some(error) = preview.error.as_ref() {
+            ctx.layout_row_dynamic(18.0, 1);
+            drawer.request_glyphs(error);
+            ctx.text(error, TextAlignment::Left as Flags);
+        }
+        ctx.layout_row_dynamic(24.0, 1);
+        if ctx.button_text("Reload") {
+            preview.request_reload();
+        }
+
+        ctx.layout_row_dynamic(20.0, 1);
+        ctx.text("Animations", TextAlignment::Left as Flags);
+        let playback = &mut preview.playback;
+        if playback.get_clips().is_empty() {
+            ctx.text("No animations.", TextAlignment::Left as Flags);
+        }
+        let mut selected = None;
+        for (index, clip) in playback.get_clips().iter().enumerate() {
+            ctx.layout_row_dynamic(18.0, 1);
+            let mut is_selected = playback.get_selected_index() == Some(index);
+            let label = format!("{} ({:.2}s)", &clip.name, clip.duration);
+            drawer.request_glyphs(&label);
+            if ctx.selectable_text(&label, TextAlignment::Left as Flags, &mut is_selected) {
+                selected = Some(index);
+            }
+        }
+        if let Some(index) = selected {
+            playback.select(index);
+        }
+        if let Some(duration) = playback.get_selected().map(|clip| clip.duration) {
+            ctx.layout_row_dynamic(24.0, 2);
+            let label = if playback.is_playing { "Pause" } else { "Play" };
+            if ctx.button_text(label) {
+                playback.is_playing = !playback.is_playing;
+            }
+            ctx.checkbox_text("Loop", &mut playback.is_looping);
+            ctx.layout_row(LayoutFormat::Dynamic, 24.0, &RATIO_TWEAK);
+            ctx.text("Time", TextAlignment::Left as Flags);
+            let mut time = playback.time;
+            if ctx.slider_float(0.0, &mut time, duration, duration / 100.0) {
+                playback.scrub(time);
+            }
+            ctx.text(
+                &format!("{:.2}", playback.time),
+                TextAlignment::Right as Flags,
+            );
+            ctx.layout_row_dynamic(22.0, 1);
+            ctx.property_float(
+                nuklear::nk_string!("#Speed:"),
+                -4.0,
+                &mut playback.speed,
+                4.0,
+                0.1,
+                0.01,
+            );
+        }
+
+        ctx.layout_row_dynamic(20.0, 1);
+        ctx.text("Materials", TextAlignment::Left as Flags);
+        if preview.info.materials.is_empty() {
+            ctx.text("No materials.", TextAlignment::Left as Flags);
+        }
+        for material in preview.info.materials.iter() {
+            ctx.layout_row_dynamic(18.0, 1);
+            drawer.request_glyphs(&material.name);
+            ctx.text(&material.name, TextAlignment::Left as Flags);
+            let [r, g, b, a] = material.base_color;
+            let rows = [
+                (
+                    "Base color",
+                    format!("{:.2}, {:.2}, {:.2}, {:.2}", r, g, b, a),
+                ),
+                (
+                    "Metallic / Roughness",
+                    format!("{:.2} / {:.2}", material.metallic, material.roughness),
+                ),
+                (
+                    "Texture",
+                    material
+                        .base_color_texture
+                        .clone()
+                        .unwrap_or_else(|| "None".to_string()),
+                ),
+                (
+                    "Alpha",
+                    format!(
+                        "{}{}",
+                        &material.alpha_mode,
+                        if material.is_double_sided {
+                            ", double sided"
+                        } else {
+                            ""
+                        }
+                    ),
+                ),
+            ];
+            ctx.layout_row(LayoutFormat::Dynamic, 18.0, &RATIO_TWEAK[..2]);
+            for (label, value) in rows.iter() {
+                ctx.text(label, TextAlignment::Left as Flags);
+                drawer.request_glyphs(value);
+                ctx.text(value, TextAlignment::Left as Flags);
+            }
+        }
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+    }
+
+    /// 各システムが登録したパラメーターをスライダーやチェックボックスで表示し、変えられた値をすぐに反映する。<br />
+    /// Show the parameters registered by the systems as sliders and checkboxes, applying changed values right away.
+    #[cfg(debug_assertions)]
+    pub fn draw_tweak_panel(&mut self, tweaks: &mut TweakRegistry) {
+        if !self.is_initialized {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, TWEAK_WINDOW) {
+            return;
+        }
+        let changes = Self::draw_tweak_parameters(ctx, tweaks.get_parameters().iter());
+        ctx.layout_row_dynamic(30.0, 1);
+        let save = ctx.button_text("Save");
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+        Self::apply_tweak_changes(tweaks, changes, save);
+    }
+
+    /// プレイヤー向けの設定画面。グラフィックスと画面表示の設定だけを表示し、リリースビルドでも使える。<br />
+    /// The settings page for players. Only graphics and interface settings are shown, and it's available in release builds too.
+    pub fn draw_settings_panel(&mut self, tweaks: &mut TweakRegistry) {
+        if !self.is_initialized {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, SETTINGS_WINDOW) {
+            return;
+        }
+        let parameters = tweaks.get_parameters().iter().filter(|parameter| {
+            SETTINGS_PREFIXES
+                .iter()
+                .any(|prefix| parameter.name.starts_with(prefix))
+        });
+        let changes = Self::draw_tweak_parameters(ctx, parameters);
+        ctx.layout_row_dynamic(30.0, 1);
+        let save = ctx.button_text("Save");
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+        Self::apply_tweak_changes(tweaks, changes, save);
+    }
+
+    /// フレーム時間のグラフと、CPUの区間とレンダーパスごとの平均の時間を表示する。<br />
+    /// メモリーの統計があれば、ヒープごとの使用量と予算に近づいた警告も出す。<br />
+    /// Show the frame time graph, and the average times of CPU scopes and render passes.<br />
+    /// With memory statistics, the usage of each heap and warnings about nearing the budget are shown as well.
+    pub fn draw_profiler_panel(&mut self, profiler: &FrameProfiler, memory: Option<&MemoryStats>) {
+        if !self.is_initialized {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, PROFILER_WINDOW) {
+            return;
+        }
+        let average_frame_time = profiler.get_average_frame_time();
+        let average_fps = if average_frame_time > 0.0 {
+            1000.0 / average_frame_time
+        } else {
+            0.0
+        };
+        ctx.layout_row_dynamic(20.0, 1);
+        ctx.text(
+            &format!("{:.2} ms ({:.0} FPS)", average_frame_time, average_fps),
+            TextAlignment::Left as Flags,
         );
-        Self::set_ui_header(drawer, ctx, "Game Menu", TextAlignment::Centered);
-        Self::set_ui_widget(drawer, ctx, 50.0, true);
+        ctx.layout_row_dynamic(20.0, 1);
+        ctx.text(
+            &format!(
+                "{:.1} allocations per frame",
+                profiler.get_average_allocations()
+            ),
+            TextAlignment::Left as Flags,
+        );
+        let history = profiler.get_history();
+        let graph_max = history
+            .iter()
+            .map(|timings| timings.frame_time)
+            .fold(PROFILER_GRAPH_MIN_MAX, f64::max);
+        ctx.layout_row_dynamic(80.0, 1);
+        if ctx.chart_begin(
+            ChartType::Lines,
+            history.len() as i32,
+            0.0,
+            graph_max as f32,
+        ) {
+            for timings in history.iter() {
+                ctx.chart_push(timings.frame_time as f32);
+            }
+            ctx.chart_end();
+        }
+        let sections = [
+            ("CPU", profiler.get_cpu_averages()),
+            ("GPU", profiler.get_gpu_averages()),
+        ];
+        for (title, averages) in sections.iter() {
+            ctx.layout_row_dynamic(20.0, 1);
+            ctx.text(title, TextAlignment::Left as Flags);
+            if averages.is_empty() {
+                ctx.text("Unavailable", TextAlignment::Left as Flags);
+            }
+            for (name, time) in averages.iter() {
+                ctx.layout_row(LayoutFormat::Dynamic, 18.0, &RATIO_W);
+                ctx.text("", TextAlignment::Left as Flags);
+                ctx.text(
+                    &format!("{}: {:.3} ms", name, time),
+                    TextAlignment::Left as Flags,
+                );
+            }
+        }
+        ctx.layout_row_dynamic(20.0, 1);
+        ctx.text("Memory", TextAlignment::Left as Flags);
+        match memory {
+            Some(stats) => {
+                ctx.text(
+                    &format!(
+                        "{} used of {} in {} allocations",
+                        format_mebibytes(stats.used_bytes),
+                        format_mebibytes(stats.block_bytes),
+                        stats.allocation_count
+                    ),
+                    TextAlignment::Left as Flags,
+                );
+                for heap in stats.heaps.iter() {
+                    ctx.layout_row(LayoutFormat::Dynamic, 18.0, &RATIO_W);
+                    ctx.text("", TextAlignment::Left as Flags);
+                    ctx.text(
+                        &format!(
+                            "{}: {} / {} ({:.0}%), {} allocations",
+                            heap.get_label(),
+                            format_mebibytes(heap.usage_bytes),
+                            format_mebibytes(heap.budget_bytes),
+                            heap.get_budget_ratio() * 100.0,
+                            heap.allocation_count
+                        ),
+                        TextAlignment::Left as Flags,
+                    );
+                }
+                let [r, g, b, a] = MEMORY_WARNING_COLOR;
+                for warning in stats.get_warnings().iter() {
+                    ctx.layout_row_dynamic(18.0, 1);
+                    ctx.text_colored(
+                        warning,
+                        TextAlignment::Left as Flags,
+                        nuklear::Color { r, g, b, a },
+                    );
+                }
+            }
+            None => ctx.text("Unavailable", TextAlignment::Left as Flags),
+        }
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+    }
 
-        if ctx.button_text("Start")
-            && !self.ui_state.show_login_box
-            && !self.ui_state.show_register_box
-            && !self.ui_state.show_login_form
-            && !network_system.read().await.is_player_login
-        {
-            self.ui_state.show_login_box = true;
+    /// パラメーターを種類に合った部品で表示し、変えられた値を返す。<br />
+    /// Show the parameters with widgets matching their kinds, returning the changed values.
+    fn draw_tweak_parameters<'a, I>(ctx: &mut Context, parameters: I) -> Vec<(String, TweakValue)>
+    where
+        I: Iterator<Item = &'a TweakParameter>,
+    {
+        let mut changes = vec![];
+        for TweakParameter { name, value } in parameters {
+            match value {
+                TweakValue::Float { value, min, max } => {
+                    ctx.layout_row(LayoutFormat::Dynamic, 24.0, &RATIO_TWEAK);
+                    ctx.text(name, TextAlignment::Left as Flags);
+                    let mut new_value = *value;
+                    if ctx.slider_float(*min, &mut new_value, *max, (max - min) / 100.0) {
+                        changes.push((
+                            name.clone(),
+                            TweakValue::Float {
+                                value: new_value,
+                                min: *min,
+                                max: *max,
+                            },
+                        ));
+                    }
+                    ctx.text(&format!("{:.2}", new_value), TextAlignment::Right as Flags);
+                }
+                TweakValue::Bool(value) => {
+                    ctx.layout_row_dynamic(24.0, 1);
+                    let mut new_value = *value;
+                    if ctx.checkbox_text(name, &mut new_value) {
+                        changes.push((name.clone(), TweakValue::Bool(new_value)));
+                    }
+                }
+                TweakValue::Enum { index, options } => {
+                    ctx.layout_row(LayoutFormat::Dynamic, 24.0, &RATIO_TWEAK[..2]);
+                    ctx.text(name, TextAlignment::Left as Flags);
+                    let size = nuklear::Vec2 { x: 200.0, y: 200.0 };
+                    if ctx.combo_begin_text(&options[*index], size) {
+                        ctx.layout_row_dynamic(20.0, 1);
+                        for (i, option) in options.iter().enumerate() {
+                            if ctx.combo_item_text(option, TextAlignment::Left as Flags) {
+                                changes.push((
+                                    name.clone(),
+                                    TweakValue::Enum {
+                                        index: i,
+                                        options: options.clone(),
+                                    },
+                                ));
+                            }
+                        }
+                        ctx.combo_end();
+                    }
+                }
+            }
+        }
+        changes
+    }
+
+    fn apply_tweak_changes(
+        tweaks: &mut TweakRegistry,
+        changes: Vec<(String, TweakValue)>,
+        save: bool,
+    ) {
+        for (name, value) in changes.into_iter() {
+            tweaks.set(&name, value);
+        }
+        if save {
+            match tweaks.save(TWEAK_CONFIG_PATH) {
+                Ok(_) => log::info!("Saved tweaks to {}.", TWEAK_CONFIG_PATH),
+                Err(e) => log::warn!("Failed to save tweaks: {}", e),
+            }
+        }
+    }
+
+    /// アニメーターごとに今の状態、混ぜている前の状態とパラメーターを表示する。<br />
+    /// Show the current state, the previous state being blended from and the parameters of each animator.
+    #[cfg(debug_assertions)]
+    pub fn draw_animation_panel(&mut self, animators: &[(String, AnimationStateInfo)]) {
+        if !self.is_initialized {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, ANIMATION_WINDOW) {
+            return;
+        }
+        if animators.is_empty() {
+            ctx.layout_row_dynamic(20.0, 1);
+            ctx.text("No animators.", TextAlignment::Left as Flags);
+        }
+        for (entity_name, info) in animators.iter() {
+            ctx.layout_row_dynamic(20.0, 1);
+            ctx.text(
+                &format!("{} ({})", entity_name, &info.machine_name),
+                TextAlignment::Left as Flags,
+            );
+            ctx.layout_row(LayoutFormat::Dynamic, 20.0, &RATIO_TWEAK[..2]);
+            ctx.text("State", TextAlignment::Left as Flags);
+            ctx.text(
+                &format!("{} ({:.2}s)", &info.state, info.state_time),
+                TextAlignment::Left as Flags,
+            );
+            if let Some(previous_state) = info.previous_state.as_ref() {
+                ctx.text("Blending from", TextAlignment::Left as Flags);
+                ctx.text(
+                    &format!("{} ({:.0}%)", previous_state, info.blend_weight * 100.0),
+                    TextAlignment::Left as Flags,
+                );
+            }
+            for (name, value) in info.parameters.iter() {
+                let value = match value {
+                    ParameterValue::Bool(value) => value.to_string(),
+                    ParameterValue::Float(value) => format!("{:.2}", value),
+                };
+                ctx.text(name, TextAlignment::Left as Flags);
+                ctx.text(&value, TextAlignment::Left as Flags);
+            }
         }
         drawer.set_font_size(ctx, 24);
         ctx.end();
+    }
+
+    /// シーンのエンティティを一覧にし、選んだエンティティの位置、色、反射とアニメーションを編集させる。<br />
+    /// 変えられた値をエンティティごとに返す。<br />
+    /// List the entities of the scene and let the selected one's transform, color, reflection and animation be edited.<br />
+    /// Returns the changed values per entity.
+    #[cfg(debug_assertions)]
+    pub fn draw_inspector_panel(
+        &mut self,
+        inspector: &mut Inspector,
+        entries: &[InspectorEntry],
+    ) -> Vec<(DefaultKey, InspectorEdit)> {
+        if !self.is_initialized {
+            return vec![];
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, INSPECTOR_WINDOW) {
+            return vec![];
+        }
+        if entries.is_empty() {
+            ctx.layout_row_dynamic(20.0, 1);
+            ctx.text("No entities.", TextAlignment::Left as Flags);
+        }
+        for entry in entries.iter() {
+            ctx.layout_row_dynamic(18.0, 1);
+            let mut is_selected = inspector.get_selected() == Some(entry.entity);
+            drawer.request_glyphs(&entry.name);
+            if ctx.selectable_text(&entry.name, TextAlignment::Left as Flags, &mut is_selected) {
+                inspector.toggle(entry.entity);
+            }
+        }
+        let mut edits = vec![];
+        if let Some(entry) = inspector.get_selected_entry(entries) {
+            let mut edited = entry.clone();
+            let PositionInfo {
+                position,
+                rotation,
+                scale,
+            } = &mut edited.position_info;
+            let mut vectors = [position, rotation, scale];
+            for ((label, step), vector) in INSPECTOR_VECTORS.iter().zip(vectors.iter_mut()) {
+                let mut values: [f32; 3] = (**vector).into();
+                ctx.layout_row_dynamic(20.0, 1);
+                ctx.text(label, TextAlignment::Left as Flags);
+                ctx.layout_row_dynamic(22.0, 3);
+                for (axis, value) in ["X", "Y", "Z"].iter().zip(values.iter_mut()) {
+                    let name = format!("#{} {}:", label, axis);
+                    ctx.property_float(
+                        nuklear::String::from(name.as_str()),
+                        -10000.0,
+                        value,
+                        10000.0,
+                        *step,
+                        *step,
+                    );
+                }
+                **vector = Vec3A::from(values);
+            }
+            let mut color: [f32; 4] = edited.object_color.into();
+            ctx.layout_row_dynamic(20.0, 1);
+            ctx.text("Color", TextAlignment::Left as Flags);
+            ctx.layout_row_dynamic(22.0, 4);
+            for (channel, value) in ["#R:", "#G:", "#B:", "#A:"].iter().zip(color.iter_mut()) {
+                ctx.property_float(
+                    nuklear::String::from(*channel),
+                    0.0,
+                    value,
+                    1.0,
+                    0.01,
+                    0.005,
+                );
+            }
+            edited.object_color = Vec4::from(color);
+            ctx.layout_row_dynamic(22.0, 2);
+            ctx.property_float(
+                nuklear::nk_string!("#Reflectivity:"),
+                0.0,
+                &mut edited.reflectivity,
+                10.0,
+                0.05,
+                0.01,
+            );
+            ctx.property_float(
+                nuklear::nk_string!("#Shine:"),
+                0.0,
+                &mut edited.shine_damper,
+                100.0,
+                0.5,
+                0.1,
+            );
+            if let Some(index) = edited.animation_state {
+                ctx.layout_row(LayoutFormat::Dynamic, 24.0, &RATIO_TWEAK[..2]);
+                ctx.text("Animation", TextAlignment::Left as Flags);
+                let size = nuklear::Vec2 { x: 200.0, y: 200.0 };
+                if ctx.combo_begin_text(&edited.animation_states[index], size) {
+                    ctx.layout_row_dynamic(20.0, 1);
+                    for (i, state) in entry.animation_states.iter().enumerate() {
+                        if ctx.combo_item_text(state, TextAlignment::Left as Flags) {
+                            edited.animation_state = Some(i);
+                        }
+                    }
+                    ctx.combo_end();
+                }
+            }
+            edits = entry
+                .get_edits(&edited)
+                .into_iter()
+                .map(|edit| (entry.entity, edit))
+                .collect();
+        }
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+        edits
+    }
+
+    /// ロビーを表示する。部屋に入っていない間は部屋の一覧と作成の画面、入った後は待合室を表示する。<br />
+    /// 部屋に登録したら、ゲームの開始を知らせる受信側を返す。<br />
+    /// Show the lobby: the room list and room creation while not in a room, and the waiting room after joining one.<br />
+    /// Returns the receiver notifying the start of the game once registered to a room.
+    pub async fn draw_lobby_ui(
+        &mut self,
+        network_system: Arc<RwLock<NetworkSystem>>,
+    ) -> anyhow::Result<Option<crossbeam::channel::Receiver<bool>>> {
+        if !self.is_initialized {
+            return Ok(None);
+        }
+        let (is_in_room, chat_messages) = {
+            let ns = network_system.read().await;
+            let is_in_room = match ns.logged_user.as_ref() {
+                Some(player) => player
+                    .lock()
+                    .await
+                    .state
+                    .as_ref()
+                    .map(|s| !s.room_id.is_empty())
+                    .unwrap_or(false),
+                None => false,
+            };
+            (is_in_room, ns.chat_messages.lock().await.clone())
+        };
+        self.draw_network_overlay(&network_system).await;
+
+        let mut receiver = None;
+        if is_in_room {
+            self.lan_browser = None;
+            self.draw_waiting_room(network_system.clone()).await;
+        } else {
+            if self.lan_browser.is_none() && !self.is_lan_unavailable {
+                match LanBrowser::start().await {
+                    Ok(browser) => self.lan_browser = Some(browser),
+                    Err(e) => {
+                        log::warn!("Failed to browse LAN hosts: {}", e);
+                        self.is_lan_unavailable = true;
+                    }
+                }
+            }
+            if let Some(browser) = self.lan_browser.as_ref() {
+                self.ui_state.lobby_inputs.lan_hosts = browser.get_hosts();
+            }
+            if self.ui_state.lobby_inputs.is_outdated {
+                self.ui_state.lobby_inputs.is_outdated = false;
+                match network_system.write().await.get_rooms().await {
+                    Ok(rooms) => self.ui_state.lobby_inputs.rooms = rooms,
+                    Err(e) => log::warn!("Failed to get rooms: {}", e),
+                }
+            }
+            let selected_room = self.draw_room_list();
+            let new_room = self.draw_create_room_box();
+            let lan_host = self.draw_lan_browser();
+            let direct_host = self.draw_direct_connect_box();
+            if let Some(address) = lan_host.or(direct_host) {
+                match network_system.write().await.connect_to_host(address).await {
+                    Ok(_) => {
+                        self.ui_state.lobby_inputs.clear();
+                        self.ui_state.lobby_inputs.is_outdated = true;
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to connect to the host: {:?}", e);
+                        self.ui_state.lobby_inputs.connection_error = Some(e.to_string());
+                    }
+                }
+            }
+
+            let mut ns = network_system.write().await;
+            let result = if let Some(room) = selected_room {
+                Some(
+                    ns.register_player(room.room_id, room.room_name, false)
+                        .await,
+                )
+            } else if let Some((room_name, max_players)) = new_room {
+                Some(ns.create_room(room_name, max_players).await)
+            } else {
+                None
+            };
+            match result {
+                Some(Ok(r)) => {
+                    self.ui_state.lobby_inputs.clear();
+                    receiver = Some(r);
+                }
+                Some(Err(e)) => {
+                    log::warn!("Failed to enter room: {}", e);
+                    self.ui_state.lobby_inputs.is_outdated = true;
+                }
+                None => (),
+            }
+        }
+        self.draw_chat_box(&chat_messages, network_system).await;
+        Ok(receiver)
+    }
+
+    pub async fn draw_title_ui(
+        &mut self,
+        network_system: Arc<RwLock<NetworkSystem>>,
+    ) -> anyhow::Result<Option<Player>> {
+        if !self.is_initialized {
+            return Ok(None);
+        }
+        let compatibility = network_system.read().await.compatibility.clone();
+        if compatibility.is_update_required() {
+            self.draw_update_required_box(&compatibility);
+            return Ok(None);
+        }
+
+        self.draw_title_menu(&network_system).await;
+        self.draw_network_overlay(&network_system).await;
+
+        if self.ui_state.is_resuming_session {
+            self.ui_state.is_resuming_session = false;
+            let result = network_system.write().await.resume_session().await;
+            match result {
+                Ok(player) => {
+                    self.ui_state.logged_in = true;
+                    return Ok(Some(player));
+                }
+                Err(e) => {
+                    // 保存したセッションが使えなければ、ログインの画面で理由を伝える。
+                    // If the saved session can't be used, the reason is shown on the login form.
+                    log::warn!("Failed to resume the saved session: {:?}", e);
+                    self.ui_state.login_inputs.remember_me = true;
+                    self.ui_state.login_inputs.login_error = Some(e.to_string());
+                    self.ui_state.show_login_form = true;
+                }
+            }
+        }
 
         if self.ui_state.show_login_box {
-            self.draw_login_box(flags);
+            self.draw_login_box();
         }
 
         if self.ui_state.show_register_box {
-            let player = self
-                .draw_register_box(flags, network_system.clone())
-                .await?;
+            let player = self.draw_register_box(network_system.clone()).await?;
             if player.is_some() {
                 return Ok(player);
             }
         }
 
         if self.ui_state.show_login_form {
-            let player = self.draw_login_form(flags, network_system).await?;
+            let player = self.draw_login_form(network_system).await?;
             if player.is_some() {
                 return Ok(player);
             }
@@ -326,67 +1523,856 @@ where
         );
     }
 
-    pub fn input_motion(&mut self, x: f64, y: f64) {
-        self.context.input_motion(x as i32, y as i32);
+    pub fn input_motion(&mut self, x: f64, y: f64) {
+        self.context.input_motion(x as i32, y as i32);
+    }
+
+    pub fn input_scroll(&mut self, mouse_scroll_delta: MouseScrollDelta) {
+        self.context.input_scroll(match mouse_scroll_delta {
+            MouseScrollDelta::LineDelta(x, y) => {
+                let altered_x = (x as f64) * MOUSE_SENSITIVITY;
+                let altered_y = (y as f64) * MOUSE_SENSITIVITY;
+                nuklear::Vec2 {
+                    x: altered_x as f32,
+                    y: altered_y as f32,
+                }
+            }
+            MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition { x, y }) => nuklear::Vec2 {
+                x: (x * MOUSE_SENSITIVITY) as f32,
+                y: (y * MOUSE_SENSITIVITY) as f32,
+            },
+        });
+    }
+
+    pub fn input_unicode(&mut self, c: char) {
+        let mut buffer = [0; 4];
+        self.drawer.request_glyphs(c.encode_utf8(&mut buffer));
+        self.context.input_unicode(c);
+    }
+
+    pub fn set_disposing(&mut self) {
+        self.is_initialized = false;
+    }
+
+    pub fn set_initialized(&mut self) {
+        self.is_initialized = true;
+    }
+
+    /// 調整パネルの表示を切り替える。<br />
+    /// Toggle the visibility of the tweak panel.
+    pub fn toggle_tweak_panel(&mut self) {
+        if self.windows.is_visible(TWEAK_WINDOW) {
+            self.windows.hide(TWEAK_WINDOW);
+        } else {
+            self.windows.show(TWEAK_WINDOW);
+        }
+    }
+
+    /// 設定画面の表示を切り替える。<br />
+    /// Toggle the visibility of the settings page.
+    pub fn toggle_settings_panel(&mut self) {
+        if self.windows.is_visible(SETTINGS_WINDOW) {
+            self.windows.hide(SETTINGS_WINDOW);
+        } else {
+            self.windows.show(SETTINGS_WINDOW);
+        }
+    }
+
+    /// プロファイラーの表示を切り替える。<br />
+    /// Toggle the visibility of the profiler.
+    pub fn toggle_profiler_panel(&mut self) {
+        if self.windows.is_visible(PROFILER_WINDOW) {
+            self.windows.hide(PROFILER_WINDOW);
+        } else {
+            self.windows.show(PROFILER_WINDOW);
+        }
+    }
+
+    /// アニメーションのパネルの表示を切り替える。<br />
+    /// Toggle the visibility of the animation panel.
+    pub fn toggle_animation_panel(&mut self) {
+        if self.windows.is_visible(ANIMATION_WINDOW) {
+            self.windows.hide(ANIMATION_WINDOW);
+        } else {
+            self.windows.show(ANIMATION_WINDOW);
+        }
+    }
+
+    /// インスペクターの表示を切り替える。<br />
+    /// Toggle the visibility of the inspector.
+    pub fn toggle_inspector_panel(&mut self) {
+        if self.windows.is_visible(INSPECTOR_WINDOW) {
+            self.windows.hide(INSPECTOR_WINDOW);
+        } else {
+            self.windows.show(INSPECTOR_WINDOW);
+        }
+    }
+
+    /// ネットワークのオーバーレイの表示を切り替える。<br />
+    /// Toggle the visibility of the network overlay.
+    pub fn toggle_network_panel(&mut self) {
+        if self.windows.is_visible(NETWORK_WINDOW) {
+            self.windows.hide(NETWORK_WINDOW);
+        } else {
+            self.windows.show(NETWORK_WINDOW);
+        }
+    }
+
+    /// コンソールの表示を切り替える。開いた時は入力欄にフォーカスを移す。<br />
+    /// Toggle the visibility of the console. Opening it moves focus to the input field.
+    pub fn toggle_console_panel(&mut self) {
+        if self.windows.is_visible(CONSOLE_WINDOW) {
+            self.windows.hide(CONSOLE_WINDOW);
+        } else {
+            self.windows.show(CONSOLE_WINDOW);
+            self.focus_console_input = true;
+        }
+    }
+
+    pub fn is_console_open(&self) -> bool {
+        self.windows.is_visible(CONSOLE_WINDOW)
+    }
+
+    pub fn toggle_login_box(&mut self) {
+        self.ui_state.show_login_box = !self.ui_state.show_login_box;
+    }
+
+    /// 窓を登録する。同じ名前の窓があれば置き換える。<br />
+    /// Register a window, replacing any window with the same name.
+    pub fn add_window(&mut self, window: UiWindow) {
+        self.windows.add(window);
+    }
+
+    pub fn bring_window_to_front(&mut self, name: &str) -> bool {
+        self.windows.bring_to_front(name)
+    }
+
+    pub fn hide_window(&mut self, name: &str) -> bool {
+        self.windows.hide(name)
+    }
+
+    pub fn is_window_visible(&self, name: &str) -> bool {
+        self.windows.is_visible(name)
+    }
+
+    pub fn show_window(&mut self, name: &str) -> bool {
+        self.windows.show(name)
+    }
+
+    /// フレームの入力を始める。前のフレームで新しい文字や大きさが使われていたら、ここでフォントをベイクし直す。<br />
+    /// Begin the input of a frame. If new characters or sizes were used in the previous frame, the font is baked again here.
+    pub fn start_input(&mut self) {
+        if self.drawer.update_font_atlas(&mut self.context, DEFAULT_FONT_SIZE) {
+            self.convert_config.set_null(self.drawer.get_draw_null_texture());
+        }
+        self.context.input_begin();
+    }
+
+    pub fn wait_idle(&self) {
+        self.drawer.wait_idle();
+    }
+
+    /// チャットの窓を表示し、入力されたメッセージを送る。<br />
+    /// Show the chat window and send the message typed in.
+    async fn draw_chat_box(
+        &mut self,
+        messages: &[IncomingMessage],
+        network_system: Arc<RwLock<NetworkSystem>>,
+    ) {
+        let mut ui_state = self.ui_state.clone();
+        let mut outgoing_message = None;
+        let mut clicked_link = None;
+        {
+            let ctx = &mut self.context;
+            let drawer = &mut self.drawer;
+            if !Self::begin_window(&mut self.windows, drawer, ctx, "Chat") {
+                return;
+            }
+            let font_size = self
+                .windows
+                .get("Chat")
+                .map(|w| w.font_size as f32)
+                .unwrap_or(14.0);
+            let first_visible = messages.len().saturating_sub(CHAT_VISIBLE_LINES);
+            for message in messages[first_visible..].iter() {
+                drawer.request_glyphs(&message.author);
+                drawer.request_glyphs(&message.message);
+                let spans = parse_message(&message.author, &message.message);
+                if let Some(target) = Self::draw_rich_line(ctx, &spans, font_size, self.emoji_atlas)
+                {
+                    clicked_link = Some(target);
+                }
+            }
+            for _ in messages.len() - first_visible..CHAT_VISIBLE_LINES {
+                ctx.layout_row_dynamic(22.0, 1);
+                ctx.spacing(1);
+            }
+            let ratio = [0.8, 0.2];
+            ctx.layout_row(LayoutFormat::Dynamic, 30.0, &ratio[..]);
+            ctx.edit_string_custom_filter(
+                EditType::Field as Flags,
+                ui_state.chat_inputs.message_input.as_mut(),
+                &mut ui_state.chat_inputs.message_length,
+                Self::free_type_filter,
+            );
+            if ctx.button_text("Send") {
+                let length = ui_state.chat_inputs.message_length as usize;
+                outgoing_message = Some(
+                    String::from_utf8_lossy(&ui_state.chat_inputs.message_input[0..length])
+                        .to_string(),
+                );
+                ui_state.chat_inputs.clear();
+            }
+            drawer.set_font_size(ctx, 24);
+            ctx.end();
+        }
+
+        // 部屋のリンクはロビーでその部屋を選び、プレイヤーのリンクは返信のために名前を入力する。
+        // A room link selects that room in the lobby, and a player link types the name in to reply.
+        match clicked_link {
+            Some(LinkTarget::Room(room_id)) => {
+                ui_state.lobby_inputs.selected_room_id = Some(room_id);
+            }
+            Some(LinkTarget::Player(name)) => {
+                let mention = format!("@{} ", name);
+                let inputs = &mut ui_state.chat_inputs;
+                let start = inputs.message_length as usize;
+                let end = start + mention.len();
+                if end <= inputs.message_input.len() {
+                    inputs.message_input[start..end].copy_from_slice(mention.as_bytes());
+                    inputs.message_length = end as i32;
+                }
+            }
+            None => (),
+        }
+        self.ui_state = ui_state;
+
+        if let Some(message) = outgoing_message {
+            let network_system = network_system.read().await;
+            if let Err(e) = network_system.send_chat_message(&message).await {
+                log::warn!("Failed to send chat message: {}", e);
+            }
+        }
+    }
+
+    /// 装飾されたテキストを一行に並べる。リンクがクリックされたら、その行き先を返す。<br />
+    /// Lay out styled text on a single row. Returns the target of a link when it is clicked.
+    fn draw_rich_line(
+        ctx: &mut Context,
+        spans: &[RichSpan],
+        font_size: f32,
+        emoji_atlas: Option<i32>,
+    ) -> Option<LinkTarget> {
+        // アトラスがなければ絵文字を元の記法のまま表示する。
+        // Without an atlas, emojis are shown in their original notation.
+        let spans = spans
+            .iter()
+            .map(|span| match span {
+                RichSpan::Emoji(index) if emoji_atlas.is_none() => RichSpan::Text {
+                    text: format!(":{}:", EMOJI_NAMES[*index]),
+                    color: None,
+                },
+                span => span.clone(),
+            })
+            .collect::<Vec<_>>();
+        let to_color = |[r, g, b, a]: [u8; 4]| nuklear::Color { r, g, b, a };
+
+        let mut clicked = None;
+        ctx.layout_row_begin(LayoutFormat::Static, 22.0, spans.len() as i32);
+        for span in spans.iter() {
+            ctx.layout_row_push(span.estimate_width(font_size));
+            match span {
+                RichSpan::Text {
+                    text,
+                    color: Some(color),
+                } => ctx.text_colored(text, TextAlignment::Left as Flags, to_color(*color)),
+                RichSpan::Text { text, color: None } => {
+                    ctx.text(text, TextAlignment::Left as Flags)
+                }
+                RichSpan::Link { text, target } => {
+                    if ctx.widget_is_mouse_clicked(nuklear::Button::Left) {
+                        clicked = Some(target.clone());
+                    }
+                    ctx.text_colored(text, TextAlignment::Left as Flags, to_color(LINK_COLOR));
+                }
+                RichSpan::Emoji(index) => {
+                    if let Some(atlas) = emoji_atlas {
+                        let mut image = nuklear::Image::with_id(atlas);
+                        // アトラスの中の一つのアイコンだけを切り出す。
+                        // Cut out a single icon of the atlas.
+                        image.w = EMOJI_SIZE * EMOJI_NAMES.len() as u16;
+                        image.h = EMOJI_SIZE;
+                        image.region = [*index as u16 * EMOJI_SIZE, 0, EMOJI_SIZE, EMOJI_SIZE];
+                        ctx.image(image);
+                    }
+                }
+            }
+        }
+        ctx.layout_row_end();
+        clicked
+    }
+
+    /// 開いていれば、ネットワークの統計と補間バッファーの深さを画面の隅に表示する。<br />
+    /// Show statistics of the network traffic and the depth of the interpolation buffers in the corner of the screen, if open.
+    async fn draw_network_overlay(&mut self, network_system: &Arc<RwLock<NetworkSystem>>) {
+        if !self.windows.is_visible(NETWORK_WINDOW) {
+            return;
+        }
+        let (network_stats, buffer_depths) = {
+            let ns = network_system.read().await;
+            let network_stats = *ns.network_stats.lock().await;
+            let buffer_depths = ns.state_queues.lock().await.get_buffer_depths();
+            (network_stats, buffer_depths)
+        };
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, NETWORK_WINDOW) {
+            return;
+        }
+        let NetworkStats {
+            sent,
+            received,
+            dropped_datagrams,
+            round_trip_time,
+            session,
+            rates,
+            grpc,
+        } = &network_stats;
+        let format_duration = |duration: Option<Duration>| match duration {
+            Some(duration) => format!("{:.1} ms", duration.as_secs_f64() * 1000.0),
+            None => "-".to_string(),
+        };
+        let buffer_depths = match buffer_depths {
+            Some((min, max)) => format!("{} - {} states", min, max),
+            None => "-".to_string(),
+        };
+        let lines = [
+            format!(
+                "gRPC {}: {} (avg {})",
+                grpc.last_call.unwrap_or("-"),
+                format_duration(grpc.last),
+                format_duration(grpc.average)
+            ),
+            format!("gRPC calls: {} / Failed: {}", grpc.calls, grpc.failures),
+            format!(
+                "Packets/s: {:.0} out / {:.0} in",
+                rates.datagrams_sent, rates.datagrams_received
+            ),
+            format!(
+                "Bytes/s: {:.0} out / {:.0} in",
+                rates.bytes_sent, rates.bytes_received
+            ),
+            format!("Interpolation buffer: {}", buffer_depths),
+            format!(
+                "Sent: {} snapshots / {} datagrams / {} B",
+                sent.messages, sent.datagrams, sent.bytes
+            ),
+            format!(
+                "Received: {} snapshots / {} datagrams / {} B",
+                received.messages, received.datagrams, received.bytes
+            ),
+            format!(
+                "Last snapshot: {} B in {} datagram(s)",
+                sent.last_message_size, sent.last_datagram_count
+            ),
+            format!(
+                "Fragmented: {} sent / {} received",
+                sent.fragmented_messages, received.fragmented_messages
+            ),
+            format!(
+                "Compression: {:.0}% sent / {:.0}% received",
+                sent.get_compression_ratio() * 100.0,
+                received.get_compression_ratio() * 100.0
+            ),
+            format!("Dropped datagrams: {}", dropped_datagrams),
+            format!("Round trip: {}", format_duration(*round_trip_time)),
+            format!(
+                "Duplicates: {} / Reordered: {} / Stale: {}",
+                session.duplicate_packets, session.reordered_packets, session.stale_packets
+            ),
+        ];
+        for line in lines.iter() {
+            ctx.layout_row_dynamic(20.0, 1);
+            ctx.text(line, TextAlignment::Left as Flags);
+        }
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+    }
+
+    /// 部屋の一覧を表示する。参加ボタンが押されたら、選ばれた部屋を返す。<br />
+    /// Show the list of rooms. Returns the selected room when the join button is pressed.
+    fn draw_room_list(&mut self) -> Option<RoomState> {
+        let mut lobby_inputs = self.ui_state.lobby_inputs.clone();
+        let mut joined_room = None;
+        {
+            let ctx = &mut self.context;
+            let drawer = &mut self.drawer;
+            if !Self::begin_window(&mut self.windows, drawer, ctx, "Rooms") {
+                return None;
+            }
+            Self::set_ui_header(drawer, ctx, "Rooms", TextAlignment::Centered);
+            drawer.set_font_size(ctx, 16);
+            let ratio = [0.5, 0.25, 0.25];
+            ctx.layout_row(LayoutFormat::Dynamic, 30.0, &ratio[..]);
+            ctx.text("Name", TextAlignment::Left as Flags);
+            ctx.text("Players", TextAlignment::Centered as Flags);
+            ctx.text("Status", TextAlignment::Centered as Flags);
+            for room in lobby_inputs.rooms.iter() {
+                ctx.layout_row(LayoutFormat::Dynamic, 30.0, &ratio[..]);
+                let mut is_selected =
+                    lobby_inputs.selected_room_id.as_deref() == Some(room.room_id.as_str());
+                let name = if room.room_name.is_empty() {
+                    &room.room_id
+                } else {
+                    &room.room_name
+                };
+                drawer.request_glyphs(name);
+                if ctx.selectable_text(name, TextAlignment::Left as Flags, &mut is_selected) {
+                    lobby_inputs.selected_room_id = if is_selected {
+                        Some(room.room_id.clone())
+                    } else {
+                        None
+                    };
+                }
+                let players = format!("{} / {}", room.current_players, room.max_players);
+                ctx.text(&players, TextAlignment::Centered as Flags);
+                let status = if room.started {
+                    "Playing"
+                } else if room.current_players >= room.max_players {
+                    "Full"
+                } else {
+                    "Waiting"
+                };
+                ctx.text(status, TextAlignment::Centered as Flags);
+            }
+            if lobby_inputs.rooms.is_empty() {
+                ctx.layout_row_dynamic(30.0, 1);
+                ctx.text(
+                    "No rooms available. Create one!",
+                    TextAlignment::Centered as Flags,
+                );
+            }
+            ctx.layout_row_dynamic(50.0, 2);
+            if ctx.button_text("Refresh") {
+                lobby_inputs.is_outdated = true;
+            }
+            if !GraphicsType::CAN_RENDER_SCENES {
+                ctx.text(SCENES_UNAVAILABLE_MESSAGE, TextAlignment::Centered as Flags);
+            } else if ctx.button_text("Join") {
+                // 始まった部屋や満員の部屋には入れない。
+                // Rooms that have started or are full cannot be joined.
+                joined_room = lobby_inputs
+                    .rooms
+                    .iter()
+                    .find(|r| lobby_inputs.selected_room_id.as_deref() == Some(r.room_id.as_str()))
+                    .filter(|r| !r.started && r.current_players < r.max_players)
+                    .cloned();
+            }
+            drawer.set_font_size(ctx, 24);
+            ctx.end();
+        }
+        self.ui_state.lobby_inputs = lobby_inputs;
+        joined_room
+    }
+
+    /// 部屋を作る画面を表示する。作成ボタンが押されたら、部屋の名前と最大人数を返す。<br />
+    /// Show the room creation box. Returns the room name and the maximum number of players when the create button is pressed.
+    fn draw_create_room_box(&mut self) -> Option<(String, i32)> {
+        let mut lobby_inputs = self.ui_state.lobby_inputs.clone();
+        let mut new_room = None;
+        {
+            let ctx = &mut self.context;
+            let drawer = &mut self.drawer;
+            if !Self::begin_window(&mut self.windows, drawer, ctx, "CreateRoom") {
+                return None;
+            }
+            Self::set_ui_header(drawer, ctx, "Create Room", TextAlignment::Centered);
+            drawer.set_font_size(ctx, 16);
+            let ratio = [0.4, 0.6];
+            ctx.layout_row(LayoutFormat::Dynamic, 50.0, &ratio[..]);
+            ctx.text("Room name: ", TextAlignment::Right as Flags);
+            ctx.edit_string_custom_filter(
+                EditType::Field as Flags,
+                lobby_inputs.room_name_input.as_mut(),
+                &mut lobby_inputs.room_name_length,
+                Self::free_type_filter,
+            );
+            ctx.layout_row_dynamic(40.0, 1);
+            ctx.property_int(
+                nuklear::nk_string!("Max players:"),
+                MIN_ROOM_PLAYERS,
+                &mut lobby_inputs.max_players,
+                MAX_ROOM_PLAYERS,
+                1,
+                1.0,
+            );
+            ctx.layout_row_dynamic(50.0, 1);
+            if !GraphicsType::CAN_RENDER_SCENES {
+                ctx.text(SCENES_UNAVAILABLE_MESSAGE, TextAlignment::Centered as Flags);
+            } else if ctx.button_text("Create") {
+                let length = lobby_inputs.room_name_length as usize;
+                let room_name = String::from_utf8_lossy(&lobby_inputs.room_name_input[0..length])
+                    .trim()
+                    .to_string();
+                if !room_name.is_empty() {
+                    new_room = Some((room_name, lobby_inputs.max_players));
+                }
+            }
+            drawer.set_font_size(ctx, 24);
+            ctx.end();
+        }
+        self.ui_state.lobby_inputs = lobby_inputs;
+        new_room
+    }
+
+    /// LANで見つかったホストの一覧を表示する。参加ボタンが押されたら、選ばれたホストを返す。<br />
+    /// Show the list of hosts found on the LAN. Returns the selected host when the join button is pressed.
+    fn draw_lan_browser(&mut self) -> Option<HostAddress> {
+        let mut lobby_inputs = self.ui_state.lobby_inputs.clone();
+        let mut joined_host = None;
+        {
+            let ctx = &mut self.context;
+            let drawer = &mut self.drawer;
+            if !Self::begin_window(&mut self.windows, drawer, ctx, "LAN") {
+                return None;
+            }
+            Self::set_ui_header(drawer, ctx, "LAN Games", TextAlignment::Centered);
+            drawer.set_font_size(ctx, 16);
+            let ratio = [0.5, 0.25, 0.25];
+            ctx.layout_row(LayoutFormat::Dynamic, 30.0, &ratio[..]);
+            ctx.text("Host", TextAlignment::Left as Flags);
+            ctx.text("Players", TextAlignment::Centered as Flags);
+            ctx.text("Open rooms", TextAlignment::Centered as Flags);
+            for host in lobby_inputs.lan_hosts.iter() {
+                ctx.layout_row(LayoutFormat::Dynamic, 30.0, &ratio[..]);
+                let address = host.get_address();
+                let mut is_selected = lobby_inputs.selected_host == Some(address);
+                let name = if host.announcement.server_name.is_empty() {
+                    address.ip.to_string()
+                } else {
+                    host.announcement.server_name.clone()
+                };
+                drawer.request_glyphs(&name);
+                if ctx.selectable_text(&name, TextAlignment::Left as Flags, &mut is_selected) {
+                    lobby_inputs.selected_host = if is_selected { Some(address) } else { None };
+                }
+                let players = host.announcement.current_players.to_string();
+                ctx.text(&players, TextAlignment::Centered as Flags);
+                let open_rooms = host.announcement.open_rooms.to_string();
+                ctx.text(&open_rooms, TextAlignment::Centered as Flags);
+            }
+            if lobby_inputs.lan_hosts.is_empty() {
+                ctx.layout_row_dynamic(30.0, 1);
+                ctx.text(
+                    "Looking for games on the LAN...",
+                    TextAlignment::Centered as Flags,
+                );
+            }
+            ctx.layout_row_dynamic(40.0, 1);
+            if ctx.button_text("Join") {
+                // 選んだ後に消えたホストには接続しない。
+                // Hosts that disappeared after being selected aren't connected to.
+                joined_host = lobby_inputs
+                    .lan_hosts
+                    .iter()
+                    .map(|h| h.get_address())
+                    .find(|a| lobby_inputs.selected_host == Some(*a));
+            }
+            drawer.set_font_size(ctx, 24);
+            ctx.end();
+        }
+        self.ui_state.lobby_inputs = lobby_inputs;
+        joined_host
+    }
+
+    /// `ip:port`を入力してホストに直接接続する画面を表示する。接続ボタンが押されたら、解析したアドレスを返す。<br />
+    /// Show the box for connecting to a host directly by `ip:port`. Returns the parsed address when the connect button is pressed.
+    fn draw_direct_connect_box(&mut self) -> Option<HostAddress> {
+        let mut lobby_inputs = self.ui_state.lobby_inputs.clone();
+        let mut address = None;
+        {
+            let ctx = &mut self.context;
+            let drawer = &mut self.drawer;
+            if !Self::begin_window(&mut self.windows, drawer, ctx, "DirectConnect") {
+                return None;
+            }
+            Self::set_ui_header(drawer, ctx, "Direct Connect", TextAlignment::Centered);
+            drawer.set_font_size(ctx, 16);
+            let ratio = [0.3, 0.4, 0.3];
+            ctx.layout_row(LayoutFormat::Dynamic, 40.0, &ratio[..]);
+            ctx.text("Address: ", TextAlignment::Right as Flags);
+            ctx.edit_string_custom_filter(
+                EditType::Field as Flags,
+                lobby_inputs.address_input.as_mut(),
+                &mut lobby_inputs.address_length,
+                Self::address_filter,
+            );
+            if ctx.button_text("Connect") {
+                let length = lobby_inputs.address_length as usize;
+                let input = String::from_utf8_lossy(&lobby_inputs.address_input[0..length]);
+                match HostAddress::parse(&input) {
+                    Ok(a) => address = Some(a),
+                    Err(e) => lobby_inputs.connection_error = Some(e.to_string()),
+                }
+            }
+            if let Some(error) = lobby_inputs.connection_error.as_ref() {
+                ctx.layout_row_dynamic(40.0, 1);
+                ctx.text_wrap(error);
+            }
+            drawer.set_font_size(ctx, 24);
+            ctx.end();
+        }
+        self.ui_state.lobby_inputs = lobby_inputs;
+        address
     }
 
-    pub fn input_scroll(&mut self, mouse_scroll_delta: MouseScrollDelta) {
-        self.context.input_scroll(match mouse_scroll_delta {
-            MouseScrollDelta::LineDelta(x, y) => {
-                let altered_x = (x as f64) * MOUSE_SENSITIVITY;
-                let altered_y = (y as f64) * MOUSE_SENSITIVITY;
-                nuklear::Vec2 {
-                    x: altered_x as f32,
-                    y: altered_y as f32,
-                }
+    /// 部屋にいるプレイヤーを表示する。オーナーには開始ボタンを表示する。<br />
+    /// Show the players in the room, with a start button for the owner.
+    async fn draw_waiting_room(&mut self, network_system: Arc<RwLock<NetworkSystem>>) {
+        let ns = network_system.read().await;
+        let is_owner = match ns.logged_user.as_ref() {
+            Some(player) => player
+                .lock()
+                .await
+                .state
+                .as_ref()
+                .map(|s| s.is_owner)
+                .unwrap_or(false),
+            None => false,
+        };
+        let mut room_state = ns.room_state.lock().await;
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, "WaitingRoom") {
+            return;
+        }
+        let title = if room_state.room_name.is_empty() {
+            "Waiting Room"
+        } else {
+            room_state.room_name.as_str()
+        };
+        drawer.request_glyphs(title);
+        Self::set_ui_header(drawer, ctx, title, TextAlignment::Centered);
+        drawer.set_font_size(ctx, 16);
+        ctx.layout_row_dynamic(30.0, 1);
+        let current_players = format!(
+            "Players: {} / {}",
+            room_state.current_players, room_state.max_players
+        );
+        ctx.text(&current_players, TextAlignment::Centered as Flags);
+        let to_color = |[r, g, b, a]: [u8; 4]| nuklear::Color { r, g, b, a };
+        let nameplates = get_room_nameplates(&room_state);
+        for (player, nameplate) in room_state.players.iter().zip(nameplates.iter()) {
+            ctx.layout_row_dynamic(30.0, 1);
+            let is_room_owner = player.state.as_ref().map(|s| s.is_owner).unwrap_or(false);
+            let mut line = nameplate.label.clone();
+            if !nameplate.title.is_empty() {
+                line = format!("{} <{}>", line, nameplate.title);
             }
-            MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition { x, y }) => nuklear::Vec2 {
-                x: (x * MOUSE_SENSITIVITY) as f32,
-                y: (y * MOUSE_SENSITIVITY) as f32,
-            },
-        });
+            if is_room_owner {
+                line = format!("{} (Owner)", line);
+            }
+            if nameplate.is_chat_muted {
+                line = format!("{} (Chat muted)", line);
+            }
+            drawer.request_glyphs(&line);
+            ctx.text_colored(
+                &line,
+                TextAlignment::Left as Flags,
+                to_color(nameplate.color),
+            );
+        }
+        let is_player_sufficient = room_state.current_players >= MIN_ROOM_PLAYERS;
+        if is_owner && is_player_sufficient {
+            let ratio = [0.25, 0.5, 0.25];
+            ctx.layout_row(LayoutFormat::Dynamic, 50.0, &ratio);
+            ctx.spacing(1);
+            if ctx.button_text("Start") {
+                room_state.started = true;
+            }
+            ctx.spacing(1);
+        } else {
+            ctx.layout_row_dynamic(50.0, 1);
+            let message = if is_owner {
+                "Wait for opponents..."
+            } else {
+                "Wait for the owner to start the game..."
+            };
+            ctx.text(message, TextAlignment::Centered as Flags);
+        }
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
     }
 
-    pub fn input_unicode(&mut self, c: char) {
-        self.context.input_unicode(c);
+    /// タイトル画面のメニューを表示する。<br />
+    /// Show the menu of the title screen.
+    async fn draw_title_menu(&mut self, network_system: &Arc<RwLock<NetworkSystem>>) {
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, "User Interface") {
+            return;
+        }
+        Self::set_ui_header(drawer, ctx, "Game Menu", TextAlignment::Centered);
+        Self::set_ui_widget(drawer, ctx, 50.0, true);
+
+        if ctx.button_text("Start")
+            && !self.ui_state.show_login_box
+            && !self.ui_state.show_register_box
+            && !self.ui_state.show_login_form
+            && !network_system.read().await.is_player_login
+        {
+            if std::path::Path::new(SESSION_PATH).exists() {
+                self.ui_state.is_resuming_session = true;
+            } else {
+                self.ui_state.show_login_box = true;
+            }
+        }
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
     }
 
-    pub fn set_disposing(&mut self) {
-        self.is_initialized = false;
+    /// サーバーとプロトコルのバージョンが合わない時、更新を促す画面を表示する。<br />
+    /// Show a screen asking the player to update when the protocol version doesn't match the server.
+    fn draw_update_required_box(&mut self, compatibility: &ProtocolCompatibility) {
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, "Update Required") {
+            return;
+        }
+        Self::set_ui_header(drawer, ctx, "Update Required", TextAlignment::Centered);
+        drawer.set_font_size(ctx, 16);
+        ctx.layout_row_dynamic(60.0, 1);
+        ctx.text_wrap("This version of the game is not compatible with the server. Please update the game to continue.");
+        if let ProtocolCompatibility::UpdateRequired {
+            server_protocol_version,
+            server_build_version,
+            message,
+        } = compatibility
+        {
+            ctx.layout_row_dynamic(30.0, 1);
+            let versions = format!(
+                "Client protocol: {}, server protocol: {} ({})",
+                PROTOCOL_VERSION, server_protocol_version, server_build_version
+            );
+            ctx.text(&versions, TextAlignment::Centered as Flags);
+            if !message.is_empty() {
+                ctx.layout_row_dynamic(60.0, 1);
+                ctx.text_wrap(message);
+            }
+        }
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
     }
 
-    pub fn set_initialized(&mut self) {
-        self.is_initialized = true;
+    /// セーフモードで起動した時、変えた設定を説明する画面を表示する。<br />
+    /// Show a screen explaining the changed settings after starting in safe mode.
+    pub fn draw_safe_mode_dialog(&mut self, changes: &[String]) {
+        if !self.is_initialized {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, SAFE_MODE_WINDOW) {
+            return;
+        }
+        Self::set_ui_header(drawer, ctx, SAFE_MODE_WINDOW, TextAlignment::Centered);
+        drawer.set_font_size(ctx, 16);
+        for change in changes.iter() {
+            ctx.layout_row_dynamic(24.0, 1);
+            ctx.text_wrap(change);
+        }
+        ctx.layout_row(LayoutFormat::Dynamic, 40.0, &RATIO_WC);
+        ctx.spacing(1);
+        let close = ctx.button_text("OK");
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+        if close {
+            self.windows.hide(SAFE_MODE_WINDOW);
+        }
     }
 
-    pub fn toggle_login_box(&mut self) {
-        self.ui_state.show_login_box = !self.ui_state.show_login_box;
+    /// 代わりの素材で置き換えた読み込みの失敗を一覧で表示する。<br />
+    /// Show the list of load failures that were replaced with placeholders.
+    pub fn draw_asset_warnings(&mut self, warnings: &[AssetWarning]) {
+        if !self.is_initialized || warnings.len() <= self.dismissed_asset_warnings {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, ASSET_WARNINGS_WINDOW) {
+            return;
+        }
+        for warning in warnings.iter() {
+            ctx.layout_row_dynamic(20.0, 1);
+            let line = format!("{}: {}", warning.kind, warning.path);
+            drawer.request_glyphs(&line);
+            ctx.text_colored(
+                &line,
+                TextAlignment::Left as Flags,
+                nuklear::Color {
+                    r: 255,
+                    g: 0,
+                    b: 255,
+                    a: 255,
+                },
+            );
+            ctx.layout_row_dynamic(20.0, 1);
+            drawer.request_glyphs(&warning.message);
+            ctx.text(&warning.message, TextAlignment::Left as Flags);
+        }
+        ctx.layout_row(LayoutFormat::Dynamic, 30.0, &RATIO_WC);
+        ctx.spacing(1);
+        let dismiss = ctx.button_text("Dismiss");
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
+        if dismiss {
+            self.dismissed_asset_warnings = warnings.len();
+        }
     }
 
-    pub fn start_input(&mut self) {
-        self.context.input_begin();
+    /// シーンを読み込んでいる間、読み込み画面とプログレスバーを表示する。<br />
+    /// Show the loading screen and its progress bar while a scene is loading.
+    pub fn draw_loading_screen(&mut self, progress: &LoadingProgress) {
+        if !self.is_initialized {
+            return;
+        }
+        let ctx = &mut self.context;
+        let drawer = &mut self.drawer;
+        if !Self::begin_window(&mut self.windows, drawer, ctx, LOADING_WINDOW) {
+            return;
+        }
+        Self::set_ui_header(drawer, ctx, "Loading...", TextAlignment::Centered);
+        ctx.layout_row_dynamic(30.0, 1);
+        ctx.prog(
+            progress.completed_tasks as nuklear::Size,
+            progress.total_tasks.max(1) as nuklear::Size,
+            false,
+        );
+        drawer.set_font_size(ctx, 16);
+        ctx.layout_row_dynamic(24.0, 1);
+        let status = format!(
+            "Assets: {} / {}    Textures uploaded: {}",
+            progress.completed_tasks, progress.total_tasks, progress.textures_uploaded
+        );
+        ctx.text(&status, TextAlignment::Centered as Flags);
+        drawer.set_font_size(ctx, 24);
+        ctx.end();
     }
 
-    pub fn wait_idle(&self) {
-        self.drawer.wait_idle();
+    /// セーフモードの説明を表示する。<br />
+    /// Show the safe mode explanation.
+    pub fn show_safe_mode_dialog(&mut self) {
+        self.windows.show(SAFE_MODE_WINDOW);
     }
 
-    fn draw_login_box(&mut self, flags: Flags) {
+    fn draw_login_box(&mut self) {
         let mut ui_state = self.ui_state.clone();
         {
             let ctx = &mut self.context;
             let drawer = &mut self.drawer;
-            drawer.set_font_size(ctx, 28);
-            ctx.begin(
-                nuklear::nk_string!("Login"),
-                nuklear::Rect {
-                    x: 500.0,
-                    y: 350.0,
-                    w: 600.0,
-                    h: 200.0,
-                },
-                flags,
-            );
+            if !Self::begin_window(&mut self.windows, drawer, ctx, "Login") {
+                return;
+            }
             Self::set_ui_header(drawer, ctx, "Login", TextAlignment::Centered);
             ctx.text_wrap("You haven't logged in. Please login or register first!");
             drawer.set_font_size(ctx, 16);
@@ -415,7 +2401,6 @@ where
 
     async fn draw_login_form(
         &mut self,
-        flags: Flags,
         network_system: Arc<RwLock<NetworkSystem>>,
     ) -> anyhow::Result<Option<Player>> {
         let mut ui_state = self.ui_state.clone();
@@ -423,17 +2408,9 @@ where
         {
             let ctx = &mut self.context;
             let drawer = &mut self.drawer;
-            drawer.set_font_size(ctx, 28);
-            ctx.begin(
-                nuklear::nk_string!("LoginForm"),
-                nuklear::Rect {
-                    x: 350.0,
-                    y: 300.0,
-                    w: 900.0,
-                    h: 400.0,
-                },
-                flags,
-            );
+            if !Self::begin_window(&mut self.windows, drawer, ctx, "LoginForm") {
+                return Ok(None);
+            }
             drawer.set_font_size(ctx, 36);
             ctx.layout_row_dynamic(50.0, 1);
             ctx.text("Login", TextAlignment::Centered as Flags);
@@ -458,32 +2435,58 @@ where
             /*for i in 0..ui_state.login_inputs.password_length {
                 ui_state.login_inputs.password_input[i as usize] = '\u{002A}' as u8;
             }*/
+            ctx.layout_row_dynamic(30.0, 1);
+            ctx.checkbox_text("Remember me", &mut ui_state.login_inputs.remember_me);
             ctx.layout_row_dynamic(50.0, 2);
             if ctx.button_text("Login") {
                 let account = std::str::from_utf8(
                     &ui_state.login_inputs.account_input
                         [0..(ui_state.login_inputs.account_length as usize)],
-                )?;
+                )?
+                .trim()
+                .to_string();
                 let password = std::str::from_utf8(
                     &ui_state.login_inputs.actual_password
                         [0..(ui_state.login_inputs.password_length as usize)],
-                )?;
-                let encoded_pass = base64::encode(password.trim());
-                let mut network_system_lock = network_system.write().await;
-                let p = network_system_lock
-                    .login(Some((account.to_string(), encoded_pass)))
-                    .await;
-                ui_state.login_inputs.clear();
-                if let Some(p) = p {
-                    ui_state.show_login_form = false;
-                    ui_state.logged_in = true;
-                    player = Some(p);
+                )?
+                .to_string();
+                let result = match validate_login(&account, &password) {
+                    Ok(_) => {
+                        let encoded_pass = base64::encode(password.trim());
+                        let mut network_system_lock = network_system.write().await;
+                        let result = network_system_lock.login(&account, &encoded_pass).await;
+                        if result.is_ok() {
+                            if let Err(e) = network_system_lock
+                                .remember_session(ui_state.login_inputs.remember_me)
+                            {
+                                log::warn!("Failed to save the session: {:?}", e);
+                            }
+                        }
+                        result
+                    }
+                    Err(e) => Err(e.into()),
+                };
+                match result {
+                    Ok(p) => {
+                        ui_state.login_inputs.clear();
+                        ui_state.show_login_form = false;
+                        ui_state.logged_in = true;
+                        player = Some(p);
+                    }
+                    Err(e) => {
+                        ui_state.login_inputs.clear_password();
+                        ui_state.login_inputs.login_error = Some(e.to_string());
+                    }
                 }
             }
             if ctx.button_text("Cancel") {
                 ui_state.login_inputs.clear();
                 ui_state.show_login_form = false;
             }
+            if let Some(error) = ui_state.login_inputs.login_error.as_ref() {
+                ctx.layout_row_dynamic(40.0, 1);
+                ctx.text_wrap(error);
+            }
             drawer.set_font_size(ctx, 24);
             ctx.end();
         }
@@ -493,7 +2496,6 @@ where
 
     async fn draw_register_box(
         &mut self,
-        flags: Flags,
         network_system: Arc<RwLock<NetworkSystem>>,
     ) -> anyhow::Result<Option<Player>> {
         let mut ui_state = self.ui_state.clone();
@@ -501,17 +2503,9 @@ where
         {
             let ctx = &mut self.context;
             let drawer = &mut self.drawer;
-            drawer.set_font_size(ctx, 28);
-            ctx.begin(
-                nuklear::nk_string!("Register"),
-                nuklear::Rect {
-                    x: 500.0,
-                    y: 300.0,
-                    w: 600.0,
-                    h: 400.0,
-                },
-                flags,
-            );
+            if !Self::begin_window(&mut self.windows, drawer, ctx, "Register") {
+                return Ok(None);
+            }
             //Self::set_ui_header(drawer, ctx, "Register", TextAlignment::Centered);
             drawer.set_font_size(ctx, 36);
             ctx.layout_row_dynamic(50.0, 1);
@@ -547,6 +2541,13 @@ where
                 &mut ui_state.registration_inputs.password_length,
                 Self::free_type_filter,
             );
+            ctx.text("Confirm Password: ", TextAlignment::Right as Flags);
+            ctx.edit_string_custom_filter(
+                EditType::Field as Flags,
+                ui_state.registration_inputs.confirmation_input.as_mut(),
+                &mut ui_state.registration_inputs.confirmation_length,
+                Self::free_type_filter,
+            );
             ctx.layout_row_dynamic(50.0, 2);
             if ctx.button_text("Register") {
                 let username = std::str::from_utf8(
@@ -565,21 +2566,34 @@ where
                     &ui_state.registration_inputs.password_input
                         [0..(ui_state.registration_inputs.password_length as usize)],
                 )?;
+                let confirmation = std::str::from_utf8(
+                    &ui_state.registration_inputs.confirmation_input
+                        [0..(ui_state.registration_inputs.confirmation_length as usize)],
+                )?;
                 let mut network_system_lock = network_system.write().await;
-                let (result, p) = network_system_lock
-                    .register(username, nickname, email, password)
+                let result = network_system_lock
+                    .register(username, nickname, email, password, confirmation)
                     .await;
-                ui_state.registration_inputs.clear();
-                if result {
-                    ui_state.show_register_box = false;
-                    ui_state.logged_in = true;
-                    player = p;
+                match result {
+                    Ok(p) => {
+                        ui_state.registration_inputs.clear();
+                        ui_state.show_register_box = false;
+                        ui_state.logged_in = true;
+                        player = Some(p);
+                    }
+                    Err(e) => {
+                        ui_state.registration_inputs.registration_error = Some(e.to_string());
+                    }
                 }
             }
             if ctx.button_text("Cancel") {
                 ui_state.registration_inputs.clear();
                 ui_state.show_register_box = false;
             }
+            if let Some(error) = ui_state.registration_inputs.registration_error.as_ref() {
+                ctx.layout_row_dynamic(40.0, 1);
+                ctx.text_wrap(error);
+            }
             drawer.set_font_size(ctx, 24);
             ctx.end();
         }
@@ -599,8 +2613,354 @@ where
         c >= '\u{0020}'
     }
 
+    /// IPv4とIPv6のアドレス、ポートに使う文字だけを通す。<br />
+    /// Only lets through characters used in IPv4 and IPv6 addresses and ports.
+    fn address_filter(_: &TextEdit, c: char) -> bool {
+        c.is_ascii_hexdigit() || c == '.' || c == ':' || c == '[' || c == ']'
+    }
+
+    /// 名前で登録した窓を、その窓のフォントと位置で始める。<br />
+    /// 窓が隠れているか登録されていなければ何もせず`false`を返し、`end`を呼んではいけない。<br />
+    /// Begin a registered window with its own font and bounds.<br />
+    /// Returns `false` without doing anything if the window is hidden or not registered, in which case `end` must not be called.
+    fn begin_window(
+        windows: &mut WindowManager,
+        drawer: &mut GraphicsType::UiDrawer,
+        ctx: &mut Context,
+        name: &str,
+    ) -> bool {
+        let window = match windows.get(name) {
+            Some(window) if window.is_visible => window,
+            _ => return false,
+        };
+        let WindowBounds { x, y, w, h } = window.bounds;
+        drawer.set_font_size(ctx, window.font_size);
+        ctx.begin(
+            nuklear::String::from(name),
+            nuklear::Rect { x, y, w, h },
+            window.flags,
+        );
+        windows.mark_drawn(name);
+        true
+    }
+
+    /// 既定の窓を登録する。接続の状況などの知らせは他の窓より手前に出す。<br />
+    /// Register the default windows. Notices such as the connection status are shown in front of other windows.
+    fn create_windows() -> WindowManager {
+        let flags = PanelFlags::Border as Flags | PanelFlags::NoScrollbar as Flags;
+        let mut windows = WindowManager::new();
+        let defaults = [
+            (
+                "User Interface",
+                WindowBounds::new(0.0, 0.0, 300.0, 900.0),
+                flags,
+                24,
+                0,
+            ),
+            (
+                "Rooms",
+                WindowBounds::new(100.0, 100.0, 800.0, 500.0),
+                flags,
+                28,
+                0,
+            ),
+            (
+                "CreateRoom",
+                WindowBounds::new(950.0, 100.0, 500.0, 280.0),
+                flags,
+                28,
+                0,
+            ),
+            (
+                "LAN",
+                WindowBounds::new(950.0, 400.0, 500.0, 250.0),
+                flags,
+                28,
+                0,
+            ),
+            (
+                "DirectConnect",
+                WindowBounds::new(950.0, 670.0, 500.0, 180.0),
+                flags,
+                28,
+                0,
+            ),
+            (
+                "WaitingRoom",
+                WindowBounds::new(500.0, 100.0, 600.0, 500.0),
+                flags,
+                28,
+                0,
+            ),
+            (
+                "Chat",
+                WindowBounds::new(10.0, 620.0, 480.0, 270.0),
+                flags,
+                14,
+                1,
+            ),
+            (
+                NETWORK_WINDOW,
+                WindowBounds::new(10.0, 10.0, 340.0, 300.0),
+                flags | PanelFlags::NoInput as Flags,
+                14,
+                1,
+            ),
+            (
+                SCOREBOARD_WINDOW,
+                WindowBounds::new(450.0, 150.0, 700.0, 400.0),
+                flags | PanelFlags::NoInput as Flags,
+                14,
+                2,
+            ),
+            (
+                RESULTS_WINDOW,
+                WindowBounds::new(550.0, 250.0, 500.0, 300.0),
+                flags,
+                28,
+                3,
+            ),
+            (
+                "Login",
+                WindowBounds::new(500.0, 350.0, 600.0, 200.0),
+                flags,
+                28,
+                2,
+            ),
+            (
+                "LoginForm",
+                WindowBounds::new(350.0, 300.0, 900.0, 400.0),
+                flags,
+                28,
+                2,
+            ),
+            (
+                "Register",
+                WindowBounds::new(500.0, 300.0, 600.0, 400.0),
+                flags,
+                28,
+                2,
+            ),
+            (
+                "Update Required",
+                WindowBounds::new(500.0, 300.0, 600.0, 300.0),
+                flags,
+                28,
+                3,
+            ),
+            (
+                "Connection",
+                WindowBounds::new(600.0, 350.0, 400.0, 160.0),
+                flags,
+                28,
+                4,
+            ),
+            (
+                LOADING_WINDOW,
+                WindowBounds::new(500.0, 370.0, 600.0, 160.0),
+                flags,
+                28,
+                4,
+            ),
+            (
+                TWEAK_WINDOW,
+                WindowBounds::new(1100.0, 10.0, 480.0, 400.0),
+                PanelFlags::Border as Flags
+                    | PanelFlags::Movable as Flags
+                    | PanelFlags::Title as Flags,
+                14,
+                1,
+            ),
+            (
+                ANIMATION_WINDOW,
+                WindowBounds::new(1100.0, 420.0, 480.0, 300.0),
+                PanelFlags::Border as Flags
+                    | PanelFlags::Movable as Flags
+                    | PanelFlags::Title as Flags,
+                14,
+                1,
+            ),
+            (
+                INSPECTOR_WINDOW,
+                WindowBounds::new(380.0, 10.0, 420.0, 560.0),
+                PanelFlags::Border as Flags
+                    | PanelFlags::Movable as Flags
+                    | PanelFlags::Title as Flags,
+                14,
+                1,
+            ),
+            (
+                SETTINGS_WINDOW,
+                WindowBounds::new(600.0, 300.0, 400.0, 200.0),
+                PanelFlags::Border as Flags
+                    | PanelFlags::Movable as Flags
+                    | PanelFlags::Title as Flags,
+                20,
+                4,
+            ),
+            (
+                SAFE_MODE_WINDOW,
+                WindowBounds::new(450.0, 250.0, 700.0, 320.0),
+                flags,
+                28,
+                4,
+            ),
+            (
+                ASSET_WARNINGS_WINDOW,
+                WindowBounds::new(1100.0, 690.0, 490.0, 200.0),
+                PanelFlags::Border as Flags
+                    | PanelFlags::Movable as Flags
+                    | PanelFlags::Title as Flags,
+                14,
+                3,
+            ),
+            (
+                PROFILER_WINDOW,
+                WindowBounds::new(10.0, 190.0, 360.0, 400.0),
+                PanelFlags::Border as Flags
+                    | PanelFlags::Movable as Flags
+                    | PanelFlags::Title as Flags,
+                14,
+                1,
+            ),
+            (
+                PREVIEW_WINDOW,
+                WindowBounds::new(1180.0, 10.0, 410.0, 700.0),
+                PanelFlags::Border as Flags
+                    | PanelFlags::Movable as Flags
+                    | PanelFlags::Title as Flags,
+                14,
+                1,
+            ),
+        ];
+        for (name, bounds, flags, font_size, z_order) in defaults.iter() {
+            windows.add(UiWindow::new(name, *bounds, *flags, *font_size, *z_order));
+        }
+        // デバッグ用の図形は他の窓より奥に描き、操作の邪魔をしない。
+        // Debug shapes are drawn behind other windows and don't get in the way of input.
+        windows.add(UiWindow::new(
+            DEBUG_DRAW_WINDOW,
+            WindowBounds::new(0.0, 0.0, 1600.0, 900.0),
+            PanelFlags::NoInput as Flags | PanelFlags::NoScrollbar as Flags,
+            14,
+            -1,
+        ));
+        windows.add(UiWindow::new(
+            NAMEPLATE_WINDOW,
+            WindowBounds::new(0.0, 0.0, 1600.0, 900.0),
+            PanelFlags::NoInput as Flags | PanelFlags::NoScrollbar as Flags,
+            14,
+            -1,
+        ));
+        windows.add(UiWindow::new(
+            JOINT_NAME_WINDOW,
+            WindowBounds::new(0.0, 0.0, 1600.0, 900.0),
+            PanelFlags::NoInput as Flags | PanelFlags::NoScrollbar as Flags,
+            14,
+            -1,
+        ));
+        windows.add(UiWindow::new(
+            FADE_WINDOW,
+            WindowBounds::new(0.0, 0.0, 1600.0, 900.0),
+            PanelFlags::NoInput as Flags | PanelFlags::NoScrollbar as Flags,
+            14,
+            5,
+        ));
+        // コンソールは`キーで開き、暗転の間も使えるよう一番手前に出す。
+        // The console is opened with the ` key and put in front of everything so it can be used during fades too.
+        windows.add(UiWindow::new(
+            CONSOLE_WINDOW,
+            WindowBounds::new(0.0, 0.0, 1600.0, 300.0),
+            flags,
+            14,
+            6,
+        ));
+        // 調整パネル、アニメーションのパネルとインスペクターは開発用なので、必要な時に開く。
+        // The tweak and animation panels and the inspector are for development, so they're opened when needed.
+        windows.hide(TWEAK_WINDOW);
+        windows.hide(ANIMATION_WINDOW);
+        windows.hide(INSPECTOR_WINDOW);
+        // 設定画面もキーで開く。
+        // The settings page is opened with a key as well.
+        windows.hide(SETTINGS_WINDOW);
+        windows.hide(PROFILER_WINDOW);
+        // セーフモードで起動した時だけ説明を表示する。
+        // The safe mode explanation is only shown when starting in safe mode.
+        windows.hide(SAFE_MODE_WINDOW);
+        windows.hide(CONSOLE_WINDOW);
+        windows.hide(NETWORK_WINDOW);
+        windows.hide(SCOREBOARD_WINDOW);
+        windows
+    }
+
+    /// バックエンドの描画器からUIのシステムを作る。<br />
+    /// Create the UI system from a backend drawer.
+    fn from_drawer(font_bytes: Vec<Vec<u8>>, mut drawer: GraphicsType::UiDrawer) -> Self {
+        let ctx = drawer.create_context(DEFAULT_FONT_SIZE);
+
+        let mut convert_config = ConvertConfig::default();
+        convert_config.set_null(drawer.get_draw_null_texture());
+        convert_config.set_circle_segment_count(22);
+        convert_config.set_curve_segment_count(22);
+        convert_config.set_arc_segment_count(22);
+        convert_config.set_global_alpha(1.0);
+        convert_config.set_shape_aa(AntiAliasing::On);
+        convert_config.set_line_aa(AntiAliasing::On);
+
+        let emoji_atlas = if std::path::Path::new(EMOJI_ATLAS_PATH).exists() {
+            drawer.add_texture_from_file(EMOJI_ATLAS_PATH).id()
+        } else {
+            log::warn!(
+                "Emoji atlas {} was not found. Emojis are shown as text.",
+                EMOJI_ATLAS_PATH
+            );
+            None
+        };
+
+        UISystem {
+            font_bytes,
+            phantom_1: PhantomData,
+            phantom_2: PhantomData,
+            phantom_3: PhantomData,
+            phantom_4: PhantomData,
+            context: ctx,
+            convert_config,
+            drawer: ManuallyDrop::new(drawer),
+            is_initialized: true,
+            ui_state: UIState::new(),
+            windows: Self::create_windows(),
+            emoji_atlas,
+            lan_browser: None,
+            is_lan_unavailable: false,
+            dismissed_asset_warnings: 0,
+            focus_console_input: false,
+        }
+    }
+
+    /// UIを描画する。<br />
+    /// Draw the UI.
+    pub fn render(
+        &mut self,
+        target: <GraphicsType::UiDrawer as UiDrawerBackend>::RenderTarget,
+        scale: nuklear::Vec2,
+    ) -> <GraphicsType::UiDrawer as UiDrawerBackend>::Signal {
+        if !self.is_initialized {
+            return Default::default();
+        }
+        let context = &mut self.context;
+        // 窓の並びが変わったら、奥から順にフォーカスして手前に移す。
+        // When the window order changed, focus the windows from back to front to move them forward.
+        if let Some(order) = self.windows.take_focus_order() {
+            for name in order.iter() {
+                context.window_set_focus(nuklear::String::from(name.as_str()));
+            }
+        }
+        let convert_config = &mut self.convert_config;
+        self.drawer.draw(target, scale, context, convert_config)
+    }
+
     fn set_ui_header(
-        drawer: &mut Drawer,
+        drawer: &mut GraphicsType::UiDrawer,
         ctx: &mut Context,
         title: &str,
         text_alignment: TextAlignment,
@@ -610,7 +2970,12 @@ where
         ctx.text(title, text_alignment as Flags);
     }
 
-    fn set_ui_widget(drawer: &mut Drawer, ctx: &mut Context, height: f32, centered: bool) {
+    fn set_ui_widget(
+        drawer: &mut GraphicsType::UiDrawer,
+        ctx: &mut Context,
+        height: f32,
+        centered: bool,
+    ) {
         drawer.set_font_size(ctx, 20);
         ctx.layout_row(
             LayoutFormat::Dynamic,
@@ -622,11 +2987,10 @@ where
 }
 
 impl UISystem<Graphics, Buffer, CommandBuffer, Image> {
-    pub fn new(graphics: &Graphics) -> Self {
-        let font_bytes = std::fs::read("resource/Comfortaa-Regular.ttf")
-            .expect("Failed to read bytes from the font file.");
+    pub fn new(graphics: &Graphics, settings: &Settings) -> anyhow::Result<Self> {
+        let font_bytes = read_font_files(&settings.ui_font, &settings.ui_fallback_fonts)?;
 
-        let mut drawer = unsafe {
+        let drawer = unsafe {
             Drawer::new(
                 graphics.logical_device.clone(),
                 graphics.instance.clone(),
@@ -644,54 +3008,39 @@ impl UISystem<Graphics, Buffer, CommandBuffer, Image> {
                 MAX_INDEX_MEMORY as u64,
                 MAX_COMMANDS_MEMORY,
                 font_bytes.as_slice(),
-            )
+                &settings.ui_glyph_ranges,
+            )?
         };
 
-        let ctx = drawer.create_context(16);
-
-        let mut convert_config = ConvertConfig::default();
-        convert_config.set_null(drawer.draw_null_texture.clone());
-        convert_config.set_circle_segment_count(22);
-        convert_config.set_curve_segment_count(22);
-        convert_config.set_arc_segment_count(22);
-        convert_config.set_global_alpha(1.0);
-        convert_config.set_shape_aa(AntiAliasing::On);
-        convert_config.set_line_aa(AntiAliasing::On);
+        Ok(Self::from_drawer(font_bytes, drawer))
+    }
 
-        UISystem {
-            font_bytes,
-            phantom_1: PhantomData,
-            phantom_2: PhantomData,
-            phantom_3: PhantomData,
-            phantom_4: PhantomData,
-            context: ctx,
-            convert_config,
-            drawer: ManuallyDrop::new(drawer),
-            is_initialized: true,
-            ui_state: UIState::new(),
-        }
+    /// スワップチェーンのMSAAのサンプル数に合わせる。作り直している間だけ呼ぶこと。<br />
+    /// Follow the MSAA sample count of the swapchain. Only call this while the swapchain is being recreated.
+    pub unsafe fn set_sample_count(&mut self, sample_count: SampleCountFlags) {
+        self.drawer.set_sample_count(sample_count);
     }
+}
 
-    pub fn render(
-        &mut self,
-        framebuffer: Framebuffer,
-        viewport: Viewport,
-        scale: nuklear::Vec2,
-        wait_semaphore: Semaphore,
-    ) -> Semaphore {
-        if !self.is_initialized {
-            return Semaphore::null();
-        }
-        let context = &mut self.context;
-        let convert_config = &mut self.convert_config;
-        self.drawer.draw(
-            framebuffer,
-            viewport,
-            scale,
-            context,
-            convert_config,
-            wait_semaphore,
-        )
+#[cfg(target_os = "windows")]
+impl UISystem<DX12::Graphics, DX12::Resource, ComPtr<ID3D12GraphicsCommandList>, DX12::Resource> {
+    pub fn new(graphics: &DX12::Graphics, settings: &Settings) -> Self {
+        let font_bytes = read_font_files(&settings.ui_font, &settings.ui_fallback_fonts)
+            .expect("Failed to read bytes from the font file.");
+
+        let drawer = unsafe {
+            DX12Drawer::new(
+                graphics.device.clone(),
+                graphics.command_queue.command_queue.clone(),
+                DXGI_FORMAT_B8G8R8A8_UNORM,
+                MAX_VERTEX_MEMORY as u32,
+                MAX_INDEX_MEMORY as u32,
+                MAX_COMMANDS_MEMORY,
+                font_bytes.as_slice(),
+                &settings.ui_glyph_ranges,
+            )
+        };
+        Self::from_drawer(font_bytes, drawer)
     }
 }
 
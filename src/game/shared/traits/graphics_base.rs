@@ -1,4 +1,4 @@
-use crate::game::traits::Disposable;
+use crate::game::traits::{Disposable, UiDrawerBackend};
 
 pub trait GraphicsBase<
     BufferType: 'static + Disposable + Clone,
@@ -6,6 +6,18 @@ pub trait GraphicsBase<
     TextureType: 'static + Disposable + Clone,
 >
 {
+    /// このグラフィックスAPIでUIを描画するバックエンド。<br />
+    /// Backend drawing the UI with this graphics API.
+    type UiDrawer: UiDrawerBackend;
+
+    /// このグラフィックスAPIでモデルが描画コマンドを記録するのに必要なもの。<br />
+    /// What models need to record their draw commands with this graphics API.
+    type RenderContext;
+
+    /// このグラフィックスAPIで試合のシーンを描画できるか。できなければ、ロビーから部屋に入れない。<br />
+    /// Whether match scenes can be rendered with this graphics API. If not, rooms can't be entered from the lobby.
+    const CAN_RENDER_SCENES: bool = true;
+
     /// グラフィックシステムは初期化完了するのかどうか。<br />
     /// Is graphic system already initialized.
     fn is_initialized(&self) -> bool;
@@ -1,10 +1,14 @@
 pub mod disposable;
 pub mod graphics_base;
 pub mod mappable;
+pub mod presence_provider;
 pub mod renderable;
 pub mod scene;
+pub mod ui_drawer_backend;
 pub use disposable::Disposable;
 pub use graphics_base::GraphicsBase;
 pub use mappable::Mappable;
+pub use presence_provider::PresenceProvider;
 pub use renderable::Renderable;
 pub use scene::Scene;
+pub use ui_drawer_backend::UiDrawerBackend;
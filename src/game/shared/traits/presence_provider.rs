@@ -0,0 +1,13 @@
+use crate::game::shared::structs::PresenceActivity;
+
+/// フレンドに活動を知らせるサービス（Discord、Steamなど）。<br />
+/// Service telling friends about the current activity, such as Discord and Steam.
+pub trait PresenceProvider {
+    fn get_name(&self) -> &str;
+
+    fn set_activity(&mut self, activity: &PresenceActivity) -> anyhow::Result<()>;
+
+    /// サービスのコールバックを処理し、プレイヤーが受け入れた参加リンクを返す。毎フレーム呼び出す。<br />
+    /// Process the callbacks of the service and return the join links the player accepted. Called every frame.
+    fn poll_join_links(&mut self) -> Vec<String>;
+}
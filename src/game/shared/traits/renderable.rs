@@ -1,13 +1,13 @@
-use crate::game::graphics::vk::{Pipeline, ThreadPool};
-use crate::game::shared::structs::{ModelMetaData, PositionInfo, PushConstant};
+use crate::game::shared::components::Bounds;
+use crate::game::shared::structs::{
+    AnimationPose, FrameInfo, ModelMetaData, PositionInfo, SkeletonPose, SpringBoneConfig,
+};
 use crate::game::shared::traits::Disposable;
 use crate::game::traits::GraphicsBase;
-use ash::vk::{CommandBufferInheritanceInfo, DescriptorSet};
-use crossbeam::sync::ShardedLock;
+use crate::game::ReleasedCommandBuffers;
 use glam::Mat4;
 use slotmap::{DefaultKey, Key};
-use std::mem::ManuallyDrop;
-use std::sync::atomic::{AtomicPtr, AtomicUsize};
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 
 /// 描画できるオブジェクト<br />
@@ -39,6 +39,24 @@ where
     /// Obtain command buffers for rendering this model.
     fn get_command_buffers(&self, frame_index: usize) -> Vec<CommandType>;
 
+    /// このモデルのコマンドバッファを既存の配列の後ろに加える。毎フレーム新しい配列を確保しないために使う。<br />
+    /// Append the command buffers of this model to an existing list, so that no new list is allocated every frame.
+    fn append_command_buffers(&self, frame_index: usize, command_buffers: &mut Vec<CommandType>) {
+        command_buffers.extend(self.get_command_buffers(frame_index));
+    }
+
+    /// 解放するために、このモデルのコマンドバッファを取り出す。GPUが使い終わってから呼ぶ。<br />
+    /// Take out the command buffers of this model to free them. Call this once the GPU is done with them.
+    fn take_command_buffers(&mut self) -> ReleasedCommandBuffers<CommandType> {
+        vec![]
+    }
+
+    /// 記録したコマンドバッファを古くなったと見なし、次の描画で記録し直させる。<br />
+    /// 描画のキーに含まれない変更をした時に呼ぶ。<br />
+    /// Mark the recorded command buffers as stale so that the next render records them again.<br />
+    /// Call this after changes that the recording key doesn't cover.
+    fn invalidate_command_buffers(&mut self) {}
+
     /// このモデルが配属されたエンティティを取得する。<br />
     /// Get the entity this model belongs to.
     fn get_entity(&self) -> DefaultKey {
@@ -53,6 +71,20 @@ where
     /// Get position info of the model.
     fn get_position_info(&self) -> PositionInfo;
 
+    /// 最後の更新で記録した、メッシュごとの骨格の姿勢。記録していなければ空。<br />
+    /// Skeleton poses per mesh recorded by the last update. Empty if they aren't being recorded.
+    fn get_skeleton_poses(&self) -> Vec<SkeletonPose> {
+        vec![]
+    }
+
+    /// 描画に使うテクスチャの番号と、それを貼る部分のワールド空間の境界ボックス。<br />
+    /// テクスチャのストリーミングが読み込むミップを選ぶのに使う。境界がなければ一番高い解像度を要求する。<br />
+    /// Indices of the textures used for drawing, and the bounding box in world space of the part they're applied to.<br />
+    /// Used by texture streaming to choose the mips to load. The highest resolution is requested without bounds.
+    fn get_texture_usages(&self) -> Vec<(usize, Option<Bounds>)> {
+        vec![]
+    }
+
     /// 主なSSBOの中にこのモデルのインデックスを取得する。<br />
     /// Get the index of this model inside the primary SSBO.
     fn get_ssbo_index(&self) -> usize;
@@ -72,20 +104,23 @@ where
         world * translation * rotate * scale
     }
 
-    /// モデルを描画する。<br />
-    /// Render this model.
-    fn render(
-        &self,
-        inheritance_info: Arc<AtomicPtr<CommandBufferInheritanceInfo>>,
-        push_constant: PushConstant,
-        viewport: ash::vk::Viewport,
-        scissor: ash::vk::Rect2D,
-        device: Arc<ash::Device>,
-        pipeline: Arc<ShardedLock<ManuallyDrop<Pipeline>>>,
-        descriptor_set: DescriptorSet,
-        thread_pool: Arc<ThreadPool>,
-        frame_index: usize,
-    );
+    /// モデルを描画する。描く部分のテクスチャによって、バインドする描述子セットを選ぶ。<br />
+    /// グラフィックスAPI固有のものは`context`にまとめ、トレイトがAPIの型に依存しないようにする。<br />
+    /// Render this model, binding the descriptor set chosen by the texture of each part.<br />
+    /// Everything specific to the graphics API is bundled in `context`, so the trait doesn't depend on the API's types.
+    fn render(&self, context: &GraphicsType::RenderContext, frame_info: FrameInfo);
+
+    /// アニメーションの状態機械が決めたポーズを設定する。骨のないモデルは無視する。<br />
+    /// Set the pose decided by an animation state machine. Models without bones ignore it.
+    fn set_animation_pose(&mut self, _pose: AnimationPose) {}
+
+    /// 揺れる骨の設定を使う。同じ設定が既に使われていれば何もしない。骨のないモデルは無視する。<br />
+    /// Use spring-bone settings. Nothing happens if the same settings are already in use. Models without bones ignore it.
+    fn set_spring_bones(&mut self, _config: Arc<SpringBoneConfig>) {}
+
+    /// 更新するたびに骨格の姿勢を記録するかどうかを設定する。骨のないモデルは無視する。<br />
+    /// Set whether the skeleton pose is recorded on every update. Models without bones ignore it.
+    fn set_skeleton_recording(&mut self, _is_recording: bool) {}
 
     /// モデルのメタデータを設定する。<br />
     /// Set this model's metadata.
@@ -95,6 +130,10 @@ where
     /// Set position info of this model.
     fn set_position_info(&mut self, position_info: PositionInfo);
 
+    /// このモデルが配属されたエンティティを設定する。<br />
+    /// Set the entity this model belongs to.
+    fn set_entity(&mut self, _entity: DefaultKey) {}
+
     /// 主なSSBOの中にこのモデルのインデックスを設定する。<br />
     /// Set the index of this model inside the primary SSBO.
     fn set_ssbo_index(&mut self, ssbo_index: usize);
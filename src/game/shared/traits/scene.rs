@@ -1,11 +1,15 @@
 use crate::game::shared::enums::SceneType;
-use crate::game::shared::structs::Primitive;
+use crate::game::shared::structs::games::replication::ReplicationEvent;
+use crate::game::shared::structs::{
+    LoadingProgress, Primitive, Ray, RenderFeatures, SaveGame, WorldBounds,
+};
+use crate::game::ui::{Console, ConsoleCommand, ConsoleReply};
 use async_trait::async_trait;
 use glam::{Vec3A, Vec4};
 use slotmap::DefaultKey;
 use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
-use winit::event::{ElementState, VirtualKeyCode};
+use winit::event::{ElementState, MouseButton, VirtualKeyCode};
 
 #[async_trait]
 pub trait Scene: Sync {
@@ -25,10 +29,37 @@ pub trait Scene: Sync {
         entity: DefaultKey,
     ) -> anyhow::Result<()>;
 
+    /// 他のプレイヤーが持つエンティティの出現・更新・消滅を、代理のエンティティに反映する。<br />
+    /// 新しくモデルを追加して読み込みが必要になったら`true`を返す。<br />
+    /// Apply the spawns, updates and despawns of entities owned by other players to proxy entities.<br />
+    /// Returns `true` if models were added and need loading.
+    fn apply_replication(&mut self, _events: Vec<ReplicationEvent>) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    /// 読み込み中のタスクを全て取り消し、もう一度ロードできる状態に戻す。読み込み中でなければ何もしない。<br />
+    /// Cancel every loading task and return to a state where the scene can be loaded again. Does nothing when not loading.
+    fn cancel_tasks(&mut self) -> anyhow::Result<()>;
+
     /// シーンの中に存在しているモデルのSSBOを作成する。<br />
     /// Create SSBOs of all models existing in this scene.
     fn create_ssbo(&self) -> anyhow::Result<()>;
 
+    /// コンソールのコマンドを実行する。このシーンが扱わないコマンドなら`None`を返す。<br />
+    /// Execute a console command. Returns `None` if this scene doesn't handle the command.
+    fn execute_command(
+        &mut self,
+        _command: &ConsoleCommand,
+    ) -> Option<anyhow::Result<ConsoleReply>> {
+        None
+    }
+
+    /// シミュレーションを固定の時間だけ一回進める。一フレームに何回呼ばれるかは決まっていない。<br />
+    /// Advance the simulation once by a fixed amount of time. It may be called any number of times per frame.
+    async fn fixed_update(&self, _delta_time: f64) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     /// 地形を生成する。<br />
     /// Generate a terrain.
     fn generate_terrain(
@@ -49,10 +80,18 @@ pub trait Scene: Sync {
     /// Get command buffers of models existing in this scene.
     fn get_command_buffers(&self);
 
+    /// このシーンの中で描画しているモデルを持つエンティティを取得する。<br />
+    /// Get the entities owning the models rendered in this scene.
+    fn get_entities(&self) -> Vec<DefaultKey>;
+
     /// このシーンの中に存在しているモデルの個数を取得する。<br />
     /// Get count of models existing in this scene.
     fn get_model_count(&self) -> Arc<AtomicUsize>;
 
+    /// このシーンが使う描画の機能を取得する。<br />
+    /// Get the rendering features this scene uses.
+    fn get_render_features(&self) -> RenderFeatures;
+
     /// シーンの名前を取得する。<br />
     /// Get this scene's name.
     fn get_scene_name(&self) -> &str;
@@ -69,6 +108,10 @@ pub trait Scene: Sync {
     /// Is this scene loaded?
     fn is_loaded(&self) -> bool;
 
+    /// マウスのボタンが押されたらのコールバック。座標はピクセル単位。<br />
+    /// Callback when a mouse button is pressed. Coordinates are in pixels.
+    fn input_button(&self, _button: MouseButton, _x: f64, _y: f64, _element_state: ElementState) {}
+
     /// キーが押されたらのコールバック。<br />
     /// Callback when a key is pressed.
     async fn input_key(&self, _key: VirtualKeyCode, _element_state: ElementState) {}
@@ -77,16 +120,70 @@ pub trait Scene: Sync {
     /// Load contents in this scene.
     async fn load_content(&mut self) -> anyhow::Result<()>;
 
+    /// セーブデータからローカルな状態を戻す。保存できないシーンではエラーになる。<br />
+    /// Restore the local state from save data. Scenes that can't be saved return an error.
+    async fn load_state(&self, _save: &SaveGame) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!(
+            "{} doesn't support loading.",
+            self.get_scene_name()
+        ))
+    }
+
+    /// 画面上の点の下にあるエンティティを選ぶ。<br />
+    /// Pick the entity under a point on the screen.
+    fn pick(&self, _x: f64, _y: f64) -> Option<DefaultKey> {
+        None
+    }
+
+    /// 終わった読み込みのタスクを待たずに確かめる。<br />
+    /// Check the finished loading tasks without waiting.
+    fn poll_tasks(&mut self) -> anyhow::Result<LoadingProgress>;
+
+    /// 光線と交差する最も近いエンティティと、その距離を空間索引で求める。<br />
+    /// Find the nearest entity intersecting the ray and its distance using the spatial index.
+    fn raycast(&self, _ray: &Ray) -> Option<(DefaultKey, f32)> {
+        None
+    }
+
+    /// このシーンが扱うコマンドをコンソールに登録する。<br />
+    /// Register the commands this scene handles to the console.
+    fn register_commands(&self, _console: &mut Console) {}
+
+    /// シーンからエンティティとそのモデルを取り除く。<br />
+    /// Remove an entity and its models from this scene.
+    fn remove_entity(&mut self, entity: DefaultKey) -> anyhow::Result<()>;
+
+    /// SSBOの番号でモデルを一つだけシーンから取り除く。エンティティはそのまま残る。<br />
+    /// Remove a single model from this scene by its SSBO index. Its entity is kept.
+    fn remove_renderable(&mut self, ssbo_index: usize) -> anyhow::Result<()>;
+
     /// シーンを描画する。<br />
     /// Render the scene.
     fn render(&self, delta_time: f64) -> anyhow::Result<()>;
 
+    /// エンティティの位置、カメラとプレイヤーの進み具合をセーブデータにする。<br />
+    /// Capture the entity transforms, the camera and the player's progress as save data.
+    async fn save_state(&self) -> anyhow::Result<SaveGame> {
+        Err(anyhow::anyhow!(
+            "{} doesn't support saving.",
+            self.get_scene_name()
+        ))
+    }
+
     /// シーンの名前を設定する。<br />
     /// Set this scene's name.
     fn set_scene_name(&mut self, scene_name: &str);
 
-    /// シーンを更新する。<br />
-    /// Update the scene.
+    /// シーンの遊べる範囲を設定する。範囲を持たないシーンでは何もしない。<br />
+    /// Set the playable area of this scene. Does nothing for scenes without bounds.
+    fn set_world_bounds(&mut self, _world_bounds: Option<WorldBounds>) {}
+
+    /// シーンのエンティティとモデルを全て片付け、もう一度ロードできる状態に戻す。<br />
+    /// Tear down all entities and models of this scene so that it can be loaded again.
+    fn unload(&mut self) -> anyhow::Result<()>;
+
+    /// 毎フレーム一回、描画の前にシーンを更新する。<br />
+    /// Update the scene once every frame, before rendering.
     async fn update(&self, delta_time: f64) -> anyhow::Result<()>;
 
     /// 全てのタスクを待つ。<br />
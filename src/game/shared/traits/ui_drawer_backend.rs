@@ -0,0 +1,61 @@
+use nuklear::{Context, ConvertConfig, DrawNullTexture, Handle, UserFont, Vec2};
+
+/// NuklearのUIを描画するバックエンド。グラフィックスAPIごとに実装する。<br />
+/// Backend rendering the Nuklear UI, implemented once per graphics API.
+pub trait UiDrawerBackend {
+    /// 一フレームの描画に必要な、バックエンド固有の描画先。<br />
+    /// Backend-specific render target needed to draw a frame.
+    type RenderTarget;
+
+    /// 描画の完了を知らせるもの。描画しなかった場合は`Default`の値を返す。<br />
+    /// What signals that drawing has finished. The `Default` value is returned when nothing was drawn.
+    type Signal: Default;
+
+    /// 画像ファイルをテクスチャとして追加し、`nuklear::Image`で使うハンドルを返す。<br />
+    /// Add an image file as a texture and return the handle used with `nuklear::Image`.
+    fn add_texture_from_file(&mut self, file_name: &str) -> Handle;
+
+    fn create_context(&mut self, font_size: u8) -> Context;
+
+    /// 描画コマンドを記録して送信する。<br />
+    /// Record and submit the draw commands.
+    fn draw(
+        &mut self,
+        target: Self::RenderTarget,
+        scale: Vec2,
+        context: &mut Context,
+        convert_config: &mut ConvertConfig,
+    ) -> Self::Signal;
+
+    fn get_draw_null_texture(&self) -> DrawNullTexture;
+
+    /// 求めた大きさに最も近い、ベイク済みのフォントを返す。<br />
+    /// Return the baked font closest to the requested size.
+    fn get_font(&self, font_size: u8) -> &UserFont;
+
+    /// テキストの文字をアトラスに加えるように頼む。アトラスは次の`update_font_atlas`でベイクし直す。<br />
+    /// Request the characters of the text to be added to the atlas, which is baked again on the next `update_font_atlas`.
+    fn request_glyphs(&mut self, text: &str);
+
+    /// ベイクされていない大きさは頼んでおき、それまでは最も近い大きさを使う。<br />
+    /// Sizes that aren't baked yet are requested, and the closest size is used until then.
+    fn set_font_size(&mut self, context: &mut Context, font_size: u8);
+
+    /// UIの頂点とインデックスを変換し、GPUのバッファーにアップロードする。<br />
+    /// Convert the UI into vertices and indices and upload them to GPU buffers.
+    fn update(
+        &mut self,
+        width: u32,
+        height: u32,
+        context: &mut Context,
+        convert_config: &mut ConvertConfig,
+    );
+
+    /// 頼まれた文字か大きさがあればアトラスをベイクし直し、コンテキストのフォントを付け直す。<br />
+    /// 古いフォントを参照する描画コマンドがない、フレームの始めに呼ぶこと。ベイクし直したら`true`を返す。<br />
+    /// Bake the atlas again if any characters or sizes were requested, and reattach the context's font.<br />
+    /// Must be called at the start of a frame, when no draw command refers to the old fonts. Returns `true` when the atlas was baked again.
+    fn update_font_atlas(&mut self, context: &mut Context, font_size: u8) -> bool;
+
+    fn wait_idle(&self);
+}
@@ -19,5 +19,9 @@ pub type ResourceManagerWeak<GraphicsType, BufferType, CommandType, TextureType>
 pub type CommandData<CommandType> =
     std::collections::HashMap<usize, (Option<Arc<Mutex<CommandPool>>>, CommandType)>;
 
+/// 解放するコマンドバッファと、それを確保したコマンドプール。<br />
+/// Command buffers to free, along with the command pools they were allocated from.
+pub type ReleasedCommandBuffers<CommandType> = Vec<(Arc<Mutex<CommandPool>>, CommandType)>;
+
 pub type UISystemHandle<GraphicsType, BufferType, CommandType, TextureType> =
     Option<Rc<RefCell<ManuallyDrop<UISystem<GraphicsType, BufferType, CommandType, TextureType>>>>>;
@@ -0,0 +1,47 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static ALLOCATION_COUNT: AtomicUsize = AtomicUsize::new(0);
+static LIVE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// ヒープの確保を数えながらシステムのアロケーターに任せる。<br />
+/// 実行ファイルで`#[global_allocator]`にした時だけ数えられる。<br />
+/// Forwards to the system allocator while counting heap allocations.<br />
+/// Allocations are only counted once an executable installs it as the `#[global_allocator]`.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+        LIVE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+        LIVE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// 起動してからヒープを確保した回数。伸ばし直しも一回と数える。<br />
+/// Number of heap allocations since startup. Reallocations count as one each.
+pub fn get_allocation_count() -> usize {
+    ALLOCATION_COUNT.load(Ordering::Relaxed)
+}
+
+/// まだ解放されていないヒープの確保の数。伸ばし直しは数を変えない。<br />
+/// Number of heap allocations not freed yet. Reallocations leave it unchanged.
+pub fn get_live_allocation_count() -> usize {
+    LIVE_ALLOCATIONS.load(Ordering::Relaxed)
+}
@@ -0,0 +1,160 @@
+//! 座標系と単位の規約。<br />
+//! - 1ワールド単位は1メートル。<br />
+//! - 右手座標系、+Yが上、ヨー0の時の正面は+Z。<br />
+//! - Vulkanのクリップ空間はYが反転するため、カメラの上方向ベクトルは(0, -1, 0)を使っています。<br />
+//! - `PositionInfo::rotation`はラジアンのオイラー角で、(x, y, z) = (ピッチ, ヨー, ロール)。回転順はヨー → ピッチ → ロール。<br />
+//! - ネットワークで回転を送る時はオイラー角ではなくクォータニオン`[x, y, z, w]`を使う。<br />
+//! Coordinate and unit conventions.<br />
+//! - One world unit is one meter.<br />
+//! - Right-handed coordinates, +Y is up, and the forward direction at zero yaw is +Z.<br />
+//! - Since Vulkan's clip space has Y flipped, the camera uses (0, -1, 0) as its up vector.<br />
+//! - `PositionInfo::rotation` stores Euler angles in radians as (x, y, z) = (pitch, yaw, roll), applied in yaw → pitch → roll order.<br />
+//! - Rotations sent over the network use quaternions `[x, y, z, w]` instead of Euler angles.
+
+use crate::game::shared::structs::games::WorldMatrixUdp;
+use crate::game::shared::structs::PositionInfo;
+use crate::protos::grpc_service::game_state::WorldMatrix;
+use glam::{Quat, Vec3A};
+
+/// ワールドの上方向。<br />
+/// The up direction of the world.
+pub const WORLD_UP: [f32; 3] = [0.0, 1.0, 0.0];
+
+/// ヨー0の時の正面方向。<br />
+/// The forward direction at zero yaw.
+pub const WORLD_FORWARD: [f32; 3] = [0.0, 0.0, 1.0];
+
+/// 配列から`Vec3A`に変換する。要素が足りない場合は既定値で埋める。<br />
+/// Convert an array into a `Vec3A`. Missing elements are filled with the default value.
+pub fn vec3_from_slice(values: &[f32], default: f32) -> Vec3A {
+    let get = |index: usize| {
+        values
+            .get(index)
+            .copied()
+            .filter(|v| v.is_finite())
+            .unwrap_or(default)
+    };
+    Vec3A::new(get(0), get(1), get(2))
+}
+
+/// `Vec3A`からネットワーク用の配列に変換する。<br />
+/// Convert a `Vec3A` into an array for network transport.
+pub fn vec3_to_vec(value: Vec3A) -> Vec<f32> {
+    vec![value.x, value.y, value.z]
+}
+
+/// オイラー角（ピッチ, ヨー, ロール）からクォータニオンに変換する。<br />
+/// Convert Euler angles (pitch, yaw, roll) into a quaternion.
+pub fn quat_from_euler(rotation: Vec3A) -> Quat {
+    Quat::from_rotation_ypr(rotation.y, rotation.x, rotation.z)
+}
+
+/// クォータニオンからオイラー角（ピッチ, ヨー, ロール）に変換する。<br />
+/// Convert a quaternion into Euler angles (pitch, yaw, roll).
+pub fn euler_from_quat(rotation: Quat) -> Vec3A {
+    let (x, y, z, w) = (rotation.x, rotation.y, rotation.z, rotation.w);
+    let sin_pitch = (2.0 * (w * x - y * z)).max(-1.0).min(1.0);
+    let pitch = sin_pitch.asin();
+    let yaw = (2.0 * (x * z + w * y)).atan2(1.0 - 2.0 * (x * x + y * y));
+    let roll = (2.0 * (x * y + w * z)).atan2(1.0 - 2.0 * (x * x + z * z));
+    Vec3A::new(pitch, yaw, roll)
+}
+
+/// 回転をネットワーク用の配列`[x, y, z, w]`に変換する。<br />
+/// Encode a rotation into a network array `[x, y, z, w]`.
+pub fn encode_rotation(rotation: Quat) -> Vec<f32> {
+    let rotation = rotation.normalize();
+    vec![rotation.x, rotation.y, rotation.z, rotation.w]
+}
+
+/// ネットワークの配列から回転を復元する。<br />
+/// 4要素ならクォータニオン、3要素なら旧形式のオイラー角として扱う。無効な値は単位クォータニオンになる。<br />
+/// Decode a rotation from a network array.<br />
+/// Four elements are treated as a quaternion, three as legacy Euler angles. Invalid values decode to identity.
+pub fn decode_rotation(values: &[f32]) -> Quat {
+    if values.iter().any(|v| !v.is_finite()) {
+        return Quat::identity();
+    }
+    match values.len() {
+        4 => {
+            let rotation = Quat::from_xyzw(values[0], values[1], values[2], values[3]);
+            if rotation.length_squared() < f32::EPSILON {
+                Quat::identity()
+            } else {
+                rotation.normalize()
+            }
+        }
+        3 => quat_from_euler(vec3_from_slice(values, 0.0)),
+        _ => Quat::identity(),
+    }
+}
+
+/// 方向ベクトルからヨーとピッチを計算する。<br />
+/// Compute yaw and pitch from a direction vector.
+pub fn yaw_pitch_from_direction(direction: Vec3A) -> (f32, f32) {
+    let direction = direction.normalize();
+    let yaw = direction.x.atan2(direction.z);
+    let pitch = (-direction.y).max(-1.0).min(1.0).asin();
+    (yaw, pitch)
+}
+
+/// ヨーとピッチから方向ベクトルを計算する。<br />
+/// Compute a direction vector from yaw and pitch.
+pub fn direction_from_yaw_pitch(yaw: f32, pitch: f32) -> Vec3A {
+    Vec3A::new(
+        pitch.cos() * yaw.sin(),
+        -pitch.sin(),
+        pitch.cos() * yaw.cos(),
+    )
+}
+
+/// ある位置から目標を見る回転（オイラー角）を計算する。<br />
+/// Compute the rotation (Euler angles) that looks from a position at a target.
+pub fn look_at_rotation(from: Vec3A, to: Vec3A) -> Vec3A {
+    let direction = to - from;
+    if direction.length_squared() < f32::EPSILON {
+        return Vec3A::zero();
+    }
+    let (yaw, pitch) = yaw_pitch_from_direction(direction);
+    Vec3A::new(pitch, yaw, 0.0)
+}
+
+impl From<&WorldMatrix> for PositionInfo {
+    fn from(world_matrix: &WorldMatrix) -> Self {
+        PositionInfo {
+            position: vec3_from_slice(&world_matrix.position, 0.0),
+            scale: vec3_from_slice(&world_matrix.scale, 1.0),
+            rotation: euler_from_quat(decode_rotation(&world_matrix.rotation)),
+        }
+    }
+}
+
+impl From<PositionInfo> for WorldMatrix {
+    fn from(position_info: PositionInfo) -> Self {
+        WorldMatrix {
+            position: vec3_to_vec(position_info.position),
+            scale: vec3_to_vec(position_info.scale),
+            rotation: encode_rotation(quat_from_euler(position_info.rotation)),
+        }
+    }
+}
+
+impl From<&WorldMatrixUdp> for PositionInfo {
+    fn from(world_matrix: &WorldMatrixUdp) -> Self {
+        PositionInfo {
+            position: vec3_from_slice(&world_matrix.position, 0.0),
+            scale: vec3_from_slice(&world_matrix.scale, 1.0),
+            rotation: euler_from_quat(decode_rotation(&world_matrix.rotation)),
+        }
+    }
+}
+
+impl From<PositionInfo> for WorldMatrixUdp {
+    fn from(position_info: PositionInfo) -> Self {
+        WorldMatrixUdp {
+            position: vec3_to_vec(position_info.position),
+            scale: vec3_to_vec(position_info.scale),
+            rotation: encode_rotation(quat_from_euler(position_info.rotation)),
+        }
+    }
+}
@@ -1,5 +1,10 @@
+pub mod allocation_counter;
 pub mod height_generator;
+pub mod math;
+pub mod model_importer;
 pub mod perlin_noise;
+pub mod spirv_reflection;
+pub use allocation_counter::*;
 pub use height_generator::HeightGenerator;
 pub use perlin_noise::PerlinNoise;
 
@@ -0,0 +1,275 @@
+use crate::game::shared::structs::Vertex;
+use crate::game::util::read_raw_data;
+use anyhow::Context;
+use glam::{Mat4, Quat, Vec2, Vec3, Vec3A};
+use gltf::image::{Data as ImageData, Format};
+use std::path::Path;
+
+/// 読み込めるモデルのファイル形式。<br />
+/// File formats of models that can be imported.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ModelFormat {
+    Gltf,
+    Obj,
+    Fbx,
+}
+
+impl ModelFormat {
+    /// 拡張子からファイル形式を判断する。<br />
+    /// Determine the file format from the extension.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|e| e.to_str())?
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "gltf" | "glb" => Some(ModelFormat::Gltf),
+            "obj" => Some(ModelFormat::Obj),
+            "fbx" => Some(ModelFormat::Fbx),
+            _ => None,
+        }
+    }
+}
+
+/// 形式に依らない一つのプリミティブ。頂点はノードのトランスフォームを適用済み。<br />
+/// A format-independent primitive. Vertices already have the node transform applied.
+#[derive(Clone, Debug, Default)]
+pub struct ImportedPrimitive {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// `ImportedModel::images`の中のテクスチャの番号。<br />
+    /// Index of the texture in `ImportedModel::images`.
+    pub texture_index: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ImportedMesh {
+    pub primitives: Vec<ImportedPrimitive>,
+}
+
+/// エンジンの頂点とテクスチャのパイプラインに合わせて読み込んだモデル。<br />
+/// A model imported into the engine's vertex and texture pipeline.
+#[derive(Clone, Debug, Default)]
+pub struct ImportedModel {
+    pub meshes: Vec<ImportedMesh>,
+    pub images: Vec<ImageData>,
+}
+
+/// 拡張子に応じた形式でモデルを読み込む。<br />
+/// Import a model in the format given by its extension.
+pub fn import_model(file_name: &str) -> anyhow::Result<ImportedModel> {
+    match ModelFormat::from_file_name(file_name) {
+        Some(ModelFormat::Gltf) => import_gltf(file_name),
+        Some(ModelFormat::Obj) => import_obj(file_name),
+        Some(ModelFormat::Fbx) => Err(anyhow::anyhow!(
+            "FBX import is not supported yet. Convert {} to glTF or OBJ.",
+            file_name
+        )),
+        None => Err(anyhow::anyhow!("Unknown model format: {}", file_name)),
+    }
+}
+
+/// glTFのデフォルトのシーンのノードを辿り、メッシュを集める。<br />
+/// Walk the nodes of the default scene of a glTF and collect its meshes.
+pub fn import_gltf(file_name: &str) -> anyhow::Result<ImportedModel> {
+    let (document, buffers, images) = read_raw_data(file_name)?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .with_context(|| "The glTF file has no scene.")?;
+    let mut meshes = vec![];
+    for node in scene.nodes() {
+        process_gltf_node(node, &buffers, Mat4::identity(), &mut meshes)?;
+    }
+    Ok(ImportedModel { meshes, images })
+}
+
+fn process_gltf_node(
+    node: gltf::Node,
+    buffers: &[gltf::buffer::Data],
+    local_transform: Mat4,
+    meshes: &mut Vec<ImportedMesh>,
+) -> anyhow::Result<()> {
+    let (t, r, s) = node.transform().decomposed();
+    let transform =
+        Mat4::from_scale_rotation_translation(Vec3::from(s), Quat::from(r), Vec3::from(t));
+    let transform = local_transform * transform;
+    if let Some(mesh) = node.mesh() {
+        meshes.push(process_gltf_mesh(mesh, buffers, transform)?);
+    }
+    for child in node.children() {
+        process_gltf_node(child, buffers, transform, meshes)?;
+    }
+    Ok(())
+}
+
+fn process_gltf_mesh(
+    mesh: gltf::Mesh,
+    buffers: &[gltf::buffer::Data],
+    local_transform: Mat4,
+) -> anyhow::Result<ImportedMesh> {
+    let mut primitives = Vec::with_capacity(5);
+    for primitive in mesh.primitives() {
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let indices = reader
+            .read_indices()
+            .with_context(|| "The glTF primitive has no indices.")?
+            .into_u32()
+            .collect::<Vec<_>>();
+        let positions = reader
+            .read_positions()
+            .with_context(|| "The glTF primitive has no positions.")?;
+        let normals = reader
+            .read_normals()
+            .with_context(|| "The glTF primitive has no normals.")?;
+        let mut uvs = reader.read_tex_coords(0).map(|uvs| uvs.into_f32());
+        let vertices = positions
+            .zip(normals)
+            .map(|(position, normal)| Vertex {
+                position: Vec3A::from(local_transform.transform_point3(Vec3::from(position))),
+                normal: Vec3A::from(normal),
+                uv: uvs
+                    .as_mut()
+                    .and_then(|uvs| uvs.next())
+                    .map(Vec2::from)
+                    .unwrap_or_else(Vec2::zero),
+            })
+            .collect::<Vec<_>>();
+        let texture_index = primitive
+            .material()
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .map(|x| x.texture().index());
+        primitives.push(ImportedPrimitive {
+            vertices,
+            indices,
+            texture_index,
+        });
+    }
+    Ok(ImportedMesh { primitives })
+}
+
+/// OBJを読み込む。面は三角形に分割し、頂点は位置、法線、UVの組み合わせごとに一つにまとめる。<br />
+/// マテリアルの拡散反射のテクスチャを、プリミティブのテクスチャにする。<br />
+/// Import an OBJ. Faces are triangulated, and each combination of position, normal and UV becomes one vertex.<br />
+/// The diffuse texture of a material becomes the texture of the primitive.
+pub fn import_obj(file_name: &str) -> anyhow::Result<ImportedModel> {
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ..Default::default()
+    };
+    let (models, materials) =
+        tobj::load_obj(file_name, &options).with_context(|| "Failed to import model from OBJ.")?;
+    // マテリアルがなくてもメッシュは表示できる。
+    // Meshes can be shown even without materials.
+    let materials = materials.unwrap_or_else(|e| {
+        log::warn!("Failed to load materials of {}: {}", file_name, e);
+        vec![]
+    });
+
+    let directory = Path::new(file_name)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let mut images = vec![];
+    let mut material_textures = vec![];
+    for material in materials.iter() {
+        if material.diffuse_texture.is_empty() {
+            material_textures.push(None);
+            continue;
+        }
+        let path = directory.join(&material.diffuse_texture);
+        match load_image(&path) {
+            Ok(image) => {
+                material_textures.push(Some(images.len()));
+                images.push(image);
+            }
+            Err(e) => {
+                log::warn!("Failed to load texture {}: {}", path.display(), e);
+                material_textures.push(None);
+            }
+        }
+    }
+
+    let meshes = models
+        .iter()
+        .map(|model| {
+            let texture_index = model
+                .mesh
+                .material_id
+                .and_then(|id| material_textures.get(id).copied().flatten());
+            ImportedMesh {
+                primitives: vec![ImportedPrimitive {
+                    vertices: get_obj_vertices(&model.mesh),
+                    indices: model.mesh.indices.clone(),
+                    texture_index,
+                }],
+            }
+        })
+        .collect();
+    Ok(ImportedModel { meshes, images })
+}
+
+/// OBJのメッシュから頂点を作る。法線がなければ面の法線を求める。<br />
+/// OBJのUVは下が原点なので、上下を反転する。<br />
+/// Build vertices from an OBJ mesh. Face normals are computed if the file has no normals.<br />
+/// OBJ UVs have their origin at the bottom, so they are flipped vertically.
+pub fn get_obj_vertices(mesh: &tobj::Mesh) -> Vec<Vertex> {
+    let vertex_count = mesh.positions.len() / 3;
+    let get_vec3 = |values: &[f32], index: usize| {
+        Vec3A::new(
+            values[index * 3],
+            values[index * 3 + 1],
+            values[index * 3 + 2],
+        )
+    };
+    let mut vertices = (0..vertex_count)
+        .map(|i| Vertex {
+            position: get_vec3(&mesh.positions, i),
+            normal: if mesh.normals.len() >= (i + 1) * 3 {
+                get_vec3(&mesh.normals, i)
+            } else {
+                Vec3A::zero()
+            },
+            uv: if mesh.texcoords.len() >= (i + 1) * 2 {
+                Vec2::new(mesh.texcoords[i * 2], 1.0 - mesh.texcoords[i * 2 + 1])
+            } else {
+                Vec2::zero()
+            },
+        })
+        .collect::<Vec<_>>();
+
+    if mesh.normals.is_empty() {
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [
+                triangle[0] as usize,
+                triangle[1] as usize,
+                triangle[2] as usize,
+            ];
+            let normal = (vertices[b].position - vertices[a].position)
+                .cross(vertices[c].position - vertices[a].position);
+            for index in [a, b, c].iter() {
+                vertices[*index].normal += normal;
+            }
+        }
+        for vertex in vertices.iter_mut() {
+            if vertex.normal.length_squared() > f32::EPSILON {
+                vertex.normal = vertex.normal.normalize();
+            }
+        }
+    }
+    vertices
+}
+
+/// 画像ファイルをテクスチャのパイプラインが扱えるRGBAの画像として読み込む。<br />
+/// Load an image file as an RGBA image the texture pipeline can handle.
+fn load_image(path: &Path) -> anyhow::Result<ImageData> {
+    let image = image::open(path)?.to_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(ImageData {
+        pixels: image.into_raw(),
+        format: Format::R8G8B8A8,
+        width,
+        height,
+    })
+}
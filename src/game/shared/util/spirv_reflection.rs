@@ -0,0 +1,100 @@
+/// SPIR-Vの先頭にある魔法数。<br />
+/// Magic number at the start of SPIR-V.
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// 命令の前にある、魔法数やバージョンなどのワード数。<br />
+/// Number of words before the instructions, such as the magic number and the version.
+const HEADER_LENGTH: usize = 5;
+
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+
+/// 描述子の変数が宣言する配列の長さ。<br />
+/// Length of the array declared by a descriptor variable.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DescriptorArrayLength {
+    /// 長さの決まった配列。MoltenVKは長さのない配列を扱えないので、macOSのシェーダーはこちらになる。<br />
+    /// An array with a fixed length. MoltenVK can't handle unsized arrays, so the macOS shaders use this.
+    Fixed(u32),
+    /// 長さのない配列か、配列ではない変数。<br />
+    /// An unsized array, or a variable that isn't an array.
+    Unsized,
+    /// そのバインディングの変数がない。<br />
+    /// No variable at that binding.
+    Missing,
+}
+
+/// SPIR-Vのワードから、セットとバインディングの合う描述子の配列の長さを読み取る。<br />
+/// 長さをシェーダーから読むので、シェーダーと別に長さを設定しなくてよい。<br />
+/// Read the length of the descriptor array at the matching set and binding from SPIR-V words.<br />
+/// The length is read from the shader, so it doesn't have to be configured separately.
+pub fn get_descriptor_array_length(
+    words: &[u32],
+    set: u32,
+    binding: u32,
+) -> anyhow::Result<DescriptorArrayLength> {
+    if words.len() < HEADER_LENGTH || words[0] != SPIRV_MAGIC {
+        return Err(anyhow::anyhow!("The shader isn't valid SPIR-V."));
+    }
+    let mut bindings = vec![];
+    let mut sets = vec![];
+    let mut variables = vec![];
+    let mut pointers = vec![];
+    let mut arrays = vec![];
+    let mut constants = vec![];
+    let mut offset = HEADER_LENGTH;
+    while offset < words.len() {
+        let word_count = (words[offset] >> 16) as usize;
+        let opcode = words[offset] & 0xffff;
+        if word_count == 0 || offset + word_count > words.len() {
+            return Err(anyhow::anyhow!(
+                "The SPIR-V instruction at word {} is truncated.",
+                offset
+            ));
+        }
+        let operands = &words[offset + 1..offset + word_count];
+        match (opcode, operands) {
+            (OP_DECORATE, [target, DECORATION_BINDING, value, ..]) => {
+                bindings.push((*target, *value))
+            }
+            (OP_DECORATE, [target, DECORATION_DESCRIPTOR_SET, value, ..]) => {
+                sets.push((*target, *value))
+            }
+            (OP_VARIABLE, [result_type, result, ..]) => variables.push((*result, *result_type)),
+            (OP_TYPE_POINTER, [result, _, pointee]) => pointers.push((*result, *pointee)),
+            (OP_TYPE_ARRAY, [result, _, length]) => arrays.push((*result, *length)),
+            (OP_CONSTANT, [_, result, value, ..]) => constants.push((*result, *value)),
+            _ => (),
+        }
+        offset += word_count;
+    }
+
+    let find = |list: &[(u32, u32)], id: u32| {
+        list.iter()
+            .find(|(key, _)| *key == id)
+            .map(|(_, value)| *value)
+    };
+    // セットの指定がない変数はセット0にある。
+    // Variables without a set decoration live in set 0.
+    let variable = bindings
+        .iter()
+        .find(|(id, value)| *value == binding && find(&sets, *id).unwrap_or(0) == set)
+        .map(|(id, _)| *id);
+    let variable = match variable {
+        Some(variable) => variable,
+        None => return Ok(DescriptorArrayLength::Missing),
+    };
+    let length = find(&variables, variable)
+        .and_then(|pointer| find(&pointers, pointer))
+        .and_then(|pointee| find(&arrays, pointee))
+        .and_then(|length| find(&constants, length));
+    Ok(match length {
+        Some(length) => DescriptorArrayLength::Fixed(length),
+        None => DescriptorArrayLength::Unsized,
+    })
+}
@@ -0,0 +1,499 @@
+use glam::Vec3A;
+use slotmap::{DefaultKey, SecondaryMap, SlotMap};
+use std::collections::{HashMap, HashSet};
+
+use crate::game::shared::components::{
+    Animator, Bounds, Mount, NetworkEntity, NetworkReplicated, ParticleEmitter, PlayerController,
+    RenderableRef, Rider, RigidBody, SpringBoneRig, Surface, Transform,
+};
+use crate::game::shared::structs::games::replication::EntitySnapshot;
+use crate::game::shared::structs::games::snapshot::QuantizedWorldMatrix;
+use crate::game::shared::structs::games::WorldMatrixUdp;
+use crate::game::shared::structs::{is_occluded, Bvh, PositionInfo, Ray, SaveGame, SavedTransform};
+use crate::game::traits::{Disposable, GraphicsBase};
+
+/// ワールドに格納できるコンポーネント。<br />
+/// A component that can be stored in the world.
+pub trait Component<GraphicsType, BufferType, CommandType, TextureType>: Sized
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    fn storage(
+        world: &World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) -> &SecondaryMap<DefaultKey, Self>;
+    fn storage_mut(
+        world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+    ) -> &mut SecondaryMap<DefaultKey, Self>;
+}
+
+macro_rules! impl_component {
+    ($component:ty, $field:ident) => {
+        impl<GraphicsType, BufferType, CommandType, TextureType>
+            Component<GraphicsType, BufferType, CommandType, TextureType> for $component
+        where
+            GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+            BufferType: 'static + Disposable + Clone,
+            CommandType: 'static + Clone,
+            TextureType: 'static + Clone + Disposable,
+        {
+            fn storage(
+                world: &World<GraphicsType, BufferType, CommandType, TextureType>,
+            ) -> &SecondaryMap<DefaultKey, Self> {
+                &world.$field
+            }
+
+            fn storage_mut(
+                world: &mut World<GraphicsType, BufferType, CommandType, TextureType>,
+            ) -> &mut SecondaryMap<DefaultKey, Self> {
+                &mut world.$field
+            }
+        }
+    };
+}
+
+impl_component!(Transform, transforms);
+impl_component!(RenderableRef<GraphicsType, BufferType, CommandType, TextureType>, renderables);
+impl_component!(RigidBody, rigid_bodies);
+impl_component!(PlayerController, player_controllers);
+impl_component!(NetworkReplicated, network_replicated);
+impl_component!(NetworkEntity, network_entities);
+impl_component!(Bounds, bounds);
+impl_component!(Animator, animators);
+impl_component!(SpringBoneRig, spring_bone_rigs);
+impl_component!(Surface, surfaces);
+impl_component!(Mount, mounts);
+impl_component!(Rider, riders);
+impl_component!(ParticleEmitter, particle_emitters);
+
+/// エンティティとそのコンポーネントを管理する。<br />
+/// Manages entities and their components.
+pub struct World<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    entities: SlotMap<DefaultKey, String>,
+    transforms: SecondaryMap<DefaultKey, Transform>,
+    renderables:
+        SecondaryMap<DefaultKey, RenderableRef<GraphicsType, BufferType, CommandType, TextureType>>,
+    rigid_bodies: SecondaryMap<DefaultKey, RigidBody>,
+    player_controllers: SecondaryMap<DefaultKey, PlayerController>,
+    network_replicated: SecondaryMap<DefaultKey, NetworkReplicated>,
+    network_entities: SecondaryMap<DefaultKey, NetworkEntity>,
+    bounds: SecondaryMap<DefaultKey, Bounds>,
+    animators: SecondaryMap<DefaultKey, Animator>,
+    spring_bone_rigs: SecondaryMap<DefaultKey, SpringBoneRig>,
+    surfaces: SecondaryMap<DefaultKey, Surface>,
+    mounts: SecondaryMap<DefaultKey, Mount>,
+    riders: SecondaryMap<DefaultKey, Rider>,
+    particle_emitters: SecondaryMap<DefaultKey, ParticleEmitter>,
+    /// 境界ボックスを持つエンティティのワールド空間での空間索引。<br />
+    /// Spatial index of the entities with bounding boxes, in world space.
+    spatial_index: Bvh,
+    /// 一つ前のシミュレーションのステップのトランスフォーム。<br />
+    /// Transforms as of the previous simulation step.
+    previous_transforms: SecondaryMap<DefaultKey, Transform>,
+    /// 描画する時点の、前のステップから今のステップまでの割合。<br />
+    /// Fraction from the previous step to the current one at which the frame is rendered.
+    interpolation_alpha: f32,
+}
+
+impl<GraphicsType, BufferType, CommandType, TextureType> Default
+    for World<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<GraphicsType, BufferType, CommandType, TextureType>
+    World<GraphicsType, BufferType, CommandType, TextureType>
+where
+    GraphicsType: 'static + GraphicsBase<BufferType, CommandType, TextureType>,
+    BufferType: 'static + Disposable + Clone,
+    CommandType: 'static + Clone,
+    TextureType: 'static + Clone + Disposable,
+{
+    pub fn new() -> Self {
+        World {
+            entities: SlotMap::new(),
+            transforms: SecondaryMap::new(),
+            renderables: SecondaryMap::new(),
+            rigid_bodies: SecondaryMap::new(),
+            player_controllers: SecondaryMap::new(),
+            network_replicated: SecondaryMap::new(),
+            network_entities: SecondaryMap::new(),
+            bounds: SecondaryMap::new(),
+            animators: SecondaryMap::new(),
+            spring_bone_rigs: SecondaryMap::new(),
+            surfaces: SecondaryMap::new(),
+            mounts: SecondaryMap::new(),
+            riders: SecondaryMap::new(),
+            particle_emitters: SecondaryMap::new(),
+            spatial_index: Bvh::default(),
+            previous_transforms: SecondaryMap::new(),
+            interpolation_alpha: 1.0,
+        }
+    }
+
+    /// 新しいエンティティを生成する。<br />
+    /// Spawn a new entity.
+    pub fn spawn(&mut self, entity_name: &str) -> DefaultKey {
+        self.entities.insert(entity_name.to_string())
+    }
+
+    /// エンティティと全てのコンポーネントを削除する。<br />
+    /// Despawn an entity along with all of its components.
+    pub fn despawn(&mut self, entity: DefaultKey) -> bool {
+        if self.entities.remove(entity).is_none() {
+            return false;
+        }
+        self.transforms.remove(entity);
+        self.previous_transforms.remove(entity);
+        self.renderables.remove(entity);
+        self.rigid_bodies.remove(entity);
+        self.player_controllers.remove(entity);
+        self.network_replicated.remove(entity);
+        self.network_entities.remove(entity);
+        self.bounds.remove(entity);
+        self.animators.remove(entity);
+        self.spring_bone_rigs.remove(entity);
+        self.surfaces.remove(entity);
+        self.mounts.remove(entity);
+        self.riders.remove(entity);
+        self.particle_emitters.remove(entity);
+        self.spatial_index.remove(entity);
+        true
+    }
+
+    pub fn contains(&self, entity: DefaultKey) -> bool {
+        self.entities.contains_key(entity)
+    }
+
+    pub fn get_entity_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn get_name(&self, entity: DefaultKey) -> Option<&str> {
+        self.entities.get(entity).map(|s| s.as_str())
+    }
+
+    /// 名前でエンティティを検索する。<br />
+    /// Find an entity by its name.
+    pub fn find_by_name(&self, entity_name: &str) -> Option<DefaultKey> {
+        self.entities
+            .iter()
+            .find(|(_, name)| name.as_str() == entity_name)
+            .map(|(key, _)| key)
+    }
+
+    /// コンポーネントを追加する。既存のコンポーネントがあれば置き換える。<br />
+    /// Insert a component, replacing the existing one if any.
+    pub fn insert<C>(&mut self, entity: DefaultKey, component: C) -> Option<C>
+    where
+        C: Component<GraphicsType, BufferType, CommandType, TextureType>,
+    {
+        if !self.entities.contains_key(entity) {
+            return None;
+        }
+        C::storage_mut(self).insert(entity, component)
+    }
+
+    pub fn remove<C>(&mut self, entity: DefaultKey) -> Option<C>
+    where
+        C: Component<GraphicsType, BufferType, CommandType, TextureType>,
+    {
+        C::storage_mut(self).remove(entity)
+    }
+
+    pub fn get<C>(&self, entity: DefaultKey) -> Option<&C>
+    where
+        C: Component<GraphicsType, BufferType, CommandType, TextureType>,
+    {
+        C::storage(self).get(entity)
+    }
+
+    pub fn get_mut<C>(&mut self, entity: DefaultKey) -> Option<&mut C>
+    where
+        C: Component<GraphicsType, BufferType, CommandType, TextureType>,
+    {
+        C::storage_mut(self).get_mut(entity)
+    }
+
+    /// 指定したコンポーネントを持つ全てのエンティティを巡回する。<br />
+    /// Iterate over all entities that have the given component.
+    pub fn iter<C>(&self) -> impl Iterator<Item = (DefaultKey, &C)>
+    where
+        C: 'static + Component<GraphicsType, BufferType, CommandType, TextureType>,
+    {
+        C::storage(self).iter()
+    }
+
+    pub fn iter_mut<C>(&mut self) -> impl Iterator<Item = (DefaultKey, &mut C)>
+    where
+        C: 'static + Component<GraphicsType, BufferType, CommandType, TextureType>,
+    {
+        C::storage_mut(self).iter_mut()
+    }
+
+    /// 補間したリモートのプレイヤーの状態をトランスフォームに反映する。ローカルプレイヤーは除く。<br />
+    /// Apply interpolated states of remote players to transforms, except for the local player.
+    pub fn apply_network_state(&mut self, states: &HashMap<String, PositionInfo>) {
+        for (entity, replicated) in self.network_replicated.iter() {
+            if replicated.is_local {
+                continue;
+            }
+            if let Some(state) = states.get(&replicated.player_id) {
+                self.transforms.insert(entity, Transform::from(*state));
+            }
+        }
+    }
+
+    /// ローカルプレイヤーが持つエンティティの今の状態を、サーバーに送る形で集める。<br />
+    /// Collect the current state of the entities owned by the local player, in the form sent to the server.
+    pub fn collect_owned_entities(&self) -> Vec<EntitySnapshot> {
+        self.network_entities
+            .iter()
+            .filter(|(_, network_entity)| network_entity.is_local)
+            .filter_map(|(entity, network_entity)| {
+                let transform = self.transforms.get(entity)?;
+                let world_matrix = WorldMatrixUdp::from(PositionInfo::from(*transform));
+                Some(EntitySnapshot {
+                    entity_id: network_entity.entity_id.clone(),
+                    owner_id: network_entity.owner_id.clone(),
+                    model: network_entity.model.clone(),
+                    world_matrix: QuantizedWorldMatrix::from(&world_matrix),
+                    current_hp: network_entity.current_hp,
+                    max_hp: network_entity.max_hp,
+                })
+            })
+            .collect()
+    }
+
+    /// 複製されるエンティティをIDで探す。<br />
+    /// Find a replicated entity by its ID.
+    pub fn find_network_entity(&self, entity_id: &str) -> Option<DefaultKey> {
+        self.network_entities
+            .iter()
+            .find(|(_, network_entity)| network_entity.entity_id == entity_id)
+            .map(|(entity, _)| entity)
+    }
+
+    /// 持ち主から届いた状態を代理のエンティティに反映する。<br />
+    /// Apply the state received from the owner to a proxy entity.
+    pub fn apply_entity_snapshot(&mut self, entity: DefaultKey, snapshot: &EntitySnapshot) {
+        let world_matrix = WorldMatrixUdp::from(snapshot.world_matrix);
+        self.transforms
+            .insert(entity, Transform::from(PositionInfo::from(&world_matrix)));
+        if let Some(network_entity) = self.network_entities.get_mut(entity) {
+            network_entity.current_hp = snapshot.current_hp;
+            network_entity.max_hp = snapshot.max_hp;
+        }
+    }
+
+    /// 光線と境界ボックスが交差するエンティティのうち最も近いものと、その距離を求める。<br />
+    /// 空間索引で候補を絞り、候補は今の境界ボックスで判定する。<br />
+    /// Find the nearest entity whose bounding box intersects the ray, along with its distance.<br />
+    /// Candidates are narrowed down with the spatial index and tested against their current bounding boxes.
+    pub fn raycast(&self, ray: &Ray) -> Option<(DefaultKey, f32)> {
+        self.spatial_index.raycast(ray, |entity| {
+            self.get_world_bounds(entity)
+                .and_then(|bounds| ray.intersect_bounds(&bounds))
+        })
+    }
+
+    /// 二点の間を、境界ボックスを持つエンティティが遮っているかどうか。<br />
+    /// 最も近いエンティティしか調べないので、それが`ignored`に入っていれば遮っていないものとする。<br />
+    /// Whether an entity with a bounding box blocks the line between two points.<br />
+    /// Only the nearest entity is tested, so the line counts as clear if it's in `ignored`.
+    pub fn is_line_blocked(&self, from: Vec3A, to: Vec3A, ignored: &[DefaultKey]) -> bool {
+        let distance = (to - from).length();
+        if distance <= 0.0 {
+            return false;
+        }
+        match self.raycast(&Ray::new(from, to - from)) {
+            Some((entity, hit)) => !ignored.contains(&entity) && is_occluded(Some(hit), distance),
+            None => false,
+        }
+    }
+
+    /// `visible`に入っていないエンティティの描画を省けるかどうか。<br />
+    /// 境界ボックスを持たないエンティティは空間索引にないので、常に描画される。<br />
+    /// Whether drawing an entity missing from `visible` can be skipped.<br />
+    /// Entities without bounding boxes aren't in the spatial index, so they're always drawn.
+    pub fn is_culled(&self, entity: DefaultKey, visible: &HashSet<DefaultKey>) -> bool {
+        !visible.contains(&entity) && self.spatial_index.contains(entity)
+    }
+
+    /// エンティティのワールド空間での境界ボックスを求める。<br />
+    /// Get the bounding box of an entity in world space.
+    pub fn get_world_bounds(&self, entity: DefaultKey) -> Option<Bounds> {
+        let bounds = self.bounds.get(entity)?;
+        Some(match self.transforms.get(entity) {
+            Some(transform) => bounds.transform(transform.get_world_matrix()),
+            None => *bounds,
+        })
+    }
+
+    pub fn get_spatial_index(&self) -> &Bvh {
+        &self.spatial_index
+    }
+
+    /// 動いたエンティティの境界ボックスを空間索引に反映する。トランスフォームを変えた後に毎フレーム呼ぶ。<br />
+    /// Apply the bounding boxes of moved entities to the spatial index. Called every frame after transforms change.
+    pub fn update_spatial_index(&mut self) {
+        let removed = self
+            .spatial_index
+            .entities()
+            .filter(|entity| !self.bounds.contains_key(*entity))
+            .collect::<Vec<_>>();
+        for entity in removed.into_iter() {
+            self.spatial_index.remove(entity);
+        }
+        let entities = self.bounds.keys().collect::<Vec<_>>();
+        for entity in entities.into_iter() {
+            if let Some(bounds) = self.get_world_bounds(entity) {
+                self.spatial_index.update(entity, bounds);
+            }
+        }
+    }
+
+    /// トランスフォームを持つ全てのエンティティを名前と一緒にセーブデータに書き込む。<br />
+    /// Write every entity with a transform into the save data along with its name.
+    pub fn save_transforms(&self, save: &mut SaveGame) {
+        save.transforms = self
+            .transforms
+            .iter()
+            .filter_map(|(entity, transform)| {
+                self.get_name(entity).map(|name| SavedTransform {
+                    name: name.to_string(),
+                    position: transform.position,
+                    rotation: transform.rotation,
+                    scale: transform.scale,
+                })
+            })
+            .collect();
+    }
+
+    /// セーブデータのトランスフォームを名前の合うエンティティに戻し、戻したエンティティを返す。<br />
+    /// Restore the transforms in the save data to the entities with matching names, returning the restored entities.
+    pub fn restore_transforms(&mut self, save: &SaveGame) -> Vec<DefaultKey> {
+        let entities = self
+            .transforms
+            .keys()
+            .filter_map(|entity| self.get_name(entity).map(|name| (entity, name)));
+        let matches = save
+            .match_transforms(entities)
+            .into_iter()
+            .map(|(entity, saved)| (entity, saved.clone()))
+            .collect::<Vec<_>>();
+        let mut restored = Vec::with_capacity(matches.len());
+        for (entity, saved) in matches.into_iter() {
+            if let Some(transform) = self.transforms.get_mut(entity) {
+                transform.position = saved.position;
+                transform.rotation = saved.rotation;
+                transform.scale = saved.scale;
+                // 元の位置から補間して滑らないように、前のステップの位置も忘れる。
+                // Forget the previous step too, so it doesn't slide over from the old placement.
+                self.previous_transforms.remove(entity);
+                restored.push(entity);
+            }
+        }
+        restored
+    }
+
+    /// アニメーターを進めて、決まったポーズを描画するモデルに渡す。<br />
+    /// Advance animators and hand the resulting poses to the models being rendered.
+    pub fn update_animators(&mut self, delta_time: f64) {
+        for (entity, animator) in self.animators.iter_mut() {
+            let position = match self.transforms.get(entity) {
+                Some(transform) => transform.position,
+                None => continue,
+            };
+            animator.update(position, delta_time as f32);
+            if let Some(renderable) = self.renderables.get(entity) {
+                renderable
+                    .0
+                    .lock()
+                    .set_animation_pose(animator.state_machine.get_pose());
+            }
+        }
+    }
+
+    /// アニメーターで起きたイベントを、エンティティと一緒に取り出す。<br />
+    /// Take the events fired by animators along with their entities.
+    pub fn take_animation_events(&mut self) -> Vec<(DefaultKey, String)> {
+        self.animators
+            .iter_mut()
+            .flat_map(|(entity, animator)| {
+                animator
+                    .state_machine
+                    .take_events()
+                    .into_iter()
+                    .map(move |event| (entity, event))
+            })
+            .collect()
+    }
+
+    /// シミュレーションを一回進める前に、今のトランスフォームを補間の始まりとして覚えておく。<br />
+    /// Remember the current transforms as the start of interpolation before the simulation advances a step.
+    pub fn begin_simulation_step(&mut self) {
+        // マップを作り直さず、今あるスロットに上書きして毎ステップの確保を避ける。
+        // Overwrite the existing slots instead of rebuilding the map, so no allocation happens every step.
+        for (entity, transform) in self.transforms.iter() {
+            match self.previous_transforms.get_mut(entity) {
+                Some(previous) => *previous = *transform,
+                None => {
+                    self.previous_transforms.insert(entity, *transform);
+                }
+            }
+        }
+    }
+
+    /// 次に描画する時点を、前のステップから今のステップまでの割合で設定する。<br />
+    /// Set the point rendered next, as a fraction from the previous step to the current one.
+    pub fn set_interpolation_alpha(&mut self, alpha: f32) {
+        self.interpolation_alpha = alpha.max(0.0).min(1.0);
+    }
+
+    /// 描画する時点のトランスフォーム。前のステップがなければ今のトランスフォームを返す。<br />
+    /// The transform at the point being rendered. Without a previous step, the current transform is returned.
+    pub fn get_interpolated_transform(&self, entity: DefaultKey) -> Option<Transform> {
+        let transform = self.transforms.get(entity)?;
+        Some(match self.previous_transforms.get(entity) {
+            Some(previous) => transform.interpolate_from(previous, self.interpolation_alpha),
+            None => *transform,
+        })
+    }
+
+    /// 補間したトランスフォームと揺れる骨の設定を描画するモデルに書き込む。<br />
+    /// Write interpolated transforms and spring-bone settings into the models being rendered.
+    pub fn sync_renderables(&self) {
+        for entity in self.transforms.keys() {
+            let transform = match self.get_interpolated_transform(entity) {
+                Some(transform) => transform,
+                None => continue,
+            };
+            if let Some(renderable) = self.renderables.get(entity) {
+                let mut renderable_lock = renderable.0.lock();
+                if let Some(rig) = self.spring_bone_rigs.get(entity) {
+                    renderable_lock.set_spring_bones(rig.config.clone());
+                }
+                renderable_lock.set_position_info(PositionInfo::from(transform));
+                let mut metadata = renderable_lock.get_model_metadata();
+                metadata.world_matrix = transform.get_world_matrix();
+                renderable_lock.set_model_metadata(metadata);
+            }
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::VecDeque;
+
+/// コンソールの開閉に使うキーの文字。入力欄には入れない。<br />
+/// Character of the key that opens and closes the console. It's kept out of the input.
+pub const CONSOLE_TOGGLE_CHAR: char = '`';
+
+/// コンソールに残す出力の最大の行数。<br />
+/// Maximum number of output lines kept in the console.
+const MAX_CONSOLE_LINES: usize = 200;
+
+/// 残す入力の履歴の最大の数。<br />
+/// Maximum number of entries kept in the input history.
+const MAX_CONSOLE_HISTORY: usize = 50;
+
+/// 登録されたコマンドの名前と説明。<br />
+/// Name and description of a registered command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsoleCommandInfo {
+    pub name: String,
+    /// 引数の書き方。`help`で表示する。<br />
+    /// How the arguments are written, shown by `help`.
+    pub usage: String,
+    pub description: String,
+}
+
+/// 入力された一行を名前と引数に分けたもの。<br />
+/// A line of input split into the name and the arguments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsoleCommand {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl ConsoleCommand {
+    /// 空白で区切り、引用符で囲んだ部分は一つの引数にする。空の行は`None`。<br />
+    /// Split on whitespace, keeping quoted parts as a single argument. An empty line gives `None`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = vec![];
+        let mut word = String::new();
+        let mut is_quoted = false;
+        let mut has_word = false;
+        for c in line.chars() {
+            match c {
+                '"' => {
+                    is_quoted = !is_quoted;
+                    has_word = true;
+                }
+                c if c.is_whitespace() && !is_quoted => {
+                    if has_word {
+                        words.push(std::mem::take(&mut word));
+                        has_word = false;
+                    }
+                }
+                c => {
+                    word.push(c);
+                    has_word = true;
+                }
+            }
+        }
+        if has_word {
+            words.push(word);
+        }
+        let mut words = words.into_iter();
+        let name = words.next()?.to_lowercase();
+        Some(ConsoleCommand {
+            name,
+            args: words.collect(),
+        })
+    }
+
+    pub fn get_arg(&self, index: usize) -> anyhow::Result<&str> {
+        self.args
+            .get(index)
+            .map(|arg| arg.as_str())
+            .ok_or_else(|| anyhow::anyhow!("{} needs at least {} arguments.", self.name, index + 1))
+    }
+
+    pub fn get_f32(&self, index: usize) -> anyhow::Result<f32> {
+        let arg = self.get_arg(index)?;
+        arg.parse::<f32>()
+            .map_err(|_| anyhow::anyhow!("{} is not a number.", arg))
+    }
+}
+
+/// シーンやシステムがコマンドを実行した結果。<br />
+/// The result of a command executed by a scene or a system.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsoleReply {
+    pub message: String,
+    /// モデルを追加したので、読み込みを済ませる必要がある？<br />
+    /// Were models added, so that loading has to be finished?
+    pub needs_loading: bool,
+}
+
+impl ConsoleReply {
+    pub fn new(message: &str) -> Self {
+        ConsoleReply {
+            message: message.to_string(),
+            needs_loading: false,
+        }
+    }
+
+    pub fn with_loading(mut self) -> Self {
+        self.needs_loading = true;
+        self
+    }
+}
+
+/// ゲーム内のコンソール。シーンやシステムが登録したコマンドを受け付け、入力の履歴と補完を持つ。<br />
+/// 入力されたコマンドは`take_submitted`で取り出して実行し、結果を`print`で表示する。<br />
+/// The in-game console. It accepts commands registered by scenes and systems, and keeps the input history and completion.<br />
+/// Submitted commands are taken with `take_submitted` and executed, and their results are shown with `print`.
+#[derive(Clone, Debug)]
+pub struct Console {
+    commands: Vec<ConsoleCommandInfo>,
+    input: String,
+    output: VecDeque<String>,
+    history: Vec<String>,
+    /// 履歴をたどっている時の位置。たどっていなければ`None`。<br />
+    /// Position while browsing the history, or `None` when not browsing.
+    history_index: Option<usize>,
+    submitted: Vec<ConsoleCommand>,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Console {
+    /// `help`と`clear`はコンソール自身が処理する。<br />
+    /// `help` and `clear` are handled by the console itself.
+    pub fn new() -> Self {
+        let mut console = Console {
+            commands: vec![],
+            input: String::new(),
+            output: VecDeque::new(),
+            history: vec![],
+            history_index: None,
+            submitted: vec![],
+        };
+        console.register("help", "", "List the available commands.");
+        console.register("clear", "", "Clear the console output.");
+        console
+    }
+
+    /// コマンドを登録する。同じ名前のコマンドがあれば置き換える。<br />
+    /// Register a command, replacing any command with the same name.
+    pub fn register(&mut self, name: &str, usage: &str, description: &str) {
+        let info = ConsoleCommandInfo {
+            name: name.to_lowercase(),
+            usage: usage.to_string(),
+            description: description.to_string(),
+        };
+        match self.commands.iter_mut().find(|c| c.name == info.name) {
+            Some(command) => *command = info,
+            None => self.commands.push(info),
+        }
+    }
+
+    pub fn get_commands(&self) -> &[ConsoleCommandInfo] {
+        &self.commands
+    }
+
+    pub fn get_input(&self) -> &str {
+        &self.input
+    }
+
+    /// 入力欄の文字列を変える。開閉のキーの文字は取り除く。<br />
+    /// Change the text of the input field. The character of the toggle key is removed.
+    pub fn set_input(&mut self, input: &str) {
+        self.input = input
+            .chars()
+            .filter(|c| *c != CONSOLE_TOGGLE_CHAR)
+            .collect();
+    }
+
+    pub fn get_output(&self) -> impl Iterator<Item = &String> {
+        self.output.iter()
+    }
+
+    pub fn print(&mut self, line: &str) {
+        for line in line.lines() {
+            if self.output.len() >= MAX_CONSOLE_LINES {
+                self.output.pop_front();
+            }
+            self.output.push_back(line.to_string());
+        }
+    }
+
+    /// 入力欄の行を実行する。登録されていないコマンドはエラーを表示し、取り出されない。<br />
+    /// Execute the line in the input field. Unregistered commands print an error and aren't taken out.
+    pub fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        self.history_index = None;
+        let command = match ConsoleCommand::parse(&line) {
+            Some(command) => command,
+            None => return,
+        };
+        let line = line.trim().to_string();
+        self.print(&format!("> {}", &line));
+        if self.history.last() != Some(&line) {
+            if self.history.len() >= MAX_CONSOLE_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(line);
+        }
+        let name = command.name.clone();
+        match name.as_str() {
+            "help" => {
+                let lines = self
+                    .commands
+                    .iter()
+                    .map(|c| match c.usage.as_str() {
+                        "" => format!("{} - {}", c.name, c.description),
+                        usage => format!("{} {} - {}", c.name, usage, c.description),
+                    })
+                    .collect::<Vec<_>>();
+                for line in lines.iter() {
+                    self.print(line);
+                }
+            }
+            "clear" => self.output.clear(),
+            name if self.commands.iter().any(|c| c.name == name) => self.submitted.push(command),
+            name => self.print(&format!("Unknown command: {}. Type help for a list.", name)),
+        }
+    }
+
+    /// 前回から入力されたコマンドを取り出す。<br />
+    /// Take the commands submitted since the last call.
+    pub fn take_submitted(&mut self) -> Vec<ConsoleCommand> {
+        std::mem::take(&mut self.submitted)
+    }
+
+    /// 一つ前の履歴を入力欄に入れる。<br />
+    /// Put the previous history entry into the input field.
+    pub fn previous_history(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    /// 一つ後の履歴を入力欄に入れる。最新より後は空の入力に戻る。<br />
+    /// Put the next history entry into the input field. Going past the newest returns to an empty input.
+    pub fn next_history(&mut self) {
+        let index = match self.history_index {
+            Some(index) => index + 1,
+            None => return,
+        };
+        if index < self.history.len() {
+            self.history_index = Some(index);
+            self.input = self.history[index].clone();
+        } else {
+            self.history_index = None;
+            self.input.clear();
+        }
+    }
+
+    /// 入力中のコマンドの名前を補完する。候補が一つなら名前を埋め、複数なら共通の部分まで埋めて候補を表示する。<br />
+    /// Complete the name of the command being typed. A single candidate fills in the name, while several fill in their common prefix and are listed.
+    pub fn complete(&mut self) {
+        if self.input.contains(char::is_whitespace) {
+            return;
+        }
+        let prefix = self.input.to_lowercase();
+        let candidates = self
+            .commands
+            .iter()
+            .filter(|c| c.name.starts_with(&prefix))
+            .map(|c| c.name.clone())
+            .collect::<Vec<_>>();
+        match candidates.as_slice() {
+            [] => (),
+            [name] => self.input = format!("{} ", name),
+            [first, rest @ ..] => {
+                let common = rest.iter().fold(first.as_str(), |common, name| {
+                    let length = common
+                        .chars()
+                        .zip(name.chars())
+                        .take_while(|(a, b)| a == b)
+                        .map(|(a, _)| a.len_utf8())
+                        .sum();
+                    &common[..length]
+                });
+                self.input = common.to_string();
+                let line = candidates.join("  ");
+                self.print(&line);
+            }
+        }
+    }
+}
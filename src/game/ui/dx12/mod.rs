@@ -0,0 +1,841 @@
+use super::font::{get_nearest_font, setup_font_atlas, FontFaces};
+use super::glyph_cache::{GlyphCache, DEFAULT_FONT_SIZES};
+use crate::game::graphics::dx12::{CommandQueue, Resource, ResourceType};
+use crate::game::shared::util::{get_nullptr, log_error};
+use crate::game::traits::UiDrawerBackend;
+use image::GenericImageView;
+use nuklear::{
+    Buffer as NkBuffer, Context, ConvertConfig, DrawNullTexture, DrawVertexLayoutAttribute,
+    DrawVertexLayoutElements, DrawVertexLayoutFormat, FontAtlas, FontAtlasFormat, FontID, Handle,
+    Size, UserFont, Vec2,
+};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::Arc;
+use winapi::ctypes::c_void;
+use winapi::shared::basetsd::{SIZE_T, UINT64};
+use winapi::shared::dxgiformat::*;
+use winapi::shared::dxgitype::DXGI_SAMPLE_DESC;
+use winapi::shared::guiddef::REFGUID;
+use winapi::shared::minwindef::{FALSE, TRUE, UINT};
+use winapi::um::d3d12::*;
+use winapi::um::d3dcommon::{ID3DBlob, D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST};
+use winapi::um::d3dcompiler::D3DCompile;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::synchapi::{CreateEventW, WaitForSingleObject};
+use winapi::um::winbase::INFINITE;
+use winapi::um::winnt::HANDLE;
+use winapi::Interface;
+use wio::com::ComPtr;
+
+/// 一度に使えるテクスチャの最大数。フォントのアトラスも含む。<br />
+/// Maximum number of textures usable at once, including the font atlas.
+const MAX_TEXTURES: UINT = 64;
+
+struct Vertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [u8; 4],
+}
+
+type Ortho = [[f32; 4]; 4];
+
+/// `D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING`。winapiではマクロなので定義されていない。<br />
+/// `D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING`, which is a macro and therefore missing in winapi.
+const DEFAULT_SHADER_4_COMPONENT_MAPPING: UINT = 0x1688;
+
+/// DirectX 12で一フレームのUIを描画する先。<br />
+/// 描画先は既に`D3D12_RESOURCE_STATE_RENDER_TARGET`の状態にある必要がある。<br />
+/// Where a frame of the UI is drawn with DirectX 12.<br />
+/// The render target has to be in `D3D12_RESOURCE_STATE_RENDER_TARGET` already.
+pub struct DrawTarget {
+    pub render_target_view: D3D12_CPU_DESCRIPTOR_HANDLE,
+    pub viewport: D3D12_VIEWPORT,
+}
+
+pub struct Drawer {
+    pub allocator: nuklear::Allocator,
+    pub draw_null_texture: DrawNullTexture,
+    nuklear_buffer: NkBuffer,
+    device: Arc<ComPtr<ID3D12Device2>>,
+    command_queue: ComPtr<ID3D12CommandQueue>,
+    command_allocator: ComPtr<ID3D12CommandAllocator>,
+    command_list: ComPtr<ID3D12GraphicsCommandList1>,
+    fence: ComPtr<ID3D12Fence>,
+    fence_event: HANDLE,
+    fence_value: UINT64,
+    root_signature: ComPtr<ID3D12RootSignature>,
+    pipeline_state: ComPtr<ID3D12PipelineState>,
+    srv_heap: ComPtr<ID3D12DescriptorHeap>,
+    srv_increment_size: UINT,
+    vertex_buffer: Resource,
+    vertex_buffer_size: UINT,
+    vertex_mapped: *mut c_void,
+    index_buffer: Resource,
+    index_buffer_size: UINT,
+    index_mapped: *mut c_void,
+    ortho: Ortho,
+    layout_elements: DrawVertexLayoutElements,
+    font_faces: FontFaces,
+    font_atlas: FontAtlas,
+    fonts: HashMap<u8, FontID>,
+    glyph_cache: GlyphCache,
+    /// アトラスが参照するフォントごとの文字の範囲。アトラスの後に解放する。<br />
+    /// Glyph ranges per font referred to by the atlas, released after it.
+    glyph_ranges: Vec<Vec<(u32, u32)>>,
+    textures: Vec<Resource>,
+}
+
+impl Drawer {
+    pub unsafe fn new(
+        device: Arc<ComPtr<ID3D12Device2>>,
+        command_queue: ComPtr<ID3D12CommandQueue>,
+        color_format: DXGI_FORMAT,
+        vertex_buffer_size: UINT,
+        index_buffer_size: UINT,
+        nk_command_buffer_size: usize,
+        font_bytes: &[Vec<u8>],
+        glyph_ranges: &[(u32, u32)],
+    ) -> Self {
+        let command_allocator = CommandQueue::create_command_allocators(&*device, 1)
+            .pop()
+            .expect("Failed to create command allocator for Nuklear.");
+        let command_list = CommandQueue::create_command_list(&*device, &command_allocator);
+        let fence = Self::create_fence(&*device);
+        let fence_event = CreateEventW(std::ptr::null_mut(), FALSE, FALSE, std::ptr::null());
+        if fence_event.is_null() {
+            panic!("Failed to create fence event for Nuklear.");
+        }
+        let root_signature = Self::create_root_signature(&*device);
+        let pipeline_state = Self::create_pipeline_state(&*device, &root_signature, color_format);
+        let srv_heap = Self::create_srv_heap(&*device);
+        let srv_increment_size =
+            device.GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV);
+        let (vertex_buffer, vertex_mapped) =
+            Self::create_upload_buffer(&*device, vertex_buffer_size);
+        let (index_buffer, index_mapped) = Self::create_upload_buffer(&*device, index_buffer_size);
+
+        let mut nk_allocator = nuklear::Allocator::new_vec();
+        let mut font_faces = FontFaces::new(font_bytes);
+        let glyph_cache = GlyphCache::with_ranges(&DEFAULT_FONT_SIZES, glyph_ranges);
+        let glyph_ranges = glyph_cache.get_font_glyph_ranges(font_faces.get_coverages());
+        let (font_atlas, fonts) = setup_font_atlas(
+            &mut nk_allocator,
+            &mut font_faces,
+            &glyph_ranges,
+            &glyph_cache.get_sizes(),
+        );
+
+        let mut drawer = Drawer {
+            nuklear_buffer: NkBuffer::with_size(&mut nk_allocator, nk_command_buffer_size),
+            draw_null_texture: DrawNullTexture::default(),
+            allocator: nk_allocator,
+            device,
+            command_queue,
+            command_allocator,
+            command_list,
+            fence,
+            fence_event,
+            fence_value: 0,
+            root_signature,
+            pipeline_state,
+            srv_heap,
+            srv_increment_size,
+            vertex_buffer,
+            vertex_buffer_size,
+            vertex_mapped,
+            index_buffer,
+            index_buffer_size,
+            index_mapped,
+            ortho: [[0.0; 4]; 4],
+            layout_elements: DrawVertexLayoutElements::new(&[
+                (
+                    DrawVertexLayoutAttribute::Position,
+                    DrawVertexLayoutFormat::Float,
+                    memoffset::offset_of!(Vertex, position),
+                ),
+                (
+                    DrawVertexLayoutAttribute::TexCoord,
+                    DrawVertexLayoutFormat::Float,
+                    memoffset::offset_of!(Vertex, uv),
+                ),
+                (
+                    DrawVertexLayoutAttribute::Color,
+                    DrawVertexLayoutFormat::R8G8B8A8,
+                    memoffset::offset_of!(Vertex, color),
+                ),
+                (
+                    DrawVertexLayoutAttribute::AttributeCount,
+                    DrawVertexLayoutFormat::Count,
+                    0,
+                ),
+            ]),
+            font_faces,
+            font_atlas,
+            fonts,
+            glyph_cache,
+            glyph_ranges,
+            textures: vec![],
+        };
+        drawer.bake_font();
+        drawer
+    }
+
+    /// フォントのアトラスをベイクし、最初のテクスチャとしてアップロードする。<br />
+    /// ベイクし直した時は、同じ位置の古いテクスチャを置き換える。<br />
+    /// Bake the font atlas and upload it as the first texture.<br />
+    /// When baked again, the old texture in the same slot is replaced.
+    unsafe fn bake_font(&mut self) {
+        let (bytes, width, height) = self.font_atlas.bake(FontAtlasFormat::Rgba32);
+        let bytes = bytes.to_vec();
+        let texture = self.upload_to_slot(bytes.as_slice(), width, height, 0);
+        if self.textures.is_empty() {
+            self.textures.push(texture);
+        } else {
+            self.textures[0] = texture;
+        }
+        self.font_atlas
+            .end(Handle::from_id(0), Some(&mut self.draw_null_texture));
+    }
+
+    unsafe fn create_fence(device: &ComPtr<ID3D12Device2>) -> ComPtr<ID3D12Fence> {
+        let mut ptr = get_nullptr();
+        let res = device.CreateFence(
+            0,
+            D3D12_FENCE_FLAG_NONE,
+            &ID3D12Fence::uuidof() as REFGUID,
+            &mut ptr as *mut _,
+        );
+        log_error(res, "Failed to create fence for Nuklear.");
+        ComPtr::from_raw(ptr as *mut ID3D12Fence)
+    }
+
+    unsafe fn create_pipeline_state(
+        device: &ComPtr<ID3D12Device2>,
+        root_signature: &ComPtr<ID3D12RootSignature>,
+        color_format: DXGI_FORMAT,
+    ) -> ComPtr<ID3D12PipelineState> {
+        let vertex_shader = Self::compile_shader("shaders/ui.hlsl", "vs_main", "vs_5_0");
+        let pixel_shader = Self::compile_shader("shaders/ui.hlsl", "ps_main", "ps_5_0");
+
+        let position = CString::new("POSITION").expect("Failed to create CString for shader.");
+        let texcoord = CString::new("TEXCOORD").expect("Failed to create CString for shader.");
+        let color = CString::new("COLOR").expect("Failed to create CString for shader.");
+        let input_elements = [
+            D3D12_INPUT_ELEMENT_DESC {
+                SemanticName: position.as_ptr(),
+                SemanticIndex: 0,
+                Format: DXGI_FORMAT_R32G32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: memoffset::offset_of!(Vertex, position) as UINT,
+                InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
+                InstanceDataStepRate: 0,
+            },
+            D3D12_INPUT_ELEMENT_DESC {
+                SemanticName: texcoord.as_ptr(),
+                SemanticIndex: 0,
+                Format: DXGI_FORMAT_R32G32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: memoffset::offset_of!(Vertex, uv) as UINT,
+                InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
+                InstanceDataStepRate: 0,
+            },
+            D3D12_INPUT_ELEMENT_DESC {
+                SemanticName: color.as_ptr(),
+                SemanticIndex: 0,
+                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                InputSlot: 0,
+                AlignedByteOffset: memoffset::offset_of!(Vertex, color) as UINT,
+                InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
+                InstanceDataStepRate: 0,
+            },
+        ];
+
+        let mut desc: D3D12_GRAPHICS_PIPELINE_STATE_DESC = Default::default();
+        desc.pRootSignature = root_signature.as_raw();
+        desc.VS = D3D12_SHADER_BYTECODE {
+            pShaderBytecode: vertex_shader.GetBufferPointer(),
+            BytecodeLength: vertex_shader.GetBufferSize(),
+        };
+        desc.PS = D3D12_SHADER_BYTECODE {
+            pShaderBytecode: pixel_shader.GetBufferPointer(),
+            BytecodeLength: pixel_shader.GetBufferSize(),
+        };
+        desc.InputLayout = D3D12_INPUT_LAYOUT_DESC {
+            pInputElementDescs: input_elements.as_ptr(),
+            NumElements: input_elements.len() as UINT,
+        };
+
+        // UIは深度を使わず、アルファブレンドで上書きする。
+        // The UI ignores depth and is alpha blended over the scene.
+        let blend_desc = &mut desc.BlendState.RenderTarget[0];
+        blend_desc.BlendEnable = TRUE;
+        blend_desc.SrcBlend = D3D12_BLEND_SRC_ALPHA;
+        blend_desc.DestBlend = D3D12_BLEND_INV_SRC_ALPHA;
+        blend_desc.BlendOp = D3D12_BLEND_OP_ADD;
+        blend_desc.SrcBlendAlpha = D3D12_BLEND_ONE;
+        blend_desc.DestBlendAlpha = D3D12_BLEND_INV_SRC_ALPHA;
+        blend_desc.BlendOpAlpha = D3D12_BLEND_OP_ADD;
+        blend_desc.LogicOp = D3D12_LOGIC_OP_NOOP;
+        blend_desc.RenderTargetWriteMask = D3D12_COLOR_WRITE_ENABLE_ALL as u8;
+        desc.SampleMask = UINT::MAX;
+        desc.RasterizerState.FillMode = D3D12_FILL_MODE_SOLID;
+        desc.RasterizerState.CullMode = D3D12_CULL_MODE_NONE;
+        desc.RasterizerState.DepthClipEnable = TRUE;
+        desc.DepthStencilState.DepthEnable = FALSE;
+        desc.DepthStencilState.StencilEnable = FALSE;
+        desc.PrimitiveTopologyType = D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE;
+        desc.NumRenderTargets = 1;
+        desc.RTVFormats[0] = color_format;
+        desc.DSVFormat = DXGI_FORMAT_UNKNOWN;
+        desc.SampleDesc = DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        };
+
+        let mut ptr = get_nullptr();
+        let res = device.CreateGraphicsPipelineState(
+            &desc as *const _,
+            &ID3D12PipelineState::uuidof() as REFGUID,
+            &mut ptr as *mut _,
+        );
+        log_error(res, "Failed to create pipeline state for Nuklear.");
+        log::info!("Successfully created pipeline state for Nuklear.");
+        ComPtr::from_raw(ptr as *mut ID3D12PipelineState)
+    }
+
+    unsafe fn compile_shader(file_name: &str, entry_point: &str, target: &str) -> ComPtr<ID3DBlob> {
+        let source = std::fs::read(file_name).expect("Failed to read shader file for Nuklear.");
+        let entry_point = CString::new(entry_point).expect("Failed to create CString for shader.");
+        let target = CString::new(target).expect("Failed to create CString for shader.");
+        let mut code = std::ptr::null_mut::<ID3DBlob>();
+        let mut errors = std::ptr::null_mut::<ID3DBlob>();
+        let res = D3DCompile(
+            source.as_ptr() as *const c_void,
+            source.len() as SIZE_T,
+            std::ptr::null(),
+            std::ptr::null(),
+            std::ptr::null_mut(),
+            entry_point.as_ptr(),
+            target.as_ptr(),
+            0,
+            0,
+            &mut code as *mut _,
+            &mut errors as *mut _,
+        );
+        if !errors.is_null() {
+            let errors = ComPtr::from_raw(errors);
+            let message = std::slice::from_raw_parts(
+                errors.GetBufferPointer() as *const u8,
+                errors.GetBufferSize(),
+            );
+            log::error!(
+                "Shader compilation message: {}",
+                String::from_utf8_lossy(message)
+            );
+        }
+        log_error(res, "Failed to compile shader for Nuklear.");
+        ComPtr::from_raw(code)
+    }
+
+    unsafe fn create_root_signature(device: &ComPtr<ID3D12Device2>) -> ComPtr<ID3D12RootSignature> {
+        let texture_range = [D3D12_DESCRIPTOR_RANGE {
+            RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+            NumDescriptors: 1,
+            BaseShaderRegister: 0,
+            RegisterSpace: 0,
+            OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+        }];
+
+        // 射影行列はルート定数、テクスチャは描画コマンドごとに切り替えるデスクリプターテーブル。
+        // The projection matrix is a root constant; the texture is a descriptor table switched per draw command.
+        let mut parameter_1 = D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+            u: Default::default(),
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_VERTEX,
+        };
+        *parameter_1.u.Constants_mut() = D3D12_ROOT_CONSTANTS {
+            ShaderRegister: 0,
+            RegisterSpace: 0,
+            Num32BitValues: (std::mem::size_of::<Ortho>() / 4) as UINT,
+        };
+        let mut parameter_2 = D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            u: Default::default(),
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+        };
+        parameter_2.u.DescriptorTable_mut().NumDescriptorRanges = texture_range.len() as UINT;
+        parameter_2.u.DescriptorTable_mut().pDescriptorRanges = texture_range.as_ptr();
+        let root_parameters = [parameter_1, parameter_2];
+
+        let static_sampler = D3D12_STATIC_SAMPLER_DESC {
+            Filter: D3D12_FILTER_MIN_MAG_MIP_LINEAR,
+            AddressU: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+            AddressV: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+            AddressW: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+            MipLODBias: 0.0,
+            MaxAnisotropy: 0,
+            ComparisonFunc: D3D12_COMPARISON_FUNC_ALWAYS,
+            BorderColor: D3D12_STATIC_BORDER_COLOR_TRANSPARENT_BLACK,
+            MinLOD: 0.0,
+            MaxLOD: 0.0,
+            ShaderRegister: 0,
+            RegisterSpace: 0,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+        };
+
+        let desc = D3D12_ROOT_SIGNATURE_DESC {
+            NumParameters: root_parameters.len() as UINT,
+            pParameters: root_parameters.as_ptr(),
+            NumStaticSamplers: 1,
+            pStaticSamplers: &static_sampler as *const _,
+            Flags: D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT,
+        };
+        let mut blob = std::ptr::null_mut::<ID3DBlob>();
+        let mut error_blob = std::ptr::null_mut::<ID3DBlob>();
+        let res = D3D12SerializeRootSignature(
+            &desc as *const _,
+            D3D_ROOT_SIGNATURE_VERSION_1,
+            &mut blob as *mut _,
+            &mut error_blob as *mut _,
+        );
+        if !error_blob.is_null() {
+            ComPtr::from_raw(error_blob);
+        }
+        log_error(res, "Failed to serialize root signature for Nuklear.");
+        let blob = ComPtr::from_raw(blob);
+
+        let mut ptr = get_nullptr();
+        let res = device.CreateRootSignature(
+            0,
+            blob.GetBufferPointer(),
+            blob.GetBufferSize(),
+            &ID3D12RootSignature::uuidof() as REFGUID,
+            &mut ptr as *mut _,
+        );
+        log_error(res, "Failed to create root signature for Nuklear.");
+        ComPtr::from_raw(ptr as *mut ID3D12RootSignature)
+    }
+
+    unsafe fn create_srv_heap(device: &ComPtr<ID3D12Device2>) -> ComPtr<ID3D12DescriptorHeap> {
+        let desc = D3D12_DESCRIPTOR_HEAP_DESC {
+            Type: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+            NumDescriptors: MAX_TEXTURES,
+            Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+            NodeMask: 0,
+        };
+        let mut ptr = get_nullptr();
+        let res = device.CreateDescriptorHeap(
+            &desc as *const _,
+            &ID3D12DescriptorHeap::uuidof() as REFGUID,
+            &mut ptr as *mut _,
+        );
+        log_error(res, "Failed to create SRV descriptor heap for Nuklear.");
+        ComPtr::from_raw(ptr as *mut ID3D12DescriptorHeap)
+    }
+
+    /// CPUから書き込むバッファーを作り、常にマップしておく。<br />
+    /// Create a buffer written by the CPU and keep it mapped.
+    unsafe fn create_upload_buffer(
+        device: &ComPtr<ID3D12Device2>,
+        size: UINT,
+    ) -> (Resource, *mut c_void) {
+        let buffer = Resource::new(
+            device,
+            ResourceType::Intermediate,
+            size as UINT64,
+            1,
+            1,
+            DXGI_FORMAT_UNKNOWN,
+            D3D12_RESOURCE_STATE_GENERIC_READ,
+            std::ptr::null(),
+            D3D12_RESOURCE_FLAG_NONE,
+        );
+        let read_range = D3D12_RANGE { Begin: 0, End: 0 };
+        let mut mapped = get_nullptr();
+        let res = buffer
+            .resource
+            .Map(0, &read_range as *const _, &mut mapped as *mut _);
+        log_error(res, "Failed to map upload buffer for Nuklear.");
+        (buffer, mapped)
+    }
+
+    /// 描画コマンドのテクスチャのハンドルに対応するデスクリプターを求める。<br />
+    /// Get the descriptor matching the texture handle of a draw command.
+    unsafe fn get_texture_descriptor(&self, mut texture: Handle) -> D3D12_GPU_DESCRIPTOR_HANDLE {
+        let index = texture
+            .id()
+            .filter(|id| *id >= 0 && (*id as usize) < self.textures.len())
+            .unwrap_or(0);
+        let mut handle = self.srv_heap.GetGPUDescriptorHandleForHeapStart();
+        handle.ptr += (index as UINT * self.srv_increment_size) as UINT64;
+        handle
+    }
+
+    /// 頼まれた文字と大きさでアトラスをベイクし直し、最初のテクスチャを置き換える。<br />
+    /// Bake the atlas again with the requested glyphs and sizes, replacing the first texture.
+    unsafe fn rebake_font(&mut self) {
+        let glyph_ranges = self
+            .glyph_cache
+            .get_font_glyph_ranges(self.font_faces.get_coverages());
+        let (font_atlas, fonts) = setup_font_atlas(
+            &mut self.allocator,
+            &mut self.font_faces,
+            &glyph_ranges,
+            &self.glyph_cache.get_sizes(),
+        );
+        // 古いアトラスを解放してから、それが参照していた範囲を入れ替える。
+        // Release the old atlas before replacing the ranges it referred to.
+        self.font_atlas = font_atlas;
+        self.glyph_ranges = glyph_ranges;
+        self.fonts = fonts;
+        self.bake_font();
+        log::info!(
+            "Baked {} glyphs in {} ranges across {} fonts and {} font sizes for Nuklear.",
+            self.glyph_cache.get_glyph_count(),
+            self.glyph_ranges
+                .iter()
+                .map(|ranges| ranges.len() - 1)
+                .sum::<usize>(),
+            self.glyph_ranges.len(),
+            self.fonts.len()
+        );
+    }
+
+    /// 記録したコマンドを送信し、完了を知らせるフェンスの値を返す。<br />
+    /// Submit the recorded commands and return the fence value signalling their completion.
+    unsafe fn submit(&mut self) -> UINT64 {
+        let res = self.command_list.Close();
+        log_error(res, "Failed to close command list for Nuklear.");
+        let command_lists = [self.command_list.as_raw() as *mut ID3D12CommandList];
+        self.command_queue
+            .ExecuteCommandLists(command_lists.len() as UINT, command_lists.as_ptr());
+        self.fence_value += 1;
+        let res = self
+            .command_queue
+            .Signal(self.fence.as_raw(), self.fence_value);
+        log_error(res, "Failed to signal fence for Nuklear.");
+        self.fence_value
+    }
+
+    /// RGBAのピクセルをテクスチャとしてアップロードし、シェーダーリソースビューを作る。<br />
+    /// 戻り値のハンドルの番号はデスクリプターヒープの位置で、上限を超えた場合はフォントのアトラスの0になる。<br />
+    /// Upload RGBA pixels as a texture and create its shader resource view.<br />
+    /// The ID of the returned handle is the position in the descriptor heap, falling back to the font atlas' 0 when the limit is exceeded.
+    unsafe fn upload_texture(&mut self, pixels: &[u8], width: u32, height: u32) -> Handle {
+        if self.textures.len() as UINT >= MAX_TEXTURES {
+            log::error!("Too many textures for Nuklear. Maximum: {}", MAX_TEXTURES);
+            return Handle::from_id(0);
+        }
+        let slot = self.textures.len() as UINT;
+        let texture = self.upload_to_slot(pixels, width, height, slot);
+        self.textures.push(texture);
+        Handle::from_id(slot as i32)
+    }
+
+    /// RGBAのピクセルをアップロードし、デスクリプターヒープの指定した位置にシェーダーリソースビューを作る。<br />
+    /// Upload RGBA pixels and create the shader resource view at the given position in the descriptor heap.
+    unsafe fn upload_to_slot(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        slot: UINT,
+    ) -> Resource {
+        let device = self.device.clone();
+        let texture = Resource::new(
+            &*device,
+            ResourceType::Image,
+            width as UINT64,
+            height,
+            1,
+            DXGI_FORMAT_R8G8B8A8_UNORM,
+            D3D12_RESOURCE_STATE_COPY_DEST,
+            std::ptr::null(),
+            D3D12_RESOURCE_FLAG_NONE,
+        );
+
+        // 行のピッチは256バイトに揃える必要がある。
+        // Row pitch has to be aligned to 256 bytes.
+        let row_size = width * 4;
+        let row_pitch = (row_size + D3D12_TEXTURE_DATA_PITCH_ALIGNMENT - 1)
+            & !(D3D12_TEXTURE_DATA_PITCH_ALIGNMENT - 1);
+        let (staging, mapped) = Self::create_upload_buffer(&*device, row_pitch * height);
+        for row in 0..height as usize {
+            std::ptr::copy_nonoverlapping(
+                pixels.as_ptr().add(row * row_size as usize),
+                (mapped as *mut u8).add(row * row_pitch as usize),
+                row_size as usize,
+            );
+        }
+        staging.resource.Unmap(0, std::ptr::null());
+
+        self.wait_idle();
+        let res = self.command_allocator.Reset();
+        log_error(res, "Failed to reset command allocator for Nuklear.");
+        let res = self
+            .command_list
+            .Reset(self.command_allocator.as_raw(), std::ptr::null_mut());
+        log_error(res, "Failed to reset command list for Nuklear.");
+
+        let mut source = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: staging.resource.as_raw(),
+            Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+            u: Default::default(),
+        };
+        *source.u.PlacedFootprint_mut() = D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
+            Offset: 0,
+            Footprint: D3D12_SUBRESOURCE_FOOTPRINT {
+                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                Width: width,
+                Height: height,
+                Depth: 1,
+                RowPitch: row_pitch,
+            },
+        };
+        let mut destination = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: texture.resource.as_raw(),
+            Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+            u: Default::default(),
+        };
+        *destination.u.SubresourceIndex_mut() = 0;
+        self.command_list.CopyTextureRegion(
+            &destination as *const _,
+            0,
+            0,
+            0,
+            &source as *const _,
+            std::ptr::null(),
+        );
+
+        let mut barrier = D3D12_RESOURCE_BARRIER {
+            Type: D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
+            Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
+            u: Default::default(),
+        };
+        *barrier.u.Transition_mut() = D3D12_RESOURCE_TRANSITION_BARRIER {
+            pResource: texture.resource.as_raw(),
+            Subresource: D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
+            StateBefore: D3D12_RESOURCE_STATE_COPY_DEST,
+            StateAfter: D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+        };
+        self.command_list.ResourceBarrier(1, &barrier as *const _);
+        self.submit();
+        // ステージングバッファーを解放する前にコピーの完了を待つ。
+        // Wait for the copy to finish before the staging buffer is released.
+        self.wait_idle();
+
+        let mut srv_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+            Shader4ComponentMapping: DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            u: Default::default(),
+        };
+        srv_desc.u.Texture2D_mut().MipLevels = 1;
+        let mut handle = self.srv_heap.GetCPUDescriptorHandleForHeapStart();
+        handle.ptr += (slot * self.srv_increment_size) as SIZE_T;
+        device.CreateShaderResourceView(texture.resource.as_raw(), &srv_desc as *const _, handle);
+        texture
+    }
+}
+
+impl UiDrawerBackend for Drawer {
+    type RenderTarget = DrawTarget;
+    type Signal = UINT64;
+
+    fn add_texture_from_file(&mut self, file_name: &str) -> Handle {
+        let img = image::open(file_name).expect("Failed to open texture file for Nuklear.");
+        let (width, height) = img.dimensions();
+        let pixels = img.to_rgba8();
+        unsafe { self.upload_texture(pixels.as_raw().as_slice(), width, height) }
+    }
+
+    fn create_context(&mut self, font_size: u8) -> Context {
+        let font = self.get_font(font_size).clone();
+        Context::new(&mut self.allocator, &font)
+    }
+
+    fn draw(
+        &mut self,
+        target: Self::RenderTarget,
+        scale: Vec2,
+        context: &mut Context,
+        convert_config: &mut ConvertConfig,
+    ) -> Self::Signal {
+        let DrawTarget {
+            render_target_view,
+            viewport,
+        } = target;
+        // 前のフレームがまだバッファーを読んでいるかもしれない。
+        // The previous frame may still be reading the buffers.
+        self.wait_idle();
+        self.update(
+            viewport.Width as u32,
+            viewport.Height as u32,
+            context,
+            convert_config,
+        );
+        unsafe {
+            let res = self.command_allocator.Reset();
+            log_error(res, "Failed to reset command allocator for Nuklear.");
+            let res = self.command_list.Reset(
+                self.command_allocator.as_raw(),
+                self.pipeline_state.as_raw(),
+            );
+            log_error(res, "Failed to reset command list for Nuklear.");
+
+            let command_list = &self.command_list;
+            command_list.SetGraphicsRootSignature(self.root_signature.as_raw());
+            let heaps = [self.srv_heap.as_raw()];
+            command_list.SetDescriptorHeaps(heaps.len() as UINT, heaps.as_ptr());
+            command_list.RSSetViewports(1, &viewport as *const _);
+            command_list.OMSetRenderTargets(
+                1,
+                &render_target_view as *const _,
+                FALSE,
+                std::ptr::null(),
+            );
+            command_list.SetGraphicsRoot32BitConstants(
+                0,
+                (std::mem::size_of::<Ortho>() / 4) as UINT,
+                self.ortho.as_ptr() as *const c_void,
+                0,
+            );
+            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            let vertex_buffer_view = D3D12_VERTEX_BUFFER_VIEW {
+                BufferLocation: self.vertex_buffer.resource.GetGPUVirtualAddress(),
+                SizeInBytes: self.vertex_buffer_size,
+                StrideInBytes: std::mem::size_of::<Vertex>() as UINT,
+            };
+            command_list.IASetVertexBuffers(0, 1, &vertex_buffer_view as *const _);
+            let index_buffer_view = D3D12_INDEX_BUFFER_VIEW {
+                BufferLocation: self.index_buffer.resource.GetGPUVirtualAddress(),
+                SizeInBytes: self.index_buffer_size,
+                Format: DXGI_FORMAT_R16_UINT,
+            };
+            command_list.IASetIndexBuffer(&index_buffer_view as *const _);
+
+            let mut index_offset = 0;
+            for cmd in context.draw_command_iterator(&self.nuklear_buffer) {
+                if cmd.elem_count() < 1 {
+                    continue;
+                }
+                let clip_rect = cmd.clip_rect();
+                let scissor = D3D12_RECT {
+                    left: ((clip_rect.x * scale.x) as i32).max(0),
+                    top: ((clip_rect.y * scale.y) as i32).max(0),
+                    right: ((clip_rect.x + clip_rect.w) * scale.x) as i32,
+                    bottom: ((clip_rect.y + clip_rect.h) * scale.y) as i32,
+                };
+                command_list.RSSetScissorRects(1, &scissor as *const _);
+                command_list
+                    .SetGraphicsRootDescriptorTable(1, self.get_texture_descriptor(cmd.texture()));
+                command_list.DrawIndexedInstanced(cmd.elem_count(), 1, index_offset, 0, 0);
+                index_offset += cmd.elem_count();
+            }
+            context.clear();
+            self.submit()
+        }
+    }
+
+    fn get_draw_null_texture(&self) -> DrawNullTexture {
+        self.draw_null_texture.clone()
+    }
+
+    fn get_font(&self, font_size: u8) -> &UserFont {
+        let font_id = get_nearest_font(&self.fonts, font_size);
+        self.font_atlas
+            .font(font_id)
+            .expect("Failed to get font in the font atlas.")
+            .handle()
+    }
+
+    fn request_glyphs(&mut self, text: &str) {
+        self.glyph_cache.request_text(text);
+    }
+
+    fn set_font_size(&mut self, context: &mut Context, font_size: u8) {
+        self.glyph_cache.request_size(font_size);
+        let font_id = get_nearest_font(&self.fonts, font_size);
+        let atlas = &mut self.font_atlas;
+        context.style_set_font(
+            atlas
+                .font(font_id)
+                .expect("Failed to get font in the font atlas.")
+                .handle(),
+        );
+    }
+
+    fn update(
+        &mut self,
+        width: u32,
+        height: u32,
+        context: &mut Context,
+        convert_config: &mut ConvertConfig,
+    ) {
+        // DirectX 12ではクリップ空間のY軸が上向きなので、Vulkanと違ってシェーダーで反転しない。
+        // Clip space Y points up in DirectX 12, so unlike Vulkan the shader doesn't flip it.
+        self.ortho = [
+            [2.0 / width as f32, 0.0, 0.0, 0.0],
+            [0.0, -2.0 / height as f32, 0.0, 0.0],
+            [0.0, 0.0, -1.0, 0.0],
+            [-1.0, 1.0, 0.0, 1.0],
+        ];
+        convert_config.set_vertex_size(std::mem::size_of::<Vertex>() as Size);
+        convert_config.set_vertex_layout(&self.layout_elements);
+        let vertex_slice = unsafe {
+            std::slice::from_raw_parts_mut(
+                self.vertex_mapped as *mut u8,
+                self.vertex_buffer_size as usize,
+            )
+        };
+        let mut v_buffer = NkBuffer::with_fixed(vertex_slice);
+        let index_slice = unsafe {
+            std::slice::from_raw_parts_mut(
+                self.index_mapped as *mut u8,
+                self.index_buffer_size as usize,
+            )
+        };
+        let mut i_buffer = NkBuffer::with_fixed(index_slice);
+        context.convert(
+            &mut self.nuklear_buffer,
+            &mut v_buffer,
+            &mut i_buffer,
+            convert_config,
+        );
+    }
+
+    fn update_font_atlas(&mut self, context: &mut Context, font_size: u8) -> bool {
+        if !self.glyph_cache.take_dirty() {
+            return false;
+        }
+        unsafe {
+            self.rebake_font();
+        }
+        self.set_font_size(context, font_size);
+        true
+    }
+
+    fn wait_idle(&self) {
+        unsafe {
+            if self.fence.GetCompletedValue() < self.fence_value {
+                let res = self
+                    .fence
+                    .SetEventOnCompletion(self.fence_value, self.fence_event);
+                log_error(res, "Failed to set fence event for Nuklear.");
+                WaitForSingleObject(self.fence_event, INFINITE);
+            }
+        }
+    }
+}
+
+impl Drop for Drawer {
+    fn drop(&mut self) {
+        self.wait_idle();
+        unsafe {
+            self.vertex_buffer.resource.Unmap(0, std::ptr::null());
+            self.index_buffer.resource.Unmap(0, std::ptr::null());
+            CloseHandle(self.fence_event);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use super::font_coverage::FontCoverage;
+use super::glyph_cache::find_nearest_size;
+use anyhow::Context;
+use nuklear::{FontAtlas, FontConfig, FontID};
+use std::collections::HashMap;
+
+/// UIのフォントと代わりのフォントを読み込む。UIのフォントは必須だが、代わりのフォントはなければ飛ばす。<br />
+/// Read the UI font and its fallback fonts. The UI font is required, but missing fallback fonts are skipped.
+pub fn read_font_files(font: &str, fallback_fonts: &[String]) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut font_bytes =
+        vec![std::fs::read(font).with_context(|| format!("Failed to read UI font: {}", font))?];
+    for fallback_font in fallback_fonts.iter() {
+        match std::fs::read(fallback_font) {
+            Ok(bytes) => font_bytes.push(bytes),
+            Err(e) => log::warn!("Failed to read fallback font {}: {}", fallback_font, e),
+        }
+    }
+    Ok(font_bytes)
+}
+
+/// UIのフォントと代わりのフォントの設定と、それぞれが持つ文字。最初がUIのフォント。<br />
+/// フォントのデータはアトラスが参照するので、アトラスより長く生かすこと。<br />
+/// Configurations of the UI font and its fallback fonts with the characters each has. The first one is the UI font.<br />
+/// The atlas refers to the font data, so the data must outlive it.
+pub struct FontFaces {
+    configs: Vec<FontConfig>,
+    coverages: Vec<FontCoverage>,
+}
+
+impl FontFaces {
+    pub fn new(font_bytes: &[Vec<u8>]) -> Self {
+        let (font, fallback_fonts) = font_bytes
+            .split_first()
+            .expect("Failed to get the UI font.");
+        // UIのフォントの文字が分からなければ、全ての文字を持つものと見なしてこれまで通りに描く。
+        // If the characters of the UI font are unknown, it's assumed to have all of them and draws as before.
+        let coverage = FontCoverage::parse(font).unwrap_or_else(|e| {
+            log::warn!("Failed to read the characters of the UI font: {}", e);
+            FontCoverage::from_ranges(vec![(0, std::char::MAX as u32)])
+        });
+        let mut configs = vec![create_font_config(font)];
+        let mut coverages = vec![coverage];
+        for (index, fallback_font) in fallback_fonts.iter().enumerate() {
+            match FontCoverage::parse(fallback_font) {
+                Ok(coverage) => {
+                    configs.push(create_font_config(fallback_font));
+                    coverages.push(coverage);
+                }
+                Err(e) => log::warn!(
+                    "Failed to read the characters of fallback font {}: {}",
+                    index,
+                    e
+                ),
+            }
+        }
+        FontFaces { configs, coverages }
+    }
+
+    pub fn get_coverages(&self) -> &[FontCoverage] {
+        &self.coverages
+    }
+}
+
+/// UIのフォントの設定を作る。<br />
+/// Create the font configuration of the UI.
+pub(crate) fn create_font_config(font_bytes: &[u8]) -> FontConfig {
+    let mut font_config = FontConfig::with_size(0.0);
+    font_config.set_oversample_h(3);
+    font_config.set_oversample_v(2);
+    font_config.set_ttf(font_bytes);
+    font_config
+}
+
+/// 与えられた文字の範囲と大きさだけをアトラスに追加する。ベイクはバックエンドに任せる。<br />
+/// 範囲はフォントごとで、代わりのフォントは同じ大きさのUIのフォントに統合する。<br />
+/// アトラスは描画中もこの範囲からグリフを探すので、範囲はアトラスより長く生かすこと。<br />
+/// Add only the given glyph ranges and sizes to an atlas. Baking is left to the backend.<br />
+/// The ranges are per font, and fallback fonts are merged into the UI font of the same size.<br />
+/// The atlas keeps looking glyphs up in these ranges while drawing, so the ranges must outlive it.
+pub fn setup_font_atlas(
+    allocator: &mut nuklear::Allocator,
+    font_faces: &mut FontFaces,
+    glyph_ranges: &[Vec<(u32, u32)>],
+    sizes: &[u8],
+) -> (FontAtlas, HashMap<u8, FontID>) {
+    let mut fonts = HashMap::new();
+    let mut atlas = FontAtlas::new(allocator);
+    atlas.begin();
+
+    for size in sizes.iter().copied() {
+        for (index, (font_config, glyph_ranges)) in font_faces
+            .configs
+            .iter_mut()
+            .zip(glyph_ranges.iter())
+            .enumerate()
+        {
+            // 最初のフォント以外は終端しかなければ飛ばす。統合は直前のフォントに対して行われる。
+            // Fonts other than the first are skipped if they only have the terminator. Merging applies to the preceding font.
+            let is_merged = index > 0;
+            if is_merged && glyph_ranges.len() <= 1 {
+                continue;
+            }
+            font_config.set_ttf_data_owned_by_atlas(false);
+            font_config.set_merge_mode(is_merged);
+            font_config.set_glyph_range(glyph_ranges);
+            font_config.set_size(size as f32);
+            let font = atlas.add_font_with_config(font_config);
+            if !is_merged {
+                fonts.insert(
+                    size,
+                    font.expect("Failed to load font into Nuklear runtime."),
+                );
+            }
+        }
+    }
+
+    (atlas, fonts)
+}
+
+/// 求めた大きさに最も近い、ベイク済みのフォントを返す。<br />
+/// Return the baked font closest to the requested size.
+pub fn get_nearest_font(fonts: &HashMap<u8, FontID>, font_size: u8) -> FontID {
+    find_nearest_size(fonts.keys().copied(), font_size)
+        .and_then(|size| fonts.get(&size))
+        .copied()
+        .expect("Failed to get any font size in Nuklear.")
+}
@@ -0,0 +1,173 @@
+use anyhow::Context;
+use std::convert::TryInto;
+
+/// フォントファイルの先頭のテーブルの記録の長さ。<br />
+/// Length of one table record at the beginning of a font file.
+const TABLE_RECORD_SIZE: usize = 16;
+
+/// フォントのコレクションを示す先頭の識別子。<br />
+/// Identifier at the beginning of a font collection.
+const COLLECTION_TAG: &[u8; 4] = b"ttcf";
+
+/// フォントが持つ文字の範囲。`cmap`テーブルから読み、グリフのない文字は含まない。<br />
+/// どのフォントで文字をベイクするかを決めるのに使う。<br />
+/// Ranges of the characters a font has, read from its `cmap` table, excluding characters without glyphs.<br />
+/// Used to decide which font bakes a character.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontCoverage {
+    /// 重ならず、昇順に並んだ範囲。両端を含む。<br />
+    /// Non-overlapping ranges in ascending order, both ends inclusive.
+    ranges: Vec<(u32, u32)>,
+}
+
+impl FontCoverage {
+    /// TrueTypeかOpenTypeのフォントから、Unicodeの`cmap`を読む。<br />
+    /// Read the Unicode `cmap` of a TrueType or OpenType font.
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.get(0..4) == Some(&COLLECTION_TAG[..]) {
+            anyhow::bail!("Font collections aren't supported.");
+        }
+        let table_count = read_u16(bytes, 4)? as usize;
+        let cmap = (0..table_count)
+            .map(|index| 12 + index * TABLE_RECORD_SIZE)
+            .find(|offset| bytes.get(*offset..*offset + 4) == Some(&b"cmap"[..]))
+            .with_context(|| "The font has no cmap table.")?;
+        let cmap = read_u32(bytes, cmap + 8)? as usize;
+
+        // Unicodeの全体を持つ形式12を、基本多言語面だけの形式4より優先する。
+        // Format 12 covering all of Unicode is preferred over format 4, which only covers the Basic Multilingual Plane.
+        let mut subtable: Option<(u16, usize)> = None;
+        for index in 0..read_u16(bytes, cmap + 2)? as usize {
+            let record = cmap + 4 + index * 8;
+            let platform_id = read_u16(bytes, record)?;
+            let encoding_id = read_u16(bytes, record + 2)?;
+            let offset = cmap + read_u32(bytes, record + 4)? as usize;
+            let is_unicode = platform_id == 0 || (platform_id == 3 && encoding_id == 1);
+            let is_full_unicode = platform_id == 0 || (platform_id == 3 && encoding_id == 10);
+            let format = read_u16(bytes, offset)?;
+            let is_better = match (format, subtable) {
+                (12, Some((12, _))) => false,
+                (12, _) => is_full_unicode,
+                (4, None) => is_unicode,
+                _ => false,
+            };
+            if is_better {
+                subtable = Some((format, offset));
+            }
+        }
+        let ranges = match subtable {
+            Some((12, offset)) => Self::read_format_12(bytes, offset)?,
+            Some((_, offset)) => Self::read_format_4(bytes, offset)?,
+            None => anyhow::bail!("The font has no Unicode cmap in format 4 or 12."),
+        };
+        Ok(FontCoverage {
+            ranges: merge_ranges(ranges),
+        })
+    }
+
+    /// `cmap`を読まずに、範囲から直接作る。<br />
+    /// Create it directly from ranges without reading a `cmap`.
+    pub fn from_ranges(ranges: Vec<(u32, u32)>) -> Self {
+        FontCoverage {
+            ranges: merge_ranges(ranges),
+        }
+    }
+
+    pub fn contains(&self, c: u32) -> bool {
+        self.ranges
+            .binary_search_by(|(start, end)| {
+                if *end < c {
+                    std::cmp::Ordering::Less
+                } else if *start > c {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    pub fn get_ranges(&self) -> &[(u32, u32)] {
+        &self.ranges
+    }
+
+    /// 区分ごとに文字とグリフの番号の差を持つ形式。グリフの配列を指す区分は、文字ごとにグリフがあるかを調べる。<br />
+    /// Format storing the difference between characters and glyph indices per segment. Segments pointing into the glyph array are checked character by character.
+    fn read_format_4(bytes: &[u8], offset: usize) -> anyhow::Result<Vec<(u32, u32)>> {
+        let segment_count = read_u16(bytes, offset + 6)? as usize / 2;
+        let end_codes = offset + 14;
+        let start_codes = end_codes + segment_count * 2 + 2;
+        let id_deltas = start_codes + segment_count * 2;
+        let id_range_offsets = id_deltas + segment_count * 2;
+        let mut ranges = vec![];
+        for segment in 0..segment_count {
+            let end = read_u16(bytes, end_codes + segment * 2)? as u32;
+            let start = read_u16(bytes, start_codes + segment * 2)? as u32;
+            let id_delta = read_u16(bytes, id_deltas + segment * 2)? as u32;
+            let id_range_offset_position = id_range_offsets + segment * 2;
+            let id_range_offset = read_u16(bytes, id_range_offset_position)? as usize;
+            // 最後の区分は0xFFFFだけの終端。
+            // The last segment is a terminator holding only 0xFFFF.
+            let end = end.min(0xFFFE);
+            if start > end {
+                continue;
+            }
+            if id_range_offset == 0 {
+                ranges.push((start, end));
+                continue;
+            }
+            for c in start..=end {
+                let position =
+                    id_range_offset_position + id_range_offset + (c - start) as usize * 2;
+                let glyph = read_u16(bytes, position)? as u32;
+                if glyph != 0 && (glyph + id_delta) & 0xFFFF != 0 {
+                    ranges.push((c, c));
+                }
+            }
+        }
+        Ok(ranges)
+    }
+
+    /// 連続した文字の組を並べた形式。<br />
+    /// Format listing groups of consecutive characters.
+    fn read_format_12(bytes: &[u8], offset: usize) -> anyhow::Result<Vec<(u32, u32)>> {
+        let group_count = read_u32(bytes, offset + 12)? as usize;
+        (0..group_count)
+            .map(|index| {
+                let group = offset + 16 + index * 12;
+                Ok((read_u32(bytes, group)?, read_u32(bytes, group + 4)?))
+            })
+            .collect()
+    }
+}
+
+/// 範囲を並べ替え、重なったり隣り合ったりする範囲を一つにまとめる。<br />
+/// Sort the ranges, merging the ones that overlap or are adjacent.
+pub fn merge_ranges(mut ranges: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    ranges.retain(|(start, end)| start <= end);
+    ranges.sort_unstable();
+    let mut merged: Vec<(u32, u32)> = vec![];
+    for (start, end) in ranges.into_iter() {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= last_end.saturating_add(1) => {
+                *last_end = (*last_end).max(end);
+            }
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> anyhow::Result<u16> {
+    let value = bytes
+        .get(offset..offset + 2)
+        .with_context(|| "The font file is truncated.")?;
+    Ok(u16::from_be_bytes(value.try_into()?))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let value = bytes
+        .get(offset..offset + 4)
+        .with_context(|| "The font file is truncated.")?;
+    Ok(u32::from_be_bytes(value.try_into()?))
+}
@@ -0,0 +1,142 @@
+use crate::game::ui::FontCoverage;
+use std::collections::BTreeSet;
+
+/// 最初からベイクする文字の範囲。ASCIIとラテン1補助。<br />
+/// Range of glyphs baked from the start: ASCII and the Latin-1 Supplement.
+pub const BASE_GLYPH_RANGE: (u32, u32) = (0x0020, 0x00FF);
+
+/// 最初からベイクするフォントの大きさ。UIの窓とウィジェットが使う大きさ。<br />
+/// Font sizes baked from the start, which are the sizes used by the UI windows and widgets.
+pub const DEFAULT_FONT_SIZES: [u8; 6] = [14, 16, 20, 24, 28, 36];
+
+/// アトラスに入れるグリフの上限。超えた文字はフォールバックのグリフで表示する。<br />
+/// Maximum number of glyphs in the atlas. Characters beyond it are shown with the fallback glyph.
+pub const MAX_CACHED_GLYPHS: usize = 8192;
+
+/// 実際に使われた文字とフォントの大きさを集め、アトラスをベイクし直す必要があるかを覚える。<br />
+/// Nuklearのベイカーはビットマップを作るのでSDFで大きさを共有できず、代わりに大きさも使われた時にベイクする。<br />
+/// Collects the characters and font sizes actually in use, remembering whether the atlas has to be baked again.<br />
+/// Nuklear's baker produces bitmaps, so sizes can't be shared through SDF; sizes are baked once they're used instead.
+#[derive(Clone, Debug)]
+pub struct GlyphCache {
+    glyphs: BTreeSet<u32>,
+    sizes: BTreeSet<u8>,
+    is_dirty: bool,
+}
+
+impl GlyphCache {
+    pub fn new(sizes: &[u8]) -> Self {
+        Self::with_ranges(sizes, &[])
+    }
+
+    /// 基本の範囲に加えて、設定された範囲も最初からベイクする。上限を超えた分は使われた時に回す。<br />
+    /// Bake the configured ranges from the start as well as the base range, leaving whatever exceeds the limit until it's used.
+    pub fn with_ranges(sizes: &[u8], ranges: &[(u32, u32)]) -> Self {
+        let mut glyphs: BTreeSet<u32> = (BASE_GLYPH_RANGE.0..=BASE_GLYPH_RANGE.1).collect();
+        for (start, end) in ranges.iter().copied() {
+            for glyph in (start..=end).filter(|glyph| std::char::from_u32(*glyph).is_some()) {
+                if glyphs.len() >= MAX_CACHED_GLYPHS {
+                    break;
+                }
+                glyphs.insert(glyph);
+            }
+        }
+        GlyphCache {
+            glyphs,
+            sizes: sizes.iter().copied().collect(),
+            is_dirty: false,
+        }
+    }
+
+    pub fn contains_glyph(&self, c: char) -> bool {
+        self.glyphs.contains(&(c as u32))
+    }
+
+    pub fn contains_size(&self, font_size: u8) -> bool {
+        self.sizes.contains(&font_size)
+    }
+
+    pub fn get_glyph_count(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn get_sizes(&self) -> Vec<u8> {
+        self.sizes.iter().copied().collect()
+    }
+
+    /// グリフを連続した範囲にまとめる。Nuklearに渡すので`(0, 0)`で終わる。<br />
+    /// Merge the glyphs into consecutive ranges, terminated with `(0, 0)` since they're handed to Nuklear.
+    pub fn get_glyph_ranges(&self) -> Vec<(u32, u32)> {
+        to_nuklear_ranges(self.glyphs.iter().copied())
+    }
+
+    /// グリフをそれを持つ最初のフォントに振り分け、フォントごとの範囲を返す。<br />
+    /// どのフォントにもない文字は、豆腐を表示させるために最初のフォントに回す。<br />
+    /// Assign each glyph to the first font that has it, returning the ranges per font.<br />
+    /// Characters no font has go to the first font so that it shows its missing glyph box.
+    pub fn get_font_glyph_ranges(&self, coverages: &[FontCoverage]) -> Vec<Vec<(u32, u32)>> {
+        let mut glyphs = vec![vec![]; coverages.len().max(1)];
+        for glyph in self.glyphs.iter().copied() {
+            let font_index = coverages
+                .iter()
+                .position(|coverage| coverage.contains(glyph))
+                .unwrap_or_default();
+            glyphs[font_index].push(glyph);
+        }
+        glyphs.into_iter().map(to_nuklear_ranges).collect()
+    }
+
+    /// テキストの中でまだベイクされていない文字を加える。加えた文字があれば`true`を返す。<br />
+    /// Add the characters in the text that haven't been baked yet. Returns `true` if any was added.
+    pub fn request_text(&mut self, text: &str) -> bool {
+        let mut is_added = false;
+        for c in text.chars().filter(|c| !c.is_control()) {
+            if self.glyphs.len() >= MAX_CACHED_GLYPHS {
+                break;
+            }
+            is_added |= self.glyphs.insert(c as u32);
+        }
+        self.is_dirty |= is_added;
+        is_added
+    }
+
+    pub fn request_size(&mut self, font_size: u8) -> bool {
+        let is_added = self.sizes.insert(font_size);
+        self.is_dirty |= is_added;
+        is_added
+    }
+
+    /// ベイクし直す必要があるかを返し、その印を消す。<br />
+    /// Return whether the atlas has to be baked again, clearing the mark.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.is_dirty, false)
+    }
+}
+
+/// 昇順のグリフを連続した範囲にまとめ、Nuklearの終端の`(0, 0)`を付ける。<br />
+/// Merge glyphs in ascending order into consecutive ranges, appending Nuklear's `(0, 0)` terminator.
+fn to_nuklear_ranges<I>(glyphs: I) -> Vec<(u32, u32)>
+where
+    I: IntoIterator<Item = u32>,
+{
+    let mut ranges: Vec<(u32, u32)> = vec![];
+    for glyph in glyphs.into_iter() {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == glyph => *end = glyph,
+            _ => ranges.push((glyph, glyph)),
+        }
+    }
+    ranges.push((0, 0));
+    ranges
+}
+
+/// 求めた大きさに最も近い大きさを探す。同じ差なら小さい方を選ぶ。<br />
+/// Find the size closest to the requested one, preferring the smaller one on a tie.
+pub fn find_nearest_size<I>(sizes: I, font_size: u8) -> Option<u8>
+where
+    I: IntoIterator<Item = u8>,
+{
+    sizes
+        .into_iter()
+        .min_by_key(|size| ((*size as i16 - font_size as i16).abs(), *size))
+}
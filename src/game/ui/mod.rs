@@ -1,2 +1,17 @@
+pub mod console;
+#[cfg(target_os = "windows")]
+pub mod dx12;
+pub mod font;
+pub mod font_coverage;
+pub mod glyph_cache;
+pub mod rich_text;
+pub mod tweak;
 pub mod vk;
+pub mod window;
+pub use console::{Console, ConsoleCommand, ConsoleCommandInfo, ConsoleReply, CONSOLE_TOGGLE_CHAR};
+pub use font_coverage::FontCoverage;
+pub use glyph_cache::{GlyphCache, DEFAULT_FONT_SIZES};
+pub use rich_text::{LinkTarget, RichSpan};
+pub use tweak::{TweakParameter, TweakRegistry, TweakValue, TWEAK_CONFIG_PATH};
 pub use vk::*;
+pub use window::{UiWindow, WindowBounds, WindowManager};
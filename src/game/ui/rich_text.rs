@@ -0,0 +1,236 @@
+/// 絵文字のアイコンを横に並べたアトラスの画像。<br />
+/// Image of the atlas with emoji icons laid out horizontally.
+pub const EMOJI_ATLAS_PATH: &str = "./textures/emoji.png";
+
+/// アトラスの中の一つの絵文字の大きさ（ピクセル）。<br />
+/// Size of a single emoji in the atlas, in pixels.
+pub const EMOJI_SIZE: u16 = 32;
+
+/// `:name:`で書ける絵文字の名前。アトラスの中の並びと同じ順。<br />
+/// Names of emojis written as `:name:`, in the same order as in the atlas.
+pub const EMOJI_NAMES: [&str; 8] = [
+    "smile",
+    "sad",
+    "laugh",
+    "heart",
+    "star",
+    "check",
+    "cross",
+    "exclamation",
+];
+
+/// システムのメッセージとリンクの色。<br />
+/// Colors of system messages and links.
+pub const SYSTEM_COLOR: [u8; 4] = [255, 204, 0, 255];
+pub const LINK_COLOR: [u8; 4] = [102, 178, 255, 255];
+
+/// 送り手がこの名前か空のメッセージはシステムのメッセージとして表示する。<br />
+/// Messages whose author has this name or is empty are shown as system messages.
+const SYSTEM_AUTHOR: &str = "System";
+
+/// 半角の文字の幅のフォントの大きさに対する割合。全角の文字はフォントの大きさと同じ幅とする。<br />
+/// Width of a half-width character relative to the font size. Full-width characters are as wide as the font size.
+const HALF_WIDTH_RATIO: f32 = 0.55;
+
+/// リンクをクリックした時の行き先。<br />
+/// Where a link leads when clicked.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkTarget {
+    Room(String),
+    Player(String),
+}
+
+/// 装飾されたテキストの一部分。<br />
+/// A piece of styled text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RichSpan {
+    /// 色がNoneの場合は既定の色で表示する。<br />
+    /// Shown in the default color if the color is None.
+    Text {
+        text: String,
+        color: Option<[u8; 4]>,
+    },
+    Link {
+        text: String,
+        target: LinkTarget,
+    },
+    /// `EMOJI_NAMES`の中の番号。<br />
+    /// Index into `EMOJI_NAMES`.
+    Emoji(usize),
+}
+
+impl RichSpan {
+    /// 表示する時の幅をフォントの大きさから見積もる。<br />
+    /// Estimate the displayed width from the font size.
+    pub fn estimate_width(&self, font_size: f32) -> f32 {
+        match self {
+            RichSpan::Text { text, .. } | RichSpan::Link { text, .. } => text
+                .chars()
+                .map(|c| {
+                    if is_full_width(c) {
+                        font_size
+                    } else {
+                        font_size * HALF_WIDTH_RATIO
+                    }
+                })
+                .sum(),
+            RichSpan::Emoji(_) => font_size,
+        }
+    }
+}
+
+fn is_full_width(c: char) -> bool {
+    ('\u{1100}'..='\u{115F}').contains(&c)
+        || ('\u{2E80}'..='\u{A4CF}').contains(&c)
+        || ('\u{AC00}'..='\u{D7A3}').contains(&c)
+        || ('\u{F900}'..='\u{FAFF}').contains(&c)
+        || ('\u{FF00}'..='\u{FF60}').contains(&c)
+        || ('\u{FFE0}'..='\u{FFE6}').contains(&c)
+}
+
+pub fn is_system_author(author: &str) -> bool {
+    author.is_empty() || author == SYSTEM_AUTHOR
+}
+
+/// チャットのメッセージを一行分の部分に分ける。<br />
+/// 送り手の名前はプレイヤーへのリンクになり、システムのメッセージは既定の色の代わりにシステムの色を使う。<br />
+/// Split a chat message into the pieces of a single line.<br />
+/// The author's name becomes a link to the player, and system messages use the system color instead of the default one.
+pub fn parse_message(author: &str, message: &str) -> Vec<RichSpan> {
+    if is_system_author(author) {
+        return parse_rich_text(message)
+            .into_iter()
+            .map(|span| match span {
+                RichSpan::Text { text, color: None } => RichSpan::Text {
+                    text,
+                    color: Some(SYSTEM_COLOR),
+                },
+                span => span,
+            })
+            .collect();
+    }
+    let mut spans = vec![
+        RichSpan::Link {
+            text: author.to_string(),
+            target: LinkTarget::Player(author.to_string()),
+        },
+        RichSpan::Text {
+            text: ":".to_string(),
+            color: None,
+        },
+    ];
+    spans.append(&mut parse_rich_text(message));
+    spans
+}
+
+/// 装飾の記法を解析する。<br />
+/// `{#rrggbb}`で色を変え、`{/}`で既定の色に戻す。`[room:ID]`は部屋へのリンク、`@name`はプレイヤーへのリンク、`:name:`は絵文字になる。<br />
+/// 解釈できない記法はそのまま文字として残す。<br />
+/// Parse the styling markup.<br />
+/// `{#rrggbb}` changes the color and `{/}` returns to the default one. `[room:ID]` links to a room, `@name` links to a player, and `:name:` becomes an emoji.<br />
+/// Markup that can't be interpreted is kept as plain text.
+pub fn parse_rich_text(text: &str) -> Vec<RichSpan> {
+    let mut spans = vec![];
+    let mut current = String::new();
+    let mut color = None;
+    let mut index = 0;
+    while index < text.len() {
+        let rest = &text[index..];
+        let at_word_start = text[..index]
+            .chars()
+            .next_back()
+            .map(|c| c.is_whitespace())
+            .unwrap_or(true);
+
+        let parsed = if rest.starts_with("{/}") {
+            Some((None, 3, Some(None)))
+        } else if let Some(new_color) = parse_color(rest) {
+            Some((None, 9, Some(Some(new_color))))
+        } else if let Some((span, length)) = parse_room_link(rest) {
+            Some((Some(span), length, None))
+        } else if let Some((span, length)) = parse_player_link(rest).filter(|_| at_word_start) {
+            Some((Some(span), length, None))
+        } else if let Some((span, length)) = parse_emoji(rest) {
+            Some((Some(span), length, None))
+        } else {
+            None
+        };
+
+        match parsed {
+            Some((span, length, new_color)) => {
+                flush_text(&mut spans, &mut current, color);
+                if let Some(span) = span {
+                    spans.push(span);
+                }
+                if let Some(new_color) = new_color {
+                    color = new_color;
+                }
+                index += length;
+            }
+            None => {
+                let c = rest.chars().next().expect("Failed to get next character.");
+                current.push(c);
+                index += c.len_utf8();
+            }
+        }
+    }
+    flush_text(&mut spans, &mut current, color);
+    spans
+}
+
+fn flush_text(spans: &mut Vec<RichSpan>, current: &mut String, color: Option<[u8; 4]>) {
+    if !current.is_empty() {
+        spans.push(RichSpan::Text {
+            text: std::mem::take(current),
+            color,
+        });
+    }
+}
+
+fn parse_color(text: &str) -> Option<[u8; 4]> {
+    let code = text.strip_prefix("{#")?.get(0..7)?;
+    if !code.ends_with('}') {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(code.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?, 255])
+}
+
+fn parse_room_link(text: &str) -> Option<(RichSpan, usize)> {
+    let body = text.strip_prefix("[room:")?;
+    let end = body.find(']')?;
+    let room_id = &body[..end];
+    if room_id.is_empty() || room_id.chars().any(|c| c.is_whitespace()) {
+        return None;
+    }
+    let span = RichSpan::Link {
+        text: format!("#{}", room_id),
+        target: LinkTarget::Room(room_id.to_string()),
+    };
+    Some((span, "[room:".len() + end + 1))
+}
+
+fn parse_player_link(text: &str) -> Option<(RichSpan, usize)> {
+    let body = text.strip_prefix('@')?;
+    let end = body
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.'))
+        .unwrap_or(body.len());
+    // 文末のピリオドは名前に含めない。
+    // A trailing period is not part of the name.
+    let name = body[..end].trim_end_matches('.');
+    if name.is_empty() {
+        return None;
+    }
+    let span = RichSpan::Link {
+        text: format!("@{}", name),
+        target: LinkTarget::Player(name.to_string()),
+    };
+    Some((span, 1 + name.len()))
+}
+
+fn parse_emoji(text: &str) -> Option<(RichSpan, usize)> {
+    let body = text.strip_prefix(':')?;
+    let end = body.find(':')?;
+    let index = EMOJI_NAMES.iter().position(|name| *name == &body[..end])?;
+    Some((RichSpan::Emoji(index), end + 2))
+}
@@ -0,0 +1,313 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// 調整パネルの値を保存する設定ファイル。<br />
+/// Config file where the values of the tweak panel are saved.
+pub const TWEAK_CONFIG_PATH: &str = "./tweaks.json";
+
+/// 調整できるパラメーターの値と範囲。<br />
+/// Value and range of a tweakable parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TweakValue {
+    Float {
+        value: f32,
+        min: f32,
+        max: f32,
+    },
+    Bool(bool),
+    /// `options`の中で選ばれている番号。<br />
+    /// Index of the selected entry in `options`.
+    Enum {
+        index: usize,
+        options: Vec<String>,
+    },
+}
+
+impl TweakValue {
+    /// 種類が同じ場合だけ、範囲に収めた新しい値を受け入れる。<br />
+    /// Accept a new value clamped to the range, only when the kinds match.
+    fn accept(&self, new_value: &TweakValue) -> Option<TweakValue> {
+        match (self, new_value) {
+            (TweakValue::Float { min, max, .. }, TweakValue::Float { value, .. }) => {
+                Some(TweakValue::Float {
+                    value: value.max(*min).min(*max),
+                    min: *min,
+                    max: *max,
+                })
+            }
+            (TweakValue::Bool(_), TweakValue::Bool(value)) => Some(TweakValue::Bool(*value)),
+            (TweakValue::Enum { options, .. }, TweakValue::Enum { index, .. })
+                if *index < options.len() =>
+            {
+                Some(TweakValue::Enum {
+                    index: *index,
+                    options: options.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn to_saved(&self) -> SavedTweak {
+        match self {
+            TweakValue::Float { value, .. } => SavedTweak::Float(*value),
+            TweakValue::Bool(value) => SavedTweak::Bool(*value),
+            TweakValue::Enum { index, options } => SavedTweak::Enum(options[*index].clone()),
+        }
+    }
+
+    fn with_saved(&self, saved: &SavedTweak) -> Option<TweakValue> {
+        let new_value = match (self, saved) {
+            (TweakValue::Float { min, max, .. }, SavedTweak::Float(value)) => TweakValue::Float {
+                value: *value,
+                min: *min,
+                max: *max,
+            },
+            (TweakValue::Bool(_), SavedTweak::Bool(value)) => TweakValue::Bool(*value),
+            (TweakValue::Enum { options, .. }, SavedTweak::Enum(name)) => TweakValue::Enum {
+                index: options.iter().position(|option| option == name)?,
+                options: options.clone(),
+            },
+            _ => return None,
+        };
+        self.accept(&new_value)
+    }
+}
+
+/// 設定ファイルに書く値。列挙型は番号ではなく名前で保存し、選択肢の並びが変わっても読めるようにする。<br />
+/// Value written to the config file. Enums are saved by name rather than index so they still load after the options are reordered.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum SavedTweak {
+    Bool(bool),
+    Float(f32),
+    Enum(String),
+}
+
+/// 名前の付いた調整できるパラメーター。<br />
+/// A named tweakable parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TweakParameter {
+    pub name: String,
+    pub value: TweakValue,
+}
+
+/// 各システムが登録したパラメーターを管理する。デバッグビルドではUIの調整パネルに自動的に並ぶ。<br />
+/// パネルで変えられた値は`take_changed`で取り出し、各システムに反映する。<br />
+/// Manages parameters registered by the systems. In debug builds they automatically appear in the tweak panel of the UI.<br />
+/// Values changed in the panel are taken with `take_changed` and applied to each system.
+#[derive(Clone, Debug, Default)]
+pub struct TweakRegistry {
+    parameters: Vec<TweakParameter>,
+    changed: Vec<String>,
+    /// 設定ファイルから読み込んだが、まだ登録されていない値。<br />
+    /// Values loaded from the config file that haven't been registered yet.
+    pending: HashMap<String, SavedTweak>,
+}
+
+impl TweakRegistry {
+    pub fn new() -> Self {
+        TweakRegistry {
+            parameters: vec![],
+            changed: vec![],
+            pending: HashMap::new(),
+        }
+    }
+
+    /// 範囲のある小数のパラメーターを登録し、今の値を返す。<br />
+    /// Register a float parameter with a range and return its current value.
+    pub fn register_f32(&mut self, name: &str, value: f32, min: f32, max: f32) -> f32 {
+        let value = TweakValue::Float {
+            value: value.max(min).min(max),
+            min,
+            max,
+        };
+        match self.register(name, value) {
+            TweakValue::Float { value, .. } => value,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn register_bool(&mut self, name: &str, value: bool) -> bool {
+        match self.register(name, TweakValue::Bool(value)) {
+            TweakValue::Bool(value) => value,
+            _ => unreachable!(),
+        }
+    }
+
+    /// 選択肢から一つを選ぶパラメーターを登録し、今選ばれている番号を返す。<br />
+    /// Register a parameter choosing one of the options and return the selected index.
+    pub fn register_enum(&mut self, name: &str, options: &[&str], index: usize) -> usize {
+        let value = TweakValue::Enum {
+            index: index.min(options.len().saturating_sub(1)),
+            options: options.iter().map(|option| option.to_string()).collect(),
+        };
+        match self.register(name, value) {
+            TweakValue::Enum { index, .. } => index,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TweakValue> {
+        self.parameters
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| &p.value)
+    }
+
+    pub fn get_f32(&self, name: &str) -> Option<f32> {
+        match self.get(name)? {
+            TweakValue::Float { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name)? {
+            TweakValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_enum(&self, name: &str) -> Option<usize> {
+        match self.get(name)? {
+            TweakValue::Enum { index, .. } => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// 登録されている順にパラメーターを返す。<br />
+    /// Get the parameters in the order they were registered.
+    pub fn get_parameters(&self) -> &[TweakParameter] {
+        &self.parameters
+    }
+
+    /// 値を変える。登録されていないか種類が違う場合は`false`を返す。<br />
+    /// Change a value. Returns `false` if the parameter isn't registered or the kind differs.
+    pub fn set(&mut self, name: &str, new_value: TweakValue) -> bool {
+        let parameter = match self.parameters.iter_mut().find(|p| p.name == name) {
+            Some(parameter) => parameter,
+            None => return false,
+        };
+        let new_value = match parameter.value.accept(&new_value) {
+            Some(value) => value,
+            None => return false,
+        };
+        if parameter.value != new_value {
+            parameter.value = new_value;
+            Self::mark_changed(&mut self.changed, name);
+        }
+        true
+    }
+
+    /// コンソールから文字列で値を変える。名前は大文字と小文字を区別せず、空白を`_`で書いてもよく、`/`の後だけでもよい。<br />
+    /// 変えたパラメーターの名前を返す。<br />
+    /// Change a value from a string in the console. Names are case-insensitive, may spell spaces as `_`, and may omit the part up to the last `/`.<br />
+    /// Returns the name of the changed parameter.
+    pub fn set_from_str(&mut self, name: &str, value: &str) -> anyhow::Result<String> {
+        let normalize = |name: &str| name.to_lowercase().replace(' ', "_");
+        let key = normalize(name);
+        let parameter = self
+            .parameters
+            .iter()
+            .find(|p| {
+                let full_name = normalize(&p.name);
+                full_name == key || full_name.rsplit('/').next() == Some(key.as_str())
+            })
+            .ok_or_else(|| anyhow::anyhow!("Unknown variable: {}.", name))?;
+        let new_value = match &parameter.value {
+            TweakValue::Float { min, max, .. } => TweakValue::Float {
+                value: value
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("{} is not a number.", value))?,
+                min: *min,
+                max: *max,
+            },
+            TweakValue::Bool(_) => TweakValue::Bool(match value.to_lowercase().as_str() {
+                "1" | "true" | "on" => true,
+                "0" | "false" | "off" => false,
+                _ => return Err(anyhow::anyhow!("{} is not a boolean.", value)),
+            }),
+            TweakValue::Enum { options, .. } => TweakValue::Enum {
+                index: options
+                    .iter()
+                    .position(|option| option.eq_ignore_ascii_case(value))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("{} is not one of {}.", value, options.join(", "))
+                    })?,
+                options: options.clone(),
+            },
+        };
+        let name = parameter.name.clone();
+        self.set(&name, new_value);
+        Ok(name)
+    }
+
+    /// 前回から変わったパラメーターの名前を取り出す。<br />
+    /// Take the names of the parameters changed since the last call.
+    pub fn take_changed(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// 設定ファイルから値を読み込む。まだ登録されていないパラメーターの値は登録された時に使う。<br />
+    /// Load values from a config file. Values of parameters not registered yet are used when they are registered.
+    pub fn load(&mut self, file_name: &str) -> anyhow::Result<()> {
+        let file = std::fs::File::open(file_name)?;
+        let saved: HashMap<String, SavedTweak> =
+            serde_json::from_reader(std::io::BufReader::new(file))?;
+        for (name, saved) in saved.into_iter() {
+            match self.parameters.iter_mut().find(|p| p.name == name) {
+                Some(parameter) => {
+                    if let Some(value) = parameter.value.with_saved(&saved) {
+                        if parameter.value != value {
+                            parameter.value = value;
+                            Self::mark_changed(&mut self.changed, &name);
+                        }
+                    }
+                }
+                None => {
+                    self.pending.insert(name, saved);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 全てのパラメーターの今の値を設定ファイルに書く。<br />
+    /// Write the current values of all parameters to a config file.
+    pub fn save(&self, file_name: &str) -> anyhow::Result<()> {
+        let saved = self
+            .parameters
+            .iter()
+            .map(|p| (p.name.as_str(), p.value.to_saved()))
+            .collect::<BTreeMap<_, _>>();
+        let file = std::fs::File::create(file_name)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), &saved)?;
+        Ok(())
+    }
+
+    /// 同じ名前のパラメーターがあれば、範囲などを更新して今の値を残す。<br />
+    /// If a parameter with the same name exists, its range and so on are updated while the current value is kept.
+    fn register(&mut self, name: &str, value: TweakValue) -> TweakValue {
+        if let Some(parameter) = self.parameters.iter_mut().find(|p| p.name == name) {
+            parameter.value = value.accept(&parameter.value).unwrap_or(value);
+            return parameter.value.clone();
+        }
+        let value = self
+            .pending
+            .remove(name)
+            .and_then(|saved| value.with_saved(&saved))
+            .unwrap_or(value);
+        self.parameters.push(TweakParameter {
+            name: name.to_string(),
+            value: value.clone(),
+        });
+        value
+    }
+
+    fn mark_changed(changed: &mut Vec<String>, name: &str) {
+        if !changed.iter().any(|n| n == name) {
+            changed.push(name.to_string());
+        }
+    }
+}
@@ -4,6 +4,8 @@ use ash::vk::{
     MemoryPropertyFlags, PhysicalDevice, SharingMode,
 };
 
+use crate::game::graphics::vk::{GraphicsError, VkResultExt};
+
 #[derive(Copy, Clone, Debug)]
 pub struct Buffer {
     pub buffer: ash::vk::Buffer,
@@ -20,7 +22,7 @@ impl Buffer {
         physical_device: PhysicalDevice,
         usage_flag: BufferUsageFlags,
         memory_properties: MemoryPropertyFlags,
-    ) -> Self {
+    ) -> Result<Self, GraphicsError> {
         let buffer_info = BufferCreateInfo::builder()
             .usage(usage_flag)
             .sharing_mode(SharingMode::EXCLUSIVE)
@@ -29,7 +31,7 @@ impl Buffer {
         unsafe {
             let buffer = device
                 .create_buffer(&buffer_info, None)
-                .expect("Failed to create buffer for Nuklear.");
+                .or_graphics_error("create buffer for Nuklear")?;
             let memory_requirements = device.get_buffer_memory_requirements(buffer);
             let allocation_info = MemoryAllocateInfo::builder()
                 .allocation_size(memory_requirements.size)
@@ -41,16 +43,16 @@ impl Buffer {
                 ));
             let device_memory = device
                 .allocate_memory(&allocation_info, None)
-                .expect("Failed to allocate memory for staging buffer.");
+                .or_graphics_error("allocate memory for Nuklear buffer")?;
             device
                 .bind_buffer_memory(buffer, device_memory, 0)
-                .expect("Failed to bind buffer memory.");
-            Buffer {
+                .or_graphics_error("bind buffer memory for Nuklear")?;
+            Ok(Buffer {
                 buffer,
                 device_memory,
                 mapped_memory: std::ptr::null_mut(),
                 buffer_size,
-            }
+            })
         }
     }
 
@@ -3,14 +3,18 @@ pub mod texture;
 pub use buffer::Buffer;
 pub use texture::Texture;
 
+use super::font::{get_nearest_font, setup_font_atlas, FontFaces};
+use super::glyph_cache::{GlyphCache, DEFAULT_FONT_SIZES};
+use crate::game::graphics::vk::{GraphicsError, VkResultExt};
+use crate::game::traits::UiDrawerBackend;
 use ash::version::DeviceV1_0;
 use ash::vk::*;
 use ash::Device;
 use image::GenericImageView;
 use nuklear::{
-    font_cyrillic_glyph_ranges, Buffer as NkBuffer, Context, ConvertConfig, DrawNullTexture,
-    DrawVertexLayoutAttribute, DrawVertexLayoutElements, DrawVertexLayoutFormat, FontAtlas,
-    FontAtlasFormat, FontConfig, FontID, Handle, Size, UserFont, Vec2,
+    Buffer as NkBuffer, Context, ConvertConfig, DrawNullTexture, DrawVertexLayoutAttribute,
+    DrawVertexLayoutElements, DrawVertexLayoutFormat, FontAtlas, FontAtlasFormat, FontID, Handle,
+    Size, UserFont, Vec2,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -23,6 +27,28 @@ struct Vertex {
 
 type Ortho = [[f32; 4]; 4];
 
+/// フォントのアトラス以外に追加できるテクスチャの最大数。<br />
+/// Maximum number of textures that can be added besides the font atlas.
+const MAX_TEXTURES: u32 = 64;
+
+/// UIの頂点シェーダー。<br />
+/// Vertex shader of the UI.
+pub const UI_VERTEX_SHADER: &str = "./shaders/ui_vert.spv";
+
+/// UIのフラグメントシェーダー。<br />
+/// Fragment shader of the UI.
+pub const UI_FRAGMENT_SHADER: &str = "./shaders/ui_frag.spv";
+
+/// Vulkanで一フレームのUIを描画する先。<br />
+/// Where a frame of the UI is drawn with Vulkan.
+pub struct DrawTarget {
+    pub framebuffer: Framebuffer,
+    pub viewport: Viewport,
+    /// UIを描画する前に待つセマフォ。<br />
+    /// Semaphore waited on before drawing the UI.
+    pub wait_semaphore: Semaphore,
+}
+
 pub struct Drawer {
     pub allocator: nuklear::Allocator,
     pub draw_null_texture: DrawNullTexture,
@@ -51,11 +77,18 @@ pub struct Drawer {
     descriptor_set_layout: DescriptorSetLayout,
     descriptor_set: DescriptorSet,
     layout_elements: DrawVertexLayoutElements,
-    font_config: FontConfig,
+    font_faces: FontFaces,
     font_atlas: FontAtlas,
     fonts: HashMap<u8, FontID>,
+    glyph_cache: GlyphCache,
+    /// アトラスが参照するフォントごとの文字の範囲。アトラスの後に解放する。<br />
+    /// Glyph ranges per font referred to by the atlas, released after it.
+    glyph_ranges: Vec<Vec<(u32, u32)>>,
     textures: Vec<Texture>,
     texture_ids: Vec<Handle>,
+    /// 追加したテクスチャごとのデスクリプターセット。ハンドルの番号から一を引いた位置にある。<br />
+    /// Descriptor set of each added texture, located at the handle's ID minus one.
+    texture_descriptor_sets: Vec<DescriptorSet>,
 }
 
 impl Drawer {
@@ -71,10 +104,11 @@ impl Drawer {
         vertex_buffer_size: u64,
         index_buffer_size: u64,
         nk_command_buffer_size: usize,
-        font_bytes: &[u8],
-    ) -> Self {
-        let semaphore = Self::create_semaphore(&*device);
-        let fence = Self::create_fence(&*device);
+        font_bytes: &[Vec<u8>],
+        glyph_ranges: &[(u32, u32)],
+    ) -> Result<Self, GraphicsError> {
+        let semaphore = Self::create_semaphore(&*device)?;
+        let fence = Self::create_fence(&*device)?;
         let renderpass =
             Self::create_renderpass(&*device, color_format, depth_format, sample_count);
         let vertex_buffer = Buffer::new(
@@ -84,7 +118,7 @@ impl Drawer {
             physical_device,
             BufferUsageFlags::VERTEX_BUFFER,
             MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE,
-        );
+        )?;
         let index_buffer = Buffer::new(
             &*device,
             index_buffer_size,
@@ -92,7 +126,7 @@ impl Drawer {
             physical_device,
             BufferUsageFlags::INDEX_BUFFER,
             MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE,
-        );
+        )?;
         let uniform_buffer = Buffer::new(
             &*device,
             std::mem::size_of::<glam::Mat4>() as u64,
@@ -100,46 +134,30 @@ impl Drawer {
             physical_device,
             BufferUsageFlags::UNIFORM_BUFFER,
             MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::HOST_VISIBLE,
-        );
-        let descriptor_pool = Self::create_descriptor_pool(&*device);
-        let descriptor_set_layout = Self::create_descriptor_set_layout(&*device);
+        )?;
+        let descriptor_pool = Self::create_descriptor_pool(&*device)?;
+        let descriptor_set_layout = Self::create_descriptor_set_layout(&*device)?;
         let layouts = [descriptor_set_layout];
-        let descriptor_set = Self::create_descriptor_set(&*device, descriptor_pool, &layouts[0..]);
-        let pipeline_layout = Self::create_pipeline_layout(&*device, &layouts[0..]);
-        let vertex_shader = Self::create_shader_module(&*device, "shaders/ui_vert.spv");
-        let fragment_shader = Self::create_shader_module(&*device, "shaders/ui_frag.spv");
-
-        let name = std::ffi::CString::new("main").expect("Failed to create CString for shader.");
-        let mut shader_stage_info = vec![PipelineShaderStageCreateInfo::builder()
-            .stage(ShaderStageFlags::VERTEX)
-            .name(name.as_c_str())
-            .module(vertex_shader)
-            .build()];
-        shader_stage_info.push(
-            PipelineShaderStageCreateInfo::builder()
-                .stage(ShaderStageFlags::FRAGMENT)
-                .name(name.as_c_str())
-                .module(fragment_shader)
-                .build(),
-        );
-        let pipeline = Self::create_pipeline(
-            &*device,
-            sample_count,
-            pipeline_layout,
-            renderpass,
-            shader_stage_info.as_slice(),
-        );
+        let descriptor_set = Self::create_descriptor_set(&*device, descriptor_pool, &layouts[0..])?;
+        let pipeline_layout = Self::create_pipeline_layout(&*device, &layouts[0..])?;
+        let pipeline =
+            Self::create_ui_pipeline(&*device, sample_count, pipeline_layout, renderpass);
 
-        device.destroy_shader_module(vertex_shader, None);
-        device.destroy_shader_module(fragment_shader, None);
-
-        let command_pool = Self::create_command_pool(&*device, graphics_queue_index);
-        let command_buffer = Self::allocate_command_buffers(&*device, command_pool);
+        let command_pool = Self::create_command_pool(&*device, graphics_queue_index)?;
+        let command_buffer = Self::allocate_command_buffers(&*device, command_pool)?;
         let mut nk_allocator = nuklear::Allocator::new_vec();
-        let mut font_config = Self::create_font_config(font_bytes);
-        let (mut atlas, fonts) = Self::setup_font_atlas(&mut nk_allocator, &mut font_config);
+        let mut font_faces = FontFaces::new(font_bytes);
+        let glyph_cache = GlyphCache::with_ranges(&DEFAULT_FONT_SIZES, glyph_ranges);
+        let glyph_ranges = glyph_cache.get_font_glyph_ranges(font_faces.get_coverages());
+        let (mut atlas, fonts) = setup_font_atlas(
+            &mut nk_allocator,
+            &mut font_faces,
+            &glyph_ranges,
+            &glyph_cache.get_sizes(),
+        );
         let mut draw_null_texture = DrawNullTexture::default();
-        let (font_image, font_sampler) = Self::bake_font(
+        let font_sampler = Self::create_font_sampler(&*device)?;
+        let font_image = Self::bake_font(
             &mut atlas,
             &*device,
             &*instance,
@@ -147,7 +165,7 @@ impl Drawer {
             command_pool,
             graphics_queue,
             &mut draw_null_texture,
-        );
+        )?;
         Self::update_write_descriptor_set(
             &uniform_buffer,
             &font_image,
@@ -156,7 +174,7 @@ impl Drawer {
             &*device,
         );
 
-        Drawer {
+        Ok(Drawer {
             nuklear_buffer: nuklear::Buffer::with_size(&mut nk_allocator, nk_command_buffer_size),
             draw_null_texture,
             logical_device: device,
@@ -202,57 +220,175 @@ impl Drawer {
                     0,
                 ),
             ]),
-            font_config,
+            font_faces,
             renderpass,
             sample_count,
             font_atlas: atlas,
             fonts,
+            glyph_cache,
+            glyph_ranges,
             allocator: nk_allocator,
             textures: vec![],
             texture_ids: vec![],
-        }
+            texture_descriptor_sets: vec![],
+        })
     }
 
-    pub fn add_texture_from_file(&mut self, file_name: &str) {
-        let raw_bytes = std::fs::read(file_name).expect("Failed to open texture file for Nuklear.");
-        let texture = Self::create_texture(
+    /// MSAAのサンプル数を変える。レンダーパスとパイプラインを作り直すので、描画していない時に呼ぶこと。<br />
+    /// Change the MSAA sample count. The renderpass and the pipeline are recreated, so this must be called while nothing is being drawn.
+    pub unsafe fn set_sample_count(&mut self, sample_count: SampleCountFlags) {
+        if self.sample_count == sample_count {
+            return;
+        }
+        self.logical_device.destroy_pipeline(self.pipeline, None);
+        self.logical_device
+            .destroy_render_pass(self.renderpass, None);
+        self.renderpass = Self::create_renderpass(
             &*self.logical_device,
-            &*self.instance,
-            self.physical_device,
-            self.command_pool,
-            self.graphics_queue,
-            raw_bytes.as_slice(),
             self.color_format,
+            self.depth_format,
+            sample_count,
         );
-        self.textures.push(texture);
-        let handle = Handle::from_id(self.textures.len() as i32);
-        self.texture_ids.push(handle);
+        self.pipeline = Self::create_ui_pipeline(
+            &*self.logical_device,
+            sample_count,
+            self.pipeline_layout,
+            self.renderpass,
+        );
+        self.sample_count = sample_count;
     }
 
-    pub fn add_texture_from_image(&mut self, image: crate::game::Image) {
-        self.textures.push(Texture {
+    unsafe fn create_ui_pipeline(
+        device: &ash::Device,
+        sample_count: SampleCountFlags,
+        pipeline_layout: PipelineLayout,
+        renderpass: RenderPass,
+    ) -> Pipeline {
+        let vertex_shader = Self::create_shader_module(device, UI_VERTEX_SHADER);
+        let fragment_shader = Self::create_shader_module(device, UI_FRAGMENT_SHADER);
+
+        let name = std::ffi::CString::new("main").expect("Failed to create CString for shader.");
+        let mut shader_stage_info = vec![PipelineShaderStageCreateInfo::builder()
+            .stage(ShaderStageFlags::VERTEX)
+            .name(name.as_c_str())
+            .module(vertex_shader)
+            .build()];
+        shader_stage_info.push(
+            PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::FRAGMENT)
+                .name(name.as_c_str())
+                .module(fragment_shader)
+                .build(),
+        );
+        let pipeline = Self::create_pipeline(
+            device,
+            sample_count,
+            pipeline_layout,
+            renderpass,
+            shader_stage_info.as_slice(),
+        );
+
+        device.destroy_shader_module(vertex_shader, None);
+        device.destroy_shader_module(fragment_shader, None);
+
+        pipeline
+    }
+
+    pub fn add_texture_from_image(&mut self, image: crate::game::Image) -> Handle {
+        self.push_texture(Texture {
             image: image.image,
             image_view: image.image_view,
             device_memory: image.device_memory,
-        });
+        })
+    }
+
+    /// テクスチャを登録し、描画コマンドから選べるようにデスクリプターセットを用意する。<br />
+    /// 上限を超えた場合はフォントのアトラスのハンドルを返す。<br />
+    /// Register a texture and prepare its descriptor set so draw commands can select it.<br />
+    /// Returns the handle of the font atlas when the limit is exceeded.
+    fn push_texture(&mut self, texture: Texture) -> Handle {
+        if self.textures.len() as u32 >= MAX_TEXTURES {
+            log::error!("Too many textures for Nuklear. Maximum: {}", MAX_TEXTURES);
+            return Handle::from_id(0);
+        }
+        let layouts = [self.descriptor_set_layout];
+        let descriptor_set = match Self::create_descriptor_set(
+            &*self.logical_device,
+            self.descriptor_pool,
+            &layouts[0..],
+        ) {
+            Ok(descriptor_set) => descriptor_set,
+            Err(e) => {
+                log::error!("Failed to add a texture for Nuklear: {}", e);
+                return Handle::from_id(0);
+            }
+        };
+        Self::update_write_descriptor_set(
+            &self.uniform_buffer,
+            &texture,
+            self.font_sampler,
+            descriptor_set,
+            &*self.logical_device,
+        );
+        self.textures.push(texture);
+        self.texture_descriptor_sets.push(descriptor_set);
         let handle = Handle::from_id(self.textures.len() as i32);
         self.texture_ids.push(handle);
+        handle
+    }
+
+    /// 描画コマンドのテクスチャのハンドルに対応するデスクリプターセットを求める。<br />
+    /// Get the descriptor set matching the texture handle of a draw command.
+    fn get_texture_descriptor_set(&self, mut texture: Handle) -> DescriptorSet {
+        texture
+            .id()
+            .filter(|id| *id > 0)
+            .and_then(|id| self.texture_descriptor_sets.get(id as usize - 1))
+            .copied()
+            .unwrap_or(self.descriptor_set)
+    }
+}
+
+impl UiDrawerBackend for Drawer {
+    type RenderTarget = DrawTarget;
+    type Signal = Semaphore;
+
+    fn add_texture_from_file(&mut self, file_name: &str) -> Handle {
+        let raw_bytes = std::fs::read(file_name).expect("Failed to open texture file for Nuklear.");
+        match Self::create_texture(
+            &*self.logical_device,
+            &*self.instance,
+            self.physical_device,
+            self.command_pool,
+            self.graphics_queue,
+            raw_bytes.as_slice(),
+            self.color_format,
+        ) {
+            Ok(texture) => self.push_texture(texture),
+            Err(e) => {
+                log::error!("Failed to create a texture for Nuklear: {}", e);
+                Handle::from_id(0)
+            }
+        }
     }
 
-    pub fn create_context(&mut self, font_size: u8) -> Context {
+    fn create_context(&mut self, font_size: u8) -> Context {
         let font = self.get_font(font_size).clone();
         Context::new(&mut self.allocator, &font)
     }
 
-    pub fn draw(
+    fn draw(
         &mut self,
-        framebuffer: Framebuffer,
-        viewport: Viewport,
+        target: Self::RenderTarget,
         scale: Vec2,
         context: &mut Context,
         convert_config: &mut ConvertConfig,
-        wait_semaphore: Semaphore,
-    ) -> Semaphore {
+    ) -> Self::Signal {
+        let DrawTarget {
+            framebuffer,
+            viewport,
+            wait_semaphore,
+        } = target;
         let cmd_begin_info =
             CommandBufferBeginInfo::builder().flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT);
         let renderpass_begin_info = RenderPassBeginInfo::builder()
@@ -318,11 +454,27 @@ impl Drawer {
             );
 
             let mut index_offset = 0;
+            let mut bound_descriptor_set = self.descriptor_set;
             for cmd in context.draw_command_iterator(&self.nuklear_buffer) {
                 if cmd.elem_count() < 1 {
                     continue;
                 }
-                let scissors = vec![Rect2D {
+                // 絵文字などの画像はフォントと別のテクスチャを使う。
+                // Images such as emojis use a texture other than the font's.
+                let descriptor_set = self.get_texture_descriptor_set(cmd.texture());
+                if descriptor_set != bound_descriptor_set {
+                    let descriptor_sets = [descriptor_set];
+                    device.cmd_bind_descriptor_sets(
+                        cmd_buffer,
+                        PipelineBindPoint::GRAPHICS,
+                        self.pipeline_layout,
+                        0,
+                        &descriptor_sets[0..],
+                        &[],
+                    );
+                    bound_descriptor_set = descriptor_set;
+                }
+                let scissors = [Rect2D {
                     offset: Offset2D {
                         x: ((cmd.clip_rect().x * scale.x) as i32).max(0),
                         y: ((cmd.clip_rect().y * scale.y) as i32).max(0),
@@ -332,7 +484,7 @@ impl Drawer {
                         height: (cmd.clip_rect().h * scale.y) as u32,
                     },
                 }];
-                device.cmd_set_scissor(cmd_buffer, 0, scissors.as_slice());
+                device.cmd_set_scissor(cmd_buffer, 0, &scissors[0..]);
                 device.cmd_draw_indexed(cmd_buffer, cmd.elem_count(), 1, index_offset, 0, 0);
                 index_offset += cmd.elem_count();
             }
@@ -346,7 +498,7 @@ impl Drawer {
             let cmd_buffers = [cmd_buffer];
             let signal_semaphore = [self.render_completed];
             let wait_semaphores = [wait_semaphore];
-            let submit_info = vec![SubmitInfo::builder()
+            let submit_info = [SubmitInfo::builder()
                 .command_buffers(&cmd_buffers[0..])
                 .signal_semaphores(&signal_semaphore[0..])
                 .wait_dst_stage_mask(&wait_stages[0..])
@@ -356,7 +508,7 @@ impl Drawer {
             device
                 .queue_submit(
                     self.graphics_queue,
-                    submit_info.as_slice(),
+                    &submit_info[0..],
                     self.command_finished,
                 )
                 .expect("Failed to submit queue for Nuklear.");
@@ -364,32 +516,35 @@ impl Drawer {
         }
     }
 
-    pub fn get_font(&self, font_size: u8) -> &UserFont {
-        let font_id = self
-            .fonts
-            .get(&font_size)
-            .expect("Failed to get requested font size in Nuklear.");
+    fn get_draw_null_texture(&self) -> DrawNullTexture {
+        self.draw_null_texture.clone()
+    }
+
+    fn get_font(&self, font_size: u8) -> &UserFont {
+        let font_id = get_nearest_font(&self.fonts, font_size);
         self.font_atlas
-            .font(*font_id)
+            .font(font_id)
             .expect("Failed to get font in the font atlas.")
             .handle()
     }
 
-    pub fn set_font_size(&mut self, context: &mut Context, font_size: u8) {
-        let font_id = self
-            .fonts
-            .get(&font_size)
-            .expect("Failed to get requested font size in Nuklear.");
+    fn request_glyphs(&mut self, text: &str) {
+        self.glyph_cache.request_text(text);
+    }
+
+    fn set_font_size(&mut self, context: &mut Context, font_size: u8) {
+        self.glyph_cache.request_size(font_size);
+        let font_id = get_nearest_font(&self.fonts, font_size);
         let atlas = &mut self.font_atlas;
         context.style_set_font(
             atlas
-                .font(*font_id)
+                .font(font_id)
                 .expect("Failed to get font in the font atlas.")
                 .handle(),
         );
     }
 
-    pub fn update(
+    fn update(
         &mut self,
         width: u32,
         height: u32,
@@ -441,16 +596,35 @@ impl Drawer {
         }
     }
 
-    pub fn wait_idle(&self) {
+    fn update_font_atlas(&mut self, context: &mut Context, font_size: u8) -> bool {
+        if !self.glyph_cache.take_dirty() {
+            return false;
+        }
+        unsafe {
+            self.rebake_font();
+        }
+        self.set_font_size(context, font_size);
+        true
+    }
+
+    fn wait_idle(&self) {
         unsafe {
             let fences = [self.command_finished];
-            self.logical_device
+            if let Err(e) = self
+                .logical_device
                 .wait_for_fences(&fences[0..], true, u64::MAX)
-                .expect("Failed to wait for fences for Nuklear.");
+            {
+                log::warn!("Failed to wait for fences for Nuklear: {}", e);
+            }
         }
     }
+}
 
-    fn allocate_command_buffers(device: &ash::Device, command_pool: CommandPool) -> CommandBuffer {
+impl Drawer {
+    fn allocate_command_buffers(
+        device: &ash::Device,
+        command_pool: CommandPool,
+    ) -> Result<CommandBuffer, GraphicsError> {
         let allocate_info = CommandBufferAllocateInfo::builder()
             .command_pool(command_pool)
             .command_buffer_count(1)
@@ -458,8 +632,8 @@ impl Drawer {
         unsafe {
             let command_buffers = device
                 .allocate_command_buffers(&allocate_info)
-                .expect("Failed to allocate command buffers for Nuklear.");
-            command_buffers[0]
+                .or_graphics_error("allocate command buffers for Nuklear")?;
+            Ok(command_buffers[0])
         }
     }
 
@@ -471,9 +645,9 @@ impl Drawer {
         command_pool: CommandPool,
         queue: Queue,
         null_texture: &mut DrawNullTexture,
-    ) -> (Texture, Sampler) {
+    ) -> Result<Texture, GraphicsError> {
         let (bytes, width, height) = atlas.bake(FontAtlasFormat::Rgba32);
-        let (font_texture, sampler) = Self::upload_atlas(
+        let font_texture = Self::upload_atlas(
             bytes,
             width,
             height,
@@ -482,39 +656,45 @@ impl Drawer {
             physical_device,
             command_pool,
             queue,
-        );
+        )?;
         let id = Handle::from_id(0);
         atlas.end(id, Some(null_texture));
-        (font_texture, sampler)
+        Ok(font_texture)
     }
 
-    fn create_command_pool(device: &ash::Device, queue_index: u32) -> CommandPool {
+    fn create_command_pool(
+        device: &ash::Device,
+        queue_index: u32,
+    ) -> Result<CommandPool, GraphicsError> {
         let pool_info = CommandPoolCreateInfo::builder().queue_family_index(queue_index);
         unsafe {
             device
                 .create_command_pool(&pool_info, None)
-                .expect("Failed to create command pool for Nuklear.")
+                .or_graphics_error("create command pool for Nuklear")
         }
     }
 
-    fn create_descriptor_pool(device: &ash::Device) -> DescriptorPool {
+    fn create_descriptor_pool(device: &ash::Device) -> Result<DescriptorPool, GraphicsError> {
+        // フォントのアトラスと、追加できる全てのテクスチャの分。
+        // One for the font atlas, plus every texture that can be added.
+        let set_count = MAX_TEXTURES + 1;
         let mut pool_sizes = vec![DescriptorPoolSize::builder()
-            .descriptor_count(1)
+            .descriptor_count(set_count)
             .ty(DescriptorType::UNIFORM_BUFFER)
             .build()];
         pool_sizes.push(
             DescriptorPoolSize::builder()
-                .descriptor_count(1)
+                .descriptor_count(set_count)
                 .ty(DescriptorType::COMBINED_IMAGE_SAMPLER)
                 .build(),
         );
         let pool_info = DescriptorPoolCreateInfo::builder()
             .pool_sizes(pool_sizes.as_slice())
-            .max_sets(1);
+            .max_sets(set_count);
         unsafe {
             device
                 .create_descriptor_pool(&pool_info, None)
-                .expect("Failed to create descriptor pool for Nuklear.")
+                .or_graphics_error("create descriptor pool for Nuklear")
         }
     }
 
@@ -522,19 +702,21 @@ impl Drawer {
         device: &ash::Device,
         descriptor_pool: DescriptorPool,
         descriptor_set_layout: &[DescriptorSetLayout],
-    ) -> DescriptorSet {
+    ) -> Result<DescriptorSet, GraphicsError> {
         let allocate_info = DescriptorSetAllocateInfo::builder()
             .descriptor_pool(descriptor_pool)
             .set_layouts(descriptor_set_layout);
         unsafe {
             let sets = device
                 .allocate_descriptor_sets(&allocate_info)
-                .expect("Failed to allocate descriptor set for Nuklear.");
-            sets[0]
+                .or_graphics_error("allocate descriptor set for Nuklear")?;
+            Ok(sets[0])
         }
     }
 
-    fn create_descriptor_set_layout(device: &ash::Device) -> DescriptorSetLayout {
+    fn create_descriptor_set_layout(
+        device: &ash::Device,
+    ) -> Result<DescriptorSetLayout, GraphicsError> {
         let mut layout_bindings = vec![DescriptorSetLayoutBinding::builder()
             .binding(0)
             .descriptor_count(1)
@@ -554,26 +736,44 @@ impl Drawer {
         unsafe {
             device
                 .create_descriptor_set_layout(&layout_info, None)
-                .expect("Failed to create descriptor set layout for Nuklear.")
+                .or_graphics_error("create descriptor set layout for Nuklear")
         }
     }
 
-    fn create_fence(device: &ash::Device) -> Fence {
+    fn create_fence(device: &ash::Device) -> Result<Fence, GraphicsError> {
         let fence_info = FenceCreateInfo::builder().flags(FenceCreateFlags::SIGNALED);
         unsafe {
             device
                 .create_fence(&fence_info, None)
-                .expect("Failed to create fence for Nuklear.")
+                .or_graphics_error("create fence for Nuklear")
         }
     }
 
-    fn create_font_config(font_bytes: &[u8]) -> FontConfig {
-        let mut font_config = FontConfig::with_size(0.0);
-        font_config.set_oversample_h(3);
-        font_config.set_oversample_v(2);
-        font_config.set_glyph_range(font_cyrillic_glyph_ranges());
-        font_config.set_ttf(font_bytes);
-        font_config
+    /// フォントのアトラスと追加したテクスチャで共有するサンプラーを作る。<br />
+    /// Create the sampler shared by the font atlas and the added textures.
+    fn create_font_sampler(device: &ash::Device) -> Result<Sampler, GraphicsError> {
+        let sampler_info = SamplerCreateInfo::builder()
+            .unnormalized_coordinates(false)
+            .mipmap_mode(SamplerMipmapMode::LINEAR)
+            .mip_lod_bias(0.0)
+            .min_lod(0.0)
+            .min_filter(Filter::LINEAR)
+            .max_lod(0.0)
+            .max_anisotropy(1.0)
+            .mag_filter(Filter::LINEAR)
+            .compare_op(CompareOp::ALWAYS)
+            .compare_enable(false)
+            .border_color(BorderColor::FLOAT_OPAQUE_WHITE)
+            .anisotropy_enable(false)
+            .address_mode_u(SamplerAddressMode::REPEAT)
+            .address_mode_v(SamplerAddressMode::REPEAT)
+            .address_mode_w(SamplerAddressMode::REPEAT);
+
+        unsafe {
+            device
+                .create_sampler(&sampler_info, None)
+                .or_graphics_error("create sampler for Nuklear texture")
+        }
     }
 
     fn create_texture(
@@ -584,7 +784,7 @@ impl Drawer {
         graphics_queue: Queue,
         raw_data: &[u8],
         color_format: ash::vk::Format,
-    ) -> Texture {
+    ) -> Result<Texture, GraphicsError> {
         let img = image::load_from_memory(raw_data).expect("Failed to read texture from memory.");
         let (width, height) = img.dimensions();
         let mut texture = Texture::new(
@@ -604,7 +804,7 @@ impl Drawer {
             physical_device,
             BufferUsageFlags::TRANSFER_SRC,
             MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-        );
+        )?;
         let buffer_memory_requirements =
             unsafe { device.get_buffer_memory_requirements(staging_buffer.buffer) };
         let mapped = unsafe {
@@ -615,7 +815,7 @@ impl Drawer {
                     buffer_memory_requirements.size,
                     MemoryMapFlags::empty(),
                 )
-                .expect("Failed to map memory for staging buffer.")
+                .or_graphics_error("map memory for Nuklear staging buffer")?
         };
         let rgba_raw_data = img.to_rgba8();
         let bgra_raw_data = img.to_bgra8();
@@ -646,7 +846,7 @@ impl Drawer {
             device.free_memory(staging_buffer.device_memory, None);
             device.destroy_buffer(staging_buffer.buffer, None);
         }
-        texture
+        Ok(texture)
     }
 
     fn create_pipeline(
@@ -773,12 +973,12 @@ impl Drawer {
     fn create_pipeline_layout(
         device: &ash::Device,
         descriptor_set_layouts: &[DescriptorSetLayout],
-    ) -> PipelineLayout {
+    ) -> Result<PipelineLayout, GraphicsError> {
         let layout_info = PipelineLayoutCreateInfo::builder().set_layouts(descriptor_set_layouts);
         unsafe {
             device
                 .create_pipeline_layout(&layout_info, None)
-                .expect("Failed to create pipeline layout for Nuklear.")
+                .or_graphics_error("create pipeline layout for Nuklear")
         }
     }
 
@@ -788,15 +988,23 @@ impl Drawer {
         depth_format: Format,
         sample_count: SampleCountFlags,
     ) -> RenderPass {
+        // MSAAを使わない時はスワップチェーンに直接描画する。
+        // Without MSAA the UI is drawn onto the swapchain directly.
+        let is_multisampled = sample_count != SampleCountFlags::TYPE_1;
+        let color_layout = if is_multisampled {
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            ImageLayout::PRESENT_SRC_KHR
+        };
         let mut attachments = vec![AttachmentDescription::builder()
             .format(color_format)
-            .initial_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .initial_layout(color_layout)
             .samples(sample_count)
             .store_op(AttachmentStoreOp::STORE)
             .stencil_store_op(AttachmentStoreOp::DONT_CARE)
             .stencil_load_op(AttachmentLoadOp::DONT_CARE)
             .load_op(AttachmentLoadOp::LOAD)
-            .final_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .final_layout(color_layout)
             .build()];
 
         attachments.push(
@@ -812,18 +1020,20 @@ impl Drawer {
                 .build(),
         );
 
-        attachments.push(
-            AttachmentDescription::builder()
-                .format(color_format)
-                .initial_layout(ImageLayout::UNDEFINED)
-                .samples(SampleCountFlags::TYPE_1)
-                .store_op(AttachmentStoreOp::STORE)
-                .stencil_store_op(AttachmentStoreOp::DONT_CARE)
-                .stencil_load_op(AttachmentLoadOp::DONT_CARE)
-                .load_op(AttachmentLoadOp::DONT_CARE)
-                .final_layout(ImageLayout::PRESENT_SRC_KHR)
-                .build(),
-        );
+        if is_multisampled {
+            attachments.push(
+                AttachmentDescription::builder()
+                    .format(color_format)
+                    .initial_layout(ImageLayout::UNDEFINED)
+                    .samples(SampleCountFlags::TYPE_1)
+                    .store_op(AttachmentStoreOp::STORE)
+                    .stencil_store_op(AttachmentStoreOp::DONT_CARE)
+                    .stencil_load_op(AttachmentLoadOp::DONT_CARE)
+                    .load_op(AttachmentLoadOp::DONT_CARE)
+                    .final_layout(ImageLayout::PRESENT_SRC_KHR)
+                    .build(),
+            );
+        }
 
         let color_reference = vec![AttachmentReference::builder()
             .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
@@ -839,12 +1049,15 @@ impl Drawer {
             .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build()];
 
-        let subpass_description = vec![SubpassDescription::builder()
+        let mut subpass_description = SubpassDescription::builder()
             .pipeline_bind_point(PipelineBindPoint::GRAPHICS)
             .color_attachments(color_reference.as_slice())
-            .depth_stencil_attachment(&depth_reference)
-            .resolve_attachments(resolve_reference.as_slice())
-            .build()];
+            .depth_stencil_attachment(&depth_reference);
+        if is_multisampled {
+            subpass_description =
+                subpass_description.resolve_attachments(resolve_reference.as_slice());
+        }
+        let subpass_description = vec![subpass_description.build()];
 
         let mut subpass_dependencies = vec![SubpassDependency::builder()
             .dst_access_mask(AccessFlags::COLOR_ATTACHMENT_WRITE)
@@ -880,12 +1093,12 @@ impl Drawer {
         }
     }
 
-    fn create_semaphore(device: &ash::Device) -> Semaphore {
+    fn create_semaphore(device: &ash::Device) -> Result<Semaphore, GraphicsError> {
         let semaphore_info = SemaphoreCreateInfo::builder();
         unsafe {
             device
                 .create_semaphore(&semaphore_info, None)
-                .expect("Failed to create semaphore for Nuklear.")
+                .or_graphics_error("create semaphore for Nuklear")
         }
     }
 
@@ -902,24 +1115,59 @@ impl Drawer {
         }
     }
 
-    fn setup_font_atlas(
-        allocator: &mut nuklear::Allocator,
-        font_config: &mut FontConfig,
-    ) -> (FontAtlas, HashMap<u8, FontID>) {
-        let mut fonts = HashMap::new();
-        let mut atlas = FontAtlas::new(allocator);
-        atlas.begin();
-
-        for i in (12..48).step_by(4) {
-            font_config.set_ttf_data_owned_by_atlas(false);
-            font_config.set_size(i as f32);
-            let font = atlas
-                .add_font_with_config(&font_config)
-                .expect("Failed to load font into Nuklear runtime.");
-            fonts.insert(i, font);
-        }
-
-        (atlas, fonts)
+    /// 頼まれた文字と大きさでアトラスをベイクし直し、テクスチャを作り直す。<br />
+    /// 文字が増えるとアトラスも大きくなるので、古いテクスチャは描画が終わるのを待ってから破棄する。<br />
+    /// Bake the atlas again with the requested glyphs and sizes, and recreate its texture.<br />
+    /// The atlas grows with the glyphs, so the old texture is destroyed after drawing has finished.
+    unsafe fn rebake_font(&mut self) {
+        self.wait_idle();
+        let glyph_ranges = self
+            .glyph_cache
+            .get_font_glyph_ranges(self.font_faces.get_coverages());
+        let (atlas, fonts) = setup_font_atlas(
+            &mut self.allocator,
+            &mut self.font_faces,
+            &glyph_ranges,
+            &self.glyph_cache.get_sizes(),
+        );
+        // 古いアトラスを解放してから、それが参照していた範囲を入れ替える。
+        // Release the old atlas before replacing the ranges it referred to.
+        self.font_atlas = atlas;
+        self.glyph_ranges = glyph_ranges;
+        self.fonts = fonts;
+
+        let device = self.logical_device.clone();
+        let font_image = Self::bake_font(
+            &mut self.font_atlas,
+            &*device,
+            &*self.instance,
+            self.physical_device,
+            self.command_pool,
+            self.graphics_queue,
+            &mut self.draw_null_texture,
+        )
+        .expect("Failed to bake the font atlas for Nuklear.");
+        Self::update_write_descriptor_set(
+            &self.uniform_buffer,
+            &font_image,
+            self.font_sampler,
+            self.descriptor_set,
+            &*device,
+        );
+        let old_image = std::mem::replace(&mut self.font_image, font_image);
+        device.free_memory(old_image.device_memory, None);
+        device.destroy_image_view(old_image.image_view, None);
+        device.destroy_image(old_image.image, None);
+        log::info!(
+            "Baked {} glyphs in {} ranges across {} fonts and {} font sizes for Nuklear.",
+            self.glyph_cache.get_glyph_count(),
+            self.glyph_ranges
+                .iter()
+                .map(|ranges| ranges.len() - 1)
+                .sum::<usize>(),
+            self.glyph_ranges.len(),
+            self.fonts.len()
+        );
     }
 
     fn update_write_descriptor_set(
@@ -973,7 +1221,7 @@ impl Drawer {
         physical_device: PhysicalDevice,
         command_pool: CommandPool,
         graphics_queue: Queue,
-    ) -> (Texture, Sampler) {
+    ) -> Result<Texture, GraphicsError> {
         let mut texture = Texture::new(
             width,
             height,
@@ -991,7 +1239,7 @@ impl Drawer {
             physical_device,
             BufferUsageFlags::TRANSFER_SRC,
             MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-        );
+        )?;
         let buffer_memory_requirements =
             unsafe { device.get_buffer_memory_requirements(staging_buffer.buffer) };
         let mapped = unsafe {
@@ -1002,7 +1250,7 @@ impl Drawer {
                     buffer_memory_requirements.size,
                     MemoryMapFlags::empty(),
                 )
-                .expect("Failed to map memory for staging buffer.")
+                .or_graphics_error("map memory for Nuklear staging buffer")?
         };
         unsafe {
             std::ptr::copy_nonoverlapping(
@@ -1026,30 +1274,7 @@ impl Drawer {
             device.free_memory(staging_buffer.device_memory, None);
             device.destroy_buffer(staging_buffer.buffer, None);
         }
-
-        let sampler_info = SamplerCreateInfo::builder()
-            .unnormalized_coordinates(false)
-            .mipmap_mode(SamplerMipmapMode::LINEAR)
-            .mip_lod_bias(0.0)
-            .min_lod(0.0)
-            .min_filter(Filter::LINEAR)
-            .max_lod(0.0)
-            .max_anisotropy(1.0)
-            .mag_filter(Filter::LINEAR)
-            .compare_op(CompareOp::ALWAYS)
-            .compare_enable(false)
-            .border_color(BorderColor::FLOAT_OPAQUE_WHITE)
-            .anisotropy_enable(false)
-            .address_mode_u(SamplerAddressMode::REPEAT)
-            .address_mode_v(SamplerAddressMode::REPEAT)
-            .address_mode_w(SamplerAddressMode::REPEAT);
-
-        let sampler = unsafe {
-            device
-                .create_sampler(&sampler_info, None)
-                .expect("Failed to create sampler for Nuklear texture.")
-        };
-        (texture, sampler)
+        Ok(texture)
     }
 }
 
@@ -1058,9 +1283,11 @@ impl Drop for Drawer {
         let device = &self.logical_device;
         let fences = [self.command_finished];
         unsafe {
-            device
-                .wait_for_fences(&fences[0..], true, u64::MAX)
-                .expect("Failed to wait for fences for Nuklear.");
+            // デバイスを失った後でも解放できるように、待てなくても続ける。
+            // Carry on even if the wait fails, so the drawer can be released after the device is lost.
+            if let Err(e) = device.wait_for_fences(&fences[0..], true, u64::MAX) {
+                log::warn!("Failed to wait for fences for Nuklear: {}", e);
+            }
             device.destroy_semaphore(self.render_completed, None);
             device.destroy_fence(self.command_finished, None);
             device.destroy_pipeline_layout(self.pipeline_layout, None);
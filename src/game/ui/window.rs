@@ -0,0 +1,174 @@
+/// UIの窓の位置と大きさ。<br />
+/// Position and size of a UI window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WindowBounds {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl WindowBounds {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        WindowBounds { x, y, w, h }
+    }
+}
+
+/// 名前で管理するUIの論理的な窓。<br />
+/// A logical UI window managed by its name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UiWindow {
+    pub name: String,
+    pub bounds: WindowBounds,
+    /// Nuklearの`PanelFlags`を組み合わせたもの。<br />
+    /// Combination of Nuklear `PanelFlags`.
+    pub flags: u32,
+    /// 窓を始める時に設定するフォントの大きさ。<br />
+    /// Font size set when the window begins.
+    pub font_size: u8,
+    /// 大きいほど手前に描画される。<br />
+    /// Windows with a larger value are drawn in front.
+    pub z_order: i32,
+    pub is_visible: bool,
+}
+
+impl UiWindow {
+    pub fn new(name: &str, bounds: WindowBounds, flags: u32, font_size: u8, z_order: i32) -> Self {
+        UiWindow {
+            name: name.to_string(),
+            bounds,
+            flags,
+            font_size,
+            z_order,
+            is_visible: true,
+        }
+    }
+}
+
+/// UIの窓を管理する。全ての窓は同じNuklearのコンテキストで作られ、一度に変換して描画される。<br />
+/// Manages UI windows. All windows are built in the same Nuklear context, and converted and drawn in a single pass.
+#[derive(Clone, Debug, Default)]
+pub struct WindowManager {
+    windows: Vec<UiWindow>,
+    drawn_windows: Vec<String>,
+    last_order: Vec<String>,
+    is_order_dirty: bool,
+}
+
+impl WindowManager {
+    pub fn new() -> Self {
+        WindowManager {
+            windows: vec![],
+            drawn_windows: vec![],
+            last_order: vec![],
+            is_order_dirty: false,
+        }
+    }
+
+    /// 窓を登録する。同じ名前の窓があれば置き換える。<br />
+    /// Register a window, replacing any window with the same name.
+    pub fn add(&mut self, window: UiWindow) {
+        match self.get_mut(&window.name) {
+            Some(existing) => *existing = window,
+            None => self.windows.push(window),
+        }
+        self.is_order_dirty = true;
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UiWindow> {
+        self.windows.iter().find(|w| w.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut UiWindow> {
+        self.windows.iter_mut().find(|w| w.name == name)
+    }
+
+    /// 窓を表示する。窓が登録されていなければ`false`を返す。<br />
+    /// Show a window. Returns `false` if no such window is registered.
+    pub fn show(&mut self, name: &str) -> bool {
+        self.set_visible(name, true)
+    }
+
+    /// 窓を隠す。窓が登録されていなければ`false`を返す。<br />
+    /// Hide a window. Returns `false` if no such window is registered.
+    pub fn hide(&mut self, name: &str) -> bool {
+        self.set_visible(name, false)
+    }
+
+    pub fn is_visible(&self, name: &str) -> bool {
+        self.get(name).map(|w| w.is_visible).unwrap_or(false)
+    }
+
+    pub fn set_z_order(&mut self, name: &str, z_order: i32) -> bool {
+        match self.get_mut(name) {
+            Some(window) => {
+                window.z_order = z_order;
+                self.is_order_dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 窓を他の全ての窓より手前に移す。<br />
+    /// Move a window in front of all other windows.
+    pub fn bring_to_front(&mut self, name: &str) -> bool {
+        let front = self
+            .windows
+            .iter()
+            .filter(|w| w.name != name)
+            .map(|w| w.z_order)
+            .max()
+            .unwrap_or(0);
+        self.set_z_order(name, front + 1)
+    }
+
+    /// 表示している窓の名前を奥から手前の順に返す。<br />
+    /// Get the names of visible windows, from back to front.
+    pub fn get_draw_order(&self) -> Vec<&str> {
+        let mut windows = self
+            .windows
+            .iter()
+            .filter(|w| w.is_visible)
+            .collect::<Vec<_>>();
+        windows.sort_by_key(|w| w.z_order);
+        windows.into_iter().map(|w| w.name.as_str()).collect()
+    }
+
+    /// このフレームで窓が始められたことを記録する。<br />
+    /// Record that a window was begun in this frame.
+    pub fn mark_drawn(&mut self, name: &str) {
+        self.drawn_windows.push(name.to_string());
+    }
+
+    /// フレームの終わりに呼ぶ。描画された窓の並びが変わった時だけ、奥から手前の順に名前を返す。<br />
+    /// Called at the end of a frame. Returns the names of the drawn windows from back to front, only when their order changed.
+    pub fn take_focus_order(&mut self) -> Option<Vec<String>> {
+        let drawn_windows = std::mem::take(&mut self.drawn_windows);
+        let order = self
+            .get_draw_order()
+            .into_iter()
+            .filter(|name| drawn_windows.iter().any(|drawn| drawn == name))
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        if !self.is_order_dirty && order == self.last_order {
+            return None;
+        }
+        self.is_order_dirty = false;
+        self.last_order = order.clone();
+        Some(order)
+    }
+
+    fn set_visible(&mut self, name: &str, is_visible: bool) -> bool {
+        match self.get_mut(name) {
+            Some(window) => {
+                if window.is_visible != is_visible {
+                    window.is_visible = is_visible;
+                    self.is_order_dirty = true;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -2,40 +2,89 @@
 use demo_game_rs::game::graphics::dx12 as DX12;
 use demo_game_rs::game::graphics::vk as VK;
 //use demo_game_rs::game::shared::structs::PushConstant;
+use demo_game_rs::game::shared::enums::SceneType;
+use demo_game_rs::game::shared::structs::{
+    validate_assets, AssetPreview, Benchmark, LaunchGuard, LaunchOptions, Settings, SoakAction,
+    SoakState, SoakTest, LAUNCH_GUARD_PATH, SAFE_MODE_WINDOW_SIZE, SETTINGS_PATH, SOAK_CSV_PATH,
+    SOAK_SYNTHETIC_PLAYERS,
+};
+use demo_game_rs::game::shared::util::CountingAllocator;
 use demo_game_rs::game::{Game, NetworkSystem};
+#[cfg(feature = "listen-server")]
+use demo_game_rs::game::{ListenServer, ListenServerSettings};
 use env_logger::Builder;
-use log::LevelFilter;
 use std::time;
 #[cfg(target_os = "windows")]
 use winapi::um::d3d12::ID3D12GraphicsCommandList;
-use winit::event::{Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event::{
+    ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent,
+};
 use winit::event_loop::{ControlFlow, EventLoop};
 #[cfg(target_os = "windows")]
 use wio::com::ComPtr;
 
+// プロファイラーに一フレームの確保の回数を表示するため、全ての確保を数える
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
 fn main() -> anyhow::Result<()> {
+    // コマンドライン引数の読み込み
+    let options = LaunchOptions::from_process_args();
+
     // 環境変数のロード
     dotenv::dotenv().ok();
 
+    // 設定ファイルを読み込む。環境変数は設定ファイルを、コマンドライン引数はさらにそれを上書きする
+    let mut settings = Settings::load(SETTINGS_PATH)?;
+    options.apply_to(&mut settings);
+
     // ログを設定する
-    let log_level = dotenv::var("LOG").unwrap();
     Builder::new()
-        .filter(
-            None,
-            match log_level.as_str() {
-                "trace" => LevelFilter::Trace,
-                "info" => LevelFilter::Info,
-                "warn" => LevelFilter::Warn,
-                "debug" => LevelFilter::Debug,
-                "error" => LevelFilter::Error,
-                _ => LevelFilter::Off,
-            },
-        )
+        .filter(None, settings.get_log_level())
         .default_format()
         .init();
 
-    // 環境変数から描画APIを決めます
-    let api = dotenv::var("API").unwrap();
+    // 素材の参照を確かめるだけの場合は、壊れた参照を表示して終了コードで結果を返す
+    if options.is_validating_assets {
+        let report = validate_assets(std::path::Path::new("."));
+        for broken in report.broken_references.iter() {
+            println!(
+                "{}: {} {}: {}",
+                broken.reference.source,
+                broken.reference.kind,
+                broken.reference.path,
+                broken.message
+            );
+        }
+        println!(
+            "Checked {} asset references, {} broken.",
+            report.graph.get_references().len(),
+            report.broken_references.len()
+        );
+        std::process::exit(if report.is_valid() { 0 } else { 1 });
+    }
+
+    // 素材をプレビューする場合は、開けない素材ならウィンドウを作る前に終了する
+    let asset_preview = options
+        .preview_asset
+        .as_deref()
+        .map(AssetPreview::new)
+        .transpose()?;
+
+    // 続けて起動に失敗したらセーフモードで起動する
+    let launch_guard = LaunchGuard::begin(LAUNCH_GUARD_PATH);
+    let safe_mode_changes = if launch_guard.is_safe_mode() {
+        log::warn!(
+            "The game failed to start {} times in a row. Starting in safe mode.",
+            launch_guard.get_failed_launches()
+        );
+        Some(launch_guard.apply_safe_mode(&mut settings))
+    } else {
+        None
+    };
+
+    // 設定から描画APIを決めます
+    let api = settings.api.clone();
     log::info!("Using API: {}", &api);
 
     // Tokio非同期ランタイムをセットアップ
@@ -51,17 +100,75 @@ fn main() -> anyhow::Result<()> {
     let mut last_second = time::Instant::now();
     let mut current_time = time::Instant::now();
 
+    // ウィンドウの大きさ
+    let (width, height) = if safe_mode_changes.is_some() {
+        SAFE_MODE_WINDOW_SIZE
+    } else {
+        options.window_size.unwrap_or((1280, 720))
+    };
+
+    // ベンチマークでフレーム時間を測る
+    let mut benchmark = if options.is_benchmark {
+        Some(Benchmark::default())
+    } else {
+        None
+    };
+
+    // ソークテストでタイトルと試合を繰り返し、リソースの数とFPSをCSVに記録する
+    let mut soak_test = match options.soak_hours {
+        Some(hours) => Some(SoakTest::new(
+            std::fs::File::create(SOAK_CSV_PATH)?,
+            hours * 3600.0,
+        )?),
+        None => None,
+    };
+
     // フレーム数
     let mut frame_count = 0_u32;
     // 時間の差
     let mut delta_time = 0.0_f64;
 
+    // ホストする場合はリッスンサーバーを起動し、自分自身に接続する
+    #[cfg(feature = "listen-server")]
+    let _listen_server = if options.is_hosting {
+        let listen_server = rt.block_on(ListenServer::start(
+            ListenServerSettings::from_settings(&settings),
+        ))?;
+        settings.server_endpoint = listen_server.settings.get_local_server_endpoint();
+        settings.udp_endpoint = listen_server.settings.get_local_udp_endpoint();
+        Some(listen_server)
+    } else {
+        None
+    };
+    if options.is_hosting && cfg!(not(feature = "listen-server")) {
+        log::warn!("This build can't host matches. Build with the listen-server feature to host.");
+    }
+
     // ネットワークシステムを初期化
-    let network_system = rt.block_on(async {
-        NetworkSystem::new()
-            .await
-            .expect("Failed to initialize network system.")
-    });
+    // offlineが設定されているか、サーバーに接続できない場合はオフラインで遊ぶ
+    let mut network_system = if settings.offline {
+        log::info!("Starting in offline mode.");
+        NetworkSystem::offline()
+    } else {
+        rt.block_on(async {
+            match NetworkSystem::new(&settings).await {
+                Ok(network_system) => network_system,
+                Err(e) => {
+                    log::warn!(
+                        "Failed to connect to the server. Starting in offline mode: {}",
+                        e
+                    );
+                    NetworkSystem::offline()
+                }
+            }
+        })
+    };
+    // 部屋に登録する時に、名札などの設定を他のプレイヤーに伝える
+    network_system.set_player_settings(settings.get_player_settings());
+    // ソークテストでは動き回るだけのプレイヤーで部屋を埋める
+    if soak_test.is_some() {
+        rt.block_on(network_system.add_synthetic_players(SOAK_SYNTHETIC_PLAYERS))?;
+    }
 
     match api.as_str() {
         "VULKAN" => {
@@ -72,11 +179,18 @@ fn main() -> anyhow::Result<()> {
                 VK::Image,
             >::new(
                 "Demo game",
-                1280.0,
-                720.0,
+                width as f64,
+                height as f64,
                 &event_loop,
                 network_system,
+                &settings,
             )?);
+            if let Some(changes) = safe_mode_changes {
+                game.enter_safe_mode(changes);
+            }
+            if let Some(preview) = asset_preview {
+                game.enter_preview_mode(preview);
+            }
             if game.initialize() {
                 rt.block_on(async {
                     game.load_content().await.expect("Failed to load content.");
@@ -86,9 +200,11 @@ fn main() -> anyhow::Result<()> {
 
             let mut mouse_x = 0.0;
             let mut mouse_y = 0.0;
+            // 押されている修飾キー
+            let mut modifiers = ModifiersState::empty();
 
             // ウィンドウのメインループ
-            event_loop.run(move |event, _target, control_flow| {
+            event_loop.run(move |event, target, control_flow| {
                 let game = &mut game;
                 let rt = &mut rt;
                 match event {
@@ -109,6 +225,12 @@ fn main() -> anyhow::Result<()> {
                         }
                         game.start_input();
                     }
+                    // メインの画面を写すウィンドウなど、別のウィンドウのイベント
+                    Event::WindowEvent { window_id, event }
+                        if window_id != game.window.borrow().id() =>
+                    {
+                        game.handle_secondary_window_event(window_id, &event);
+                    }
                     // ウィンドウ全般のイベント
                     Event::WindowEvent { event, .. } => match event {
                         // ウィンドウを閉じる
@@ -140,6 +262,18 @@ fn main() -> anyhow::Result<()> {
                                 }
                                 *control_flow = ControlFlow::Exit;
                             }
+                            // Alt+Enterでフルスクリーンを切り替える
+                            VirtualKeyCode::Return
+                                if modifiers.alt() && state == ElementState::Pressed =>
+                            {
+                                game.toggle_fullscreen();
+                            }
+                            // F7でメインの画面を写すウィンドウを開閉する
+                            VirtualKeyCode::F7 if state == ElementState::Pressed => {
+                                if let Err(e) = game.toggle_mirror_window(target) {
+                                    log::warn!("Failed to toggle the mirror window: {}", e);
+                                }
+                            }
                             _ => {
                                 /*let mut camera = game.camera.borrow_mut();
                                 println!(
@@ -157,6 +291,10 @@ fn main() -> anyhow::Result<()> {
                                 });
                             }
                         },
+                        // 修飾キーの変化
+                        WindowEvent::ModifiersChanged(state) => {
+                            modifiers = state;
+                        }
                         // マウスの移動
                         WindowEvent::CursorMoved {
                             position: winit::dpi::PhysicalPosition { x, y },
@@ -175,19 +313,15 @@ fn main() -> anyhow::Result<()> {
                         }
                         // ウィンドウのサイズ調整
                         WindowEvent::Resized(winit::dpi::PhysicalSize { width, height }) => {
-                            let current_scene = game.current_scene;
-                            game.graphics
-                                .write()
-                                .recreate_swapchain(width, height, current_scene)
+                            game.recreate_swapchain(width, height)
                                 .expect("Failed to recreate swapchain.");
-                            if width > 0 && height > 0 {
-                                game.scene_manager
-                                    .create_ssbo()
-                                    .expect("Failed to create SSBO for skinned models.");
-                            }
                         }
                         _ => (),
                     },
+                    // 正常に終了したので失敗の記録を消す
+                    Event::LoopDestroyed => {
+                        launch_guard.finish();
+                    }
                     // 全てのウィンドウのイベント処理が完了する
                     Event::MainEventsCleared => {
                         // 入力完了
@@ -200,8 +334,102 @@ fn main() -> anyhow::Result<()> {
                                 .expect("Failed to update the game.");
                         });
 
+                        // コンソールから終了した
+                        if game.is_terminating {
+                            unsafe {
+                                std::mem::ManuallyDrop::drop(game);
+                            }
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+
                         // ゲームを描画
                         game.render(delta_time).expect("Failed to render the game.");
+
+                        // ベンチマークが終わったら結果を出して終了
+                        if let Some(benchmark) = benchmark.as_mut() {
+                            benchmark.record(delta_time);
+                            if benchmark.is_finished() {
+                                if let Some(report) = benchmark.get_report() {
+                                    log::info!("Benchmark finished: {:?}", report);
+                                    println!(
+                                        "Frames: {} / Average: {:.1} FPS, {:.2} ms / Min: {:.2} ms / Max: {:.2} ms / 99th percentile: {:.2} ms",
+                                        report.frames,
+                                        report.average_fps,
+                                        report.average_frame_time,
+                                        report.min_frame_time,
+                                        report.max_frame_time,
+                                        report.percentile_99_frame_time
+                                    );
+                                }
+                                unsafe {
+                                    game.is_terminating = true;
+                                    std::mem::ManuallyDrop::drop(game);
+                                }
+                                *control_flow = ControlFlow::Exit;
+                                return;
+                            }
+                        }
+
+                        // ソークテストでは一定時間ごとにタイトルに戻り、終わったら漏れを出して終了
+                        if let Some(soak_test) = soak_test.as_mut() {
+                            rt.block_on(game.move_synthetic_players(delta_time));
+                            let state = if game.is_loading() {
+                                SoakState::Loading
+                            } else if game.current_scene == SceneType::GAME {
+                                SoakState::Game
+                            } else {
+                                SoakState::Title
+                            };
+                            let action = soak_test
+                                .update(delta_time, state, || {
+                                    game.get_resource_counts()
+                                        .expect("Failed to count resources.")
+                                })
+                                .expect("Failed to record the soak test.");
+                            match action {
+                                SoakAction::Continue => (),
+                                SoakAction::ReturnToTitle => {
+                                    log::info!(
+                                        "Soak test: returning to the title after cycle {}.",
+                                        soak_test.get_cycle() + 1
+                                    );
+                                    rt.block_on(game.return_to_title())
+                                        .expect("Failed to return to the title.");
+                                }
+                                SoakAction::Finish => {
+                                    for leak in soak_test.get_leaks() {
+                                        println!("Leaked {}", leak);
+                                    }
+                                    println!(
+                                        "Soak test finished after {} cycles: {}. Samples were written to {}.",
+                                        soak_test.get_cycle(),
+                                        if soak_test.is_passed() { "passed" } else { "failed" },
+                                        SOAK_CSV_PATH
+                                    );
+                                    let is_passed = soak_test.is_passed();
+                                    unsafe {
+                                        game.is_terminating = true;
+                                        std::mem::ManuallyDrop::drop(game);
+                                    }
+                                    // 漏れが見つかったら終了コードで知らせる
+                                    if !is_passed {
+                                        launch_guard.finish();
+                                        std::process::exit(1);
+                                    }
+                                    *control_flow = ControlFlow::Exit;
+                                    return;
+                                }
+                            }
+                        }
+
+                        // 垂直同期を使わない時はフレームレートの上限まで待つ
+                        if let Some(frame_time) = game.get_frame_time_limit() {
+                            let elapsed = current_time.elapsed();
+                            if elapsed < frame_time {
+                                std::thread::sleep(frame_time - elapsed);
+                            }
+                        }
                     }
                     _ => (),
                 }
@@ -217,40 +445,106 @@ fn main() -> anyhow::Result<()> {
                     DX12::Resource,
                 >::new(
                     "Demo game",
-                    1280.0,
-                    720.0,
+                    width as f64,
+                    height as f64,
                     &event_loop,
                     network_system,
+                    &settings,
                 ));
                 if game.initialize() {
                     game.load_content();
                 }
-                println!("Game content loaded.");
+                log::info!("Game content loaded.");
+                log::warn!("DX12 only runs the login and lobby. Use API=VULKAN to play matches.");
+
+                let mut mouse_x = 0.0;
+                let mut mouse_y = 0.0;
+                // DX12ではシーンを描画しないので、ログインとロビーのUIだけを動かす
                 event_loop.run(move |event, _target, control_flow| {
-                    //*control_flow = ControlFlow::Poll;
                     let game = &mut game;
+                    let rt = &mut rt;
                     match event {
+                        // FPSを計算及び入力の受け
+                        Event::NewEvents(_) => {
+                            delta_time = current_time.elapsed().as_secs_f64();
+                            current_time = time::Instant::now();
+                            frame_count += 1;
+                            let elapsed = last_second.elapsed().as_secs_f64();
+                            if elapsed > 1.0 {
+                                game.window.borrow().set_title(&format!(
+                                    "Demo Engine (DX12) / FPS: {} / Frame Time: {}",
+                                    frame_count,
+                                    1000 / frame_count
+                                ));
+                                frame_count = 0;
+                                last_second = time::Instant::now();
+                            }
+                            game.start_input();
+                        }
                         Event::WindowEvent { event, .. } => match event {
                             WindowEvent::CloseRequested => {
                                 std::mem::ManuallyDrop::drop(game);
                                 *control_flow = ControlFlow::Exit;
                             }
+                            WindowEvent::ReceivedCharacter(c) => {
+                                game.input_unicode(c);
+                            }
                             WindowEvent::KeyboardInput {
                                 input:
                                     KeyboardInput {
                                         virtual_keycode: Some(virtual_key_code),
+                                        state,
                                         ..
                                     },
                                 ..
                             } => match virtual_key_code {
                                 VirtualKeyCode::Escape => {
+                                    game.is_terminating = true;
                                     std::mem::ManuallyDrop::drop(game);
                                     *control_flow = ControlFlow::Exit;
                                 }
-                                _ => (),
+                                _ => {
+                                    rt.block_on(async {
+                                        game.input_key(virtual_key_code, state).await;
+                                    });
+                                }
                             },
+                            WindowEvent::CursorMoved {
+                                position: winit::dpi::PhysicalPosition { x, y },
+                                ..
+                            } => {
+                                mouse_x = x;
+                                mouse_y = y;
+                                game.input_motion(x, y);
+                            }
+                            WindowEvent::MouseInput { state, button, .. } => {
+                                game.input_button(button, mouse_x, mouse_y, state);
+                            }
+                            WindowEvent::MouseWheel { delta, .. } => {
+                                game.input_scroll(delta);
+                            }
+                            WindowEvent::Resized(winit::dpi::PhysicalSize { width, height }) => {
+                                game.recreate_swapchain(width, height)
+                                    .expect("Failed to recreate swapchain.");
+                            }
                             _ => (),
                         },
+                        Event::LoopDestroyed => {
+                            launch_guard.finish();
+                        }
+                        Event::MainEventsCleared => {
+                            game.end_input();
+                            rt.block_on(async {
+                                game.update().await.expect("Failed to update the game.");
+                            });
+                            // コンソールから終了した
+                            if game.is_terminating {
+                                std::mem::ManuallyDrop::drop(game);
+                                *control_flow = ControlFlow::Exit;
+                                return;
+                            }
+                            game.render(delta_time).expect("Failed to render the game.");
+                        }
                         _ => (),
                     }
                 });
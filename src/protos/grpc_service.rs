@@ -1,4 +1,24 @@
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HandshakeRequest {
+    #[prost(uint32, tag = "1")]
+    pub protocol_version: u32,
+    #[prost(string, tag = "2")]
+    pub build_version: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HandshakeReply {
+    #[prost(bool, tag = "1")]
+    pub compatible: bool,
+    #[prost(uint32, tag = "2")]
+    pub server_protocol_version: u32,
+    #[prost(uint32, tag = "3")]
+    pub min_protocol_version: u32,
+    #[prost(string, tag = "4")]
+    pub server_build_version: std::string::String,
+    #[prost(string, tag = "5")]
+    pub message: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RegisterRequest {
     #[prost(string, tag = "1")]
     pub user_name: std::string::String,
@@ -28,6 +48,9 @@ pub struct LoginRequest {
     pub password: std::string::String,
     #[prost(string, tag = "3")]
     pub jwt_token: std::string::String,
+    /// Refresh token of an earlier login, used in place of the password to resume a saved session.
+    #[prost(string, tag = "4")]
+    pub refresh_token: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct LoginReply {
@@ -37,6 +60,9 @@ pub struct LoginReply {
     pub message: std::string::String,
     #[prost(message, optional, tag = "3")]
     pub player: ::std::option::Option<game_state::Player>,
+    /// Token to log in again without the password. A new one is issued on every login.
+    #[prost(string, tag = "4")]
+    pub refresh_token: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct MessageRecord {
@@ -111,6 +137,12 @@ pub mod game_state {
         pub is_alive: bool,
         #[prost(message, optional, tag = "6")]
         pub world_matrix: ::std::option::Option<WorldMatrix>,
+        /// Player ID of whoever last damaged this entity. Empty if no one has.
+        #[prost(string, tag = "7")]
+        pub last_attacker_id: std::string::String,
+        /// Stable ID of the mount this entity is riding. Empty while on foot.
+        #[prost(string, tag = "8")]
+        pub mount_id: std::string::String,
     }
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct PlayerState {
@@ -123,6 +155,23 @@ pub mod game_state {
         #[prost(message, optional, tag = "4")]
         pub state: ::std::option::Option<EntityState>,
     }
+    /// Choices each player makes about how others see them. Sent when joining a room.
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct PlayerSettings {
+        /// Preferred nameplate color packed as 0xRRGGBBAA.
+        #[prost(uint32, tag = "1")]
+        pub nameplate_color: u32,
+        #[prost(string, tag = "2")]
+        pub title: std::string::String,
+        #[prost(string, tag = "3")]
+        pub badge: std::string::String,
+        /// Hides this player's own nameplate from everyone else.
+        #[prost(bool, tag = "4")]
+        pub hide_nameplate: bool,
+        /// The player doesn't read chat, so others shouldn't expect replies.
+        #[prost(bool, tag = "5")]
+        pub mute_chat: bool,
+    }
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct Rooms {
         #[prost(message, repeated, tag = "1")]
@@ -136,6 +185,14 @@ pub mod game_state {
         pub room_name: std::string::String,
         #[prost(message, optional, tag = "3")]
         pub player: ::std::option::Option<Player>,
+        /// Only used when creating a new room. 0 lets the server decide.
+        #[prost(int32, tag = "4")]
+        pub max_players: i32,
+        #[prost(message, optional, tag = "5")]
+        pub settings: ::std::option::Option<PlayerSettings>,
+        /// Token issued to the player on login.
+        #[prost(string, tag = "6")]
+        pub jwt_token: std::string::String,
     }
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct RoomState {
@@ -153,13 +210,20 @@ pub mod game_state {
         pub players: ::std::vec::Vec<Player>,
         #[prost(string, tag = "7")]
         pub message: std::string::String,
+        /// Settings of each player in the room, keyed by player ID.
+        #[prost(map = "string, message", tag = "8")]
+        pub player_settings: ::std::collections::HashMap<std::string::String, PlayerSettings>,
     }
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct StartGameRequest {
         #[prost(message, optional, tag = "1")]
         pub room_state: ::std::option::Option<RoomState>,
+        /// Versioned terrain payload with quantized heights, opaque to the server.
         #[prost(bytes, tag = "2")]
         pub terrain_vertices: std::vec::Vec<u8>,
+        /// Token issued to the room owner on login.
+        #[prost(string, tag = "3")]
+        pub jwt_token: std::string::String,
     }
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct GetTerrainRequest {
@@ -170,6 +234,17 @@ pub mod game_state {
     pub struct GetTerrainReply {
         #[prost(bytes, tag = "1")]
         pub terrain_vertices: std::vec::Vec<u8>,
+        /// Compressed patches of the chunks modified since the game started, opaque to the server.
+        #[prost(bytes, tag = "2")]
+        pub terrain_patches: std::vec::Vec<u8>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct UpdateTerrainRequest {
+        #[prost(string, tag = "1")]
+        pub room_id: std::string::String,
+        /// Compressed patches of the chunks modified since the game started, opaque to the server.
+        #[prost(bytes, tag = "2")]
+        pub terrain_patches: std::vec::Vec<u8>,
     }
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct ProgressGameRequest {
@@ -178,6 +253,27 @@ pub mod game_state {
         #[prost(string, tag = "2")]
         pub room_id: std::string::String,
     }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ReportMatchResultRequest {
+        #[prost(string, tag = "1")]
+        pub room_id: std::string::String,
+        #[prost(string, tag = "2")]
+        pub player_id: std::string::String,
+        /// Player ID of the winner seen by the client. Empty for a draw.
+        #[prost(string, tag = "3")]
+        pub winner_id: std::string::String,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ReportMatchResultReply {
+        /// The reporting player with updated win counts and credits.
+        #[prost(message, optional, tag = "1")]
+        pub player: ::std::option::Option<Player>,
+        /// Winner decided by the server from the first report. Empty for a draw.
+        #[prost(string, tag = "2")]
+        pub winner_id: std::string::String,
+        #[prost(int32, tag = "3")]
+        pub earned_credits: i32,
+    }
 }
 #[doc = r" Generated client implementations."]
 pub mod grpc_service_client {
@@ -212,6 +308,21 @@ pub mod grpc_service_client {
             let inner = tonic::client::Grpc::with_interceptor(inner, interceptor);
             Self { inner }
         }
+        #[doc = " Negotiate the protocol version before any other request."]
+        pub async fn handshake(
+            &mut self,
+            request: impl tonic::IntoRequest<super::HandshakeRequest>,
+        ) -> Result<tonic::Response<super::HandshakeReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/grpc_service.GrpcService/Handshake");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         #[doc = " Register player to the database."]
         pub async fn register(
             &mut self,
@@ -343,6 +454,41 @@ pub mod grpc_service_client {
             let path = http::uri::PathAndQuery::from_static("/grpc_service.GrpcService/GetTerrain");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        #[doc = " Replace the terrain patches of a started room."]
+        #[doc = " Players joining later receive them together with the terrain."]
+        pub async fn update_terrain(
+            &mut self,
+            request: impl tonic::IntoRequest<super::game_state::UpdateTerrainRequest>,
+        ) -> Result<tonic::Response<super::Empty>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/grpc_service.GrpcService/UpdateTerrain");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " Report the result of a finished match and leave the room."]
+        #[doc = " Win counts and credits of the reporting player are updated."]
+        pub async fn report_match_result(
+            &mut self,
+            request: impl tonic::IntoRequest<super::game_state::ReportMatchResultRequest>,
+        ) -> Result<tonic::Response<super::game_state::ReportMatchResultReply>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/grpc_service.GrpcService/ReportMatchResult");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         #[doc = " Progress the game."]
         #[doc = " Unused."]
         pub async fn progress_game(
@@ -386,6 +532,11 @@ pub mod grpc_service_server {
     #[doc = "Generated trait containing gRPC methods that should be implemented for use with GrpcServiceServer."]
     #[async_trait]
     pub trait GrpcService: Send + Sync + 'static {
+        #[doc = " Negotiate the protocol version before any other request."]
+        async fn handshake(
+            &self,
+            request: tonic::Request<super::HandshakeRequest>,
+        ) -> Result<tonic::Response<super::HandshakeReply>, tonic::Status>;
         #[doc = " Register player to the database."]
         async fn register(
             &self,
@@ -437,6 +588,18 @@ pub mod grpc_service_server {
             &self,
             request: tonic::Request<super::game_state::GetTerrainRequest>,
         ) -> Result<tonic::Response<super::game_state::GetTerrainReply>, tonic::Status>;
+        #[doc = " Replace the terrain patches of a started room."]
+        #[doc = " Players joining later receive them together with the terrain."]
+        async fn update_terrain(
+            &self,
+            request: tonic::Request<super::game_state::UpdateTerrainRequest>,
+        ) -> Result<tonic::Response<super::Empty>, tonic::Status>;
+        #[doc = " Report the result of a finished match and leave the room."]
+        #[doc = " Win counts and credits of the reporting player are updated."]
+        async fn report_match_result(
+            &self,
+            request: tonic::Request<super::game_state::ReportMatchResultRequest>,
+        ) -> Result<tonic::Response<super::game_state::ReportMatchResultReply>, tonic::Status>;
         #[doc = "Server streaming response type for the ProgressGame method."]
         type ProgressGameStream: Stream<Item = Result<super::game_state::RoomState, tonic::Status>>
             + Send
@@ -481,6 +644,39 @@ pub mod grpc_service_server {
         fn call(&mut self, req: http::Request<B>) -> Self::Future {
             let inner = self.inner.clone();
             match req.uri().path() {
+                "/grpc_service.GrpcService/Handshake" => {
+                    #[allow(non_camel_case_types)]
+                    struct HandshakeSvc<T: GrpcService>(pub Arc<T>);
+                    impl<T: GrpcService> tonic::server::UnaryService<super::HandshakeRequest>
+                        for HandshakeSvc<T>
+                    {
+                        type Response = super::HandshakeReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::HandshakeRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).handshake(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = HandshakeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/grpc_service.GrpcService/Register" => {
                     #[allow(non_camel_case_types)]
                     struct RegisterSvc<T: GrpcService>(pub Arc<T>);
@@ -737,6 +933,74 @@ pub mod grpc_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/grpc_service.GrpcService/UpdateTerrain" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateTerrainSvc<T: GrpcService>(pub Arc<T>);
+                    impl<T: GrpcService>
+                        tonic::server::UnaryService<super::game_state::UpdateTerrainRequest>
+                        for UpdateTerrainSvc<T>
+                    {
+                        type Response = super::Empty;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::game_state::UpdateTerrainRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).update_terrain(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = UpdateTerrainSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/grpc_service.GrpcService/ReportMatchResult" => {
+                    #[allow(non_camel_case_types)]
+                    struct ReportMatchResultSvc<T: GrpcService>(pub Arc<T>);
+                    impl<T: GrpcService>
+                        tonic::server::UnaryService<super::game_state::ReportMatchResultRequest>
+                        for ReportMatchResultSvc<T>
+                    {
+                        type Response = super::game_state::ReportMatchResultReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::game_state::ReportMatchResultRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).report_match_result(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ReportMatchResultSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/grpc_service.GrpcService/ProgressGame" => {
                     #[allow(non_camel_case_types)]
                     struct ProgressGameSvc<T: GrpcService>(pub Arc<T>);
@@ -0,0 +1,26 @@
+use demo_game_rs::game::shared::util::{
+    get_allocation_count, get_live_allocation_count, CountingAllocator,
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn counts_heap_allocations() {
+    let before = get_allocation_count();
+    let values = vec![1_u64; 16];
+    let text = format!("{}", values.len());
+    assert!(get_allocation_count() >= before + 2);
+    assert_eq!(text, "16");
+}
+
+#[test]
+fn live_allocations_go_back_down_when_freed() {
+    // 他のテストのスレッドも確保するので、多めに確保して余裕を持って比べる。
+    // Threads of other tests allocate too, so allocate plenty and compare with some slack.
+    let before = get_live_allocation_count();
+    let boxes = (0..1000_u64).map(Box::new).collect::<Vec<_>>();
+    assert!(get_live_allocation_count() >= before + 900);
+    drop(boxes);
+    assert!(get_live_allocation_count() < before + 100);
+}
@@ -0,0 +1,152 @@
+use demo_game_rs::game::shared::structs::{
+    AnimationStateMachine, AnimationStateMachineData, ParameterValue,
+};
+use demo_game_rs::game::Animator;
+use glam::Vec3A;
+use std::sync::Arc;
+
+const CHARACTER: &str = r#"{
+    "name": "Character",
+    "initial_state": "Idle",
+    "parameters": { "speed": 0.0, "is_attacking": false },
+    "states": [
+        { "name": "Idle", "clip": "idle" },
+        { "name": "Run", "clip": "run", "speed": 2.0 },
+        { "name": "Attack", "clip": "attack", "is_looping": false }
+    ],
+    "transitions": [
+        {
+            "to": "Attack",
+            "conditions": [{ "parameter": "is_attacking", "comparison": "Equal", "value": true }]
+        },
+        {
+            "from": "Idle",
+            "to": "Run",
+            "conditions": [{ "parameter": "speed", "comparison": "Greater", "value": 0.5 }],
+            "blend_time": 0.5
+        },
+        {
+            "from": "Attack",
+            "to": "Idle",
+            "conditions": [{ "parameter": "is_attacking", "comparison": "Equal", "value": false }]
+        }
+    ]
+}"#;
+
+fn character() -> AnimationStateMachine {
+    let data = AnimationStateMachineData::from_json(CHARACTER)
+        .expect("Failed to load animation state machine.");
+    AnimationStateMachine::new(Arc::new(data))
+}
+
+#[test]
+fn rejects_invalid_definitions() {
+    let invalid = [
+        r#"{ "name": "A", "initial_state": "Missing", "states": [] }"#,
+        r#"{ "name": "A", "initial_state": "Idle", "states": [
+            { "name": "Idle", "clip": "idle" }, { "name": "Idle", "clip": "idle" }
+        ] }"#,
+        r#"{ "name": "A", "initial_state": "Idle",
+            "states": [{ "name": "Idle", "clip": "idle" }],
+            "transitions": [{ "to": "Run" }] }"#,
+        r#"{ "name": "A", "initial_state": "Idle",
+            "parameters": { "speed": 0.0 },
+            "states": [{ "name": "Idle", "clip": "idle" }],
+            "transitions": [{ "to": "Idle", "conditions": [
+                { "parameter": "speed", "comparison": "Equal", "value": true }
+            ] }] }"#,
+    ];
+    for json in invalid.iter() {
+        assert!(AnimationStateMachineData::from_json(json).is_err());
+    }
+}
+
+#[test]
+fn transitions_when_conditions_are_met() {
+    let mut machine = character();
+    assert_eq!(machine.get_current_state(), "Idle");
+    assert!(machine.set_float("speed", 0.2));
+    machine.update(0.1);
+    assert_eq!(machine.get_current_state(), "Idle");
+
+    // 定義にないパラメーターや種類の違う値は設定できない。
+    // Undeclared parameters and values of a different kind can't be set.
+    assert!(!machine.set_float("jump", 1.0));
+    assert!(!machine.set_bool("speed", true));
+    assert_eq!(
+        machine.get_parameter("speed"),
+        Some(ParameterValue::Float(0.2))
+    );
+
+    machine.set_float("speed", 1.0);
+    machine.update(0.1);
+    assert_eq!(machine.get_current_state(), "Run");
+
+    // `from`のない遷移はどの状態からでも起きる。
+    // Transitions without `from` happen from any state.
+    machine.set_bool("is_attacking", true);
+    machine.update(0.1);
+    assert_eq!(machine.get_current_state(), "Attack");
+    machine.set_bool("is_attacking", false);
+    machine.update(0.1);
+    assert_eq!(machine.get_current_state(), "Idle");
+}
+
+#[test]
+fn blends_from_previous_state() {
+    let mut machine = character();
+    machine.update(1.0);
+    machine.set_float("speed", 1.0);
+    machine.update(0.0);
+    let pose = machine.get_pose();
+    assert_eq!(pose.current.clip, "run");
+    assert_eq!(pose.blend_weight, 0.0);
+    let previous = pose.previous.expect("Failed to get the previous clip.");
+    assert_eq!(previous.clip, "idle");
+    assert_eq!(previous.time, 1.0);
+
+    // 今の状態は速さの倍率で進み、混ぜる重みは実時間で進む。
+    // The current state advances by its speed multiplier, while the blend weight follows real time.
+    machine.update(0.25);
+    let pose = machine.get_pose();
+    assert_eq!(pose.current.time, 0.5);
+    assert_eq!(pose.blend_weight, 0.5);
+    let info = machine.get_debug_info();
+    assert_eq!(info.state, "Run");
+    assert_eq!(info.previous_state.as_deref(), Some("Idle"));
+
+    machine.update(0.25);
+    let pose = machine.get_pose();
+    assert_eq!(pose.previous, None);
+    assert_eq!(pose.blend_weight, 1.0);
+}
+
+#[test]
+fn animator_feeds_movement_speed() {
+    let mut animator = Animator::new(character());
+    animator.update(Vec3A::zero(), 0.5);
+    assert_eq!(animator.state_machine.get_current_state(), "Idle");
+    animator.update(Vec3A::new(1.0, 0.0, 0.0), 0.5);
+    assert_eq!(
+        animator.state_machine.get_parameter("speed"),
+        Some(ParameterValue::Float(2.0))
+    );
+    assert_eq!(animator.state_machine.get_current_state(), "Run");
+}
+
+#[test]
+fn plays_a_state_directly() {
+    let mut machine = character();
+    assert_eq!(machine.get_state_names(), vec!["Idle", "Run", "Attack"]);
+    machine.set_float("speed", 1.0);
+    machine.update(0.1);
+    assert!(machine.get_pose().previous.is_some());
+
+    assert!(machine.play_state("Attack"));
+    let pose = machine.get_pose();
+    assert_eq!(machine.get_current_state(), "Attack");
+    assert_eq!(pose.current.time, 0.0);
+    assert!(pose.previous.is_none());
+    assert!(!machine.play_state("Jump"));
+    assert_eq!(machine.get_current_state(), "Attack");
+}
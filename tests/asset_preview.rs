@@ -0,0 +1,221 @@
+use demo_game_rs::game::shared::components::Bounds;
+use demo_game_rs::game::shared::structs::{
+    AnimationPlayback, FileWatcher, OrbitCamera, PreviewAssetInfo, PreviewAssetKind, PreviewClip,
+};
+use glam::Vec3A;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+fn create_preview_root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).expect("Failed to create preview directory.");
+    root
+}
+
+fn clip(name: &str, duration: f32) -> PreviewClip {
+    PreviewClip {
+        name: name.to_string(),
+        duration,
+    }
+}
+
+#[test]
+fn detects_asset_kinds_from_extensions() {
+    let kind = |path: &str| PreviewAssetKind::from_path(Path::new(path));
+    assert_eq!(
+        kind("./models/tank/scene.gltf"),
+        Some(PreviewAssetKind::Model)
+    );
+    assert_eq!(
+        kind("./models/cesiumMan/CesiumMan.GLB"),
+        Some(PreviewAssetKind::Model)
+    );
+    assert_eq!(
+        kind("./textures/grass.ktx2"),
+        Some(PreviewAssetKind::Texture)
+    );
+    assert_eq!(
+        kind("./textures/grass.PNG"),
+        Some(PreviewAssetKind::Texture)
+    );
+    assert_eq!(kind("./models/teapot.obj"), None);
+    assert_eq!(kind("./README"), None);
+}
+
+#[test]
+fn reads_materials_and_external_files_of_gltf() {
+    let root = create_preview_root("demo_game_asset_preview_gltf");
+    let path = root.join("tank.gltf");
+    std::fs::write(
+        &path,
+        r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "tank.bin", "byteLength": 4 }],
+            "images": [{ "uri": "hull.png" }],
+            "textures": [{ "source": 0 }],
+            "materials": [
+                {
+                    "name": "Hull",
+                    "pbrMetallicRoughness": {
+                        "baseColorFactor": [1.0, 0.5, 0.25, 1.0],
+                        "metallicFactor": 0.2,
+                        "baseColorTexture": { "index": 0 }
+                    },
+                    "alphaMode": "MASK",
+                    "doubleSided": true
+                },
+                {}
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let info = PreviewAssetInfo::read(&path).expect("Failed to read glTF.");
+    assert_eq!(info.kind, PreviewAssetKind::Model);
+    assert_eq!(info.animation_count, 0);
+    assert_eq!(
+        info.dependencies,
+        vec![root.join("tank.bin"), root.join("hull.png")]
+    );
+    assert_eq!(info.materials.len(), 2);
+    let hull = &info.materials[0];
+    assert_eq!(hull.name, "Hull");
+    assert_eq!(hull.base_color, [1.0, 0.5, 0.25, 1.0]);
+    assert_eq!(hull.metallic, 0.2);
+    assert_eq!(hull.roughness, 1.0);
+    assert_eq!(hull.base_color_texture.as_deref(), Some("hull.png"));
+    assert_eq!(hull.alpha_mode, "Mask");
+    assert!(hull.is_double_sided);
+    // 名前のないマテリアルは番号で呼ぶ。
+    // Unnamed materials are called by their index.
+    assert_eq!(info.materials[1].name, "Material 1");
+    assert_eq!(info.materials[1].base_color_texture, None);
+}
+
+#[test]
+fn reads_texture_sizes() {
+    let root = create_preview_root("demo_game_asset_preview_texture");
+    let path = root.join("banner.png");
+    image::RgbaImage::new(4, 2)
+        .save(&path)
+        .expect("Failed to save texture.");
+    let info = PreviewAssetInfo::read(&path).expect("Failed to read texture.");
+    assert_eq!(info.kind, PreviewAssetKind::Texture);
+    assert_eq!(info.texture_size, Some((4, 2)));
+    assert!(PreviewAssetInfo::read(&root.join("missing.png")).is_err());
+    assert!(PreviewAssetInfo::read(&root.join("notes.txt")).is_err());
+}
+
+#[test]
+fn frames_bounds_and_orbits_around_the_target() {
+    let mut orbit = OrbitCamera::new();
+    let bounds = Bounds::new(Vec3A::new(-1.0, 0.0, -1.0), Vec3A::new(1.0, 2.0, 1.0));
+    orbit.frame(&bounds, 60.0);
+    assert_eq!(orbit.target, Vec3A::new(0.0, 1.0, 0.0));
+    // 画角の半分が30度なので、半径の2倍の距離になる。
+    // Half the field of view is 30 degrees, so the distance is twice the radius.
+    let radius = 3.0_f32.sqrt();
+    assert!((orbit.distance - radius * 2.0).abs() < 1e-4);
+    let distance = (orbit.get_position() - orbit.target).length();
+    assert!((distance - orbit.distance).abs() < 1e-4);
+
+    orbit.rotate(0.0, 10000.0);
+    assert!(orbit.pitch < std::f32::consts::FRAC_PI_2);
+    assert!(orbit.get_position().y > orbit.target.y);
+
+    let far = orbit.distance;
+    orbit.zoom(1.0);
+    assert!(orbit.distance < far);
+    orbit.zoom(-2.0);
+    assert!(orbit.distance > far);
+    orbit.zoom(1000.0);
+    assert!(orbit.distance > 0.0);
+}
+
+#[test]
+fn rotates_only_while_dragging() {
+    let mut orbit = OrbitCamera::new();
+    let yaw = orbit.yaw;
+    orbit.drag(100.0, 0.0);
+    assert_eq!(orbit.yaw, yaw);
+    orbit.begin_drag(100.0, 100.0);
+    orbit.drag(150.0, 100.0);
+    assert!(orbit.yaw < yaw);
+    let dragged_yaw = orbit.yaw;
+    orbit.end_drag();
+    orbit.drag(300.0, 100.0);
+    assert_eq!(orbit.yaw, dragged_yaw);
+}
+
+#[test]
+fn reports_changes_once_they_settle() {
+    let mut watcher = FileWatcher::new(vec![PathBuf::from("./missing/asset.gltf")]);
+    assert!(!watcher.update(vec![None]));
+    let saved = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+    // 書き込みの途中かもしれないので、一回目は変更としない。
+    // The file may still be being written, so the first sighting isn't a change.
+    assert!(!watcher.update(vec![Some(saved)]));
+    assert!(watcher.update(vec![Some(saved)]));
+    assert!(!watcher.update(vec![Some(saved)]));
+
+    let first = SystemTime::UNIX_EPOCH + Duration::from_secs(200);
+    let second = SystemTime::UNIX_EPOCH + Duration::from_secs(201);
+    assert!(!watcher.update(vec![Some(first)]));
+    assert!(!watcher.update(vec![Some(second)]));
+    assert!(watcher.update(vec![Some(second)]));
+}
+
+#[test]
+fn plays_loops_and_scrubs_clips() {
+    let mut playback = AnimationPlayback::new();
+    assert_eq!(playback.get_sample(), None);
+    playback.set_clips(vec![clip("Walk", 2.0), clip("Idle", 1.0)]);
+    assert_eq!(playback.get_clips()[0].name, "Idle");
+    assert_eq!(
+        playback.get_selected().map(|c| c.name.as_str()),
+        Some("Idle")
+    );
+
+    playback.advance(1.25);
+    assert!((playback.time - 0.25).abs() < 1e-5);
+
+    playback.select(1);
+    playback.is_looping = false;
+    playback.advance(3.0);
+    assert_eq!(playback.time, 2.0);
+    assert!(!playback.is_playing);
+
+    playback.scrub(5.0);
+    assert_eq!(playback.time, 2.0);
+    playback.scrub(0.5);
+    assert_eq!(playback.time, 0.5);
+    assert!(!playback.is_playing);
+    playback.advance(1.0);
+    assert_eq!(playback.time, 0.5);
+
+    let sample = playback.get_sample().expect("Failed to sample the clip.");
+    assert_eq!(sample.clip, "Walk");
+    assert_eq!(sample.time, 0.5);
+    assert!(!sample.is_looping);
+}
+
+#[test]
+fn keeps_the_selected_clip_after_reloading() {
+    let mut playback = AnimationPlayback::new();
+    playback.set_clips(vec![clip("Idle", 1.0), clip("Walk", 2.0)]);
+    playback.select(1);
+    playback.scrub(1.5);
+    playback.set_clips(vec![clip("Run", 0.5), clip("Walk", 1.0), clip("Idle", 1.0)]);
+    assert_eq!(
+        playback.get_selected().map(|c| c.name.as_str()),
+        Some("Walk")
+    );
+    assert_eq!(playback.time, 1.0);
+
+    playback.set_clips(vec![clip("Jump", 0.8)]);
+    assert_eq!(playback.get_selected_index(), Some(0));
+    playback.set_clips(vec![]);
+    assert_eq!(playback.get_selected(), None);
+    assert_eq!(playback.time, 0.0);
+}
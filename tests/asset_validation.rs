@@ -0,0 +1,64 @@
+use demo_game_rs::game::shared::structs::{check_spirv, validate_assets, AssetKind, ENGINE_SOURCE};
+use std::path::PathBuf;
+
+fn create_asset_root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("cutscenes")).expect("Failed to create cutscene directory.");
+    std::fs::create_dir_all(root.join("models/tank")).expect("Failed to create model directory.");
+    root
+}
+
+#[test]
+fn checks_spirv_headers() {
+    let mut spirv = vec![0x03, 0x02, 0x23, 0x07];
+    spirv.extend_from_slice(&[0; 16]);
+    assert!(check_spirv(&spirv).is_ok());
+    assert!(check_spirv(&spirv[..19]).is_err());
+    spirv[0] = 0;
+    assert!(check_spirv(&spirv).is_err());
+}
+
+#[test]
+fn reports_broken_references_with_paths() {
+    let root = create_asset_root("demo_game_asset_validation");
+    std::fs::write(root.join("cutscenes/broken.json"), "{ \"name\": ").unwrap();
+    std::fs::write(
+        root.join("models/tank/tank.gltf"),
+        r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "tank.bin", "byteLength": 4 }],
+            "images": [{ "uri": "missing.png" }]
+        }"#,
+    )
+    .unwrap();
+    std::fs::write(root.join("models/tank/tank.bin"), [0_u8; 4]).unwrap();
+
+    let report = validate_assets(&root);
+    assert!(!report.is_valid());
+    let is_broken = |kind: AssetKind, path: &str| {
+        report
+            .broken_references
+            .iter()
+            .any(|b| b.reference.kind == kind && b.reference.path == path)
+    };
+    assert!(is_broken(AssetKind::Scene, "./cutscenes/broken.json"));
+    assert!(is_broken(AssetKind::Texture, "./models/tank/missing.png"));
+    assert!(!is_broken(AssetKind::Model, "./models/tank/tank.gltf"));
+    assert!(!is_broken(AssetKind::Model, "./models/tank/tank.bin"));
+    // シェーダーはまだコンパイルされていないので、全て見つからない。
+    // No shader is compiled yet, so all of them are missing.
+    assert!(report
+        .broken_references
+        .iter()
+        .filter(|b| b.reference.kind == AssetKind::Shader)
+        .all(|b| b.reference.source == ENGINE_SOURCE && b.message == "File not found."));
+
+    let dependencies = report
+        .graph
+        .get_dependencies("./models/tank/tank.gltf")
+        .map(|r| r.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(dependencies, vec![AssetKind::Model, AssetKind::Texture]);
+    let _ = std::fs::remove_dir_all(&root);
+}
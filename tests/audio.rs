@@ -0,0 +1,25 @@
+use demo_game_rs::game::shared::systems::{get_distance_attenuation, AudioSystem};
+
+#[test]
+fn attenuates_sounds_by_distance() {
+    // 基準距離までは減衰しない。
+    // Sounds aren't attenuated within the reference distance.
+    assert_eq!(get_distance_attenuation(0.0), 1.0);
+    assert_eq!(get_distance_attenuation(2.0), 1.0);
+    assert!((get_distance_attenuation(4.0) - 0.5).abs() < 1e-6);
+    assert!((get_distance_attenuation(20.0) - 0.1).abs() < 1e-6);
+    assert!(get_distance_attenuation(30.0) < get_distance_attenuation(20.0));
+
+    // 最大距離より遠い音は聞こえない。
+    // Sounds beyond the maximum distance are silent.
+    assert!(get_distance_attenuation(60.0) > 0.0);
+    assert_eq!(get_distance_attenuation(60.1), 0.0);
+}
+
+#[test]
+fn fails_to_load_missing_sounds() {
+    let mut audio_system = AudioSystem::new();
+    assert!(audio_system
+        .load_sound("Missing", "./sounds/missing.ogg")
+        .is_err());
+}
@@ -0,0 +1,51 @@
+use demo_game_rs::game::graphics::vk::physical_device::FeatureSupport;
+
+fn create_feature_support() -> FeatureSupport {
+    FeatureSupport {
+        geometry_shader: true,
+        tessellation_shader: true,
+        sample_rate_shading: true,
+        sampler_anisotropy: true,
+        shader_sampled_image_array_dynamic_indexing: true,
+        runtime_descriptor_array: true,
+        descriptor_binding_partially_bound: true,
+        shader_sampled_image_array_non_uniform_indexing: true,
+        descriptor_binding_sampled_image_update_after_bind: true,
+        descriptor_binding_variable_descriptor_count: true,
+        descriptor_binding_update_unused_while_pending: true,
+        multi_draw_indirect: true,
+        shader_draw_parameters: true,
+        shader_clip_distance: true,
+        texture_compression_bc: true,
+        texture_compression_astc_ldr: true,
+    }
+}
+
+#[test]
+fn supports_bindless_with_every_descriptor_indexing_feature() {
+    assert!(create_feature_support().supports_bindless());
+}
+
+#[test]
+fn requires_every_descriptor_indexing_feature_for_bindless() {
+    let disable_features: [fn(&mut FeatureSupport); 6] = [
+        |f| f.runtime_descriptor_array = false,
+        |f| f.descriptor_binding_partially_bound = false,
+        |f| f.shader_sampled_image_array_non_uniform_indexing = false,
+        |f| f.descriptor_binding_sampled_image_update_after_bind = false,
+        |f| f.descriptor_binding_variable_descriptor_count = false,
+        |f| f.descriptor_binding_update_unused_while_pending = false,
+    ];
+    for disable_feature in disable_features.iter() {
+        let mut feature_support = create_feature_support();
+        disable_feature(&mut feature_support);
+        assert!(!feature_support.supports_bindless());
+    }
+
+    // 描述子インデックスに関係ない機能は影響しない。
+    // Features unrelated to descriptor indexing don't matter.
+    let mut feature_support = create_feature_support();
+    feature_support.geometry_shader = false;
+    feature_support.multi_draw_indirect = false;
+    assert!(feature_support.supports_bindless());
+}
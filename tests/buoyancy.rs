@@ -0,0 +1,47 @@
+use demo_game_rs::game::shared::components::Buoyancy;
+use demo_game_rs::game::shared::structs::{get_buoyant_force, WaterVolume, Wave};
+use glam::{Quat, Vec2, Vec3A};
+
+#[test]
+fn samples_wave_height_inside_the_volume() {
+    let water = WaterVolume::new(Vec2::new(10.0, 10.0), Vec2::new(-10.0, -10.0), 2.0)
+        .with_wave(Wave::new(0.5, 8.0, 1.0, Vec2::new(2.0, 0.0)));
+    assert_eq!(water.get_height(0.0, 0.0, 0.0), Some(2.0));
+    // 波長の4分の1で波の頂点になる。
+    // The wave peaks a quarter wavelength along its direction.
+    let peak = water.get_height(2.0, 5.0, 0.0).unwrap();
+    assert!((peak - 2.5).abs() < 1e-5);
+    let later = water.get_height(2.0, 5.0, 4.0).unwrap();
+    assert!((later - 1.5).abs() < 1e-5);
+    assert_eq!(water.get_height(11.0, 0.0, 0.0), None);
+}
+
+#[test]
+fn buoyant_force_grows_with_depth_until_submerged() {
+    assert_eq!(get_buoyant_force(2.0, -10.0, 1.0, -0.5, 1.0), 0.0);
+    assert!((get_buoyant_force(2.0, -10.0, 1.0, 0.5, 1.0) - 10.0).abs() < 1e-5);
+    assert!((get_buoyant_force(2.0, -10.0, 1.0, 3.0, 1.0) - 20.0).abs() < 1e-5);
+}
+
+#[test]
+fn decks_follow_platform_rotation() {
+    let platform = Buoyancy::platform(Vec3A::new(2.0, 0.5, 1.0));
+    assert!((platform.get_volume() - 8.0).abs() < 1e-5);
+    let position = Vec3A::new(0.0, 1.0, 0.0);
+    let height = platform.get_deck_height(position, Quat::identity(), Vec3A::new(1.5, 3.0, 0.5));
+    assert_eq!(height, Some(1.5));
+    assert_eq!(
+        platform.get_deck_height(position, Quat::identity(), Vec3A::new(2.5, 3.0, 0.0)),
+        None
+    );
+
+    // 90度回すと、長い辺がZ軸に沿う。
+    // Turning it 90 degrees lays the long side along the Z axis.
+    let rotation = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+    assert!(platform
+        .get_deck_height(position, rotation, Vec3A::new(0.0, 3.0, 1.5))
+        .is_some());
+    let points = platform.get_sample_points(position, rotation);
+    assert!(points.iter().all(|p| (p.y - 0.5).abs() < 1e-5));
+    assert!(points.iter().all(|p| (p.z.abs() - 2.0).abs() < 1e-5));
+}
@@ -0,0 +1,96 @@
+use demo_game_rs::game::shared::components::Bounds;
+use demo_game_rs::game::shared::structs::{Bvh, Frustum, Ray};
+use glam::{Mat4, Vec3, Vec3A};
+use slotmap::{DefaultKey, SlotMap};
+
+fn unit_box(center: Vec3A) -> Bounds {
+    Bounds::new(center - Vec3A::splat(0.5), center + Vec3A::splat(0.5))
+}
+
+fn keys(count: usize) -> Vec<DefaultKey> {
+    let mut map = SlotMap::new();
+    (0..count).map(|_| map.insert(())).collect()
+}
+
+fn row_of_boxes(count: usize) -> (Bvh, Vec<DefaultKey>) {
+    let entities = keys(count);
+    let mut bvh = Bvh::new(0.0);
+    for (index, entity) in entities.iter().enumerate() {
+        bvh.insert(*entity, unit_box(Vec3A::new(index as f32 * 2.0, 0.0, 0.0)));
+    }
+    (bvh, entities)
+}
+
+#[test]
+fn raycast_finds_the_nearest_box() {
+    let (bvh, entities) = row_of_boxes(16);
+    let ray = Ray::new(Vec3A::new(-10.0, 0.0, 0.0), Vec3A::unit_x());
+    let (entity, distance) = bvh
+        .raycast(&ray, |entity| {
+            bvh.get_bounds(entity)
+                .and_then(|bounds| ray.intersect_bounds(&bounds))
+        })
+        .expect("The ray missed every box.");
+    assert_eq!(entity, entities[0]);
+    assert!((distance - 9.5).abs() < 1e-5);
+
+    let ray = Ray::new(Vec3A::new(6.0, 10.0, 0.0), -Vec3A::unit_y());
+    let hit = bvh.raycast(&ray, |entity| {
+        bvh.get_bounds(entity)
+            .and_then(|bounds| ray.intersect_bounds(&bounds))
+    });
+    assert_eq!(hit.map(|(entity, _)| entity), Some(entities[3]));
+}
+
+#[test]
+fn tree_stays_shallow() {
+    let (bvh, _) = row_of_boxes(64);
+    assert_eq!(bvh.len(), 64);
+    assert!(bvh.get_height() < 16, "Height was {}.", bvh.get_height());
+}
+
+#[test]
+fn small_movements_stay_inside_the_margin() {
+    let entities = keys(2);
+    let mut bvh = Bvh::new(0.5);
+    bvh.insert(entities[0], unit_box(Vec3A::zero()));
+    bvh.insert(entities[1], unit_box(Vec3A::new(5.0, 0.0, 0.0)));
+    assert!(!bvh.update(entities[0], unit_box(Vec3A::new(0.25, 0.0, 0.0))));
+    assert!(bvh.update(entities[0], unit_box(Vec3A::new(20.0, 0.0, 0.0))));
+
+    // 動いた箱は合わせ直した木から見つかる。
+    // The moved box is found through the refitted tree.
+    let found = bvh.query(|bounds| bounds.max.x > 15.0);
+    assert_eq!(found, vec![entities[0]]);
+}
+
+#[test]
+fn removing_keeps_the_other_entities() {
+    let (mut bvh, entities) = row_of_boxes(5);
+    assert!(bvh.remove(entities[2]));
+    assert!(!bvh.remove(entities[2]));
+    assert_eq!(bvh.len(), 4);
+    let mut found = bvh.query(|_| true);
+    found.sort();
+    let mut expected = entities.clone();
+    expected.remove(2);
+    expected.sort();
+    assert_eq!(found, expected);
+
+    for entity in expected.iter() {
+        bvh.remove(*entity);
+    }
+    assert!(bvh.is_empty());
+    assert_eq!(bvh.get_height(), 0);
+}
+
+#[test]
+fn frustum_query_skips_boxes_behind_the_camera() {
+    let (mut bvh, entities) = row_of_boxes(2);
+    bvh.update(entities[0], unit_box(Vec3A::new(0.0, 0.0, -10.0)));
+    bvh.update(entities[1], unit_box(Vec3A::new(0.0, 0.0, 10.0)));
+    let projection = Mat4::perspective_rh(90.0_f32.to_radians(), 1.0, 0.1, 100.0);
+    let view = Mat4::look_at_rh(Vec3::zero(), -Vec3::unit_z(), Vec3::unit_y());
+    let frustum = Frustum::new(projection * view);
+    assert_eq!(bvh.query_frustum(&frustum), vec![entities[0]]);
+}
@@ -0,0 +1,38 @@
+use demo_game_rs::game::shared::structs::games::connection::{
+    get_backoff_delay, ConnectionEvent, ConnectionMonitor, ConnectionState,
+};
+use std::time::Duration;
+
+#[test]
+fn backoff_grows_and_is_capped() {
+    for attempt in 0..4 {
+        let expected = Duration::from_millis(500 * (1 << attempt));
+        let delay = get_backoff_delay(attempt);
+        assert!(delay >= expected.mul_f64(0.8) && delay <= expected.mul_f64(1.2));
+    }
+    for attempt in 5..64 {
+        assert!(get_backoff_delay(attempt) <= Duration::from_secs(16).mul_f64(1.2));
+    }
+}
+
+#[test]
+fn reports_connection_loss_once() {
+    let monitor = ConnectionMonitor::new();
+    let handle = monitor.clone();
+    handle.check_status(&tonic::Status::unauthenticated("Invalid JWT token."));
+    assert!(monitor.is_connected());
+
+    handle.check_status(&tonic::Status::unavailable("Connection reset."));
+    handle.report_lost("Broken pipe.");
+    assert_eq!(
+        monitor.get_state(),
+        ConnectionState::Reconnecting { attempt: 0 }
+    );
+    assert_eq!(
+        monitor.poll_events(),
+        vec![ConnectionEvent::ConnectionLost {
+            reason: "Connection reset.".to_string()
+        }]
+    );
+    assert!(monitor.poll_events().is_empty());
+}
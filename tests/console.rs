@@ -0,0 +1,102 @@
+use demo_game_rs::game::ui::{Console, ConsoleCommand};
+
+fn submit(console: &mut Console, line: &str) {
+    console.set_input(line);
+    console.submit();
+}
+
+#[test]
+fn parses_names_and_quoted_arguments() {
+    let command = ConsoleCommand::parse("  Spawn \"my model.gltf\" 10 0   5 ").unwrap();
+    assert_eq!(command.name, "spawn");
+    assert_eq!(command.args, vec!["my model.gltf", "10", "0", "5"]);
+    assert_eq!(command.get_f32(1).unwrap(), 10.0);
+    assert!(command.get_f32(0).is_err());
+    assert!(command.get_arg(4).is_err());
+
+    assert_eq!(ConsoleCommand::parse("   "), None);
+    assert_eq!(ConsoleCommand::parse("set name \"\"").unwrap().args[1], "");
+}
+
+#[test]
+fn only_registered_commands_are_submitted() {
+    let mut console = Console::new();
+    console.register("teleport", "[x z]", "Move the local player.");
+    submit(&mut console, "teleport 1 2");
+    submit(&mut console, "fly");
+    // `help`と`clear`はコンソールの中で処理される。
+    // `help` and `clear` are handled inside the console.
+    submit(&mut console, "help");
+
+    let submitted = console.take_submitted();
+    assert_eq!(submitted.len(), 1);
+    assert_eq!(submitted[0].args, vec!["1", "2"]);
+    assert!(console.take_submitted().is_empty());
+    assert!(console
+        .get_output()
+        .any(|line| line.starts_with("Unknown command: fly")));
+    assert!(console
+        .get_output()
+        .any(|line| line == "teleport [x z] - Move the local player."));
+
+    submit(&mut console, "clear");
+    assert_eq!(console.get_output().count(), 0);
+    assert_eq!(console.get_input(), "");
+}
+
+#[test]
+fn browses_the_history() {
+    let mut console = Console::new();
+    submit(&mut console, "help");
+    submit(&mut console, "clear");
+    // 続けて同じ行を入力しても、履歴には一つだけ残る。
+    // Entering the same line in a row only keeps it once in the history.
+    submit(&mut console, "clear");
+
+    console.previous_history();
+    assert_eq!(console.get_input(), "clear");
+    console.previous_history();
+    assert_eq!(console.get_input(), "help");
+    console.previous_history();
+    assert_eq!(console.get_input(), "help");
+    console.next_history();
+    assert_eq!(console.get_input(), "clear");
+    console.next_history();
+    assert_eq!(console.get_input(), "");
+}
+
+#[test]
+fn completes_command_names() {
+    let mut console = Console::new();
+    console.register("spawn", "<model>", "Spawn a model.");
+    console.register("set", "<variable> <value>", "Set a variable.");
+    console.register("settings", "", "Open the settings.");
+
+    console.set_input("sp");
+    console.complete();
+    assert_eq!(console.get_input(), "spawn ");
+
+    // 候補が複数なら共通の部分まで埋め、候補を表示する。
+    // Several candidates fill in their common prefix and are listed.
+    console.set_input("s");
+    console.complete();
+    assert_eq!(console.get_input(), "s");
+    assert_eq!(
+        console.get_output().last().map(|line| line.as_str()),
+        Some("spawn  set  settings")
+    );
+    console.set_input("se");
+    console.complete();
+    assert_eq!(console.get_input(), "set");
+
+    console.set_input("spawn tank");
+    console.complete();
+    assert_eq!(console.get_input(), "spawn tank");
+}
+
+#[test]
+fn keeps_the_toggle_key_out_of_the_input() {
+    let mut console = Console::new();
+    console.set_input("`help`");
+    assert_eq!(console.get_input(), "help");
+}
@@ -0,0 +1,31 @@
+use demo_game_rs::game::shared::structs::Counts;
+use std::sync::atomic::Ordering;
+
+#[test]
+fn released_ssbo_indices_are_reused_before_new_ones() {
+    let mut counts = Counts::new();
+    assert_eq!(counts.acquire_ssbo_index(), 0);
+    assert_eq!(counts.acquire_ssbo_index(), 1);
+    assert_eq!(counts.acquire_ssbo_index(), 2);
+
+    counts.release_ssbo_index(1);
+    // 同じインデックスを二回解放しても、一回しか再利用されない。
+    // Releasing the same index twice only lets it be reused once.
+    counts.release_ssbo_index(1);
+    assert_eq!(counts.acquire_ssbo_index(), 1);
+    assert_eq!(counts.acquire_ssbo_index(), 3);
+}
+
+#[test]
+fn reset_starts_counting_from_zero() {
+    let mut counts = Counts::new();
+    counts.acquire_ssbo_index();
+    counts.acquire_ssbo_index();
+    counts.release_ssbo_index(0);
+    counts.model_count.store(2, Ordering::SeqCst);
+
+    counts.reset();
+    assert!(counts.free_ssbo_indices.is_empty());
+    assert_eq!(counts.model_count.load(Ordering::SeqCst), 0);
+    assert_eq!(counts.acquire_ssbo_index(), 0);
+}
@@ -0,0 +1,128 @@
+use demo_game_rs::game::shared::structs::games::credentials::{
+    get_token_expiry, is_token_expiring, validate_login, validate_registration, CredentialError,
+    SavedSession, MIN_PASSWORD_LENGTH, TOKEN_REFRESH_MARGIN,
+};
+use std::time::{Duration, UNIX_EPOCH};
+
+fn create_token(claims: &str) -> String {
+    let encode = |s: &str| base64::encode_config(s, base64::URL_SAFE_NO_PAD);
+    format!(
+        "{}.{}.signature",
+        encode(r#"{"alg":"HS256","typ":"JWT"}"#),
+        encode(claims)
+    )
+}
+
+#[test]
+fn accepts_valid_registration() {
+    assert_eq!(
+        validate_registration(
+            "carol_01",
+            "Carol",
+            "carol@example.com",
+            "carol-pass",
+            "carol-pass"
+        ),
+        Ok(())
+    );
+    assert_eq!(validate_login("carol@example.com", "carol-pass"), Ok(()));
+}
+
+#[test]
+fn reports_the_first_invalid_field() {
+    assert_eq!(
+        validate_registration("", "Carol", "carol@example.com", "pass", "pass"),
+        Err(CredentialError::Missing("Username"))
+    );
+    assert_eq!(
+        validate_registration("carol smith", "Carol", "carol@example.com", "pass", "pass"),
+        Err(CredentialError::InvalidName("Username"))
+    );
+    assert_eq!(
+        validate_registration(
+            "carol",
+            "Carol",
+            "carol@example",
+            "carol-pass",
+            "carol-pass"
+        ),
+        Err(CredentialError::InvalidEmail)
+    );
+    assert_eq!(
+        validate_registration("carol", "Carol", "carol@example.com", "short", "short"),
+        Err(CredentialError::PasswordTooShort(MIN_PASSWORD_LENGTH))
+    );
+    assert_eq!(
+        validate_registration(
+            "carol",
+            "Carol",
+            "carol@example.com",
+            "carol-pass",
+            "carol-pas"
+        ),
+        Err(CredentialError::PasswordMismatch)
+    );
+    assert_eq!(
+        validate_login("   ", "carol-pass"),
+        Err(CredentialError::Missing("Username or email"))
+    );
+    assert!(matches!(
+        validate_login(&"a".repeat(64), "carol-pass"),
+        Err(CredentialError::TooLong(_, _))
+    ));
+}
+
+#[test]
+fn reads_token_expiry() {
+    let token = create_token(r#"{"sub":"bot","exp":1000}"#);
+    let expiry = UNIX_EPOCH + Duration::from_secs(1000);
+    assert_eq!(get_token_expiry(&token), Some(expiry));
+    assert!(!is_token_expiring(
+        &token,
+        expiry - TOKEN_REFRESH_MARGIN - Duration::from_secs(1)
+    ));
+    assert!(is_token_expiring(&token, expiry - TOKEN_REFRESH_MARGIN));
+    assert!(is_token_expiring(&token, expiry + Duration::from_secs(1)));
+
+    // 失効する時刻が分からないトークンは、サーバーに断られるまで使う。
+    // Tokens without a known expiry are used until the server rejects them.
+    let without_expiry = create_token(r#"{"sub":"bot"}"#);
+    assert_eq!(get_token_expiry(&without_expiry), None);
+    assert!(!is_token_expiring(&without_expiry, expiry));
+    assert_eq!(get_token_expiry("mock-token"), None);
+}
+
+#[test]
+fn saves_and_deletes_session() {
+    let path = std::env::temp_dir().join("demo_game_session.json");
+    let session = SavedSession {
+        server_endpoint: "http://127.0.0.1:50051".to_string(),
+        account: "carol".to_string(),
+        token: create_token(r#"{"exp":1000}"#),
+        refresh_token: "refresh-token".to_string(),
+    };
+    session.save(&path).unwrap();
+    assert_eq!(SavedSession::load(&path).unwrap(), session);
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(!content.contains("password"));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o077, 0);
+
+        // 他の人が読めるファイルに上書きしても、権限は絞られる。
+        // Overwriting a file readable by others narrows its permissions as well.
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        session.save(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o077, 0);
+    }
+
+    SavedSession::delete(&path).unwrap();
+    assert!(!path.exists());
+    assert!(SavedSession::delete(&path).is_ok());
+    assert!(SavedSession::load(&path).is_err());
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,170 @@
+use demo_game_rs::game::shared::structs::games::interpolation_debug::InterpolationDebugView;
+use demo_game_rs::game::shared::structs::{
+    DebugDraw, DebugShape, DebugVertex, ScreenShape, SPHERE_SEGMENTS,
+};
+use glam::{Mat4, Vec2, Vec3, Vec3A};
+
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+fn view_projection() -> Mat4 {
+    let projection = Mat4::perspective_rh(90.0_f32.to_radians(), 1.0, 0.1, 100.0);
+    let view = Mat4::look_at_rh(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(0.0, -1.0, 0.0),
+    );
+    projection * view
+}
+
+fn to_vec3(vertex: &DebugVertex) -> Vec3A {
+    let [x, y, z] = vertex.position;
+    Vec3A::new(x, y, z)
+}
+
+fn assert_near(actual: Vec2, expected: Vec2) {
+    assert!(
+        (actual - expected).length() < 1e-3,
+        "{:?} != {:?}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn projects_points_onto_the_screen() {
+    let mut debug_draw = DebugDraw::new();
+    debug_draw.point(Vec3A::new(0.0, 0.0, -10.0), 4.0, WHITE);
+    debug_draw.point(Vec3A::new(10.0, 0.0, -10.0), 4.0, WHITE);
+    // カメラの後ろの点は描かない。
+    // Points behind the camera aren't drawn.
+    debug_draw.point(Vec3A::new(0.0, 0.0, 10.0), 4.0, WHITE);
+
+    let shapes = debug_draw.project(view_projection(), 800.0, 600.0);
+    assert_eq!(shapes.len(), 2);
+    match shapes[0] {
+        ScreenShape::Circle { center, radius, .. } => {
+            assert_near(center, Vec2::new(400.0, 300.0));
+            assert_eq!(radius, 4.0);
+        }
+        _ => panic!("A point was projected as a line."),
+    }
+    match shapes[1] {
+        ScreenShape::Circle { center, .. } => assert!((center.y - 300.0).abs() < 1e-3),
+        _ => panic!("A point was projected as a line."),
+    }
+}
+
+#[test]
+fn cuts_lines_reaching_behind_the_camera() {
+    let mut debug_draw = DebugDraw::new();
+    debug_draw.line(
+        Vec3A::new(0.0, 0.0, -10.0),
+        Vec3A::new(0.0, 0.0, 10.0),
+        2.0,
+        WHITE,
+    );
+    debug_draw.line(
+        Vec3A::new(0.0, 0.0, 5.0),
+        Vec3A::new(0.0, 0.0, 10.0),
+        2.0,
+        WHITE,
+    );
+    let shapes = debug_draw.project(view_projection(), 800.0, 600.0);
+    assert_eq!(shapes.len(), 1);
+    match shapes[0] {
+        ScreenShape::Line {
+            from,
+            to,
+            thickness,
+            ..
+        } => {
+            assert_near(from, Vec2::new(400.0, 300.0));
+            assert!(to.x.is_finite() && to.y.is_finite());
+            assert_eq!(thickness, 2.0);
+        }
+        _ => panic!("A line was projected as a point."),
+    }
+}
+
+#[test]
+fn draws_interpolation_traces() {
+    let mut view = InterpolationDebugView::new();
+    view.record_received("remote", Vec3A::zero());
+    assert!(view.get_trace("remote").is_none());
+
+    view.set_enabled(true);
+    view.record_received("remote", Vec3A::new(0.0, 0.0, 0.0));
+    view.record_received("remote", Vec3A::new(1.0, 0.0, 0.0));
+    let window = (Vec3A::new(0.0, 0.0, 0.0), Vec3A::new(1.0, 0.0, 0.0));
+    view.record_rendered("remote", Vec3A::new(0.25, 0.0, 0.0), window, false);
+    view.record_rendered("remote", Vec3A::new(0.5, 0.0, 0.0), window, false);
+
+    let mut debug_draw = DebugDraw::new();
+    view.draw(&mut debug_draw);
+    let shapes = debug_draw.get_shapes();
+    let points = shapes
+        .iter()
+        .filter(|shape| matches!(shape, DebugShape::Point { .. }))
+        .count();
+    // 受け取った二つの点と、今描画している位置。
+    // The two received dots and the position being rendered now.
+    assert_eq!(points, 3);
+    // 補間の範囲と、描画した位置の軌跡。
+    // The interpolation window and the trail of rendered positions.
+    assert_eq!(shapes.len() - points, 2);
+
+    view.retain_players(|player_id| player_id != "remote");
+    assert!(view.get_trace("remote").is_none());
+}
+
+#[test]
+fn debug_vertex_is_sixteen_bytes() {
+    assert_eq!(std::mem::size_of::<DebugVertex>(), 16);
+}
+
+#[test]
+fn aabb_has_twelve_unit_edges() {
+    let mut debug_draw = DebugDraw::new();
+    debug_draw.draw_aabb(Vec3A::zero(), Vec3A::one(), WHITE);
+    let vertices = debug_draw.get_line_vertices();
+    assert_eq!(vertices.len(), 24);
+    for pair in vertices.chunks_exact(2) {
+        let length = (to_vec3(&pair[1]) - to_vec3(&pair[0])).length();
+        assert!((length - 1.0).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn sphere_points_lie_on_the_radius() {
+    let mut debug_draw = DebugDraw::new();
+    let center = Vec3A::new(1.0, 2.0, 3.0);
+    debug_draw.draw_sphere(center, 2.0, WHITE);
+    let vertices = debug_draw.get_line_vertices();
+    assert_eq!(vertices.len(), SPHERE_SEGMENTS * 3 * 2);
+    assert!(vertices
+        .iter()
+        .all(|vertex| ((to_vec3(vertex) - center).length() - 2.0).abs() < 1e-5));
+}
+
+#[test]
+fn frustum_of_identity_spans_clip_space() {
+    let mut debug_draw = DebugDraw::new();
+    debug_draw.draw_frustum(Mat4::identity(), WHITE);
+    let vertices = debug_draw.get_line_vertices();
+    assert_eq!(vertices.len(), 24);
+    for vertex in vertices.iter() {
+        let position = to_vec3(vertex);
+        assert!((position.x.abs() - 1.0).abs() < 1e-5);
+        assert!((position.y.abs() - 1.0).abs() < 1e-5);
+        assert!(position.z.abs() < 1e-5 || (position.z - 1.0).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn taking_line_vertices_empties_the_list() {
+    let mut debug_draw = DebugDraw::new();
+    debug_draw.draw_line(Vec3A::zero(), Vec3A::unit_x(), WHITE);
+    assert!(!debug_draw.is_empty());
+    assert_eq!(debug_draw.take_line_vertices().len(), 2);
+    assert!(debug_draw.is_empty());
+}
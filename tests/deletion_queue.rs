@@ -0,0 +1,32 @@
+use demo_game_rs::game::shared::structs::DeletionQueue;
+
+#[test]
+fn keeps_deletions_until_their_frame_is_finished() {
+    let mut queue = DeletionQueue::new();
+    queue.push(3, "vertex buffer");
+    queue.push(3, "index buffer");
+    queue.push(5, "texture");
+    assert_eq!(queue.len(), 3);
+
+    assert!(queue.collect_completed(2).is_empty());
+    assert_eq!(
+        queue.collect_completed(4),
+        vec!["vertex buffer", "index buffer"]
+    );
+    assert_eq!(queue.len(), 1);
+    // 同じフレームを二回終わらせても、もう一度取り出されることはない。
+    // Finishing the same frame twice doesn't take anything out again.
+    assert!(queue.collect_completed(4).is_empty());
+    assert_eq!(queue.collect_completed(5), vec!["texture"]);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn drains_everything_when_idle() {
+    let mut queue = DeletionQueue::new();
+    queue.push(10, 1);
+    queue.push(12, 2);
+    assert_eq!(queue.drain(), vec![1, 2]);
+    assert!(queue.is_empty());
+    assert!(queue.collect_completed(u64::MAX).is_empty());
+}
@@ -0,0 +1,77 @@
+use demo_game_rs::game::shared::structs::{
+    CrashReport, FrameSubmission, ValidationLog, ValidationMessage, ValidationSeverity,
+};
+
+#[test]
+fn validation_log_keeps_the_latest_messages() {
+    let mut log = ValidationLog::new(2);
+    assert!(log.is_empty());
+    log.push(ValidationSeverity::Warning, "first");
+    log.push(ValidationSeverity::Error, "second");
+    log.push(ValidationSeverity::Error, "third");
+    assert_eq!(log.len(), 2);
+    let messages = log
+        .get_messages()
+        .into_iter()
+        .map(|message| message.message)
+        .collect::<Vec<_>>();
+    assert_eq!(messages, vec!["second".to_string(), "third".to_string()]);
+
+    let mut log = ValidationLog::new(0);
+    log.push(ValidationSeverity::Error, "ignored");
+    assert!(log.is_empty());
+}
+
+#[test]
+fn frame_submission_counts_command_buffers_per_pass() {
+    let mut frame = FrameSubmission::default();
+    frame.add_secondary_command_buffers(3);
+    assert!(frame.passes.is_empty());
+
+    frame.begin(1);
+    frame.add_pass("Shadow");
+    frame.add_secondary_command_buffers(2);
+    frame.add_pass("Scene");
+    frame.add_secondary_command_buffers(4);
+    frame.add_secondary_command_buffers(1);
+    assert_eq!(frame.frame_index, 1);
+    assert_eq!(frame.passes[0].secondary_command_buffers, 2);
+    assert_eq!(frame.passes[1].secondary_command_buffers, 5);
+    assert_eq!(frame.get_secondary_command_buffer_count(), 7);
+
+    frame.begin(2);
+    assert!(frame.passes.is_empty());
+    assert_eq!(frame.get_secondary_command_buffer_count(), 0);
+}
+
+#[test]
+fn crash_report_lists_every_section() {
+    let mut frame = FrameSubmission::default();
+    frame.begin(0);
+    frame.add_pass("Scene");
+    frame.add_secondary_command_buffers(12);
+    let report = CrashReport {
+        reason: "The graphics device was lost while trying to submit the queue.".to_string(),
+        frame,
+        validation_messages: vec![ValidationMessage {
+            severity: ValidationSeverity::Error,
+            message: "Invalid image layout".to_string(),
+        }],
+        pipelines: vec!["BasicShader (1 pipelines)".to_string()],
+        checkpoints: vec![],
+        memory_statistics: None,
+    };
+    let text = report.to_string();
+    assert!(text.starts_with("Reason: The graphics device was lost"));
+    assert!(text.contains("[Frame 0] 12 secondary command buffers"));
+    assert!(text.contains("  Scene: 12 secondary command buffers"));
+    assert!(text.contains("  BasicShader (1 pipelines)"));
+    assert!(text.contains("[Checkpoints]\n  Unavailable"));
+    assert!(text.contains("  ERROR: Invalid image layout"));
+    assert!(text.contains("[Memory]\nUnavailable"));
+
+    let directory = std::env::temp_dir().join("demo_game_crash_reports");
+    let path = report.write(&directory).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,40 @@
+use demo_game_rs::game::shared::structs::FixedTimestep;
+use demo_game_rs::game::Transform;
+use glam::Vec3A;
+
+#[test]
+fn runs_a_step_for_each_full_timestep() {
+    let mut timestep = FixedTimestep::new(0.1, 5);
+    assert_eq!(timestep.advance(0.05), 0);
+    assert!((timestep.get_alpha() - 0.5).abs() < 1e-5);
+    assert_eq!(timestep.advance(0.1), 1);
+    assert!((timestep.get_alpha() - 0.5).abs() < 1e-5);
+    assert_eq!(timestep.advance(0.26), 3);
+    assert!((timestep.get_alpha() - 0.1).abs() < 1e-5);
+}
+
+#[test]
+fn drops_time_it_cant_catch_up_on() {
+    let mut timestep = FixedTimestep::new(0.1, 5);
+    assert_eq!(timestep.advance(2.0), 5);
+    assert!(timestep.get_alpha().abs() < 1e-5);
+    assert_eq!(timestep.advance(0.1), 1);
+    assert_eq!(timestep.advance(-1.0), 0);
+}
+
+#[test]
+fn interpolates_from_the_previous_step() {
+    let previous = Transform::new();
+    let mut current = Transform::new();
+    current.position = Vec3A::new(2.0, 0.0, 4.0);
+    current.rotation = Vec3A::new(0.0, 1.0, 0.0);
+    let halfway = current.interpolate_from(&previous, 0.5);
+    assert!((halfway.position - Vec3A::new(1.0, 0.0, 2.0)).length() < 1e-5);
+    assert!((halfway.rotation - Vec3A::new(0.0, 0.5, 0.0)).length() < 1e-5);
+    assert!((halfway.scale - Vec3A::one()).length() < 1e-5);
+
+    let end = current.interpolate_from(&previous, 1.0);
+    assert!((end.position - current.position).length() < 1e-5);
+    let unchanged = current.interpolate_from(&current, 0.25);
+    assert!((unchanged.rotation - current.rotation).length() < 1e-5);
+}
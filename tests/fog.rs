@@ -0,0 +1,61 @@
+use demo_game_rs::game::shared::structs::{FogSettings, FOG_EDGE_START};
+use demo_game_rs::game::ui::TweakRegistry;
+
+const FAR_PLANE: f32 = 1000.0;
+
+#[test]
+fn thickens_with_distance_and_thins_with_height() {
+    let fog = FogSettings::default();
+    assert!((fog.get_visibility(0.0, 0.0, FAR_PLANE) - 1.0).abs() < 1e-5);
+    let near = fog.get_visibility(50.0, 0.0, FAR_PLANE);
+    let far = fog.get_visibility(300.0, 0.0, FAR_PLANE);
+    assert!(far < near);
+    assert!(near < 1.0);
+
+    let high = fog.get_visibility(300.0, 100.0, FAR_PLANE);
+    assert!(high > far);
+    // 基準の高さより下は、基準の高さと同じ濃さ。
+    // Below the base height, the fog is as thick as at the base height.
+    let low = fog.get_visibility(300.0, -20.0, FAR_PLANE);
+    assert!((low - far).abs() < 1e-5);
+}
+
+#[test]
+fn reaches_the_sky_color_before_the_far_plane() {
+    let fog = FogSettings {
+        density: 0.0,
+        ..FogSettings::default()
+    };
+    let edge_start = FAR_PLANE * FOG_EDGE_START;
+    assert!((fog.get_visibility(edge_start, 0.0, FAR_PLANE) - 1.0).abs() < 1e-5);
+    let fading = fog.get_visibility((edge_start + FAR_PLANE) * 0.5, 0.0, FAR_PLANE);
+    assert!(fading > 0.0 && fading < 1.0);
+    assert!(fog.get_visibility(FAR_PLANE, 0.0, FAR_PLANE).abs() < 1e-5);
+}
+
+#[test]
+fn disabled_fog_keeps_everything_visible() {
+    let fog = FogSettings {
+        is_enabled: false,
+        ..FogSettings::default()
+    };
+    assert!((fog.get_visibility(FAR_PLANE, 0.0, FAR_PLANE) - 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn reads_the_settings_from_tweaks() {
+    let mut tweaks = TweakRegistry::new();
+    let mut fog = FogSettings::default();
+    fog.register_tweaks(&mut tweaks);
+    assert_eq!(fog, FogSettings::default());
+
+    tweaks
+        .set_from_str("fog_density", "0.01")
+        .expect("Failed to set the fog density.");
+    tweaks
+        .set_from_str("fog", "off")
+        .expect("Failed to disable the fog.");
+    fog.apply_tweaks(&tweaks);
+    assert!((fog.density - 0.01).abs() < 1e-5);
+    assert!(!fog.is_enabled);
+}
@@ -0,0 +1,62 @@
+use demo_game_rs::game::ui::font::{read_font_files, setup_font_atlas, FontFaces};
+use demo_game_rs::game::ui::GlyphCache;
+use nuklear::FontAtlasFormat;
+
+const UI_FONT: &str = "resource/Comfortaa-Regular.ttf";
+const FALLBACK_FONT: &str = "resource/HiraMaruProN-W4.otf";
+
+#[test]
+fn skips_missing_fallback_fonts() {
+    let fallback_fonts = vec![
+        "resource/Missing.ttf".to_string(),
+        FALLBACK_FONT.to_string(),
+    ];
+    let font_bytes = read_font_files(UI_FONT, &fallback_fonts).expect("Failed to read fonts.");
+    assert_eq!(font_bytes.len(), 2);
+    // UIのフォントは必須。
+    // The UI font is required.
+    assert!(read_font_files("resource/Missing.ttf", &fallback_fonts).is_err());
+}
+
+#[test]
+fn reads_the_characters_of_each_face() {
+    let font_bytes =
+        read_font_files(UI_FONT, &[FALLBACK_FONT.to_string()]).expect("Failed to read fonts.");
+    let font_faces = FontFaces::new(&font_bytes);
+    let coverages = font_faces.get_coverages();
+    assert_eq!(coverages.len(), 2);
+    assert!(!coverages[0].contains('あ' as u32));
+    assert!(coverages[1].contains('あ' as u32));
+
+    // 読めない代わりのフォントは飛ばし、読めないUIのフォントは全ての文字を持つものと見なす。
+    // Unreadable fallback fonts are skipped, and an unreadable UI font is assumed to have every character.
+    let font_faces = FontFaces::new(&[b"not a font".to_vec(), b"not a font".to_vec()]);
+    assert_eq!(font_faces.get_coverages().len(), 1);
+    assert!(font_faces.get_coverages()[0].contains('あ' as u32));
+}
+
+#[test]
+fn adds_every_size_to_the_atlas() {
+    let font_bytes =
+        read_font_files(UI_FONT, &[FALLBACK_FONT.to_string()]).expect("Failed to read fonts.");
+    let mut font_faces = FontFaces::new(&font_bytes);
+    let mut glyph_cache = GlyphCache::new(&[16, 24]);
+    glyph_cache.request_text("こんにちは");
+    let glyph_ranges = glyph_cache.get_font_glyph_ranges(font_faces.get_coverages());
+
+    let mut allocator = nuklear::Allocator::new_vec();
+    let (mut atlas, fonts) = setup_font_atlas(
+        &mut allocator,
+        &mut font_faces,
+        &glyph_ranges,
+        &glyph_cache.get_sizes(),
+    );
+    // 代わりのフォントは同じ大きさのUIのフォントに統合されるので、大きさごとに一つだけ。
+    // Fallback fonts are merged into the UI font of the same size, so there's only one per size.
+    let mut sizes = fonts.keys().copied().collect::<Vec<_>>();
+    sizes.sort_unstable();
+    assert_eq!(sizes, vec![16, 24]);
+    let (bytes, width, height) = atlas.bake(FontAtlasFormat::Rgba32);
+    assert!(width > 0 && height > 0);
+    assert_eq!(bytes.len(), (width * height * 4) as usize);
+}
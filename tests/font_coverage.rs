@@ -0,0 +1,46 @@
+use demo_game_rs::game::ui::font_coverage::merge_ranges;
+use demo_game_rs::game::ui::FontCoverage;
+
+fn read_coverage(path: &str) -> FontCoverage {
+    let bytes = std::fs::read(path).expect("Failed to read the font.");
+    FontCoverage::parse(&bytes).expect("Failed to read the cmap of the font.")
+}
+
+#[test]
+fn reads_the_characters_of_the_shipped_fonts() {
+    let comfortaa = read_coverage("resource/Comfortaa-Regular.ttf");
+    assert!(comfortaa.contains('A' as u32));
+    assert!(!comfortaa.contains('あ' as u32));
+
+    // 日本語のフォントは形式12か形式4の`cmap`から仮名と漢字を読む。
+    // The Japanese font reads kana and kanji from a format 12 or format 4 `cmap`.
+    let hira_maru = read_coverage("resource/HiraMaruProN-W4.otf");
+    assert!(hira_maru.contains('あ' as u32));
+    assert!(hira_maru.contains('漢' as u32));
+    assert!(!hira_maru.contains(0x10FFFF));
+}
+
+#[test]
+fn rejects_files_that_are_not_fonts() {
+    assert!(FontCoverage::parse(&[]).is_err());
+    assert!(FontCoverage::parse(b"ttcf\0\0\0\0").is_err());
+    assert!(FontCoverage::parse(&[0; 12]).is_err());
+}
+
+#[test]
+fn merges_overlapping_and_adjacent_ranges() {
+    assert_eq!(
+        merge_ranges(vec![
+            (0x40, 0x4F),
+            (0x20, 0x30),
+            (0x31, 0x35),
+            (0x45, 0x60),
+            (9, 1)
+        ]),
+        vec![(0x20, 0x35), (0x40, 0x60)]
+    );
+    let coverage = FontCoverage::from_ranges(vec![(0x3040, 0x309F), (0x30A0, 0x30FF)]);
+    assert_eq!(coverage.get_ranges(), &[(0x3040, 0x30FF)]);
+    assert!(coverage.contains(0x30A0));
+    assert!(!coverage.contains(0x3100));
+}
@@ -0,0 +1,125 @@
+use demo_game_rs::game::shared::components::Bounds;
+use demo_game_rs::game::shared::enums::SurfaceMaterial;
+use demo_game_rs::game::shared::structs::{
+    find_prop_material, AnimationStateMachine, AnimationStateMachineData, SurfaceMaterialMap,
+    FOOTSTEP_EVENT,
+};
+use glam::Vec3A;
+use std::sync::Arc;
+
+const WALKER: &str = r#"{
+    "name": "Walker",
+    "initial_state": "Walk",
+    "states": [
+        {
+            "name": "Walk",
+            "clip": "walk",
+            "duration": 1.0,
+            "events": [
+                { "name": "footstep", "time": 0.25 },
+                { "name": "footstep", "time": 0.75 }
+            ]
+        }
+    ]
+}"#;
+
+const SURFACES: &str = r#"{
+    "default_material": "Grass",
+    "terrain_layers": [{ "material": "Rock", "min_slope": 30.0 }],
+    "footsteps": {
+        "Grass": {
+            "sounds": ["grass_0.ogg", "grass_1.ogg"],
+            "puff": { "color": [0.4, 0.5, 0.3, 0.6], "count": 6, "speed": 0.4, "lifetime": 0.5 }
+        },
+        "Wood": {
+            "sounds": ["wood_0.ogg"],
+            "puff": { "color": [0.5, 0.4, 0.3, 0.4], "count": 3, "speed": 0.3, "lifetime": 0.4 }
+        }
+    }
+}"#;
+
+fn walker() -> AnimationStateMachine {
+    let data = AnimationStateMachineData::from_json(WALKER)
+        .expect("Failed to load animation state machine.");
+    AnimationStateMachine::new(Arc::new(data))
+}
+
+fn surfaces() -> SurfaceMaterialMap {
+    SurfaceMaterialMap::from_json(SURFACES).expect("Failed to load surface materials.")
+}
+
+#[test]
+fn looping_states_repeat_their_events() {
+    let mut machine = walker();
+    machine.update(0.2);
+    assert!(machine.take_events().is_empty());
+
+    machine.update(0.1);
+    assert_eq!(machine.take_events(), vec![FOOTSTEP_EVENT.to_string()]);
+    assert!(machine.take_events().is_empty());
+
+    // 0.3秒から2.3秒までに、0.75、1.25、1.75、2.25秒の四歩。
+    // Four steps between 0.3 and 2.3 seconds: at 0.75, 1.25, 1.75 and 2.25.
+    machine.update(2.0);
+    assert_eq!(machine.take_events().len(), 4);
+}
+
+#[test]
+fn rejects_looping_events_without_duration() {
+    let json = WALKER.replace(r#""duration": 1.0,"#, "");
+    assert!(AnimationStateMachineData::from_json(&json).is_err());
+
+    let json = WALKER.replace(r#""time": 0.75"#, r#""time": 1.5"#);
+    assert!(AnimationStateMachineData::from_json(&json).is_err());
+}
+
+#[test]
+fn terrain_material_follows_the_slope() {
+    let map = surfaces();
+    let flat = map.get_ground_material(0.0, 0.0, |_, _| Some(1.0));
+    assert_eq!(flat, Some(SurfaceMaterial::Grass));
+
+    // 高さが1メートルごとに1メートル上がる斜面は45度。
+    // A slope rising a meter per meter is 45 degrees.
+    let steep = map.get_ground_material(0.0, 0.0, |x, _| Some(x));
+    assert_eq!(steep, Some(SurfaceMaterial::Rock));
+
+    assert_eq!(map.get_ground_material(0.0, 0.0, |_, _| None), None);
+}
+
+#[test]
+fn missing_footsteps_fall_back_to_the_default_material() {
+    let map = surfaces();
+    let rock = map
+        .get_footstep_set(SurfaceMaterial::Rock)
+        .expect("The default footsteps should be used.");
+    assert_eq!(rock.sounds[0], "grass_0.ogg");
+    assert_eq!(map.get_sound_files().count(), 3);
+
+    let json = SURFACES.replace(
+        r#""default_material": "Grass""#,
+        r#""default_material": "Rock""#,
+    );
+    assert!(SurfaceMaterialMap::from_json(&json).is_err());
+}
+
+#[test]
+fn props_under_the_feet_override_the_terrain() {
+    let crate_top = Bounds::new(Vec3A::new(-1.0, 0.0, -1.0), Vec3A::new(1.0, 1.0, 1.0));
+    let plank = Bounds::new(Vec3A::new(-1.0, 0.0, -1.0), Vec3A::new(1.0, 1.1, 1.0));
+    let props = vec![
+        (crate_top, SurfaceMaterial::Rock),
+        (plank, SurfaceMaterial::Wood),
+    ];
+
+    let on_top = find_prop_material(props.clone(), Vec3A::new(0.0, 1.0, 0.0));
+    assert_eq!(on_top, Some(SurfaceMaterial::Wood));
+
+    // 横に立っている時や、小物の下の地面に立っている時は踏んでいない。
+    // Standing beside a prop, or on the ground below its top, isn't stepping on it.
+    assert_eq!(
+        find_prop_material(props.clone(), Vec3A::new(2.0, 1.0, 0.0)),
+        None
+    );
+    assert_eq!(find_prop_material(props, Vec3A::new(0.0, 0.0, 0.0)), None);
+}
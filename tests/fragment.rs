@@ -0,0 +1,179 @@
+use demo_game_rs::game::shared::structs::games::fragment::{
+    split_message, FragmentAssembler, MAX_DATAGRAM_SIZE, MAX_FRAGMENT_COUNT, MAX_FRAGMENT_PAYLOAD,
+};
+use demo_game_rs::game::shared::structs::games::network_stats::TrafficStats;
+use demo_game_rs::game::shared::structs::games::payload::PayloadError;
+use demo_game_rs::game::shared::structs::games::protocol::PACKET_HEADER_SIZE;
+use rand::{Rng, SeedableRng};
+
+// データグラムの中でのフラグメントの番号、数、フラグの位置。
+// Offsets of the fragment index, count and flags within a datagram.
+const INDEX_OFFSET: usize = PACKET_HEADER_SIZE + 4;
+const COUNT_OFFSET: usize = PACKET_HEADER_SIZE + 5;
+const FLAGS_OFFSET: usize = PACKET_HEADER_SIZE + 6;
+
+/// 圧縮できない乱数のメッセージ。<br />
+/// A random message that can't be compressed.
+fn create_random_message(size: usize) -> Vec<u8> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(4524);
+    (0..size).map(|_| rng.gen()).collect()
+}
+
+#[test]
+fn compresses_messages_into_a_single_datagram() {
+    let message = vec![7u8; 10_000];
+    let mut sent = TrafficStats::default();
+    let datagrams = split_message(1, &message, &mut sent).expect("Failed to split message.");
+    assert_eq!(datagrams.len(), 1);
+    assert!(datagrams[0].len() <= MAX_DATAGRAM_SIZE);
+    assert_eq!(datagrams[0][FLAGS_OFFSET], 1);
+    assert_eq!(sent.messages, 1);
+    assert_eq!(sent.fragmented_messages, 0);
+    assert!(sent.get_compression_ratio() < 0.1);
+
+    let mut received = TrafficStats::default();
+    let mut assembler = FragmentAssembler::new();
+    assert_eq!(
+        assembler.receive(&datagrams[0], &mut received),
+        Ok(Some(message))
+    );
+    assert_eq!(received.uncompressed_bytes, 10_000);
+}
+
+#[test]
+fn reassembles_fragments_in_any_order() {
+    let message = create_random_message(MAX_FRAGMENT_PAYLOAD * 2 + 10);
+    let mut sent = TrafficStats::default();
+    let datagrams = split_message(2, &message, &mut sent).expect("Failed to split message.");
+    assert_eq!(datagrams.len(), 3);
+    // 圧縮しても小さくならないので、そのまま送る。
+    // Compression doesn't make it smaller, so it's sent as is.
+    for (index, datagram) in datagrams.iter().enumerate() {
+        assert!(datagram.len() <= MAX_DATAGRAM_SIZE);
+        assert_eq!(datagram[INDEX_OFFSET] as usize, index);
+        assert_eq!(datagram[COUNT_OFFSET], 3);
+        assert_eq!(datagram[FLAGS_OFFSET], 0);
+    }
+    assert_eq!(sent.fragmented_messages, 1);
+
+    let mut received = TrafficStats::default();
+    let mut assembler = FragmentAssembler::new();
+    assert_eq!(assembler.receive(&datagrams[2], &mut received), Ok(None));
+    assert_eq!(assembler.receive(&datagrams[0], &mut received), Ok(None));
+    // 重複したフラグメントは無視する。
+    // Duplicate fragments are ignored.
+    assert_eq!(assembler.receive(&datagrams[0], &mut received), Ok(None));
+    assert_eq!(
+        assembler.receive(&datagrams[1], &mut received),
+        Ok(Some(message))
+    );
+    assert_eq!(received.datagrams, 3);
+    assert_eq!(received.fragmented_messages, 1);
+}
+
+#[test]
+fn round_trips_empty_messages() {
+    let datagrams =
+        split_message(3, &[], &mut TrafficStats::default()).expect("Failed to split message.");
+    assert_eq!(datagrams.len(), 1);
+    let mut assembler = FragmentAssembler::new();
+    assert_eq!(
+        assembler.receive(&datagrams[0], &mut TrafficStats::default()),
+        Ok(Some(vec![]))
+    );
+}
+
+#[test]
+fn refuses_to_split_messages_needing_too_many_fragments() {
+    let message = create_random_message(MAX_FRAGMENT_PAYLOAD * MAX_FRAGMENT_COUNT + 1);
+    let mut sent = TrafficStats::default();
+    assert!(split_message(4, &message, &mut sent).is_err());
+    assert_eq!(sent.messages, 0);
+}
+
+#[test]
+fn rejects_invalid_fragments() {
+    let message = create_random_message(MAX_FRAGMENT_PAYLOAD * 2 + 10);
+    let datagrams =
+        split_message(5, &message, &mut TrafficStats::default()).expect("Failed to split message.");
+    let mut stats = TrafficStats::default();
+    let mut assembler = FragmentAssembler::new();
+
+    assert_eq!(
+        assembler.receive(&datagrams[0][..FLAGS_OFFSET], &mut stats),
+        Err(PayloadError::InvalidHeader)
+    );
+    let mut out_of_range = datagrams[0].clone();
+    out_of_range[INDEX_OFFSET] = 3;
+    assert!(matches!(
+        assembler.receive(&out_of_range, &mut stats),
+        Err(PayloadError::InvalidFragment { sequence: 5, .. })
+    ));
+    let mut too_many = datagrams[0].clone();
+    too_many[COUNT_OFFSET] = MAX_FRAGMENT_COUNT as u8 + 1;
+    assert!(matches!(
+        assembler.receive(&too_many, &mut stats),
+        Err(PayloadError::InvalidFragment { .. })
+    ));
+    let mut unknown_flags = datagrams[0].clone();
+    unknown_flags[FLAGS_OFFSET] = 0x80;
+    assert!(matches!(
+        assembler.receive(&unknown_flags, &mut stats),
+        Err(PayloadError::InvalidFragment { .. })
+    ));
+    // 最後以外のフラグメントは満杯でなければならない。
+    // Every fragment but the last has to be full.
+    assert!(matches!(
+        assembler.receive(&datagrams[0][..datagrams[0].len() - 1], &mut stats),
+        Err(PayloadError::InvalidFragment { .. })
+    ));
+    assert_eq!(stats.messages, 0);
+}
+
+#[test]
+fn drops_messages_with_inconsistent_headers() {
+    let message = create_random_message(MAX_FRAGMENT_PAYLOAD * 2 + 10);
+    let datagrams =
+        split_message(6, &message, &mut TrafficStats::default()).expect("Failed to split message.");
+    let mut stats = TrafficStats::default();
+    let mut assembler = FragmentAssembler::new();
+    assert_eq!(assembler.receive(&datagrams[0], &mut stats), Ok(None));
+    let mut inconsistent = datagrams[1].clone();
+    inconsistent[COUNT_OFFSET] = 4;
+    assert!(matches!(
+        assembler.receive(&inconsistent, &mut stats),
+        Err(PayloadError::InvalidFragment { sequence: 6, .. })
+    ));
+
+    // 組み立て中のメッセージは捨てられたので、最初から受け取り直す必要がある。
+    // The message being reassembled was dropped, so it has to be received from the start again.
+    assert_eq!(assembler.receive(&datagrams[1], &mut stats), Ok(None));
+    assert_eq!(assembler.receive(&datagrams[2], &mut stats), Ok(None));
+    assert_eq!(
+        assembler.receive(&datagrams[0], &mut stats),
+        Ok(Some(message))
+    );
+}
+
+#[test]
+fn drops_the_oldest_pending_messages() {
+    let message = create_random_message(MAX_FRAGMENT_PAYLOAD + 10);
+    let messages = (0..9)
+        .map(|sequence| {
+            split_message(sequence, &message, &mut TrafficStats::default())
+                .expect("Failed to split message.")
+        })
+        .collect::<Vec<_>>();
+    let mut stats = TrafficStats::default();
+    let mut assembler = FragmentAssembler::new();
+    for datagrams in messages.iter() {
+        assert_eq!(assembler.receive(&datagrams[0], &mut stats), Ok(None));
+    }
+    // 最も古いメッセージは捨てられ、最新のものはまだ組み立てられる。
+    // The oldest message was dropped while the newest can still be reassembled.
+    assert_eq!(assembler.receive(&messages[0][1], &mut stats), Ok(None));
+    assert_eq!(
+        assembler.receive(&messages[8][1], &mut stats),
+        Ok(Some(message))
+    );
+}
@@ -0,0 +1,65 @@
+use demo_game_rs::game::shared::structs::{FrameProfiler, FrameTimings, PROFILER_HISTORY};
+use std::time::Duration;
+
+#[test]
+fn keeps_a_limited_history() {
+    let mut profiler = FrameProfiler::new();
+    for i in 0..PROFILER_HISTORY + 10 {
+        profiler.end_frame(i as f64 / 1000.0);
+    }
+    let history = profiler.get_history();
+    assert_eq!(history.len(), PROFILER_HISTORY);
+    assert_eq!(history.front().map(|t| t.frame_time), Some(10.0));
+}
+
+#[test]
+fn averages_cpu_scopes_and_gpu_passes() {
+    let mut profiler = FrameProfiler::new();
+    profiler.record_cpu("Update", Duration::from_millis(2));
+    profiler.add_timings(FrameTimings {
+        frame_time: 0.0,
+        cpu_scopes: vec![("Record".to_string(), 1.0)],
+        gpu_passes: vec![("Shadow".to_string(), 0.5), ("Scene".to_string(), 3.0)],
+        allocations: 0,
+    });
+    profiler.end_frame(0.016);
+    // GPUの結果が届かなかったフレームは平均に含めない。
+    // Frames without GPU results aren't included in the average.
+    profiler.record_cpu("Update", Duration::from_millis(4));
+    profiler.end_frame(0.020);
+
+    assert!((profiler.get_average_frame_time() - 18.0).abs() < 1e-9);
+    let cpu = profiler.get_cpu_averages();
+    assert_eq!(cpu[0].0, "Update");
+    assert!((cpu[0].1 - 3.0).abs() < 1e-9);
+    assert_eq!(cpu[1], ("Record".to_string(), 1.0));
+    assert_eq!(
+        profiler.get_gpu_averages(),
+        vec![("Shadow".to_string(), 0.5), ("Scene".to_string(), 3.0)]
+    );
+    assert!((profiler.get_history()[0].get_gpu_time() - 3.5).abs() < 1e-9);
+}
+
+#[test]
+fn reports_nothing_without_frames() {
+    let profiler = FrameProfiler::new();
+    assert_eq!(profiler.get_average_frame_time(), 0.0);
+    assert!(profiler.get_gpu_averages().is_empty());
+}
+
+#[test]
+fn counts_allocations_from_the_second_frame() {
+    let mut profiler = FrameProfiler::new();
+    profiler.record_allocations(100);
+    profiler.end_frame(0.016);
+    profiler.record_allocations(130);
+    profiler.end_frame(0.016);
+    profiler.record_allocations(140);
+    profiler.end_frame(0.016);
+
+    let history = profiler.get_history();
+    assert_eq!(history[0].allocations, 0);
+    assert_eq!(history[1].allocations, 30);
+    assert_eq!(history[2].allocations, 10);
+    assert!((profiler.get_average_allocations() - 40.0 / 3.0).abs() < 1e-9);
+}
@@ -0,0 +1,83 @@
+use demo_game_rs::game::ui::glyph_cache::{find_nearest_size, BASE_GLYPH_RANGE};
+use demo_game_rs::game::ui::{FontCoverage, GlyphCache, DEFAULT_FONT_SIZES};
+
+#[test]
+fn starts_with_the_base_range() {
+    let mut cache = GlyphCache::new(&DEFAULT_FONT_SIZES);
+    assert_eq!(cache.get_glyph_ranges(), vec![BASE_GLYPH_RANGE, (0, 0)]);
+    assert!(!cache.take_dirty());
+    assert!(!cache.request_text("Hello, world!"));
+    assert!(!cache.take_dirty());
+}
+
+#[test]
+fn requests_new_glyphs_once() {
+    let mut cache = GlyphCache::new(&DEFAULT_FONT_SIZES);
+    assert!(cache.request_text("Привет 世界"));
+    assert!(cache.contains_glyph('世'));
+    assert!(cache.take_dirty());
+    assert!(!cache.take_dirty());
+    assert!(!cache.request_text("世界"));
+    assert!(!cache.take_dirty());
+}
+
+#[test]
+fn merges_consecutive_glyphs_into_ranges() {
+    let mut cache = GlyphCache::new(&[]);
+    cache.request_text("абвд\n");
+    assert_eq!(
+        cache.get_glyph_ranges(),
+        vec![BASE_GLYPH_RANGE, (0x0430, 0x0432), (0x0434, 0x0434), (0, 0)]
+    );
+}
+
+#[test]
+fn bakes_new_sizes_and_falls_back_to_the_nearest() {
+    let mut cache = GlyphCache::new(&[16, 24]);
+    assert!(cache.request_size(18));
+    assert!(!cache.request_size(24));
+    assert!(cache.take_dirty());
+    assert_eq!(cache.get_sizes(), vec![16, 18, 24]);
+
+    assert_eq!(find_nearest_size(vec![16, 24], 18), Some(16));
+    assert_eq!(find_nearest_size(vec![16, 24], 20), Some(16));
+    assert_eq!(find_nearest_size(vec![16, 24], 22), Some(24));
+    assert_eq!(find_nearest_size(vec![], 22), None);
+}
+
+#[test]
+fn preloads_configured_ranges() {
+    let mut cache = GlyphCache::with_ranges(&[], &[(0x3041, 0x3043), (0xD800, 0xD801)]);
+    // サロゲートは文字ではないので飛ばす。
+    // Surrogates aren't characters, so they're skipped.
+    assert_eq!(
+        cache.get_glyph_ranges(),
+        vec![BASE_GLYPH_RANGE, (0x3041, 0x3043), (0, 0)]
+    );
+    assert!(!cache.request_text("ぁあぃ"));
+    assert!(!cache.take_dirty());
+}
+
+#[test]
+fn assigns_glyphs_to_the_first_font_having_them() {
+    let mut cache = GlyphCache::new(&[]);
+    cache.request_text("Яあ☃");
+    let coverages = vec![
+        FontCoverage::from_ranges(vec![(0x20, 0x7E)]),
+        FontCoverage::from_ranges(vec![(0x20, 0x04FF)]),
+        FontCoverage::from_ranges(vec![(0x20, 0xFFFF)]),
+    ];
+    let ranges = cache.get_font_glyph_ranges(&coverages);
+    assert_eq!(ranges[0], vec![(0x20, 0x7E), (0, 0)]);
+    assert_eq!(ranges[1], vec![(0x7F, 0xFF), (0x042F, 0x042F), (0, 0)]);
+    assert_eq!(ranges[2], vec![(0x2603, 0x2603), (0x3042, 0x3042), (0, 0)]);
+
+    // どのフォントにもない文字は最初のフォントに回る。
+    // Characters no font has go to the first font.
+    let ranges = cache.get_font_glyph_ranges(&coverages[..2]);
+    assert_eq!(
+        ranges[0],
+        vec![(0x20, 0x7E), (0x2603, 0x2603), (0x3042, 0x3042), (0, 0)]
+    );
+    assert_eq!(ranges.len(), 2);
+}
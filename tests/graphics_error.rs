@@ -0,0 +1,91 @@
+use ash::vk;
+use demo_game_rs::game::graphics::vk::{GraphicsError, VkResultExt};
+
+#[test]
+fn classifies_vulkan_results() {
+    assert_eq!(
+        GraphicsError::from_vk("submit the queue", vk::Result::ERROR_DEVICE_LOST),
+        GraphicsError::DeviceLost("submit the queue")
+    );
+    assert_eq!(
+        GraphicsError::from_vk("present", vk::Result::ERROR_OUT_OF_DATE_KHR),
+        GraphicsError::SwapchainOutOfDate
+    );
+    assert_eq!(
+        GraphicsError::from_vk("present", vk::Result::SUBOPTIMAL_KHR),
+        GraphicsError::SwapchainOutOfDate
+    );
+    assert_eq!(
+        GraphicsError::from_vk("create buffer", vk::Result::ERROR_OUT_OF_DEVICE_MEMORY),
+        GraphicsError::OutOfMemory {
+            operation: "create buffer",
+            memory: "device",
+        }
+    );
+    assert_eq!(
+        GraphicsError::from_vk("create buffer", vk::Result::ERROR_OUT_OF_HOST_MEMORY),
+        GraphicsError::OutOfMemory {
+            operation: "create buffer",
+            memory: "host",
+        }
+    );
+    assert_eq!(
+        GraphicsError::from_vk("create sampler", vk::Result::ERROR_INITIALIZATION_FAILED),
+        GraphicsError::Vulkan {
+            operation: "create sampler",
+            result: vk::Result::ERROR_INITIALIZATION_FAILED,
+        }
+    );
+}
+
+#[test]
+fn only_lost_devices_and_stale_swapchains_are_recoverable() {
+    assert!(GraphicsError::DeviceLost("present").is_recoverable());
+    assert!(GraphicsError::DeviceLost("present").is_device_lost());
+    assert!(GraphicsError::SwapchainOutOfDate.is_recoverable());
+    assert!(!GraphicsError::SwapchainOutOfDate.is_device_lost());
+    assert!(!GraphicsError::Unavailable("logical device").is_recoverable());
+    assert!(!GraphicsError::allocation("create image", "out of memory").is_recoverable());
+}
+
+#[test]
+fn finds_graphics_errors_behind_context() {
+    let error = anyhow::Error::from(GraphicsError::DeviceLost("wait for fences"))
+        .context("Failed to render the title scene.");
+    assert_eq!(
+        GraphicsError::find(&error),
+        Some(&GraphicsError::DeviceLost("wait for fences"))
+    );
+
+    let error = anyhow::anyhow!("Failed to read the model file.");
+    assert_eq!(GraphicsError::find(&error), None);
+}
+
+#[test]
+fn describes_the_failed_operation() {
+    assert_eq!(
+        GraphicsError::DeviceLost("submit the queue").to_string(),
+        "The graphics device was lost while trying to submit the queue."
+    );
+    assert_eq!(
+        GraphicsError::allocation("create image", "VK_ERROR_OUT_OF_DEVICE_MEMORY").to_string(),
+        "Failed to create image: VK_ERROR_OUT_OF_DEVICE_MEMORY"
+    );
+}
+
+#[test]
+fn converts_vulkan_results() {
+    let ok: Result<u32, vk::Result> = Ok(3);
+    assert_eq!(
+        ok.or_graphics_error("acquire the next swapchain image"),
+        Ok(3)
+    );
+
+    let lost: Result<u32, vk::Result> = Err(vk::Result::ERROR_DEVICE_LOST);
+    assert_eq!(
+        lost.or_graphics_error("acquire the next swapchain image"),
+        Err(GraphicsError::DeviceLost(
+            "acquire the next swapchain image"
+        ))
+    );
+}
@@ -0,0 +1,142 @@
+use demo_game_rs::game::shared::enums::{PresentMode, WindowMode};
+use demo_game_rs::game::shared::structs::{GraphicsSettings, MSAA_SAMPLE_COUNTS};
+use demo_game_rs::game::ui::{TweakRegistry, TweakValue};
+use std::time::Duration;
+
+#[test]
+fn falls_back_to_the_highest_supported_sample_count() {
+    let settings = GraphicsSettings {
+        msaa_samples: 8,
+        ..GraphicsSettings::default()
+    };
+    assert_eq!(settings.get_msaa_samples(&MSAA_SAMPLE_COUNTS), 8);
+    assert_eq!(settings.get_msaa_samples(&[1, 2, 4]), 4);
+
+    // 対応している数がなければMSAAを使わない。
+    // Without any supported count MSAA is turned off.
+    assert_eq!(settings.get_msaa_samples(&[]), 1);
+}
+
+#[test]
+fn offers_only_supported_sample_counts() {
+    let mut tweaks = TweakRegistry::new();
+    let mut settings = GraphicsSettings::default();
+    settings.register_tweaks(&mut tweaks, &[1, 2, 4]);
+    match tweaks.get("Graphics/MSAA") {
+        Some(TweakValue::Enum { index, options }) => {
+            assert_eq!(options, &vec!["1x", "2x", "4x"]);
+            assert_eq!(*index, 2);
+        }
+        value => panic!("Unexpected MSAA tweak: {:?}", value),
+    }
+}
+
+#[test]
+fn applies_the_chosen_sample_count() {
+    let mut tweaks = TweakRegistry::new();
+    let mut settings = GraphicsSettings::default();
+    settings.register_tweaks(&mut tweaks, &MSAA_SAMPLE_COUNTS);
+    assert_eq!(settings.msaa_samples, 4);
+
+    let value = TweakValue::Enum {
+        index: 0,
+        options: vec![],
+    };
+    assert!(tweaks.set("Graphics/MSAA", value));
+    settings.apply_tweaks(&tweaks, &MSAA_SAMPLE_COUNTS);
+    assert_eq!(settings.msaa_samples, 1);
+}
+
+#[test]
+fn caps_the_frame_rate_only_without_vsync() {
+    let mut settings = GraphicsSettings {
+        present_mode: PresentMode::Immediate,
+        max_fps: 50,
+        ..GraphicsSettings::default()
+    };
+    assert_eq!(
+        settings.get_frame_time_limit(),
+        Some(Duration::from_millis(20))
+    );
+
+    settings.present_mode = PresentMode::Fifo;
+    assert_eq!(settings.get_frame_time_limit(), None);
+
+    settings.present_mode = PresentMode::Mailbox;
+    settings.max_fps = 0;
+    assert_eq!(settings.get_frame_time_limit(), None);
+}
+
+#[test]
+fn keeps_a_custom_frame_rate_cap_selectable() {
+    let mut tweaks = TweakRegistry::new();
+    let mut settings = GraphicsSettings {
+        max_fps: 75,
+        ..GraphicsSettings::default()
+    };
+    settings.register_tweaks(&mut tweaks, &MSAA_SAMPLE_COUNTS);
+    assert_eq!(settings.max_fps, 75);
+    match tweaks.get("Graphics/Max FPS") {
+        Some(TweakValue::Enum { index, options }) => {
+            assert_eq!(options[0], "Unlimited");
+            assert_eq!(options[*index], "75");
+        }
+        value => panic!("Unexpected frame rate cap tweak: {:?}", value),
+    }
+
+    let value = TweakValue::Enum {
+        index: 0,
+        options: vec![],
+    };
+    tweaks.set("Graphics/Max FPS", value);
+    let value = TweakValue::Enum {
+        index: 0,
+        options: vec![],
+    };
+    tweaks.set("Graphics/Present Mode", value);
+    settings.apply_tweaks(&tweaks, &MSAA_SAMPLE_COUNTS);
+    assert_eq!(settings.max_fps, 0);
+    assert_eq!(settings.present_mode, PresentMode::Fifo);
+    assert_eq!(
+        PresentMode::from_name("mailbox"),
+        Some(PresentMode::Mailbox)
+    );
+}
+
+#[test]
+fn scales_the_render_resolution() {
+    let mut settings = GraphicsSettings::default();
+    assert_eq!(settings.get_render_extent(1280, 720), (1280, 720));
+
+    settings.render_scale = 50;
+    assert_eq!(settings.get_render_extent(1280, 720), (640, 360));
+    settings.render_scale = 150;
+    assert_eq!(settings.get_render_extent(1280, 720), (1920, 1080));
+
+    // 小さすぎるウィンドウでも1ピクセルは残す。
+    // Even a tiny window keeps a single pixel.
+    settings.render_scale = 50;
+    assert_eq!(settings.get_render_extent(1, 1), (1, 1));
+}
+
+#[test]
+fn toggles_the_window_mode_through_the_tweaks() {
+    let mut tweaks = TweakRegistry::new();
+    let mut settings = GraphicsSettings::default();
+    settings.register_tweaks(&mut tweaks, &MSAA_SAMPLE_COUNTS);
+    tweaks.take_changed();
+    assert_eq!(settings.window_mode, WindowMode::Windowed);
+
+    assert!(GraphicsSettings::set_window_mode(
+        &mut tweaks,
+        WindowMode::Exclusive
+    ));
+    assert_eq!(tweaks.take_changed(), vec!["Graphics/Window Mode"]);
+    settings.apply_tweaks(&tweaks, &MSAA_SAMPLE_COUNTS);
+    assert_eq!(settings.window_mode, WindowMode::Exclusive);
+    assert!(settings.window_mode.is_fullscreen());
+    assert_eq!(
+        WindowMode::from_name("borderless"),
+        Some(WindowMode::Borderless)
+    );
+}
@@ -0,0 +1,102 @@
+use demo_game_rs::game::shared::components::Bounds;
+use demo_game_rs::game::shared::structs::{
+    DrawArguments, DrawInfo, IndirectBatch, IndirectCullPushConstant, IndirectDrawList,
+};
+use glam::{Vec3A, Vec4};
+
+fn push_draw(list: &mut IndirectDrawList<u32>, key: u32, model_index: u32) {
+    list.push(
+        key,
+        DrawArguments::new(6, 1, model_index * 6, 0),
+        DrawInfo::new(model_index, 0, 1),
+    );
+}
+
+#[test]
+fn groups_draws_by_key_in_first_appearance_order() {
+    let mut list = IndirectDrawList::new();
+    push_draw(&mut list, 7, 0);
+    push_draw(&mut list, 3, 1);
+    push_draw(&mut list, 7, 2);
+    push_draw(&mut list, 3, 3);
+    push_draw(&mut list, 7, 4);
+
+    let data = list.build(16);
+    assert_eq!(
+        data.batches,
+        vec![
+            IndirectBatch {
+                key: 7,
+                first_draw: 0,
+                draw_count: 3,
+            },
+            IndirectBatch {
+                key: 3,
+                first_draw: 3,
+                draw_count: 2,
+            },
+        ]
+    );
+    let model_indices = data
+        .infos
+        .iter()
+        .map(|info| info.model_index)
+        .collect::<Vec<_>>();
+    assert_eq!(model_indices, vec![0, 2, 4, 1, 3]);
+    let first_indices = data
+        .arguments
+        .iter()
+        .map(|arguments| arguments.first_index)
+        .collect::<Vec<_>>();
+    assert_eq!(first_indices, vec![0, 12, 24, 6, 18]);
+}
+
+#[test]
+fn splits_batches_beyond_the_max_draw_count() {
+    let mut list = IndirectDrawList::new();
+    for model_index in 0..5 {
+        push_draw(&mut list, 1, model_index);
+    }
+
+    let data = list.build(2);
+    let ranges = data
+        .batches
+        .iter()
+        .map(|batch| (batch.first_draw, batch.draw_count))
+        .collect::<Vec<_>>();
+    assert_eq!(ranges, vec![(0, 2), (2, 2), (4, 1)]);
+    assert_eq!(data.arguments.len(), 5);
+
+    list.clear();
+    assert!(list.is_empty());
+    assert!(list.build(2).batches.is_empty());
+}
+
+#[test]
+fn bounding_sphere_encloses_the_bounds() {
+    let info = DrawInfo::new(2, 5, 1);
+    assert!(info.bounding_sphere[3] < 0.0);
+
+    let bounds = Bounds::new(Vec3A::new(-1.0, 0.0, 2.0), Vec3A::new(1.0, 2.0, 4.0));
+    let info = info.with_bounds(&bounds);
+    let expected = [0.0, 1.0, 3.0, 3.0_f32.sqrt()];
+    for (value, expected) in info.bounding_sphere.iter().zip(expected.iter()) {
+        assert!((value - expected).abs() < 1e-5);
+    }
+    assert_eq!(info.model_index, 2);
+    assert_eq!(info.texture_index, 5);
+}
+
+#[test]
+fn cull_push_constant_copies_planes() {
+    let mut planes = [Vec4::zero(); 6];
+    for (index, plane) in planes.iter_mut().enumerate() {
+        *plane = Vec4::new(index as f32, 1.0, 2.0, -(index as f32));
+    }
+    let push_constant = IndirectCullPushConstant::new(&planes, 42);
+    assert_eq!(push_constant.draw_count, 42);
+    assert_eq!(push_constant.planes[3], [3.0, 1.0, 2.0, -3.0]);
+    assert_eq!(push_constant.planes[5], [5.0, 1.0, 2.0, -5.0]);
+    assert_eq!(std::mem::size_of::<DrawArguments>(), 20);
+    assert_eq!(std::mem::size_of::<IndirectCullPushConstant>(), 112);
+}
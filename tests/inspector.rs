@@ -0,0 +1,56 @@
+use demo_game_rs::game::shared::structs::{
+    Inspector, InspectorEdit, InspectorEntry, ModelMetaData, PositionInfo,
+};
+use glam::{Mat4, Vec3A, Vec4};
+use slotmap::{DefaultKey, SlotMap};
+
+fn entry(entity: DefaultKey) -> InspectorEntry {
+    let metadata = ModelMetaData::new(Mat4::identity(), Vec4::one(), 1.0, 10.0);
+    InspectorEntry::new(entity, "Barrel", PositionInfo::default(), &metadata)
+        .with_animation(vec!["Idle".to_string(), "Run".to_string()], "Idle")
+}
+
+#[test]
+fn reports_only_changed_values() {
+    let mut entities = SlotMap::new();
+    let original = entry(entities.insert(()));
+    assert_eq!(original.animation_state, Some(0));
+    assert!(original.get_edits(&original.clone()).is_empty());
+
+    let mut edited = original.clone();
+    edited.position_info.position = Vec3A::new(1.0, 2.0, 3.0);
+    edited.shine_damper = 5.0;
+    edited.animation_state = Some(1);
+    assert_eq!(
+        original.get_edits(&edited),
+        vec![
+            InspectorEdit::PositionInfo(edited.position_info),
+            InspectorEdit::Material {
+                object_color: Vec4::one(),
+                reflectivity: 1.0,
+                shine_damper: 5.0,
+            },
+            InspectorEdit::AnimationState("Run".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn clears_the_selection_when_the_entity_leaves() {
+    let mut entities = SlotMap::new();
+    let first = entities.insert(());
+    let second = entities.insert(());
+    let mut inspector = Inspector::new();
+    inspector.toggle(first);
+    let entries = vec![entry(first), entry(second)];
+    assert_eq!(
+        inspector.get_selected_entry(&entries).map(|e| e.entity),
+        Some(first)
+    );
+    inspector.toggle(first);
+    assert_eq!(inspector.get_selected(), None);
+
+    inspector.toggle(second);
+    assert!(inspector.get_selected_entry(&entries[..1]).is_none());
+    assert_eq!(inspector.get_selected(), None);
+}
@@ -0,0 +1,147 @@
+use demo_game_rs::game::shared::structs::games::interpolation::{
+    InterpolationBuffer, PredictionHistory, StateQueues, INTERPOLATION_DELAY,
+};
+use demo_game_rs::game::shared::structs::PositionInfo;
+use glam::Vec3A;
+use std::time::{Duration, Instant};
+
+fn create_state(x: f32, yaw: f32) -> PositionInfo {
+    PositionInfo {
+        position: Vec3A::new(x, 0.0, 0.0),
+        scale: Vec3A::one(),
+        rotation: Vec3A::new(0.0, yaw, 0.0),
+    }
+}
+
+#[test]
+fn interpolates_between_states() {
+    let start = Instant::now();
+    let mut buffer = InterpolationBuffer::new();
+    buffer.push(start, create_state(0.0, 0.0));
+    buffer.push(
+        start + Duration::from_millis(100),
+        create_state(10.0, std::f32::consts::FRAC_PI_2),
+    );
+
+    let state = buffer
+        .sample(start + Duration::from_millis(50))
+        .expect("Failed to sample interpolation buffer.");
+    assert!((state.position.x - 5.0).abs() < 1e-3);
+    assert!((state.rotation.y - std::f32::consts::FRAC_PI_4).abs() < 1e-3);
+
+    // 範囲外の時刻では端の状態に留まる。
+    // Times out of range stay at the nearest end.
+    let state = buffer
+        .sample(start + Duration::from_secs(1))
+        .expect("Failed to sample interpolation buffer.");
+    assert_eq!(state.position.x, 10.0);
+
+    // 前の状態より古い状態は無視される。
+    // States older than the previous one are ignored.
+    buffer.push(start, create_state(-10.0, 0.0));
+    assert_eq!(buffer.len(), 2);
+}
+
+#[test]
+fn renders_remote_players_in_the_past() {
+    let start = Instant::now();
+    let mut queues = StateQueues::new();
+    for i in 0..5 {
+        queues.push_room_state(
+            start + Duration::from_millis(50 * i),
+            vec![
+                ("local".to_string(), create_state(i as f32, 0.0)),
+                ("remote".to_string(), create_state(i as f32 * 2.0, 0.0)),
+            ],
+            "local",
+        );
+    }
+    // ローカルのプレイヤーは補間バッファーを持たない。
+    // The local player has no interpolation buffer.
+    assert_eq!(queues.get_buffer_depths(), Some((5, 5)));
+
+    let states = queues.sample_remote(start + Duration::from_millis(175) + INTERPOLATION_DELAY);
+    assert!(!states.contains_key("local"));
+    let remote = states.get("remote").expect("Failed to get remote state.");
+    assert!((remote.position.x - 7.0).abs() < 1e-3);
+    assert_eq!(queues.take_local_server_states().len(), 5);
+
+    // 部屋を出たプレイヤーの状態は消える。
+    // States of players who left the room are dropped.
+    queues.push_room_state(start + Duration::from_millis(300), vec![], "local");
+    assert!(queues
+        .sample_remote(start + Duration::from_secs(1))
+        .is_empty());
+    assert_eq!(queues.get_buffer_depths(), None);
+}
+
+#[test]
+fn reconciles_local_prediction() {
+    let start = Instant::now();
+    let mut prediction = PredictionHistory::new();
+    for i in 0..10 {
+        prediction.record(
+            start + Duration::from_millis(16 * i),
+            create_state(i as f32, 0.0),
+        );
+    }
+    // サーバーが少し前の予測を返してきたなら補正しない。
+    // No correction when the server echoes a slightly older prediction.
+    assert!(prediction.reconcile(&create_state(4.2, 0.0)).is_none());
+
+    // どの予測とも合わなければサーバーの状態に補正する。
+    // Correct to the server state when it matches no prediction.
+    let correction = prediction
+        .reconcile(&create_state(-20.0, 0.0))
+        .expect("Failed to get correction.");
+    assert_eq!(correction.position.x, -20.0);
+    assert!(prediction.reconcile(&create_state(0.0, 0.0)).is_none());
+}
+
+#[test]
+fn records_interpolation_only_while_debugging() {
+    let start = Instant::now();
+    let mut queues = StateQueues::new();
+    let push = |queues: &mut StateQueues, i: u64| {
+        queues.push_room_state(
+            start + Duration::from_millis(50 * i),
+            vec![("remote".to_string(), create_state(i as f32, 0.0))],
+            "local",
+        );
+    };
+    push(&mut queues, 0);
+    queues.sample_remote(start + INTERPOLATION_DELAY);
+    assert!(queues.debug_view.get_trace("remote").is_none());
+
+    queues.debug_view.set_enabled(true);
+    for i in 1..4 {
+        push(&mut queues, i);
+    }
+    queues.sample_remote(start + Duration::from_millis(75) + INTERPOLATION_DELAY);
+    let trace = queues
+        .debug_view
+        .get_trace("remote")
+        .expect("Failed to get interpolation trace.");
+    assert_eq!(trace.received.len(), 3);
+    assert_eq!(trace.rendered.len(), 1);
+    assert!((trace.rendered[0].x - 1.5).abs() < 1e-3);
+    assert_eq!(
+        trace.window,
+        Some((Vec3A::new(1.0, 0.0, 0.0), Vec3A::new(2.0, 0.0, 0.0)))
+    );
+    assert!(!trace.is_starved);
+
+    // 新しい状態が届かなければ、最後の状態で止まっていると分かる。
+    // Without newer states, the entity is reported as stuck at the last state.
+    queues.sample_remote(start + Duration::from_secs(1));
+    let trace = queues.debug_view.get_trace("remote").unwrap();
+    assert!(trace.is_starved);
+    assert_eq!(trace.rendered.len(), 2);
+
+    queues.clear();
+    assert!(queues.debug_view.is_enabled());
+    assert!(queues.debug_view.get_trace("remote").is_none());
+    queues.debug_view.set_enabled(false);
+    push(&mut queues, 5);
+    assert!(queues.debug_view.get_trace("remote").is_none());
+}
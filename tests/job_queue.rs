@@ -0,0 +1,66 @@
+use demo_game_rs::game::shared::structs::{JobPriority, JobQueue, SyncPoint};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn takes_render_critical_jobs_before_streaming_ones() {
+    let queue = JobQueue::new();
+    assert!(queue.push(JobPriority::Streaming, "load terrain"));
+    assert!(queue.push(JobPriority::Streaming, "load model"));
+    assert!(queue.push(JobPriority::RenderCritical, "record player"));
+    assert_eq!(queue.len(), 3);
+
+    assert_eq!(queue.try_pop(), Some("record player"));
+    assert_eq!(queue.try_pop(), Some("load terrain"));
+    assert_eq!(queue.pop(), Some("load model"));
+    assert!(queue.is_empty());
+    assert_eq!(queue.try_pop(), None);
+}
+
+#[test]
+fn closing_wakes_waiting_workers_and_refuses_jobs() {
+    let queue = Arc::new(JobQueue::<u32>::new());
+    let worker = {
+        let queue = queue.clone();
+        std::thread::spawn(move || queue.pop())
+    };
+    std::thread::sleep(Duration::from_millis(20));
+    queue.close();
+    assert_eq!(worker.join().expect("Failed to join the worker."), None);
+    assert!(!queue.push(JobPriority::RenderCritical, 1));
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn wakes_a_waiting_worker_for_a_new_job() {
+    let queue = Arc::new(JobQueue::new());
+    let worker = {
+        let queue = queue.clone();
+        std::thread::spawn(move || queue.pop())
+    };
+    std::thread::sleep(Duration::from_millis(20));
+    queue.push(JobPriority::Streaming, 7);
+    assert_eq!(worker.join().expect("Failed to join the worker."), Some(7));
+}
+
+#[test]
+fn sync_point_waits_for_every_added_job() {
+    let sync_point = Arc::new(SyncPoint::new());
+    sync_point.wait();
+
+    let workers = (0..4)
+        .map(|_| {
+            sync_point.add();
+            let sync_point = sync_point.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(10));
+                sync_point.done();
+            })
+        })
+        .collect::<Vec<_>>();
+    sync_point.wait();
+    assert_eq!(sync_point.get_pending(), 0);
+    for worker in workers {
+        worker.join().expect("Failed to join the worker.");
+    }
+}
@@ -0,0 +1,112 @@
+use demo_game_rs::game::shared::structs::games::interpolation::StateQueues;
+use demo_game_rs::game::shared::structs::{
+    create_kill_cam, DeathEvent, LifeEvent, LifeTracker, PositionInfo, KILL_CAM_DURATION,
+};
+use glam::Vec3A;
+use std::time::Instant;
+
+fn create_position(x: f32, z: f32) -> PositionInfo {
+    PositionInfo {
+        position: Vec3A::new(x, 0.0, z),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn flies_behind_the_killer() {
+    let death = DeathEvent {
+        attacker_id: Some("remote".to_string()),
+        position: create_position(0.0, 10.0),
+        attacker_position: Some(create_position(0.0, 0.0)),
+    };
+    let cutscene = create_kill_cam(
+        &death,
+        Vec3A::new(0.0, 10.0, 0.0),
+        Vec3A::new(0.0, 0.0, 10.0),
+    );
+    assert!((cutscene.get_duration() - KILL_CAM_DURATION).abs() < 1e-6);
+
+    let frame = cutscene.sample(KILL_CAM_DURATION);
+    let position = frame
+        .camera_position
+        .expect("Failed to get camera position.");
+    assert!(position.z < 0.0);
+    assert!(position.y > 0.0);
+    // 倒れた位置の方、つまり+Zを向く。
+    // The camera faces the death location, which is towards +Z.
+    let (yaw, _) = frame
+        .camera_rotation
+        .expect("Failed to get camera rotation.");
+    assert!(yaw.abs() < 1e-3);
+    assert_eq!(frame.fade, Some(1.0));
+}
+
+#[test]
+fn orbits_the_death_location_without_a_killer() {
+    let death = DeathEvent {
+        attacker_id: None,
+        position: create_position(5.0, 5.0),
+        attacker_position: None,
+    };
+    let cutscene = create_kill_cam(
+        &death,
+        Vec3A::new(5.0, 10.0, -5.0),
+        Vec3A::new(5.0, 0.0, 5.0),
+    );
+    let center = Vec3A::new(5.0, 0.0, 5.0);
+    let mut previous = None;
+    for step in 2..=5 {
+        let frame = cutscene.sample(step as f32);
+        let position = frame
+            .camera_position
+            .expect("Failed to get camera position.");
+        let offset = position - center;
+        let radius = Vec3A::new(offset.x, 0.0, offset.z).length();
+        assert!((radius - 12.0).abs() < 1.0);
+        assert_ne!(previous, Some(position));
+        previous = Some(position);
+    }
+    assert_eq!(cutscene.sample(0.0).fade, Some(0.0));
+}
+
+#[test]
+fn reports_death_with_attacker_position() {
+    let mut states = StateQueues::new();
+    states.push_room_state(
+        Instant::now(),
+        vec![
+            ("local".to_string(), create_position(1.0, 0.0)),
+            ("remote".to_string(), create_position(8.0, 0.0)),
+        ],
+        "local",
+    );
+    let mut tracker = LifeTracker::new();
+    tracker.push(true, "", "local", &states);
+    tracker.push(false, "remote", "local", &states);
+    // 倒れたままの状態が続いても、もう一度は報告しない。
+    // Staying down isn't reported again.
+    tracker.push(false, "remote", "local", &states);
+
+    let events = tracker.take_events();
+    assert_eq!(events.len(), 1);
+    match &events[0] {
+        LifeEvent::Died(death) => {
+            assert_eq!(death.attacker_id.as_deref(), Some("remote"));
+            assert_eq!(death.position.position.x, 1.0);
+            let attacker_position = death
+                .attacker_position
+                .expect("Failed to get attacker position.");
+            assert_eq!(attacker_position.position.x, 8.0);
+        }
+        event => panic!("Unexpected life event: {:?}", event),
+    }
+
+    tracker.push(true, "", "local", &states);
+    assert_eq!(tracker.take_events(), vec![LifeEvent::Respawned]);
+
+    // 片付けた後の最初の報告は、変化として扱わない。
+    // The first report after clearing isn't treated as a change.
+    tracker.clear();
+    tracker.push(false, "remote", "local", &states);
+    assert!(tracker.take_events().is_empty());
+}
@@ -0,0 +1,110 @@
+use ash::vk::Format;
+use demo_game_rs::game::shared::structs::{
+    get_block_count, Ktx2Texture, SupercompressionScheme, TranscodeTarget,
+};
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+fn create_file(width: u32, height: u32, level_count: u32, face_count: u32) -> Vec<u8> {
+    let dfd_offset = 80 + 24 * level_count as usize;
+    let dfd_length = 44;
+    let mut level_data = vec![];
+    let mut level_index = vec![];
+    let mut offset = dfd_offset + dfd_length;
+    for level in 0..level_count {
+        let size = ((width >> level).max(1) * (height >> level).max(1) * 4) as usize;
+        level_index.push((offset as u64, size as u64));
+        level_data.extend(std::iter::repeat(level as u8).take(size));
+        offset += size;
+    }
+
+    let mut bytes = IDENTIFIER.to_vec();
+    for value in [
+        Format::R8G8B8A8_UNORM.as_raw() as u32,
+        1,
+        width,
+        height,
+        0,
+        0,
+        face_count,
+        level_count,
+        0,
+        dfd_offset as u32,
+        dfd_length as u32,
+        0,
+        0,
+    ]
+    .iter()
+    {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&0_u64.to_le_bytes());
+    bytes.extend_from_slice(&0_u64.to_le_bytes());
+    for (offset, length) in level_index.iter() {
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&length.to_le_bytes());
+        bytes.extend_from_slice(&length.to_le_bytes());
+    }
+    bytes.extend(std::iter::repeat(0).take(dfd_length));
+    bytes.extend(level_data);
+    bytes
+}
+
+#[test]
+fn parses_uncompressed_levels() {
+    let bytes = create_file(8, 4, 3, 1);
+    let texture = Ktx2Texture::parse(&bytes).expect("Failed to parse KTX2 file.");
+    assert_eq!(texture.format, Format::R8G8B8A8_UNORM);
+    assert_eq!(texture.supercompression, SupercompressionScheme::None);
+    assert!(!texture.is_uastc);
+    assert_eq!(texture.levels.len(), 3);
+    assert_eq!(texture.get_level_extent(1), (4, 2));
+    assert_eq!(texture.get_level_extent(2), (2, 1));
+
+    let level = texture
+        .get_level_data(&bytes, 2)
+        .expect("Failed to read mip level.");
+    assert_eq!(level.len(), 2 * 4);
+    assert!(level.iter().all(|b| *b == 2));
+    assert!(texture.get_level_data(&bytes, 3).is_err());
+    assert!(texture
+        .transcode_level(&bytes, 0, TranscodeTarget::Rgba8)
+        .is_err());
+}
+
+#[test]
+fn rejects_unsupported_and_truncated_files() {
+    assert!(Ktx2Texture::parse(&[0; 100]).is_err());
+    // キューブマップ
+    // Cubemap
+    assert!(Ktx2Texture::parse(&create_file(4, 4, 1, 6)).is_err());
+    // 大きさに対してミップが多すぎる
+    // Too many mips for the size
+    assert!(Ktx2Texture::parse(&create_file(4, 4, 4, 1)).is_err());
+
+    let bytes = create_file(4, 4, 3, 1);
+    assert!(Ktx2Texture::parse(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn chooses_the_smallest_supported_target() {
+    assert_eq!(TranscodeTarget::choose(true, true), TranscodeTarget::Bc7);
+    assert_eq!(
+        TranscodeTarget::choose(false, true),
+        TranscodeTarget::Astc4x4
+    );
+    assert_eq!(
+        TranscodeTarget::choose(false, false),
+        TranscodeTarget::Rgba8
+    );
+    assert!(!TranscodeTarget::Rgba8.is_compressed());
+    assert_eq!(
+        TranscodeTarget::Astc4x4.get_format(),
+        Format::ASTC_4X4_UNORM_BLOCK
+    );
+    assert_eq!(get_block_count(1), 1);
+    assert_eq!(get_block_count(8), 2);
+    assert_eq!(get_block_count(9), 3);
+}
@@ -0,0 +1,107 @@
+use demo_game_rs::game::shared::structs::games::lan_discovery::{
+    DiscoveredHosts, HostAddress, LanAnnouncement, HOST_EXPIRY,
+};
+use demo_game_rs::game::shared::structs::games::payload::PayloadError;
+use demo_game_rs::game::shared::structs::games::protocol::PROTOCOL_VERSION;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+fn create_announcement() -> LanAnnouncement {
+    LanAnnouncement {
+        server_name: "Living room".to_string(),
+        build_version: "0.1.0".to_string(),
+        grpc_port: 50051,
+        udp_port: 50052,
+        open_rooms: 1,
+        current_players: 3,
+    }
+}
+
+#[test]
+fn announcements_survive_the_round_trip() {
+    let announcement = create_announcement();
+    let bytes = announcement
+        .encode()
+        .expect("Failed to encode announcement.");
+    assert_eq!(LanAnnouncement::decode(&bytes), Ok(announcement.clone()));
+
+    let host = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+    assert_eq!(
+        announcement.get_server_endpoint(host),
+        "http://192.168.1.20:50051"
+    );
+    assert_eq!(announcement.get_udp_endpoint(host), "192.168.1.20:50052");
+}
+
+#[test]
+fn rejects_foreign_and_incompatible_announcements() {
+    let mut bytes = create_announcement().encode().unwrap();
+    assert_eq!(
+        LanAnnouncement::decode(&bytes[2..]),
+        Err(PayloadError::InvalidHeader)
+    );
+    assert!(LanAnnouncement::decode(&bytes[..bytes.len() - 1]).is_err());
+
+    bytes[4..8].copy_from_slice(&(PROTOCOL_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        LanAnnouncement::decode(&bytes),
+        Err(PayloadError::VersionMismatch {
+            expected: PROTOCOL_VERSION,
+            actual: PROTOCOL_VERSION + 1,
+        })
+    );
+
+    let mut announcement = create_announcement();
+    announcement.server_name = "x".repeat(100);
+    let bytes = announcement.encode().unwrap();
+    assert!(matches!(
+        LanAnnouncement::decode(&bytes),
+        Err(PayloadError::OutOfRange {
+            field: "server_name",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn parses_direct_connect_addresses() {
+    let address = HostAddress::parse(" 192.168.1.20:50051 ").expect("Failed to parse address.");
+    assert_eq!(address.ip, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)));
+    assert_eq!(address.grpc_port, 50051);
+    assert_eq!(address.udp_port, 50052);
+    assert_eq!(address.get_server_endpoint(), "http://192.168.1.20:50051");
+    assert_eq!(address.get_udp_endpoint(), "192.168.1.20:50052");
+
+    let address = HostAddress::parse("[::1]:7000").expect("Failed to parse IPv6 address.");
+    assert_eq!(address.get_server_endpoint(), "http://[::1]:7000");
+
+    assert!(HostAddress::parse("192.168.1.20").is_err());
+    assert!(HostAddress::parse("192.168.1.20:0").is_err());
+    assert!(HostAddress::parse("192.168.1.20:65535").is_err());
+    assert!(HostAddress::parse("localhost:50051").is_err());
+}
+
+#[test]
+fn discovered_hosts_are_refreshed_and_expire() {
+    let now = Instant::now();
+    let first = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+    let second = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 21));
+    let mut hosts = DiscoveredHosts::new();
+    hosts.update(first, create_announcement(), now);
+    hosts.update(second, create_announcement(), now);
+
+    let mut announcement = create_announcement();
+    announcement.current_players = 4;
+    let later = now + HOST_EXPIRY;
+    hosts.update(first, announcement, later);
+    assert_eq!(hosts.get_hosts().len(), 2);
+    assert_eq!(hosts.get_hosts()[0].announcement.current_players, 4);
+
+    hosts.remove_stale(later + Duration::from_millis(1));
+    assert_eq!(hosts.get_hosts().len(), 1);
+    assert_eq!(hosts.get_hosts()[0].ip, first);
+    assert_eq!(
+        hosts.get_hosts()[0].get_address(),
+        HostAddress::parse("192.168.1.20:50051").unwrap()
+    );
+}
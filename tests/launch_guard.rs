@@ -0,0 +1,51 @@
+use demo_game_rs::game::shared::enums::{PresentMode, WindowMode};
+use demo_game_rs::game::shared::structs::{
+    GraphicsSettings, LaunchGuard, Settings, SAFE_MODE_THRESHOLD,
+};
+
+#[test]
+fn enters_safe_mode_after_repeated_failures() {
+    let path = std::env::temp_dir().join("demo_game_launch_guard_failures.lock");
+    std::fs::remove_file(&path).ok();
+    for failures in 0..SAFE_MODE_THRESHOLD {
+        let guard = LaunchGuard::begin(&path);
+        assert_eq!(guard.get_failed_launches(), failures);
+        assert!(!guard.is_safe_mode());
+    }
+    let guard = LaunchGuard::begin(&path);
+    assert!(guard.is_safe_mode());
+
+    let mut settings = Settings {
+        debug: true,
+        window_mode: "Exclusive".to_string(),
+        ..Settings::default()
+    };
+    assert!(!guard.apply_safe_mode(&mut settings).is_empty());
+    assert!(settings.safe_mode && settings.offline && !settings.debug);
+    assert_eq!(settings.window_mode, "Windowed");
+    assert_eq!(
+        GraphicsSettings::from_settings(&settings),
+        GraphicsSettings::lowest()
+    );
+    guard.finish();
+    assert!(!path.exists());
+}
+
+#[test]
+fn clean_exits_reset_the_failures() {
+    let path = std::env::temp_dir().join("demo_game_launch_guard_clean.lock");
+    std::fs::remove_file(&path).ok();
+    LaunchGuard::begin(&path).finish();
+    let guard = LaunchGuard::begin(&path);
+    assert_eq!(guard.get_failed_launches(), 0);
+    guard.finish();
+}
+
+#[test]
+fn safe_mode_uses_the_lowest_quality() {
+    let settings = GraphicsSettings::lowest();
+    assert_eq!(settings.msaa_samples, 1);
+    assert_eq!(settings.present_mode, PresentMode::Fifo);
+    assert_eq!(settings.window_mode, WindowMode::Windowed);
+    assert!(settings.render_scale <= GraphicsSettings::default().render_scale);
+}
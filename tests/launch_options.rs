@@ -0,0 +1,146 @@
+use demo_game_rs::game::shared::structs::{
+    parse_soak_hours, parse_window_size, Benchmark, LaunchOptions, Settings, SOAK_DURATION_HOURS,
+};
+
+#[test]
+fn parses_launch_options() {
+    let options = LaunchOptions::from_args(vec![
+        "demo_game_rs",
+        "--api",
+        "Vulkan",
+        "--server",
+        "http://127.0.0.1:50051",
+        "--scene",
+        "./cutscenes/intro.json",
+        "--windowed",
+        "1600x900",
+        "--benchmark",
+    ])
+    .expect("Failed to parse launch options.");
+    assert_eq!(options.api.as_deref(), Some("VULKAN"));
+    assert_eq!(
+        options.server_endpoint.as_deref(),
+        Some("http://127.0.0.1:50051")
+    );
+    assert_eq!(
+        options.scene_file.as_deref(),
+        Some("./cutscenes/intro.json")
+    );
+    assert_eq!(options.window_size, Some((1600, 900)));
+    assert!(options.is_benchmark);
+    assert!(!options.is_offline);
+
+    let options = LaunchOptions::from_args(vec!["demo_game_rs", "--offline"])
+        .expect("Failed to parse launch options.");
+    assert!(options.is_offline);
+    assert!(!options.is_hosting);
+    assert_eq!(options.api, None);
+
+    let options = LaunchOptions::from_args(vec!["demo_game_rs", "--host"])
+        .expect("Failed to parse launch options.");
+    assert!(options.is_hosting);
+    assert!(!options.is_validating_assets);
+
+    let options = LaunchOptions::from_args(vec!["demo_game_rs", "--validate-assets"])
+        .expect("Failed to parse launch options.");
+    assert!(options.is_validating_assets);
+    assert_eq!(options.preview_asset, None);
+
+    let options =
+        LaunchOptions::from_args(vec!["demo_game_rs", "--preview", "./models/tank/tank.gltf"])
+            .expect("Failed to parse launch options.");
+    assert_eq!(
+        options.preview_asset.as_deref(),
+        Some("./models/tank/tank.gltf")
+    );
+    assert_eq!(options.soak_hours, None);
+
+    let options = LaunchOptions::from_args(vec!["demo_game_rs", "--soak"])
+        .expect("Failed to parse launch options.");
+    assert_eq!(options.soak_hours, Some(SOAK_DURATION_HOURS));
+
+    let options = LaunchOptions::from_args(vec!["demo_game_rs", "--soak", "0.5"])
+        .expect("Failed to parse launch options.");
+    assert_eq!(options.soak_hours, Some(0.5));
+}
+
+#[test]
+fn overrides_settings_with_launch_options() {
+    let options = LaunchOptions::from_args(vec![
+        "demo_game_rs",
+        "--api",
+        "dx12",
+        "--scene",
+        "./cutscenes/custom.json",
+        "--windowed",
+        "1600x900",
+        "--benchmark",
+    ])
+    .expect("Failed to parse launch options.");
+    let mut settings = Settings {
+        window_mode: "Borderless".to_string(),
+        max_fps: 60,
+        ..Settings::default()
+    };
+    options.apply_to(&mut settings);
+    assert_eq!(settings.api, "DX12");
+    assert_eq!(settings.scene_file, "./cutscenes/custom.json");
+    assert_eq!(settings.window_mode, "Windowed");
+    // ベンチマークはオフラインで、垂直同期とフレームレートの上限を外して測る。
+    // Benchmarks are measured offline, without vertical sync or a frame rate cap.
+    assert!(settings.offline);
+    assert_eq!(settings.present_mode, "IMMEDIATE");
+    assert_eq!(settings.max_fps, 0);
+    assert!(settings.validate().is_ok());
+
+    // 指定しなかったものは変えない。
+    // Anything not given is left as it is.
+    let mut settings = Settings::default();
+    LaunchOptions::from_args(vec!["demo_game_rs"])
+        .expect("Failed to parse launch options.")
+        .apply_to(&mut settings);
+    assert_eq!(settings, Settings::default());
+}
+
+#[test]
+fn rejects_invalid_launch_options() {
+    assert!(LaunchOptions::from_args(vec!["demo_game_rs", "--api", "metal"]).is_err());
+    assert!(LaunchOptions::from_args(vec!["demo_game_rs", "--windowed", "big"]).is_err());
+    assert!(LaunchOptions::from_args(vec!["demo_game_rs", "--host", "--offline"]).is_err());
+    assert!(LaunchOptions::from_args(vec![
+        "demo_game_rs",
+        "--preview",
+        "./models/tank/tank.gltf",
+        "--host"
+    ])
+    .is_err());
+    assert!(LaunchOptions::from_args(vec!["demo_game_rs", "--soak", "-1"]).is_err());
+    assert!(LaunchOptions::from_args(vec!["demo_game_rs", "--soak", "--benchmark"]).is_err());
+    assert_eq!(parse_soak_hours("nan"), None);
+    assert_eq!(parse_window_size("1280X720"), Some((1280, 720)));
+    assert_eq!(parse_window_size("0x720"), None);
+    assert_eq!(parse_window_size("1280"), None);
+}
+
+#[test]
+fn summarizes_frame_times_after_the_warmup() {
+    let mut benchmark = Benchmark::new(1.0, 1.0);
+    // 読み込みで長くなった最初のフレームは測らない。
+    // The first frame, long from loading, isn't measured.
+    benchmark.record(1.5);
+    assert_eq!(benchmark.get_report(), None);
+    for _ in 0..9 {
+        benchmark.record(0.01);
+    }
+    benchmark.record(0.1);
+    assert!(!benchmark.is_finished());
+    benchmark.record(0.5);
+    assert!(benchmark.is_finished());
+
+    let report = benchmark.get_report().expect("Failed to get the report.");
+    assert_eq!(report.frames, 11);
+    assert!((report.min_frame_time - 10.0).abs() < 1e-6);
+    assert!((report.max_frame_time - 500.0).abs() < 1e-6);
+    assert!((report.percentile_99_frame_time - 500.0).abs() < 1e-6);
+    assert!((report.average_fps - 11.0 / 0.69).abs() < 1e-6);
+}
@@ -0,0 +1,342 @@
+#![cfg(feature = "listen-server")]
+
+#[allow(dead_code)]
+mod support;
+
+use demo_game_rs::game::shared::structs::games::terrain_payload::TerrainPayload;
+use demo_game_rs::game::shared::structs::{Primitive, Vertex};
+use demo_game_rs::game::shared::systems::{
+    ListenServer, ListenServerHandle, ListenServerSettings, NetworkSystem,
+};
+use demo_game_rs::protos::grpc_service::game_state::{
+    Player, RegisterPlayerRequest, RoomState, StartGameRequest,
+};
+use demo_game_rs::protos::grpc_service::grpc_service_client::GrpcServiceClient;
+use demo_game_rs::protos::grpc_service::{Empty, LoginRequest, RegisterRequest};
+use demo_game_rs::protos::jwt_token_service::jwt_token_service_client::JwtTokenServiceClient;
+use demo_game_rs::protos::jwt_token_service::AccessRequest;
+use glam::{Vec2, Vec3A};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use support::MockServer;
+use tonic::transport::{Channel, Endpoint};
+
+/// 非同期の処理を待つ最大の時間。<br />
+/// Maximum time to wait for asynchronous work.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// テストごとに別のポートでリッスンサーバーを始める。<br />
+/// Start a listen server on ports of its own for each test.
+async fn start_server(grpc_port: u16) -> (ListenServerHandle, SocketAddr) {
+    let handle = ListenServer::start(ListenServerSettings {
+        server_name: "Test".to_string(),
+        grpc_port,
+        udp_port: grpc_port + 1,
+        is_announced: false,
+    })
+    .await
+    .expect("Failed to start listen server.");
+    (handle, SocketAddr::from((Ipv4Addr::LOCALHOST, grpc_port)))
+}
+
+/// サーバーが受け付け始めるまで接続を試みる。<br />
+/// Try to connect until the server starts accepting connections.
+async fn connect_channel(address: SocketAddr) -> Channel {
+    let endpoint = Endpoint::from_shared(MockServer::get_settings(address).server_endpoint)
+        .expect("Failed to parse server endpoint.");
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        loop {
+            if let Ok(channel) = endpoint.connect().await {
+                return channel;
+            }
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out connecting to the listen server.")
+}
+
+/// 接続し、JWTトークンを取得する。<br />
+/// Connect and acquire a JWT token.
+async fn connect(address: SocketAddr) -> (GrpcServiceClient<Channel>, String) {
+    let channel = connect_channel(address).await;
+    let token = JwtTokenServiceClient::new(channel.clone())
+        .access(AccessRequest {
+            user_name: "player".to_string(),
+            password: String::new(),
+        })
+        .await
+        .expect("Failed to acquire JWT token.")
+        .into_inner()
+        .token;
+    (GrpcServiceClient::new(channel), token)
+}
+
+async fn login(
+    client: &mut GrpcServiceClient<Channel>,
+    token: &str,
+    account: &str,
+    password: &str,
+) -> Player {
+    let reply = client
+        .login(LoginRequest {
+            account: account.to_string(),
+            password: password.to_string(),
+            jwt_token: token.to_string(),
+            refresh_token: String::new(),
+        })
+        .await
+        .expect("Failed to log in.")
+        .into_inner();
+    assert!(reply.status, "{}", reply.message);
+    reply.player.expect("Failed to get logged in player.")
+}
+
+/// 部屋に入り、最初に届いた部屋のステートを返す。<br />
+/// Join a room and return the first room state received.
+async fn join_room(
+    client: &mut GrpcServiceClient<Channel>,
+    token: &str,
+    room_id: &str,
+    player: Player,
+) -> Result<RoomState, tonic::Status> {
+    let mut stream = client
+        .register_player(RegisterPlayerRequest {
+            room_id: room_id.to_string(),
+            room_name: "Test room".to_string(),
+            player: Some(player),
+            max_players: 0,
+            settings: None,
+            jwt_token: token.to_string(),
+        })
+        .await?
+        .into_inner();
+    Ok(stream.message().await?.expect("Failed to get room state."))
+}
+
+fn create_terrain() -> Vec<u8> {
+    let vertices = (0..4)
+        .map(|i| Vertex {
+            position: Vec3A::new((i % 2) as f32, i as f32 * 0.5, (i / 2) as f32),
+            normal: Vec3A::new(0.0, 1.0, 0.0),
+            uv: Vec2::new((i % 2) as f32, (i / 2) as f32),
+        })
+        .collect::<Vec<_>>();
+    let primitive = Primitive {
+        vertices,
+        indices: vec![0, 1, 2, 2, 1, 3],
+        texture_index: None,
+        is_disposed: false,
+    };
+    TerrainPayload::from_primitive(&primitive)
+        .expect("Failed to create terrain payload.")
+        .encode()
+        .expect("Failed to encode terrain payload.")
+}
+
+#[tokio::test]
+async fn hashes_passwords_and_never_sends_them_back() {
+    let (_handle, address) = start_server(50161).await;
+    let (mut client, token) = connect(address).await;
+    let registered = client
+        .register(RegisterRequest {
+            user_name: "alice".to_string(),
+            nickname: "Alice".to_string(),
+            email: "alice@example.com".to_string(),
+            password: "alice-pass".to_string(),
+            jwt_token: token.clone(),
+        })
+        .await
+        .expect("Failed to register.")
+        .into_inner();
+    assert!(registered.status);
+    assert!(registered
+        .player
+        .expect("Failed to get player.")
+        .password
+        .is_empty());
+
+    let rejected = client
+        .login(LoginRequest {
+            account: "alice".to_string(),
+            password: "wrong-pass".to_string(),
+            jwt_token: token.clone(),
+            refresh_token: String::new(),
+        })
+        .await
+        .expect("Failed to send login.")
+        .into_inner();
+    assert!(!rejected.status);
+    assert!(rejected.player.is_none());
+
+    let player = login(&mut client, &token, "alice", "alice-pass").await;
+    assert!(player.password.is_empty());
+
+    // クライアントが送ったパスワードは、部屋の他のプレイヤーに届かない。
+    // A password sent by the client doesn't reach the other players in the room.
+    let leaking_player = Player {
+        password: "alice-pass".to_string(),
+        nickname: "Mallory".to_string(),
+        ..player
+    };
+    let room_state = join_room(&mut client, &token, "room", leaking_player)
+        .await
+        .expect("Failed to join room.");
+    assert_eq!(room_state.players.len(), 1);
+    assert!(room_state.players[0].password.is_empty());
+    assert_eq!(room_state.players[0].nickname, "Alice");
+    let rooms = client
+        .get_rooms(Empty {})
+        .await
+        .expect("Failed to get rooms.")
+        .into_inner()
+        .rooms;
+    assert!(rooms
+        .iter()
+        .flat_map(|r| r.players.iter())
+        .all(|p| p.password.is_empty()));
+}
+
+#[tokio::test]
+async fn rejects_players_without_their_token() {
+    let (_handle, address) = start_server(50163).await;
+    let (mut client, alice_token) = connect(address).await;
+    let status = client
+        .login(LoginRequest {
+            account: "alice".to_string(),
+            password: "alice-pass".to_string(),
+            jwt_token: "forged-token".to_string(),
+            refresh_token: String::new(),
+        })
+        .await
+        .expect_err("Logged in with a forged token.");
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+    let alice = login(&mut client, &alice_token, "alice", "alice-pass").await;
+    let (mut other_client, bob_token) = connect(address).await;
+    login(&mut other_client, &bob_token, "bob", "bob-pass").await;
+
+    // 他のプレイヤーのトークンでは、そのプレイヤーになりすませない。
+    // A player can't be impersonated with the token of another player.
+    let status = join_room(&mut other_client, &bob_token, "room", alice.clone())
+        .await
+        .expect_err("Joined the room as another player.");
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    let status = join_room(&mut other_client, "", "room", alice.clone())
+        .await
+        .expect_err("Joined the room without a token.");
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    assert!(join_room(&mut client, &alice_token, "room", alice)
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn only_the_owner_can_start_the_game() {
+    let (_handle, address) = start_server(50165).await;
+    let (mut alice_client, alice_token) = connect(address).await;
+    let alice = login(&mut alice_client, &alice_token, "alice", "alice-pass").await;
+    let (mut bob_client, bob_token) = connect(address).await;
+    let bob = login(&mut bob_client, &bob_token, "bob", "bob-pass").await;
+    join_room(&mut alice_client, &alice_token, "room", alice)
+        .await
+        .expect("Failed to create room.");
+    let room_state = join_room(&mut bob_client, &bob_token, "room", bob)
+        .await
+        .expect("Failed to join room.");
+
+    let start = |token: &str| StartGameRequest {
+        room_state: Some(room_state.clone()),
+        terrain_vertices: create_terrain(),
+        jwt_token: token.to_string(),
+    };
+    let status = bob_client
+        .start_game(start(&bob_token))
+        .await
+        .expect_err("Started the game without owning the room.");
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    let started = alice_client
+        .start_game(start(&alice_token))
+        .await
+        .expect("Failed to start game.")
+        .into_inner();
+    assert!(started.started);
+}
+
+#[tokio::test]
+async fn logs_in_with_refresh_tokens_only_once() {
+    let (_handle, address) = start_server(50169).await;
+    let (mut client, token) = connect(address).await;
+    let request = |password: &str, refresh_token: &str| LoginRequest {
+        account: "alice".to_string(),
+        password: password.to_string(),
+        jwt_token: token.clone(),
+        refresh_token: refresh_token.to_string(),
+    };
+    let issued = client
+        .login(request("alice-pass", ""))
+        .await
+        .expect("Failed to log in.")
+        .into_inner()
+        .refresh_token;
+    assert!(!issued.is_empty());
+    let renewed = client
+        .login(request("", &issued))
+        .await
+        .expect("Failed to log in.")
+        .into_inner();
+    assert!(renewed.status, "{}", renewed.message);
+    assert!(renewed.player.is_some());
+    assert_ne!(renewed.refresh_token, issued);
+
+    // 使ったリフレッシュトークンや偽のトークンではログインできない。
+    // Refresh tokens that have been used or forged can't be used to log in.
+    for refresh_token in [issued.as_str(), "forged-refresh-token"].iter() {
+        let rejected = client
+            .login(request("", refresh_token))
+            .await
+            .expect("Failed to send login.")
+            .into_inner();
+        assert!(!rejected.status);
+        assert!(rejected.player.is_none());
+    }
+    let renewed_again = client
+        .login(request("", &renewed.refresh_token))
+        .await
+        .expect("Failed to log in.")
+        .into_inner();
+    assert!(renewed_again.status);
+}
+
+#[tokio::test]
+async fn network_system_logs_in_and_hosts_rooms() {
+    let (_handle, address) = start_server(50167).await;
+    connect_channel(address).await;
+    let mut network_system = NetworkSystem::connect(MockServer::get_settings(address))
+        .await
+        .expect("Failed to connect to listen server.");
+    let player = network_system
+        .register(
+            "alice",
+            "Alice",
+            "alice@example.com",
+            "alice-pass",
+            "alice-pass",
+        )
+        .await
+        .expect("Failed to register.");
+    assert!(player.password.is_empty());
+    network_system
+        .create_room("Test room".to_string(), 2)
+        .await
+        .expect("Failed to create room.");
+
+    let mut other_system = NetworkSystem::connect(MockServer::get_settings(address))
+        .await
+        .expect("Failed to connect to listen server.");
+    assert!(other_system
+        .login("alice", &base64::encode("wrong-pass"))
+        .await
+        .is_err());
+}
@@ -0,0 +1,93 @@
+use demo_game_rs::game::shared::structs::{LoadTask, LoadTaskError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+struct DropCounter(Arc<AtomicUsize>);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn get_error(result: anyhow::Result<u32>) -> LoadTaskError {
+    *result
+        .expect_err("The task should have failed.")
+        .downcast_ref::<LoadTaskError>()
+        .expect("The error should be a LoadTaskError.")
+}
+
+#[test]
+fn polls_without_waiting() {
+    let (sender, mut task) = LoadTask::channel();
+    assert_eq!(task.try_take().unwrap(), None);
+    assert!(!task.is_finished());
+
+    sender.complete(7_u32);
+    assert!(task.is_finished());
+    assert_eq!(task.try_take().unwrap(), Some(7));
+}
+
+#[test]
+fn waits_for_a_result_from_another_thread() {
+    let (sender, mut task) = LoadTask::channel();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(10));
+        sender.complete(3_u32);
+    });
+    assert_eq!(task.wait().unwrap(), 3);
+}
+
+#[test]
+fn resolves_as_a_future_on_the_tokio_runtime() {
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let (sender, task) = LoadTask::channel();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(10));
+        sender.complete(5_u32);
+    });
+    assert_eq!(runtime.block_on(task).unwrap(), 5);
+}
+
+#[test]
+fn fails_when_the_sender_is_dropped_without_a_result() {
+    let (sender, mut task) = LoadTask::<u32>::channel();
+    drop(sender);
+    assert_eq!(get_error(task.wait()), LoadTaskError::Abandoned);
+}
+
+#[test]
+fn cancelled_tasks_resolve_with_an_error() {
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let (sender, task) = LoadTask::<u32>::channel();
+    task.cancel();
+    assert!(task.is_finished());
+    assert_eq!(get_error(runtime.block_on(task)), LoadTaskError::Cancelled);
+    sender.complete(1);
+}
+
+#[test]
+fn results_of_cancelled_tasks_are_dropped() {
+    let drops = Arc::new(AtomicUsize::new(0));
+
+    let (sender, task) = LoadTask::channel();
+    task.cancel();
+    assert!(sender.is_cancelled());
+    sender.complete(DropCounter(drops.clone()));
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+    let (sender, task) = LoadTask::channel();
+    sender.complete(DropCounter(drops.clone()));
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+    drop(task);
+    assert_eq!(drops.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn dropping_a_task_cancels_it() {
+    let (sender, task) = LoadTask::<u32>::channel();
+    assert!(!sender.is_cancelled());
+    drop(task);
+    assert!(sender.is_cancelled());
+}
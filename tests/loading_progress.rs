@@ -0,0 +1,20 @@
+use demo_game_rs::game::shared::structs::LoadingProgress;
+
+#[test]
+fn fraction_follows_completed_tasks() {
+    let progress = LoadingProgress::new(3, 4);
+    assert!((progress.get_fraction() - 0.75).abs() < 1e-6);
+    assert!(!progress.is_complete());
+
+    let progress = LoadingProgress::new(4, 4);
+    assert!((progress.get_fraction() - 1.0).abs() < 1e-6);
+    assert!(progress.is_complete());
+}
+
+#[test]
+fn loading_without_tasks_is_complete() {
+    let progress = LoadingProgress::default();
+    assert_eq!(progress.get_fraction(), 1.0);
+    assert!(progress.is_complete());
+    assert_eq!(progress.textures_uploaded, 0);
+}
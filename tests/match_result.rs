@@ -0,0 +1,148 @@
+use demo_game_rs::game::shared::structs::games::match_result::{
+    get_scoreboard, settle_player, MatchOutcome, MatchResults, MatchTracker, PARTICIPATION_CREDITS,
+    WIN_CREDITS,
+};
+use demo_game_rs::protos::grpc_service::game_state::{EntityState, Player, PlayerState, RoomState};
+
+fn create_player(player_id: &str, current_hp: i32, win_count: i32) -> Player {
+    Player {
+        player_id: player_id.to_string(),
+        nickname: format!("{} name", player_id),
+        win_count,
+        state: Some(PlayerState {
+            is_in_game: true,
+            state: Some(EntityState {
+                current_hp,
+                max_hp: 100,
+                is_alive: current_hp > 0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn create_room(players: Vec<Player>) -> RoomState {
+    RoomState {
+        started: true,
+        current_players: players.len() as i32,
+        players,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn decides_the_last_player_standing_once() {
+    let mut tracker = MatchTracker::new();
+    let mut room_state = create_room(vec![
+        create_player("alice", 100, 0),
+        create_player("bob", 40, 0),
+        create_player("carol", 0, 0),
+    ]);
+    room_state.started = false;
+    assert_eq!(tracker.update(&room_state), None);
+    room_state.started = true;
+    assert_eq!(tracker.update(&room_state), None);
+
+    room_state.players[1] = create_player("bob", 0, 0);
+    let outcome = MatchOutcome::Victory {
+        winner_id: "alice".to_string(),
+    };
+    assert_eq!(tracker.update(&room_state), Some(outcome.clone()));
+    // 決着は一度だけ知らせる。
+    // The outcome is only reported once.
+    assert_eq!(tracker.update(&room_state), None);
+    assert_eq!(tracker.get_outcome(), Some(&outcome));
+
+    tracker.reset();
+    for player in room_state.players.iter_mut() {
+        *player = create_player(&player.player_id, 0, 0);
+    }
+    assert_eq!(tracker.update(&room_state), Some(MatchOutcome::Draw));
+}
+
+#[test]
+fn counts_players_who_left_as_down() {
+    let mut tracker = MatchTracker::new();
+    let mut room_state = create_room(vec![
+        create_player("alice", 100, 0),
+        create_player("bob", 100, 0),
+    ]);
+    assert_eq!(tracker.update(&room_state), None);
+    room_state.players.pop();
+    assert_eq!(
+        tracker.update(&room_state),
+        Some(MatchOutcome::Victory {
+            winner_id: "alice".to_string()
+        })
+    );
+
+    // 一人で始めた試合は終わらない。
+    // A match started alone never ends.
+    let mut tracker = MatchTracker::new();
+    let room_state = create_room(vec![create_player("alice", 0, 0)]);
+    assert_eq!(tracker.update(&room_state), None);
+}
+
+#[test]
+fn sorts_the_scoreboard() {
+    let mut unknown = create_player("dave", 0, 5);
+    unknown.state = None;
+    let room_state = create_room(vec![
+        create_player("alice", 0, 9),
+        create_player("bob", 30, 1),
+        create_player("carol", 80, 0),
+        unknown,
+    ]);
+    let rows = get_scoreboard(&room_state);
+    let order = rows
+        .iter()
+        .map(|r| r.player_id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(order, vec!["carol", "bob", "dave", "alice"]);
+    assert!(rows[2].is_standing);
+    assert!(!rows[3].is_standing);
+    assert_eq!(rows[0].current_hp, 80);
+    assert_eq!(rows[0].max_hp, 100);
+    assert_eq!(rows[3].win_count, 9);
+}
+
+#[test]
+fn settles_credits_and_win_counts() {
+    let victory = MatchOutcome::from_winner_id("alice");
+    let mut winner = create_player("alice", 100, 2);
+    let mut loser = create_player("bob", 0, 0);
+    assert_eq!(settle_player(&mut winner, &victory), WIN_CREDITS);
+    assert_eq!(settle_player(&mut loser, &victory), PARTICIPATION_CREDITS);
+    assert_eq!((winner.win_count, winner.lose_count), (3, 0));
+    assert_eq!((loser.win_count, loser.lose_count), (0, 1));
+    assert_eq!(winner.credits, WIN_CREDITS);
+
+    let draw = MatchOutcome::from_winner_id("");
+    assert_eq!(draw, MatchOutcome::Draw);
+    assert_eq!(settle_player(&mut loser, &draw), PARTICIPATION_CREDITS);
+    assert_eq!((loser.win_count, loser.lose_count), (0, 1));
+    assert_eq!(loser.credits, PARTICIPATION_CREDITS * 2);
+}
+
+#[test]
+fn describes_results_for_the_local_player() {
+    let room_state = create_room(vec![
+        create_player("alice", 100, 0),
+        create_player("bob", 0, 0),
+    ]);
+    let victory = MatchOutcome::from_winner_id("alice");
+    let mut winner = room_state.players[0].clone();
+    let earned_credits = settle_player(&mut winner, &victory);
+    let results = MatchResults::new(victory.clone(), &room_state, &winner, earned_credits);
+    assert_eq!(results.get_title(), "Victory");
+    assert_eq!(results.winner_name.as_deref(), Some("alice name"));
+    assert_eq!(results.win_count, 1);
+
+    let results = MatchResults::new(victory, &room_state, &room_state.players[1], 0);
+    assert_eq!(results.get_title(), "Defeat");
+    let results = MatchResults::new(MatchOutcome::Draw, &room_state, &winner, 0);
+    assert_eq!(results.get_title(), "Draw");
+    assert_eq!(results.winner_name, None);
+}
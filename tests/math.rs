@@ -0,0 +1,120 @@
+use demo_game_rs::game::shared::structs::PositionInfo;
+use demo_game_rs::game::shared::util::math::{
+    decode_rotation, direction_from_yaw_pitch, encode_rotation, euler_from_quat, look_at_rotation,
+    quat_from_euler, vec3_from_slice, yaw_pitch_from_direction,
+};
+use demo_game_rs::protos::grpc_service::game_state::WorldMatrix;
+use glam::{Quat, Vec3A};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+#[test]
+fn faces_forward_at_zero_yaw() {
+    let forward = direction_from_yaw_pitch(0.0, 0.0);
+    assert!((forward - Vec3A::new(0.0, 0.0, 1.0)).length() < 1e-6);
+    let right = direction_from_yaw_pitch(FRAC_PI_2, 0.0);
+    assert!((right - Vec3A::new(1.0, 0.0, 0.0)).length() < 1e-6);
+    // 正のピッチは下を向く。
+    // A positive pitch looks down.
+    assert!(direction_from_yaw_pitch(0.0, FRAC_PI_4).y < 0.0);
+}
+
+#[test]
+fn rotates_forward_the_same_way_as_yaw_and_pitch() {
+    for &(yaw, pitch) in &[(0.3, 0.2), (-2.5, -0.7), (1.2, 1.0), (3.0, 0.0)] {
+        let rotation = quat_from_euler(Vec3A::new(pitch, yaw, 0.0));
+        let direction = rotation * Vec3A::new(0.0, 0.0, 1.0);
+        assert!((direction - direction_from_yaw_pitch(yaw, pitch)).length() < 1e-5);
+
+        let (restored_yaw, restored_pitch) = yaw_pitch_from_direction(direction);
+        assert!((restored_yaw - yaw).abs() < 1e-4);
+        assert!((restored_pitch - pitch).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn converts_euler_angles_through_quaternions() {
+    for &rotation in &[
+        Vec3A::zero(),
+        Vec3A::new(0.4, 1.3, -0.2),
+        Vec3A::new(-1.2, -3.0, 2.5),
+        Vec3A::new(0.0, FRAC_PI_2, 0.0),
+    ] {
+        let restored = euler_from_quat(quat_from_euler(rotation));
+        assert!((restored - rotation).length() < 1e-4);
+    }
+}
+
+#[test]
+fn looks_at_targets() {
+    let rotation = look_at_rotation(Vec3A::zero(), Vec3A::new(10.0, 0.0, 0.0));
+    assert!((rotation - Vec3A::new(0.0, FRAC_PI_2, 0.0)).length() < 1e-6);
+    let rotation = look_at_rotation(Vec3A::zero(), Vec3A::new(0.0, -5.0, 5.0));
+    assert!((rotation.x - FRAC_PI_4).abs() < 1e-6);
+    // 同じ位置なら回転しない。
+    // No rotation for the same position.
+    assert_eq!(look_at_rotation(Vec3A::one(), Vec3A::one()), Vec3A::zero());
+}
+
+#[test]
+fn fills_invalid_elements_with_defaults() {
+    assert_eq!(
+        vec3_from_slice(&[1.0, 2.0, 3.0], 0.0),
+        Vec3A::new(1.0, 2.0, 3.0)
+    );
+    assert_eq!(vec3_from_slice(&[5.0], 1.0), Vec3A::new(5.0, 1.0, 1.0));
+    assert_eq!(
+        vec3_from_slice(&[f32::NAN, f32::INFINITY, 2.0], 0.0),
+        Vec3A::new(0.0, 0.0, 2.0)
+    );
+}
+
+#[test]
+fn decodes_rotations_from_the_network() {
+    let rotation = Quat::from_rotation_y(1.0);
+    let scaled = Quat::from_xyzw(
+        rotation.x * 2.0,
+        rotation.y * 2.0,
+        rotation.z * 2.0,
+        rotation.w * 2.0,
+    );
+    let encoded = encode_rotation(scaled);
+    assert_eq!(encoded.len(), 4);
+    assert!(decode_rotation(&encoded).dot(rotation).abs() > 0.9999);
+
+    // 旧形式のオイラー角も読める。
+    // Legacy Euler angles can be read as well.
+    let legacy = decode_rotation(&[0.0, 1.0, 0.0]);
+    assert!(legacy.dot(rotation).abs() > 0.9999);
+
+    // 無効な値は単位クォータニオンになる。
+    // Invalid values decode to identity.
+    for values in &[
+        vec![],
+        vec![1.0, 2.0],
+        vec![0.0, 0.0, 0.0, 0.0],
+        vec![f32::NAN, 0.0, 0.0, 1.0],
+    ] {
+        assert_eq!(decode_rotation(values), Quat::identity());
+    }
+}
+
+#[test]
+fn converts_world_matrices() {
+    let position_info = PositionInfo {
+        position: Vec3A::new(1.0, 2.0, 3.0),
+        scale: Vec3A::new(2.0, 2.0, 2.0),
+        rotation: Vec3A::new(0.1, -0.8, 0.3),
+    };
+    let world_matrix = WorldMatrix::from(position_info);
+    assert_eq!(world_matrix.rotation.len(), 4);
+    let restored = PositionInfo::from(&world_matrix);
+    assert_eq!(restored.position, position_info.position);
+    assert_eq!(restored.scale, position_info.scale);
+    assert!((restored.rotation - position_info.rotation).length() < 1e-4);
+
+    // 足りない大きさは1、位置は0で埋める。
+    // Missing scale is filled with one and position with zero.
+    let restored = PositionInfo::from(&WorldMatrix::default());
+    assert_eq!(restored.position, Vec3A::zero());
+    assert_eq!(restored.scale, Vec3A::one());
+}
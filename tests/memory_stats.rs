@@ -0,0 +1,66 @@
+use demo_game_rs::game::shared::structs::{
+    format_mebibytes, HeapUsage, MemoryMonitor, MemoryStats, MEMORY_STATS_INTERVAL,
+};
+
+const MEBIBYTE: u64 = 1024 * 1024;
+
+fn heap(heap_index: usize, is_device_local: bool, usage: u64, budget: u64) -> HeapUsage {
+    HeapUsage {
+        heap_index,
+        is_device_local,
+        size: budget * MEBIBYTE,
+        usage_bytes: usage * MEBIBYTE,
+        budget_bytes: budget * MEBIBYTE,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn heaps_near_their_budget_raise_warnings() {
+    let stats = MemoryStats {
+        heaps: vec![heap(0, true, 950, 1000), heap(1, false, 100, 1000)],
+        ..Default::default()
+    };
+    assert!((stats.get_device_local_pressure() - 0.95).abs() < 1e-9);
+    assert!(stats.is_near_budget());
+    assert_eq!(
+        stats.get_warnings(),
+        vec!["Heap 0 (device local) is at 95% of its budget (950.0 MiB / 1000.0 MiB).".to_string()]
+    );
+
+    // 予算のないヒープは逼迫していると見なさない。
+    // Heaps without a budget are never considered under pressure.
+    let stats = MemoryStats {
+        heaps: vec![heap(0, true, 10, 0)],
+        ..Default::default()
+    };
+    assert_eq!(stats.get_device_local_pressure(), 0.0);
+    assert!(!stats.is_near_budget());
+    assert_eq!(format_mebibytes(3 * MEBIBYTE / 2), "1.5 MiB");
+}
+
+#[test]
+fn monitor_refreshes_on_an_interval_and_warns_once() {
+    let mut monitor = MemoryMonitor::new();
+    assert!(monitor.get_stats().is_none());
+    assert!(monitor.tick(0.0));
+    assert!(!monitor.tick(MEMORY_STATS_INTERVAL / 2.0));
+    assert!(monitor.tick(MEMORY_STATS_INTERVAL / 2.0));
+
+    let near = MemoryStats {
+        heaps: vec![heap(0, true, 980, 1000)],
+        ..Default::default()
+    };
+    assert_eq!(monitor.set_stats(near.clone()).len(), 1);
+    assert!(monitor.set_stats(near.clone()).is_empty());
+    assert_eq!(monitor.get_stats(), Some(&near));
+
+    // 予算から離れた後でまた近づけば、もう一度警告する。
+    // Nearing the budget again after moving away from it warns once more.
+    let relaxed = MemoryStats {
+        heaps: vec![heap(0, true, 500, 1000)],
+        ..Default::default()
+    };
+    assert!(monitor.set_stats(relaxed).is_empty());
+    assert_eq!(monitor.set_stats(near).len(), 1);
+}
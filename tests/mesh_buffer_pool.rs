@@ -0,0 +1,62 @@
+use demo_game_rs::game::graphics::vk::mesh_buffer_pool::{
+    BufferRange, FreeListAllocator, MeshRangeAllocator,
+};
+
+#[test]
+fn allocates_aligned_ranges_and_keeps_the_gaps_free() {
+    let mut allocator = FreeListAllocator::new(100);
+    assert_eq!(allocator.allocate(10, 1), Some(BufferRange::new(0, 10)));
+    assert_eq!(allocator.allocate(24, 12), Some(BufferRange::new(12, 24)));
+    assert_eq!(allocator.get_free_size(), 66);
+    assert_eq!(allocator.get_largest_free_size(), 64);
+
+    assert_eq!(allocator.allocate(2, 1), Some(BufferRange::new(10, 2)));
+    assert_eq!(allocator.get_largest_free_size(), 64);
+    assert_eq!(allocator.allocate(65, 1), None);
+    assert_eq!(allocator.allocate(0, 1), None);
+}
+
+#[test]
+fn freed_ranges_merge_with_their_neighbours() {
+    let mut allocator = FreeListAllocator::new(30);
+    let first = allocator.allocate(10, 1).unwrap();
+    let second = allocator.allocate(10, 1).unwrap();
+    let third = allocator.allocate(10, 1).unwrap();
+    assert_eq!(allocator.get_free_size(), 0);
+
+    allocator.free(first);
+    allocator.free(third);
+    assert_eq!(allocator.get_free_size(), 20);
+    assert_eq!(allocator.get_largest_free_size(), 10);
+    assert_eq!(allocator.allocate(20, 1), None);
+
+    allocator.free(second);
+    assert_eq!(allocator.get_largest_free_size(), 30);
+    assert_eq!(allocator.allocate(30, 1), Some(BufferRange::new(0, 30)));
+}
+
+#[test]
+fn mesh_ranges_fill_blocks_before_adding_new_ones() {
+    let mut allocator = MeshRangeAllocator::new(480, 64);
+    let first = allocator.allocate(240, 48, 32).unwrap();
+    let second = allocator.allocate(96, 48, 32).unwrap();
+    assert_eq!(allocator.get_block_count(), 1);
+    assert_eq!(second.block_index, 0);
+    assert_eq!(second.get_first_vertex(), 5);
+    assert_eq!(second.get_first_index(), 8);
+
+    // インデックスのブロックが一杯なので、最初のブロックに頂点の範囲を残さない。
+    // The index block is full, so the vertex range isn't kept in the first block.
+    let third = allocator.allocate(48, 48, 4).unwrap();
+    assert_eq!(third.block_index, 1);
+    assert_eq!(allocator.get_block_count(), 2);
+
+    allocator.free(&first);
+    let fourth = allocator.allocate(48, 48, 4).unwrap();
+    assert_eq!(fourth.block_index, 0);
+    assert_eq!(fourth.get_first_vertex(), 0);
+    assert_eq!(fourth.get_first_index(), 0);
+
+    assert!(allocator.allocate(528, 48, 4).is_none());
+    assert_eq!(allocator.get_block_count(), 2);
+}
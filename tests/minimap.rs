@@ -0,0 +1,130 @@
+use demo_game_rs::game::shared::structs::{
+    Minimap, MinimapBlip, Primitive, SpriteAtlas, SpriteBatch, Vertex, MINIMAP_SPRITE,
+};
+use glam::{Vec2, Vec3A};
+
+fn create_slope(vertex_count: u32, spacing: f32) -> Primitive {
+    let mut vertices = vec![];
+    for z in 0..vertex_count {
+        for x in 0..vertex_count {
+            vertices.push(Vertex {
+                position: Vec3A::new(x as f32 * spacing, x as f32, z as f32 * spacing),
+                normal: Vec3A::unit_y(),
+                uv: Vec2::zero(),
+            });
+        }
+    }
+    Primitive {
+        vertices,
+        indices: vec![],
+        texture_index: None,
+        is_disposed: false,
+    }
+}
+
+fn rendered_minimap() -> Minimap {
+    let mut minimap = Minimap::new(16);
+    minimap
+        .render_terrain(&create_slope(8, 10.0), Vec3A::new(-35.0, 0.0, -35.0))
+        .unwrap();
+    minimap
+}
+
+fn brightness(minimap: &Minimap, x: usize, y: usize) -> u32 {
+    let offset = (y * minimap.get_resolution() as usize + x) * 4;
+    minimap.get_pixels()[offset..offset + 3]
+        .iter()
+        .map(|channel| *channel as u32)
+        .sum()
+}
+
+#[test]
+fn higher_ground_is_brighter() {
+    let minimap = rendered_minimap();
+    assert!(minimap.has_terrain());
+    assert!(brightness(&minimap, 15, 8) > brightness(&minimap, 0, 8));
+    assert!(minimap.get_pixels().chunks(4).all(|pixel| pixel[3] == 255));
+}
+
+#[test]
+fn rejects_terrain_that_isnt_a_grid() {
+    let mut primitive = create_slope(4, 1.0);
+    primitive.vertices.pop();
+    let mut minimap = Minimap::new(16);
+    assert!(minimap.render_terrain(&primitive, Vec3A::zero()).is_err());
+    assert!(!minimap.has_terrain());
+}
+
+#[test]
+fn maps_world_positions_onto_the_terrain() {
+    let minimap = rendered_minimap();
+    let corner = minimap.world_to_map(Vec3A::new(-35.0, 0.0, -35.0));
+    assert!((corner - Vec2::zero()).length() < 1e-5);
+    let corner = minimap.world_to_map(Vec3A::new(35.0, 0.0, 35.0));
+    assert!((corner - Vec2::one()).length() < 1e-5);
+    assert!((minimap.get_center() - Vec3A::zero()).length() < 1e-5);
+}
+
+#[test]
+fn view_stays_inside_the_map() {
+    let mut minimap = rendered_minimap();
+    minimap.set_zoom(2.0);
+    let (min, max) = minimap.get_view(Vec3A::new(-35.0, 0.0, 0.0));
+    assert!((min - Vec2::new(0.0, 0.25)).length() < 1e-5);
+    assert!((max - Vec2::new(0.5, 0.75)).length() < 1e-5);
+
+    minimap.set_zoom(100.0);
+    assert!((minimap.get_zoom() - 8.0).abs() < 1e-5);
+    minimap.set_zoom(0.0);
+    let (min, max) = minimap.get_view(Vec3A::new(30.0, 0.0, 30.0));
+    assert!((min - Vec2::zero()).length() < 1e-5);
+    assert!((max - Vec2::one()).length() < 1e-5);
+}
+
+#[test]
+fn draws_only_the_blips_inside_the_view() {
+    let mut minimap = rendered_minimap();
+    let mut atlas = SpriteAtlas::new(64, 64);
+    atlas
+        .add(MINIMAP_SPRITE, 16, 16, &[0; 16 * 16 * 4])
+        .unwrap();
+    atlas.replace(MINIMAP_SPRITE, minimap.get_pixels()).unwrap();
+    minimap.set_zoom(2.0);
+    let blips = [
+        MinimapBlip {
+            position: Vec3A::zero(),
+            color: [255, 0, 0, 255],
+        },
+        MinimapBlip {
+            position: Vec3A::new(34.0, 0.0, 34.0),
+            color: [0, 0, 255, 255],
+        },
+    ];
+    let mut batch = SpriteBatch::new();
+    minimap.draw(&mut batch, &atlas, Vec3A::zero(), &blips);
+    // 枠と地図と、見える範囲にある一つの点。
+    // The frame, the map and the single blip inside the view.
+    assert_eq!(batch.get_sprites().len(), 3);
+    assert_eq!(batch.get_sprites()[2].color, [255, 0, 0, 255]);
+
+    minimap.set_visible(false);
+    batch.clear();
+    minimap.draw(&mut batch, &atlas, Vec3A::zero(), &blips);
+    assert!(batch.is_empty());
+}
+
+#[test]
+fn replacing_needs_the_same_size() {
+    let mut atlas = SpriteAtlas::new(64, 64);
+    let region = atlas.add("icon", 2, 2, &[0; 2 * 2 * 4]).unwrap();
+    assert_eq!(atlas.replace("icon", &[255; 2 * 2 * 4]).unwrap(), region);
+    assert!(atlas.replace("icon", &[255; 3 * 3 * 4]).is_err());
+    assert!(atlas.replace("missing", &[255; 4]).is_err());
+    let x = (region.uv_min.x * 64.0).round() as usize;
+    let y = (region.uv_min.y * 64.0).round() as usize;
+    let offset = (y * 64 + x) * 4;
+    assert_eq!(
+        &atlas.get_pixels()[offset..offset + 4],
+        &[255, 255, 255, 255]
+    );
+}
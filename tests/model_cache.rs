@@ -0,0 +1,44 @@
+use demo_game_rs::game::shared::structs::{get_cache_key, CacheLookup, ModelCache};
+
+#[test]
+fn shares_models_until_the_last_instance_is_released() {
+    let mut cache = ModelCache::new();
+    assert_eq!(cache.acquire("tank"), CacheLookup::Miss);
+    assert_eq!(cache.acquire("tank"), CacheLookup::Pending);
+    assert!(cache.insert("tank", 7));
+    // 二回目の読み込み結果は最初のモデルを置き換えない。
+    // A second load result doesn't replace the first model.
+    assert!(!cache.insert("tank", 8));
+    assert_eq!(cache.acquire("tank"), CacheLookup::Hit(7));
+    assert_eq!(cache.get_reference_count("tank"), 3);
+
+    assert_eq!(cache.release("tank"), None);
+    assert_eq!(cache.release("tank"), None);
+    assert_eq!(cache.release("tank"), Some(7));
+    assert!(!cache.contains("tank"));
+    assert_eq!(cache.release("tank"), None);
+}
+
+#[test]
+fn models_removed_before_loading_are_not_cached() {
+    let mut cache = ModelCache::new();
+    assert_eq!(cache.acquire("bison"), CacheLookup::Miss);
+    assert_eq!(cache.release("bison"), None);
+    assert!(!cache.insert("bison", 1));
+    assert!(cache.drain().is_empty());
+}
+
+#[test]
+fn different_spellings_of_a_path_share_a_key() {
+    let directory = std::env::temp_dir().join(format!("demo_game_cache_{}", std::process::id()));
+    std::fs::create_dir_all(directory.join("models")).expect("Failed to create model directory.");
+    std::fs::write(directory.join("models/tank.gltf"), "{}").unwrap();
+    let direct = directory.join("models/tank.gltf");
+    let indirect = directory.join("models/../models/./tank.gltf");
+    assert_eq!(
+        get_cache_key(&direct.to_string_lossy()),
+        get_cache_key(&indirect.to_string_lossy())
+    );
+    assert_eq!(get_cache_key("./missing.gltf"), "./missing.gltf");
+    let _ = std::fs::remove_dir_all(&directory);
+}
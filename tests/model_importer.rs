@@ -0,0 +1,82 @@
+use demo_game_rs::game::util::model_importer::{import_model, ModelFormat};
+use std::path::PathBuf;
+
+fn create_model_directory(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).expect("Failed to create model directory.");
+    root
+}
+
+#[test]
+fn detects_formats_from_extensions() {
+    assert_eq!(
+        ModelFormat::from_file_name("./models/tank/tank.GLTF"),
+        Some(ModelFormat::Gltf)
+    );
+    assert_eq!(
+        ModelFormat::from_file_name("crate.obj"),
+        Some(ModelFormat::Obj)
+    );
+    assert_eq!(
+        ModelFormat::from_file_name("rig.fbx"),
+        Some(ModelFormat::Fbx)
+    );
+    assert_eq!(ModelFormat::from_file_name("notes.txt"), None);
+    assert!(import_model("rig.fbx").is_err());
+}
+
+#[test]
+fn imports_textured_obj_quads() {
+    let root = create_model_directory("demo_game_obj_import");
+    std::fs::write(
+        root.join("quad.obj"),
+        "mtllib quad.mtl\n\
+         o Quad\n\
+         v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+         vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+         vn 0 0 1\n\
+         usemtl Painted\n\
+         f 1/1/1 2/2/1 3/3/1 4/4/1\n",
+    )
+    .unwrap();
+    std::fs::write(root.join("quad.mtl"), "newmtl Painted\nmap_Kd paint.png\n").unwrap();
+    image::RgbaImage::new(2, 3)
+        .save(root.join("paint.png"))
+        .expect("Failed to save texture.");
+
+    let model = import_model(&root.join("quad.obj").to_string_lossy()).unwrap();
+    assert_eq!(model.meshes.len(), 1);
+    assert_eq!(model.images.len(), 1);
+    assert_eq!((model.images[0].width, model.images[0].height), (2, 3));
+
+    let primitive = &model.meshes[0].primitives[0];
+    assert_eq!(primitive.texture_index, Some(0));
+    assert_eq!(primitive.vertices.len(), 4);
+    assert_eq!(primitive.indices.len(), 6);
+    // OBJのUVは上下が反転される。
+    // OBJ UVs are flipped vertically.
+    assert_eq!(primitive.vertices[0].uv.y, 1.0);
+    assert_eq!(primitive.vertices[2].uv.y, 0.0);
+    assert_eq!(primitive.vertices[1].normal.z, 1.0);
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn computes_missing_obj_normals() {
+    let root = create_model_directory("demo_game_obj_normals");
+    std::fs::write(
+        root.join("triangle.obj"),
+        "v 0 0 0\nv 1 0 0\nv 0 0 -1\nf 1 2 3\n",
+    )
+    .unwrap();
+
+    let model = import_model(&root.join("triangle.obj").to_string_lossy()).unwrap();
+    assert!(model.images.is_empty());
+    let primitive = &model.meshes[0].primitives[0];
+    assert_eq!(primitive.texture_index, None);
+    for vertex in primitive.vertices.iter() {
+        assert!((vertex.normal.y - 1.0).abs() < 1e-6);
+    }
+    let _ = std::fs::remove_dir_all(&root);
+}
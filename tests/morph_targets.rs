@@ -0,0 +1,75 @@
+use demo_game_rs::game::shared::structs::{
+    blend_morph_weights, pack_morph_targets, sample_morph_weights, Animation, Channel,
+    ChannelOutputs, MorphTargets, MAX_MORPH_TARGETS,
+};
+use glam::{Vec3A, Vec4};
+use gltf::animation::Interpolation;
+
+fn create_animation(interpolation: Interpolation) -> Animation {
+    Animation {
+        channels: vec![Channel {
+            target_node_index: 3,
+            inputs: vec![0.0, 1.0],
+            outputs: ChannelOutputs::MorphTargetWeights(vec![0.0, 1.0, 1.0, 0.0]),
+            interpolation,
+        }],
+        current_time: 0.0,
+    }
+}
+
+#[test]
+fn pack_offsets_skip_primitives_without_targets() {
+    let mut targets = MorphTargets::new(2);
+    assert!(targets.add_target(vec![Vec3A::new(1.0, 0.0, 0.0)], None));
+    let (data, offsets) = pack_morph_targets(vec![None, Some(&targets), None]);
+    assert_eq!(offsets, vec![0, 1, 0]);
+    assert_eq!(data.len(), 1 + 1 + 2 * 2);
+    assert_eq!(data[0], Vec4::zero());
+    assert_eq!(data[1], Vec4::new(1.0, 2.0, 0.0, 0.0));
+    assert_eq!(data[2], Vec4::new(1.0, 0.0, 0.0, 0.0));
+    // 足りない頂点の差分は0で埋められる。
+    // Deltas of missing vertices are filled with zero.
+    assert_eq!(data[4], Vec4::zero());
+}
+
+#[test]
+fn targets_are_capped_and_applied_by_weight() {
+    let mut targets = MorphTargets::new(1);
+    for _ in 0..MAX_MORPH_TARGETS {
+        assert!(targets.add_target(vec![Vec3A::new(0.0, 1.0, 0.0)], None));
+    }
+    assert!(!targets.add_target(vec![Vec3A::one()], None));
+    assert_eq!(targets.get_target_count(), MAX_MORPH_TARGETS);
+
+    let (position, normal) = targets.apply(0, Vec3A::zero(), Vec3A::unit_z(), &[0.5, 0.25]);
+    assert!((position.y - 0.75).abs() < 1e-6);
+    assert_eq!(normal, Vec3A::unit_z());
+}
+
+#[test]
+fn weights_follow_linear_and_step_channels() {
+    let linear = create_animation(Interpolation::Linear);
+    let weights = sample_morph_weights(&linear, 0.25, 3, &[0.0, 0.0]);
+    assert!((weights[0] - 0.25).abs() < 1e-6);
+    assert!((weights[1] - 0.75).abs() < 1e-6);
+
+    let step = create_animation(Interpolation::Step);
+    assert_eq!(
+        sample_morph_weights(&step, 0.75, 3, &[0.0, 0.0]),
+        vec![0.0, 1.0]
+    );
+
+    // 他のノードのチャンネルは既定の重みを変えない。
+    // Channels of other nodes leave the default weights untouched.
+    assert_eq!(
+        sample_morph_weights(&linear, 0.5, 4, &[0.5, 0.5]),
+        vec![0.5, 0.5]
+    );
+}
+
+#[test]
+fn blends_weights_between_clips() {
+    let weights = blend_morph_weights(&[0.0, 1.0], &[1.0, 0.0], 0.25);
+    assert!((weights[0] - 0.25).abs() < 1e-6);
+    assert!((weights[1] - 0.75).abs() < 1e-6);
+}
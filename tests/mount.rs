@@ -0,0 +1,90 @@
+use demo_game_rs::game::shared::components::Mount;
+use demo_game_rs::game::shared::systems::MountOwners;
+use glam::Vec3A;
+
+#[test]
+fn builds_up_speed_and_coasts_to_a_stop() {
+    let mut mount = Mount::new("mount-1");
+    let mut position = Vec3A::zero();
+    let mut yaw = 0.0;
+    mount.throttle = 1.0;
+    mount.step(&mut position, &mut yaw, 1.0);
+    assert!((mount.speed - mount.acceleration).abs() < 1e-4);
+    assert!((position.z - mount.acceleration).abs() < 1e-4);
+    for _ in 0..10 {
+        mount.step(&mut position, &mut yaw, 1.0);
+    }
+    assert!((mount.speed - mount.max_speed).abs() < 1e-4);
+
+    // アクセルを離しても、すぐには止まらない。
+    // Releasing the throttle doesn't stop it at once.
+    mount.throttle = 0.0;
+    mount.step(&mut position, &mut yaw, 1.0);
+    assert!((mount.speed - (mount.max_speed - mount.drag)).abs() < 1e-4);
+    mount.throttle = -1.0;
+    mount.step(&mut position, &mut yaw, 1.0);
+    assert!((mount.speed - (mount.max_speed - mount.drag - mount.braking)).abs() < 1e-4);
+}
+
+#[test]
+fn turns_along_its_turning_radius() {
+    let mut mount = Mount::new("mount-1");
+    mount.speed = mount.turning_radius;
+    mount.throttle = 1.0;
+    mount.acceleration = 0.0;
+    mount.steering = 1.0;
+    let mut position = Vec3A::zero();
+    let mut yaw = 0.0;
+    let steps = 1000;
+    let quarter_turn = std::f32::consts::FRAC_PI_2;
+    for _ in 0..steps {
+        mount.step(&mut position, &mut yaw, quarter_turn / steps as f32);
+    }
+    // 四分の一周回ると、回転半径だけ横と前に進む。
+    // After a quarter turn, it has moved one turning radius sideways and forwards.
+    assert!((yaw - quarter_turn).abs() < 1e-3);
+    assert!((position.x - mount.turning_radius).abs() < 0.05);
+    assert!((position.z - mount.turning_radius).abs() < 0.05);
+
+    // 止まっていればハンドルを切っても向きは変わらない。
+    // Steering doesn't change the heading while stopped.
+    let mut stopped = Mount::new("mount-2");
+    stopped.steering = 1.0;
+    let mut still_yaw = 0.0;
+    let mut still_position = Vec3A::zero();
+    stopped.step(&mut still_position, &mut still_yaw, 1.0);
+    assert_eq!(still_yaw, 0.0);
+}
+
+#[test]
+fn seats_follow_the_heading() {
+    let mount = Mount::new("mount-1");
+    assert!(mount.is_free());
+    let seat = mount.get_seat_position(Vec3A::new(1.0, 0.0, 1.0), 0.0);
+    assert_eq!(seat, Vec3A::new(1.0, 2.0, 1.0));
+    let dismount = mount.get_dismount_position(Vec3A::zero(), std::f32::consts::PI);
+    assert!((dismount.x - 4.0).abs() < 1e-4);
+    assert!(dismount.z.abs() < 1e-4);
+}
+
+#[test]
+fn tracks_mount_owners_by_mount() {
+    let mut owners = MountOwners::new();
+    owners.push(vec![
+        ("local".to_string(), "mount-1".to_string()),
+        ("remote".to_string(), String::new()),
+    ]);
+    let mounts = owners.get();
+    assert_eq!(mounts.len(), 1);
+    assert_eq!(mounts.get("mount-1").map(|s| s.as_str()), Some("local"));
+
+    // 降りたプレイヤーの乗り物は空く。
+    // The mount of a player who got off becomes free.
+    owners.push(vec![
+        ("local".to_string(), String::new()),
+        ("remote".to_string(), "mount-2".to_string()),
+    ]);
+    let mounts = owners.get();
+    assert!(!mounts.contains_key("mount-1"));
+    assert_eq!(mounts.get("mount-2").map(|s| s.as_str()), Some("remote"));
+}
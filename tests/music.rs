@@ -0,0 +1,91 @@
+use demo_game_rs::game::shared::enums::MusicLayer;
+use demo_game_rs::game::shared::structs::MusicSet;
+use demo_game_rs::game::shared::systems::{MusicDirector, MusicEvent};
+
+// 120BPMなので一拍は0.5秒、一小節は2秒、クロスフェードは1秒。
+// At 120 BPM a beat is 0.5 seconds, a bar is 2 seconds and the crossfade is 1 second.
+const MUSIC: &str = r#"{
+    "GAME_SCENE": {
+        "tempo": 120.0,
+        "crossfade_beats": 2.0,
+        "tension_distance": 20.0,
+        "stems": {
+            "Exploration": "explore.ogg",
+            "Tension": "tension.ogg",
+            "Combat": "combat.ogg"
+        }
+    }
+}"#;
+
+fn director() -> MusicDirector {
+    let music_set = MusicSet::from_json(MUSIC, "GAME_SCENE").expect("Failed to load music.");
+    let mut director = MusicDirector::new();
+    director.set_music_set(music_set);
+    director
+}
+
+#[test]
+fn loads_the_music_of_a_scene() {
+    let music_set = MusicSet::from_json(MUSIC, "GAME_SCENE").expect("Failed to load music.");
+    assert_eq!(music_set.beats_per_bar, 4);
+    assert_eq!(
+        music_set.get_stem_files(),
+        vec!["explore.ogg", "tension.ogg", "combat.ogg"]
+    );
+    assert!(MusicSet::from_json(MUSIC, "TITLE_SCENE").is_err());
+
+    let json = MUSIC.replace(r#""Tension": "tension.ogg","#, "");
+    assert!(MusicSet::from_json(&json, "GAME_SCENE").is_err());
+}
+
+#[test]
+fn gameplay_decides_the_target_layer() {
+    let mut director = director();
+    assert_eq!(director.get_target_layer(), MusicLayer::Exploration);
+
+    director.handle_event(MusicEvent::EnemyProximity(Some(40.0)));
+    assert_eq!(director.get_target_layer(), MusicLayer::Exploration);
+    director.handle_event(MusicEvent::EnemyProximity(Some(10.0)));
+    assert_eq!(director.get_target_layer(), MusicLayer::Tension);
+    director.handle_event(MusicEvent::EnemyProximity(None));
+
+    director.handle_event(MusicEvent::HealthChanged {
+        current: 20,
+        max: 100,
+    });
+    assert_eq!(director.get_target_layer(), MusicLayer::Tension);
+
+    director.handle_event(MusicEvent::CombatStarted);
+    assert_eq!(director.get_target_layer(), MusicLayer::Combat);
+}
+
+#[test]
+fn combat_starts_on_the_next_beat() {
+    let mut director = director();
+    director.advance(0.1);
+    director.handle_event(MusicEvent::CombatStarted);
+
+    director.advance(0.3);
+    assert_eq!(director.get_current_layer(), MusicLayer::Exploration);
+    director.advance(0.2);
+    assert_eq!(director.get_current_layer(), MusicLayer::Combat);
+
+    let volumes = director.advance(0.5);
+    assert!(volumes[MusicLayer::Combat.index()] > volumes[MusicLayer::Exploration.index()]);
+    let volumes = director.advance(1.0);
+    assert_eq!(volumes, [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn calming_down_waits_for_the_next_bar() {
+    let mut director = director();
+    director.handle_event(MusicEvent::CombatStarted);
+    director.advance(0.5);
+    assert_eq!(director.get_current_layer(), MusicLayer::Combat);
+
+    director.handle_event(MusicEvent::CombatEnded);
+    director.advance(1.0);
+    assert_eq!(director.get_current_layer(), MusicLayer::Combat);
+    director.advance(0.6);
+    assert_eq!(director.get_current_layer(), MusicLayer::Exploration);
+}
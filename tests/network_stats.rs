@@ -0,0 +1,69 @@
+use demo_game_rs::game::shared::structs::games::network_stats::{
+    CallLatency, RateMeter, TrafficStats, RATE_INTERVAL,
+};
+use std::time::{Duration, Instant};
+
+fn create_traffic(datagrams: u64, bytes: u64) -> TrafficStats {
+    TrafficStats {
+        datagrams,
+        bytes,
+        ..TrafficStats::default()
+    }
+}
+
+#[test]
+fn measures_traffic_per_second() {
+    let start = Instant::now();
+    let mut meter = RateMeter::new();
+    let rates = meter.sample(start, &create_traffic(10, 1000), &create_traffic(5, 500));
+    assert_eq!(rates.datagrams_sent, 0.0);
+
+    // 間隔が経つまでは前の値のまま。
+    // The previous values are kept until the interval has passed.
+    let rates = meter.sample(
+        start + RATE_INTERVAL / 2,
+        &create_traffic(20, 2000),
+        &create_traffic(5, 500),
+    );
+    assert_eq!(rates.bytes_sent, 0.0);
+
+    let rates = meter.sample(
+        start + Duration::from_secs(2),
+        &create_traffic(50, 5000),
+        &create_traffic(25, 2500),
+    );
+    assert!((rates.datagrams_sent - 20.0).abs() < 1e-5);
+    assert!((rates.bytes_sent - 2000.0).abs() < 1e-5);
+    assert!((rates.datagrams_received - 10.0).abs() < 1e-5);
+    assert!((rates.bytes_received - 1000.0).abs() < 1e-5);
+}
+
+#[test]
+fn treats_reset_counters_as_no_traffic() {
+    let start = Instant::now();
+    let mut meter = RateMeter::new();
+    meter.sample(start, &create_traffic(50, 5000), &create_traffic(50, 5000));
+    let rates = meter.sample(
+        start + RATE_INTERVAL,
+        &create_traffic(0, 0),
+        &create_traffic(10, 100),
+    );
+    assert_eq!(rates.datagrams_sent, 0.0);
+    assert_eq!(rates.bytes_sent, 0.0);
+    assert_eq!(rates.datagrams_received, 0.0);
+}
+
+#[test]
+fn averages_call_latency() {
+    let mut latency = CallLatency::default();
+    assert!(latency.average.is_none());
+    latency.record("GetRooms", Duration::from_millis(80), true);
+    assert_eq!(latency.average, Some(Duration::from_millis(80)));
+
+    latency.record("Login", Duration::from_millis(160), false);
+    assert_eq!(latency.last_call, Some("Login"));
+    assert_eq!(latency.last, Some(Duration::from_millis(160)));
+    assert_eq!(latency.average, Some(Duration::from_millis(90)));
+    assert_eq!(latency.calls, 2);
+    assert_eq!(latency.failures, 1);
+}
@@ -0,0 +1,797 @@
+mod support;
+
+use demo_game_rs::game::shared::structs::games::match_result::{
+    MatchOutcome, PARTICIPATION_CREDITS, WIN_CREDITS,
+};
+use demo_game_rs::game::shared::structs::games::protocol::ProtocolCompatibility;
+use demo_game_rs::game::shared::structs::games::replication::{EntitySnapshot, ReplicationEvent};
+use demo_game_rs::game::shared::structs::games::terrain_patch::TerrainPatch;
+use demo_game_rs::game::shared::structs::{LifeEvent, PositionInfo, Primitive, Vertex};
+use demo_game_rs::game::shared::systems::NetworkSystem;
+use demo_game_rs::protos::grpc_service::game_state::{EntityState, WorldMatrix};
+use glam::{Vec2, Vec3A};
+use std::net::SocketAddr;
+use std::time::Duration;
+use support::{MockServer, CANNED_PLAYERS, CANNED_ROOM_ID};
+
+/// 非同期の処理を待つ最大の時間。<br />
+/// Maximum time to wait for asynchronous work.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+async fn connect(address: SocketAddr) -> NetworkSystem {
+    NetworkSystem::connect(MockServer::get_settings(address))
+        .await
+        .expect("Failed to connect to mock server.")
+}
+
+/// あらかじめ用意されたプレイヤーでログインする。<br />
+/// Log in as one of the canned players.
+async fn login(network_system: &mut NetworkSystem, index: usize) {
+    let (account, _, password) = CANNED_PLAYERS[index];
+    network_system
+        .login(account, &base64::encode(password))
+        .await
+        .expect("Failed to log in as canned player.");
+}
+
+/// ゲームが始まったという通知を待つ。<br />
+/// Wait for the notification that the game has started.
+async fn wait_for_start(recv: &crossbeam::channel::Receiver<bool>) -> bool {
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        loop {
+            if let Ok(started) = recv.try_recv() {
+                return started;
+            }
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the game to start.")
+}
+
+/// ゲーム中のプレイヤーの状態を決め、そのプレイヤーのIDを返す。ワールド行列のない状態は送られない。<br />
+/// Set the in-game state of the player, returning the player's ID. States without a world matrix aren't sent.
+async fn set_player_state(
+    network_system: &NetworkSystem,
+    is_alive: bool,
+    mount_id: &str,
+) -> String {
+    let mut player = network_system
+        .logged_user
+        .as_ref()
+        .expect("Failed to get logged in player.")
+        .lock()
+        .await;
+    player.state.get_or_insert_with(Default::default).state = Some(EntityState {
+        current_hp: 100,
+        max_hp: 100,
+        current_sp: 100,
+        max_sp: 100,
+        is_alive,
+        world_matrix: Some(WorldMatrix::from(PositionInfo::default())),
+        last_attacker_id: String::new(),
+        mount_id: mount_id.to_string(),
+    });
+    player.player_id.clone()
+}
+
+fn create_entity(entity_id: &str, owner_id: &str) -> EntitySnapshot {
+    EntitySnapshot {
+        entity_id: entity_id.to_string(),
+        owner_id: owner_id.to_string(),
+        model: "tank.gltf".to_string(),
+        current_hp: 100,
+        max_hp: 100,
+        ..Default::default()
+    }
+}
+
+fn create_terrain() -> Primitive {
+    let vertices = (0..4)
+        .map(|i| Vertex {
+            position: Vec3A::new((i % 2) as f32, i as f32 * 0.5, (i / 2) as f32),
+            normal: Vec3A::new(0.0, 1.0, 0.0),
+            uv: Vec2::new((i % 2) as f32, (i / 2) as f32),
+        })
+        .collect::<Vec<_>>();
+    Primitive {
+        vertices,
+        indices: vec![0, 1, 2, 2, 1, 3],
+        texture_index: None,
+        is_disposed: false,
+    }
+}
+
+#[tokio::test]
+async fn connects_and_logs_in() {
+    let (_server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let mut network_system = connect(address).await;
+    assert_eq!(
+        network_system.compatibility,
+        ProtocolCompatibility::Compatible
+    );
+
+    let (account, nickname, password) = CANNED_PLAYERS[0];
+    let rejected = network_system
+        .login(account, &base64::encode("wrong"))
+        .await;
+    assert!(rejected.is_err());
+    assert!(!network_system.is_player_login);
+
+    let player = network_system
+        .login(account, &base64::encode(password))
+        .await
+        .expect("Failed to log in.");
+    assert_eq!(player.nickname, nickname);
+    assert!(network_system.is_player_login);
+}
+
+#[tokio::test]
+async fn registers_new_player() {
+    let (_server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let mut network_system = connect(address).await;
+    let player = network_system
+        .register(
+            "carol",
+            "Carol",
+            "carol@example.com",
+            "carol-pass",
+            "carol-pass",
+        )
+        .await
+        .expect("Failed to register.");
+    assert_eq!(player.nickname, "Carol");
+
+    let taken = network_system
+        .register(
+            "carol",
+            "Carol",
+            "carol@example.com",
+            "carol-pass",
+            "carol-pass",
+        )
+        .await
+        .expect_err("Registered the same user name twice.");
+    assert_eq!(taken.to_string(), "User name is already taken.");
+
+    // 入力が正しくなければサーバーに送らない。
+    // Invalid inputs aren't sent to the server.
+    let invalid = network_system
+        .register("dave", "Dave", "dave@example", "dave-pass", "dave-pass")
+        .await
+        .expect_err("Registered with an invalid email address.");
+    assert_eq!(invalid.to_string(), "The email address isn't valid.");
+}
+
+#[tokio::test]
+async fn joins_room_and_exchanges_terrain() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let rooms = connect(address)
+        .await
+        .get_rooms()
+        .await
+        .expect("Failed to get rooms.");
+    assert_eq!(rooms.len(), 1);
+    assert_eq!(rooms[0].room_id, CANNED_ROOM_ID);
+
+    let mut host = connect(address).await;
+    let mut guest = connect(address).await;
+    login(&mut host, 0).await;
+    login(&mut guest, 1).await;
+    let host_started = host
+        .register_player(CANNED_ROOM_ID.to_string(), String::new(), true)
+        .await
+        .expect("Failed to register host to room.");
+    let guest_started = guest
+        .register_player(CANNED_ROOM_ID.to_string(), String::new(), false)
+        .await
+        .expect("Failed to register guest to room.");
+
+    // ホストは全員が部屋に入ったのを確認してからゲームを始める。
+    // The host starts the game only after everyone has joined the room.
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        while host.room_state.lock().await.current_players < 2 {
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the guest to join.");
+
+    let terrain = create_terrain();
+    host.start_game(terrain.clone())
+        .await
+        .expect("Failed to start game.");
+    assert!(wait_for_start(&host_started).await);
+    assert!(wait_for_start(&guest_started).await);
+    assert!(
+        server
+            .get_room(CANNED_ROOM_ID)
+            .await
+            .expect("Failed to get room from mock server.")
+            .started
+    );
+    assert!(server.get_stored_terrain(CANNED_ROOM_ID).await.is_some());
+
+    let received = guest.get_terrain().await.expect("Failed to get terrain.");
+    assert_eq!(received.indices, terrain.indices);
+    assert_eq!(received.vertices.len(), terrain.vertices.len());
+    // 高さは量子化して送るので、誤差の範囲で一致すればよい。
+    // Heights are sent quantized, so they only need to match within the error.
+    for (received, expected) in received.vertices.iter().zip(terrain.vertices.iter()) {
+        assert!((received.position - expected.position).length() < 1e-3);
+        assert_eq!(received.uv, expected.uv);
+    }
+}
+
+#[tokio::test]
+async fn hands_out_terrain_patches_with_the_terrain() {
+    let (_server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let mut host = connect(address).await;
+    let mut guest = connect(address).await;
+    login(&mut host, 0).await;
+    login(&mut guest, 1).await;
+    host.register_player(CANNED_ROOM_ID.to_string(), String::new(), true)
+        .await
+        .expect("Failed to register host to room.");
+    let guest_started = guest
+        .register_player(CANNED_ROOM_ID.to_string(), String::new(), false)
+        .await
+        .expect("Failed to register guest to room.");
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        while host.room_state.lock().await.current_players < 2 {
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the guest to join.");
+    let terrain = create_terrain();
+    host.start_game(terrain.clone())
+        .await
+        .expect("Failed to start game.");
+    assert!(wait_for_start(&guest_started).await);
+
+    // ゲームが始まった後に地形を変え、後から地形を取りに来たプレイヤーにも届くようにする。
+    // Modify the terrain after the game started, so players fetching the terrain later receive it too.
+    let mut modified = terrain.clone();
+    modified.vertices[3].position.y -= 2.0;
+    let patch = TerrainPatch::diff(0, 0, &terrain, &modified)
+        .expect("Failed to diff terrain.")
+        .expect("Modified terrain has no patch.");
+    host.update_terrain(&[patch])
+        .await
+        .expect("Failed to update terrain.");
+
+    let received = guest.get_terrain().await.expect("Failed to get terrain.");
+    for (received, expected) in received.vertices.iter().zip(modified.vertices.iter()) {
+        assert!((received.position - expected.position).length() < 1e-3);
+    }
+}
+
+#[tokio::test]
+async fn reports_match_results_and_leaves_room() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let mut host = connect(address).await;
+    let mut guest = connect(address).await;
+    login(&mut host, 0).await;
+    login(&mut guest, 1).await;
+    host.register_player(CANNED_ROOM_ID.to_string(), String::new(), true)
+        .await
+        .expect("Failed to register host to room.");
+    let guest_started = guest
+        .register_player(CANNED_ROOM_ID.to_string(), String::new(), false)
+        .await
+        .expect("Failed to register guest to room.");
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        while host.room_state.lock().await.current_players < 2 {
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the guest to join.");
+    host.start_game(create_terrain())
+        .await
+        .expect("Failed to start game.");
+    assert!(wait_for_start(&guest_started).await);
+
+    let host_id = host
+        .logged_user
+        .as_ref()
+        .expect("Failed to get host player.")
+        .lock()
+        .await
+        .player_id
+        .clone();
+    let results = host
+        .report_match_result(&MatchOutcome::Victory {
+            winner_id: host_id.clone(),
+        })
+        .await
+        .expect("Failed to report match result.");
+    assert_eq!(results.get_title(), "Victory");
+    assert_eq!(results.earned_credits, WIN_CREDITS);
+    assert_eq!(results.win_count, 1);
+
+    // 後から食い違う報告をしても、最初の報告の勝者に従う。
+    // A conflicting report afterwards still follows the winner of the first report.
+    let results = guest
+        .report_match_result(&MatchOutcome::Draw)
+        .await
+        .expect("Failed to report match result.");
+    assert_eq!(results.get_title(), "Defeat");
+    assert_eq!(results.winner_name.as_deref(), Some(CANNED_PLAYERS[0].1));
+    assert_eq!(results.earned_credits, PARTICIPATION_CREDITS);
+    assert_eq!(results.credits, PARTICIPATION_CREDITS);
+    assert_eq!(results.lose_count, 1);
+    assert!(server
+        .get_room(CANNED_ROOM_ID)
+        .await
+        .expect("Failed to get room from mock server.")
+        .players
+        .is_empty());
+
+    guest.leave_room().await;
+    let room_state = guest.room_state.lock().await;
+    assert!(!room_state.started);
+    assert!(room_state.room_id.is_empty());
+    let player = guest
+        .logged_user
+        .as_ref()
+        .expect("Failed to get guest player.")
+        .lock()
+        .await
+        .clone();
+    assert_eq!(player.lose_count, 1);
+    assert!(player.state.map(|s| s.room_id.is_empty()).unwrap_or(true));
+}
+
+#[tokio::test]
+async fn exchanges_chat_messages() {
+    let (_server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let mut sender = connect(address).await;
+    let mut receiver = connect(address).await;
+    login(&mut sender, 0).await;
+    login(&mut receiver, 1).await;
+    sender.start_chat().await.expect("Failed to start chat.");
+    receiver.start_chat().await.expect("Failed to start chat.");
+    assert_eq!(receiver.chat_messages.lock().await.len(), 2);
+
+    sender
+        .send_chat_message("  Good luck!  ")
+        .await
+        .expect("Failed to send chat message.");
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        while receiver.chat_messages.lock().await.len() < 3 {
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the chat message.");
+
+    let messages = receiver.chat_messages.lock().await;
+    let last = messages.last().expect("Failed to get last chat message.");
+    assert_eq!(last.author, CANNED_PLAYERS[0].1);
+    assert_eq!(last.message, "Good luck!");
+}
+
+#[tokio::test]
+async fn creates_room_with_max_players() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let mut network_system = connect(address).await;
+    login(&mut network_system, 0).await;
+    network_system
+        .create_room("New Room".to_string(), 2)
+        .await
+        .expect("Failed to create room.");
+
+    let rooms = network_system
+        .get_rooms()
+        .await
+        .expect("Failed to get rooms.");
+    let room = rooms
+        .iter()
+        .find(|r| r.room_name == "New Room")
+        .expect("Failed to find created room.");
+    assert_eq!(room.max_players, 2);
+    assert_eq!(room.current_players, 1);
+    assert!(server
+        .get_room(&room.room_id)
+        .await
+        .expect("Failed to get room from mock server.")
+        .players[0]
+        .state
+        .as_ref()
+        .map(|s| s.is_owner)
+        .unwrap_or(false));
+}
+
+#[tokio::test]
+async fn starts_offline_game_without_server() {
+    let mut network_system = NetworkSystem::offline();
+    assert!(network_system.is_offline());
+    assert!(network_system.is_player_login);
+    assert!(network_system.get_rooms().await.is_err());
+
+    network_system
+        .start_game(create_terrain())
+        .await
+        .expect("Failed to start offline game.");
+    network_system
+        .progress_game()
+        .await
+        .expect("Failed to progress offline game.");
+    let room_state = network_system
+        .progress_recv
+        .take()
+        .expect("Failed to get progress receiver.")
+        .await
+        .expect("Failed to receive offline room state.");
+    assert!(room_state.started);
+    assert_eq!(room_state.players.len(), 1);
+
+    // オフラインのチャットは自分のメッセージだけを表示する。
+    // Offline chat only shows our own messages.
+    network_system
+        .send_chat_message("Hello")
+        .await
+        .expect("Failed to send offline chat message.");
+    let messages = network_system.chat_messages.lock().await;
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].message, "Hello");
+}
+
+/// UDPの中継を持つ部屋でゲームを始め、両方のプレイヤーでゲームを推進する。<br />
+/// ホストとゲスト、それぞれのプレイヤーのIDを返す。ゲストは乗り物に乗っている。<br />
+/// Start a game in a room with a UDP relay and progress it with both players.<br />
+/// Returns the host, the guest and the IDs of their players. The guest is riding a mount.
+async fn start_progressing(
+    server: &MockServer,
+    address: SocketAddr,
+) -> (NetworkSystem, NetworkSystem, String, String) {
+    let mut settings = MockServer::get_settings(address);
+    settings.udp_endpoint = server
+        .start_udp()
+        .await
+        .expect("Failed to start mock UDP relay.")
+        .to_string();
+    let mut host = NetworkSystem::connect(settings.clone())
+        .await
+        .expect("Failed to connect to mock server.");
+    let mut guest = NetworkSystem::connect(settings)
+        .await
+        .expect("Failed to connect to mock server.");
+    login(&mut host, 0).await;
+    login(&mut guest, 1).await;
+    host.register_player(CANNED_ROOM_ID.to_string(), String::new(), true)
+        .await
+        .expect("Failed to register host to room.");
+    let guest_started = guest
+        .register_player(CANNED_ROOM_ID.to_string(), String::new(), false)
+        .await
+        .expect("Failed to register guest to room.");
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        while host.room_state.lock().await.current_players < 2 {
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the guest to join.");
+    host.start_game(create_terrain())
+        .await
+        .expect("Failed to start game.");
+    assert!(wait_for_start(&guest_started).await);
+
+    // ゲームを始めた時と同じ入口から推進し、UDPの複製も一緒に始まる。
+    // Progress through the same entry point as starting a game, which starts UDP replication as well.
+    let host_id = set_player_state(&host, true, "").await;
+    let guest_id = set_player_state(&guest, true, "boat-1").await;
+    host.progress_game()
+        .await
+        .expect("Failed to progress host game.");
+    guest
+        .progress_game()
+        .await
+        .expect("Failed to progress guest game.");
+    (host, guest, host_id, guest_id)
+}
+
+/// 条件を満たす複製のイベントが届くのを待つ。<br />
+/// Wait for a replication event matching the predicate to arrive.
+async fn wait_for_replication<F>(network_system: &NetworkSystem, predicate: F) -> ReplicationEvent
+where
+    F: Fn(&ReplicationEvent) -> bool,
+{
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        loop {
+            let events = network_system.replication.lock().await.take_events();
+            if let Some(event) = events.into_iter().find(|e| predicate(e)) {
+                return event;
+            }
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for a replication event.")
+}
+
+#[tokio::test]
+async fn replicates_entities_while_progressing() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let (mut host, mut guest, host_id, guest_id) = start_progressing(&server, address).await;
+    host.replication
+        .lock()
+        .await
+        .set_owned_entities(vec![create_entity("host-tank", &host_id)]);
+    guest
+        .replication
+        .lock()
+        .await
+        .set_owned_entities(vec![create_entity("guest-tank", &guest_id)]);
+
+    let spawned = wait_for_replication(&host, |e| matches!(e, ReplicationEvent::Spawned(_))).await;
+    match spawned {
+        ReplicationEvent::Spawned(entity) => {
+            assert_eq!(entity.entity_id, "guest-tank");
+            assert_eq!(entity.owner_id, guest_id);
+        }
+        _ => unreachable!(),
+    }
+    assert_eq!(server.get_replicated_entities().await.len(), 2);
+
+    host.leave_room().await;
+    guest.leave_room().await;
+}
+
+#[tokio::test]
+async fn despawns_entities_their_owner_stopped_sending() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let (mut host, mut guest, _, guest_id) = start_progressing(&server, address).await;
+    guest
+        .replication
+        .lock()
+        .await
+        .set_owned_entities(vec![create_entity("guest-tank", &guest_id)]);
+    wait_for_replication(&host, |e| matches!(e, ReplicationEvent::Spawned(_))).await;
+
+    // 持ち主が手放したエンティティは、他のプレイヤーからも消える。
+    // Entities released by their owner disappear for other players as well.
+    guest.replication.lock().await.set_owned_entities(vec![]);
+    let despawned =
+        wait_for_replication(&host, |e| matches!(e, ReplicationEvent::Despawned(_))).await;
+    match despawned {
+        ReplicationEvent::Despawned(entity_id) => assert_eq!(entity_id, "guest-tank"),
+        _ => unreachable!(),
+    }
+    assert!(server.get_replicated_entities().await.is_empty());
+
+    host.leave_room().await;
+    guest.leave_room().await;
+}
+
+#[tokio::test]
+async fn closes_udp_session_when_leaving_room() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let (mut host, mut guest, _, _) = start_progressing(&server, address).await;
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        while host.network_stats.lock().await.received.messages == 0 {
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the UDP session to open.");
+
+    // 部屋を出ると、セッションを通したスナップショットの送信も止まる。
+    // Leaving the room stops sending snapshots through the session as well.
+    host.leave_room().await;
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+    let sent = host.network_stats.lock().await.sent.messages;
+    tokio::time::delay_for(Duration::from_millis(200)).await;
+    assert_eq!(host.network_stats.lock().await.sent.messages, sent);
+
+    guest.leave_room().await;
+}
+
+#[tokio::test]
+async fn measures_traffic_and_round_trip_time_while_progressing() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let (mut host, mut guest, _, _) = start_progressing(&server, address).await;
+
+    // 往復時間はセッションのピングで測られるので、最初のピングまで待つ。
+    // The round-trip time is measured by the pings of the session, so wait for the first ping.
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        while host.network_stats.lock().await.round_trip_time.is_none() {
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the round-trip time.");
+    let stats = *host.network_stats.lock().await;
+    assert!(stats.sent.messages > 0);
+    assert!(stats.sent.bytes > 0);
+    assert!(stats.received.messages > 0);
+    assert!(stats.received.datagrams > 0);
+    assert_eq!(stats.dropped_datagrams, 0);
+
+    host.leave_room().await;
+    guest.leave_room().await;
+}
+
+/// ローカルプレイヤーの生死のイベントが届くのを待つ。<br />
+/// Wait for a life event of the local player to arrive.
+async fn wait_for_life_event(network_system: &NetworkSystem) -> LifeEvent {
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        loop {
+            let events = network_system.life_tracker.lock().await.take_events();
+            if let Some(event) = events.into_iter().next() {
+                return event;
+            }
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for a life event.")
+}
+
+/// ローカルプレイヤーを含む部屋のステートが届くのを待つ。<br />
+/// Wait for a room state including the local player to arrive.
+async fn wait_for_room_state(network_system: &NetworkSystem) {
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        loop {
+            let states = network_system
+                .state_queues
+                .lock()
+                .await
+                .take_local_server_states();
+            if !states.is_empty() {
+                break;
+            }
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the room state.");
+}
+
+#[tokio::test]
+async fn reports_local_death_from_room_state_while_progressing() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let (mut host, mut guest, _, _) = start_progressing(&server, address).await;
+
+    // 生死はUDPではなく、gRPCの部屋のステートで届く。
+    // Lives arrive with the gRPC room state rather than over UDP.
+    wait_for_room_state(&host).await;
+    set_player_state(&host, false, "").await;
+    assert!(matches!(
+        wait_for_life_event(&host).await,
+        LifeEvent::Died(_)
+    ));
+
+    host.leave_room().await;
+    guest.leave_room().await;
+}
+
+#[tokio::test]
+async fn tracks_mount_owners_while_progressing() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let (mut host, mut guest, _, guest_id) = start_progressing(&server, address).await;
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        loop {
+            let mounts = host.mount_owners.lock().await.get();
+            if mounts.get("boat-1") == Some(&guest_id) {
+                break;
+            }
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the guest's mount.");
+
+    // 降りた乗り物は誰のものでもなくなる。
+    // A mount that was left no longer belongs to anyone.
+    set_player_state(&guest, true, "").await;
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        while !host.mount_owners.lock().await.get().is_empty() {
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the guest to dismount.");
+
+    host.leave_room().await;
+    guest.leave_room().await;
+}
+
+#[tokio::test]
+async fn replicates_mounts_from_live_player_state_over_udp() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let (mut host, mut guest, _, guest_id) = start_progressing(&server, address).await;
+
+    // UDPのスナップショットも、ゲームの推進で送るプレイヤーの状態から乗り物を取る。
+    // UDP snapshots also take the mount from the player state sent while progressing the game.
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        loop {
+            let is_mounted = host
+                .room_state_udp
+                .lock()
+                .await
+                .players
+                .iter()
+                .any(|p| p.player_id == guest_id && p.state.state.mount_id == "boat-1");
+            if is_mounted {
+                break;
+            }
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("Timed out waiting for the guest's mount over UDP.");
+
+    host.leave_room().await;
+    guest.leave_room().await;
+}
+
+#[tokio::test]
+async fn reports_killer_and_respawn_while_progressing() {
+    let (server, address) = MockServer::start()
+        .await
+        .expect("Failed to start mock server.");
+    let (mut host, mut guest, _, guest_id) = start_progressing(&server, address).await;
+    wait_for_room_state(&host).await;
+
+    // キルカメラのキラーも、部屋のステートで届いた最後の攻撃者から決まる。
+    // The killer for the kill cam is also decided by the last attacker in the room state.
+    {
+        let player = host
+            .logged_user
+            .as_ref()
+            .expect("Failed to get logged in player.");
+        let mut player = player.lock().await;
+        let state = player
+            .state
+            .as_mut()
+            .and_then(|s| s.state.as_mut())
+            .expect("Failed to get player state.");
+        state.is_alive = false;
+        state.last_attacker_id = guest_id.clone();
+    }
+    match wait_for_life_event(&host).await {
+        LifeEvent::Died(death) => {
+            assert_eq!(death.attacker_id, Some(guest_id));
+        }
+        event => panic!("Expected a death, got {:?}.", event),
+    }
+
+    set_player_state(&host, true, "").await;
+    assert_eq!(wait_for_life_event(&host).await, LifeEvent::Respawned);
+
+    host.leave_room().await;
+    guest.leave_room().await;
+}
@@ -0,0 +1,124 @@
+use demo_game_rs::game::shared::components::Bounds;
+use demo_game_rs::game::shared::structs::{OcclusionCuller, MAX_OCCLUSION_QUERIES};
+use demo_game_rs::game::ui::TweakRegistry;
+use glam::Vec3A;
+use slotmap::{DefaultKey, SlotMap};
+
+fn unit_box(center: Vec3A) -> Bounds {
+    Bounds::new(center - Vec3A::splat(0.5), center + Vec3A::splat(0.5))
+}
+
+fn spawn(count: usize) -> Vec<DefaultKey> {
+    let mut map = SlotMap::new();
+    (0..count).map(|_| map.insert(())).collect()
+}
+
+#[test]
+fn hides_entities_whose_boxes_drew_nothing() {
+    let entities = spawn(2);
+    let candidates = vec![
+        (entities[0], unit_box(Vec3A::new(0.0, 0.0, -10.0))),
+        (entities[1], unit_box(Vec3A::new(5.0, 0.0, -10.0))),
+    ];
+    let mut culler = OcclusionCuller::new(2);
+    assert!(culler.queue(&candidates, Vec3A::zero()).is_empty());
+
+    let queries = culler.take_queries(0);
+    assert_eq!(queries.len(), 2);
+    assert_eq!(queries[0].entity, entities[0]);
+    // 境界ボックスは少し広げて描く。
+    // Bounding boxes are drawn slightly grown.
+    assert!(queries[0].bounds.min.x < -0.5);
+    assert!(culler.take_queries(1).is_empty());
+
+    culler.apply_results(0, &[0, 12]);
+    assert!(culler.is_occluded(entities[0]));
+    assert!(!culler.is_occluded(entities[1]));
+    let occluded = culler.queue(&candidates, Vec3A::zero());
+    assert_eq!(occluded.len(), 1);
+    assert!(occluded.contains(&entities[0]));
+
+    // 隠れたエンティティも調べ続け、見えれば描く。
+    // Hidden entities are still tested, and drawn once they show up.
+    assert_eq!(culler.take_queries(1).len(), 2);
+    culler.apply_results(1, &[3, 12]);
+    assert_eq!(culler.get_occluded_count(), 0);
+}
+
+#[test]
+fn keeps_the_previous_state_without_results() {
+    let entities = spawn(1);
+    let candidates = vec![(entities[0], unit_box(Vec3A::new(0.0, 0.0, -10.0)))];
+    let mut culler = OcclusionCuller::new(1);
+    culler.queue(&candidates, Vec3A::zero());
+    culler.take_queries(0);
+    culler.apply_results(0, &[0]);
+    assert!(culler.is_occluded(entities[0]));
+
+    culler.queue(&candidates, Vec3A::zero());
+    culler.take_queries(0);
+    culler.apply_results(0, &[]);
+    assert!(culler.is_occluded(entities[0]));
+}
+
+#[test]
+fn draws_boxes_around_the_camera_without_testing() {
+    let entities = spawn(1);
+    let candidates = vec![(entities[0], unit_box(Vec3A::new(0.0, 0.0, -10.0)))];
+    let mut culler = OcclusionCuller::new(1);
+    culler.queue(&candidates, Vec3A::zero());
+    culler.take_queries(0);
+    culler.apply_results(0, &[0]);
+
+    let eye = Vec3A::new(0.0, 0.0, -10.8);
+    assert!(culler.queue(&candidates, eye).is_empty());
+    assert!(culler.take_queries(0).is_empty());
+}
+
+#[test]
+fn forgets_entities_that_leave_the_frustum() {
+    let entities = spawn(1);
+    let candidates = vec![(entities[0], unit_box(Vec3A::new(0.0, 0.0, -10.0)))];
+    let mut culler = OcclusionCuller::new(1);
+    culler.queue(&candidates, Vec3A::zero());
+    culler.take_queries(0);
+    culler.apply_results(0, &[0]);
+
+    assert!(culler.queue(&[], Vec3A::zero()).is_empty());
+    assert!(!culler.is_occluded(entities[0]));
+    assert!(culler.queue(&candidates, Vec3A::zero()).is_empty());
+}
+
+#[test]
+fn limits_the_queries_of_a_frame() {
+    let entities = spawn(MAX_OCCLUSION_QUERIES + 10);
+    let candidates = entities
+        .iter()
+        .map(|entity| (*entity, unit_box(Vec3A::new(0.0, 0.0, -10.0))))
+        .collect::<Vec<_>>();
+    let mut culler = OcclusionCuller::new(1);
+    culler.queue(&candidates, Vec3A::zero());
+    assert_eq!(culler.take_queries(0).len(), MAX_OCCLUSION_QUERIES);
+}
+
+#[test]
+fn disabling_draws_everything() {
+    let entities = spawn(1);
+    let candidates = vec![(entities[0], unit_box(Vec3A::new(0.0, 0.0, -10.0)))];
+    let mut tweaks = TweakRegistry::new();
+    let mut culler = OcclusionCuller::new(1);
+    culler.register_tweaks(&mut tweaks);
+    assert!(culler.is_enabled());
+    culler.queue(&candidates, Vec3A::zero());
+    culler.take_queries(0);
+    culler.apply_results(0, &[0]);
+
+    tweaks
+        .set_from_str("occlusion_culling", "off")
+        .expect("Failed to disable occlusion culling.");
+    culler.apply_tweaks(&tweaks);
+    assert!(!culler.is_enabled());
+    assert_eq!(culler.get_occluded_count(), 0);
+    assert!(culler.queue(&candidates, Vec3A::zero()).is_empty());
+    assert!(culler.take_queries(0).is_empty());
+}
@@ -0,0 +1,93 @@
+use demo_game_rs::game::shared::components::{ParticleEffect, ParticleEmitter};
+use demo_game_rs::game::shared::structs::{
+    ColorKey, ColorOverLife, DustPuff, ParticleRange, ParticleRangeAllocator,
+};
+use glam::Vec4;
+
+fn close(a: Vec4, b: Vec4) -> bool {
+    (a - b).abs().cmple(Vec4::splat(1e-5)).all()
+}
+
+#[test]
+fn color_over_life_interpolates_between_keys() {
+    let red = Vec4::new(1.0, 0.0, 0.0, 1.0);
+    let blue = Vec4::new(0.0, 0.0, 1.0, 0.0);
+    let colors = ColorOverLife::new(&[ColorKey::new(1.0, blue), ColorKey::new(0.0, red)]);
+    assert!(close(colors.evaluate(0.0), red));
+    assert!(close(colors.evaluate(0.5), Vec4::new(0.5, 0.0, 0.5, 0.5)));
+    assert!(close(colors.evaluate(1.0), blue));
+    assert!(close(colors.evaluate(2.0), blue));
+}
+
+#[test]
+fn color_over_life_pads_with_the_last_key() {
+    let white = Vec4::one();
+    let colors = ColorOverLife::constant(white);
+    assert!(colors.get_keys().iter().all(|key| key.color == white));
+    assert!(close(colors.evaluate(0.7), white));
+    let faded = ColorOverLife::fade_out(white);
+    assert!(faded.evaluate(1.0).w.abs() < 1e-5);
+}
+
+#[test]
+fn emitter_carries_fractional_spawns_over() {
+    let mut emitter = ParticleEmitter::new(10.0, 1.0, ColorOverLife::constant(Vec4::one()));
+    let spawned = (0..4).map(|_| emitter.update(0.25)).collect::<Vec<_>>();
+    assert_eq!(spawned, vec![2, 3, 2, 3]);
+    assert!(!emitter.is_finished());
+}
+
+#[test]
+fn burst_emitter_spawns_once_and_finishes_after_its_lifetime() {
+    let mut emitter = ParticleEmitter::from_effect(ParticleEffect::Sparks);
+    assert_eq!(emitter.update(0.016), emitter.burst);
+    assert_eq!(emitter.update(0.016), 0);
+    assert!(!emitter.is_finished());
+    emitter.update(emitter.lifetime);
+    assert!(emitter.is_finished());
+}
+
+#[test]
+fn dust_puffs_become_burst_emitters() {
+    let puff = DustPuff {
+        color: [0.5, 0.4, 0.3, 0.8],
+        count: 6,
+        speed: 1.0,
+        lifetime: 0.5,
+    };
+    let mut emitter = ParticleEmitter::from(puff);
+    assert_eq!(emitter.max_particles, 6);
+    assert_eq!(emitter.update(0.1), 6);
+    assert!(close(
+        emitter.color_over_life.evaluate(0.0),
+        Vec4::new(0.5, 0.4, 0.3, 0.8)
+    ));
+}
+
+#[test]
+fn range_allocator_reuses_and_merges_freed_ranges() {
+    let mut allocator = ParticleRangeAllocator::new(100);
+    let first = allocator.allocate(40).unwrap();
+    let second = allocator.allocate(40).unwrap();
+    assert_eq!((first.base, second.base), (0, 40));
+    assert!(allocator.allocate(30).is_none());
+
+    allocator.free(&first);
+    let third = allocator.allocate(30).unwrap();
+    assert_eq!(third.base, 0);
+    allocator.free(&third);
+    allocator.free(&second);
+    assert_eq!(allocator.get_free_count(), 100);
+    assert_eq!(allocator.allocate(100).unwrap().base, 0);
+}
+
+#[test]
+fn range_spawns_wrap_around() {
+    let mut range = ParticleRange::new(10, 8);
+    assert!(range.take_is_new());
+    assert!(!range.take_is_new());
+    assert_eq!(range.advance(5), (0, 5));
+    assert_eq!(range.advance(5), (5, 5));
+    assert_eq!(range.advance(20), (2, 8));
+    assert_eq!(range.advance(1), (2, 1));
+}
@@ -0,0 +1,355 @@
+use demo_game_rs::game::shared::structs::games::payload::{parse_terrain, PayloadError};
+use demo_game_rs::game::shared::structs::games::protocol::encode_packet;
+use demo_game_rs::game::shared::structs::games::snapshot::{
+    PlayerSnapshot, SnapshotAck, SnapshotDecoder, SnapshotEncoder,
+};
+use demo_game_rs::game::shared::structs::games::terrain_payload::{
+    generate_grid_indices, parse_terrain_payload, TerrainPayload, TERRAIN_PAYLOAD_VERSION,
+};
+use demo_game_rs::game::shared::structs::{Primitive, Vertex};
+use glam::{Vec2, Vec3A};
+use rand::{Rng, SeedableRng};
+
+fn create_terrain() -> Primitive {
+    Primitive {
+        vertices: vec![Vertex::default(); 3],
+        indices: vec![0, 1, 2],
+        texture_index: Some(5),
+        is_disposed: false,
+    }
+}
+
+#[test]
+fn accepts_valid_terrain() {
+    let bytes = serde_json::to_vec(&create_terrain()).expect("Failed to serialize terrain.");
+    let primitive = parse_terrain(&bytes).expect("Failed to parse terrain.");
+    assert_eq!(primitive.indices, vec![0, 1, 2]);
+    assert_eq!(primitive.texture_index, None);
+}
+
+#[test]
+fn rejects_invalid_terrain() {
+    let mut terrain = create_terrain();
+    terrain.indices = vec![0, 1, 3];
+    let bytes = serde_json::to_vec(&terrain).expect("Failed to serialize terrain.");
+    assert!(matches!(
+        parse_terrain(&bytes),
+        Err(PayloadError::OutOfRange { .. })
+    ));
+
+    let mut terrain = create_terrain();
+    terrain.indices = vec![0, 1];
+    let bytes = serde_json::to_vec(&terrain).expect("Failed to serialize terrain.");
+    assert!(matches!(
+        parse_terrain(&bytes),
+        Err(PayloadError::Malformed(_))
+    ));
+
+    assert!(matches!(
+        parse_terrain(br#"{"vertices": 12"#),
+        Err(PayloadError::Malformed(_))
+    ));
+}
+
+#[test]
+fn rejects_non_finite_vertices() {
+    let mut terrain = create_terrain();
+    terrain.vertices[1].position = Vec3A::new(0.0, f32::NAN, 0.0);
+    let bytes = serde_json::to_vec(&terrain).expect("Failed to serialize terrain.");
+    assert!(parse_terrain(&bytes).is_err());
+
+    let mut terrain = create_terrain();
+    terrain.vertices[2].normal = Vec3A::new(0.0, f32::INFINITY, 0.0);
+    let bytes = serde_json::to_vec(&terrain).expect("Failed to serialize terrain.");
+    assert!(parse_terrain(&bytes).is_err());
+}
+
+/// 起伏のある格子状の地形を作る。<br />
+/// Create a hilly grid terrain.
+fn create_grid_terrain(vertex_count: u32) -> Primitive {
+    let last = (vertex_count - 1) as f32;
+    let vertices = (0..vertex_count * vertex_count)
+        .map(|i| {
+            let (x, z) = ((i % vertex_count) as f32, (i / vertex_count) as f32);
+            Vertex {
+                position: Vec3A::new(
+                    x / last * 800.0,
+                    (x * 0.3).sin() * (z * 0.2).cos() * 75.0,
+                    z / last * 800.0,
+                ),
+                normal: Vec3A::new(0.0, 1.0, 0.0),
+                uv: Vec2::new(x / last, z / last),
+            }
+        })
+        .collect();
+    Primitive {
+        vertices,
+        indices: generate_grid_indices(vertex_count),
+        texture_index: None,
+        is_disposed: false,
+    }
+}
+
+#[test]
+fn reconstructs_terrain_from_payload() {
+    let terrain = create_grid_terrain(64);
+    let bytes = TerrainPayload::from_primitive(&terrain)
+        .expect("Failed to create terrain payload.")
+        .encode()
+        .expect("Failed to encode terrain payload.");
+    let json = serde_json::to_vec(&terrain).expect("Failed to serialize terrain.");
+    assert!(bytes.len() * 10 < json.len());
+
+    let primitive = parse_terrain_payload(&bytes).expect("Failed to parse terrain payload.");
+    assert_eq!(primitive.indices, terrain.indices);
+    for (received, expected) in primitive.vertices.iter().zip(terrain.vertices.iter()) {
+        assert!((received.position - expected.position).length() < 0.01);
+        assert!((received.uv - expected.uv).length() < 1e-5);
+    }
+
+    // 古いクライアントが送ったJSONもまだ受け付ける。
+    // JSON sent by older clients is still accepted.
+    assert!(parse_terrain_payload(&json).is_ok());
+}
+
+#[test]
+fn rejects_tampered_terrain_payload() {
+    let payload = TerrainPayload::from_primitive(&create_grid_terrain(16))
+        .expect("Failed to create terrain payload.");
+
+    let mut tampered = payload.clone();
+    tampered.heights[3] ^= 1;
+    assert!(matches!(
+        TerrainPayload::decode(
+            &tampered
+                .encode()
+                .expect("Failed to encode terrain payload.")
+        ),
+        Err(PayloadError::Malformed(_))
+    ));
+
+    let mut newer = payload.clone();
+    newer.version = TERRAIN_PAYLOAD_VERSION + 1;
+    assert!(matches!(
+        TerrainPayload::decode(&newer.encode().expect("Failed to encode terrain payload.")),
+        Err(PayloadError::VersionMismatch { .. })
+    ));
+
+    let mut truncated = payload;
+    truncated.heights.pop();
+    assert!(TerrainPayload::decode(
+        &truncated
+            .encode()
+            .expect("Failed to encode terrain payload.")
+    )
+    .is_err());
+
+    assert!(matches!(
+        TerrainPayload::decode(b"DGTR not deflated"),
+        Err(PayloadError::Malformed(_))
+    ));
+}
+
+#[test]
+fn decodes_round_trip_snapshot() {
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let player = PlayerSnapshot {
+        player_id: "player-0".to_string(),
+        max_hp: 100,
+        current_hp: 100,
+        ..Default::default()
+    };
+    let datagrams = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    let mut decoded = None;
+    for datagram in datagrams.iter() {
+        decoded = decoder
+            .decode(datagram)
+            .expect("Failed to decode snapshot.");
+    }
+    let (snapshot, _) = decoded.expect("Failed to get decoded snapshot.");
+    assert_eq!(snapshot.players, vec![player]);
+}
+
+#[test]
+fn sends_only_changed_fields_after_acknowledgement() {
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let mut player = PlayerSnapshot {
+        player_id: "player-0".to_string(),
+        max_hp: 100,
+        current_hp: 100,
+        ..Default::default()
+    };
+    player.world_matrix.position = [120_000, 0, -45_000];
+
+    let full = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    for datagram in full.iter() {
+        decoder
+            .decode(datagram)
+            .expect("Failed to decode snapshot.");
+    }
+    encoder.acknowledge(decoder.get_ack().expect("Failed to get acknowledgement."));
+
+    player.current_hp = 90;
+    player.world_matrix.position[0] += 15;
+    let delta = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    assert!(delta[0].len() < full[0].len());
+    let (snapshot, _) = decoder
+        .decode(&delta[0])
+        .expect("Failed to decode snapshot.")
+        .expect("Failed to get decoded snapshot.");
+    assert_eq!(snapshot.players, vec![player]);
+}
+
+#[test]
+fn acknowledges_earlier_snapshots_with_bits() {
+    let ack = SnapshotAck {
+        sequence: 10,
+        bits: 0b101,
+    };
+    assert!(ack.contains(10));
+    assert!(ack.contains(9));
+    assert!(!ack.contains(8));
+    assert!(ack.contains(7));
+    assert!(!ack.contains(11));
+
+    // 届かなかったスナップショットは確認されず、受け取った最新のものが基準になる。
+    // A snapshot that never arrived isn't acknowledged, and the newest received one becomes the base.
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let mut player = PlayerSnapshot {
+        player_id: "player-0".to_string(),
+        max_hp: 100,
+        ..Default::default()
+    };
+    for sequence in 1..=3 {
+        player.current_hp = sequence * 10;
+        let datagrams = encoder
+            .encode(true, vec![player.clone()], vec![], None)
+            .expect("Failed to encode snapshot.");
+        if sequence != 3 {
+            decoder
+                .decode(&datagrams[0])
+                .expect("Failed to decode snapshot.");
+        }
+    }
+    let ack = decoder.get_ack().expect("Failed to get acknowledgement.");
+    assert_eq!(
+        ack,
+        SnapshotAck {
+            sequence: 2,
+            bits: 0b1
+        }
+    );
+    encoder.acknowledge(ack);
+
+    player.current_hp = 40;
+    let datagrams = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    let (snapshot, _) = decoder
+        .decode(&datagrams[0])
+        .expect("Failed to decode snapshot.")
+        .expect("Failed to get decoded snapshot.");
+    assert_eq!(snapshot.players, vec![player]);
+}
+
+#[test]
+fn survives_random_datagrams() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(4526);
+    let mut decoder = SnapshotDecoder::new();
+    for _ in 0..10_000 {
+        let length = rng.gen_range(0..1300);
+        let mut payload = vec![0_u8; length];
+        rng.fill(&mut payload[..]);
+        // 半分はヘッダーを正しくして、フラグメントやbincodeの解析まで届かせる。
+        // Half of them get a valid header so they reach fragment and bincode parsing.
+        let datagram = if rng.gen_bool(0.5) {
+            encode_packet(&payload)
+        } else {
+            payload
+        };
+        let _ = decoder.decode(&datagram);
+    }
+}
+
+#[test]
+fn replicates_last_attacker_only_when_it_changes() {
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let mut player = PlayerSnapshot {
+        player_id: "player-0".to_string(),
+        max_hp: 100,
+        current_hp: 100,
+        is_alive: true,
+        ..Default::default()
+    };
+    let full = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    decoder
+        .decode(&full[0])
+        .expect("Failed to decode snapshot.");
+    encoder.acknowledge(decoder.get_ack().expect("Failed to get acknowledgement."));
+
+    player.current_hp = 0;
+    player.is_alive = false;
+    player.last_attacker_id = "player-1".to_string();
+    let delta = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    let (snapshot, _) = decoder
+        .decode(&delta[0])
+        .expect("Failed to decode snapshot.")
+        .expect("Failed to get decoded snapshot.");
+    assert_eq!(snapshot.players, vec![player.clone()]);
+    encoder.acknowledge(decoder.get_ack().expect("Failed to get acknowledgement."));
+
+    // 変わらなければ、攻撃したプレイヤーは基準のスナップショットから引き継がれる。
+    // Without a change, the attacker carries over from the base snapshot.
+    let unchanged = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    let (snapshot, _) = decoder
+        .decode(&unchanged[0])
+        .expect("Failed to decode snapshot.")
+        .expect("Failed to get decoded snapshot.");
+    assert_eq!(snapshot.players[0].last_attacker_id, "player-1");
+}
+
+#[test]
+fn replicates_mount_only_when_the_rider_gets_on_or_off() {
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let mut player = PlayerSnapshot {
+        player_id: "player-0".to_string(),
+        mount_id: "mount-1".to_string(),
+        ..Default::default()
+    };
+    let full = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    let (snapshot, _) = decoder
+        .decode(&full[0])
+        .expect("Failed to decode snapshot.")
+        .expect("Failed to get decoded snapshot.");
+    assert_eq!(snapshot.players[0].mount_id, "mount-1");
+    encoder.acknowledge(decoder.get_ack().expect("Failed to get acknowledgement."));
+
+    player.mount_id.clear();
+    let delta = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    let (snapshot, _) = decoder
+        .decode(&delta[0])
+        .expect("Failed to decode snapshot.")
+        .expect("Failed to get decoded snapshot.");
+    assert_eq!(snapshot.players, vec![player]);
+}
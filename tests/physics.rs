@@ -0,0 +1,106 @@
+use ash::vk::CommandBuffer;
+use demo_game_rs::game::graphics::vk::{Buffer, Graphics, Image};
+use demo_game_rs::game::shared::components::{PlayerController, Transform};
+use demo_game_rs::game::shared::structs::{Primitive, Ray, Vertex};
+use demo_game_rs::game::shared::systems::PhysicsSystem;
+use demo_game_rs::game::shared::World;
+use glam::{Vec2, Vec3A};
+
+type VkWorld = World<Graphics, Buffer, CommandBuffer, Image>;
+
+/// 20メートル四方で、X = 10に高さ3メートルの尾根がある地形。<br />
+/// A 20 meter square terrain with a 3 meter high ridge along X = 10.
+fn create_ridge() -> Primitive {
+    let vertices = (0..9)
+        .map(|i| {
+            let (x, z) = ((i % 3) as f32 * 10.0, (i / 3) as f32 * 10.0);
+            Vertex {
+                position: Vec3A::new(x, 3.0 - (x - 10.0).abs() * 0.3, z),
+                normal: Vec3A::new(0.0, 1.0, 0.0),
+                uv: Vec2::new(x / 20.0, z / 20.0),
+            }
+        })
+        .collect::<Vec<_>>();
+    Primitive {
+        vertices,
+        indices: vec![],
+        texture_index: None,
+        is_disposed: false,
+    }
+}
+
+#[test]
+fn samples_terrain_heights() {
+    let mut world = VkWorld::new();
+    let terrain = world.spawn("Terrain");
+    let mut physics = PhysicsSystem::new();
+    physics
+        .add_terrain_collider(terrain, &create_ridge(), Vec3A::new(0.0, 1.0, 0.0))
+        .expect("Failed to add terrain collider.");
+
+    let height = physics
+        .get_ground_height(15.0, 5.0)
+        .expect("Failed to get ground height.");
+    assert!((height - 2.5).abs() < 1e-4);
+    assert!(physics.get_ground_height(-1.0, 5.0).is_none());
+
+    let ray = Ray::new(Vec3A::new(10.0, 10.0, 10.0), Vec3A::new(0.0, -1.0, 0.0));
+    let (entity, distance) = physics
+        .raycast_terrain(&ray, 20.0)
+        .expect("Failed to hit the terrain.");
+    assert_eq!(entity, terrain);
+    assert!((distance - 6.0).abs() < 1e-3);
+    assert!(physics.raycast_terrain(&ray, 5.0).is_none());
+
+    // 尾根の上を通る線は遮られず、尾根の向こう側は麓から見えない。
+    // A line passing over the ridge isn't blocked, and the far side of the ridge can't be seen from its foot.
+    assert!(
+        !physics.is_line_blocked_by_terrain(Vec3A::new(5.0, 10.0, 5.0), Vec3A::new(15.0, 3.0, 5.0))
+    );
+    assert!(
+        physics.is_line_blocked_by_terrain(Vec3A::new(2.0, 2.0, 5.0), Vec3A::new(18.0, 2.0, 5.0))
+    );
+
+    physics.remove_body(terrain);
+    assert!(physics.get_ground_height(15.0, 5.0).is_none());
+}
+
+#[test]
+fn drops_the_character_onto_the_terrain_and_walks_to_the_target() {
+    let mut world = VkWorld::new();
+    let terrain = world.spawn("Terrain");
+    let player = world.spawn("Player");
+    let start = Vec3A::new(10.0, 5.0, 10.0);
+    world.insert(
+        player,
+        Transform {
+            position: start,
+            ..Default::default()
+        },
+    );
+    world.insert(
+        player,
+        PlayerController {
+            move_target: Some(Vec3A::new(15.0, 0.0, 10.0)),
+            ..Default::default()
+        },
+    );
+    let mut physics = PhysicsSystem::new();
+    physics
+        .add_terrain_collider(terrain, &create_ridge(), Vec3A::new(0.0, 1.0, 0.0))
+        .expect("Failed to add terrain collider.");
+    physics.add_character(player, start, 0.5, 0.5);
+    assert!(!physics.is_character_grounded());
+
+    for _ in 0..120 {
+        physics.step(&mut world);
+    }
+    assert!(physics.is_character_grounded());
+    let transform = world.get::<Transform>(player).unwrap();
+    assert!((transform.position - Vec3A::new(15.0, 2.5, 10.0)).length() < 1e-3);
+    assert!(world
+        .get::<PlayerController>(player)
+        .unwrap()
+        .move_target
+        .is_none());
+}
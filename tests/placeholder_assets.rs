@@ -0,0 +1,51 @@
+use demo_game_rs::game::shared::structs::{
+    create_checker_pixels, create_unit_cube, AssetKind, AssetWarnings,
+};
+
+#[test]
+fn checker_alternates_magenta_and_black() {
+    let pixels = create_checker_pixels(4, 2);
+    assert_eq!(pixels.len(), 4 * 4 * 4);
+    let get_pixel = |x: usize, y: usize| &pixels[(y * 4 + x) * 4..(y * 4 + x) * 4 + 4];
+    assert_eq!(get_pixel(0, 0), &[255, 0, 255, 255]);
+    assert_eq!(get_pixel(1, 1), &[255, 0, 255, 255]);
+    assert_eq!(get_pixel(2, 0), &[0, 0, 0, 255]);
+    assert_eq!(get_pixel(0, 2), &[0, 0, 0, 255]);
+    assert_eq!(get_pixel(3, 3), &[255, 0, 255, 255]);
+}
+
+#[test]
+fn unit_cube_has_flat_faces() {
+    let (vertices, indices) = create_unit_cube();
+    assert_eq!(vertices.len(), 24);
+    assert_eq!(indices.len(), 36);
+    assert!(indices.iter().all(|i| (*i as usize) < vertices.len()));
+    for vertex in vertices.iter() {
+        assert!((vertex.position.abs().max_element() - 0.5).abs() < 1e-6);
+        assert!((vertex.position.dot(vertex.normal) - 0.5).abs() < 1e-6);
+    }
+    for face in indices.chunks(6) {
+        let a = vertices[face[0] as usize].position;
+        let b = vertices[face[1] as usize].position;
+        let c = vertices[face[2] as usize].position;
+        let normal = vertices[face[0] as usize].normal;
+        assert!((b - a).cross(c - a).dot(normal) > 0.0);
+    }
+}
+
+#[test]
+fn warnings_are_listed_once_per_asset() {
+    let mut warnings = AssetWarnings::new();
+    assert!(warnings.is_empty());
+    assert!(warnings.report(AssetKind::Texture, "textures/grass.png", "Not found."));
+    assert!(!warnings.report(AssetKind::Texture, "textures/grass.png", "Not found."));
+    assert!(warnings.report(AssetKind::Shader, "textures/grass.png", "Not found."));
+    assert!(warnings.report(AssetKind::Model, "models/tree.gltf", "Invalid glTF."));
+    assert_eq!(warnings.get_warnings().len(), 3);
+    assert_eq!(warnings.get_warnings()[2].kind, AssetKind::Model);
+    assert_eq!(warnings.get_warnings()[2].message, "Invalid glTF.");
+    assert_eq!(AssetKind::Shader.to_string(), "Shader");
+
+    warnings.clear();
+    assert!(warnings.is_empty());
+}
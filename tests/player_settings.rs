@@ -0,0 +1,138 @@
+use demo_game_rs::game::shared::structs::games::player_settings::{
+    get_remote_nameplates, get_room_nameplates, pack_color, parse_color, sanitize_settings,
+    unpack_color, DEFAULT_NAMEPLATE_COLOR, MAX_BADGE_LENGTH, MAX_TITLE_LENGTH,
+};
+use demo_game_rs::game::shared::structs::Settings;
+use demo_game_rs::protos::grpc_service::game_state::{Player, PlayerSettings, RoomState};
+
+fn create_room(settings: &[(&str, PlayerSettings)]) -> RoomState {
+    let players = ["host", "guest", "late"]
+        .iter()
+        .map(|id| Player {
+            player_id: id.to_string(),
+            nickname: format!("{} name", id),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    RoomState {
+        current_players: players.len() as i32,
+        players,
+        player_settings: settings
+            .iter()
+            .map(|(id, s)| (id.to_string(), s.clone()))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn parses_and_packs_colors() {
+    assert_eq!(parse_color("#FF8000").unwrap(), [255, 128, 0, 255]);
+    assert_eq!(parse_color(" 00ff0080 ").unwrap(), [0, 255, 0, 128]);
+    assert!(parse_color("#FFF").is_err());
+    assert!(parse_color("#GG0000").is_err());
+    assert!(parse_color("#+F0000").is_err());
+
+    assert_eq!(pack_color([0x12, 0x34, 0x56, 0x78]), 0x1234_5678);
+    assert_eq!(unpack_color(0x1234_5678), [0x12, 0x34, 0x56, 0x78]);
+}
+
+#[test]
+fn sanitizes_settings_before_sharing_them() {
+    let mut settings = PlayerSettings {
+        nameplate_color: pack_color([255, 0, 0, 10]),
+        title: format!("  \u{7}{}  ", "a".repeat(40)),
+        badge: " GM ".to_string(),
+        ..Default::default()
+    };
+    sanitize_settings(&mut settings);
+    assert_eq!(settings.title.chars().count(), MAX_TITLE_LENGTH);
+    assert!(!settings.title.contains('\u{7}'));
+    assert_eq!(settings.badge, "GM");
+    assert_eq!(unpack_color(settings.nameplate_color), [255, 0, 0, 128]);
+
+    let mut settings = PlayerSettings {
+        badge: "あいうえおかきくけこ".to_string(),
+        ..Default::default()
+    };
+    sanitize_settings(&mut settings);
+    assert_eq!(settings.badge.chars().count(), MAX_BADGE_LENGTH);
+    // 色を選んでいなければ、既定の色のまま。
+    // Without a picked color, the default color stays.
+    assert_eq!(settings.nameplate_color, 0);
+}
+
+#[test]
+fn builds_nameplates_from_the_room_state() {
+    let room = create_room(&[(
+        "guest",
+        PlayerSettings {
+            nameplate_color: pack_color([0, 128, 255, 255]),
+            title: "Explorer".to_string(),
+            badge: "VIP".to_string(),
+            mute_chat: true,
+            ..Default::default()
+        },
+    )]);
+    let nameplates = get_room_nameplates(&room);
+    assert_eq!(nameplates.len(), 3);
+    assert_eq!(nameplates[0].label, "host name");
+    assert_eq!(nameplates[0].color, DEFAULT_NAMEPLATE_COLOR);
+    assert!(!nameplates[0].is_chat_muted);
+    assert_eq!(nameplates[1].label, "[VIP] guest name");
+    assert_eq!(nameplates[1].title, "Explorer");
+    assert_eq!(nameplates[1].color, [0, 128, 255, 255]);
+    assert!(nameplates[1].is_chat_muted);
+}
+
+#[test]
+fn leaves_out_hidden_and_local_nameplates() {
+    let room = create_room(&[(
+        "late",
+        PlayerSettings {
+            hide_nameplate: true,
+            ..Default::default()
+        },
+    )]);
+    let nameplates = get_remote_nameplates(&room, "host", false);
+    assert_eq!(
+        nameplates
+            .iter()
+            .map(|n| n.player_id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["guest"]
+    );
+    assert!(get_remote_nameplates(&room, "host", true).is_empty());
+}
+
+#[test]
+fn reads_player_settings_from_the_settings_file() {
+    let mut settings = Settings::parse(
+        "nameplate_color = \"#00FF00\"\ntitle = \"Ranger\"\nbadge = \"PRO\"\nmute_chat = true",
+    )
+    .expect("Failed to parse settings.");
+    assert!(settings.validate().is_ok());
+    let player_settings = settings.get_player_settings();
+    assert_eq!(
+        unpack_color(player_settings.nameplate_color),
+        [0, 255, 0, 255]
+    );
+    assert_eq!(player_settings.title, "Ranger");
+    assert_eq!(player_settings.badge, "PRO");
+    assert!(player_settings.mute_chat);
+    assert!(!player_settings.hide_nameplate);
+
+    settings
+        .apply_overrides(|key| match key {
+            "NAMEPLATE_COLOR" => Some("#0000FF80".to_string()),
+            "HIDE_NAMEPLATE" => Some("true".to_string()),
+            _ => None,
+        })
+        .expect("Failed to apply overrides.");
+    let player_settings = settings.get_player_settings();
+    assert_eq!(
+        unpack_color(player_settings.nameplate_color),
+        [0, 0, 255, 128]
+    );
+    assert!(player_settings.hide_nameplate);
+}
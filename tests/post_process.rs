@@ -0,0 +1,127 @@
+use demo_game_rs::game::shared::structs::{
+    PassOutput, PostProcessPushConstant, PostProcessSettings, PostProcessStep, RenderFeatures,
+    RenderGraph, RenderPassNode, HDR_SCENE_ATTACHMENT, POST_PROCESS_BLOOM, POST_PROCESS_TONEMAP,
+};
+use demo_game_rs::game::ui::{TweakRegistry, TweakValue};
+
+fn build_graph(settings: &PostProcessSettings) -> RenderGraph {
+    let mut graph = RenderGraph::new();
+    graph
+        .add_pass(RenderPassNode::new("Scene", PassOutput::Sampled).write(HDR_SCENE_ATTACHMENT))
+        .expect("The scene pass should register.");
+    for (_, node) in settings.get_render_pass_nodes().into_iter() {
+        graph
+            .add_pass(node)
+            .expect("Post-processing passes should register in order.");
+    }
+    graph
+}
+
+fn get_order(graph: &RenderGraph) -> Vec<&str> {
+    graph
+        .get_execution_order()
+        .map(|node| node.name.as_str())
+        .collect()
+}
+
+#[test]
+fn runs_every_step_by_default() {
+    let settings = PostProcessSettings::default();
+    let graph = build_graph(&settings);
+    assert_eq!(
+        get_order(&graph),
+        vec![
+            "Scene",
+            "Bloom Bright Pass",
+            "Bloom Blur Horizontal",
+            "Bloom Blur Vertical",
+            "Composite",
+            "FXAA",
+        ]
+    );
+    assert_eq!(settings.get_final_step(), PostProcessStep::Fxaa);
+    let presenting = graph
+        .get_execution_order()
+        .filter(|node| node.output == PassOutput::Present)
+        .map(|node| node.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(presenting, vec!["FXAA"]);
+}
+
+#[test]
+fn disabled_effects_drop_their_passes() {
+    let settings = PostProcessSettings {
+        is_bloom_enabled: false,
+        is_fxaa_enabled: false,
+        ..PostProcessSettings::default()
+    };
+    let graph = build_graph(&settings);
+    assert_eq!(get_order(&graph), vec!["Scene", "Composite"]);
+
+    // 合成だけが残り、スワップチェーンに書く。
+    // Only the composite remains, and it writes the swapchain.
+    let composite = graph.get_pass("Composite").unwrap();
+    assert_eq!(composite.output, PassOutput::Present);
+    assert_eq!(composite.reads, vec![HDR_SCENE_ATTACHMENT.to_string()]);
+    assert_eq!(graph.get_dependencies("Composite"), vec!["Scene"]);
+
+    // ポストエフェクトを使わないシーンでも同じパスになる。
+    // A scene without post effects ends up with the same passes.
+    let settings = PostProcessSettings::default().with_features(RenderFeatures::UI_LAYERS);
+    assert_eq!(settings.get_final_step(), PostProcessStep::Composite);
+    assert!(settings.is_tonemap_enabled);
+    assert_eq!(
+        get_order(&build_graph(&settings)),
+        vec!["Scene", "Composite"]
+    );
+}
+
+#[test]
+fn removes_passes_from_the_back() {
+    let settings = PostProcessSettings::default();
+    let mut graph = build_graph(&settings);
+    graph
+        .remove_pass("Bloom Bright Pass")
+        .expect_err("A pass that is still read shouldn't be removed.");
+    assert!(graph.get_pass("Bloom Bright Pass").is_some());
+
+    for step in settings.get_steps().into_iter().rev() {
+        graph
+            .remove_pass(step.get_pass_name())
+            .expect("Passes should be removable from the back.");
+    }
+    assert_eq!(get_order(&graph), vec!["Scene"]);
+}
+
+#[test]
+fn push_constants_follow_the_settings() {
+    let settings = PostProcessSettings {
+        is_tonemap_enabled: false,
+        exposure: 2.0,
+        ..PostProcessSettings::default()
+    };
+    let blur = PostProcessPushConstant::new(&settings, PostProcessStep::BlurVertical, 640, 360);
+    assert_eq!(blur.direction, [0.0, 1.0]);
+    assert_eq!(blur.texel_size, [1.0 / 640.0, 1.0 / 360.0]);
+
+    let composite = PostProcessPushConstant::new(&settings, PostProcessStep::Composite, 1280, 720);
+    assert_eq!(composite.direction, [0.0, 0.0]);
+    assert_eq!(composite.exposure, 2.0);
+    assert_eq!(composite.flags & POST_PROCESS_TONEMAP, 0);
+    assert_eq!(composite.flags & POST_PROCESS_BLOOM, POST_PROCESS_BLOOM);
+    assert_eq!(composite.boundary_warning, 0.0);
+    assert_eq!(composite.with_boundary_warning(3.0).boundary_warning, 1.0);
+}
+
+#[test]
+fn toggles_effects_through_tweaks() {
+    let mut tweaks = TweakRegistry::new();
+    let mut settings = PostProcessSettings::default();
+    settings.register_tweaks(&mut tweaks);
+    assert!(settings.is_fxaa_enabled);
+
+    tweaks.set("Post Process/FXAA", TweakValue::Bool(false));
+    settings.apply_tweaks(&tweaks);
+    assert!(!settings.is_fxaa_enabled);
+    assert_eq!(settings.get_final_step(), PostProcessStep::Composite);
+}
@@ -0,0 +1,100 @@
+use demo_game_rs::game::shared::structs::{get_join_link, parse_join_link, PresenceActivity};
+use demo_game_rs::game::shared::systems::PresenceSystem;
+use demo_game_rs::game::shared::traits::PresenceProvider;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// 知らされた活動を記録し、用意した参加リンクを返すサービス。<br />
+/// Service recording the reported activities and returning prepared join links.
+struct RecordingProvider {
+    activities: Rc<RefCell<Vec<PresenceActivity>>>,
+    join_links: Rc<RefCell<Vec<String>>>,
+}
+
+impl PresenceProvider for RecordingProvider {
+    fn get_name(&self) -> &str {
+        "Recording"
+    }
+
+    fn set_activity(&mut self, activity: &PresenceActivity) -> anyhow::Result<()> {
+        self.activities.borrow_mut().push(activity.clone());
+        Ok(())
+    }
+
+    fn poll_join_links(&mut self) -> Vec<String> {
+        self.join_links.borrow_mut().drain(..).collect()
+    }
+}
+
+fn room(players: i32) -> PresenceActivity {
+    PresenceActivity::InRoom {
+        room_id: "AbC123x".to_string(),
+        room_name: "Friends".to_string(),
+        players,
+        max_players: 8,
+    }
+}
+
+#[test]
+fn describes_the_activity() {
+    assert_eq!(PresenceActivity::InLobby.get_details(), "In Lobby");
+    assert_eq!(PresenceActivity::InLobby.get_state(), None);
+
+    let activity = room(3);
+    assert_eq!(activity.get_details(), "In Lobby");
+    assert_eq!(activity.get_state(), Some("3/8 players".to_string()));
+    assert_eq!(
+        activity.get_join_link(),
+        Some("demo-game://join/AbC123x".to_string())
+    );
+
+    let activity = PresenceActivity::InMatch {
+        room_id: "AbC123x".to_string(),
+        map: "Friends".to_string(),
+        players: 3,
+        max_players: 8,
+    };
+    assert_eq!(activity.get_details(), "In Match on Friends");
+    assert_eq!(activity.get_join_link(), None);
+
+    // 満員の部屋には参加できない。
+    // Full rooms can't be joined.
+    assert_eq!(room(8).get_join_link(), None);
+}
+
+#[test]
+fn parses_join_links() {
+    assert_eq!(
+        parse_join_link(&get_join_link("AbC123x")),
+        Some("AbC123x".to_string())
+    );
+    assert_eq!(parse_join_link("demo-game://join/"), None);
+    assert_eq!(parse_join_link("demo-game://join/../etc"), None);
+    assert_eq!(parse_join_link("https://example.com/AbC123x"), None);
+}
+
+#[test]
+fn reports_only_changed_activities_and_keeps_join_requests() {
+    let activities = Rc::new(RefCell::new(vec![]));
+    let join_links = Rc::new(RefCell::new(vec![]));
+    let provider = RecordingProvider {
+        activities: activities.clone(),
+        join_links: join_links.clone(),
+    };
+    let mut presence_system = PresenceSystem::with_providers(vec![Box::new(provider)]);
+
+    presence_system.update(PresenceActivity::InLobby);
+    presence_system.update(PresenceActivity::InLobby);
+    presence_system.update(room(3));
+    assert_eq!(
+        *activities.borrow(),
+        vec![PresenceActivity::InLobby, room(3)]
+    );
+
+    join_links
+        .borrow_mut()
+        .extend(vec!["not a link".to_string(), get_join_link("Zz9")]);
+    presence_system.update(room(3));
+    assert_eq!(presence_system.take_join_request(), Some("Zz9".to_string()));
+    assert_eq!(presence_system.take_join_request(), None);
+}
@@ -0,0 +1,53 @@
+use demo_game_rs::game::shared::structs::games::payload::PayloadError;
+use demo_game_rs::game::shared::structs::games::protocol::{
+    decode_packet, encode_packet, ProtocolCompatibility, PACKET_HEADER_SIZE, PACKET_MAGIC,
+    PROTOCOL_VERSION,
+};
+
+#[test]
+fn round_trips_payloads_through_the_header() {
+    let packet = encode_packet(b"hello");
+    assert_eq!(packet.len(), PACKET_HEADER_SIZE + 5);
+    assert_eq!(packet[0..2], PACKET_MAGIC);
+    assert_eq!(decode_packet(&packet), Ok(&b"hello"[..]));
+    assert_eq!(decode_packet(&encode_packet(&[])), Ok(&[][..]));
+}
+
+#[test]
+fn rejects_packets_without_a_valid_header() {
+    assert_eq!(decode_packet(&[]), Err(PayloadError::InvalidHeader));
+    // ヘッダーより短いパケットは、マジックナンバーが正しくても受け付けない。
+    // Packets shorter than the header aren't accepted even with the right magic number.
+    assert_eq!(
+        decode_packet(&encode_packet(&[])[..PACKET_HEADER_SIZE - 1]),
+        Err(PayloadError::InvalidHeader)
+    );
+    let mut packet = encode_packet(b"hello");
+    packet[0] = b'X';
+    assert_eq!(decode_packet(&packet), Err(PayloadError::InvalidHeader));
+}
+
+#[test]
+fn rejects_packets_from_other_protocol_versions() {
+    let mut packet = encode_packet(b"hello");
+    packet[2..PACKET_HEADER_SIZE].copy_from_slice(&(PROTOCOL_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        decode_packet(&packet),
+        Err(PayloadError::VersionMismatch {
+            expected: PROTOCOL_VERSION,
+            actual: PROTOCOL_VERSION + 1,
+        })
+    );
+}
+
+#[test]
+fn requires_updates_only_for_incompatible_servers() {
+    assert!(!ProtocolCompatibility::Unchecked.is_update_required());
+    assert!(!ProtocolCompatibility::Compatible.is_update_required());
+    let compatibility = ProtocolCompatibility::UpdateRequired {
+        server_protocol_version: PROTOCOL_VERSION + 1,
+        server_build_version: "9.9.9".to_string(),
+        message: "Please update the client.".to_string(),
+    };
+    assert!(compatibility.is_update_required());
+}
@@ -0,0 +1,104 @@
+use demo_game_rs::game::shared::components::Bounds;
+use demo_game_rs::game::shared::structs::{Primitive, Ray, Vertex};
+use demo_game_rs::game::shared::Camera;
+use glam::{Mat4, Vec3, Vec3A};
+
+fn unit_box() -> Bounds {
+    Bounds::new(Vec3A::splat(-1.0), Vec3A::one())
+}
+
+#[test]
+fn normalizes_the_direction() {
+    let ray = Ray::new(Vec3A::new(1.0, 2.0, 3.0), Vec3A::new(0.0, 0.0, 10.0));
+    assert_eq!(ray.direction, Vec3A::new(0.0, 0.0, 1.0));
+    assert_eq!(ray.get_point(2.0), Vec3A::new(1.0, 2.0, 5.0));
+}
+
+#[test]
+fn intersects_bounding_boxes() {
+    let ray = Ray::new(Vec3A::new(-5.0, 0.0, 0.0), Vec3A::new(1.0, 0.0, 0.0));
+    let distance = ray
+        .intersect_bounds(&unit_box())
+        .expect("Failed to hit the box.");
+    assert!((distance - 4.0).abs() < 1e-6);
+
+    let ray = Ray::new(Vec3A::new(-5.0, 2.0, 0.0), Vec3A::new(1.0, 0.0, 0.0));
+    assert!(ray.intersect_bounds(&unit_box()).is_none());
+    // 箱の後ろを向いている光線は当たらず、箱の中から出る光線は距離0で当たる。
+    // A ray facing away from the box misses, and a ray starting inside it hits at distance zero.
+    let ray = Ray::new(Vec3A::new(-5.0, 0.0, 0.0), Vec3A::new(-1.0, 0.0, 0.0));
+    assert!(ray.intersect_bounds(&unit_box()).is_none());
+    let ray = Ray::new(Vec3A::zero(), Vec3A::new(0.0, 1.0, 0.0));
+    assert_eq!(ray.intersect_bounds(&unit_box()), Some(0.0));
+}
+
+#[test]
+fn intersects_triangles() {
+    let (a, b, c) = (
+        Vec3A::new(0.0, 0.0, 0.0),
+        Vec3A::new(2.0, 0.0, 0.0),
+        Vec3A::new(0.0, 0.0, 2.0),
+    );
+    let ray = Ray::new(Vec3A::new(0.5, 3.0, 0.5), Vec3A::new(0.0, -1.0, 0.0));
+    let distance = ray
+        .intersect_triangle(a, b, c)
+        .expect("Failed to hit the triangle.");
+    assert!((distance - 3.0).abs() < 1e-6);
+
+    let outside = Ray::new(Vec3A::new(1.5, 3.0, 1.5), Vec3A::new(0.0, -1.0, 0.0));
+    assert!(outside.intersect_triangle(a, b, c).is_none());
+    let behind = Ray::new(Vec3A::new(0.5, 3.0, 0.5), Vec3A::new(0.0, 1.0, 0.0));
+    assert!(behind.intersect_triangle(a, b, c).is_none());
+    // 三角形と平行な光線は当たらない。
+    // A ray parallel to the triangle never hits.
+    let parallel = Ray::new(Vec3A::new(-1.0, 0.0, 0.5), Vec3A::new(1.0, 0.0, 0.0));
+    assert!(parallel.intersect_triangle(a, b, c).is_none());
+}
+
+#[test]
+fn builds_bounds_from_vertices() {
+    assert!(Bounds::from_primitives(&[]).is_none());
+    let primitive = Primitive {
+        vertices: [
+            Vec3A::new(1.0, -2.0, 0.0),
+            Vec3A::new(-1.0, 3.0, 0.5),
+            Vec3A::new(0.0, 0.0, -4.0),
+        ]
+        .iter()
+        .map(|position| Vertex {
+            position: *position,
+            ..Default::default()
+        })
+        .collect(),
+        indices: vec![0, 1, 2],
+        texture_index: None,
+        is_disposed: false,
+    };
+    let bounds = Bounds::from_primitives(&[primitive]).expect("Failed to build bounds.");
+    assert_eq!(bounds.min, Vec3A::new(-1.0, -2.0, -4.0));
+    assert_eq!(bounds.max, Vec3A::new(1.0, 3.0, 0.5));
+    assert_eq!(bounds.get_center(), Vec3A::new(0.0, 0.5, -1.75));
+}
+
+#[test]
+fn transforms_bounds_by_their_corners() {
+    let matrix = Mat4::from_translation(Vec3::new(10.0, 0.0, 0.0))
+        * Mat4::from_rotation_y(std::f32::consts::FRAC_PI_4);
+    let bounds = unit_box().transform(matrix);
+    // 45度回すと、箱は対角線の分だけ広がる。
+    // Rotating by 45 degrees widens the box to its diagonal.
+    let half_diagonal = 2.0f32.sqrt();
+    assert!((bounds.min - Vec3A::new(10.0 - half_diagonal, -1.0, -half_diagonal)).length() < 1e-5);
+    assert!((bounds.max - Vec3A::new(10.0 + half_diagonal, 1.0, half_diagonal)).length() < 1e-5);
+}
+
+#[test]
+fn casts_rays_through_the_center_of_the_screen_at_the_target() {
+    let camera = Camera::new(1280.0, 720.0);
+    let ray = camera.screen_point_to_ray(640.0, 360.0);
+    let expected = (camera.target - camera.position).normalize();
+    assert!((ray.direction - expected).length() < 1e-4);
+    // 光線はニアクリップ面から始まる。
+    // The ray starts at the near plane.
+    assert!((ray.origin - camera.position).length() < 0.2);
+}
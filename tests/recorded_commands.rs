@@ -0,0 +1,66 @@
+use demo_game_rs::game::shared::structs::{RecordKey, RecordedCommands};
+
+#[test]
+fn keys_change_with_generation_and_recorded_values() {
+    let key = RecordKey::new(1)
+        .with(42_u64)
+        .with((3_usize, 7_u32))
+        .finish();
+    assert_eq!(
+        key,
+        RecordKey::new(1)
+            .with(42_u64)
+            .with((3_usize, 7_u32))
+            .finish()
+    );
+    assert_ne!(
+        key,
+        RecordKey::new(2)
+            .with(42_u64)
+            .with((3_usize, 7_u32))
+            .finish()
+    );
+    assert_ne!(
+        key,
+        RecordKey::new(1)
+            .with(43_u64)
+            .with((3_usize, 7_u32))
+            .finish()
+    );
+    assert_ne!(
+        key,
+        RecordKey::new(1)
+            .with(42_u64)
+            .with((3_usize, 8_u32))
+            .finish()
+    );
+}
+
+#[test]
+fn tracks_recorded_keys_per_frame() {
+    let mut recorded_commands = RecordedCommands::new();
+    assert!(!recorded_commands.is_up_to_date(0, 10));
+
+    recorded_commands.set_recorded(2, 10);
+    assert!(recorded_commands.is_up_to_date(2, 10));
+    assert!(!recorded_commands.is_up_to_date(2, 11));
+    assert!(!recorded_commands.is_up_to_date(0, 10));
+    assert!(!recorded_commands.is_up_to_date(1, 10));
+
+    recorded_commands.set_recorded(0, 10);
+    recorded_commands.set_recorded(2, 11);
+    assert!(recorded_commands.is_up_to_date(0, 10));
+    assert!(recorded_commands.is_up_to_date(2, 11));
+    assert!(!recorded_commands.is_up_to_date(2, 10));
+}
+
+#[test]
+fn invalidating_records_every_frame_again() {
+    let mut recorded_commands = RecordedCommands::new();
+    recorded_commands.set_recorded(0, 10);
+    recorded_commands.set_recorded(1, 10);
+    recorded_commands.invalidate();
+    assert!(!recorded_commands.is_up_to_date(0, 10));
+    assert!(!recorded_commands.is_up_to_date(1, 10));
+    assert_eq!(recorded_commands, RecordedCommands::default());
+}
@@ -0,0 +1,29 @@
+use demo_game_rs::game::shared::structs::RenderFeatures;
+
+#[test]
+fn combines_and_removes_features() {
+    let features = RenderFeatures::POST_EFFECTS | RenderFeatures::UI_LAYERS;
+    assert!(features.contains(RenderFeatures::UI_LAYERS));
+    assert!(features.contains(RenderFeatures::NONE));
+    assert!(!features.contains(RenderFeatures::SHADOWS | RenderFeatures::UI_LAYERS));
+    assert_eq!(
+        features.without(RenderFeatures::POST_EFFECTS),
+        RenderFeatures::UI_LAYERS
+    );
+
+    let mut all = RenderFeatures::NONE;
+    for feature in [
+        RenderFeatures::SHADOWS,
+        RenderFeatures::WATER,
+        RenderFeatures::VEGETATION,
+        RenderFeatures::POST_EFFECTS,
+        RenderFeatures::UI_LAYERS,
+    ]
+    .iter()
+    {
+        assert!(!all.contains(*feature));
+        all |= *feature;
+    }
+    assert_eq!(all, RenderFeatures::ALL);
+    assert_eq!(RenderFeatures::default(), RenderFeatures::ALL);
+}
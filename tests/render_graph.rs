@@ -0,0 +1,161 @@
+use demo_game_rs::game::shared::structs::{
+    PassOutput, RenderFeatures, RenderGraph, RenderPassNode,
+};
+
+fn get_order(graph: &RenderGraph) -> Vec<&str> {
+    graph
+        .get_execution_order()
+        .map(|node| node.name.as_str())
+        .collect()
+}
+
+#[test]
+fn orders_passes_by_attachment_dependencies() {
+    let mut graph = RenderGraph::new();
+    graph
+        .add_pass(
+            RenderPassNode::new("Post Process", PassOutput::Present)
+                .read("Scene")
+                .write("Backbuffer"),
+        )
+        .expect_err("Reading an attachment nobody writes should fail.");
+
+    graph
+        .add_pass(RenderPassNode::new("Shadow", PassOutput::Sampled).write("Shadow Map"))
+        .expect("Failed to add shadow pass.");
+    graph
+        .add_pass(
+            RenderPassNode::new("Scene", PassOutput::Sampled)
+                .read("Shadow Map")
+                .write("Scene"),
+        )
+        .expect("Failed to add scene pass.");
+    graph
+        .add_pass(
+            RenderPassNode::new("Post Process", PassOutput::Present)
+                .read("Scene")
+                .write("Backbuffer"),
+        )
+        .expect("Failed to add post-process pass.");
+    assert_eq!(get_order(&graph), vec!["Shadow", "Scene", "Post Process"]);
+    assert_eq!(graph.get_dependencies("Post Process"), vec!["Scene"]);
+    assert!(graph
+        .add_pass(RenderPassNode::new("Shadow", PassOutput::Sampled))
+        .is_err());
+}
+
+#[test]
+fn keeps_registration_order_for_independent_passes() {
+    let mut graph = RenderGraph::new();
+    graph
+        .add_pass(
+            RenderPassNode::new("Water", PassOutput::Present)
+                .read("Reflection")
+                .write("Backbuffer")
+                .enabled(false),
+        )
+        .expect("Failed to add water pass.");
+    graph
+        .add_pass(RenderPassNode::new("Primary", PassOutput::Present).write("Backbuffer"))
+        .expect("Failed to add primary pass.");
+    graph
+        .add_pass(RenderPassNode::new("Reflection", PassOutput::Sampled).write("Reflection"))
+        .expect("Failed to add reflection pass.");
+
+    // 無効なパスは順番に含まれず、有効にすると依存先の後に並ぶ。
+    // Disabled passes aren't ordered, and once enabled they come after their dependencies.
+    assert_eq!(get_order(&graph), vec!["Primary", "Reflection"]);
+    graph
+        .set_enabled("Water", true)
+        .expect("Failed to enable water pass.");
+    assert_eq!(get_order(&graph), vec!["Primary", "Reflection", "Water"]);
+
+    assert!(graph.set_enabled("Reflection", false).is_err());
+    assert!(graph
+        .get_pass("Reflection")
+        .map(|node| node.is_enabled)
+        .unwrap_or(false));
+}
+
+#[test]
+fn rejects_cycles() {
+    let mut graph = RenderGraph::new();
+    graph
+        .add_pass(RenderPassNode::new("A", PassOutput::Sampled).write("A"))
+        .expect("Failed to add pass A.");
+    graph
+        .add_pass(
+            RenderPassNode::new("B", PassOutput::Sampled)
+                .read("A")
+                .write("B"),
+        )
+        .expect("Failed to add pass B.");
+    graph
+        .add_pass(
+            RenderPassNode::new("C", PassOutput::Sampled)
+                .read("B")
+                .write("A"),
+        )
+        .expect_err("B and C depend on each other.");
+    assert_eq!(get_order(&graph), vec!["A", "B"]);
+
+    // 読むパスが残っている間は、書くパスを無効にできない。
+    // A writing pass can't be disabled while a pass still reads from it.
+    assert!(graph.set_enabled("A", false).is_err());
+    assert!(graph.set_enabled("B", false).is_ok());
+    assert_eq!(get_order(&graph), vec!["A"]);
+}
+
+#[test]
+fn skips_passes_of_unused_features() {
+    let mut graph = RenderGraph::new();
+    graph
+        .add_pass(
+            RenderPassNode::new("Shadow", PassOutput::Sampled)
+                .write("Shadow Map")
+                .requires(RenderFeatures::SHADOWS),
+        )
+        .expect("Failed to add shadow pass.");
+    graph
+        .add_pass(
+            RenderPassNode::new("Reflection", PassOutput::Sampled)
+                .write("Reflection")
+                .requires(RenderFeatures::WATER),
+        )
+        .expect("Failed to add reflection pass.");
+    graph
+        .add_pass(RenderPassNode::new("Scene", PassOutput::Present).write("Backbuffer"))
+        .expect("Failed to add scene pass.");
+    assert_eq!(get_order(&graph), vec!["Shadow", "Reflection", "Scene"]);
+
+    graph
+        .set_features(RenderFeatures::WATER | RenderFeatures::UI_LAYERS)
+        .expect("Failed to set features.");
+    assert_eq!(get_order(&graph), vec!["Reflection", "Scene"]);
+    graph
+        .set_features(RenderFeatures::NONE)
+        .expect("Failed to set features.");
+    assert_eq!(get_order(&graph), vec!["Scene"]);
+}
+
+#[test]
+fn keeps_features_that_would_break_the_graph() {
+    let mut graph = RenderGraph::new();
+    graph
+        .add_pass(
+            RenderPassNode::new("Shadow", PassOutput::Sampled)
+                .write("Shadow Map")
+                .requires(RenderFeatures::SHADOWS),
+        )
+        .expect("Failed to add shadow pass.");
+    graph
+        .add_pass(
+            RenderPassNode::new("Scene", PassOutput::Present)
+                .read("Shadow Map")
+                .write("Backbuffer"),
+        )
+        .expect("Failed to add scene pass.");
+    assert!(graph.set_features(RenderFeatures::NONE).is_err());
+    assert_eq!(graph.get_features(), RenderFeatures::ALL);
+    assert_eq!(get_order(&graph), vec!["Shadow", "Scene"]);
+}
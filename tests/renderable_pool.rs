@@ -0,0 +1,88 @@
+use ash::vk::CommandBuffer;
+use demo_game_rs::game::graphics::vk::{Buffer, Graphics, Image};
+use demo_game_rs::game::shared::structs::{PositionInfo, RenderablePool, SkinnedModel};
+use demo_game_rs::game::shared::traits::Renderable;
+use demo_game_rs::game::shared::World;
+use demo_game_rs::game::LockableRenderable;
+use glam::{Mat4, Vec3, Vec3A, Vec4};
+use parking_lot::Mutex;
+use slotmap::DefaultKey;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Weak};
+
+type VkRenderable = LockableRenderable<Graphics, Buffer, CommandBuffer, Image>;
+
+fn create_instance(entity: DefaultKey) -> VkRenderable {
+    let (document, buffers, _) =
+        gltf::import("./models/cesiumMan/CesiumMan.glb").expect("Failed to import glTF model.");
+    let model = SkinnedModel::create_model(
+        "./models/cesiumMan/CesiumMan.glb",
+        Arc::new(AtomicUsize::new(0)),
+        0,
+        document,
+        buffers,
+        vec![],
+        Weak::new(),
+        PositionInfo::default(),
+        Vec4::one(),
+        0,
+        entity,
+    );
+    Arc::new(Mutex::new(Box::new(model)))
+}
+
+#[test]
+fn hands_out_instances_until_the_pool_is_empty() {
+    let mut world = World::<Graphics, Buffer, CommandBuffer, Image>::new();
+    let entities = vec![world.spawn("Bullet"), world.spawn("Bullet")];
+    let mut pool = RenderablePool::new(
+        "CesiumMan.glb",
+        entities.iter().map(|e| create_instance(*e)).collect(),
+    );
+    assert_eq!(pool.get_available_count(), 2);
+
+    let position_info = PositionInfo {
+        position: Vec3A::new(1.0, 2.0, 3.0),
+        scale: Vec3A::one(),
+        rotation: Vec3A::zero(),
+    };
+    let (first, renderable) = pool
+        .acquire(position_info)
+        .expect("Failed to acquire an instance.");
+    assert!(entities.contains(&first));
+    assert!(pool.is_active(first));
+    // 取り出したインスタンスのワールド行列は、すぐに描画できるように更新されている。
+    // The world matrix of an acquired instance is updated so it can be drawn right away.
+    assert_eq!(
+        renderable.lock().get_model_metadata().world_matrix,
+        Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+    );
+
+    let (second, _) = pool
+        .acquire(PositionInfo::default())
+        .expect("Failed to acquire an instance.");
+    assert_ne!(first, second);
+    assert!(pool.acquire(PositionInfo::default()).is_none());
+    assert_eq!(pool.get_active_count(), 2);
+    assert_eq!(pool.get_available_count(), 0);
+}
+
+#[test]
+fn reuses_released_instances() {
+    let mut world = World::<Graphics, Buffer, CommandBuffer, Image>::new();
+    let entity = world.spawn("Bullet");
+    let mut pool = RenderablePool::new("CesiumMan.glb", vec![create_instance(entity)]);
+    let (acquired, _) = pool
+        .acquire(PositionInfo::default())
+        .expect("Failed to acquire an instance.");
+    assert!(pool.release(acquired).is_some());
+    assert!(!pool.is_active(acquired));
+    // 使用中でないエンティティは戻せない。
+    // Entities that aren't active can't be released.
+    assert!(pool.release(acquired).is_none());
+
+    let (reacquired, _) = pool
+        .acquire(PositionInfo::default())
+        .expect("Failed to acquire the released instance.");
+    assert_eq!(reacquired, acquired);
+}
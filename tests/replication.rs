@@ -0,0 +1,172 @@
+use demo_game_rs::game::shared::structs::games::payload::PayloadError;
+use demo_game_rs::game::shared::structs::games::replication::{
+    EntityRegistry, EntitySnapshot, ProxyTracker, ReplicationEvent, ReplicationQueues,
+    MAX_OWNED_ENTITIES,
+};
+use demo_game_rs::game::shared::structs::games::snapshot::{SnapshotDecoder, SnapshotEncoder};
+
+fn create_entity(entity_id: &str, owner_id: &str) -> EntitySnapshot {
+    EntitySnapshot {
+        entity_id: entity_id.to_string(),
+        owner_id: owner_id.to_string(),
+        model: "tank.gltf".to_string(),
+        current_hp: 100,
+        max_hp: 100,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn only_owners_update_their_entities() {
+    let mut registry = EntityRegistry::new();
+    assert_eq!(
+        registry.accept("alice", vec![create_entity("alice:1", "alice")]),
+        0
+    );
+    // 他のプレイヤーのエンティティを名乗ったり、奪ったりはできない。
+    // Players can't claim to be another owner, or take over an entity of someone else.
+    let mut stolen = create_entity("alice:1", "bob");
+    stolen.current_hp = 0;
+    let rejected = registry.accept(
+        "bob",
+        vec![
+            stolen,
+            create_entity("bob:1", "alice"),
+            create_entity("bob:2", "bob"),
+        ],
+    );
+    assert_eq!(rejected, 2);
+    let entities = registry.get_entities();
+    assert_eq!(entities.len(), 2);
+    assert_eq!(entities[0].owner_id, "alice");
+    assert_eq!(entities[0].current_hp, 100);
+    assert_eq!(entities[1].entity_id, "bob:2");
+
+    // 送られなかったエンティティは消え、いなくなった持ち主のものも消える。
+    // Entities that weren't sent are despawned, as are the ones of an owner who left.
+    registry.accept("alice", vec![]);
+    assert_eq!(registry.len(), 1);
+    registry.remove_owner("bob");
+    assert!(registry.is_empty());
+}
+
+#[test]
+fn limits_entities_per_owner() {
+    let mut registry = EntityRegistry::new();
+    let entities = (0..MAX_OWNED_ENTITIES + 3)
+        .map(|i| create_entity(&format!("alice:{}", i), "alice"))
+        .collect();
+    assert_eq!(registry.accept("alice", entities), 3);
+    assert_eq!(registry.len(), MAX_OWNED_ENTITIES);
+}
+
+#[test]
+fn tracks_proxies_of_remote_entities() {
+    let mut tracker = ProxyTracker::new();
+    let mine = create_entity("local:1", "local");
+    let mut theirs = create_entity("remote:1", "remote");
+    let events = tracker.update(&[mine.clone(), theirs.clone()], "local");
+    assert_eq!(events, vec![ReplicationEvent::Spawned(theirs.clone())]);
+    assert!(tracker
+        .update(&[mine.clone(), theirs.clone()], "local")
+        .is_empty());
+
+    theirs.world_matrix.position[0] = 1500;
+    let events = tracker.update(&[theirs.clone()], "local");
+    assert_eq!(events, vec![ReplicationEvent::Updated(theirs.clone())]);
+
+    theirs.model = "barrel.gltf".to_string();
+    let events = tracker.update(&[theirs.clone()], "local");
+    assert_eq!(
+        events,
+        vec![
+            ReplicationEvent::Despawned("remote:1".to_string()),
+            ReplicationEvent::Spawned(theirs.clone()),
+        ]
+    );
+
+    let events = tracker.update(&[], "local");
+    assert_eq!(
+        events,
+        vec![ReplicationEvent::Despawned("remote:1".to_string())]
+    );
+    tracker.update(&[theirs], "local");
+    assert_eq!(
+        tracker.clear(),
+        vec![ReplicationEvent::Despawned("remote:1".to_string())]
+    );
+}
+
+#[test]
+fn queues_despawns_of_proxies_when_cleared() {
+    let mut queues = ReplicationQueues::new();
+    queues.set_owned_entities(vec![create_entity("local:1", "local")]);
+    let theirs = create_entity("remote:1", "remote");
+    queues.push_entities(&[theirs.clone()], "local");
+    queues.push_entities(&[theirs.clone()], "local");
+    assert_eq!(
+        queues.take_events(),
+        vec![ReplicationEvent::Spawned(theirs)]
+    );
+    assert!(queues.take_events().is_empty());
+
+    // 部屋を出たら、シーンに残っている代理は消える。
+    // After leaving the room, the proxies still in the scene are despawned.
+    queues.clear();
+    assert!(queues.get_owned_entities().is_empty());
+    assert_eq!(
+        queues.take_events(),
+        vec![ReplicationEvent::Despawned("remote:1".to_string())]
+    );
+}
+
+#[test]
+fn sends_spawns_and_despawns_in_snapshots() {
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let first = create_entity("remote:1", "remote");
+    let mut second = create_entity("remote:2", "remote");
+    let datagrams = encoder
+        .encode(true, vec![], vec![first.clone(), second.clone()], None)
+        .expect("Failed to encode snapshot.");
+    let (snapshot, _) = decoder
+        .decode(&datagrams[0])
+        .expect("Failed to decode snapshot.")
+        .expect("Failed to get decoded snapshot.");
+    assert_eq!(snapshot.entities, vec![first.clone(), second.clone()]);
+    encoder.acknowledge(decoder.get_ack().expect("Failed to get acknowledgement."));
+
+    // 確認された基準からは、消えたエンティティと変わったエンティティだけが送られる。
+    // Against an acknowledged base, only despawned and changed entities are sent.
+    second.current_hp = 40;
+    let datagrams = encoder
+        .encode(true, vec![], vec![second.clone()], None)
+        .expect("Failed to encode snapshot.");
+    let (snapshot, _) = decoder
+        .decode(&datagrams[0])
+        .expect("Failed to decode snapshot.")
+        .expect("Failed to get decoded snapshot.");
+    assert_eq!(snapshot.entities, vec![second]);
+}
+
+#[test]
+fn rejects_entities_with_paths_as_models() {
+    let mut entity = create_entity("remote:1", "remote");
+    assert!(entity.validate().is_ok());
+    for model in ["../session.json", "/etc/passwd", "tank\\tank.gltf", ""].iter() {
+        entity.model = model.to_string();
+        assert!(matches!(entity.validate(), Err(PayloadError::Malformed(_))));
+    }
+    entity.model = "tank.gltf".to_string();
+    entity.current_hp = -1;
+    assert!(entity.validate().is_err());
+
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let mut invalid = create_entity("remote:1", "remote");
+    invalid.model = "../../secret".to_string();
+    let datagrams = encoder
+        .encode(true, vec![], vec![invalid], None)
+        .expect("Failed to encode snapshot.");
+    assert!(decoder.decode(&datagrams[0]).is_err());
+}
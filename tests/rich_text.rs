@@ -0,0 +1,93 @@
+use demo_game_rs::game::ui::rich_text::{parse_message, parse_rich_text, SYSTEM_COLOR};
+use demo_game_rs::game::ui::{LinkTarget, RichSpan};
+
+fn text(text: &str, color: Option<[u8; 4]>) -> RichSpan {
+    RichSpan::Text {
+        text: text.to_string(),
+        color,
+    }
+}
+
+#[test]
+fn parses_color_codes_and_resets() {
+    let spans = parse_rich_text("a{#ff8000}b{/}c");
+    assert_eq!(
+        spans,
+        vec![
+            text("a", None),
+            text("b", Some([255, 128, 0, 255])),
+            text("c", None),
+        ]
+    );
+
+    // 不正な色の指定はそのまま文字として残る。
+    // Malformed color codes are kept as plain text.
+    assert_eq!(parse_rich_text("{#zzzzzz}"), vec![text("{#zzzzzz}", None)]);
+}
+
+#[test]
+fn parses_room_and_player_links() {
+    let spans = parse_rich_text("join [room:42] with @alice.");
+    assert_eq!(
+        spans,
+        vec![
+            text("join ", None),
+            RichSpan::Link {
+                text: "#42".to_string(),
+                target: LinkTarget::Room("42".to_string()),
+            },
+            text(" with ", None),
+            RichSpan::Link {
+                text: "@alice".to_string(),
+                target: LinkTarget::Player("alice".to_string()),
+            },
+            text(".", None),
+        ]
+    );
+
+    // 単語の途中の`@`はリンクにならない。
+    // An `@` in the middle of a word isn't a link.
+    assert_eq!(
+        parse_rich_text("mail@example"),
+        vec![text("mail@example", None)]
+    );
+}
+
+#[test]
+fn parses_known_emojis_only() {
+    let spans = parse_rich_text("hi :smile: :unknown:");
+    assert_eq!(
+        spans,
+        vec![
+            text("hi ", None),
+            RichSpan::Emoji(0),
+            text(" :unknown:", None),
+        ]
+    );
+}
+
+#[test]
+fn colors_system_messages_and_links_authors() {
+    let spans = parse_message("System", "Room {#00ff00}opened{/}.");
+    assert_eq!(
+        spans,
+        vec![
+            text("Room ", Some(SYSTEM_COLOR)),
+            text("opened", Some([0, 255, 0, 255])),
+            text(".", Some(SYSTEM_COLOR)),
+        ]
+    );
+
+    let spans = parse_message("bob", "hello");
+    assert_eq!(
+        spans,
+        vec![
+            RichSpan::Link {
+                text: "bob".to_string(),
+                target: LinkTarget::Player("bob".to_string()),
+            },
+            text(":", None),
+            text("hello", None),
+        ]
+    );
+}
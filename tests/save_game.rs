@@ -0,0 +1,134 @@
+use demo_game_rs::game::shared::structs::games::payload::PayloadError;
+use demo_game_rs::game::shared::structs::games::terrain_patch::{HeightRun, TerrainPatch};
+use demo_game_rs::game::shared::structs::{
+    SaveGame, SavedCamera, SavedProgress, SavedTransform, SAVE_VERSION,
+};
+use glam::Vec3A;
+use slotmap::{DefaultKey, SlotMap};
+
+fn saved(name: &str, x: f32) -> SavedTransform {
+    SavedTransform {
+        name: name.to_string(),
+        position: Vec3A::new(x, 0.0, 0.0),
+        rotation: Vec3A::zero(),
+        scale: Vec3A::one(),
+    }
+}
+
+fn create_save() -> SaveGame {
+    let mut save = SaveGame::new("Terrain");
+    save.transforms = vec![saved("Tree", 1.0), saved("Tree", 2.0), saved("Rock", 3.0)];
+    save.camera = Some(SavedCamera {
+        position: Vec3A::new(0.0, 10.0, -10.0),
+        target: Vec3A::zero(),
+    });
+    save.progress = Some(SavedProgress {
+        current_hp: 40,
+        max_hp: 100,
+        current_sp: 10,
+        max_sp: 50,
+    });
+    save
+}
+
+#[test]
+fn round_trips_through_bytes() {
+    let save = create_save();
+    let bytes = save.encode().unwrap();
+    assert_eq!(&bytes[..4], b"DGSV");
+    assert_eq!(SaveGame::decode(&bytes).unwrap(), save);
+}
+
+#[test]
+fn keeps_terrain_patches() {
+    let mut save = create_save();
+    assert_eq!(save.get_terrain_patches(), Ok(vec![]));
+    let patches = vec![TerrainPatch {
+        grid_x: 0,
+        grid_z: 0,
+        vertex_count: 8,
+        runs: vec![HeightRun {
+            start: 9,
+            heights: vec![-1.0, -1.5],
+        }],
+    }];
+    save.set_terrain_patches(&patches).unwrap();
+    let loaded = SaveGame::decode(&save.encode().unwrap()).unwrap();
+    assert_eq!(loaded.get_terrain_patches(), Ok(patches));
+}
+
+#[test]
+fn loads_saves_from_before_terrain_patches() {
+    // バージョン1のセーブには地形のパッチが無い。
+    // Saves of version 1 have no terrain patches.
+    let save = create_save();
+    let mut bytes = save.encode().unwrap();
+    bytes.truncate(bytes.len() - 8);
+    bytes[4..8].copy_from_slice(&1u32.to_le_bytes());
+    assert_eq!(SaveGame::decode(&bytes).unwrap(), save);
+}
+
+#[test]
+fn rejects_other_files_and_newer_versions() {
+    let mut bytes = create_save().encode().unwrap();
+    assert!(matches!(
+        SaveGame::decode(&bytes[..6]),
+        Err(PayloadError::InvalidHeader)
+    ));
+
+    bytes[4..8].copy_from_slice(&(SAVE_VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        SaveGame::decode(&bytes),
+        Err(PayloadError::VersionMismatch { .. })
+    ));
+
+    bytes[0] = b'X';
+    assert!(matches!(
+        SaveGame::decode(&bytes),
+        Err(PayloadError::InvalidHeader)
+    ));
+}
+
+#[test]
+fn rejects_non_finite_values() {
+    let mut save = create_save();
+    save.transforms[1].position.y = f32::NAN;
+    let bytes = save.encode().unwrap();
+    assert!(matches!(
+        SaveGame::decode(&bytes),
+        Err(PayloadError::NonFinite(_))
+    ));
+}
+
+#[test]
+fn matches_entities_with_the_same_name_in_order() {
+    let save = create_save();
+    let mut keys: SlotMap<DefaultKey, ()> = SlotMap::new();
+    let first_tree = keys.insert(());
+    let barrel = keys.insert(());
+    let second_tree = keys.insert(());
+    let third_tree = keys.insert(());
+    let matches = save.match_transforms(vec![
+        (first_tree, "Tree"),
+        (barrel, "Barrel"),
+        (second_tree, "Tree"),
+        (third_tree, "Tree"),
+    ]);
+    // 保存されていない樽と三本目の木はそのまま。
+    // The barrel and the third tree weren't saved, so they're left alone.
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].0, first_tree);
+    assert!((matches[0].1.position.x - 1.0).abs() < 1e-5);
+    assert_eq!(matches[1].0, second_tree);
+    assert!((matches[1].1.position.x - 2.0).abs() < 1e-5);
+}
+
+#[test]
+fn defeated_players_load_with_full_health() {
+    let mut progress = create_save().progress.unwrap();
+    assert_eq!(progress.get_loaded_hp(), 40);
+    progress.current_hp = 0;
+    assert_eq!(progress.get_loaded_hp(), 100);
+    progress.current_hp = 150;
+    assert_eq!(progress.get_loaded_hp(), 100);
+}
@@ -0,0 +1,33 @@
+use ash::vk::{Extent2D, Offset3D};
+use demo_game_rs::game::graphics::vk::secondary_window::get_letterbox_region;
+
+fn extent(width: u32, height: u32) -> Extent2D {
+    Extent2D { width, height }
+}
+
+fn corners(region: [Offset3D; 2]) -> [(i32, i32, i32); 2] {
+    [
+        (region[0].x, region[0].y, region[0].z),
+        (region[1].x, region[1].y, region[1].z),
+    ]
+}
+
+#[test]
+fn fills_targets_with_the_same_aspect_ratio() {
+    let region = get_letterbox_region(extent(1280, 720), extent(640, 360));
+    assert_eq!(corners(region), [(0, 0, 0), (640, 360, 1)]);
+}
+
+#[test]
+fn centers_the_source_in_wider_and_taller_targets() {
+    let region = get_letterbox_region(extent(1280, 720), extent(800, 360));
+    assert_eq!(corners(region), [(80, 0, 0), (720, 360, 1)]);
+    let region = get_letterbox_region(extent(1280, 720), extent(640, 480));
+    assert_eq!(corners(region), [(0, 60, 0), (640, 420, 1)]);
+}
+
+#[test]
+fn ignores_empty_sources() {
+    let region = get_letterbox_region(extent(0, 720), extent(640, 360));
+    assert_eq!(corners(region), [(0, 0, 0), (0, 0, 0)]);
+}
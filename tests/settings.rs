@@ -0,0 +1,181 @@
+use demo_game_rs::game::shared::structs::{Settings, SETTINGS_PATH};
+use log::LevelFilter;
+use std::collections::HashMap;
+
+fn overrides(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars = vars
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<HashMap<_, _>>();
+    move |key| vars.get(key).cloned()
+}
+
+#[test]
+fn uses_defaults_for_missing_items() {
+    let settings = Settings::parse("").expect("Failed to parse settings.");
+    assert_eq!(settings, Settings::default());
+    assert!(settings.validate().is_ok());
+
+    let settings = Settings::parse("api = \"DX12\"\ninflight_buffer_count = 3\nratio = 2.5")
+        .expect("Failed to parse settings.");
+    assert_eq!(settings.api, "DX12");
+    assert_eq!(settings.inflight_buffer_count, 3);
+    assert!((settings.ratio - 2.5).abs() < 1e-5);
+    assert_eq!(settings.log, "info");
+    assert!(Settings::parse("ratio = \"wide\"").is_err());
+}
+
+#[test]
+fn reads_the_shipped_settings_file() {
+    let content = std::fs::read_to_string(SETTINGS_PATH).expect("Failed to read settings.toml.");
+    let settings = Settings::parse(&content).expect("Failed to parse settings.toml.");
+    assert!(settings.validate().is_ok());
+}
+
+#[test]
+fn overrides_settings_with_environment_variables() {
+    let mut settings = Settings::default();
+    settings
+        .apply_overrides(overrides(&[
+            ("LOG", "Debug"),
+            ("API", "dx12"),
+            ("DEBUG", "true"),
+            ("INFLIGHT_BUFFER_COUNT", " 3 "),
+            ("LIGHT_X", "-50.5"),
+            ("DAY_LENGTH", "120"),
+        ]))
+        .expect("Failed to apply overrides.");
+    assert_eq!(settings.log, "debug");
+    assert_eq!(settings.api, "DX12");
+    assert!(settings.debug);
+    assert_eq!(settings.inflight_buffer_count, 3);
+    assert!((settings.light_x + 50.5).abs() < 1e-5);
+    assert!((settings.day_length - 120.0).abs() < 1e-5);
+    // 環境変数がない項目はそのまま。
+    // Items without an environment variable are left as they are.
+    assert!((settings.light_z - 10000.0).abs() < 1e-5);
+    assert_eq!(settings.get_log_level(), LevelFilter::Debug);
+}
+
+#[test]
+fn overrides_ui_fonts_and_glyph_ranges() {
+    let mut settings = Settings::default();
+    settings
+        .apply_overrides(overrides(&[
+            ("UI_FONT", "resource/Roboto-Regular.ttf"),
+            ("UI_FALLBACK_FONTS", "resource/HiraMaruProN-W4.otf, ,"),
+            ("UI_GLYPH_RANGES", "3040-30FF, 0x4E00-0x4E0F,FF01"),
+        ]))
+        .expect("Failed to apply overrides.");
+    assert_eq!(settings.ui_font, "resource/Roboto-Regular.ttf");
+    assert_eq!(
+        settings.ui_fallback_fonts,
+        vec!["resource/HiraMaruProN-W4.otf".to_string()]
+    );
+    assert_eq!(
+        settings.ui_glyph_ranges,
+        vec![(0x3040, 0x30FF), (0x4E00, 0x4E0F), (0xFF01, 0xFF01)]
+    );
+    assert!(settings.validate().is_ok());
+
+    let error = settings
+        .apply_overrides(overrides(&[("UI_GLYPH_RANGES", "3040-kana")]))
+        .expect_err("An unreadable glyph range was accepted.");
+    assert!(error.to_string().contains("UI_GLYPH_RANGES"));
+}
+
+#[test]
+fn overrides_connection_and_scene_settings() {
+    let mut settings = Settings::default();
+    assert!(!settings.offline);
+    assert!(settings.server_endpoint.is_empty());
+    assert_eq!(settings.get_water(), None);
+    settings
+        .apply_overrides(overrides(&[
+            ("OFFLINE", "true"),
+            ("SERVER_ENDPOINT", "http://127.0.0.1:50051"),
+            ("UDP_BINDPOINT", "0.0.0.0:50060"),
+            ("LOGIN_PASS", " pass "),
+            ("PRESENT_MODE", "fifo"),
+            ("MAX_FPS", "144"),
+            ("WATER_POS", "10"),
+            ("WATER_HEIGHT", "-2.5"),
+            ("WATER_SCALE", "30"),
+            ("SCENE_FILE", "./cutscenes/custom.json"),
+            ("DISCORD_CLIENT_ID", "1234567890"),
+            ("HOST_GRPC_PORT", "50071"),
+        ]))
+        .expect("Failed to apply overrides.");
+    assert!(settings.offline);
+    assert_eq!(settings.server_endpoint, "http://127.0.0.1:50051");
+    assert_eq!(settings.udp_bind_point, "0.0.0.0:50060");
+    // パスワードは空白も含めてそのまま使う。
+    // Passwords are used as they are, spaces included.
+    assert_eq!(settings.login_pass, " pass ");
+    assert_eq!(settings.max_fps, 144);
+    assert_eq!(settings.get_water(), Some((10.0, -2.5, 30.0)));
+    assert_eq!(settings.scene_file, "./cutscenes/custom.json");
+    assert_eq!(settings.discord_client_id, 1_234_567_890);
+    assert_eq!(settings.host_grpc_port, 50071);
+    assert_eq!(settings.host_udp_port, Settings::default().host_udp_port);
+    assert!(settings.validate().is_ok());
+
+    assert!(settings
+        .apply_overrides(overrides(&[("HOST_UDP_PORT", "70000")]))
+        .is_err());
+}
+
+#[test]
+fn rejects_unreadable_overrides() {
+    let mut settings = Settings::default();
+    let error = settings
+        .apply_overrides(overrides(&[("INFLIGHT_BUFFER_COUNT", "two")]))
+        .expect_err("An unreadable override was accepted.");
+    assert!(error.to_string().contains("INFLIGHT_BUFFER_COUNT"));
+    assert!(settings
+        .apply_overrides(overrides(&[("DEBUG", "1")]))
+        .is_err());
+}
+
+#[test]
+fn rejects_unusable_values() {
+    let invalid = |change: fn(&mut Settings)| {
+        let mut settings = Settings::default();
+        change(&mut settings);
+        settings.validate().is_err()
+    };
+    assert!(invalid(|s| s.api = "METAL".to_string()));
+    assert!(invalid(|s| s.log = "loud".to_string()));
+    assert!(invalid(|s| s.inflight_buffer_count = 0));
+    assert!(invalid(|s| s.inflight_buffer_count = 5));
+    assert!(invalid(|s| s.ratio = 0.0));
+    assert!(invalid(|s| s.light_x = f32::NAN));
+    assert!(invalid(|s| s.day_length = 0.0));
+    assert!(invalid(|s| s.day_length = f32::INFINITY));
+    assert!(invalid(|s| s.nameplate_color = "red".to_string()));
+    assert!(invalid(|s| s.ui_font = String::new()));
+    assert!(invalid(|s| s.ui_glyph_ranges = vec![(0x30FF, 0x3040)]));
+    assert!(invalid(|s| s.ui_glyph_ranges = vec![(0x3040, 0x110000)]));
+    assert!(invalid(|s| s.present_mode = "VSYNC".to_string()));
+    assert!(invalid(|s| s.window_mode = "Maximized".to_string()));
+    assert!(invalid(|s| s.world_border = -1.0));
+    assert!(invalid(|s| s.water_scale = -1.0));
+    assert!(invalid(|s| s.water_height = f32::NAN));
+    assert!(invalid(|s| s.host_name = String::new()));
+    assert!(invalid(|s| s.host_name = "a".repeat(65)));
+
+    let mut settings = Settings::default();
+    settings.api = "METAL".to_string();
+    let error = settings.validate().expect_err("METAL was accepted.");
+    assert!(error.to_string().contains("VULKAN, DX12"));
+}
+
+#[test]
+fn converts_log_levels() {
+    let mut settings = Settings::default();
+    assert_eq!(settings.get_log_level(), LevelFilter::Info);
+    settings.log = "off".to_string();
+    assert_eq!(settings.get_log_level(), LevelFilter::Off);
+    settings.log = "trace".to_string();
+    assert_eq!(settings.get_log_level(), LevelFilter::Trace);
+}
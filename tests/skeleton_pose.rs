@@ -0,0 +1,68 @@
+use demo_game_rs::game::shared::structs::{DebugDraw, Joint, SkeletonPose};
+use glam::{Mat4, Quat, Vec3, Vec3A};
+
+fn joint(name: &str, index: usize, bind_position: Vec3, children: Vec<Joint>) -> Joint {
+    Joint {
+        name: name.to_string(),
+        node_index: index,
+        index,
+        children,
+        inverse_bind_matrices: Mat4::from_translation(-bind_position),
+        translation: Vec3A::from(bind_position),
+        rotation: Quat::identity(),
+        scale: Vec3A::one(),
+    }
+}
+
+fn skeleton() -> Joint {
+    let hand = joint("hand", 2, Vec3::new(0.0, 2.0, 0.0), vec![]);
+    let arm = joint("arm", 1, Vec3::new(0.0, 1.0, 0.0), vec![hand]);
+    joint("root", 0, Vec3::zero(), vec![arm])
+}
+
+#[test]
+fn bind_pose_puts_joints_at_their_bind_positions() {
+    let root = skeleton();
+    let matrices = [Mat4::identity(); 3];
+    let pose = SkeletonPose::new(&root, &matrices, Mat4::from_translation(Vec3::unit_x()));
+    let names = pose
+        .joints
+        .iter()
+        .map(|joint| joint.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["root", "arm", "hand"]);
+    assert_eq!(pose.joints[0].parent, None);
+    assert_eq!(pose.joints[2].parent, Some(1));
+    assert!((pose.joints[2].position - Vec3A::new(1.0, 2.0, 0.0)).length() < 1e-5);
+}
+
+#[test]
+fn animated_joints_follow_their_matrices() {
+    let root = skeleton();
+    let mut matrices = [Mat4::identity(); 3];
+    // 腕から先を上に一つ持ち上げる。
+    // Lift the arm and everything below it up by one.
+    matrices[1] = Mat4::from_translation(Vec3::unit_y());
+    matrices[2] = Mat4::from_translation(Vec3::unit_y());
+    let pose = SkeletonPose::new(&root, &matrices, Mat4::identity());
+    assert!((pose.joints[1].position - Vec3A::new(0.0, 2.0, 0.0)).length() < 1e-5);
+    assert!((pose.joints[2].position - Vec3A::new(0.0, 3.0, 0.0)).length() < 1e-5);
+}
+
+#[test]
+fn joints_without_matrices_are_skipped() {
+    let root = skeleton();
+    let pose = SkeletonPose::new(&root, &[Mat4::identity(); 2], Mat4::identity());
+    assert_eq!(pose.joints.len(), 2);
+}
+
+#[test]
+fn draws_bones_and_joint_markers() {
+    let root = skeleton();
+    let pose = SkeletonPose::new(&root, &[Mat4::identity(); 3], Mat4::identity());
+    let mut debug_draw = DebugDraw::new();
+    pose.draw(&mut debug_draw);
+    // 骨が二本と、関節ごとに三本の線の十字。
+    // Two bones, and a cross of three lines for each joint.
+    assert_eq!(debug_draw.get_line_vertices().len(), (2 + 3 * 3) * 2);
+}
@@ -0,0 +1,83 @@
+use ash::vk::CommandBuffer;
+use demo_game_rs::game::graphics::vk::{Buffer, Graphics, Image};
+use demo_game_rs::game::shared::components::{Bounds, Transform};
+use demo_game_rs::game::shared::structs::{Frustum, PositionInfo, SkinnedModel};
+use demo_game_rs::game::shared::traits::Renderable;
+use demo_game_rs::game::shared::World;
+use glam::{Mat4, Vec3, Vec3A, Vec4};
+use slotmap::DefaultKey;
+use std::collections::HashSet;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Weak};
+
+type VkSkinnedModel = SkinnedModel<Graphics, Buffer, CommandBuffer, Image>;
+
+fn load_skinned_model(entity: DefaultKey) -> VkSkinnedModel {
+    let (document, buffers, _) =
+        gltf::import("./models/cesiumMan/CesiumMan.glb").expect("Failed to import glTF model.");
+    SkinnedModel::create_model(
+        "./models/cesiumMan/CesiumMan.glb",
+        Arc::new(AtomicUsize::new(0)),
+        0,
+        document,
+        buffers,
+        vec![],
+        Weak::new(),
+        PositionInfo::default(),
+        Vec4::one(),
+        0,
+        entity,
+    )
+}
+
+#[test]
+fn skinned_models_belong_to_their_entity() {
+    let mut world = World::<Graphics, Buffer, CommandBuffer, Image>::new();
+    let entity = world.spawn("CesiumMan");
+    let mut model = load_skinned_model(entity);
+    assert_eq!(model.get_entity(), entity);
+
+    let other = world.spawn("Tank");
+    model.set_entity(other);
+    assert_eq!(model.get_entity(), other);
+}
+
+#[test]
+fn culls_skinned_models_outside_the_frustum() {
+    let mut world = World::<Graphics, Buffer, CommandBuffer, Image>::new();
+    let ahead = world.spawn("Ahead");
+    let behind = world.spawn("Behind");
+    let unbounded = world.spawn("Unbounded");
+    for (entity, z) in [(ahead, -10.0), (behind, 10.0)].iter() {
+        world.insert(*entity, Transform::default());
+        let center = Vec3A::new(0.0, 0.0, *z);
+        world.insert(
+            *entity,
+            Bounds::new(center - Vec3A::splat(0.5), center + Vec3A::splat(0.5)),
+        );
+    }
+    world.update_spatial_index();
+    let projection = Mat4::perspective_rh(90.0_f32.to_radians(), 1.0, 0.1, 100.0);
+    let view = Mat4::look_at_rh(Vec3::zero(), -Vec3::unit_z(), Vec3::unit_y());
+    let visible_entities = world
+        .get_spatial_index()
+        .query_frustum(&Frustum::new(projection * view))
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    // シーンは描画するモデルをエンティティで選ぶので、スキンモデルも自分のエンティティを返さなければならない。
+    // The scene picks models to draw by their entity, so skinned models must return theirs as well.
+    let models = vec![
+        load_skinned_model(ahead),
+        load_skinned_model(behind),
+        load_skinned_model(unbounded),
+    ];
+    let visible = models
+        .iter()
+        .filter(|m| !world.is_culled(m.get_entity(), &visible_entities))
+        .map(|m| m.get_entity())
+        .collect::<Vec<_>>();
+    // 境界ボックスを持たないエンティティは常に描画される。
+    // Entities without bounding boxes are always drawn.
+    assert_eq!(visible, vec![ahead, unbounded]);
+}
@@ -0,0 +1,238 @@
+use demo_game_rs::game::shared::structs::games::payload::PayloadError;
+use demo_game_rs::game::shared::structs::games::replication::EntitySnapshot;
+use demo_game_rs::game::shared::structs::games::snapshot::{
+    PlayerSnapshot, QuantizedWorldMatrix, Snapshot, SnapshotDecoder, SnapshotEncoder,
+};
+use demo_game_rs::game::shared::structs::games::{PlayerUdp, RoomStateUdp, WorldMatrixUdp};
+use glam::Quat;
+
+fn create_player(player_id: &str) -> PlayerSnapshot {
+    PlayerSnapshot {
+        player_id: player_id.to_string(),
+        is_in_game: true,
+        current_hp: 100,
+        max_hp: 100,
+        current_sp: 50,
+        max_sp: 50,
+        is_alive: true,
+        ..Default::default()
+    }
+}
+
+fn create_entity(entity_id: &str, owner_id: &str) -> EntitySnapshot {
+    EntitySnapshot {
+        entity_id: entity_id.to_string(),
+        owner_id: owner_id.to_string(),
+        model: "crate.gltf".to_string(),
+        current_hp: 10,
+        max_hp: 10,
+        ..Default::default()
+    }
+}
+
+/// 全てのデータグラムをデコードし、最後に揃ったスナップショットを返す。<br />
+/// Decode all datagrams and return the last snapshot that was completed.
+fn decode_all(decoder: &mut SnapshotDecoder, datagrams: &[Vec<u8>]) -> Option<Snapshot> {
+    let mut decoded = None;
+    for datagram in datagrams.iter() {
+        if let Some((snapshot, _)) = decoder
+            .decode(datagram)
+            .expect("Failed to decode snapshot.")
+        {
+            decoded = Some(snapshot);
+        }
+    }
+    decoded
+}
+
+/// 送って確認応答を返し、次のスナップショットが差分になるようにする。<br />
+/// Send and acknowledge, so that the next snapshot becomes a delta.
+fn send_and_acknowledge(
+    encoder: &mut SnapshotEncoder,
+    decoder: &mut SnapshotDecoder,
+    players: Vec<PlayerSnapshot>,
+    entities: Vec<EntitySnapshot>,
+) -> Snapshot {
+    let datagrams = encoder
+        .encode(true, players, entities, None)
+        .expect("Failed to encode snapshot.");
+    let snapshot = decode_all(decoder, &datagrams).expect("Failed to get decoded snapshot.");
+    encoder.acknowledge(decoder.get_ack().expect("Failed to get acknowledgement."));
+    snapshot
+}
+
+#[test]
+fn quantizes_world_matrices_within_precision() {
+    let rotation = Quat::from_rotation_ypr(1.2, -0.4, 0.3);
+    let world_matrix = WorldMatrixUdp {
+        position: vec![12.3456, -0.0004, 987.6543],
+        scale: vec![1.0, 2.5, 0.333],
+        rotation: vec![rotation.x, rotation.y, rotation.z, rotation.w],
+    };
+    let restored = WorldMatrixUdp::from(QuantizedWorldMatrix::from(&world_matrix));
+
+    // 位置は1ミリメートル、大きさは0.01単位に丸められる。
+    // Positions are rounded to a millimeter and scales to a hundredth.
+    for (original, restored) in world_matrix.position.iter().zip(restored.position.iter()) {
+        assert!((original - restored).abs() <= 0.0005);
+    }
+    for (original, restored) in world_matrix.scale.iter().zip(restored.scale.iter()) {
+        assert!((original - restored).abs() <= 0.005);
+    }
+    let restored_rotation = Quat::from_xyzw(
+        restored.rotation[0],
+        restored.rotation[1],
+        restored.rotation[2],
+        restored.rotation[3],
+    );
+    assert!(restored_rotation.dot(rotation).abs() > 0.9999);
+}
+
+#[test]
+fn clamps_scales_out_of_range() {
+    let world_matrix = WorldMatrixUdp {
+        position: vec![],
+        scale: vec![-3.0, 1_000_000.0, f32::NAN],
+        rotation: vec![],
+    };
+    let quantized = QuantizedWorldMatrix::from(&world_matrix);
+    // 足りない要素と有限でない値は既定値になる。
+    // Missing elements and non-finite values fall back to the defaults.
+    assert_eq!(quantized.position, [0, 0, 0]);
+    assert_eq!(quantized.scale, [0, u16::MAX, 100]);
+    assert_eq!(quantized.rotation, [0, 0, 0, i16::MAX]);
+}
+
+#[test]
+fn round_trips_deltas_of_every_field() {
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let mut player = create_player("player-0");
+    send_and_acknowledge(&mut encoder, &mut decoder, vec![player.clone()], vec![]);
+
+    player.is_owner = true;
+    player.current_hp = 0;
+    player.max_hp = 120;
+    player.current_sp = 0;
+    player.max_sp = 80;
+    player.is_alive = false;
+    player.world_matrix.position = [-250_000, 1_500, i32::MAX];
+    player.world_matrix.scale = [200, 100, 50];
+    player.world_matrix.rotation = [0, i16::MIN, 0, i16::MAX];
+    player.last_attacker_id = "player-1".to_string();
+    player.mount_id = "horse-0".to_string();
+    let snapshot = send_and_acknowledge(&mut encoder, &mut decoder, vec![player.clone()], vec![]);
+    assert_eq!(snapshot.players, vec![player.clone()]);
+
+    // 位置の差が整数の範囲を超えても、折り返して元に戻る。
+    // A position difference beyond the integer range still wraps back to the original.
+    player.world_matrix.position[2] = i32::MIN;
+    player.mount_id.clear();
+    let snapshot = send_and_acknowledge(&mut encoder, &mut decoder, vec![player.clone()], vec![]);
+    assert_eq!(snapshot.players, vec![player]);
+}
+
+#[test]
+fn removes_players_and_entities_missing_from_delta() {
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let players = vec![create_player("player-0"), create_player("player-1")];
+    let entities = vec![
+        create_entity("player-0-0", "player-0"),
+        create_entity("player-1-0", "player-1"),
+    ];
+    send_and_acknowledge(
+        &mut encoder,
+        &mut decoder,
+        players.clone(),
+        entities.clone(),
+    );
+
+    let mut moved = entities[0].clone();
+    moved.world_matrix.position = [1_000, 0, 0];
+    let spawned = create_entity("player-0-1", "player-0");
+    let snapshot = send_and_acknowledge(
+        &mut encoder,
+        &mut decoder,
+        vec![players[0].clone()],
+        vec![moved.clone(), spawned.clone()],
+    );
+    assert_eq!(snapshot.players, vec![players[0].clone()]);
+    assert_eq!(snapshot.entities, vec![moved, spawned]);
+}
+
+#[test]
+fn ignores_stale_packets_and_rejects_missing_bases() {
+    let mut encoder = SnapshotEncoder::new();
+    let mut decoder = SnapshotDecoder::new();
+    let mut player = create_player("player-0");
+    let first = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    player.current_hp = 90;
+    let second = encoder
+        .encode(true, vec![player.clone()], vec![], None)
+        .expect("Failed to encode snapshot.");
+    assert!(decode_all(&mut decoder, &second).is_some());
+    assert_eq!(decode_all(&mut decoder, &first), None);
+
+    // 相手が知らない基準の差分は、基準が失われたものとして拒否する。
+    // A delta against a base the other side never had is rejected as a missing base.
+    let mut other_encoder = SnapshotEncoder::new();
+    let mut other_decoder = SnapshotDecoder::new();
+    send_and_acknowledge(
+        &mut other_encoder,
+        &mut other_decoder,
+        vec![player.clone()],
+        vec![],
+    );
+    player.current_hp = 80;
+    let delta = other_encoder
+        .encode(true, vec![player], vec![], None)
+        .expect("Failed to encode snapshot.");
+    let mut fresh_decoder = SnapshotDecoder::new();
+    assert_eq!(
+        fresh_decoder.decode(&delta[0]).err(),
+        Some(PayloadError::MissingBase(1))
+    );
+}
+
+#[test]
+fn applies_snapshot_to_room_state() {
+    let stale = PlayerUdp {
+        player_id: "player-9".to_string(),
+        ..Default::default()
+    };
+    let kept = PlayerUdp {
+        player_id: "player-0".to_string(),
+        nickname: "Alice".to_string(),
+        ..Default::default()
+    };
+    let mut room_state = RoomStateUdp {
+        players: vec![stale, kept],
+        ..Default::default()
+    };
+
+    let mut player = create_player("player-0");
+    player.current_hp = 40;
+    let snapshot = Snapshot {
+        sequence: 1,
+        started: true,
+        players: vec![player, create_player("player-1")],
+        entities: vec![],
+    };
+    snapshot.apply_to(&mut room_state);
+
+    assert!(room_state.started);
+    assert_eq!(room_state.current_players, 2);
+    let ids = room_state
+        .players
+        .iter()
+        .map(|p| p.player_id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec!["player-0", "player-1"]);
+    // スナップショットにない情報はそのまま残る。
+    // Data not carried by snapshots is left as it is.
+    assert_eq!(room_state.players[0].nickname, "Alice");
+    assert_eq!(room_state.players[0].state.state.current_hp, 40);
+}
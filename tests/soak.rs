@@ -0,0 +1,118 @@
+use demo_game_rs::game::shared::structs::{
+    get_synthetic_player_position, ResourceCounts, SoakAction, SoakState, SoakTest,
+};
+
+fn get_counts(models: usize, heap_allocations: usize) -> ResourceCounts {
+    ResourceCounts {
+        vram_bytes: 64 * 1024 * 1024,
+        vram_allocations: 40,
+        descriptor_pools: 2,
+        models,
+        textures: 8,
+        entities: 12,
+        heap_allocations,
+    }
+}
+
+fn run_cycle(soak_test: &mut SoakTest<Vec<u8>>, counts: ResourceCounts) -> SoakAction {
+    assert_eq!(
+        soak_test
+            .update(1.0, SoakState::Loading, || counts)
+            .unwrap(),
+        SoakAction::Continue
+    );
+    assert_eq!(
+        soak_test.update(1.0, SoakState::Game, || counts).unwrap(),
+        SoakAction::Continue
+    );
+    assert_eq!(
+        soak_test.update(1.0, SoakState::Game, || counts).unwrap(),
+        SoakAction::ReturnToTitle
+    );
+    soak_test.update(1.0, SoakState::Title, || counts).unwrap()
+}
+
+#[test]
+fn writes_samples_to_the_csv() {
+    let mut soak_test = SoakTest::with_intervals(vec![], 100.0, 2.0, 2.0).unwrap();
+    let counts = get_counts(3, 1000);
+    for _ in 0..4 {
+        soak_test.update(0.5, SoakState::Game, || counts).unwrap();
+    }
+    let csv = String::from_utf8(soak_test.into_writer()).unwrap();
+    let lines = csv.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("elapsed_seconds,cycle,scene,fps,vram_bytes"));
+    assert_eq!(lines[1], "2.0,0,game,2.0,67108864,40,2,3,8,12,1000");
+}
+
+#[test]
+fn the_first_cycle_sets_the_baseline() {
+    let mut soak_test = SoakTest::with_intervals(vec![], 100.0, 2.0, 50.0).unwrap();
+    assert_eq!(soak_test.get_baseline(), None);
+    assert_eq!(
+        run_cycle(&mut soak_test, get_counts(3, 1000)),
+        SoakAction::Continue
+    );
+    assert_eq!(soak_test.get_cycle(), 1);
+    assert_eq!(soak_test.get_baseline(), Some(&get_counts(3, 1000)));
+
+    // ヒープの確保の揺れは許す。
+    // Wobbles in heap allocations are allowed.
+    assert_eq!(
+        run_cycle(&mut soak_test, get_counts(3, 1040)),
+        SoakAction::Continue
+    );
+    assert!(soak_test.is_passed());
+}
+
+#[test]
+fn stops_at_the_first_leak() {
+    let mut soak_test = SoakTest::with_intervals(vec![], 100.0, 2.0, 50.0).unwrap();
+    run_cycle(&mut soak_test, get_counts(3, 1000));
+    assert_eq!(
+        run_cycle(&mut soak_test, get_counts(5, 2000)),
+        SoakAction::Finish
+    );
+    assert!(!soak_test.is_passed());
+    let leaks = soak_test
+        .get_leaks()
+        .iter()
+        .map(|leak| leak.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        leaks,
+        vec![
+            "Models: 3 -> 5 (+2)",
+            "Heap allocations: 1000 -> 2000 (+1000)"
+        ]
+    );
+}
+
+#[test]
+fn finishes_once_the_duration_passes() {
+    let mut soak_test = SoakTest::with_intervals(vec![], 3.0, 10.0, 50.0).unwrap();
+    let counts = get_counts(3, 1000);
+    assert_eq!(
+        soak_test.update(2.0, SoakState::Game, || counts).unwrap(),
+        SoakAction::Continue
+    );
+    assert_eq!(
+        soak_test.update(2.0, SoakState::Game, || counts).unwrap(),
+        SoakAction::Finish
+    );
+    assert!(soak_test.is_passed());
+}
+
+#[test]
+fn synthetic_players_are_spread_around_a_circle() {
+    let first = get_synthetic_player_position(0, 4, 0.0);
+    let second = get_synthetic_player_position(1, 4, 0.0);
+    assert!((first.length() - second.length()).abs() < 1e-5);
+    assert!(first.dot(second).abs() < 1e-3);
+    assert!(first.y.abs() < 1e-5);
+
+    let moved = get_synthetic_player_position(0, 4, 1.0);
+    assert!((moved.length() - first.length()).abs() < 1e-5);
+    assert!((moved - first).length() > 1e-3);
+}
@@ -0,0 +1,70 @@
+use demo_game_rs::game::shared::util::spirv_reflection::{
+    get_descriptor_array_length, DescriptorArrayLength,
+};
+
+const HEADER: [u32; 5] = [0x0723_0203, 0x0001_0000, 0, 100, 0];
+
+fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+    let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+    words.extend_from_slice(operands);
+    words
+}
+
+fn sampler_module(set: Option<u32>, binding: u32, length: Option<u32>) -> Vec<u32> {
+    let mut words = HEADER.to_vec();
+    if let Some(set) = set {
+        words.extend(instruction(71, &[10, 34, set]));
+    }
+    words.extend(instruction(71, &[10, 33, binding]));
+    words.extend(instruction(21, &[2, 32, 0]));
+    words.extend(instruction(27, &[3, 1]));
+    match length {
+        Some(length) => {
+            words.extend(instruction(43, &[2, 6, length]));
+            words.extend(instruction(28, &[4, 3, 6]));
+        }
+        None => words.extend(instruction(29, &[4, 3])),
+    }
+    words.extend(instruction(32, &[5, 0, 4]));
+    words.extend(instruction(59, &[5, 10, 0]));
+    words
+}
+
+#[test]
+fn reads_fixed_array_lengths() {
+    let words = sampler_module(None, 3, Some(16));
+    assert_eq!(
+        get_descriptor_array_length(&words, 0, 3).unwrap(),
+        DescriptorArrayLength::Fixed(16)
+    );
+    let words = sampler_module(Some(1), 3, Some(8));
+    assert_eq!(
+        get_descriptor_array_length(&words, 1, 3).unwrap(),
+        DescriptorArrayLength::Fixed(8)
+    );
+}
+
+#[test]
+fn tells_unsized_and_missing_bindings_apart() {
+    let words = sampler_module(None, 3, None);
+    assert_eq!(
+        get_descriptor_array_length(&words, 0, 3).unwrap(),
+        DescriptorArrayLength::Unsized
+    );
+    assert_eq!(
+        get_descriptor_array_length(&words, 0, 2).unwrap(),
+        DescriptorArrayLength::Missing
+    );
+    assert_eq!(
+        get_descriptor_array_length(&words, 1, 3).unwrap(),
+        DescriptorArrayLength::Missing
+    );
+}
+
+#[test]
+fn rejects_invalid_spirv() {
+    assert!(get_descriptor_array_length(&[0, 0, 0, 0, 0], 0, 3).is_err());
+    let mut words = sampler_module(None, 3, Some(16));
+    words.truncate(words.len() - 1);
+    assert!(get_descriptor_array_length(&words, 0, 3).is_err());
+}
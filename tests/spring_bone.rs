@@ -0,0 +1,146 @@
+use demo_game_rs::game::shared::structs::{
+    Capsule, Joint, SpringBoneConfig, SpringBones, SpringChain, SpringChainConfig,
+};
+use glam::{Mat4, Quat, Vec3, Vec3A};
+use std::sync::Arc;
+
+fn chain_config(stiffness: f32) -> SpringChainConfig {
+    SpringChainConfig {
+        name: "Tail".to_string(),
+        joints: vec!["root".to_string(), "tip".to_string()],
+        stiffness,
+        damping: 0.1,
+        gravity_scale: 1.0,
+        radius: 0.0,
+    }
+}
+
+fn joint(name: &str, index: usize, translation: Vec3A, children: Vec<Joint>) -> Joint {
+    Joint {
+        name: name.to_string(),
+        node_index: index,
+        index,
+        children,
+        inverse_bind_matrices: Mat4::from_translation(-Vec3::from(translation)),
+        translation,
+        rotation: Quat::identity(),
+        scale: Vec3A::one(),
+    }
+}
+
+#[test]
+fn validates_config() {
+    let config = SpringBoneConfig::from_json(
+        r#"{
+            "chains": [{ "name": "Hair", "joints": ["hair_1", "hair_2", "hair_3"] }],
+            "colliders": [{ "joint": "head", "end": [0.0, 0.2, 0.0], "radius": 0.1 }]
+        }"#,
+    )
+    .expect("Failed to load spring bone config.");
+    assert_eq!(config.gravity, [0.0, -9.8, 0.0]);
+    assert_eq!(config.chains[0].stiffness, 0.1);
+
+    let invalid = [
+        r#"{ "chains": [{ "name": "Hair", "joints": ["hair_1"] }] }"#,
+        r#"{ "chains": [{ "name": "Hair", "joints": ["a", "b"], "damping": 2.0 }] }"#,
+        r#"{ "chains": [], "colliders": [{ "joint": "head", "radius": 0.0 }] }"#,
+    ];
+    for json in invalid.iter() {
+        assert!(SpringBoneConfig::from_json(json).is_err());
+    }
+}
+
+#[test]
+fn pushes_points_out_of_capsules() {
+    let capsule = Capsule {
+        start: Vec3A::zero(),
+        end: Vec3A::new(0.0, 2.0, 0.0),
+        radius: 0.5,
+    };
+    assert_eq!(
+        capsule.get_closest_point(Vec3A::new(1.0, 3.0, 0.0)),
+        Vec3A::new(0.0, 2.0, 0.0)
+    );
+    assert_eq!(
+        capsule.push_out(Vec3A::new(0.2, 1.0, 0.0), 0.1),
+        Vec3A::new(0.6, 1.0, 0.0)
+    );
+    let outside = Vec3A::new(1.0, 1.0, 0.0);
+    assert_eq!(capsule.push_out(outside, 0.1), outside);
+}
+
+#[test]
+fn chain_swings_under_gravity_and_keeps_length() {
+    let animated = [Vec3A::zero(), Vec3A::new(1.0, 0.0, 0.0)];
+    let gravity = Vec3A::new(0.0, -9.8, 0.0);
+
+    // 最初の更新は状態を作るだけで動かない。
+    // The first update only sets up the state without moving.
+    let mut chain = SpringChain::new(chain_config(0.0));
+    assert_eq!(chain.simulate(&animated, 0.1, gravity, &[]), &animated[..]);
+    for _ in 0..30 {
+        chain.simulate(&animated, 1.0 / 30.0, gravity, &[]);
+    }
+    let tip = chain.simulate(&animated, 1.0 / 30.0, gravity, &[])[1];
+    assert!(tip.y < -0.1);
+    assert!((tip.length() - 1.0).abs() < 1e-4);
+
+    // 完全に硬い鎖はアニメーションの姿勢から動かない。
+    // A fully stiff chain doesn't leave the animated pose.
+    let mut stiff = SpringChain::new(chain_config(1.0));
+    stiff.simulate(&animated, 1.0 / 30.0, Vec3A::zero(), &[]);
+    let simulated = stiff.simulate(&animated, 1.0 / 30.0, Vec3A::zero(), &[]);
+    assert_eq!(simulated, &animated[..]);
+
+    // コライダーは先端を押し出す。
+    // Colliders push the tip out.
+    let floor = Capsule {
+        start: Vec3A::new(-5.0, -1.0, 0.0),
+        end: Vec3A::new(5.0, -1.0, 0.0),
+        radius: 0.9,
+    };
+    let mut blocked = SpringChain::new(chain_config(0.0));
+    for _ in 0..30 {
+        blocked.simulate(&animated, 1.0 / 30.0, gravity, &[floor]);
+    }
+    let tip = blocked.simulate(&animated, 1.0 / 30.0, gravity, &[floor])[1];
+    assert!(tip.y >= -0.1 - 1e-4);
+}
+
+#[test]
+fn rotates_joint_transforms_toward_swung_positions() {
+    let root = joint(
+        "root",
+        0,
+        Vec3A::zero(),
+        vec![joint("tip", 1, Vec3A::new(1.0, 0.0, 0.0), vec![])],
+    );
+    let config = SpringBoneConfig {
+        gravity: [0.0, -9.8, 0.0],
+        chains: vec![chain_config(0.0)],
+        colliders: vec![],
+    };
+    let mut spring_bones = SpringBones::new(Arc::new(config), &root);
+    assert!(!spring_bones.is_empty());
+
+    let mut buffer = [Mat4::identity(); 500];
+    for _ in 0..30 {
+        buffer = [Mat4::identity(); 500];
+        spring_bones.update(1.0 / 30.0, Mat4::identity(), &mut buffer);
+    }
+    let tip = buffer[1]
+        * Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0))
+        * glam::Vec4::new(0.0, 0.0, 0.0, 1.0);
+    assert!(tip.y < -0.1);
+    assert!((Vec3A::new(tip.x, tip.y, tip.z).length() - 1.0).abs() < 1e-3);
+
+    let missing = SpringBoneConfig {
+        gravity: [0.0, -9.8, 0.0],
+        chains: vec![SpringChainConfig {
+            joints: vec!["root".to_string(), "hair".to_string()],
+            ..chain_config(0.0)
+        }],
+        colliders: vec![],
+    };
+    assert!(SpringBones::new(Arc::new(missing), &root).is_empty());
+}
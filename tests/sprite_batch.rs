@@ -0,0 +1,117 @@
+use demo_game_rs::game::shared::structs::{
+    build_sprite_vertices, Anchor, Sprite, SpriteAtlas, SpriteBatch, SpriteVertex,
+};
+use glam::Vec2;
+
+fn solid(width: u32, height: u32) -> Vec<u8> {
+    vec![200; (width * height * 4) as usize]
+}
+
+#[test]
+fn atlas_packs_images_without_overlapping() {
+    let mut atlas = SpriteAtlas::new(64, 64);
+    let first = atlas.add("first", 16, 8, &solid(16, 8)).unwrap();
+    let second = atlas.add("second", 16, 8, &solid(16, 8)).unwrap();
+    assert!(first.uv_max.x <= second.uv_min.x);
+    assert!((first.uv_max.x - first.uv_min.x - 16.0 / 64.0).abs() < 1e-5);
+    assert!((first.uv_max.y - first.uv_min.y - 8.0 / 64.0).abs() < 1e-5);
+    assert_eq!(atlas.get_region("second"), Some(second));
+
+    // 棚に収まらなければ次の棚に移る。
+    // An image that doesn't fit on the shelf moves to the next one.
+    let wide = atlas.add("wide", 40, 4, &solid(40, 4)).unwrap();
+    assert!(wide.uv_min.y >= first.uv_max.y);
+}
+
+#[test]
+fn atlas_rejects_bad_images() {
+    let mut atlas = SpriteAtlas::new(32, 32);
+    assert!(atlas.add("icon", 4, 4, &solid(4, 4)).is_ok());
+    assert!(atlas.add("icon", 4, 4, &solid(4, 4)).is_err());
+    assert!(atlas.add("short", 4, 4, &solid(4, 3)).is_err());
+    assert!(atlas.add("huge", 64, 64, &solid(64, 64)).is_err());
+}
+
+#[test]
+fn white_region_samples_a_single_white_texel() {
+    let atlas = SpriteAtlas::new(32, 32);
+    let region = atlas.get_white_region();
+    assert_eq!(region.uv_min, region.uv_max);
+    let x = (region.uv_min.x * 32.0) as usize;
+    let y = (region.uv_min.y * 32.0) as usize;
+    let offset = (y * 32 + x) * 4;
+    assert_eq!(
+        &atlas.get_pixels()[offset..offset + 4],
+        &[255, 255, 255, 255]
+    );
+}
+
+#[test]
+fn anchored_sprites_scale_with_the_window() {
+    let atlas = SpriteAtlas::new(32, 32);
+    let sprite = Sprite::new(atlas.get_white_region())
+        .anchored(Anchor::BottomRight, Vec2::new(-10.0, -10.0))
+        .with_size(Vec2::new(100.0, 20.0));
+    let (top_left, bottom_right) = sprite.get_screen_rect(1280.0, 720.0);
+    assert!((bottom_right - Vec2::new(1270.0, 710.0)).length() < 1e-5);
+    assert!((top_left - Vec2::new(1170.0, 690.0)).length() < 1e-5);
+
+    // 高さが倍になれば、大きさもオフセットも倍になる。
+    // Doubling the height doubles both the size and the offset.
+    let (top_left, bottom_right) = sprite.get_screen_rect(2560.0, 1440.0);
+    assert!((bottom_right - Vec2::new(2540.0, 1420.0)).length() < 1e-5);
+    assert!((top_left - Vec2::new(2340.0, 1380.0)).length() < 1e-5);
+}
+
+#[test]
+fn centered_pivot_overrides_the_anchor() {
+    let atlas = SpriteAtlas::new(32, 32);
+    let sprite = Sprite::new(atlas.get_white_region())
+        .anchored(Anchor::TopLeft, Vec2::new(50.0, 50.0))
+        .with_size(Vec2::new(20.0, 20.0))
+        .with_pivot(Vec2::new(0.5, 0.5));
+    let (top_left, bottom_right) = sprite.get_screen_rect(1280.0, 720.0);
+    assert!((top_left - Vec2::new(40.0, 40.0)).length() < 1e-5);
+    assert!((bottom_right - Vec2::new(60.0, 60.0)).length() < 1e-5);
+}
+
+#[test]
+fn batch_builds_six_vertices_per_sprite() {
+    assert_eq!(std::mem::size_of::<SpriteVertex>(), 20);
+    let atlas = SpriteAtlas::new(32, 32);
+    let mut batch = SpriteBatch::new();
+    batch.fill_rect(
+        &atlas,
+        Anchor::TopLeft,
+        Vec2::zero(),
+        Vec2::new(1280.0, 720.0),
+        [255, 0, 0, 255],
+    );
+    batch.fill_rect(
+        &atlas,
+        Anchor::Center,
+        Vec2::zero(),
+        Vec2::new(10.0, 10.0),
+        [0, 255, 0, 128],
+    );
+    let vertices = build_sprite_vertices(batch.get_sprites(), 1280.0, 720.0);
+    assert_eq!(vertices.len(), 12);
+
+    // 画面いっぱいの四角形は正規化デバイス座標の隅に届く。
+    // A rectangle filling the screen reaches the corners of normalized device coordinates.
+    let corners = vertices[..6]
+        .iter()
+        .map(|vertex| Vec2::new(vertex.position[0], vertex.position[1]))
+        .collect::<Vec<_>>();
+    assert!(corners
+        .iter()
+        .any(|corner| (*corner - Vec2::new(-1.0, -1.0)).length() < 1e-5));
+    assert!(corners
+        .iter()
+        .any(|corner| (*corner - Vec2::new(1.0, 1.0)).length() < 1e-5));
+    assert_eq!(vertices[6].color, [0, 255, 0, 128]);
+
+    assert!(build_sprite_vertices(batch.get_sprites(), 0.0, 720.0).is_empty());
+    assert_eq!(batch.take_sprites().len(), 2);
+    assert!(batch.is_empty());
+}
@@ -0,0 +1,40 @@
+use demo_game_rs::game::graphics::vk::staging_ring::RingAllocator;
+
+#[test]
+fn allocates_aligned_regions_in_order() {
+    let mut ring = RingAllocator::new(256);
+    assert_eq!(ring.allocate(10, 16), Some(0));
+    assert_eq!(ring.allocate(20, 16), Some(16));
+    assert_eq!(ring.allocate(300, 16), None);
+    assert_eq!(ring.get_region_count(), 2);
+}
+
+#[test]
+fn wraps_around_once_the_oldest_region_is_released() {
+    let mut ring = RingAllocator::new(100);
+    let first = ring.allocate(40, 1).unwrap();
+    let second = ring.allocate(40, 1).unwrap();
+    assert_eq!(ring.allocate(40, 1), None);
+
+    ring.release(first);
+    assert_eq!(ring.allocate(40, 1), Some(0));
+    assert_eq!(ring.allocate(10, 1), None);
+
+    ring.release(second);
+    assert_eq!(ring.allocate(50, 1), Some(40));
+}
+
+#[test]
+fn keeps_regions_until_earlier_ones_are_released() {
+    let mut ring = RingAllocator::new(100);
+    let first = ring.allocate(50, 1).unwrap();
+    let second = ring.allocate(50, 1).unwrap();
+
+    ring.release(second);
+    assert_eq!(ring.get_region_count(), 2);
+    assert_eq!(ring.allocate(10, 1), None);
+
+    ring.release(first);
+    assert!(ring.is_empty());
+    assert_eq!(ring.allocate(100, 1), Some(0));
+}
@@ -0,0 +1,103 @@
+use demo_game_rs::game::shared::structs::{
+    fade_color, get_distance_alpha, get_fill, is_occluded, SpriteAtlas, SpriteBatch, StatusBar,
+    StatusTarget, BILLBOARD_FADE_START, BILLBOARD_MAX_DISTANCE,
+};
+use glam::{Mat4, Vec2, Vec3A};
+
+const WIDTH: f32 = 1280.0;
+const HEIGHT: f32 = 720.0;
+
+fn create_target(current_sp: i32, max_sp: i32) -> StatusTarget {
+    StatusTarget {
+        position: Vec3A::new(0.5, -0.5, 0.5),
+        current_hp: 30,
+        max_hp: 120,
+        current_sp,
+        max_sp,
+    }
+}
+
+#[test]
+fn fills_are_clamped() {
+    assert!((get_fill(30, 120) - 0.25).abs() < 1e-5);
+    assert!((get_fill(150, 100) - 1.0).abs() < 1e-5);
+    assert!(get_fill(-5, 100).abs() < 1e-5);
+    assert!(get_fill(10, 0).abs() < 1e-5);
+}
+
+#[test]
+fn fades_out_with_distance() {
+    assert!((get_distance_alpha(1.0) - 1.0).abs() < 1e-5);
+    assert!((get_distance_alpha(BILLBOARD_FADE_START) - 1.0).abs() < 1e-5);
+    let middle = (BILLBOARD_FADE_START + BILLBOARD_MAX_DISTANCE) * 0.5;
+    assert!((get_distance_alpha(middle) - 0.5).abs() < 1e-5);
+    assert!(get_distance_alpha(BILLBOARD_MAX_DISTANCE).abs() < 1e-5);
+    assert_eq!(fade_color([10, 20, 30, 200], 0.5), [10, 20, 30, 100]);
+    assert_eq!(fade_color([10, 20, 30, 200], 2.0), [10, 20, 30, 200]);
+}
+
+#[test]
+fn only_blockers_in_front_occlude() {
+    assert!(!is_occluded(None, 10.0));
+    assert!(is_occluded(Some(4.0), 10.0));
+    // 目標のすぐ手前で当たったものは遮らない。
+    // Hits right in front of the target don't block it.
+    assert!(!is_occluded(Some(9.9), 10.0));
+    assert!(!is_occluded(Some(12.0), 10.0));
+}
+
+#[test]
+fn projects_targets_on_screen() {
+    let eye = Vec3A::new(0.5, -0.5, -5.0);
+    let bar = StatusBar::project(&create_target(0, 0), Mat4::identity(), eye, WIDTH, HEIGHT)
+        .expect("Failed to project the status bar.");
+    assert!((bar.screen_position - Vec2::new(WIDTH * 0.75, HEIGHT * 0.25)).length() < 1e-3);
+    assert!((bar.hp_fill - 0.25).abs() < 1e-5);
+    assert_eq!(bar.sp_fill, None);
+    assert!((bar.alpha - 1.0).abs() < 1e-5);
+
+    let bar = StatusBar::project(&create_target(10, 40), Mat4::identity(), eye, WIDTH, HEIGHT)
+        .expect("Failed to project the status bar.");
+    assert!((bar.sp_fill.expect("Failed to get the SP fill.") - 0.25).abs() < 1e-5);
+
+    let mut target = create_target(0, 0);
+    target.position = Vec3A::new(2.0, 0.0, 0.0);
+    assert_eq!(
+        StatusBar::project(&target, Mat4::identity(), eye, WIDTH, HEIGHT),
+        None
+    );
+    let far_eye = Vec3A::new(0.5, -0.5, -BILLBOARD_MAX_DISTANCE - 1.0);
+    assert_eq!(
+        StatusBar::project(
+            &create_target(0, 0),
+            Mat4::identity(),
+            far_eye,
+            WIDTH,
+            HEIGHT
+        ),
+        None
+    );
+}
+
+#[test]
+fn draws_a_frame_and_the_filled_bars() {
+    let atlas = SpriteAtlas::default();
+    let mut batch = SpriteBatch::new();
+    let bar = StatusBar {
+        screen_position: Vec2::new(WIDTH * 0.5, HEIGHT * 0.5),
+        hp_fill: 0.5,
+        sp_fill: Some(0.0),
+        alpha: 0.5,
+    };
+    bar.draw(&mut batch, &atlas, HEIGHT);
+    // 空のSPのバーは描かず、枠と体力のバーだけになる。
+    // The empty SP bar isn't drawn, leaving only the frame and the HP bar.
+    let sprites = batch.get_sprites();
+    assert_eq!(sprites.len(), 2);
+    let (frame_min, frame_max) = sprites[0].get_screen_rect(WIDTH, HEIGHT);
+    assert!((frame_max.y - HEIGHT * 0.5).abs() < 1e-3);
+    assert!(((frame_min.x + frame_max.x) * 0.5 - WIDTH * 0.5).abs() < 1e-3);
+    let (hp_min, hp_max) = sprites[1].get_screen_rect(WIDTH, HEIGHT);
+    assert!(hp_max.x - hp_min.x < (frame_max.x - frame_min.x) * 0.5);
+    assert!(sprites.iter().all(|s| s.color[3] < 255));
+}
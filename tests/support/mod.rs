@@ -0,0 +1,605 @@
+use demo_game_rs::game::shared::structs::games::fragment::MAX_DATAGRAM_SIZE;
+use demo_game_rs::game::shared::structs::games::match_result::{settle_player, MatchOutcome};
+use demo_game_rs::game::shared::structs::games::protocol::PROTOCOL_VERSION;
+use demo_game_rs::game::shared::structs::games::replication::{EntityRegistry, EntitySnapshot};
+use demo_game_rs::game::shared::structs::games::snapshot::{SnapshotDecoder, SnapshotEncoder};
+use demo_game_rs::game::shared::structs::games::udp_session::{Received, SessionTable};
+use demo_game_rs::game::shared::systems::ConnectionSettings;
+use demo_game_rs::protos::grpc_service::game_state::{
+    GetTerrainReply, GetTerrainRequest, Player, PlayerState, ProgressGameRequest,
+    RegisterPlayerRequest, ReportMatchResultReply, ReportMatchResultRequest, RoomState, Rooms,
+    StartGameRequest, UpdateTerrainRequest,
+};
+use demo_game_rs::protos::grpc_service::grpc_service_server::{GrpcService, GrpcServiceServer};
+use demo_game_rs::protos::grpc_service::{
+    Empty, HandshakeReply, HandshakeRequest, IncomingMessage, IncomingMessages, LoginReply,
+    LoginRequest, MessageRecord, RegisterReply, RegisterRequest,
+};
+use demo_game_rs::protos::jwt_token_service::access_reply::user::UserType;
+use demo_game_rs::protos::jwt_token_service::access_reply::User;
+use demo_game_rs::protos::jwt_token_service::jwt_token_service_server::{
+    JwtTokenService, JwtTokenServiceServer,
+};
+use demo_game_rs::protos::jwt_token_service::{AccessReply, AccessRequest};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::{mpsc, Mutex};
+use tonic::{Request, Response, Status, Streaming};
+
+/// モックサーバーが発行するJWTトークン。<br />
+/// JWT token issued by the mock server.
+pub const MOCK_TOKEN: &str = "mock-jwt-token";
+
+/// あらかじめ用意された部屋のID。<br />
+/// ID of the canned room.
+pub const CANNED_ROOM_ID: &str = "canned-room";
+
+/// あらかじめ用意されたプレイヤー（アカウント名、ニックネーム、パスワード）。<br />
+/// Canned players as (account name, nickname, password).
+pub const CANNED_PLAYERS: [(&str, &str, &str); 2] =
+    [("alice", "Alice", "alice-pass"), ("bob", "Bob", "bob-pass")];
+
+const CHAT_HISTORY: [(&str, &str); 2] = [("Alice", "Hello!"), ("Bob", "Hi, Alice.")];
+
+/// モックサーバーが保持する状態。<br />
+/// State held by the mock server.
+#[derive(Default)]
+struct MockState {
+    players: Vec<Player>,
+    rooms: HashMap<String, RoomState>,
+    terrains: HashMap<String, Vec<u8>>,
+    terrain_patches: HashMap<String, Vec<u8>>,
+    outcomes: HashMap<String, MatchOutcome>,
+    room_subscribers: HashMap<String, Vec<mpsc::UnboundedSender<Result<RoomState, Status>>>>,
+    chat_history: Vec<IncomingMessage>,
+    chat_subscribers: Vec<mpsc::UnboundedSender<Result<IncomingMessage, Status>>>,
+    entities: EntityRegistry,
+}
+
+impl MockState {
+    fn new() -> Self {
+        let players = CANNED_PLAYERS
+            .iter()
+            .enumerate()
+            .map(|(index, (user_name, nickname, password))| {
+                create_player(&format!("player-{}", index), user_name, nickname, password)
+            })
+            .collect::<Vec<_>>();
+        let mut rooms = HashMap::new();
+        rooms.insert(
+            CANNED_ROOM_ID.to_string(),
+            RoomState {
+                room_id: CANNED_ROOM_ID.to_string(),
+                room_name: "Canned Room".to_string(),
+                current_players: 0,
+                max_players: 4,
+                started: false,
+                players: vec![],
+                message: String::new(),
+                player_settings: HashMap::new(),
+            },
+        );
+        let chat_history = CHAT_HISTORY
+            .iter()
+            .map(|(author, message)| IncomingMessage {
+                author: author.to_string(),
+                message: message.to_string(),
+            })
+            .collect::<Vec<_>>();
+        MockState {
+            players,
+            rooms,
+            chat_history,
+            ..Default::default()
+        }
+    }
+
+    /// 部屋の状態を購読しているクライアントに配る。切断したクライアントは取り除く。<br />
+    /// Broadcast the room state to subscribed clients, removing disconnected ones.
+    fn broadcast_room(&mut self, room_id: &str) {
+        let room = match self.rooms.get(room_id) {
+            Some(room) => room.clone(),
+            None => return,
+        };
+        if let Some(subscribers) = self.room_subscribers.get_mut(room_id) {
+            subscribers.retain(|s| s.send(Ok(room.clone())).is_ok());
+        }
+    }
+
+    fn broadcast_chat(&mut self, message: IncomingMessage) {
+        self.chat_subscribers
+            .retain(|s| s.send(Ok(message.clone())).is_ok());
+        self.chat_history.push(message);
+    }
+}
+
+fn create_player(player_id: &str, user_name: &str, nickname: &str, password: &str) -> Player {
+    Player {
+        player_id: player_id.to_string(),
+        user_name: user_name.to_string(),
+        nickname: nickname.to_string(),
+        password: base64::encode(password),
+        join_date: String::new(),
+        last_login: String::new(),
+        win_count: 0,
+        lose_count: 0,
+        credits: 0,
+        email: format!("{}@example.com", user_name),
+        state: Some(PlayerState {
+            is_in_game: false,
+            room_id: String::new(),
+            is_owner: false,
+            state: None,
+        }),
+    }
+}
+
+/// 外部のインフラなしでネットワークの流れを試すための、プロセス内のモックgRPCサーバー。<br />
+/// An in-process mock gRPC server for exercising network flows without external infrastructure.
+#[derive(Clone)]
+pub struct MockServer {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockServer {
+    /// ローカルの空いているポートでモックサーバーを起動し、そのアドレスを返す。<br />
+    /// Start the mock server on a free local port and return its address.
+    pub async fn start() -> anyhow::Result<(Self, SocketAddr)> {
+        let server = MockServer {
+            state: Arc::new(Mutex::new(MockState::new())),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let grpc_service = GrpcServiceServer::new(server.clone());
+        let jwt_service = JwtTokenServiceServer::new(server.clone());
+        tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .add_service(grpc_service)
+                .add_service(jwt_service)
+                .serve_with_incoming(listener)
+                .await
+                .expect("Failed to run mock gRPC server.");
+        });
+        Ok((server, address))
+    }
+
+    /// モックサーバーに接続するための設定。<br />
+    /// Settings for connecting to the mock server.
+    /// モックサーバーがアカウントに発行するリフレッシュトークン。<br />
+    /// Refresh token the mock server issues to an account.
+    pub fn get_refresh_token(user_name: &str) -> String {
+        format!("mock-refresh-{}", user_name)
+    }
+
+    pub fn get_settings(address: SocketAddr) -> ConnectionSettings {
+        ConnectionSettings {
+            server_endpoint: format!("http://{}", address),
+            udp_bind_point: "127.0.0.1:0".to_string(),
+            udp_endpoint: String::new(),
+            login_name: "mock".to_string(),
+            login_pass: "mock".to_string(),
+        }
+    }
+
+    /// 部屋に保存された地形のデータ。<br />
+    /// Terrain data stored for a room.
+    pub async fn get_stored_terrain(&self, room_id: &str) -> Option<Vec<u8>> {
+        self.state.lock().await.terrains.get(room_id).cloned()
+    }
+
+    pub async fn get_room(&self, room_id: &str) -> Option<RoomState> {
+        self.state.lock().await.rooms.get(room_id).cloned()
+    }
+
+    /// UDPで持ち主から受け取ったエンティティ。<br />
+    /// Entities received from their owners over UDP.
+    pub async fn get_replicated_entities(&self) -> Vec<EntitySnapshot> {
+        self.state.lock().await.entities.get_entities()
+    }
+
+    /// ローカルの空いているポートでUDPの中継を起動し、そのアドレスを返す。<br />
+    /// セッションを開いたクライアントのエンティティを受け付け、部屋の全てのエンティティを送り返す。<br />
+    /// Start the UDP relay on a free local port and return its address.<br />
+    /// Entities from clients with an open session are accepted, and all entities in the room are sent back.
+    pub async fn start_udp(&self) -> anyhow::Result<SocketAddr> {
+        let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+        let address = socket.local_addr()?;
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let mut sessions = SessionTable::new(rand::random());
+            let mut peers = HashMap::new();
+            let mut players = HashMap::new();
+            let mut buffer = [0_u8; MAX_DATAGRAM_SIZE];
+            while let Ok((size, from)) = socket.recv_from(&mut buffer).await {
+                let received =
+                    sessions.receive(from, &buffer[0..size], Instant::now(), |token, _| {
+                        token == MOCK_TOKEN
+                    });
+                let datagram = match received {
+                    Ok(Received::Reply(reply)) => {
+                        let _ = socket.send_to(&reply, from).await;
+                        continue;
+                    }
+                    Ok(Received::Datagram(datagram)) => datagram,
+                    _ => continue,
+                };
+                let player_id = match sessions.get(&from) {
+                    Some(session) => session.player_id.clone(),
+                    None => continue,
+                };
+                let (encoder, decoder) = peers
+                    .entry(from)
+                    .or_insert_with(|| (SnapshotEncoder::new(), SnapshotDecoder::new()));
+                let (snapshot, ack) = match decoder.decode(&datagram) {
+                    Ok(Some(result)) => result,
+                    _ => continue,
+                };
+                if let Some(ack) = ack {
+                    encoder.acknowledge(ack);
+                }
+                for player in snapshot.players.into_iter() {
+                    players.insert(player.player_id.clone(), player);
+                }
+                let entities = {
+                    let mut state = state.lock().await;
+                    state.entities.accept(&player_id, snapshot.entities);
+                    state.entities.get_entities()
+                };
+                let datagrams = match encoder.encode(
+                    true,
+                    players.values().cloned().collect(),
+                    entities,
+                    decoder.get_ack(),
+                ) {
+                    Ok(datagrams) => datagrams,
+                    Err(_) => continue,
+                };
+                for datagram in datagrams.iter() {
+                    if let Some(packet) = sessions.wrap(&from, datagram) {
+                        let _ = socket.send_to(&packet, from).await;
+                    }
+                }
+            }
+        });
+        Ok(address)
+    }
+}
+
+#[tonic::async_trait]
+impl JwtTokenService for MockServer {
+    async fn access(
+        &self,
+        request: Request<AccessRequest>,
+    ) -> Result<Response<AccessReply>, Status> {
+        let request = request.into_inner();
+        Ok(Response::new(AccessReply {
+            token: MOCK_TOKEN.to_string(),
+            user_details: Some(User {
+                r#type: UserType::Bot as i32,
+                user_name: request.user_name,
+                user_role: "Bot".to_string(),
+                password: request.password,
+            }),
+            expiry: "2099-12-31T00:00:00Z".to_string(),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl GrpcService for MockServer {
+    async fn handshake(
+        &self,
+        request: Request<HandshakeRequest>,
+    ) -> Result<Response<HandshakeReply>, Status> {
+        let request = request.into_inner();
+        Ok(Response::new(HandshakeReply {
+            compatible: request.protocol_version == PROTOCOL_VERSION,
+            server_protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: PROTOCOL_VERSION,
+            server_build_version: "mock".to_string(),
+            message: String::new(),
+        }))
+    }
+
+    async fn register(
+        &self,
+        request: Request<RegisterRequest>,
+    ) -> Result<Response<RegisterReply>, Status> {
+        let request = request.into_inner();
+        if request.jwt_token != MOCK_TOKEN {
+            return Err(Status::unauthenticated("Invalid JWT token."));
+        }
+        let mut state = self.state.lock().await;
+        if state
+            .players
+            .iter()
+            .any(|p| p.user_name == request.user_name)
+        {
+            return Ok(Response::new(RegisterReply {
+                status: false,
+                message: "User name is already taken.".to_string(),
+                player: None,
+            }));
+        }
+        let mut player = create_player(
+            &format!("player-{}", state.players.len()),
+            &request.user_name,
+            &request.nickname,
+            "",
+        );
+        player.password = request.password;
+        player.email = request.email;
+        state.players.push(player.clone());
+        Ok(Response::new(RegisterReply {
+            status: true,
+            message: String::new(),
+            player: Some(player),
+        }))
+    }
+
+    async fn login(&self, request: Request<LoginRequest>) -> Result<Response<LoginReply>, Status> {
+        let request = request.into_inner();
+        if request.jwt_token != MOCK_TOKEN {
+            return Err(Status::unauthenticated("Invalid JWT token."));
+        }
+        let state = self.state.lock().await;
+        let player = state
+            .players
+            .iter()
+            .find(|p| {
+                p.user_name == request.account
+                    && (p.password == request.password
+                        || request.refresh_token == Self::get_refresh_token(&p.user_name))
+            })
+            .cloned();
+        let refresh_token = player
+            .as_ref()
+            .map(|p| Self::get_refresh_token(&p.user_name))
+            .unwrap_or_default();
+        Ok(Response::new(LoginReply {
+            status: player.is_some(),
+            message: String::new(),
+            player,
+            refresh_token,
+        }))
+    }
+
+    async fn get_chat_history(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<IncomingMessages>, Status> {
+        Ok(Response::new(IncomingMessages {
+            messages: self.state.lock().await.chat_history.clone(),
+        }))
+    }
+
+    type ChatStream = mpsc::UnboundedReceiver<Result<IncomingMessage, Status>>;
+
+    async fn chat(
+        &self,
+        request: Request<Streaming<MessageRecord>>,
+    ) -> Result<Response<Self::ChatStream>, Status> {
+        let mut inbound = request.into_inner();
+        let (send, recv) = mpsc::unbounded_channel();
+        self.state.lock().await.chat_subscribers.push(send);
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            while let Ok(Some(record)) = inbound.message().await {
+                let mut state = state.lock().await;
+                let author = state
+                    .players
+                    .iter()
+                    .find(|p| p.player_id == record.player_id)
+                    .map(|p| p.nickname.clone())
+                    .unwrap_or(record.player_id);
+                state.broadcast_chat(IncomingMessage {
+                    author,
+                    message: record.message,
+                });
+            }
+        });
+        Ok(Response::new(recv))
+    }
+
+    async fn get_rooms(&self, _request: Request<Empty>) -> Result<Response<Rooms>, Status> {
+        let state = self.state.lock().await;
+        let mut rooms = state.rooms.values().cloned().collect::<Vec<_>>();
+        rooms.sort_by(|a, b| a.room_id.cmp(&b.room_id));
+        Ok(Response::new(Rooms { rooms }))
+    }
+
+    type RegisterPlayerStream = mpsc::UnboundedReceiver<Result<RoomState, Status>>;
+
+    async fn register_player(
+        &self,
+        request: Request<RegisterPlayerRequest>,
+    ) -> Result<Response<Self::RegisterPlayerStream>, Status> {
+        let request = request.into_inner();
+        let player = request
+            .player
+            .ok_or_else(|| Status::invalid_argument("Player is missing."))?;
+        let mut state = self.state.lock().await;
+        let room = state
+            .rooms
+            .entry(request.room_id.clone())
+            .or_insert_with(|| RoomState {
+                room_id: request.room_id.clone(),
+                room_name: request.room_name.clone(),
+                current_players: 0,
+                max_players: if request.max_players > 0 {
+                    request.max_players
+                } else {
+                    4
+                },
+                started: false,
+                players: vec![],
+                message: String::new(),
+                player_settings: HashMap::new(),
+            });
+        if room.current_players >= room.max_players {
+            return Err(Status::resource_exhausted("Room is full."));
+        }
+        room.players.retain(|p| p.player_id != player.player_id);
+        if let Some(settings) = request.settings {
+            room.player_settings
+                .insert(player.player_id.clone(), settings);
+        }
+        room.players.push(player);
+        room.current_players = room.players.len() as i32;
+
+        let (send, recv) = mpsc::unbounded_channel();
+        state
+            .room_subscribers
+            .entry(request.room_id.clone())
+            .or_insert_with(Vec::new)
+            .push(send);
+        state.broadcast_room(&request.room_id);
+        Ok(Response::new(recv))
+    }
+
+    async fn start_game(
+        &self,
+        request: Request<StartGameRequest>,
+    ) -> Result<Response<RoomState>, Status> {
+        let request = request.into_inner();
+        let room_id = request
+            .room_state
+            .map(|r| r.room_id)
+            .ok_or_else(|| Status::invalid_argument("Room state is missing."))?;
+        let mut state = self.state.lock().await;
+        let room = state
+            .rooms
+            .get_mut(&room_id)
+            .ok_or_else(|| Status::not_found("Room doesn't exist."))?;
+        room.started = true;
+        for player in room.players.iter_mut() {
+            if let Some(player_state) = player.state.as_mut() {
+                player_state.is_in_game = true;
+                player_state.room_id = room_id.clone();
+            }
+        }
+        let room = room.clone();
+        state
+            .terrains
+            .insert(room_id.clone(), request.terrain_vertices);
+        state.broadcast_room(&room_id);
+        // ゲームが始まったら部屋のストリームを閉じる。
+        // Close the room streams once the game has started.
+        state.room_subscribers.remove(&room_id);
+        Ok(Response::new(room))
+    }
+
+    async fn get_terrain(
+        &self,
+        request: Request<GetTerrainRequest>,
+    ) -> Result<Response<GetTerrainReply>, Status> {
+        let room_id = request.into_inner().room_id;
+        let state = self.state.lock().await;
+        let terrain_vertices = state
+            .terrains
+            .get(&room_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("Terrain hasn't been uploaded."))?;
+        let terrain_patches = state
+            .terrain_patches
+            .get(&room_id)
+            .cloned()
+            .unwrap_or_default();
+        Ok(Response::new(GetTerrainReply {
+            terrain_vertices,
+            terrain_patches,
+        }))
+    }
+
+    async fn update_terrain(
+        &self,
+        request: Request<UpdateTerrainRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        let request = request.into_inner();
+        let mut state = self.state.lock().await;
+        if !state.terrains.contains_key(&request.room_id) {
+            return Err(Status::not_found("Terrain hasn't been uploaded."));
+        }
+        state
+            .terrain_patches
+            .insert(request.room_id, request.terrain_patches);
+        Ok(Response::new(Empty {}))
+    }
+
+    /// 最初の報告で勝者を決め、報告したプレイヤーを精算して部屋から出す。<br />
+    /// Decide the winner by the first report, settle the reporting player and remove them from the room.
+    async fn report_match_result(
+        &self,
+        request: Request<ReportMatchResultRequest>,
+    ) -> Result<Response<ReportMatchResultReply>, Status> {
+        let request = request.into_inner();
+        let mut state = self.state.lock().await;
+        let room = state
+            .rooms
+            .get_mut(&request.room_id)
+            .ok_or_else(|| Status::not_found("Room doesn't exist."))?;
+        if !room
+            .players
+            .iter()
+            .any(|p| p.player_id == request.player_id)
+        {
+            return Err(Status::permission_denied("Player isn't in the room."));
+        }
+        room.players.retain(|p| p.player_id != request.player_id);
+        room.current_players = room.players.len() as i32;
+        let outcome = state
+            .outcomes
+            .entry(request.room_id.clone())
+            .or_insert_with(|| MatchOutcome::from_winner_id(&request.winner_id))
+            .clone();
+        let player = state
+            .players
+            .iter_mut()
+            .find(|p| p.player_id == request.player_id)
+            .ok_or_else(|| Status::unauthenticated("Player hasn't logged in."))?;
+        let earned_credits = settle_player(player, &outcome);
+        Ok(Response::new(ReportMatchResultReply {
+            player: Some(player.clone()),
+            winner_id: outcome.get_winner_id().unwrap_or_default().to_string(),
+            earned_credits,
+        }))
+    }
+
+    type ProgressGameStream = mpsc::UnboundedReceiver<Result<RoomState, Status>>;
+
+    async fn progress_game(
+        &self,
+        request: Request<Streaming<ProgressGameRequest>>,
+    ) -> Result<Response<Self::ProgressGameStream>, Status> {
+        let mut inbound = request.into_inner();
+        let (send, recv) = mpsc::unbounded_channel();
+        let state = self.state.clone();
+        // 送られてきたプレイヤーで部屋を書き換え、その部屋を送り返す。
+        // Update the room with the player sent, and send the room back.
+        tokio::spawn(async move {
+            while let Ok(Some(request)) = inbound.message().await {
+                let mut state = state.lock().await;
+                let room = match state.rooms.get_mut(&request.room_id) {
+                    Some(room) => room,
+                    None => break,
+                };
+                if let Some(player) = request.player {
+                    if let Some(p) = room
+                        .players
+                        .iter_mut()
+                        .find(|p| p.player_id == player.player_id)
+                    {
+                        *p = player;
+                    }
+                }
+                if send.send(Ok(room.clone())).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(recv))
+    }
+}
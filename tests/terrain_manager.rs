@@ -0,0 +1,49 @@
+use demo_game_rs::game::graphics::vk::Buffer;
+use demo_game_rs::game::shared::structs::games::terrain_payload::generate_grid_indices;
+use demo_game_rs::game::shared::structs::{
+    get_grid_vertex_count, Primitive, TerrainManager, Vertex,
+};
+
+fn create_chunk(vertex_count: u32) -> Primitive {
+    Primitive {
+        vertices: vec![Vertex::default(); (vertex_count * vertex_count) as usize],
+        indices: generate_grid_indices(vertex_count),
+        texture_index: None,
+        is_disposed: false,
+    }
+}
+
+#[test]
+fn detects_grid_chunks_by_resolution() {
+    for &vertex_count in &[2, 16, 128] {
+        assert_eq!(
+            get_grid_vertex_count(&create_chunk(vertex_count)),
+            Some(vertex_count)
+        );
+    }
+}
+
+#[test]
+fn rejects_chunks_that_cannot_share_index_buffers() {
+    // 頂点が一つだけでは格子にならない。
+    // A single vertex doesn't make a grid.
+    assert_eq!(get_grid_vertex_count(&create_chunk(1)), None);
+
+    let mut not_square = create_chunk(8);
+    not_square.vertices.pop();
+    assert_eq!(get_grid_vertex_count(&not_square), None);
+
+    // インデックスの数が格子と違えば、専用のバッファを使う。
+    // Chunks whose index count differs from the grid use dedicated buffers.
+    let mut custom_indices = create_chunk(8);
+    custom_indices.indices.truncate(6);
+    assert_eq!(get_grid_vertex_count(&custom_indices), None);
+}
+
+#[test]
+fn starts_without_shared_buffers() {
+    let terrain_manager = TerrainManager::<Buffer>::new();
+    assert_eq!(terrain_manager.get_index_buffer_count(), 0);
+    terrain_manager.clear();
+    assert_eq!(terrain_manager.get_index_buffer_count(), 0);
+}
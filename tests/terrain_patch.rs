@@ -0,0 +1,95 @@
+use demo_game_rs::game::shared::structs::games::payload::PayloadError;
+use demo_game_rs::game::shared::structs::games::terrain_patch::{
+    decode_patches, encode_patches, HeightRun, TerrainPatch,
+};
+use demo_game_rs::game::shared::structs::games::terrain_payload::generate_grid_indices;
+use demo_game_rs::game::shared::structs::{Primitive, Vertex};
+use glam::{Vec2, Vec3A};
+
+fn create_terrain(vertex_count: u32) -> Primitive {
+    let mut vertices = vec![];
+    for z in 0..vertex_count {
+        for x in 0..vertex_count {
+            vertices.push(Vertex {
+                position: Vec3A::new(x as f32, (x + z) as f32 * 0.5, z as f32),
+                normal: Vec3A::unit_y(),
+                uv: Vec2::zero(),
+            });
+        }
+    }
+    Primitive {
+        vertices,
+        indices: generate_grid_indices(vertex_count),
+        texture_index: None,
+        is_disposed: false,
+    }
+}
+
+#[test]
+fn unmodified_chunks_have_no_patch() {
+    let terrain = create_terrain(8);
+    assert_eq!(TerrainPatch::diff(0, 0, &terrain, &terrain), Ok(None));
+}
+
+#[test]
+fn diffs_modified_heights_into_runs() {
+    let baseline = create_terrain(8);
+    let mut modified = baseline.clone();
+    for index in [9, 10, 11, 40].iter() {
+        modified.vertices[*index].position.y -= 2.0;
+    }
+
+    let patch = TerrainPatch::diff(1, -2, &baseline, &modified)
+        .expect("Failed to diff terrain.")
+        .expect("Modified terrain has no patch.");
+    assert_eq!(patch.get_modified_count(), 4);
+    assert_eq!(patch.runs.len(), 2);
+    assert_eq!(patch.runs[0].start, 9);
+    assert_eq!(patch.runs[1].start, 40);
+
+    let mut restored = baseline.clone();
+    patch.apply(&mut restored).expect("Failed to apply patch.");
+    for (vertex, expected) in restored.vertices.iter().zip(modified.vertices.iter()) {
+        assert_eq!(vertex.position, expected.position);
+    }
+    assert_ne!(restored.vertices[10].normal, Vec3A::unit_y());
+    assert_eq!(restored.vertices[63].normal, Vec3A::unit_y());
+}
+
+#[test]
+fn survives_the_save_file_round_trip() {
+    let baseline = create_terrain(16);
+    let mut modified = baseline.clone();
+    modified.vertices[100].position.y = 42.0;
+    let patch = TerrainPatch::diff(0, 0, &baseline, &modified)
+        .unwrap()
+        .unwrap();
+
+    let bytes = encode_patches(&[patch.clone()]).expect("Failed to encode patches.");
+    assert_eq!(decode_patches(&bytes), Ok(vec![patch]));
+    assert_eq!(
+        decode_patches(&bytes[1..]),
+        Err(PayloadError::InvalidHeader)
+    );
+}
+
+#[test]
+fn rejects_patches_outside_the_chunk() {
+    let patch = TerrainPatch {
+        grid_x: 0,
+        grid_z: 0,
+        vertex_count: 4,
+        runs: vec![HeightRun {
+            start: 15,
+            heights: vec![1.0, 2.0],
+        }],
+    };
+    let mut terrain = create_terrain(4);
+    assert!(matches!(
+        patch.apply(&mut terrain),
+        Err(PayloadError::OutOfRange { field: "runs", .. })
+    ));
+
+    let bytes = encode_patches(&[patch]).unwrap();
+    assert!(decode_patches(&bytes).is_err());
+}
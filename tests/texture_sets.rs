@@ -0,0 +1,107 @@
+use ash::vk::{DescriptorSet, Handle};
+use demo_game_rs::game::graphics::vk::{TextureDescriptorSets, TextureLimits, TextureSetLayout};
+
+fn limits(max_per_stage: u32, shader_array_length: Option<u32>) -> TextureLimits {
+    TextureLimits {
+        max_per_stage,
+        shader_array_length,
+        bindless_capacity: 0,
+    }
+}
+
+#[test]
+fn keeps_unsized_arrays_in_one_set_under_the_limit() {
+    let layout = limits(16, None)
+        .plan(10)
+        .expect("Failed to plan texture sets.");
+    assert_eq!(
+        layout,
+        TextureSetLayout {
+            textures_per_set: 16,
+            array_length: 10,
+            set_count: 1,
+        }
+    );
+    assert_eq!(layout.locate(9), (0, 9));
+    assert_eq!(layout.get_texture_range(0, 10), 0..10);
+}
+
+#[test]
+fn splits_textures_over_the_device_limit() {
+    let layout = limits(16, None)
+        .plan(40)
+        .expect("Failed to plan texture sets.");
+    assert_eq!(layout.set_count, 3);
+    // 分けた時は、全てのセットが同じレイアウトを使えるように同じ長さにする。
+    // When split, every set has the same length so they can share one layout.
+    assert_eq!(layout.array_length, 16);
+    assert_eq!(layout.locate(15), (0, 15));
+    assert_eq!(layout.locate(16), (1, 0));
+    assert_eq!(layout.locate(39), (2, 7));
+    assert_eq!(layout.get_texture_range(2, 40), 32..40);
+    assert_eq!(layout.get_capacity(), 48);
+    // どのセットにも入らない番号は、範囲外を読まずに最初のテクスチャを使う。
+    // Indices outside every set use the first texture instead of reading out of range.
+    assert_eq!(layout.locate(48), (0, 0));
+}
+
+#[test]
+fn pads_fixed_shader_arrays() {
+    let layout = limits(16, Some(16))
+        .plan(3)
+        .expect("Failed to plan texture sets.");
+    assert_eq!(layout.array_length, 16);
+    assert_eq!(layout.set_count, 1);
+
+    let layout = limits(16, Some(16))
+        .plan(17)
+        .expect("Failed to plan texture sets.");
+    assert_eq!(layout.set_count, 2);
+    assert_eq!(layout.locate(16), (1, 0));
+}
+
+#[test]
+fn rejects_shader_arrays_the_device_cannot_hold() {
+    let error = limits(8, Some(16))
+        .plan(4)
+        .expect_err("A shader array over the device limit was accepted.");
+    assert!(error
+        .to_string()
+        .contains("tex_sampler in the shaders to 8 or less"));
+    assert!(limits(0, None).plan(4).is_err());
+    assert!(limits(16, Some(16)).plan(0).is_err());
+    assert!(limits(16, None).plan(0).is_ok());
+}
+
+#[test]
+fn uses_the_bindless_capacity_for_each_set() {
+    let limits = TextureLimits {
+        max_per_stage: 16,
+        shader_array_length: None,
+        bindless_capacity: 4096,
+    };
+    let layout = limits.plan(5000).expect("Failed to plan texture sets.");
+    assert_eq!(layout.textures_per_set, 4096);
+    assert_eq!(layout.array_length, 4096);
+    assert_eq!(layout.set_count, 2);
+    let layout = limits.plan(0).expect("Failed to plan texture sets.");
+    assert_eq!(layout.set_count, 1);
+}
+
+#[test]
+fn locates_descriptor_sets_by_texture() {
+    let layout = limits(4, None)
+        .plan(6)
+        .expect("Failed to plan texture sets.");
+    let first = DescriptorSet::from_raw(1);
+    let second = DescriptorSet::from_raw(2);
+    let sets = TextureDescriptorSets::new(layout, vec![first, second]);
+    assert_eq!(sets.locate(2), (first, 2));
+    assert_eq!(sets.locate(5), (second, 1));
+    assert_eq!(sets.get_sets().len(), 2);
+    assert!(TextureDescriptorSets::default().is_empty());
+    assert_eq!(
+        TextureDescriptorSets::default().locate(3),
+        (DescriptorSet::null(), 0)
+    );
+}
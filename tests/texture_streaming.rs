@@ -0,0 +1,110 @@
+use demo_game_rs::game::shared::structs::{
+    get_desired_mip, get_low_res_mip, get_mip_count, get_screen_coverage, ResidencyChange,
+    StreamedTexture, TextureResidency, MAX_STREAMING_JOBS, STREAMING_EVICTION_PRESSURE,
+};
+
+fn texture(side: u32, resident_mip: u32) -> StreamedTexture {
+    StreamedTexture::new(side, side, get_mip_count(side, side), 4, resident_mip)
+}
+
+#[test]
+fn mips_follow_screen_coverage() {
+    assert_eq!(get_mip_count(1024, 512), 10);
+    assert_eq!(get_mip_count(1, 1), 1);
+    assert_eq!(get_low_res_mip(1024, 1024, 10), 4);
+    assert_eq!(get_low_res_mip(32, 32, 5), 0);
+
+    // カメラが境界球の中にあれば画面全体を占める。
+    // With the camera inside the bounding sphere it covers the whole screen.
+    assert_eq!(get_screen_coverage(2.0, 1.0, 1.0, 720.0), 720.0);
+    let near = get_screen_coverage(1.0, 10.0, 70f32.to_radians(), 720.0);
+    let far = get_screen_coverage(1.0, 100.0, 70f32.to_radians(), 720.0);
+    assert!(near > far && far > 0.0);
+
+    assert_eq!(get_desired_mip(1024, 1024, 10, 2048.0), 0);
+    assert_eq!(get_desired_mip(1024, 1024, 10, 256.0), 2);
+    assert_eq!(get_desired_mip(1024, 1024, 10, 0.0), 9);
+}
+
+#[test]
+fn streams_requested_mips_within_headroom() {
+    let mut residency = TextureResidency::new();
+    residency.register(0, texture(1024, 4));
+    residency.register(1, texture(1024, 4));
+
+    // 統計を取るまでは何も読み込まない。
+    // Nothing is streamed in before statistics are taken.
+    residency.begin_requests();
+    residency.request(0, 0);
+    assert!(residency.plan().is_empty());
+
+    let full_size = texture(1024, 0).get_size(0) - texture(1024, 4).get_size(4);
+    residency.set_budget(0.5, full_size);
+    residency.begin_requests();
+    residency.request(0, 0);
+    residency.request(1, 0);
+    assert_eq!(
+        residency.plan(),
+        vec![ResidencyChange::Stream {
+            texture_index: 0,
+            mip: 0
+        }]
+    );
+    assert_eq!(residency.get_pending_count(), 1);
+
+    residency.complete(0, 0);
+    assert_eq!(residency.get(0).unwrap().resident_mip, 0);
+    assert_eq!(residency.get_pending_count(), 0);
+}
+
+#[test]
+fn evicts_surplus_mips_under_pressure() {
+    let mut residency = TextureResidency::new();
+    for texture_index in 0..(MAX_STREAMING_JOBS + 1) {
+        residency.register(texture_index, texture(1024, 0));
+    }
+    residency.set_budget(STREAMING_EVICTION_PRESSURE, 0);
+    residency.begin_requests();
+    for texture_index in 0..MAX_STREAMING_JOBS {
+        residency.request(texture_index, 0);
+    }
+    // 要求されなかったテクスチャから捨て、一度に動かすのは上限まで。
+    // Textures that weren't requested are evicted first, moving no more than the limit at once.
+    let changes = residency.plan();
+    assert_eq!(changes.len(), MAX_STREAMING_JOBS);
+    assert_eq!(
+        changes[0],
+        ResidencyChange::Evict {
+            texture_index: MAX_STREAMING_JOBS,
+            mip: 4
+        }
+    );
+    assert!(changes[1..]
+        .iter()
+        .all(|change| matches!(change, ResidencyChange::Evict { mip: 1, .. })));
+    assert!(residency.plan().is_empty());
+}
+
+#[test]
+fn released_textures_return_to_low_resolution() {
+    let mut residency = TextureResidency::new();
+    residency.register(0, texture(1024, 4));
+    residency.register(1, texture(1024, 0));
+
+    // 低い解像度のテクスチャはすぐに登録を消す。
+    // Textures at low resolution are unregistered right away.
+    residency.release(0);
+    assert!(!residency.contains(0));
+
+    residency.release(1);
+    assert!(residency.contains(1));
+    assert_eq!(
+        residency.plan(),
+        vec![ResidencyChange::Evict {
+            texture_index: 1,
+            mip: 4
+        }]
+    );
+    residency.complete(1, 4);
+    assert!(residency.is_empty());
+}
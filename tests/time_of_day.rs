@@ -0,0 +1,74 @@
+use demo_game_rs::game::shared::structs::{TimeOfDay, NOON};
+use glam::Vec3A;
+
+#[test]
+fn advances_and_wraps_around_the_day() {
+    let mut time_of_day = TimeOfDay::new(240.0);
+    assert!((time_of_day.get_time() - NOON).abs() < 1e-5);
+    assert!((time_of_day.get_hours() - 12.0).abs() < 1e-5);
+
+    time_of_day.advance(60.0);
+    assert!((time_of_day.get_hours() - 18.0).abs() < 1e-3);
+    time_of_day.advance(120.0);
+    assert!((time_of_day.get_hours() - 6.0).abs() < 1e-3);
+
+    time_of_day.set_hours(-1.0);
+    assert!((time_of_day.get_hours() - 23.0).abs() < 1e-3);
+    time_of_day.set_time(f32::NAN);
+    assert!((time_of_day.get_hours() - 23.0).abs() < 1e-3);
+}
+
+#[test]
+fn pauses_and_keeps_valid_day_lengths() {
+    let mut time_of_day = TimeOfDay::new(100.0);
+    time_of_day.set_paused(true);
+    time_of_day.advance(50.0);
+    assert!((time_of_day.get_time() - NOON).abs() < 1e-5);
+    time_of_day.set_paused(false);
+    time_of_day.advance(25.0);
+    assert!((time_of_day.get_time() - 0.75).abs() < 1e-5);
+
+    time_of_day.set_day_length(0.0);
+    time_of_day.set_day_length(f32::NAN);
+    assert!((time_of_day.get_day_length() - 100.0).abs() < 1e-5);
+    assert!(
+        (TimeOfDay::new(-1.0).get_day_length() - TimeOfDay::default().get_day_length()).abs()
+            < 1e-5
+    );
+}
+
+#[test]
+fn keeps_the_original_light_at_noon() {
+    let daylight = TimeOfDay::new(600.0).get_daylight(10000.0, 10000.0);
+    let expected = Vec3A::new(10000.0, 20000.0, 10000.0);
+    assert!((daylight.light_position - expected).length() < 1.0);
+    assert!((daylight.ambient_intensity - 0.5).abs() < 1e-5);
+    assert!((daylight.light_color.x - 1.0).abs() < 1e-5);
+    assert!((daylight.sky_color.x - 0.5).abs() < 1e-5);
+}
+
+#[test]
+fn moves_the_sun_and_darkens_at_night() {
+    let mut time_of_day = TimeOfDay::default();
+    time_of_day.set_hours(6.0);
+    assert!(time_of_day.get_sun_height().abs() < 1e-5);
+    let sunrise = time_of_day.get_daylight(10000.0, 10000.0);
+    assert!(sunrise.light_position.y.abs() < 1.0);
+    assert!(sunrise.light_color.z < sunrise.light_color.x);
+
+    time_of_day.set_hours(0.0);
+    assert!((time_of_day.get_sun_height() + 1.0).abs() < 1e-5);
+    let midnight = time_of_day.get_daylight(10000.0, 10000.0);
+    // 夜は月が照らすので、光源は地平線より上にある。
+    // The moon lights the night, so the light is above the horizon.
+    assert!(midnight.light_position.y > 0.0);
+    assert!(midnight.ambient_intensity < 0.5);
+    assert!(midnight.sky_color.x < 0.1);
+
+    // 真上から照らしても東の向きを決められる。
+    // The direction of east can be found even when lit from straight above.
+    time_of_day.set_hours(9.0);
+    let morning = time_of_day.get_daylight(0.0, 0.0);
+    assert!(morning.light_position.x.is_finite());
+    assert!(morning.light_position.y > 0.0);
+}
@@ -0,0 +1,95 @@
+use demo_game_rs::game::shared::structs::{
+    sample_track, Cutscene, CutscenePlayer, Easing, Keyframe,
+};
+use glam::Vec3A;
+
+fn keyframe(time: f32, value: f32, easing: Easing) -> Keyframe<f32> {
+    Keyframe {
+        time,
+        value,
+        easing,
+    }
+}
+
+#[test]
+fn samples_tracks_with_easing() {
+    let linear = [
+        keyframe(1.0, 0.0, Easing::Linear),
+        keyframe(3.0, 10.0, Easing::Linear),
+    ];
+    assert_eq!(sample_track::<f32>(&[], 1.0), None);
+    assert_eq!(sample_track(&linear, 0.0), Some(0.0));
+    assert_eq!(sample_track(&linear, 2.0), Some(5.0));
+    assert_eq!(sample_track(&linear, 4.0), Some(10.0));
+
+    // イージングは次のキーフレームに向かう区間に使う。
+    // The easing is used for the segment leading to the next keyframe.
+    let eased = [
+        keyframe(0.0, 0.0, Easing::Linear),
+        keyframe(2.0, 10.0, Easing::EaseIn),
+    ];
+    assert_eq!(sample_track(&eased, 1.0), Some(2.5));
+    let step = [
+        keyframe(0.0, 0.0, Easing::Linear),
+        keyframe(2.0, 10.0, Easing::Step),
+    ];
+    assert_eq!(sample_track(&step, 1.9), Some(0.0));
+    assert_eq!(sample_track(&step, 2.0), Some(10.0));
+}
+
+#[test]
+fn loads_cutscene_from_json() {
+    let cutscene = Cutscene::from_json(
+        r#"{
+            "name": "Test",
+            "camera": {
+                "position": [
+                    { "time": 2.0, "value": [10.0, 0.0, 0.0] },
+                    { "time": 0.0, "value": [0.0, 0.0, 0.0] }
+                ],
+                "fade": [{ "time": 0.0, "value": 1.0 }]
+            },
+            "entities": [
+                { "entity": "Player 1", "rotation": [{ "time": 4.0, "value": [0.0, 180.0, 0.0] }] }
+            ]
+        }"#,
+    )
+    .expect("Failed to load cutscene.");
+    assert_eq!(cutscene.get_duration(), 4.0);
+
+    // キーフレームは時間の順に並べ替えられる。
+    // Keyframes are sorted by time.
+    let frame = cutscene.sample(1.0);
+    assert_eq!(frame.camera_position, Some(Vec3A::new(5.0, 0.0, 0.0)));
+    assert_eq!(frame.camera_rotation, None);
+    assert_eq!(frame.fade, Some(1.0));
+    assert_eq!(
+        frame.entities[0].rotation,
+        Some(Vec3A::new(0.0, 180.0_f32.to_radians(), 0.0))
+    );
+
+    assert!(Cutscene::from_json(r#"{ "entities": [{ "entity": "" }] }"#).is_err());
+    assert!(
+        Cutscene::from_json(r#"{ "camera": { "fade": [{ "time": -1.0, "value": 0.0 }] } }"#)
+            .is_err()
+    );
+}
+
+#[test]
+fn plays_and_skips_cutscene() {
+    let cutscene = Cutscene::from_json(
+        r#"{ "camera": { "field_of_view": [
+            { "time": 0.0, "value": 60.0 },
+            { "time": 2.0, "value": 80.0 }
+        ] } }"#,
+    )
+    .expect("Failed to load cutscene.");
+    let mut player = CutscenePlayer::new(cutscene);
+    assert_eq!(player.advance(1.0).field_of_view, Some(70.0));
+    assert!(!player.is_finished());
+
+    player.skip();
+    assert!(player.is_finished());
+    assert_eq!(player.advance(0.0).field_of_view, Some(80.0));
+    assert_eq!(player.get_time(), 2.0);
+}
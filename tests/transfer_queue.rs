@@ -0,0 +1,50 @@
+use ash::vk::{QueueFamilyProperties, QueueFlags};
+use demo_game_rs::game::graphics::vk::physical_device::QueueIndices;
+
+fn create_family(queue_flags: QueueFlags, queue_count: u32) -> QueueFamilyProperties {
+    QueueFamilyProperties {
+        queue_flags,
+        queue_count,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn prefers_transfer_only_families() {
+    let families = [
+        create_family(
+            QueueFlags::GRAPHICS | QueueFlags::COMPUTE | QueueFlags::TRANSFER,
+            16,
+        ),
+        create_family(QueueFlags::COMPUTE | QueueFlags::TRANSFER, 8),
+        create_family(QueueFlags::TRANSFER | QueueFlags::SPARSE_BINDING, 2),
+    ];
+    assert_eq!(QueueIndices::find_transfer_family(&families), Some(2));
+}
+
+#[test]
+fn falls_back_to_compute_families_without_graphics() {
+    let families = [
+        create_family(
+            QueueFlags::GRAPHICS | QueueFlags::COMPUTE | QueueFlags::TRANSFER,
+            16,
+        ),
+        create_family(QueueFlags::COMPUTE | QueueFlags::TRANSFER, 8),
+    ];
+    assert_eq!(QueueIndices::find_transfer_family(&families), Some(1));
+}
+
+#[test]
+fn finds_no_dedicated_family_on_graphics_only_devices() {
+    // キューのないファミリーや、グラフィックスにも対応するファミリーは使わない。
+    // Families without queues and families supporting graphics aren't used.
+    let families = [
+        create_family(
+            QueueFlags::GRAPHICS | QueueFlags::COMPUTE | QueueFlags::TRANSFER,
+            1,
+        ),
+        create_family(QueueFlags::TRANSFER, 0),
+    ];
+    assert_eq!(QueueIndices::find_transfer_family(&families), None);
+    assert_eq!(QueueIndices::find_transfer_family(&[]), None);
+}
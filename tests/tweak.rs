@@ -0,0 +1,121 @@
+use demo_game_rs::game::ui::{TweakRegistry, TweakValue};
+
+#[test]
+fn registers_and_clamps_parameters() {
+    let mut tweaks = TweakRegistry::new();
+    assert_eq!(tweaks.register_f32("Fog Density", 2.0, 0.0, 1.0), 1.0);
+    assert!(tweaks.register_bool("Shadows", true));
+    assert_eq!(tweaks.register_enum("Quality", &["Low", "High"], 1), 1);
+    let names = tweaks
+        .get_parameters()
+        .iter()
+        .map(|p| p.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Fog Density", "Shadows", "Quality"]);
+
+    // 登録し直しても、今の値と並びは変わらない。
+    // Registering again keeps the current value and order.
+    tweaks.set(
+        "Fog Density",
+        TweakValue::Float {
+            value: 0.25,
+            min: 0.0,
+            max: 1.0,
+        },
+    );
+    assert_eq!(tweaks.register_f32("Fog Density", 0.5, 0.0, 1.0), 0.25);
+    assert_eq!(tweaks.get_parameters()[0].name, "Fog Density");
+}
+
+#[test]
+fn tracks_changed_parameters() {
+    let mut tweaks = TweakRegistry::new();
+    tweaks.register_f32("Light Angle", 45.0, 0.0, 90.0);
+    tweaks.register_bool("Shadows", true);
+    assert!(tweaks.take_changed().is_empty());
+
+    assert!(tweaks.set("Shadows", TweakValue::Bool(false)));
+    assert!(tweaks.set(
+        "Light Angle",
+        TweakValue::Float {
+            value: 120.0,
+            min: 0.0,
+            max: 180.0,
+        },
+    ));
+    assert_eq!(tweaks.get_f32("Light Angle"), Some(90.0));
+    assert_eq!(tweaks.take_changed(), vec!["Shadows", "Light Angle"]);
+    assert!(tweaks.take_changed().is_empty());
+
+    // 種類の違う値や登録されていない名前は受け付けない。
+    // Values of another kind and unregistered names are rejected.
+    assert!(!tweaks.set("Light Angle", TweakValue::Bool(true)));
+    assert!(!tweaks.set("Missing", TweakValue::Bool(true)));
+
+    // 同じ値を設定しても変わったことにはならない。
+    // Setting the same value doesn't count as a change.
+    assert!(tweaks.set("Shadows", TweakValue::Bool(false)));
+    assert!(tweaks.take_changed().is_empty());
+}
+
+#[test]
+fn saves_and_loads_values() {
+    let path = std::env::temp_dir().join(format!("tweaks_{}.json", std::process::id()));
+    let path = path.to_str().expect("Failed to convert path to string.");
+
+    let mut tweaks = TweakRegistry::new();
+    tweaks.register_f32("Camera Smoothing", 0.2, 0.0, 1.0);
+    tweaks.register_enum("Quality", &["Low", "Medium", "High"], 0);
+    tweaks.set(
+        "Camera Smoothing",
+        TweakValue::Float {
+            value: 0.75,
+            min: 0.0,
+            max: 1.0,
+        },
+    );
+    tweaks.set(
+        "Quality",
+        TweakValue::Enum {
+            index: 2,
+            options: vec![],
+        },
+    );
+    tweaks.save(path).expect("Failed to save tweaks.");
+
+    // 読み込んだ値は登録された時に使われる。列挙型は名前で探す。
+    // Loaded values are used when registered. Enums are looked up by name.
+    let mut loaded = TweakRegistry::new();
+    loaded.load(path).expect("Failed to load tweaks.");
+    assert_eq!(loaded.register_f32("Camera Smoothing", 0.2, 0.0, 1.0), 0.75);
+    assert_eq!(loaded.register_enum("Quality", &["High", "Low"], 1), 0);
+    assert!(loaded.register_bool("Shadows", true));
+
+    std::fs::remove_file(path).expect("Failed to remove saved tweaks.");
+}
+
+#[test]
+fn sets_values_from_console_strings() {
+    let mut tweaks = TweakRegistry::new();
+    tweaks.register_f32("Graphics/Render Scale", 1.0, 0.5, 2.0);
+    tweaks.register_bool("Physics/Debug Draw", false);
+    tweaks.register_enum("Graphics/MSAA", &["1x", "4x"], 0);
+    tweaks.take_changed();
+
+    // 名前は最後の`/`より後だけでもよく、空白は`_`で書ける。
+    // Names may be just the part after the last `/`, with spaces written as `_`.
+    assert_eq!(
+        tweaks.set_from_str("render_scale", "3").unwrap(),
+        "Graphics/Render Scale"
+    );
+    assert_eq!(tweaks.get_f32("Graphics/Render Scale"), Some(2.0));
+    tweaks.set_from_str("physics/debug_draw", "on").unwrap();
+    assert_eq!(tweaks.get_bool("Physics/Debug Draw"), Some(true));
+    tweaks.set_from_str("MSAA", "4X").unwrap();
+    assert_eq!(tweaks.get_enum("Graphics/MSAA"), Some(1));
+    assert_eq!(tweaks.take_changed().len(), 3);
+
+    assert!(tweaks.set_from_str("fog", "1").is_err());
+    assert!(tweaks.set_from_str("render_scale", "high").is_err());
+    assert!(tweaks.set_from_str("msaa", "8x").is_err());
+}
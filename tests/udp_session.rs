@@ -0,0 +1,209 @@
+use demo_game_rs::game::shared::structs::games::fragment::MAX_DATAGRAM_SIZE;
+use demo_game_rs::game::shared::structs::games::payload::PayloadError;
+use demo_game_rs::game::shared::structs::games::protocol::encode_packet;
+use demo_game_rs::game::shared::structs::games::snapshot::{PlayerSnapshot, SnapshotEncoder};
+use demo_game_rs::game::shared::structs::games::udp_session::{
+    decode_session_packet, ClientSession, ControlMessage, PacketOrder, Received, ReplayWindow,
+    SessionPacket, SessionState, SessionTable, HANDSHAKE_RETRY_INTERVAL, KEEP_ALIVE_INTERVAL,
+    MAX_HANDSHAKE_ATTEMPTS, SESSION_TIMEOUT,
+};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+const TOKEN: &str = "issued-token";
+
+/// `TOKEN`がaliceに発行されたものとして確かめる。<br />
+/// Check tokens as if `TOKEN` was issued to alice.
+fn is_alice_token(token: &str, player_id: &str) -> bool {
+    token == TOKEN && player_id == "alice"
+}
+
+fn get_address(port: u16) -> SocketAddr {
+    SocketAddr::from(([192, 168, 0, 10], port))
+}
+
+fn expect_reply(received: Result<Received, PayloadError>) -> Vec<u8> {
+    match received.expect("Failed to receive packet.") {
+        Received::Reply(reply) => reply,
+        other => panic!("Expected a reply, got {:?}.", other),
+    }
+}
+
+/// ハンドシェイクを最後まで進めたクライアントとサーバーを作る。<br />
+/// Create a client and a server that have completed the handshake.
+fn establish(now: Instant) -> (ClientSession, SessionTable) {
+    let address = get_address(40000);
+    let mut client = ClientSession::new(TOKEN, "alice", 7, now);
+    let mut table = SessionTable::new(42);
+    let hello = client.poll(now).expect("Failed to get hello.");
+    let challenge = expect_reply(table.receive(address, &hello, now, is_alice_token));
+    let response = expect_reply(client.receive(&challenge, now));
+    let accepted = expect_reply(table.receive(address, &response, now, is_alice_token));
+    assert_eq!(client.receive(&accepted, now), Ok(Received::Nothing));
+    assert!(client.is_established());
+    (client, table)
+}
+
+#[test]
+fn establishes_session_and_relays_datagrams() {
+    let now = Instant::now();
+    let (mut client, mut table) = establish(now);
+    let address = get_address(40000);
+    assert_eq!(
+        table.get(&address).map(|s| s.player_id.as_str()),
+        Some("alice")
+    );
+
+    let datagram = encode_packet(b"snapshot fragment");
+    let packet = client.wrap(&datagram).unwrap();
+    assert_eq!(
+        table.receive(address, &packet, now, |_, _| false),
+        Ok(Received::Datagram(datagram.clone()))
+    );
+    assert_eq!(
+        table.receive(address, &packet, now, |_, _| false),
+        Ok(Received::Nothing)
+    );
+    assert_eq!(table.get_stats().duplicate_packets, 1);
+
+    let reply = table.wrap(&address, &datagram).unwrap();
+    assert_eq!(
+        client.receive(&reply, now),
+        Ok(Received::Datagram(datagram))
+    );
+}
+
+#[test]
+fn wrapped_fragments_fit_in_a_datagram() {
+    let now = Instant::now();
+    let (mut client, _) = establish(now);
+    let mut encoder = SnapshotEncoder::new();
+    // 圧縮されないよう、プレイヤーIDを乱数で埋める。
+    // Player IDs are filled with random characters so they don't compress.
+    let players = (0..64)
+        .map(|i| PlayerSnapshot {
+            player_id: format!("{}-{}", i, rand::random::<u128>()),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let datagrams = encoder.encode(true, players, vec![], None).unwrap();
+    assert!(datagrams.len() > 1);
+    for datagram in datagrams.iter() {
+        assert!(client.wrap(datagram).unwrap().len() <= MAX_DATAGRAM_SIZE);
+    }
+}
+
+#[test]
+fn rejects_invalid_tokens_and_cookies() {
+    let now = Instant::now();
+    let mut client = ClientSession::new("forged-token", "mallory", 1, now);
+    let mut table = SessionTable::new(42);
+    let hello = client.poll(now).unwrap();
+    let rejected = expect_reply(table.receive(get_address(40000), &hello, now, is_alice_token));
+    assert_eq!(client.receive(&rejected, now), Ok(Received::Nothing));
+    assert!(matches!(client.get_state(), SessionState::Failed(_)));
+
+    // 他のプレイヤーに発行されたトークンでは、なりすませない。
+    // A token issued to another player can't be used to impersonate them.
+    let mut client = ClientSession::new(TOKEN, "mallory", 3, now);
+    let hello = client.poll(now).unwrap();
+    let rejected = expect_reply(table.receive(get_address(40000), &hello, now, is_alice_token));
+    assert_eq!(client.receive(&rejected, now), Ok(Received::Nothing));
+    assert!(matches!(client.get_state(), SessionState::Failed(_)));
+
+    // 問いかけへの答えを別のアドレスから送っても、セッションは開けない。
+    // Answering the challenge from another address doesn't open a session.
+    let mut client = ClientSession::new(TOKEN, "alice", 2, now);
+    let hello = client.poll(now).unwrap();
+    let challenge = expect_reply(table.receive(get_address(40000), &hello, now, is_alice_token));
+    let response = expect_reply(client.receive(&challenge, now));
+    let rejected = expect_reply(table.receive(get_address(40001), &response, now, |_, _| true));
+    assert!(matches!(
+        decode_session_packet(&rejected),
+        Ok(SessionPacket::Control(ControlMessage::Rejected { .. }))
+    ));
+    assert!(table.get(&get_address(40001)).is_none());
+}
+
+#[test]
+fn measures_round_trip_time() {
+    let now = Instant::now();
+    let (mut client, mut table) = establish(now);
+    let ping = client.poll(now).expect("Failed to get ping.");
+    let pong = expect_reply(table.receive(get_address(40000), &ping, now, |_, _| false));
+    client
+        .receive(&pong, now + Duration::from_millis(40))
+        .unwrap();
+    assert_eq!(
+        client.get_round_trip_time(),
+        Some(Duration::from_millis(40))
+    );
+
+    assert!(client.poll(now + Duration::from_millis(500)).is_none());
+    let ping = client.poll(now + KEEP_ALIVE_INTERVAL).unwrap();
+    let pong = expect_reply(table.receive(get_address(40000), &ping, now, |_, _| false));
+    client
+        .receive(
+            &pong,
+            now + KEEP_ALIVE_INTERVAL + Duration::from_millis(120),
+        )
+        .unwrap();
+    assert_eq!(
+        client.get_round_trip_time(),
+        Some(Duration::from_millis(50))
+    );
+}
+
+#[test]
+fn handles_lost_and_rebound_sessions() {
+    let now = Instant::now();
+    let mut client = ClientSession::new(TOKEN, "alice", 3, now);
+    for attempt in 0..MAX_HANDSHAKE_ATTEMPTS {
+        let at = now + HANDSHAKE_RETRY_INTERVAL * attempt;
+        assert!(client.poll(at).is_some());
+    }
+    assert!(client
+        .poll(now + HANDSHAKE_RETRY_INTERVAL * MAX_HANDSHAKE_ATTEMPTS)
+        .is_none());
+    assert!(matches!(client.get_state(), SessionState::Failed(_)));
+
+    let (mut client, mut table) = establish(now);
+    // NATの対応付けが変わり、サーバーから見たアドレスが変わった。
+    // The NAT mapping changed, so the server sees a different address.
+    let packet = client.wrap(&encode_packet(b"fragment")).unwrap();
+    let rejected = expect_reply(table.receive(get_address(40002), &packet, now, |_, _| false));
+    client.receive(&rejected, now).unwrap();
+    assert_eq!(client.get_state(), &SessionState::Connecting);
+    assert!(matches!(
+        decode_session_packet(&client.poll(now).unwrap()),
+        Ok(SessionPacket::Control(ControlMessage::Hello { .. }))
+    ));
+
+    let (mut client, mut table) = establish(now);
+    let later = now + SESSION_TIMEOUT + Duration::from_secs(1);
+    assert_eq!(table.remove_expired(later).len(), 1);
+    client.poll(later);
+    assert!(matches!(client.get_state(), SessionState::Failed(_)));
+}
+
+#[test]
+fn detects_duplicate_and_reordered_packets() {
+    let mut window = ReplayWindow::default();
+    assert_eq!(window.accept(10), PacketOrder::InOrder);
+    assert_eq!(window.accept(12), PacketOrder::InOrder);
+    assert_eq!(window.accept(11), PacketOrder::Reordered);
+    assert_eq!(window.accept(11), PacketOrder::Duplicate);
+    assert_eq!(window.accept(10), PacketOrder::Duplicate);
+    assert_eq!(window.accept(12), PacketOrder::Duplicate);
+    assert_eq!(window.accept(100), PacketOrder::InOrder);
+    assert_eq!(window.accept(12), PacketOrder::TooOld);
+    assert_eq!(window.accept(40), PacketOrder::Reordered);
+
+    // 番号が一周しても続けて受け取れる。
+    // Sequences keep being accepted after wrapping around.
+    let mut window = ReplayWindow::default();
+    assert_eq!(window.accept(u32::MAX), PacketOrder::InOrder);
+    assert_eq!(window.accept(1), PacketOrder::InOrder);
+    assert_eq!(window.accept(0), PacketOrder::Reordered);
+    assert_eq!(window.accept(u32::MAX), PacketOrder::Duplicate);
+}
@@ -0,0 +1,68 @@
+use demo_game_rs::game::ui::{UiWindow, WindowBounds, WindowManager};
+
+fn create_window(name: &str, z_order: i32) -> UiWindow {
+    UiWindow::new(
+        name,
+        WindowBounds::new(0.0, 0.0, 100.0, 100.0),
+        0,
+        16,
+        z_order,
+    )
+}
+
+#[test]
+fn shows_and_hides_windows_by_name() {
+    let mut windows = WindowManager::new();
+    windows.add(create_window("Chat", 1));
+    windows.add(create_window("Menu", 0));
+    assert!(windows.is_visible("Chat"));
+
+    assert!(windows.hide("Chat"));
+    assert!(!windows.is_visible("Chat"));
+    assert_eq!(windows.get_draw_order(), vec!["Menu"]);
+    assert!(windows.show("Chat"));
+    assert_eq!(windows.get_draw_order(), vec!["Menu", "Chat"]);
+
+    // 登録されていない窓は操作できない。
+    // Windows that aren't registered can't be changed.
+    assert!(!windows.show("Missing"));
+    assert!(!windows.is_visible("Missing"));
+}
+
+#[test]
+fn reports_focus_order_only_when_it_changes() {
+    let mut windows = WindowManager::new();
+    windows.add(create_window("Connection", 4));
+    windows.add(create_window("Menu", 0));
+    windows.add(create_window("Login", 2));
+
+    windows.mark_drawn("Connection");
+    windows.mark_drawn("Menu");
+    assert_eq!(
+        windows.take_focus_order(),
+        Some(vec!["Menu".to_string(), "Connection".to_string()])
+    );
+    windows.mark_drawn("Connection");
+    windows.mark_drawn("Menu");
+    assert_eq!(windows.take_focus_order(), None);
+
+    // 新しく描画された窓は奥行きの順に並ぶ。
+    // A newly drawn window is placed by its z-order.
+    windows.mark_drawn("Menu");
+    windows.mark_drawn("Login");
+    windows.mark_drawn("Connection");
+    assert_eq!(
+        windows.take_focus_order(),
+        Some(vec![
+            "Menu".to_string(),
+            "Login".to_string(),
+            "Connection".to_string()
+        ])
+    );
+
+    assert!(windows.bring_to_front("Menu"));
+    assert_eq!(
+        windows.get_draw_order(),
+        vec!["Login", "Connection", "Menu"]
+    );
+}
@@ -0,0 +1,106 @@
+use demo_game_rs::game::shared::structs::{
+    RippleField, WaterEvent, WaterSurface, WaterVolume, RIPPLE_TIMESTEP,
+};
+use glam::{Vec2, Vec3A};
+use slotmap::{DefaultKey, SlotMap};
+
+fn create_surface() -> WaterSurface {
+    WaterSurface::new(WaterVolume::new(
+        Vec2::new(-10.0, -10.0),
+        Vec2::new(10.0, 10.0),
+        1.0,
+    ))
+}
+
+fn create_key() -> DefaultKey {
+    let mut keys = SlotMap::new();
+    keys.insert(())
+}
+
+fn energy(field: &RippleField) -> f32 {
+    let resolution = field.get_resolution() as f32;
+    let mut total = 0.0;
+    for y in 0..field.get_resolution() {
+        for x in 0..field.get_resolution() {
+            let uv = Vec2::new((x as f32 + 0.5) / resolution, (y as f32 + 0.5) / resolution);
+            total += field.sample(uv).abs();
+        }
+    }
+    total
+}
+
+#[test]
+fn ripples_spread_and_fade() {
+    let mut field = RippleField::new(32);
+    let center = Vec2::splat(0.5);
+    let nearby = Vec2::new(0.5 + 4.0 / 32.0, 0.5);
+    field.disturb(center, 0.05, 1.0);
+    assert!(field.sample(center) < 0.0);
+    assert!(field.sample(nearby).abs() < 1e-5);
+
+    for _ in 0..4 {
+        field.step();
+    }
+    assert!(field.sample(nearby).abs() > 1e-5);
+
+    let start = energy(&field);
+    for _ in 0..600 {
+        field.step();
+    }
+    assert!(energy(&field) < start * 0.1);
+    assert_eq!(field.sample(Vec2::new(1.5, 0.5)), 0.0);
+}
+
+#[test]
+fn encodes_flat_water_as_the_middle() {
+    let mut field = RippleField::new(8);
+    let pixels = field.get_pixels();
+    assert_eq!(pixels.len(), 8 * 8 * 4);
+    assert!(pixels.chunks(4).all(|p| p == [128, 128, 128, 255]));
+
+    field.disturb(Vec2::splat(0.5), 0.2, 1.0);
+    assert!(field.get_pixels().chunks(4).any(|p| p[0] < 128));
+    field.clear();
+    assert!(field.get_pixels().chunks(4).all(|p| p[0] == 128));
+}
+
+#[test]
+fn reports_crossings_of_the_surface() {
+    let mut surface = create_surface();
+    let entity = create_key();
+    assert_eq!(surface.height_at(11.0, 0.0), None);
+    assert!((surface.height_at(2.0, 2.0).unwrap() - 1.0).abs() < 1e-5);
+
+    let above = Vec3A::new(2.0, 3.0, 2.0);
+    let below = Vec3A::new(2.0, 0.0, 2.0);
+    // 初めて見たものは出入りしたことにならない。
+    // Something seen for the first time doesn't count as crossing.
+    assert!(surface
+        .update(RIPPLE_TIMESTEP, &[(entity, above, 0.5)])
+        .is_empty());
+    assert_eq!(
+        surface.update(RIPPLE_TIMESTEP, &[(entity, below, 0.5)]),
+        vec![WaterEvent::Entered {
+            entity,
+            position: below
+        }]
+    );
+    // 飛び込んだ所の水面が揺れる。
+    // The surface ripples where it was entered.
+    assert!((surface.height_at(2.0, 2.0).unwrap() - 1.0).abs() > 1e-4);
+    assert_eq!(
+        surface.update(RIPPLE_TIMESTEP, &[(entity, above, 0.5)]),
+        vec![WaterEvent::Exited {
+            entity,
+            position: above
+        }]
+    );
+
+    // 一覧から消えたものは忘れるので、次に水中で見ても入ったことにならない。
+    // Things dropped from the list are forgotten, so seeing them under water next doesn't count as entering.
+    assert!(surface.update(RIPPLE_TIMESTEP, &[]).is_empty());
+    assert!(surface
+        .update(RIPPLE_TIMESTEP, &[(entity, below, 0.5)])
+        .is_empty());
+    assert!((surface.get_time() - RIPPLE_TIMESTEP * 5.0).abs() < 1e-5);
+}
@@ -0,0 +1,96 @@
+use ash::vk::CommandBuffer;
+use demo_game_rs::game::graphics::vk::{Buffer, Graphics, Image};
+use demo_game_rs::game::shared::components::{NetworkReplicated, PlayerController, Transform};
+use demo_game_rs::game::shared::structs::PositionInfo;
+use demo_game_rs::game::shared::World;
+use glam::Vec3A;
+use std::collections::HashMap;
+
+type VkWorld = World<Graphics, Buffer, CommandBuffer, Image>;
+
+fn create_transform(x: f32) -> Transform {
+    Transform {
+        position: Vec3A::new(x, 0.0, 0.0),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn stores_components_per_entity() {
+    let mut world = VkWorld::new();
+    let tank = world.spawn("Tank");
+    let barrel = world.spawn("Barrel");
+    assert_eq!(world.get_entity_count(), 2);
+    assert_eq!(world.get_name(tank), Some("Tank"));
+    assert_eq!(world.find_by_name("Barrel"), Some(barrel));
+    assert_eq!(world.find_by_name("Tree"), None);
+
+    assert!(world.insert(tank, create_transform(1.0)).is_none());
+    world.insert(barrel, create_transform(2.0));
+    // 同じ種類のコンポーネントは置き換えられ、前のものが返る。
+    // A component of the same type is replaced and the previous one is returned.
+    let previous = world
+        .insert(tank, create_transform(3.0))
+        .expect("Failed to replace the transform.");
+    assert_eq!(previous.position.x, 1.0);
+    assert_eq!(world.get::<Transform>(tank).unwrap().position.x, 3.0);
+    assert!(world.get::<PlayerController>(tank).is_none());
+
+    world.get_mut::<Transform>(barrel).unwrap().position.x = 4.0;
+    let mut positions = world
+        .iter::<Transform>()
+        .map(|(_, transform)| transform.position.x)
+        .collect::<Vec<_>>();
+    positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(positions, vec![3.0, 4.0]);
+
+    assert!(world.remove::<Transform>(barrel).is_some());
+    assert!(world.get::<Transform>(barrel).is_none());
+    assert!(world.contains(barrel));
+}
+
+#[test]
+fn despawning_removes_every_component() {
+    let mut world = VkWorld::new();
+    let tank = world.spawn("Tank");
+    world.insert(tank, create_transform(1.0));
+    world.insert(tank, NetworkReplicated::new("remote".to_string(), false));
+    assert!(world.despawn(tank));
+    assert!(!world.contains(tank));
+    assert!(world.get::<Transform>(tank).is_none());
+    assert!(world.get::<NetworkReplicated>(tank).is_none());
+    assert!(!world.despawn(tank));
+
+    // 削除したエンティティにはコンポーネントを追加できない。
+    // Components can't be added to despawned entities.
+    assert!(world.insert(tank, create_transform(2.0)).is_none());
+    assert_eq!(world.iter::<Transform>().count(), 0);
+}
+
+#[test]
+fn applies_network_state_to_remote_players_only() {
+    let mut world = VkWorld::new();
+    let local = world.spawn("Local");
+    let remote = world.spawn("Remote");
+    world.insert(local, create_transform(0.0));
+    world.insert(local, NetworkReplicated::new("local".to_string(), true));
+    world.insert(remote, NetworkReplicated::new("remote".to_string(), false));
+
+    let states = vec![
+        (
+            "local".to_string(),
+            PositionInfo::from(create_transform(5.0)),
+        ),
+        (
+            "remote".to_string(),
+            PositionInfo::from(create_transform(7.0)),
+        ),
+    ]
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+    world.apply_network_state(&states);
+    // ローカルプレイヤーはこのクライアントが動かすので、上書きしない。
+    // The local player is driven by this client, so it isn't overwritten.
+    assert_eq!(world.get::<Transform>(local).unwrap().position.x, 0.0);
+    assert_eq!(world.get::<Transform>(remote).unwrap().position.x, 7.0);
+}
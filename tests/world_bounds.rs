@@ -0,0 +1,62 @@
+use demo_game_rs::game::shared::structs::{BoundsResponse, WorldBounds};
+use glam::{Vec2, Vec3A};
+
+fn get_bounds() -> WorldBounds {
+    WorldBounds::from_terrain(Vec2::new(-400.0, -400.0), 800.0, 40.0)
+}
+
+#[test]
+fn keeps_a_border_inside_the_terrain() {
+    let bounds = get_bounds();
+    assert_eq!(bounds.min, Vec2::new(-360.0, -360.0));
+    assert_eq!(bounds.max, Vec2::new(360.0, 360.0));
+    assert!(bounds.contains(Vec3A::new(0.0, 50.0, 0.0)));
+    assert!(!bounds.contains(Vec3A::new(380.0, 0.0, 0.0)));
+
+    // 縁が広すぎれば中心の一点になる。
+    // A border that is too wide collapses the area to the center.
+    let collapsed = WorldBounds::from_terrain(Vec2::zero(), 10.0, 20.0);
+    assert_eq!(collapsed.min, Vec2::new(5.0, 5.0));
+    assert_eq!(collapsed.max, Vec2::new(5.0, 5.0));
+}
+
+#[test]
+fn clamps_horizontally_and_keeps_the_height() {
+    let bounds = get_bounds();
+    let clamped = bounds.clamp(Vec3A::new(1000.0, 12.0, -1000.0));
+    assert_eq!(clamped, Vec3A::new(360.0, 12.0, -360.0));
+    let inside = Vec3A::new(10.0, 3.0, -20.0);
+    assert_eq!(bounds.clamp(inside), inside);
+}
+
+#[test]
+fn warns_more_strongly_towards_the_edge() {
+    let bounds = get_bounds();
+    assert_eq!(bounds.get_warning(Vec3A::zero()), 0.0);
+    let near = bounds.get_warning(Vec3A::new(350.0, 0.0, 0.0));
+    assert!((near - 0.5).abs() < 1e-5);
+    assert_eq!(bounds.get_warning(Vec3A::new(400.0, 0.0, 0.0)), 1.0);
+    assert!((bounds.get_distance_to_edge(Vec3A::new(370.0, 0.0, 0.0)) + 10.0).abs() < 1e-5);
+}
+
+#[test]
+fn pushes_back_gradually() {
+    let bounds = get_bounds();
+    let outside = Vec3A::new(400.0, 0.0, 0.0);
+    let pushed = bounds.push_back(outside, 0.5);
+    assert!((pushed.x - 385.0).abs() < 1e-4);
+    // 残りが一度に動ける距離より短ければ、境界でぴったり止まる。
+    // When the rest is shorter than a single step, it stops exactly on the edge.
+    let settled = bounds.push_back(Vec3A::new(361.0, 0.0, 0.0), 0.5);
+    assert_eq!(settled, Vec3A::new(360.0, 0.0, 0.0));
+}
+
+#[test]
+fn only_damages_outside_when_configured() {
+    let outside = Vec3A::new(0.0, 0.0, 500.0);
+    assert_eq!(get_bounds().get_damage(outside, 1.0), 0.0);
+
+    let bounds = get_bounds().with_response(BoundsResponse::Damage);
+    assert_eq!(bounds.get_damage(outside, 0.5), 5.0);
+    assert_eq!(bounds.get_damage(Vec3A::zero(), 0.5), 0.0);
+}